@@ -0,0 +1,108 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Batched jni calls.
+//!
+//! each jni call has a fixed overhead which is significant for short tasks,
+//! so elements and values are passed in arrays with one call instead of one
+//! call per element.
+
+use std::collections::VecDeque;
+
+use datafusion::common::Result;
+use jni::objects::{GlobalRef, JObject};
+
+use crate::{
+    jni_bridge::{JavaClasses, LocalRef, THREAD_JNIENV},
+    jni_call_static, jni_map_error_with_env, jni_new_global_ref, jni_new_string,
+};
+
+/// reads elements of a scala iterator, fetching at most `batch_size`
+/// elements with each jni call.
+pub struct BatchedScalaIterator {
+    iter: GlobalRef,
+    batch_size: usize,
+    buffered: VecDeque<GlobalRef>,
+    exhausted: bool,
+}
+
+impl BatchedScalaIterator {
+    pub fn new(iter: GlobalRef, batch_size: usize) -> Self {
+        Self {
+            iter,
+            batch_size: batch_size.max(1),
+            buffered: VecDeque::new(),
+            exhausted: false,
+        }
+    }
+
+    pub fn next(&mut self) -> Result<Option<GlobalRef>> {
+        if self.buffered.is_empty() && !self.exhausted {
+            let elements = jni_call_static!(
+                JniBridge.nextElements(self.iter.as_obj(), self.batch_size as i32) -> JObject
+            )?;
+            let num_elements = jni_get_array_length(elements.as_obj())?;
+            for i in 0..num_elements {
+                let element = jni_get_object_array_element(elements.as_obj(), i)?;
+                self.buffered
+                    .push_back(jni_new_global_ref!(element.as_obj())?);
+            }
+            self.exhausted = num_elements < self.batch_size;
+        }
+        Ok(self.buffered.pop_front())
+    }
+}
+
+/// creates a java `String[]` array.
+pub fn new_string_array(values: &[&str]) -> Result<LocalRef<'static>> {
+    let string_class = JavaClasses::get().cJavaString.class;
+    THREAD_JNIENV.with(|env| {
+        let array = jni_map_error_with_env!(
+            env,
+            env.new_object_array(values.len() as i32, string_class, JObject::null())
+        )?;
+        let array = LocalRef(JObject::from(array));
+        for (i, &value) in values.iter().enumerate() {
+            let value = jni_new_string!(value)?;
+            jni_map_error_with_env!(
+                env,
+                env.set_object_array_element(*array.as_obj(), i as i32, value.as_obj())
+            )?;
+        }
+        Ok(array)
+    })
+}
+
+/// creates a java `long[]` array.
+pub fn new_long_array(values: &[i64]) -> Result<LocalRef<'static>> {
+    THREAD_JNIENV.with(|env| {
+        let array = jni_map_error_with_env!(env, env.new_long_array(values.len() as i32))?;
+        let array = LocalRef(JObject::from(array));
+        jni_map_error_with_env!(env, env.set_long_array_region(*array.as_obj(), 0, values))?;
+        Ok(array)
+    })
+}
+
+fn jni_get_array_length(array: JObject) -> Result<usize> {
+    THREAD_JNIENV.with(|env| {
+        jni_map_error_with_env!(env, env.get_array_length(*array)).map(|len| len as usize)
+    })
+}
+
+fn jni_get_object_array_element(array: JObject, index: usize) -> Result<LocalRef<'static>> {
+    THREAD_JNIENV.with(|env| {
+        jni_map_error_with_env!(env, env.get_object_array_element(*array, index as i32))
+            .map(LocalRef)
+    })
+}
@@ -0,0 +1,91 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Per-call-site statistics of jni calls.
+//!
+//! calls made by `jni_call!` and `jni_call_static!` are recorded into the
+//! stats installed in the calling thread, so that jni overhead of a task can
+//! be measured. threads without installed stats do not time their calls.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JniCallSiteStats {
+    pub num_calls: usize,
+    pub elapsed: Duration,
+}
+
+#[derive(Debug, Default)]
+pub struct JniCallStats {
+    sites: Mutex<HashMap<&'static str, JniCallSiteStats>>,
+}
+
+impl JniCallStats {
+    pub fn record(&self, site: &'static str, elapsed: Duration) {
+        let mut sites = self.sites.lock().unwrap();
+        let site_stats = sites.entry(site).or_default();
+        site_stats.num_calls += 1;
+        site_stats.elapsed += elapsed;
+    }
+
+    /// returns stats of all call sites, most time-consuming first
+    pub fn sites(&self) -> Vec<(&'static str, JniCallSiteStats)> {
+        let mut sites = self
+            .sites
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(&site, &site_stats)| (site, site_stats))
+            .collect::<Vec<_>>();
+        sites.sort_by(|(_, s1), (_, s2)| s2.elapsed.cmp(&s1.elapsed));
+        sites
+    }
+
+    pub fn total(&self) -> JniCallSiteStats {
+        let sites = self.sites.lock().unwrap();
+        JniCallSiteStats {
+            num_calls: sites.values().map(|s| s.num_calls).sum(),
+            elapsed: sites.values().map(|s| s.elapsed).sum(),
+        }
+    }
+}
+
+thread_local! {
+    static THREAD_JNI_CALL_STATS: RefCell<Option<Arc<JniCallStats>>> = RefCell::new(None);
+}
+
+/// installs the stats recording jni calls of the current thread, or removes
+/// it with `None`.
+pub fn set_thread_jni_call_stats(stats: Option<Arc<JniCallStats>>) {
+    THREAD_JNI_CALL_STATS.with(|thread_stats| *thread_stats.borrow_mut() = stats);
+}
+
+/// runs a jni call and records its elapsed time to the call site.
+#[inline]
+pub fn record_jni_call<T>(site: &'static str, f: impl FnOnce() -> T) -> T {
+    THREAD_JNI_CALL_STATS.with(|thread_stats| match &*thread_stats.borrow() {
+        Some(stats) => {
+            let start_time = Instant::now();
+            let result = f();
+            stats.record(site, start_time.elapsed());
+            result
+        }
+        None => f(),
+    })
+}
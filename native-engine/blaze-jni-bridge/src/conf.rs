@@ -30,17 +30,70 @@ macro_rules! define_conf {
 
 define_conf!(IntConf, BATCH_SIZE);
 define_conf!(DoubleConf, MEMORY_FRACTION);
+define_conf!(StringConf, MEMORY_CONSUMER_SHARES);
 define_conf!(BooleanConf, SMJ_INEQUALITY_JOIN_ENABLE);
 define_conf!(BooleanConf, CASE_CONVERT_FUNCTIONS_ENABLE);
 define_conf!(BooleanConf, INPUT_BATCH_STATISTICS_ENABLE);
+define_conf!(BooleanConf, EXPR_METRICS_ENABLE);
+define_conf!(IntConf, EXPR_METRICS_SAMPLE_INTERVAL);
+define_conf!(IntConf, PREDICATE_CACHE_CAPACITY);
+define_conf!(BooleanConf, FILTER_ADAPTIVE_PREDICATE_ORDER_ENABLE);
 define_conf!(BooleanConf, IGNORE_CORRUPTED_FILES);
+define_conf!(BooleanConf, CASE_SENSITIVE);
 define_conf!(BooleanConf, PARTIAL_AGG_SKIPPING_ENABLE);
 define_conf!(DoubleConf, PARTIAL_AGG_SKIPPING_RATIO);
 define_conf!(IntConf, PARTIAL_AGG_SKIPPING_MIN_ROWS);
+define_conf!(BooleanConf, AGG_FIXED_KEY_HASHING_ENABLE);
 define_conf!(BooleanConf, PARQUET_ENABLE_PAGE_FILTERING);
 define_conf!(BooleanConf, PARQUET_ENABLE_BLOOM_FILTER);
+define_conf!(BooleanConf, PARQUET_ENABLE_FILTER_PUSHDOWN);
+define_conf!(BooleanConf, PARQUET_ENABLE_POINT_LOOKUP_PRUNING);
+define_conf!(BooleanConf, PARQUET_FIELD_ID_READ_ENABLED);
+define_conf!(DoubleConf, PARQUET_COLUMN_CHUNK_CACHE_FRACTION);
+define_conf!(BooleanConf, ORC_ENABLE_STRIPE_PRUNING);
+define_conf!(BooleanConf, ORC_FORCE_POSITIONAL_EVOLUTION);
 define_conf!(StringConf, SPARK_IO_COMPRESSION_CODEC);
+define_conf!(IntConf, SPARK_IO_COMPRESSION_ZSTD_LEVEL);
+define_conf!(StringConf, IPC_COMPRESSION_CODEC);
+define_conf!(DoubleConf, IPC_COMPRESSION_SKIP_RATIO);
 define_conf!(StringConf, SPILL_COMPRESSION_CODEC);
+define_conf!(IntConf, SPILL_COMPRESSION_LEVEL);
+define_conf!(StringConf, SORT_SPILL_COMPRESSION_CODEC);
+define_conf!(IntConf, SORT_SPILL_COMPRESSION_LEVEL);
+define_conf!(StringConf, AGG_SPILL_COMPRESSION_CODEC);
+define_conf!(IntConf, AGG_SPILL_COMPRESSION_LEVEL);
+define_conf!(StringConf, SPILL_REMOTE_DIR);
+define_conf!(LongConf, SPILL_LOCAL_DISK_QUOTA);
+define_conf!(LongConf, SPILL_TASK_DISK_QUOTA);
+define_conf!(LongConf, SPILL_EXECUTOR_DISK_QUOTA);
+define_conf!(BooleanConf, OFF_HEAP_MEMORY_ENABLED);
+define_conf!(LongConf, OFF_HEAP_MEMORY_SIZE);
+define_conf!(BooleanConf, SHUFFLE_OFF_HEAP_STAGING_ENABLE);
+define_conf!(DoubleConf, SHUFFLE_OFF_HEAP_STAGING_FRACTION);
+define_conf!(IntConf, REPLAY_LOG_STAGE);
+define_conf!(IntConf, REPLAY_LOG_PARTITION);
+define_conf!(StringConf, REPLAY_LOG_DIR);
+define_conf!(IntConf, METRICS_UPDATE_INTERVAL_MS);
+define_conf!(IntConf, TASK_CANCELLATION_CHECK_INTERVAL_MS);
+define_conf!(IntConf, JNI_CALL_MAX_RETRIES);
+define_conf!(IntConf, JNI_CALL_RETRY_BACKOFF_MS);
+define_conf!(IntConf, FFI_OUTPUT_MAX_ROWS);
+define_conf!(LongConf, FFI_OUTPUT_MAX_BYTES);
+define_conf!(IntConf, SHUFFLE_WRITE_FAILOVER_MAX_RETRIES);
+define_conf!(BooleanConf, SHJ_FALLBACK_TO_SMJ_ENABLE);
+define_conf!(LongConf, OUTPUT_BATCH_MAX_BYTES);
+define_conf!(IntConf, SMJ_SKEWED_KEY_MIN_ROWS);
+define_conf!(IntConf, SMJ_SUB_BATCH_SIZE);
+define_conf!(LongConf, COALESCE_TARGET_BATCH_BYTES);
+define_conf!(IntConf, COALESCE_MIN_BATCH_ROWS);
+define_conf!(IntConf, COALESCE_MAX_BATCH_ROWS);
+define_conf!(IntConf, IPC_READER_PREFETCH_DEPTH);
+define_conf!(IntConf, FLIGHT_SERVER_PORT);
+define_conf!(StringConf, FLIGHT_RESULT_ID);
+define_conf!(BooleanConf, EXPLAIN_ANALYZE_ENABLE);
+define_conf!(StringConf, EXPLAIN_ANALYZE_FORMAT);
+define_conf!(StringConf, TRACING_OTLP_ENDPOINT);
+define_conf!(StringConf, FS_NATIVE_URLS);
 
 pub trait BooleanConf {
     fn key(&self) -> &'static str;
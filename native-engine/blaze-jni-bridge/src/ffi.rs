@@ -0,0 +1,78 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Batch exchange with the JVM through the Arrow C Data Interface.
+//!
+//! batches are passed as pointers to `FFI_ArrowArray` structs and buffers are
+//! never copied. the receiving side takes the ownership by moving the struct
+//! out (the source struct is marked as released), and calls its release
+//! callback after all imported buffers are dropped. the exporting side keeps
+//! the struct alive until the call returns, and releases it if it was not
+//! moved, for example when the callee throws before importing.
+
+use datafusion::{
+    arrow::{
+        array::{Array, RecordBatch, RecordBatchOptions, StructArray},
+        datatypes::{DataType, SchemaRef},
+        ffi::{from_ffi_and_data_type, FFI_ArrowArray},
+    },
+    common::{DataFusionError, Result},
+};
+
+/// imports a batch exported by the JVM. `export` is called with the pointer
+/// of an empty `FFI_ArrowArray` struct, and returns false if there are no
+/// more batches.
+pub fn import_batch_from_jvm(
+    schema: &SchemaRef,
+    export: impl FnOnce(i64) -> Result<bool>,
+) -> Result<Option<RecordBatch>> {
+    // dropping an unconsumed struct calls its release callback, so the jvm
+    // side memory is not leaked if anything goes wrong after exporting
+    let mut ffi_array = FFI_ArrowArray::empty();
+    if !export(&mut ffi_array as *mut FFI_ArrowArray as i64)? {
+        return Ok(None);
+    }
+    if ffi_array.is_released() {
+        return Err(DataFusionError::Execution(
+            "jvm returned a released ffi array".to_string(),
+        ));
+    }
+
+    // safety: the struct is filled by the jvm exporter with the expected schema,
+    // the ownership is moved into the imported array data
+    let data_type = DataType::Struct(schema.fields().clone());
+    let imported = unsafe { from_ffi_and_data_type(ffi_array, data_type)? };
+    let struct_array = StructArray::from(imported);
+    Ok(Some(RecordBatch::try_new_with_options(
+        schema.clone(),
+        struct_array.columns().to_vec(),
+        &RecordBatchOptions::new().with_row_count(Some(struct_array.len())),
+    )?))
+}
+
+/// exports a batch to the JVM. `import` is called with the pointer of an
+/// `FFI_ArrowArray` struct referencing the batch buffers, the JVM importer
+/// moves the struct and releases the buffers after the imported vectors are
+/// closed.
+pub fn export_batch_to_jvm(
+    batch: RecordBatch,
+    import: impl FnOnce(i64) -> Result<()>,
+) -> Result<()> {
+    let struct_array = StructArray::from(batch);
+    let ffi_array = FFI_ArrowArray::new(&struct_array.to_data());
+    drop(struct_array); // buffers are now owned by the ffi array
+
+    // the struct is released when dropped here if the jvm did not move it
+    import(&ffi_array as *const FFI_ArrowArray as i64)
+}
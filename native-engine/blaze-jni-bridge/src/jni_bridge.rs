@@ -261,15 +261,18 @@ macro_rules! jni_call {
             $obj,
             stringify!($method),
             $crate::jvalues!($($args,)*));
-        $crate::jni_map_error_with_env!(
-            $env,
-            $env.call_method_unchecked(
-                $obj,
+        let obj = $obj;
+        let args = $crate::jvalues_sys!($($args,)*);
+        let result = $crate::call_stats::record_jni_call(
+            concat!(stringify!($clsname), ".", stringify!($method)),
+            || $env.call_method_unchecked(
+                obj,
                 $crate::jni_bridge::paste! {$crate::jni_bridge::JavaClasses::get().[<c $clsname>].[<method_ $method>]},
                 $crate::jni_bridge::paste! {$crate::jni_bridge::JavaClasses::get().[<c $clsname>].[<method_ $method _ret>]}.clone(),
-                $crate::jvalues_sys!($($args,)*)
-            )
-        )
+                args,
+            ),
+        );
+        $crate::jni_map_error_with_env!($env, result)
     }}
 }
 
@@ -297,15 +300,17 @@ macro_rules! jni_call_static {
             stringify!($clsname),
             stringify!($method),
             $crate::jvalues!($($args,)*));
-        $crate::jni_map_error_with_env!(
-            $env,
-            $env.call_static_method_unchecked(
+        let args = $crate::jvalues_sys!($($args,)*);
+        let result = $crate::call_stats::record_jni_call(
+            concat!(stringify!($clsname), ".", stringify!($method)),
+            || $env.call_static_method_unchecked(
                 $crate::jni_bridge::paste! {$crate::jni_bridge::JavaClasses::get().[<c $clsname>].class},
                 $crate::jni_bridge::paste! {$crate::jni_bridge::JavaClasses::get().[<c $clsname>].[<method_ $method>]},
                 $crate::jni_bridge::paste! {$crate::jni_bridge::JavaClasses::get().[<c $clsname>].[<method_ $method _ret>]}.clone(),
-                $crate::jvalues_sys!($($args,)*)
-            )
-        )
+                args,
+            ),
+        );
+        $crate::jni_map_error_with_env!($env, result)
     }}
 }
 
@@ -396,6 +401,7 @@ pub struct JavaClasses<'a> {
     pub cJavaLong: JavaLong<'a>,
     pub cJavaURI: JavaURI<'a>,
     pub cJavaBuffer: JavaBuffer<'a>,
+    pub cJavaString: JavaString<'a>,
 
     pub cScalaIterator: ScalaIterator<'a>,
     pub cScalaTuple2: ScalaTuple2<'a>,
@@ -411,10 +417,12 @@ pub struct JavaClasses<'a> {
     pub cSparkMetricNode: SparkMetricNode<'a>,
     pub cSparkUDFWrapperContext: SparkUDFWrapperContext<'a>,
     pub cSparkUDTFWrapperContext: SparkUDTFWrapperContext<'a>,
+    pub cSparkUDAFWrapperContext: SparkUDAFWrapperContext<'a>,
     pub cBlazeConf: BlazeConf<'a>,
     pub cBlazeRssPartitionWriterBase: BlazeRssPartitionWriterBase<'a>,
     pub cBlazeCallNativeWrapper: BlazeCallNativeWrapper<'a>,
     pub cBlazeOnHeapSpillManager: BlazeOnHeapSpillManager<'a>,
+    pub cBlazeOffHeapStagingMemory: BlazeOffHeapStagingMemory<'a>,
     pub cBlazeNativeParquetSinkUtils: BlazeNativeParquetSinkUtils<'a>,
     pub cBlazeBlockObject: BlazeBlockObject<'a>,
     pub cBlazeArrowFFIExporter: BlazeArrowFFIExporter<'a>,
@@ -457,6 +465,7 @@ impl JavaClasses<'static> {
                 cJavaAutoCloseable: JavaAutoCloseable::new(env)?,
                 cJavaURI: JavaURI::new(env)?,
                 cJavaBuffer: JavaBuffer::new(env)?,
+                cJavaString: JavaString::new(env)?,
 
                 cScalaIterator: ScalaIterator::new(env)?,
                 cScalaTuple2: ScalaTuple2::new(env)?,
@@ -472,10 +481,12 @@ impl JavaClasses<'static> {
                 cSparkMetricNode: SparkMetricNode::new(env)?,
                 cSparkUDFWrapperContext: SparkUDFWrapperContext::new(env)?,
                 cSparkUDTFWrapperContext: SparkUDTFWrapperContext::new(env)?,
+                cSparkUDAFWrapperContext: SparkUDAFWrapperContext::new(env)?,
                 cBlazeConf: BlazeConf::new(env)?,
                 cBlazeRssPartitionWriterBase: BlazeRssPartitionWriterBase::new(env)?,
                 cBlazeCallNativeWrapper: BlazeCallNativeWrapper::new(env)?,
                 cBlazeOnHeapSpillManager: BlazeOnHeapSpillManager::new(env)?,
+                cBlazeOffHeapStagingMemory: BlazeOffHeapStagingMemory::new(env)?,
                 cBlazeNativeParquetSinkUtils: BlazeNativeParquetSinkUtils::new(env)?,
                 cBlazeBlockObject: BlazeBlockObject::new(env)?,
                 cBlazeArrowFFIExporter: BlazeArrowFFIExporter::new(env)?,
@@ -523,6 +534,8 @@ pub struct JniBridge<'a> {
     pub method_getTaskContext_ret: ReturnType,
     pub method_getTaskOnHeapSpillManager: JStaticMethodID,
     pub method_getTaskOnHeapSpillManager_ret: ReturnType,
+    pub method_getTaskOffHeapStagingMemory: JStaticMethodID,
+    pub method_getTaskOffHeapStagingMemory_ret: ReturnType,
     pub method_isTaskRunning: JStaticMethodID,
     pub method_isTaskRunning_ret: ReturnType,
     pub method_isDriverSide: JStaticMethodID,
@@ -535,6 +548,14 @@ pub struct JniBridge<'a> {
     pub method_getDirectMemoryUsed_ret: ReturnType,
     pub method_getDirectWriteSpillToDiskFile: JStaticMethodID,
     pub method_getDirectWriteSpillToDiskFile_ret: ReturnType,
+    pub method_getExecutorHost: JStaticMethodID,
+    pub method_getExecutorHost_ret: ReturnType,
+    pub method_getAuthSecret: JStaticMethodID,
+    pub method_getAuthSecret_ret: ReturnType,
+    pub method_isReinvocableProvider: JStaticMethodID,
+    pub method_isReinvocableProvider_ret: ReturnType,
+    pub method_nextElements: JStaticMethodID,
+    pub method_nextElements_ret: ReturnType,
 }
 impl<'a> JniBridge<'a> {
     pub const SIG_TYPE: &'static str = "org/apache/spark/sql/blaze/JniBridge";
@@ -585,6 +606,12 @@ impl<'a> JniBridge<'a> {
                 "()Lorg/apache/spark/sql/blaze/memory/OnHeapSpillManager;",
             )?,
             method_getTaskOnHeapSpillManager_ret: ReturnType::Object,
+            method_getTaskOffHeapStagingMemory: env.get_static_method_id(
+                class,
+                "getTaskOffHeapStagingMemory",
+                "()Lorg/apache/spark/sql/blaze/memory/OffHeapStagingMemory;",
+            )?,
+            method_getTaskOffHeapStagingMemory_ret: ReturnType::Object,
             method_isTaskRunning: env.get_static_method_id(class, "isTaskRunning", "()Z")?,
             method_isTaskRunning_ret: ReturnType::Primitive(Primitive::Boolean),
             method_isDriverSide: env.get_static_method_id(class, "isDriverSide", "()Z")?,
@@ -613,6 +640,30 @@ impl<'a> JniBridge<'a> {
                 "()Ljava/lang/String;",
             )?,
             method_getDirectWriteSpillToDiskFile_ret: ReturnType::Object,
+            method_getExecutorHost: env.get_static_method_id(
+                class,
+                "getExecutorHost",
+                "()Ljava/lang/String;",
+            )?,
+            method_getExecutorHost_ret: ReturnType::Object,
+            method_getAuthSecret: env.get_static_method_id(
+                class,
+                "getAuthSecret",
+                "()Ljava/lang/String;",
+            )?,
+            method_getAuthSecret_ret: ReturnType::Object,
+            method_isReinvocableProvider: env.get_static_method_id(
+                class,
+                "isReinvocableProvider",
+                "(Ljava/lang/Object;)Z",
+            )?,
+            method_isReinvocableProvider_ret: ReturnType::Primitive(Primitive::Boolean),
+            method_nextElements: env.get_static_method_id(
+                class,
+                "nextElements",
+                "(Lscala/collection/Iterator;I)[Ljava/lang/Object;",
+            )?,
+            method_nextElements_ret: ReturnType::Array,
         })
     }
 }
@@ -812,6 +863,19 @@ impl<'a> JavaBuffer<'a> {
     }
 }
 
+#[allow(non_snake_case)]
+pub struct JavaString<'a> {
+    pub class: JClass<'a>,
+}
+impl<'a> JavaString<'a> {
+    pub const SIG_TYPE: &'static str = "java/lang/String";
+
+    pub fn new(env: &JNIEnv<'a>) -> JniResult<JavaString<'a>> {
+        let class = get_global_jclass(env, Self::SIG_TYPE)?;
+        Ok(JavaString { class })
+    }
+}
+
 #[allow(non_snake_case)]
 pub struct ScalaIterator<'a> {
     pub class: JClass<'a>,
@@ -932,6 +996,10 @@ pub struct HadoopFileSystem<'a> {
     pub method_open_ret: ReturnType,
     pub method_create: JMethodID,
     pub method_create_ret: ReturnType,
+    pub method_delete: JMethodID,
+    pub method_delete_ret: ReturnType,
+    pub method_exists: JMethodID,
+    pub method_exists_ret: ReturnType,
 }
 impl<'a> HadoopFileSystem<'a> {
     pub const SIG_TYPE: &'static str = "org/apache/hadoop/fs/FileSystem";
@@ -954,6 +1022,10 @@ impl<'a> HadoopFileSystem<'a> {
                 "(Lorg/apache/hadoop/fs/Path;)Lorg/apache/hadoop/fs/FSDataOutputStream;",
             )?,
             method_create_ret: ReturnType::Object,
+            method_delete: env.get_method_id(class, "delete", "(Lorg/apache/hadoop/fs/Path;Z)Z")?,
+            method_delete_ret: ReturnType::Primitive(Primitive::Boolean),
+            method_exists: env.get_method_id(class, "exists", "(Lorg/apache/hadoop/fs/Path;)Z")?,
+            method_exists_ret: ReturnType::Primitive(Primitive::Boolean),
         })
     }
 }
@@ -1047,6 +1119,8 @@ pub struct SparkMetricNode<'a> {
     pub method_getChild_ret: ReturnType,
     pub method_add: JMethodID,
     pub method_add_ret: ReturnType,
+    pub method_addAll: JMethodID,
+    pub method_addAll_ret: ReturnType,
 }
 impl<'a> SparkMetricNode<'a> {
     pub const SIG_TYPE: &'static str = "org/apache/spark/sql/blaze/MetricNode";
@@ -1063,6 +1137,8 @@ impl<'a> SparkMetricNode<'a> {
             method_getChild_ret: ReturnType::Object,
             method_add: env.get_method_id(class, "add", "(Ljava/lang/String;J)V")?,
             method_add_ret: ReturnType::Primitive(Primitive::Void),
+            method_addAll: env.get_method_id(class, "addAll", "([Ljava/lang/String;[J)V")?,
+            method_addAll_ret: ReturnType::Primitive(Primitive::Void),
         })
     }
 }
@@ -1194,6 +1270,35 @@ impl<'a> SparkUDTFWrapperContext<'a> {
     }
 }
 
+#[allow(non_snake_case)]
+pub struct SparkUDAFWrapperContext<'a> {
+    pub class: JClass<'a>,
+    pub ctor: JMethodID,
+    pub method_update: JMethodID,
+    pub method_update_ret: ReturnType,
+    pub method_merge: JMethodID,
+    pub method_merge_ret: ReturnType,
+    pub method_eval: JMethodID,
+    pub method_eval_ret: ReturnType,
+}
+impl<'a> SparkUDAFWrapperContext<'a> {
+    pub const SIG_TYPE: &'static str = "org/apache/spark/sql/blaze/SparkUDAFWrapperContext";
+
+    pub fn new(env: &JNIEnv<'a>) -> JniResult<SparkUDAFWrapperContext<'a>> {
+        let class = get_global_jclass(env, Self::SIG_TYPE)?;
+        Ok(SparkUDAFWrapperContext {
+            class,
+            ctor: env.get_method_id(class, "<init>", "(Ljava/nio/ByteBuffer;)V")?,
+            method_update: env.get_method_id(class, "update", "(JJJ)V")?,
+            method_update_ret: ReturnType::Primitive(Primitive::Void),
+            method_merge: env.get_method_id(class, "merge", "(JJJ)V")?,
+            method_merge_ret: ReturnType::Primitive(Primitive::Void),
+            method_eval: env.get_method_id(class, "eval", "(JJ)V")?,
+            method_eval_ret: ReturnType::Primitive(Primitive::Void),
+        })
+    }
+}
+
 #[allow(non_snake_case)]
 pub struct BlazeCallNativeWrapper<'a> {
     pub class: JClass<'a>,
@@ -1207,6 +1312,10 @@ pub struct BlazeCallNativeWrapper<'a> {
     pub method_importBatch_ret: ReturnType,
     pub method_setError: JMethodID,
     pub method_setError_ret: ReturnType,
+    pub method_setSparkError: JMethodID,
+    pub method_setSparkError_ret: ReturnType,
+    pub method_setPlanDump: JMethodID,
+    pub method_setPlanDump_ret: ReturnType,
 }
 impl<'a> BlazeCallNativeWrapper<'a> {
     pub const SIG_TYPE: &'static str = "org/apache/spark/sql/blaze/BlazeCallNativeWrapper";
@@ -1233,6 +1342,14 @@ impl<'a> BlazeCallNativeWrapper<'a> {
             method_importBatch_ret: ReturnType::Primitive(Primitive::Void),
             method_setError: env.get_method_id(class, "setError", "(Ljava/lang/Throwable;)V")?,
             method_setError_ret: ReturnType::Primitive(Primitive::Void),
+            method_setSparkError: env.get_method_id(
+                class,
+                "setSparkError",
+                "(Ljava/lang/String;Ljava/lang/String;)V",
+            )?,
+            method_setSparkError_ret: ReturnType::Primitive(Primitive::Void),
+            method_setPlanDump: env.get_method_id(class, "setPlanDump", "(Ljava/lang/String;)V")?,
+            method_setPlanDump_ret: ReturnType::Primitive(Primitive::Void),
         })
     }
 }
@@ -1284,6 +1401,29 @@ impl<'a> BlazeOnHeapSpillManager<'a> {
     }
 }
 
+#[allow(non_snake_case)]
+pub struct BlazeOffHeapStagingMemory<'a> {
+    pub class: JClass<'a>,
+    pub method_acquire: JMethodID,
+    pub method_acquire_ret: ReturnType,
+    pub method_release: JMethodID,
+    pub method_release_ret: ReturnType,
+}
+impl<'a> BlazeOffHeapStagingMemory<'a> {
+    pub const SIG_TYPE: &'static str = "org/apache/spark/sql/blaze/memory/OffHeapStagingMemory";
+
+    pub fn new(env: &JNIEnv<'a>) -> JniResult<BlazeOffHeapStagingMemory<'a>> {
+        let class = get_global_jclass(env, Self::SIG_TYPE)?;
+        Ok(BlazeOffHeapStagingMemory {
+            class,
+            method_acquire: env.get_method_id(class, "acquire", "(J)J")?,
+            method_acquire_ret: ReturnType::Primitive(Primitive::Long),
+            method_release: env.get_method_id(class, "release", "(J)V")?,
+            method_release_ret: ReturnType::Primitive(Primitive::Void),
+        })
+    }
+}
+
 #[allow(non_snake_case)]
 pub struct BlazeNativeParquetSinkUtils<'a> {
     pub class: JClass<'a>,
@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::time::Duration;
+
 use datafusion::common::Result;
 use jni::{
     objects::GlobalRef,
@@ -19,7 +21,12 @@ use jni::{
 };
 use once_cell::sync::OnceCell;
 
+use crate::conf::IntConf;
+
+pub mod batched;
+pub mod call_stats;
 pub mod conf;
+pub mod ffi;
 pub mod jni_bridge;
 
 pub fn is_jni_bridge_inited() -> bool {
@@ -41,6 +48,61 @@ pub fn is_task_running() -> bool {
     is_task_running_impl().expect("calling JniBridge.isTaskRunning() error")
 }
 
+/// Calls a recoverable jni function with retrying. the function is retried
+/// with exponential backoff (spark.blaze.jniCall.retryBackoffMs) until it
+/// succeeds or the max number of retries (spark.blaze.jniCall.maxRetries) is
+/// reached. the function must be idempotent, non-idempotent calls (like taking
+/// a resource with `JniBridge.getResource()`) must not be retried.
+///
+/// fetch failures and errors after the task is killed are never retried, so
+/// that spark can handle them as usual.
+pub fn jni_call_with_retry<T>(desc: &str, f: impl FnMut() -> Result<T>) -> Result<T> {
+    if !is_jni_bridge_inited() {
+        // only for testing
+        return retry_with_backoff(desc, 0, 0, f);
+    }
+    let max_retries = conf::JNI_CALL_MAX_RETRIES.value()?.max(0) as u32;
+    let backoff_ms = conf::JNI_CALL_RETRY_BACKOFF_MS.value()?.max(0) as u64;
+    retry_with_backoff(desc, max_retries, backoff_ms, f)
+}
+
+fn retry_with_backoff<T>(
+    desc: &str,
+    max_retries: u32,
+    backoff_ms: u64,
+    mut f: impl FnMut() -> Result<T>,
+) -> Result<T> {
+    let mut num_retries = 0;
+    loop {
+        let err = match f() {
+            Ok(result) => return Ok(result),
+            Err(err) => err,
+        };
+        if num_retries >= max_retries || !is_retryable_jni_error(&err.to_string()) {
+            return Err(err);
+        }
+        if !is_task_running() {
+            return Err(err);
+        }
+        let backoff = Duration::from_millis(backoff_ms << num_retries.min(10));
+        num_retries += 1;
+        log::warn!("{desc} failed, retrying in {backoff:?} ({num_retries}/{max_retries}): {err}");
+        std::thread::sleep(backoff);
+    }
+}
+
+fn is_retryable_jni_error(message: &str) -> bool {
+    const NON_RETRYABLE_EXCEPTIONS: &[&str] = &[
+        "FetchFailedException",
+        "TaskKilledException",
+        "InterruptedException",
+        "OutOfMemoryError",
+    ];
+    !NON_RETRYABLE_EXCEPTIONS
+        .iter()
+        .any(|exception| message.contains(exception))
+}
+
 pub fn java_true() -> &'static GlobalRef {
     static OBJ_TRUE: OnceCell<GlobalRef> = OnceCell::new();
     OBJ_TRUE.get_or_init(|| {
@@ -56,3 +118,51 @@ pub fn java_false() -> &'static GlobalRef {
         jni_new_global_ref!(false_local.as_obj()).unwrap()
     })
 }
+
+#[cfg(test)]
+mod test {
+    use datafusion::common::{DataFusionError, Result};
+
+    use crate::retry_with_backoff;
+
+    #[test]
+    fn test_retry_with_backoff() -> Result<()> {
+        // the first attempt fails with a recoverable error
+        let mut num_calls = 0;
+        let result = retry_with_backoff("test", 3, 1, || {
+            num_calls += 1;
+            if num_calls == 1 {
+                return Err(DataFusionError::Execution(
+                    "java.io.IOException".to_string(),
+                ));
+            }
+            Ok(num_calls)
+        })?;
+        assert_eq!(result, 2);
+
+        // the original error is returned after exhausting retries
+        let mut num_calls = 0;
+        let err = retry_with_backoff("test", 2, 1, || -> Result<()> {
+            num_calls += 1;
+            Err(DataFusionError::Execution(format!(
+                "IOException #{num_calls}"
+            )))
+        })
+        .expect_err("retries are not exhausted");
+        assert_eq!(num_calls, 3);
+        assert!(err.to_string().contains("IOException #3"), "{err}");
+
+        // fetch failures are never retried
+        let mut num_calls = 0;
+        let err = retry_with_backoff("test", 3, 1, || -> Result<()> {
+            num_calls += 1;
+            Err(DataFusionError::Execution(
+                "FetchFailedException".to_string(),
+            ))
+        })
+        .expect_err("fetch failure is retried");
+        assert_eq!(num_calls, 1);
+        assert!(err.to_string().contains("FetchFailedException"), "{err}");
+        Ok(())
+    }
+}
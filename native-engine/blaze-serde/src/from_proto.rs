@@ -15,14 +15,15 @@
 //! Serde code to convert from protocol buffers to Rust data structures.
 
 use std::{
+    any::Any,
     convert::{TryFrom, TryInto},
     sync::Arc,
 };
 
 use arrow::{
-    array::RecordBatch,
+    array::{new_empty_array, RecordBatch},
     compute::SortOptions,
-    datatypes::{Field, FieldRef, SchemaRef},
+    datatypes::{DataType, Field, FieldRef, SchemaRef},
 };
 use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
 use datafusion::{
@@ -44,25 +45,46 @@ use datafusion::{
             BinaryExpr, CaseExpr, CastExpr, Column, IsNotNullExpr, IsNullExpr, Literal,
             NegativeExpr, NotExpr, PhysicalSortExpr,
         },
-        union::UnionExec,
+        joins::utils::{ColumnIndex, JoinFilter},
         ColumnStatistics, ExecutionPlan, Partitioning, PhysicalExpr, Statistics,
     },
     prelude::create_udf,
+    scalar::ScalarValue,
 };
-use datafusion_ext_commons::downcast_any;
+use datafusion_ext_commons::{arrow::spark_cast::CastMode, downcast_any};
 use datafusion_ext_exprs::{
-    bloom_filter_might_contain::BloomFilterMightContainExpr, cast::TryCastExpr,
-    get_indexed_field::GetIndexedFieldExpr, get_map_value::GetMapValueExpr,
-    named_struct::NamedStructExpr, row_num::RowNumExpr,
+    bloom_filter_might_contain::BloomFilterMightContainExpr,
+    cast::TryCastExpr,
+    create_map::CreateMapExpr,
+    decimal_arithmetic::{DecimalArithmeticExpr, DecimalArithmeticOp},
+    from_json::FromJsonExpr,
+    get_indexed_field::GetIndexedFieldExpr,
+    get_map_value::GetMapValueExpr,
+    higher_order_function::{HigherOrderFunction, HigherOrderFunctionExpr, LambdaFunction},
+    input_file_name::InputFileNameExpr,
+    lambda_variable::LambdaVariableExpr,
+    monotonically_increasing_id::MonotonicallyIncreasingIdExpr,
+    named_struct::NamedStructExpr,
+    raise_error::RaiseErrorExpr,
+    row_num::RowNumExpr,
+    spark_partition_id::SparkPartitionIdExpr,
     spark_scalar_subquery_wrapper::SparkScalarSubqueryWrapperExpr,
-    spark_udf_wrapper::SparkUDFWrapperExpr, string_contains::StringContainsExpr,
-    string_ends_with::StringEndsWithExpr, string_starts_with::StringStartsWithExpr,
+    spark_udf_wrapper::SparkUDFWrapperExpr,
+    string_contains::StringContainsExpr,
+    string_ends_with::StringEndsWithExpr,
+    string_starts_with::StringStartsWithExpr,
 };
 use datafusion_ext_plans::{
-    agg::{agg::create_agg, AggExecMode, AggExpr, AggFunction, AggMode, GroupingExpr},
+    agg::{
+        agg::{create_agg, create_udaf_agg},
+        AggExecMode, AggExpr, AggFunction, AggMode, GroupingExpr,
+    },
     agg_exec::AggExec,
     broadcast_join_build_hash_map_exec::BroadcastJoinBuildHashMapExec,
     broadcast_join_exec::BroadcastJoinExec,
+    broadcast_nested_loop_join_exec::BroadcastNestedLoopJoinExec,
+    coalesce_partitions_exec::CoalescePartitionsExec,
+    csv_exec::CsvExec,
     debug_exec::DebugExec,
     empty_partitions_exec::EmptyPartitionsExec,
     expand_exec::ExpandExec,
@@ -70,20 +92,39 @@ use datafusion_ext_plans::{
     filter_exec::FilterExec,
     generate::{create_generator, create_udtf_generator},
     generate_exec::GenerateExec,
+    interval_join_exec::IntervalJoinExec,
     ipc_reader_exec::IpcReaderExec,
     ipc_writer_exec::IpcWriterExec,
+    json_exec::JsonExec,
     limit_exec::LimitExec,
     orc_exec::OrcExec,
     parquet_exec::ParquetExec,
     parquet_sink_exec::ParquetSinkExec,
     project_exec::ProjectExec,
+    range_exec::RangeExec,
     rename_columns_exec::RenameColumnsExec,
     rss_shuffle_writer_exec::RssShuffleWriterExec,
+    scan::{
+        aggregate_pushdown::{PushedAggFunction, PushedAggregate},
+        csv::CsvOptions,
+        deletion_vector::DeletionVectorDescriptor,
+        json::JsonOptions,
+        partition_pruning::DynamicPartitionFilter,
+        text::ParseMode,
+    },
+    shuffle::{range_partitioning::RangePartitioning, ShufflePartitioning},
     shuffle_writer_exec::ShuffleWriterExec,
     sort_exec::SortExec,
     sort_merge_join_exec::SortMergeJoinExec,
-    window::{WindowExpr, WindowFunction, WindowRankType},
+    take_ordered_and_project_exec::TakeOrderedAndProjectExec,
+    top_k_frequent_exec::TopKFrequentExec,
+    union_exec::{UnionExec, UnionInput},
+    window::{
+        WindowExpr, WindowFrame, WindowFrameBound, WindowFunction, WindowOffsetType,
+        WindowRankType, WindowValueType,
+    },
     window_exec::WindowExec,
+    window_group_limit_exec::WindowGroupLimitExec,
 };
 use object_store::{path::Path, ObjectMeta};
 
@@ -154,7 +195,7 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
             }
             PhysicalPlanType::Filter(filter) => {
                 let input: Arc<dyn ExecutionPlan> = convert_box_required!(filter.input)?;
-                let predicates = filter
+                let predicates: Vec<Arc<dyn PhysicalExpr>> = filter
                     .expr
                     .iter()
                     .map(|expr| {
@@ -164,7 +205,21 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
                         )?)
                     })
                     .collect::<Result<_, Self::Error>>()?;
-                Ok(Arc::new(FilterExec::try_new(predicates, input)?))
+                let runtime_filters = filter
+                    .runtime_filters
+                    .iter()
+                    .map(|runtime_filter| {
+                        let key = bind(
+                            try_parse_physical_expr_required(&runtime_filter.key, &input.schema())?,
+                            &input.schema(),
+                        )?;
+                        Ok((key, runtime_filter.id.clone()))
+                    })
+                    .collect::<Result<_, Self::Error>>()?;
+                let input = push_down_filter_predicates(input, &predicates)?;
+                Ok(Arc::new(
+                    FilterExec::try_new(predicates, input)?.with_runtime_filters(runtime_filters),
+                ))
             }
             PhysicalPlanType::ParquetScan(scan) => {
                 let conf: FileScanConfig = scan.base_conf.as_ref().unwrap().try_into()?;
@@ -177,11 +232,16 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
                     .fold(phys_expr::lit(true), |a, b| {
                         Arc::new(BinaryExpr::new(a, Operator::And, b))
                     });
-                Ok(Arc::new(ParquetExec::new(
-                    conf,
-                    scan.fs_resource_id.clone(),
-                    Some(predicate),
-                )))
+                let dynamic_partition_filters =
+                    parse_dynamic_partition_filters(scan.base_conf.as_ref().unwrap());
+                let mut scan_exec =
+                    ParquetExec::new(conf, scan.fs_resource_id.clone(), Some(predicate))
+                        .with_dynamic_partition_filters(dynamic_partition_filters);
+                if !scan.pushed_aggregates.is_empty() {
+                    scan_exec = scan_exec
+                        .with_pushed_aggregates(parse_pushed_aggregates(&scan.pushed_aggregates));
+                }
+                Ok(Arc::new(scan_exec))
             }
             PhysicalPlanType::OrcScan(scan) => {
                 let conf: FileScanConfig = scan.base_conf.as_ref().unwrap().try_into()?;
@@ -194,11 +254,55 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
                     .fold(phys_expr::lit(true), |a, b| {
                         Arc::new(BinaryExpr::new(a, Operator::And, b))
                     });
-                Ok(Arc::new(OrcExec::new(
-                    conf,
-                    scan.fs_resource_id.clone(),
-                    Some(predicate),
-                )))
+                let dynamic_partition_filters =
+                    parse_dynamic_partition_filters(scan.base_conf.as_ref().unwrap());
+                let mut scan_exec =
+                    OrcExec::new(conf, scan.fs_resource_id.clone(), Some(predicate))
+                        .with_dynamic_partition_filters(dynamic_partition_filters);
+                if !scan.pushed_aggregates.is_empty() {
+                    scan_exec = scan_exec
+                        .with_pushed_aggregates(parse_pushed_aggregates(&scan.pushed_aggregates));
+                }
+                Ok(Arc::new(scan_exec))
+            }
+            PhysicalPlanType::CsvScan(scan) => {
+                let conf: FileScanConfig = scan.base_conf.as_ref().unwrap().try_into()?;
+                let options = scan.options.as_ref().expect("missing csv options");
+                let csv_options = CsvOptions {
+                    delimiter: options.delimiter.clone(),
+                    quote: options.quote.chars().next(),
+                    escape: options.escape.chars().next(),
+                    header: options.header,
+                    null_value: options.null_value.clone(),
+                    date_format: options.date_format.clone(),
+                    mode: protobuf::ParseMode::try_from(options.mode)
+                        .expect("invalid ParseMode")
+                        .into(),
+                    column_name_of_corrupt_record: options.column_name_of_corrupt_record.clone(),
+                };
+                let dynamic_partition_filters =
+                    parse_dynamic_partition_filters(scan.base_conf.as_ref().unwrap());
+                Ok(Arc::new(
+                    CsvExec::try_new(conf, scan.fs_resource_id.clone(), csv_options)?
+                        .with_dynamic_partition_filters(dynamic_partition_filters),
+                ))
+            }
+            PhysicalPlanType::JsonScan(scan) => {
+                let conf: FileScanConfig = scan.base_conf.as_ref().unwrap().try_into()?;
+                let options = scan.options.as_ref().expect("missing json options");
+                let json_options = JsonOptions {
+                    date_format: options.date_format.clone(),
+                    mode: protobuf::ParseMode::try_from(options.mode)
+                        .expect("invalid ParseMode")
+                        .into(),
+                    column_name_of_corrupt_record: options.column_name_of_corrupt_record.clone(),
+                };
+                let dynamic_partition_filters =
+                    parse_dynamic_partition_filters(scan.base_conf.as_ref().unwrap());
+                Ok(Arc::new(
+                    JsonExec::try_new(conf, scan.fs_resource_id.clone(), json_options)?
+                        .with_dynamic_partition_filters(dynamic_partition_filters),
+                ))
             }
             PhysicalPlanType::HashJoin(hash_join) => {
                 let schema = Arc::new(convert_required!(hash_join.schema)?);
@@ -269,22 +373,29 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
                 let join_type = protobuf::JoinType::try_from(sort_merge_join.join_type)
                     .expect("invalid JoinType");
 
-                Ok(Arc::new(SortMergeJoinExec::try_new(
-                    schema,
-                    left,
-                    right,
-                    on,
-                    join_type
-                        .try_into()
-                        .map_err(|_| proto_error("invalid JoinType"))?,
-                    sort_options,
-                )?))
+                Ok(Arc::new(
+                    SortMergeJoinExec::try_new(
+                        schema,
+                        left,
+                        right,
+                        on,
+                        join_type
+                            .try_into()
+                            .map_err(|_| proto_error("invalid JoinType"))?,
+                        sort_options,
+                        sort_merge_join.null_equals_null,
+                    )?
+                    .with_runtime_filter_ids(
+                        parse_runtime_filter_ids(&sort_merge_join.left_runtime_filter_ids),
+                        parse_runtime_filter_ids(&sort_merge_join.right_runtime_filter_ids),
+                    ),
+                ))
             }
             PhysicalPlanType::ShuffleWriter(shuffle_writer) => {
                 let input: Arc<dyn ExecutionPlan> = convert_box_required!(shuffle_writer.input)?;
 
-                let output_partitioning = parse_protobuf_partitioning(
-                    input.clone(),
+                let output_partitioning = parse_protobuf_shuffle_partitioning(
+                    &input.schema(),
                     shuffle_writer.output_partitioning.as_ref(),
                 )?;
 
@@ -299,8 +410,8 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
                 let input: Arc<dyn ExecutionPlan> =
                     convert_box_required!(rss_shuffle_writer.input)?;
 
-                let output_partitioning = parse_protobuf_partitioning(
-                    input.clone(),
+                let output_partitioning = parse_protobuf_shuffle_partitioning(
+                    &input.schema(),
                     rss_shuffle_writer.output_partitioning.as_ref(),
                 )?;
                 Ok(Arc::new(RssShuffleWriterExec::try_new(
@@ -319,10 +430,13 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
             }
             PhysicalPlanType::IpcReader(ipc_reader) => {
                 let schema = Arc::new(convert_required!(ipc_reader.schema)?);
+                let partitioning =
+                    parse_protobuf_partitioning(&schema, ipc_reader.partitioning.as_ref())?;
                 Ok(Arc::new(IpcReaderExec::new(
                     ipc_reader.num_partitions as usize,
                     ipc_reader.ipc_provider_resource_id.clone(),
                     schema,
+                    partitioning,
                 )))
             }
             PhysicalPlanType::Debug(debug) => {
@@ -417,28 +531,148 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
 
                 let cached_build_hash_map_id = broadcast_join.cached_build_hash_map_id.clone();
 
-                Ok(Arc::new(BroadcastJoinExec::try_new(
+                Ok(Arc::new(
+                    BroadcastJoinExec::try_new(
+                        schema,
+                        left,
+                        right,
+                        on,
+                        join_type
+                            .try_into()
+                            .map_err(|_| proto_error("invalid JoinType"))?,
+                        broadcast_side
+                            .try_into()
+                            .map_err(|_| proto_error("invalid BroadcastSide"))?,
+                        true,
+                        Some(cached_build_hash_map_id),
+                    )?
+                    .with_runtime_filter_ids(parse_runtime_filter_ids(
+                        &broadcast_join.runtime_filter_ids,
+                    )),
+                ))
+            }
+            PhysicalPlanType::BroadcastNestedLoopJoin(bnlj) => {
+                let schema = Arc::new(convert_required!(bnlj.schema)?);
+                let left: Arc<dyn ExecutionPlan> = convert_box_required!(bnlj.left)?;
+                let right: Arc<dyn ExecutionPlan> = convert_box_required!(bnlj.right)?;
+
+                let join_type =
+                    protobuf::JoinType::try_from(bnlj.join_type).expect("invalid JoinType");
+                let broadcast_side = protobuf::JoinSide::try_from(bnlj.broadcast_side)
+                    .expect("invalid BroadcastSide");
+
+                // join filter is evaluated on an intermediate schema, whose
+                // columns are referenced from the left/right side
+                let join_filter = bnlj
+                    .join_filter
+                    .as_ref()
+                    .map(|join_filter| {
+                        let filter_schema = Arc::new(convert_required!(join_filter.schema)?);
+                        let expression = bind(
+                            try_parse_physical_expr_required(
+                                &join_filter.expression,
+                                &filter_schema,
+                            )?,
+                            &filter_schema,
+                        )?;
+                        let column_indices = join_filter
+                            .column_indices
+                            .iter()
+                            .map(|column_index| {
+                                let side = protobuf::JoinSide::try_from(column_index.side)
+                                    .expect("invalid JoinSide");
+                                ColumnIndex {
+                                    index: column_index.index as usize,
+                                    side: side.into(),
+                                }
+                            })
+                            .collect();
+                        Ok::<_, PlanSerDeError>(JoinFilter::new(
+                            expression,
+                            column_indices,
+                            filter_schema.as_ref().clone(),
+                        ))
+                    })
+                    .transpose()?;
+
+                Ok(Arc::new(BroadcastNestedLoopJoinExec::try_new(
+                    schema,
+                    left,
+                    right,
+                    join_type
+                        .try_into()
+                        .map_err(|_| proto_error("invalid JoinType"))?,
+                    broadcast_side
+                        .try_into()
+                        .map_err(|_| proto_error("invalid BroadcastSide"))?,
+                    join_filter,
+                )?))
+            }
+            PhysicalPlanType::IntervalJoin(interval_join) => {
+                let schema = Arc::new(convert_required!(interval_join.schema)?);
+                let left: Arc<dyn ExecutionPlan> = convert_box_required!(interval_join.left)?;
+                let right: Arc<dyn ExecutionPlan> = convert_box_required!(interval_join.right)?;
+
+                let join_type = protobuf::JoinType::try_from(interval_join.join_type)
+                    .expect("invalid JoinType");
+                let broadcast_side = protobuf::JoinSide::try_from(interval_join.broadcast_side)
+                    .expect("invalid BroadcastSide");
+
+                let parse_bound =
+                    |expr: &Option<protobuf::PhysicalExprNode>,
+                     schema: &SchemaRef|
+                     -> Result<Arc<dyn PhysicalExpr>, PlanSerDeError> {
+                        Ok(bind(
+                            try_parse_physical_expr_required(expr, schema)?,
+                            schema,
+                        )?)
+                    };
+                let left_bounds = (
+                    parse_bound(&interval_join.left_start, &left.schema())?,
+                    parse_bound(&interval_join.left_end, &left.schema())?,
+                );
+                let right_bounds = (
+                    parse_bound(&interval_join.right_start, &right.schema())?,
+                    parse_bound(&interval_join.right_end, &right.schema())?,
+                );
+
+                Ok(Arc::new(IntervalJoinExec::try_new(
                     schema,
                     left,
                     right,
-                    on,
                     join_type
                         .try_into()
                         .map_err(|_| proto_error("invalid JoinType"))?,
                     broadcast_side
                         .try_into()
                         .map_err(|_| proto_error("invalid BroadcastSide"))?,
-                    true,
-                    Some(cached_build_hash_map_id),
+                    left_bounds,
+                    right_bounds,
+                    interval_join.left_end_inclusive,
+                    interval_join.right_end_inclusive,
                 )?))
             }
             PhysicalPlanType::Union(union) => {
-                let inputs: Vec<Arc<dyn ExecutionPlan>> = union
-                    .children
+                let schema = Arc::new(convert_required!(union.schema)?);
+                let inputs = union
+                    .input
                     .iter()
-                    .map(|i| i.try_into())
-                    .collect::<Result<Vec<_>, _>>()?;
-                Ok(Arc::new(UnionExec::new(inputs)))
+                    .map(|input| {
+                        let plan: Arc<dyn ExecutionPlan> = convert_required!(input.input)?;
+                        Ok(UnionInput(plan, input.partition as usize))
+                    })
+                    .collect::<Result<Vec<_>, PlanSerDeError>>()?;
+                Ok(Arc::new(UnionExec::new(
+                    inputs,
+                    schema,
+                    union.num_partitions as usize,
+                    union.cur_partition as usize,
+                )))
+            }
+            PhysicalPlanType::CoalescePartitions(coalesce_partitions) => {
+                let input: Arc<dyn ExecutionPlan> =
+                    convert_box_required!(coalesce_partitions.input)?;
+                Ok(Arc::new(CoalescePartitionsExec::new(input)))
             }
             PhysicalPlanType::EmptyPartitions(empty_partitions) => {
                 let schema = Arc::new(convert_required!(empty_partitions.schema)?);
@@ -447,6 +681,16 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
                     empty_partitions.num_partitions as usize,
                 )))
             }
+            PhysicalPlanType::Range(range) => {
+                let schema = Arc::new(convert_required!(range.schema)?);
+                Ok(Arc::new(RangeExec::try_new(
+                    range.start,
+                    range.end,
+                    range.step,
+                    range.num_slices as usize,
+                    schema,
+                )?))
+            }
             PhysicalPlanType::RenameColumns(rename_columns) => {
                 let input: Arc<dyn ExecutionPlan> = convert_box_required!(rename_columns.input)?;
                 Ok(Arc::new(RenameColumnsExec::try_new(
@@ -477,15 +721,22 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
                     })
                     .collect::<Vec<_>>();
 
+                let grouping_types = agg
+                    .grouping_type
+                    .iter()
+                    .map(|data_type| data_type.try_into())
+                    .collect::<Result<Vec<DataType>, _>>()?;
                 let physical_groupings: Vec<GroupingExpr> = agg
                     .grouping_expr
                     .iter()
                     .zip(agg.grouping_expr_name.iter())
-                    .map(|(expr, name)| {
+                    .enumerate()
+                    .map(|(idx, (expr, name))| {
                         try_parse_physical_expr(expr, &input_schema).and_then(|expr| {
                             Ok(bind(expr, &input_schema).map(|expr| GroupingExpr {
                                 expr,
                                 field_name: name.to_owned(),
+                                key_type: grouping_types.get(idx).cloned(),
                             })?)
                         })
                     })
@@ -521,12 +772,24 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
                             })
                             .collect::<Result<Vec<_>, _>>()?;
 
+                        let agg = match AggFunction::from(agg_function) {
+                            AggFunction::Udaf => {
+                                let udaf = agg_node.udaf.as_ref().unwrap();
+                                let serialized = udaf.serialized.clone();
+                                let return_type = convert_required!(udaf.return_type)?;
+                                create_udaf_agg(
+                                    serialized,
+                                    return_type,
+                                    udaf.return_nullable,
+                                    agg_children_exprs,
+                                )?
+                            }
+                            agg_function => {
+                                create_agg(agg_function, &agg_children_exprs, &input_schema)?
+                            }
+                        };
                         Ok(AggExpr {
-                            agg: create_agg(
-                                AggFunction::from(agg_function),
-                                &agg_children_exprs,
-                                &input_schema,
-                            )?,
+                            agg,
                             mode,
                             field_name: name.to_owned(),
                         })
@@ -543,7 +806,7 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
             }
             PhysicalPlanType::Limit(limit) => {
                 let input: Arc<dyn ExecutionPlan> = convert_box_required!(limit.input)?;
-                Ok(Arc::new(LimitExec::new(input, limit.limit)))
+                Ok(Arc::new(LimitExec::new(input, limit.limit, limit.offset)))
             }
             PhysicalPlanType::FfiReader(ffi_reader) => {
                 let schema = Arc::new(convert_required!(ffi_reader.schema)?);
@@ -555,7 +818,11 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
             }
             PhysicalPlanType::CoalesceBatches(coalesce_batches) => {
                 let input: Arc<dyn ExecutionPlan> = convert_box_required!(coalesce_batches.input)?;
-                Ok(Arc::new(LimitExec::new(input, coalesce_batches.batch_size)))
+                Ok(Arc::new(LimitExec::new(
+                    input,
+                    coalesce_batches.batch_size,
+                    0,
+                )))
             }
             PhysicalPlanType::Expand(expand) => {
                 let schema = Arc::new(convert_required!(expand.schema)?);
@@ -619,6 +886,18 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
                                 protobuf::WindowFunction::DenseRank => {
                                     WindowFunction::RankLike(WindowRankType::DenseRank)
                                 }
+                                protobuf::WindowFunction::Lag => {
+                                    WindowFunction::Offset(WindowOffsetType::Lag)
+                                }
+                                protobuf::WindowFunction::Lead => {
+                                    WindowFunction::Offset(WindowOffsetType::Lead)
+                                }
+                                protobuf::WindowFunction::FirstValue => {
+                                    WindowFunction::Value(WindowValueType::FirstValue)
+                                }
+                                protobuf::WindowFunction::LastValue => {
+                                    WindowFunction::Value(WindowValueType::LastValue)
+                                }
                             },
                             protobuf::WindowFunctionType::Agg => match w.agg_func() {
                                 protobuf::AggFunction::Min => WindowFunction::Agg(AggFunction::Min),
@@ -649,9 +928,18 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
                                 protobuf::AggFunction::BrickhouseCombineUnique => {
                                     WindowFunction::Agg(AggFunction::BrickhouseCombineUnique)
                                 }
+                                protobuf::AggFunction::Udaf => {
+                                    WindowFunction::Agg(AggFunction::Udaf)
+                                }
                             },
                         };
-                        Ok::<_, Self::Error>(WindowExpr::new(window_func, children, field))
+                        let frame = w.frame.as_ref().map(WindowFrame::from).unwrap_or_default();
+                        Ok::<_, Self::Error>(
+                            WindowExpr::new(window_func, children, field)
+                                .with_frame(frame)
+                                .with_offset(w.offset)
+                                .with_ignore_nulls(w.ignore_nulls),
+                        )
                     })
                     .collect::<Result<Vec<_>, _>>()?;
 
@@ -747,6 +1035,16 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
                         datafusion_ext_plans::generate::GenerateFunc::JsonTuple,
                         children,
                     )?,
+                    GenerateFunction::Inline => create_generator(
+                        &input_schema,
+                        datafusion_ext_plans::generate::GenerateFunc::Inline,
+                        children,
+                    )?,
+                    GenerateFunction::Stack => create_generator(
+                        &input_schema,
+                        datafusion_ext_plans::generate::GenerateFunc::Stack,
+                        children,
+                    )?,
                     GenerateFunction::Udtf => {
                         let udtf = pb_generator.udtf.as_ref().unwrap();
                         let serialized = udtf.serialized.clone();
@@ -781,12 +1079,169 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
                 for prop in &parquet_sink.prop {
                     props.push((prop.key.clone(), prop.value.clone()));
                 }
-                Ok(Arc::new(ParquetSinkExec::new(
-                    convert_box_required!(parquet_sink.input)?,
-                    parquet_sink.fs_resource_id.clone(),
-                    parquet_sink.num_dyn_parts as usize,
-                    props,
-                )))
+                let bucket_columns = parquet_sink
+                    .bucket_columns
+                    .iter()
+                    .map(|&idx| idx as usize)
+                    .collect();
+                Ok(Arc::new(
+                    ParquetSinkExec::new(
+                        convert_box_required!(parquet_sink.input)?,
+                        parquet_sink.fs_resource_id.clone(),
+                        parquet_sink.num_dyn_parts as usize,
+                        props,
+                    )
+                    .with_bucketing(bucket_columns, parquet_sink.num_buckets as usize)
+                    .with_max_concurrent_writers(parquet_sink.max_concurrent_writers as usize),
+                ))
+            }
+            PhysicalPlanType::TopKFrequent(top_k_frequent) => {
+                let input: Arc<dyn ExecutionPlan> = convert_box_required!(top_k_frequent.input)?;
+                let expr = bind(
+                    try_parse_physical_expr_required(&top_k_frequent.expr, &input.schema())?,
+                    &input.schema(),
+                )?;
+                Ok(Arc::new(TopKFrequentExec::try_new(
+                    input,
+                    expr,
+                    top_k_frequent.k as usize,
+                )?))
+            }
+            PhysicalPlanType::TakeOrderedAndProject(take_ordered) => {
+                let input: Arc<dyn ExecutionPlan> = convert_box_required!(take_ordered.input)?;
+                let sort_exprs = take_ordered
+                    .sort_expr
+                    .iter()
+                    .map(|expr| {
+                        let expr = expr.expr_type.as_ref().ok_or_else(|| {
+                            proto_error(format!(
+                                "physical_plan::from_proto() Unexpected expr {:?}",
+                                self
+                            ))
+                        })?;
+                        if let ExprType::Sort(sort_expr) = expr {
+                            let expr = sort_expr
+                                .expr
+                                .as_ref()
+                                .ok_or_else(|| {
+                                    proto_error(format!(
+                                        "physical_plan::from_proto() Unexpected sort expr {:?}",
+                                        self
+                                    ))
+                                })?
+                                .as_ref();
+                            Ok(PhysicalSortExpr {
+                                expr: bind(
+                                    try_parse_physical_expr(expr, &input.schema())?,
+                                    &input.schema(),
+                                )?,
+                                options: SortOptions {
+                                    descending: !sort_expr.asc,
+                                    nulls_first: sort_expr.nulls_first,
+                                },
+                            })
+                        } else {
+                            Err(PlanSerDeError::General(format!(
+                                "physical_plan::from_proto() {:?}",
+                                self
+                            )))
+                        }
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                let projection = take_ordered
+                    .projection_expr
+                    .iter()
+                    .zip(take_ordered.projection_expr_name.iter())
+                    .map(|(expr, name)| {
+                        Ok((
+                            bind(
+                                try_parse_physical_expr(expr, &input.schema())?,
+                                &input.schema(),
+                            )?,
+                            name.to_string(),
+                        ))
+                    })
+                    .collect::<Result<Vec<(Arc<dyn PhysicalExpr>, String)>, Self::Error>>()?;
+                Ok(Arc::new(TakeOrderedAndProjectExec::try_new(
+                    input,
+                    sort_exprs,
+                    take_ordered.limit as usize,
+                    take_ordered.offset as usize,
+                    projection,
+                )?))
+            }
+            PhysicalPlanType::WindowGroupLimit(window_group_limit) => {
+                let input: Arc<dyn ExecutionPlan> =
+                    convert_box_required!(window_group_limit.input)?;
+                let partition_specs = window_group_limit
+                    .partition_spec
+                    .iter()
+                    .map(|expr| {
+                        Ok(bind(
+                            try_parse_physical_expr(expr, &input.schema())?,
+                            &input.schema(),
+                        )?)
+                    })
+                    .collect::<Result<Vec<_>, Self::Error>>()?;
+
+                let order_specs = window_group_limit
+                    .order_spec
+                    .iter()
+                    .map(|expr| {
+                        let expr = expr.expr_type.as_ref().ok_or_else(|| {
+                            proto_error(format!(
+                                "physical_plan::from_proto() Unexpected expr {:?}",
+                                self
+                            ))
+                        })?;
+                        if let protobuf::physical_expr_node::ExprType::Sort(sort_expr) = expr {
+                            let expr = sort_expr
+                                .expr
+                                .as_ref()
+                                .ok_or_else(|| {
+                                    proto_error(format!(
+                                        "physical_plan::from_proto() Unexpected sort expr {:?}",
+                                        self
+                                    ))
+                                })?
+                                .as_ref();
+                            Ok(PhysicalSortExpr {
+                                expr: bind(
+                                    try_parse_physical_expr(expr, &input.schema())?,
+                                    &input.schema(),
+                                )?,
+                                options: SortOptions {
+                                    descending: !sort_expr.asc,
+                                    nulls_first: sort_expr.nulls_first,
+                                },
+                            })
+                        } else {
+                            Err(PlanSerDeError::General(format!(
+                                "physical_plan::from_proto() {:?}",
+                                self
+                            )))
+                        }
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let rank_type = match window_group_limit.rank_func() {
+                    protobuf::WindowFunction::RowNumber => WindowRankType::RowNumber,
+                    protobuf::WindowFunction::Rank => WindowRankType::Rank,
+                    protobuf::WindowFunction::DenseRank => WindowRankType::DenseRank,
+                    other => {
+                        return Err(PlanSerDeError::General(format!(
+                            "physical_plan::from_proto() unsupported window group limit function: {:?}",
+                            other
+                        )));
+                    }
+                };
+                Ok(Arc::new(WindowGroupLimitExec::try_new(
+                    input,
+                    partition_specs,
+                    order_specs,
+                    rank_type,
+                    window_group_limit.limit as usize,
+                )?))
             }
         }
     }
@@ -804,6 +1259,91 @@ impl From<&protobuf::BoundReference> for Column {
     }
 }
 
+impl From<&protobuf::WindowFrame> for WindowFrame {
+    fn from(frame: &protobuf::WindowFrame) -> Self {
+        let parse_bound = |bound: Option<&protobuf::WindowFrameBound>| match bound {
+            Some(bound) => match bound.bound_type() {
+                protobuf::WindowFrameBoundType::UnboundedPreceding => {
+                    WindowFrameBound::UnboundedPreceding
+                }
+                protobuf::WindowFrameBoundType::Preceding => {
+                    WindowFrameBound::Preceding(bound.offset as usize)
+                }
+                protobuf::WindowFrameBoundType::CurrentRow => WindowFrameBound::CurrentRow,
+                protobuf::WindowFrameBoundType::Following => {
+                    WindowFrameBound::Following(bound.offset as usize)
+                }
+                protobuf::WindowFrameBoundType::UnboundedFollowing => {
+                    WindowFrameBound::UnboundedFollowing
+                }
+            },
+            None => WindowFrameBound::CurrentRow,
+        };
+        let start = parse_bound(frame.start.as_ref());
+        let end = parse_bound(frame.end.as_ref());
+        match frame.units() {
+            protobuf::WindowFrameUnits::Rows => WindowFrame::new(start, end),
+            protobuf::WindowFrameUnits::Range => WindowFrame::new_range(start, end),
+        }
+    }
+}
+
+impl From<protobuf::CastMode> for CastMode {
+    fn from(mode: protobuf::CastMode) -> Self {
+        match mode {
+            protobuf::CastMode::Legacy => CastMode::Legacy,
+            protobuf::CastMode::Ansi => CastMode::Ansi,
+            protobuf::CastMode::Try => CastMode::Try,
+        }
+    }
+}
+
+impl From<protobuf::DecimalArithmeticOp> for DecimalArithmeticOp {
+    fn from(op: protobuf::DecimalArithmeticOp) -> Self {
+        match op {
+            protobuf::DecimalArithmeticOp::Add => DecimalArithmeticOp::Add,
+            protobuf::DecimalArithmeticOp::Subtract => DecimalArithmeticOp::Subtract,
+            protobuf::DecimalArithmeticOp::Multiply => DecimalArithmeticOp::Multiply,
+            protobuf::DecimalArithmeticOp::Divide => DecimalArithmeticOp::Divide,
+            protobuf::DecimalArithmeticOp::Remainder => DecimalArithmeticOp::Remainder,
+        }
+    }
+}
+
+impl From<protobuf::HigherOrderFunction> for HigherOrderFunction {
+    fn from(func: protobuf::HigherOrderFunction) -> Self {
+        match func {
+            protobuf::HigherOrderFunction::HofTransform => HigherOrderFunction::Transform,
+            protobuf::HigherOrderFunction::HofFilter => HigherOrderFunction::Filter,
+            protobuf::HigherOrderFunction::HofExists => HigherOrderFunction::Exists,
+            protobuf::HigherOrderFunction::HofForAll => HigherOrderFunction::ForAll,
+            protobuf::HigherOrderFunction::HofAggregate => HigherOrderFunction::Aggregate,
+            protobuf::HigherOrderFunction::HofZipWith => HigherOrderFunction::ZipWith,
+        }
+    }
+}
+
+impl From<protobuf::ParseMode> for ParseMode {
+    fn from(mode: protobuf::ParseMode) -> Self {
+        match mode {
+            protobuf::ParseMode::Permissive => ParseMode::Permissive,
+            protobuf::ParseMode::DropMalformed => ParseMode::DropMalformed,
+            protobuf::ParseMode::FailFast => ParseMode::FailFast,
+        }
+    }
+}
+
+impl From<protobuf::PushedAggFunction> for PushedAggFunction {
+    fn from(function: protobuf::PushedAggFunction) -> Self {
+        match function {
+            protobuf::PushedAggFunction::CountRows => PushedAggFunction::CountRows,
+            protobuf::PushedAggFunction::Count => PushedAggFunction::Count,
+            protobuf::PushedAggFunction::Min => PushedAggFunction::Min,
+            protobuf::PushedAggFunction::Max => PushedAggFunction::Max,
+        }
+    }
+}
+
 impl From<protobuf::ScalarFunction> for Arc<ScalarUDF> {
     fn from(f: protobuf::ScalarFunction) -> Self {
         use datafusion::functions as f;
@@ -988,7 +1528,26 @@ fn try_parse_physical_expr(
             ExprType::TryCast(e) => {
                 let expr = try_parse_physical_expr_box_required(&e.expr, input_schema)?;
                 let cast_type = convert_required!(e.arrow_type)?;
-                Arc::new(TryCastExpr::new(expr, cast_type))
+                let mode = protobuf::CastMode::try_from(e.mode).expect("invalid CastMode");
+                let time_zone = Some(e.time_zone.clone()).filter(|tz| !tz.is_empty());
+                Arc::new(
+                    TryCastExpr::new(expr, cast_type)
+                        .with_cast_mode(mode.into())
+                        .with_time_zone(time_zone),
+                )
+            }
+            ExprType::DecimalArithmeticExpr(e) => {
+                let op = protobuf::DecimalArithmeticOp::try_from(e.op)
+                    .expect("invalid DecimalArithmeticOp");
+                Arc::new(
+                    DecimalArithmeticExpr::new(
+                        try_parse_physical_expr_box_required(&e.l, input_schema)?,
+                        try_parse_physical_expr_box_required(&e.r, input_schema)?,
+                        op.into(),
+                        convert_required!(e.return_type)?,
+                    )
+                    .with_fail_on_overflow(e.fail_on_overflow),
+                )
             }
             ExprType::ScalarFunction(e) => {
                 let scalar_function =
@@ -1061,11 +1620,50 @@ fn try_parse_physical_expr(
                 Arc::new(StringContainsExpr::new(expr, e.infix.clone()))
             }
             ExprType::RowNumExpr(_) => Arc::new(RowNumExpr::default()),
+            ExprType::SparkPartitionIdExpr(_) => Arc::new(SparkPartitionIdExpr),
+            ExprType::MonotonicallyIncreasingIdExpr(_) => {
+                Arc::new(MonotonicallyIncreasingIdExpr::default())
+            }
+            ExprType::InputFileNameExpr(_) => Arc::new(InputFileNameExpr),
+            ExprType::RaiseErrorExpr(e) => Arc::new(RaiseErrorExpr::new(
+                try_parse_physical_expr_box_required(&e.message, input_schema)?,
+                convert_required!(e.return_type)?,
+            )),
             ExprType::BloomFilterMightContainExpr(e) => Arc::new(BloomFilterMightContainExpr::new(
                 e.uuid.clone(),
                 try_parse_physical_expr_box_required(&e.bloom_filter_expr, input_schema)?,
                 try_parse_physical_expr_box_required(&e.value_expr, input_schema)?,
             )),
+            ExprType::FromJsonExpr(e) => Arc::new(FromJsonExpr::try_new(
+                try_parse_physical_expr_box_required(&e.expr, input_schema)?,
+                convert_required!(e.return_type)?,
+            )?),
+            ExprType::HigherOrderFunctionExpr(e) => {
+                let func = protobuf::HigherOrderFunction::try_from(e.func)
+                    .expect("invalid HigherOrderFunction");
+                let lambda = e
+                    .lambda
+                    .as_ref()
+                    .ok_or_else(|| proto_error("Missing required field in protobuf"))?;
+                Arc::new(HigherOrderFunctionExpr::try_new(
+                    func.into(),
+                    e.args
+                        .iter()
+                        .map(|x| try_parse_physical_expr(x, input_schema))
+                        .collect::<Result<Vec<_>, _>>()?,
+                    try_parse_lambda_function(lambda, input_schema)?,
+                    e.finish
+                        .as_ref()
+                        .map(|finish| try_parse_lambda_function(finish, input_schema))
+                        .transpose()?,
+                    convert_required!(e.return_type)?,
+                )?)
+            }
+            ExprType::LambdaVariableExpr(e) => Arc::new(LambdaVariableExpr::new(
+                e.name.clone(),
+                convert_required!(e.arrow_type)?,
+                e.nullable,
+            )),
             ExprType::ScAndExpr(e) => {
                 let l = try_parse_physical_expr_box_required(&e.left, input_schema)?;
                 let r = try_parse_physical_expr_box_required(&e.right, input_schema)?;
@@ -1083,6 +1681,14 @@ fn try_parse_physical_expr(
                 try_parse_physical_expr_box_required(&e.pattern, input_schema)?,
             )),
 
+            ExprType::CreateMap(e) => Arc::new(CreateMapExpr::try_new(
+                e.args
+                    .iter()
+                    .map(|x| try_parse_physical_expr(x, input_schema))
+                    .collect::<Result<Vec<_>, _>>()?,
+                convert_required!(e.return_type)?,
+                e.dedup_last_win,
+            )?),
             ExprType::NamedStruct(e) => {
                 let data_type = convert_required!(e.return_type)?;
                 Arc::new(NamedStructExpr::try_new(
@@ -1098,6 +1704,71 @@ fn try_parse_physical_expr(
     Ok(pexpr)
 }
 
+fn parse_dynamic_partition_filters(
+    conf: &protobuf::FileScanExecConf,
+) -> Vec<DynamicPartitionFilter> {
+    conf.dynamic_partition_filters
+        .iter()
+        .map(|filter| DynamicPartitionFilter {
+            partition_column: filter.partition_column as usize,
+            resource_id: filter.resource_id.clone(),
+        })
+        .collect()
+}
+
+fn parse_pushed_aggregates(aggs: &[protobuf::PushedAggregate]) -> Vec<PushedAggregate> {
+    aggs.iter()
+        .map(|agg| PushedAggregate {
+            function: protobuf::PushedAggFunction::try_from(agg.function)
+                .expect("invalid PushedAggFunction")
+                .into(),
+            column: agg.column as usize,
+        })
+        .collect()
+}
+
+/// pushes down filter predicates to the underlying parquet scan, which may be
+/// wrapped by renaming its columns. predicates are bound by column indices, so
+/// they are not affected by renaming.
+fn push_down_filter_predicates(
+    input: Arc<dyn ExecutionPlan>,
+    predicates: &[Arc<dyn PhysicalExpr>],
+) -> Result<Arc<dyn ExecutionPlan>, PlanSerDeError> {
+    if let Some(parquet_exec) = input.as_any().downcast_ref::<ParquetExec>() {
+        return Ok(Arc::new(parquet_exec.with_filter_predicates(predicates)));
+    }
+    if input.as_any().is::<RenameColumnsExec>() {
+        let scan = input.children()[0].clone();
+        if scan.as_any().is::<ParquetExec>() {
+            let scan = push_down_filter_predicates(scan, predicates)?;
+            return Ok(input.with_new_children(vec![scan])?);
+        }
+    }
+    Ok(input)
+}
+
+fn parse_runtime_filter_ids(ids: &[String]) -> Vec<Option<String>> {
+    ids.iter()
+        .map(|id| Some(id.clone()).filter(|id| !id.is_empty()))
+        .collect()
+}
+
+// lambda bodies reference columns of the input schema as well as lambda
+// variables, which are appended to the input batch during evaluation
+fn try_parse_lambda_function(
+    lambda: &protobuf::PhysicalLambdaFunctionNode,
+    input_schema: &SchemaRef,
+) -> Result<LambdaFunction, PlanSerDeError> {
+    Ok(LambdaFunction::new(
+        try_parse_physical_expr_box_required(&lambda.body, input_schema)?,
+        lambda
+            .variables
+            .iter()
+            .map(|field| Ok(Arc::new(field.try_into()?)))
+            .collect::<Result<Vec<_>, PlanSerDeError>>()?,
+    ))
+}
+
 fn try_parse_physical_expr_required(
     proto: &Option<protobuf::PhysicalExprNode>,
     input_schema: &SchemaRef,
@@ -1120,10 +1791,35 @@ fn try_parse_physical_expr_box_required(
     }
 }
 
+pub fn parse_protobuf_partition_pruning(
+    request: &protobuf::PartitionPruningRequest,
+) -> Result<(SchemaRef, Vec<Arc<dyn PhysicalExpr>>), PlanSerDeError> {
+    let partition_schema: SchemaRef = Arc::new(convert_required!(request.partition_schema)?);
+    let pruning_predicates = request
+        .pruning_predicates
+        .iter()
+        .map(|expr| {
+            let expr = try_parse_physical_expr(expr, &partition_schema)?;
+            Ok(bind(expr, &partition_schema)?)
+        })
+        .collect::<Result<Vec<_>, PlanSerDeError>>()?;
+    Ok((partition_schema, pruning_predicates))
+}
+
 pub fn parse_protobuf_partitioning(
-    input: Arc<dyn ExecutionPlan>,
+    input_schema: &SchemaRef,
     partitioning: Option<&protobuf::PhysicalRepartition>,
 ) -> Result<Option<Partitioning>, PlanSerDeError> {
+    Ok(
+        parse_protobuf_shuffle_partitioning(input_schema, partitioning)?
+            .map(|partitioning| partitioning.to_df_partitioning()),
+    )
+}
+
+pub fn parse_protobuf_shuffle_partitioning(
+    input_schema: &SchemaRef,
+    partitioning: Option<&protobuf::PhysicalRepartition>,
+) -> Result<Option<ShufflePartitioning>, PlanSerDeError> {
     partitioning.map_or(Ok(None), |p| {
         let plan = p.repartition_type.as_ref().ok_or_else(|| {
             proto_error(format!(
@@ -1132,32 +1828,93 @@ pub fn parse_protobuf_partitioning(
             ))
         })?;
         match plan {
-            RepartitionType::SingleRepartition(..) => {
-                Ok(Some(Partitioning::UnknownPartitioning(1)))
-            }
+            RepartitionType::SingleRepartition(..) => Ok(Some(ShufflePartitioning::Single)),
             RepartitionType::HashRepartition(hash_part) => {
                 // let hash_part = p.hash_repartition;
                 let expr = hash_part
                     .hash_expr
                     .iter()
                     .map(|e| {
-                        try_parse_physical_expr(e, &input.schema())
-                            .and_then(|e| Ok(bind(e, &input.schema())?))
+                        try_parse_physical_expr(e, input_schema)
+                            .and_then(|e| Ok(bind(e, input_schema)?))
                     })
                     .collect::<Result<Vec<Arc<dyn PhysicalExpr>>, _>>()?;
-                Ok(Some(Partitioning::Hash(
+                Ok(Some(ShufflePartitioning::Hash(
                     expr,
                     hash_part.partition_count.try_into().unwrap(),
                 )))
             }
 
-            RepartitionType::RoundRobinRepartition(round_robin_part) => Ok(Some(
-                Partitioning::RoundRobinBatch(round_robin_part.partition_count.try_into().unwrap()),
-            )),
+            RepartitionType::RoundRobinRepartition(round_robin_part) => {
+                Ok(Some(ShufflePartitioning::RoundRobin(
+                    round_robin_part.partition_count.try_into().unwrap(),
+                )))
+            }
+
+            RepartitionType::RangeRepartition(range_part) => {
+                let sort_exprs = range_part
+                    .sort_expr
+                    .iter()
+                    .map(|e| try_parse_physical_sort_expr(e, input_schema))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                // bound values are flattened in row-major order
+                let num_cols = sort_exprs.len().max(1);
+                let bounds = sort_exprs
+                    .iter()
+                    .enumerate()
+                    .map(|(i, sort_expr)| {
+                        let values = range_part
+                            .bound_values
+                            .iter()
+                            .skip(i)
+                            .step_by(num_cols)
+                            .map(|value| value.try_into())
+                            .collect::<Result<Vec<ScalarValue>, _>>()?;
+                        if values.is_empty() {
+                            return Ok(new_empty_array(&sort_expr.expr.data_type(input_schema)?));
+                        }
+                        Ok(ScalarValue::iter_to_array(values)?)
+                    })
+                    .collect::<Result<Vec<_>, PlanSerDeError>>()?;
+                Ok(Some(ShufflePartitioning::Range(Arc::new(
+                    RangePartitioning::try_new(
+                        sort_exprs,
+                        range_part.partition_count.try_into().unwrap(),
+                        bounds,
+                        input_schema,
+                    )?,
+                ))))
+            }
         }
     })
 }
 
+fn try_parse_physical_sort_expr(
+    expr: &protobuf::PhysicalExprNode,
+    input_schema: &SchemaRef,
+) -> Result<PhysicalSortExpr, PlanSerDeError> {
+    match expr.expr_type.as_ref() {
+        Some(ExprType::Sort(sort_expr)) => {
+            let expr = sort_expr
+                .expr
+                .as_ref()
+                .ok_or_else(|| proto_error("Missing required field in protobuf"))?;
+            Ok(PhysicalSortExpr {
+                expr: bind(try_parse_physical_expr(expr, input_schema)?, input_schema)?,
+                options: SortOptions {
+                    descending: !sort_expr.asc,
+                    nulls_first: sort_expr.nulls_first,
+                },
+            })
+        }
+        _ => Err(proto_error(format!(
+            "physical_plan::from_proto() Unexpected sort expr {:?}",
+            expr
+        ))),
+    }
+}
+
 impl TryFrom<&protobuf::PartitionedFile> for PartitionedFile {
     type Error = PlanSerDeError;
 
@@ -1177,11 +1934,28 @@ impl TryFrom<&protobuf::PartitionedFile> for PartitionedFile {
                 .collect::<Result<Vec<_>, _>>()?,
             range: val.range.as_ref().map(|v| v.try_into()).transpose()?,
             statistics: None,
-            extensions: None,
+            extensions: val.deletion_vector.as_ref().map(|deletion_vector| {
+                Arc::new(DeletionVectorDescriptor::from(deletion_vector))
+                    as Arc<dyn Any + Send + Sync>
+            }),
         })
     }
 }
 
+impl From<&protobuf::DeletionVector> for DeletionVectorDescriptor {
+    fn from(value: &protobuf::DeletionVector) -> Self {
+        if value.path.is_empty() {
+            DeletionVectorDescriptor::Inline(value.inline_data.clone().into())
+        } else {
+            DeletionVectorDescriptor::Stored {
+                path: value.path.clone(),
+                offset: value.offset as usize,
+                size: value.size as usize,
+            }
+        }
+    }
+}
+
 impl TryFrom<&protobuf::FileRange> for FileRange {
     type Error = PlanSerDeError;
 
@@ -1296,3 +2070,71 @@ impl TryInto<FileScanConfig> for &protobuf::FileScanExecConf {
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::{
+        datasource::{object_store::ObjectStoreUrl, physical_plan::FileScanConfig},
+        logical_expr::Operator,
+        physical_expr::{
+            expressions::{BinaryExpr, Column, Literal},
+            PhysicalExprRef,
+        },
+        physical_plan::{empty::EmptyExec, ExecutionPlan},
+        scalar::ScalarValue,
+    };
+    use datafusion_ext_plans::{parquet_exec::ParquetExec, rename_columns_exec::RenameColumnsExec};
+
+    use crate::from_proto::push_down_filter_predicates;
+
+    fn file_schema() -> Arc<Schema> {
+        Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, true),
+            Field::new("b", DataType::Int32, true),
+        ]))
+    }
+
+    fn predicate(name: &str, index: usize) -> PhysicalExprRef {
+        Arc::new(BinaryExpr::new(
+            Arc::new(Column::new(name, index)),
+            Operator::Gt,
+            Arc::new(Literal::new(ScalarValue::Int32(Some(1)))),
+        ))
+    }
+
+    #[test]
+    fn test_push_down_filter_predicates_through_renaming() {
+        let scan_config = FileScanConfig::new(ObjectStoreUrl::local_filesystem(), file_schema())
+            .with_file_groups(vec![vec![]]);
+        let scan = Arc::new(ParquetExec::new(scan_config, "fs".to_string(), None));
+        let renamed = Arc::new(
+            RenameColumnsExec::try_new(scan, vec!["x".to_string(), "y".to_string()]).unwrap(),
+        );
+
+        // predicates are bound to the renamed schema
+        let pushed = push_down_filter_predicates(renamed.clone(), &[predicate("y", 1)]).unwrap();
+        assert!(pushed.as_any().is::<RenameColumnsExec>());
+        assert_eq!(pushed.schema(), renamed.schema());
+        let pushed_scan = pushed.children()[0]
+            .as_any()
+            .downcast_ref::<ParquetExec>()
+            .unwrap();
+        assert_eq!(format!("{}", pushed_scan.predicate().unwrap()), "b@1 > 1");
+    }
+
+    #[test]
+    fn test_push_down_filter_predicates_unsupported_input() {
+        let renamed: Arc<dyn ExecutionPlan> = Arc::new(
+            RenameColumnsExec::try_new(
+                Arc::new(EmptyExec::new(file_schema())),
+                vec!["x".to_string(), "y".to_string()],
+            )
+            .unwrap(),
+        );
+        let pushed = push_down_filter_predicates(renamed.clone(), &[predicate("y", 1)]).unwrap();
+        assert!(Arc::ptr_eq(&pushed, &renamed));
+    }
+}
@@ -138,6 +138,7 @@ impl From<protobuf::AggFunction> for AggFunction {
             protobuf::AggFunction::BloomFilter => AggFunction::BloomFilter,
             protobuf::AggFunction::BrickhouseCollect => AggFunction::BrickhouseCollect,
             protobuf::AggFunction::BrickhouseCombineUnique => AggFunction::BrickhouseCombineUnique,
+            protobuf::AggFunction::Udaf => AggFunction::Udaf,
         }
     }
 }
@@ -415,7 +416,8 @@ impl TryInto<Field> for &protobuf::Field {
             self.name.as_str(),
             pb_datatype.as_ref().try_into()?,
             self.nullable,
-        ))
+        )
+        .with_metadata(self.metadata.clone()))
     }
 }
 
@@ -28,9 +28,14 @@ use datafusion::{
     },
     prelude::{SessionConfig, SessionContext},
 };
-use datafusion_ext_plans::memmgr::MemManager;
+use datafusion_ext_commons::fs::register_fs_from_conf;
+use datafusion_ext_plans::{
+    common::resource_usage::get_stage_resource_usage,
+    memmgr::{off_heap::OffHeapQuota, spill::init_remote_spill, MemManager},
+};
 use jni::{
     objects::{JClass, JObject},
+    sys::jlongArray,
     JNIEnv,
 };
 use once_cell::sync::OnceCell;
@@ -65,6 +70,7 @@ pub extern "system" fn Java_org_apache_spark_sql_blaze_JniBridge_callNative(
                 let memory_fraction = conf::MEMORY_FRACTION.value()?;
                 let batch_size = conf::BATCH_SIZE.value()? as usize;
                 MemManager::init((max_memory as f64 * memory_fraction) as usize);
+                OffHeapQuota::init(OffHeapQuota::capacity_from_conf()?);
 
                 let session_config = SessionConfig::new().with_batch_size(batch_size);
                 let runtime_config =
@@ -73,6 +79,19 @@ pub extern "system" fn Java_org_apache_spark_sql_blaze_JniBridge_callNative(
                 let session = SessionContext::new_with_config_rt(session_config, runtime);
                 Ok::<_, DataFusionError>(session)
             })?;
+
+            // register native filesystems for configured storages
+            log::info!("initializing native filesystems");
+            register_fs_from_conf()?;
+            init_remote_spill()?;
+
+            // start flight server for serving shuffle outputs to remote peers
+            #[cfg(feature = "flight")]
+            crate::flight::init_flight_server()?;
+
+            // export tracing spans of native tasks and operators
+            #[cfg(feature = "otlp")]
+            crate::otlp::init_otlp_tracing()?;
             Ok::<_, DataFusionError>(())
         })?;
         let native_wrapper = jni_new_global_ref!(native_wrapper)?;
@@ -110,6 +129,33 @@ pub extern "system" fn Java_org_apache_spark_sql_blaze_JniBridge_finalizeNative(
     runtime.finalize();
 }
 
+#[allow(non_snake_case)]
+#[no_mangle]
+pub extern "system" fn Java_org_apache_spark_sql_blaze_JniBridge_getStageResourceUsage(
+    env: JNIEnv,
+    _: JClass,
+    stage_id: i32,
+) -> jlongArray {
+    *handle_unwinded_scope(|| -> Result<JObject> {
+        let Some(usage) = get_stage_resource_usage(stage_id as usize) else {
+            return Ok(JObject::null());
+        };
+
+        // layout must be consistent with NativeResourceUsage.fromNative()
+        let values = [
+            usage.num_tasks as i64,
+            usage.cpu_time_ns as i64,
+            usage.peak_mem_used as i64,
+            usage.mem_spilled_bytes as i64,
+            usage.disk_spilled_bytes as i64,
+            usage.io_bytes as i64,
+        ];
+        let array = jni_map_error!(env.new_long_array(values.len() as i32))?;
+        jni_map_error!(env.set_long_array_region(array, 0, &values))?;
+        Ok(JObject::from(array))
+    })
+}
+
 #[allow(non_snake_case)]
 #[no_mangle]
 pub extern "system" fn Java_org_apache_spark_sql_blaze_JniBridge_onExit(_: JNIEnv, _: JClass) {
@@ -117,4 +163,7 @@ pub extern "system" fn Java_org_apache_spark_sql_blaze_JniBridge_onExit(_: JNIEn
     if MemManager::initialized() {
         MemManager::get().dump_status();
     }
+
+    #[cfg(feature = "otlp")]
+    crate::otlp::shutdown_otlp_tracing();
 }
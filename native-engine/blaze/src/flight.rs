@@ -0,0 +1,294 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Arrow Flight server serving shuffle outputs and task results of this
+//! executor.
+//!
+//! remote peers fetch a reduce partition of a committed shuffle output with
+//! `DoGet` and the ticket `shuffle/<shuffle_id>/<map_id>/<reduce_id>`. only
+//! outputs registered by native shuffle writers are served. outputs of tasks
+//! run with `spark.blaze.flight.resultId` are streamed to clients like BI tools
+//! with the ticket `result/<result_id>/<partition>`.
+//!
+//! the server listens on the executor host and requires spark.authenticate.
+//! clients send the spark auth secret as the payload of `Handshake`, and pass
+//! the returned token as `authorization: Bearer <token>` in other requests.
+
+use std::net::ToSocketAddrs;
+
+use arrow::{datatypes::SchemaRef, record_batch::RecordBatch};
+use arrow_flight::{
+    encode::FlightDataEncoderBuilder,
+    error::FlightError,
+    flight_service_server::{FlightService, FlightServiceServer},
+    Action, ActionType, Criteria, Empty, FlightData, FlightDescriptor, FlightInfo,
+    HandshakeRequest, HandshakeResponse, PollInfo, PutResult, SchemaResult, Ticket,
+};
+use blaze_jni_bridge::{
+    conf::{self, IntConf},
+    jni_call_static, jni_get_string,
+};
+use datafusion::{common::Result, error::DataFusionError};
+use datafusion_ext_commons::df_execution_err;
+use datafusion_ext_plans::shuffle::flight_registry::{get_shuffle_output, take_result_stream};
+use futures::{stream::BoxStream, Stream, StreamExt, TryStreamExt};
+use once_cell::sync::OnceCell;
+use tonic::{transport::Server, Request, Response, Status, Streaming};
+
+/// starts the flight server if `spark.blaze.flight.server.port` is set, the
+/// server is started once and runs until the executor exits.
+pub fn init_flight_server() -> Result<()> {
+    static STARTED: OnceCell<()> = OnceCell::new();
+    STARTED.get_or_try_init(|| {
+        let port = conf::FLIGHT_SERVER_PORT.value()?;
+        if port <= 0 {
+            return Ok(());
+        }
+        let auth_secret = jni_call_static!(JniBridge.getAuthSecret() -> JObject)?;
+        if auth_secret.as_obj().is_null() {
+            log::warn!("flight server is not started: spark.authenticate is not enabled");
+            return Ok(());
+        }
+        let auth_secret = jni_get_string!(auth_secret.as_obj().into())?;
+        let host = jni_get_string!(jni_call_static!(JniBridge.getExecutorHost() -> JObject)?
+            .as_obj()
+            .into())?;
+        let Some(addr) = (host.as_str(), port as u16).to_socket_addrs()?.next() else {
+            return df_execution_err!("cannot resolve executor host: {host}");
+        };
+        let service = BlazeFlightService {
+            auth_secret,
+            token: uuid::Uuid::new_v4().to_string(),
+        };
+
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .thread_name("blaze-flight")
+            .enable_all()
+            .build()?;
+        std::thread::Builder::new()
+            .name("blaze-flight-server".to_string())
+            .spawn(move || {
+                log::info!("starting flight server on {addr}");
+                let server = Server::builder()
+                    .add_service(FlightServiceServer::new(service))
+                    .serve(addr);
+                if let Err(err) = runtime.block_on(server) {
+                    log::error!("flight server exited with error: {err}");
+                }
+            })?;
+        Ok::<_, DataFusionError>(())
+    })?;
+    Ok(())
+}
+
+struct BlazeFlightService {
+    auth_secret: String,
+    token: String,
+}
+
+impl BlazeFlightService {
+    fn check_token<T>(&self, request: &Request<T>) -> Result<(), Status> {
+        let token = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        match token {
+            Some(token) if constant_time_eq(token.as_bytes(), self.token.as_bytes()) => Ok(()),
+            _ => Err(Status::unauthenticated("missing or invalid token")),
+        }
+    }
+}
+
+type FlightStream<T> = BoxStream<'static, Result<T, Status>>;
+
+#[tonic::async_trait]
+impl FlightService for BlazeFlightService {
+    type HandshakeStream = FlightStream<HandshakeResponse>;
+    type ListFlightsStream = FlightStream<FlightInfo>;
+    type DoGetStream = FlightStream<FlightData>;
+    type DoPutStream = FlightStream<PutResult>;
+    type DoActionStream = FlightStream<arrow_flight::Result>;
+    type ListActionsStream = FlightStream<ActionType>;
+    type DoExchangeStream = FlightStream<FlightData>;
+
+    async fn do_get(
+        &self,
+        request: Request<Ticket>,
+    ) -> Result<Response<Self::DoGetStream>, Status> {
+        self.check_token(&request)?;
+        let ticket = std::str::from_utf8(&request.get_ref().ticket)
+            .map_err(|_| Status::invalid_argument("ticket is not utf-8"))?;
+        let not_found = || Status::not_found(format!("ticket not found: {ticket}"));
+
+        let flight_data_stream = match parse_ticket(ticket) {
+            Some(FlightTicket::Shuffle(shuffle_id, map_id, reduce_id)) => {
+                let output = get_shuffle_output(shuffle_id, map_id).ok_or_else(not_found)?;
+                let schema = output.schema.clone();
+
+                // read batches lazily in a blocking thread, stops once the client is gone
+                let (sender, receiver) = tokio::sync::mpsc::channel(2);
+                tokio::task::spawn_blocking(move || {
+                    let reader = match output.read_partition(reduce_id) {
+                        Ok(reader) => reader,
+                        Err(err) => {
+                            let _ = sender.blocking_send(Err(err));
+                            return;
+                        }
+                    };
+                    for batch in reader {
+                        let is_err = batch.is_err();
+                        if sender.blocking_send(batch).is_err() || is_err {
+                            break;
+                        }
+                    }
+                });
+                let batch_stream = futures::stream::unfold(receiver, |mut receiver| async move {
+                    let batch = receiver.recv().await?;
+                    Some((batch, receiver))
+                });
+                encode_flight_data(schema, batch_stream)
+            }
+            Some(FlightTicket::Result(result_id, partition)) => {
+                let stream = take_result_stream(result_id, partition).ok_or_else(not_found)?;
+                let schema = stream.schema.clone();
+                let batch_stream = futures::stream::unfold(stream, |mut stream| async move {
+                    let batch = stream.next().await?;
+                    Some((batch, stream))
+                });
+                encode_flight_data(schema, batch_stream)
+            }
+            None => {
+                return Err(Status::invalid_argument(format!(
+                    "invalid ticket: {ticket}"
+                )))
+            }
+        };
+        Ok(Response::new(flight_data_stream))
+    }
+
+    async fn handshake(
+        &self,
+        request: Request<Streaming<HandshakeRequest>>,
+    ) -> Result<Response<Self::HandshakeStream>, Status> {
+        let handshake = request
+            .into_inner()
+            .message()
+            .await?
+            .ok_or_else(|| Status::invalid_argument("missing handshake request"))?;
+        if !constant_time_eq(&handshake.payload, self.auth_secret.as_bytes()) {
+            return Err(Status::unauthenticated("invalid auth secret"));
+        }
+        let response = HandshakeResponse {
+            protocol_version: handshake.protocol_version,
+            payload: self.token.clone().into(),
+        };
+        Ok(Response::new(futures::stream::iter([Ok(response)]).boxed()))
+    }
+
+    async fn list_flights(
+        &self,
+        _request: Request<Criteria>,
+    ) -> Result<Response<Self::ListFlightsStream>, Status> {
+        Err(Status::unimplemented("list_flights"))
+    }
+
+    async fn get_flight_info(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        Err(Status::unimplemented("get_flight_info"))
+    }
+
+    async fn poll_flight_info(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> Result<Response<PollInfo>, Status> {
+        Err(Status::unimplemented("poll_flight_info"))
+    }
+
+    async fn get_schema(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> Result<Response<SchemaResult>, Status> {
+        Err(Status::unimplemented("get_schema"))
+    }
+
+    async fn do_put(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoPutStream>, Status> {
+        Err(Status::unimplemented("do_put"))
+    }
+
+    async fn do_action(
+        &self,
+        _request: Request<Action>,
+    ) -> Result<Response<Self::DoActionStream>, Status> {
+        Err(Status::unimplemented("do_action"))
+    }
+
+    async fn list_actions(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<Self::ListActionsStream>, Status> {
+        Err(Status::unimplemented("list_actions"))
+    }
+
+    async fn do_exchange(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoExchangeStream>, Status> {
+        Err(Status::unimplemented("do_exchange"))
+    }
+}
+
+fn encode_flight_data(
+    schema: SchemaRef,
+    batch_stream: impl Stream<Item = Result<RecordBatch>> + Send + 'static,
+) -> FlightStream<FlightData> {
+    FlightDataEncoderBuilder::new()
+        .with_schema(schema)
+        .build(batch_stream.map_err(|err| FlightError::from_external_error(Box::new(err))))
+        .map_err(Status::from)
+        .boxed()
+}
+
+enum FlightTicket<'a> {
+    Shuffle(u64, u64, usize),
+    Result(&'a str, usize),
+}
+
+fn parse_ticket(ticket: &str) -> Option<FlightTicket> {
+    if let Some((result_id, partition)) = ticket
+        .strip_prefix("result/")
+        .and_then(|rest| rest.rsplit_once('/'))
+    {
+        let partition = partition.parse().ok()?;
+        return (!result_id.is_empty()).then_some(FlightTicket::Result(result_id, partition));
+    }
+    let mut parts = ticket.strip_prefix("shuffle/")?.split('/');
+    let shuffle_id = parts.next()?.parse().ok()?;
+    let map_id = parts.next()?.parse().ok()?;
+    let reduce_id = parts.next()?.parse().ok()?;
+    parts
+        .next()
+        .is_none()
+        .then_some(FlightTicket::Shuffle(shuffle_id, map_id, reduce_id))
+}
+
+/// compares secrets in constant time
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
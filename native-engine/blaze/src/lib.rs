@@ -19,8 +19,13 @@ use jni::objects::{JObject, JThrowable};
 
 mod alloc;
 mod exec;
+#[cfg(feature = "flight")]
+mod flight;
 mod logging;
 mod metrics;
+#[cfg(feature = "otlp")]
+mod otlp;
+mod partition_pruning;
 mod rt;
 
 fn handle_unwinded(err: Box<dyn Any + Send>) {
@@ -14,9 +14,20 @@
 
 use std::sync::Arc;
 
-use blaze_jni_bridge::{jni_call, jni_new_string};
+use blaze_jni_bridge::{
+    batched::{new_long_array, new_string_array},
+    jni_bridge::JavaClasses,
+    jni_call, jni_map_error,
+};
 use datafusion::{common::Result, physical_plan::ExecutionPlan};
-use jni::objects::JObject;
+use datafusion_ext_plans::common::metric_registry::NATIVE_METRICS;
+use jni::{
+    objects::{JClass, JObject},
+    sys::jobjectArray,
+    JNIEnv,
+};
+
+use crate::handle_unwinded_scope;
 
 pub fn update_spark_metric_node(
     metric_node: JObject,
@@ -26,12 +37,14 @@ pub fn update_spark_metric_node(
         return Ok(());
     }
 
-    // update current node
-    update_metrics(
+    // update current node, metric values are cumulative and may be updated
+    // several times during execution
+    add_metric_values(
         metric_node,
         &execution_plan
             .metrics()
             .unwrap_or_default()
+            .aggregate_by_name()
             .iter()
             .map(|m| m.value())
             .map(|m| (m.name(), m.as_usize() as i64))
@@ -48,10 +61,38 @@ pub fn update_spark_metric_node(
     Ok(())
 }
 
-fn update_metrics(metric_node: JObject, metric_values: &[(&str, i64)]) -> Result<()> {
-    for &(name, value) in metric_values {
-        let jname = jni_new_string!(&name)?;
-        jni_call!(SparkMetricNode(metric_node).add(jname.as_obj(), value) -> ())?;
+/// adds metric values of a node with one jni call.
+pub fn add_metric_values(metric_node: JObject, metric_values: &[(&str, i64)]) -> Result<()> {
+    if metric_values.is_empty() {
+        return Ok(());
     }
+    let (names, values): (Vec<&str>, Vec<i64>) = metric_values.iter().copied().unzip();
+    let names = new_string_array(&names)?;
+    let values = new_long_array(&values)?;
+    jni_call!(SparkMetricNode(metric_node).addAll(names.as_obj(), values.as_obj()) -> ())?;
     Ok(())
 }
+
+/// returns definitions of all native metrics, in the format of
+/// `name:kind:displayName`, used by the spark side to create SQLMetrics.
+#[allow(non_snake_case)]
+#[no_mangle]
+pub extern "system" fn Java_org_apache_spark_sql_blaze_JniBridge_getNativeMetricDefinitions(
+    env: JNIEnv,
+    _: JClass,
+) -> jobjectArray {
+    *handle_unwinded_scope(|| -> Result<JObject> {
+        JavaClasses::init(&env);
+        let array = jni_map_error!(env.new_object_array(
+            NATIVE_METRICS.len() as i32,
+            "java/lang/String",
+            JObject::null(),
+        ))?;
+        for (i, def) in NATIVE_METRICS.iter().enumerate() {
+            let def_string = format!("{}:{}:{}", def.name, def.kind.as_str(), def.display_name);
+            let def_string = jni_map_error!(env.new_string(def_string))?;
+            jni_map_error!(env.set_object_array_element(array, i as i32, def_string))?;
+        }
+        Ok(JObject::from(array))
+    })
+}
@@ -0,0 +1,85 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! OTLP exporter of native tracing spans.
+//!
+//! spans of native tasks and operators are exported to the collector at
+//! `spark.blaze.tracing.otlpEndpoint`, so that traces of all executors can be
+//! inspected together. spans are not collected if the endpoint is not set.
+
+use blaze_jni_bridge::conf::{self, StringConf};
+use datafusion::{common::Result, error::DataFusionError};
+use datafusion_ext_commons::df_execution_err;
+use once_cell::sync::OnceCell;
+use opentelemetry::{trace::TracerProvider as _, KeyValue};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{runtime, trace::TracerProvider, Resource};
+use tokio::runtime::Runtime;
+use tracing_subscriber::layer::SubscriberExt;
+
+struct OtlpTracing {
+    // exporting runs in its own runtime since task runtimes are shut down
+    // when tasks finish
+    _runtime: Runtime,
+    provider: TracerProvider,
+}
+
+static OTLP_TRACING: OnceCell<Option<OtlpTracing>> = OnceCell::new();
+
+/// installs the OTLP exporter if the endpoint is configured, the exporter is
+/// installed once and shared by all tasks of the executor.
+pub fn init_otlp_tracing() -> Result<()> {
+    OTLP_TRACING.get_or_try_init(|| {
+        let endpoint = conf::TRACING_OTLP_ENDPOINT.value()?;
+        if endpoint.is_empty() {
+            return Ok(None);
+        }
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("blaze-otlp")
+            .enable_all()
+            .build()?;
+        let _guard = runtime.enter();
+
+        let exporter = SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(&endpoint)
+            .build()
+            .or_else(|err| df_execution_err!("error creating otlp exporter: {err}"))?;
+        let provider = TracerProvider::builder()
+            .with_batch_exporter(exporter, runtime::Tokio)
+            .with_resource(Resource::new(vec![KeyValue::new("service.name", "blaze")]))
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("blaze")));
+        tracing::subscriber::set_global_default(subscriber)
+            .or_else(|err| df_execution_err!("error setting tracing subscriber: {err}"))?;
+
+        log::info!("exporting native tracing spans to {endpoint}");
+        Ok::<_, DataFusionError>(Some(OtlpTracing {
+            _runtime: runtime,
+            provider,
+        }))
+    })?;
+    Ok(())
+}
+
+/// flushes pending spans, called when the executor exits.
+pub fn shutdown_otlp_tracing() {
+    if let Some(Some(otlp_tracing)) = OTLP_TRACING.get() {
+        if let Err(err) = otlp_tracing.provider.shutdown() {
+            log::warn!("error shutting down otlp exporter: {err}");
+        }
+    }
+}
@@ -0,0 +1,75 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use arrow::{
+    array::{Array, RecordBatch, RecordBatchOptions, StructArray},
+    datatypes::DataType,
+    ffi::{from_ffi_and_data_type, FFI_ArrowArray},
+};
+use blaze_jni_bridge::{jni_bridge::JavaClasses, *};
+use blaze_serde::{
+    from_proto::parse_protobuf_partition_pruning, protobuf::PartitionPruningRequest,
+};
+use datafusion::common::Result;
+use datafusion_ext_commons::df_execution_err;
+use datafusion_ext_plans::scan::partition_pruning::prune_partitions;
+use jni::{
+    objects::{JClass, JObject},
+    sys::jintArray,
+    JNIEnv,
+};
+use prost::Message;
+
+use crate::handle_unwinded_scope;
+
+/// evaluates partition pruning predicates on the partition values exported
+/// from jvm via ffi, returns indices of the surviving partitions.
+#[allow(non_snake_case)]
+#[no_mangle]
+pub extern "system" fn Java_org_apache_spark_sql_blaze_JniBridge_prunePartitions(
+    env: JNIEnv,
+    _: JClass,
+    raw_request: JObject,
+    partition_values_ffi_array_ptr: i64,
+) -> jintArray {
+    *handle_unwinded_scope(|| -> Result<JObject> {
+        JavaClasses::init(&env);
+
+        let raw_request = jni_convert_byte_array!(raw_request)?;
+        let request = PartitionPruningRequest::decode(raw_request.as_slice())
+            .or_else(|err| df_execution_err!("cannot decode partition pruning request: {err:?}"))?;
+        let (partition_schema, pruning_predicates) = parse_protobuf_partition_pruning(&request)?;
+
+        // import partition values, the ffi array is moved out and released by us
+        let ffi_array = unsafe {
+            FFI_ArrowArray::from_raw(partition_values_ffi_array_ptr as *mut FFI_ArrowArray)
+        };
+        let import_data_type = DataType::Struct(partition_schema.fields().clone());
+        let imported = unsafe { from_ffi_and_data_type(ffi_array, import_data_type)? };
+        let struct_array = StructArray::from(imported);
+        let partition_values = RecordBatch::try_new_with_options(
+            partition_schema,
+            struct_array.columns().to_vec(),
+            &RecordBatchOptions::new().with_row_count(Some(struct_array.len())),
+        )?;
+
+        let survived_indices = prune_partitions(&pruning_predicates, &partition_values)?
+            .into_iter()
+            .map(|idx| idx as i32)
+            .collect::<Vec<_>>();
+        let survived_array = jni_map_error!(env.new_int_array(survived_indices.len() as i32))?;
+        jni_map_error!(env.set_int_array_region(survived_array, 0, &survived_indices))?;
+        Ok(JObject::from(survived_array))
+    })
+}
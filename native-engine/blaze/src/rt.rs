@@ -15,18 +15,22 @@
 use std::{
     error::Error,
     panic::AssertUnwindSafe,
-    sync::{mpsc::Receiver, Arc},
+    sync::{
+        mpsc::{Receiver, RecvTimeoutError, Sender},
+        Arc,
+    },
+    time::Duration,
 };
 
-use arrow::{
-    array::{Array, StructArray},
-    ffi::{FFI_ArrowArray, FFI_ArrowSchema},
-    record_batch::RecordBatch,
-};
+use arrow::{ffi::FFI_ArrowSchema, record_batch::RecordBatch};
 use blaze_jni_bridge::{
-    is_task_running, jni_bridge::JavaClasses, jni_call, jni_call_static, jni_convert_byte_array,
-    jni_exception_check, jni_exception_occurred, jni_new_global_ref, jni_new_object,
-    jni_new_string,
+    call_stats::{set_thread_jni_call_stats, JniCallStats},
+    conf::{self, BooleanConf, IntConf, LongConf, StringConf},
+    ffi::export_batch_to_jvm,
+    is_task_running,
+    jni_bridge::JavaClasses,
+    jni_call, jni_call_static, jni_convert_byte_array, jni_exception_check, jni_exception_occurred,
+    jni_new_global_ref, jni_new_object, jni_new_string,
 };
 use blaze_serde::protobuf::TaskDefinition;
 use datafusion::{
@@ -37,31 +41,52 @@ use datafusion::{
         displayable, empty::EmptyExec, metrics::ExecutionPlanMetricsSet, ExecutionPlan,
     },
 };
-use datafusion_ext_commons::{df_execution_err, downcast_any};
+use datafusion_ext_commons::{
+    arrow::view::{cast_batch_to_plain, plain_schema},
+    blaze_error::find_error_code,
+    df_execution_err, downcast_any,
+    spark_task_context::SparkTaskContext,
+};
 use datafusion_ext_plans::{
-    common::execution_context::{cancel_all_tasks, ExecutionContext},
+    common::{
+        execution_context::{cancel_all_tasks, ExecutionContext},
+        explain_analyze::{explain_analyze, ExplainFormat},
+        predicate_cache::PredicateCache,
+        replay_log::ReplayLog,
+        resource_usage::{record_task_resource_usage, ResourceUsage, TaskMemUsage},
+    },
     ipc_writer_exec::IpcWriterExec,
+    memmgr::spill_manager::{SpillCleanupGuard, SpillManager},
     parquet_sink_exec::ParquetSinkExec,
+    shuffle::flight_registry::ResultStreamWriter,
     shuffle_writer_exec::ShuffleWriterExec,
 };
 use futures::{FutureExt, StreamExt};
 use jni::objects::{GlobalRef, JObject};
 use prost::Message;
 use tokio::{runtime::Runtime, task::JoinHandle};
+use tracing::{info_span, Instrument};
 
 use crate::{
     handle_unwinded_scope,
     logging::{THREAD_PARTITION_ID, THREAD_STAGE_ID},
-    metrics::update_spark_metric_node,
+    metrics::{add_metric_values, update_spark_metric_node},
 };
 
 pub struct NativeExecutionRuntime {
+    stage_id: usize,
     exec_ctx: Arc<ExecutionContext>,
     native_wrapper: GlobalRef,
     plan: Arc<dyn ExecutionPlan>,
+    task_mem_usage: Arc<TaskMemUsage>,
+    jni_call_stats: Arc<JniCallStats>,
+    native_task_context: Arc<SparkTaskContext>,
     batch_receiver: Receiver<Result<Option<RecordBatch>>>,
     tokio_runtime: Runtime,
     join_handle: JoinHandle<()>,
+    metrics_updater_stopper: Option<Sender<()>>,
+    cancellation_watcher_stopper: Option<Sender<()>>,
+    _spill_cleanup_guard: SpillCleanupGuard, // removes spill files left by the task
 }
 
 impl NativeExecutionRuntime {
@@ -79,8 +104,38 @@ impl NativeExecutionRuntime {
         let stage_id = task_id.stage_id as usize;
         let partition_id = task_id.partition_id as usize;
         let plan = &task_definition.plan.expect("plan is empty");
+
+        // init replay log, saving the task definition for replaying
+        let replay_log = ReplayLog::try_new_from_blaze_conf(stage_id, partition_id)?;
+        if let Some(replay_log) = &replay_log {
+            replay_log.save_task_definition(&raw_task_definition)?;
+        }
         drop(raw_task_definition);
 
+        // partition/attempt info and current input file of the task, used by
+        // context-dependent expressions
+        let native_task_context = Arc::new(SparkTaskContext::new(
+            stage_id,
+            partition_id,
+            task_id.task_attempt_id,
+        ));
+
+        // filter masks are shared among operators of the task
+        let predicate_cache = PredicateCache::try_new_from_blaze_conf()?;
+
+        // memory used by consumers of the task, for resource usage summary
+        let task_mem_usage = Arc::new(TaskMemUsage::default());
+        let task_mem_usage_cloned = task_mem_usage.clone();
+
+        // jni calls of all threads of the task, for measuring jni overhead
+        let jni_call_stats = Arc::new(JniCallStats::default());
+        let jni_call_stats_cloned = jni_call_stats.clone();
+        set_thread_jni_call_stats(Some(jni_call_stats.clone()));
+
+        // spill files of the task, removed when the task is finalized
+        let spill_manager = Arc::new(SpillManager::default());
+        let spill_manager_cloned = spill_manager.clone();
+
         // get execution plan
         let execution_plan: Arc<dyn ExecutionPlan> = plan
             .try_into()
@@ -124,6 +179,7 @@ impl NativeExecutionRuntime {
 
         // create tokio runtime
         // propagate classloader and task context to spawned children threads
+        let native_task_context_cloned = native_task_context.clone();
         let spark_task_context = jni_call_static!(JniBridge.getTaskContext() -> JObject)?;
         let spark_task_context_global = jni_new_global_ref!(spark_task_context.as_obj())?;
         let tokio_runtime = tokio::runtime::Builder::new_multi_thread()
@@ -139,6 +195,12 @@ impl NativeExecutionRuntime {
                 );
                 THREAD_STAGE_ID.set(stage_id);
                 THREAD_PARTITION_ID.set(partition_id);
+                ReplayLog::set_current(replay_log.clone());
+                PredicateCache::set_current(predicate_cache.clone());
+                SparkTaskContext::set_current(Some(native_task_context_cloned.clone()));
+                TaskMemUsage::set_current(Some(task_mem_usage_cloned.clone()));
+                SpillManager::set_current(Some(spill_manager_cloned.clone()));
+                set_thread_jni_call_stats(Some(jni_call_stats_cloned.clone()));
             })
             .build()?;
 
@@ -164,14 +226,31 @@ impl NativeExecutionRuntime {
                 && downcast_any!(execution_plan_cloned, ShuffleWriterExec).is_err()
             {
                 stream = exec_ctx_cloned.coalesce_with_default_batch_size(stream);
+
+                // split large batches for row-based jvm consumers
+                let max_rows = conf::FFI_OUTPUT_MAX_ROWS.value()?.max(0) as usize;
+                let max_bytes = conf::FFI_OUTPUT_MAX_BYTES.value()?.max(0) as usize;
+                stream = exec_ctx_cloned.split_output_batches(stream, max_rows, max_bytes);
             }
 
-            // init ffi schema
-            let ffi_schema = FFI_ArrowSchema::try_from(stream.schema().as_ref())?;
+            // init ffi schema, view arrays are not supported by jvm consumers and are
+            // converted to plain arrays
+            let output_schema = plain_schema(&stream.schema());
+            let ffi_schema = FFI_ArrowSchema::try_from(output_schema.as_ref())?;
             jni_call!(BlazeCallNativeWrapper(native_wrapper_cloned.as_obj())
                 .importSchema(&ffi_schema as *const FFI_ArrowSchema as i64) -> ()
             )?;
 
+            // stream results to flight clients instead of the jvm if requested
+            let result_id = conf::FLIGHT_RESULT_ID.value()?;
+            let result_writer = (cfg!(feature = "flight")
+                && !result_id.is_empty()
+                && conf::FLIGHT_SERVER_PORT.value()? > 0)
+                .then(|| {
+                    log::info!("streaming results with ticket: result/{result_id}/{partition_id}");
+                    ResultStreamWriter::new(&result_id, partition_id, output_schema.clone())
+                });
+
             // produce batches
             while let Some(batch) = AssertUnwindSafe(stream.next())
                 .catch_unwind()
@@ -184,17 +263,32 @@ impl NativeExecutionRuntime {
                 .transpose()
                 .or_else(|err| df_execution_err!("{err}"))?
             {
+                let batch = cast_batch_to_plain(batch, &output_schema)?;
+                if let Some(result_writer) = &result_writer {
+                    result_writer.write(batch).await?;
+                    continue;
+                }
                 batch_sender
                     .send(Ok(Some(batch)))
                     .or_else(|err| df_execution_err!("send batch error: {err}"))?;
             }
+            if let Some(result_writer) = result_writer {
+                result_writer.finish();
+            }
             batch_sender
                 .send(Ok(None))
                 .or_else(|err| df_execution_err!("send batch error: {err}"))?;
+            if let Some(replay_log) = ReplayLog::current() {
+                replay_log.flush()?;
+                log::info!("replay log written: {}", replay_log.path().display());
+            }
             log::info!("task finished");
             Ok::<_, DataFusionError>(())
         };
 
+        let consume_stream =
+            consume_stream.instrument(info_span!("native_task", stage_id, partition_id));
+
         let native_wrapper_cloned = native_wrapper.clone();
         let join_handle = tokio_runtime.spawn(async move {
             consume_stream.await.unwrap_or_else(|err| {
@@ -228,13 +322,61 @@ impl NativeExecutionRuntime {
             });
         });
 
+        // publish metrics periodically, so that memory usage and spills of running
+        // tasks can be seen in spark ui
+        let metrics_update_interval_ms = conf::METRICS_UPDATE_INTERVAL_MS.value()?;
+        let metrics_updater_stopper = (metrics_update_interval_ms > 0).then(|| {
+            let (stopper, stop_receiver) = std::sync::mpsc::channel::<()>();
+            let native_wrapper = native_wrapper.clone();
+            let plan = execution_plan.clone();
+            let jni_call_stats = jni_call_stats.clone();
+            let interval = Duration::from_millis(metrics_update_interval_ms as u64);
+            tokio_runtime.spawn_blocking(move || {
+                // stops when the stopper is dropped
+                while let Err(RecvTimeoutError::Timeout) = stop_receiver.recv_timeout(interval) {
+                    if let Err(err) = update_metrics(&native_wrapper, &plan, &jni_call_stats) {
+                        log::warn!("error updating metrics periodically: {err}");
+                    }
+                }
+            });
+            stopper
+        });
+
+        // watch whether the task is killed by spark, so that long-running native
+        // loops abort promptly instead of running until the next batch boundary
+        let cancellation_check_interval_ms = conf::TASK_CANCELLATION_CHECK_INTERVAL_MS.value()?;
+        let cancellation_watcher_stopper = (cancellation_check_interval_ms > 0).then(|| {
+            let (stopper, stop_receiver) = std::sync::mpsc::channel::<()>();
+            let native_task_context = native_task_context.clone();
+            let task_ctx = exec_ctx.task_ctx();
+            let interval = Duration::from_millis(cancellation_check_interval_ms as u64);
+            tokio_runtime.spawn_blocking(move || {
+                while let Err(RecvTimeoutError::Timeout) = stop_receiver.recv_timeout(interval) {
+                    if !is_task_running() {
+                        log::warn!("task is killed, cancelling native execution");
+                        native_task_context.cancel();
+                        cancel_all_tasks(&task_ctx);
+                        break;
+                    }
+                }
+            });
+            stopper
+        });
+
         let native_execution_runtime = Self {
+            stage_id,
             exec_ctx: exec_ctx.clone(),
             native_wrapper: native_wrapper.clone(),
             plan: execution_plan.clone(),
+            task_mem_usage,
+            jni_call_stats,
+            native_task_context,
             tokio_runtime,
             batch_receiver,
             join_handle,
+            metrics_updater_stopper,
+            cancellation_watcher_stopper,
+            _spill_cleanup_guard: SpillCleanupGuard(spill_manager),
         };
         Ok(native_execution_runtime)
     }
@@ -247,11 +389,11 @@ impl NativeExecutionRuntime {
                 .or_else(|err| df_execution_err!("receive batch error: {err}"))??
             {
                 Some(batch) => {
-                    let struct_array = StructArray::from(batch);
-                    let ffi_array = FFI_ArrowArray::new(&struct_array.to_data());
-                    jni_call!(BlazeCallNativeWrapper(self.native_wrapper.as_obj())
-                        .importBatch(&ffi_array as *const FFI_ArrowArray as i64) -> ()
-                    )?;
+                    export_batch_to_jvm(batch, |ffi_array_ptr| {
+                        jni_call!(BlazeCallNativeWrapper(self.native_wrapper.as_obj())
+                            .importBatch(ffi_array_ptr) -> ()
+                        )
+                    })?;
                     Ok(true)
                 }
                 None => Ok(false),
@@ -271,30 +413,107 @@ impl NativeExecutionRuntime {
         }
     }
 
-    pub fn finalize(self) {
+    pub fn finalize(mut self) {
         let partition = self.exec_ctx.partition_id();
 
         log::info!("(partition={partition}) native execution finalizing");
-        self.update_metrics().unwrap_or_default();
+        drop(self.cancellation_watcher_stopper.take());
+        drop(self.metrics_updater_stopper.take());
+        update_metrics(&self.native_wrapper, &self.plan, &self.jni_call_stats).unwrap_or_default();
+        self.record_resource_usage();
+        self.log_jni_call_stats();
+        if let Err(err) = self.dump_plan() {
+            log::warn!("(partition={partition}) error dumping executed plan: {err}");
+        }
         drop(self.plan);
         drop(self.batch_receiver);
 
-        cancel_all_tasks(&self.exec_ctx.task_ctx()); // cancel all pending streams
+        // cancel all pending streams and running loops. the task context is
+        // always cancelled here, so a spill merge still running during finalize
+        // (e.g. output not fully consumed by spark) fails with "task cancelled"
+        // instead of being drained. spill files are removed with the spill
+        // cleanup guard
+        self.native_task_context.cancel();
+        cancel_all_tasks(&self.exec_ctx.task_ctx());
         self.join_handle.abort();
         self.tokio_runtime.shutdown_background();
+        set_thread_jni_call_stats(None);
         log::info!("(partition={partition}) native execution finalized");
     }
 
-    fn update_metrics(&self) -> Result<()> {
-        let metrics = jni_call!(
-            BlazeCallNativeWrapper(self.native_wrapper.as_obj()).getMetrics() -> JObject
+    fn log_jni_call_stats(&self) {
+        const NUM_LOGGED_CALL_SITES: usize = 5;
+        let total = self.jni_call_stats.total();
+        let top_sites = self
+            .jni_call_stats
+            .sites()
+            .into_iter()
+            .take(NUM_LOGGED_CALL_SITES)
+            .map(|(site, s)| format!("{site}: {} calls in {:?}", s.num_calls, s.elapsed))
+            .collect::<Vec<_>>();
+        log::info!(
+            "(partition={}) jni calls: {} calls in {:?}, top call sites: [{}]",
+            self.exec_ctx.partition_id(),
+            total.num_calls,
+            total.elapsed,
+            top_sites.join(", "),
+        );
+    }
+
+    fn dump_plan(&self) -> Result<()> {
+        if !conf::EXPLAIN_ANALYZE_ENABLE.value()? {
+            return Ok(());
+        }
+        let format = ExplainFormat::parse(&conf::EXPLAIN_ANALYZE_FORMAT.value()?)?;
+        let plan_dump = jni_new_string!(explain_analyze(&self.plan, format))?;
+        jni_call!(BlazeCallNativeWrapper(self.native_wrapper.as_obj())
+            .setPlanDump(plan_dump.as_obj()) -> ()
         )?;
-        update_spark_metric_node(metrics.as_obj(), self.plan.clone())?;
         Ok(())
     }
+
+    fn record_resource_usage(&self) {
+        let usage = ResourceUsage::from_task_plan(&self.plan, self.task_mem_usage.peak_mem_used());
+        log::info!(
+            "(partition={}) task resource usage: {usage:?}",
+            self.exec_ctx.partition_id()
+        );
+        record_task_resource_usage(self.stage_id, &usage);
+    }
+}
+
+fn update_metrics(
+    native_wrapper: &GlobalRef,
+    plan: &Arc<dyn ExecutionPlan>,
+    jni_call_stats: &JniCallStats,
+) -> Result<()> {
+    let metrics = jni_call!(
+        BlazeCallNativeWrapper(native_wrapper.as_obj()).getMetrics() -> JObject
+    )?;
+    update_spark_metric_node(metrics.as_obj(), plan.clone())?;
+
+    // jni overhead of the whole task is reported in the root node
+    let jni_calls = jni_call_stats.total();
+    add_metric_values(
+        metrics.as_obj(),
+        &[
+            ("jni_call_count", jni_calls.num_calls as i64),
+            ("jni_call_time", jni_calls.elapsed.as_nanos() as i64),
+        ],
+    )?;
+    Ok(())
 }
 
 fn set_error(native_wrapper: &GlobalRef, message: &str, cause: Option<JObject>) -> Result<()> {
+    // errors with error codes are thrown as the corresponding spark exceptions
+    if let (None, Some((error_class, error_message))) = (&cause, find_error_code(message)) {
+        let error_class = jni_new_string!(error_class.to_owned())?;
+        let error_message = jni_new_string!(error_message.to_owned())?;
+        jni_call!(BlazeCallNativeWrapper(native_wrapper.as_obj())
+            .setSparkError(error_class.as_obj(), error_message.as_obj()) -> ())?;
+        return Ok(());
+    }
+
     let message = jni_new_string!(message.to_owned())?;
     let e = jni_new_object!(JavaRuntimeException(
         message.as_obj(),
@@ -224,7 +224,7 @@ fn try_cast_string_array_to_integer(array: &dyn Array, cast_type: &DataType) ->
 
             for v in array.iter() {
                 match v {
-                    Some(s) => builder.append_option(to_integer(s)),
+                    Some(s) => builder.append_option(to_integer(trim_all(s), true)),
                     None => builder.append_null(),
                 }
             }
@@ -241,8 +241,17 @@ fn try_cast_string_array_to_integer(array: &dyn Array, cast_type: &DataType) ->
     })
 }
 
+/// trims leading and trailing whitespaces and control characters, like spark's
+/// `UTF8String.trimAll()`
+pub(crate) fn trim_all(s: &str) -> &str {
+    s.trim_matches(|c: char| c.is_whitespace() || c.is_control())
+}
+
 // this implementation is original copied from spark UTF8String.scala
-fn to_integer<T: Bounded + FromPrimitive + Integer + Signed + Copy>(input: &str) -> Option<T> {
+pub(crate) fn to_integer<T: Bounded + FromPrimitive + Integer + Signed + Copy>(
+    input: &str,
+    allow_decimal: bool,
+) -> Option<T> {
     let bytes = input.as_bytes();
 
     if bytes.is_empty() {
@@ -268,7 +277,7 @@ fn to_integer<T: Bounded + FromPrimitive + Integer + Signed + Copy>(input: &str)
     while offset < bytes.len() {
         let b = bytes[offset];
         offset += 1;
-        if b == separator {
+        if b == separator && allow_decimal {
             // We allow decimals and will return a truncated integral in that case.
             // Therefore we won't throw an exception here (checking the fractional
             // part happens below.)
@@ -16,17 +16,24 @@ use std::sync::Arc;
 
 use arrow::{
     array::{
-        downcast_primitive, make_array, new_empty_array, Array, ArrayRef, ArrowPrimitiveType,
-        AsArray, BooleanBufferBuilder, BufferBuilder, Capacities, GenericByteArray,
-        MutableArrayData, PrimitiveArray, RecordBatch, RecordBatchOptions,
+        downcast_integer, downcast_primitive, make_array, new_empty_array, Array, ArrayRef,
+        ArrowPrimitiveType, AsArray, BooleanBufferBuilder, BufferBuilder, Capacities,
+        GenericByteArray, GenericByteViewArray, MutableArrayData, PrimitiveArray, RecordBatch,
+        RecordBatchOptions,
     },
     buffer::{NullBuffer, OffsetBuffer, ScalarBuffer},
     datatypes::{
-        ArrowNativeType, BinaryType, ByteArrayType, LargeBinaryType, LargeUtf8Type, Utf8Type,
+        ArrowDictionaryKeyType, ArrowNativeType, BinaryType, BinaryViewType, ByteArrayType,
+        ByteViewType, LargeBinaryType, LargeUtf8Type, StringViewType, Utf8Type,
     },
 };
 use arrow_schema::{DataType, SchemaRef};
 
+use crate::arrow::{
+    dictionary::{new_dictionary_array, unify_dictionaries},
+    view::offset_view_buffer_index,
+};
+
 /// coalesce batches without checking there schemas, invokers must make
 /// sure all arrays have the same schema
 pub fn coalesce_batches_unchecked(schema: SchemaRef, batches: &[RecordBatch]) -> RecordBatch {
@@ -86,6 +93,25 @@ pub fn coalesce_arrays_unchecked(data_type: &DataType, arrays: &[ArrayRef]) -> A
         DataType::LargeBinary => {
             return coalesce_bytes_arrays_unchecked::<LargeBinaryType>(arrays);
         }
+        DataType::Utf8View => {
+            return coalesce_byte_view_arrays_unchecked::<StringViewType>(arrays);
+        }
+        DataType::BinaryView => {
+            return coalesce_byte_view_arrays_unchecked::<BinaryViewType>(arrays);
+        }
+        DataType::Dictionary(key_type, _) => {
+            macro_rules! dictionary_helper {
+                ($t:ty) => {{
+                    if let Some(coalesced) = coalesce_dictionary_arrays_unchecked::<$t>(arrays) {
+                        return coalesced;
+                    }
+                }};
+            }
+            downcast_integer! {
+                key_type.as_ref() => (dictionary_helper),
+                _ => {},
+            }
+        }
         _ => {},
     }
 
@@ -168,6 +194,53 @@ where
     ))
 }
 
+/// coalesces view arrays by their views, data buffers are shared instead of
+/// being copied
+fn coalesce_byte_view_arrays_unchecked<T: ByteViewType + ?Sized>(arrays: &[ArrayRef]) -> ArrayRef {
+    let items_len = arrays.iter().map(|a| a.len()).sum();
+    let mut views: Vec<u128> = Vec::with_capacity(items_len);
+    let mut buffers = vec![];
+
+    for array in arrays {
+        let array = array.as_byte_view::<T>();
+        let buffer_offset = buffers.len() as u32;
+        views.extend(
+            array
+                .views()
+                .iter()
+                .map(|&view| offset_view_buffer_index(view, buffer_offset)),
+        );
+        buffers.extend(array.data_buffers().iter().cloned());
+    }
+
+    // safety: views are offsetted to the coalesced data buffers
+    let nulls = coalesce_null_buffer(items_len, arrays);
+    Arc::new(unsafe {
+        GenericByteViewArray::<T>::new_unchecked(ScalarBuffer::from(views), buffers, nulls)
+    })
+}
+
+/// coalesces dictionary arrays by their keys, dictionaries are unified so the
+/// output stays dictionary-encoded without duplicated values
+fn coalesce_dictionary_arrays_unchecked<K: ArrowDictionaryKeyType>(
+    arrays: &[ArrayRef],
+) -> Option<ArrayRef> {
+    let dicts = arrays
+        .iter()
+        .map(|array| array.as_dictionary::<K>())
+        .collect::<Vec<_>>();
+    let (shared_values, keys) = unify_dictionaries(&dicts)?;
+    let keys = keys
+        .into_iter()
+        .map(|keys| Arc::new(keys) as ArrayRef)
+        .collect::<Vec<_>>();
+    let coalesced_keys = coalesce_primitive_arrays_unchecked::<K>(&keys, &K::DATA_TYPE);
+    Some(new_dictionary_array(
+        coalesced_keys.as_primitive::<K>().clone(),
+        shared_values,
+    ))
+}
+
 fn coalesce_null_buffer(items_len: usize, arrays: &[ArrayRef]) -> Option<NullBuffer> {
     arrays.iter().any(|array| array.nulls().is_some()).then(|| {
         let mut valids = BooleanBufferBuilder::new(items_len);
@@ -184,7 +257,7 @@ fn coalesce_null_buffer(items_len: usize, arrays: &[ArrayRef]) -> Option<NullBuf
 
 #[cfg(test)]
 mod tests {
-    use arrow::array::{Int32Array, StringArray};
+    use arrow::array::{DictionaryArray, Int32Array, StringArray, StringViewArray};
     use datafusion::common::Result;
 
     use super::*;
@@ -251,4 +324,51 @@ mod tests {
         assert_eq!(&coalesced, &coalesced_std);
         Ok(())
     }
+
+    #[test]
+    fn test_coalesce_string_view() -> Result<()> {
+        let array1: ArrayRef = Arc::new(StringViewArray::from(vec![
+            Some("a value longer than 12 bytes"),
+            None,
+            Some("short"),
+        ]));
+        let array2: ArrayRef = Arc::new(StringViewArray::from(vec![
+            Some("another value longer than 12 bytes"),
+            Some("a value longer than 12 bytes"),
+        ]));
+        let test = vec![array1.slice(1, 2), array2, array1];
+        let coalesced = coalesce_arrays_unchecked(&DataType::Utf8View, &test);
+        let coalesced_std =
+            arrow::compute::concat(&test.iter().map(|a| a.as_ref()).collect::<Vec<_>>())?;
+        assert_eq!(&coalesced, &coalesced_std);
+        Ok(())
+    }
+
+    #[test]
+    fn test_coalesce_dictionary() -> Result<()> {
+        let dict_type = DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8));
+        let values1: ArrayRef = Arc::new(StringArray::from(vec!["a", "b"]));
+        let values2: ArrayRef = Arc::new(StringArray::from(vec!["b", "c"]));
+        let test: Vec<ArrayRef> = vec![
+            Arc::new(DictionaryArray::new(
+                Int32Array::from(vec![Some(0), None, Some(1), Some(0)]),
+                values1,
+            )),
+            Arc::new(DictionaryArray::new(
+                Int32Array::from(vec![1, 0, 1, 1]),
+                values2,
+            )),
+        ];
+        let coalesced = coalesce_arrays_unchecked(&dict_type, &test);
+        assert_eq!(coalesced.data_type(), &dict_type);
+        assert_eq!(coalesced.as_any_dictionary().values().len(), 3);
+
+        let coalesced_std =
+            arrow::compute::concat(&test.iter().map(|a| a.as_ref()).collect::<Vec<_>>())?;
+        assert_eq!(
+            &arrow::compute::cast(&coalesced, &DataType::Utf8)?,
+            &arrow::compute::cast(&coalesced_std, &DataType::Utf8)?,
+        );
+        Ok(())
+    }
 }
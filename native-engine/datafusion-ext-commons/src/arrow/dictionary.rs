@@ -0,0 +1,178 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::HashMap, sync::Arc};
+
+use arrow::{
+    array::{Array, ArrayRef, AsArray, DictionaryArray, PrimitiveArray},
+    datatypes::{ArrowDictionaryKeyType, ArrowNativeType, DataType},
+};
+
+/// returns true if the data type is string/binary, which are the value types
+/// worth keeping dictionary-encoded.
+pub fn is_dictionary_value_type(data_type: &DataType) -> bool {
+    matches!(data_type, DataType::Utf8 | DataType::Binary)
+}
+
+/// rewrites dictionary arrays to share one dictionary, returns the shared
+/// values and the rewritten keys of each array. arrays built on the shared
+/// values can be interleaved/concatenated by their keys only, and the values
+/// are transferred only once in ipc streams.
+///
+/// returns None if the arrays cannot be cheaply unified, that is, values are
+/// not string/binary, or the merged values are more than the total rows.
+pub fn unify_dictionaries<K: ArrowDictionaryKeyType>(
+    arrays: &[&DictionaryArray<K>],
+) -> Option<(ArrayRef, Vec<PrimitiveArray<K>>)> {
+    let first_values = arrays.first()?.values();
+    if arrays
+        .iter()
+        .all(|array| Arc::ptr_eq(array.values(), first_values))
+    {
+        let keys = arrays.iter().map(|array| array.keys().clone()).collect();
+        return Some((first_values.clone(), keys));
+    }
+
+    let value_type = first_values.data_type();
+    if !is_dictionary_value_type(value_type) {
+        return None;
+    }
+    let num_rows = arrays.iter().map(|array| array.len()).sum::<usize>();
+
+    // merge distinct values, null values are remapped to null keys
+    let mut merged: HashMap<&[u8], K::Native> = HashMap::new();
+    let mut merged_indices: Vec<(usize, usize)> = vec![];
+    let mut key_mappings = Vec::with_capacity(arrays.len());
+    for (array_idx, array) in arrays.iter().enumerate() {
+        let values = array.values();
+        let mut key_mapping = Vec::with_capacity(values.len());
+        for value_idx in 0..values.len() {
+            let Some(value) = bytes_value(values, value_idx) else {
+                key_mapping.push(None);
+                continue;
+            };
+            let next_key = merged.len();
+            let key = match merged.get(value) {
+                Some(&key) => key,
+                None => {
+                    if next_key >= num_rows {
+                        return None; // not a low cardinality column
+                    }
+                    let key = K::Native::from_usize(next_key)?; // key overflow
+                    merged.insert(value, key);
+                    merged_indices.push((array_idx, value_idx));
+                    key
+                }
+            };
+            key_mapping.push(Some(key));
+        }
+        key_mappings.push(key_mapping);
+    }
+
+    let value_arrays = arrays
+        .iter()
+        .map(|array| array.values().as_ref())
+        .collect::<Vec<_>>();
+    let merged_values = arrow::compute::interleave(&value_arrays, &merged_indices).ok()?;
+    let keys = arrays
+        .iter()
+        .zip(key_mappings)
+        .map(|(array, key_mapping)| {
+            array
+                .keys()
+                .iter()
+                .map(|key| key.and_then(|key| key_mapping[key.as_usize()]))
+                .collect::<PrimitiveArray<K>>()
+        })
+        .collect();
+    Some((merged_values, keys))
+}
+
+/// builds a dictionary array from keys and values returned by
+/// [`unify_dictionaries`].
+pub fn new_dictionary_array<K: ArrowDictionaryKeyType>(
+    keys: PrimitiveArray<K>,
+    values: ArrayRef,
+) -> ArrayRef {
+    // safety: keys are remapped into the shared values
+    Arc::new(unsafe { DictionaryArray::new_unchecked(keys, values) })
+}
+
+fn bytes_value(values: &ArrayRef, idx: usize) -> Option<&[u8]> {
+    if values.is_null(idx) {
+        return None;
+    }
+    match values.data_type() {
+        DataType::Utf8 => Some(values.as_string::<i32>().value(idx).as_bytes()),
+        DataType::Binary => Some(values.as_binary::<i32>().value(idx)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{Array, ArrayRef, AsArray, DictionaryArray, Int32Array, StringArray},
+        datatypes::Int32Type,
+    };
+
+    use crate::arrow::dictionary::{new_dictionary_array, unify_dictionaries};
+
+    #[test]
+    fn test_unify_dictionaries() {
+        let values1: ArrayRef = Arc::new(StringArray::from(vec!["a", "b", "c"]));
+        let values2: ArrayRef = Arc::new(StringArray::from(vec![Some("c"), None, Some("d")]));
+        let dict1 = DictionaryArray::new(
+            Int32Array::from(vec![Some(2), Some(0), None, Some(2)]),
+            values1.clone(),
+        );
+        let dict2 = DictionaryArray::new(Int32Array::from(vec![0, 1, 2, 0]), values2);
+
+        // arrays sharing the same values are not rewritten
+        let (shared_values, keys) = unify_dictionaries(&[&dict1, &dict1]).unwrap();
+        assert!(Arc::ptr_eq(&shared_values, &values1));
+        assert_eq!(&keys[1], dict1.keys());
+
+        let (shared_values, keys) = unify_dictionaries(&[&dict1, &dict2]).unwrap();
+        let expected_values: ArrayRef = Arc::new(StringArray::from(vec!["a", "b", "c", "d"]));
+        assert_eq!(&shared_values, &expected_values);
+        for (dict, keys) in [dict1, dict2].iter().zip(keys) {
+            let unified = new_dictionary_array(keys, shared_values.clone());
+            let unified = unified.as_dictionary::<Int32Type>();
+            for i in 0..dict.len() {
+                let expected = dict
+                    .key(i)
+                    .filter(|&k| dict.values().is_valid(k))
+                    .map(|k| dict.values().as_string::<i32>().value(k));
+                let actual = unified
+                    .key(i)
+                    .map(|k| unified.values().as_string::<i32>().value(k));
+                assert_eq!(actual, expected);
+            }
+        }
+
+        // high cardinality dictionaries are not unified
+        let dict3 = DictionaryArray::new(
+            Int32Array::from(vec![0]),
+            Arc::new(StringArray::from(vec!["x", "y"])) as ArrayRef,
+        );
+        let dict4 = DictionaryArray::new(
+            Int32Array::from(vec![0]),
+            Arc::new(StringArray::from(vec!["z"])) as ArrayRef,
+        );
+        assert!(unify_dictionaries::<Int32Type>(&[&dict3, &dict4]).is_none());
+    }
+}
@@ -15,5 +15,8 @@
 pub mod array_size;
 pub mod cast;
 pub mod coalesce;
+pub mod dictionary;
 pub mod eq_comparator;
 pub mod selection;
+pub mod spark_cast;
+pub mod view;
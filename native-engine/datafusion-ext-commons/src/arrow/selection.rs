@@ -16,17 +16,24 @@ use std::sync::Arc;
 
 use arrow::{
     array::{
-        downcast_primitive, Array, ArrayRef, ArrowPrimitiveType, BinaryArray, BooleanBufferBuilder,
-        BufferBuilder, GenericByteArray, PrimitiveArray, StringArray,
+        downcast_integer, downcast_primitive, Array, ArrayRef, ArrowPrimitiveType, AsArray,
+        BinaryArray, BooleanBufferBuilder, BufferBuilder, GenericByteArray, GenericByteViewArray,
+        PrimitiveArray, StringArray,
     },
-    buffer::{MutableBuffer, NullBuffer, OffsetBuffer},
-    datatypes::{ArrowNativeType, ByteArrayType},
+    buffer::{Buffer, MutableBuffer, NullBuffer, OffsetBuffer},
+    datatypes::{ArrowNativeType, BinaryViewType, ByteArrayType, ByteViewType, StringViewType},
     record_batch::{RecordBatch, RecordBatchOptions},
 };
 use arrow_schema::DataType;
 use datafusion::common::Result;
 
-use crate::{downcast_any, prefetch_read_data};
+use crate::{
+    arrow::{
+        dictionary::{new_dictionary_array, unify_dictionaries},
+        view::offset_view_buffer_index,
+    },
+    downcast_any, prefetch_read_data,
+};
 
 pub fn take_batch<T: ArrowPrimitiveType>(
     batch: RecordBatch,
@@ -214,9 +221,59 @@ pub fn create_array_interleaver(
         Ok(Arc::new(array))
     }
 
+    #[inline]
+    fn interleave_byte_views<T: ByteViewType + ?Sized>(
+        interleaver: &Interleave<GenericByteViewArray<T>>,
+        buffers: &[Buffer],
+        buffer_offsets: &[u32],
+        indices: &[(usize, usize)],
+    ) -> Result<ArrayRef> {
+        let nulls = interleaver.nulls(indices);
+        let mut views = Vec::with_capacity(indices.len());
+        for &(a, b) in indices {
+            let array = &interleaver.arrays[a];
+            if array.is_valid(b) {
+                views.push(offset_view_buffer_index(
+                    array.views()[b],
+                    buffer_offsets[a],
+                ));
+            } else {
+                views.push(0);
+            }
+        }
+
+        // safety: views are offsetted to the shared data buffers
+        let array = unsafe {
+            GenericByteViewArray::<T>::new_unchecked(views.into(), buffers.to_vec(), nulls)
+        };
+        Ok(Arc::new(array))
+    }
+
     if !values.is_empty() {
         let dt = values[0].data_type();
 
+        // values of view arrays are not copied, all data buffers are shared by
+        // the interleaved arrays
+        macro_rules! byte_view_helper {
+            ($t:ty) => {{
+                let interleaver = Interleave::new(
+                    values
+                        .iter()
+                        .map(|v| v.as_byte_view::<$t>().clone())
+                        .collect::<Vec<_>>(),
+                );
+                let mut buffers = vec![];
+                let mut buffer_offsets = vec![];
+                for array in &interleaver.arrays {
+                    buffer_offsets.push(buffers.len() as u32);
+                    buffers.extend(array.data_buffers().iter().cloned());
+                }
+                return Ok(Box::new(move |indices| {
+                    interleave_byte_views(&interleaver, &buffers, &buffer_offsets, indices)
+                }));
+            }};
+        }
+
         macro_rules! primitive_helper {
             ($t:ty, $dt:ident) => {{
                 let interleaver = Interleave::new(
@@ -261,6 +318,34 @@ pub fn create_array_interleaver(
                     interleave_bytes::<_, false>(&interleaver, indices)
                 }));
             }
+            DataType::Utf8View => byte_view_helper!(StringViewType),
+            DataType::BinaryView => byte_view_helper!(BinaryViewType),
+            DataType::Dictionary(key_type, _) => {
+                macro_rules! dictionary_helper {
+                    ($t:ty) => {{
+                        let dicts = values
+                            .iter()
+                            .map(|v| v.as_dictionary::<$t>())
+                            .collect::<Vec<_>>();
+                        if let Some((shared_values, keys)) = unify_dictionaries(&dicts) {
+                            let keys = keys
+                                .into_iter()
+                                .map(|keys| Arc::new(keys) as ArrayRef)
+                                .collect::<Vec<_>>();
+                            let keys_interleaver =
+                                create_array_interleaver(&keys, with_prefetching)?;
+                            return Ok(Box::new(move |indices| {
+                                let keys = keys_interleaver(indices)?.as_primitive::<$t>().clone();
+                                Ok(new_dictionary_array(keys, shared_values.clone()))
+                            }));
+                        }
+                    }};
+                }
+                downcast_integer! {
+                    key_type.as_ref() => (dictionary_helper),
+                    _ => {},
+                }
+            }
             _ => {},
         }
     }
@@ -0,0 +1,1150 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Spark compatible casting.
+//!
+//! casts are evaluated in one of spark's eval modes: in legacy mode invalid
+//! inputs produce nulls and integral overflows are wrapped, in ansi mode both
+//! are errors, and in try mode (`try_cast`) both produce nulls. timestamps are
+//! parsed and formatted in the session time zone of spark, or UTC if not
+//! specified. type pairs not handled here, including nested types, fall back
+//! to [`cast`].
+
+use std::{
+    fmt::{Display, LowerExp},
+    str::FromStr,
+    sync::Arc,
+};
+
+use arrow::{
+    array::{timezone::Tz, *},
+    datatypes::*,
+};
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, Offset, TimeDelta, TimeZone};
+use datafusion::common::Result;
+use num::Float;
+
+use crate::{
+    arrow::cast::{cast, to_integer, trim_all},
+    df_execution_err, df_spark_err,
+    spark_error::{CAST_INVALID_INPUT, CAST_OVERFLOW},
+};
+
+const MICROS_PER_SECOND: i64 = 1_000_000;
+const UNIX_EPOCH_DAYS_FROM_CE: i32 = 719_163;
+
+/// java's short zone ids (`ZoneId.SHORT_IDS`), which are accepted by spark
+const SHORT_ZONE_IDS: &[(&str, &str)] = &[
+    ("ACT", "Australia/Darwin"),
+    ("AET", "Australia/Sydney"),
+    ("AGT", "America/Argentina/Buenos_Aires"),
+    ("ART", "Africa/Cairo"),
+    ("AST", "America/Anchorage"),
+    ("BET", "America/Sao_Paulo"),
+    ("BST", "Asia/Dhaka"),
+    ("CAT", "Africa/Harare"),
+    ("CNT", "America/St_Johns"),
+    ("CST", "America/Chicago"),
+    ("CTT", "Asia/Shanghai"),
+    ("EAT", "Africa/Addis_Ababa"),
+    ("ECT", "Europe/Paris"),
+    ("IET", "America/Indiana/Indianapolis"),
+    ("IST", "Asia/Kolkata"),
+    ("JST", "Asia/Tokyo"),
+    ("MIT", "Pacific/Apia"),
+    ("NET", "Asia/Yerevan"),
+    ("NST", "Pacific/Auckland"),
+    ("PLT", "Asia/Karachi"),
+    ("PNT", "America/Phoenix"),
+    ("PRT", "America/Puerto_Rico"),
+    ("PST", "America/Los_Angeles"),
+    ("SST", "Pacific/Guadalcanal"),
+    ("VST", "Asia/Ho_Chi_Minh"),
+    ("EST", "-05:00"),
+    ("MST", "-07:00"),
+    ("HST", "-10:00"),
+];
+
+/// Eval mode of a cast, corresponds to spark's `EvalMode`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum CastMode {
+    #[default]
+    Legacy,
+    Ansi,
+    Try,
+}
+
+pub fn spark_cast(array: &dyn Array, cast_type: &DataType, mode: CastMode) -> Result<ArrayRef> {
+    spark_cast_with_time_zone(array, cast_type, mode, &Tz::from_str("UTC")?)
+}
+
+/// casts with the given time zone for converting between timestamps and local
+/// dates/times, like spark's `Cast.timeZoneId`
+pub fn spark_cast_with_time_zone(
+    array: &dyn Array,
+    cast_type: &DataType,
+    mode: CastMode,
+    tz: &Tz,
+) -> Result<ArrayRef> {
+    match (array.data_type(), cast_type) {
+        (from_type, to_type) if from_type == to_type => Ok(make_array(array.to_data())),
+
+        (DataType::Utf8, _) => cast_string(array.as_string(), cast_type, mode, tz),
+
+        // numeric to numeric
+        (from_type, to_type) if is_integral(from_type) && is_integral(to_type) => {
+            let values = widen_integral(array)?;
+            cast_integral(values.iter().map(|v| v.map(i128::from)), cast_type, mode)
+        }
+        (from_type, to_type) if is_floating(from_type) && is_integral(to_type) => {
+            cast_floating_to_integral(&widen_floating(array)?, cast_type, mode)
+        }
+        (DataType::Decimal128(_, scale), to_type) if is_integral(to_type) && *scale >= 0 => {
+            let divisor = 10i128.pow(*scale as u32);
+            let values = array.as_primitive::<Decimal128Type>();
+            cast_integral(
+                values.iter().map(|v| v.map(|v| v / divisor)),
+                cast_type,
+                mode,
+            )
+        }
+        (from_type, &DataType::Decimal128(precision, scale))
+            if is_integral(from_type) && scale >= 0 =>
+        {
+            let values = widen_integral(array)?;
+            cast_values::<_, Decimal128Type>(values.iter(), cast_type, mode, CAST_OVERFLOW, |v| {
+                rescale_decimal(v as i128, 0, precision, scale)
+            })
+        }
+        (
+            &DataType::Decimal128(from_precision, from_scale),
+            &DataType::Decimal128(precision, scale),
+        ) => {
+            let values = array.as_primitive::<Decimal128Type>();
+            cast_values::<_, Decimal128Type>(
+                decimal_values(values, from_precision, from_scale),
+                cast_type,
+                mode,
+                CAST_OVERFLOW,
+                |v| rescale_decimal(v.value, from_scale, precision, scale),
+            )
+        }
+        (from_type, &DataType::Decimal128(precision, scale)) if is_floating(from_type) => {
+            // same as spark, which converts doubles with `BigDecimal(Double.toString(v))`
+            let values = widen_floating(array)?;
+            cast_values::<_, Decimal128Type>(values.iter(), cast_type, mode, CAST_OVERFLOW, |v| {
+                parse_decimal(&format_java_floating(v), precision, scale)
+            })
+        }
+        (&DataType::Decimal128(precision, scale), DataType::Float32 | DataType::Float64) => {
+            let values = decimal_values(array.as_primitive(), precision, scale);
+            match cast_type {
+                DataType::Float32 => {
+                    cast_values::<_, Float32Type>(values, cast_type, mode, CAST_OVERFLOW, |v| {
+                        v.to_string().parse().ok()
+                    })
+                }
+                _ => cast_values::<_, Float64Type>(values, cast_type, mode, CAST_OVERFLOW, |v| {
+                    v.to_string().parse().ok()
+                }),
+            }
+        }
+
+        // numeric to string
+        (DataType::Float32, DataType::Utf8) => Ok(Arc::new(
+            array
+                .as_primitive::<Float32Type>()
+                .iter()
+                .map(|v| v.map(format_java_floating))
+                .collect::<StringArray>(),
+        )),
+        (DataType::Float64, DataType::Utf8) => Ok(Arc::new(
+            array
+                .as_primitive::<Float64Type>()
+                .iter()
+                .map(|v| v.map(format_java_floating))
+                .collect::<StringArray>(),
+        )),
+
+        // datetime
+        (DataType::Timestamp(TimeUnit::Microsecond, _), DataType::Utf8) => Ok(Arc::new(
+            array
+                .as_primitive::<TimestampMicrosecondType>()
+                .iter()
+                .map(|v| v.and_then(|v| format_timestamp(v, tz)))
+                .collect::<StringArray>(),
+        )),
+        (DataType::Timestamp(TimeUnit::Microsecond, _), to_type) if is_integral(to_type) => {
+            let values = array.as_primitive::<TimestampMicrosecondType>();
+            let seconds = values
+                .iter()
+                .map(|v| v.map(|v| v.div_euclid(MICROS_PER_SECOND) as i128));
+            cast_integral(seconds, cast_type, mode)
+        }
+        (DataType::Timestamp(TimeUnit::Microsecond, _), DataType::Float32) => {
+            let values = array.as_primitive::<TimestampMicrosecondType>();
+            cast_values::<_, Float32Type>(values.iter(), cast_type, mode, CAST_OVERFLOW, |v| {
+                Some((v as f64 / MICROS_PER_SECOND as f64) as f32)
+            })
+        }
+        (DataType::Timestamp(TimeUnit::Microsecond, _), DataType::Float64) => {
+            let values = array.as_primitive::<TimestampMicrosecondType>();
+            cast_values::<_, Float64Type>(values.iter(), cast_type, mode, CAST_OVERFLOW, |v| {
+                Some(v as f64 / MICROS_PER_SECOND as f64)
+            })
+        }
+        (DataType::Timestamp(TimeUnit::Microsecond, _), DataType::Date32) => {
+            let values = array.as_primitive::<TimestampMicrosecondType>();
+            cast_values::<_, Date32Type>(values.iter(), cast_type, mode, CAST_OVERFLOW, |v| {
+                Some(days_from_epoch(&utc_micros_to_local(v, tz)?.date()))
+            })
+        }
+        (DataType::Date32, DataType::Timestamp(TimeUnit::Microsecond, _)) => {
+            let values = array.as_primitive::<Date32Type>();
+            cast_values::<_, TimestampMicrosecondType>(
+                values.iter(),
+                cast_type,
+                mode,
+                CAST_OVERFLOW,
+                |v| {
+                    let date = date_from_epoch_days(v)?;
+                    local_to_utc_micros(&date.and_hms_opt(0, 0, 0)?, tz)
+                },
+            )
+        }
+        (from_type, DataType::Timestamp(TimeUnit::Microsecond, _)) if is_integral(from_type) => {
+            let values = widen_integral(array)?;
+            cast_values::<_, TimestampMicrosecondType>(
+                values.iter(),
+                cast_type,
+                mode,
+                CAST_OVERFLOW,
+                |v| match mode {
+                    CastMode::Legacy => Some(v.saturating_mul(MICROS_PER_SECOND)),
+                    _ => v.checked_mul(MICROS_PER_SECOND),
+                },
+            )
+        }
+        (from_type, DataType::Timestamp(TimeUnit::Microsecond, _)) if is_floating(from_type) => {
+            let values = widen_floating(array)?;
+            cast_values::<_, TimestampMicrosecondType>(
+                values.iter(),
+                cast_type,
+                mode,
+                CAST_INVALID_INPUT,
+                |v| v.is_finite().then(|| (v * MICROS_PER_SECOND as f64) as i64),
+            )
+        }
+        (DataType::Date32, to_type) if is_integral(to_type) || is_floating(to_type) => {
+            // spark returns nulls for casting dates to numbers
+            Ok(new_null_array(cast_type, array.len()))
+        }
+
+        _ => cast(array, cast_type),
+    }
+}
+
+fn cast_string(
+    array: &StringArray,
+    cast_type: &DataType,
+    mode: CastMode,
+    tz: &Tz,
+) -> Result<ArrayRef> {
+    // like spark, fractional parts are truncated only in legacy mode
+    let allow_decimal = mode == CastMode::Legacy;
+
+    macro_rules! cast_with {
+        ($arrow_type:ty, $f:expr) => {{
+            cast_values::<_, $arrow_type>(array.iter(), cast_type, mode, CAST_INVALID_INPUT, $f)
+        }};
+    }
+    match cast_type {
+        DataType::Int8 => cast_with!(Int8Type, |s| to_integer(trim_all(s), allow_decimal)),
+        DataType::Int16 => cast_with!(Int16Type, |s| to_integer(trim_all(s), allow_decimal)),
+        DataType::Int32 => cast_with!(Int32Type, |s| to_integer(trim_all(s), allow_decimal)),
+        DataType::Int64 => cast_with!(Int64Type, |s| to_integer(trim_all(s), allow_decimal)),
+        DataType::Float32 => cast_with!(Float32Type, parse_java_floating),
+        DataType::Float64 => cast_with!(Float64Type, parse_java_floating),
+        &DataType::Decimal128(precision, scale) => {
+            cast_with!(Decimal128Type, |s| parse_decimal(s, precision, scale))
+        }
+        DataType::Date32 => cast_with!(Date32Type, |s| Some(days_from_epoch(&parse_date(s)?))),
+        DataType::Timestamp(TimeUnit::Microsecond, _) => {
+            cast_with!(TimestampMicrosecondType, |s| parse_timestamp(s, tz))
+        }
+        DataType::Boolean => {
+            let mut builder = BooleanBuilder::with_capacity(array.len());
+            for value in array.iter() {
+                match value.map(|s| (s, parse_boolean(s))) {
+                    Some((_, Some(b))) => builder.append_value(b),
+                    Some((s, None)) if mode == CastMode::Ansi => {
+                        return cast_error(CAST_INVALID_INPUT, s, cast_type);
+                    }
+                    _ => builder.append_null(),
+                }
+            }
+            Ok(Arc::new(builder.finish()))
+        }
+        _ => cast(array, cast_type),
+    }
+}
+
+/// casts non-null values with `f`, values failed to cast are errors in ansi
+/// mode, or nulls in other modes
+fn cast_values<V: Display + Copy, T: ArrowPrimitiveType>(
+    values: impl Iterator<Item = Option<V>>,
+    cast_type: &DataType,
+    mode: CastMode,
+    error_class: &str,
+    f: impl Fn(V) -> Option<T::Native>,
+) -> Result<ArrayRef> {
+    let mut builder = PrimitiveBuilder::<T>::with_capacity(values.size_hint().0)
+        .with_data_type(cast_type.clone());
+    for value in values {
+        match value.map(|v| (v, f(v))) {
+            Some((_, Some(casted))) => builder.append_value(casted),
+            Some((v, None)) if mode == CastMode::Ansi => {
+                return cast_error(error_class, v, cast_type);
+            }
+            _ => builder.append_null(),
+        }
+    }
+    Ok(Arc::new(builder.finish()))
+}
+
+fn cast_error<T>(error_class: &str, value: impl Display, cast_type: &DataType) -> Result<T> {
+    df_spark_err!(
+        error_class,
+        "the value '{value}' cannot be cast to {cast_type} in ansi mode, \
+        use try_cast to tolerate malformed input and return null instead"
+    )
+}
+
+/// casts integral values to an integral type, out of range values are wrapped
+/// like java in legacy mode
+fn cast_integral(
+    values: impl Iterator<Item = Option<i128>>,
+    cast_type: &DataType,
+    mode: CastMode,
+) -> Result<ArrayRef> {
+    macro_rules! cast_to {
+        ($arrow_type:ty, $native:ty) => {{
+            cast_values::<_, $arrow_type>(values, cast_type, mode, CAST_OVERFLOW, |v| match mode {
+                CastMode::Legacy => Some(v as i64 as $native),
+                _ => <$native>::try_from(v).ok(),
+            })
+        }};
+    }
+    match cast_type {
+        DataType::Int8 => cast_to!(Int8Type, i8),
+        DataType::Int16 => cast_to!(Int16Type, i16),
+        DataType::Int32 => cast_to!(Int32Type, i32),
+        DataType::Int64 => cast_to!(Int64Type, i64),
+        other => df_execution_err!("cast_integral: unsupported cast type: {other}"),
+    }
+}
+
+/// casts floating values to an integral type. in legacy mode, values are
+/// converted to int/long first like java, so that NaN is zero and out of range
+/// values are saturated (then wrapped for byte/short)
+fn cast_floating_to_integral(
+    values: &Float64Array,
+    cast_type: &DataType,
+    mode: CastMode,
+) -> Result<ArrayRef> {
+    macro_rules! cast_to {
+        ($arrow_type:ty, $native:ty, $java_native:ty) => {{
+            cast_values::<_, $arrow_type>(values.iter(), cast_type, mode, CAST_OVERFLOW, |v| {
+                match mode {
+                    CastMode::Legacy => Some(v as $java_native as $native),
+                    _ => {
+                        let v = v.trunc();
+                        let in_range = v >= <$native>::MIN as f64 && v <= <$native>::MAX as f64;
+                        in_range.then(|| v as $native)
+                    }
+                }
+            })
+        }};
+    }
+    match cast_type {
+        DataType::Int8 => cast_to!(Int8Type, i8, i32),
+        DataType::Int16 => cast_to!(Int16Type, i16, i32),
+        DataType::Int32 => cast_to!(Int32Type, i32, i32),
+        DataType::Int64 => cast_to!(Int64Type, i64, i64),
+        other => df_execution_err!("cast_floating_to_integral: unsupported cast type: {other}"),
+    }
+}
+
+fn is_integral(dt: &DataType) -> bool {
+    matches!(
+        dt,
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64
+    )
+}
+
+fn is_floating(dt: &DataType) -> bool {
+    matches!(dt, DataType::Float32 | DataType::Float64)
+}
+
+fn widen_integral(array: &dyn Array) -> Result<Int64Array> {
+    let widened = arrow::compute::cast(array, &DataType::Int64)?;
+    Ok(widened.as_primitive::<Int64Type>().clone())
+}
+
+fn widen_floating(array: &dyn Array) -> Result<Float64Array> {
+    let widened = arrow::compute::cast(array, &DataType::Float64)?;
+    Ok(widened.as_primitive::<Float64Type>().clone())
+}
+
+/// A decimal value displayed in its plain string form
+#[derive(Clone, Copy)]
+struct DecimalValue {
+    value: i128,
+    precision: u8,
+    scale: i8,
+}
+
+impl Display for DecimalValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let formatted = Decimal128Type::format_decimal(self.value, self.precision, self.scale);
+        write!(f, "{formatted}")
+    }
+}
+
+fn decimal_values(
+    array: &Decimal128Array,
+    precision: u8,
+    scale: i8,
+) -> impl Iterator<Item = Option<DecimalValue>> + '_ {
+    array.iter().map(move |v| {
+        v.map(|value| DecimalValue {
+            value,
+            precision,
+            scale,
+        })
+    })
+}
+
+/// rescales an unscaled decimal value, rounding half up like java's
+/// `BigDecimal.setScale()`. returns None if the result does not fit into the
+/// precision
+fn rescale_decimal(value: i128, from_scale: i8, precision: u8, scale: i8) -> Option<i128> {
+    let scale_diff = scale as i32 - from_scale as i32;
+    let rescaled = if scale_diff >= 0 {
+        value.checked_mul(10i128.checked_pow(scale_diff as u32)?)?
+    } else {
+        match 10i128.checked_pow(-scale_diff as u32) {
+            Some(divisor) => {
+                let (quotient, remainder) = (value / divisor, value % divisor);
+                if remainder.unsigned_abs() * 2 >= divisor as u128 {
+                    quotient + value.signum()
+                } else {
+                    quotient
+                }
+            }
+            None => 0, // the divisor is larger than any decimal value
+        }
+    };
+    (rescaled.unsigned_abs() < 10u128.pow(precision as u32)).then_some(rescaled)
+}
+
+/// parses a decimal string in java's `BigDecimal` syntax (with an optional
+/// exponent), rounding half up to the scale. returns None if the string is
+/// malformed or the value does not fit into the precision
+fn parse_decimal(s: &str, precision: u8, scale: i8) -> Option<i128> {
+    let s = trim_all(s).as_bytes();
+    let (negative, s) = match s.first()? {
+        b'-' => (true, &s[1..]),
+        b'+' => (false, &s[1..]),
+        _ => (false, s),
+    };
+    let (mantissa, exponent) = match s.iter().position(|&b| b == b'e' || b == b'E') {
+        Some(pos) => (
+            &s[..pos],
+            std::str::from_utf8(&s[pos + 1..])
+                .ok()?
+                .parse::<i32>()
+                .ok()?,
+        ),
+        None => (s, 0),
+    };
+    let (int_part, frac_part) = match mantissa.iter().position(|&b| b == b'.') {
+        Some(pos) => (&mantissa[..pos], &mantissa[pos + 1..]),
+        None => (mantissa, &[][..]),
+    };
+    if int_part.is_empty() && frac_part.is_empty() {
+        return None;
+    }
+    if !int_part.iter().chain(frac_part).all(u8::is_ascii_digit) {
+        return None;
+    }
+
+    // value = digits * 10^(exponent - frac_len), unscaled = value * 10^scale
+    let digits = int_part
+        .iter()
+        .chain(frac_part)
+        .skip_while(|&&b| b == b'0')
+        .map(|&b| (b - b'0') as i128)
+        .collect::<Vec<_>>();
+    let shift = exponent as i64 - frac_part.len() as i64 + scale as i64;
+    let num_kept_digits = digits.len() as i64 + shift.min(0);
+    if digits.is_empty() || num_kept_digits < 0 {
+        return Some(0);
+    }
+    if num_kept_digits + shift.max(0) > 38 {
+        return None;
+    }
+
+    let kept_digits = &digits[..num_kept_digits as usize];
+    let mut unscaled = kept_digits.iter().fold(0i128, |acc, &d| acc * 10 + d);
+    if shift > 0 {
+        unscaled *= 10i128.pow(shift as u32);
+    } else if digits
+        .get(num_kept_digits as usize)
+        .is_some_and(|&d| d >= 5)
+    {
+        unscaled += 1;
+    }
+    if unscaled.unsigned_abs() >= 10u128.pow(precision as u32) {
+        return None;
+    }
+    Some(if negative { -unscaled } else { unscaled })
+}
+
+/// parses a floating value like java's `Double.parseDouble()`, with spark's
+/// special literals like `inf` and `nan`
+fn parse_java_floating<F: Float + FromStr>(s: &str) -> Option<F> {
+    let s = s.trim_matches(|c: char| c <= ' ');
+    match s.to_ascii_lowercase().as_str() {
+        "inf" | "+inf" | "infinity" | "+infinity" => return Some(F::infinity()),
+        "-inf" | "-infinity" => return Some(F::neg_infinity()),
+        "nan" => return Some(F::nan()),
+        _ => {}
+    }
+    if matches!(s, "+NaN" | "-NaN") {
+        return Some(F::nan());
+    }
+
+    // java allows type suffixes like `1.5f` and `1.5d`
+    let s = s
+        .strip_suffix(|c: char| matches!(c, 'f' | 'F' | 'd' | 'D'))
+        .unwrap_or(s);
+    if !s
+        .bytes()
+        .all(|b| b.is_ascii_digit() || matches!(b, b'+' | b'-' | b'.' | b'e' | b'E'))
+    {
+        return None;
+    }
+    s.parse().ok()
+}
+
+/// formats a floating value like java's `Double.toString()` and
+/// `Float.toString()`: plain notation for magnitudes in [1e-3, 1e7), otherwise
+/// computerized scientific notation like `1.0E-5`
+fn format_java_floating<F: Float + LowerExp>(v: F) -> String {
+    if v.is_nan() {
+        return "NaN".to_string();
+    }
+    if v.is_infinite() {
+        return if v > F::zero() {
+            "Infinity"
+        } else {
+            "-Infinity"
+        }
+        .to_string();
+    }
+
+    // shortest representation like `-1.2345e3`
+    let sci = format!("{v:e}");
+    let (negative, sci) = match sci.strip_prefix('-') {
+        Some(sci) => (true, sci),
+        None => (false, sci.as_str()),
+    };
+    let (mantissa, exponent) = sci.split_once('e').expect("invalid scientific notation");
+    let exponent = exponent.parse::<i32>().expect("invalid exponent");
+    let digits = mantissa.replace('.', "");
+
+    let mut formatted = String::new();
+    if negative {
+        formatted.push('-');
+    }
+    if (-3..7).contains(&exponent) {
+        if exponent < 0 {
+            formatted.push_str("0.");
+            formatted.push_str(&"0".repeat((-exponent - 1) as usize));
+            formatted.push_str(&digits);
+        } else {
+            let int_len = exponent as usize + 1;
+            if digits.len() <= int_len {
+                formatted.push_str(&digits);
+                formatted.push_str(&"0".repeat(int_len - digits.len()));
+                formatted.push_str(".0");
+            } else {
+                formatted.push_str(&digits[..int_len]);
+                formatted.push('.');
+                formatted.push_str(&digits[int_len..]);
+            }
+        }
+    } else {
+        formatted.push_str(&digits[..1]);
+        formatted.push('.');
+        formatted.push_str(if digits.len() > 1 { &digits[1..] } else { "0" });
+        formatted.push('E');
+        formatted.push_str(&exponent.to_string());
+    }
+    formatted
+}
+
+/// parses a boolean like spark's `StringUtils.isTrueString()` and
+/// `StringUtils.isFalseString()`
+fn parse_boolean(s: &str) -> Option<bool> {
+    match trim_all(s).to_ascii_lowercase().as_str() {
+        "t" | "true" | "y" | "yes" | "1" => Some(true),
+        "f" | "false" | "n" | "no" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// parses a date like spark's `DateTimeUtils.stringToDate()`, supported formats
+/// are `[+-]yyyy*`, `[+-]yyyy*-[m]m`, `[+-]yyyy*-[m]m-[d]d` and
+/// `[+-]yyyy*-[m]m-[d]d[ T]*`
+fn parse_date(s: &str) -> Option<NaiveDate> {
+    let (date, _) = parse_date_prefix(trim_all(s).as_bytes())?;
+    Some(date)
+}
+
+/// parses the date part of a date or timestamp string, returns the date and
+/// the position after the date part
+fn parse_date_prefix(bytes: &[u8]) -> Option<(NaiveDate, usize)> {
+    let is_valid_digits = |segment: usize, num_digits: usize| match segment {
+        0 => (4..=7).contains(&num_digits),
+        _ => (1..=2).contains(&num_digits),
+    };
+
+    let mut segments = [1i32; 3];
+    let mut segment = 0;
+    let mut value = 0i32;
+    let mut num_digits = 0;
+    let mut pos = 0;
+    let sign = match bytes.first()? {
+        b'-' => -1,
+        _ => 1,
+    };
+    if matches!(bytes[0], b'-' | b'+') {
+        pos += 1;
+    }
+
+    while pos < bytes.len() && !matches!(bytes[pos], b' ' | b'T') {
+        let b = bytes[pos];
+        if segment < 2 && b == b'-' {
+            if !is_valid_digits(segment, num_digits) {
+                return None;
+            }
+            segments[segment] = value;
+            segment += 1;
+            value = 0;
+            num_digits = 0;
+        } else if b.is_ascii_digit() && num_digits < 7 {
+            value = value * 10 + (b - b'0') as i32;
+            num_digits += 1;
+        } else {
+            return None;
+        }
+        pos += 1;
+    }
+    if !is_valid_digits(segment, num_digits) || (segment < 2 && pos < bytes.len()) {
+        return None;
+    }
+    segments[segment] = value;
+
+    let date = NaiveDate::from_ymd_opt(sign * segments[0], segments[1] as u32, segments[2] as u32)?;
+    Some((date, pos))
+}
+
+/// parses a timestamp like spark's `DateTimeUtils.stringToTimestamp()`, which
+/// is a date optionally followed by ` ` or `T`, a time like
+/// `[h]h:[m]m:[s]s.[ms][ms][ms][us][us][us]` (fields can be omitted from the
+/// right) and a zone id. zone-less timestamps are in `default_tz`. returns
+/// microseconds since epoch.
+fn parse_timestamp(s: &str, default_tz: &Tz) -> Option<i64> {
+    let bytes = trim_all(s).as_bytes();
+    let (date, mut pos) = parse_date_prefix(bytes)?;
+    let mut time = [0u32; 3];
+    let mut micros = 0u32;
+    let mut tz = None;
+
+    pos += 1; // skip ' ' or 'T'
+    if pos < bytes.len() {
+        let mut segment = 0;
+        let mut num_digits = 0;
+        while pos < bytes.len() {
+            let b = bytes[pos];
+            if b.is_ascii_digit() && num_digits < 2 {
+                time[segment] = time[segment] * 10 + (b - b'0') as u32;
+                num_digits += 1;
+            } else if b == b':' && segment < 2 && num_digits > 0 {
+                segment += 1;
+                num_digits = 0;
+            } else {
+                break;
+            }
+            pos += 1;
+        }
+        if num_digits == 0 {
+            return None;
+        }
+
+        // fraction of second, digits after microseconds are truncated
+        if segment == 2 && pos < bytes.len() && bytes[pos] == b'.' {
+            pos += 1;
+            let mut num_fraction_digits = 0;
+            while pos < bytes.len() && bytes[pos].is_ascii_digit() {
+                if num_fraction_digits < 6 {
+                    micros = micros * 10 + (bytes[pos] - b'0') as u32;
+                    num_fraction_digits += 1;
+                }
+                pos += 1;
+            }
+            micros *= 10u32.pow(6 - num_fraction_digits);
+        }
+
+        while pos < bytes.len() && bytes[pos] == b' ' {
+            pos += 1;
+        }
+        if pos < bytes.len() {
+            tz = Some(parse_zone_id(std::str::from_utf8(&bytes[pos..]).ok()?)?);
+        }
+    }
+
+    let local = date.and_hms_micro_opt(time[0], time[1], time[2], micros)?;
+    local_to_utc_micros(&local, tz.as_ref().unwrap_or(default_tz))
+}
+
+/// parses a zone id like java's `ZoneId.of()` with short ids, supported zone
+/// ids are `Z`, offsets like `+08:00` optionally prefixed by `UTC`, `GMT` or
+/// `UT`, short ids like `PST` and region ids like `Asia/Shanghai`
+pub fn parse_zone_id(zone_id: &str) -> Option<Tz> {
+    if zone_id == "Z" {
+        return Tz::from_str("+00:00").ok();
+    }
+
+    let offset = ["UTC", "GMT", "UT"]
+        .iter()
+        .find_map(|prefix| zone_id.strip_prefix(prefix))
+        .unwrap_or(zone_id);
+    if offset.is_empty() {
+        return Tz::from_str("+00:00").ok();
+    }
+    let mut chars = offset.chars();
+    if let Some(sign @ ('+' | '-')) = chars.next() {
+        let offset = chars.as_str();
+        if !offset.is_ascii() {
+            return None;
+        }
+        let (hours, minutes) = match offset.len() {
+            1 | 2 => (offset, "0"),
+            4 => offset.split_at(2),
+            5 if offset.as_bytes()[2] == b':' => (&offset[..2], &offset[3..]),
+            _ => return None,
+        };
+        if !hours
+            .bytes()
+            .chain(minutes.bytes())
+            .all(|b| b.is_ascii_digit())
+        {
+            return None;
+        }
+        let (hours, minutes) = (hours.parse::<u32>().ok()?, minutes.parse::<u32>().ok()?);
+        if hours > 18 || minutes > 59 {
+            return None;
+        }
+        return Tz::from_str(&format!("{sign}{hours:02}:{minutes:02}")).ok();
+    }
+    if offset.len() != zone_id.len() {
+        return None; // prefixed but not followed by an offset
+    }
+
+    let zone_id = SHORT_ZONE_IDS
+        .iter()
+        .find(|(short_id, _)| *short_id == zone_id)
+        .map(|(_, id)| *id)
+        .unwrap_or(zone_id);
+    Tz::from_str(zone_id).ok()
+}
+
+/// converts a local date time to microseconds since epoch. like java, the
+/// earlier offset is used for overlapped local times, and local times in a gap
+/// are shifted later by the length of the gap
+pub fn local_to_utc_micros(local: &NaiveDateTime, tz: &Tz) -> Option<i64> {
+    match tz.from_local_datetime(local).earliest() {
+        Some(datetime) => Some(datetime.timestamp_micros()),
+        None => {
+            let offset_before_gap = tz
+                .offset_from_utc_datetime(&local.checked_sub_signed(TimeDelta::days(1))?)
+                .fix()
+                .local_minus_utc();
+            let micros = local.and_utc().timestamp_micros();
+            micros.checked_sub(offset_before_gap as i64 * MICROS_PER_SECOND)
+        }
+    }
+}
+
+pub fn utc_micros_to_local(micros: i64, tz: &Tz) -> Option<NaiveDateTime> {
+    let seconds = micros.div_euclid(MICROS_PER_SECOND);
+    let nanos = micros.rem_euclid(MICROS_PER_SECOND) as u32 * 1000;
+    let utc = DateTime::from_timestamp(seconds, nanos)?;
+    Some(utc.with_timezone(tz).naive_local())
+}
+
+/// formats a timestamp like spark: `yyyy-MM-dd HH:mm:ss[.SSSSSS]` with trailing
+/// zeros of the fraction omitted
+fn format_timestamp(micros: i64, tz: &Tz) -> Option<String> {
+    let local = utc_micros_to_local(micros, tz)?;
+    let mut formatted = local.format("%Y-%m-%d %H:%M:%S").to_string();
+    let fraction = micros.rem_euclid(MICROS_PER_SECOND);
+    if fraction > 0 {
+        formatted.push('.');
+        formatted.push_str(format!("{fraction:06}").trim_end_matches('0'));
+    }
+    Some(formatted)
+}
+
+pub fn days_from_epoch(date: &NaiveDate) -> i32 {
+    date.num_days_from_ce() - UNIX_EPOCH_DAYS_FROM_CE
+}
+
+pub fn date_from_epoch_days(days: i32) -> Option<NaiveDate> {
+    NaiveDate::from_num_days_from_ce_opt(days.checked_add(UNIX_EPOCH_DAYS_FROM_CE)?)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{str::FromStr, sync::Arc};
+
+    use arrow::{
+        array::{timezone::Tz, *},
+        datatypes::*,
+    };
+    use datafusion::common::Result;
+
+    use crate::arrow::spark_cast::{spark_cast, spark_cast_with_time_zone, CastMode};
+
+    fn cast_strings(
+        values: &[Option<&str>],
+        cast_type: DataType,
+        mode: CastMode,
+    ) -> Result<ArrayRef> {
+        let array: ArrayRef = Arc::new(StringArray::from(values.to_vec()));
+        spark_cast(&array, &cast_type, mode)
+    }
+
+    #[test]
+    fn test_string_to_integral() -> Result<()> {
+        let values = [Some(" 12\t"), Some("1.5"), Some("abc"), Some("300"), None];
+        let casted = cast_strings(&values, DataType::Int32, CastMode::Legacy)?;
+        assert_eq!(
+            casted.as_primitive::<Int32Type>(),
+            &Int32Array::from(vec![Some(12), Some(1), None, Some(300), None])
+        );
+
+        let casted = cast_strings(&values, DataType::Int8, CastMode::Try)?;
+        assert_eq!(
+            casted.as_primitive::<Int8Type>(),
+            &Int8Array::from(vec![Some(12), None, None, None, None])
+        );
+
+        assert!(cast_strings(&values[..1], DataType::Int32, CastMode::Ansi).is_ok());
+        assert!(cast_strings(&values[..2], DataType::Int32, CastMode::Ansi).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_integral_overflow() -> Result<()> {
+        let array: ArrayRef = Arc::new(Int64Array::from(vec![Some(1), Some(300), None]));
+        let casted = spark_cast(&array, &DataType::Int8, CastMode::Legacy)?;
+        assert_eq!(
+            casted.as_primitive::<Int8Type>(),
+            &Int8Array::from(vec![Some(1), Some(44), None])
+        );
+        let casted = spark_cast(&array, &DataType::Int8, CastMode::Try)?;
+        assert_eq!(
+            casted.as_primitive::<Int8Type>(),
+            &Int8Array::from(vec![Some(1), None, None])
+        );
+        assert!(spark_cast(&array, &DataType::Int8, CastMode::Ansi).is_err());
+
+        let array: ArrayRef = Arc::new(Float64Array::from(vec![1e10, -1.9, f64::NAN]));
+        let casted = spark_cast(&array, &DataType::Int8, CastMode::Legacy)?;
+        assert_eq!(
+            casted.as_primitive::<Int8Type>(),
+            &Int8Array::from(vec![-1, -1, 0])
+        );
+        let casted = spark_cast(&array, &DataType::Int64, CastMode::Try)?;
+        assert_eq!(
+            casted.as_primitive::<Int64Type>(),
+            &Int64Array::from(vec![Some(10000000000), Some(-1), None])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_to_floating() -> Result<()> {
+        let values = [
+            Some(" 1.5d "),
+            Some("-2e3"),
+            Some("inf"),
+            Some("-Infinity"),
+            Some("1.5x"),
+            None,
+        ];
+        let casted = cast_strings(&values, DataType::Float64, CastMode::Legacy)?;
+        assert_eq!(
+            casted.as_primitive::<Float64Type>(),
+            &Float64Array::from(vec![
+                Some(1.5),
+                Some(-2000.0),
+                Some(f64::INFINITY),
+                Some(f64::NEG_INFINITY),
+                None,
+                None,
+            ])
+        );
+        let casted = cast_strings(
+            &[Some("NaN"), Some("nan")],
+            DataType::Float32,
+            CastMode::Legacy,
+        )?;
+        assert!(casted
+            .as_primitive::<Float32Type>()
+            .values()
+            .iter()
+            .all(|v| v.is_nan()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_to_boolean() -> Result<()> {
+        let values = [Some(" Yes"), Some("f"), Some("0"), Some("maybe"), None];
+        let casted = cast_strings(&values, DataType::Boolean, CastMode::Legacy)?;
+        assert_eq!(
+            casted.as_boolean(),
+            &BooleanArray::from(vec![Some(true), Some(false), Some(false), None, None])
+        );
+        assert!(cast_strings(&values, DataType::Boolean, CastMode::Ansi).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_decimal() -> Result<()> {
+        let values = [
+            Some("1.2345"),
+            Some("1.235"),
+            Some("-1.235"),
+            Some("1e2"),
+            Some(".5E-2"),
+            Some("12345"),
+            Some("1.2.3"),
+        ];
+        let casted = cast_strings(&values, DataType::Decimal128(5, 2), CastMode::Legacy)?;
+        assert_eq!(
+            casted.as_primitive::<Decimal128Type>(),
+            &Decimal128Array::from(vec![
+                Some(123),
+                Some(124),
+                Some(-124),
+                Some(10000),
+                Some(1),
+                None,
+                None,
+            ])
+            .with_precision_and_scale(5, 2)?
+        );
+
+        let array: ArrayRef = Arc::new(
+            Decimal128Array::from(vec![12345, -12355, 99999]).with_precision_and_scale(5, 3)?,
+        );
+        let casted = spark_cast(&array, &DataType::Decimal128(3, 1), CastMode::Legacy)?;
+        assert_eq!(
+            casted.as_primitive::<Decimal128Type>(),
+            &Decimal128Array::from(vec![Some(123), Some(-124), None])
+                .with_precision_and_scale(3, 1)?
+        );
+        assert!(spark_cast(&array, &DataType::Decimal128(3, 1), CastMode::Ansi).is_err());
+
+        let array: ArrayRef = Arc::new(Float64Array::from(vec![0.1, 1e-10, f64::NAN]));
+        let casted = spark_cast(&array, &DataType::Decimal128(10, 3), CastMode::Legacy)?;
+        assert_eq!(
+            casted.as_primitive::<Decimal128Type>(),
+            &Decimal128Array::from(vec![Some(100), Some(0), None])
+                .with_precision_and_scale(10, 3)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_floating_to_string() -> Result<()> {
+        let array: ArrayRef = Arc::new(Float64Array::from(vec![
+            1.0,
+            -0.0,
+            123456.789,
+            1e7,
+            0.001,
+            0.0001,
+            -1.5e-10,
+            f64::NAN,
+            f64::NEG_INFINITY,
+        ]));
+        let casted = spark_cast(&array, &DataType::Utf8, CastMode::Legacy)?;
+        assert_eq!(
+            casted.as_string::<i32>(),
+            &StringArray::from(vec![
+                "1.0",
+                "-0.0",
+                "123456.789",
+                "1.0E7",
+                "0.001",
+                "1.0E-4",
+                "-1.5E-10",
+                "NaN",
+                "-Infinity",
+            ])
+        );
+
+        let array: ArrayRef = Arc::new(Float32Array::from(vec![1.1f32, 3.4028235e38]));
+        let casted = spark_cast(&array, &DataType::Utf8, CastMode::Legacy)?;
+        assert_eq!(
+            casted.as_string::<i32>(),
+            &StringArray::from(vec!["1.1", "3.4028235E38"])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_to_date() -> Result<()> {
+        let values = [
+            Some("2020-01-01"),
+            Some(" 2020-1-2 "),
+            Some("2020"),
+            Some("2020-03"),
+            Some("2020-01-01T12:34:56"),
+            Some("2020-01-01 anything"),
+            Some("2020-13-01"),
+            Some("2020-01-01abc"),
+            Some("20-01-01"),
+        ];
+        let casted = cast_strings(&values, DataType::Date32, CastMode::Legacy)?;
+        assert_eq!(
+            casted.as_primitive::<Date32Type>(),
+            &Date32Array::from(vec![
+                Some(18262),
+                Some(18263),
+                Some(18262),
+                Some(18322),
+                Some(18262),
+                Some(18262),
+                None,
+                None,
+                None,
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_to_timestamp() -> Result<()> {
+        let values = [
+            Some("2020-01-01 12:34:56.789"),
+            Some("2020-01-01T12:34:56Z"),
+            Some("2020-01-01 12:34:56+08:00"),
+            Some("2020-01-01 12:34:56 UTC+8"),
+            Some("2020-01-01 12:34:56 Asia/Shanghai"),
+            Some("2020-01-01 12:34"),
+            Some("2020-01-01"),
+            Some("2020-01-01 25:00:00"),
+            Some("2020-01-01 12:34:56 Mars/Olympus"),
+        ];
+        let casted = cast_strings(
+            &values,
+            DataType::Timestamp(TimeUnit::Microsecond, None),
+            CastMode::Legacy,
+        )?;
+        assert_eq!(
+            casted.as_primitive::<TimestampMicrosecondType>(),
+            &TimestampMicrosecondArray::from(vec![
+                Some(1577882096789000),
+                Some(1577882096000000),
+                Some(1577853296000000),
+                Some(1577853296000000),
+                Some(1577853296000000),
+                Some(1577882040000000),
+                Some(1577836800000000),
+                None,
+                None,
+            ])
+        );
+
+        let casted = spark_cast(&casted, &DataType::Utf8, CastMode::Legacy)?;
+        assert_eq!(
+            casted.as_string::<i32>(),
+            &StringArray::from(vec![
+                Some("2020-01-01 12:34:56.789"),
+                Some("2020-01-01 12:34:56"),
+                Some("2020-01-01 04:34:56"),
+                Some("2020-01-01 04:34:56"),
+                Some("2020-01-01 04:34:56"),
+                Some("2020-01-01 12:34:00"),
+                Some("2020-01-01 00:00:00"),
+                None,
+                None,
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_timestamp_with_time_zone() -> Result<()> {
+        let tz = Tz::from_str("Asia/Shanghai")?;
+        let timestamp_type = DataType::Timestamp(TimeUnit::Microsecond, None);
+        let array: ArrayRef = Arc::new(StringArray::from(vec![
+            Some("2020-01-01 12:34:56"),
+            Some("2020-01-01T12:34:56Z"),
+        ]));
+        let casted = spark_cast_with_time_zone(&array, &timestamp_type, CastMode::Legacy, &tz)?;
+        assert_eq!(
+            casted.as_primitive::<TimestampMicrosecondType>(),
+            &TimestampMicrosecondArray::from(vec![Some(1577853296000000), Some(1577882096000000)])
+        );
+
+        let formatted = spark_cast_with_time_zone(&casted, &DataType::Utf8, CastMode::Legacy, &tz)?;
+        assert_eq!(
+            formatted.as_string::<i32>(),
+            &StringArray::from(vec![
+                Some("2020-01-01 12:34:56"),
+                Some("2020-01-01 20:34:56")
+            ])
+        );
+
+        let dates = spark_cast_with_time_zone(&casted, &DataType::Date32, CastMode::Legacy, &tz)?;
+        assert_eq!(
+            dates.as_primitive::<Date32Type>(),
+            &Date32Array::from(vec![Some(18262), Some(18262)])
+        );
+        let casted = spark_cast_with_time_zone(&dates, &timestamp_type, CastMode::Legacy, &tz)?;
+        assert_eq!(
+            casted.as_primitive::<TimestampMicrosecondType>(),
+            &TimestampMicrosecondArray::from(vec![Some(1577808000000000), Some(1577808000000000)])
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,176 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Utf8View/BinaryView support.
+//!
+//! view arrays reference their values in shared data buffers, so they are
+//! coalesced and interleaved by copying 16-byte views instead of the values.
+//! the JVM side only understands plain string/binary arrays, so view arrays
+//! are converted back to plain arrays at the JNI boundary, and written as plain
+//! arrays in ipc streams.
+
+use std::sync::Arc;
+
+use arrow::{
+    array::{ArrayRef, RecordBatch, RecordBatchOptions},
+    datatypes::{DataType, Field, Fields, Schema, SchemaRef},
+};
+use datafusion::common::Result;
+
+/// values not longer than this are inlined in views
+const MAX_INLINE_VIEW_LEN: u32 = 12;
+
+pub fn is_view_type(data_type: &DataType) -> bool {
+    matches!(data_type, DataType::Utf8View | DataType::BinaryView)
+}
+
+/// returns a view pointing to the same value after data buffers are
+/// appended to another buffer list at `buffer_offset`.
+#[inline]
+pub fn offset_view_buffer_index(view: u128, buffer_offset: u32) -> u128 {
+    if view as u32 <= MAX_INLINE_VIEW_LEN {
+        return view; // inlined value
+    }
+    let buffer_index = ((view >> 64) as u32).wrapping_add(buffer_offset);
+    (view & !((u32::MAX as u128) << 64)) | ((buffer_index as u128) << 64)
+}
+
+/// converts view types (including nested ones) to the corresponding plain
+/// types.
+pub fn plain_data_type(data_type: &DataType) -> DataType {
+    match data_type {
+        DataType::Utf8View => DataType::Utf8,
+        DataType::BinaryView => DataType::Binary,
+        DataType::List(field) => DataType::List(plain_field(field)),
+        DataType::Struct(fields) => DataType::Struct(plain_fields(fields)),
+        DataType::Map(field, sorted) => DataType::Map(plain_field(field), *sorted),
+        other => other.clone(),
+    }
+}
+
+pub fn plain_schema(schema: &SchemaRef) -> SchemaRef {
+    if !schema.fields().iter().any(|f| has_view_type(f.data_type())) {
+        return schema.clone();
+    }
+    Arc::new(Schema::new_with_metadata(
+        plain_fields(schema.fields()),
+        schema.metadata().clone(),
+    ))
+}
+
+/// casts view arrays in the batch to plain arrays, `plain_schema` is created
+/// by [`plain_schema`].
+pub fn cast_batch_to_plain(batch: RecordBatch, plain_schema: &SchemaRef) -> Result<RecordBatch> {
+    if batch.schema().fields() == plain_schema.fields() {
+        return Ok(batch);
+    }
+    let cols = batch
+        .columns()
+        .iter()
+        .zip(plain_schema.fields())
+        .map(|(col, field)| {
+            if col.data_type() == field.data_type() {
+                return Ok(col.clone());
+            }
+            Ok(arrow::compute::cast(col, field.data_type())?)
+        })
+        .collect::<Result<Vec<ArrayRef>>>()?;
+    Ok(RecordBatch::try_new_with_options(
+        plain_schema.clone(),
+        cols,
+        &RecordBatchOptions::new().with_row_count(Some(batch.num_rows())),
+    )?)
+}
+
+fn has_view_type(data_type: &DataType) -> bool {
+    &plain_data_type(data_type) != data_type
+}
+
+fn plain_field(field: &Arc<Field>) -> Arc<Field> {
+    if !has_view_type(field.data_type()) {
+        return field.clone();
+    }
+    Arc::new(
+        field
+            .as_ref()
+            .clone()
+            .with_data_type(plain_data_type(field.data_type())),
+    )
+}
+
+fn plain_fields(fields: &Fields) -> Fields {
+    fields.iter().map(plain_field).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{ArrayRef, AsArray, Int32Array, ListArray, RecordBatch, StringViewArray},
+        datatypes::{DataType, Field, Int32Type},
+    };
+    use datafusion::common::Result;
+
+    use crate::arrow::view::{cast_batch_to_plain, offset_view_buffer_index, plain_schema};
+
+    #[test]
+    fn test_offset_view_buffer_index() {
+        let array = StringViewArray::from(vec!["short", "a value longer than 12 bytes"]);
+        let views = array.views();
+        assert_eq!(offset_view_buffer_index(views[0], 3), views[0]);
+
+        let offsetted = offset_view_buffer_index(views[1], 3);
+        assert_eq!(offsetted as u64, views[1] as u64); // length and prefix
+        assert_eq!((offsetted >> 64) as u32, (views[1] >> 64) as u32 + 3);
+        assert_eq!((offsetted >> 96) as u32, (views[1] >> 96) as u32); // offset
+    }
+
+    #[test]
+    fn test_cast_batch_to_plain() -> Result<()> {
+        let view_array: ArrayRef = Arc::new(StringViewArray::from(vec![
+            Some("a"),
+            None,
+            Some("a value longer than 12 bytes"),
+        ]));
+        let list_array: ArrayRef =
+            Arc::new(ListArray::from_iter_primitive::<Int32Type, _, _>(vec![
+                Some(vec![Some(1)]),
+                None,
+                Some(vec![]),
+            ]));
+        let int_array: ArrayRef = Arc::new(Int32Array::from(vec![1, 2, 3]));
+        let batch = RecordBatch::try_from_iter(vec![
+            ("v", view_array),
+            ("l", list_array),
+            ("i", int_array),
+        ])?;
+
+        let schema = plain_schema(&batch.schema());
+        assert_eq!(schema.field(0), &Field::new("v", DataType::Utf8, true));
+        assert_eq!(schema.field(1), batch.schema().field(1));
+
+        let plain_batch = cast_batch_to_plain(batch.clone(), &schema)?;
+        let strings = plain_batch.column(0).as_string::<i32>();
+        assert_eq!(
+            strings.iter().collect::<Vec<_>>(),
+            vec![Some("a"), None, Some("a value longer than 12 bytes")],
+        );
+        assert_eq!(plain_batch.column(1), batch.column(1));
+
+        // schemas without views are not changed
+        assert!(Arc::ptr_eq(&plain_schema(&schema), &schema));
+        Ok(())
+    }
+}
@@ -0,0 +1,216 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Structured errors of native execution.
+//!
+//! errors are raised as [`BlazeError`] carried by `DataFusionError::External`,
+//! see [`df_blaze_err`](crate::df_blaze_err). errors are formatted as
+//! `[ERROR_CODE] message`, since they are passed across threads as strings
+//! (like panics in output_with_sender). when the task fails, the error code is
+//! found in the final error message with [`find_error_code`] and the
+//! corresponding spark exception is thrown in the JVM.
+
+use std::{
+    error::Error,
+    fmt::{Debug, Display, Formatter},
+};
+
+use arrow::error::ArrowError;
+use datafusion::common::DataFusionError;
+
+use crate::spark_error::find_spark_error;
+
+pub const MEMORY_EXCEEDED: &str = "MEMORY_EXCEEDED";
+pub const SPILL_IO_FAILURE: &str = "SPILL_IO_FAILURE";
+pub const UNSUPPORTED_EXPRESSION: &str = "UNSUPPORTED_EXPRESSION";
+pub const DATA_CORRUPTION: &str = "DATA_CORRUPTION";
+
+const BLAZE_ERROR_CODES: &[&str] = &[
+    MEMORY_EXCEEDED,
+    SPILL_IO_FAILURE,
+    UNSUPPORTED_EXPRESSION,
+    DATA_CORRUPTION,
+];
+
+#[derive(Clone, PartialEq, Eq)]
+pub enum BlazeError {
+    /// memory used by the task exceeds its limit and cannot be spilled
+    MemoryExceeded(String),
+    /// spills cannot be written or read
+    SpillIoFailure(String),
+    /// expressions or functions not supported by native engine
+    UnsupportedExpression(String),
+    /// shuffle/spill/input data cannot be decoded
+    DataCorruption(String),
+    /// spark visible errors like ansi arithmetic errors, see
+    /// [`spark_error`](crate::spark_error)
+    SparkError {
+        error_class: &'static str,
+        message: String,
+    },
+}
+
+impl BlazeError {
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            BlazeError::MemoryExceeded(_) => MEMORY_EXCEEDED,
+            BlazeError::SpillIoFailure(_) => SPILL_IO_FAILURE,
+            BlazeError::UnsupportedExpression(_) => UNSUPPORTED_EXPRESSION,
+            BlazeError::DataCorruption(_) => DATA_CORRUPTION,
+            BlazeError::SparkError { error_class, .. } => error_class,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            BlazeError::MemoryExceeded(message)
+            | BlazeError::SpillIoFailure(message)
+            | BlazeError::UnsupportedExpression(message)
+            | BlazeError::DataCorruption(message)
+            | BlazeError::SparkError { message, .. } => message,
+        }
+    }
+
+    /// finds the blaze error carried by a datafusion error
+    pub fn find_in(err: &DataFusionError) -> Option<&BlazeError> {
+        match err {
+            DataFusionError::External(err) => find_in_std_error(err.as_ref()),
+            DataFusionError::ArrowError(ArrowError::ExternalError(err), _) => {
+                find_in_std_error(err.as_ref())
+            }
+            DataFusionError::IoError(err) => find_in_std_error(err),
+            DataFusionError::Context(_, err) => BlazeError::find_in(err),
+            DataFusionError::Shared(err) => BlazeError::find_in(err),
+            _ => None,
+        }
+    }
+}
+
+fn find_in_std_error<'a>(err: &'a (dyn Error + 'static)) -> Option<&'a BlazeError> {
+    let mut cur = Some(err);
+    while let Some(err) = cur {
+        if let Some(blaze_error) = err.downcast_ref::<BlazeError>() {
+            return Some(blaze_error);
+        }
+        if let Some(df_err) = err.downcast_ref::<DataFusionError>() {
+            return BlazeError::find_in(df_err);
+        }
+        // source() of io errors skips the wrapped error
+        if let Some(io_err) = err.downcast_ref::<std::io::Error>() {
+            cur = io_err
+                .get_ref()
+                .map(|inner| inner as &(dyn Error + 'static));
+            continue;
+        }
+        cur = err.source();
+    }
+    None
+}
+
+impl Display for BlazeError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "[{}] {}", self.error_code(), self.message())
+    }
+}
+
+// errors are often formatted with {:?} when wrapped, the error code must be
+// kept in the message
+impl Debug for BlazeError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl Error for BlazeError {}
+
+impl From<BlazeError> for DataFusionError {
+    fn from(err: BlazeError) -> Self {
+        DataFusionError::External(Box::new(err))
+    }
+}
+
+/// finds the error code in an error message, including spark error classes,
+/// returns the error code and the message following it.
+pub fn find_error_code(message: &str) -> Option<(&'static str, &str)> {
+    let blaze_error = BLAZE_ERROR_CODES
+        .iter()
+        .filter_map(|&error_code| {
+            let prefix = format!("[{error_code}] ");
+            message
+                .rfind(&prefix)
+                .map(|pos| (pos, error_code, &message[pos + prefix.len()..]))
+        })
+        .max_by_key(|&(pos, ..)| pos);
+
+    // the innermost (last) error is the one originally raised
+    let spark_error = find_spark_error(message).map(|(error_class, error_message)| {
+        let pos = message.len() - error_message.len() - error_class.len() - 3;
+        (pos, error_class, error_message)
+    });
+    blaze_error
+        .into_iter()
+        .chain(spark_error)
+        .max_by_key(|&(pos, ..)| pos)
+        .map(|(_, error_code, error_message)| (error_code, error_message))
+}
+
+#[cfg(test)]
+mod test {
+    use datafusion::common::{DataFusionError, Result};
+
+    use crate::{
+        blaze_error::{find_error_code, BlazeError, DATA_CORRUPTION, SPILL_IO_FAILURE},
+        df_blaze_err, df_spark_err,
+        spark_error::DIVIDE_BY_ZERO,
+    };
+
+    #[test]
+    fn test_blaze_error() {
+        let err: Result<()> = df_blaze_err!(SpillIoFailure, "disk quota exceeded");
+        let err = err.unwrap_err().context("writing spill");
+        assert_eq!(
+            BlazeError::find_in(&err),
+            Some(&BlazeError::SpillIoFailure(
+                "disk quota exceeded".to_string()
+            ))
+        );
+        let message = format!("task panics: {err}");
+        assert_eq!(
+            find_error_code(&message),
+            Some((SPILL_IO_FAILURE, "disk quota exceeded"))
+        );
+
+        // carried by io errors
+        let err = DataFusionError::IoError(std::io::Error::other(BlazeError::DataCorruption(
+            "invalid block".to_string(),
+        )));
+        assert_eq!(
+            BlazeError::find_in(&err).unwrap().error_code(),
+            DATA_CORRUPTION
+        );
+
+        // spark errors are blaze errors
+        let err: Result<()> = df_spark_err!(DIVIDE_BY_ZERO, "division by zero");
+        let err = err.unwrap_err();
+        assert_eq!(
+            BlazeError::find_in(&err).unwrap().error_code(),
+            DIVIDE_BY_ZERO
+        );
+        assert_eq!(
+            find_error_code(&format!("[DATA_CORRUPTION] outer: {err}")),
+            Some((DIVIDE_BY_ZERO, "division by zero"))
+        );
+        assert_eq!(find_error_code("execution error"), None);
+    }
+}
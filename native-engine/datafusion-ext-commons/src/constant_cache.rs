@@ -0,0 +1,134 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Executor-level registry of constant state derived from expressions (parsed
+//! json paths, bloom filters, etc.). tasks of the same stage running in one
+//! executor share the state instead of re-initializing it in every task.
+//!
+//! entries are keyed by the state type and an expression fingerprint, which
+//! must uniquely identify the state within the type.
+
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    sync::{Arc, Mutex, Weak},
+};
+
+use datafusion::common::Result;
+use once_cell::sync::OnceCell;
+
+type Key = (TypeId, String);
+type AnyState = dyn Any + Send + Sync;
+type WeakSlot = Arc<Mutex<Option<Weak<AnyState>>>>;
+
+/// max number of retained entries, the registry is cleared when exceeded
+const MAX_RETAINED_ENTRIES: usize = 4096;
+
+static RETAINED: OnceCell<Mutex<HashMap<Key, Arc<AnyState>>>> = OnceCell::new();
+static SHARED: OnceCell<Mutex<HashMap<Key, WeakSlot>>> = OnceCell::new();
+
+/// Gets the cached state for the fingerprint, or initializes and caches it.
+/// the state is retained for the whole executor lifetime, so it should be
+/// used for small state like parsed paths and patterns.
+pub fn get_or_init_retained<T: Any + Send + Sync>(
+    fingerprint: &str,
+    init: impl FnOnce() -> Result<T>,
+) -> Result<Arc<T>> {
+    let key = (TypeId::of::<T>(), fingerprint.to_string());
+    let retained = RETAINED.get_or_init(Mutex::default);
+    if let Some(cached) = retained.lock().unwrap().get(&key) {
+        return Ok(downcast_state(cached.clone()));
+    }
+
+    // initialize without holding the lock, concurrent initializations of the
+    // same key are harmless and the first inserted one wins
+    let new: Arc<AnyState> = Arc::new(init()?);
+    let mut retained = retained.lock().unwrap();
+    if retained.len() >= MAX_RETAINED_ENTRIES {
+        retained.clear();
+    }
+    Ok(downcast_state(retained.entry(key).or_insert(new).clone()))
+}
+
+/// Gets the cached state for the fingerprint, or initializes and caches it.
+/// the state is only shared among the tasks holding it and released after
+/// all of them are finished, so it can be used for large state like bloom
+/// filters. concurrent initializations of the same key are serialized.
+pub fn get_or_init_shared<T: Any + Send + Sync>(
+    fingerprint: &str,
+    init: impl FnOnce() -> Result<T>,
+) -> Result<Arc<T>> {
+    let key = (TypeId::of::<T>(), fingerprint.to_string());
+
+    // remove expired keys and insert new key
+    let slot = {
+        let mut shared = SHARED.get_or_init(Mutex::default).lock().unwrap();
+        shared.retain(|_, slot| {
+            Arc::strong_count(slot) > 1
+                || slot
+                    .lock()
+                    .unwrap()
+                    .as_ref()
+                    .is_some_and(|weak| weak.strong_count() > 0)
+        });
+        shared.entry(key).or_default().clone()
+    };
+
+    let mut slot = slot.lock().unwrap();
+    if let Some(cached) = slot.as_ref().and_then(|weak| weak.upgrade()) {
+        return Ok(downcast_state(cached));
+    }
+    let new = Arc::new(init()?);
+    let new_any: Arc<AnyState> = new.clone();
+    *slot = Some(Arc::downgrade(&new_any));
+    Ok(new)
+}
+
+fn downcast_state<T: Any + Send + Sync>(state: Arc<AnyState>) -> Arc<T> {
+    // keys contain the type id, so the downcasting never fails
+    state
+        .downcast::<T>()
+        .unwrap_or_else(|_| unreachable!("constant state type mismatched"))
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use datafusion::common::Result;
+
+    use crate::constant_cache::{get_or_init_retained, get_or_init_shared};
+
+    #[test]
+    fn test_constant_cache() -> Result<()> {
+        // retained state is initialized once
+        let s1 = get_or_init_retained("test-retained", || Ok("s1".to_string()))?;
+        let s2 = get_or_init_retained("test-retained", || Ok("s2".to_string()))?;
+        assert_eq!(s1.as_str(), "s1");
+        assert!(Arc::ptr_eq(&s1, &s2));
+
+        // same fingerprint with different types are different entries
+        let i1 = get_or_init_retained("test-retained", || Ok(1i32))?;
+        assert_eq!(*i1, 1);
+
+        // shared state is reused only while alive
+        let s1 = get_or_init_shared("test-shared", || Ok("s1".to_string()))?;
+        let s2 = get_or_init_shared("test-shared", || Ok("s2".to_string()))?;
+        assert!(Arc::ptr_eq(&s1, &s2));
+        drop((s1, s2));
+        let s3 = get_or_init_shared("test-shared", || Ok("s3".to_string()))?;
+        assert_eq!(s3.as_str(), "s3");
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    os::unix::fs::FileExt,
+    path::Path,
+    sync::Arc,
+};
+
+use datafusion::common::Result;
+
+use crate::fs::{strip_local_scheme, FileReader, FileSystem, FileWriter};
+
+pub struct LocalFileSystem;
+
+impl FileSystem for LocalFileSystem {
+    fn open(&self, path: &str) -> Result<Arc<dyn FileReader>> {
+        let file = File::open(strip_local_scheme(path))?;
+        Ok(Arc::new(LocalFileReader(file)))
+    }
+
+    fn create(&self, path: &str) -> Result<Box<dyn FileWriter>> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(strip_local_scheme(path))?;
+        Ok(Box::new(LocalFileWriter { file, pos: 0 }))
+    }
+
+    fn mkdirs(&self, path: &str) -> Result<()> {
+        std::fs::create_dir_all(strip_local_scheme(path))?;
+        Ok(())
+    }
+
+    fn remove(&self, path: &str) -> Result<()> {
+        std::fs::remove_file(strip_local_scheme(path))?;
+        Ok(())
+    }
+
+    fn exists(&self, path: &str) -> Result<bool> {
+        Ok(Path::new(strip_local_scheme(path)).try_exists()?)
+    }
+}
+
+struct LocalFileReader(File);
+
+impl FileReader for LocalFileReader {
+    fn read_fully(&self, pos: u64, buf: &mut [u8]) -> Result<()> {
+        self.0.read_exact_at(buf, pos)?;
+        Ok(())
+    }
+}
+
+struct LocalFileWriter {
+    file: File,
+    pos: u64,
+}
+
+impl Write for LocalFileWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.file.write(buf)?;
+        self.pos += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl FileWriter for LocalFileWriter {
+    fn position(&self) -> u64 {
+        self.pos
+    }
+
+    fn close(mut self: Box<Self>) -> Result<()> {
+        self.file.flush()?;
+        Ok(())
+    }
+}
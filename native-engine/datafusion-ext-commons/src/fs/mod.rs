@@ -0,0 +1,290 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Filesystem abstraction for native file IO (shuffle output, spills and
+//! scans). filesystems are registered by url scheme, paths without a scheme
+//! are treated as local paths.
+//!
+//! the local filesystem is always registered. other storages listed in
+//! `spark.blaze.fs.nativeUrls` are served natively by object stores
+//! (hdfs-native for HDFS with the "hdfs" feature, and S3 with the "s3"
+//! feature), otherwise scans fall back to the hadoop filesystem provided by
+//! jvm.
+
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    sync::{Arc, RwLock},
+};
+
+use blaze_jni_bridge::{
+    conf::{StringConf, FS_NATIVE_URLS},
+    is_jni_bridge_inited,
+};
+use datafusion::common::Result;
+use object_store::ObjectStore;
+use once_cell::sync::OnceCell;
+
+use crate::{
+    df_execution_err,
+    fs::{local_fs::LocalFileSystem, object_store_fs::ObjectStoreFileSystem},
+    hadoop_fs::FsProvider,
+};
+
+pub mod local_fs;
+pub mod object_store_fs;
+
+pub trait FileSystem: Send + Sync {
+    /// opens a file for positional reading
+    fn open(&self, path: &str) -> Result<Arc<dyn FileReader>>;
+
+    /// creates a file for writing, existing file is truncated
+    fn create(&self, path: &str) -> Result<Box<dyn FileWriter>>;
+
+    fn mkdirs(&self, path: &str) -> Result<()>;
+
+    fn remove(&self, path: &str) -> Result<()>;
+
+    fn exists(&self, path: &str) -> Result<bool>;
+}
+
+pub trait FileReader: Send + Sync {
+    fn read_fully(&self, pos: u64, buf: &mut [u8]) -> Result<()>;
+}
+
+pub trait FileWriter: Write + Send + Sync {
+    /// returns number of bytes written
+    fn position(&self) -> u64;
+
+    /// flushes and closes the file, written data may not be visible before
+    /// closing on some storages
+    fn close(self: Box<Self>) -> Result<()>;
+}
+
+/// Sequential reader on a [`FileReader`] starting from position 0
+pub struct FileReaderStream {
+    reader: Arc<dyn FileReader>,
+    pos: u64,
+    len: u64,
+}
+
+impl FileReaderStream {
+    pub fn new(reader: Arc<dyn FileReader>, len: u64) -> Self {
+        Self {
+            reader,
+            pos: 0,
+            len,
+        }
+    }
+}
+
+impl Read for FileReaderStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read_len = buf.len().min((self.len - self.pos) as usize);
+        self.reader
+            .read_fully(self.pos, &mut buf[..read_len])
+            .map_err(std::io::Error::other)?;
+        self.pos += read_len as u64;
+        Ok(read_len)
+    }
+}
+
+type FsRegistry = RwLock<HashMap<String, Arc<dyn FileSystem>>>;
+
+fn fs_registry() -> &'static FsRegistry {
+    static FS_REGISTRY: OnceCell<FsRegistry> = OnceCell::new();
+    FS_REGISTRY.get_or_init(|| {
+        let local_fs: Arc<dyn FileSystem> = Arc::new(LocalFileSystem);
+        RwLock::new(HashMap::from([("file".to_string(), local_fs)]))
+    })
+}
+
+/// registers a filesystem for all paths of the url scheme (like `file`), or
+/// of a storage (like `s3://bucket`), replacing the existing one
+pub fn register_fs(scheme_or_url: &str, fs: Arc<dyn FileSystem>) {
+    let key = match path_scheme(scheme_or_url) {
+        Some(scheme) => registry_key(scheme, path_authority(scheme_or_url)),
+        None => scheme_or_url.to_lowercase(),
+    };
+    log::info!("registering native filesystem for: {key}");
+    fs_registry().write().unwrap().insert(key, fs);
+}
+
+/// registers an object store for the url scheme or storage, paths are mapped
+/// to object locations without scheme and authority
+pub fn register_object_store(scheme_or_url: &str, store: Arc<dyn ObjectStore>) {
+    register_fs(scheme_or_url, Arc::new(ObjectStoreFileSystem::new(store)));
+}
+
+/// creates and registers an object store serving the storage of the url, like
+/// `hdfs://nameservice` or `s3://bucket`
+pub fn register_fs_for_url(url: &str) -> Result<Arc<dyn FileSystem>> {
+    let store: Arc<dyn ObjectStore> = match path_scheme(url).map(|s| s.to_lowercase()).as_deref() {
+        #[cfg(feature = "hdfs")]
+        Some("hdfs" | "viewfs") => Arc::new(
+            hdfs_native_object_store::HdfsObjectStore::with_url(url)
+                .map_err(|err| datafusion::common::DataFusionError::External(Box::new(err)))?,
+        ),
+        #[cfg(feature = "s3")]
+        Some("s3" | "s3a") => Arc::new(
+            object_store::aws::AmazonS3Builder::from_env()
+                .with_url(url)
+                .build()?,
+        ),
+        _ => return df_execution_err!("no native filesystem available for: {url}"),
+    };
+    let fs: Arc<dyn FileSystem> = Arc::new(ObjectStoreFileSystem::new(store));
+    register_fs(url, fs.clone());
+    Ok(fs)
+}
+
+/// registers native filesystems of storages listed in
+/// `spark.blaze.fs.nativeUrls`, called when initializing native environment
+pub fn register_fs_from_conf() -> Result<()> {
+    if !is_jni_bridge_inited() {
+        return Ok(());
+    }
+    let urls = FS_NATIVE_URLS.value()?;
+    for url in urls.split(',').map(str::trim).filter(|url| !url.is_empty()) {
+        register_fs_for_url(url)?;
+    }
+    Ok(())
+}
+
+/// returns the registered filesystem of the path, paths without a scheme are
+/// served by the local filesystem. filesystems registered for the storage of
+/// the path are preferred to those registered for the scheme.
+pub fn get_fs(path: &str) -> Option<Arc<dyn FileSystem>> {
+    let scheme = path_scheme(path).unwrap_or("file");
+    let registry = fs_registry().read().unwrap();
+    registry
+        .get(&registry_key(scheme, path_authority(path)))
+        .or_else(|| registry.get(&scheme.to_lowercase()))
+        .cloned()
+}
+
+/// returns the local filesystem, used for shuffle output and spills
+pub fn local_fs() -> Arc<dyn FileSystem> {
+    get_fs("file:///").expect("local filesystem not registered")
+}
+
+/// returns the registered filesystem for a fully qualified path, or the
+/// hadoop filesystem provided by jvm. paths without a scheme are resolved by
+/// hadoop, since they are relative to the default filesystem.
+pub fn resolve_fs(path: &str, fs_provider: &FsProvider) -> Result<Arc<dyn FileSystem>> {
+    if path_scheme(path).is_some() {
+        if let Some(fs) = get_fs(path) {
+            return Ok(fs);
+        }
+    }
+    Ok(Arc::new(fs_provider.provide(path)?))
+}
+
+fn path_scheme(path: &str) -> Option<&str> {
+    let (scheme, _) = path.split_once("://")?;
+    let is_valid_scheme = !scheme.is_empty()
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    is_valid_scheme.then_some(scheme)
+}
+
+/// returns the authority of a path like `scheme://authority/a/b`
+fn path_authority(path: &str) -> Option<&str> {
+    let (_, rest) = path.split_once("://")?;
+    let authority = rest.split('/').next().unwrap_or_default();
+    (!authority.is_empty()).then_some(authority)
+}
+
+fn registry_key(scheme: &str, authority: Option<&str>) -> String {
+    match authority {
+        Some(authority) => format!("{}://{authority}", scheme.to_lowercase()),
+        None => scheme.to_lowercase(),
+    }
+}
+
+/// removes scheme from local paths like `file:///a/b`
+pub fn strip_local_scheme(path: &str) -> &str {
+    path.strip_prefix("file://").unwrap_or(path)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{Read, Write},
+        sync::Arc,
+    };
+
+    use datafusion::common::Result;
+    use object_store::memory::InMemory;
+
+    use crate::fs::{
+        get_fs, local_fs, path_authority, path_scheme, register_fs_for_url, register_object_store,
+        FileReaderStream,
+    };
+
+    #[test]
+    fn test_path_scheme() {
+        assert_eq!(path_scheme("hdfs://nn:8020/a/b"), Some("hdfs"));
+        assert_eq!(path_scheme("file:///tmp/a"), Some("file"));
+        assert_eq!(path_scheme("/tmp/a"), None);
+        assert_eq!(path_scheme("/tmp/a://b"), None);
+        assert!(get_fs("/tmp/a").is_some());
+        assert!(get_fs("unknown://a/b").is_none());
+        assert_eq!(path_authority("s3://bucket/a/b"), Some("bucket"));
+        assert_eq!(path_authority("file:///tmp/a"), None);
+        assert_eq!(path_authority("/tmp/a"), None);
+    }
+
+    #[test]
+    fn test_register_fs() {
+        register_object_store("mem", Arc::new(InMemory::new()));
+        register_object_store("mem://bucket1", Arc::new(InMemory::new()));
+        let fs1 = get_fs("mem://bucket1/a").unwrap();
+        let fs2 = get_fs("mem://bucket2/a").unwrap();
+        assert!(!Arc::ptr_eq(&fs1, &fs2));
+        assert!(Arc::ptr_eq(&fs1, &get_fs("MEM://bucket1/b").unwrap()));
+        assert!(Arc::ptr_eq(&fs2, &get_fs("mem://bucket3/a").unwrap()));
+        assert!(register_fs_for_url("unknown://bucket").is_err());
+    }
+
+    #[test]
+    fn test_local_fs() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = format!("{}/sub/data", dir.path().to_string_lossy());
+        let fs = local_fs();
+        fs.mkdirs(&format!("{}/sub", dir.path().to_string_lossy()))?;
+
+        let mut writer = fs.create(&path)?;
+        writer.write_all(b"hello ")?;
+        writer.write_all(b"world")?;
+        assert_eq!(writer.position(), 11);
+        writer.close()?;
+
+        let reader = fs.open(&format!("file://{path}"))?;
+        let mut buf = [0u8; 5];
+        reader.read_fully(6, &mut buf)?;
+        assert_eq!(&buf, b"world");
+
+        let mut read = String::new();
+        FileReaderStream::new(reader, 11).read_to_string(&mut read)?;
+        assert_eq!(read, "hello world");
+
+        assert!(fs.exists(&path)?);
+        fs.remove(&path)?;
+        assert!(!fs.exists(&path)?);
+        assert!(fs.open(&path).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,223 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    future::Future,
+    io::Write,
+    sync::{Arc, Mutex},
+};
+
+use datafusion::common::{DataFusionError, Result};
+use object_store::{path::Path, ObjectStore, WriteMultipart};
+use tokio::runtime::{Handle, RuntimeFlavor};
+
+use crate::{
+    df_execution_err,
+    fs::{FileReader, FileSystem, FileWriter},
+};
+
+/// max number of parts being uploaded concurrently by a writer
+const MAX_CONCURRENT_UPLOAD_PARTS: usize = 8;
+
+/// [`FileSystem`] on an object store. object stores are async, the calls are
+/// blocked on the current tokio runtime, which must be a multi-thread runtime.
+pub struct ObjectStoreFileSystem {
+    store: Arc<dyn ObjectStore>,
+}
+
+impl ObjectStoreFileSystem {
+    pub fn new(store: Arc<dyn ObjectStore>) -> Self {
+        Self { store }
+    }
+}
+
+impl FileSystem for ObjectStoreFileSystem {
+    fn open(&self, path: &str) -> Result<Arc<dyn FileReader>> {
+        Ok(Arc::new(ObjectStoreFileReader {
+            store: self.store.clone(),
+            location: object_location(path)?,
+        }))
+    }
+
+    fn create(&self, path: &str) -> Result<Box<dyn FileWriter>> {
+        let location = object_location(path)?;
+        let upload = block_on(self.store.put_multipart(&location))?;
+        Ok(Box::new(ObjectStoreFileWriter {
+            upload: Mutex::new(WriteMultipart::new(upload)),
+            position: 0,
+        }))
+    }
+
+    fn mkdirs(&self, _path: &str) -> Result<()> {
+        Ok(()) // object stores have no directories
+    }
+
+    fn remove(&self, path: &str) -> Result<()> {
+        let location = object_location(path)?;
+        block_on(self.store.delete(&location))?;
+        Ok(())
+    }
+
+    fn exists(&self, path: &str) -> Result<bool> {
+        let location = object_location(path)?;
+        block_on(async {
+            match self.store.head(&location).await {
+                Ok(_) => Ok(true),
+                Err(object_store::Error::NotFound { .. }) => Ok(false),
+                Err(err) => Err(err),
+            }
+        })
+    }
+}
+
+struct ObjectStoreFileReader {
+    store: Arc<dyn ObjectStore>,
+    location: Path,
+}
+
+impl FileReader for ObjectStoreFileReader {
+    fn read_fully(&self, pos: u64, buf: &mut [u8]) -> Result<()> {
+        let range = pos as usize..pos as usize + buf.len();
+        let bytes = block_on(self.store.get_range(&self.location, range))?;
+        if bytes.len() != buf.len() {
+            return df_execution_err!(
+                "unexpected end of object {}: expect {} bytes, got {}",
+                self.location,
+                buf.len(),
+                bytes.len()
+            );
+        }
+        buf.copy_from_slice(&bytes);
+        Ok(())
+    }
+}
+
+/// written data is uploaded as parts of a multipart upload in background,
+/// the object is completed and becomes visible on closing.
+struct ObjectStoreFileWriter {
+    upload: Mutex<WriteMultipart>, // only for Sync, never locked
+    position: u64,
+}
+
+impl Write for ObjectStoreFileWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let upload = self.upload.get_mut().unwrap();
+        block_on(upload.wait_for_capacity(MAX_CONCURRENT_UPLOAD_PARTS))
+            .map_err(std::io::Error::other)?;
+        upload.write(buf);
+        self.position += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl FileWriter for ObjectStoreFileWriter {
+    fn position(&self) -> u64 {
+        self.position
+    }
+
+    fn close(self: Box<Self>) -> Result<()> {
+        block_on(self.upload.into_inner().unwrap().finish())?;
+        Ok(())
+    }
+}
+
+/// maps `scheme://authority/a/b` to object location `a/b`
+fn object_location(path: &str) -> Result<Path> {
+    let Some((_scheme, rest)) = path.split_once("://") else {
+        return Path::parse(path).map_err(|err| DataFusionError::External(Box::new(err)));
+    };
+    let location = rest.split_once('/').map(|(_, location)| location);
+    Path::parse(location.unwrap_or_default())
+        .map_err(|err| DataFusionError::External(Box::new(err)))
+}
+
+fn block_on<T>(fut: impl Future<Output = object_store::Result<T>>) -> Result<T> {
+    // block_in_place() panics outside of a multi-thread runtime
+    let handle = match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => handle,
+        Ok(_) => return df_execution_err!("object store io requires a multi-thread runtime"),
+        Err(err) => return df_execution_err!("object store io requires a tokio runtime: {err}"),
+    };
+    tokio::task::block_in_place(|| handle.block_on(fut))
+        .map_err(|err| DataFusionError::External(Box::new(err)))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{Read, Write},
+        sync::Arc,
+    };
+
+    use object_store::memory::InMemory;
+
+    use crate::fs::{
+        object_store_fs::{object_location, ObjectStoreFileSystem},
+        FileReaderStream, FileSystem,
+    };
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_object_store_fs() -> datafusion::common::Result<()> {
+        let fs = ObjectStoreFileSystem::new(Arc::new(InMemory::new()));
+        let path = "memory://bucket/spills/data";
+        let data = (0..20_000_000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+
+        // written in multiple parts
+        let mut writer = fs.create(path)?;
+        for chunk in data.chunks(1000000) {
+            writer.write_all(chunk)?;
+        }
+        assert_eq!(writer.position(), data.len() as u64);
+        assert!(fs.open(path)?.read_fully(0, &mut [0u8; 1]).is_err());
+        writer.close()?;
+
+        let mut read = vec![];
+        FileReaderStream::new(fs.open(path)?, data.len() as u64).read_to_end(&mut read)?;
+        assert!(read == data);
+
+        assert!(fs.exists(path)?);
+        fs.remove(path)?;
+        assert!(!fs.exists(path)?);
+        assert!(fs.open(path)?.read_fully(0, &mut [0u8; 1]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_object_store_fs_without_runtime() {
+        let fs = ObjectStoreFileSystem::new(Arc::new(InMemory::new()));
+        assert!(fs.exists("memory://bucket/data").is_err());
+        assert!(fs.create("memory://bucket/data").is_err());
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_object_store_fs_on_current_thread_runtime() {
+        let fs = ObjectStoreFileSystem::new(Arc::new(InMemory::new()));
+        assert!(fs.exists("memory://bucket/data").is_err());
+    }
+
+    #[test]
+    fn test_object_location() -> datafusion::common::Result<()> {
+        assert_eq!(
+            object_location("s3://bucket/a/b.parquet")?.as_ref(),
+            "a/b.parquet"
+        );
+        assert_eq!(object_location("hdfs://nn:8020/a/b")?.as_ref(), "a/b");
+        assert_eq!(object_location("a/b")?.as_ref(), "a/b");
+        Ok(())
+    }
+}
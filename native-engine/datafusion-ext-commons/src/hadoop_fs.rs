@@ -12,16 +12,22 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::sync::Arc;
+use std::{io::Write, sync::Arc};
 
 use blaze_jni_bridge::{
     jni_call, jni_call_static, jni_new_direct_byte_buffer, jni_new_global_ref, jni_new_object,
     jni_new_string,
 };
 use datafusion::{error::Result, physical_plan::metrics::Time};
-use jni::objects::{GlobalRef, JObject};
+use jni::{
+    objects::{GlobalRef, JObject},
+    sys::JNI_FALSE,
+};
 
-use crate::df_execution_err;
+use crate::{
+    df_execution_err,
+    fs::{FileReader, FileSystem, FileWriter},
+};
 
 #[derive(Clone)]
 pub struct Fs {
@@ -78,6 +84,46 @@ impl Fs {
     }
 }
 
+/// hadoop filesystem provided by jvm
+impl FileSystem for Fs {
+    fn open(&self, path: &str) -> Result<Arc<dyn FileReader>> {
+        Ok(Fs::open(self, path)?)
+    }
+
+    fn create(&self, path: &str) -> Result<Box<dyn FileWriter>> {
+        Ok(Box::new(HadoopFileWriter {
+            inner: Fs::create(self, path)?,
+            pos: 0,
+        }))
+    }
+
+    fn mkdirs(&self, path: &str) -> Result<()> {
+        Fs::mkdirs(self, path)
+    }
+
+    fn remove(&self, path: &str) -> Result<()> {
+        let _timer = self.io_time.timer();
+        let path_str = jni_new_string!(path)?;
+        let path_uri = jni_new_object!(JavaURI(path_str.as_obj()))?;
+        let path = jni_new_object!(HadoopPath(path_uri.as_obj()))?;
+        let succeeded = jni_call!(
+            HadoopFileSystem(self.fs.as_obj()).delete(path.as_obj(), JNI_FALSE) -> bool
+        )?;
+        if !succeeded {
+            df_execution_err!("fs.delete not succeeded")?;
+        }
+        Ok(())
+    }
+
+    fn exists(&self, path: &str) -> Result<bool> {
+        let _timer = self.io_time.timer();
+        let path_str = jni_new_string!(path)?;
+        let path_uri = jni_new_object!(JavaURI(path_str.as_obj()))?;
+        let path = jni_new_object!(HadoopPath(path_uri.as_obj()))?;
+        jni_call!(HadoopFileSystem(self.fs.as_obj()).exists(path.as_obj()) -> bool)
+    }
+}
+
 pub struct FsDataInputWrapper {
     obj: GlobalRef,
     io_time: Time,
@@ -94,6 +140,12 @@ impl FsDataInputWrapper {
     }
 }
 
+impl FileReader for FsDataInputWrapper {
+    fn read_fully(&self, pos: u64, buf: &mut [u8]) -> Result<()> {
+        FsDataInputWrapper::read_fully(self, pos, buf)
+    }
+}
+
 impl Drop for FsDataInputWrapper {
     fn drop(&mut self) {
         let _timer = self.io_time.timer();
@@ -127,6 +179,36 @@ impl Drop for FsDataOutputWrapper {
     }
 }
 
+struct HadoopFileWriter {
+    inner: Arc<FsDataOutputWrapper>,
+    pos: u64,
+}
+
+impl Write for HadoopFileWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.inner.write_fully(buf).map_err(std::io::Error::other)?;
+        self.pos += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl FileWriter for HadoopFileWriter {
+    fn position(&self) -> u64 {
+        self.pos
+    }
+
+    fn close(self: Box<Self>) -> Result<()> {
+        match Arc::into_inner(self.inner) {
+            Some(inner) => inner.close(),
+            None => Ok(()), // closed when the last reference is dropped
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct FsProvider {
     fs_provider: GlobalRef,
@@ -33,6 +33,44 @@ pub fn spark_compatible_murmur3_hash_long(value: i64, seed: i32) -> i32 {
     hash_long(value, seed)
 }
 
+/// number of values hashed in each unrolled chunk of batch hashing
+const BATCH_LANES: usize = 16;
+
+/// computes spark's hashInt() of every value, with the corresponding element
+/// of `hashes` as seed. values are processed in fixed-size chunks so that the
+/// loop is unrolled and vectorized by the compiler.
+pub fn spark_compatible_murmur3_hash_int_batch(values: &[i32], hashes: &mut [i32]) {
+    assert_eq!(values.len(), hashes.len());
+    let mut value_chunks = values.chunks_exact(BATCH_LANES);
+    let mut hash_chunks = hashes.chunks_exact_mut(BATCH_LANES);
+    for (values, hashes) in (&mut value_chunks).zip(&mut hash_chunks) {
+        for (hash, &value) in hashes.iter_mut().zip(values) {
+            *hash = hash_int(value, *hash);
+        }
+    }
+    let remainder = value_chunks.remainder();
+    for (hash, &value) in hash_chunks.into_remainder().iter_mut().zip(remainder) {
+        *hash = hash_int(value, *hash);
+    }
+}
+
+/// computes spark's hashLong() of every value, see
+/// [`spark_compatible_murmur3_hash_int_batch`]
+pub fn spark_compatible_murmur3_hash_long_batch(values: &[i64], hashes: &mut [i32]) {
+    assert_eq!(values.len(), hashes.len());
+    let mut value_chunks = values.chunks_exact(BATCH_LANES);
+    let mut hash_chunks = hashes.chunks_exact_mut(BATCH_LANES);
+    for (values, hashes) in (&mut value_chunks).zip(&mut hash_chunks) {
+        for (hash, &value) in hashes.iter_mut().zip(values) {
+            *hash = hash_long(value, *hash);
+        }
+    }
+    let remainder = value_chunks.remainder();
+    for (hash, &value) in hash_chunks.into_remainder().iter_mut().zip(remainder) {
+        *hash = hash_long(value, *hash);
+    }
+}
+
 #[inline]
 fn mix_k1(mut k1: i32) -> i32 {
     k1 *= 0xcc9e2d51u32 as i32;
@@ -71,6 +109,12 @@ fn hash_bytes_by_int(data: &[u8], seed: i32) -> i32 {
     h1
 }
 
+#[inline]
+fn hash_int(input: i32, seed: i32) -> i32 {
+    let h1 = mix_h1(seed, mix_k1(input));
+    fmix(h1, 4)
+}
+
 #[inline]
 fn hash_long(input: i64, seed: i32) -> i32 {
     let low = input as i32;
@@ -100,4 +144,26 @@ mod tests {
         ];
         assert_eq!(_hashes, _expected)
     }
+
+    #[test]
+    fn test_murmur3_batch() {
+        let ints = (-100..100).chain([i32::MIN, i32::MAX]).collect::<Vec<_>>();
+        let mut hashes = (0..ints.len() as i32).collect::<Vec<_>>();
+        let expected = ints
+            .iter()
+            .zip(&hashes)
+            .map(|(v, &seed)| spark_compatible_murmur3_hash(v.to_le_bytes(), seed))
+            .collect::<Vec<_>>();
+        spark_compatible_murmur3_hash_int_batch(&ints, &mut hashes);
+        assert_eq!(hashes, expected);
+
+        let longs = (-100..100).chain([i64::MIN, i64::MAX]).collect::<Vec<_>>();
+        let mut hashes = vec![42; longs.len()];
+        let expected = longs
+            .iter()
+            .map(|v| spark_compatible_murmur3_hash(v.to_le_bytes(), 42))
+            .collect::<Vec<_>>();
+        spark_compatible_murmur3_hash_long_batch(&longs, &mut hashes);
+        assert_eq!(hashes, expected);
+    }
 }
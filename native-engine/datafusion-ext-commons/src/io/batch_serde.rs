@@ -27,22 +27,102 @@ use datafusion::common::Result;
 use unchecked_index::unchecked_index;
 
 use crate::{
-    df_unimplemented_err,
-    io::{read_bytes_slice, read_len, write_len},
+    arrow::{
+        dictionary::is_dictionary_value_type,
+        view::{is_view_type, plain_data_type},
+    },
+    df_execution_err, df_unimplemented_err,
+    io::{read_bytes_slice, read_len, read_u8, write_len, write_u8},
 };
 
-pub fn write_batch(num_rows: usize, cols: &[ArrayRef], mut output: impl Write) -> Result<()> {
+// how values of a dictionary-encoded array are transferred
+const DICT_UNCHANGED: u8 = 0;
+const DICT_REPLACEMENT: u8 = 1;
+const DICT_DELTA: u8 = 2;
+
+// how top-level string/binary columns are transferred. dictionary-encoded
+// columns are transferred as is and can be read as either dictionary or plain
+// arrays, so that they stay encoded when the reader expects plain arrays (like
+// shuffle readers whose schemas come from spark).
+const BYTES_PLAIN: u8 = 0;
+const BYTES_DICTIONARY: u8 = 1;
+
+/// Dictionaries of top-level dictionary-encoded columns which have been
+/// transferred in a stream. a dictionary is transferred again only when it is
+/// replaced, and only its new values are transferred when it is extended
+/// (delta dictionary). writer and reader must see the same sequence of
+/// batches, and the writer must clear it at every point where a reader may
+/// start reading.
+#[derive(Default)]
+pub struct TransferredDictionaries {
+    dicts: Vec<Option<ArrayRef>>,
+}
+
+impl TransferredDictionaries {
+    pub fn clear(&mut self) {
+        self.dicts.clear();
+    }
+
+    fn get_mut(&mut self, col_idx: usize) -> &mut Option<ArrayRef> {
+        if self.dicts.len() <= col_idx {
+            self.dicts.resize(col_idx + 1, None);
+        }
+        &mut self.dicts[col_idx]
+    }
+}
+
+pub fn write_batch(num_rows: usize, cols: &[ArrayRef], output: impl Write) -> Result<()> {
+    write_batch_with_dictionaries(
+        num_rows,
+        cols,
+        &mut TransferredDictionaries::default(),
+        output,
+    )
+}
+
+pub fn write_batch_with_dictionaries(
+    num_rows: usize,
+    cols: &[ArrayRef],
+    dictionaries: &mut TransferredDictionaries,
+    mut output: impl Write,
+) -> Result<()> {
     // write number of columns and rows
     write_len(num_rows, &mut output)?;
 
     // write columns
-    for col in cols {
-        write_array(col, &mut output)?;
+    for (col_idx, col) in cols.iter().enumerate() {
+        match col.data_type() {
+            data_type if is_dictionary_value_type(data_type) || is_view_type(data_type) => {
+                write_u8(BYTES_PLAIN, &mut output)?;
+                write_array(col, &mut output)?;
+            }
+            DataType::Dictionary(_, value_type) if is_dictionary_value_type(value_type) => {
+                // keys are always transferred as int32 so readers expecting plain
+                // arrays know how to decode them
+                let col = cast_dictionary_keys_to_int32(col)?;
+                let dict = dictionaries.get_mut(col_idx);
+                write_u8(BYTES_DICTIONARY, &mut output)?;
+                *dict = Some(write_dictionary_array(&col, dict.as_ref(), &mut output)?);
+            }
+            DataType::Dictionary(..) => {
+                let dict = dictionaries.get_mut(col_idx);
+                *dict = Some(write_dictionary_array(col, dict.as_ref(), &mut output)?);
+            }
+            _ => write_array(col, &mut output)?,
+        }
     }
     Ok(())
 }
 
-pub fn read_batch(mut input: impl Read, schema: &SchemaRef) -> Result<(usize, Vec<ArrayRef>)> {
+pub fn read_batch(input: impl Read, schema: &SchemaRef) -> Result<(usize, Vec<ArrayRef>)> {
+    read_batch_with_dictionaries(input, schema, &mut TransferredDictionaries::default())
+}
+
+pub fn read_batch_with_dictionaries(
+    mut input: impl Read,
+    schema: &SchemaRef,
+    dictionaries: &mut TransferredDictionaries,
+) -> Result<(usize, Vec<ArrayRef>)> {
     // read number of columns and rows
     let num_rows = read_len(&mut input)?;
 
@@ -50,11 +130,86 @@ pub fn read_batch(mut input: impl Read, schema: &SchemaRef) -> Result<(usize, Ve
     let cols = schema
         .fields()
         .into_iter()
-        .map(|field| read_array(&mut input, &field.data_type(), num_rows))
+        .enumerate()
+        .map(|(col_idx, field)| match field.data_type() {
+            data_type if is_bytes_column_type(data_type) => read_bytes_column(
+                num_rows,
+                &mut input,
+                data_type,
+                dictionaries.get_mut(col_idx),
+            ),
+            DataType::Dictionary(key_type, value_type) => {
+                let dict = dictionaries.get_mut(col_idx);
+                let array = read_dictionary_array(
+                    num_rows,
+                    &mut input,
+                    key_type,
+                    value_type,
+                    dict.as_ref(),
+                )?;
+                *dict = Some(array.as_any_dictionary().values().clone());
+                Ok(array)
+            }
+            data_type => read_array(&mut input, data_type, num_rows),
+        })
         .collect::<Result<_>>()?;
     Ok((num_rows, cols))
 }
 
+fn is_bytes_column_type(data_type: &DataType) -> bool {
+    match data_type {
+        DataType::Dictionary(_, value_type) => is_dictionary_value_type(value_type),
+        data_type => is_dictionary_value_type(data_type) || is_view_type(data_type),
+    }
+}
+
+fn cast_dictionary_keys_to_int32(array: &ArrayRef) -> Result<ArrayRef> {
+    let dict = array.as_any_dictionary();
+    if dict.keys().data_type() == &DataType::Int32 {
+        return Ok(array.clone());
+    }
+    let keys = arrow::compute::cast(dict.keys(), &DataType::Int32)?;
+    let dict = DictionaryArray::<Int32Type>::try_new(
+        keys.as_primitive::<Int32Type>().clone(),
+        dict.values().clone(),
+    )?;
+    Ok(Arc::new(dict))
+}
+
+/// reads a top-level string/binary column, the column may be written as a
+/// plain or dictionary array and is converted to the expected data type.
+fn read_bytes_column<R: Read>(
+    num_rows: usize,
+    input: &mut R,
+    data_type: &DataType,
+    dict: &mut Option<ArrayRef>,
+) -> Result<ArrayRef> {
+    let value_type = match data_type {
+        DataType::Dictionary(_, value_type) => value_type.as_ref().clone(),
+        data_type => plain_data_type(data_type),
+    };
+    let value_type = &value_type;
+    let array = match read_u8(input)? {
+        BYTES_PLAIN => read_array(input, value_type, num_rows)?,
+        BYTES_DICTIONARY => {
+            let array = read_dictionary_array(
+                num_rows,
+                input,
+                &DataType::Int32,
+                value_type,
+                dict.as_ref(),
+            )?;
+            *dict = Some(array.as_any_dictionary().values().clone());
+            array
+        }
+        other => return df_execution_err!("invalid bytes column encoding: {other}"),
+    };
+    if array.data_type() != data_type {
+        return Ok(arrow::compute::cast(&array, data_type)?);
+    }
+    Ok(array)
+}
+
 pub fn write_array<W: Write>(array: &dyn Array, output: &mut W) -> Result<()> {
     macro_rules! write_primitive {
         ($ty:ident) => {{
@@ -80,6 +235,8 @@ pub fn write_array<W: Write>(array: &dyn Array, output: &mut W) -> Result<()> {
         DataType::Decimal128(..) => write_primitive!(Decimal128),
         DataType::Utf8 => write_bytes_array(as_string_array(array), output)?,
         DataType::Binary => write_bytes_array(as_generic_binary_array::<i32>(array), output)?,
+        DataType::Utf8View => write_byte_view_array(array.as_string_view(), output)?,
+        DataType::BinaryView => write_byte_view_array(array.as_binary_view(), output)?,
         DataType::Date32 => write_primitive!(Date32),
         DataType::Date64 => write_primitive!(Date64),
         DataType::Timestamp(TimeUnit::Second, _) => write_primitive!(TimestampSecond),
@@ -89,6 +246,9 @@ pub fn write_array<W: Write>(array: &dyn Array, output: &mut W) -> Result<()> {
         DataType::List(_field) => write_list_array(as_list_array(array), output)?,
         DataType::Map(..) => write_map_array(as_map_array(array), output)?,
         DataType::Struct(_) => write_struct_array(as_struct_array(array), output)?,
+        DataType::Dictionary(..) => {
+            write_dictionary_array(array, None, output)?;
+        }
         other => df_unimplemented_err!("unsupported data type: {other}")?,
     }
     Ok(())
@@ -130,11 +290,19 @@ pub fn read_array<R: Read>(
         DataType::Timestamp(TimeUnit::Nanosecond, _) => read_primitive!(TimestampNanosecond),
         DataType::Utf8 => read_bytes_array(num_rows, input, DataType::Utf8)?,
         DataType::Binary => read_bytes_array(num_rows, input, DataType::Binary)?,
+        DataType::Utf8View | DataType::BinaryView => {
+            let plain_type = plain_data_type(data_type);
+            let array = read_bytes_array(num_rows, input, plain_type)?;
+            arrow::compute::cast(&array, data_type)?
+        }
         DataType::List(list_field) => read_list_array(num_rows, input, list_field)?,
         DataType::Map(map_field, is_sorted) => {
             read_map_array(num_rows, input, map_field, *is_sorted)?
         }
         DataType::Struct(fields) => read_struct_array(num_rows, input, fields)?,
+        DataType::Dictionary(key_type, value_type) => {
+            read_dictionary_array(num_rows, input, key_type, value_type, None)?
+        }
         other => df_unimplemented_err!("unsupported data type: {other}")?,
     })
 }
@@ -409,6 +577,80 @@ fn read_struct_array<R: Read>(num_rows: usize, input: &mut R, fields: &Fields) -
     Ok(make_array(array_data))
 }
 
+/// writes a dictionary array, dictionary values are omitted or written as a
+/// delta if they are the same as or extended from the previously transferred
+/// values. returns the dictionary values.
+fn write_dictionary_array<W: Write>(
+    array: &dyn Array,
+    prev_values: Option<&ArrayRef>,
+    output: &mut W,
+) -> Result<ArrayRef> {
+    let dict = array.as_any_dictionary();
+    let values = dict.values();
+    let num_prev_values = match prev_values {
+        Some(prev_values) if Arc::ptr_eq(prev_values, values) => Some(values.len()),
+        Some(prev_values)
+            if prev_values.len() <= values.len()
+                && values.slice(0, prev_values.len()).to_data() == prev_values.to_data() =>
+        {
+            Some(prev_values.len())
+        }
+        _ => None,
+    };
+
+    match num_prev_values {
+        Some(n) if n == values.len() => write_u8(DICT_UNCHANGED, output)?,
+        Some(n) => {
+            write_u8(DICT_DELTA, output)?;
+            write_len(values.len() - n, output)?;
+            write_array(&values.slice(n, values.len() - n), output)?;
+        }
+        None => {
+            write_u8(DICT_REPLACEMENT, output)?;
+            write_len(values.len(), output)?;
+            write_array(values, output)?;
+        }
+    }
+    write_array(dict.keys(), output)?;
+    Ok(values.clone())
+}
+
+fn read_dictionary_array<R: Read>(
+    num_rows: usize,
+    input: &mut R,
+    key_type: &DataType,
+    value_type: &DataType,
+    prev_values: Option<&ArrayRef>,
+) -> Result<ArrayRef> {
+    let values = match (read_u8(input)?, prev_values) {
+        (DICT_UNCHANGED, Some(prev_values)) => prev_values.clone(),
+        (DICT_DELTA, Some(prev_values)) => {
+            let delta_len = read_len(input)?;
+            let delta = read_array(input, value_type, delta_len)?;
+            arrow::compute::concat(&[prev_values.as_ref(), delta.as_ref()])?
+        }
+        (DICT_REPLACEMENT, _) => {
+            let values_len = read_len(input)?;
+            read_array(input, value_type, values_len)?
+        }
+        (DICT_UNCHANGED | DICT_DELTA, None) => {
+            return df_execution_err!("dictionary delta/reference without previous dictionary");
+        }
+        (other, _) => return df_execution_err!("invalid dictionary tag: {other}"),
+    };
+    let keys = read_array(input, key_type, num_rows)?;
+    let array_data = keys
+        .into_data()
+        .into_builder()
+        .data_type(DataType::Dictionary(
+            Box::new(key_type.clone()),
+            Box::new(value_type.clone()),
+        ))
+        .child_data(vec![values.into_data()])
+        .build()?;
+    Ok(make_array(array_data))
+}
+
 fn write_boolean_array<W: Write>(array: &BooleanArray, output: &mut W) -> Result<()> {
     let array_data = array.to_data();
     if let Some(null_buffer) = array_data.nulls() {
@@ -485,6 +727,37 @@ fn write_bytes_array<T: ByteArrayType<Offset = i32>, W: Write>(
     Ok(())
 }
 
+/// writes a view array in the same format as plain bytes arrays
+fn write_byte_view_array<T: ByteViewType + ?Sized, W: Write>(
+    array: &GenericByteViewArray<T>,
+    output: &mut W,
+) -> Result<()> {
+    if let Some(null_buffer) = array.nulls() {
+        write_len(1, output)?;
+        write_bits_buffer(
+            null_buffer.buffer(),
+            null_buffer.offset(),
+            null_buffer.len(),
+            output,
+        )?;
+    } else {
+        write_len(0, output)?;
+    }
+
+    let lens = (0..array.len())
+        .map(|i| match array.is_valid(i) {
+            true => array.views()[i] as u32 as i32,
+            false => 0,
+        })
+        .collect::<Vec<_>>();
+    write_primitive_raw_array(&lens, output)?;
+    for i in (0..array.len()).filter(|&i| array.is_valid(i)) {
+        let value: &[u8] = array.value(i).as_ref();
+        output.write_all(value)?;
+    }
+    Ok(())
+}
+
 fn read_bytes_array<R: Read>(
     num_rows: usize,
     input: &mut R,
@@ -643,6 +916,101 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_write_and_read_batch_for_dictionary() {
+        let dict_array: ArrayRef = Arc::new(DictionaryArray::new(
+            Int32Array::from(vec![Some(2), Some(0), None, Some(1), Some(2)]),
+            Arc::new(StringArray::from(vec!["a", "b", "c"])),
+        ));
+        let struct_array: ArrayRef = Arc::new(StructArray::from(vec![(
+            Arc::new(Field::new("d", dict_array.data_type().clone(), true)),
+            dict_array.clone(),
+        )]));
+        let batch = RecordBatch::try_from_iter_with_nullable(vec![
+            ("dict", dict_array, true),
+            ("struct", struct_array, true),
+        ])
+        .unwrap();
+
+        // test read after write
+        let mut buf = vec![];
+        write_batch(batch.num_rows(), batch.columns(), &mut buf).unwrap();
+        let mut cursor = Cursor::new(buf);
+        let (decoded_num_rows, decoded_cols) = read_batch(&mut cursor, &batch.schema()).unwrap();
+        assert_eq!(
+            recover_named_batch(decoded_num_rows, &decoded_cols, batch.schema()).unwrap(),
+            batch
+        );
+
+        // test read after write sliced
+        let sliced = batch.slice(1, 3);
+        let mut buf = vec![];
+        write_batch(sliced.num_rows(), sliced.columns(), &mut buf).unwrap();
+        let mut cursor = Cursor::new(buf);
+        let (decoded_num_rows, decoded_cols) = read_batch(&mut cursor, &batch.schema()).unwrap();
+        assert_eq!(
+            recover_named_batch(decoded_num_rows, &decoded_cols, batch.schema()).unwrap(),
+            sliced
+        );
+    }
+
+    #[test]
+    fn test_write_and_read_batch_for_dictionary_as_plain() {
+        let dict_array: ArrayRef = Arc::new(DictionaryArray::new(
+            Int8Array::from(vec![Some(2), Some(0), None, Some(1), Some(2)]),
+            Arc::new(StringArray::from(vec!["a", "b", "c"])),
+        ));
+        let plain_array = arrow::compute::cast(&dict_array, &DataType::Utf8).unwrap();
+        let dict_schema = Arc::new(Schema::new(vec![Field::new(
+            "s",
+            dict_array.data_type().clone(),
+            true,
+        )]));
+        let plain_schema = Arc::new(Schema::new(vec![Field::new("s", DataType::Utf8, true)]));
+
+        // dictionary arrays can be read as plain arrays, and vice versa
+        let mut buf = vec![];
+        write_batch(5, &[dict_array.clone()], &mut buf).unwrap();
+        let (_, decoded_cols) = read_batch(&mut Cursor::new(&buf), &plain_schema).unwrap();
+        assert_eq!(&decoded_cols[0], &plain_array);
+        let (_, decoded_cols) = read_batch(&mut Cursor::new(&buf), &dict_schema).unwrap();
+        assert_eq!(&decoded_cols[0], &dict_array);
+
+        let mut buf = vec![];
+        write_batch(5, &[plain_array.clone()], &mut buf).unwrap();
+        let (_, decoded_cols) = read_batch(&mut Cursor::new(&buf), &dict_schema).unwrap();
+        assert_eq!(
+            &arrow::compute::cast(&decoded_cols[0], &DataType::Utf8).unwrap(),
+            &plain_array
+        );
+    }
+
+    #[test]
+    fn test_write_and_read_batch_for_view() {
+        let view_array: ArrayRef = Arc::new(StringViewArray::from(vec![
+            Some("a value longer than 12 bytes"),
+            None,
+            Some("short"),
+            Some(""),
+        ]));
+        let plain_array = arrow::compute::cast(&view_array, &DataType::Utf8).unwrap();
+        let view_schema = Arc::new(Schema::new(vec![Field::new("s", DataType::Utf8View, true)]));
+        let plain_schema = Arc::new(Schema::new(vec![Field::new("s", DataType::Utf8, true)]));
+
+        // view arrays are written as plain arrays
+        for (array, expected) in [
+            (view_array.clone(), plain_array.clone()),
+            (view_array.slice(1, 3), plain_array.slice(1, 3)),
+        ] {
+            let mut buf = vec![];
+            write_batch(array.len(), &[array.clone()], &mut buf).unwrap();
+            let (_, decoded_cols) = read_batch(&mut Cursor::new(&buf), &view_schema).unwrap();
+            assert_eq!(&decoded_cols[0], &array);
+            let (_, decoded_cols) = read_batch(&mut Cursor::new(&buf), &plain_schema).unwrap();
+            assert_eq!(&decoded_cols[0], &expected);
+        }
+    }
+
     #[test]
     fn test_write_and_read_batch_for_list() {
         let data = vec![
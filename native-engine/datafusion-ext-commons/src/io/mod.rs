@@ -19,7 +19,7 @@ use arrow::{
     datatypes::SchemaRef,
     record_batch::RecordBatch,
 };
-pub use batch_serde::{read_array, write_array};
+pub use batch_serde::{read_array, write_array, TransferredDictionaries};
 use datafusion::common::Result;
 pub use scalar_serde::{read_scalar, write_scalar};
 
@@ -52,19 +52,45 @@ pub fn read_raw_slice<T: Sized + Copy>(
     input.read_exact(raw_slice)
 }
 
-pub fn write_one_batch(num_rows: usize, cols: &[ArrayRef], mut output: impl Write) -> Result<()> {
+pub fn write_one_batch(num_rows: usize, cols: &[ArrayRef], output: impl Write) -> Result<()> {
+    write_one_batch_with_dictionaries(
+        num_rows,
+        cols,
+        &mut TransferredDictionaries::default(),
+        output,
+    )
+}
+
+/// like `write_one_batch()`, but dictionaries already transferred in the
+/// stream are not written again
+pub fn write_one_batch_with_dictionaries(
+    num_rows: usize,
+    cols: &[ArrayRef],
+    dictionaries: &mut TransferredDictionaries,
+    mut output: impl Write,
+) -> Result<()> {
     assert!(cols.iter().all(|col| col.len() == num_rows));
 
     let mut batch_data = vec![];
-    batch_serde::write_batch(num_rows, cols, &mut batch_data)?;
+    batch_serde::write_batch_with_dictionaries(num_rows, cols, dictionaries, &mut batch_data)?;
     write_len(batch_data.len(), &mut output)?;
     output.write_all(&batch_data)?;
     Ok(())
 }
 
 pub fn read_one_batch(
+    input: impl Read,
+    schema: &SchemaRef,
+) -> Result<Option<(usize, Vec<ArrayRef>)>> {
+    read_one_batch_with_dictionaries(input, schema, &mut TransferredDictionaries::default())
+}
+
+/// like `read_one_batch()`, reads batches written by
+/// `write_one_batch_with_dictionaries()`
+pub fn read_one_batch_with_dictionaries(
     mut input: impl Read,
     schema: &SchemaRef,
+    dictionaries: &mut TransferredDictionaries,
 ) -> Result<Option<(usize, Vec<ArrayRef>)>> {
     let batch_data_len = match read_len(&mut input) {
         Ok(len) => len,
@@ -76,7 +102,8 @@ pub fn read_one_batch(
         }
     };
     let mut input = input.take(batch_data_len as u64);
-    let (num_rows, cols) = batch_serde::read_batch(&mut input, schema)?;
+    let (num_rows, cols) =
+        batch_serde::read_batch_with_dictionaries(&mut input, schema, dictionaries)?;
 
     // consume trailing bytes
     std::io::copy(&mut input, &mut std::io::sink())?;
@@ -27,12 +27,17 @@ use unchecked_index::UncheckedIndex;
 
 pub mod algorithm;
 pub mod arrow;
+pub mod blaze_error;
+pub mod constant_cache;
+pub mod fs;
 pub mod hadoop_fs;
 pub mod hash;
 pub mod io;
 pub mod spark_bit_array;
 pub mod spark_bloom_filter;
+pub mod spark_error;
 pub mod spark_hash;
+pub mod spark_task_context;
 pub mod uda;
 
 #[macro_export]
@@ -41,6 +46,28 @@ macro_rules! df_execution_err {
         Err(datafusion::common::DataFusionError::Execution(format!($($arg)*)))
     }
 }
+/// raises a spark visible error with the given error class, see
+/// [`spark_error`]
+#[macro_export]
+macro_rules! df_spark_err {
+    ($error_class:expr, $($arg:tt)*) => {
+        Err(datafusion::common::DataFusionError::External(Box::new(
+            $crate::blaze_error::BlazeError::SparkError {
+                error_class: $error_class,
+                message: format!($($arg)*),
+            },
+        )))
+    }
+}
+/// raises a structured error of the given kind, see [`blaze_error`]
+#[macro_export]
+macro_rules! df_blaze_err {
+    ($kind:ident, $($arg:tt)*) => {
+        Err(datafusion::common::DataFusionError::External(Box::new(
+            $crate::blaze_error::BlazeError::$kind(format!($($arg)*)),
+        )))
+    }
+}
 #[macro_export]
 macro_rules! df_unimplemented_err {
     ($($arg:tt)*) => {
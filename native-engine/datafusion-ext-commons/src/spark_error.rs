@@ -0,0 +1,85 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Spark visible errors.
+//!
+//! errors which spark reports to users (like ansi mode overflows) are raised
+//! as execution errors formatted as `[ERROR_CLASS] message`, see
+//! [`df_spark_err`](crate::df_spark_err). when the task fails, the error class
+//! is extracted from the error message and the corresponding exception is
+//! thrown in the JVM, so that users see the same exceptions as in spark.
+
+pub const ARITHMETIC_OVERFLOW: &str = "ARITHMETIC_OVERFLOW";
+pub const CAST_INVALID_INPUT: &str = "CAST_INVALID_INPUT";
+pub const CAST_OVERFLOW: &str = "CAST_OVERFLOW";
+pub const DIVIDE_BY_ZERO: &str = "DIVIDE_BY_ZERO";
+pub const INVALID_ARRAY_INDEX: &str = "INVALID_ARRAY_INDEX";
+pub const NUMERIC_VALUE_OUT_OF_RANGE: &str = "NUMERIC_VALUE_OUT_OF_RANGE";
+pub const USER_RAISED_EXCEPTION: &str = "USER_RAISED_EXCEPTION";
+
+const SPARK_ERROR_CLASSES: &[&str] = &[
+    ARITHMETIC_OVERFLOW,
+    CAST_INVALID_INPUT,
+    CAST_OVERFLOW,
+    DIVIDE_BY_ZERO,
+    INVALID_ARRAY_INDEX,
+    NUMERIC_VALUE_OUT_OF_RANGE,
+    USER_RAISED_EXCEPTION,
+];
+
+/// finds the spark error in an error message, returns the error class and
+/// the message following the error class.
+///
+/// errors are wrapped by the operators they pass through, so the innermost
+/// (last) error class in the message is the one originally raised.
+pub fn find_spark_error(message: &str) -> Option<(&'static str, &str)> {
+    SPARK_ERROR_CLASSES
+        .iter()
+        .filter_map(|&error_class| {
+            let prefix = format!("[{error_class}] ");
+            message
+                .rfind(&prefix)
+                .map(|pos| (pos, error_class, &message[pos + prefix.len()..]))
+        })
+        .max_by_key(|&(pos, ..)| pos)
+        .map(|(_, error_class, error_message)| (error_class, error_message))
+}
+
+#[cfg(test)]
+mod test {
+    use datafusion::common::Result;
+
+    use crate::{
+        df_spark_err,
+        spark_error::{find_spark_error, DIVIDE_BY_ZERO, NUMERIC_VALUE_OUT_OF_RANGE},
+    };
+
+    #[test]
+    fn test_find_spark_error() {
+        let err: Result<()> = df_spark_err!(DIVIDE_BY_ZERO, "division by zero");
+        let message = format!("task panics: poll record batch error: {}", err.unwrap_err());
+        assert_eq!(
+            find_spark_error(&message),
+            Some((DIVIDE_BY_ZERO, "division by zero"))
+        );
+
+        let message = "[DIVIDE_BY_ZERO] outer: [NUMERIC_VALUE_OUT_OF_RANGE] inner";
+        assert_eq!(
+            find_spark_error(message),
+            Some((NUMERIC_VALUE_OUT_OF_RANGE, "inner"))
+        );
+        assert_eq!(find_spark_error("[UNKNOWN_CLASS] error"), None);
+        assert_eq!(find_spark_error("execution error"), None);
+    }
+}
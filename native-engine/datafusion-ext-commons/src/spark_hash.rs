@@ -14,20 +14,27 @@
 
 //! Functionality used both on logical and physical plans
 
-use arrow::{
-    array::*,
-    datatypes::{
-        ArrowDictionaryKeyType, ArrowNativeType, DataType, Int16Type, Int32Type, Int64Type,
-        Int8Type, TimeUnit,
+use arrow::{array::*, buffer::NullBuffer, datatypes::*};
+
+use crate::hash::{
+    mur::{
+        spark_compatible_murmur3_hash, spark_compatible_murmur3_hash_int_batch,
+        spark_compatible_murmur3_hash_long_batch,
     },
+    xxhash::spark_compatible_xxhash64_hash,
 };
 
-use crate::hash::{mur::spark_compatible_murmur3_hash, xxhash::spark_compatible_xxhash64_hash};
-
 pub fn create_murmur3_hashes(len: usize, arrays: &[ArrayRef], seed: i32) -> Vec<i32> {
-    create_hashes(len, arrays, seed, |data: &[u8], seed: i32| {
-        spark_compatible_murmur3_hash(data, seed)
-    })
+    let h = |data: &[u8], seed: i32| spark_compatible_murmur3_hash(data, seed);
+    let mut hash_buffer = vec![seed; len];
+    for (i, col) in arrays.iter().enumerate() {
+        // all rows of the first column are hashed with the same seed
+        let uniform_seed = (i == 0).then_some(seed);
+        if !hash_array_murmur3_batch(col, &mut hash_buffer, uniform_seed) {
+            hash_array(col, &mut hash_buffer, h);
+        }
+    }
+    hash_buffer
 }
 
 pub fn create_xxhash64_hashes(len: usize, arrays: &[ArrayRef], seed: i64) -> Vec<i64> {
@@ -39,8 +46,9 @@ pub fn create_xxhash64_hashes(len: usize, arrays: &[ArrayRef], seed: i64) -> Vec
 /// Creates hash values for every row, based on the values in the
 /// columns.
 ///
-/// The number of rows to hash is determined by `hashes_buffer.len()`.
-/// `hashes_buffer` should be pre-sized appropriately
+/// like spark, each column is hashed with the hash of previous columns as
+/// seed, and null values are skipped, so a row of all nulls is hashed to
+/// `seed`.
 #[inline]
 pub fn create_hashes<T: num::PrimInt>(
     len: usize,
@@ -48,52 +56,203 @@ pub fn create_hashes<T: num::PrimInt>(
     seed: T,
     h: impl Fn(&[u8], T) -> T + Copy,
 ) -> Vec<T> {
-    if arrays.is_empty() {
-        return vec![seed; len];
+    let mut hash_buffer = vec![seed; len];
+    for col in arrays {
+        hash_array(col, &mut hash_buffer, h);
     }
-    let mut hash_buffer = vec![T::zero(); len];
+    hash_buffer
+}
 
-    // hash first column
-    hash_array(&arrays[0], &mut hash_buffer, seed, true, h);
+/// fast path of murmur3 hashing, where fixed-width columns are hashed with
+/// vectorized batch functions, and each distinct value of dictionary columns
+/// is hashed only once if all rows have the same seed. returns false if the
+/// column is not supported.
+fn hash_array_murmur3_batch(
+    array: &ArrayRef,
+    hashes_buffer: &mut [i32],
+    uniform_seed: Option<i32>,
+) -> bool {
+    assert_eq!(array.len(), hashes_buffer.len());
+
+    // null values are skipped, so hash all values and keep the old hashes of nulls
+    fn hash_with_nulls(
+        nulls: Option<&NullBuffer>,
+        hashes_buffer: &mut [i32],
+        hash_batch: impl FnOnce(&mut [i32]),
+    ) {
+        match nulls.filter(|nulls| nulls.null_count() > 0) {
+            None => hash_batch(hashes_buffer),
+            Some(nulls) => {
+                let mut new_hashes = hashes_buffer.to_vec();
+                hash_batch(&mut new_hashes);
+                for ((hash, new_hash), valid) in
+                    hashes_buffer.iter_mut().zip(new_hashes).zip(nulls.iter())
+                {
+                    *hash = if valid { new_hash } else { *hash };
+                }
+            }
+        }
+    }
 
-    // hash rest columns
-    for col in arrays.iter().skip(1) {
-        hash_array(col, &mut hash_buffer, seed, false, h);
+    macro_rules! hash_ints {
+        ($values:expr) => {{
+            let values: &[i32] = $values;
+            hash_with_nulls(array.nulls(), hashes_buffer, |hashes| {
+                spark_compatible_murmur3_hash_int_batch(values, hashes)
+            });
+        }};
+    }
+    macro_rules! hash_longs {
+        ($values:expr) => {{
+            let values: &[i64] = $values;
+            hash_with_nulls(array.nulls(), hashes_buffer, |hashes| {
+                spark_compatible_murmur3_hash_long_batch(values, hashes)
+            });
+        }};
+    }
+
+    match array.data_type() {
+        DataType::Int8 => {
+            let values = array.as_primitive::<Int8Type>().values();
+            hash_ints!(&values.iter().map(|&v| v as i32).collect::<Vec<_>>());
+        }
+        DataType::Int16 => {
+            let values = array.as_primitive::<Int16Type>().values();
+            hash_ints!(&values.iter().map(|&v| v as i32).collect::<Vec<_>>());
+        }
+        DataType::Int32 => hash_ints!(array.as_primitive::<Int32Type>().values()),
+        DataType::Date32 => hash_ints!(array.as_primitive::<Date32Type>().values()),
+        DataType::Int64 => hash_longs!(array.as_primitive::<Int64Type>().values()),
+        DataType::Date64 => hash_longs!(array.as_primitive::<Date64Type>().values()),
+        DataType::Timestamp(TimeUnit::Second, _) => {
+            hash_longs!(array.as_primitive::<TimestampSecondType>().values())
+        }
+        DataType::Timestamp(TimeUnit::Millisecond, _) => {
+            hash_longs!(array.as_primitive::<TimestampMillisecondType>().values())
+        }
+        DataType::Timestamp(TimeUnit::Microsecond, _) => {
+            hash_longs!(array.as_primitive::<TimestampMicrosecondType>().values())
+        }
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => {
+            hash_longs!(array.as_primitive::<TimestampNanosecondType>().values())
+        }
+        DataType::Float32 => {
+            let values = array.as_primitive::<Float32Type>().values();
+            hash_ints!(&values
+                .iter()
+                .map(|&v| java_float_bits(v))
+                .collect::<Vec<_>>());
+        }
+        DataType::Float64 => {
+            let values = array.as_primitive::<Float64Type>().values();
+            hash_longs!(&values
+                .iter()
+                .map(|&v| java_double_bits(v))
+                .collect::<Vec<_>>());
+        }
+        &DataType::Decimal128(precision, _) if precision <= 18 => {
+            let values = array.as_primitive::<Decimal128Type>().values();
+            hash_longs!(&values.iter().map(|&v| v as i64).collect::<Vec<_>>());
+        }
+        DataType::Dictionary(..) => {
+            let Some(seed) = uniform_seed else {
+                return false;
+            };
+            let dict_array = array.as_any_dictionary();
+            let dict_values = dict_array.values();
+            let value_hashes =
+                create_murmur3_hashes(dict_values.len(), &[dict_values.clone()], seed);
+            let keys = dict_array.normalized_keys();
+            for (i, (hash, key)) in hashes_buffer.iter_mut().zip(keys).enumerate() {
+                if array.is_valid(i) {
+                    *hash = value_hashes[key];
+                }
+            }
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// float bits hashed by spark, where -0.0 is normalized to 0.0 and NaNs are
+/// canonicalized like java's Float.floatToIntBits()
+#[inline]
+fn java_float_bits(value: f32) -> i32 {
+    if value == 0.0 {
+        0
+    } else if value.is_nan() {
+        0x7fc00000
+    } else {
+        value.to_bits() as i32
+    }
+}
+
+/// double bits hashed by spark, see [`java_float_bits`]
+#[inline]
+fn java_double_bits(value: f64) -> i64 {
+    if value == 0.0 {
+        0
+    } else if value.is_nan() {
+        0x7ff8000000000000
+    } else {
+        value.to_bits() as i64
+    }
+}
+
+/// bytes of decimals hashed by spark: the unscaled long value if precision
+/// fits in a long, otherwise the bytes of java's BigInteger.toByteArray()
+struct DecimalHashBytes {
+    bytes: [u8; 16],
+    start: usize,
+}
+
+impl DecimalHashBytes {
+    #[inline]
+    fn new(unscaled: i128, precision: u8) -> Self {
+        if precision <= 18 {
+            let mut bytes = [0u8; 16];
+            bytes[8..].copy_from_slice(&(unscaled as i64).to_le_bytes());
+            return Self { bytes, start: 8 };
+        }
+
+        // minimal big-endian two's-complement representation
+        let bytes = unscaled.to_be_bytes();
+        let mut start = 0;
+        while start < bytes.len() - 1
+            && ((bytes[start] == 0x00 && bytes[start + 1] & 0x80 == 0)
+                || (bytes[start] == 0xff && bytes[start + 1] & 0x80 != 0))
+        {
+            start += 1;
+        }
+        Self { bytes, start }
+    }
+}
+
+impl AsRef<[u8]> for DecimalHashBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes[self.start..]
     }
-    hash_buffer
 }
 
 #[inline]
 fn hash_array<T: num::PrimInt>(
     array: &ArrayRef,
     hashes_buffer: &mut [T],
-    initial_seed: T,
-    is_initial: bool,
     h: impl Fn(&[u8], T) -> T + Copy,
 ) {
     assert_eq!(array.len(), hashes_buffer.len());
 
-    macro_rules! initial_seed_or {
-        ($h:expr) => {{
-            if is_initial {
-                initial_seed
-            } else {
-                $h
-            }
-        }};
-    }
-
     macro_rules! hash_array {
         ($array_type:ident, $column:ident, $hashes:ident, $h:expr) => {
             let array = $column.as_any().downcast_ref::<$array_type>().unwrap();
             if array.null_count() == 0 {
                 for (i, hash) in $hashes.iter_mut().enumerate() {
-                    *hash = $h(&array.value(i).as_ref(), initial_seed_or!(*hash));
+                    *hash = $h(&array.value(i).as_ref(), *hash);
                 }
             } else {
                 for (i, hash) in $hashes.iter_mut().enumerate() {
                     if !array.is_null(i) {
-                        *hash = $h(&array.value(i).as_ref(), initial_seed_or!(*hash));
+                        *hash = $h(&array.value(i).as_ref(), *hash);
                     }
                 }
             }
@@ -107,42 +266,31 @@ fn hash_array<T: num::PrimInt>(
 
             if array.null_count() == 0 {
                 for (hash, value) in $hashes.iter_mut().zip(values.iter()) {
-                    *hash = $h(
-                        (*value as $ty).to_le_bytes().as_ref(),
-                        initial_seed_or!(*hash),
-                    );
+                    *hash = $h((*value as $ty).to_le_bytes().as_ref(), *hash);
                 }
             } else {
                 for (i, (hash, value)) in $hashes.iter_mut().zip(values.iter()).enumerate() {
                     if !array.is_null(i) {
-                        *hash = $h(
-                            (*value as $ty).to_le_bytes().as_ref(),
-                            initial_seed_or!(*hash),
-                        );
+                        *hash = $h((*value as $ty).to_le_bytes().as_ref(), *hash);
                     }
                 }
             }
         };
     }
 
-    macro_rules! hash_array_decimal {
-        ($array_type:ident, $column:ident, $hashes:ident, $h:expr) => {
+    macro_rules! hash_array_mapped {
+        ($array_type:ident, $column:ident, $hashes:ident, $h:expr, $to_bytes:expr) => {
             let array = $column.as_any().downcast_ref::<$array_type>().unwrap();
+            let values = array.values();
 
             if array.null_count() == 0 {
-                for (i, hash) in $hashes.iter_mut().enumerate() {
-                    *hash = $h(
-                        array.value(i).to_le_bytes().as_ref(),
-                        initial_seed_or!(*hash),
-                    );
+                for (hash, &value) in $hashes.iter_mut().zip(values.iter()) {
+                    *hash = $h($to_bytes(value).as_ref(), *hash);
                 }
             } else {
-                for (i, hash) in $hashes.iter_mut().enumerate() {
+                for (i, (hash, &value)) in $hashes.iter_mut().zip(values.iter()).enumerate() {
                     if !array.is_null(i) {
-                        *hash = $h(
-                            array.value(i).to_le_bytes().as_ref(),
-                            initial_seed_or!(*hash),
-                        );
+                        *hash = $h($to_bytes(value).as_ref(), *hash);
                     }
                 }
             }
@@ -153,25 +301,9 @@ fn hash_array<T: num::PrimInt>(
         DataType::Null => {}
         DataType::Boolean => {
             let array = array.as_any().downcast_ref::<BooleanArray>().unwrap();
-            if array.null_count() == 0 {
-                for (i, hash) in hashes_buffer.iter_mut().enumerate() {
-                    *hash = h(
-                        (if array.value(i) { 1u32 } else { 0u32 })
-                            .to_le_bytes()
-                            .as_ref(),
-                        initial_seed_or!(*hash),
-                    );
-                }
-            } else {
-                for (i, hash) in hashes_buffer.iter_mut().enumerate() {
-                    if !array.is_null(i) {
-                        *hash = h(
-                            (if array.value(i) { 1u32 } else { 0u32 })
-                                .to_le_bytes()
-                                .as_ref(),
-                            initial_seed_or!(*hash),
-                        );
-                    }
+            for (i, hash) in hashes_buffer.iter_mut().enumerate() {
+                if array.is_valid(i) {
+                    *hash = h((array.value(i) as i32).to_le_bytes().as_ref(), *hash);
                 }
             }
         }
@@ -188,10 +320,14 @@ fn hash_array<T: num::PrimInt>(
             hash_array_primitive!(Int64Array, array, i64, hashes_buffer, h);
         }
         DataType::Float32 => {
-            hash_array_primitive!(Float32Array, array, f32, hashes_buffer, h);
+            hash_array_mapped!(Float32Array, array, hashes_buffer, h, |v: f32| {
+                java_float_bits(v).to_le_bytes()
+            });
         }
         DataType::Float64 => {
-            hash_array_primitive!(Float64Array, array, f64, hashes_buffer, h);
+            hash_array_mapped!(Float64Array, array, hashes_buffer, h, |v: f64| {
+                java_double_bits(v).to_le_bytes()
+            });
         }
         DataType::Timestamp(TimeUnit::Second, _) => {
             hash_array_primitive!(TimestampSecondArray, array, i64, hashes_buffer, h);
@@ -223,38 +359,22 @@ fn hash_array<T: num::PrimInt>(
         DataType::LargeUtf8 => {
             hash_array!(LargeStringArray, array, hashes_buffer, h);
         }
-        DataType::Decimal128(..) => {
-            hash_array_decimal!(Decimal128Array, array, hashes_buffer, h);
+        DataType::Utf8View => {
+            hash_array!(StringViewArray, array, hashes_buffer, h);
+        }
+        DataType::BinaryView => {
+            hash_array!(BinaryViewArray, array, hashes_buffer, h);
+        }
+        &DataType::Decimal128(precision, _) => {
+            hash_array_mapped!(Decimal128Array, array, hashes_buffer, h, |v: i128| {
+                DecimalHashBytes::new(v, precision)
+            });
         }
         DataType::Dictionary(index_type, _) => match index_type.as_ref() {
-            DataType::Int8 => create_hashes_dictionary::<Int8Type, _>(
-                array,
-                hashes_buffer,
-                initial_seed,
-                is_initial,
-                h,
-            ),
-            DataType::Int16 => create_hashes_dictionary::<Int16Type, _>(
-                array,
-                hashes_buffer,
-                initial_seed,
-                is_initial,
-                h,
-            ),
-            DataType::Int32 => create_hashes_dictionary::<Int32Type, _>(
-                array,
-                hashes_buffer,
-                initial_seed,
-                is_initial,
-                h,
-            ),
-            DataType::Int64 => create_hashes_dictionary::<Int64Type, _>(
-                array,
-                hashes_buffer,
-                initial_seed,
-                is_initial,
-                h,
-            ),
+            DataType::Int8 => create_hashes_dictionary::<Int8Type, _>(array, hashes_buffer, h),
+            DataType::Int16 => create_hashes_dictionary::<Int16Type, _>(array, hashes_buffer, h),
+            DataType::Int32 => create_hashes_dictionary::<Int32Type, _>(array, hashes_buffer, h),
+            DataType::Int64 => create_hashes_dictionary::<Int64Type, _>(array, hashes_buffer, h),
             other => panic!("Unsupported dictionary type in hasher hashing: {other}"),
         },
         _ => {
@@ -270,16 +390,10 @@ fn hash_array<T: num::PrimInt>(
 fn create_hashes_dictionary<K: ArrowDictionaryKeyType, T: num::PrimInt>(
     array: &ArrayRef,
     hashes_buffer: &mut [T],
-    initial_seed: T,
-    is_initial: bool,
     h: impl Fn(&[u8], T) -> T + Copy,
 ) {
     let dict_array = array.as_any().downcast_ref::<DictionaryArray<K>>().unwrap();
 
-    if is_initial {
-        hashes_buffer.fill(initial_seed);
-    }
-
     // Hash each dictionary value once, and then use that computed
     // hash for each key value to avoid a potentially expensive
     // redundant hashing for large dictionary elements (e.g. strings)
@@ -300,24 +414,21 @@ fn hash_one<T: num::PrimInt>(
     macro_rules! hash_one_primitive {
         ($array_type:ident, $column:ident, $ty:ident, $hash:ident, $idx:ident, $h:expr) => {
             let array = $column.as_any().downcast_ref::<$array_type>().unwrap();
-            *$hash = $h(
-                (array.value($idx as usize) as $ty).to_le_bytes().as_ref(),
-                *$hash,
-            );
+            *$hash = $h((array.value($idx) as $ty).to_le_bytes().as_ref(), *$hash);
         };
     }
 
-    macro_rules! hash_one_binary {
-        ($array_type:ident, $column:ident, $hash:ident, $idx:ident, $h:expr) => {
+    macro_rules! hash_one_mapped {
+        ($array_type:ident, $column:ident, $hash:ident, $idx:ident, $h:expr, $to_bytes:expr) => {
             let array = $column.as_any().downcast_ref::<$array_type>().unwrap();
-            *$hash = $h(&array.value($idx as usize).as_ref(), *$hash);
+            *$hash = $h($to_bytes(array.value($idx)).as_ref(), *$hash);
         };
     }
 
-    macro_rules! hash_one_decimal {
+    macro_rules! hash_one_binary {
         ($array_type:ident, $column:ident, $hash:ident, $idx:ident, $h:expr) => {
             let array = $column.as_any().downcast_ref::<$array_type>().unwrap();
-            *$hash = $h(array.value($idx as usize).to_le_bytes().as_ref(), *$hash);
+            *$hash = $h(&array.value($idx as usize).as_ref(), *$hash);
         };
     }
 
@@ -326,12 +437,7 @@ fn hash_one<T: num::PrimInt>(
             DataType::Null => {}
             DataType::Boolean => {
                 let array = col.as_any().downcast_ref::<BooleanArray>().unwrap();
-                *hash = h(
-                    (if array.value(idx) { 1u32 } else { 0u32 })
-                        .to_le_bytes()
-                        .as_ref(),
-                    *hash,
-                );
+                *hash = h((array.value(idx) as i32).to_le_bytes().as_ref(), *hash);
             }
             DataType::Int8 => {
                 hash_one_primitive!(Int8Array, col, i32, hash, idx, h);
@@ -346,18 +452,22 @@ fn hash_one<T: num::PrimInt>(
                 hash_one_primitive!(Int64Array, col, i64, hash, idx, h);
             }
             DataType::Float32 => {
-                hash_one_primitive!(Float32Array, col, f32, hash, idx, h);
+                hash_one_mapped!(Float32Array, col, hash, idx, h, |v: f32| {
+                    java_float_bits(v).to_le_bytes()
+                });
             }
             DataType::Float64 => {
-                hash_one_primitive!(Float64Array, col, f64, hash, idx, h);
+                hash_one_mapped!(Float64Array, col, hash, idx, h, |v: f64| {
+                    java_double_bits(v).to_le_bytes()
+                });
             }
-            DataType::Timestamp(TimeUnit::Second, None) => {
+            DataType::Timestamp(TimeUnit::Second, _) => {
                 hash_one_primitive!(TimestampSecondArray, col, i64, hash, idx, h);
             }
-            DataType::Timestamp(TimeUnit::Millisecond, None) => {
+            DataType::Timestamp(TimeUnit::Millisecond, _) => {
                 hash_one_primitive!(TimestampMillisecondArray, col, i64, hash, idx, h);
             }
-            DataType::Timestamp(TimeUnit::Microsecond, None) => {
+            DataType::Timestamp(TimeUnit::Microsecond, _) => {
                 hash_one_primitive!(TimestampMicrosecondArray, col, i64, hash, idx, h);
             }
             DataType::Timestamp(TimeUnit::Nanosecond, _) => {
@@ -381,8 +491,16 @@ fn hash_one<T: num::PrimInt>(
             DataType::LargeUtf8 => {
                 hash_one_binary!(LargeStringArray, col, hash, idx, h);
             }
-            DataType::Decimal128(..) => {
-                hash_one_decimal!(Decimal128Array, col, hash, idx, h);
+            DataType::Utf8View => {
+                hash_one_binary!(StringViewArray, col, hash, idx, h);
+            }
+            DataType::BinaryView => {
+                hash_one_binary!(BinaryViewArray, col, hash, idx, h);
+            }
+            &DataType::Decimal128(precision, _) => {
+                hash_one_mapped!(Decimal128Array, col, hash, idx, h, |v: i128| {
+                    DecimalHashBytes::new(v, precision)
+                });
             }
             DataType::List(..) => {
                 let list_array = col.as_any().downcast_ref::<ListArray>().unwrap();
@@ -418,11 +536,12 @@ mod tests {
 
     use arrow::{
         array::{
-            make_array, Array, ArrayData, ArrayRef, Int32Array, Int64Array, Int8Array, MapArray,
-            StringArray, StructArray, UInt32Array,
+            make_array, Array, ArrayData, ArrayRef, Decimal128Array, DictionaryArray, Float32Array,
+            Float64Array, Int32Array, Int64Array, Int8Array, MapArray, StringArray, StructArray,
+            UInt32Array,
         },
         buffer::Buffer,
-        datatypes::{DataType, Field, ToByteSlice},
+        datatypes::{DataType, Field, Int32Type, ToByteSlice},
     };
 
     use super::*;
@@ -540,6 +659,153 @@ mod tests {
             -235771157374669727,
         ];
         assert_eq!(hashes, expected);
+
+        // view arrays are hashed by values
+        let view = arrow::compute::cast(i.as_ref(), &DataType::Utf8View).unwrap();
+        assert_eq!(create_xxhash64_hashes(5, &[view.clone()], 42), expected);
+        assert_eq!(
+            create_murmur3_hashes(5, &[view], 42),
+            create_murmur3_hashes(5, &[i], 42)
+        );
+    }
+
+    #[test]
+    fn test_nulls_and_floats() {
+        // nulls are skipped, like spark
+        let i = Arc::new(Int32Array::from(vec![None, Some(0)])) as ArrayRef;
+        let hashes = create_murmur3_hashes(2, &[i.clone(), i], 42);
+        let hash_0 = spark_compatible_murmur3_hash(0i32.to_le_bytes(), 42);
+        assert_eq!(
+            hashes,
+            vec![
+                42,
+                spark_compatible_murmur3_hash(0i32.to_le_bytes(), hash_0)
+            ]
+        );
+
+        // -0.0 is hashed as 0.0 and NaNs are canonicalized
+        let f = Arc::new(Float32Array::from(vec![
+            0.0,
+            -0.0,
+            f32::from_bits(0x7fc00001),
+        ])) as ArrayRef;
+        let hashes = create_murmur3_hashes(3, &[f], 42);
+        assert_eq!(hashes[0], 933211791); // spark: hash(0.0f)
+        assert_eq!(hashes[1], 933211791);
+        assert_eq!(
+            hashes[2],
+            spark_compatible_murmur3_hash(0x7fc00000i32.to_le_bytes(), 42)
+        );
+
+        let d = Arc::new(Float64Array::from(vec![0.0, -0.0])) as ArrayRef;
+        let hashes = create_murmur3_hashes(2, &[d], 42);
+        assert_eq!(hashes, vec![-1670924195, -1670924195]); // spark: hash(0.0d)
+    }
+
+    #[test]
+    fn test_decimal() {
+        // decimals with precision <= 18 are hashed as unscaled longs
+        let d = Arc::new(
+            Decimal128Array::from(vec![Some(1), None])
+                .with_precision_and_scale(10, 2)
+                .unwrap(),
+        ) as ArrayRef;
+        let l = Arc::new(Int64Array::from(vec![Some(1), None])) as ArrayRef;
+        assert_eq!(
+            create_murmur3_hashes(2, &[d], 42),
+            create_murmur3_hashes(2, &[l], 42)
+        );
+
+        // bigger decimals are hashed as bytes of java's BigInteger.toByteArray()
+        let cases: [(i128, &[u8]); 6] = [
+            (0, &[0x00]),
+            (-1, &[0xff]),
+            (128, &[0x00, 0x80]),
+            (-129, &[0xff, 0x7f]),
+            (32767, &[0x7f, 0xff]),
+            (-32768, &[0x80, 0x00]),
+        ];
+        for (unscaled, expected) in cases {
+            assert_eq!(DecimalHashBytes::new(unscaled, 38).as_ref(), expected);
+        }
+        let d = Arc::new(
+            Decimal128Array::from(vec![Some(128)])
+                .with_precision_and_scale(38, 0)
+                .unwrap(),
+        ) as ArrayRef;
+        assert_eq!(
+            create_murmur3_hashes(1, &[d], 42),
+            vec![spark_compatible_murmur3_hash([0x00, 0x80], 42)]
+        );
+    }
+
+    #[test]
+    fn test_struct_with_nulls() {
+        let fields = vec![
+            Arc::new(Field::new("a", DataType::Int32, true)),
+            Arc::new(Field::new("b", DataType::Utf8, true)),
+        ];
+        let s = Arc::new(StructArray::from(vec![
+            (
+                fields[0].clone(),
+                Arc::new(Int32Array::from(vec![Some(1), Some(1)])) as ArrayRef,
+            ),
+            (
+                fields[1].clone(),
+                Arc::new(StringArray::from(vec![None, Some("a")])) as ArrayRef,
+            ),
+        ])) as ArrayRef;
+        let a = Arc::new(Int32Array::from(vec![1, 1])) as ArrayRef;
+        let b = Arc::new(StringArray::from(vec![None, Some("a")])) as ArrayRef;
+
+        // struct fields are hashed like individual columns
+        assert_eq!(
+            create_murmur3_hashes(2, &[s.clone()], 42),
+            create_murmur3_hashes(2, &[a.clone(), b.clone()], 42),
+        );
+        assert_eq!(
+            create_xxhash64_hashes(2, &[s], 42),
+            create_xxhash64_hashes(2, &[a, b], 42),
+        );
+    }
+
+    #[test]
+    fn test_murmur3_batch_hashing() {
+        let h = |data: &[u8], seed: i32| spark_compatible_murmur3_hash(data, seed);
+        let dict = Arc::new(
+            vec![Some("a"), None, Some("b"), Some("a"), Some("c")]
+                .into_iter()
+                .collect::<DictionaryArray<Int32Type>>(),
+        ) as ArrayRef;
+        let columns: Vec<ArrayRef> = vec![
+            dict.clone(),
+            Arc::new(Int8Array::from(vec![
+                Some(1),
+                None,
+                Some(-1),
+                Some(2),
+                Some(3),
+            ])),
+            Arc::new(Int64Array::from(vec![Some(1), Some(2), None, Some(4), Some(5)]).slice(0, 5)),
+            Arc::new(Float64Array::from(vec![0.0, -0.0, 1.5, f64::NAN, -2.5])),
+            Arc::new(
+                Decimal128Array::from(vec![Some(100), None, Some(-1), Some(0), Some(7)])
+                    .with_precision_and_scale(10, 2)
+                    .unwrap(),
+            ),
+            dict,
+        ];
+
+        // batch hashing is identical to hashing row by row
+        assert_eq!(
+            create_murmur3_hashes(5, &columns, 42),
+            create_hashes(5, &columns, 42, h)
+        );
+        let sliced = columns.iter().map(|c| c.slice(1, 3)).collect::<Vec<_>>();
+        assert_eq!(
+            create_murmur3_hashes(3, &sliced, 42),
+            create_hashes(3, &sliced, 42, h)
+        );
     }
 
     #[test]
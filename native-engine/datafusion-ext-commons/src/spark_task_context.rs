@@ -0,0 +1,116 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    cell::RefCell,
+    sync::{
+        atomic::{AtomicBool, Ordering::Relaxed},
+        Arc, Mutex,
+    },
+};
+
+use datafusion::common::Result;
+
+use crate::df_execution_err;
+
+thread_local! {
+    static THREAD_SPARK_TASK_CONTEXT: RefCell<Option<Arc<SparkTaskContext>>> =
+        const { RefCell::new(None) };
+}
+
+/// spark task information used by context-dependent expressions, like
+/// spark_partition_id() and input_file_name(). the context is shared by all
+/// threads of the task's runtime.
+#[derive(Debug, Default)]
+pub struct SparkTaskContext {
+    stage_id: usize,
+    partition_id: usize,
+    task_attempt_id: i64,
+    input_file_name: Mutex<Arc<str>>,
+    cancelled: AtomicBool,
+}
+
+impl SparkTaskContext {
+    pub fn new(stage_id: usize, partition_id: usize, task_attempt_id: i64) -> Self {
+        Self {
+            stage_id,
+            partition_id,
+            task_attempt_id,
+            input_file_name: Mutex::new(Arc::from("")),
+            cancelled: AtomicBool::new(false),
+        }
+    }
+
+    /// sets the task context of current thread, called when starting threads
+    /// of the task's runtime
+    pub fn set_current(task_context: Option<Arc<Self>>) {
+        THREAD_SPARK_TASK_CONTEXT.with(|cur| *cur.borrow_mut() = task_context);
+    }
+
+    pub fn current() -> Option<Arc<Self>> {
+        THREAD_SPARK_TASK_CONTEXT.with(|cur| cur.borrow().clone())
+    }
+
+    /// returns an error if the task of current thread is cancelled. it is
+    /// cheap enough to be checked in long-running loops (like merging spills
+    /// or decoding files), so that killed tasks stop without waiting for the
+    /// next batch boundary.
+    pub fn check_cancelled() -> Result<()> {
+        if Self::is_current_cancelled() {
+            return df_execution_err!("task cancelled");
+        }
+        Ok(())
+    }
+
+    pub fn is_current_cancelled() -> bool {
+        THREAD_SPARK_TASK_CONTEXT
+            .with(|cur| cur.borrow().as_ref().is_some_and(|cur| cur.is_cancelled()))
+    }
+
+    /// marks the task as cancelled, called when the task is killed and always
+    /// called when the task is finalized. loops checking `check_cancelled()`
+    /// fail with "task cancelled" after that, even if the task has succeeded.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Relaxed)
+    }
+
+    pub fn stage_id(&self) -> usize {
+        self.stage_id
+    }
+
+    pub fn partition_id(&self) -> usize {
+        self.partition_id
+    }
+
+    pub fn task_attempt_id(&self) -> i64 {
+        self.task_attempt_id
+    }
+
+    /// name of the file currently being read by the scan operator, empty if
+    /// no files are read, like spark's InputFileBlockHolder
+    pub fn input_file_name(&self) -> Arc<str> {
+        self.input_file_name.lock().unwrap().clone()
+    }
+
+    pub fn set_input_file_name(&self, input_file_name: &str) {
+        let mut cur = self.input_file_name.lock().unwrap();
+        if cur.as_ref() != input_file_name {
+            *cur = Arc::from(input_file_name);
+        }
+    }
+}
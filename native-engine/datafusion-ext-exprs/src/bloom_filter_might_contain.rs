@@ -14,11 +14,10 @@
 
 use std::{
     any::Any,
-    collections::HashMap,
     fmt::{Debug, Display, Formatter},
     hash::Hasher,
     io::Cursor,
-    sync::{Arc, Weak},
+    sync::Arc,
 };
 
 use arrow::{
@@ -31,10 +30,10 @@ use datafusion::{
     physical_expr::PhysicalExpr,
 };
 use datafusion_ext_commons::{
-    arrow::cast::cast, df_execution_err, df_unimplemented_err, spark_bloom_filter::SparkBloomFilter,
+    arrow::cast::cast, constant_cache::get_or_init_shared, df_execution_err, df_unimplemented_err,
+    spark_bloom_filter::SparkBloomFilter,
 };
 use once_cell::sync::OnceCell;
-use parking_lot::Mutex;
 
 pub struct BloomFilterMightContainExpr {
     uuid: String,
@@ -58,13 +57,6 @@ impl BloomFilterMightContainExpr {
     }
 }
 
-impl Drop for BloomFilterMightContainExpr {
-    fn drop(&mut self) {
-        drop(self.bloom_filter.take());
-        clear_cached_bloom_filter();
-    }
-}
-
 impl Display for BloomFilterMightContainExpr {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "{self:?}")
@@ -106,7 +98,7 @@ impl PhysicalExpr for BloomFilterMightContainExpr {
     fn evaluate(&self, batch: &RecordBatch) -> Result<ColumnarValue> {
         // init bloom filter
         let bloom_filter = self.bloom_filter.get_or_try_init(|| {
-            get_cached_bloom_filter(&self.uuid, || {
+            get_or_init_shared(&self.uuid, || {
                 match self.bloom_filter_expr.evaluate(batch)? {
                     ColumnarValue::Scalar(ScalarValue::Binary(Some(v))) => {
                         Ok(SparkBloomFilter::read_from(&mut Cursor::new(v.as_slice()))?)
@@ -157,36 +149,3 @@ impl PhysicalExpr for BloomFilterMightContainExpr {
         self.value_expr.dyn_hash(state);
     }
 }
-
-type Slot = Arc<Mutex<Weak<SparkBloomFilter>>>;
-static CACHED_BLOOM_FILTER: OnceCell<Arc<Mutex<HashMap<String, Slot>>>> = OnceCell::new();
-
-fn get_cached_bloom_filter(
-    uuid: &str,
-    init: impl FnOnce() -> Result<SparkBloomFilter>,
-) -> Result<Arc<SparkBloomFilter>> {
-    // remove expire keys and insert new key
-    let slot = {
-        let cached_bloom_filter = CACHED_BLOOM_FILTER.get_or_init(|| Arc::default());
-        let mut cached_bloom_filter = cached_bloom_filter.lock();
-        cached_bloom_filter
-            .entry(uuid.to_string())
-            .or_default()
-            .clone()
-    };
-
-    let mut slot = slot.lock();
-    if let Some(cached) = slot.upgrade() {
-        Ok(cached)
-    } else {
-        let new = Arc::new(init()?);
-        *slot = Arc::downgrade(&new);
-        Ok(new)
-    }
-}
-
-fn clear_cached_bloom_filter() {
-    let cached_bloom_filter = CACHED_BLOOM_FILTER.get_or_init(|| Arc::default());
-    let mut cached_bloom_filter = cached_bloom_filter.lock();
-    cached_bloom_filter.retain(|_, v| Arc::strong_count(v) > 0);
-}
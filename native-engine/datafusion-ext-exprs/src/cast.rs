@@ -19,10 +19,14 @@ use std::{
     sync::Arc,
 };
 
-use arrow::{datatypes::*, record_batch::RecordBatch};
+use arrow::{array::ArrayRef, datatypes::*, record_batch::RecordBatch};
 use datafusion::{
     common::Result, logical_expr::ColumnarValue, physical_expr::PhysicalExpr, scalar::ScalarValue,
 };
+use datafusion_ext_commons::{
+    arrow::spark_cast::{parse_zone_id, spark_cast, spark_cast_with_time_zone, CastMode},
+    df_execution_err,
+};
 
 use crate::down_cast_any_ref;
 
@@ -31,26 +35,67 @@ use crate::down_cast_any_ref;
 pub struct TryCastExpr {
     pub expr: Arc<dyn PhysicalExpr>,
     pub cast_type: DataType,
+    pub mode: CastMode,
+    pub time_zone: Option<String>,
 }
 
 impl PartialEq<dyn Any> for TryCastExpr {
     fn eq(&self, other: &dyn Any) -> bool {
         down_cast_any_ref(other)
             .downcast_ref::<Self>()
-            .map(|x| self.expr.eq(&x.expr) && self.cast_type == x.cast_type)
+            .map(|x| {
+                self.expr.eq(&x.expr)
+                    && self.cast_type == x.cast_type
+                    && self.mode == x.mode
+                    && self.time_zone == x.time_zone
+            })
             .unwrap_or(false)
     }
 }
 
 impl TryCastExpr {
     pub fn new(expr: Arc<dyn PhysicalExpr>, cast_type: DataType) -> Self {
-        Self { expr, cast_type }
+        Self {
+            expr,
+            cast_type,
+            mode: CastMode::default(),
+            time_zone: None,
+        }
+    }
+
+    pub fn with_cast_mode(mut self, mode: CastMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// sets the session time zone for casts involving timestamps, UTC is used
+    /// if not set
+    pub fn with_time_zone(mut self, time_zone: Option<String>) -> Self {
+        self.time_zone = time_zone;
+        self
+    }
+
+    fn cast(&self, array: &ArrayRef) -> Result<ArrayRef> {
+        match &self.time_zone {
+            Some(time_zone) => match parse_zone_id(time_zone) {
+                Some(tz) => spark_cast_with_time_zone(array, &self.cast_type, self.mode, &tz),
+                None => df_execution_err!("cast: invalid time zone: {time_zone}"),
+            },
+            None => spark_cast(array, &self.cast_type, self.mode),
+        }
     }
 }
 
 impl Display for TryCastExpr {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "cast({} AS {:?})", self.expr, self.cast_type)
+        match self.mode {
+            CastMode::Legacy => write!(f, "cast({} AS {:?})", self.expr, self.cast_type),
+            mode => write!(
+                f,
+                "cast({} AS {:?}, mode={mode:?})",
+                self.expr, self.cast_type
+            ),
+        }
     }
 }
 
@@ -69,15 +114,10 @@ impl PhysicalExpr for TryCastExpr {
 
     fn evaluate(&self, batch: &RecordBatch) -> Result<ColumnarValue> {
         Ok(match self.expr.evaluate(batch)? {
-            ColumnarValue::Array(array) => ColumnarValue::Array(
-                datafusion_ext_commons::arrow::cast::cast(&array, &self.cast_type)?,
-            ),
+            ColumnarValue::Array(array) => ColumnarValue::Array(self.cast(&array)?),
             ColumnarValue::Scalar(scalar) => {
                 let array = scalar.to_array()?;
-                ColumnarValue::Scalar(ScalarValue::try_from_array(
-                    &datafusion_ext_commons::arrow::cast::cast(&array, &self.cast_type)?,
-                    0,
-                )?)
+                ColumnarValue::Scalar(ScalarValue::try_from_array(&self.cast(&array)?, 0)?)
             }
         })
     }
@@ -90,10 +130,11 @@ impl PhysicalExpr for TryCastExpr {
         self: Arc<Self>,
         children: Vec<Arc<dyn PhysicalExpr>>,
     ) -> Result<Arc<dyn PhysicalExpr>> {
-        Ok(Arc::new(Self::new(
-            children[0].clone(),
-            self.cast_type.clone(),
-        )))
+        Ok(Arc::new(
+            Self::new(children[0].clone(), self.cast_type.clone())
+                .with_cast_mode(self.mode)
+                .with_time_zone(self.time_zone.clone()),
+        ))
     }
 
     fn dyn_hash(&self, state: &mut dyn Hasher) {
@@ -106,8 +147,8 @@ mod test {
     use std::sync::Arc;
 
     use arrow::{
-        array::{ArrayRef, Float32Array, Int32Array, StringArray},
-        datatypes::{DataType, Field, Schema},
+        array::{ArrayRef, Float32Array, Int32Array, StringArray, TimestampMicrosecondArray},
+        datatypes::{DataType, Field, Schema, TimeUnit},
         record_batch::RecordBatch,
     };
     use datafusion::physical_expr::{expressions as phys_expr, PhysicalExpr};
@@ -236,4 +277,40 @@ mod test {
         ]));
         assert_eq!(&ret, &expected);
     }
+
+    #[test]
+    fn test_time_zone() {
+        // input: Array
+        // cast Utf8 into Timestamp in session time zone
+        let string_arr: ArrayRef = Arc::new(StringArray::from(vec![
+            Some("2020-01-01 12:34:56"),
+            Some("2020-01-01 12:34:56Z"),
+            None,
+        ]));
+
+        let schema = Arc::new(Schema::new(vec![Field::new("col", DataType::Utf8, true)]));
+
+        let batch =
+            RecordBatch::try_new(schema, vec![string_arr]).expect("Error creating RecordBatch");
+
+        let cast_type = DataType::Timestamp(TimeUnit::Microsecond, None);
+
+        let expr = Arc::new(
+            TryCastExpr::new(phys_expr::col("col", &batch.schema()).unwrap(), cast_type)
+                .with_time_zone(Some("America/Los_Angeles".to_string())),
+        );
+
+        let ret = expr
+            .evaluate(&batch)
+            .expect("Error evaluating expr")
+            .into_array(batch.num_rows())
+            .unwrap();
+
+        let expected: ArrayRef = Arc::new(TimestampMicrosecondArray::from(vec![
+            Some(1577910896000000),
+            Some(1577882096000000),
+            None,
+        ]));
+        assert_eq!(&ret, &expected);
+    }
 }
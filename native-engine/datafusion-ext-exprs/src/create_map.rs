@@ -0,0 +1,310 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Debug, Formatter},
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use arrow::{
+    array::{Array, ArrayRef, MapArray, StructArray},
+    buffer::OffsetBuffer,
+    compute::{cast, interleave},
+    datatypes::{DataType, Schema},
+    record_batch::RecordBatch,
+    row::{RowConverter, SortField},
+};
+use datafusion::{common::Result, logical_expr::ColumnarValue, physical_expr::PhysicalExpr};
+use datafusion_ext_commons::df_execution_err;
+
+use crate::down_cast_any_ref;
+
+/// expression to create a map from key/value pairs, like spark's map().
+/// null keys are not allowed, duplicated keys are either rejected or the
+/// last value wins (spark.sql.mapKeyDedupPolicy=LAST_WIN).
+#[derive(Debug, Hash)]
+pub struct CreateMapExpr {
+    args: Vec<Arc<dyn PhysicalExpr>>,
+    return_type: DataType,
+    dedup_last_win: bool,
+}
+
+impl CreateMapExpr {
+    pub fn try_new(
+        args: Vec<Arc<dyn PhysicalExpr>>,
+        return_type: DataType,
+        dedup_last_win: bool,
+    ) -> Result<Self> {
+        if args.len() % 2 != 0 {
+            return df_execution_err!("CreateMap expects even number of arguments");
+        }
+        if !matches!(return_type, DataType::Map(..)) {
+            return df_execution_err!(
+                "CreateMap expects returning map type, but got {return_type}"
+            );
+        }
+        Ok(Self {
+            args,
+            return_type,
+            dedup_last_win,
+        })
+    }
+}
+
+impl std::fmt::Display for CreateMapExpr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CreateMap(")?;
+        for (i, arg) in self.args.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{arg}")?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl PartialEq<dyn Any> for CreateMapExpr {
+    fn eq(&self, other: &dyn Any) -> bool {
+        down_cast_any_ref(other)
+            .downcast_ref::<Self>()
+            .map(|x| {
+                self.args.len() == x.args.len()
+                    && self.args.iter().zip(&x.args).all(|(a, b)| a.eq(b))
+                    && self.return_type == x.return_type
+                    && self.dedup_last_win == x.dedup_last_win
+            })
+            .unwrap_or(false)
+    }
+}
+
+impl PhysicalExpr for CreateMapExpr {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn data_type(&self, _input_schema: &Schema) -> Result<DataType> {
+        Ok(self.return_type.clone())
+    }
+
+    fn nullable(&self, _input_schema: &Schema) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn evaluate(&self, batch: &RecordBatch) -> Result<ColumnarValue> {
+        let num_rows = batch.num_rows();
+        let DataType::Map(entries_field, sorted) = &self.return_type else {
+            unreachable!()
+        };
+        let DataType::Struct(entry_fields) = entries_field.data_type() else {
+            return df_execution_err!("CreateMap expects struct entries, got {entries_field}");
+        };
+
+        let mut keys = vec![];
+        let mut values = vec![];
+        for pair in self.args.chunks(2) {
+            let key = pair[0].evaluate(batch)?.into_array(num_rows)?;
+            let value = pair[1].evaluate(batch)?.into_array(num_rows)?;
+            if key.null_count() > 0 {
+                return df_execution_err!("Cannot use null as map key");
+            }
+            keys.push(cast(&key, entry_fields[0].data_type())?);
+            values.push(cast(&value, entry_fields[1].data_type())?);
+        }
+
+        // collect (key_idx, value_idx) of each entry, the first key position is kept
+        // for duplicated keys
+        let mut entries = Vec::with_capacity(num_rows * keys.len());
+        let mut lengths = Vec::with_capacity(num_rows);
+        if keys.len() <= 1 {
+            for row_idx in 0..num_rows {
+                entries.extend((0..keys.len()).map(|i| ((i, row_idx), (i, row_idx))));
+                lengths.push(keys.len());
+            }
+        } else {
+            let converter =
+                RowConverter::new(vec![SortField::new(entry_fields[0].data_type().clone())])?;
+            let key_rows = keys
+                .iter()
+                .map(|key| converter.convert_columns(&[key.clone()]))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            for row_idx in 0..num_rows {
+                let row_start = entries.len();
+                for i in 0..keys.len() {
+                    let key_row = key_rows[i].row(row_idx);
+                    let dup = entries[row_start..]
+                        .iter_mut()
+                        .find(|(key, _)| key_rows[key.0].row(row_idx) == key_row);
+                    match dup {
+                        Some(_) if !self.dedup_last_win => {
+                            return df_execution_err!(
+                                "Duplicate map key was found, please check the input data. \
+                                 If you want to remove the duplicated keys, you can set \
+                                 spark.sql.mapKeyDedupPolicy to LAST_WIN so that the key \
+                                 inserted at last takes precedence."
+                            );
+                        }
+                        Some((_, value)) => *value = (i, row_idx),
+                        None => entries.push(((i, row_idx), (i, row_idx))),
+                    }
+                }
+                lengths.push(entries.len() - row_start);
+            }
+        }
+
+        let (key_indices, value_indices): (Vec<_>, Vec<_>) = entries.into_iter().unzip();
+        let (keys, values) = if self.args.is_empty() {
+            (
+                arrow::array::new_empty_array(entry_fields[0].data_type()),
+                arrow::array::new_empty_array(entry_fields[1].data_type()),
+            )
+        } else {
+            let keys = keys.iter().map(|k| k.as_ref()).collect::<Vec<_>>();
+            let values = values.iter().map(|v| v.as_ref()).collect::<Vec<_>>();
+            (
+                interleave(&keys, &key_indices)?,
+                interleave(&values, &value_indices)?,
+            )
+        };
+        let entries = StructArray::try_new(entry_fields.clone(), vec![keys, values], None)?;
+        let map: ArrayRef = Arc::new(MapArray::try_new(
+            entries_field.clone(),
+            OffsetBuffer::from_lengths(lengths),
+            entries,
+            None,
+            *sorted,
+        )?);
+        Ok(ColumnarValue::Array(map))
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn PhysicalExpr>> {
+        self.args.iter().collect()
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn PhysicalExpr>>,
+    ) -> Result<Arc<dyn PhysicalExpr>> {
+        Ok(Arc::new(Self::try_new(
+            children,
+            self.return_type.clone(),
+            self.dedup_last_win,
+        )?))
+    }
+
+    fn dyn_hash(&self, state: &mut dyn Hasher) {
+        let mut s = state;
+        self.hash(&mut s);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{
+            Array, ArrayRef, AsArray, Int32Array, Int32Builder, MapBuilder, StringArray,
+            StringBuilder,
+        },
+        datatypes::{DataType, Field, Fields},
+        record_batch::RecordBatch,
+    };
+    use datafusion::{
+        common::Result,
+        physical_expr::{expressions::col, PhysicalExpr},
+    };
+
+    use crate::create_map::CreateMapExpr;
+
+    fn map_type() -> DataType {
+        let entries = Fields::from(vec![
+            Field::new("keys", DataType::Utf8, false),
+            Field::new("values", DataType::Int32, true),
+        ]);
+        DataType::Map(
+            Arc::new(Field::new("entries", DataType::Struct(entries), false)),
+            false,
+        )
+    }
+
+    fn test_batch() -> Result<RecordBatch> {
+        let k1: ArrayRef = Arc::new(StringArray::from(vec!["a", "b", "c"]));
+        let v1: ArrayRef = Arc::new(Int32Array::from(vec![Some(1), None, Some(3)]));
+        let k2: ArrayRef = Arc::new(StringArray::from(vec!["x", "b", "y"]));
+        let v2: ArrayRef = Arc::new(Int32Array::from(vec![10, 20, 30]));
+        Ok(RecordBatch::try_from_iter(vec![
+            ("k1", k1),
+            ("v1", v1),
+            ("k2", k2),
+            ("v2", v2),
+        ])?)
+    }
+
+    #[test]
+    fn test_create_map() -> Result<()> {
+        let batch = test_batch()?;
+        let schema = batch.schema();
+        let args = ["k1", "v1", "k2", "v2"]
+            .iter()
+            .map(|name| col(name, &schema))
+            .collect::<Result<Vec<_>>>()?;
+
+        // duplicated keys are rejected by default
+        let expr = CreateMapExpr::try_new(args.clone(), map_type(), false)?;
+        assert!(expr.evaluate(&batch).is_err());
+
+        // the last value wins
+        let expr = CreateMapExpr::try_new(args, map_type(), true)?;
+        let output = expr.evaluate(&batch)?.into_array(batch.num_rows())?;
+
+        let mut builder = MapBuilder::new(None, StringBuilder::new(), Int32Builder::new());
+        for entries in [
+            vec![("a", Some(1)), ("x", Some(10))],
+            vec![("b", Some(20))],
+            vec![("c", Some(3)), ("y", Some(30))],
+        ] {
+            for (key, value) in entries {
+                builder.keys().append_value(key);
+                builder.values().append_option(value);
+            }
+            builder.append(true)?;
+        }
+        let expected: ArrayRef = Arc::new(builder.finish());
+        assert_eq!(&output, &expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_empty_map_and_null_keys() -> Result<()> {
+        let batch = test_batch()?;
+        let schema = batch.schema();
+
+        let expr = CreateMapExpr::try_new(vec![], map_type(), false)?;
+        let output = expr.evaluate(&batch)?.into_array(batch.num_rows())?;
+        assert_eq!(output.len(), 3);
+        assert_eq!(output.as_map().entries().len(), 0);
+
+        let expr = CreateMapExpr::try_new(
+            vec![col("v1", &schema)?, col("k1", &schema)?],
+            map_type(),
+            false,
+        )?;
+        assert!(expr.evaluate(&batch).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,362 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Display, Formatter},
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use arrow::{
+    array::{Array, AsArray, Decimal128Array, Decimal128Builder},
+    datatypes::{i256, DataType, Decimal128Type, DecimalType, Schema},
+    record_batch::RecordBatch,
+};
+use datafusion::{
+    common::{Result, ScalarValue},
+    logical_expr::ColumnarValue,
+    physical_expr::PhysicalExpr,
+};
+use datafusion_ext_commons::{
+    df_execution_err, df_spark_err,
+    spark_error::{DIVIDE_BY_ZERO, NUMERIC_VALUE_OUT_OF_RANGE},
+};
+
+use crate::down_cast_any_ref;
+
+/// scale of intermediate quotients, same as spark's `DecimalType.MAX_SCALE`
+const DIVIDE_SCALE: i32 = 38;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DecimalArithmeticOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+}
+
+/// decimal arithmetic compatible with spark. the result type is decided with
+/// spark's precision promotion rules, and the exact result is rounded half up
+/// to the result scale like `Decimal.toPrecision()`
+#[derive(Debug, Hash)]
+pub struct DecimalArithmeticExpr {
+    lhs: Arc<dyn PhysicalExpr>,
+    rhs: Arc<dyn PhysicalExpr>,
+    op: DecimalArithmeticOp,
+    return_type: DataType,
+    fail_on_overflow: bool,
+}
+
+impl PartialEq<dyn Any> for DecimalArithmeticExpr {
+    fn eq(&self, other: &dyn Any) -> bool {
+        down_cast_any_ref(other)
+            .downcast_ref::<Self>()
+            .map(|x| {
+                self.lhs.eq(&x.lhs)
+                    && self.rhs.eq(&x.rhs)
+                    && self.op == x.op
+                    && self.return_type == x.return_type
+                    && self.fail_on_overflow == x.fail_on_overflow
+            })
+            .unwrap_or(false)
+    }
+}
+
+impl DecimalArithmeticExpr {
+    pub fn new(
+        lhs: Arc<dyn PhysicalExpr>,
+        rhs: Arc<dyn PhysicalExpr>,
+        op: DecimalArithmeticOp,
+        return_type: DataType,
+    ) -> Self {
+        Self {
+            lhs,
+            rhs,
+            op,
+            return_type,
+            fail_on_overflow: false,
+        }
+    }
+
+    /// overflows and divisions by zero are errors instead of nulls, like spark
+    /// in ansi mode
+    pub fn with_fail_on_overflow(mut self, fail_on_overflow: bool) -> Self {
+        self.fail_on_overflow = fail_on_overflow;
+        self
+    }
+}
+
+impl Display for DecimalArithmeticExpr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Decimal{:?}({}, {}, {:?})",
+            self.op, self.lhs, self.rhs, self.return_type
+        )
+    }
+}
+
+impl PhysicalExpr for DecimalArithmeticExpr {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn data_type(&self, _input_schema: &Schema) -> Result<DataType> {
+        Ok(self.return_type.clone())
+    }
+
+    fn nullable(&self, _input_schema: &Schema) -> Result<bool> {
+        Ok(true)
+    }
+
+    fn evaluate(&self, batch: &RecordBatch) -> Result<ColumnarValue> {
+        let &DataType::Decimal128(precision, scale) = &self.return_type else {
+            return df_execution_err!("decimal arithmetic: invalid type: {}", self.return_type);
+        };
+        let lhs = self.lhs.evaluate(batch)?;
+        let rhs = self.rhs.evaluate(batch)?;
+        let is_scalar = matches!(
+            (&lhs, &rhs),
+            (ColumnarValue::Scalar(_), ColumnarValue::Scalar(_))
+        );
+        let num_rows = if is_scalar { 1 } else { batch.num_rows() };
+        let lhs = lhs.into_array(num_rows)?;
+        let rhs = rhs.into_array(num_rows)?;
+        let (Some(lhs), Some(rhs)) = (
+            lhs.as_primitive_opt::<Decimal128Type>(),
+            rhs.as_primitive_opt::<Decimal128Type>(),
+        ) else {
+            return df_execution_err!(
+                "decimal arithmetic: invalid operand types: {}, {}",
+                lhs.data_type(),
+                rhs.data_type(),
+            );
+        };
+
+        let result =
+            decimal_arithmetic(self.op, lhs, rhs, precision, scale, self.fail_on_overflow)?;
+        if is_scalar {
+            return Ok(ColumnarValue::Scalar(ScalarValue::try_from_array(
+                &result, 0,
+            )?));
+        }
+        Ok(ColumnarValue::Array(Arc::new(result)))
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn PhysicalExpr>> {
+        vec![&self.lhs, &self.rhs]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn PhysicalExpr>>,
+    ) -> Result<Arc<dyn PhysicalExpr>> {
+        Ok(Arc::new(
+            Self::new(
+                children[0].clone(),
+                children[1].clone(),
+                self.op,
+                self.return_type.clone(),
+            )
+            .with_fail_on_overflow(self.fail_on_overflow),
+        ))
+    }
+
+    fn dyn_hash(&self, state: &mut dyn Hasher) {
+        let mut s = state;
+        self.hash(&mut s);
+    }
+}
+
+/// computes `lhs op rhs` on decimal arrays, with the result rounded to the
+/// given precision and scale. overflowed results and divisions by zero are
+/// nulls, or errors if `fail_on_overflow` is set
+pub fn decimal_arithmetic(
+    op: DecimalArithmeticOp,
+    lhs: &Decimal128Array,
+    rhs: &Decimal128Array,
+    precision: u8,
+    scale: i8,
+    fail_on_overflow: bool,
+) -> Result<Decimal128Array> {
+    let (lhs_scale, rhs_scale) = (lhs.scale() as i32, rhs.scale() as i32);
+    let max_unscaled = pow10(precision as i32).expect("invalid decimal precision");
+    let mut builder = Decimal128Builder::with_capacity(lhs.len())
+        .with_data_type(DataType::Decimal128(precision, scale));
+
+    for (l, r) in lhs.iter().zip(rhs.iter()) {
+        let (Some(l), Some(r)) = (l, r) else {
+            builder.append_null();
+            continue;
+        };
+        if r == 0
+            && matches!(
+                op,
+                DecimalArithmeticOp::Divide | DecimalArithmeticOp::Remainder
+            )
+        {
+            if fail_on_overflow {
+                return df_spark_err!(DIVIDE_BY_ZERO, "division by zero");
+            }
+            builder.append_null();
+            continue;
+        }
+
+        let (l, r) = (i256::from_i128(l), i256::from_i128(r));
+        let result = match op {
+            DecimalArithmeticOp::Add | DecimalArithmeticOp::Subtract => {
+                let common_scale = lhs_scale.max(rhs_scale);
+                rescale(l, lhs_scale, common_scale)
+                    .zip(rescale(r, rhs_scale, common_scale))
+                    .and_then(|(l, r)| match op {
+                        DecimalArithmeticOp::Add => l.checked_add(r),
+                        _ => l.checked_sub(r),
+                    })
+                    .and_then(|v| rescale(v, common_scale, scale as i32))
+            }
+            DecimalArithmeticOp::Multiply => l
+                .checked_mul(r)
+                .and_then(|v| rescale(v, lhs_scale + rhs_scale, scale as i32)),
+            DecimalArithmeticOp::Divide => {
+                // like spark, the quotient is rounded to scale 38 before rounded to the
+                // result scale
+                let shift = DIVIDE_SCALE + rhs_scale - lhs_scale;
+                let quotient = if shift >= 0 {
+                    pow10(shift)
+                        .and_then(|p| l.checked_mul(p))
+                        .and_then(|l| div_round_half_up(l, r))
+                } else {
+                    pow10(-shift)
+                        .and_then(|p| r.checked_mul(p))
+                        .and_then(|r| div_round_half_up(l, r))
+                };
+                quotient.and_then(|v| rescale(v, DIVIDE_SCALE, scale as i32))
+            }
+            DecimalArithmeticOp::Remainder => {
+                let common_scale = lhs_scale.max(rhs_scale);
+                rescale(l, lhs_scale, common_scale)
+                    .zip(rescale(r, rhs_scale, common_scale))
+                    .and_then(|(l, r)| l.checked_rem(r))
+                    .and_then(|v| rescale(v, common_scale, scale as i32))
+            }
+        };
+
+        match result {
+            Some(v) if v.wrapping_abs() < max_unscaled => builder.append_value(v.as_i128()),
+            _ if fail_on_overflow => {
+                return df_spark_err!(
+                    NUMERIC_VALUE_OUT_OF_RANGE,
+                    "the result of {} {:?} {} cannot be represented as Decimal({precision}, {scale}), \
+                    set spark.sql.ansi.enabled to false to bypass this error",
+                    Decimal128Type::format_decimal(l.as_i128(), lhs.precision(), lhs.scale()),
+                    op,
+                    Decimal128Type::format_decimal(r.as_i128(), rhs.precision(), rhs.scale()),
+                );
+            }
+            _ => builder.append_null(),
+        }
+    }
+    Ok(builder.finish())
+}
+
+fn pow10(exp: i32) -> Option<i256> {
+    i256::from_i128(10).checked_pow(u32::try_from(exp).ok()?)
+}
+
+/// rescales an unscaled value, rounding half up. returns None on overflow
+fn rescale(value: i256, from_scale: i32, to_scale: i32) -> Option<i256> {
+    if to_scale >= from_scale {
+        return value.checked_mul(pow10(to_scale - from_scale)?);
+    }
+    match pow10(from_scale - to_scale) {
+        Some(divisor) => div_round_half_up(value, divisor),
+        None => Some(i256::ZERO), // the divisor is larger than any value
+    }
+}
+
+/// divides and rounds half up (away from zero), like java's
+/// `RoundingMode.HALF_UP`
+fn div_round_half_up(dividend: i256, divisor: i256) -> Option<i256> {
+    let quotient = dividend.checked_div(divisor)?;
+    let remainder = dividend.checked_rem(divisor)?;
+    let doubled_remainder = remainder.wrapping_abs().checked_mul(i256::from_i128(2))?;
+    if doubled_remainder < divisor.wrapping_abs() {
+        return Some(quotient);
+    }
+    if dividend.is_negative() != divisor.is_negative() {
+        quotient.checked_sub(i256::ONE)
+    } else {
+        quotient.checked_add(i256::ONE)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use arrow::array::Decimal128Array;
+    use datafusion::common::Result;
+
+    use crate::decimal_arithmetic::{decimal_arithmetic, DecimalArithmeticOp};
+
+    fn decimals(values: Vec<Option<i128>>, precision: u8, scale: i8) -> Result<Decimal128Array> {
+        Ok(Decimal128Array::from(values).with_precision_and_scale(precision, scale)?)
+    }
+
+    #[test]
+    fn test_decimal_arithmetic() -> Result<()> {
+        // 1.25, -3.50, 99.99, null
+        let lhs = decimals(vec![Some(125), Some(-350), Some(9999), None], 4, 2)?;
+        // 0.333, 0.001, 0.000, 1.000
+        let rhs = decimals(vec![Some(333), Some(1), Some(0), Some(1000)], 4, 3)?;
+
+        let result = decimal_arithmetic(DecimalArithmeticOp::Add, &lhs, &rhs, 6, 3, false)?;
+        assert_eq!(
+            result,
+            decimals(vec![Some(1583), Some(-3499), Some(99990), None], 6, 3)?
+        );
+
+        let result = decimal_arithmetic(DecimalArithmeticOp::Subtract, &lhs, &rhs, 6, 3, false)?;
+        assert_eq!(
+            result,
+            decimals(vec![Some(917), Some(-3501), Some(99990), None], 6, 3)?
+        );
+
+        // exact products are 0.41625, -0.00350, 0, rounded half up to scale 4
+        let result = decimal_arithmetic(DecimalArithmeticOp::Multiply, &lhs, &rhs, 9, 4, false)?;
+        assert_eq!(
+            result,
+            decimals(vec![Some(4163), Some(-35), Some(0), None], 9, 4)?
+        );
+
+        // exact quotients are 3.753753..., -3500, division by zero
+        let result = decimal_arithmetic(DecimalArithmeticOp::Divide, &lhs, &rhs, 10, 6, false)?;
+        assert_eq!(
+            result,
+            decimals(vec![Some(3753754), Some(-3500000000), None, None], 10, 6)?
+        );
+
+        let result = decimal_arithmetic(DecimalArithmeticOp::Remainder, &lhs, &rhs, 4, 3, false)?;
+        assert_eq!(
+            result,
+            decimals(vec![Some(251), Some(0), None, None], 4, 3)?
+        );
+
+        // overflows
+        let result = decimal_arithmetic(DecimalArithmeticOp::Divide, &lhs, &rhs, 5, 2, false)?;
+        assert_eq!(result, decimals(vec![Some(375), None, None, None], 5, 2)?);
+        assert!(decimal_arithmetic(DecimalArithmeticOp::Divide, &lhs, &rhs, 5, 2, true).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,314 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Debug, Formatter},
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use arrow::{
+    array::{Array, ArrayRef, AsArray, RecordBatch, StructArray},
+    buffer::NullBuffer,
+    compute::concat_batches,
+    datatypes::{DataType, Schema, SchemaRef},
+    json::{reader::Decoder, ReaderBuilder},
+};
+use datafusion::{
+    common::{Result, ScalarValue},
+    logical_expr::ColumnarValue,
+    physical_expr::PhysicalExpr,
+};
+use datafusion_ext_commons::df_execution_err;
+
+use crate::down_cast_any_ref;
+
+/// implements spark's from_json() with a struct schema in PERMISSIVE mode.
+/// null or empty inputs produce null structs, malformed records produce
+/// structs with all fields null.
+#[derive(Debug, Hash)]
+pub struct FromJsonExpr {
+    child: Arc<dyn PhysicalExpr>,
+    return_type: DataType,
+    return_schema: SchemaRef,
+}
+
+impl FromJsonExpr {
+    pub fn try_new(child: Arc<dyn PhysicalExpr>, return_type: DataType) -> Result<Self> {
+        let return_schema = match &return_type {
+            DataType::Struct(fields) => Arc::new(Schema::new(fields.clone())),
+            other => df_execution_err!("FromJson expects returning struct type, but got {other}")?,
+        };
+        Ok(Self {
+            child,
+            return_type,
+            return_schema,
+        })
+    }
+}
+
+impl std::fmt::Display for FromJsonExpr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "FromJson({}, {})", self.child, self.return_type)
+    }
+}
+
+impl PartialEq<dyn Any> for FromJsonExpr {
+    fn eq(&self, other: &dyn Any) -> bool {
+        down_cast_any_ref(other)
+            .downcast_ref::<Self>()
+            .map(|x| self.child.eq(&x.child) && self.return_type == x.return_type)
+            .unwrap_or(false)
+    }
+}
+
+impl PhysicalExpr for FromJsonExpr {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn data_type(&self, _input_schema: &Schema) -> Result<DataType> {
+        Ok(self.return_type.clone())
+    }
+
+    fn nullable(&self, _input_schema: &Schema) -> Result<bool> {
+        Ok(true)
+    }
+
+    fn evaluate(&self, batch: &RecordBatch) -> Result<ColumnarValue> {
+        match self.child.evaluate(batch)? {
+            ColumnarValue::Array(array) => Ok(ColumnarValue::Array(from_json(
+                &array,
+                self.return_schema.clone(),
+            )?)),
+            ColumnarValue::Scalar(scalar) => {
+                let array = from_json(&scalar.to_array()?, self.return_schema.clone())?;
+                Ok(ColumnarValue::Scalar(ScalarValue::try_from_array(
+                    &array, 0,
+                )?))
+            }
+        }
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn PhysicalExpr>> {
+        vec![&self.child]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn PhysicalExpr>>,
+    ) -> Result<Arc<dyn PhysicalExpr>> {
+        Ok(Arc::new(Self::try_new(
+            children[0].clone(),
+            self.return_type.clone(),
+        )?))
+    }
+
+    fn dyn_hash(&self, state: &mut dyn Hasher) {
+        let mut s = state;
+        self.hash(&mut s);
+    }
+}
+
+/// parses json strings into a struct array of the given schema
+pub fn from_json(json_strings: &ArrayRef, schema: SchemaRef) -> Result<ArrayRef> {
+    let Some(json_strings) = json_strings.as_string_opt::<i32>() else {
+        return df_execution_err!("FromJson: invalid input type: {}", json_strings.data_type());
+    };
+    let mut parser = JsonRowParser::try_new(schema.clone(), json_strings.len())?;
+    let mut valids = Vec::with_capacity(json_strings.len());
+
+    for json_string in json_strings {
+        let valid = match json_string {
+            Some(json_string) => parser.parse_row(json_string.as_bytes())?,
+            None => {
+                parser.parse_row(b"{}")?;
+                false
+            }
+        };
+        valids.push(valid);
+    }
+    let batch = parser.finish()?;
+    let (fields, columns, _) = StructArray::from(batch).into_parts();
+    Ok(Arc::new(StructArray::try_new(
+        fields,
+        columns,
+        Some(NullBuffer::from(valids)),
+    )?))
+}
+
+/// decodes json rows with arrow's json decoder. the decoder works on a stream
+/// of json values and cannot recover from malformed inputs, so rows are checked
+/// one by one and the decoder is rebuilt with the valid pending rows when a
+/// malformed row is found. values not convertible to the schema are detected
+/// only when flushing, in which case the pending rows are flushed one by one.
+struct JsonRowParser<'a> {
+    schema: SchemaRef,
+    batch_size: usize,
+    decoder: Decoder,
+    pending_rows: Vec<&'a [u8]>,
+    flushed_batches: Vec<RecordBatch>,
+    null_fields_batch: RecordBatch,
+}
+
+impl<'a> JsonRowParser<'a> {
+    fn try_new(schema: SchemaRef, num_rows: usize) -> Result<Self> {
+        // one more row for the trailing values of malformed inputs
+        let batch_size = num_rows + 1;
+        let mut null_fields_decoder = Self::new_decoder(schema.clone(), 1)?;
+        null_fields_decoder.decode(b"{}")?;
+        let null_fields_batch = null_fields_decoder
+            .flush()?
+            .expect("missing null fields batch");
+
+        Ok(Self {
+            decoder: Self::new_decoder(schema.clone(), batch_size)?,
+            schema,
+            batch_size,
+            pending_rows: vec![],
+            flushed_batches: vec![],
+            null_fields_batch,
+        })
+    }
+
+    fn new_decoder(schema: SchemaRef, batch_size: usize) -> Result<Decoder> {
+        Ok(ReaderBuilder::new(schema)
+            .with_batch_size(batch_size)
+            .with_coerce_primitive(true)
+            .with_strict_mode(false)
+            .build_decoder()?)
+    }
+
+    /// parses a row, returns false if the row is empty and should be null
+    fn parse_row(&mut self, row: &'a [u8]) -> Result<bool> {
+        let num_buffered_rows = self.decoder.len();
+        let decoded = match self.decoder.decode(row) {
+            Ok(num_decoded) => num_decoded == row.len() && !self.decoder.has_partial_record(),
+            Err(_) => false,
+        };
+        if decoded {
+            match self.decoder.len() - num_buffered_rows {
+                1 => {
+                    self.pending_rows.push(row);
+                    return Ok(true);
+                }
+                0 => {
+                    // empty input, decoded as `{}` to keep the row
+                    self.decoder.decode(b"{}")?;
+                    self.pending_rows.push(b"{}");
+                    return Ok(false);
+                }
+                _ => {}
+            }
+        }
+
+        // malformed row, rebuild the decoder with the valid pending rows and
+        // replace this row with a row of all null fields
+        self.decoder = Self::new_decoder(self.schema.clone(), self.batch_size)?;
+        for &pending_row in &self.pending_rows {
+            self.decoder.decode(pending_row)?;
+        }
+        self.flush()?;
+        self.flushed_batches.push(self.null_fields_batch.clone());
+        Ok(true)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let pending_rows = std::mem::take(&mut self.pending_rows);
+        match self.decoder.flush() {
+            Ok(batch) => self.flushed_batches.extend(batch),
+            Err(_) => {
+                self.decoder = Self::new_decoder(self.schema.clone(), self.batch_size)?;
+                for pending_row in pending_rows {
+                    let mut decoder = Self::new_decoder(self.schema.clone(), 1)?;
+                    decoder.decode(pending_row)?;
+                    self.flushed_batches.push(match decoder.flush() {
+                        Ok(Some(batch)) => batch,
+                        _ => self.null_fields_batch.clone(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<RecordBatch> {
+        self.flush()?;
+        Ok(concat_batches(&self.schema, &self.flushed_batches)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{ArrayRef, RecordBatch, StringArray},
+        datatypes::{DataType, Field, Fields, Schema},
+    };
+    use datafusion::{assert_batches_eq, common::Result};
+
+    use crate::from_json::from_json;
+
+    #[test]
+    fn test_from_json() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, true),
+            Field::new("name", DataType::Utf8, true),
+            Field::new(
+                "tags",
+                DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))),
+                true,
+            ),
+            Field::new(
+                "nested",
+                DataType::Struct(Fields::from(vec![Field::new("x", DataType::Float64, true)])),
+                true,
+            ),
+        ]));
+        let json_strings: ArrayRef = Arc::new(StringArray::from(vec![
+            Some(r#"{"id": 1, "name": "a", "tags": ["x", "y"], "nested": {"x": 1.5}}"#),
+            Some(r#"{"id": 2, "name": 123, "unknown": true}"#),
+            None,
+            Some(r#"{"id": 3, "name": "c""#),
+            Some(r#"}{"id": 4}"#),
+            Some(r#"   "#),
+            Some(r#"[{"id": 5}]"#),
+            Some(r#"{"id": 6} {"id": 7}"#),
+            Some(r#"{"id": 8}"#),
+        ]));
+        let output = from_json(&json_strings, schema)?;
+        let output_batch = RecordBatch::try_from_iter(vec![("output", output)])?;
+        assert_batches_eq!(
+            vec![
+                "+--------------------------------------------------+",
+                "| output                                           |",
+                "+--------------------------------------------------+",
+                "| {id: 1, name: a, tags: [x, y], nested: {x: 1.5}} |",
+                "| {id: 2, name: 123, tags: , nested: }             |",
+                "|                                                  |",
+                "| {id: , name: , tags: , nested: }                 |",
+                "| {id: , name: , tags: , nested: }                 |",
+                "|                                                  |",
+                "| {id: , name: , tags: , nested: }                 |",
+                "| {id: , name: , tags: , nested: }                 |",
+                "| {id: 8, name: , tags: , nested: }                |",
+                "+--------------------------------------------------+",
+            ],
+            &[output_batch]
+        );
+        Ok(())
+    }
+}
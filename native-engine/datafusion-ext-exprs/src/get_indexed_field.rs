@@ -75,7 +75,8 @@ impl PhysicalExpr for GetIndexedFieldExpr {
 
     fn nullable(&self, input_schema: &Schema) -> Result<bool> {
         let data_type = self.arg.data_type(input_schema)?;
-        get_indexed_field(&data_type, &self.key).map(|f| f.is_nullable())
+        let nullable = self.arg.nullable(input_schema)?;
+        get_indexed_field(&data_type, &self.key).map(|f| nullable || f.is_nullable())
     }
 
     fn evaluate(&self, batch: &RecordBatch) -> Result<ColumnarValue> {
@@ -114,9 +115,16 @@ impl PhysicalExpr for GetIndexedFieldExpr {
             }
             (DataType::Struct(_), ScalarValue::Int32(Some(k))) => {
                 let as_struct_array = as_struct_array(&array)?;
-                Ok(ColumnarValue::Array(
-                    as_struct_array.column(*k as usize).clone(),
-                ))
+                let field = as_struct_array.column(*k as usize);
+
+                // fields of null structs are null
+                if as_struct_array.null_count() > 0 {
+                    return Ok(ColumnarValue::Array(nullif(
+                        field,
+                        &is_null(as_struct_array)?,
+                    )?));
+                }
+                Ok(ColumnarValue::Array(field.clone()))
             }
             (DataType::List(_), key) => df_execution_err!(
                 "get indexed field is only possible on lists with int64 indexes. \
@@ -248,4 +256,26 @@ mod test {
         assert_batches_eq!(expected, &[output_batch]);
         Ok(())
     }
+
+    #[test]
+    fn test_struct_with_nulls() -> Result<(), Box<dyn std::error::Error>> {
+        let fields = Fields::from(vec![Field::new("a", DataType::Int32, false)]);
+        let array: ArrayRef = Arc::new(StructArray::try_new(
+            fields,
+            vec![Arc::new(Int32Array::from(vec![1, 2, 3]))],
+            Some(vec![true, false, true].into()),
+        )?);
+        let input_batch = RecordBatch::try_from_iter_with_nullable(vec![("s", array, true)])?;
+
+        let get_indexed = Arc::new(GetIndexedFieldExpr::new(
+            Arc::new(Column::new("s", 0)),
+            ScalarValue::from(0_i32),
+        ));
+        assert!(get_indexed.nullable(&input_batch.schema())?);
+
+        let output_array = get_indexed.evaluate(&input_batch)?.into_array(0)?;
+        let expected: ArrayRef = Arc::new(Int32Array::from(vec![Some(1), None, Some(3)]));
+        assert_eq!(&output_array, &expected);
+        Ok(())
+    }
 }
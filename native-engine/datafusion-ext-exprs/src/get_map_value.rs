@@ -87,7 +87,7 @@ impl PhysicalExpr for GetMapValueExpr {
     }
 
     fn evaluate(&self, batch: &RecordBatch) -> Result<ColumnarValue> {
-        let array = self.arg.evaluate(batch)?.into_array(1)?;
+        let array = self.arg.evaluate(batch)?.into_array(batch.num_rows())?;
         match (array.data_type(), &self.key) {
             (DataType::Map(..), _) if self.key.is_null() => {
                 df_unimplemented_err!("map key not support Null Type")
@@ -101,12 +101,18 @@ impl PhysicalExpr for GetMapValueExpr {
                 let mut mutable =
                     MutableArrayData::new(vec![&value_data], true, as_map_array.len());
 
-                for (start, end) in as_map_array
+                for (row_idx, (start, end)) in as_map_array
                     .value_offsets()
                     .iter()
                     .map(|offset| *offset as usize)
                     .tuple_windows()
+                    .enumerate()
                 {
+                    // values of null maps are null
+                    if as_map_array.is_null(row_idx) {
+                        mutable.extend_nulls(1);
+                        continue;
+                    }
                     let mut found = false;
                     for key_idx in start..end {
                         if comparator(key_idx, 0).is_eq() {
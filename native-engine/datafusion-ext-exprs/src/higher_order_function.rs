@@ -0,0 +1,692 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Debug, Formatter},
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use arrow::{
+    array::{
+        Array, ArrayRef, AsArray, BooleanArray, Int32Array, ListArray, RecordBatch,
+        RecordBatchOptions, UInt32Array,
+    },
+    buffer::{NullBuffer, OffsetBuffer},
+    compute::{cast, filter, interleave, take},
+    datatypes::{DataType, Field, FieldRef, Schema},
+};
+use datafusion::{common::Result, logical_expr::ColumnarValue, physical_expr::PhysicalExpr};
+use datafusion_ext_commons::df_execution_err;
+
+use crate::down_cast_any_ref;
+
+/// spark's higher-order functions on arrays
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HigherOrderFunction {
+    /// transform(array, (x[, i]) -> ...)
+    Transform,
+    /// filter(array, (x[, i]) -> ...)
+    Filter,
+    /// exists(array, x -> ...), with three-valued logic
+    Exists,
+    /// forall(array, x -> ...)
+    ForAll,
+    /// aggregate(array, zero, (acc, x) -> ..., acc -> ...)
+    Aggregate,
+    /// zip_with(array1, array2, (x, y) -> ...)
+    ZipWith,
+}
+
+/// a lambda function, the variables are appended to the input batch in order
+/// and referenced by [`crate::lambda_variable::LambdaVariableExpr`]
+#[derive(Debug, Clone, Hash)]
+pub struct LambdaFunction {
+    pub body: Arc<dyn PhysicalExpr>,
+    pub variables: Vec<FieldRef>,
+}
+
+impl LambdaFunction {
+    pub fn new(body: Arc<dyn PhysicalExpr>, variables: Vec<FieldRef>) -> Self {
+        Self { body, variables }
+    }
+
+    fn eq(&self, other: &Self) -> bool {
+        self.body.eq(&other.body) && self.variables == other.variables
+    }
+
+    /// evaluates the lambda function with the given variables, rows of the
+    /// input batch are taken by `row_indices` to match the variables
+    fn evaluate(
+        &self,
+        batch: &RecordBatch,
+        row_indices: Option<&UInt32Array>,
+        variables: Vec<ArrayRef>,
+    ) -> Result<ArrayRef> {
+        let num_rows = row_indices.map(|r| r.len()).unwrap_or(batch.num_rows());
+        let mut columns = match row_indices {
+            Some(row_indices) => batch
+                .columns()
+                .iter()
+                .map(|column| Ok(take(column, row_indices, None)?))
+                .collect::<Result<Vec<_>>>()?,
+            None => batch.columns().to_vec(),
+        };
+        columns.extend(variables);
+
+        // lambda variables are always nullable in case of nulls from missing elements
+        let mut fields = batch.schema().fields().to_vec();
+        fields.extend(
+            self.variables
+                .iter()
+                .map(|field| Arc::new(Field::new(field.name(), field.data_type().clone(), true))),
+        );
+        let lambda_batch = RecordBatch::try_new_with_options(
+            Arc::new(Schema::new(fields)),
+            columns,
+            &RecordBatchOptions::new().with_row_count(Some(num_rows)),
+        )?;
+        self.body.evaluate(&lambda_batch)?.into_array(num_rows)
+    }
+}
+
+/// evaluates higher-order functions by flattening elements of arrays, so that
+/// lambda functions are evaluated in vectorized way. columns of the input
+/// batch referenced by lambda functions are repeated for each element.
+#[derive(Debug, Hash)]
+pub struct HigherOrderFunctionExpr {
+    func: HigherOrderFunction,
+    args: Vec<Arc<dyn PhysicalExpr>>,
+    lambda: LambdaFunction,
+    finish: Option<LambdaFunction>,
+    return_type: DataType,
+}
+
+impl HigherOrderFunctionExpr {
+    pub fn try_new(
+        func: HigherOrderFunction,
+        args: Vec<Arc<dyn PhysicalExpr>>,
+        lambda: LambdaFunction,
+        finish: Option<LambdaFunction>,
+        return_type: DataType,
+    ) -> Result<Self> {
+        let num_variables = lambda.variables.len();
+        let valid = match func {
+            HigherOrderFunction::Transform | HigherOrderFunction::Filter => {
+                args.len() == 1 && (1..=2).contains(&num_variables)
+            }
+            HigherOrderFunction::Exists | HigherOrderFunction::ForAll => {
+                args.len() == 1 && num_variables == 1
+            }
+            HigherOrderFunction::Aggregate => {
+                args.len() == 2
+                    && num_variables == 2
+                    && finish.as_ref().is_some_and(|f| f.variables.len() == 1)
+            }
+            HigherOrderFunction::ZipWith => args.len() == 2 && num_variables == 2,
+        };
+        if !valid {
+            return df_execution_err!(
+                "invalid higher-order function {func:?} with {} args and {num_variables} lambda \
+                 variables",
+                args.len(),
+            );
+        }
+        Ok(Self {
+            func,
+            args,
+            lambda,
+            finish,
+            return_type,
+        })
+    }
+
+    /// number of array/value arguments, the following children are bodies of
+    /// lambda functions, which are evaluated on flattened elements
+    pub fn num_args(&self) -> usize {
+        self.args.len()
+    }
+
+    fn transform(&self, batch: &RecordBatch, list: &ListArray) -> Result<ArrayRef> {
+        let elements = Elements::new(list);
+        let mut variables = vec![take(list.values(), &elements.value_indices, None)?];
+        if self.lambda.variables.len() == 2 {
+            variables.push(Arc::new(elements.positions.clone()));
+        }
+        let values = self
+            .lambda
+            .evaluate(batch, Some(&elements.row_indices), variables)?;
+        make_list(&self.return_type, elements.lengths, values, list.nulls())
+    }
+
+    fn filter(&self, batch: &RecordBatch, list: &ListArray) -> Result<ArrayRef> {
+        let elements = Elements::new(list);
+        let values = take(list.values(), &elements.value_indices, None)?;
+        let mut variables = vec![values.clone()];
+        if self.lambda.variables.len() == 2 {
+            variables.push(Arc::new(elements.positions.clone()));
+        }
+        let predicate = self
+            .lambda
+            .evaluate(batch, Some(&elements.row_indices), variables)?;
+        let predicate = predicate.as_boolean();
+
+        let mut lengths = vec![0; list.len()];
+        for (row_idx, retained) in elements.row_indices.values().iter().zip(predicate) {
+            if retained == Some(true) {
+                lengths[*row_idx as usize] += 1;
+            }
+        }
+        let values = filter(&values, predicate)?;
+        make_list(&self.return_type, lengths, values, list.nulls())
+    }
+
+    fn exists_or_for_all(&self, batch: &RecordBatch, list: &ListArray) -> Result<ArrayRef> {
+        let elements = Elements::new(list);
+        let values = take(list.values(), &elements.value_indices, None)?;
+        let predicate = self
+            .lambda
+            .evaluate(batch, Some(&elements.row_indices), vec![values])?;
+        let predicate = predicate.as_boolean();
+
+        // exists: true if any element is true, otherwise null if any is null
+        // forall: false if any element is false, otherwise null if any is null
+        let short_circuit = self.func == HigherOrderFunction::Exists;
+        let mut results: Vec<Option<bool>> = (0..list.len())
+            .map(|row_idx| list.is_valid(row_idx).then_some(!short_circuit))
+            .collect();
+        let mut has_nulls = vec![false; list.len()];
+        for (row_idx, p) in elements.row_indices.values().iter().zip(predicate) {
+            match p {
+                Some(p) if p == short_circuit => results[*row_idx as usize] = Some(p),
+                None => has_nulls[*row_idx as usize] = true,
+                _ => {}
+            }
+        }
+        for (result, has_nulls) in results.iter_mut().zip(has_nulls) {
+            if has_nulls && *result == Some(!short_circuit) {
+                *result = None;
+            }
+        }
+        Ok(Arc::new(BooleanArray::from(results)))
+    }
+
+    fn aggregate(
+        &self,
+        batch: &RecordBatch,
+        list: &ListArray,
+        zero: &ArrayRef,
+    ) -> Result<ArrayRef> {
+        let offsets = list.value_offsets();
+        let valid_rows = (0..list.len())
+            .filter(|&row_idx| list.is_valid(row_idx))
+            .collect::<Vec<_>>();
+        let max_len = valid_rows
+            .iter()
+            .map(|&row_idx| offsets[row_idx + 1] - offsets[row_idx])
+            .max()
+            .unwrap_or(0);
+
+        // merge the k-th elements of all arrays at a time
+        let mut acc = zero.clone();
+        for k in 0..max_len {
+            let (row_indices, value_indices): (Vec<u32>, Vec<u32>) = valid_rows
+                .iter()
+                .filter(|&&row_idx| offsets[row_idx] + k < offsets[row_idx + 1])
+                .map(|&row_idx| (row_idx as u32, (offsets[row_idx] + k) as u32))
+                .unzip();
+            let row_indices = UInt32Array::from(row_indices);
+            let variables = vec![
+                take(&acc, &row_indices, None)?,
+                take(list.values(), &UInt32Array::from(value_indices), None)?,
+            ];
+            let mut merged = self.lambda.evaluate(batch, Some(&row_indices), variables)?;
+            if merged.data_type() != acc.data_type() {
+                merged = cast(&merged, acc.data_type())?;
+            }
+
+            let mut sources = (0..acc.len())
+                .map(|row_idx| (0, row_idx))
+                .collect::<Vec<_>>();
+            for (merged_idx, &row_idx) in row_indices.values().iter().enumerate() {
+                sources[row_idx as usize] = (1, merged_idx);
+            }
+            acc = interleave(&[acc.as_ref(), merged.as_ref()], &sources)?;
+        }
+
+        // finish only valid rows, null arrays produce nulls
+        let Some(finish) = self.finish.as_ref() else {
+            return df_execution_err!("aggregate: missing finish function");
+        };
+        let valid_row_indices = UInt32Array::from_iter_values(valid_rows.iter().map(|&r| r as u32));
+        let finished = finish.evaluate(
+            batch,
+            Some(&valid_row_indices),
+            vec![take(&acc, &valid_row_indices, None)?],
+        )?;
+        if valid_rows.len() == list.len() {
+            return Ok(finished);
+        }
+        let mut num_finished = 0u32;
+        let finished_indices = UInt32Array::from_iter((0..list.len()).map(|row_idx| {
+            list.is_valid(row_idx).then(|| {
+                num_finished += 1;
+                num_finished - 1
+            })
+        }));
+        Ok(take(&finished, &finished_indices, None)?)
+    }
+
+    fn zip_with(
+        &self,
+        batch: &RecordBatch,
+        left: &ListArray,
+        right: &ListArray,
+    ) -> Result<ArrayRef> {
+        let nulls = NullBuffer::union(left.nulls(), right.nulls());
+        let left_offsets = left.value_offsets();
+        let right_offsets = right.value_offsets();
+        let mut lengths = vec![0; left.len()];
+        let mut row_indices = vec![];
+        let mut left_indices = vec![];
+        let mut right_indices = vec![];
+
+        for row_idx in 0..left.len() {
+            if nulls.as_ref().is_some_and(|nulls| nulls.is_null(row_idx)) {
+                continue;
+            }
+            let left_range = left_offsets[row_idx] as u32..left_offsets[row_idx + 1] as u32;
+            let right_range = right_offsets[row_idx] as u32..right_offsets[row_idx + 1] as u32;
+            let len = left_range.len().max(right_range.len());
+            for k in 0..len as u32 {
+                row_indices.push(row_idx as u32);
+                left_indices.push(Some(left_range.start + k).filter(|i| left_range.contains(i)));
+                right_indices.push(Some(right_range.start + k).filter(|i| right_range.contains(i)));
+            }
+            lengths[row_idx] = len;
+        }
+
+        let variables = vec![
+            take(left.values(), &UInt32Array::from(left_indices), None)?,
+            take(right.values(), &UInt32Array::from(right_indices), None)?,
+        ];
+        let values =
+            self.lambda
+                .evaluate(batch, Some(&UInt32Array::from(row_indices)), variables)?;
+        make_list(&self.return_type, lengths, values, nulls.as_ref())
+    }
+}
+
+/// flattened elements of valid arrays
+struct Elements {
+    row_indices: UInt32Array,
+    value_indices: UInt32Array,
+    positions: Int32Array,
+    lengths: Vec<usize>,
+}
+
+impl Elements {
+    fn new(list: &ListArray) -> Self {
+        let offsets = list.value_offsets();
+        let mut row_indices = vec![];
+        let mut value_indices = vec![];
+        let mut positions = vec![];
+        let mut lengths = vec![0; list.len()];
+
+        for row_idx in (0..list.len()).filter(|&row_idx| list.is_valid(row_idx)) {
+            let (start, end) = (offsets[row_idx], offsets[row_idx + 1]);
+            for value_idx in start..end {
+                row_indices.push(row_idx as u32);
+                value_indices.push(value_idx as u32);
+                positions.push(value_idx - start);
+            }
+            lengths[row_idx] = (end - start) as usize;
+        }
+        Self {
+            row_indices: row_indices.into(),
+            value_indices: value_indices.into(),
+            positions: positions.into(),
+            lengths,
+        }
+    }
+}
+
+fn make_list(
+    return_type: &DataType,
+    lengths: Vec<usize>,
+    values: ArrayRef,
+    nulls: Option<&NullBuffer>,
+) -> Result<ArrayRef> {
+    let DataType::List(field) = return_type else {
+        return df_execution_err!("expect list return type, but got {return_type}");
+    };
+    let values = match values.data_type() == field.data_type() {
+        true => values,
+        false => cast(&values, field.data_type())?,
+    };
+    Ok(Arc::new(ListArray::try_new(
+        field.clone(),
+        OffsetBuffer::from_lengths(lengths),
+        values,
+        nulls.cloned(),
+    )?))
+}
+
+fn as_list<'a>(array: &'a ArrayRef, func: HigherOrderFunction) -> Result<&'a ListArray> {
+    match array.as_list_opt::<i32>() {
+        Some(list) => Ok(list),
+        None => df_execution_err!("{func:?}: expect list argument, got {}", array.data_type()),
+    }
+}
+
+impl std::fmt::Display for HigherOrderFunctionExpr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let lambda = |lambda: &LambdaFunction| {
+            let variables = lambda.variables.iter().map(|v| v.name().as_str());
+            format!(
+                "({}) -> {}",
+                variables.collect::<Vec<_>>().join(", "),
+                lambda.body
+            )
+        };
+        write!(f, "{:?}(", self.func)?;
+        for arg in &self.args {
+            write!(f, "{arg}, ")?;
+        }
+        write!(f, "{}", lambda(&self.lambda))?;
+        if let Some(finish) = &self.finish {
+            write!(f, ", {}", lambda(finish))?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl PartialEq<dyn Any> for HigherOrderFunctionExpr {
+    fn eq(&self, other: &dyn Any) -> bool {
+        down_cast_any_ref(other)
+            .downcast_ref::<Self>()
+            .map(|x| {
+                self.func == x.func
+                    && self.args.len() == x.args.len()
+                    && self.args.iter().zip(&x.args).all(|(a, b)| a.eq(b))
+                    && self.lambda.eq(&x.lambda)
+                    && match (&self.finish, &x.finish) {
+                        (Some(f1), Some(f2)) => f1.eq(f2),
+                        (None, None) => true,
+                        _ => false,
+                    }
+                    && self.return_type == x.return_type
+            })
+            .unwrap_or(false)
+    }
+}
+
+impl PhysicalExpr for HigherOrderFunctionExpr {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn data_type(&self, _input_schema: &Schema) -> Result<DataType> {
+        Ok(self.return_type.clone())
+    }
+
+    fn nullable(&self, _input_schema: &Schema) -> Result<bool> {
+        Ok(true)
+    }
+
+    fn evaluate(&self, batch: &RecordBatch) -> Result<ColumnarValue> {
+        let args = self
+            .args
+            .iter()
+            .map(|arg| arg.evaluate(batch)?.into_array(batch.num_rows()))
+            .collect::<Result<Vec<_>>>()?;
+        let list = as_list(&args[0], self.func)?;
+
+        Ok(ColumnarValue::Array(match self.func {
+            HigherOrderFunction::Transform => self.transform(batch, list)?,
+            HigherOrderFunction::Filter => self.filter(batch, list)?,
+            HigherOrderFunction::Exists | HigherOrderFunction::ForAll => {
+                self.exists_or_for_all(batch, list)?
+            }
+            HigherOrderFunction::Aggregate => self.aggregate(batch, list, &args[1])?,
+            HigherOrderFunction::ZipWith => {
+                self.zip_with(batch, list, as_list(&args[1], self.func)?)?
+            }
+        }))
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn PhysicalExpr>> {
+        let mut children = self.args.iter().collect::<Vec<_>>();
+        children.push(&self.lambda.body);
+        children.extend(self.finish.as_ref().map(|finish| &finish.body));
+        children
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn PhysicalExpr>>,
+    ) -> Result<Arc<dyn PhysicalExpr>> {
+        let num_args = self.args.len();
+        let lambda = LambdaFunction::new(children[num_args].clone(), self.lambda.variables.clone());
+        let finish = self.finish.as_ref().map(|finish| {
+            LambdaFunction::new(children[num_args + 1].clone(), finish.variables.clone())
+        });
+        Ok(Arc::new(Self::try_new(
+            self.func,
+            children[..num_args].to_vec(),
+            lambda,
+            finish,
+            self.return_type.clone(),
+        )?))
+    }
+
+    fn dyn_hash(&self, state: &mut dyn Hasher) {
+        let mut s = state;
+        self.hash(&mut s);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{ArrayRef, BooleanArray, Int32Array, ListArray, RecordBatch},
+        datatypes::{DataType, Field, Int32Type},
+    };
+    use datafusion::{
+        common::Result,
+        logical_expr::Operator,
+        physical_expr::{
+            expressions::{binary, col, lit},
+            PhysicalExpr,
+        },
+    };
+
+    use crate::{
+        higher_order_function::{HigherOrderFunction, HigherOrderFunctionExpr, LambdaFunction},
+        lambda_variable::LambdaVariableExpr,
+    };
+
+    fn test_batch() -> Result<RecordBatch> {
+        let arrays: ArrayRef = Arc::new(ListArray::from_iter_primitive::<Int32Type, _, _>(vec![
+            Some(vec![Some(1), Some(2), Some(3)]),
+            Some(vec![]),
+            None,
+            Some(vec![Some(4), None]),
+        ]));
+        let offsets: ArrayRef = Arc::new(Int32Array::from(vec![10, 20, 30, 40]));
+        Ok(RecordBatch::try_from_iter(vec![
+            ("arrays", arrays),
+            ("offsets", offsets),
+        ])?)
+    }
+
+    fn var(name: &str) -> Arc<dyn PhysicalExpr> {
+        Arc::new(LambdaVariableExpr::new(
+            name.to_string(),
+            DataType::Int32,
+            true,
+        ))
+    }
+
+    fn lambda(body: Arc<dyn PhysicalExpr>, names: &[&str]) -> LambdaFunction {
+        let variables = names
+            .iter()
+            .map(|name| Arc::new(Field::new(*name, DataType::Int32, true)))
+            .collect();
+        LambdaFunction::new(body, variables)
+    }
+
+    fn list_type() -> DataType {
+        DataType::new_list(DataType::Int32, true)
+    }
+
+    #[test]
+    fn test_transform_and_filter() -> Result<()> {
+        let batch = test_batch()?;
+        let schema = batch.schema();
+
+        // transform(arrays, (x, i) -> x + i + offsets)
+        let body = binary(
+            binary(var("x"), Operator::Plus, var("i"), &schema)?,
+            Operator::Plus,
+            col("offsets", &schema)?,
+            &schema,
+        )?;
+        let expr = HigherOrderFunctionExpr::try_new(
+            HigherOrderFunction::Transform,
+            vec![col("arrays", &schema)?],
+            lambda(body, &["x", "i"]),
+            None,
+            list_type(),
+        )?;
+        let output = expr.evaluate(&batch)?.into_array(batch.num_rows())?;
+        let expected: ArrayRef = Arc::new(ListArray::from_iter_primitive::<Int32Type, _, _>(vec![
+            Some(vec![Some(11), Some(13), Some(15)]),
+            Some(vec![]),
+            None,
+            Some(vec![Some(44), None]),
+        ]));
+        assert_eq!(&output, &expected);
+
+        // filter(arrays, x -> x > 1)
+        let body = binary(var("x"), Operator::Gt, lit(1i32), &schema)?;
+        let expr = HigherOrderFunctionExpr::try_new(
+            HigherOrderFunction::Filter,
+            vec![col("arrays", &schema)?],
+            lambda(body, &["x"]),
+            None,
+            list_type(),
+        )?;
+        let output = expr.evaluate(&batch)?.into_array(batch.num_rows())?;
+        let expected: ArrayRef = Arc::new(ListArray::from_iter_primitive::<Int32Type, _, _>(vec![
+            Some(vec![Some(2), Some(3)]),
+            Some(vec![]),
+            None,
+            Some(vec![Some(4)]),
+        ]));
+        assert_eq!(&output, &expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_exists_and_for_all() -> Result<()> {
+        let batch = test_batch()?;
+        let schema = batch.schema();
+
+        // exists(arrays, x -> x > 2), forall(arrays, x -> x > 2)
+        for (func, expected) in [
+            (
+                HigherOrderFunction::Exists,
+                vec![Some(true), Some(false), None, Some(true)],
+            ),
+            (
+                HigherOrderFunction::ForAll,
+                vec![Some(false), Some(true), None, None],
+            ),
+        ] {
+            let body = binary(var("x"), Operator::Gt, lit(2i32), &schema)?;
+            let expr = HigherOrderFunctionExpr::try_new(
+                func,
+                vec![col("arrays", &schema)?],
+                lambda(body, &["x"]),
+                None,
+                DataType::Boolean,
+            )?;
+            let output = expr.evaluate(&batch)?.into_array(batch.num_rows())?;
+            let expected: ArrayRef = Arc::new(BooleanArray::from(expected));
+            assert_eq!(&output, &expected, "{func:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_aggregate() -> Result<()> {
+        let batch = test_batch()?;
+        let schema = batch.schema();
+
+        // aggregate(arrays, offsets, (acc, x) -> acc + x, acc -> acc * 2)
+        let merge = binary(var("acc"), Operator::Plus, var("x"), &schema)?;
+        let finish = binary(var("acc"), Operator::Multiply, lit(2i32), &schema)?;
+        let expr = HigherOrderFunctionExpr::try_new(
+            HigherOrderFunction::Aggregate,
+            vec![col("arrays", &schema)?, col("offsets", &schema)?],
+            lambda(merge, &["acc", "x"]),
+            Some(lambda(finish, &["acc"])),
+            DataType::Int32,
+        )?;
+        let output = expr.evaluate(&batch)?.into_array(batch.num_rows())?;
+        let expected: ArrayRef = Arc::new(Int32Array::from(vec![Some(32), Some(40), None, None]));
+        assert_eq!(&output, &expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_zip_with() -> Result<()> {
+        let batch = test_batch()?;
+
+        // zip_with(arrays, right, (x, y) -> x * y), shorter arrays are padded with
+        // nulls
+        let right: ArrayRef = Arc::new(ListArray::from_iter_primitive::<Int32Type, _, _>(vec![
+            Some(vec![Some(1), Some(2)]),
+            Some(vec![Some(5)]),
+            Some(vec![Some(6)]),
+            Some(vec![Some(7), Some(8)]),
+        ]));
+        let batch = RecordBatch::try_from_iter(vec![
+            ("arrays", batch.column(0).clone()),
+            ("right", right),
+        ])?;
+        let schema = batch.schema();
+        let body = binary(var("x"), Operator::Multiply, var("y"), &schema)?;
+        let expr = HigherOrderFunctionExpr::try_new(
+            HigherOrderFunction::ZipWith,
+            vec![col("arrays", &schema)?, col("right", &schema)?],
+            lambda(body, &["x", "y"]),
+            None,
+            list_type(),
+        )?;
+        let output = expr.evaluate(&batch)?.into_array(batch.num_rows())?;
+        let expected: ArrayRef = Arc::new(ListArray::from_iter_primitive::<Int32Type, _, _>(vec![
+            Some(vec![Some(1), Some(4), None]),
+            Some(vec![None]),
+            None,
+            Some(vec![Some(28), None]),
+        ]));
+        assert_eq!(&output, &expected);
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Debug, Display, Formatter},
+    hash::Hasher,
+    sync::Arc,
+};
+
+use arrow::{
+    datatypes::{DataType, Schema},
+    record_batch::RecordBatch,
+};
+use datafusion::{
+    common::{Result, ScalarValue},
+    logical_expr::ColumnarValue,
+    physical_expr::PhysicalExpr,
+};
+use datafusion_ext_commons::spark_task_context::SparkTaskContext;
+
+use crate::down_cast_any_ref;
+
+/// input_file_name(): name of the file being read by the scan operator of the
+/// current task, or empty string if unknown
+#[derive(Default)]
+pub struct InputFileNameExpr;
+
+impl Display for InputFileNameExpr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "InputFileName")
+    }
+}
+
+impl Debug for InputFileNameExpr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "InputFileName")
+    }
+}
+
+impl PartialEq<dyn Any> for InputFileNameExpr {
+    fn eq(&self, other: &dyn Any) -> bool {
+        down_cast_any_ref(other).is::<Self>()
+    }
+}
+
+impl PhysicalExpr for InputFileNameExpr {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn data_type(&self, _input_schema: &Schema) -> Result<DataType> {
+        Ok(DataType::Utf8)
+    }
+
+    fn nullable(&self, _input_schema: &Schema) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn evaluate(&self, _batch: &RecordBatch) -> Result<ColumnarValue> {
+        let input_file_name = SparkTaskContext::current()
+            .map(|task_context| task_context.input_file_name().to_string())
+            .unwrap_or_default();
+        Ok(ColumnarValue::Scalar(ScalarValue::Utf8(Some(
+            input_file_name,
+        ))))
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn PhysicalExpr>> {
+        vec![]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        _children: Vec<Arc<dyn PhysicalExpr>>,
+    ) -> Result<Arc<dyn PhysicalExpr>> {
+        Ok(self)
+    }
+
+    fn dyn_hash(&self, state: &mut dyn Hasher) {
+        state.write("InputFileName".as_bytes())
+    }
+}
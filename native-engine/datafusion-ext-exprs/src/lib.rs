@@ -18,10 +18,19 @@ use datafusion::physical_expr::PhysicalExpr;
 
 pub mod bloom_filter_might_contain;
 pub mod cast;
+pub mod create_map;
+pub mod decimal_arithmetic;
+pub mod from_json;
 pub mod get_indexed_field;
 pub mod get_map_value;
+pub mod higher_order_function;
+pub mod input_file_name;
+pub mod lambda_variable;
+pub mod monotonically_increasing_id;
 pub mod named_struct;
+pub mod raise_error;
 pub mod row_num;
+pub mod spark_partition_id;
 pub mod spark_scalar_subquery_wrapper;
 pub mod spark_udf_wrapper;
 pub mod string_contains;
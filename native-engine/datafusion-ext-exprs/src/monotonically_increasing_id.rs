@@ -0,0 +1,133 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Debug, Display, Formatter},
+    hash::Hasher,
+    sync::{
+        atomic::{AtomicI64, Ordering::SeqCst},
+        Arc,
+    },
+};
+
+use arrow::{
+    array::{Int64Array, RecordBatch},
+    datatypes::{DataType, Schema},
+};
+use datafusion::{common::Result, logical_expr::ColumnarValue, physical_expr::PhysicalExpr};
+use datafusion_ext_commons::spark_task_context::SparkTaskContext;
+
+/// monotonically_increasing_id(): the partition id is put in the upper 31
+/// bits, and the lower 33 bits are the row number within each partition,
+/// same as spark.
+#[derive(Default)]
+pub struct MonotonicallyIncreasingIdExpr {
+    count: AtomicI64,
+}
+
+impl Display for MonotonicallyIncreasingIdExpr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MonotonicallyIncreasingId")
+    }
+}
+
+impl Debug for MonotonicallyIncreasingIdExpr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MonotonicallyIncreasingId")
+    }
+}
+
+impl PartialEq<dyn Any> for MonotonicallyIncreasingIdExpr {
+    fn eq(&self, _other: &dyn Any) -> bool {
+        // nondeterministic
+        false
+    }
+}
+
+impl PhysicalExpr for MonotonicallyIncreasingIdExpr {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn data_type(&self, _input_schema: &Schema) -> Result<DataType> {
+        Ok(DataType::Int64)
+    }
+
+    fn nullable(&self, _input_schema: &Schema) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn evaluate(&self, batch: &RecordBatch) -> Result<ColumnarValue> {
+        let partition_id = SparkTaskContext::current()
+            .map(|task_context| task_context.partition_id())
+            .unwrap_or(0);
+        let num_rows = batch.num_rows() as i64;
+        let count = self.count.fetch_add(num_rows, SeqCst);
+        let id_base = (partition_id as i64) << 33;
+        let array: Int64Array = (count..count + num_rows).map(|i| id_base + i).collect();
+        Ok(ColumnarValue::Array(Arc::new(array)))
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn PhysicalExpr>> {
+        vec![]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        _children: Vec<Arc<dyn PhysicalExpr>>,
+    ) -> Result<Arc<dyn PhysicalExpr>> {
+        Ok(Arc::new(Self::default()))
+    }
+
+    fn dyn_hash(&self, state: &mut dyn Hasher) {
+        state.write("MonotonicallyIncreasingId".as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{ArrayRef, Int32Array, Int64Array, RecordBatch},
+        datatypes::{DataType, Field, Schema},
+    };
+    use datafusion::{common::Result, physical_expr::PhysicalExpr};
+    use datafusion_ext_commons::spark_task_context::SparkTaskContext;
+
+    use crate::monotonically_increasing_id::MonotonicallyIncreasingIdExpr;
+
+    #[test]
+    fn test_monotonically_increasing_id() -> Result<()> {
+        SparkTaskContext::set_current(Some(Arc::new(SparkTaskContext::new(0, 3, 0))));
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(schema, vec![Arc::new(Int32Array::from(vec![0, 0, 0]))])?;
+
+        let expr = MonotonicallyIncreasingIdExpr::default();
+        let output = expr.evaluate(&batch)?.into_array(3)?;
+        let expected: ArrayRef = Arc::new(Int64Array::from(vec![
+            25769803776,
+            25769803777,
+            25769803778,
+        ]));
+        assert_eq!(&output, &expected);
+
+        let output = expr.evaluate(&batch.slice(0, 1))?.into_array(1)?;
+        let expected: ArrayRef = Arc::new(Int64Array::from(vec![25769803779]));
+        assert_eq!(&output, &expected);
+        SparkTaskContext::set_current(None);
+        Ok(())
+    }
+}
@@ -28,7 +28,7 @@ use datafusion::{
     },
     common::Result,
     logical_expr::ColumnarValue,
-    physical_expr::{physical_exprs_bag_equal, PhysicalExpr},
+    physical_expr::{physical_exprs_equal, PhysicalExpr},
 };
 use datafusion_ext_commons::{df_execution_err, io::recover_named_batch};
 
@@ -69,8 +69,7 @@ impl PartialEq<dyn Any> for NamedStructExpr {
         down_cast_any_ref(other)
             .downcast_ref::<Self>()
             .map(|x| {
-                physical_exprs_bag_equal(&self.values, &x.values)
-                    && self.return_type == x.return_type
+                physical_exprs_equal(&self.values, &x.values) && self.return_type == x.return_type
             })
             .unwrap_or(false)
     }
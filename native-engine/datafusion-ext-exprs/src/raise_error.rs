@@ -0,0 +1,176 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Display, Formatter},
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use arrow::{
+    array::{Array, AsArray},
+    datatypes::{DataType, Schema},
+    record_batch::RecordBatch,
+};
+use datafusion::{
+    common::{Result, ScalarValue},
+    logical_expr::ColumnarValue,
+    physical_expr::PhysicalExpr,
+};
+use datafusion_ext_commons::{df_execution_err, df_spark_err, spark_error::USER_RAISED_EXCEPTION};
+
+use crate::down_cast_any_ref;
+
+/// raise_error(message): fails the task with the message of the first row.
+/// also used for assert_true(), which is rewritten to
+/// `if(cond, null, raise_error(message))` by spark.
+///
+/// conditional expressions may evaluate this expression with an empty batch
+/// when no rows take the branch, in which case no error is raised.
+#[derive(Debug, Hash)]
+pub struct RaiseErrorExpr {
+    message: Arc<dyn PhysicalExpr>,
+    return_type: DataType,
+}
+
+impl RaiseErrorExpr {
+    pub fn new(message: Arc<dyn PhysicalExpr>, return_type: DataType) -> Self {
+        Self {
+            message,
+            return_type,
+        }
+    }
+}
+
+impl Display for RaiseErrorExpr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RaiseError({})", self.message)
+    }
+}
+
+impl PartialEq<dyn Any> for RaiseErrorExpr {
+    fn eq(&self, other: &dyn Any) -> bool {
+        down_cast_any_ref(other)
+            .downcast_ref::<Self>()
+            .map(|x| self.message.eq(&x.message) && self.return_type == x.return_type)
+            .unwrap_or(false)
+    }
+}
+
+impl PhysicalExpr for RaiseErrorExpr {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn data_type(&self, _input_schema: &Schema) -> Result<DataType> {
+        Ok(self.return_type.clone())
+    }
+
+    fn nullable(&self, _input_schema: &Schema) -> Result<bool> {
+        Ok(true)
+    }
+
+    fn evaluate(&self, batch: &RecordBatch) -> Result<ColumnarValue> {
+        if batch.num_rows() == 0 {
+            return Ok(ColumnarValue::Scalar(ScalarValue::try_from(
+                &self.return_type,
+            )?));
+        }
+
+        let messages = self.message.evaluate(batch)?.into_array(batch.num_rows())?;
+        let Some(messages) = messages.as_string_opt::<i32>() else {
+            return df_execution_err!(
+                "RaiseError: expect string message, got {}",
+                messages.data_type()
+            );
+        };
+        match messages.is_valid(0) {
+            true => df_spark_err!(USER_RAISED_EXCEPTION, "{}", messages.value(0)),
+            false => df_spark_err!(USER_RAISED_EXCEPTION, "null"),
+        }
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn PhysicalExpr>> {
+        vec![&self.message]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn PhysicalExpr>>,
+    ) -> Result<Arc<dyn PhysicalExpr>> {
+        Ok(Arc::new(Self::new(
+            children[0].clone(),
+            self.return_type.clone(),
+        )))
+    }
+
+    fn dyn_hash(&self, state: &mut dyn Hasher) {
+        let mut s = state;
+        self.hash(&mut s);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{ArrayRef, Int32Array, RecordBatch},
+        datatypes::DataType,
+    };
+    use datafusion::{
+        common::{Result, ScalarValue},
+        logical_expr::Operator,
+        physical_expr::{
+            expressions::{binary, lit, CaseExpr, Column},
+            PhysicalExpr,
+        },
+    };
+    use datafusion_ext_commons::spark_error::{find_spark_error, USER_RAISED_EXCEPTION};
+
+    use crate::raise_error::RaiseErrorExpr;
+
+    #[test]
+    fn test_raise_error() -> Result<()> {
+        let array: ArrayRef = Arc::new(Int32Array::from(vec![1, 2, 3]));
+        let batch = RecordBatch::try_from_iter_with_nullable(vec![("a", array, false)])?;
+        let raise_error: Arc<dyn PhysicalExpr> =
+            Arc::new(RaiseErrorExpr::new(lit("a is too large"), DataType::Int32));
+
+        // case when a > 2 then raise_error(...) else a end
+        let cond = binary(
+            Arc::new(Column::new("a", 0)),
+            Operator::Gt,
+            lit(ScalarValue::Int32(Some(2))),
+            &batch.schema(),
+        )?;
+        let case = CaseExpr::try_new(
+            None,
+            vec![(cond, raise_error)],
+            Some(Arc::new(Column::new("a", 0))),
+        )?;
+        let err = case.evaluate(&batch).unwrap_err().to_string();
+        assert_eq!(
+            find_spark_error(&err),
+            Some((USER_RAISED_EXCEPTION, "a is too large"))
+        );
+
+        // no rows take the raising branch
+        let output = case.evaluate(&batch.slice(0, 2))?.into_array(2)?;
+        let expected: ArrayRef = Arc::new(Int32Array::from(vec![1, 2]));
+        assert_eq!(&output, &expected);
+        Ok(())
+    }
+}
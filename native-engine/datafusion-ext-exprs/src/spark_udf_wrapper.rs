@@ -20,7 +20,7 @@ use std::{
 };
 
 use arrow::{
-    array::{as_struct_array, make_array, Array, ArrayRef, StructArray},
+    array::{as_struct_array, make_array, new_empty_array, Array, ArrayRef, StructArray},
     datatypes::{DataType, Field, Schema, SchemaRef},
     ffi::{from_ffi, FFI_ArrowArray, FFI_ArrowSchema},
     record_batch::{RecordBatch, RecordBatchOptions},
@@ -29,7 +29,7 @@ use blaze_jni_bridge::{
     is_task_running, jni_call, jni_new_direct_byte_buffer, jni_new_global_ref, jni_new_object,
 };
 use datafusion::{
-    error::Result, logical_expr::ColumnarValue, physical_expr::physical_exprs_bag_equal,
+    error::Result, logical_expr::ColumnarValue, physical_expr::physical_exprs_equal,
     physical_plan::PhysicalExpr,
 };
 use datafusion_ext_commons::{arrow::cast::cast, df_execution_err};
@@ -53,7 +53,7 @@ impl PartialEq<dyn Any> for SparkUDFWrapperExpr {
         down_cast_any_ref(other)
             .downcast_ref::<Self>()
             .map(|x| {
-                physical_exprs_bag_equal(&self.params, &x.params)
+                physical_exprs_equal(&self.params, &x.params)
                     && self.serialized == x.serialized
                     && self.return_type == x.return_type
                     && self.return_nullable == x.return_nullable
@@ -122,6 +122,10 @@ impl PhysicalExpr for SparkUDFWrapperExpr {
             df_execution_err!("SparkUDFWrapper: is_task_running=false")?;
         }
 
+        // no need to round trip to JVM for empty batches
+        if batch.num_rows() == 0 {
+            return Ok(ColumnarValue::Array(new_empty_array(&self.return_type)));
+        }
         let batch_schema = batch.schema();
 
         // init params schema
@@ -206,3 +210,38 @@ fn invoke_udf(
     let import_array = as_struct_array(&import_struct_array).column(0).clone();
     Ok(import_array)
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{array::*, datatypes::*, record_batch::RecordBatch};
+    use datafusion::{
+        logical_expr::ColumnarValue,
+        physical_plan::{expressions::Column, PhysicalExpr},
+    };
+
+    use super::SparkUDFWrapperExpr;
+
+    #[test]
+    fn test_empty_batch() -> Result<(), Box<dyn std::error::Error>> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+        let batch = RecordBatch::new_empty(schema);
+
+        // evaluated without creating the JVM context
+        let expr = SparkUDFWrapperExpr::try_new(
+            vec![],
+            DataType::Utf8,
+            true,
+            vec![Arc::new(Column::new("a", 0))],
+        )?;
+        let ColumnarValue::Array(output) = expr.evaluate(&batch)? else {
+            panic!("expect array output");
+        };
+        assert_eq!(output.len(), 0);
+        assert_eq!(output.data_type(), &DataType::Utf8);
+        assert!(expr.jcontext.get().is_none());
+        assert!(expr.params_schema.get().is_none());
+        Ok(())
+    }
+}
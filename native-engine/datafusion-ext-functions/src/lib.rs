@@ -17,16 +17,20 @@
 use std::sync::Arc;
 
 use datafusion::{common::Result, logical_expr::ScalarFunctionImplementation};
-use datafusion_ext_commons::df_unimplemented_err;
+use datafusion_ext_commons::df_blaze_err;
 
 mod brickhouse;
+mod spark_ansi_arithmetic;
 mod spark_check_overflow;
 mod spark_dates;
+mod spark_datetime_pattern;
 pub mod spark_get_json_object;
 mod spark_make_array;
 mod spark_make_decimal;
+mod spark_map;
 mod spark_murmur3_hash;
 mod spark_null_if;
+mod spark_regexp;
 mod spark_strings;
 mod spark_unscaled_value;
 mod spark_xxhash64;
@@ -36,6 +40,11 @@ pub fn create_spark_ext_function(name: &str) -> Result<ScalarFunctionImplementat
         "Placeholder" => Arc::new(|_| panic!("placeholder() should never be called")),
         "NullIf" => Arc::new(spark_null_if::spark_null_if),
         "NullIfZero" => Arc::new(spark_null_if::spark_null_if_zero),
+        "AnsiAdd" => Arc::new(spark_ansi_arithmetic::spark_ansi_add),
+        "AnsiSubtract" => Arc::new(spark_ansi_arithmetic::spark_ansi_subtract),
+        "AnsiMultiply" => Arc::new(spark_ansi_arithmetic::spark_ansi_multiply),
+        "AnsiDivide" => Arc::new(spark_ansi_arithmetic::spark_ansi_divide),
+        "AnsiRemainder" => Arc::new(spark_ansi_arithmetic::spark_ansi_remainder),
         "UnscaledValue" => Arc::new(spark_unscaled_value::spark_unscaled_value),
         "MakeDecimal" => Arc::new(spark_make_decimal::spark_make_decimal),
         "CheckOverflow" => Arc::new(spark_check_overflow::spark_check_overflow),
@@ -44,7 +53,14 @@ pub fn create_spark_ext_function(name: &str) -> Result<ScalarFunctionImplementat
         "GetJsonObject" => Arc::new(spark_get_json_object::spark_get_json_object),
         "GetParsedJsonObject" => Arc::new(spark_get_json_object::spark_get_parsed_json_object),
         "ParseJson" => Arc::new(spark_get_json_object::spark_parse_json),
+        "RLike" => Arc::new(spark_regexp::spark_rlike),
+        "RegExpExtract" => Arc::new(spark_regexp::spark_regexp_extract),
+        "RegExpExtractAll" => Arc::new(spark_regexp::spark_regexp_extract_all),
+        "RegExpReplace" => Arc::new(spark_regexp::spark_regexp_replace),
         "MakeArray" => Arc::new(spark_make_array::array),
+        "MapKeys" => Arc::new(spark_map::spark_map_keys),
+        "MapValues" => Arc::new(spark_map::spark_map_values),
+        "ElementAt" => Arc::new(spark_map::spark_element_at),
         "StringSpace" => Arc::new(spark_strings::string_space),
         "StringRepeat" => Arc::new(spark_strings::string_repeat),
         "StringSplit" => Arc::new(spark_strings::string_split),
@@ -55,7 +71,16 @@ pub fn create_spark_ext_function(name: &str) -> Result<ScalarFunctionImplementat
         "Year" => Arc::new(spark_dates::spark_year),
         "Month" => Arc::new(spark_dates::spark_month),
         "Day" => Arc::new(spark_dates::spark_day),
+        "FromUnixTime" => Arc::new(spark_dates::spark_from_unixtime),
+        "UnixTimestamp" => Arc::new(spark_dates::spark_unix_timestamp),
+        "DateFormat" => Arc::new(spark_dates::spark_date_format),
+        "TruncDate" => Arc::new(spark_dates::spark_trunc_date),
+        "DateTrunc" => Arc::new(spark_dates::spark_date_trunc),
+        "AddMonths" => Arc::new(spark_dates::spark_add_months),
+        "MonthsBetween" => Arc::new(spark_dates::spark_months_between),
+        "NextDay" => Arc::new(spark_dates::spark_next_day),
+        "MakeDate" => Arc::new(spark_dates::spark_make_date),
         "BrickhouseArrayUnion" => Arc::new(brickhouse::array_union::array_union),
-        _ => df_unimplemented_err!("spark ext function not implemented: {name}")?,
+        _ => df_blaze_err!(UnsupportedExpression, "spark ext function not implemented: {name}")?,
     })
 }
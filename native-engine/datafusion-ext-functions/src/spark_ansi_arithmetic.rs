@@ -0,0 +1,237 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Arithmetic of integral and floating types in ansi mode, where overflows
+//! and division by zero fail the query with spark errors instead of wrapping
+//! or returning null. decimal arithmetic is handled by DecimalArithmeticExpr.
+
+use arrow::{
+    array::*,
+    compute::kernels::numeric,
+    datatypes::{ArrowNativeTypeOp, DataType},
+    downcast_primitive_array,
+    error::ArrowError,
+};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_plan::ColumnarValue,
+};
+use datafusion_ext_commons::{
+    df_execution_err, df_spark_err,
+    spark_error::{ARITHMETIC_OVERFLOW, DIVIDE_BY_ZERO},
+};
+
+pub fn spark_ansi_add(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    ansi_arithmetic(args, AnsiArithmeticOp::Add)
+}
+
+pub fn spark_ansi_subtract(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    ansi_arithmetic(args, AnsiArithmeticOp::Subtract)
+}
+
+pub fn spark_ansi_multiply(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    ansi_arithmetic(args, AnsiArithmeticOp::Multiply)
+}
+
+pub fn spark_ansi_divide(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    ansi_arithmetic(args, AnsiArithmeticOp::Divide)
+}
+
+pub fn spark_ansi_remainder(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    ansi_arithmetic(args, AnsiArithmeticOp::Remainder)
+}
+
+#[derive(Clone, Copy)]
+enum AnsiArithmeticOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+}
+
+impl AnsiArithmeticOp {
+    fn try_function_name(&self) -> &'static str {
+        match self {
+            AnsiArithmeticOp::Add => "try_add",
+            AnsiArithmeticOp::Subtract => "try_subtract",
+            AnsiArithmeticOp::Multiply => "try_multiply",
+            AnsiArithmeticOp::Divide | AnsiArithmeticOp::Remainder => "try_divide",
+        }
+    }
+}
+
+fn ansi_arithmetic(args: &[ColumnarValue], op: AnsiArithmeticOp) -> Result<ColumnarValue> {
+    if args.len() != 2 {
+        return df_execution_err!("ansi arithmetic expects 2 args, got {}", args.len());
+    }
+    let num_rows = args
+        .iter()
+        .find_map(|arg| match arg {
+            ColumnarValue::Array(array) => Some(array.len()),
+            ColumnarValue::Scalar(_) => None,
+        })
+        .unwrap_or(1);
+    let lhs = to_datum(&args[0])?;
+    let rhs = to_datum(&args[1])?;
+
+    // floating division by zero does not fail in arrow, so check divisors first.
+    // empty batches are not checked since a scalar divisor is not evaluated on
+    // any rows.
+    if matches!(op, AnsiArithmeticOp::Divide | AnsiArithmeticOp::Remainder)
+        && num_rows > 0
+        && has_zero(rhs.get().0)?
+    {
+        return divide_by_zero_err(op);
+    }
+
+    let result = match op {
+        AnsiArithmeticOp::Add => numeric::add(lhs.as_ref(), rhs.as_ref()),
+        AnsiArithmeticOp::Subtract => numeric::sub(lhs.as_ref(), rhs.as_ref()),
+        AnsiArithmeticOp::Multiply => numeric::mul(lhs.as_ref(), rhs.as_ref()),
+        AnsiArithmeticOp::Divide => numeric::div(lhs.as_ref(), rhs.as_ref()),
+        AnsiArithmeticOp::Remainder => numeric::rem(lhs.as_ref(), rhs.as_ref()),
+    };
+    let result = match result {
+        Ok(result) => result,
+        Err(ArrowError::ArithmeticOverflow(_)) => {
+            return df_spark_err!(
+                ARITHMETIC_OVERFLOW,
+                "{} overflow. Use '{}' to tolerate overflow and return NULL instead. \
+                If necessary set \"spark.sql.ansi.enabled\" to \"false\" to bypass this error.",
+                overflow_type_name(&args[0].data_type()),
+                op.try_function_name(),
+            );
+        }
+        Err(ArrowError::DivideByZero) => return divide_by_zero_err(op),
+        Err(err) => return Err(err.into()),
+    };
+
+    Ok(match (&args[0], &args[1]) {
+        (ColumnarValue::Scalar(_), ColumnarValue::Scalar(_)) => {
+            ColumnarValue::Scalar(ScalarValue::try_from_array(&result, 0)?)
+        }
+        _ => ColumnarValue::Array(result),
+    })
+}
+
+fn to_datum(value: &ColumnarValue) -> Result<Box<dyn Datum>> {
+    Ok(match value {
+        ColumnarValue::Array(array) => Box::new(array.clone()),
+        ColumnarValue::Scalar(scalar) => Box::new(scalar.to_scalar()?),
+    })
+}
+
+fn has_zero(array: &dyn Array) -> Result<bool> {
+    // -0.0 is also treated as zero, like java
+    Ok(downcast_primitive_array!(
+        array => array.iter().flatten().any(|v| v.is_zero()),
+        other => return df_execution_err!("ansi arithmetic: unsupported divisor type: {other}"),
+    ))
+}
+
+fn overflow_type_name(data_type: &DataType) -> String {
+    match data_type {
+        DataType::Int8 => "byte".to_string(),
+        DataType::Int16 => "short".to_string(),
+        DataType::Int32 => "integer".to_string(),
+        DataType::Int64 => "long".to_string(),
+        other => other.to_string(),
+    }
+}
+
+fn divide_by_zero_err<T>(op: AnsiArithmeticOp) -> Result<T> {
+    df_spark_err!(
+        DIVIDE_BY_ZERO,
+        "Division by zero. Use `{}` to tolerate divisor being 0 and return NULL instead. \
+        If necessary set \"spark.sql.ansi.enabled\" to \"false\" to bypass this error.",
+        op.try_function_name(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::array::{ArrayRef, Float64Array, Int32Array};
+    use datafusion::{
+        common::{Result, ScalarValue},
+        physical_plan::ColumnarValue,
+    };
+    use datafusion_ext_commons::spark_error::{
+        find_spark_error, ARITHMETIC_OVERFLOW, DIVIDE_BY_ZERO,
+    };
+
+    use crate::spark_ansi_arithmetic::{spark_ansi_add, spark_ansi_divide, spark_ansi_multiply};
+
+    #[test]
+    fn test_ansi_overflow() -> Result<()> {
+        let lhs: ArrayRef = Arc::new(Int32Array::from(vec![Some(1), None, Some(i32::MAX)]));
+        let output = spark_ansi_add(&[
+            ColumnarValue::Array(lhs.slice(0, 2)),
+            ColumnarValue::Scalar(ScalarValue::Int32(Some(1))),
+        ])?
+        .into_array(2)?;
+        let expected: ArrayRef = Arc::new(Int32Array::from(vec![Some(2), None]));
+        assert_eq!(&output, &expected);
+
+        let err = spark_ansi_add(&[
+            ColumnarValue::Array(lhs),
+            ColumnarValue::Scalar(ScalarValue::Int32(Some(1))),
+        ])
+        .unwrap_err()
+        .to_string();
+        let (error_class, message) = find_spark_error(&err).unwrap();
+        assert_eq!(error_class, ARITHMETIC_OVERFLOW);
+        assert!(message.starts_with("integer overflow. Use 'try_add'"));
+
+        let output = spark_ansi_multiply(&[
+            ColumnarValue::Scalar(ScalarValue::Int64(Some(3))),
+            ColumnarValue::Scalar(ScalarValue::Int64(Some(4))),
+        ])?;
+        assert!(matches!(
+            output,
+            ColumnarValue::Scalar(ScalarValue::Int64(Some(12)))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_ansi_divide_by_zero() -> Result<()> {
+        let lhs: ArrayRef = Arc::new(Float64Array::from(vec![Some(1.0), Some(2.0)]));
+        let rhs: ArrayRef = Arc::new(Float64Array::from(vec![Some(2.0), None]));
+        let output =
+            spark_ansi_divide(&[ColumnarValue::Array(lhs.clone()), ColumnarValue::Array(rhs)])?
+                .into_array(2)?;
+        let expected: ArrayRef = Arc::new(Float64Array::from(vec![Some(0.5), None]));
+        assert_eq!(&output, &expected);
+
+        let err = spark_ansi_divide(&[
+            ColumnarValue::Array(lhs.clone()),
+            ColumnarValue::Scalar(ScalarValue::Float64(Some(-0.0))),
+        ])
+        .unwrap_err()
+        .to_string();
+        assert_eq!(find_spark_error(&err).unwrap().0, DIVIDE_BY_ZERO);
+
+        // zero divisors are not evaluated on empty batches
+        let output = spark_ansi_divide(&[
+            ColumnarValue::Array(lhs.slice(0, 0)),
+            ColumnarValue::Scalar(ScalarValue::Float64(Some(0.0))),
+        ])?
+        .into_array(0)?;
+        assert!(output.is_empty());
+        Ok(())
+    }
+}
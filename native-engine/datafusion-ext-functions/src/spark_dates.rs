@@ -12,8 +12,35 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use arrow::compute::{date_part, DatePart};
-use datafusion::{common::Result, physical_plan::ColumnarValue};
+use std::sync::Arc;
+
+use arrow::{
+    array::{
+        timezone::Tz, Array, ArrayRef, AsArray, Date32Array, Float64Array, Int64Array, StringArray,
+        TimestampMicrosecondArray,
+    },
+    compute::{cast, date_part, DatePart},
+    datatypes::{DataType, Date32Type, Int32Type, Int64Type, TimestampMicrosecondType},
+};
+use chrono::{DateTime, Datelike, Days, LocalResult, Months, NaiveDate, Offset, TimeZone, Weekday};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_plan::ColumnarValue,
+};
+use datafusion_ext_commons::{
+    arrow::spark_cast::{
+        date_from_epoch_days, days_from_epoch, local_to_utc_micros, parse_zone_id,
+        utc_micros_to_local,
+    },
+    constant_cache::get_or_init_retained,
+    df_execution_err,
+};
+
+use crate::spark_datetime_pattern::DateTimePattern;
+
+const MICROS_PER_MILLIS: i64 = 1_000;
+const MICROS_PER_SECOND: i64 = 1_000_000;
+const SECONDS_PER_DAY: i64 = 86_400;
 
 pub fn spark_year(args: &[ColumnarValue]) -> Result<ColumnarValue> {
     let input = args[0].clone().into_array(1)?;
@@ -30,6 +57,402 @@ pub fn spark_day(args: &[ColumnarValue]) -> Result<ColumnarValue> {
     Ok(ColumnarValue::Array(date_part(&input, DatePart::Day)?))
 }
 
+/// from_unixtime(seconds, format, time_zone)
+pub fn spark_from_unixtime(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let Some(pattern) = literal_pattern(&args[1], "from_unixtime")? else {
+        return Ok(ColumnarValue::Scalar(ScalarValue::Utf8(None)));
+    };
+    let tz = literal_time_zone(&args[2], "from_unixtime")?;
+
+    let seconds = cast(&args[0].clone().into_array(1)?, &DataType::Int64)?;
+    let formatted: StringArray = seconds
+        .as_primitive::<Int64Type>()
+        .iter()
+        .map(|seconds| format_micros(seconds?.checked_mul(MICROS_PER_SECOND)?, &pattern, &tz))
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(formatted)))
+}
+
+/// unix_timestamp(string/date/timestamp, format, time_zone), unparsable
+/// strings produce nulls
+pub fn spark_unix_timestamp(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let tz = literal_time_zone(&args[2], "unix_timestamp")?;
+    let input = args[0].clone().into_array(1)?;
+
+    let seconds: Int64Array = match input.data_type() {
+        DataType::Utf8 => {
+            let Some(pattern) = literal_pattern(&args[1], "unix_timestamp")? else {
+                return Ok(ColumnarValue::Scalar(ScalarValue::Int64(None)));
+            };
+            input
+                .as_string::<i32>()
+                .iter()
+                .map(|s| {
+                    let micros = parse_micros(s?, &pattern, &tz)?;
+                    Some(micros.div_euclid(MICROS_PER_SECOND))
+                })
+                .collect()
+        }
+        DataType::Date32 => input
+            .as_primitive::<Date32Type>()
+            .iter()
+            .map(|days| Some(days_to_micros(days?, &tz)?.div_euclid(MICROS_PER_SECOND)))
+            .collect(),
+        DataType::Timestamp(..) => timestamp_array(&input, "unix_timestamp")?
+            .iter()
+            .map(|micros| Some(micros?.div_euclid(MICROS_PER_SECOND)))
+            .collect(),
+        other => df_execution_err!("unix_timestamp: unsupported input type: {other}")?,
+    };
+    Ok(ColumnarValue::Array(Arc::new(seconds)))
+}
+
+/// date_format(timestamp, format, time_zone)
+pub fn spark_date_format(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let Some(pattern) = literal_pattern(&args[1], "date_format")? else {
+        return Ok(ColumnarValue::Scalar(ScalarValue::Utf8(None)));
+    };
+    let tz = literal_time_zone(&args[2], "date_format")?;
+
+    let input = args[0].clone().into_array(1)?;
+    let formatted: StringArray = timestamp_array(&input, "date_format")?
+        .iter()
+        .map(|micros| format_micros(micros?, &pattern, &tz))
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(formatted)))
+}
+
+/// trunc(date, format), produces nulls for invalid formats
+pub fn spark_trunc_date(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let level = match literal_trunc_level(&args[1], "trunc")? {
+        Some(level) if level >= TruncLevel::Week => level,
+        _ => return Ok(ColumnarValue::Scalar(ScalarValue::Date32(None))),
+    };
+    let input = args[0].clone().into_array(1)?;
+    let truncated: Date32Array = date_array(&input, "trunc")?
+        .iter()
+        .map(|days| {
+            let date = date_from_epoch_days(days?)?;
+            Some(days_from_epoch(&trunc_date(date, level)?))
+        })
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(truncated)))
+}
+
+/// date_trunc(format, timestamp, time_zone), produces nulls for invalid
+/// formats
+pub fn spark_date_trunc(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let input = args[1].clone().into_array(1)?;
+    let Some(level) = literal_trunc_level(&args[0], "date_trunc")? else {
+        return Ok(ColumnarValue::Scalar(ScalarValue::try_from(
+            input.data_type(),
+        )?));
+    };
+    let tz = literal_time_zone(&args[2], "date_trunc")?;
+
+    let truncated: TimestampMicrosecondArray = timestamp_array(&input, "date_trunc")?
+        .iter()
+        .map(|micros| trunc_timestamp(micros?, level, &tz))
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(
+        truncated.with_data_type(input.data_type().clone()),
+    )))
+}
+
+/// add_months(date, num_months), days exceeding the resulting month are
+/// clamped to its last day
+pub fn spark_add_months(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let num_rows = num_rows(args);
+    let dates = args[0].clone().into_array(num_rows)?;
+    let num_months = cast(&args[1].clone().into_array(num_rows)?, &DataType::Int32)?;
+
+    let added: Date32Array = date_array(&dates, "add_months")?
+        .iter()
+        .zip(num_months.as_primitive::<Int32Type>())
+        .map(|(days, num_months)| {
+            let date = date_from_epoch_days(days?)?;
+            let num_months = num_months?;
+            let added = if num_months >= 0 {
+                date.checked_add_months(Months::new(num_months as u32))?
+            } else {
+                date.checked_sub_months(Months::new(num_months.unsigned_abs()))?
+            };
+            Some(days_from_epoch(&added))
+        })
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(added)))
+}
+
+/// months_between(timestamp1, timestamp2, round_off, time_zone)
+pub fn spark_months_between(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let round_off = match &args[2] {
+        ColumnarValue::Scalar(ScalarValue::Boolean(Some(round_off))) => *round_off,
+        ColumnarValue::Scalar(ScalarValue::Boolean(None)) => {
+            return Ok(ColumnarValue::Scalar(ScalarValue::Float64(None)));
+        }
+        _ => df_execution_err!("months_between round_off only supports literal boolean")?,
+    };
+    let tz = literal_time_zone(&args[3], "months_between")?;
+
+    let num_rows = num_rows(args);
+    let input1 = args[0].clone().into_array(num_rows)?;
+    let input2 = args[1].clone().into_array(num_rows)?;
+    let months: Float64Array = timestamp_array(&input1, "months_between")?
+        .iter()
+        .zip(timestamp_array(&input2, "months_between")?)
+        .map(|(micros1, micros2)| months_between(micros1?, micros2?, round_off, &tz))
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(months)))
+}
+
+/// next_day(date, day_of_week), produces nulls for invalid days of week
+pub fn spark_next_day(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let num_rows = num_rows(args);
+    let dates = args[0].clone().into_array(num_rows)?;
+    let days_of_week = args[1].clone().into_array(num_rows)?;
+
+    let next_days: Date32Array = date_array(&dates, "next_day")?
+        .iter()
+        .zip(days_of_week.as_string::<i32>())
+        .map(|(days, day_of_week)| {
+            let date = date_from_epoch_days(days?)?;
+            let day_of_week = parse_day_of_week(day_of_week?)?;
+            let num_days = match (day_of_week.num_days_from_monday() + 7
+                - date.weekday().num_days_from_monday())
+                % 7
+            {
+                0 => 7,
+                num_days => num_days,
+            };
+            Some(days_from_epoch(
+                &date.checked_add_days(Days::new(num_days as u64))?,
+            ))
+        })
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(next_days)))
+}
+
+/// make_date(year, month, day), produces nulls for invalid dates
+pub fn spark_make_date(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let num_rows = num_rows(args);
+    let years = cast(&args[0].clone().into_array(num_rows)?, &DataType::Int32)?;
+    let months = cast(&args[1].clone().into_array(num_rows)?, &DataType::Int32)?;
+    let days = cast(&args[2].clone().into_array(num_rows)?, &DataType::Int32)?;
+
+    let dates: Date32Array = years
+        .as_primitive::<Int32Type>()
+        .iter()
+        .zip(months.as_primitive::<Int32Type>())
+        .zip(days.as_primitive::<Int32Type>())
+        .map(|((year, month), day)| {
+            let (month, day) = (u32::try_from(month?).ok()?, u32::try_from(day?).ok()?);
+            let date = NaiveDate::from_ymd_opt(year?, month, day)?;
+            Some(days_from_epoch(&date))
+        })
+        .collect();
+    Ok(ColumnarValue::Array(Arc::new(dates)))
+}
+
+/// levels of trunc() and date_trunc(), in ascending order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum TruncLevel {
+    Microsecond,
+    Millisecond,
+    Second,
+    Minute,
+    Hour,
+    Day,
+    Week,
+    Month,
+    Quarter,
+    Year,
+}
+
+impl TruncLevel {
+    fn parse(format: &str) -> Option<Self> {
+        Some(match format.to_ascii_uppercase().as_str() {
+            "MICROSECOND" => TruncLevel::Microsecond,
+            "MILLISECOND" => TruncLevel::Millisecond,
+            "SECOND" => TruncLevel::Second,
+            "MINUTE" => TruncLevel::Minute,
+            "HOUR" => TruncLevel::Hour,
+            "DAY" | "DD" => TruncLevel::Day,
+            "WEEK" => TruncLevel::Week,
+            "MON" | "MONTH" | "MM" => TruncLevel::Month,
+            "QUARTER" => TruncLevel::Quarter,
+            "YEAR" | "YYYY" | "YY" => TruncLevel::Year,
+            _ => return None,
+        })
+    }
+}
+
+fn trunc_date(date: NaiveDate, level: TruncLevel) -> Option<NaiveDate> {
+    match level {
+        TruncLevel::Week => {
+            date.checked_sub_days(Days::new(date.weekday().num_days_from_monday() as u64))
+        }
+        TruncLevel::Month => date.with_day(1),
+        TruncLevel::Quarter => NaiveDate::from_ymd_opt(date.year(), date.month0() / 3 * 3 + 1, 1),
+        TruncLevel::Year => NaiveDate::from_ymd_opt(date.year(), 1, 1),
+        _ => Some(date),
+    }
+}
+
+fn trunc_timestamp(micros: i64, level: TruncLevel, tz: &Tz) -> Option<i64> {
+    let unit = match level {
+        TruncLevel::Microsecond => return Some(micros),
+        TruncLevel::Millisecond => return Some(micros - micros.rem_euclid(MICROS_PER_MILLIS)),
+        TruncLevel::Second => return Some(micros - micros.rem_euclid(MICROS_PER_SECOND)),
+        TruncLevel::Minute => 60,
+        TruncLevel::Hour => 3600,
+        TruncLevel::Day => SECONDS_PER_DAY,
+        _ => {
+            let date = utc_micros_to_local(micros, tz)?.date();
+            return days_to_micros(days_from_epoch(&trunc_date(date, level)?), tz);
+        }
+    };
+
+    // truncate in local time, keeping the original offset if it is still valid
+    // for the truncated time like java's ZonedDateTime.truncatedTo()
+    let datetime = DateTime::from_timestamp_micros(micros)?.with_timezone(tz);
+    let local = datetime.naive_local();
+    let local_seconds = local.and_utc().timestamp();
+    let truncated_seconds = local_seconds - local_seconds.rem_euclid(unit);
+    let truncated = DateTime::from_timestamp(truncated_seconds, 0)?.naive_utc();
+    match tz.from_local_datetime(&truncated) {
+        LocalResult::Ambiguous(earliest, latest) => {
+            let offset = datetime.offset().fix();
+            let preferred = if latest.offset().fix() == offset {
+                latest
+            } else {
+                earliest
+            };
+            Some(preferred.timestamp_micros())
+        }
+        _ => local_to_utc_micros(&truncated, tz),
+    }
+}
+
+fn months_between(micros1: i64, micros2: i64, round_off: bool, tz: &Tz) -> Option<f64> {
+    let date1 = utc_micros_to_local(micros1, tz)?.date();
+    let date2 = utc_micros_to_local(micros2, tz)?.date();
+    let months1 = date1.year() as i64 * 12 + date1.month() as i64;
+    let months2 = date2.year() as i64 * 12 + date2.month() as i64;
+    let month_diff = (months1 - months2) as f64;
+
+    let is_last_day = |date: NaiveDate| date.succ_opt().map(|d| d.day() == 1).unwrap_or(true);
+    if date1.day() == date2.day() || (is_last_day(date1) && is_last_day(date2)) {
+        return Some(month_diff);
+    }
+
+    // like spark and hive, use seconds to avoid precision loss
+    let seconds_in_day1 =
+        (micros1 - days_to_micros(days_from_epoch(&date1), tz)?) / MICROS_PER_SECOND;
+    let seconds_in_day2 =
+        (micros2 - days_to_micros(days_from_epoch(&date2), tz)?) / MICROS_PER_SECOND;
+    let seconds_diff = (date1.day() as i64 - date2.day() as i64) * SECONDS_PER_DAY
+        + seconds_in_day1
+        - seconds_in_day2;
+    let diff = month_diff + seconds_diff as f64 / (31 * SECONDS_PER_DAY) as f64;
+    if round_off {
+        return Some((diff * 1e8 + 0.5).floor() / 1e8);
+    }
+    Some(diff)
+}
+
+fn parse_day_of_week(s: &str) -> Option<Weekday> {
+    Some(match s.to_ascii_uppercase().as_str() {
+        "MO" | "MON" | "MONDAY" => Weekday::Mon,
+        "TU" | "TUE" | "TUESDAY" => Weekday::Tue,
+        "WE" | "WED" | "WEDNESDAY" => Weekday::Wed,
+        "TH" | "THU" | "THURSDAY" => Weekday::Thu,
+        "FR" | "FRI" | "FRIDAY" => Weekday::Fri,
+        "SA" | "SAT" | "SATURDAY" => Weekday::Sat,
+        "SU" | "SUN" | "SUNDAY" => Weekday::Sun,
+        _ => return None,
+    })
+}
+
+fn format_micros(micros: i64, pattern: &DateTimePattern, tz: &Tz) -> Option<String> {
+    let datetime = DateTime::from_timestamp_micros(micros)?.with_timezone(tz);
+    let offset_seconds = datetime.offset().fix().local_minus_utc();
+    Some(pattern.format(&datetime.naive_local(), offset_seconds))
+}
+
+fn parse_micros(s: &str, pattern: &DateTimePattern, tz: &Tz) -> Option<i64> {
+    let (local, offset_seconds) = pattern.parse(s)?;
+    match offset_seconds {
+        Some(offset_seconds) => {
+            let micros = local.and_utc().timestamp_micros();
+            micros.checked_sub(offset_seconds as i64 * MICROS_PER_SECOND)
+        }
+        None => local_to_utc_micros(&local, tz),
+    }
+}
+
+/// converts days since epoch to micros of the start of the day in time zone
+fn days_to_micros(days: i32, tz: &Tz) -> Option<i64> {
+    let start_of_day = date_from_epoch_days(days)?.and_hms_opt(0, 0, 0)?;
+    local_to_utc_micros(&start_of_day, tz)
+}
+
+fn num_rows(args: &[ColumnarValue]) -> usize {
+    args.iter()
+        .find_map(|arg| match arg {
+            ColumnarValue::Array(array) => Some(array.len()),
+            ColumnarValue::Scalar(_) => None,
+        })
+        .unwrap_or(1)
+}
+
+fn date_array<'a>(input: &'a ArrayRef, fn_name: &str) -> Result<&'a Date32Array> {
+    match input.as_primitive_opt::<Date32Type>() {
+        Some(dates) => Ok(dates),
+        None => df_execution_err!("{fn_name}: unsupported input type: {}", input.data_type()),
+    }
+}
+
+fn timestamp_array<'a>(
+    input: &'a ArrayRef,
+    fn_name: &str,
+) -> Result<&'a TimestampMicrosecondArray> {
+    match input.as_primitive_opt::<TimestampMicrosecondType>() {
+        Some(timestamps) => Ok(timestamps),
+        None => df_execution_err!("{fn_name}: unsupported input type: {}", input.data_type()),
+    }
+}
+
+fn literal_pattern(arg: &ColumnarValue, fn_name: &str) -> Result<Option<Arc<DateTimePattern>>> {
+    match arg {
+        ColumnarValue::Scalar(ScalarValue::Utf8(Some(pattern))) => {
+            Ok(Some(get_or_init_retained(pattern, || {
+                DateTimePattern::try_new(pattern)
+            })?))
+        }
+        ColumnarValue::Scalar(ScalarValue::Utf8(None)) => Ok(None),
+        _ => df_execution_err!("{fn_name} format only supports literal string"),
+    }
+}
+
+fn literal_trunc_level(arg: &ColumnarValue, fn_name: &str) -> Result<Option<TruncLevel>> {
+    match arg {
+        ColumnarValue::Scalar(ScalarValue::Utf8(format)) => {
+            Ok(format.as_deref().and_then(TruncLevel::parse))
+        }
+        _ => df_execution_err!("{fn_name} format only supports literal string"),
+    }
+}
+
+fn literal_time_zone(arg: &ColumnarValue, fn_name: &str) -> Result<Tz> {
+    match arg {
+        ColumnarValue::Scalar(ScalarValue::Utf8(Some(zone_id))) => match parse_zone_id(zone_id) {
+            Some(tz) => Ok(tz),
+            None => df_execution_err!("{fn_name}: invalid time zone: {zone_id}"),
+        },
+        _ => df_execution_err!("{fn_name} time zone only supports literal string"),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
@@ -38,6 +461,18 @@ mod tests {
 
     use super::*;
 
+    fn literal(value: impl Into<ScalarValue>) -> ColumnarValue {
+        ColumnarValue::Scalar(value.into())
+    }
+
+    fn timestamps(seconds: Vec<Option<i64>>) -> ColumnarValue {
+        ColumnarValue::Array(Arc::new(TimestampMicrosecondArray::from_iter(
+            seconds
+                .into_iter()
+                .map(|s| s.map(|s| s * MICROS_PER_SECOND)),
+        )))
+    }
+
     #[test]
     fn test_spark_year() {
         let input = Arc::new(Date32Array::from(vec![
@@ -95,4 +530,189 @@ mod tests {
             &expected_ret
         );
     }
+
+    #[test]
+    fn test_spark_from_unixtime() -> Result<()> {
+        let input = ColumnarValue::Array(Arc::new(Int64Array::from(vec![
+            Some(0),
+            Some(1700000000),
+            None,
+        ])));
+        let output = spark_from_unixtime(&[
+            input,
+            literal("yyyy-MM-dd HH:mm:ss"),
+            literal("Asia/Shanghai"),
+        ])?;
+        let expected: ArrayRef = Arc::new(StringArray::from(vec![
+            Some("1970-01-01 08:00:00"),
+            Some("2023-11-15 06:13:20"),
+            None,
+        ]));
+        assert_eq!(&output.into_array(1)?, &expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_spark_unix_timestamp() -> Result<()> {
+        let input = ColumnarValue::Array(Arc::new(StringArray::from(vec![
+            Some("2023-11-15 06:13:20"),
+            Some("2023-11-15"),
+            None,
+        ])));
+        let output = spark_unix_timestamp(&[
+            input,
+            literal("yyyy-MM-dd HH:mm:ss"),
+            literal("Asia/Shanghai"),
+        ])?;
+        let expected: ArrayRef = Arc::new(Int64Array::from(vec![Some(1700000000), None, None]));
+        assert_eq!(&output.into_array(1)?, &expected);
+
+        let input = ColumnarValue::Array(Arc::new(Date32Array::from(vec![Some(1), None])));
+        let output = spark_unix_timestamp(&[
+            input,
+            literal("yyyy-MM-dd HH:mm:ss"),
+            literal("Asia/Shanghai"),
+        ])?;
+        let expected: ArrayRef = Arc::new(Int64Array::from(vec![Some(57600), None]));
+        assert_eq!(&output.into_array(1)?, &expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_spark_date_format() -> Result<()> {
+        let output = spark_date_format(&[
+            timestamps(vec![Some(1700000000), None]),
+            literal("EEE, d MMM yyyy HH:mm:ss.SSS"),
+            literal("UTC"),
+        ])?;
+        let expected: ArrayRef = Arc::new(StringArray::from(vec![
+            Some("Tue, 14 Nov 2023 22:13:20.000"),
+            None,
+        ]));
+        assert_eq!(&output.into_array(1)?, &expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_spark_trunc_date() -> Result<()> {
+        let input = ColumnarValue::Array(Arc::new(Date32Array::from(vec![Some(19858), None])));
+        for (format, expected) in [
+            ("YEAR", Some(19723)),
+            ("quarter", Some(19814)),
+            ("MM", Some(19844)),
+            ("WEEK", Some(19856)),
+            ("DAY", None),
+            ("invalid", None),
+        ] {
+            let output = spark_trunc_date(&[input.clone(), literal(format)])?;
+            let expected: ArrayRef = Arc::new(Date32Array::from(vec![expected, None]));
+            assert_eq!(&output.into_array(2)?, &expected, "format: {format}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_spark_date_trunc() -> Result<()> {
+        // 2024-11-03 01:30:00 in America/Los_Angeles, before and after the
+        // daylight saving time ends
+        let input = timestamps(vec![Some(1730622600), Some(1730626200), None]);
+        for (format, expected) in [
+            ("HOUR", vec![Some(1730620800), Some(1730624400), None]),
+            ("DD", vec![Some(1730617200), Some(1730617200), None]),
+            ("SECOND", vec![Some(1730622600), Some(1730626200), None]),
+        ] {
+            let output = spark_date_trunc(&[
+                literal(format),
+                input.clone(),
+                literal("America/Los_Angeles"),
+            ])?;
+            let expected = timestamps(expected).into_array(1)?;
+            assert_eq!(&output.into_array(1)?, &expected, "format: {format}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_spark_add_months() -> Result<()> {
+        let input = ColumnarValue::Array(Arc::new(Date32Array::from(vec![
+            Some(19753),
+            Some(19813),
+            None,
+        ])));
+        let num_months =
+            ColumnarValue::Array(Arc::new(Int32Array::from(vec![Some(1), Some(-1), Some(1)])));
+        let output = spark_add_months(&[input, num_months])?;
+        let expected: ArrayRef = Arc::new(Date32Array::from(vec![Some(19782), Some(19782), None]));
+        assert_eq!(&output.into_array(1)?, &expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_spark_months_between() -> Result<()> {
+        let input1 = timestamps(vec![Some(857125800), Some(19782 * 86400), None]);
+        let input2 = timestamps(vec![Some(846633600), Some(19753 * 86400), Some(0)]);
+        let output = spark_months_between(&[
+            input1.clone(),
+            input2.clone(),
+            literal(true),
+            literal("UTC"),
+        ])?;
+        let expected: ArrayRef =
+            Arc::new(Float64Array::from(vec![Some(3.94959677), Some(1.0), None]));
+        assert_eq!(&output.into_array(1)?, &expected);
+
+        let output = spark_months_between(&[input1, input2, literal(false), literal("UTC")])?;
+        let expected: ArrayRef = Arc::new(Float64Array::from(vec![
+            Some(3.9495967741935485),
+            Some(1.0),
+            None,
+        ]));
+        assert_eq!(&output.into_array(1)?, &expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_spark_next_day() -> Result<()> {
+        let input = ColumnarValue::Array(Arc::new(Date32Array::from(vec![
+            Some(16449),
+            Some(16449),
+            Some(16449),
+            None,
+        ])));
+        let days_of_week = ColumnarValue::Array(Arc::new(StringArray::from(vec![
+            Some("TU"),
+            Some("wed"),
+            Some("xx"),
+            Some("MON"),
+        ])));
+        let output = spark_next_day(&[input, days_of_week])?;
+        let expected: ArrayRef = Arc::new(Date32Array::from(vec![
+            Some(16455),
+            Some(16456),
+            None,
+            None,
+        ]));
+        assert_eq!(&output.into_array(1)?, &expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_spark_make_date() -> Result<()> {
+        let output = spark_make_date(&[
+            ColumnarValue::Array(Arc::new(Int32Array::from(vec![
+                Some(2013),
+                Some(2019),
+                None,
+            ]))),
+            ColumnarValue::Array(Arc::new(Int32Array::from(vec![Some(7), Some(2), Some(1)]))),
+            literal(15i32),
+        ])?;
+        let expected: ArrayRef = Arc::new(Date32Array::from(vec![Some(15901), None, None]));
+        assert_eq!(&output.into_array(1)?, &expected);
+
+        let output = spark_make_date(&[literal(2019i32), literal(2i32), literal(30i32)])?;
+        let expected: ArrayRef = Arc::new(Date32Array::from(vec![None]));
+        assert_eq!(&output.into_array(1)?, &expected);
+        Ok(())
+    }
 }
@@ -0,0 +1,486 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Datetime patterns of spark (`DateTimeFormatter`/`SimpleDateFormat`
+//! letters), used by date_format, from_unixtime and unix_timestamp.
+//!
+//! supported letters are `y`, `M`, `L`, `d`, `D`, `Q`, `q`, `E`, `a`, `H`,
+//! `h`, `K`, `k`, `m`, `s`, `S`, `Z`, `X` and `x`, texts are in english.
+//! patterns with other letters or optional sections are rejected.
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike};
+use datafusion::common::Result;
+use datafusion_ext_commons::df_execution_err;
+
+const SHORT_MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+const SHORT_WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+const WEEKDAYS: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum PatternToken {
+    Literal(String),
+    Field(char, usize),
+}
+
+/// A compiled datetime pattern
+#[derive(Debug, Clone)]
+pub struct DateTimePattern {
+    tokens: Vec<PatternToken>,
+}
+
+impl DateTimePattern {
+    pub fn try_new(pattern: &str) -> Result<Self> {
+        let mut tokens = vec![];
+        let mut chars = pattern.chars().peekable();
+        let mut literal = String::new();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\'' => {
+                    // '' is a quote, otherwise quoted until the next quote
+                    if chars.peek() == Some(&'\'') {
+                        chars.next();
+                        literal.push('\'');
+                        continue;
+                    }
+                    loop {
+                        match chars.next() {
+                            Some('\'') if chars.peek() == Some(&'\'') => {
+                                chars.next();
+                                literal.push('\'');
+                            }
+                            Some('\'') => break,
+                            Some(c) => literal.push(c),
+                            None => df_execution_err!("unclosed quote in pattern: {pattern}")?,
+                        }
+                    }
+                }
+                c if c.is_ascii_alphabetic() => {
+                    let mut width = 1;
+                    while chars.peek() == Some(&c) {
+                        chars.next();
+                        width += 1;
+                    }
+                    let max_width = match c {
+                        'y' | 'S' => 9,
+                        'Z' => 5,
+                        'M' | 'L' | 'E' => 4,
+                        'D' | 'X' | 'x' => 3,
+                        'd' | 'Q' | 'q' | 'H' | 'h' | 'K' | 'k' | 'm' | 's' => 2,
+                        'a' => 1,
+                        _ => 0,
+                    };
+                    if width > max_width {
+                        return df_execution_err!(
+                            "unsupported pattern letters '{}' in pattern: {pattern}",
+                            c.to_string().repeat(width),
+                        );
+                    }
+                    if !literal.is_empty() {
+                        tokens.push(PatternToken::Literal(std::mem::take(&mut literal)));
+                    }
+                    tokens.push(PatternToken::Field(c, width));
+                }
+                '[' | ']' | '{' | '}' | '#' => {
+                    df_execution_err!("unsupported character '{c}' in pattern: {pattern}")?;
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            tokens.push(PatternToken::Literal(literal));
+        }
+        Ok(Self { tokens })
+    }
+
+    /// formats a local date time with its offset from utc in seconds
+    pub fn format(&self, local: &NaiveDateTime, offset_seconds: i32) -> String {
+        let mut formatted = String::new();
+        for token in &self.tokens {
+            let (letter, width) = match token {
+                PatternToken::Literal(literal) => {
+                    formatted.push_str(literal);
+                    continue;
+                }
+                &PatternToken::Field(letter, width) => (letter, width),
+            };
+            let number = |formatted: &mut String, value: i64| match width {
+                1 => formatted.push_str(&value.to_string()),
+                _ => formatted.push_str(&format!("{value:0width$}")),
+            };
+            match letter {
+                'y' if width == 2 => number(&mut formatted, local.year().rem_euclid(100) as i64),
+                'y' => number(&mut formatted, local.year() as i64),
+                'M' | 'L' => match width {
+                    3 => formatted.push_str(SHORT_MONTHS[local.month0() as usize]),
+                    4 => formatted.push_str(MONTHS[local.month0() as usize]),
+                    _ => number(&mut formatted, local.month() as i64),
+                },
+                'd' => number(&mut formatted, local.day() as i64),
+                'D' => number(&mut formatted, local.ordinal() as i64),
+                'Q' | 'q' => number(&mut formatted, (local.month0() / 3 + 1) as i64),
+                'E' => {
+                    let weekday = local.weekday().num_days_from_monday() as usize;
+                    match width {
+                        4 => formatted.push_str(WEEKDAYS[weekday]),
+                        _ => formatted.push_str(SHORT_WEEKDAYS[weekday]),
+                    }
+                }
+                'a' => formatted.push_str(if local.hour() < 12 { "AM" } else { "PM" }),
+                'H' => number(&mut formatted, local.hour() as i64),
+                'h' => number(&mut formatted, ((local.hour() + 11) % 12 + 1) as i64),
+                'K' => number(&mut formatted, (local.hour() % 12) as i64),
+                'k' => number(&mut formatted, (local.hour() + 23) as i64 % 24 + 1),
+                'm' => number(&mut formatted, local.minute() as i64),
+                's' => number(&mut formatted, local.second() as i64),
+                'S' => {
+                    let nanos = format!("{:09}", local.nanosecond() % 1_000_000_000);
+                    formatted.push_str(&nanos[..width]);
+                }
+                'Z' | 'X' | 'x' => format_offset(&mut formatted, letter, width, offset_seconds),
+                _ => unreachable!("unsupported pattern letter: {letter}"),
+            }
+        }
+        formatted
+    }
+
+    /// parses a local date time and the optional parsed offset from utc in
+    /// seconds. like spark, missing fields default to 1970-01-01 00:00:00 and
+    /// the whole input must be parsed.
+    pub fn parse(&self, s: &str) -> Option<(NaiveDateTime, Option<i32>)> {
+        let mut parser = FieldsParser::new(s);
+        let mut fields = ParsedFields::default();
+
+        for (i, token) in self.tokens.iter().enumerate() {
+            let (letter, width) = match token {
+                PatternToken::Literal(literal) => {
+                    parser.literal(literal)?;
+                    continue;
+                }
+                &PatternToken::Field(letter, width) => (letter, width),
+            };
+
+            // adjacent numeric fields (like yyyyMMdd) are parsed in fixed widths
+            let next_is_numeric = matches!(
+                self.tokens.get(i + 1),
+                Some(PatternToken::Field(c, w)) if is_numeric_field(*c, *w)
+            );
+            let (min_digits, max_digits) = match (letter, width) {
+                ('y', 2) => (2, 2),
+                ('y', w) if next_is_numeric => (w, w),
+                ('y', w) => (w, 9),
+                ('S', w) => (1, w),
+                ('D', 1) => (1, 3),
+                (_, 1) if !next_is_numeric => (1, 2),
+                (_, w) => (w, w),
+            };
+
+            match letter {
+                'y' => {
+                    let year = parser.number(min_digits, max_digits)? as i32;
+                    fields.year = if width == 2 { 2000 + year } else { year };
+                }
+                'M' | 'L' if width >= 3 => {
+                    let names = if width == 3 { SHORT_MONTHS } else { MONTHS };
+                    fields.month = parser.text(&names)? as u32 + 1;
+                }
+                'M' | 'L' => fields.month = parser.number(min_digits, max_digits)? as u32,
+                'd' => fields.day = parser.number(min_digits, max_digits)? as u32,
+                'D' => fields.day_of_year = Some(parser.number(min_digits, max_digits)? as u32),
+                'Q' | 'q' => {
+                    parser.number(min_digits, max_digits)?;
+                }
+                'E' => {
+                    let names = if width == 4 { WEEKDAYS } else { SHORT_WEEKDAYS };
+                    parser.text(&names)?;
+                }
+                'a' => fields.pm = Some(parser.text(&["AM", "PM"])? == 1),
+                'H' => fields.hour = parser.number(min_digits, max_digits)? as u32,
+                'h' | 'K' => {
+                    fields.hour = parser.number(min_digits, max_digits)? as u32 % 12;
+                }
+                'k' => fields.hour = parser.number(min_digits, max_digits)? as u32 % 24,
+                'm' => fields.minute = parser.number(min_digits, max_digits)? as u32,
+                's' => fields.second = parser.number(min_digits, max_digits)? as u32,
+                'S' => {
+                    let start = parser.pos;
+                    let fraction = parser.number(min_digits, max_digits)? as u32;
+                    let num_digits = (parser.pos - start) as u32;
+                    fields.nanos = fraction * 10u32.pow(9 - num_digits);
+                }
+                'Z' | 'X' | 'x' => fields.offset_seconds = Some(parser.offset(letter)?),
+                _ => return None,
+            }
+        }
+        if !parser.is_finished() {
+            return None;
+        }
+
+        if fields.pm == Some(true) && fields.hour < 12 {
+            fields.hour += 12;
+        }
+        let date = match fields.day_of_year {
+            Some(day_of_year) => NaiveDate::from_yo_opt(fields.year, day_of_year)?,
+            None => NaiveDate::from_ymd_opt(fields.year, fields.month, fields.day)?,
+        };
+        let local =
+            date.and_hms_nano_opt(fields.hour, fields.minute, fields.second, fields.nanos)?;
+        Some((local, fields.offset_seconds))
+    }
+}
+
+fn is_numeric_field(letter: char, width: usize) -> bool {
+    match letter {
+        'M' | 'L' => width <= 2,
+        'y' | 'd' | 'D' | 'Q' | 'q' | 'H' | 'h' | 'K' | 'k' | 'm' | 's' | 'S' => true,
+        _ => false,
+    }
+}
+
+fn format_offset(formatted: &mut String, letter: char, width: usize, offset_seconds: i32) {
+    let sign = if offset_seconds < 0 { '-' } else { '+' };
+    let hours = offset_seconds.abs() / 3600;
+    let minutes = offset_seconds.abs() / 60 % 60;
+
+    match (letter, width) {
+        ('X', _) | ('Z', 5) if offset_seconds == 0 => formatted.push('Z'),
+        ('Z', 4) if offset_seconds == 0 => formatted.push_str("GMT"),
+        ('Z', 4) => formatted.push_str(&format!("GMT{sign}{hours:02}:{minutes:02}")),
+        ('Z', 5) | (_, 3) => formatted.push_str(&format!("{sign}{hours:02}:{minutes:02}")),
+        ('X' | 'x', 1) if minutes == 0 => formatted.push_str(&format!("{sign}{hours:02}")),
+        _ => formatted.push_str(&format!("{sign}{hours:02}{minutes:02}")),
+    }
+}
+
+struct ParsedFields {
+    year: i32,
+    month: u32,
+    day: u32,
+    day_of_year: Option<u32>,
+    hour: u32,
+    minute: u32,
+    second: u32,
+    nanos: u32,
+    pm: Option<bool>,
+    offset_seconds: Option<i32>,
+}
+
+impl Default for ParsedFields {
+    fn default() -> Self {
+        Self {
+            year: 1970,
+            month: 1,
+            day: 1,
+            day_of_year: None,
+            hour: 0,
+            minute: 0,
+            second: 0,
+            nanos: 0,
+            pm: None,
+            offset_seconds: None,
+        }
+    }
+}
+
+struct FieldsParser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> FieldsParser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            input: input.as_bytes(),
+            pos: 0,
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.pos == self.input.len()
+    }
+
+    fn remaining(&self) -> &'a [u8] {
+        &self.input[self.pos..]
+    }
+
+    /// matches a literal case insensitively
+    fn literal(&mut self, literal: &str) -> Option<()> {
+        let remaining = self.remaining();
+        if remaining.len() < literal.len()
+            || !remaining[..literal.len()].eq_ignore_ascii_case(literal.as_bytes())
+        {
+            return None;
+        }
+        self.pos += literal.len();
+        Some(())
+    }
+
+    fn number(&mut self, min_digits: usize, max_digits: usize) -> Option<i64> {
+        let num_digits = self
+            .remaining()
+            .iter()
+            .take(max_digits)
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        if num_digits < min_digits {
+            return None;
+        }
+        let digits = &self.remaining()[..num_digits];
+        self.pos += num_digits;
+        Some(digits.iter().fold(0, |v, b| v * 10 + (b - b'0') as i64))
+    }
+
+    /// matches one of the names case insensitively, returns the index of the
+    /// longest matched name
+    fn text(&mut self, names: &[&str]) -> Option<usize> {
+        let remaining = self.remaining();
+        let (idx, name) = names
+            .iter()
+            .enumerate()
+            .filter(|(_, name)| {
+                remaining.len() >= name.len()
+                    && remaining[..name.len()].eq_ignore_ascii_case(name.as_bytes())
+            })
+            .max_by_key(|(_, name)| name.len())?;
+        self.pos += name.len();
+        Some(idx)
+    }
+
+    /// parses an offset like `Z`, `GMT`, `+08`, `+0800`, `+08:00` or
+    /// `GMT+08:00`, returns the offset in seconds
+    fn offset(&mut self, letter: char) -> Option<i32> {
+        if letter != 'x' && self.literal("Z").is_some() {
+            return Some(0);
+        }
+        if letter == 'Z'
+            && self.literal("GMT").is_some()
+            && !matches!(self.remaining().first(), Some(b'+' | b'-'))
+        {
+            return Some(0);
+        }
+        let sign = match self.remaining().first()? {
+            b'+' => 1,
+            b'-' => -1,
+            _ => return None,
+        };
+        self.pos += 1;
+        let hours = self.number(2, 2)? as i32;
+        let _ = self.literal(":");
+        let minutes = self.number(2, 2).unwrap_or(0) as i32;
+        if hours > 18 || minutes > 59 {
+            return None;
+        }
+        Some(sign * (hours * 3600 + minutes * 60))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+
+    use crate::spark_datetime_pattern::DateTimePattern;
+
+    #[test]
+    fn test_format() {
+        let local = NaiveDate::from_ymd_opt(2024, 3, 9)
+            .unwrap()
+            .and_hms_micro_opt(15, 4, 5, 123456)
+            .unwrap();
+        let cases = [
+            ("yyyy-MM-dd HH:mm:ss", "2024-03-09 15:04:05"),
+            ("yy/M/d h:m:s a", "24/3/9 3:4:5 PM"),
+            ("EEE, dd MMM yyyy", "Sat, 09 Mar 2024"),
+            ("EEEE MMMM D 'Q'Q", "Saturday March 69 Q1"),
+            ("HH:mm:ss.SSS 'o''clock'", "15:04:05.123 o'clock"),
+            (
+                "yyyyMMddHHmmssSSSSSS Z XXX",
+                "20240309150405123456 +0800 +08:00",
+            ),
+        ];
+        for (pattern, expected) in cases {
+            let pattern = DateTimePattern::try_new(pattern).unwrap();
+            assert_eq!(pattern.format(&local, 8 * 3600), expected);
+        }
+        assert!(DateTimePattern::try_new("yyyy-MM-dd G").is_err());
+        assert!(DateTimePattern::try_new("yyyy[-MM]").is_err());
+    }
+
+    #[test]
+    fn test_parse() {
+        let datetime = |y, m, d, h, mi, s, micros| {
+            NaiveDate::from_ymd_opt(y, m, d)
+                .unwrap()
+                .and_hms_micro_opt(h, mi, s, micros)
+                .unwrap()
+        };
+        let cases = [
+            (
+                "yyyy-MM-dd HH:mm:ss",
+                "2024-03-09 15:04:05",
+                Some((datetime(2024, 3, 9, 15, 4, 5, 0), None)),
+            ),
+            (
+                "yyyyMMdd",
+                "20240309",
+                Some((datetime(2024, 3, 9, 0, 0, 0, 0), None)),
+            ),
+            (
+                "dd/MMM/yyyy:HH:mm:ss Z",
+                "09/mar/2024:15:04:05 -0700",
+                Some((datetime(2024, 3, 9, 15, 4, 5, 0), Some(-7 * 3600))),
+            ),
+            (
+                "h:mm a, yyyy-M-d",
+                "3:04 PM, 2024-3-9",
+                Some((datetime(2024, 3, 9, 15, 4, 0, 0), None)),
+            ),
+            (
+                "HH:mm:ss.SSSSSS",
+                "15:04:05.12",
+                Some((datetime(1970, 1, 1, 15, 4, 5, 120000), None)),
+            ),
+            ("yyyy-MM-dd", "2024-02-30", None),
+            ("yyyy-MM-dd", "2024-03-09 15:04:05", None),
+            ("yyyy-MM-dd", "2024-3-09", None),
+        ];
+        for (pattern, input, expected) in cases {
+            let pattern = DateTimePattern::try_new(pattern).unwrap();
+            assert_eq!(pattern.parse(input), expected, "input: {input}");
+        }
+    }
+}
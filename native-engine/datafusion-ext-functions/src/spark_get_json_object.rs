@@ -22,7 +22,9 @@ use datafusion::{
     common::{Result, ScalarValue},
     physical_plan::ColumnarValue,
 };
-use datafusion_ext_commons::{downcast_any, uda::UserDefinedArray};
+use datafusion_ext_commons::{
+    constant_cache::get_or_init_retained, downcast_any, uda::UserDefinedArray,
+};
 use sonic_rs::{JsonContainerTrait, JsonValueTrait};
 
 /// implement hive/spark's UDFGetJson
@@ -50,9 +52,10 @@ pub fn spark_get_json_object(args: &[ColumnarValue]) -> Result<ColumnarValue> {
         _ => unreachable!("path should be ScalarValue"),
     };
 
-    let mut evaluator = match HiveGetJsonObjectEvaluator::try_new(path_string) {
-        Ok(evaluator) => evaluator,
-        Err(_) => {
+    let evaluator = get_cached_evaluator(path_string)?;
+    let evaluator = match evaluator.as_ref() {
+        Some(evaluator) => evaluator,
+        None => {
             return Ok(ColumnarValue::Array(new_null_array(
                 &DataType::Utf8,
                 json_strings.len(),
@@ -125,9 +128,10 @@ pub fn spark_get_parsed_json_object(args: &[ColumnarValue]) -> Result<ColumnarVa
         _ => unreachable!("path should be ScalarValue"),
     };
 
-    let mut evaluator = match HiveGetJsonObjectEvaluator::try_new(path_string) {
-        Ok(evaluator) => evaluator,
-        Err(_) => {
+    let evaluator = get_cached_evaluator(path_string)?;
+    let evaluator = match evaluator.as_ref() {
+        Some(evaluator) => evaluator,
+        None => {
             return Ok(ColumnarValue::Array(new_null_array(
                 &DataType::Utf8,
                 json_array.len(),
@@ -198,6 +202,13 @@ enum HiveGetJsonObjectError {
     InvalidInput,
 }
 
+/// parsed json paths are cached across tasks, invalid paths are cached as None
+fn get_cached_evaluator(json_path: &str) -> Result<Arc<Option<HiveGetJsonObjectEvaluator>>> {
+    get_or_init_retained(json_path, || {
+        Ok(HiveGetJsonObjectEvaluator::try_new(json_path).ok())
+    })
+}
+
 struct HiveGetJsonObjectEvaluator {
     matchers: Vec<HiveGetJsonObjectMatcher>,
 }
@@ -222,7 +233,7 @@ impl HiveGetJsonObjectEvaluator {
     }
 
     fn evaluate(
-        &mut self,
+        &self,
         json_str: &str,
     ) -> std::result::Result<Option<String>, HiveGetJsonObjectError> {
         // first try parsing with sonic-rs and fail-backing to serde-json
@@ -240,7 +251,7 @@ impl HiveGetJsonObjectEvaluator {
     }
 
     fn evaluate_with_value_serde_json(
-        &mut self,
+        &self,
         root_value: &serde_json::Value,
     ) -> std::result::Result<Option<String>, HiveGetJsonObjectError> {
         let mut root_value: Cow<serde_json::Value> = Cow::Borrowed(root_value);
@@ -260,7 +271,7 @@ impl HiveGetJsonObjectEvaluator {
     }
 
     fn evaluate_with_value_sonic(
-        &mut self,
+        &self,
         root_value: &sonic_rs::Value,
     ) -> std::result::Result<Option<String>, HiveGetJsonObjectError> {
         let mut root_value: Cow<sonic_rs::Value> = Cow::Borrowed(root_value);
@@ -352,6 +363,10 @@ impl HiveGetJsonObjectMatcher {
             }
             Some('[') => {
                 chars.next();
+                if chars.peek().cloned() == Some('\'') {
+                    // spark's quoted child like $['name']
+                    return Self::parse_quoted_child(chars).map(Some);
+                }
                 let mut index_str = String::new();
                 loop {
                     match chars.peek() {
@@ -379,6 +394,24 @@ impl HiveGetJsonObjectMatcher {
         }
     }
 
+    fn parse_quoted_child(
+        chars: &mut std::iter::Peekable<std::str::Chars>,
+    ) -> std::result::Result<Self, HiveGetJsonObjectError> {
+        chars.next(); // skip opening quote
+        let mut child_name = String::new();
+        loop {
+            match chars.next() {
+                Some('\'') => break,
+                Some(c) => child_name.push(c),
+                None => return Err(HiveGetJsonObjectError::InvalidJsonPath),
+            }
+        }
+        if child_name.is_empty() || chars.next() != Some(']') {
+            return Err(HiveGetJsonObjectError::InvalidJsonPath);
+        }
+        Ok(Self::Child(child_name))
+    }
+
     fn evaluate_serde_json<'a>(&self, value: &'a serde_json::Value) -> Cow<'a, serde_json::Value> {
         match self {
             HiveGetJsonObjectMatcher::Root => {
@@ -585,6 +618,15 @@ mod test {
             Some("pear".to_owned())
         );
 
+        let path = "$['store']['bicycle'].color";
+        assert_eq!(
+            HiveGetJsonObjectEvaluator::try_new(path)
+                .unwrap()
+                .evaluate(input)
+                .unwrap(),
+            Some("red".to_owned())
+        );
+
         let path = "$.non_exist_key";
         assert_eq!(
             HiveGetJsonObjectEvaluator::try_new(path)
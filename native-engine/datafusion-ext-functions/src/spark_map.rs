@@ -0,0 +1,239 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Map and array access expressions
+
+use std::sync::Arc;
+
+use arrow::{
+    array::*,
+    compute::{take, SortOptions},
+    datatypes::{DataType, Field, Int32Type},
+};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_plan::ColumnarValue,
+};
+use datafusion_ext_commons::df_execution_err;
+
+/// map_keys(map): keys of the map as an array
+pub fn spark_map_keys(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    map_entries_to_list(&args[0], 0)
+}
+
+/// map_values(map): values of the map as an array
+pub fn spark_map_values(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    map_entries_to_list(&args[0], 1)
+}
+
+fn map_entries_to_list(arg: &ColumnarValue, column_idx: usize) -> Result<ColumnarValue> {
+    let array = arg.clone().into_array(1)?;
+    let Some(map) = array.as_map_opt() else {
+        return df_execution_err!("expect map argument, got {}", array.data_type());
+    };
+
+    // spark's array type of keys/values keeps the nullability of map entries
+    let entry_field = &map.entries().fields()[column_idx];
+    let list: ArrayRef = Arc::new(ListArray::try_new(
+        Arc::new(Field::new(
+            "item",
+            entry_field.data_type().clone(),
+            entry_field.is_nullable(),
+        )),
+        map.offsets().clone(),
+        map.entries().column(column_idx).clone(),
+        map.nulls().cloned(),
+    )?);
+    Ok(match arg {
+        ColumnarValue::Array(_) => ColumnarValue::Array(list),
+        ColumnarValue::Scalar(_) => ColumnarValue::Scalar(ScalarValue::try_from_array(&list, 0)?),
+    })
+}
+
+/// element_at(array, index) with 1-based index (negative index accesses
+/// elements from the end), or element_at(map, key). null is returned for
+/// out-of-bound indices or missing keys, like spark in non-ANSI mode.
+pub fn spark_element_at(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let num_rows = args
+        .iter()
+        .find_map(|arg| match arg {
+            ColumnarValue::Array(array) => Some(array.len()),
+            ColumnarValue::Scalar(_) => None,
+        })
+        .unwrap_or(1);
+    let collection = args[0].clone().into_array(num_rows)?;
+    let key = args[1].clone().into_array(num_rows)?;
+
+    let mut take_indices = UInt32Builder::with_capacity(num_rows);
+    let values = match collection.data_type() {
+        DataType::List(_) => {
+            let list = collection.as_list::<i32>();
+            let Some(indices) = key.as_primitive_opt::<Int32Type>() else {
+                return df_execution_err!(
+                    "element_at: expect int32 index, got {}",
+                    key.data_type()
+                );
+            };
+            let offsets = list.value_offsets();
+            for row_idx in 0..num_rows {
+                if list.is_null(row_idx) || indices.is_null(row_idx) {
+                    take_indices.append_null();
+                    continue;
+                }
+                let (start, end) = (offsets[row_idx] as i64, offsets[row_idx + 1] as i64);
+                let value_idx = match indices.value(row_idx) as i64 {
+                    0 => return df_execution_err!("SQL array indices start at 1"),
+                    idx if idx > 0 => start + idx - 1,
+                    idx => end + idx,
+                };
+                if value_idx >= start && value_idx < end {
+                    take_indices.append_value(value_idx as u32);
+                } else {
+                    take_indices.append_null();
+                }
+            }
+            list.values()
+        }
+        DataType::Map(..) => {
+            let map = collection.as_map();
+            if map.key_type() != key.data_type() {
+                return df_execution_err!(
+                    "element_at: expect key type {}, got {}",
+                    map.key_type(),
+                    key.data_type(),
+                );
+            }
+            let comparator = make_comparator(map.keys(), &key, SortOptions::default())?;
+            let offsets = map.value_offsets();
+            for row_idx in 0..num_rows {
+                if map.is_null(row_idx) || key.is_null(row_idx) {
+                    take_indices.append_null();
+                    continue;
+                }
+                let (start, end) = (offsets[row_idx] as usize, offsets[row_idx + 1] as usize);
+                let found = (start..end).find(|&i| comparator(i, row_idx).is_eq());
+                take_indices.append_option(found.map(|i| i as u32));
+            }
+            map.values()
+        }
+        other => return df_execution_err!("element_at: expect array or map, got {other}"),
+    };
+    let output = take(values, &take_indices.finish(), None)?;
+
+    Ok(match &args[0] {
+        ColumnarValue::Scalar(_)
+            if num_rows == 1 && matches!(args[1], ColumnarValue::Scalar(_)) =>
+        {
+            ColumnarValue::Scalar(ScalarValue::try_from_array(&output, 0)?)
+        }
+        _ => ColumnarValue::Array(output),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::*,
+        datatypes::{DataType, Field, Int32Type},
+    };
+    use datafusion::{
+        common::{Result, ScalarValue},
+        physical_plan::ColumnarValue,
+    };
+
+    use crate::spark_map::{spark_element_at, spark_map_keys, spark_map_values};
+
+    fn test_map() -> Result<ArrayRef> {
+        let mut builder = MapBuilder::new(None, StringBuilder::new(), Int32Builder::new());
+        builder.keys().append_value("a");
+        builder.values().append_value(1);
+        builder.keys().append_value("b");
+        builder.values().append_null();
+        builder.append(true)?;
+        builder.append(false)?;
+        builder.keys().append_value("c");
+        builder.values().append_value(3);
+        builder.append(true)?;
+        Ok(Arc::new(builder.finish()))
+    }
+
+    #[test]
+    fn test_map_keys_and_values() -> Result<()> {
+        let map = test_map()?;
+        let keys = spark_map_keys(&[ColumnarValue::Array(map.clone())])?.into_array(3)?;
+        let mut expected = ListBuilder::new(StringBuilder::new()).with_field(Arc::new(Field::new(
+            "item",
+            DataType::Utf8,
+            false,
+        )));
+        expected.append_value([Some("a"), Some("b")]);
+        expected.append_null();
+        expected.append_value([Some("c")]);
+        let expected: ArrayRef = Arc::new(expected.finish());
+        assert_eq!(&keys, &expected);
+
+        let values = spark_map_values(&[ColumnarValue::Array(map)])?.into_array(3)?;
+        let expected: ArrayRef = Arc::new(ListArray::from_iter_primitive::<Int32Type, _, _>(vec![
+            Some(vec![Some(1), None]),
+            None,
+            Some(vec![Some(3)]),
+        ]));
+        assert_eq!(&values, &expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_element_at() -> Result<()> {
+        let map = test_map()?;
+        let output = spark_element_at(&[
+            ColumnarValue::Array(map),
+            ColumnarValue::Scalar(ScalarValue::from("c")),
+        ])?
+        .into_array(3)?;
+        let expected: ArrayRef = Arc::new(Int32Array::from(vec![None, None, Some(3)]));
+        assert_eq!(&output, &expected);
+
+        let list: ArrayRef = Arc::new(ListArray::from_iter_primitive::<Int32Type, _, _>(vec![
+            Some(vec![Some(1), Some(2), Some(3)]),
+            Some(vec![Some(4)]),
+            None,
+            Some(vec![Some(5), Some(6)]),
+        ]));
+        let indices: ArrayRef = Arc::new(Int32Array::from(vec![Some(2), Some(-1), Some(1), None]));
+        let output = spark_element_at(&[
+            ColumnarValue::Array(list.clone()),
+            ColumnarValue::Array(indices),
+        ])?
+        .into_array(4)?;
+        let expected: ArrayRef = Arc::new(Int32Array::from(vec![Some(2), Some(4), None, None]));
+        assert_eq!(&output, &expected);
+
+        let output = spark_element_at(&[
+            ColumnarValue::Array(list.clone()),
+            ColumnarValue::Scalar(ScalarValue::from(-3i32)),
+        ])?
+        .into_array(4)?;
+        let expected: ArrayRef = Arc::new(Int32Array::from(vec![Some(1), None, None, None]));
+        assert_eq!(&output, &expected);
+
+        assert!(spark_element_at(&[
+            ColumnarValue::Array(list),
+            ColumnarValue::Scalar(ScalarValue::from(0i32)),
+        ])
+        .is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,546 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Regexp functions compatible with spark (rlike, regexp_extract,
+//! regexp_extract_all and regexp_replace).
+//!
+//! java patterns are translated to rust regex syntax before compiling:
+//! - `\d`, `\w`, `\s` and their negations match ascii characters only, like
+//!   java's defaults.
+//! - `.` does not match `\r` and `\n`, and `\Q...\E` is quoted.
+//! - posix classes like `\p{Alpha}` are translated to ascii classes.
+//!
+//! patterns with constructs not supported by rust regex (lookarounds,
+//! backreferences, atomic groups, possessive quantifiers, `\G`, `\Z` and some
+//! java specific classes and flags) are rejected and should fall back to
+//! spark. remaining known divergences:
+//! - `.` matches `\u0085`, `\u2028` and `\u2029`, and `$` without multiline
+//!   mode does not match before a final line terminator.
+//! - `(?i)` is unicode aware, while java's is ascii only without `(?u)`.
+//! - positions of regexp_replace are counted in chars instead of utf-16 units.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use arrow::{
+    array::{Array, ArrayRef, BooleanArray, ListBuilder, StringArray, StringBuilder},
+    datatypes::{DataType, Field},
+};
+use datafusion::{
+    common::{cast::as_string_array, Result, ScalarValue},
+    physical_plan::ColumnarValue,
+};
+use datafusion_ext_commons::df_execution_err;
+use regex::Regex;
+
+/// max number of compiled patterns cached in an executor
+const PATTERN_CACHE_CAPACITY: usize = 256;
+
+/// rlike(str, regexp)
+pub fn spark_rlike(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let Some(regex) = literal_regex(&args[1], "rlike")? else {
+        return Ok(ColumnarValue::Scalar(ScalarValue::Boolean(None)));
+    };
+    let string_array = args[0].clone().into_array(1)?;
+    let matched = BooleanArray::from_iter(
+        as_string_array(&string_array)?
+            .iter()
+            .map(|s| s.map(|s| regex.is_match(s))),
+    );
+    Ok(ColumnarValue::Array(Arc::new(matched)))
+}
+
+/// regexp_extract(str, regexp, idx)
+pub fn spark_regexp_extract(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let (Some(regex), Some(idx)) = (
+        literal_regex(&args[1], "regexp_extract")?,
+        literal_group_idx(&args[2], "regexp_extract")?,
+    ) else {
+        return Ok(ColumnarValue::Scalar(ScalarValue::Utf8(None)));
+    };
+    let idx = check_group_idx(&regex, idx)?;
+
+    let string_array = args[0].clone().into_array(1)?;
+    let extracted = StringArray::from_iter(as_string_array(&string_array)?.iter().map(|s| {
+        s.map(|s| match regex.captures(s) {
+            Some(captures) => captures.get(idx).map(|m| m.as_str()).unwrap_or(""),
+            None => "",
+        })
+    }));
+    Ok(ColumnarValue::Array(Arc::new(extracted)))
+}
+
+/// regexp_extract_all(str, regexp, idx)
+pub fn spark_regexp_extract_all(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let (Some(regex), Some(idx)) = (
+        literal_regex(&args[1], "regexp_extract_all")?,
+        literal_group_idx(&args[2], "regexp_extract_all")?,
+    ) else {
+        let item_field = Arc::new(Field::new("item", DataType::Utf8, true));
+        return Ok(ColumnarValue::Scalar(ScalarValue::try_from(
+            &DataType::List(item_field),
+        )?));
+    };
+    let idx = check_group_idx(&regex, idx)?;
+
+    let string_array = args[0].clone().into_array(1)?;
+    let mut extracted_builder = ListBuilder::new(StringBuilder::new());
+    for s in as_string_array(&string_array)? {
+        match s {
+            Some(s) => {
+                for captures in regex.captures_iter(s) {
+                    let extracted = captures.get(idx).map(|m| m.as_str()).unwrap_or("");
+                    extracted_builder.values().append_value(extracted);
+                }
+                extracted_builder.append(true);
+            }
+            None => extracted_builder.append_null(),
+        }
+    }
+    Ok(ColumnarValue::Array(Arc::new(extracted_builder.finish())))
+}
+
+/// regexp_replace(str, regexp, rep[, pos])
+pub fn spark_regexp_replace(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let rep = match &args[2] {
+        ColumnarValue::Scalar(ScalarValue::Utf8(rep)) => rep.as_deref(),
+        _ => df_execution_err!("regexp_replace replacement only supports literal string")?,
+    };
+    let pos = match args.get(3) {
+        Some(ColumnarValue::Scalar(ScalarValue::Int32(pos))) => *pos,
+        None => Some(1),
+        _ => df_execution_err!("regexp_replace position only supports literal int32")?,
+    };
+    let (Some(regex), Some(rep), Some(pos)) =
+        (literal_regex(&args[1], "regexp_replace")?, rep, pos)
+    else {
+        return Ok(ColumnarValue::Scalar(ScalarValue::Utf8(None)));
+    };
+    if pos <= 0 {
+        return df_execution_err!(
+            "regexp_replace position must be greater than zero, but got {pos}"
+        );
+    }
+    let rep = translate_java_replacement(rep, regex.captures_len() - 1)?;
+    let start_chars = pos as usize - 1;
+
+    let string_array = args[0].clone().into_array(1)?;
+    let replaced = StringArray::from_iter(as_string_array(&string_array)?.iter().map(|s| {
+        s.map(|s| {
+            let start = match s.char_indices().nth(start_chars) {
+                Some((start, _)) => start,
+                None if start_chars == 0 => 0,
+                None => return s.to_string(),
+            };
+            let (prefix, suffix) = s.split_at(start);
+            let mut replaced = prefix.to_string();
+            replaced.push_str(&regex.replace_all(suffix, rep.as_str()));
+            replaced
+        })
+    }));
+    Ok(ColumnarValue::Array(Arc::new(replaced)))
+}
+
+fn literal_regex(arg: &ColumnarValue, fn_name: &str) -> Result<Option<Arc<Regex>>> {
+    match arg {
+        ColumnarValue::Scalar(ScalarValue::Utf8(Some(pattern))) => {
+            Ok(Some(get_cached_regex(pattern)?))
+        }
+        ColumnarValue::Scalar(ScalarValue::Utf8(None)) => Ok(None),
+        _ => df_execution_err!("{fn_name} pattern only supports literal string"),
+    }
+}
+
+fn literal_group_idx(arg: &ColumnarValue, fn_name: &str) -> Result<Option<i32>> {
+    match arg {
+        ColumnarValue::Scalar(ScalarValue::Int32(idx)) => Ok(*idx),
+        _ => df_execution_err!("{fn_name} group index only supports literal int32"),
+    }
+}
+
+fn check_group_idx(regex: &Regex, idx: i32) -> Result<usize> {
+    let group_count = regex.captures_len() - 1;
+    if idx < 0 {
+        return df_execution_err!("The specified group index cannot be less than zero");
+    }
+    if idx as usize > group_count {
+        return df_execution_err!(
+            "Regex group count is {group_count}, but the specified group index is {idx}"
+        );
+    }
+    Ok(idx as usize)
+}
+
+/// gets the compiled regex of a java pattern from the executor-level LRU
+/// cache, so that evaluators of all tasks share the compiled patterns
+fn get_cached_regex(java_pattern: &str) -> Result<Arc<Regex>> {
+    static PATTERN_CACHE: OnceLock<Mutex<PatternCache>> = OnceLock::new();
+    let cache = PATTERN_CACHE.get_or_init(|| Mutex::new(PatternCache::default()));
+    if let Some(regex) = cache.lock().unwrap().get(java_pattern) {
+        return Ok(regex);
+    }
+
+    // compile without holding the lock
+    let translated = match translate_java_regex(java_pattern) {
+        Ok(translated) => translated,
+        Err(reason) => {
+            return df_execution_err!("unsupported regexp pattern {java_pattern:?}: {reason}")
+        }
+    };
+    let regex = match Regex::new(&translated) {
+        Ok(regex) => Arc::new(regex),
+        Err(err) => return df_execution_err!("invalid regexp pattern {java_pattern:?}: {err}"),
+    };
+    cache
+        .lock()
+        .unwrap()
+        .insert(java_pattern.to_string(), regex.clone());
+    Ok(regex)
+}
+
+#[derive(Default)]
+struct PatternCache {
+    entries: HashMap<String, (u64, Arc<Regex>)>,
+    lru: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+impl PatternCache {
+    fn get(&mut self, pattern: &str) -> Option<Arc<Regex>> {
+        self.tick += 1;
+        let (tick, regex) = self.entries.get_mut(pattern)?;
+        let key = self.lru.remove(tick).expect("missing lru entry");
+        *tick = self.tick;
+        self.lru.insert(self.tick, key);
+        Some(regex.clone())
+    }
+
+    fn insert(&mut self, pattern: String, regex: Arc<Regex>) {
+        if self.entries.contains_key(&pattern) {
+            return;
+        }
+        while self.entries.len() >= PATTERN_CACHE_CAPACITY {
+            let Some((_, evicted)) = self.lru.pop_first() else {
+                break;
+            };
+            self.entries.remove(&evicted);
+        }
+        self.tick += 1;
+        self.lru.insert(self.tick, pattern.clone());
+        self.entries.insert(pattern, (self.tick, regex));
+    }
+}
+
+/// translates a java pattern to rust regex syntax, returns the reason if the
+/// pattern contains unsupported constructs
+fn translate_java_regex(pattern: &str) -> std::result::Result<String, String> {
+    // CRLF mode: `.` does not match `\r` and `\n`
+    let mut translated = String::from("(?R)");
+    let chars = pattern.chars().collect::<Vec<_>>();
+    let mut class_depth = 0;
+    let mut follows_quantifier = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        i += 1;
+        let is_quantifier = class_depth == 0 && matches!(c, '*' | '+' | '?' | '}');
+        if c == '+' && follows_quantifier {
+            return Err("possessive quantifiers are not supported".to_string());
+        }
+        follows_quantifier = is_quantifier;
+
+        match c {
+            '\\' => {
+                let Some(&escaped) = chars.get(i) else {
+                    return Err("trailing backslash".to_string());
+                };
+                i += 1;
+                match escaped {
+                    'd' => translated.push_str("[0-9]"),
+                    'D' => translated.push_str("[^0-9]"),
+                    'w' => translated.push_str("[a-zA-Z0-9_]"),
+                    'W' => translated.push_str("[^a-zA-Z0-9_]"),
+                    's' => translated.push_str(r"[\t\n\x0B\f\r ]"),
+                    'S' => translated.push_str(r"[^\t\n\x0B\f\r ]"),
+                    'e' => translated.push_str(r"\x1B"),
+                    'Q' => {
+                        let quoted_end = (i..chars.len())
+                            .find(|&j| chars[j] == '\\' && chars.get(j + 1) == Some(&'E'))
+                            .unwrap_or(chars.len());
+                        let quoted = chars[i..quoted_end].iter().collect::<String>();
+                        translated.push_str(&regex::escape(&quoted));
+                        i = (quoted_end + 2).min(chars.len());
+                    }
+                    'p' | 'P' => {
+                        let (name, next) = match chars.get(i) {
+                            Some('{') => {
+                                let Some(end) = (i..chars.len()).find(|&j| chars[j] == '}') else {
+                                    return Err("unclosed character property".to_string());
+                                };
+                                (chars[i + 1..end].iter().collect::<String>(), end + 1)
+                            }
+                            Some(&name) => (name.to_string(), i + 1),
+                            None => return Err("missing character property".to_string()),
+                        };
+                        i = next;
+                        translated.push_str(&translate_java_property(&name, escaped == 'P')?);
+                    }
+                    'b' | 'B' | 'A' | 'z' | 't' | 'n' | 'r' | 'f' | 'a' | 'x' | 'u' => {
+                        translated.push('\\');
+                        translated.push(escaped);
+                    }
+                    '1'..='9' | 'k' => return Err("backreferences are not supported".to_string()),
+                    c if c.is_ascii_alphanumeric() => {
+                        return Err(format!("escape sequence \\{c} is not supported"));
+                    }
+                    c => translated.push_str(&regex::escape(&c.to_string())),
+                }
+            }
+            '(' if class_depth == 0 && chars.get(i) == Some(&'?') => {
+                let group = chars[i + 1..].iter().take(3).collect::<String>();
+                if group.starts_with('=')
+                    || group.starts_with('!')
+                    || group.starts_with("<=")
+                    || group.starts_with("<!")
+                {
+                    return Err("lookarounds are not supported".to_string());
+                }
+                if group.starts_with('>') {
+                    return Err("atomic groups are not supported".to_string());
+                }
+                translated.push_str("(?");
+                i += 1;
+                if group.starts_with('<') || group.starts_with(':') {
+                    continue;
+                }
+
+                // inline flags like (?i) or (?s-m:X)
+                while let Some(&flag) = chars.get(i) {
+                    match flag {
+                        'i' | 'm' | 's' | 'x' | '-' => translated.push(flag),
+                        'u' => {} // rust regex is always unicode aware
+                        ')' | ':' => break,
+                        flag => return Err(format!("flag {flag} is not supported")),
+                    }
+                    i += 1;
+                }
+                if translated.ends_with("(?") && chars.get(i) == Some(&')') {
+                    translated.truncate(translated.len() - 2); // all flags are dropped
+                    i += 1;
+                }
+            }
+            '[' => {
+                class_depth += 1;
+                translated.push('[');
+                if chars.get(i) == Some(&'^') {
+                    translated.push('^');
+                    i += 1;
+                }
+                // java has no posix classes like [[:alpha:]]
+                if chars.get(i) == Some(&':') {
+                    translated.push_str(r"\:");
+                    i += 1;
+                }
+            }
+            ']' if class_depth > 0 => {
+                class_depth -= 1;
+                translated.push(']');
+            }
+            '-' if class_depth > 0 && chars.get(i) == Some(&'-') => {
+                return Err("class difference is not supported".to_string());
+            }
+            '~' if class_depth > 0 => translated.push_str(r"\~"),
+            c => translated.push(c),
+        }
+    }
+    Ok(translated)
+}
+
+fn translate_java_property(name: &str, negated: bool) -> std::result::Result<String, String> {
+    let posix_class = match name {
+        "Lower" => "lower",
+        "Upper" => "upper",
+        "ASCII" => "ascii",
+        "Alpha" => "alpha",
+        "Digit" => "digit",
+        "Alnum" => "alnum",
+        "Punct" => "punct",
+        "Graph" => "graph",
+        "Print" => "print",
+        "Blank" => "blank",
+        "Cntrl" => "cntrl",
+        "XDigit" => "xdigit",
+        "Space" => "space",
+        name if name.starts_with("java") || name.starts_with("In") => {
+            return Err(format!("character property {name} is not supported"));
+        }
+        name => {
+            let name = name.strip_prefix("Is").unwrap_or(name);
+            let p = if negated { 'P' } else { 'p' };
+            return Ok(format!("\\{p}{{{name}}}"));
+        }
+    };
+    let negation = if negated { "^" } else { "" };
+    Ok(format!("[[:{negation}{posix_class}:]]"))
+}
+
+/// translates a java replacement string (with `$1`, `${name}` and `\`
+/// escapes) to rust regex syntax
+fn translate_java_replacement(rep: &str, group_count: usize) -> Result<String> {
+    let mut translated = String::with_capacity(rep.len());
+    let mut chars = rep.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('$') => translated.push_str("$$"),
+                Some(escaped) => translated.push(escaped),
+                None => df_execution_err!("regexp_replace: character to be escaped is missing")?,
+            },
+            '$' => match chars.next() {
+                Some('{') => {
+                    let name = chars.by_ref().take_while(|&c| c != '}').collect::<String>();
+                    translated.push_str(&format!("${{{name}}}"));
+                }
+                Some(digit @ '0'..='9') => {
+                    // like java, takes as many digits as they form a valid group number
+                    let mut group = digit as usize - '0' as usize;
+                    if group > group_count {
+                        df_execution_err!("regexp_replace: no group {group}")?;
+                    }
+                    while let Some(&next @ '0'..='9') = chars.peek() {
+                        let next_group = group * 10 + (next as usize - '0' as usize);
+                        if next_group > group_count {
+                            break;
+                        }
+                        group = next_group;
+                        chars.next();
+                    }
+                    translated.push_str(&format!("${{{group}}}"));
+                }
+                _ => df_execution_err!("regexp_replace: illegal group reference")?,
+            },
+            c => translated.push(c),
+        }
+    }
+    Ok(translated)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::array::{ArrayRef, AsArray, BooleanArray, StringArray};
+    use datafusion::{
+        common::{Result, ScalarValue},
+        physical_plan::ColumnarValue,
+    };
+
+    use crate::spark_regexp::{
+        spark_regexp_extract, spark_regexp_extract_all, spark_regexp_replace, spark_rlike,
+        translate_java_regex,
+    };
+
+    fn strings(values: Vec<Option<&str>>) -> ColumnarValue {
+        ColumnarValue::Array(Arc::new(StringArray::from(values)))
+    }
+
+    fn literal(value: impl Into<ScalarValue>) -> ColumnarValue {
+        ColumnarValue::Scalar(value.into())
+    }
+
+    #[test]
+    fn test_translate_java_regex() {
+        assert_eq!(
+            translate_java_regex(r"^\d+\.\w*$").unwrap(),
+            r"(?R)^[0-9]+\.[a-zA-Z0-9_]*$"
+        );
+        assert_eq!(
+            translate_java_regex(r"\Qa.b\E[\p{Alpha}~]").unwrap(),
+            r"(?R)a\.b[[[:alpha:]]\~]"
+        );
+        assert_eq!(
+            translate_java_regex(r"(?iu)(?<name>x)\p{IsLatin}").unwrap(),
+            r"(?R)(?i)(?<name>x)\p{Latin}"
+        );
+        assert!(translate_java_regex(r"a(?=b)").is_err());
+        assert!(translate_java_regex(r"(a)\1").is_err());
+        assert!(translate_java_regex(r"a*+").is_err());
+        assert!(translate_java_regex(r"(?>a)").is_err());
+        assert!(translate_java_regex(r"\p{javaLowerCase}").is_err());
+    }
+
+    #[test]
+    fn test_rlike() -> Result<()> {
+        let input = strings(vec![Some("abc123"), Some("٣"), Some("a\rb"), None]);
+        let output = spark_rlike(&[input.clone(), literal(r"\d")])?.into_array(4)?;
+        let expected: ArrayRef = Arc::new(BooleanArray::from(vec![
+            Some(true),
+            Some(false),
+            Some(false),
+            None,
+        ]));
+        assert_eq!(&output, &expected);
+
+        let output = spark_rlike(&[input, literal("a.b")])?.into_array(4)?;
+        assert!(!output.as_boolean().value(2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_regexp_extract() -> Result<()> {
+        let input = strings(vec![Some("100-200, 300-400"), Some("foo"), None]);
+        let output = spark_regexp_extract(&[input.clone(), literal(r"(\d+)-(\d+)"), literal(2)])?
+            .into_array(3)?;
+        assert_eq!(
+            output.as_string::<i32>(),
+            &StringArray::from(vec![Some("200"), Some(""), None])
+        );
+        assert!(spark_regexp_extract(&[input.clone(), literal(r"(\d+)"), literal(2)]).is_err());
+
+        let output = spark_regexp_extract_all(&[input, literal(r"(\d+)-(\d+)"), literal(1)])?
+            .into_array(3)?;
+        let output = output.as_list::<i32>();
+        assert_eq!(
+            output.value(0).as_string::<i32>(),
+            &StringArray::from(vec!["100", "300"])
+        );
+        assert_eq!(output.value(1).len(), 0);
+        assert!(output.is_null(2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_regexp_replace() -> Result<()> {
+        let input = strings(vec![Some("100-200"), Some("abc"), None]);
+        let output =
+            spark_regexp_replace(&[input.clone(), literal(r"(\d+)-(\d+)"), literal(r"$2\$$10")])?
+                .into_array(3)?;
+        assert_eq!(
+            output.as_string::<i32>(),
+            &StringArray::from(vec![Some("200$1000"), Some("abc"), None])
+        );
+
+        let output = spark_regexp_replace(&[input, literal("[0-9a]"), literal("x"), literal(2)])?
+            .into_array(3)?;
+        assert_eq!(
+            output.as_string::<i32>(),
+            &StringArray::from(vec![Some("1xx-xxx"), Some("abc"), None])
+        );
+        Ok(())
+    }
+}
@@ -24,7 +24,7 @@ use datafusion_ext_exprs::cast::TryCastExpr;
 
 use crate::agg::{
     acc::AccColumnRef, avg, bloom_filter, brickhouse, collect, first, first_ignores_null, maxmin,
-    sum, AggFunction,
+    spark_udaf_wrapper::SparkUDAFWrapper, sum, AggFunction,
 };
 
 pub trait Agg: Send + Sync + Debug {
@@ -270,5 +270,22 @@ pub fn create_agg(
                 arg_list_inner_type,
             )?)
         }
+        AggFunction::Udaf => {
+            return df_execution_err!("udaf should be created with create_udaf_agg()");
+        }
     })
 }
+
+pub fn create_udaf_agg(
+    serialized: Vec<u8>,
+    return_type: DataType,
+    return_nullable: bool,
+    children: Vec<Arc<dyn PhysicalExpr>>,
+) -> Result<Arc<dyn Agg>> {
+    Ok(Arc::new(SparkUDAFWrapper::try_new(
+        serialized,
+        return_type,
+        return_nullable,
+        children,
+    )?))
+}
@@ -18,26 +18,31 @@ use std::{
 };
 
 use arrow::{
-    array::{Array, ArrayRef, BinaryArray, RecordBatchOptions},
-    datatypes::{DataType, Field, Fields, Schema, SchemaRef},
+    array::{Array, ArrayRef, AsArray, BinaryArray, Int64Array, RecordBatchOptions},
+    datatypes::{DataType, Field, Fields, Int64Type, Schema, SchemaRef},
     record_batch::RecordBatch,
     row::{RowConverter, Rows, SortField},
 };
 use blaze_jni_bridge::{
     conf,
-    conf::{DoubleConf, IntConf},
+    conf::{BooleanConf, DoubleConf, IntConf},
     is_jni_bridge_inited,
 };
 use datafusion::{
     common::{cast::as_binary_array, Result},
     physical_expr::PhysicalExprRef,
 };
+use datafusion_ext_commons::df_execution_err;
 use parking_lot::Mutex;
 
 use crate::{
     agg::{
         acc::AccTable,
         agg::{Agg, IdxSelection},
+        first::AggFirst,
+        first_ignores_null::AggFirstIgnoresNull,
+        grouping_key::{canonical_key_type, canonicalize_key},
+        maxmin::{AggMax, AggMin},
         AggExecMode, AggExpr, AggMode, GroupingExpr, AGG_BUF_COLUMN_NAME,
     },
     common::{
@@ -53,9 +58,11 @@ pub struct AggContext {
     pub need_final_merge: bool,
     pub need_partial_update_aggs: Vec<(usize, Arc<dyn Agg>)>,
     pub need_partial_merge_aggs: Vec<(usize, Arc<dyn Agg>)>,
+    pub key_restored_aggs: Vec<(usize, usize)>, // (agg_idx, grouping_idx)
 
     pub output_schema: SchemaRef,
     pub grouping_row_converter: Arc<Mutex<RowConverter>>,
+    pub fixed_key_int_type: Option<DataType>,
     pub groupings: Vec<GroupingExpr>,
     pub aggs: Vec<AggExpr>,
     pub supports_partial_skipping: bool,
@@ -86,7 +93,10 @@ impl AggContext {
                 .map(|grouping: &GroupingExpr| {
                     Ok(Field::new(
                         grouping.field_name.as_str(),
-                        grouping.expr.data_type(&input_schema)?,
+                        canonical_key_type(
+                            &grouping.expr.data_type(&input_schema)?,
+                            grouping.key_type.as_ref(),
+                        ),
                         grouping.expr.nullable(&input_schema)?,
                     ))
                 })
@@ -100,18 +110,42 @@ impl AggContext {
                 .collect(),
         )?));
 
+        // single fixed-width keys are hashed directly without row conversion
+        let fixed_key_int_type = match grouping_schema.fields().as_ref() {
+            [field] if fixed_key_hashing_enabled()? => fixed_key_int_type(field.data_type()),
+            _ => None,
+        };
+
         // final aggregates may not exist along with partial/partial-merge
-        let need_partial_update = aggs.iter().any(|agg| agg.mode == AggMode::Partial);
         let need_partial_merge = aggs.iter().any(|agg| agg.mode != AggMode::Partial);
         let need_final_merge = aggs.iter().any(|agg| agg.mode == AggMode::Final);
         assert!(!(need_final_merge && aggs.iter().any(|agg| agg.mode != AggMode::Final)));
 
+        // partial aggs on grouping keys (like max(key)) always have the same
+        // values as the keys. like pruning sort keys, they are not updated and
+        // their values are restored from the grouping rows on output, so the
+        // keys are not stored twice
+        let key_restored_aggs: Vec<(usize, usize)> = aggs
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, agg)| {
+                key_restored_grouping_idx(agg, &groupings, &grouping_schema)
+                    .map(|grouping_idx| (idx, grouping_idx))
+            })
+            .collect();
+        let is_key_restored = |agg_idx: usize| {
+            key_restored_aggs
+                .iter()
+                .any(|&(key_restored_agg_idx, _)| key_restored_agg_idx == agg_idx)
+        };
+
         let need_partial_update_aggs: Vec<(usize, Arc<dyn Agg>)> = aggs
             .iter()
             .enumerate()
-            .filter(|(_idx, agg)| agg.mode.is_partial())
+            .filter(|(idx, agg)| agg.mode.is_partial() && !is_key_restored(*idx))
             .map(|(idx, agg)| (idx, agg.agg.clone()))
             .collect();
+        let need_partial_update = !need_partial_update_aggs.is_empty();
         let need_partial_merge_aggs: Vec<(usize, Arc<dyn Agg>)> = aggs
             .iter()
             .enumerate()
@@ -140,10 +174,9 @@ impl AggContext {
             .concat(),
         ));
 
-        let agg_exprs_flatten: Vec<PhysicalExprRef> = aggs
+        let agg_exprs_flatten: Vec<PhysicalExprRef> = need_partial_update_aggs
             .iter()
-            .filter(|agg| agg.mode.is_partial())
-            .flat_map(|agg| agg.agg.exprs())
+            .flat_map(|(_idx, agg)| agg.exprs())
             .collect();
         let agg_expr_evaluator_output_schema = Arc::new(Schema::new(
             agg_exprs_flatten
@@ -183,8 +216,10 @@ impl AggContext {
             need_final_merge,
             need_partial_update_aggs,
             need_partial_merge_aggs,
+            key_restored_aggs,
             output_schema,
             grouping_row_converter,
+            fixed_key_int_type,
             groupings,
             aggs,
             agg_expr_evaluator,
@@ -205,20 +240,55 @@ impl AggContext {
         )
     }
 
-    pub fn create_grouping_rows(&self, input_batch: &RecordBatch) -> Result<Rows> {
-        let grouping_arrays: Vec<ArrayRef> = self
-            .groupings
+    fn evaluate_grouping_arrays(&self, input_batch: &RecordBatch) -> Result<Vec<ArrayRef>> {
+        self.groupings
             .iter()
-            .map(|grouping| grouping.expr.evaluate(&input_batch))
-            .map(|r| r.and_then(|columnar| columnar.into_array(input_batch.num_rows())))
+            .zip(self.output_schema.fields())
+            .map(|(grouping, field)| {
+                let array = grouping
+                    .expr
+                    .evaluate(&input_batch)?
+                    .into_array(input_batch.num_rows())?;
+                canonicalize_key(array, field.data_type())
+            })
             .collect::<Result<_>>()
-            .map_err(|err| err.context("agg: evaluating grouping arrays error"))?;
+            .map_err(|err| err.context("agg: evaluating grouping arrays error"))
+    }
+
+    pub fn create_grouping_rows(&self, input_batch: &RecordBatch) -> Result<Rows> {
+        let grouping_arrays = self.evaluate_grouping_arrays(input_batch)?;
         Ok(self
             .grouping_row_converter
             .lock()
             .convert_columns(&grouping_arrays)?)
     }
 
+    /// evaluates the single fixed-width grouping key as int64 values, only
+    /// available if `fixed_key_int_type` is set
+    pub fn create_fixed_keys(&self, input_batch: &RecordBatch) -> Result<Int64Array> {
+        let Some(int_type) = &self.fixed_key_int_type else {
+            return df_execution_err!("agg: grouping key is not fixed-width");
+        };
+        let grouping_arrays = self.evaluate_grouping_arrays(input_batch)?;
+        let int_keys = arrow::compute::cast(&grouping_arrays[0], int_type)?;
+        let keys = arrow::compute::cast(&int_keys, &DataType::Int64)?;
+        Ok(keys.as_primitive::<Int64Type>().clone())
+    }
+
+    /// converts int64 values of the fixed-width grouping key to grouping rows
+    pub fn convert_fixed_keys_to_rows(&self, keys: &ArrayRef) -> Result<Rows> {
+        let Some(int_type) = &self.fixed_key_int_type else {
+            return df_execution_err!("agg: grouping key is not fixed-width");
+        };
+        let key_type = self.output_schema.field(0).data_type();
+        let int_keys = arrow::compute::cast(keys, int_type)?;
+        let grouping_array = arrow::compute::cast(&int_keys, key_type)?;
+        Ok(self
+            .grouping_row_converter
+            .lock()
+            .convert_columns(&[grouping_array])?)
+    }
+
     pub fn update_batch_to_acc_table(
         &self,
         batch: &RecordBatch,
@@ -228,19 +298,13 @@ impl AggContext {
         // partial update
         if self.need_partial_update {
             let agg_exprs_batch = self.agg_expr_evaluator.filter_project(&batch)?;
-            let mut input_arrays = Vec::with_capacity(self.aggs.len());
+            let mut input_arrays = vec![vec![]; self.aggs.len()];
             let mut offset = 0;
-            for agg in &self.aggs {
-                if agg.mode.is_partial() {
-                    let num_agg_exprs = agg.agg.exprs().len();
-                    let prepared = agg.agg.prepare_partial_args(
-                        &agg_exprs_batch.columns()[offset..][..num_agg_exprs],
-                    )?;
-                    input_arrays.push(prepared);
-                    offset += num_agg_exprs;
-                } else {
-                    input_arrays.push(vec![]);
-                }
+            for (agg_idx, agg) in &self.need_partial_update_aggs {
+                let num_agg_exprs = agg.exprs().len();
+                input_arrays[*agg_idx] = agg
+                    .prepare_partial_args(&agg_exprs_batch.columns()[offset..][..num_agg_exprs])?;
+                offset += num_agg_exprs;
             }
 
             self.partial_update(
@@ -311,6 +375,8 @@ impl AggContext {
             keys.iter()
                 .map(|key| grouping_row_parser.parse(key.as_ref())),
         )?;
+        drop(grouping_row_converter);
+        self.restore_key_restored_accs(acc_table, acc_idx, &grouping_columns)?;
         let agg_columns = self.build_agg_columns(acc_table, acc_idx)?;
 
         // at least one column exists
@@ -320,6 +386,26 @@ impl AggContext {
         )?)
     }
 
+    /// updates accs of key restored aggs with the grouping columns, must be
+    /// called before building agg columns
+    fn restore_key_restored_accs(
+        &self,
+        acc_table: &mut AccTable,
+        acc_idx: IdxSelection,
+        grouping_columns: &[ArrayRef],
+    ) -> Result<()> {
+        for &(agg_idx, grouping_idx) in &self.key_restored_aggs {
+            let acc_col = &mut acc_table.cols_mut()[agg_idx];
+            self.aggs[agg_idx].agg.partial_update(
+                acc_col,
+                acc_idx,
+                &[grouping_columns[grouping_idx].clone()],
+                IdxSelection::Range(0, acc_idx.len()),
+            )?;
+        }
+        Ok(())
+    }
+
     pub fn partial_update(
         &self,
         acc_table: &mut AccTable,
@@ -387,12 +473,12 @@ impl AggContext {
         )?;
 
         // create output batch
-        let grouping_columns = self
-            .groupings
-            .iter()
-            .map(|grouping| grouping.expr.evaluate(&batch))
-            .map(|r| r.and_then(|columnar| columnar.into_array(batch_num_rows)))
-            .collect::<Result<Vec<ArrayRef>>>()?;
+        let grouping_columns = self.evaluate_grouping_arrays(&batch)?;
+        self.restore_key_restored_accs(
+            &mut acc_table,
+            IdxSelection::Range(0, batch_num_rows),
+            &grouping_columns,
+        )?;
         let agg_columns =
             self.build_agg_columns(&mut acc_table, IdxSelection::Range(0, batch_num_rows))?;
         let output_batch = RecordBatch::try_new_with_options(
@@ -408,3 +494,51 @@ impl AggContext {
         return Ok(());
     }
 }
+
+/// returns index of the grouping key if the agg is a partial max/min/first of
+/// the key, whose values are always equal to the key in each group
+fn key_restored_grouping_idx(
+    agg: &AggExpr,
+    groupings: &[GroupingExpr],
+    grouping_schema: &Schema,
+) -> Option<usize> {
+    let agg_any = agg.agg.as_any();
+    let is_key_determined = agg_any.is::<AggMax>()
+        || agg_any.is::<AggMin>()
+        || agg_any.is::<AggFirst>()
+        || agg_any.is::<AggFirstIgnoresNull>();
+    if agg.mode != AggMode::Partial || !is_key_determined {
+        return None;
+    }
+    let exprs = agg.agg.exprs();
+    let [arg] = exprs.as_slice() else {
+        return None;
+    };
+    groupings
+        .iter()
+        .zip(grouping_schema.fields())
+        .position(|(grouping, field)| {
+            grouping.expr.eq(arg) && field.data_type() == agg.agg.data_type()
+        })
+}
+
+/// returns the integer type that values of a fixed-width key type are hashed
+/// as, keys are widened to int64 when hashing
+fn fixed_key_int_type(key_type: &DataType) -> Option<DataType> {
+    match key_type {
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64 => {
+            Some(key_type.clone())
+        }
+        DataType::Date32 => Some(DataType::Int32),
+        DataType::Date64 | DataType::Timestamp(..) => Some(DataType::Int64),
+        _ => None,
+    }
+}
+
+fn fixed_key_hashing_enabled() -> Result<bool> {
+    if is_jni_bridge_inited() {
+        conf::AGG_FIXED_KEY_HASHING_ENABLE.value()
+    } else {
+        Ok(true) // for testing
+    }
+}
@@ -18,7 +18,7 @@ use std::{
     sync::{Arc, Weak},
 };
 
-use arrow::{record_batch::RecordBatch, row::Rows};
+use arrow::{array::ArrayRef, record_batch::RecordBatch, row::Rows};
 use async_trait::async_trait;
 use bytesize::ByteSize;
 use datafusion::{
@@ -32,19 +32,29 @@ use datafusion_ext_commons::{
     },
     batch_size, df_execution_err, downcast_any,
     io::{read_bytes_slice, read_len, write_len},
+    spark_task_context::SparkTaskContext,
 };
 use futures::lock::Mutex;
 use smallvec::SmallVec;
 
 use crate::{
-    agg::{acc::AccTable, agg::IdxSelection, agg_ctx::AggContext, agg_hash_map::AggHashMap},
+    agg::{
+        acc::AccTable, agg::IdxSelection, agg_ctx::AggContext, agg_hash_map::AggHashMap,
+        fixed_key_hash_map::FixedKeyAggHashMap,
+    },
     common::{
         execution_context::{ExecutionContext, WrappedRecordBatchSender},
+        replay_log::ReplayEvent,
         timer_helper::TimerHelper,
         SliceAsRawBytes,
     },
     memmgr::{
-        spill::{try_new_spill, Spill, SpillCompressedReader, SpillCompressedWriter},
+        metrics::SpillMetrics,
+        spill::{
+            agg_spill_compression, try_new_spill, Spill, SpillCompressedReader,
+            SpillCompressedWriter,
+        },
+        quota::MemConsumerType,
         MemConsumer, MemConsumerInfo, MemManager,
     },
 };
@@ -157,7 +167,7 @@ impl AggTable {
         if spills.is_empty() {
             let num_records = in_mem.num_records();
             let mem_used = in_mem.mem_used();
-            let mut keys = in_mem.hashing_data.map.take_keys();
+            let mut keys = in_mem.hashing_data.map.take_keys(&self.agg_ctx)?;
             let mut acc_table = in_mem.hashing_data.acc_table;
 
             // output in reversed order, so we can truncate records and free
@@ -225,6 +235,8 @@ impl AggTable {
         while let cur_bucket_idx = cursors.peek().cur_bucket_idx
             && cur_bucket_idx < NUM_SPILL_BUCKETS
         {
+            SparkTaskContext::check_cancelled()?;
+
             // process current bucket
             while let mut min_cursor = cursors.peek_mut()
                 && min_cursor.cur_bucket_idx == cur_bucket_idx
@@ -282,6 +294,14 @@ impl MemConsumer for AggTable {
             .expect("consumer info not set")
     }
 
+    fn consumer_type(&self) -> MemConsumerType {
+        MemConsumerType::Agg
+    }
+
+    fn spill_metrics(&self) -> Option<&SpillMetrics> {
+        Some(self.exec_ctx.spill_metrics())
+    }
+
     async fn spill(&self) -> Result<()> {
         if self.agg_ctx.supports_partial_skipping {
             return df_execution_err!("AGG_SPILL_PARTIAL_SKIPPING");
@@ -297,6 +317,9 @@ impl MemConsumer for AggTable {
             }
         }
         let cur_in_mem = in_mem.renew(next_in_mem_mode);
+        self.exec_ctx.log_replay_event(|| ReplayEvent::Spill {
+            mem_size: cur_in_mem.mem_used(),
+        });
 
         let spill_metrics = self.exec_ctx.spill_metrics().clone();
         let cur_spill = tokio::task::spawn_blocking(move || {
@@ -416,10 +439,77 @@ impl InMemTable {
     }
 }
 
+/// hash map of grouping keys. single fixed-width keys are hashed directly
+/// and converted to rows only when the records are spilled or output.
+enum HashingMap {
+    Rows(AggHashMap),
+    FixedKey(FixedKeyAggHashMap),
+}
+
+impl HashingMap {
+    fn new(agg_ctx: &AggContext) -> Self {
+        match agg_ctx.fixed_key_int_type {
+            Some(_) => HashingMap::FixedKey(FixedKeyAggHashMap::default()),
+            None => HashingMap::Rows(AggHashMap::default()),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            HashingMap::Rows(map) => map.len(),
+            HashingMap::FixedKey(map) => map.len(),
+        }
+    }
+
+    fn mem_size(&self) -> usize {
+        match self {
+            HashingMap::Rows(map) => map.mem_size(),
+            HashingMap::FixedKey(map) => map.mem_size(),
+        }
+    }
+
+    fn upsert_batch(&mut self, agg_ctx: &AggContext, batch: &RecordBatch) -> Result<Vec<u32>> {
+        Ok(match self {
+            HashingMap::Rows(map) => {
+                let grouping_rows = agg_ctx.create_grouping_rows(batch)?;
+                map.upsert_records(
+                    grouping_rows
+                        .iter()
+                        .map(|row| row.as_ref().as_raw_bytes())
+                        .collect(),
+                )
+            }
+            HashingMap::FixedKey(map) => map.upsert_records(&agg_ctx.create_fixed_keys(batch)?),
+        })
+    }
+
+    fn take_keys(&mut self, agg_ctx: &AggContext) -> Result<Vec<OwnedKey>> {
+        Ok(match self {
+            HashingMap::Rows(map) => map.take_keys(),
+            HashingMap::FixedKey(map) => fixed_keys_to_owned(agg_ctx, &map.take_keys())?,
+        })
+    }
+
+    fn into_keys(self, agg_ctx: &AggContext) -> Result<Vec<OwnedKey>> {
+        Ok(match self {
+            HashingMap::Rows(map) => map.into_keys(),
+            HashingMap::FixedKey(map) => fixed_keys_to_owned(agg_ctx, &map.into_keys())?,
+        })
+    }
+}
+
+fn fixed_keys_to_owned(agg_ctx: &AggContext, keys: &ArrayRef) -> Result<Vec<OwnedKey>> {
+    let rows = agg_ctx.convert_fixed_keys_to_rows(keys)?;
+    Ok(rows
+        .iter()
+        .map(|row| OwnedKey::from(row.as_ref()))
+        .collect())
+}
+
 pub struct HashingData {
     agg_ctx: Arc<AggContext>,
     acc_table: AccTable,
-    map: AggHashMap,
+    map: HashingMap,
     num_input_records: usize,
     hashing_time: Time,
 }
@@ -428,7 +518,7 @@ impl HashingData {
     fn new(agg_ctx: Arc<AggContext>, hashing_time: Time) -> Self {
         Self {
             acc_table: agg_ctx.create_acc_table(0),
-            map: AggHashMap::default(),
+            map: HashingMap::new(&agg_ctx),
             num_input_records: 0,
             agg_ctx,
             hashing_time,
@@ -455,13 +545,7 @@ impl HashingData {
         let num_rows = batch.num_rows();
         self.num_input_records += num_rows;
 
-        let grouping_rows = self.agg_ctx.create_grouping_rows(&batch)?;
-        let record_indices = self.map.upsert_records(
-            grouping_rows
-                .iter()
-                .map(|row| row.as_ref().as_raw_bytes())
-                .collect(),
-        );
+        let record_indices = self.map.upsert_batch(&self.agg_ctx, &batch)?;
         self.acc_table.resize(self.map.len());
         self.agg_ctx.update_batch_to_acc_table(
             &batch,
@@ -473,7 +557,7 @@ impl HashingData {
 
     fn try_into_spill(self, spill: &mut Box<dyn Spill>) -> Result<()> {
         // sort all records using radix sort on hashcodes of keys
-        let key_rows = self.map.into_keys();
+        let key_rows = self.map.into_keys(&self.agg_ctx)?;
         let acc_table = self.acc_table;
         let mut entries = key_rows
             .iter()
@@ -486,7 +570,7 @@ impl HashingData {
             *bucket_id as usize
         });
 
-        let mut writer = spill.get_compressed_writer();
+        let mut writer = spill.get_compressed_writer_with(agg_spill_compression());
         let mut offset = 0;
         for (cur_bucket_id, bucket_count) in bucket_counts.into_iter().enumerate() {
             if bucket_count == 0 {
@@ -588,7 +672,7 @@ impl MergingData {
             *bucket_id as usize
         });
 
-        let mut writer = spill.get_compressed_writer();
+        let mut writer = spill.get_compressed_writer_with(agg_spill_compression());
         let mut offset = 0;
         for (cur_bucket_id, bucket_count) in bucket_counts.into_iter().enumerate() {
             if bucket_count == 0 {
@@ -668,7 +752,7 @@ pub struct RecordsSpillCursor<'a> {
 
 impl<'a> RecordsSpillCursor<'a> {
     fn try_from_spill(spill: &'a mut Box<dyn Spill>, agg_ctx: &Arc<AggContext>) -> Result<Self> {
-        let mut input = spill.get_compressed_reader();
+        let mut input = spill.get_compressed_reader_with(agg_spill_compression())?;
         Ok(Self {
             agg_ctx: agg_ctx.clone(),
             cur_bucket_idx: read_len(&mut input)?,
@@ -715,7 +715,7 @@ mod tests {
 
         let mut acc_col_unspill = AccSetColumn::empty(DataType::Int32);
         acc_col_unspill
-            .unspill(3, &mut spill.get_compressed_reader())
+            .unspill(3, &mut spill.get_compressed_reader().unwrap())
             .unwrap();
 
         assert_eq!(acc_col.take_values(0), acc_col_unspill.take_values(0));
@@ -0,0 +1,205 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, Int64Array};
+use datafusion_ext_commons::{prefetch_write_data, unchecked};
+use unchecked_index::UncheckedIndex;
+
+// map<key: i64, value: u32> where value is the index of accumulators. used for
+// single fixed-width grouping keys (int/long/date/timestamp) which are hashed
+// directly instead of being converted to rows.
+pub struct FixedKeyAggHashMap {
+    // open-addressing slots of record_idx + 1, 0 for empty slots
+    slots: UncheckedIndex<Vec<u32>>,
+    slots_mod_bits: u32,
+    keys: UncheckedIndex<Vec<i64>>,
+    null_record_idx: Option<u32>,
+}
+
+impl Default for FixedKeyAggHashMap {
+    fn default() -> Self {
+        Self {
+            slots: unchecked!(vec![]),
+            slots_mod_bits: 0,
+            keys: unchecked!(vec![]),
+            null_record_idx: None,
+        }
+    }
+}
+
+impl FixedKeyAggHashMap {
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn mem_size(&self) -> usize {
+        size_of_val(self)
+            + self.slots.capacity() * size_of::<u32>()
+            + self.keys.capacity() * size_of::<i64>()
+    }
+
+    pub fn upsert_records(&mut self, keys: &Int64Array) -> Vec<u32> {
+        tokio::task::block_in_place(|| {
+            self.reserve(keys.len());
+            let hashes = keys
+                .values()
+                .iter()
+                .map(|&key| fixed_key_hash(key))
+                .collect::<Vec<_>>();
+            const PREFETCH_AHEAD: usize = 8;
+
+            let mut record_indices = Vec::with_capacity(keys.len());
+            for i in 0..keys.len() {
+                if i + PREFETCH_AHEAD < hashes.len() {
+                    let slot = self.slot_of(hashes[i + PREFETCH_AHEAD]);
+                    prefetch_write_data!(&self.slots[slot]);
+                }
+                if keys.is_null(i) {
+                    record_indices.push(self.upsert_null());
+                } else {
+                    record_indices.push(self.upsert_one(keys.value(i), hashes[i]));
+                }
+            }
+            record_indices
+        })
+    }
+
+    /// takes all keys as an int64 array and clears the map
+    pub fn take_keys(&mut self) -> ArrayRef {
+        self.slots.fill(0);
+        let keys = std::mem::take(&mut *self.keys);
+        let null_record_idx = std::mem::take(&mut self.null_record_idx);
+        keys_to_array(keys, null_record_idx)
+    }
+
+    pub fn into_keys(mut self) -> ArrayRef {
+        let keys = std::mem::take(&mut *self.keys);
+        keys_to_array(keys, self.null_record_idx)
+    }
+
+    fn reserve(&mut self, num_new_items: usize) {
+        let num_reserved_items = self.len() + num_new_items;
+        let new_slots_mod_bits = (num_reserved_items.max(128) * 2)
+            .next_power_of_two()
+            .trailing_zeros();
+        if new_slots_mod_bits > self.slots_mod_bits {
+            self.rehash(new_slots_mod_bits);
+        }
+    }
+
+    #[inline]
+    fn slot_of(&self, hash: u64) -> usize {
+        (hash >> (64 - self.slots_mod_bits)) as usize
+    }
+
+    #[inline]
+    fn upsert_one(&mut self, key: i64, hash: u64) -> u32 {
+        let mask = (1 << self.slots_mod_bits) - 1;
+        let mut slot = self.slot_of(hash);
+        loop {
+            let value = self.slots[slot];
+            if value == 0 {
+                let record_idx = self.keys.len() as u32;
+                self.slots[slot] = record_idx + 1;
+                self.keys.push(key);
+                return record_idx;
+            }
+            if self.keys[value as usize - 1] == key {
+                return value - 1;
+            }
+            slot = (slot + 1) & mask;
+        }
+    }
+
+    fn upsert_null(&mut self) -> u32 {
+        *self.null_record_idx.get_or_insert_with(|| {
+            // null keys take a placeholder value, which is never matched since
+            // null record is not put into slots
+            self.keys.push(0);
+            self.keys.len() as u32 - 1
+        })
+    }
+
+    fn rehash(&mut self, slots_mod_bits: u32) {
+        let mask = (1 << slots_mod_bits) - 1;
+        let mut rehashed_slots = unchecked!(vec![0u32; 1 << slots_mod_bits]);
+        for &value in self.slots.iter().filter(|&&value| value != 0) {
+            let key = self.keys[value as usize - 1];
+            let mut slot = (fixed_key_hash(key) >> (64 - slots_mod_bits)) as usize;
+            while rehashed_slots[slot] != 0 {
+                slot = (slot + 1) & mask;
+            }
+            rehashed_slots[slot] = value;
+        }
+        self.slots_mod_bits = slots_mod_bits;
+        self.slots = rehashed_slots;
+    }
+}
+
+#[inline]
+fn fixed_key_hash(key: i64) -> u64 {
+    // fibonacci hashing, higher bits are used as slot index
+    (key as u64).wrapping_mul(0x9E3779B97F4A7C15)
+}
+
+fn keys_to_array(keys: Vec<i64>, null_record_idx: Option<u32>) -> ArrayRef {
+    match null_record_idx {
+        Some(null_record_idx) => Arc::new(Int64Array::from_iter(
+            keys.into_iter()
+                .enumerate()
+                .map(|(i, key)| (i != null_record_idx as usize).then_some(key)),
+        )),
+        None => Arc::new(Int64Array::from(keys)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use arrow::{
+        array::{AsArray, Int64Array},
+        datatypes::Int64Type,
+    };
+
+    use crate::agg::fixed_key_hash_map::FixedKeyAggHashMap;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_fixed_key_hash_map() {
+        let mut map = FixedKeyAggHashMap::default();
+        let keys = Int64Array::from(vec![Some(3), None, Some(-1), Some(3), None, Some(i64::MIN)]);
+        assert_eq!(map.upsert_records(&keys), vec![0, 1, 2, 0, 1, 3]);
+
+        // rehashed with many keys
+        let keys = Int64Array::from_iter_values((0..100000).map(|i| i * 7 - 1));
+        let record_indices = map.upsert_records(&keys);
+        assert_eq!(map.len(), 100003);
+        assert_eq!(record_indices[0], 2); // -1 is inserted
+        assert_eq!(record_indices[1], 4);
+
+        let taken = map.take_keys();
+        let taken = taken.as_primitive::<Int64Type>();
+        assert_eq!(taken.len(), 100003);
+        assert_eq!(taken.null_count(), 1);
+        assert!(taken.is_null(1));
+        assert_eq!(taken.value(0), 3);
+        assert_eq!(taken.value(3), i64::MIN);
+        assert_eq!(map.len(), 0);
+
+        // map is reusable after taking keys
+        let keys = Int64Array::from(vec![Some(5), Some(3), None, Some(5)]);
+        assert_eq!(map.upsert_records(&keys), vec![0, 1, 2, 0]);
+        assert_eq!(map.into_keys().null_count(), 1);
+    }
+}
@@ -0,0 +1,188 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Canonicalization of grouping keys. natively evaluated keys may have a
+//! different decimal scale or timestamp unit than the declared key type (for
+//! example, columns read from files written with another schema). such keys
+//! are converted to the declared type before encoding, so that equal values
+//! are grouped together as in spark.
+
+use std::sync::Arc;
+
+use arrow::{
+    array::{Array, ArrayRef, AsArray, Decimal128Array, Int64Array},
+    datatypes::{DataType, Decimal128Type, Int64Type, TimeUnit},
+};
+use datafusion::common::Result;
+use datafusion_ext_commons::df_execution_err;
+
+/// returns the type that keys are encoded with, which is the declared type
+/// for decimal and timestamp keys, or the evaluated type otherwise
+pub fn canonical_key_type(data_type: &DataType, declared_type: Option<&DataType>) -> DataType {
+    match (data_type, declared_type) {
+        (DataType::Decimal128(..), Some(declared @ DataType::Decimal128(..)))
+        | (DataType::Timestamp(..), Some(declared @ DataType::Timestamp(..))) => declared.clone(),
+        _ => data_type.clone(),
+    }
+}
+
+/// converts keys to the canonical key type. decimals are rescaled with
+/// HALF_UP rounding and become null on overflow, timestamps are converted
+/// with floor division like spark.
+pub fn canonicalize_key(keys: ArrayRef, key_type: &DataType) -> Result<ArrayRef> {
+    if keys.data_type() == key_type {
+        return Ok(keys);
+    }
+    Ok(match (keys.data_type(), key_type) {
+        (&DataType::Decimal128(_, scale), &DataType::Decimal128(precision, to_scale)) => {
+            Arc::new(rescale_decimal(
+                keys.as_primitive::<Decimal128Type>(),
+                scale,
+                precision,
+                to_scale,
+            )?)
+        }
+        (DataType::Timestamp(unit, _), DataType::Timestamp(to_unit, _)) => {
+            let values = arrow::compute::cast(&keys, &DataType::Int64)?;
+            let values = values.as_primitive::<Int64Type>();
+            let (units, to_units) = (units_per_second(unit), units_per_second(to_unit));
+            let converted: Int64Array = if to_units >= units {
+                values.unary_opt(|v| v.checked_mul(to_units / units))
+            } else {
+                values.unary(|v| v.div_euclid(units / to_units))
+            };
+            arrow::compute::cast(&converted, key_type)?
+        }
+        (from, to) => df_execution_err!("cannot canonicalize grouping key from {from} to {to}")?,
+    })
+}
+
+fn rescale_decimal(
+    array: &Decimal128Array,
+    scale: i8,
+    precision: u8,
+    to_scale: i8,
+) -> Result<Decimal128Array> {
+    let max_abs = 10i128.pow(precision as u32) - 1;
+    let scale_diff = (to_scale as i32 - scale as i32).unsigned_abs();
+    let factor = 10i128.checked_pow(scale_diff);
+    let rescaled: Decimal128Array = if to_scale >= scale {
+        array.unary_opt(|v| factor.and_then(|factor| v.checked_mul(factor)))
+    } else {
+        array.unary(|v| match factor {
+            Some(factor) => {
+                let (quotient, remainder) = (v / factor, v % factor);
+                if remainder.abs() >= factor / 2 {
+                    quotient + v.signum()
+                } else {
+                    quotient
+                }
+            }
+            None => 0,
+        })
+    };
+    let rescaled: Decimal128Array = rescaled.unary_opt(|v| (v.abs() <= max_abs).then_some(v));
+    Ok(rescaled.with_precision_and_scale(precision, to_scale)?)
+}
+
+fn units_per_second(unit: &TimeUnit) -> i64 {
+    match unit {
+        TimeUnit::Second => 1,
+        TimeUnit::Millisecond => 1_000,
+        TimeUnit::Microsecond => 1_000_000,
+        TimeUnit::Nanosecond => 1_000_000_000,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{ArrayRef, AsArray, Decimal128Array, TimestampNanosecondArray},
+        datatypes::{DataType, Decimal128Type, TimeUnit, TimestampMicrosecondType},
+    };
+    use datafusion::common::Result;
+
+    use crate::agg::grouping_key::{canonical_key_type, canonicalize_key};
+
+    #[test]
+    fn test_canonicalize_decimal_key() -> Result<()> {
+        let key_type = canonical_key_type(
+            &DataType::Decimal128(10, 3),
+            Some(&DataType::Decimal128(5, 1)),
+        );
+        assert_eq!(key_type, DataType::Decimal128(5, 1));
+
+        let keys: ArrayRef = Arc::new(
+            Decimal128Array::from(vec![
+                Some(1250),
+                Some(-1250),
+                Some(1249),
+                None,
+                Some(99999999),
+            ])
+            .with_precision_and_scale(10, 3)?,
+        );
+        let canonicalized = canonicalize_key(keys, &key_type)?;
+        assert_eq!(canonicalized.data_type(), &key_type);
+        assert_eq!(
+            canonicalized
+                .as_primitive::<Decimal128Type>()
+                .iter()
+                .collect::<Vec<_>>(),
+            vec![Some(13), Some(-13), Some(12), None, None],
+        );
+
+        let keys: ArrayRef =
+            Arc::new(Decimal128Array::from(vec![Some(12)]).with_precision_and_scale(5, 1)?);
+        let canonicalized = canonicalize_key(keys, &DataType::Decimal128(10, 3))?;
+        assert_eq!(
+            canonicalized.as_primitive::<Decimal128Type>().value(0),
+            1200
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_canonicalize_timestamp_key() -> Result<()> {
+        let declared_type = DataType::Timestamp(TimeUnit::Microsecond, None);
+        let key_type = canonical_key_type(
+            &DataType::Timestamp(TimeUnit::Nanosecond, Some("UTC".into())),
+            Some(&declared_type),
+        );
+        assert_eq!(key_type, declared_type);
+
+        let keys: ArrayRef = Arc::new(
+            TimestampNanosecondArray::from(vec![Some(1_000_500), Some(-1), None])
+                .with_timezone("UTC"),
+        );
+        let canonicalized = canonicalize_key(keys, &key_type)?;
+        assert_eq!(canonicalized.data_type(), &key_type);
+        assert_eq!(
+            canonicalized
+                .as_primitive::<TimestampMicrosecondType>()
+                .iter()
+                .collect::<Vec<_>>(),
+            vec![Some(1_000), Some(-1), None],
+        );
+
+        // other types are not canonicalized
+        assert_eq!(
+            canonical_key_type(&DataType::Int32, Some(&DataType::Int64)),
+            DataType::Int32,
+        );
+        Ok(())
+    }
+}
@@ -20,7 +20,12 @@ use std::{
     sync::Arc,
 };
 
-use arrow::{array::*, datatypes::*};
+use arrow::{
+    array::*,
+    compute::take,
+    datatypes::*,
+    row::{RowConverter, SortField},
+};
 use datafusion::{common::Result, physical_expr::PhysicalExpr, scalar::ScalarValue};
 use datafusion_ext_commons::downcast_any;
 use paste::paste;
@@ -40,14 +45,24 @@ pub type AggMin = AggMaxMin<AggMinParams>;
 pub struct AggMaxMin<P: AggMaxMinParams> {
     child: Arc<dyn PhysicalExpr>,
     data_type: DataType,
+    // nested values (structs/lists) are compared in row format
+    row_converter: Option<Arc<RowConverter>>,
     _phantom: PhantomData<P>,
 }
 
 impl<P: AggMaxMinParams> AggMaxMin<P> {
     pub fn try_new(child: Arc<dyn PhysicalExpr>, data_type: DataType) -> Result<Self> {
+        let row_converter = if is_row_encoded_type(&data_type) {
+            Some(Arc::new(RowConverter::new(vec![SortField::new(
+                data_type.clone(),
+            )])?))
+        } else {
+            None
+        };
         Ok(Self {
             child,
             data_type,
+            row_converter,
             _phantom: Default::default(),
         })
     }
@@ -84,6 +99,10 @@ impl<P: AggMaxMinParams> Agg for AggMaxMin<P> {
     }
 
     fn create_acc_column(&self, num_rows: usize) -> AccColumnRef {
+        if self.row_converter.is_some() {
+            // row-encoded values are accumulated as bytes
+            return Box::new(AccGenericColumn::new(&DataType::Binary, num_rows));
+        }
         Box::new(AccGenericColumn::new(&self.data_type, num_rows))
     }
 
@@ -144,6 +163,30 @@ impl<P: AggMaxMinParams> Agg for AggMaxMin<P> {
             }};
         }
 
+        if let Some(row_converter) = &self.row_converter {
+            let partial_arg = &partial_args[0];
+            let partial_rows = row_converter.convert_columns(&[partial_arg.clone()])?;
+            idx_for_zipped! {
+                ((acc_idx, partial_arg_idx) in (acc_idx, partial_arg_idx)) => {
+                    if !partial_arg.is_valid(partial_arg_idx) {
+                        continue;
+                    }
+                    let partial_value: &[u8] = partial_rows.row(partial_arg_idx).as_ref();
+                    if accs.bytes_value(acc_idx).is_none() {
+                        accs.set_bytes_value(acc_idx, Some(AccBytes::from(partial_value)));
+                        continue;
+                    }
+                    let acc_value = accs.bytes_value(acc_idx).unwrap();
+                    if partial_value.cmp(acc_value.as_ref()) == P::ORD {
+                        accs.set_bytes_value(acc_idx, Some(AccBytes::from(partial_value)));
+                    }
+                }
+            }
+            let new_heap_mem_used = accs.items_heap_mem_used(acc_idx);
+            accs.add_heap_mem_used(new_heap_mem_used - old_heap_mem_used);
+            return Ok(());
+        }
+
         match self.data_type {
             DataType::Null => {}
             DataType::Boolean => handle_prim!(Boolean),
@@ -224,7 +267,12 @@ impl<P: AggMaxMinParams> Agg for AggMaxMin<P> {
             }}
         }
 
-        match self.data_type {
+        // row-encoded values are merged as bytes
+        let acc_data_type = match &self.row_converter {
+            Some(_) => &DataType::Binary,
+            None => &self.data_type,
+        };
+        match acc_data_type {
             DataType::Null => {}
             DataType::Boolean => handle_prim!(bool),
             DataType::Int8 => handle_prim!(i8),
@@ -290,10 +338,40 @@ impl<P: AggMaxMinParams> Agg for AggMaxMin<P> {
 
     fn final_merge(&self, accs: &mut AccColumnRef, acc_idx: IdxSelection<'_>) -> Result<ArrayRef> {
         let accs = downcast_any!(accs, mut AccGenericColumn).unwrap();
-        accs.to_array(acc_idx, &self.data_type)
+        let Some(row_converter) = &self.row_converter else {
+            return accs.to_array(acc_idx, &self.data_type);
+        };
+
+        // decode non-null rows and put nulls back to their positions
+        let acc_rows = accs.to_array(acc_idx, &DataType::Binary)?;
+        let acc_rows = acc_rows.as_binary::<i32>();
+        let row_parser = row_converter.parser();
+        let mut valid_idx = 0;
+        let take_indices = acc_rows
+            .iter()
+            .map(|row| {
+                row.map(|_| {
+                    valid_idx += 1;
+                    valid_idx - 1
+                })
+            })
+            .collect::<UInt32Array>();
+        let values = row_converter
+            .convert_rows(acc_rows.iter().flatten().map(|row| row_parser.parse(row)))?;
+        Ok(take(&values[0], &take_indices, None)?)
     }
 }
 
+fn is_row_encoded_type(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Struct(_)
+            | DataType::List(_)
+            | DataType::LargeList(_)
+            | DataType::FixedSizeList(..)
+    )
+}
+
 pub trait AggMaxMinParams: 'static + Send + Sync {
     const NAME: &'static str;
     const ORD: Ordering;
@@ -311,3 +389,101 @@ impl AggMaxMinParams for AggMinParams {
     const NAME: &'static str = "min";
     const ORD: Ordering = Ordering::Less;
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::*,
+        datatypes::{DataType, Field, Fields, Int32Type},
+    };
+    use datafusion::{common::Result, physical_expr::expressions::Column};
+
+    use crate::agg::{
+        agg::{Agg, IdxSelection},
+        maxmin::{AggMax, AggMin},
+    };
+
+    #[test]
+    fn test_maxmin_nested() -> Result<()> {
+        let fields = Fields::from(vec![
+            Field::new("a", DataType::Int32, true),
+            Field::new("b", DataType::Utf8, true),
+        ]);
+        let data_type = DataType::Struct(fields.clone());
+        let structs: ArrayRef = Arc::new(StructArray::new(
+            fields,
+            vec![
+                Arc::new(Int32Array::from(vec![
+                    Some(2),
+                    None,
+                    Some(2),
+                    Some(1),
+                    None,
+                ])),
+                Arc::new(StringArray::from(vec!["x", "y", "z", "w", "v"])),
+            ],
+            Some(vec![true, true, true, true, false].into()),
+        ));
+        let child = Arc::new(Column::new("s", 0));
+        let max = AggMax::try_new(child.clone(), data_type.clone())?;
+        let min = AggMin::try_new(child, data_type)?;
+
+        // rows 0..3 go to group 0, rows 3..5 go to group 1
+        let acc_indices = [0usize, 0, 0, 1, 1];
+        let mut max_accs = max.create_acc_column(2);
+        let mut min_accs = min.create_acc_column(2);
+        let aggs: [(&dyn Agg, _); 2] = [(&max, &mut max_accs), (&min, &mut min_accs)];
+        for (agg, accs) in aggs {
+            agg.partial_update(
+                accs,
+                IdxSelection::Indices(&acc_indices),
+                &[structs.clone()],
+                IdxSelection::Range(0, 5),
+            )?;
+        }
+
+        // merge into new accs, then evaluate
+        let mut merged_max_accs = max.create_acc_column(2);
+        max.partial_merge(
+            &mut merged_max_accs,
+            IdxSelection::Range(0, 2),
+            &mut max_accs,
+            IdxSelection::Range(0, 2),
+        )?;
+        let max_values = max.final_merge(&mut merged_max_accs, IdxSelection::Range(0, 2))?;
+        let min_values = min.final_merge(&mut min_accs, IdxSelection::Range(0, 2))?;
+
+        // nulls are smaller than non-null values inside structs
+        assert_eq!(max_values.as_ref(), structs.slice(2, 2).as_ref());
+        let min_values = min_values.as_struct();
+        assert_eq!(
+            min_values.column(0).as_primitive::<Int32Type>(),
+            &Int32Array::from(vec![None, Some(1)]),
+        );
+        assert_eq!(
+            min_values.column(1).as_string::<i32>(),
+            &StringArray::from(vec!["y", "w"]),
+        );
+
+        // lists are compared by elements
+        let lists: ArrayRef = Arc::new(ListArray::from_iter_primitive::<Int32Type, _, _>(vec![
+            Some(vec![Some(1), Some(2)]),
+            Some(vec![Some(1), Some(2), Some(0)]),
+            None,
+            Some(vec![Some(0), Some(9)]),
+        ]));
+        let max = AggMax::try_new(Arc::new(Column::new("l", 0)), lists.data_type().clone())?;
+        let mut accs = max.create_acc_column(1);
+        max.partial_update(
+            &mut accs,
+            IdxSelection::Single(0),
+            &[lists.clone()],
+            IdxSelection::Range(0, 4),
+        )?;
+        let max_values = max.final_merge(&mut accs, IdxSelection::Single(0))?;
+        assert_eq!(max_values.as_ref(), lists.slice(1, 1).as_ref());
+        Ok(())
+    }
+}
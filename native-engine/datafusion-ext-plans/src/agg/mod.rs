@@ -24,12 +24,16 @@ pub mod collect;
 pub mod count;
 pub mod first;
 pub mod first_ignores_null;
+pub mod fixed_key_hash_map;
+pub mod grouping_key;
 pub mod maxmin;
+pub mod spark_udaf_wrapper;
 pub mod sum;
 
 use std::{fmt::Debug, sync::Arc};
 
 use agg::Agg;
+use arrow::datatypes::DataType;
 use datafusion::physical_expr::PhysicalExpr;
 
 pub const AGG_BUF_COLUMN_NAME: &str = "#9223372036854775807";
@@ -74,12 +78,14 @@ pub enum AggFunction {
     BloomFilter,
     BrickhouseCollect,
     BrickhouseCombineUnique,
+    Udaf,
 }
 
 #[derive(Debug, Clone)]
 pub struct GroupingExpr {
     pub field_name: String,
     pub expr: Arc<dyn PhysicalExpr>,
+    pub key_type: Option<DataType>, // declared key type used for canonicalization
 }
 
 #[derive(Debug, Clone)]
@@ -0,0 +1,485 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    collections::HashMap,
+    fmt::{Debug, Formatter},
+    sync::Arc,
+};
+
+use arrow::{
+    array::{
+        make_array, Array, ArrayRef, AsArray, BinaryArray, Int32Array, RecordBatch,
+        RecordBatchOptions, StructArray, UInt32Array,
+    },
+    compute::take,
+    datatypes::{DataType, Field, Schema, SchemaRef},
+    ffi::{from_ffi, FFI_ArrowArray, FFI_ArrowSchema},
+};
+use blaze_jni_bridge::{
+    is_task_running, jni_call, jni_new_direct_byte_buffer, jni_new_global_ref, jni_new_object,
+};
+use datafusion::{common::Result, physical_expr::PhysicalExpr};
+use datafusion_ext_commons::{df_execution_err, downcast_any};
+use jni::objects::GlobalRef;
+use once_cell::sync::OnceCell;
+
+use crate::{
+    agg::{
+        acc::{AccBytes, AccColumnRef, AccGenericColumn},
+        agg::{Agg, IdxSelection},
+    },
+    idx_for, idx_for_zipped,
+};
+
+/// aggregate function evaluated in the JVM, used for hive UDAFs and other
+/// TypedImperativeAggregate functions not supported natively. accumulators
+/// are kept as the serialized aggregation buffers of the JVM function.
+pub struct SparkUDAFWrapper {
+    serialized: Vec<u8>,
+    return_type: DataType,
+    return_nullable: bool,
+    children: Vec<Arc<dyn PhysicalExpr>>,
+    context: OnceCell<Arc<dyn UDAFContext>>,
+}
+
+impl SparkUDAFWrapper {
+    pub fn try_new(
+        serialized: Vec<u8>,
+        return_type: DataType,
+        return_nullable: bool,
+        children: Vec<Arc<dyn PhysicalExpr>>,
+    ) -> Result<Self> {
+        Ok(Self {
+            serialized,
+            return_type,
+            return_nullable,
+            children,
+            context: OnceCell::new(),
+        })
+    }
+
+    fn context(&self) -> Result<&Arc<dyn UDAFContext>> {
+        self.context.get_or_try_init(|| {
+            let serialized_buf = jni_new_direct_byte_buffer!(&self.serialized)?;
+            let jcontext_local = jni_new_object!(SparkUDAFWrapperContext(serialized_buf.as_obj()))?;
+            let context: Arc<dyn UDAFContext> = Arc::new(JvmUDAFContext {
+                jcontext: jni_new_global_ref!(jcontext_local.as_obj())?,
+                accs_schema: Arc::new(Schema::new(vec![Field::new("acc", DataType::Binary, true)])),
+                result_schema: Arc::new(Schema::new(vec![Field::new(
+                    "result",
+                    self.return_type.clone(),
+                    self.return_nullable,
+                )])),
+            });
+            Ok(context)
+        })
+    }
+
+    /// updates the selected accumulators with rows of (local acc index, ...),
+    /// the local acc index refers to the position in `acc_indices.distinct`.
+    /// each accumulator is exchanged with the JVM only once.
+    fn update_accs(
+        &self,
+        accs: &mut AccGenericColumn,
+        acc_indices: AccIndices,
+        args: Vec<ArrayRef>,
+        is_merge: bool,
+    ) -> Result<()> {
+        if !is_task_running() {
+            df_execution_err!("SparkUDAFWrapper: is_task_running=false")?;
+        }
+        let context = self.context()?;
+        let mut rows: Vec<ArrayRef> = vec![Arc::new(Int32Array::from(acc_indices.local_ids))];
+        rows.extend(args);
+        let rows_array = make_struct_array(rows)?;
+
+        let accs_array = take_accs_array(accs, &acc_indices.distinct);
+        let updated = context.update(&accs_array, &rows_array, is_merge)?;
+        put_accs_array(accs, &acc_indices.distinct, &updated);
+        Ok(())
+    }
+}
+
+impl Debug for SparkUDAFWrapper {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SparkUDAFWrapper({:?})", self.children)
+    }
+}
+
+impl Agg for SparkUDAFWrapper {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn exprs(&self) -> Vec<Arc<dyn PhysicalExpr>> {
+        self.children.clone()
+    }
+
+    fn with_new_exprs(&self, exprs: Vec<Arc<dyn PhysicalExpr>>) -> Result<Arc<dyn Agg>> {
+        Ok(Arc::new(Self::try_new(
+            self.serialized.clone(),
+            self.return_type.clone(),
+            self.return_nullable,
+            exprs,
+        )?))
+    }
+
+    fn data_type(&self) -> &DataType {
+        &self.return_type
+    }
+
+    fn nullable(&self) -> bool {
+        self.return_nullable
+    }
+
+    fn create_acc_column(&self, num_rows: usize) -> AccColumnRef {
+        // null accumulators are treated as newly created buffers in the JVM
+        Box::new(AccGenericColumn::new(&DataType::Binary, num_rows))
+    }
+
+    fn partial_update(
+        &self,
+        accs: &mut AccColumnRef,
+        acc_idx: IdxSelection<'_>,
+        partial_args: &[ArrayRef],
+        partial_arg_idx: IdxSelection<'_>,
+    ) -> Result<()> {
+        let accs = downcast_any!(accs, mut AccGenericColumn)?;
+        let mut acc_indices = AccIndices::default();
+        let mut partial_arg_indices = vec![];
+        idx_for_zipped! {
+            ((acc_idx, partial_arg_idx) in (acc_idx, partial_arg_idx)) => {
+                acc_indices.push(acc_idx);
+                partial_arg_indices.push(partial_arg_idx as u32);
+            }
+        }
+        if partial_arg_indices.is_empty() {
+            return Ok(());
+        }
+
+        let partial_arg_indices = UInt32Array::from(partial_arg_indices);
+        let args = partial_args
+            .iter()
+            .map(|partial_arg| Ok(take(partial_arg, &partial_arg_indices, None)?))
+            .collect::<Result<Vec<_>>>()?;
+        self.update_accs(accs, acc_indices, args, false)
+    }
+
+    fn partial_merge(
+        &self,
+        accs: &mut AccColumnRef,
+        acc_idx: IdxSelection<'_>,
+        merging_accs: &mut AccColumnRef,
+        merging_acc_idx: IdxSelection<'_>,
+    ) -> Result<()> {
+        let accs = downcast_any!(accs, mut AccGenericColumn)?;
+        let merging_accs = downcast_any!(merging_accs, mut AccGenericColumn)?;
+        let mut acc_indices = AccIndices::default();
+        let mut merging_buffers = vec![];
+        idx_for_zipped! {
+            ((acc_idx, merging_acc_idx) in (acc_idx, merging_acc_idx)) => {
+                // null merging accumulators were never updated, skip them
+                if let Some(merging_buffer) = merging_accs.take_bytes_value(merging_acc_idx) {
+                    acc_indices.push(acc_idx);
+                    merging_buffers.push(merging_buffer);
+                }
+            }
+        }
+        if merging_buffers.is_empty() {
+            return Ok(());
+        }
+
+        let merging_array: ArrayRef = Arc::new(BinaryArray::from_iter_values(
+            merging_buffers.iter().map(|buffer| buffer.as_ref()),
+        ));
+        self.update_accs(accs, acc_indices, vec![merging_array], true)
+    }
+
+    fn final_merge(&self, accs: &mut AccColumnRef, acc_idx: IdxSelection<'_>) -> Result<ArrayRef> {
+        if !is_task_running() {
+            df_execution_err!("SparkUDAFWrapper: is_task_running=false")?;
+        }
+        let context = self.context()?;
+        let accs = downcast_any!(accs, mut AccGenericColumn)?;
+        let mut acc_indices = AccIndices::default();
+        idx_for! {
+            (acc_idx in acc_idx) => {
+                acc_indices.push(acc_idx);
+            }
+        }
+        let accs_array = take_accs_array(accs, &acc_indices.distinct);
+        let results = context.eval(&accs_array)?;
+
+        // repeated accumulators are evaluated once
+        if acc_indices.distinct.len() == acc_indices.local_ids.len() {
+            return Ok(results);
+        }
+        let local_ids = Int32Array::from(acc_indices.local_ids);
+        Ok(take(&results, &local_ids, None)?)
+    }
+}
+
+/// maps selected accumulator indices to distinct local ids, so that each
+/// accumulator is exchanged with the JVM only once
+#[derive(Default)]
+struct AccIndices {
+    distinct: Vec<usize>,
+    local_ids: Vec<i32>,
+    local_id_map: HashMap<usize, i32>,
+}
+
+impl AccIndices {
+    fn push(&mut self, acc_idx: usize) {
+        let distinct = &mut self.distinct;
+        let local_id = *self.local_id_map.entry(acc_idx).or_insert_with(|| {
+            distinct.push(acc_idx);
+            distinct.len() as i32 - 1
+        });
+        self.local_ids.push(local_id);
+    }
+}
+
+/// exchanges serialized aggregation buffers with the aggregate function. null
+/// buffers stand for newly created ones, and rows of `update()` start with the
+/// index of the updated buffer.
+trait UDAFContext: Send + Sync {
+    /// returns the updated buffers in the same order
+    fn update(&self, accs: &StructArray, rows: &StructArray, is_merge: bool)
+        -> Result<StructArray>;
+
+    /// returns the evaluated results of the buffers
+    fn eval(&self, accs: &StructArray) -> Result<ArrayRef>;
+}
+
+struct JvmUDAFContext {
+    jcontext: GlobalRef,
+    accs_schema: SchemaRef,
+    result_schema: SchemaRef,
+}
+
+impl UDAFContext for JvmUDAFContext {
+    fn update(
+        &self,
+        accs: &StructArray,
+        rows: &StructArray,
+        is_merge: bool,
+    ) -> Result<StructArray> {
+        let mut export_accs_ffi_array = FFI_ArrowArray::new(&accs.to_data());
+        let mut export_rows_ffi_array = FFI_ArrowArray::new(&rows.to_data());
+        let mut import_ffi_array = FFI_ArrowArray::empty();
+        if is_merge {
+            jni_call!(SparkUDAFWrapperContext(self.jcontext.as_obj()).merge(
+                &mut export_accs_ffi_array as *mut FFI_ArrowArray as i64,
+                &mut export_rows_ffi_array as *mut FFI_ArrowArray as i64,
+                &mut import_ffi_array as *mut FFI_ArrowArray as i64,
+            ) -> ())?;
+        } else {
+            jni_call!(SparkUDAFWrapperContext(self.jcontext.as_obj()).update(
+                &mut export_accs_ffi_array as *mut FFI_ArrowArray as i64,
+                &mut export_rows_ffi_array as *mut FFI_ArrowArray as i64,
+                &mut import_ffi_array as *mut FFI_ArrowArray as i64,
+            ) -> ())?;
+        }
+        import_struct_array(import_ffi_array, &self.accs_schema)
+    }
+
+    fn eval(&self, accs: &StructArray) -> Result<ArrayRef> {
+        let mut export_accs_ffi_array = FFI_ArrowArray::new(&accs.to_data());
+        let mut import_ffi_array = FFI_ArrowArray::empty();
+        jni_call!(SparkUDAFWrapperContext(self.jcontext.as_obj()).eval(
+            &mut export_accs_ffi_array as *mut FFI_ArrowArray as i64,
+            &mut import_ffi_array as *mut FFI_ArrowArray as i64,
+        ) -> ())?;
+        let results = import_struct_array(import_ffi_array, &self.result_schema)?;
+        Ok(results.column(0).clone())
+    }
+}
+
+/// takes the accumulators out of the column as serialized buffers, heap memory
+/// of the taken buffers is no longer counted in the column.
+/// `acc_indices` must be distinct.
+fn take_accs_array(accs: &mut AccGenericColumn, acc_indices: &[usize]) -> StructArray {
+    let taken_heap_mem_used = accs.items_heap_mem_used(IdxSelection::Indices(acc_indices));
+    if let AccGenericColumn::Bytes { heap_mem_used, .. } = accs {
+        *heap_mem_used -= taken_heap_mem_used;
+    }
+    let buffers = acc_indices
+        .iter()
+        .map(|&acc_idx| accs.take_bytes_value(acc_idx))
+        .collect::<Vec<_>>();
+    let accs_array: ArrayRef = Arc::new(BinaryArray::from_iter(
+        buffers
+            .iter()
+            .map(|buffer| buffer.as_ref().map(|b| b.as_ref())),
+    ));
+    StructArray::from(vec![(
+        Arc::new(Field::new("acc", DataType::Binary, true)),
+        accs_array,
+    )])
+}
+
+/// writes the updated buffers back to the taken accumulators
+fn put_accs_array(accs: &mut AccGenericColumn, acc_indices: &[usize], updated: &StructArray) {
+    let updated = updated.column(0).as_binary::<i32>();
+    for (i, &acc_idx) in acc_indices.iter().enumerate() {
+        let bytes = updated
+            .is_valid(i)
+            .then(|| AccBytes::from(updated.value(i)));
+        accs.set_bytes_value(acc_idx, bytes);
+    }
+    accs.add_heap_mem_used(accs.items_heap_mem_used(IdxSelection::Indices(acc_indices)));
+}
+
+fn make_struct_array(columns: Vec<ArrayRef>) -> Result<StructArray> {
+    let num_rows = columns[0].len();
+    let fields = columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| Field::new(format!("c{i}"), column.data_type().clone(), true))
+        .collect::<Vec<_>>();
+    let batch = RecordBatch::try_new_with_options(
+        Arc::new(Schema::new(fields)),
+        columns,
+        &RecordBatchOptions::new().with_row_count(Some(num_rows)),
+    )?;
+    Ok(StructArray::from(batch))
+}
+
+fn import_struct_array(ffi_array: FFI_ArrowArray, schema: &Schema) -> Result<StructArray> {
+    let import_ffi_schema = FFI_ArrowSchema::try_from(schema)?;
+    let import_array = make_array(unsafe { from_ffi(ffi_array, &import_ffi_schema)? });
+    Ok(import_array.as_struct().clone())
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{Array, ArrayRef, AsArray, BinaryArray, Int64Array, StructArray},
+        datatypes::{DataType, Field, Int32Type, Int64Type},
+    };
+    use datafusion::common::Result;
+
+    use super::{SparkUDAFWrapper, UDAFContext};
+    use crate::agg::{
+        acc::{AccColumnRef, AccGenericColumn},
+        agg::{Agg, IdxSelection},
+    };
+
+    /// sums int64 values, buffers are padded so that they are counted as heap
+    /// memory of the accumulator column
+    struct SumContext;
+
+    const BUFFER_LEN: usize = 32;
+
+    fn encode(sum: i64) -> Vec<u8> {
+        let mut buffer = sum.to_le_bytes().to_vec();
+        buffer.resize(BUFFER_LEN, 0);
+        buffer
+    }
+
+    fn decode(buffer: &[u8]) -> i64 {
+        i64::from_le_bytes(buffer[..8].try_into().unwrap())
+    }
+
+    impl UDAFContext for SumContext {
+        fn update(
+            &self,
+            accs: &StructArray,
+            rows: &StructArray,
+            is_merge: bool,
+        ) -> Result<StructArray> {
+            let accs = accs.column(0).as_binary::<i32>();
+            let mut sums = (0..accs.len())
+                .map(|i| accs.is_valid(i).then(|| decode(accs.value(i))))
+                .collect::<Vec<_>>();
+            let local_ids = rows.column(0).as_primitive::<Int32Type>();
+            for row in 0..rows.len() {
+                let value = if is_merge {
+                    decode(rows.column(1).as_binary::<i32>().value(row))
+                } else {
+                    rows.column(1).as_primitive::<Int64Type>().value(row)
+                };
+                *sums[local_ids.value(row) as usize].get_or_insert(0) += value;
+            }
+            let updated: ArrayRef = Arc::new(BinaryArray::from_iter(
+                sums.into_iter().map(|sum| sum.map(encode)),
+            ));
+            Ok(StructArray::from(vec![(
+                Arc::new(Field::new("acc", DataType::Binary, true)),
+                updated,
+            )]))
+        }
+
+        fn eval(&self, accs: &StructArray) -> Result<ArrayRef> {
+            let accs = accs.column(0).as_binary::<i32>();
+            Ok(Arc::new(Int64Array::from_iter(
+                (0..accs.len()).map(|i| accs.is_valid(i).then(|| decode(accs.value(i)))),
+            )))
+        }
+    }
+
+    fn heap_mem_used(accs: &mut AccColumnRef) -> usize {
+        match accs.as_any_mut().downcast_mut::<AccGenericColumn>() {
+            Some(AccGenericColumn::Bytes { heap_mem_used, .. }) => *heap_mem_used,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_repeated_acc_indices() -> Result<()> {
+        let agg = SparkUDAFWrapper::try_new(vec![], DataType::Int64, true, vec![])?;
+        assert!(agg.context.set(Arc::new(SumContext)).is_ok());
+
+        // update: acc0 <- 1 + 3 + 5, acc2 <- 2 + 4, acc1 is never updated
+        let mut accs = agg.create_acc_column(3);
+        let args: ArrayRef = Arc::new(Int64Array::from(vec![1, 2, 3, 4, 5]));
+        agg.partial_update(
+            &mut accs,
+            IdxSelection::Indices(&[0, 2, 0, 2, 0]),
+            &[args],
+            IdxSelection::Range(0, 5),
+        )?;
+        assert_eq!(heap_mem_used(&mut accs), 2 * BUFFER_LEN);
+
+        // merge: acc0 <- m0 + m2, acc2 <- m1, null m3 is skipped
+        let mut merging_accs = agg.create_acc_column(4);
+        let args: ArrayRef = Arc::new(Int64Array::from(vec![10, 100, 1000]));
+        agg.partial_update(
+            &mut merging_accs,
+            IdxSelection::Range(0, 3),
+            &[args],
+            IdxSelection::Range(0, 3),
+        )?;
+        agg.partial_merge(
+            &mut accs,
+            IdxSelection::Indices(&[0, 2, 0, 1]),
+            &mut merging_accs,
+            IdxSelection::Range(0, 4),
+        )?;
+        assert_eq!(heap_mem_used(&mut accs), 2 * BUFFER_LEN);
+
+        // eval: repeated accs are evaluated once and taken out of the column
+        let results = agg.final_merge(&mut accs, IdxSelection::Indices(&[2, 0, 2, 1, 0]))?;
+        assert_eq!(
+            results.as_primitive::<Int64Type>(),
+            &Int64Array::from(vec![Some(106), Some(1019), Some(106), None, Some(1019)]),
+        );
+        assert_eq!(heap_mem_used(&mut accs), 0);
+        Ok(())
+    }
+}
@@ -23,7 +23,7 @@ use arrow::{
     datatypes::SchemaRef,
 };
 use datafusion::{
-    common::{Result, Statistics},
+    common::{stats::Precision, Result, Statistics},
     error::DataFusionError,
     execution::context::TaskContext,
     physical_expr::EquivalenceProperties,
@@ -44,7 +44,10 @@ use crate::{
         agg_table::{AggTable, OwnedKey},
         AggExecMode, AggExpr, GroupingExpr,
     },
-    common::{execution_context::ExecutionContext, timer_helper::TimerHelper},
+    common::{
+        execution_context::ExecutionContext, partitioning::derive_projected_partitioning,
+        statistics::project_statistics, timer_helper::TimerHelper,
+    },
     expand_exec::ExpandExec,
     memmgr::MemManager,
     project_exec::ProjectExec,
@@ -110,7 +113,15 @@ impl ExecutionPlan for AggExec {
         self.props.get_or_init(|| {
             PlanProperties::new(
                 EquivalenceProperties::new(self.schema()),
-                self.input.output_partitioning().clone(),
+                derive_projected_partitioning(
+                    self.input.output_partitioning(),
+                    &self
+                        .agg_ctx
+                        .groupings
+                        .iter()
+                        .map(|grouping| (grouping.expr.clone(), grouping.field_name.clone()))
+                        .collect::<Vec<_>>(),
+                ),
                 ExecutionMode::Bounded,
             )
         })
@@ -147,7 +158,21 @@ impl ExecutionPlan for AggExec {
     }
 
     fn statistics(&self) -> Result<Statistics> {
-        todo!()
+        if self.agg_ctx.groupings.is_empty() {
+            // outputs one row in each partition
+            let num_partitions = self.output_partitioning().partition_count();
+            return Ok(Statistics {
+                num_rows: Precision::Inexact(num_partitions),
+                total_byte_size: Precision::Absent,
+                column_statistics: Statistics::unknown_column(&self.schema()),
+            });
+        }
+
+        // number of groups is unknown, so the input is an upper bound
+        let mut stats = project_statistics(self.input.statistics()?, &self.schema());
+        stats.num_rows = stats.num_rows.to_inexact();
+        stats.total_byte_size = Precision::Absent;
+        Ok(stats)
     }
 }
 
@@ -343,8 +368,19 @@ fn execute_agg_sorted(
                     IdxSelection::Indices(&acc_indices),
                 )?;
 
-                if staging_keys.len() >= batch_size {
+                // groups are emitted once the keys change. the last group may
+                // continue in the next batch, so it is kept in staging
+                if staging_keys.len() > batch_size {
+                    let last_key = staging_keys.pop().unwrap();
+                    let last_acc = agg_ctx.freeze_acc_table(
+                        &staging_acc_table,
+                        IdxSelection::Single(staging_keys.len()),
+                    )?;
+                    staging_acc_table.resize(staging_keys.len());
                     flush_staging!();
+                    staging_keys.push(last_key);
+                    agg_ctx.unfreeze_acc_table(&mut staging_acc_table, &[&last_acc[0]])?;
+                    staging_acc_table.resize(1);
                 }
             }
 
@@ -365,17 +401,18 @@ mod test {
         record_batch::RecordBatch,
     };
     use datafusion::{
-        assert_batches_sorted_eq,
-        common::{Result, ScalarValue},
+        assert_batches_eq, assert_batches_sorted_eq,
+        common::{DataFusionError, Result, ScalarValue},
         physical_expr::{expressions as phys_expr, expressions::Column},
         physical_plan::{common, memory::MemoryExec, ExecutionPlan},
         prelude::SessionContext,
     };
+    use datafusion_ext_commons::spark_task_context::SparkTaskContext;
 
     use crate::{
         agg::{
             agg::create_agg,
-            AggExecMode::HashAgg,
+            AggExecMode::{HashAgg, SortAgg},
             AggExpr, AggFunction,
             AggMode::{Final, Partial},
             GroupingExpr,
@@ -569,6 +606,7 @@ mod test {
             vec![GroupingExpr {
                 field_name: "c".to_string(),
                 expr: Arc::new(Column::new("c", 2)),
+                key_type: None,
             }],
             aggs_agg_expr.clone(),
             false,
@@ -580,6 +618,7 @@ mod test {
             vec![GroupingExpr {
                 field_name: "c".to_string(),
                 expr: Arc::new(Column::new("c", 0)),
+                key_type: None,
             }],
             aggs_agg_expr
                 .into_iter()
@@ -615,6 +654,257 @@ mod test {
         assert_batches_sorted_eq!(expected, &batches);
         Ok(())
     }
+
+    #[test]
+    fn test_agg_cancelled() -> Result<()> {
+        MemManager::init(1000); // small memory config to trigger spill
+        let n = 100000;
+        let keys = (0..n).collect::<Vec<_>>();
+        let input = build_table(
+            ("a", &keys),
+            ("b", &keys),
+            ("c", &keys),
+            ("d", &keys),
+            ("e", &keys),
+            ("f", &keys),
+            ("g", &keys),
+            ("h", &keys),
+        );
+        let agg_expr_sum = create_agg(
+            AggFunction::Sum,
+            &[phys_expr::col("b", &input.schema())?],
+            &input.schema(),
+        )?;
+        let agg_exec = Arc::new(AggExec::try_new(
+            HashAgg,
+            vec![GroupingExpr {
+                field_name: "a".to_string(),
+                expr: Arc::new(Column::new("a", 0)),
+                key_type: None,
+            }],
+            vec![AggExpr {
+                field_name: "agg_expr_sum".to_string(),
+                mode: Partial,
+                agg: agg_expr_sum,
+            }],
+            false,
+            input,
+        )?);
+
+        // run with a cancelled task context, like the native runtime of a
+        // killed task
+        let task_context = Arc::new(SparkTaskContext::new(0, 0, 0));
+        task_context.cancel();
+        let task_context_cloned = task_context.clone();
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .on_thread_start(move || {
+                SparkTaskContext::set_current(Some(task_context_cloned.clone()));
+            })
+            .build()?;
+        let result = runtime.block_on(async move {
+            let task_ctx = SessionContext::new().task_ctx();
+            common::collect(agg_exec.execute(0, task_ctx)?).await
+        });
+
+        let err = result.expect_err("agg is not cancelled");
+        assert!(err.to_string().contains("task cancelled"), "{err}");
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_agg_key_restored() -> Result<()> {
+        MemManager::init(10000);
+
+        let input = build_table(
+            ("a", &vec![2, 9, 3, 1, 0, 4, 6]),
+            ("b", &vec![1, 0, 0, 3, 5, 6, 3]),
+            ("c", &vec![7, 8, 7, 8, 9, 2, 5]),
+            ("d", &vec![-7, 86, 71, 83, 90, -2, 5]),
+            ("e", &vec![-7, 86, 71, 83, 90, -2, 5]),
+            ("f", &vec![0, 1, 2, 3, 4, 5, 6]),
+            ("g", &vec![6, 3, 6, 3, 1, 5, 4]),
+            ("h", &vec![6, 3, 6, 3, 1, 5, 4]),
+        );
+        let new_agg_expr = |field_name: &str, agg_function: AggFunction, col: &str| {
+            Ok::<_, DataFusionError>(AggExpr {
+                field_name: field_name.to_string(),
+                mode: Partial,
+                agg: create_agg(
+                    agg_function,
+                    &[phys_expr::col(col, &input.schema())?],
+                    &input.schema(),
+                )?,
+            })
+        };
+        let aggs_agg_expr = vec![
+            new_agg_expr("max_c", AggFunction::Max, "c")?,
+            new_agg_expr("first_c", AggFunction::First, "c")?,
+            new_agg_expr("min_d", AggFunction::Min, "d")?,
+        ];
+
+        let agg_exec_partial = AggExec::try_new(
+            HashAgg,
+            vec![GroupingExpr {
+                field_name: "c".to_string(),
+                expr: Arc::new(Column::new("c", 2)),
+                key_type: None,
+            }],
+            aggs_agg_expr.clone(),
+            false,
+            input,
+        )?;
+
+        // max(c) and first(c) are restored from grouping keys
+        assert_eq!(
+            agg_exec_partial.agg_ctx.key_restored_aggs,
+            vec![(0, 0), (1, 0)]
+        );
+
+        let agg_exec_final = AggExec::try_new(
+            HashAgg,
+            vec![GroupingExpr {
+                field_name: "c".to_string(),
+                expr: Arc::new(Column::new("c", 0)),
+                key_type: None,
+            }],
+            aggs_agg_expr
+                .into_iter()
+                .map(|mut agg| {
+                    agg.agg = agg
+                        .agg
+                        .with_new_exprs(vec![Arc::new(phys_expr::Literal::new(
+                            ScalarValue::Null,
+                        ))])?;
+                    agg.mode = Final;
+                    Ok(agg)
+                })
+                .collect::<Result<_>>()?,
+            false,
+            Arc::new(agg_exec_partial),
+        )?;
+
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+        let output_final = agg_exec_final.execute(0, task_ctx)?;
+        let batches = common::collect(output_final).await?;
+        let expected = vec![
+            "+---+-------+---------+-------+",
+            "| c | max_c | first_c | min_d |",
+            "+---+-------+---------+-------+",
+            "| 2 | 2     | 2       | -2    |",
+            "| 5 | 5     | 5       | 5     |",
+            "| 7 | 7     | 7       | -7    |",
+            "| 8 | 8     | 8       | 83    |",
+            "| 9 | 9     | 9       | 90    |",
+            "+---+-------+---------+-------+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_sort_agg() -> Result<()> {
+        MemManager::init(10000);
+
+        // input is ordered on the grouping key, the group of key=3 spans two batches
+        let batch1 = build_table_i32(
+            ("a", &vec![1, 1, 2, 3, 3]),
+            ("b", &vec![1, 2, 3, 4, 5]),
+            ("c", &vec![0, 0, 0, 0, 0]),
+            ("d", &vec![0, 0, 0, 0, 0]),
+            ("e", &vec![0, 0, 0, 0, 0]),
+            ("f", &vec![0, 0, 0, 0, 0]),
+            ("g", &vec![0, 0, 0, 0, 0]),
+            ("h", &vec![0, 0, 0, 0, 0]),
+        );
+        let batch2 = build_table_i32(
+            ("a", &vec![3, 4, 5, 5]),
+            ("b", &vec![6, 7, 8, 9]),
+            ("c", &vec![0, 0, 0, 0]),
+            ("d", &vec![0, 0, 0, 0]),
+            ("e", &vec![0, 0, 0, 0]),
+            ("f", &vec![0, 0, 0, 0]),
+            ("g", &vec![0, 0, 0, 0]),
+            ("h", &vec![0, 0, 0, 0]),
+        );
+        let schema = batch1.schema();
+        let input = Arc::new(MemoryExec::try_new(
+            &[vec![batch1, batch2]],
+            schema.clone(),
+            None,
+        )?);
+
+        let aggs_agg_expr = vec![
+            AggExpr {
+                field_name: "sum_b".to_string(),
+                mode: Partial,
+                agg: create_agg(AggFunction::Sum, &[phys_expr::col("b", &schema)?], &schema)?,
+            },
+            AggExpr {
+                field_name: "count_b".to_string(),
+                mode: Partial,
+                agg: create_agg(
+                    AggFunction::Count,
+                    &[phys_expr::col("b", &schema)?],
+                    &schema,
+                )?,
+            },
+        ];
+        let agg_exec_partial = AggExec::try_new(
+            SortAgg,
+            vec![GroupingExpr {
+                field_name: "a".to_string(),
+                expr: Arc::new(Column::new("a", 0)),
+                key_type: None,
+            }],
+            aggs_agg_expr.clone(),
+            false,
+            input,
+        )?;
+        let agg_exec_final = AggExec::try_new(
+            SortAgg,
+            vec![GroupingExpr {
+                field_name: "a".to_string(),
+                expr: Arc::new(Column::new("a", 0)),
+                key_type: None,
+            }],
+            aggs_agg_expr
+                .into_iter()
+                .map(|mut agg| {
+                    agg.agg = agg
+                        .agg
+                        .with_new_exprs(vec![Arc::new(phys_expr::Literal::new(
+                            ScalarValue::Null,
+                        ))])?;
+                    agg.mode = Final;
+                    Ok(agg)
+                })
+                .collect::<Result<_>>()?,
+            false,
+            Arc::new(agg_exec_partial),
+        )?;
+
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+        let output_final = agg_exec_final.execute(0, task_ctx)?;
+        let batches = common::collect(output_final).await?;
+
+        // groups are output in the input order
+        let expected = vec![
+            "+---+-------+---------+",
+            "| a | sum_b | count_b |",
+            "+---+-------+---------+",
+            "| 1 | 3     | 2       |",
+            "| 2 | 3     | 1       |",
+            "| 3 | 15    | 3       |",
+            "| 4 | 7     | 1       |",
+            "| 5 | 17    | 2       |",
+            "+---+-------+---------+",
+        ];
+        assert_batches_eq!(expected, &batches);
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -702,6 +992,7 @@ mod fuzztest {
             vec![GroupingExpr {
                 field_name: format!("key"),
                 expr: phys_expr::col("key", &schema)?,
+                key_type: None,
             }],
             vec![
                 AggExpr {
@@ -729,6 +1020,7 @@ mod fuzztest {
             vec![GroupingExpr {
                 field_name: format!("key"),
                 expr: phys_expr::col("key", &schema)?,
+                key_type: None,
             }],
             vec![
                 AggExpr {
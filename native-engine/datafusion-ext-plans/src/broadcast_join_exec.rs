@@ -21,15 +21,16 @@ use std::{
 };
 
 use arrow::{
-    array::RecordBatch,
+    array::{RecordBatch, RecordBatchOptions},
     compute::{concat_batches, SortOptions},
     datatypes::{DataType, SchemaRef},
 };
 use async_trait::async_trait;
+use blaze_jni_bridge::{conf, conf::BooleanConf, is_jni_bridge_inited};
 use datafusion::{
-    common::{DataFusionError, JoinSide, Result, Statistics},
+    common::{JoinSide, Result, Statistics},
     execution::context::TaskContext,
-    physical_expr::{EquivalenceProperties, PhysicalExprRef},
+    physical_expr::{EquivalenceProperties, PhysicalExprRef, PhysicalSortExpr},
     physical_plan::{
         joins::utils::JoinOn,
         metrics::{ExecutionPlanMetricsSet, MetricsSet, Time},
@@ -38,8 +39,11 @@ use datafusion::{
         PlanProperties, SendableRecordBatchStream,
     },
 };
-use datafusion_ext_commons::{batch_size, df_execution_err};
-use futures::{StreamExt, TryStreamExt};
+use datafusion_ext_commons::{
+    batch_size, df_execution_err,
+    io::{read_one_batch, write_one_batch},
+};
+use futures::{Stream, StreamExt, TryStreamExt};
 use hashbrown::HashMap;
 use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
@@ -48,10 +52,18 @@ use crate::{
     common::{
         column_pruning::ExecuteWithColumnPruning,
         execution_context::{ExecutionContext, WrappedRecordBatchSender},
+        partitioning::{derive_join_partitioning, derive_partitioning},
+        runtime_filter::publish_runtime_filters,
+        statistics::estimate_join_statistics,
+        stream_exec::RecordBatchStreamExec,
         timer_helper::TimerHelper,
     },
     joins::{
         bhj::{
+            build_side_collector::{
+                partition_batch, read_spilled_batches, BuildSideCollector, CollectedBuildSide,
+                NUM_SPILLED_PARTITIONS,
+            },
             full_join::{
                 LProbedFullOuterJoiner, LProbedInnerJoiner, LProbedLeftJoiner, LProbedRightJoiner,
                 RProbedFullOuterJoiner, RProbedInnerJoiner, RProbedLeftJoiner, RProbedRightJoiner,
@@ -67,6 +79,9 @@ use crate::{
         join_utils::{JoinType, JoinType::*},
         JoinParams, JoinProjection,
     },
+    memmgr::spill::{try_new_spill, Spill},
+    sort_exec::SortExec,
+    sort_merge_join_exec,
 };
 
 #[derive(Debug)]
@@ -79,6 +94,7 @@ pub struct BroadcastJoinExec {
     schema: SchemaRef,
     is_built: bool, // true for BroadcastHashJoin, false for ShuffledHashJoin
     cached_build_hash_map_id: Option<String>,
+    runtime_filter_ids: Vec<Option<String>>,
     metrics: ExecutionPlanMetricsSet,
     props: OnceCell<PlanProperties>,
 }
@@ -103,6 +119,7 @@ impl BroadcastJoinExec {
             schema,
             is_built,
             cached_build_hash_map_id,
+            runtime_filter_ids: vec![],
             metrics: ExecutionPlanMetricsSet::new(),
             props: OnceCell::new(),
         })
@@ -120,6 +137,14 @@ impl BroadcastJoinExec {
         self.broadcast_side
     }
 
+    /// publishes min/max runtime filters of the built side keys, with one
+    /// optional filter id for each key. the planner must ensure that probed
+    /// rows not matching any built row are not needed in the output.
+    pub fn with_runtime_filter_ids(mut self, runtime_filter_ids: Vec<Option<String>>) -> Self {
+        self.runtime_filter_ids = runtime_filter_ids;
+        self
+    }
+
     fn create_join_params(&self, projection: &[usize]) -> Result<JoinParams> {
         let left_schema = self.left.schema();
         let right_schema = self.right.schema();
@@ -165,6 +190,7 @@ impl BroadcastJoinExec {
             right_keys,
             batch_size: batch_size(),
             sort_options: vec![SortOptions::default(); self.on.len()],
+            null_equals_null: false,
             projection,
             key_data_types,
         })
@@ -188,6 +214,7 @@ impl BroadcastJoinExec {
         let broadcast_side = self.broadcast_side;
         let is_built = self.is_built;
         let cached_build_hash_map_id = self.cached_build_hash_map_id.clone();
+        let runtime_filter_ids = self.runtime_filter_ids.clone();
 
         let exec_ctx_cloned = exec_ctx.clone();
         let output_stream = exec_ctx_cloned.clone().output_with_sender(
@@ -204,6 +231,7 @@ impl BroadcastJoinExec {
                     join_params,
                     broadcast_side,
                     cached_build_hash_map_id,
+                    runtime_filter_ids,
                     is_built,
                     exec_ctx_cloned,
                     sender,
@@ -243,8 +271,14 @@ impl ExecutionPlan for BroadcastJoinExec {
             PlanProperties::new(
                 EquivalenceProperties::new(self.schema()),
                 match self.broadcast_side {
-                    JoinSide::Left => self.right.output_partitioning().clone(),
-                    JoinSide::Right => self.left.output_partitioning().clone(),
+                    JoinSide::Left => {
+                        derive_join_partitioning(&self.left, &self.right, &self.schema)
+                    }
+                    JoinSide::Right => derive_partitioning(
+                        self.left.output_partitioning(),
+                        &self.schema,
+                        |col| Some(col.index()),
+                    ),
                 },
                 ExecutionMode::Bounded,
             )
@@ -259,16 +293,19 @@ impl ExecutionPlan for BroadcastJoinExec {
         self: Arc<Self>,
         children: Vec<Arc<dyn ExecutionPlan>>,
     ) -> Result<Arc<dyn ExecutionPlan>> {
-        Ok(Arc::new(Self::try_new(
-            self.schema.clone(),
-            children[0].clone(),
-            children[1].clone(),
-            self.on.iter().cloned().collect(),
-            self.join_type,
-            self.broadcast_side,
-            self.is_built,
-            None,
-        )?))
+        Ok(Arc::new(
+            Self::try_new(
+                self.schema.clone(),
+                children[0].clone(),
+                children[1].clone(),
+                self.on.iter().cloned().collect(),
+                self.join_type,
+                self.broadcast_side,
+                self.is_built,
+                None,
+            )?
+            .with_runtime_filter_ids(self.runtime_filter_ids.clone()),
+        ))
     }
 
     fn execute(
@@ -285,7 +322,12 @@ impl ExecutionPlan for BroadcastJoinExec {
     }
 
     fn statistics(&self) -> Result<Statistics> {
-        unimplemented!()
+        Ok(estimate_join_statistics(
+            &self.left.statistics()?,
+            &self.right.statistics()?,
+            self.join_type,
+            &self.schema,
+        ))
     }
 }
 
@@ -304,7 +346,7 @@ impl DisplayAs for BroadcastJoinExec {
 }
 
 async fn execute_join_with_map(
-    mut probed: SendableRecordBatchStream,
+    mut probed: impl Stream<Item = Result<RecordBatch>> + Unpin + Send,
     map: Arc<JoinHashMap>,
     join_params: JoinParams,
     broadcast_side: JoinSide,
@@ -373,6 +415,7 @@ async fn execute_join(
     join_params: JoinParams,
     broadcast_side: JoinSide,
     cached_build_hash_map_id: Option<String>,
+    runtime_filter_ids: Vec<Option<String>>,
     is_built: bool,
     exec_ctx: Arc<ExecutionContext>,
     sender: Arc<WrappedRecordBatchSender>,
@@ -393,7 +436,7 @@ async fn execute_join(
     };
 
     // fetch two sides asynchronously to eagerly fetch probed side
-    let (probed, map) = futures::try_join!(
+    let (probed, built) = futures::try_join!(
         async {
             let probed_input = exec_ctx.stat_input(exec_ctx.execute(&probed_plan)?);
             let probed_schema = probed_input.schema();
@@ -406,14 +449,15 @@ async fn execute_join(
         },
         async {
             if is_built {
-                collect_join_hash_map(
+                let map = collect_join_hash_map(
                     exec_ctx.clone(),
                     cached_build_hash_map_id,
                     built_plan,
                     &map_keys,
                     build_time.clone(),
                 )
-                .await
+                .await?;
+                Ok(BuiltSide::HashMap(map))
             } else {
                 build_join_hash_map(exec_ctx.clone(), built_plan, &map_keys, build_time.clone())
                     .await
@@ -426,19 +470,209 @@ async fn execute_join(
         .elapsed_compute()
         .add_duration(build_time.duration());
 
-    execute_join_with_map(
-        probed,
-        map,
-        join_params,
-        broadcast_side,
-        exec_ctx,
-        probed_side_hash_time,
-        probed_side_search_time,
-        probed_side_compare_time,
-        build_output_time,
-        sender,
-    )
-    .await
+    match built {
+        BuiltSide::HashMap(map) => {
+            // filters are available until the join is finished
+            let _runtime_filters = publish_runtime_filters(
+                &runtime_filter_ids,
+                map.key_columns(),
+                exec_ctx.partition_id(),
+            )?;
+            execute_join_with_map(
+                probed,
+                map,
+                join_params,
+                broadcast_side,
+                exec_ctx,
+                probed_side_hash_time,
+                probed_side_search_time,
+                probed_side_compare_time,
+                build_output_time,
+                sender,
+            )
+            .await
+        }
+        BuiltSide::Spilled(data_schema, partitioned_spills) if is_smj_fallback_enabled()? => {
+            execute_join_with_smj_fallback(
+                probed,
+                data_schema,
+                partitioned_spills,
+                join_params,
+                broadcast_side,
+                exec_ctx,
+                sender,
+            )
+            .await
+        }
+        BuiltSide::Spilled(data_schema, partitioned_spills) => {
+            execute_join_with_spilled_built_side(
+                probed,
+                data_schema,
+                partitioned_spills,
+                join_params,
+                broadcast_side,
+                exec_ctx,
+                build_time,
+                probed_side_hash_time,
+                probed_side_search_time,
+                probed_side_compare_time,
+                build_output_time,
+                sender,
+            )
+            .await
+        }
+    }
+}
+
+fn is_smj_fallback_enabled() -> Result<bool> {
+    if !is_jni_bridge_inited() {
+        return Ok(true);
+    }
+    conf::SHJ_FALLBACK_TO_SMJ_ENABLE.value()
+}
+
+/// falls back to sort-merge join when the built side does not fit in memory.
+/// both sides are sorted by join keys with the spillable sorter, which also
+/// handles heavily skewed keys that cannot be split by partitioning.
+async fn execute_join_with_smj_fallback(
+    probed: SendableRecordBatchStream,
+    data_schema: SchemaRef,
+    partitioned_spills: Vec<Vec<Box<dyn Spill>>>,
+    join_params: JoinParams,
+    broadcast_side: JoinSide,
+    exec_ctx: Arc<ExecutionContext>,
+    sender: Arc<WrappedRecordBatchSender>,
+) -> Result<()> {
+    log::warn!(
+        "hash join built side does not fit in memory, falling back to sort-merge join \
+         (partition={})",
+        exec_ctx.partition_id(),
+    );
+
+    // read spilled built side partition by partition
+    let built_schema = data_schema.clone();
+    let built_batches = futures::stream::iter(partitioned_spills)
+        .map(move |spills| read_spilled_batches(&spills, &built_schema))
+        .map_ok(|batches| futures::stream::iter(batches.into_iter().map(Ok)))
+        .try_flatten();
+    let built: SendableRecordBatchStream =
+        Box::pin(RecordBatchStreamAdapter::new(data_schema, built_batches));
+
+    let sort = |input: SendableRecordBatchStream, keys: &[PhysicalExprRef]| {
+        let sort_exprs = keys
+            .iter()
+            .zip(&join_params.sort_options)
+            .map(|(key, options)| PhysicalSortExpr {
+                expr: key.clone(),
+                options: *options,
+            })
+            .collect();
+        let input = Arc::new(RecordBatchStreamExec::new(input));
+        let sort_exec = SortExec::new(input, sort_exprs, None);
+        sort_exec.execute(exec_ctx.partition_id(), exec_ctx.task_ctx())
+    };
+    let (left, right) = match broadcast_side {
+        JoinSide::Left => (
+            sort(built, &join_params.left_keys)?,
+            sort(probed, &join_params.right_keys)?,
+        ),
+        JoinSide::Right => (
+            sort(probed, &join_params.left_keys)?,
+            sort(built, &join_params.right_keys)?,
+        ),
+    };
+    sort_merge_join_exec::execute_join(left, right, join_params, exec_ctx, sender).await
+}
+
+/// joins with the spilled built side partition by partition. the probed side
+/// is partitioned in the same way and spilled before joining, so that each
+/// hash map is built from only one partition of the built side.
+async fn execute_join_with_spilled_built_side(
+    mut probed: SendableRecordBatchStream,
+    data_schema: SchemaRef,
+    partitioned_spills: Vec<Vec<Box<dyn Spill>>>,
+    join_params: JoinParams,
+    broadcast_side: JoinSide,
+    exec_ctx: Arc<ExecutionContext>,
+    build_time: Time,
+    probed_side_hash_time: Time,
+    probed_side_search_time: Time,
+    probed_side_compare_time: Time,
+    build_output_time: Time,
+    sender: Arc<WrappedRecordBatchSender>,
+) -> Result<()> {
+    let (map_keys, probed_keys) = match broadcast_side {
+        JoinSide::Left => (join_params.left_keys.clone(), join_params.right_keys.clone()),
+        JoinSide::Right => (join_params.right_keys.clone(), join_params.left_keys.clone()),
+    };
+
+    // partition and spill probed side
+    let probed_schema = probed.schema();
+    let mut probed_spills = (0..NUM_SPILLED_PARTITIONS)
+        .map(|_| try_new_spill(exec_ctx.spill_metrics()))
+        .collect::<Result<Vec<_>>>()?;
+    let mut probed_writers = probed_spills
+        .iter_mut()
+        .map(|spill| spill.get_compressed_writer())
+        .collect::<Vec<_>>();
+    while let Some(batch) = probed.next().await.transpose()? {
+        for (partition, batch) in partition_batch(batch, &probed_keys)? {
+            write_one_batch(
+                batch.num_rows(),
+                batch.columns(),
+                &mut probed_writers[partition],
+            )?;
+        }
+    }
+    for writer in probed_writers {
+        writer.finish()?;
+    }
+
+    for (built_spills, probed_spill) in partitioned_spills.into_iter().zip(probed_spills) {
+        let map = exec_ctx.baseline_metrics().elapsed_compute().with_timer(|| {
+            build_time.with_timer(|| {
+                let data_batches = read_spilled_batches(&built_spills, &data_schema)?;
+                let data_batch = concat_batches(&data_schema, data_batches.iter())?;
+                create_join_hash_map(data_batch, &map_keys)
+            })
+        })?;
+
+        let mut probed_reader = probed_spill.get_compressed_reader()?;
+        let probed_batches = std::iter::from_fn(|| {
+            let (num_rows, cols) = match read_one_batch(&mut probed_reader, &probed_schema) {
+                Ok(Some(batch)) => batch,
+                Ok(None) => return None,
+                Err(err) => return Some(Err(err)),
+            };
+            Some(
+                RecordBatch::try_new_with_options(
+                    probed_schema.clone(),
+                    cols,
+                    &RecordBatchOptions::new().with_row_count(Some(num_rows)),
+                )
+                .map_err(Into::into),
+            )
+        });
+        execute_join_with_map(
+            futures::stream::iter(probed_batches),
+            Arc::new(map),
+            join_params.clone(),
+            broadcast_side,
+            exec_ctx.clone(),
+            probed_side_hash_time.clone(),
+            probed_side_search_time.clone(),
+            probed_side_compare_time.clone(),
+            build_output_time.clone(),
+            sender.clone(),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+enum BuiltSide {
+    HashMap(Arc<JoinHashMap>),
+    Spilled(SchemaRef, Vec<Vec<Box<dyn Spill>>>),
 }
 
 async fn build_join_hash_map(
@@ -446,25 +680,39 @@ async fn build_join_hash_map(
     built_plan: Arc<dyn ExecutionPlan>,
     key_exprs: &[PhysicalExprRef],
     build_time: Time,
-) -> Result<Arc<JoinHashMap>> {
-    let input = exec_ctx.execute_with_input_stats(&built_plan)?;
+) -> Result<BuiltSide> {
+    let mut input = exec_ctx.execute_with_input_stats(&built_plan)?;
     let data_schema = input.schema();
-    let hash_map_schema = join_hash_map_schema(&data_schema);
-    let data_batches: Vec<RecordBatch> = input.try_collect().await?;
 
-    let join_hash_map = build_time.with_timer(|| {
-        let data_batch = concat_batches(&data_schema, data_batches.iter())?;
-        if data_batch.num_rows() == 0 {
-            return Ok(Arc::new(JoinHashMap::create_empty(
-                hash_map_schema,
-                key_exprs,
-            )?));
+    // collect built side data with a spillable collector, if the data is too
+    // large to fit in memory, it is partitioned and spilled
+    let collector = BuildSideCollector::new(&exec_ctx, key_exprs);
+    while let Some(batch) = input.next().await.transpose()? {
+        collector.push(batch).await?;
+    }
+    let data_batches = match collector.finish().await? {
+        CollectedBuildSide::InMem(data_batches) => data_batches,
+        CollectedBuildSide::Spilled(partitioned_spills) => {
+            return Ok(BuiltSide::Spilled(data_schema, partitioned_spills));
         }
+    };
 
-        let join_hash_map = JoinHashMap::create_from_data_batch(data_batch, key_exprs)?;
-        Ok::<_, DataFusionError>(Arc::new(join_hash_map))
+    let join_hash_map = build_time.with_timer(|| {
+        let data_batch = concat_batches(&data_schema, data_batches.iter())?;
+        create_join_hash_map(data_batch, key_exprs)
     })?;
-    Ok(join_hash_map)
+    Ok(BuiltSide::HashMap(Arc::new(join_hash_map)))
+}
+
+fn create_join_hash_map(
+    data_batch: RecordBatch,
+    key_exprs: &[PhysicalExprRef],
+) -> Result<JoinHashMap> {
+    if data_batch.num_rows() == 0 {
+        let hash_map_schema = join_hash_map_schema(&data_batch.schema());
+        return JoinHashMap::create_empty(hash_map_schema, key_exprs);
+    }
+    JoinHashMap::create_from_data_batch(data_batch, key_exprs)
 }
 
 async fn collect_join_hash_map(
@@ -564,3 +812,164 @@ async fn get_cached_join_hash_map<Fut: Future<Output = Result<JoinHashMap>> + Se
         Ok(new)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{Int32Array, RecordBatch},
+        datatypes::{DataType, Field, Schema},
+        util::pretty::pretty_format_batches,
+    };
+    use datafusion::{
+        common::{JoinSide, Result},
+        physical_expr::{expressions::Column, PhysicalExprRef},
+        physical_plan::{common, memory::MemoryExec, ExecutionPlan},
+        prelude::SessionContext,
+    };
+    use futures::StreamExt;
+
+    use crate::{
+        broadcast_join_exec::{execute_join_with_smj_fallback, BroadcastJoinExec},
+        common::execution_context::ExecutionContext,
+        joins::{
+            bhj::build_side_collector::{BuildSideCollector, CollectedBuildSide},
+            join_utils::{build_join_schema, JoinType::*},
+        },
+        memmgr::{MemConsumer, MemManager},
+    };
+
+    fn build_table(suffix: &str, keys: Vec<Option<i32>>) -> Arc<dyn ExecutionPlan> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(format!("a{suffix}"), DataType::Int32, false),
+            Field::new(format!("b{suffix}"), DataType::Int32, true),
+        ]));
+        let ids = (0..keys.len() as i32).collect::<Vec<_>>();
+        let batches = keys
+            .chunks(3)
+            .zip(ids.chunks(3))
+            .map(|(keys, ids)| {
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![
+                        Arc::new(Int32Array::from(ids.to_vec())),
+                        Arc::new(Int32Array::from(keys.to_vec())),
+                    ],
+                )
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+        Arc::new(MemoryExec::try_new(&[batches], schema, None).unwrap())
+    }
+
+    /// executes the join with a spilled built side, which falls back to
+    /// sort-merge join
+    async fn execute_spilled(join: &BroadcastJoinExec) -> Result<Vec<RecordBatch>> {
+        let task_ctx = SessionContext::new().task_ctx();
+        let projection = (0..join.schema.fields().len()).collect::<Vec<_>>();
+        let join_params = join.create_join_params(&projection)?;
+        let exec_ctx = ExecutionContext::new(
+            task_ctx,
+            0,
+            join_params.projection.schema.clone(),
+            &join.metrics,
+        );
+        let (probed_plan, built_plan, map_keys) = match join.broadcast_side {
+            JoinSide::Left => (&join.right, &join.left, &join_params.left_keys),
+            JoinSide::Right => (&join.left, &join.right, &join_params.right_keys),
+        };
+
+        let collector = BuildSideCollector::new(&exec_ctx, map_keys);
+        let mut built = exec_ctx.execute(built_plan)?;
+        while let Some(batch) = built.next().await.transpose()? {
+            collector.push(batch).await?;
+            collector.spill().await?;
+        }
+        let CollectedBuildSide::Spilled(partitioned_spills) = collector.finish().await? else {
+            unreachable!("built side is not spilled")
+        };
+
+        let probed = exec_ctx.execute(probed_plan)?;
+        let data_schema = built_plan.schema();
+        let broadcast_side = join.broadcast_side;
+        let exec_ctx_cloned = exec_ctx.clone();
+        let output = exec_ctx.output_with_sender("HashJoin", move |sender| {
+            execute_join_with_smj_fallback(
+                probed,
+                data_schema,
+                partitioned_spills,
+                join_params,
+                broadcast_side,
+                exec_ctx_cloned,
+                sender,
+            )
+        });
+        common::collect(output).await
+    }
+
+    fn sorted_rows(batches: &[RecordBatch]) -> Result<Vec<String>> {
+        let formatted = pretty_format_batches(batches)?.to_string();
+        let mut rows = formatted
+            .lines()
+            .filter(|line| line.starts_with('|'))
+            .map(|line| line.to_string())
+            .collect::<Vec<_>>();
+        rows.sort();
+        Ok(rows)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_smj_fallback() -> Result<()> {
+        MemManager::init(1000000);
+        let left_keys = vec![
+            Some(1),
+            Some(1),
+            Some(2),
+            Some(3),
+            None,
+            Some(5),
+            Some(5),
+            Some(7),
+        ];
+        let right_keys = vec![Some(1), Some(2), Some(2), Some(4), None, Some(5), Some(8)];
+
+        for join_type in [
+            Inner, Left, Right, Full, LeftSemi, LeftAnti, RightSemi, RightAnti, Existence,
+        ] {
+            for broadcast_side in [JoinSide::Left, JoinSide::Right] {
+                let left = build_table("1", left_keys.clone());
+                let right = build_table("2", right_keys.clone());
+                let on = vec![(
+                    Arc::new(Column::new_with_schema("b1", &left.schema())?) as PhysicalExprRef,
+                    Arc::new(Column::new_with_schema("b2", &right.schema())?) as PhysicalExprRef,
+                )];
+                let schema = build_join_schema(&left.schema(), &right.schema(), join_type)?;
+                let join = BroadcastJoinExec::try_new(
+                    schema,
+                    left,
+                    right,
+                    on,
+                    join_type,
+                    broadcast_side,
+                    false,
+                    None,
+                )?;
+
+                let in_mem_output =
+                    common::collect(join.execute(0, SessionContext::new().task_ctx())?).await?;
+                let spilled_output = execute_spilled(&join).await?;
+                assert!(
+                    in_mem_output.iter().any(|batch| batch.num_rows() > 0),
+                    "{join_type:?} {broadcast_side:?}",
+                );
+                assert_eq!(
+                    sorted_rows(&spilled_output)?,
+                    sorted_rows(&in_mem_output)?,
+                    "{join_type:?} {broadcast_side:?}",
+                );
+            }
+        }
+        Ok(())
+    }
+}
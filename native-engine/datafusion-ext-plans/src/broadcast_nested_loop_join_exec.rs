@@ -0,0 +1,695 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{any::Any, fmt::Formatter, ops::Range, sync::Arc};
+
+use arrow::{
+    array::{
+        new_null_array, ArrayRef, AsArray, BooleanArray, RecordBatch, RecordBatchOptions,
+        UInt32Array,
+    },
+    compute::concat_batches,
+    datatypes::{DataType, Field, Schema, SchemaRef, UInt32Type},
+};
+use datafusion::{
+    common::{JoinSide, Result, Statistics},
+    execution::context::TaskContext,
+    physical_expr::EquivalenceProperties,
+    physical_plan::{
+        joins::utils::JoinFilter,
+        metrics::{ExecutionPlanMetricsSet, MetricsSet},
+        DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, ExecutionPlanProperties,
+        PlanProperties, SendableRecordBatchStream,
+    },
+};
+use datafusion_ext_commons::{arrow::selection::take_cols, batch_size};
+use futures::{StreamExt, TryStreamExt};
+use once_cell::sync::OnceCell;
+
+use crate::{
+    common::{
+        cached_exprs_evaluator::CachedExprsEvaluator,
+        column_pruning::ExecuteWithColumnPruning,
+        execution_context::{ExecutionContext, WrappedRecordBatchSender},
+        partitioning::{derive_join_partitioning, derive_partitioning},
+        timer_helper::TimerHelper,
+    },
+    joins::{join_hash_map::join_data_schema, join_utils::JoinType, JoinProjection},
+};
+
+/// Joins the streamed side with the broadcast side by evaluating the join
+/// filter on every pair of rows, used for joins without equality keys (e.g.
+/// `a.ts BETWEEN b.start AND b.end`). the broadcast side (hash map batches
+/// built by the broadcast exchange, of which only the data columns are used)
+/// is collected in memory. joins without filter are cartesian products.
+#[derive(Debug)]
+pub struct BroadcastNestedLoopJoinExec {
+    left: Arc<dyn ExecutionPlan>,
+    right: Arc<dyn ExecutionPlan>,
+    join_type: JoinType,
+    broadcast_side: JoinSide,
+    join_filter: Option<JoinFilter>,
+    schema: SchemaRef,
+    metrics: ExecutionPlanMetricsSet,
+    props: OnceCell<PlanProperties>,
+}
+
+impl BroadcastNestedLoopJoinExec {
+    pub fn try_new(
+        schema: SchemaRef,
+        left: Arc<dyn ExecutionPlan>,
+        right: Arc<dyn ExecutionPlan>,
+        join_type: JoinType,
+        broadcast_side: JoinSide,
+        join_filter: Option<JoinFilter>,
+    ) -> Result<Self> {
+        Ok(Self {
+            left,
+            right,
+            join_type,
+            broadcast_side,
+            join_filter,
+            schema,
+            metrics: ExecutionPlanMetricsSet::new(),
+            props: OnceCell::new(),
+        })
+    }
+
+    fn execute_with_projection(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+        projection: Vec<usize>,
+    ) -> Result<SendableRecordBatchStream> {
+        let (left_schema, right_schema) = match self.broadcast_side {
+            JoinSide::Left => (join_data_schema(&self.left.schema()), self.right.schema()),
+            JoinSide::Right => (self.left.schema(), join_data_schema(&self.right.schema())),
+        };
+        let projection = JoinProjection::try_new(
+            self.join_type,
+            &self.schema,
+            &left_schema,
+            &right_schema,
+            &projection,
+        )?;
+        let exec_ctx =
+            ExecutionContext::new(context, partition, projection.schema.clone(), &self.metrics);
+        let (streamed_plan, built_plan) = match self.broadcast_side {
+            JoinSide::Left => (self.right.clone(), self.left.clone()),
+            JoinSide::Right => (self.left.clone(), self.right.clone()),
+        };
+        let filter = match &self.join_filter {
+            Some(join_filter) => {
+                let evaluator = CachedExprsEvaluator::try_new(
+                    vec![join_filter.expression().clone()],
+                    vec![],
+                    Arc::new(join_filter.schema().clone()),
+                )?
+                .with_expr_metrics(&exec_ctx)?;
+                Some((evaluator, join_filter.clone()))
+            }
+            None => None,
+        };
+        let join_type = self.join_type;
+        let broadcast_side = self.broadcast_side;
+
+        let exec_ctx_cloned = exec_ctx.clone();
+        let output = exec_ctx.clone().output_with_sender(
+            "BroadcastNestedLoopJoin",
+            move |sender| async move {
+                let elapsed_compute = exec_ctx_cloned.baseline_metrics().elapsed_compute().clone();
+                let _timer = elapsed_compute.timer();
+                sender.exclude_time(&elapsed_compute);
+
+                // collect broadcast side in memory
+                let built_schema = join_data_schema(&built_plan.schema());
+                let built_data_indices = (0..built_schema.fields().len()).collect::<Vec<_>>();
+                let built_batches: Vec<RecordBatch> = elapsed_compute
+                    .exclude_timer_async(
+                        exec_ctx_cloned
+                            .execute_with_input_stats(&built_plan)?
+                            .try_collect(),
+                    )
+                    .await?;
+                let built_data_batches = built_batches
+                    .iter()
+                    .map(|batch| batch.project(&built_data_indices))
+                    .collect::<std::result::Result<Vec<_>, _>>()?;
+                let built = concat_batches(&built_schema, &built_data_batches)?;
+
+                let mut joiner = NestedLoopJoiner::new(
+                    join_type,
+                    broadcast_side,
+                    filter,
+                    projection,
+                    built,
+                    sender,
+                );
+                let mut streamed = exec_ctx_cloned.execute_with_input_stats(&streamed_plan)?;
+                while let Some(batch) = elapsed_compute
+                    .exclude_timer_async(streamed.next())
+                    .await
+                    .transpose()?
+                {
+                    joiner.join_streamed_batch(batch).await?;
+                }
+                joiner.finish().await?;
+                exec_ctx_cloned
+                    .baseline_metrics()
+                    .record_output(joiner.num_output_rows);
+                Ok(())
+            },
+        );
+        Ok(exec_ctx.coalesce_with_default_batch_size(output))
+    }
+}
+
+impl ExecuteWithColumnPruning for BroadcastNestedLoopJoinExec {
+    fn execute_projected(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+        projection: &[usize],
+    ) -> Result<SendableRecordBatchStream> {
+        self.execute_with_projection(partition, context, projection.to_vec())
+    }
+}
+
+impl DisplayAs for BroadcastNestedLoopJoinExec {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "BroadcastNestedLoopJoin: join_type={:?}, broadcast_side={:?}, filter={:?}",
+            self.join_type,
+            self.broadcast_side,
+            self.join_filter.as_ref().map(|filter| filter.expression()),
+        )
+    }
+}
+
+impl ExecutionPlan for BroadcastNestedLoopJoinExec {
+    fn name(&self) -> &str {
+        "BroadcastNestedLoopJoin"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.props.get_or_init(|| {
+            PlanProperties::new(
+                EquivalenceProperties::new(self.schema()),
+                match self.broadcast_side {
+                    JoinSide::Left => {
+                        derive_join_partitioning(&self.left, &self.right, &self.schema)
+                    }
+                    JoinSide::Right => {
+                        derive_partitioning(self.left.output_partitioning(), &self.schema, |col| {
+                            Some(col.index())
+                        })
+                    }
+                },
+                ExecutionMode::Bounded,
+            )
+        })
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.left, &self.right]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(Self::try_new(
+            self.schema.clone(),
+            children[0].clone(),
+            children[1].clone(),
+            self.join_type,
+            self.broadcast_side,
+            self.join_filter.clone(),
+        )?))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let projection = (0..self.schema.fields().len()).collect();
+        self.execute_with_projection(partition, context, projection)
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        Ok(Statistics::new_unknown(&self.schema))
+    }
+}
+
+/// Rows of one side to output besides the joined pairs
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SideOutput {
+    None,
+    Matched,   // semi join
+    Unmatched, // outer/anti join
+    Existence, // existence join, all rows with `exists` column
+}
+
+struct NestedLoopJoiner {
+    join_type: JoinType,
+    broadcast_side: JoinSide,
+    filter: Option<(CachedExprsEvaluator, JoinFilter)>,
+    intermediate_schema: SchemaRef,
+    projection: JoinProjection,
+    built: RecordBatch,
+    built_matched: Vec<bool>,
+    sender: Arc<WrappedRecordBatchSender>,
+    num_output_rows: usize,
+}
+
+impl NestedLoopJoiner {
+    fn new(
+        join_type: JoinType,
+        broadcast_side: JoinSide,
+        filter: Option<(CachedExprsEvaluator, JoinFilter)>,
+        projection: JoinProjection,
+        built: RecordBatch,
+        sender: Arc<WrappedRecordBatchSender>,
+    ) -> Self {
+        // indices of paired rows are appended after the filter columns, so
+        // that they are retained after filtering
+        let filter_fields = filter
+            .as_ref()
+            .map(|(_, join_filter)| join_filter.schema().fields().to_vec())
+            .unwrap_or_default();
+        let intermediate_schema = Arc::new(Schema::new(
+            [
+                filter_fields,
+                vec![
+                    Arc::new(Field::new("__streamed_idx", DataType::UInt32, false)),
+                    Arc::new(Field::new("__built_idx", DataType::UInt32, false)),
+                ],
+            ]
+            .concat(),
+        ));
+        let built_matched = vec![false; built.num_rows()];
+        Self {
+            join_type,
+            broadcast_side,
+            filter,
+            intermediate_schema,
+            projection,
+            built,
+            built_matched,
+            sender,
+            num_output_rows: 0,
+        }
+    }
+
+    fn streamed_side(&self) -> JoinSide {
+        match self.broadcast_side {
+            JoinSide::Left => JoinSide::Right,
+            JoinSide::Right => JoinSide::Left,
+        }
+    }
+
+    fn outputs_pairs(&self) -> bool {
+        matches!(
+            self.join_type,
+            JoinType::Inner | JoinType::Left | JoinType::Right | JoinType::Full
+        )
+    }
+
+    fn side_output(&self, side: JoinSide) -> SideOutput {
+        match (self.join_type, side) {
+            (JoinType::Left | JoinType::Full | JoinType::LeftAnti, JoinSide::Left) => {
+                SideOutput::Unmatched
+            }
+            (JoinType::Right | JoinType::Full | JoinType::RightAnti, JoinSide::Right) => {
+                SideOutput::Unmatched
+            }
+            (JoinType::LeftSemi, JoinSide::Left) | (JoinType::RightSemi, JoinSide::Right) => {
+                SideOutput::Matched
+            }
+            (JoinType::Existence, JoinSide::Left) => SideOutput::Existence,
+            _ => SideOutput::None,
+        }
+    }
+
+    async fn join_streamed_batch(&mut self, streamed: RecordBatch) -> Result<()> {
+        let num_streamed_rows = streamed.num_rows();
+        let num_built_rows = self.built.num_rows();
+        let mut streamed_matched = vec![false; num_streamed_rows];
+
+        if num_built_rows > 0 {
+            // each chunk of streamed rows generates about batch_size pairs
+            let num_rows_per_chunk = (batch_size() / num_built_rows).max(1);
+            for start in (0..num_streamed_rows).step_by(num_rows_per_chunk) {
+                let end = (start + num_rows_per_chunk).min(num_streamed_rows);
+                let (streamed_indices, built_indices) = self.find_matches(&streamed, start..end)?;
+                for &idx in streamed_indices.values() {
+                    streamed_matched[idx as usize] = true;
+                }
+                for &idx in built_indices.values() {
+                    self.built_matched[idx as usize] = true;
+                }
+                if self.outputs_pairs() && !streamed_indices.is_empty() {
+                    self.output_pairs(&streamed, streamed_indices, built_indices)
+                        .await?;
+                }
+            }
+        }
+        self.output_side_rows(self.streamed_side(), &streamed, &streamed_matched)
+            .await
+    }
+
+    async fn finish(&mut self) -> Result<()> {
+        let built = self.built.clone();
+        let built_matched = std::mem::take(&mut self.built_matched);
+        self.output_side_rows(self.broadcast_side, &built, &built_matched)
+            .await
+    }
+
+    /// returns indices of (streamed, built) row pairs satisfying the join
+    /// filter, with streamed rows in the specified range
+    fn find_matches(
+        &self,
+        streamed: &RecordBatch,
+        streamed_range: Range<usize>,
+    ) -> Result<(UInt32Array, UInt32Array)> {
+        let num_built_rows = self.built.num_rows();
+        let num_pairs = streamed_range.len() * num_built_rows;
+        let streamed_indices = UInt32Array::from_iter_values(
+            streamed_range
+                .clone()
+                .flat_map(|idx| std::iter::repeat(idx as u32).take(num_built_rows)),
+        );
+        let built_indices =
+            UInt32Array::from_iter_values(streamed_range.flat_map(|_| 0..num_built_rows as u32));
+
+        let Some((evaluator, join_filter)) = &self.filter else {
+            return Ok((streamed_indices, built_indices));
+        };
+        let (left, left_indices, right, right_indices) = match self.broadcast_side {
+            JoinSide::Left => (&self.built, &built_indices, streamed, &streamed_indices),
+            JoinSide::Right => (streamed, &streamed_indices, &self.built, &built_indices),
+        };
+        let mut intermediate_cols = join_filter
+            .column_indices()
+            .iter()
+            .map(|column_index| {
+                let (batch, indices) = match column_index.side {
+                    JoinSide::Left => (left, left_indices),
+                    JoinSide::Right => (right, right_indices),
+                };
+                Ok(arrow::compute::take(
+                    batch.column(column_index.index),
+                    indices,
+                    None,
+                )?)
+            })
+            .collect::<Result<Vec<ArrayRef>>>()?;
+        intermediate_cols.push(Arc::new(streamed_indices));
+        intermediate_cols.push(Arc::new(built_indices));
+        let intermediate = RecordBatch::try_new_with_options(
+            self.intermediate_schema.clone(),
+            intermediate_cols,
+            &RecordBatchOptions::new().with_row_count(Some(num_pairs)),
+        )?;
+
+        let filtered = evaluator.filter(&intermediate)?;
+        let num_cols = filtered.num_columns();
+        Ok((
+            filtered
+                .column(num_cols - 2)
+                .as_primitive::<UInt32Type>()
+                .clone(),
+            filtered
+                .column(num_cols - 1)
+                .as_primitive::<UInt32Type>()
+                .clone(),
+        ))
+    }
+
+    async fn output_pairs(
+        &mut self,
+        streamed: &RecordBatch,
+        streamed_indices: UInt32Array,
+        built_indices: UInt32Array,
+    ) -> Result<()> {
+        let (left_cols, right_cols) = match self.broadcast_side {
+            JoinSide::Left => (
+                take_cols(
+                    &self.projection.project_left(self.built.columns()),
+                    built_indices,
+                )?,
+                take_cols(
+                    &self.projection.project_right(streamed.columns()),
+                    streamed_indices,
+                )?,
+            ),
+            JoinSide::Right => (
+                take_cols(
+                    &self.projection.project_left(streamed.columns()),
+                    streamed_indices,
+                )?,
+                take_cols(
+                    &self.projection.project_right(self.built.columns()),
+                    built_indices,
+                )?,
+            ),
+        };
+        self.output([left_cols, right_cols].concat()).await
+    }
+
+    async fn output_side_rows(
+        &mut self,
+        side: JoinSide,
+        batch: &RecordBatch,
+        matched: &[bool],
+    ) -> Result<()> {
+        let output_cols = match self.side_output(side) {
+            SideOutput::None => return Ok(()),
+            SideOutput::Existence => {
+                if batch.num_rows() == 0 {
+                    return Ok(());
+                }
+                let exists_col = Arc::new(BooleanArray::from(matched.to_vec()));
+                self.projection
+                    .project_exists(self.projection.project_left(batch.columns()), exists_col)
+            }
+            side_output => {
+                let selected_matched = side_output == SideOutput::Matched;
+                let indices = UInt32Array::from_iter_values(
+                    matched
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, &m)| m == selected_matched)
+                        .map(|(idx, _)| idx as u32),
+                );
+                if indices.is_empty() {
+                    return Ok(());
+                }
+
+                // columns of the other side are filled with nulls in outer
+                // joins, and are empty in semi/anti joins
+                let num_rows = indices.len();
+                let nulls = |schema: &SchemaRef| {
+                    schema
+                        .fields()
+                        .iter()
+                        .map(|field| new_null_array(field.data_type(), num_rows))
+                        .collect::<Vec<_>>()
+                };
+                match side {
+                    JoinSide::Left => [
+                        take_cols(&self.projection.project_left(batch.columns()), indices)?,
+                        nulls(&self.projection.right_schema),
+                    ]
+                    .concat(),
+                    JoinSide::Right => [
+                        nulls(&self.projection.left_schema),
+                        take_cols(&self.projection.project_right(batch.columns()), indices)?,
+                    ]
+                    .concat(),
+                }
+            }
+        };
+        self.output(output_cols).await
+    }
+
+    async fn output(&mut self, cols: Vec<ArrayRef>) -> Result<()> {
+        let num_rows = cols.first().map(|col| col.len()).unwrap_or_default();
+        let output_batch = RecordBatch::try_new_with_options(
+            self.projection.schema.clone(),
+            cols,
+            &RecordBatchOptions::new().with_row_count(Some(num_rows)),
+        )?;
+        self.num_output_rows += num_rows;
+        self.sender.send(output_batch).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{Int32Array, RecordBatch},
+        datatypes::{DataType, Field, Schema},
+    };
+    use datafusion::{
+        assert_batches_sorted_eq,
+        common::{JoinSide, Result},
+        logical_expr::Operator,
+        physical_expr::expressions::{binary, col},
+        physical_plan::{
+            common,
+            joins::utils::{ColumnIndex, JoinFilter},
+            memory::MemoryExec,
+            ExecutionPlan,
+        },
+        prelude::SessionContext,
+    };
+
+    use crate::{
+        broadcast_join_build_hash_map_exec::BroadcastJoinBuildHashMapExec,
+        broadcast_nested_loop_join_exec::BroadcastNestedLoopJoinExec,
+        joins::join_utils::{build_join_schema, JoinType},
+        memmgr::MemManager,
+    };
+
+    fn build_table(name: &str, values: Vec<i32>) -> Result<Arc<dyn ExecutionPlan>> {
+        let schema = Arc::new(Schema::new(vec![Field::new(name, DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from(values))])?;
+        Ok(Arc::new(MemoryExec::try_new(&[vec![batch]], schema, None)?))
+    }
+
+    async fn join_collect(
+        join_type: JoinType,
+        broadcast_side: JoinSide,
+    ) -> Result<Vec<RecordBatch>> {
+        MemManager::init(1000000);
+        let left = build_table("a", vec![1, 5, 10])?;
+        let right = build_table("b", vec![2, 4, 6])?;
+
+        // filter: a < b
+        let filter_schema = Schema::new(vec![
+            Field::new("a", DataType::Int32, false),
+            Field::new("b", DataType::Int32, false),
+        ]);
+        let filter_expr = binary(
+            col("a", &filter_schema)?,
+            Operator::Lt,
+            col("b", &filter_schema)?,
+            &filter_schema,
+        )?;
+        let column_indices = vec![
+            ColumnIndex {
+                index: 0,
+                side: JoinSide::Left,
+            },
+            ColumnIndex {
+                index: 0,
+                side: JoinSide::Right,
+            },
+        ];
+        let join_filter = JoinFilter::new(filter_expr, column_indices, filter_schema);
+
+        let schema = build_join_schema(&left.schema(), &right.schema(), join_type)?;
+        let (left, right): (Arc<dyn ExecutionPlan>, Arc<dyn ExecutionPlan>) = match broadcast_side {
+            JoinSide::Left => (
+                Arc::new(BroadcastJoinBuildHashMapExec::new(left, vec![])),
+                right,
+            ),
+            JoinSide::Right => (
+                left,
+                Arc::new(BroadcastJoinBuildHashMapExec::new(right, vec![])),
+            ),
+        };
+        let join = BroadcastNestedLoopJoinExec::try_new(
+            schema,
+            left,
+            right,
+            join_type,
+            broadcast_side,
+            Some(join_filter),
+        )?;
+        let stream = join.execute(0, SessionContext::new().task_ctx())?;
+        common::collect(stream).await
+    }
+
+    #[tokio::test]
+    async fn test_nested_loop_join() -> Result<()> {
+        for broadcast_side in [JoinSide::Left, JoinSide::Right] {
+            let batches = join_collect(JoinType::Inner, broadcast_side).await?;
+            let expected = vec![
+                "+---+---+",
+                "| a | b |",
+                "+---+---+",
+                "| 1 | 2 |",
+                "| 1 | 4 |",
+                "| 1 | 6 |",
+                "| 5 | 6 |",
+                "+---+---+",
+            ];
+            assert_batches_sorted_eq!(expected, &batches);
+
+            let batches = join_collect(JoinType::Full, broadcast_side).await?;
+            let expected = vec![
+                "+----+---+",
+                "| a  | b |",
+                "+----+---+",
+                "| 1  | 2 |",
+                "| 1  | 4 |",
+                "| 1  | 6 |",
+                "| 5  | 6 |",
+                "| 10 |   |",
+                "+----+---+",
+            ];
+            assert_batches_sorted_eq!(expected, &batches);
+
+            let batches = join_collect(JoinType::RightAnti, broadcast_side).await?;
+            let expected = vec!["++", "++"];
+            assert_batches_sorted_eq!(expected, &batches);
+
+            let batches = join_collect(JoinType::LeftSemi, broadcast_side).await?;
+            let expected = vec!["+---+", "| a |", "+---+", "| 1 |", "| 5 |", "+---+"];
+            assert_batches_sorted_eq!(expected, &batches);
+
+            let batches = join_collect(JoinType::Existence, broadcast_side).await?;
+            let expected = vec![
+                "+----+--------+",
+                "| a  | exists |",
+                "+----+--------+",
+                "| 1  | true   |",
+                "| 10 | false  |",
+                "| 5  | true   |",
+                "+----+--------+",
+            ];
+            assert_batches_sorted_eq!(expected, &batches);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,165 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{any::Any, fmt::Formatter, sync::Arc};
+
+use arrow::datatypes::SchemaRef;
+use datafusion::{
+    common::{Result, Statistics},
+    execution::context::TaskContext,
+    physical_expr::EquivalenceProperties,
+    physical_plan::{
+        metrics::{ExecutionPlanMetricsSet, MetricsSet},
+        DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, ExecutionPlanProperties,
+        Partitioning, PlanProperties, SendableRecordBatchStream,
+    },
+};
+use datafusion_ext_commons::df_execution_err;
+use once_cell::sync::OnceCell;
+
+use crate::{common::execution_context::ExecutionContext, union_exec::execute_union};
+
+/// Merges all partitions of the input into a single partition. input
+/// partitions are read one after another in the current task.
+#[derive(Debug)]
+pub struct CoalescePartitionsExec {
+    input: Arc<dyn ExecutionPlan>,
+    metrics: ExecutionPlanMetricsSet,
+    props: OnceCell<PlanProperties>,
+}
+
+impl CoalescePartitionsExec {
+    pub fn new(input: Arc<dyn ExecutionPlan>) -> Self {
+        Self {
+            input,
+            metrics: ExecutionPlanMetricsSet::new(),
+            props: OnceCell::new(),
+        }
+    }
+}
+
+impl DisplayAs for CoalescePartitionsExec {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "CoalescePartitionsExec")
+    }
+}
+
+impl ExecutionPlan for CoalescePartitionsExec {
+    fn name(&self) -> &str {
+        "CoalescePartitionsExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.props.get_or_init(|| {
+            PlanProperties::new(
+                EquivalenceProperties::new(self.schema()),
+                Partitioning::UnknownPartitioning(1),
+                ExecutionMode::Bounded,
+            )
+        })
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.input]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(Self::new(children[0].clone())))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        if partition != 0 {
+            return df_execution_err!("CoalescePartitionsExec: invalid partition {partition}");
+        }
+        let exec_ctx =
+            ExecutionContext::new(context.clone(), partition, self.schema(), &self.metrics);
+        let num_input_partitions = self.input.output_partitioning().partition_count();
+        let inputs = (0..num_input_partitions)
+            .map(|input_partition| {
+                Ok(exec_ctx.stat_input(self.input.execute(input_partition, context.clone())?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        execute_union(inputs, exec_ctx)
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        // all rows of the input are output
+        self.input.statistics()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::Int32Array,
+        datatypes::{DataType, Field, Schema},
+        record_batch::RecordBatch,
+    };
+    use datafusion::{
+        assert_batches_eq,
+        common::Result,
+        physical_plan::{common, memory::MemoryExec, ExecutionPlan, ExecutionPlanProperties},
+        prelude::SessionContext,
+    };
+
+    use crate::coalesce_partitions_exec::CoalescePartitionsExec;
+
+    #[tokio::test]
+    async fn test_coalesce_partitions() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let partitions = [vec![1, 2], vec![], vec![3]]
+            .into_iter()
+            .map(|values| {
+                Ok(vec![RecordBatch::try_new(
+                    schema.clone(),
+                    vec![Arc::new(Int32Array::from(values))],
+                )?])
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let input = Arc::new(MemoryExec::try_new(&partitions, schema, None)?);
+        let coalesce = CoalescePartitionsExec::new(input);
+        assert_eq!(coalesce.output_partitioning().partition_count(), 1);
+
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+        let output = coalesce.execute(0, task_ctx)?;
+        let batches = common::collect(output).await?;
+        let expected = vec![
+            "+---+", "| a |", "+---+", "| 1 |", "| 2 |", "| 3 |", "+---+",
+        ];
+        assert_batches_eq!(expected, &batches);
+        Ok(())
+    }
+}
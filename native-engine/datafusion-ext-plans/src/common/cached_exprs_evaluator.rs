@@ -19,37 +19,58 @@ use std::{
     fmt::{Debug, Display, Formatter},
     hash::{Hash, Hasher},
     rc::Rc,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicUsize, Ordering::Relaxed},
+        Arc,
+    },
+    time::Instant,
 };
 
 use arrow::{
     array::{Array, ArrayRef, BooleanArray},
-    compute::{filter, filter_record_batch, prep_null_mask_filter},
+    compute::{and, filter, filter_record_batch, prep_null_mask_filter},
     datatypes::{DataType, Schema, SchemaRef},
     record_batch::{RecordBatch, RecordBatchOptions},
 };
+use blaze_jni_bridge::{
+    conf::{self, BooleanConf, IntConf},
+    is_jni_bridge_inited,
+};
 use datafusion::{
     common::{
         cast::as_boolean_array,
         tree_node::{Transformed, TreeNode},
         Result, ScalarValue,
     },
+    logical_expr::Operator,
     physical_expr::{
-        expressions::{CaseExpr, Column, Literal, NoOp, SCAndExpr, SCOrExpr},
+        expressions::{
+            BinaryExpr, CaseExpr, CastExpr, Column, InListExpr, IsNotNullExpr, IsNullExpr,
+            LikeExpr, Literal, NoOp, NotExpr, SCAndExpr, SCOrExpr,
+        },
         PhysicalExpr, PhysicalExprRef,
     },
     physical_expr_common::utils::scatter,
-    physical_plan::ColumnarValue,
+    physical_plan::{metrics::Time, ColumnarValue},
 };
 use datafusion_ext_commons::{arrow::cast::cast, uda::UserDefinedArray};
+use datafusion_ext_exprs::higher_order_function::HigherOrderFunctionExpr;
 use itertools::Itertools;
 use parking_lot::Mutex;
 
+use crate::common::{
+    execution_context::ExecutionContext, predicate_cache::PredicateCache,
+    selection_vector::SelectedBatch,
+};
+
 pub struct CachedExprsEvaluator {
     transformed_projection_exprs: Vec<PhysicalExprRef>,
     transformed_pruned_filter_exprs: Vec<(PhysicalExprRef, Vec<usize>)>,
+    filter_fingerprints: Vec<Option<String>>,
     output_schema: SchemaRef,
     cache: Cache,
+    expr_metrics: Option<ExprMetrics>,
+    predicate_ordering: Option<PredicateOrdering>,
 }
 
 impl CachedExprsEvaluator {
@@ -63,32 +84,110 @@ impl CachedExprsEvaluator {
         let (transformed_filter_exprs, transformed_projection_exprs) =
             transformed_exprs.split_at(filter_exprs.len());
 
-        let transformed_pruned_filter_exprs = transformed_filter_exprs
+        let transformed_pruned_filter_exprs: Vec<_> = transformed_filter_exprs
             .into_iter()
             .map(|expr| prune_expr_cols(expr))
             .collect();
+        let filter_fingerprints = transformed_pruned_filter_exprs
+            .iter()
+            .map(|(expr, _)| predicate_fingerprint(expr))
+            .collect();
         let transformed_projection_exprs = transformed_projection_exprs.to_vec();
+        let predicate_ordering = PredicateOrdering::from_blaze_conf(&filter_exprs)?;
 
         Ok(Self {
             transformed_projection_exprs,
             transformed_pruned_filter_exprs,
+            filter_fingerprints,
             output_schema,
             cache,
+            expr_metrics: None,
+            predicate_ordering,
         })
     }
 
+    /// enables sampled evaluation timers of each filter/projection expression
+    /// if spark.blaze.exprMetrics.enable is set. timers are registered in the
+    /// owning operator's metrics as `filter_expr_time_<i>` and
+    /// `project_expr_time_<i>`.
+    pub fn with_expr_metrics(mut self, exec_ctx: &ExecutionContext) -> Result<Self> {
+        self.expr_metrics = ExprMetrics::from_blaze_conf(
+            exec_ctx,
+            self.transformed_pruned_filter_exprs.len(),
+            self.transformed_projection_exprs.len(),
+        )?;
+        Ok(self)
+    }
+
     pub fn filter(&self, batch: &RecordBatch) -> Result<RecordBatch> {
-        self.cache.with(|_| self.filter_impl(batch))
+        let sampled = self.sampled_expr_metrics();
+        self.cache.with(|_| self.filter_impl(batch, sampled))
+    }
+
+    /// refines the selection of a lazily filtered batch, rows are not compacted
+    /// and predicates are only evaluated on the already selected rows
+    pub fn filter_selected(&self, selected: SelectedBatch) -> Result<SelectedBatch> {
+        let sampled = self.sampled_expr_metrics();
+        let init_filtered = match selected.selection() {
+            Some(selection) => FilterStat::Some(selection.clone()),
+            None => FilterStat::AllRetained,
+        };
+        let filtered = self
+            .cache
+            .with(|_| self.filter_stat_impl(selected.batch(), init_filtered, sampled))?;
+        match filtered {
+            FilterStat::AllRetained => Ok(selected),
+            FilterStat::AllFiltered => Ok(SelectedBatch::new_empty(selected.batch().schema())),
+            FilterStat::Some(selection) => {
+                SelectedBatch::try_new_with_selection(selected.batch().clone(), selection)
+            }
+        }
     }
 
     pub fn filter_project(&self, batch: &RecordBatch) -> Result<RecordBatch> {
-        self.cache.with(|_| self.filter_project_impl(batch))
+        let sampled = self.sampled_expr_metrics();
+        self.cache
+            .with(|_| self.filter_project_impl(batch, sampled))
     }
 
-    fn filter_impl(&self, batch: &RecordBatch) -> Result<RecordBatch> {
-        // filter
-        let mut current_filtered = FilterStat::AllRetained;
-        for (filter_expr, proj) in &self.transformed_pruned_filter_exprs {
+    fn sampled_expr_metrics(&self) -> Option<&ExprMetrics> {
+        self.expr_metrics
+            .as_ref()
+            .filter(|expr_metrics| expr_metrics.sample())
+    }
+
+    fn filter_impl(
+        &self,
+        batch: &RecordBatch,
+        sampled: Option<&ExprMetrics>,
+    ) -> Result<RecordBatch> {
+        let batch = match self.filter_stat_impl(batch, FilterStat::AllRetained, sampled)? {
+            FilterStat::AllFiltered => RecordBatch::new_empty(batch.schema()),
+            FilterStat::AllRetained => batch.clone(),
+            FilterStat::Some(selected) => filter_record_batch(batch, &selected)?,
+        };
+        Ok(batch)
+    }
+
+    fn filter_stat_impl(
+        &self,
+        batch: &RecordBatch,
+        init_filtered: FilterStat,
+        sampled: Option<&ExprMetrics>,
+    ) -> Result<FilterStat> {
+        let mut current_filtered = init_filtered;
+        let mut num_selected = match &current_filtered {
+            FilterStat::AllRetained => batch.num_rows(),
+            FilterStat::AllFiltered => 0,
+            FilterStat::Some(selected) => selected.true_count(),
+        };
+        let order = match &self.predicate_ordering {
+            Some(predicate_ordering) => predicate_ordering.current_order(),
+            None => (0..self.transformed_pruned_filter_exprs.len()).collect(),
+        };
+        for i in order {
+            let (filter_expr, proj) = &self.transformed_pruned_filter_exprs[i];
+
             // save previous selected, used for scattering
             let previous_selected = if let FilterStat::Some(array) = &current_filtered {
                 Some(array.clone())
@@ -97,9 +196,31 @@ impl CachedExprsEvaluator {
             };
 
             // execute current filtering
-            current_filtered = filter_one_pred(batch, filter_expr, proj, current_filtered)?;
+            let start_time =
+                (sampled.is_some() || self.predicate_ordering.is_some()).then(|| Instant::now());
+            current_filtered = filter_one_pred(
+                batch,
+                filter_expr,
+                proj,
+                self.filter_fingerprints[i].as_deref(),
+                current_filtered,
+            )?;
+            if let (Some(expr_metrics), Some(start_time)) = (sampled, start_time) {
+                expr_metrics.record(&expr_metrics.filter_timers[i], start_time);
+            }
+            if let (Some(predicate_ordering), Some(start_time)) =
+                (&self.predicate_ordering, start_time)
+            {
+                let num_input_rows = num_selected;
+                num_selected = match &current_filtered {
+                    FilterStat::AllRetained => batch.num_rows(),
+                    FilterStat::AllFiltered => 0,
+                    FilterStat::Some(selected) => selected.true_count(),
+                };
+                predicate_ordering.record(i, num_input_rows, num_selected, start_time);
+            }
             if let FilterStat::AllFiltered = &current_filtered {
-                return Ok(RecordBatch::new_empty(batch.schema()));
+                return Ok(FilterStat::AllFiltered);
             }
             if let FilterStat::Some(selected) = &current_filtered {
                 self.cache.update_all(|value| {
@@ -125,17 +246,16 @@ impl CachedExprsEvaluator {
                 })?;
             }
         }
-        let batch = match current_filtered {
-            FilterStat::AllFiltered => RecordBatch::new_empty(batch.schema()),
-            FilterStat::AllRetained => batch.clone(),
-            FilterStat::Some(selected) => filter_record_batch(batch, &selected)?,
-        };
-        Ok(batch)
+        Ok(current_filtered)
     }
 
-    fn filter_project_impl(&self, batch: &RecordBatch) -> Result<RecordBatch> {
+    fn filter_project_impl(
+        &self,
+        batch: &RecordBatch,
+        sampled: Option<&ExprMetrics>,
+    ) -> Result<RecordBatch> {
         // execute filters, cache are retained for later projection
-        let filtered_batch = self.filter_impl(batch)?;
+        let filtered_batch = self.filter_impl(batch, sampled)?;
         if filtered_batch.num_rows() == 0 {
             return Ok(RecordBatch::new_empty(self.output_schema.clone()));
         }
@@ -145,10 +265,15 @@ impl CachedExprsEvaluator {
             .transformed_projection_exprs
             .iter()
             .zip(self.output_schema.fields())
-            .map(|(expr, field)| {
+            .enumerate()
+            .map(|(i, (expr, field))| {
+                let start_time = sampled.map(|_| Instant::now());
                 let col = expr
                     .evaluate(&filtered_batch)?
                     .into_array(filtered_batch.num_rows())?;
+                if let (Some(expr_metrics), Some(start_time)) = (sampled, start_time) {
+                    expr_metrics.record(&expr_metrics.projection_timers[i], start_time);
+                }
                 if col.data_type() != field.data_type() {
                     return cast(col.as_ref(), field.data_type());
                 }
@@ -163,6 +288,209 @@ impl CachedExprsEvaluator {
     }
 }
 
+/// Adaptive evaluation order of conjunctive filter predicates. predicates are
+/// ranked by `cost_per_row / (1 - selectivity)` with running statistics, so
+/// that cheap and selective predicates are evaluated first and later ones are
+/// evaluated only on the remaining rows. predicates which may fail on some rows
+/// (like casting or division) are never moved, other predicates are only
+/// reordered between them.
+struct PredicateOrdering {
+    reorderable: Vec<bool>,
+    stats: Vec<PredicateStats>,
+    order: Mutex<Vec<usize>>,
+    num_evaluated: AtomicUsize,
+}
+
+#[derive(Default)]
+struct PredicateStats {
+    input_rows: AtomicUsize,
+    output_rows: AtomicUsize,
+    elapsed_nanos: AtomicUsize,
+}
+
+impl PredicateOrdering {
+    const REORDER_INTERVAL: usize = 8;
+
+    fn from_blaze_conf(filter_exprs: &[PhysicalExprRef]) -> Result<Option<Self>> {
+        if !is_jni_bridge_inited() || !conf::FILTER_ADAPTIVE_PREDICATE_ORDER_ENABLE.value()? {
+            return Ok(None);
+        }
+        Ok(Self::try_new(filter_exprs))
+    }
+
+    fn try_new(filter_exprs: &[PhysicalExprRef]) -> Option<Self> {
+        let reorderable = filter_exprs
+            .iter()
+            .map(|expr| is_reorderable_predicate(expr))
+            .collect::<Vec<_>>();
+        if reorderable.iter().filter(|&&r| r).count() < 2 {
+            return None;
+        }
+        Some(Self {
+            stats: (0..filter_exprs.len())
+                .map(|_| PredicateStats::default())
+                .collect(),
+            order: Mutex::new((0..filter_exprs.len()).collect()),
+            num_evaluated: AtomicUsize::new(0),
+            reorderable,
+        })
+    }
+
+    fn current_order(&self) -> Vec<usize> {
+        let num_evaluated = self.num_evaluated.fetch_add(1, Relaxed) + 1;
+        let mut order = self.order.lock();
+        if num_evaluated % Self::REORDER_INTERVAL == 0 {
+            let ranks = self
+                .stats
+                .iter()
+                .map(|stats| {
+                    let input_rows = stats.input_rows.load(Relaxed);
+                    let output_rows = stats.output_rows.load(Relaxed);
+                    let elapsed_nanos = stats.elapsed_nanos.load(Relaxed);
+                    predicate_rank(input_rows, output_rows, elapsed_nanos)
+                })
+                .collect::<Vec<_>>();
+            *order = adaptive_order(&ranks, &self.reorderable);
+        }
+        order.clone()
+    }
+
+    fn record(&self, i: usize, input_rows: usize, output_rows: usize, start_time: Instant) {
+        let stats = &self.stats[i];
+        stats.input_rows.fetch_add(input_rows, Relaxed);
+        stats.output_rows.fetch_add(output_rows, Relaxed);
+        stats
+            .elapsed_nanos
+            .fetch_add(start_time.elapsed().as_nanos() as usize, Relaxed);
+    }
+}
+
+/// rank of a predicate, lower ranked predicates are evaluated first. returns
+/// None if the predicate has not been evaluated on any rows.
+fn predicate_rank(input_rows: usize, output_rows: usize, elapsed_nanos: usize) -> Option<f64> {
+    if input_rows == 0 {
+        return None;
+    }
+    let selectivity = output_rows as f64 / input_rows as f64;
+    let cost_per_row = elapsed_nanos as f64 / input_rows as f64;
+    Some(cost_per_row / (1.0 - selectivity).max(1e-3))
+}
+
+/// sorts reorderable predicates by rank, predicates without rank are kept
+/// before ranked ones, and non-reorderable predicates are never moved
+fn adaptive_order(ranks: &[Option<f64>], reorderable: &[bool]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..ranks.len()).collect();
+    let mut start = 0;
+    while start < order.len() {
+        if !reorderable[start] {
+            start += 1;
+            continue;
+        }
+        let end = (start..order.len())
+            .find(|&i| !reorderable[i])
+            .unwrap_or(order.len());
+        order[start..end].sort_by(|&a, &b| {
+            let rank_a = ranks[a].unwrap_or(f64::NEG_INFINITY);
+            let rank_b = ranks[b].unwrap_or(f64::NEG_INFINITY);
+            rank_a.total_cmp(&rank_b)
+        });
+        start = end;
+    }
+    order
+}
+
+/// returns true if the predicate cannot fail on any rows, so that evaluating
+/// it before or after other predicates does not change the result
+pub(crate) fn is_reorderable_predicate(expr: &PhysicalExprRef) -> bool {
+    let any = expr.as_any();
+    let is_safe_node = if let Some(binary) = any.downcast_ref::<BinaryExpr>() {
+        matches!(
+            binary.op(),
+            Operator::Eq
+                | Operator::NotEq
+                | Operator::Lt
+                | Operator::LtEq
+                | Operator::Gt
+                | Operator::GtEq
+                | Operator::And
+                | Operator::Or
+                | Operator::IsDistinctFrom
+                | Operator::IsNotDistinctFrom
+        )
+    } else {
+        any.is::<Column>()
+            || any.is::<Literal>()
+            || any.is::<IsNullExpr>()
+            || any.is::<IsNotNullExpr>()
+            || any.is::<NotExpr>()
+            || any.is::<InListExpr>()
+            || any.is::<LikeExpr>()
+            || any.is::<SCAndExpr>()
+            || any.is::<SCOrExpr>()
+    };
+    is_safe_node
+        && expr
+            .children()
+            .iter()
+            .all(|&child| is_reorderable_predicate(child))
+}
+
+/// Sampled evaluation timers of each filter/projection expression. only one of
+/// every `sample_interval` evaluated batches is measured, and the measured time
+/// is scaled up by the interval as an estimation of the total time. time of a
+/// common sub-expression is counted to the first expression evaluating it.
+struct ExprMetrics {
+    sample_interval: usize,
+    num_evaluated: AtomicUsize,
+    filter_timers: Vec<Time>,
+    projection_timers: Vec<Time>,
+}
+
+impl ExprMetrics {
+    fn from_blaze_conf(
+        exec_ctx: &ExecutionContext,
+        num_filters: usize,
+        num_projections: usize,
+    ) -> Result<Option<Self>> {
+        if !is_jni_bridge_inited() || !conf::EXPR_METRICS_ENABLE.value()? {
+            return Ok(None);
+        }
+        let sample_interval = conf::EXPR_METRICS_SAMPLE_INTERVAL.value()?.max(1) as usize;
+        Ok(Some(Self::new(
+            exec_ctx,
+            sample_interval,
+            num_filters,
+            num_projections,
+        )))
+    }
+
+    fn new(
+        exec_ctx: &ExecutionContext,
+        sample_interval: usize,
+        num_filters: usize,
+        num_projections: usize,
+    ) -> Self {
+        Self {
+            sample_interval,
+            num_evaluated: AtomicUsize::new(0),
+            filter_timers: (0..num_filters)
+                .map(|i| exec_ctx.register_timer_metric(&format!("filter_expr_time_{i}")))
+                .collect(),
+            projection_timers: (0..num_projections)
+                .map(|i| exec_ctx.register_timer_metric(&format!("project_expr_time_{i}")))
+                .collect(),
+        }
+    }
+
+    fn sample(&self) -> bool {
+        self.num_evaluated.fetch_add(1, Relaxed) % self.sample_interval == 0
+    }
+
+    fn record(&self, timer: &Time, start_time: Instant) {
+        timer.add_duration(start_time.elapsed() * self.sample_interval as u32);
+    }
+}
+
 fn transform_to_cached_exprs(exprs: &[PhysicalExprRef]) -> Result<(Vec<PhysicalExprRef>, Cache)> {
     // count all children exprs
     fn count(expr: &PhysicalExprRef, expr_counts: &mut HashMap<ExprKey, usize>) {
@@ -218,6 +546,12 @@ fn transform_to_cached_exprs(exprs: &[PhysicalExprRef]) -> Result<(Vec<PhysicalE
                     }
                 }
             }
+        } else if let Some(hof) = expr.as_any().downcast_ref::<HigherOrderFunctionExpr>() {
+            // lambda bodies are evaluated on flattened elements - only arguments can be
+            // cached
+            expr.children()[..hof.num_args()].iter().for_each(|child| {
+                collect_dups(child, current_count, expr_counts, dups);
+            });
         } else {
             expr.children().iter().for_each(|child| {
                 collect_dups(child, current_count, expr_counts, dups);
@@ -275,6 +609,18 @@ fn transform_to_cached_exprs(exprs: &[PhysicalExprRef]) -> Result<(Vec<PhysicalE
                 }
             }
             expr.clone().with_new_children(children)?
+        } else if let Some(hof) = expr.as_any().downcast_ref::<HigherOrderFunctionExpr>() {
+            // lambda bodies are evaluated on flattened elements - only arguments can be
+            // cached
+            let mut children = expr
+                .children()
+                .iter()
+                .map(|&child| child.clone())
+                .collect::<Vec<_>>();
+            for child in &mut children[..hof.num_args()] {
+                *child = transform(child.clone(), cached_expr_ids, cache)?;
+            }
+            expr.clone().with_new_children(children)?
         } else {
             expr.clone().with_new_children(
                 expr.children()
@@ -473,11 +819,35 @@ fn prune_expr_cols(expr: &PhysicalExprRef) -> (PhysicalExprRef, Vec<usize>) {
     (transformed, mapped_cols)
 }
 
+/// fingerprint of a filter predicate, used as the key of predicate cache.
+/// only predicates composed of deterministic builtin exprs are cacheable
+fn predicate_fingerprint(expr: &PhysicalExprRef) -> Option<String> {
+    fn is_deterministic(expr: &PhysicalExprRef) -> bool {
+        let any = expr.as_any();
+        let is_deterministic_builtin = any.is::<Column>()
+            || any.is::<Literal>()
+            || any.is::<BinaryExpr>()
+            || any.is::<InListExpr>()
+            || any.is::<IsNullExpr>()
+            || any.is::<IsNotNullExpr>()
+            || any.is::<NotExpr>()
+            || any.is::<CastExpr>()
+            || any.is::<LikeExpr>()
+            || any.is::<SCAndExpr>()
+            || any.is::<SCOrExpr>()
+            || any.is::<CaseExpr>()
+            || any.is::<CachedExpr>();
+        is_deterministic_builtin && expr.children().into_iter().all(is_deterministic)
+    }
+    is_deterministic(expr).then(|| format!("{expr:?}"))
+}
+
 /// Execute one filter predicate expr on a record batch with existed FilterStat
 fn filter_one_pred(
     batch: &RecordBatch,
     pruned_pred_expr: &PhysicalExprRef,
     pruned_projection: &[usize],
+    fingerprint: Option<&str>,
     current_filtered: FilterStat,
 ) -> Result<FilterStat> {
     let current_selected: Option<BooleanArray> = match &current_filtered {
@@ -487,6 +857,20 @@ fn filter_one_pred(
     };
 
     let pruned_batch = batch.project(pruned_projection)?;
+
+    // reuse the mask evaluated by other operators on the same batch
+    let predicate_cache = fingerprint.and_then(|fp| Some((PredicateCache::current()?, fp)));
+    if let Some((predicate_cache, fingerprint)) = &predicate_cache {
+        let cached =
+            predicate_cache.get(fingerprint, pruned_batch.columns(), pruned_batch.num_rows());
+        if let Some(mask) = cached {
+            return Ok(match &current_selected {
+                Some(selected) => FilterStat::Some(and(&mask, selected)?),
+                None => FilterStat::Some(mask),
+            });
+        }
+    }
+
     let pred_ret = match &current_selected {
         Some(selected) => pruned_pred_expr.evaluate_selection(&pruned_batch, selected)?,
         None => pruned_pred_expr.evaluate(&pruned_batch)?,
@@ -500,7 +884,46 @@ fn filter_one_pred(
             if new_selected.null_count() > 0 {
                 new_selected = prep_null_mask_filter(&new_selected);
             }
+
+            // only masks evaluated on all rows are cached
+            if let (Some((predicate_cache, fingerprint)), None) =
+                (&predicate_cache, &current_selected)
+            {
+                predicate_cache.insert(
+                    fingerprint,
+                    pruned_batch.columns(),
+                    pruned_batch.num_rows(),
+                    new_selected.clone(),
+                );
+            }
             Ok(FilterStat::Some(new_selected))
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::common::cached_exprs_evaluator::{adaptive_order, predicate_rank};
+
+    #[test]
+    fn test_adaptive_order() {
+        // cheap selective predicates go first, unevaluated ones are kept first
+        let ranks = vec![
+            predicate_rank(100, 90, 1000), // rank = 100
+            predicate_rank(100, 10, 450),  // rank = 5
+            predicate_rank(0, 0, 0),       // no rank
+            predicate_rank(100, 50, 500),  // rank = 10
+            predicate_rank(100, 50, 100),  // rank = 2
+        ];
+        assert_eq!(
+            adaptive_order(&ranks, &[true, true, true, true, true]),
+            vec![2, 4, 1, 3, 0]
+        );
+
+        // non-reorderable predicates are never moved
+        assert_eq!(
+            adaptive_order(&ranks, &[true, true, false, true, true]),
+            vec![1, 0, 2, 4, 3]
+        );
+    }
+}
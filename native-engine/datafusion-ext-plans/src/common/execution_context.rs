@@ -16,34 +16,47 @@ use std::{
     future::Future,
     panic::AssertUnwindSafe,
     pin::Pin,
-    sync::{Arc, Weak},
+    sync::{
+        atomic::{AtomicUsize, Ordering::SeqCst},
+        Arc, Weak,
+    },
     task::{ready, Context, Poll},
     time::Instant,
 };
 
 use arrow::{array::RecordBatch, datatypes::SchemaRef};
-use blaze_jni_bridge::{conf, conf::BooleanConf, is_jni_bridge_inited, is_task_running};
+use blaze_jni_bridge::{
+    conf,
+    conf::{BooleanConf, LongConf},
+    is_jni_bridge_inited, is_task_running,
+};
 use datafusion::{
     common::Result,
     execution::{RecordBatchStream, SendableRecordBatchStream, TaskContext},
     physical_plan::{
-        metrics::{BaselineMetrics, Count, ExecutionPlanMetricsSet, MetricBuilder, Time},
+        metrics::{BaselineMetrics, Count, ExecutionPlanMetricsSet, Gauge, MetricBuilder, Time},
         stream::{RecordBatchReceiverStream, RecordBatchStreamAdapter},
         ExecutionPlan,
     },
 };
 use datafusion_ext_commons::{
     arrow::{array_size::ArraySize, coalesce::coalesce_batches_unchecked},
-    batch_size, df_execution_err, suggested_output_batch_mem_size,
+    df_execution_err,
+    spark_task_context::SparkTaskContext,
 };
 use futures::{Stream, StreamExt};
 use futures_util::FutureExt;
 use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
 use tokio::sync::mpsc::Sender;
+use tracing::{info_span, Instrument, Span};
 
 use crate::{
-    common::{column_pruning::ExecuteWithColumnPruning, timer_helper::TimerHelper},
+    common::{
+        column_pruning::ExecuteWithColumnPruning,
+        replay_log::{ReplayEvent, ReplayLog},
+        timer_helper::TimerHelper,
+    },
     memmgr::metrics::SpillMetrics,
 };
 
@@ -55,6 +68,8 @@ pub struct ExecutionContext {
     baseline_metrics: BaselineMetrics,
     spill_metrics: OnceCell<SpillMetrics>,
     input_stat_metrics: OnceCell<Option<InputBatchStatistics>>,
+    replay_log: Option<(Arc<ReplayLog>, usize)>,
+    num_replay_inputs: AtomicUsize,
 }
 
 impl ExecutionContext {
@@ -72,6 +87,11 @@ impl ExecutionContext {
             metrics: metrics.clone(),
             spill_metrics: OnceCell::new(),
             input_stat_metrics: OnceCell::new(),
+            replay_log: ReplayLog::current().map(|replay_log| {
+                let op_id = replay_log.next_op_id();
+                (replay_log, op_id)
+            }),
+            num_replay_inputs: AtomicUsize::new(0),
         })
     }
 
@@ -110,6 +130,31 @@ impl ExecutionContext {
             .counter(name.to_owned(), self.partition_id)
     }
 
+    pub fn register_gauge_metric(&self, name: &str) -> Gauge {
+        MetricBuilder::new(self.execution_plan_metrics()).gauge(name.to_owned(), self.partition_id)
+    }
+
+    /// creates a tracing span of an operator, spans of inputs executed inside
+    /// it are created as its children.
+    pub fn operator_span(&self, operator: &str) -> Span {
+        let stage_id = SparkTaskContext::current().map(|ctx| ctx.stage_id());
+        info_span!(
+            "native_operator",
+            operator,
+            stage_id,
+            partition_id = self.partition_id,
+        )
+    }
+
+    /// writes an event to the replay log if replay logging is enabled
+    pub fn log_replay_event(&self, event: impl FnOnce() -> ReplayEvent) {
+        if let Some((replay_log, op_id)) = &self.replay_log {
+            replay_log.log(*op_id, &event());
+        }
+    }
+
+    /// coalesces small batches, the target number of rows is adapted to the
+    /// observed average row size, see [`CoalesceTargets`]
     pub fn coalesce_with_default_batch_size(
         self: &Arc<Self>,
         input: SendableRecordBatchStream,
@@ -119,26 +164,13 @@ impl ExecutionContext {
             staging_batches: Vec<RecordBatch>,
             staging_rows: usize,
             staging_batches_mem_size: usize,
-            batch_size: usize,
+            batch_size: AdaptiveBatchSize,
             elapsed_compute: Time,
+            input_batches: Count,
+            output_batches: Count,
         }
 
         impl CoalesceStream {
-            pub fn new(
-                input: SendableRecordBatchStream,
-                batch_size: usize,
-                elapsed_compute: Time,
-            ) -> Self {
-                Self {
-                    input,
-                    staging_batches: vec![],
-                    staging_rows: 0,
-                    staging_batches_mem_size: 0,
-                    batch_size,
-                    elapsed_compute,
-                }
-            }
-
             fn coalesce(&mut self) -> Result<RecordBatch> {
                 // better concat_batches() implementation that releases old batch columns asap.
                 let schema = self.input.schema();
@@ -146,15 +178,17 @@ impl ExecutionContext {
                 self.staging_batches.clear();
                 self.staging_rows = 0;
                 self.staging_batches_mem_size = 0;
+                self.output_batches.add(1);
                 Ok(coalesced_batch)
             }
 
             fn should_flush(&self) -> bool {
-                let size_limit = suggested_output_batch_mem_size();
+                let target_rows = self.batch_size.target_rows();
+                let target_mem_size = self.batch_size.targets.mem_size;
                 let (batch_size_limit, mem_size_limit) = if self.staging_batches.len() > 1 {
-                    (self.batch_size, size_limit)
+                    (target_rows, target_mem_size)
                 } else {
-                    (self.batch_size / 2, size_limit / 2)
+                    (target_rows / 2, target_mem_size / 2)
                 };
                 self.staging_rows >= batch_size_limit
                     || self.staging_batches_mem_size > mem_size_limit
@@ -178,8 +212,11 @@ impl ExecutionContext {
                             let _timer = elapsed_time.timer();
                             let num_rows = batch.num_rows();
                             if num_rows > 0 {
-                                self.staging_rows += batch.num_rows();
-                                self.staging_batches_mem_size += batch.get_array_mem_size();
+                                let mem_size = batch.get_array_mem_size();
+                                self.input_batches.add(1);
+                                self.batch_size.observe(num_rows, mem_size);
+                                self.staging_rows += num_rows;
+                                self.staging_batches_mem_size += mem_size;
                                 self.staging_batches.push(batch);
                                 if self.should_flush() {
                                     let coalesced = self.coalesce()?;
@@ -206,11 +243,43 @@ impl ExecutionContext {
             staging_batches: vec![],
             staging_rows: 0,
             staging_batches_mem_size: 0,
-            batch_size: batch_size(),
+            batch_size: AdaptiveBatchSize::new(CoalesceTargets::get()),
             elapsed_compute: self.baseline_metrics().elapsed_compute().clone(),
+            input_batches: self.register_counter_metric("coalesce_input_batches"),
+            output_batches: self.register_counter_metric("coalesce_output_batches"),
         })
     }
 
+    /// splits output batches into smaller ones, so that each batch has at
+    /// most `max_rows` rows and `max_bytes` memory size (0 for unlimited).
+    /// used at the ffi boundary where batches are consumed by row-based jvm
+    /// operators.
+    pub fn split_output_batches(
+        self: &Arc<Self>,
+        input: SendableRecordBatchStream,
+        max_rows: usize,
+        max_bytes: usize,
+    ) -> SendableRecordBatchStream {
+        if max_rows == 0 && max_bytes == 0 {
+            return input;
+        }
+        let elapsed_compute = self.baseline_metrics().elapsed_compute().clone();
+        Box::pin(RecordBatchStreamAdapter::new(
+            input.schema(),
+            input.flat_map(move |batch_result| {
+                let _timer = elapsed_compute.timer();
+                let splitted: Vec<Result<RecordBatch>> = match batch_result {
+                    Ok(batch) => split_batch(batch, max_rows, max_bytes)
+                        .into_iter()
+                        .map(Ok)
+                        .collect(),
+                    Err(err) => vec![Err(err)],
+                };
+                futures::stream::iter(splitted)
+            }),
+        ))
+    }
+
     pub fn execute_with_input_stats(
         self: &Arc<Self>,
         input: &Arc<dyn ExecutionPlan>,
@@ -232,6 +301,7 @@ impl ExecutionContext {
         self: &Arc<Self>,
         input: &Arc<dyn ExecutionPlan>,
     ) -> Result<SendableRecordBatchStream> {
+        let _span = self.operator_span(input.name()).entered();
         input.execute(self.partition_id, self.task_ctx.clone())
     }
 
@@ -240,6 +310,7 @@ impl ExecutionContext {
         input: &Arc<dyn ExecutionPlan>,
         projection: &[usize],
     ) -> Result<SendableRecordBatchStream> {
+        let _span = self.operator_span(input.name()).entered();
         input.execute_projected(self.partition_id, self.task_ctx.clone(), projection)
     }
 
@@ -255,7 +326,7 @@ impl ExecutionContext {
             .expect("error creating input batch statistics")
         });
 
-        if let Some(input_batch_statistics) = input_batch_statistics.clone() {
+        let input = if let Some(input_batch_statistics) = input_batch_statistics.clone() {
             let stat_input: SendableRecordBatchStream = Box::pin(RecordBatchStreamAdapter::new(
                 input.schema(),
                 input.inspect(move |batch_result| {
@@ -264,7 +335,31 @@ impl ExecutionContext {
                     }
                 }),
             ));
-            return stat_input;
+            stat_input
+        } else {
+            input
+        };
+
+        if self.replay_log.is_some() {
+            let exec_ctx = self.clone();
+            let input_idx = self.num_replay_inputs.fetch_add(1, SeqCst);
+            let mut batch_idx = 0;
+            let replay_input: SendableRecordBatchStream = Box::pin(RecordBatchStreamAdapter::new(
+                input.schema(),
+                input.inspect(move |batch_result| {
+                    if let Ok(batch) = &batch_result {
+                        exec_ctx.log_replay_event(|| ReplayEvent::InputBatch {
+                            input: input_idx,
+                            batch: batch_idx,
+                            num_rows: batch.num_rows(),
+                            digest: ReplayEvent::batch_digest(batch)
+                                .expect("error computing batch digest"),
+                        });
+                        batch_idx += 1;
+                    }
+                }),
+            ));
+            return replay_input;
         }
         input
     }
@@ -279,9 +374,12 @@ impl ExecutionContext {
         let wrapped_sender =
             WrappedRecordBatchSender::new(self.clone(), stream_builder.tx().clone());
 
+        // time spent in the output pipeline is traced under the operator's span
+        let span = info_span!("output_with_sender", desc);
+
         stream_builder.spawn(async move {
             let result = AssertUnwindSafe(async move {
-                if let Err(err) = output(wrapped_sender).await {
+                if let Err(err) = output(wrapped_sender).instrument(span).await {
                     panic!("output_with_sender[{desc}]: output() returns error: {err}");
                 }
             })
@@ -358,6 +456,7 @@ pub struct WrappedRecordBatchSender {
     exec_ctx: Arc<ExecutionContext>,
     sender: Sender<Result<RecordBatch>>,
     exclude_time: OnceCell<Time>,
+    max_batch_bytes: usize,
 }
 
 impl WrappedRecordBatchSender {
@@ -366,12 +465,17 @@ impl WrappedRecordBatchSender {
             exec_ctx,
             sender,
             exclude_time: OnceCell::new(),
+            max_batch_bytes: output_batch_max_bytes(),
         });
         let mut working_senders = working_senders().lock();
         working_senders.push(Arc::downgrade(&wrapped));
         wrapped
     }
 
+    pub fn exec_ctx(&self) -> &Arc<ExecutionContext> {
+        &self.exec_ctx
+    }
+
     pub fn exclude_time(&self, exclude_time: &Time) {
         assert!(
             self.exclude_time.get().is_none(),
@@ -383,10 +487,18 @@ impl WrappedRecordBatchSender {
     pub async fn send(&self, batch: RecordBatch) {
         let exclude_time = self.exclude_time.get().cloned();
         let send_time = exclude_time.as_ref().map(|_| Instant::now());
-        self.sender
-            .send(Ok(batch))
-            .await
-            .unwrap_or_else(|err| panic!("output_with_sender: send error: {err}"));
+        if SparkTaskContext::is_current_cancelled() {
+            panic!("output_with_sender: task cancelled");
+        }
+
+        // split oversized batches (typically with very wide string columns) to
+        // avoid OOM in downstream operators
+        for batch in split_batch(batch, 0, self.max_batch_bytes) {
+            self.sender
+                .send(Ok(batch))
+                .await
+                .unwrap_or_else(|err| panic!("output_with_sender: send error: {err}"));
+        }
 
         send_time.inspect(|send_time| {
             exclude_time
@@ -397,6 +509,138 @@ impl WrappedRecordBatchSender {
     }
 }
 
+/// Target memory size and bounds of number of rows of coalesced batches.
+#[derive(Clone, Copy, Debug)]
+pub struct CoalesceTargets {
+    pub mem_size: usize,
+    pub min_rows: usize,
+    pub max_rows: usize,
+}
+
+impl CoalesceTargets {
+    pub fn get() -> Self {
+        static COALESCE_TARGETS: OnceCell<CoalesceTargets> = OnceCell::new();
+        *COALESCE_TARGETS.get_or_init(|| {
+            let targets = if is_jni_bridge_inited() {
+                Self::from_blaze_conf().unwrap_or_else(|err| {
+                    log::warn!("error reading coalesce confs, using defaults: {err}");
+                    Self::default()
+                })
+            } else {
+                Self::default() // for testing
+            };
+            Self {
+                mem_size: targets.mem_size.max(1),
+                min_rows: targets.min_rows.max(1),
+                max_rows: targets.max_rows.max(targets.min_rows).max(1),
+            }
+        })
+    }
+
+    fn from_blaze_conf() -> Result<Self> {
+        Ok(Self {
+            mem_size: conf::COALESCE_TARGET_BATCH_BYTES.value()?.max(0) as usize,
+            min_rows: conf::COALESCE_MIN_BATCH_ROWS.value()?.max(0) as usize,
+            max_rows: conf::COALESCE_MAX_BATCH_ROWS.value()?.max(0) as usize,
+        })
+    }
+}
+
+impl Default for CoalesceTargets {
+    fn default() -> Self {
+        Self {
+            mem_size: 16777216,
+            min_rows: 128,
+            max_rows: 65536,
+        }
+    }
+}
+
+/// Number of rows of coalesced batches, computed from the target memory size
+/// and the average row size observed so far in the stream, so that wide rows
+/// are not coalesced into huge batches, and narrow rows are not kept in tiny
+/// batches.
+struct AdaptiveBatchSize {
+    targets: CoalesceTargets,
+    observed_rows: usize,
+    observed_mem_size: usize,
+}
+
+impl AdaptiveBatchSize {
+    fn new(targets: CoalesceTargets) -> Self {
+        Self {
+            targets,
+            observed_rows: 0,
+            observed_mem_size: 0,
+        }
+    }
+
+    fn observe(&mut self, num_rows: usize, mem_size: usize) {
+        self.observed_rows += num_rows;
+        self.observed_mem_size += mem_size;
+    }
+
+    fn target_rows(&self) -> usize {
+        if self.observed_rows == 0 {
+            return self.targets.max_rows;
+        }
+        let avg_row_size = (self.observed_mem_size / self.observed_rows).max(1);
+        (self.targets.mem_size / avg_row_size).clamp(self.targets.min_rows, self.targets.max_rows)
+    }
+}
+
+fn output_batch_max_bytes() -> usize {
+    static OUTPUT_BATCH_MAX_BYTES: OnceCell<usize> = OnceCell::new();
+    *OUTPUT_BATCH_MAX_BYTES.get_or_init(|| {
+        if is_jni_bridge_inited() {
+            conf::OUTPUT_BATCH_MAX_BYTES
+                .value()
+                .map(|max_bytes| max_bytes.max(0) as usize)
+                .unwrap_or(0)
+        } else {
+            0 // for testing
+        }
+    })
+}
+
+fn split_batch(batch: RecordBatch, max_rows: usize, max_bytes: usize) -> Vec<RecordBatch> {
+    let num_rows = batch.num_rows();
+    if num_rows <= 1 {
+        return vec![batch];
+    }
+    let mut rows_per_batch = if max_rows > 0 { max_rows } else { num_rows };
+    if max_bytes > 0 {
+        // estimate rows by average row size
+        let mem_size = get_batch_slice_mem_size(&batch);
+        if mem_size > max_bytes {
+            rows_per_batch = rows_per_batch.min(num_rows * max_bytes / mem_size);
+        }
+    }
+    let rows_per_batch = rows_per_batch.max(1);
+    if num_rows <= rows_per_batch {
+        return vec![batch];
+    }
+    (0..num_rows)
+        .step_by(rows_per_batch)
+        .map(|offset| batch.slice(offset, rows_per_batch.min(num_rows - offset)))
+        .collect()
+}
+
+/// returns memory size of the sliced ranges of the batch. unlike
+/// get_array_mem_size(), buffers shared by slices are not counted as a whole.
+fn get_batch_slice_mem_size(batch: &RecordBatch) -> usize {
+    batch
+        .columns()
+        .iter()
+        .map(|array| {
+            array
+                .to_data()
+                .get_slice_memory_size()
+                .unwrap_or_else(|_| array.as_ref().get_array_mem_size())
+        })
+        .sum()
+}
+
 pub fn cancel_all_tasks(task_ctx: &Arc<TaskContext>) {
     let mut working_senders = working_senders().lock();
     *working_senders = std::mem::take(&mut *working_senders)
@@ -414,3 +658,93 @@ pub fn cancel_all_tasks(task_ctx: &Arc<TaskContext>) {
         })
         .collect();
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::array::{Int32Array, RecordBatch, StringArray};
+    use datafusion::common::Result;
+
+    use crate::common::execution_context::{split_batch, AdaptiveBatchSize, CoalesceTargets};
+
+    #[test]
+    fn test_split_batch() -> Result<()> {
+        let array = Arc::new(Int32Array::from_iter_values(0..10));
+        let batch = RecordBatch::try_from_iter([("a", array as _)])?;
+        let num_rows = |batches: &[RecordBatch]| {
+            batches
+                .iter()
+                .map(|batch| batch.num_rows())
+                .collect::<Vec<_>>()
+        };
+
+        // unlimited
+        assert_eq!(num_rows(&split_batch(batch.clone(), 0, 0)), vec![10]);
+
+        // limited by rows
+        assert_eq!(num_rows(&split_batch(batch.clone(), 4, 0)), vec![4, 4, 2]);
+
+        // limited by bytes, each row takes 4 bytes
+        assert_eq!(num_rows(&split_batch(batch.clone(), 0, 20)), vec![5, 5]);
+        assert_eq!(
+            num_rows(&split_batch(batch.clone(), 3, 20)),
+            vec![3, 3, 3, 1]
+        );
+        assert_eq!(num_rows(&split_batch(batch, 0, 1)), vec![1; 10]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_batch_wide_string() -> Result<()> {
+        let array = Arc::new(StringArray::from_iter_values(
+            (0..10).map(|i| i.to_string().repeat(1000)),
+        ));
+        let batch = RecordBatch::try_from_iter([("s", array as _)])?;
+        let num_rows = |batches: &[RecordBatch]| {
+            batches
+                .iter()
+                .map(|batch| batch.num_rows())
+                .collect::<Vec<_>>()
+        };
+
+        // each row takes 1000 bytes of values and 4 bytes of offsets
+        assert_eq!(
+            num_rows(&split_batch(batch.clone(), 0, 4100)),
+            vec![4, 4, 2]
+        );
+
+        // slices are sized by their own rows, not the shared buffers
+        let sliced = batch.slice(2, 3);
+        assert_eq!(num_rows(&split_batch(sliced.clone(), 0, 4100)), vec![3]);
+        assert_eq!(num_rows(&split_batch(sliced, 0, 2100)), vec![2, 1]);
+
+        // single rows are never split
+        let single = batch.slice(0, 1);
+        assert_eq!(num_rows(&split_batch(single, 0, 100)), vec![1]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_adaptive_batch_size() {
+        let targets = CoalesceTargets {
+            mem_size: 1000,
+            min_rows: 4,
+            max_rows: 100,
+        };
+        let mut batch_size = AdaptiveBatchSize::new(targets);
+        assert_eq!(batch_size.target_rows(), 100);
+
+        // narrow rows are bounded by max_rows
+        batch_size.observe(10, 20);
+        assert_eq!(batch_size.target_rows(), 100);
+
+        // 30 bytes per row in average
+        batch_size.observe(10, 580);
+        assert_eq!(batch_size.target_rows(), 33);
+
+        // wide rows are bounded by min_rows
+        batch_size.observe(10, 100000);
+        assert_eq!(batch_size.target_rows(), 4);
+    }
+}
@@ -0,0 +1,150 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! EXPLAIN ANALYZE-style dump of an executed native plan.
+//!
+//! each node is rendered with its aggregated metrics (rows, batches, spills,
+//! time, etc.), so that slow stages can be inspected without the spark ui.
+
+use std::{fmt::Write, sync::Arc};
+
+use datafusion::{
+    common::Result,
+    physical_plan::{displayable, metrics::MetricsSet, ExecutionPlan},
+};
+use datafusion_ext_commons::df_execution_err;
+use serde_json::{json, Map, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExplainFormat {
+    Text,
+    Json,
+}
+
+impl ExplainFormat {
+    pub fn parse(format: &str) -> Result<Self> {
+        match format.to_ascii_lowercase().as_str() {
+            "text" => Ok(ExplainFormat::Text),
+            "json" => Ok(ExplainFormat::Json),
+            _ => df_execution_err!("unsupported explain analyze format: {format}"),
+        }
+    }
+}
+
+/// renders the plan tree with metrics of each node.
+pub fn explain_analyze(plan: &Arc<dyn ExecutionPlan>, format: ExplainFormat) -> String {
+    match format {
+        ExplainFormat::Text => {
+            let mut output = String::new();
+            render_text(plan, 0, &mut output);
+            output
+        }
+        ExplainFormat::Json => render_json(plan).to_string(),
+    }
+}
+
+fn render_text(plan: &Arc<dyn ExecutionPlan>, depth: usize, output: &mut String) {
+    let metrics = node_metrics(plan)
+        .iter()
+        .map(|m| format!("{}={}", m.value().name(), m.value()))
+        .collect::<Vec<_>>();
+    let _ = writeln!(
+        output,
+        "{:indent$}{}, metrics=[{}]",
+        "",
+        node_description(plan),
+        metrics.join(", "),
+        indent = depth * 2,
+    );
+    for child in plan.children() {
+        render_text(child, depth + 1, output);
+    }
+}
+
+fn render_json(plan: &Arc<dyn ExecutionPlan>) -> Value {
+    // raw values are used, times are in nanoseconds and sizes are in bytes
+    let metrics = node_metrics(plan)
+        .iter()
+        .map(|m| (m.value().name().to_string(), json!(m.value().as_usize())))
+        .collect::<Map<_, _>>();
+    let children = plan
+        .children()
+        .into_iter()
+        .map(render_json)
+        .collect::<Vec<_>>();
+    json!({
+        "name": plan.name(),
+        "description": node_description(plan),
+        "metrics": metrics,
+        "children": children,
+    })
+}
+
+fn node_description(plan: &Arc<dyn ExecutionPlan>) -> String {
+    displayable(plan.as_ref())
+        .one_line()
+        .to_string()
+        .trim_end()
+        .to_string()
+}
+
+fn node_metrics(plan: &Arc<dyn ExecutionPlan>) -> MetricsSet {
+    plan.metrics()
+        .unwrap_or_default()
+        .aggregate_by_name()
+        .sorted_for_display()
+        .timestamps_removed()
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::{
+        common::Result,
+        physical_plan::{empty::EmptyExec, ExecutionPlan},
+    };
+    use serde_json::Value;
+
+    use crate::{
+        common::explain_analyze::{explain_analyze, ExplainFormat},
+        limit_exec::LimitExec,
+    };
+
+    #[test]
+    fn test_explain_analyze() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+        let plan: Arc<dyn ExecutionPlan> =
+            Arc::new(LimitExec::new(Arc::new(EmptyExec::new(schema)), 10, 0));
+
+        let text = explain_analyze(&plan, ExplainFormat::Text);
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("LimitExec"));
+        assert!(lines[1].starts_with("  EmptyExec"));
+
+        let json: Value = serde_json::from_str(&explain_analyze(&plan, ExplainFormat::Json))
+            .expect("invalid json");
+        assert_eq!(json["name"], "LimitExec");
+        assert_eq!(json["children"][0]["name"], "EmptyExec");
+        assert!(json["children"][0]["children"]
+            .as_array()
+            .is_some_and(|children| children.is_empty()));
+
+        assert_eq!(ExplainFormat::parse("JSON")?, ExplainFormat::Json);
+        assert!(ExplainFormat::parse("yaml").is_err());
+        Ok(())
+    }
+}
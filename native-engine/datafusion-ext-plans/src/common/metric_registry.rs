@@ -0,0 +1,205 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Registry of native metrics shown in spark ui.
+//!
+//! metric values are published to spark by name, and spark only keeps values
+//! of the SQLMetrics created when planning. the spark side fetches this
+//! registry to create SQLMetrics of all native metrics, so a metric registered
+//! by exec nodes is shown in spark ui after being added here.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    Count,
+    Size,
+    NanoTiming,
+}
+
+impl MetricKind {
+    /// name of the kind, consistent with the spark side
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MetricKind::Count => "count",
+            MetricKind::Size => "size",
+            MetricKind::NanoTiming => "nanoTiming",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MetricDef {
+    pub name: &'static str,
+    pub kind: MetricKind,
+    pub display_name: &'static str,
+}
+
+const fn def(name: &'static str, kind: MetricKind, display_name: &'static str) -> MetricDef {
+    MetricDef {
+        name,
+        kind,
+        display_name,
+    }
+}
+
+pub const NATIVE_METRICS: &[MetricDef] = &[
+    def("output_rows", MetricKind::Count, "Native.output_rows"),
+    def("output_batches", MetricKind::Count, "Native.output_batches"),
+    def(
+        "elapsed_compute",
+        MetricKind::NanoTiming,
+        "Native.elapsed_compute",
+    ),
+    // input batch statistics, only published if enabled
+    def(
+        "input_batch_count",
+        MetricKind::Count,
+        "Native.input_batches",
+    ),
+    def("input_row_count", MetricKind::Count, "Native.input_rows"),
+    def(
+        "input_batch_mem_size",
+        MetricKind::Size,
+        "Native.input_mem_bytes",
+    ),
+    // batches before and after coalescing
+    def(
+        "coalesce_input_batches",
+        MetricKind::Count,
+        "Native.coalesce_input_batches",
+    ),
+    def(
+        "coalesce_output_batches",
+        MetricKind::Count,
+        "Native.coalesce_output_batches",
+    ),
+    // spills
+    def(
+        "mem_spill_count",
+        MetricKind::Count,
+        "Native.mem_spill_count",
+    ),
+    def("mem_spill_size", MetricKind::Size, "Native.mem_spill_size"),
+    def(
+        "mem_spill_iotime",
+        MetricKind::NanoTiming,
+        "Native.mem_spill_iotime",
+    ),
+    def(
+        "disk_spill_size",
+        MetricKind::Size,
+        "Native.disk_spill_size",
+    ),
+    def(
+        "disk_spill_iotime",
+        MetricKind::NanoTiming,
+        "Native.disk_spill_iotime",
+    ),
+    def("spill_count", MetricKind::Count, "Native.spill_count"),
+    def("mem_used_peak", MetricKind::Size, "Native.mem_used_peak"),
+    // joins
+    def(
+        "build_hash_map_time",
+        MetricKind::NanoTiming,
+        "Native.build_hash_map_time",
+    ),
+    def(
+        "probed_side_hash_time",
+        MetricKind::NanoTiming,
+        "Native.probed_side_hash_time",
+    ),
+    def(
+        "probed_side_search_time",
+        MetricKind::NanoTiming,
+        "Native.probed_side_search_time",
+    ),
+    def(
+        "probed_side_compare_time",
+        MetricKind::NanoTiming,
+        "Native.probed_side_compare_time",
+    ),
+    def(
+        "build_output_time",
+        MetricKind::NanoTiming,
+        "Native.build_output_time",
+    ),
+    def(
+        "hash_collisions",
+        MetricKind::Count,
+        "Native.hash_collisions",
+    ),
+    def(
+        "skewed_key_count",
+        MetricKind::Count,
+        "Native.skewed_key_count",
+    ),
+    def(
+        "skewed_key_max_rows",
+        MetricKind::Count,
+        "Native.skewed_key_max_rows",
+    ),
+    // filters
+    def(
+        "filter_input_rows",
+        MetricKind::Count,
+        "Native.filter_input_rows",
+    ),
+    def(
+        "runtime_filter_pruned_rows",
+        MetricKind::Count,
+        "Native.runtime_filter_pruned_rows",
+    ),
+    // others
+    def("sort_time", MetricKind::NanoTiming, "Native.sort_time"),
+    def(
+        "output_io_time",
+        MetricKind::NanoTiming,
+        "Native.output_io_time",
+    ),
+    def(
+        "shuffle_read_total_time",
+        MetricKind::NanoTiming,
+        "Native.shuffle_read_total_time",
+    ),
+    def("jni_call_count", MetricKind::Count, "Native.jni_call_count"),
+    def(
+        "jni_call_time",
+        MetricKind::NanoTiming,
+        "Native.jni_call_time",
+    ),
+];
+
+pub fn lookup_metric_def(name: &str) -> Option<&'static MetricDef> {
+    NATIVE_METRICS.iter().find(|def| def.name == name)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use crate::common::metric_registry::{lookup_metric_def, MetricKind, NATIVE_METRICS};
+
+    #[test]
+    fn test_native_metrics() {
+        let names = NATIVE_METRICS
+            .iter()
+            .map(|def| def.name)
+            .collect::<HashSet<_>>();
+        assert_eq!(names.len(), NATIVE_METRICS.len(), "duplicated metric names");
+
+        let def = lookup_metric_def("hash_collisions").expect("metric not registered");
+        assert_eq!(def.kind, MetricKind::Count);
+        assert_eq!(def.kind.as_str(), "count");
+        assert!(lookup_metric_def("unknown_metric").is_none());
+    }
+}
@@ -15,7 +15,17 @@
 pub mod cached_exprs_evaluator;
 pub mod column_pruning;
 pub mod execution_context;
+pub mod explain_analyze;
 pub mod ipc_compression;
+pub mod metric_registry;
+pub mod partitioning;
+pub mod predicate_cache;
+pub mod replay_log;
+pub mod resource_usage;
+pub mod runtime_filter;
+pub mod selection_vector;
+pub mod statistics;
+pub mod stream_exec;
 pub mod timer_helper;
 
 pub trait SliceAsRawBytes {
@@ -0,0 +1,171 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use arrow::datatypes::SchemaRef;
+use datafusion::{
+    physical_expr::{expressions::Column, PhysicalExprRef},
+    physical_plan::{ExecutionPlan, ExecutionPlanProperties, Partitioning},
+};
+
+/// Re-derives partitioning of an operator's output from its input
+/// partitioning. `map_column` maps an input column to the index of the same
+/// column in the output schema. hash partitioning is kept (with rebound
+/// columns) only if all referenced columns are still in the output, otherwise
+/// falls back to UnknownPartitioning.
+pub fn derive_partitioning(
+    input_partitioning: &Partitioning,
+    output_schema: &SchemaRef,
+    map_column: impl Fn(&Column) -> Option<usize>,
+) -> Partitioning {
+    match input_partitioning {
+        Partitioning::Hash(exprs, num_partitions) => {
+            let remap = |expr: &PhysicalExprRef| {
+                remap_columns(expr, &|col| {
+                    let idx = map_column(col)?;
+                    let field = output_schema.fields().get(idx)?;
+                    (field.name() == col.name()).then(|| Column::new(field.name(), idx))
+                })
+            };
+            match exprs.iter().map(remap).collect::<Option<Vec<_>>>() {
+                Some(exprs) => Partitioning::Hash(exprs, *num_partitions),
+                None => Partitioning::UnknownPartitioning(*num_partitions),
+            }
+        }
+        other => other.clone(),
+    }
+}
+
+/// re-derives partitioning of a projection, in which output columns are named
+/// by the given names
+pub fn derive_projected_partitioning(
+    input_partitioning: &Partitioning,
+    projection: &[(PhysicalExprRef, String)],
+) -> Partitioning {
+    let Partitioning::Hash(exprs, num_partitions) = input_partitioning else {
+        return input_partitioning.clone();
+    };
+    let remap = |expr: &PhysicalExprRef| {
+        remap_columns(expr, &|col| {
+            projection.iter().enumerate().find_map(|(idx, (e, name))| {
+                let projected_col = e.as_any().downcast_ref::<Column>()?;
+                (projected_col.index() == col.index()).then(|| Column::new(name, idx))
+            })
+        })
+    };
+    match exprs.iter().map(remap).collect::<Option<Vec<_>>>() {
+        Some(exprs) => Partitioning::Hash(exprs, *num_partitions),
+        None => Partitioning::UnknownPartitioning(*num_partitions),
+    }
+}
+
+/// re-derives partitioning of a join from its right side, whose columns are
+/// placed after left columns in the output, or are the only columns in the
+/// output of right semi/anti joins
+pub fn derive_join_partitioning(
+    left: &Arc<dyn ExecutionPlan>,
+    right: &Arc<dyn ExecutionPlan>,
+    output_schema: &SchemaRef,
+) -> Partitioning {
+    let num_left_cols = left.schema().fields().len();
+    let num_right_cols = right.schema().fields().len();
+    let right_offset = if output_schema.fields().len() >= num_left_cols + num_right_cols {
+        num_left_cols
+    } else {
+        0
+    };
+    derive_partitioning(right.output_partitioning(), output_schema, |col| {
+        Some(right_offset + col.index())
+    })
+}
+
+fn remap_columns(
+    expr: &PhysicalExprRef,
+    map_column: &dyn Fn(&Column) -> Option<Column>,
+) -> Option<PhysicalExprRef> {
+    if let Some(col) = expr.as_any().downcast_ref::<Column>() {
+        return Some(Arc::new(map_column(col)?));
+    }
+    let children = expr.children();
+    if children.is_empty() {
+        return Some(expr.clone());
+    }
+    let new_children = children
+        .into_iter()
+        .map(|child| remap_columns(child, map_column))
+        .collect::<Option<Vec<_>>>()?;
+    expr.clone().with_new_children(new_children).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::{
+        physical_expr::{expressions::Column, PhysicalExprRef},
+        physical_plan::Partitioning,
+    };
+
+    use crate::common::partitioning::{derive_partitioning, derive_projected_partitioning};
+
+    fn col(name: &str, idx: usize) -> PhysicalExprRef {
+        Arc::new(Column::new(name, idx))
+    }
+
+    #[test]
+    fn test_derive_projected_partitioning() {
+        let input_partitioning = Partitioning::Hash(vec![col("b", 1)], 10);
+
+        // b is kept as output column 0
+        let derived = derive_projected_partitioning(
+            &input_partitioning,
+            &[
+                (col("b", 1), "x".to_string()),
+                (col("a", 0), "a".to_string()),
+            ],
+        );
+        let Partitioning::Hash(exprs, 10) = &derived else {
+            panic!("unexpected partitioning: {derived:?}");
+        };
+        assert_eq!(exprs[0].to_string(), "x@0");
+
+        // b is pruned
+        let derived =
+            derive_projected_partitioning(&input_partitioning, &[(col("a", 0), "a".to_string())]);
+        assert!(matches!(derived, Partitioning::UnknownPartitioning(10)));
+    }
+
+    #[test]
+    fn test_derive_partitioning() {
+        // right side columns are placed after 2 left side columns
+        let output_schema = Arc::new(Schema::new(vec![
+            Field::new("l1", DataType::Int32, false),
+            Field::new("l2", DataType::Int32, false),
+            Field::new("r1", DataType::Int32, false),
+        ]));
+        let right_partitioning = Partitioning::Hash(vec![col("r1", 0)], 4);
+        let derived =
+            derive_partitioning(&right_partitioning, &output_schema, |c| Some(c.index() + 2));
+        let Partitioning::Hash(exprs, 4) = &derived else {
+            panic!("unexpected partitioning: {derived:?}");
+        };
+        assert_eq!(exprs[0].to_string(), "r1@2");
+
+        // mismatched column name
+        let derived = derive_partitioning(&right_partitioning, &output_schema, |c| Some(c.index()));
+        assert!(matches!(derived, Partitioning::UnknownPartitioning(4)));
+    }
+}
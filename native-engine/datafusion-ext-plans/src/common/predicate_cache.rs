@@ -0,0 +1,173 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicUsize, Ordering::Relaxed},
+        Arc,
+    },
+};
+
+use arrow::array::{ArrayRef, BooleanArray};
+use blaze_jni_bridge::conf::{self, IntConf};
+use datafusion::common::Result;
+use parking_lot::Mutex;
+
+thread_local! {
+    static THREAD_PREDICATE_CACHE: RefCell<Option<Arc<PredicateCache>>> = const { RefCell::new(None) };
+}
+
+/// Task-level cache of evaluated filter masks. when the same predicate appears
+/// in more than one operator of a stage (e.g. dynamic pruning filters pushed
+/// into several places), the mask computed by the first operator is reused by
+/// the others if they see the same input batch.
+///
+/// an entry is keyed by the predicate's fingerprint and the identity of its
+/// input arrays (arrays are compared by pointer and kept alive by the entry,
+/// so a key cannot be reused by another batch).
+pub struct PredicateCache {
+    capacity: usize,
+    entries: Mutex<VecDeque<PredicateCacheEntry>>,
+    num_hits: AtomicUsize,
+}
+
+struct PredicateCacheEntry {
+    fingerprint: String,
+    inputs: Vec<ArrayRef>,
+    num_rows: usize,
+    mask: BooleanArray,
+}
+
+impl PredicateCacheEntry {
+    fn matches(&self, fingerprint: &str, inputs: &[ArrayRef], num_rows: usize) -> bool {
+        self.num_rows == num_rows
+            && self.fingerprint == fingerprint
+            && self.inputs.len() == inputs.len()
+            && self
+                .inputs
+                .iter()
+                .zip(inputs)
+                .all(|(a, b)| Arc::ptr_eq(a, b))
+    }
+}
+
+impl PredicateCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::default(),
+            num_hits: AtomicUsize::new(0),
+        }
+    }
+
+    /// creates a predicate cache if spark.blaze.predicateCache.capacity > 0
+    pub fn try_new_from_blaze_conf() -> Result<Option<Arc<Self>>> {
+        let capacity = conf::PREDICATE_CACHE_CAPACITY.value()?;
+        if capacity <= 0 {
+            return Ok(None);
+        }
+        Ok(Some(Arc::new(Self::new(capacity as usize))))
+    }
+
+    /// sets the predicate cache of current thread, called when starting
+    /// threads of the task's runtime
+    pub fn set_current(predicate_cache: Option<Arc<Self>>) {
+        THREAD_PREDICATE_CACHE.with(|cur| *cur.borrow_mut() = predicate_cache);
+    }
+
+    pub fn current() -> Option<Arc<Self>> {
+        THREAD_PREDICATE_CACHE.with(|cur| cur.borrow().clone())
+    }
+
+    pub fn num_hits(&self) -> usize {
+        self.num_hits.load(Relaxed)
+    }
+
+    /// returns the cached mask of the predicate evaluated on the inputs
+    pub fn get(
+        &self,
+        fingerprint: &str,
+        inputs: &[ArrayRef],
+        num_rows: usize,
+    ) -> Option<BooleanArray> {
+        let entries = self.entries.lock();
+        let entry = entries
+            .iter()
+            .find(|entry| entry.matches(fingerprint, inputs, num_rows))?;
+        self.num_hits.fetch_add(1, Relaxed);
+        Some(entry.mask.clone())
+    }
+
+    /// caches the mask of the predicate evaluated on all rows of the inputs,
+    /// the eldest entry is evicted if capacity is exceeded
+    pub fn insert(
+        &self,
+        fingerprint: &str,
+        inputs: &[ArrayRef],
+        num_rows: usize,
+        mask: BooleanArray,
+    ) {
+        let mut entries = self.entries.lock();
+        if entries
+            .iter()
+            .any(|entry| entry.matches(fingerprint, inputs, num_rows))
+        {
+            return;
+        }
+        if entries.len() >= self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(PredicateCacheEntry {
+            fingerprint: fingerprint.to_string(),
+            inputs: inputs.to_vec(),
+            num_rows,
+            mask,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::array::{ArrayRef, BooleanArray, Int32Array};
+
+    use crate::common::predicate_cache::PredicateCache;
+
+    #[test]
+    fn test_predicate_cache() {
+        let cache = PredicateCache::new(2);
+        let a: ArrayRef = Arc::new(Int32Array::from(vec![1, 2, 3]));
+        let a_copied: ArrayRef = Arc::new(Int32Array::from(vec![1, 2, 3]));
+        let b: ArrayRef = Arc::new(Int32Array::from(vec![4, 5, 6]));
+        let mask = BooleanArray::from(vec![true, false, true]);
+
+        cache.insert("a > 1", &[a.clone()], 3, mask.clone());
+        assert_eq!(cache.get("a > 1", &[a.clone()], 3), Some(mask.clone()));
+
+        // different fingerprint or different input arrays
+        assert_eq!(cache.get("a > 2", &[a.clone()], 3), None);
+        assert_eq!(cache.get("a > 1", &[a_copied], 3), None);
+        assert_eq!(cache.num_hits(), 1);
+
+        // the eldest entry is evicted
+        cache.insert("b > 1", &[b.clone()], 3, mask.clone());
+        cache.insert("b > 2", &[b.clone()], 3, mask.clone());
+        assert_eq!(cache.get("a > 1", &[a], 3), None);
+        assert!(cache.get("b > 1", &[b.clone()], 3).is_some());
+        assert!(cache.get("b > 2", &[b], 3).is_some());
+    }
+}
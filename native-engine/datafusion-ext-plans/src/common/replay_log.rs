@@ -0,0 +1,314 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    cell::RefCell,
+    fmt::{Display, Formatter},
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering::SeqCst},
+        Arc,
+    },
+};
+
+use arrow::record_batch::RecordBatch;
+use blaze_jni_bridge::conf::{self, IntConf, StringConf};
+use datafusion::common::{DataFusionError, Result};
+use datafusion_ext_commons::{
+    df_execution_err, hash::xxhash::spark_compatible_xxhash64_hash, io::write_one_batch,
+};
+use parking_lot::Mutex;
+
+thread_local! {
+    static THREAD_REPLAY_LOG: RefCell<Option<Arc<ReplayLog>>> = const { RefCell::new(None) };
+}
+
+/// Replay log of a single task, enabled by spark.blaze.replayLog.partition.
+/// digests of all input batches and decisions made by operators (spill points,
+/// merge order) are appended to the log, together with the task definition, so
+/// that a non-deterministic wrong result can be replayed offline and bisected
+/// by diffing logs of different runs.
+///
+/// each line is formatted as `op=<op_id> <event>`, where op_id is the creation
+/// order of the operator's execution context in the task.
+pub struct ReplayLog {
+    path: PathBuf,
+    writer: Mutex<BufWriter<File>>,
+    num_ops: AtomicUsize,
+}
+
+impl ReplayLog {
+    pub fn try_new(path: &Path) -> Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            writer: Mutex::new(BufWriter::new(File::create(path)?)),
+            num_ops: AtomicUsize::new(0),
+        })
+    }
+
+    /// creates a replay log if replay logging is enabled for the partition
+    pub fn try_new_from_blaze_conf(
+        stage_id: usize,
+        partition_id: usize,
+    ) -> Result<Option<Arc<Self>>> {
+        let replay_partition = conf::REPLAY_LOG_PARTITION.value()?;
+        let replay_stage = conf::REPLAY_LOG_STAGE.value()?;
+        if replay_partition < 0 || replay_partition as usize != partition_id {
+            return Ok(None);
+        }
+        if replay_stage >= 0 && replay_stage as usize != stage_id {
+            return Ok(None);
+        }
+
+        let dir = PathBuf::from(conf::REPLAY_LOG_DIR.value()?);
+        std::fs::create_dir_all(&dir)?;
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default();
+        let path = dir.join(format!(
+            "replay-stage-{stage_id}-part-{partition_id}-{timestamp}.log"
+        ));
+        log::info!("replay logging enabled, writing to {}", path.display());
+        Ok(Some(Arc::new(Self::try_new(&path)?)))
+    }
+
+    /// sets the replay log of current thread, called when starting threads of
+    /// the task's runtime
+    pub fn set_current(replay_log: Option<Arc<Self>>) {
+        THREAD_REPLAY_LOG.with(|cur| *cur.borrow_mut() = replay_log);
+    }
+
+    pub fn current() -> Option<Arc<Self>> {
+        THREAD_REPLAY_LOG.with(|cur| cur.borrow().clone())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn next_op_id(&self) -> usize {
+        self.num_ops.fetch_add(1, SeqCst)
+    }
+
+    /// saves the raw task definition beside the log, which is used to
+    /// re-execute the task offline
+    pub fn save_task_definition(&self, raw_task_definition: &[u8]) -> Result<()> {
+        std::fs::write(self.path.with_extension("task"), raw_task_definition)?;
+        Ok(())
+    }
+
+    pub fn log(&self, op_id: usize, event: &ReplayEvent) {
+        let mut writer = self.writer.lock();
+        if let Err(err) = writeln!(writer, "op={op_id} {event}") {
+            log::warn!("error writing replay log: {err}");
+        }
+    }
+
+    pub fn flush(&self) -> Result<()> {
+        self.writer.lock().flush()?;
+        Ok(())
+    }
+
+    /// parses a line written by `log()`, returns (op_id, event)
+    pub fn parse_line(line: &str) -> Result<(usize, ReplayEvent)> {
+        let op_id = line
+            .split_once(' ')
+            .and_then(|(op, event)| Some((op.strip_prefix("op=")?.parse::<usize>().ok()?, event)));
+        let (op_id, event) = match op_id {
+            Some(parsed) => parsed,
+            None => return df_execution_err!("invalid replay log: {line}"),
+        };
+        Ok((op_id, event.parse()?))
+    }
+}
+
+impl Drop for ReplayLog {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayEvent {
+    /// a batch is read from the `input`-th input stream of the operator
+    InputBatch {
+        input: usize,
+        batch: usize,
+        num_rows: usize,
+        digest: u64,
+    },
+
+    /// in-memory data of the operator is spilled
+    Spill { mem_size: usize },
+
+    /// spills are merged in the order they are created, `output` is true if
+    /// the merged result is the final output
+    MergeSpills { num_spills: usize, output: bool },
+}
+
+impl ReplayEvent {
+    /// computes digest of batch content, which does not depend on how the
+    /// arrays are laid out in memory
+    pub fn batch_digest(batch: &RecordBatch) -> Result<u64> {
+        let mut buf = vec![];
+        write_one_batch(batch.num_rows(), batch.columns(), &mut buf)?;
+        Ok(spark_compatible_xxhash64_hash(&buf, 42) as u64)
+    }
+}
+
+impl Display for ReplayEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayEvent::InputBatch {
+                input,
+                batch,
+                num_rows,
+                digest,
+            } => write!(
+                f,
+                "input_batch input={input} batch={batch} num_rows={num_rows} digest={digest:016x}"
+            ),
+            ReplayEvent::Spill { mem_size } => write!(f, "spill mem_size={mem_size}"),
+            ReplayEvent::MergeSpills { num_spills, output } => {
+                write!(f, "merge_spills num_spills={num_spills} output={output}")
+            }
+        }
+    }
+}
+
+impl FromStr for ReplayEvent {
+    type Err = DataFusionError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.split(' ');
+        let kind = parts.next().unwrap_or_default();
+        let fields = parts
+            .map(|part| part.split_once('=').unwrap_or((part, "")))
+            .collect::<Vec<_>>();
+
+        fn field<'a>(fields: &[(&str, &'a str)], name: &str) -> Result<&'a str> {
+            match fields.iter().find(|(k, _)| *k == name) {
+                Some((_, v)) => Ok(*v),
+                None => df_execution_err!("missing field in replay event: {name}"),
+            }
+        }
+        fn parse_field<T: FromStr>(fields: &[(&str, &str)], name: &str) -> Result<T>
+        where
+            T::Err: Display,
+        {
+            field(fields, name)?
+                .parse()
+                .or_else(|err| df_execution_err!("invalid field {name} in replay event: {err}"))
+        }
+
+        Ok(match kind {
+            "input_batch" => ReplayEvent::InputBatch {
+                input: parse_field(&fields, "input")?,
+                batch: parse_field(&fields, "batch")?,
+                num_rows: parse_field(&fields, "num_rows")?,
+                digest: u64::from_str_radix(field(&fields, "digest")?, 16).or_else(|err| {
+                    df_execution_err!("invalid field digest in replay event: {err}")
+                })?,
+            },
+            "spill" => ReplayEvent::Spill {
+                mem_size: parse_field(&fields, "mem_size")?,
+            },
+            "merge_spills" => ReplayEvent::MergeSpills {
+                num_spills: parse_field(&fields, "num_spills")?,
+                output: parse_field(&fields, "output")?,
+            },
+            _ => return df_execution_err!("unknown replay event: {s}"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{io::BufRead, sync::Arc};
+
+    use arrow::{
+        array::{ArrayRef, Int32Array, StringArray},
+        record_batch::RecordBatch,
+    };
+    use datafusion::common::Result;
+
+    use crate::common::replay_log::{ReplayEvent, ReplayLog};
+
+    #[test]
+    fn test_replay_log() -> Result<()> {
+        let batch = RecordBatch::try_from_iter([
+            (
+                "a",
+                Arc::new(Int32Array::from(vec![1, 2, 3, 4])) as ArrayRef,
+            ),
+            (
+                "b",
+                Arc::new(StringArray::from(vec!["x", "y", "z", "w"])) as ArrayRef,
+            ),
+        ])?;
+
+        // digest only depends on the content
+        let sliced = RecordBatch::try_from_iter([
+            (
+                "a",
+                Arc::new(Int32Array::from(vec![0, 1, 2, 3, 4]).slice(1, 4)) as ArrayRef,
+            ),
+            (
+                "b",
+                Arc::new(StringArray::from(vec!["", "x", "y", "z", "w"]).slice(1, 4)) as ArrayRef,
+            ),
+        ])?;
+        let digest = ReplayEvent::batch_digest(&batch)?;
+        assert_eq!(digest, ReplayEvent::batch_digest(&sliced)?);
+        assert_ne!(digest, ReplayEvent::batch_digest(&batch.slice(0, 3))?);
+
+        let events = vec![
+            (
+                0,
+                ReplayEvent::InputBatch {
+                    input: 0,
+                    batch: 0,
+                    num_rows: 4,
+                    digest,
+                },
+            ),
+            (1, ReplayEvent::Spill { mem_size: 1024 }),
+            (
+                1,
+                ReplayEvent::MergeSpills {
+                    num_spills: 2,
+                    output: true,
+                },
+            ),
+        ];
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("replay.log");
+        let replay_log = ReplayLog::try_new(&path)?;
+        for (op_id, event) in &events {
+            replay_log.log(*op_id, event);
+        }
+        replay_log.flush()?;
+
+        let parsed = std::io::BufReader::new(std::fs::File::open(&path)?)
+            .lines()
+            .map(|line| ReplayLog::parse_line(&line?))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(parsed, events);
+        Ok(())
+    }
+}
@@ -0,0 +1,176 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Native resource usage of tasks, summarized per stage. the summary of a
+//! stage is updated when each of its tasks finishes in this executor, so it
+//! can be retrieved after the last partition is done for chargeback and
+//! regression tracking, without scraping operator metrics.
+
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicIsize, AtomicUsize, Ordering::Relaxed},
+        Arc,
+    },
+};
+
+use datafusion::physical_plan::ExecutionPlan;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+
+thread_local! {
+    static THREAD_TASK_MEM_USAGE: RefCell<Option<Arc<TaskMemUsage>>> = const { RefCell::new(None) };
+}
+
+/// max number of stages whose summaries are retained, summaries of the
+/// earliest stages are removed when exceeded
+const MAX_RETAINED_STAGES: usize = 1024;
+
+static STAGE_RESOURCE_USAGES: OnceCell<Mutex<BTreeMap<usize, ResourceUsage>>> = OnceCell::new();
+
+/// Managed memory used by memory consumers registered in a task
+#[derive(Debug, Default)]
+pub struct TaskMemUsage {
+    mem_used: AtomicIsize,
+    peak_mem_used: AtomicUsize,
+}
+
+impl TaskMemUsage {
+    /// sets the memory usage of current thread, called when starting threads
+    /// of the task's runtime
+    pub fn set_current(task_mem_usage: Option<Arc<Self>>) {
+        THREAD_TASK_MEM_USAGE.with(|cur| *cur.borrow_mut() = task_mem_usage);
+    }
+
+    pub fn current() -> Option<Arc<Self>> {
+        THREAD_TASK_MEM_USAGE.with(|cur| cur.borrow().clone())
+    }
+
+    pub fn update_with_diff(&self, diff_used: isize) {
+        let new_used = self.mem_used.fetch_add(diff_used, Relaxed) + diff_used;
+        self.peak_mem_used
+            .fetch_max(new_used.max(0) as usize, Relaxed);
+    }
+
+    pub fn peak_mem_used(&self) -> usize {
+        self.peak_mem_used.load(Relaxed)
+    }
+}
+
+/// Resource usage of a task or a whole stage
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceUsage {
+    pub num_tasks: usize,
+    pub cpu_time_ns: usize,        // elapsed compute time of all operators
+    pub peak_mem_used: usize,      // max of all tasks
+    pub mem_spilled_bytes: usize,  // bytes spilled to on-heap memory
+    pub disk_spilled_bytes: usize, // bytes spilled to disk
+    pub io_bytes: usize,           // bytes of files and shuffle data read/written
+}
+
+impl ResourceUsage {
+    /// collects resource usage of a finished task from the executed plan
+    pub fn from_task_plan(plan: &Arc<dyn ExecutionPlan>, peak_mem_used: usize) -> Self {
+        let mut usage = Self {
+            num_tasks: 1,
+            peak_mem_used,
+            ..Default::default()
+        };
+        usage.collect_plan_metrics(plan.as_ref());
+        usage
+    }
+
+    fn collect_plan_metrics(&mut self, plan: &dyn ExecutionPlan) {
+        for metric in plan.metrics().unwrap_or_default().iter() {
+            let value = metric.value();
+            match value.name() {
+                "elapsed_compute" => self.cpu_time_ns += value.as_usize(),
+                "mem_spill_size" => self.mem_spilled_bytes += value.as_usize(),
+                "disk_spill_size" => self.disk_spilled_bytes += value.as_usize(),
+                "bytes_scanned" | "bytes_written" | "data_size" => {
+                    self.io_bytes += value.as_usize()
+                }
+                "size" if plan.name() == "IpcReaderExec" => self.io_bytes += value.as_usize(),
+                _ => {}
+            }
+        }
+        for child in plan.children() {
+            self.collect_plan_metrics(child.as_ref());
+        }
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        self.num_tasks += other.num_tasks;
+        self.cpu_time_ns += other.cpu_time_ns;
+        self.peak_mem_used = self.peak_mem_used.max(other.peak_mem_used);
+        self.mem_spilled_bytes += other.mem_spilled_bytes;
+        self.disk_spilled_bytes += other.disk_spilled_bytes;
+        self.io_bytes += other.io_bytes;
+    }
+}
+
+/// adds resource usage of a finished task to the summary of its stage
+pub fn record_task_resource_usage(stage_id: usize, usage: &ResourceUsage) {
+    let mut stage_usages = STAGE_RESOURCE_USAGES.get_or_init(Mutex::default).lock();
+    stage_usages.entry(stage_id).or_default().merge(usage);
+    while stage_usages.len() > MAX_RETAINED_STAGES {
+        stage_usages.pop_first();
+    }
+}
+
+/// returns resource usage summary of all finished tasks of the stage
+pub fn get_stage_resource_usage(stage_id: usize) -> Option<ResourceUsage> {
+    let stage_usages = STAGE_RESOURCE_USAGES.get_or_init(Mutex::default).lock();
+    stage_usages.get(&stage_id).copied()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::common::resource_usage::{
+        get_stage_resource_usage, record_task_resource_usage, ResourceUsage, TaskMemUsage,
+    };
+
+    #[test]
+    fn test_resource_usage() {
+        let task_mem_usage = TaskMemUsage::default();
+        task_mem_usage.update_with_diff(100);
+        task_mem_usage.update_with_diff(50);
+        task_mem_usage.update_with_diff(-120);
+        task_mem_usage.update_with_diff(60);
+        assert_eq!(task_mem_usage.peak_mem_used(), 150);
+
+        let task_usage = |cpu_time_ns, peak_mem_used| ResourceUsage {
+            num_tasks: 1,
+            cpu_time_ns,
+            peak_mem_used,
+            io_bytes: 1000,
+            ..Default::default()
+        };
+        assert!(get_stage_resource_usage(10001).is_none());
+        record_task_resource_usage(10001, &task_usage(20, 300));
+        record_task_resource_usage(10001, &task_usage(30, 200));
+        record_task_resource_usage(10002, &task_usage(40, 500));
+        assert_eq!(
+            get_stage_resource_usage(10001),
+            Some(ResourceUsage {
+                num_tasks: 2,
+                cpu_time_ns: 50,
+                peak_mem_used: 300,
+                io_bytes: 2000,
+                ..Default::default()
+            }),
+        );
+    }
+}
@@ -0,0 +1,329 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Runtime min/max filters of join keys. joins publish the key range of the
+//! built (or finished) side, filters and scans under the other side skip
+//! batches whose key range cannot match.
+//!
+//! filters are identified by planner-assigned ids and the partition, and are
+//! available as long as the publishing join holds them.
+
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    pin::Pin,
+    sync::{Arc, Mutex, Weak},
+    task::{ready, Context, Poll},
+};
+
+use arrow::{
+    array::{ArrayRef, AsArray, RecordBatch},
+    compute::{max, max_string, min, min_string},
+    datatypes::{
+        DataType, Date32Type, Decimal128Type, Int16Type, Int32Type, Int64Type, Int8Type, TimeUnit,
+        TimestampMicrosecondType,
+    },
+};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_expr::PhysicalExprRef,
+    physical_plan::{metrics::Count, stream::RecordBatchStreamAdapter, SendableRecordBatchStream},
+};
+use futures::{Stream, StreamExt};
+use once_cell::sync::OnceCell;
+
+use crate::common::execution_context::ExecutionContext;
+
+type FilterKey = (String, usize);
+
+static REGISTRY: OnceCell<Mutex<HashMap<FilterKey, Weak<RuntimeFilter>>>> = OnceCell::new();
+
+/// Published key range, `None` if there are no non-null keys so that nothing
+/// can match
+#[derive(Debug)]
+pub struct RuntimeFilter {
+    range: Option<(ScalarValue, ScalarValue)>,
+}
+
+impl RuntimeFilter {
+    /// returns whether keys in the range cannot match any published key,
+    /// null keys never match
+    fn excludes(&self, range: &Option<(ScalarValue, ScalarValue)>) -> bool {
+        match (&self.range, range) {
+            (None, _) | (_, None) => true,
+            (Some((min, max)), Some((keys_min, keys_max))) => {
+                keys_min.partial_cmp(max) == Some(Ordering::Greater)
+                    || keys_max.partial_cmp(min) == Some(Ordering::Less)
+            }
+        }
+    }
+}
+
+pub fn get_runtime_filter(id: &str, partition: usize) -> Option<Arc<RuntimeFilter>> {
+    let registry = REGISTRY.get_or_init(Mutex::default).lock().unwrap();
+    registry
+        .get(&(id.to_string(), partition))
+        .and_then(|filter| filter.upgrade())
+}
+
+/// Accumulates the range of keys and publishes it as a runtime filter
+pub struct RuntimeFilterBuilder {
+    id: String,
+    range: Option<(ScalarValue, ScalarValue)>,
+}
+
+impl RuntimeFilterBuilder {
+    /// returns None if min/max filter is not supported on the key type
+    pub fn new(id: String, data_type: &DataType) -> Option<Self> {
+        is_supported_key_type(data_type).then_some(Self { id, range: None })
+    }
+
+    pub fn update(&mut self, keys: &ArrayRef) -> Result<()> {
+        let Some((keys_min, keys_max)) = array_min_max(keys)? else {
+            return Ok(());
+        };
+        self.range = Some(match self.range.take() {
+            None => (keys_min, keys_max),
+            Some((min, max)) => (
+                if keys_min < min { keys_min } else { min },
+                if keys_max > max { keys_max } else { max },
+            ),
+        });
+        Ok(())
+    }
+
+    /// publishes the filter, which is available until the returned handle
+    /// is dropped
+    pub fn publish(self, partition: usize) -> Arc<RuntimeFilter> {
+        log::info!(
+            "publishing runtime filter {} (partition={partition}): {:?}",
+            self.id,
+            self.range,
+        );
+        let filter = Arc::new(RuntimeFilter { range: self.range });
+        let mut registry = REGISTRY.get_or_init(Mutex::default).lock().unwrap();
+        registry.retain(|_, filter| filter.strong_count() > 0);
+        registry.insert((self.id, partition), Arc::downgrade(&filter));
+        filter
+    }
+}
+
+/// publishes runtime filters of all keys, keys without filter id or with
+/// unsupported types are skipped
+pub fn publish_runtime_filters(
+    filter_ids: &[Option<String>],
+    key_columns: &[ArrayRef],
+    partition: usize,
+) -> Result<Vec<Arc<RuntimeFilter>>> {
+    let mut filters = vec![];
+    for (filter_id, keys) in filter_ids.iter().zip(key_columns) {
+        if let Some(filter_id) = filter_id {
+            if let Some(mut builder) =
+                RuntimeFilterBuilder::new(filter_id.clone(), keys.data_type())
+            {
+                builder.update(keys)?;
+                filters.push(builder.publish(partition));
+            }
+        }
+    }
+    Ok(filters)
+}
+
+/// wraps the stream to publish runtime filters of keys once the stream is
+/// finished. the filters are available until the returned stream is dropped.
+pub fn publish_runtime_filters_on_finish(
+    input: SendableRecordBatchStream,
+    filter_ids: &[Option<String>],
+    key_exprs: &[PhysicalExprRef],
+    partition: usize,
+) -> Result<SendableRecordBatchStream> {
+    let schema = input.schema();
+    let mut builders = vec![];
+    for (filter_id, key_expr) in filter_ids.iter().zip(key_exprs) {
+        if let Some(filter_id) = filter_id {
+            let data_type = key_expr.data_type(&schema)?;
+            if let Some(builder) = RuntimeFilterBuilder::new(filter_id.clone(), &data_type) {
+                builders.push((key_expr.clone(), builder));
+            }
+        }
+    }
+    if builders.is_empty() {
+        return Ok(input);
+    }
+    let stream = PublishOnFinishStream {
+        input,
+        builders,
+        published: vec![],
+        partition,
+    };
+    Ok(Box::pin(RecordBatchStreamAdapter::new(schema, stream)))
+}
+
+struct PublishOnFinishStream {
+    input: SendableRecordBatchStream,
+    builders: Vec<(PhysicalExprRef, RuntimeFilterBuilder)>,
+    published: Vec<Arc<RuntimeFilter>>, // keeps published filters available
+    partition: usize,
+}
+
+impl Stream for PublishOnFinishStream {
+    type Item = Result<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match ready!(self.input.poll_next_unpin(cx)) {
+            Some(Ok(batch)) => {
+                for (key_expr, builder) in &mut self.builders {
+                    let keys = key_expr.evaluate(&batch)?.into_array(batch.num_rows())?;
+                    builder.update(&keys)?;
+                }
+                Poll::Ready(Some(Ok(batch)))
+            }
+            None => {
+                let partition = self.partition;
+                for (_, builder) in std::mem::take(&mut self.builders) {
+                    let filter = builder.publish(partition);
+                    self.published.push(filter);
+                }
+                Poll::Ready(None)
+            }
+            err => Poll::Ready(err),
+        }
+    }
+}
+
+/// Skips batches whose key range cannot match the published runtime filters
+pub struct RuntimeFilterPruner {
+    filters: Vec<(PhysicalExprRef, String)>,
+    partition: usize,
+    pruned_rows: Count,
+}
+
+impl RuntimeFilterPruner {
+    pub fn new(filters: Vec<(PhysicalExprRef, String)>, exec_ctx: &ExecutionContext) -> Self {
+        Self {
+            filters,
+            partition: exec_ctx.partition_id(),
+            pruned_rows: exec_ctx.register_counter_metric("runtime_filter_pruned_rows"),
+        }
+    }
+
+    pub fn can_prune(&self, batch: &RecordBatch) -> Result<bool> {
+        for (key_expr, filter_id) in &self.filters {
+            let Some(filter) = get_runtime_filter(filter_id, self.partition) else {
+                continue;
+            };
+            let keys = key_expr.evaluate(batch)?.into_array(batch.num_rows())?;
+            if !is_supported_key_type(keys.data_type()) {
+                continue;
+            }
+            if filter.excludes(&array_min_max(&keys)?) {
+                self.pruned_rows.add(batch.num_rows());
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+fn is_supported_key_type(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Int8
+            | DataType::Int16
+            | DataType::Int32
+            | DataType::Int64
+            | DataType::Date32
+            | DataType::Timestamp(TimeUnit::Microsecond, _)
+            | DataType::Decimal128(..)
+            | DataType::Utf8
+    )
+}
+
+/// returns min/max of non-null values, the array must be of supported type
+fn array_min_max(array: &ArrayRef) -> Result<Option<(ScalarValue, ScalarValue)>> {
+    let data_type = array.data_type();
+    macro_rules! primitive_min_max {
+        ($t:ty) => {{
+            let array = array.as_primitive::<$t>();
+            match (min(array), max(array)) {
+                (Some(min), Some(max)) => Some((
+                    ScalarValue::new_primitive::<$t>(Some(min), data_type)?,
+                    ScalarValue::new_primitive::<$t>(Some(max), data_type)?,
+                )),
+                _ => None,
+            }
+        }};
+    }
+    Ok(match data_type {
+        DataType::Int8 => primitive_min_max!(Int8Type),
+        DataType::Int16 => primitive_min_max!(Int16Type),
+        DataType::Int32 => primitive_min_max!(Int32Type),
+        DataType::Int64 => primitive_min_max!(Int64Type),
+        DataType::Date32 => primitive_min_max!(Date32Type),
+        DataType::Timestamp(TimeUnit::Microsecond, _) => {
+            primitive_min_max!(TimestampMicrosecondType)
+        }
+        DataType::Decimal128(..) => primitive_min_max!(Decimal128Type),
+        DataType::Utf8 => {
+            let array = array.as_string::<i32>();
+            match (min_string(array), max_string(array)) {
+                (Some(min), Some(max)) => Some((ScalarValue::from(min), ScalarValue::from(max))),
+                _ => None,
+            }
+        }
+        other => unreachable!("unsupported runtime filter key type: {other}"),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::array::{ArrayRef, Int32Array, StringArray};
+    use datafusion::common::Result;
+
+    use crate::common::runtime_filter::{
+        array_min_max, get_runtime_filter, publish_runtime_filters,
+    };
+
+    #[test]
+    fn test_runtime_filter() -> Result<()> {
+        let keys: ArrayRef = Arc::new(Int32Array::from(vec![Some(5), None, Some(3), Some(9)]));
+        let filters = publish_runtime_filters(&[Some("rf-test".to_string())], &[keys], 0)?;
+        let filter = get_runtime_filter("rf-test", 0).expect("runtime filter not published");
+        assert!(get_runtime_filter("rf-test", 1).is_none());
+
+        let range = |values: Vec<Option<i32>>| {
+            let array: ArrayRef = Arc::new(Int32Array::from(values));
+            array_min_max(&array)
+        };
+        assert!(filter.excludes(&range(vec![Some(10), Some(12)])?));
+        assert!(filter.excludes(&range(vec![Some(1), Some(2)])?));
+        assert!(filter.excludes(&range(vec![None, None])?));
+        assert!(!filter.excludes(&range(vec![Some(1), Some(4)])?));
+        assert!(!filter.excludes(&range(vec![Some(9), None, Some(20)])?));
+
+        // filters are unavailable after dropped
+        drop((filter, filters));
+        assert!(get_runtime_filter("rf-test", 0).is_none());
+
+        // empty key range excludes everything
+        let keys: ArrayRef = Arc::new(StringArray::from(vec![None::<&str>]));
+        let _filters = publish_runtime_filters(&[Some("rf-test-str".to_string())], &[keys], 0)?;
+        let filter = get_runtime_filter("rf-test-str", 0).expect("runtime filter not published");
+        let array: ArrayRef = Arc::new(StringArray::from(vec!["a"]));
+        assert!(filter.excludes(&array_min_max(&array)?));
+        Ok(())
+    }
+}
@@ -0,0 +1,289 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Lazily filtered batches.
+//!
+//! a filter produces a selection vector (a boolean mask without nulls) over
+//! its input batch instead of compacting the batch immediately. consumers may
+//! refine the selection with more predicates, and the selected rows are copied
+//! only once when the batches are finally coalesced.
+
+use arrow::{
+    array::{Array, BooleanArray, RecordBatch},
+    compute::filter_record_batch,
+    datatypes::SchemaRef,
+};
+use datafusion::common::Result;
+use datafusion_ext_commons::{
+    arrow::{
+        array_size::ArraySize, coalesce::coalesce_batches_unchecked,
+        selection::create_batch_interleaver,
+    },
+    df_execution_err, suggested_output_batch_mem_size,
+};
+use futures::stream::BoxStream;
+
+/// batches with selectivity lower than this are compacted as soon as they are
+/// staged, so that large input batches are not retained for a few rows
+const MIN_DEFERRED_SELECTIVITY: f64 = 0.25;
+
+pub type SendableSelectedBatchStream = BoxStream<'static, Result<SelectedBatch>>;
+
+/// A batch with a selection of its rows, unselected rows are logically
+/// removed but not yet compacted
+#[derive(Debug, Clone)]
+pub struct SelectedBatch {
+    batch: RecordBatch,
+    selection: Option<BooleanArray>,
+    num_rows: usize,
+}
+
+impl SelectedBatch {
+    /// creates a batch with all rows selected
+    pub fn new(batch: RecordBatch) -> Self {
+        let num_rows = batch.num_rows();
+        Self {
+            batch,
+            selection: None,
+            num_rows,
+        }
+    }
+
+    pub fn new_empty(schema: SchemaRef) -> Self {
+        Self::new(RecordBatch::new_empty(schema))
+    }
+
+    /// creates a batch with the selected rows, selection must not contain nulls
+    pub fn try_new_with_selection(batch: RecordBatch, selection: BooleanArray) -> Result<Self> {
+        if selection.len() != batch.num_rows() || selection.null_count() > 0 {
+            return df_execution_err!(
+                "invalid selection: len={}, null_count={}, batch_num_rows={}",
+                selection.len(),
+                selection.null_count(),
+                batch.num_rows(),
+            );
+        }
+        let num_rows = selection.true_count();
+        if num_rows == batch.num_rows() {
+            return Ok(Self::new(batch));
+        }
+        if num_rows == 0 {
+            return Ok(Self::new_empty(batch.schema()));
+        }
+        Ok(Self {
+            batch,
+            selection: Some(selection),
+            num_rows,
+        })
+    }
+
+    /// the underlying batch, including unselected rows
+    pub fn batch(&self) -> &RecordBatch {
+        &self.batch
+    }
+
+    /// selection of the underlying batch, None if all rows are selected
+    pub fn selection(&self) -> Option<&BooleanArray> {
+        self.selection.as_ref()
+    }
+
+    /// number of selected rows
+    pub fn num_rows(&self) -> usize {
+        self.num_rows
+    }
+
+    pub fn selectivity(&self) -> f64 {
+        match self.batch.num_rows() {
+            0 => 1.0,
+            n => self.num_rows as f64 / n as f64,
+        }
+    }
+
+    /// compacts the selected rows into a new batch
+    pub fn materialize(&self) -> Result<RecordBatch> {
+        match &self.selection {
+            Some(selection) => Ok(filter_record_batch(&self.batch, selection)?),
+            None => Ok(self.batch.clone()),
+        }
+    }
+}
+
+/// Coalesces selected batches into batches of the target size. selected rows
+/// of all staged batches are copied in one pass, instead of being compacted
+/// per batch then concatenated.
+pub struct SelectedBatchCoalescer {
+    schema: SchemaRef,
+    batch_size: usize,
+    staging_batches: Vec<SelectedBatch>,
+    staging_rows: usize,
+    staging_batches_mem_size: usize,
+}
+
+impl SelectedBatchCoalescer {
+    pub fn new(schema: SchemaRef, batch_size: usize) -> Self {
+        Self {
+            schema,
+            batch_size,
+            staging_batches: vec![],
+            staging_rows: 0,
+            staging_batches_mem_size: 0,
+        }
+    }
+
+    /// stages a selected batch, returns a coalesced batch if the staged rows
+    /// are enough to be flushed
+    pub fn push(&mut self, selected: SelectedBatch) -> Result<Option<RecordBatch>> {
+        if selected.num_rows() == 0 {
+            return Ok(None);
+        }
+        let selected = if selected.selectivity() < MIN_DEFERRED_SELECTIVITY {
+            SelectedBatch::new(selected.materialize()?)
+        } else {
+            selected
+        };
+
+        // estimate the memory size of the selected rows
+        let mem_size = selected.batch().get_array_mem_size() as f64 * selected.selectivity();
+        self.staging_rows += selected.num_rows();
+        self.staging_batches_mem_size += mem_size as usize;
+        self.staging_batches.push(selected);
+
+        if self.should_flush() {
+            return Ok(Some(self.flush()?));
+        }
+        Ok(None)
+    }
+
+    /// flushes remaining staged rows
+    pub fn finish(&mut self) -> Result<Option<RecordBatch>> {
+        if self.staging_batches.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(self.flush()?))
+    }
+
+    fn should_flush(&self) -> bool {
+        let size_limit = suggested_output_batch_mem_size();
+        let (batch_size_limit, mem_size_limit) = if self.staging_batches.len() > 1 {
+            (self.batch_size, size_limit)
+        } else {
+            (self.batch_size / 2, size_limit / 2)
+        };
+        self.staging_rows >= batch_size_limit || self.staging_batches_mem_size > mem_size_limit
+    }
+
+    fn flush(&mut self) -> Result<RecordBatch> {
+        let staging_batches = std::mem::take(&mut self.staging_batches);
+        self.staging_rows = 0;
+        self.staging_batches_mem_size = 0;
+
+        if staging_batches.len() == 1 {
+            return staging_batches[0].materialize();
+        }
+        let batches = staging_batches
+            .iter()
+            .map(|selected| selected.batch().clone())
+            .collect::<Vec<_>>();
+        if staging_batches
+            .iter()
+            .all(|selected| selected.selection().is_none())
+        {
+            return Ok(coalesce_batches_unchecked(self.schema.clone(), &batches));
+        }
+
+        let mut indices = vec![];
+        for (batch_idx, selected) in staging_batches.iter().enumerate() {
+            match selected.selection() {
+                Some(selection) => indices.extend(
+                    selection
+                        .values()
+                        .set_indices()
+                        .map(|row_idx| (batch_idx, row_idx)),
+                ),
+                None => {
+                    indices.extend((0..selected.num_rows()).map(|row_idx| (batch_idx, row_idx)))
+                }
+            }
+        }
+        let interleaver = create_batch_interleaver(&batches, false)?;
+        interleaver(&indices)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{ArrayRef, BooleanArray, Int32Array, RecordBatch, StringArray},
+        datatypes::{DataType, Field, Schema},
+    };
+    use datafusion::{assert_batches_eq, common::Result};
+
+    use crate::common::selection_vector::{SelectedBatch, SelectedBatchCoalescer};
+
+    #[test]
+    fn test_selected_batch_coalescer() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, true),
+            Field::new("b", DataType::Utf8, true),
+        ]));
+        let batch = |a: Vec<Option<i32>>, b: Vec<&str>| -> Result<RecordBatch> {
+            Ok(RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from(a)) as ArrayRef,
+                    Arc::new(StringArray::from(b)) as ArrayRef,
+                ],
+            )?)
+        };
+
+        let selected1 = SelectedBatch::try_new_with_selection(
+            batch(
+                vec![Some(1), None, Some(3), Some(4)],
+                vec!["a", "b", "c", "d"],
+            )?,
+            BooleanArray::from(vec![true, true, false, true]),
+        )?;
+        let selected2 = SelectedBatch::new(batch(vec![Some(5), Some(6)], vec!["e", "f"])?);
+        let selected3 = SelectedBatch::try_new_with_selection(
+            batch(vec![Some(7), Some(8)], vec!["g", "h"])?,
+            BooleanArray::from(vec![false, false]),
+        )?;
+        assert_eq!(selected1.num_rows(), 3);
+        assert_eq!(selected3.num_rows(), 0);
+
+        let mut coalescer = SelectedBatchCoalescer::new(schema.clone(), 100);
+        assert!(coalescer.push(selected1)?.is_none());
+        assert!(coalescer.push(selected2)?.is_none());
+        assert!(coalescer.push(selected3)?.is_none());
+        let coalesced = coalescer.finish()?.expect("missing coalesced batch");
+        assert_batches_eq!(
+            vec![
+                "+---+---+",
+                "| a | b |",
+                "+---+---+",
+                "| 1 | a |",
+                "|   | b |",
+                "| 4 | d |",
+                "| 5 | e |",
+                "| 6 | f |",
+                "+---+---+",
+            ],
+            &[coalesced]
+        );
+        assert!(coalescer.finish()?.is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,563 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use arrow::datatypes::Schema;
+use datafusion::common::{stats::Precision, Statistics};
+
+use crate::joins::join_utils::JoinType;
+
+/// selectivity of filter predicates which cannot be analyzed, same as
+/// datafusion's default filter selectivity
+pub const DEFAULT_FILTER_SELECTIVITY: f64 = 0.2;
+
+/// estimates statistics of an operator which outputs about `ratio` of its
+/// input rows. column statistics are kept but become inexact.
+pub fn scale_statistics(stats: Statistics, ratio: f64) -> Statistics {
+    let scale = |v: &usize| (*v as f64 * ratio).ceil() as usize;
+    Statistics {
+        num_rows: to_inexact(stats.num_rows.map(|v| scale(&v))),
+        total_byte_size: to_inexact(stats.total_byte_size.map(|v| scale(&v))),
+        column_statistics: stats
+            .column_statistics
+            .into_iter()
+            .map(|col_stats| col_stats.to_inexact())
+            .collect(),
+    }
+}
+
+/// estimates statistics of an operator which outputs at most `fetch` rows of
+/// its input
+pub fn limit_statistics(stats: Statistics, fetch: usize) -> Statistics {
+    match stats.num_rows {
+        Precision::Exact(num_rows) | Precision::Inexact(num_rows) if num_rows <= fetch => stats,
+        Precision::Exact(num_rows) => {
+            let mut stats = scale_statistics(stats, fetch as f64 / num_rows as f64);
+            stats.num_rows = Precision::Exact(fetch);
+            stats
+        }
+        Precision::Inexact(num_rows) => {
+            let mut stats = scale_statistics(stats, fetch as f64 / num_rows as f64);
+            stats.num_rows = Precision::Inexact(fetch);
+            stats
+        }
+        Precision::Absent => Statistics {
+            num_rows: Precision::Inexact(fetch),
+            total_byte_size: Precision::Absent,
+            column_statistics: stats
+                .column_statistics
+                .into_iter()
+                .map(|col_stats| col_stats.to_inexact())
+                .collect(),
+        },
+    }
+}
+
+/// estimates statistics of join output. without column statistics of join
+/// keys, equal joins are assumed to be like foreign key joins, in which each
+/// row of the larger side matches one row of the other side.
+pub fn estimate_join_statistics(
+    left: &Statistics,
+    right: &Statistics,
+    join_type: JoinType,
+    output_schema: &Schema,
+) -> Statistics {
+    let column_statistics = Statistics::unknown_column(output_schema);
+    let (Some(&lrows), Some(&rrows)) = (left.num_rows.get_value(), right.num_rows.get_value())
+    else {
+        return Statistics {
+            num_rows: Precision::Absent,
+            total_byte_size: Precision::Absent,
+            column_statistics,
+        };
+    };
+    let num_rows = match join_type {
+        JoinType::Inner | JoinType::Left | JoinType::Right => lrows.max(rrows),
+        JoinType::Full => lrows + rrows,
+        JoinType::LeftSemi | JoinType::LeftAnti | JoinType::Existence => lrows,
+        JoinType::RightSemi | JoinType::RightAnti => rrows,
+    };
+
+    // estimates byte size with average row sizes of the output sides
+    let avg_row_size = |stats: &Statistics, num_rows: usize| {
+        let total_byte_size = *stats.total_byte_size.get_value()?;
+        Some(total_byte_size as f64 / num_rows.max(1) as f64)
+    };
+    let output_row_size = match join_type {
+        JoinType::LeftSemi | JoinType::LeftAnti | JoinType::Existence => avg_row_size(left, lrows),
+        JoinType::RightSemi | JoinType::RightAnti => avg_row_size(right, rrows),
+        _ => avg_row_size(left, lrows)
+            .zip(avg_row_size(right, rrows))
+            .map(|(l, r)| l + r),
+    };
+    let total_byte_size = match output_row_size {
+        Some(row_size) => Precision::Inexact((row_size * num_rows as f64).ceil() as usize),
+        None => Precision::Absent,
+    };
+    Statistics {
+        num_rows: Precision::Inexact(num_rows),
+        total_byte_size,
+        column_statistics,
+    }
+}
+
+/// estimates statistics of an operator which outputs all rows of its inputs,
+/// like union. column statistics are unknown since inputs may be casted.
+pub fn sum_statistics(
+    stats: impl IntoIterator<Item = Statistics>,
+    output_schema: &Schema,
+) -> Statistics {
+    let sum = |a: Precision<usize>, b: Precision<usize>| match (a, b) {
+        (Precision::Exact(a), Precision::Exact(b)) => Precision::Exact(a + b),
+        (a, b) => match (a.get_value(), b.get_value()) {
+            (Some(a), Some(b)) => Precision::Inexact(a + b),
+            _ => Precision::Absent,
+        },
+    };
+    let mut num_rows = Precision::Exact(0);
+    let mut total_byte_size = Precision::Exact(0);
+    for stats in stats {
+        num_rows = sum(num_rows, stats.num_rows);
+        total_byte_size = sum(total_byte_size, stats.total_byte_size);
+    }
+    Statistics {
+        num_rows,
+        total_byte_size,
+        column_statistics: Statistics::unknown_column(output_schema),
+    }
+}
+
+/// estimates statistics of an operator which outputs one row for each input
+/// row with different columns, like project. column statistics are unknown.
+pub fn project_statistics(stats: Statistics, output_schema: &Schema) -> Statistics {
+    Statistics {
+        num_rows: stats.num_rows,
+        total_byte_size: to_inexact(stats.total_byte_size),
+        column_statistics: Statistics::unknown_column(output_schema),
+    }
+}
+
+/// estimates statistics of an operator which skips the first `offset` rows of
+/// its input
+pub fn skip_statistics(stats: Statistics, offset: usize) -> Statistics {
+    let input_rows = stats.num_rows.get_value().copied();
+    match input_rows {
+        Some(input_rows) if offset > 0 && input_rows > 0 => {
+            let num_rows = stats
+                .num_rows
+                .map(|num_rows| num_rows.saturating_sub(offset));
+            let ratio = input_rows.saturating_sub(offset) as f64 / input_rows as f64;
+            let mut stats = scale_statistics(stats, ratio);
+            stats.num_rows = num_rows;
+            stats
+        }
+        _ => stats,
+    }
+}
+
+fn to_inexact(precision: Precision<usize>) -> Precision<usize> {
+    match precision {
+        Precision::Exact(v) => Precision::Inexact(v),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{Int32Array, ListArray, RecordBatch},
+        datatypes::{DataType, Field, Int32Type, Schema},
+    };
+    use datafusion::{
+        common::{stats::Precision, JoinSide, Result, Statistics},
+        datasource::physical_plan::FileScanConfig,
+        execution::object_store::ObjectStoreUrl,
+        logical_expr::Operator,
+        physical_expr::{
+            expressions::{BinaryExpr, Column, Literal},
+            PhysicalExpr, PhysicalSortExpr,
+        },
+        physical_plan::{
+            memory::{MemoryExec, MemoryStream},
+            ExecutionPlan,
+        },
+        scalar::ScalarValue,
+    };
+
+    use crate::{
+        agg::{agg::create_agg, AggExecMode, AggExpr, AggFunction, AggMode, GroupingExpr},
+        agg_exec::AggExec,
+        broadcast_join_build_hash_map_exec::BroadcastJoinBuildHashMapExec,
+        broadcast_join_exec::BroadcastJoinExec,
+        broadcast_nested_loop_join_exec::BroadcastNestedLoopJoinExec,
+        coalesce_partitions_exec::CoalescePartitionsExec,
+        common::{
+            statistics::{
+                estimate_join_statistics, limit_statistics, project_statistics, scale_statistics,
+                skip_statistics, sum_statistics,
+            },
+            stream_exec::RecordBatchStreamExec,
+        },
+        csv_exec::CsvExec,
+        debug_exec::DebugExec,
+        empty_partitions_exec::EmptyPartitionsExec,
+        expand_exec::ExpandExec,
+        ffi_reader_exec::FFIReaderExec,
+        filter_exec::FilterExec,
+        generate::{create_generator, GenerateFunc},
+        generate_exec::GenerateExec,
+        interval_join_exec::IntervalJoinExec,
+        ipc_reader_exec::IpcReaderExec,
+        ipc_writer_exec::IpcWriterExec,
+        joins::join_utils::{build_join_schema, JoinType},
+        json_exec::JsonExec,
+        limit_exec::LimitExec,
+        orc_exec::OrcExec,
+        parquet_exec::ParquetExec,
+        parquet_sink_exec::ParquetSinkExec,
+        project_exec::ProjectExec,
+        range_exec::RangeExec,
+        rename_columns_exec::RenameColumnsExec,
+        rss_shuffle_writer_exec::RssShuffleWriterExec,
+        scan::{csv::CsvOptions, json::JsonOptions},
+        shuffle::ShufflePartitioning,
+        shuffle_writer_exec::ShuffleWriterExec,
+        sort_exec::SortExec,
+        sort_merge_join_exec::SortMergeJoinExec,
+        take_ordered_and_project_exec::TakeOrderedAndProjectExec,
+        top_k_frequent_exec::TopKFrequentExec,
+        union_exec::{UnionExec, UnionInput},
+        window::{WindowExpr, WindowFunction, WindowRankType},
+        window_exec::WindowExec,
+        window_group_limit_exec::WindowGroupLimitExec,
+    };
+
+    fn stats(num_rows: Precision<usize>, total_byte_size: Precision<usize>) -> Statistics {
+        let schema = Schema::new(vec![Field::new("a", DataType::Int32, true)]);
+        Statistics {
+            num_rows,
+            total_byte_size,
+            column_statistics: Statistics::unknown_column(&schema),
+        }
+    }
+
+    #[test]
+    fn test_sum_statistics() {
+        let schema = Schema::new(vec![Field::new("a", DataType::Int32, true)]);
+        let exact = stats(Precision::Exact(10), Precision::Exact(40));
+        let inexact = stats(Precision::Inexact(20), Precision::Absent);
+
+        let summed = sum_statistics([exact.clone(), exact.clone()], &schema);
+        assert_eq!(summed.num_rows, Precision::Exact(20));
+        assert_eq!(summed.total_byte_size, Precision::Exact(80));
+        let summed = sum_statistics([exact, inexact], &schema);
+        assert_eq!(summed.num_rows, Precision::Inexact(30));
+        assert_eq!(summed.total_byte_size, Precision::Absent);
+        assert_eq!(summed.column_statistics.len(), 1);
+    }
+
+    #[test]
+    fn test_scale_and_limit_statistics() {
+        let input = stats(Precision::Exact(1000), Precision::Exact(8000));
+        let scaled = scale_statistics(input.clone(), 0.2);
+        assert_eq!(scaled.num_rows, Precision::Inexact(200));
+        assert_eq!(scaled.total_byte_size, Precision::Inexact(1600));
+
+        let limited = limit_statistics(input.clone(), 10);
+        assert_eq!(limited.num_rows, Precision::Exact(10));
+        assert_eq!(limited.total_byte_size, Precision::Inexact(80));
+        let limited = limit_statistics(input, 2000);
+        assert_eq!(limited.num_rows, Precision::Exact(1000));
+
+        let limited = limit_statistics(stats(Precision::Absent, Precision::Absent), 10);
+        assert_eq!(limited.num_rows, Precision::Inexact(10));
+    }
+
+    #[test]
+    fn test_estimate_join_statistics() {
+        let schema = Schema::new(vec![
+            Field::new("l", DataType::Int32, true),
+            Field::new("r", DataType::Int32, true),
+        ]);
+        let left = stats(Precision::Exact(100), Precision::Exact(400));
+        let right = stats(Precision::Inexact(10), Precision::Inexact(80));
+
+        let inner = estimate_join_statistics(&left, &right, JoinType::Inner, &schema);
+        assert_eq!(inner.num_rows, Precision::Inexact(100));
+        assert_eq!(inner.total_byte_size, Precision::Inexact(1200));
+        assert_eq!(inner.column_statistics.len(), 2);
+
+        let full = estimate_join_statistics(&left, &right, JoinType::Full, &schema);
+        assert_eq!(full.num_rows, Precision::Inexact(110));
+
+        let semi = estimate_join_statistics(&left, &right, JoinType::RightSemi, &schema);
+        assert_eq!(semi.num_rows, Precision::Inexact(10));
+        assert_eq!(semi.total_byte_size, Precision::Inexact(80));
+
+        let unknown = stats(Precision::Absent, Precision::Absent);
+        let inner = estimate_join_statistics(&left, &unknown, JoinType::Inner, &schema);
+        assert_eq!(inner.num_rows, Precision::Absent);
+    }
+
+    #[test]
+    fn test_project_and_skip_statistics() {
+        let schema = Schema::new(vec![
+            Field::new("x", DataType::Int32, true),
+            Field::new("y", DataType::Int32, true),
+        ]);
+        let input = stats(Precision::Exact(100), Precision::Exact(400));
+        let projected = project_statistics(input.clone(), &schema);
+        assert_eq!(projected.num_rows, Precision::Exact(100));
+        assert_eq!(projected.total_byte_size, Precision::Inexact(400));
+        assert_eq!(projected.column_statistics.len(), 2);
+
+        let skipped = skip_statistics(input.clone(), 30);
+        assert_eq!(skipped.num_rows, Precision::Exact(70));
+        assert_eq!(skipped.total_byte_size, Precision::Inexact(280));
+        let skipped = skip_statistics(input, 200);
+        assert_eq!(skipped.num_rows, Precision::Exact(0));
+        let skipped = skip_statistics(stats(Precision::Absent, Precision::Absent), 10);
+        assert_eq!(skipped.num_rows, Precision::Absent);
+    }
+
+    #[test]
+    fn test_native_operator_statistics() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, false),
+            Field::new("b", DataType::Int32, false),
+            Field::new_list("l", Field::new_list_field(DataType::Int32, true), true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..10)),
+                Arc::new(Int32Array::from_iter_values((0..10).rev())),
+                Arc::new(ListArray::from_iter_primitive::<Int32Type, _, _>(
+                    (0..10).map(|i| Some(vec![Some(i), None])),
+                )),
+            ],
+        )?;
+        let input: Arc<dyn ExecutionPlan> = Arc::new(MemoryExec::try_new(
+            &[vec![batch.clone()]],
+            schema.clone(),
+            None,
+        )?);
+        let col = |name: &str| -> Arc<dyn PhysicalExpr> {
+            Arc::new(Column::new_with_schema(name, &schema).unwrap())
+        };
+        let sort_exprs = vec![PhysicalSortExpr {
+            expr: col("b"),
+            options: Default::default(),
+        }];
+        let join_schema = build_join_schema(&schema, &schema, JoinType::Inner)?;
+        let join_on = vec![(col("a"), col("a"))];
+        let scan_schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+        let scan_config = FileScanConfig::new(ObjectStoreUrl::local_filesystem(), scan_schema)
+            .with_file_groups(vec![vec![]]);
+
+        let plans: Vec<Arc<dyn ExecutionPlan>> = vec![
+            Arc::new(AggExec::try_new(
+                AggExecMode::HashAgg,
+                vec![GroupingExpr {
+                    field_name: "a".to_string(),
+                    expr: col("a"),
+                    key_type: None,
+                }],
+                vec![AggExpr {
+                    field_name: "cnt".to_string(),
+                    mode: AggMode::Partial,
+                    agg: create_agg(AggFunction::Count, &[col("b")], &schema)?,
+                }],
+                false,
+                input.clone(),
+            )?),
+            Arc::new(BroadcastJoinExec::try_new(
+                join_schema.clone(),
+                input.clone(),
+                Arc::new(BroadcastJoinBuildHashMapExec::new(
+                    input.clone(),
+                    vec![col("a")],
+                )),
+                join_on.clone(),
+                JoinType::Inner,
+                JoinSide::Right,
+                true,
+                None,
+            )?),
+            Arc::new(BroadcastNestedLoopJoinExec::try_new(
+                join_schema.clone(),
+                input.clone(),
+                input.clone(),
+                JoinType::Inner,
+                JoinSide::Right,
+                None,
+            )?),
+            Arc::new(CoalescePartitionsExec::new(input.clone())),
+            Arc::new(CsvExec::try_new(
+                scan_config.clone(),
+                "fs".to_string(),
+                CsvOptions::default(),
+            )?),
+            Arc::new(DebugExec::new(input.clone(), "debug".to_string())),
+            Arc::new(EmptyPartitionsExec::new(schema.clone(), 2)),
+            Arc::new(ExpandExec::try_new(
+                schema.clone(),
+                vec![
+                    vec![col("a"), col("b"), col("l")],
+                    vec![col("b"), col("a"), col("l")],
+                ],
+                input.clone(),
+            )?),
+            Arc::new(FFIReaderExec::new(1, "ffi".to_string(), schema.clone())),
+            Arc::new(FilterExec::try_new(
+                vec![Arc::new(BinaryExpr::new(
+                    col("a"),
+                    Operator::Gt,
+                    Arc::new(Literal::new(ScalarValue::Int32(Some(5)))),
+                ))],
+                input.clone(),
+            )?),
+            Arc::new(GenerateExec::try_new(
+                input.clone(),
+                create_generator(&schema, GenerateFunc::Explode, vec![col("l")])?,
+                vec![Column::new("a", 0)],
+                Arc::new(Schema::new(vec![Field::new("e", DataType::Int32, true)])),
+                false,
+            )?),
+            Arc::new(IntervalJoinExec::try_new(
+                join_schema.clone(),
+                input.clone(),
+                input.clone(),
+                JoinType::Inner,
+                JoinSide::Right,
+                (col("a"), col("b")),
+                (col("a"), col("b")),
+                true,
+                true,
+            )?),
+            Arc::new(IpcReaderExec::new(
+                1,
+                "ipc".to_string(),
+                schema.clone(),
+                None,
+            )),
+            Arc::new(IpcWriterExec::new(input.clone(), "ipc".to_string())),
+            Arc::new(JsonExec::try_new(
+                scan_config.clone(),
+                "fs".to_string(),
+                JsonOptions::default(),
+            )?),
+            Arc::new(LimitExec::new(input.clone(), 3, 1)),
+            Arc::new(OrcExec::new(scan_config.clone(), "fs".to_string(), None)),
+            Arc::new(ParquetExec::new(
+                scan_config.clone(),
+                "fs".to_string(),
+                None,
+            )),
+            Arc::new(ParquetSinkExec::new(
+                input.clone(),
+                "fs".to_string(),
+                0,
+                vec![],
+            )),
+            Arc::new(ProjectExec::try_new(
+                vec![(col("a"), "x".to_string())],
+                input.clone(),
+            )?),
+            Arc::new(RangeExec::try_new(
+                0,
+                10,
+                1,
+                1,
+                Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)])),
+            )?),
+            Arc::new(RecordBatchStreamExec::new(Box::pin(MemoryStream::try_new(
+                vec![batch],
+                schema.clone(),
+                None,
+            )?))),
+            Arc::new(RenameColumnsExec::try_new(
+                input.clone(),
+                vec!["x".to_string(), "y".to_string(), "z".to_string()],
+            )?),
+            Arc::new(RssShuffleWriterExec::try_new(
+                input.clone(),
+                ShufflePartitioning::Single,
+                "rss".to_string(),
+            )?),
+            Arc::new(ShuffleWriterExec::try_new(
+                input.clone(),
+                ShufflePartitioning::Single,
+                "data".to_string(),
+                "index".to_string(),
+            )?),
+            Arc::new(SortExec::new(input.clone(), sort_exprs.clone(), Some(5))),
+            Arc::new(SortMergeJoinExec::try_new(
+                join_schema.clone(),
+                input.clone(),
+                input.clone(),
+                join_on,
+                JoinType::Inner,
+                vec![Default::default()],
+                false,
+            )?),
+            Arc::new(TakeOrderedAndProjectExec::try_new(
+                input.clone(),
+                sort_exprs.clone(),
+                3,
+                0,
+                vec![(col("a"), "x".to_string())],
+            )?),
+            Arc::new(TopKFrequentExec::try_new(input.clone(), col("a"), 3)?),
+            Arc::new(UnionExec::new(
+                vec![UnionInput(input.clone(), 0), UnionInput(input.clone(), 0)],
+                schema.clone(),
+                1,
+                0,
+            )),
+            Arc::new(WindowExec::try_new(
+                input.clone(),
+                vec![WindowExpr::new(
+                    WindowFunction::RankLike(WindowRankType::RowNumber),
+                    vec![],
+                    Arc::new(Field::new("rn", DataType::Int32, false)),
+                )],
+                vec![col("a")],
+                sort_exprs.clone(),
+            )?),
+            Arc::new(WindowGroupLimitExec::try_new(
+                input.clone(),
+                vec![col("a")],
+                sort_exprs,
+                WindowRankType::RowNumber,
+                2,
+            )?),
+        ];
+
+        for plan in plans {
+            let stats = plan.statistics()?;
+            assert_eq!(
+                stats.column_statistics.len(),
+                plan.schema().fields().len(),
+                "wrong number of column statistics: {}",
+                plan.name(),
+            );
+            if let Some(&num_rows) = stats.num_rows.get_value() {
+                assert!(num_rows <= 200, "too many rows: {}", plan.name());
+            }
+        }
+        Ok(())
+    }
+}
@@ -122,6 +122,6 @@ impl ExecutionPlan for DebugExec {
     }
 
     fn statistics(&self) -> Result<Statistics> {
-        todo!()
+        self.input.statistics()
     }
 }
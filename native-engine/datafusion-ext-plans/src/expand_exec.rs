@@ -25,14 +25,17 @@ use datafusion::{
     physical_plan::{
         metrics::{ExecutionPlanMetricsSet, MetricsSet},
         DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, ExecutionPlanProperties,
-        PlanProperties, SendableRecordBatchStream,
+        Partitioning, PlanProperties, SendableRecordBatchStream,
     },
 };
 use datafusion_ext_commons::{arrow::cast::cast, df_execution_err};
 use futures::StreamExt;
 use once_cell::sync::OnceCell;
 
-use crate::common::execution_context::ExecutionContext;
+use crate::common::{
+    execution_context::ExecutionContext,
+    statistics::{project_statistics, scale_statistics},
+};
 
 #[derive(Debug, Clone)]
 pub struct ExpandExec {
@@ -96,7 +99,10 @@ impl ExecutionPlan for ExpandExec {
         self.props.get_or_init(|| {
             PlanProperties::new(
                 EquivalenceProperties::new(self.schema()),
-                self.input.output_partitioning().clone(),
+                // output columns are not derived from input columns
+                Partitioning::UnknownPartitioning(
+                    self.input.output_partitioning().partition_count(),
+                ),
                 ExecutionMode::Bounded,
             )
         })
@@ -135,7 +141,9 @@ impl ExecutionPlan for ExpandExec {
     }
 
     fn statistics(&self) -> Result<Statistics> {
-        todo!()
+        let num_projections = self.projections.len() as f64;
+        let input_stats = scale_statistics(self.input.statistics()?, num_projections);
+        Ok(project_statistics(input_stats, &self.schema))
     }
 }
 
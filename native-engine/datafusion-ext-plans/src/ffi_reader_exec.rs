@@ -18,12 +18,10 @@ use std::{
     sync::Arc,
 };
 
-use arrow::{
-    array::{Array, RecordBatch, RecordBatchOptions, StructArray},
-    datatypes::{DataType, SchemaRef},
-    ffi::{from_ffi_and_data_type, FFI_ArrowArray},
+use arrow::datatypes::SchemaRef;
+use blaze_jni_bridge::{
+    ffi::import_batch_from_jvm, jni_call, jni_call_static, jni_new_global_ref, jni_new_string,
 };
-use blaze_jni_bridge::{jni_call, jni_call_static, jni_new_global_ref, jni_new_string};
 use datafusion::{
     error::Result,
     execution::context::TaskContext,
@@ -135,7 +133,7 @@ impl ExecutionPlan for FFIReaderExec {
     }
 
     fn statistics(&self) -> Result<Statistics> {
-        todo!()
+        Ok(Statistics::new_unknown(&self.schema()))
     }
 }
 
@@ -150,33 +148,19 @@ fn read_ffi(
         .clone()
         .output_with_sender("FFIReader", move |sender| async move {
             loop {
-                let batch = {
-                    // load batch from ffi
-                    let mut ffi_arrow_array = FFI_ArrowArray::empty();
-                    let ffi_arrow_array_ptr = &mut ffi_arrow_array as *mut FFI_ArrowArray as i64;
-                    let has_next = jni_call!(
+                let imported = import_batch_from_jvm(&schema, |ffi_arrow_array_ptr| {
+                    jni_call!(
                         BlazeArrowFFIExporter(exporter.as_obj())
                             .exportNextBatch(ffi_arrow_array_ptr) -> bool
-                    )?;
-
-                    if !has_next {
-                        break;
-                    }
-                    let import_data_type = DataType::Struct(schema.fields().clone());
-                    let imported =
-                        unsafe { from_ffi_and_data_type(ffi_arrow_array, import_data_type)? };
-                    let struct_array = StructArray::from(imported);
-                    let batch = RecordBatch::try_new_with_options(
-                        schema.clone(),
-                        struct_array.columns().to_vec(),
-                        &RecordBatchOptions::new().with_row_count(Some(struct_array.len())),
-                    )?;
-                    size_counter.add(batch.get_array_mem_size());
-                    exec_ctx_cloned
-                        .baseline_metrics()
-                        .record_output(batch.num_rows());
-                    batch
+                    )
+                })?;
+                let Some(batch) = imported else {
+                    break;
                 };
+                size_counter.add(batch.get_array_mem_size());
+                exec_ctx_cloned
+                    .baseline_metrics()
+                    .record_output(batch.num_rows());
                 sender.send(batch).await;
             }
             Ok(())
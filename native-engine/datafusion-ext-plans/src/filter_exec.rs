@@ -25,15 +25,19 @@ use datafusion::{
         PlanProperties, SendableRecordBatchStream,
     },
 };
-use datafusion_ext_commons::df_execution_err;
+use datafusion_ext_commons::{batch_size, df_execution_err, downcast_any};
 use futures::StreamExt;
 use itertools::Itertools;
 use once_cell::sync::OnceCell;
 
 use crate::{
     common::{
-        cached_exprs_evaluator::CachedExprsEvaluator, column_pruning::ExecuteWithColumnPruning,
+        cached_exprs_evaluator::CachedExprsEvaluator,
+        column_pruning::ExecuteWithColumnPruning,
         execution_context::ExecutionContext,
+        runtime_filter::RuntimeFilterPruner,
+        selection_vector::{SelectedBatch, SelectedBatchCoalescer, SendableSelectedBatchStream},
+        statistics::{scale_statistics, DEFAULT_FILTER_SELECTIVITY},
     },
     project_exec::ProjectExec,
 };
@@ -42,6 +46,7 @@ use crate::{
 pub struct FilterExec {
     input: Arc<dyn ExecutionPlan>,
     predicates: Vec<PhysicalExprRef>,
+    runtime_filters: Vec<(PhysicalExprRef, String)>,
     metrics: ExecutionPlanMetricsSet,
     props: OnceCell<PlanProperties>,
 }
@@ -65,6 +70,7 @@ impl FilterExec {
         Ok(Self {
             input,
             predicates,
+            runtime_filters: vec![],
             metrics: ExecutionPlanMetricsSet::new(),
             props: OnceCell::new(),
         })
@@ -73,6 +79,64 @@ impl FilterExec {
     pub fn predicates(&self) -> &[PhysicalExprRef] {
         &self.predicates
     }
+
+    /// skips input batches whose keys cannot match the runtime filters
+    /// published by joins, filters are specified as (key, filter_id)
+    pub fn with_runtime_filters(mut self, runtime_filters: Vec<(PhysicalExprRef, String)>) -> Self {
+        self.runtime_filters = runtime_filters;
+        self
+    }
+
+    pub fn runtime_filters(&self) -> &[(PhysicalExprRef, String)] {
+        &self.runtime_filters
+    }
+
+    /// creates the execution context used when this filter is fused into the
+    /// projection above it. metrics of the whole fused filter chain are
+    /// recorded on the topmost filter.
+    pub(crate) fn fused_execution_context(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Arc<ExecutionContext> {
+        ExecutionContext::new(context, partition, self.schema(), &self.metrics)
+    }
+
+    /// executes the filter without compacting filtered batches, the returned
+    /// selections are materialized by the consumer. filters directly under
+    /// this one are also executed lazily, so stacked filters only refine the
+    /// selections.
+    pub fn execute_selected(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableSelectedBatchStream> {
+        let exec_ctx = ExecutionContext::new(context, partition, self.schema(), &self.metrics);
+        self.execute_selected_with_ctx(exec_ctx)
+    }
+
+    fn execute_selected_with_ctx(
+        &self,
+        exec_ctx: Arc<ExecutionContext>,
+    ) -> Result<SendableSelectedBatchStream> {
+        let input = match downcast_any!(self.input, FilterExec) {
+            Ok(filter_exec) => {
+                filter_exec.execute_selected(exec_ctx.partition_id(), exec_ctx.task_ctx())?
+            }
+            Err(_) => exec_ctx
+                .execute_with_input_stats(&self.input)?
+                .map(|batch| batch.map(SelectedBatch::new))
+                .boxed(),
+        };
+        let runtime_filter_pruner = (!self.runtime_filters.is_empty())
+            .then(|| RuntimeFilterPruner::new(self.runtime_filters.clone(), &exec_ctx));
+        execute_filter(
+            input,
+            self.predicates.clone(),
+            runtime_filter_pruner,
+            exec_ctx,
+        )
+    }
 }
 
 impl DisplayAs for FilterExec {
@@ -116,10 +180,10 @@ impl ExecutionPlan for FilterExec {
         self: Arc<Self>,
         children: Vec<Arc<dyn ExecutionPlan>>,
     ) -> Result<Arc<dyn ExecutionPlan>> {
-        Ok(Arc::new(Self::try_new(
-            self.predicates.clone(),
-            children[0].clone(),
-        )?))
+        Ok(Arc::new(
+            Self::try_new(self.predicates.clone(), children[0].clone())?
+                .with_runtime_filters(self.runtime_filters.clone()),
+        ))
     }
 
     fn execute(
@@ -127,11 +191,9 @@ impl ExecutionPlan for FilterExec {
         partition: usize,
         context: Arc<TaskContext>,
     ) -> Result<SendableRecordBatchStream> {
-        let predicates = self.predicates.clone();
         let exec_ctx = ExecutionContext::new(context, partition, self.schema(), &self.metrics);
-        let input = exec_ctx.execute_with_input_stats(&self.input)?;
-        let filtered = execute_filter(input, predicates, exec_ctx.clone())?;
-        Ok(exec_ctx.coalesce_with_default_batch_size(filtered))
+        let selected = self.execute_selected_with_ctx(exec_ctx.clone())?;
+        Ok(execute_materialize(selected, exec_ctx))
     }
 
     fn metrics(&self) -> Option<MetricsSet> {
@@ -139,7 +201,10 @@ impl ExecutionPlan for FilterExec {
     }
 
     fn statistics(&self) -> Result<Statistics> {
-        todo!()
+        Ok(scale_statistics(
+            self.input.statistics()?,
+            DEFAULT_FILTER_SELECTIVITY,
+        ))
     }
 }
 
@@ -169,27 +234,63 @@ impl ExecuteWithColumnPruning for FilterExec {
 }
 
 fn execute_filter(
-    mut input: SendableRecordBatchStream,
+    input: SendableSelectedBatchStream,
     predicates: Vec<PhysicalExprRef>,
+    runtime_filter_pruner: Option<RuntimeFilterPruner>,
     exec_ctx: Arc<ExecutionContext>,
-) -> Result<SendableRecordBatchStream> {
-    let input_schema = input.schema();
+) -> Result<SendableSelectedBatchStream> {
     let cached_exprs_evaluator =
-        CachedExprsEvaluator::try_new(predicates, vec![], input_schema.clone())?;
+        CachedExprsEvaluator::try_new(predicates, vec![], exec_ctx.output_schema())?
+            .with_expr_metrics(&exec_ctx)?;
+    let input_rows = exec_ctx.register_counter_metric("filter_input_rows");
 
-    Ok(exec_ctx
+    Ok(input
+        .map(move |selected| {
+            let selected = selected?;
+            let _timer = exec_ctx.baseline_metrics().elapsed_compute().timer();
+            input_rows.add(selected.num_rows());
+            if let Some(pruner) = &runtime_filter_pruner {
+                if pruner.can_prune(selected.batch())? {
+                    return Ok(SelectedBatch::new_empty(exec_ctx.output_schema()));
+                }
+            }
+            let filtered = cached_exprs_evaluator.filter_selected(selected)?;
+            exec_ctx
+                .baseline_metrics()
+                .record_output(filtered.num_rows());
+            Ok(filtered)
+        })
+        .boxed())
+}
+
+/// compacts and coalesces the lazily filtered batches, selected rows of the
+/// coalesced batches are copied only once
+fn execute_materialize(
+    mut input: SendableSelectedBatchStream,
+    exec_ctx: Arc<ExecutionContext>,
+) -> SendableRecordBatchStream {
+    let mut coalescer = SelectedBatchCoalescer::new(exec_ctx.output_schema(), batch_size());
+    exec_ctx
         .clone()
         .output_with_sender("Filter", move |sender| async move {
             sender.exclude_time(exec_ctx.baseline_metrics().elapsed_compute());
 
-            while let Some(batch) = input.next().await.transpose()? {
+            while let Some(selected) = input.next().await.transpose()? {
+                let coalesced = {
+                    let _timer = exec_ctx.baseline_metrics().elapsed_compute().timer();
+                    coalescer.push(selected)?
+                };
+                if let Some(batch) = coalesced {
+                    sender.send(batch).await;
+                }
+            }
+            let coalesced = {
                 let _timer = exec_ctx.baseline_metrics().elapsed_compute().timer();
-                let filtered_batch = cached_exprs_evaluator.filter(&batch)?;
-                exec_ctx
-                    .baseline_metrics()
-                    .record_output(filtered_batch.num_rows());
-                sender.send(filtered_batch).await;
+                coalescer.finish()?
+            };
+            if let Some(batch) = coalesced {
+                sender.send(batch).await;
             }
             Ok(())
-        }))
+        })
 }
@@ -0,0 +1,85 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use arrow::{array::*, buffer::NullBuffer, record_batch::RecordBatch};
+use datafusion::{common::Result, physical_expr::PhysicalExpr};
+use itertools::Itertools;
+
+use crate::generate::{GeneratedRows, Generator};
+
+/// inline(array<struct>): generates one row for each struct element, null
+/// elements generate rows of nulls.
+#[derive(Debug)]
+pub struct Inline {
+    child: Arc<dyn PhysicalExpr>,
+}
+
+impl Inline {
+    pub fn new(child: Arc<dyn PhysicalExpr>) -> Self {
+        Self { child }
+    }
+}
+
+impl Generator for Inline {
+    fn exprs(&self) -> Vec<Arc<dyn PhysicalExpr>> {
+        vec![self.child.clone()]
+    }
+
+    fn with_new_exprs(&self, exprs: Vec<Arc<dyn PhysicalExpr>>) -> Result<Arc<dyn Generator>> {
+        Ok(Arc::new(Self {
+            child: exprs[0].clone(),
+        }))
+    }
+
+    fn eval(&self, batch: &RecordBatch) -> Result<GeneratedRows> {
+        let input_array = self.child.evaluate(batch)?.into_array(batch.num_rows())?;
+        let list = as_list_array(&input_array);
+        let value_offsets = list.value_offsets();
+        let mut orig_row_id_builder = Int32Builder::new();
+        let mut value_idx_builder = UInt32Builder::new();
+
+        // build row_id and value index arrays, null lists may have non-empty
+        // value ranges, so values are taken by indices
+        for (orig_row_id, (&start, &end)) in value_offsets.iter().tuple_windows().enumerate() {
+            if list.is_valid(orig_row_id) && start < end {
+                for i in start..end {
+                    orig_row_id_builder.append_value(orig_row_id as i32);
+                    value_idx_builder.append_value(i as u32);
+                }
+            }
+        }
+
+        let orig_row_ids = orig_row_id_builder.finish();
+        let values = arrow::compute::take(list.values(), &value_idx_builder.finish(), None)?;
+        let structs = as_struct_array(&values);
+
+        // fields of null struct elements are also null
+        let cols = structs
+            .columns()
+            .iter()
+            .map(|col| {
+                if structs.null_count() == 0 {
+                    return Ok(col.clone());
+                }
+                let nulls = NullBuffer::union(structs.nulls(), col.nulls());
+                Ok(make_array(
+                    col.to_data().into_builder().nulls(nulls).build()?,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(GeneratedRows { orig_row_ids, cols })
+    }
+}
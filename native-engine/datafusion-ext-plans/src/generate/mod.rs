@@ -13,8 +13,10 @@
 // limitations under the License.
 
 mod explode;
+mod inline;
 mod json_tuple;
 mod spark_udtf_wrapper;
+mod stack;
 
 use std::{fmt::Debug, sync::Arc};
 
@@ -32,8 +34,10 @@ use datafusion_ext_commons::{df_execution_err, df_unimplemented_err, downcast_an
 
 use crate::generate::{
     explode::{ExplodeArray, ExplodeMap},
+    inline::Inline,
     json_tuple::JsonTuple,
     spark_udtf_wrapper::SparkUDTFWrapper,
+    stack::Stack,
 };
 
 pub trait Generator: Debug + Send + Sync {
@@ -59,6 +63,8 @@ pub enum GenerateFunc {
     Explode,
     PosExplode,
     JsonTuple,
+    Inline,
+    Stack,
     UDTF,
 }
 
@@ -91,6 +97,24 @@ pub fn create_generator(
                 })
                 .collect::<Result<_>>()?,
         ))),
+        GenerateFunc::Inline => match children[0].data_type(input_schema)? {
+            DataType::List(field) if matches!(field.data_type(), DataType::Struct(..)) => {
+                Ok(Arc::new(Inline::new(children[0].clone())))
+            }
+            other => df_unimplemented_err!("unsupported inline type: {other}"),
+        },
+        GenerateFunc::Stack => {
+            let num_rows = match downcast_any!(children[0], Literal)?.value() {
+                ScalarValue::Int32(Some(n)) => *n as i64,
+                ScalarValue::Int64(Some(n)) => *n,
+                _ => df_execution_err!("stack() accepts only literal integer number of rows")?,
+            };
+            Ok(Arc::new(Stack::try_new(
+                input_schema,
+                num_rows,
+                children[1..].to_vec(),
+            )?))
+        }
         GenerateFunc::UDTF => {
             unreachable!("UDTF should be handled in create_generator")
         }
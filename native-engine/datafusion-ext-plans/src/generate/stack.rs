@@ -0,0 +1,118 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use arrow::{
+    array::*,
+    compute::interleave,
+    datatypes::{DataType, SchemaRef},
+    record_batch::RecordBatch,
+};
+use datafusion::{common::Result, physical_expr::PhysicalExpr};
+use datafusion_ext_commons::{arrow::cast::cast, df_execution_err};
+
+use crate::generate::{GeneratedRows, Generator};
+
+/// stack(n, v1, ..., vk): separates values of each input row into n rows of
+/// ceil(k / n) columns, missing values at the end are filled with nulls.
+#[derive(Debug)]
+pub struct Stack {
+    num_rows: usize,
+    values: Vec<Arc<dyn PhysicalExpr>>,
+    value_types: Vec<DataType>,
+}
+
+impl Stack {
+    pub fn try_new(
+        input_schema: &SchemaRef,
+        num_rows: i64,
+        values: Vec<Arc<dyn PhysicalExpr>>,
+    ) -> Result<Self> {
+        if num_rows <= 0 {
+            return df_execution_err!("stack() requires a positive number of rows, got {num_rows}");
+        }
+        let num_rows = num_rows as usize;
+        let num_cols = values.len().div_ceil(num_rows);
+
+        // type of each output column is the first non-null type of its values
+        let mut value_types = vec![DataType::Null; num_cols];
+        for (i, value) in values.iter().enumerate() {
+            let col_type = &mut value_types[i % num_cols];
+            if *col_type == DataType::Null {
+                *col_type = value.data_type(input_schema)?;
+            }
+        }
+        Ok(Self {
+            num_rows,
+            values,
+            value_types,
+        })
+    }
+}
+
+impl Generator for Stack {
+    fn exprs(&self) -> Vec<Arc<dyn PhysicalExpr>> {
+        self.values.clone()
+    }
+
+    fn with_new_exprs(&self, exprs: Vec<Arc<dyn PhysicalExpr>>) -> Result<Arc<dyn Generator>> {
+        Ok(Arc::new(Self {
+            num_rows: self.num_rows,
+            values: exprs,
+            value_types: self.value_types.clone(),
+        }))
+    }
+
+    fn eval(&self, batch: &RecordBatch) -> Result<GeneratedRows> {
+        let num_cols = self.value_types.len();
+        let value_arrays = self
+            .values
+            .iter()
+            .map(|value| value.evaluate(batch)?.into_array(batch.num_rows()))
+            .collect::<Result<Vec<_>>>()?;
+
+        let orig_row_ids = Int32Array::from_iter_values(
+            (0..batch.num_rows() as i32)
+                .flat_map(|row_id| itertools::repeat_n(row_id, self.num_rows)),
+        );
+        let cols = self
+            .value_types
+            .iter()
+            .enumerate()
+            .map(|(col_idx, value_type)| {
+                // sources: [null, values of this column...]
+                let mut sources: Vec<ArrayRef> = vec![new_null_array(value_type, 1)];
+                for value_array in value_arrays.iter().skip(col_idx).step_by(num_cols) {
+                    sources.push(match value_array.data_type() {
+                        DataType::Null => new_null_array(value_type, value_array.len()),
+                        _ => cast(value_array, value_type)?,
+                    });
+                }
+                let num_sources = sources.len();
+                let indices = (0..batch.num_rows())
+                    .flat_map(|row_idx| {
+                        (0..self.num_rows).map(move |i| match i + 1 < num_sources {
+                            true => (i + 1, row_idx),
+                            false => (0, 0),
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                let sources = sources.iter().map(|s| s.as_ref()).collect::<Vec<_>>();
+                Ok(interleave(&sources, &indices)?)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(GeneratedRows { orig_row_ids, cols })
+    }
+}
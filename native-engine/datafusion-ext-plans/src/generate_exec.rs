@@ -40,7 +40,10 @@ use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
 
 use crate::{
-    common::{execution_context::ExecutionContext, timer_helper::TimerHelper},
+    common::{
+        execution_context::ExecutionContext, partitioning::derive_partitioning,
+        timer_helper::TimerHelper,
+    },
     generate::Generator,
 };
 
@@ -131,7 +134,15 @@ impl ExecutionPlan for GenerateExec {
         self.props.get_or_init(|| {
             PlanProperties::new(
                 EquivalenceProperties::new(self.schema()),
-                self.input.output_partitioning().clone(),
+                derive_partitioning(
+                    self.input.output_partitioning(),
+                    &self.output_schema,
+                    |col| {
+                        self.required_child_output_cols
+                            .iter()
+                            .position(|required| required.index() == col.index())
+                    },
+                ),
                 ExecutionMode::Bounded,
             )
         })
@@ -174,7 +185,8 @@ impl ExecutionPlan for GenerateExec {
     }
 
     fn statistics(&self) -> Result<Statistics> {
-        todo!()
+        // number of generated rows is unknown
+        Ok(Statistics::new_unknown(&self.output_schema))
     }
 }
 
@@ -360,11 +372,11 @@ fn execute_generate(
 mod test {
     use std::sync::Arc;
 
-    use arrow::{array::*, datatypes::*, record_batch::RecordBatch};
+    use arrow::{array::*, buffer::OffsetBuffer, datatypes::*, record_batch::RecordBatch};
     use datafusion::{
         assert_batches_eq,
-        common::Result,
-        physical_expr::expressions::Column,
+        common::{Result, ScalarValue},
+        physical_expr::expressions::{Column, Literal},
         physical_plan::{common, memory::MemoryExec, ExecutionPlan},
         prelude::SessionContext,
     };
@@ -552,4 +564,114 @@ mod test {
         assert_batches_eq!(expected, &batches);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_inline_and_stack() -> Result<()> {
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+
+        let struct_fields = Fields::from(vec![
+            Field::new("x", DataType::Int32, true),
+            Field::new("y", DataType::Utf8, true),
+        ]);
+        let structs = StructArray::try_new(
+            struct_fields.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![Some(1), Some(2), None, Some(4)])),
+                Arc::new(StringArray::from(vec![
+                    Some("p"),
+                    None,
+                    Some("r"),
+                    Some("s"),
+                ])),
+            ],
+            Some(vec![true, false, true, true].into()),
+        )?;
+        let col_a: ArrayRef = Arc::new(Int32Array::from(vec![Some(10), Some(20), Some(30)]));
+        let col_b: ArrayRef = Arc::new(ListArray::try_new(
+            Arc::new(Field::new("item", DataType::Struct(struct_fields), true)),
+            OffsetBuffer::new(vec![0, 2, 3, 4].into()),
+            Arc::new(structs),
+            Some(vec![true, false, true].into()),
+        )?);
+        let col_c: ArrayRef = Arc::new(StringArray::from(vec![Some("u"), Some("v"), None]));
+        let input_batch = RecordBatch::try_from_iter_with_nullable(vec![
+            ("a", col_a, true),
+            ("b", col_b, true),
+            ("c", col_c, true),
+        ])?;
+        let input = Arc::new(MemoryExec::try_new(
+            &[vec![input_batch.clone()]],
+            input_batch.schema(),
+            None,
+        )?);
+
+        // inline (outer)
+        let generator = create_generator(
+            &input.schema(),
+            GenerateFunc::Inline,
+            vec![Arc::new(Column::new("b", 1))],
+        )?;
+        let generate = Arc::new(GenerateExec::try_new(
+            input.clone(),
+            generator,
+            vec![Column::new("a", 0)],
+            Arc::new(Schema::new(vec![
+                Field::new("x", DataType::Int32, true),
+                Field::new("y", DataType::Utf8, true),
+            ])),
+            true,
+        )?);
+        let output = generate.execute(0, task_ctx.clone())?;
+        let batches = common::collect(output).await?;
+        let expected = vec![
+            "+----+---+---+",
+            "| a  | x | y |",
+            "+----+---+---+",
+            "| 10 | 1 | p |",
+            "| 10 |   |   |",
+            "| 20 |   |   |",
+            "| 30 | 4 | s |",
+            "+----+---+---+",
+        ];
+        assert_batches_eq!(expected, &batches);
+
+        // stack(2, a, c, a)
+        let generator = create_generator(
+            &input.schema(),
+            GenerateFunc::Stack,
+            vec![
+                Arc::new(Literal::new(ScalarValue::Int32(Some(2)))),
+                Arc::new(Column::new("a", 0)),
+                Arc::new(Column::new("c", 2)),
+                Arc::new(Column::new("a", 0)),
+            ],
+        )?;
+        let generate = Arc::new(GenerateExec::try_new(
+            input.clone(),
+            generator,
+            vec![Column::new("a", 0)],
+            Arc::new(Schema::new(vec![
+                Field::new("col0", DataType::Int32, true),
+                Field::new("col1", DataType::Utf8, true),
+            ])),
+            false,
+        )?);
+        let output = generate.execute(0, task_ctx.clone())?;
+        let batches = common::collect(output).await?;
+        let expected = vec![
+            "+----+------+------+",
+            "| a  | col0 | col1 |",
+            "+----+------+------+",
+            "| 10 | 10   | u    |",
+            "| 10 | 10   |      |",
+            "| 20 | 20   | v    |",
+            "| 20 | 20   |      |",
+            "| 30 | 30   |      |",
+            "| 30 | 30   |      |",
+            "+----+------+------+",
+        ];
+        assert_batches_eq!(expected, &batches);
+        Ok(())
+    }
 }
@@ -0,0 +1,739 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{any::Any, fmt::Formatter, sync::Arc};
+
+use arrow::{
+    array::{new_null_array, ArrayRef, RecordBatch, RecordBatchOptions, UInt32Array},
+    compute::{concat_batches, sort_to_indices, SortOptions},
+    datatypes::{DataType, SchemaRef},
+    row::{Row, RowConverter, Rows, SortField},
+};
+use datafusion::{
+    common::{JoinSide, Result, Statistics},
+    execution::context::TaskContext,
+    physical_expr::{EquivalenceProperties, PhysicalExprRef, PhysicalSortExpr},
+    physical_plan::{
+        metrics::{ExecutionPlanMetricsSet, MetricsSet},
+        DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, ExecutionPlanProperties,
+        PlanProperties, SendableRecordBatchStream,
+    },
+};
+use datafusion_ext_commons::{
+    arrow::selection::{take_batch, take_cols},
+    batch_size, df_execution_err,
+};
+use futures::{StreamExt, TryStreamExt};
+use once_cell::sync::OnceCell;
+
+use crate::{
+    common::{
+        column_pruning::ExecuteWithColumnPruning,
+        execution_context::{ExecutionContext, WrappedRecordBatchSender},
+        partitioning::{derive_join_partitioning, derive_partitioning},
+        stream_exec::RecordBatchStreamExec,
+        timer_helper::TimerHelper,
+    },
+    joins::{join_hash_map::join_data_schema, join_utils::JoinType, JoinProjection},
+    sort_exec::SortExec,
+};
+
+/// Joins rows whose intervals overlap, used for conditions like
+/// `a.ts BETWEEN b.start AND b.end` (a point is an interval with equal start
+/// and end) or `a.start < b.end AND b.start < a.end`, which would otherwise
+/// be joined by nested loops.
+///
+/// the broadcast side is collected in memory and sorted by interval start,
+/// the streamed side is sorted by interval start with the spillable sorter.
+/// streamed rows are joined in order with a sweep line: built rows are
+/// activated once started and evicted once ended, so each streamed row is
+/// only compared with the active built rows and built rows starting within
+/// its interval.
+///
+/// condition: `left.start <(=) right.end AND right.start <(=) left.end`, where
+/// `<=` is used for the side whose end is inclusive. all start/end
+/// expressions must have the same data type, rows with null bounds never
+/// match.
+#[derive(Debug)]
+pub struct IntervalJoinExec {
+    left: Arc<dyn ExecutionPlan>,
+    right: Arc<dyn ExecutionPlan>,
+    join_type: JoinType,
+    broadcast_side: JoinSide,
+    left_bounds: (PhysicalExprRef, PhysicalExprRef),
+    right_bounds: (PhysicalExprRef, PhysicalExprRef),
+    left_end_inclusive: bool,
+    right_end_inclusive: bool,
+    schema: SchemaRef,
+    metrics: ExecutionPlanMetricsSet,
+    props: OnceCell<PlanProperties>,
+}
+
+impl IntervalJoinExec {
+    pub fn try_new(
+        schema: SchemaRef,
+        left: Arc<dyn ExecutionPlan>,
+        right: Arc<dyn ExecutionPlan>,
+        join_type: JoinType,
+        broadcast_side: JoinSide,
+        left_bounds: (PhysicalExprRef, PhysicalExprRef),
+        right_bounds: (PhysicalExprRef, PhysicalExprRef),
+        left_end_inclusive: bool,
+        right_end_inclusive: bool,
+    ) -> Result<Self> {
+        if !matches!(
+            join_type,
+            JoinType::Inner | JoinType::Left | JoinType::Right | JoinType::Full
+        ) {
+            return df_execution_err!("interval join does not support join type: {join_type:?}");
+        }
+        Ok(Self {
+            left,
+            right,
+            join_type,
+            broadcast_side,
+            left_bounds,
+            right_bounds,
+            left_end_inclusive,
+            right_end_inclusive,
+            schema,
+            metrics: ExecutionPlanMetricsSet::new(),
+            props: OnceCell::new(),
+        })
+    }
+
+    fn bound_data_type(&self) -> Result<DataType> {
+        let left_schema = self.left.schema();
+        let right_schema = self.right.schema();
+        let data_types = [
+            self.left_bounds.0.data_type(&left_schema)?,
+            self.left_bounds.1.data_type(&left_schema)?,
+            self.right_bounds.0.data_type(&right_schema)?,
+            self.right_bounds.1.data_type(&right_schema)?,
+        ];
+        if data_types.iter().any(|dt| dt != &data_types[0]) {
+            return df_execution_err!("interval join bounds type mismatched: {data_types:?}");
+        }
+        Ok(data_types[0].clone())
+    }
+
+    fn execute_with_projection(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+        projection: Vec<usize>,
+    ) -> Result<SendableRecordBatchStream> {
+        let (left_schema, right_schema) = match self.broadcast_side {
+            JoinSide::Left => (join_data_schema(&self.left.schema()), self.right.schema()),
+            JoinSide::Right => (self.left.schema(), join_data_schema(&self.right.schema())),
+        };
+        let projection = JoinProjection::try_new(
+            self.join_type,
+            &self.schema,
+            &left_schema,
+            &right_schema,
+            &projection,
+        )?;
+        let exec_ctx =
+            ExecutionContext::new(context, partition, projection.schema.clone(), &self.metrics);
+        let (streamed_plan, built_plan) = match self.broadcast_side {
+            JoinSide::Left => (self.right.clone(), self.left.clone()),
+            JoinSide::Right => (self.left.clone(), self.right.clone()),
+        };
+        let (streamed_bounds, built_bounds) = match self.broadcast_side {
+            JoinSide::Left => (self.right_bounds.clone(), self.left_bounds.clone()),
+            JoinSide::Right => (self.left_bounds.clone(), self.right_bounds.clone()),
+        };
+        let (streamed_end_inclusive, built_end_inclusive) = match self.broadcast_side {
+            JoinSide::Left => (self.right_end_inclusive, self.left_end_inclusive),
+            JoinSide::Right => (self.left_end_inclusive, self.right_end_inclusive),
+        };
+        let bound_converter = RowConverter::new(vec![SortField::new_with_options(
+            self.bound_data_type()?,
+            SortOptions::default(),
+        )])?;
+        let join_type = self.join_type;
+        let broadcast_side = self.broadcast_side;
+
+        let exec_ctx_cloned = exec_ctx.clone();
+        let output =
+            exec_ctx
+                .clone()
+                .output_with_sender("IntervalJoin", move |sender| async move {
+                    let elapsed_compute =
+                        exec_ctx_cloned.baseline_metrics().elapsed_compute().clone();
+                    let _timer = elapsed_compute.timer();
+                    sender.exclude_time(&elapsed_compute);
+
+                    // collect broadcast side in memory and sort by interval start
+                    let built_schema = join_data_schema(&built_plan.schema());
+                    let built_data_indices = (0..built_schema.fields().len()).collect::<Vec<_>>();
+                    let built_batches: Vec<RecordBatch> = elapsed_compute
+                        .exclude_timer_async(
+                            exec_ctx_cloned
+                                .execute_with_input_stats(&built_plan)?
+                                .try_collect(),
+                        )
+                        .await?;
+                    let built_data_batches = built_batches
+                        .iter()
+                        .map(|batch| batch.project(&built_data_indices))
+                        .collect::<std::result::Result<Vec<_>, _>>()?;
+                    let built = concat_batches(&built_schema, &built_data_batches)?;
+                    let built_starts = built_bounds
+                        .0
+                        .evaluate(&built)?
+                        .into_array(built.num_rows())?;
+                    let built = take_batch(built, sort_to_indices(&built_starts, None, None)?)?;
+                    let built_bounds =
+                        IntervalBounds::try_new(&bound_converter, &built_bounds, &built)?;
+
+                    // sort streamed side by interval start
+                    let streamed_sort_expr = PhysicalSortExpr {
+                        expr: streamed_bounds.0.clone(),
+                        options: SortOptions::default(),
+                    };
+                    let streamed_input =
+                        exec_ctx_cloned.execute_with_input_stats(&streamed_plan)?;
+                    let mut streamed = SortExec::new(
+                        Arc::new(RecordBatchStreamExec::new(streamed_input)),
+                        vec![streamed_sort_expr],
+                        None,
+                    )
+                    .execute(exec_ctx_cloned.partition_id(), exec_ctx_cloned.task_ctx())?;
+
+                    let mut joiner = IntervalJoiner {
+                        join_type,
+                        broadcast_side,
+                        projection,
+                        streamed_end_inclusive,
+                        built_end_inclusive,
+                        built_matched: vec![false; built.num_rows()],
+                        built,
+                        built_bounds,
+                        built_cursor: 0,
+                        active_built_rows: vec![],
+                        sender,
+                        num_output_rows: 0,
+                    };
+                    while let Some(batch) = elapsed_compute
+                        .exclude_timer_async(streamed.next())
+                        .await
+                        .transpose()?
+                    {
+                        let bounds =
+                            IntervalBounds::try_new(&bound_converter, &streamed_bounds, &batch)?;
+                        joiner.join_streamed_batch(batch, bounds).await?;
+                    }
+                    joiner.finish().await?;
+                    exec_ctx_cloned
+                        .baseline_metrics()
+                        .record_output(joiner.num_output_rows);
+                    Ok(())
+                });
+        Ok(exec_ctx.coalesce_with_default_batch_size(output))
+    }
+}
+
+impl ExecuteWithColumnPruning for IntervalJoinExec {
+    fn execute_projected(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+        projection: &[usize],
+    ) -> Result<SendableRecordBatchStream> {
+        self.execute_with_projection(partition, context, projection.to_vec())
+    }
+}
+
+impl DisplayAs for IntervalJoinExec {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "IntervalJoin: join_type={:?}, broadcast_side={:?}, left=[{}, {}{}, right=[{}, {}{}",
+            self.join_type,
+            self.broadcast_side,
+            self.left_bounds.0,
+            self.left_bounds.1,
+            if self.left_end_inclusive { "]" } else { ")" },
+            self.right_bounds.0,
+            self.right_bounds.1,
+            if self.right_end_inclusive { "]" } else { ")" },
+        )
+    }
+}
+
+impl ExecutionPlan for IntervalJoinExec {
+    fn name(&self) -> &str {
+        "IntervalJoin"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.props.get_or_init(|| {
+            PlanProperties::new(
+                EquivalenceProperties::new(self.schema()),
+                match self.broadcast_side {
+                    JoinSide::Left => {
+                        derive_join_partitioning(&self.left, &self.right, &self.schema)
+                    }
+                    JoinSide::Right => {
+                        derive_partitioning(self.left.output_partitioning(), &self.schema, |col| {
+                            Some(col.index())
+                        })
+                    }
+                },
+                ExecutionMode::Bounded,
+            )
+        })
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.left, &self.right]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(Self::try_new(
+            self.schema.clone(),
+            children[0].clone(),
+            children[1].clone(),
+            self.join_type,
+            self.broadcast_side,
+            self.left_bounds.clone(),
+            self.right_bounds.clone(),
+            self.left_end_inclusive,
+            self.right_end_inclusive,
+        )?))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let projection = (0..self.schema.fields().len()).collect();
+        self.execute_with_projection(partition, context, projection)
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        Ok(Statistics::new_unknown(&self.schema))
+    }
+}
+
+/// Interval bounds of a batch in comparable row format
+struct IntervalBounds {
+    starts: Rows,
+    ends: Rows,
+    valid: Vec<bool>,
+}
+
+impl IntervalBounds {
+    fn try_new(
+        converter: &RowConverter,
+        bounds: &(PhysicalExprRef, PhysicalExprRef),
+        batch: &RecordBatch,
+    ) -> Result<Self> {
+        let num_rows = batch.num_rows();
+        let starts = bounds.0.evaluate(batch)?.into_array(num_rows)?;
+        let ends = bounds.1.evaluate(batch)?.into_array(num_rows)?;
+        let valid = (0..num_rows)
+            .map(|i| starts.is_valid(i) && ends.is_valid(i))
+            .collect();
+        Ok(Self {
+            starts: converter.convert_columns(&[starts])?,
+            ends: converter.convert_columns(&[ends])?,
+            valid,
+        })
+    }
+
+    fn start(&self, idx: usize) -> Row {
+        self.starts.row(idx)
+    }
+
+    fn end(&self, idx: usize) -> Row {
+        self.ends.row(idx)
+    }
+}
+
+/// returns whether a start is before an end, or at the end if inclusive
+fn starts_before(start: Row, end: Row, end_inclusive: bool) -> bool {
+    if end_inclusive {
+        start <= end
+    } else {
+        start < end
+    }
+}
+
+struct IntervalJoiner {
+    join_type: JoinType,
+    broadcast_side: JoinSide,
+    projection: JoinProjection,
+    streamed_end_inclusive: bool,
+    built_end_inclusive: bool,
+    built: RecordBatch,
+    built_bounds: IntervalBounds,
+    built_matched: Vec<bool>,
+    built_cursor: usize,           // next built row to be activated
+    active_built_rows: Vec<usize>, // started built rows which are not ended
+    sender: Arc<WrappedRecordBatchSender>,
+    num_output_rows: usize,
+}
+
+impl IntervalJoiner {
+    fn streamed_side(&self) -> JoinSide {
+        match self.broadcast_side {
+            JoinSide::Left => JoinSide::Right,
+            JoinSide::Right => JoinSide::Left,
+        }
+    }
+
+    fn outputs_unmatched(&self, side: JoinSide) -> bool {
+        matches!(
+            (self.join_type, side),
+            (JoinType::Left | JoinType::Full, JoinSide::Left)
+                | (JoinType::Right | JoinType::Full, JoinSide::Right)
+        )
+    }
+
+    fn matches(
+        &self,
+        streamed_bounds: &IntervalBounds,
+        streamed_idx: usize,
+        built_idx: usize,
+    ) -> bool {
+        self.built_bounds.valid[built_idx]
+            && starts_before(
+                self.built_bounds.start(built_idx),
+                streamed_bounds.end(streamed_idx),
+                self.streamed_end_inclusive,
+            )
+            && starts_before(
+                streamed_bounds.start(streamed_idx),
+                self.built_bounds.end(built_idx),
+                self.built_end_inclusive,
+            )
+    }
+
+    async fn join_streamed_batch(
+        &mut self,
+        streamed: RecordBatch,
+        streamed_bounds: IntervalBounds,
+    ) -> Result<()> {
+        let num_built_rows = self.built.num_rows();
+        let mut streamed_matched = vec![false; streamed.num_rows()];
+        let mut streamed_indices = vec![];
+        let mut built_indices = vec![];
+
+        for streamed_idx in 0..streamed.num_rows() {
+            if !streamed_bounds.valid[streamed_idx] {
+                continue;
+            }
+            let streamed_start = streamed_bounds.start(streamed_idx);
+            let streamed_end = streamed_bounds.end(streamed_idx);
+
+            // activate built rows starting before the streamed row. streamed
+            // rows are sorted by start, so the built rows are started for all
+            // later streamed rows
+            while self.built_cursor < num_built_rows
+                && self.built_bounds.start(self.built_cursor) <= streamed_start
+            {
+                if self.built_bounds.valid[self.built_cursor] {
+                    self.active_built_rows.push(self.built_cursor);
+                }
+                self.built_cursor += 1;
+            }
+
+            // evict built rows ending before the streamed row, they cannot
+            // match any later streamed rows
+            let built_bounds = &self.built_bounds;
+            let built_end_inclusive = self.built_end_inclusive;
+            self.active_built_rows.retain(|&built_idx| {
+                starts_before(
+                    streamed_start,
+                    built_bounds.end(built_idx),
+                    built_end_inclusive,
+                )
+            });
+
+            // candidates are active built rows and built rows starting within
+            // the streamed row
+            let candidates = self.active_built_rows.iter().copied().chain(
+                (self.built_cursor..num_built_rows)
+                    .take_while(|&built_idx| self.built_bounds.start(built_idx) <= streamed_end),
+            );
+            let matched_built_rows = candidates
+                .filter(|&built_idx| self.matches(&streamed_bounds, streamed_idx, built_idx))
+                .collect::<Vec<_>>();
+            for built_idx in matched_built_rows {
+                streamed_matched[streamed_idx] = true;
+                self.built_matched[built_idx] = true;
+                streamed_indices.push(streamed_idx as u32);
+                built_indices.push(built_idx as u32);
+            }
+            if streamed_indices.len() >= batch_size() {
+                let streamed_indices = std::mem::take(&mut streamed_indices);
+                let built_indices = std::mem::take(&mut built_indices);
+                self.output_pairs(&streamed, streamed_indices, built_indices)
+                    .await?;
+            }
+        }
+        if !streamed_indices.is_empty() {
+            self.output_pairs(&streamed, streamed_indices, built_indices)
+                .await?;
+        }
+        self.output_unmatched(self.streamed_side(), &streamed, &streamed_matched)
+            .await
+    }
+
+    async fn finish(&mut self) -> Result<()> {
+        let built = self.built.clone();
+        let built_matched = std::mem::take(&mut self.built_matched);
+        self.output_unmatched(self.broadcast_side, &built, &built_matched)
+            .await
+    }
+
+    async fn output_pairs(
+        &mut self,
+        streamed: &RecordBatch,
+        streamed_indices: Vec<u32>,
+        built_indices: Vec<u32>,
+    ) -> Result<()> {
+        let streamed_indices = UInt32Array::from(streamed_indices);
+        let built_indices = UInt32Array::from(built_indices);
+        let (left_cols, right_cols) = match self.broadcast_side {
+            JoinSide::Left => (
+                take_cols(
+                    &self.projection.project_left(self.built.columns()),
+                    built_indices,
+                )?,
+                take_cols(
+                    &self.projection.project_right(streamed.columns()),
+                    streamed_indices,
+                )?,
+            ),
+            JoinSide::Right => (
+                take_cols(
+                    &self.projection.project_left(streamed.columns()),
+                    streamed_indices,
+                )?,
+                take_cols(
+                    &self.projection.project_right(self.built.columns()),
+                    built_indices,
+                )?,
+            ),
+        };
+        self.output([left_cols, right_cols].concat()).await
+    }
+
+    async fn output_unmatched(
+        &mut self,
+        side: JoinSide,
+        batch: &RecordBatch,
+        matched: &[bool],
+    ) -> Result<()> {
+        if !self.outputs_unmatched(side) {
+            return Ok(());
+        }
+        let indices = UInt32Array::from_iter_values(
+            matched
+                .iter()
+                .enumerate()
+                .filter(|(_, &m)| !m)
+                .map(|(idx, _)| idx as u32),
+        );
+        if indices.is_empty() {
+            return Ok(());
+        }
+
+        // columns of the other side are filled with nulls
+        let num_rows = indices.len();
+        let nulls = |schema: &SchemaRef| {
+            schema
+                .fields()
+                .iter()
+                .map(|field| new_null_array(field.data_type(), num_rows))
+                .collect::<Vec<_>>()
+        };
+        let output_cols = match side {
+            JoinSide::Left => [
+                take_cols(&self.projection.project_left(batch.columns()), indices)?,
+                nulls(&self.projection.right_schema),
+            ]
+            .concat(),
+            JoinSide::Right => [
+                nulls(&self.projection.left_schema),
+                take_cols(&self.projection.project_right(batch.columns()), indices)?,
+            ]
+            .concat(),
+        };
+        self.output(output_cols).await
+    }
+
+    async fn output(&mut self, cols: Vec<ArrayRef>) -> Result<()> {
+        let num_rows = cols.first().map(|col| col.len()).unwrap_or_default();
+        let output_batch = RecordBatch::try_new_with_options(
+            self.projection.schema.clone(),
+            cols,
+            &RecordBatchOptions::new().with_row_count(Some(num_rows)),
+        )?;
+        self.num_output_rows += num_rows;
+        self.sender.send(output_batch).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{Int32Array, RecordBatch},
+        datatypes::{DataType, Field, Schema},
+    };
+    use datafusion::{
+        assert_batches_sorted_eq,
+        common::{JoinSide, Result},
+        physical_expr::expressions::col,
+        physical_plan::{common, memory::MemoryExec, ExecutionPlan},
+        prelude::SessionContext,
+    };
+
+    use crate::{
+        broadcast_join_build_hash_map_exec::BroadcastJoinBuildHashMapExec,
+        interval_join_exec::IntervalJoinExec,
+        joins::join_utils::{build_join_schema, JoinType},
+        memmgr::MemManager,
+    };
+
+    fn build_table(
+        names: (&str, &str),
+        values: (Vec<Option<i32>>, Vec<Option<i32>>),
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(names.0, DataType::Int32, true),
+            Field::new(names.1, DataType::Int32, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(values.0)),
+                Arc::new(Int32Array::from(values.1)),
+            ],
+        )?;
+        Ok(Arc::new(MemoryExec::try_new(&[vec![batch]], schema, None)?))
+    }
+
+    async fn join_collect(
+        join_type: JoinType,
+        broadcast_side: JoinSide,
+        right_end_inclusive: bool,
+    ) -> Result<Vec<RecordBatch>> {
+        MemManager::init(1000000);
+
+        // left: points, right: intervals
+        let left = build_table(
+            ("ts", "ts2"),
+            (
+                vec![Some(5), Some(1), Some(10), Some(7), None],
+                vec![Some(5), Some(1), Some(10), Some(7), None],
+            ),
+        )?;
+        let right = build_table(
+            ("start", "end"),
+            (
+                vec![Some(4), Some(0), Some(6), Some(3), Some(8)],
+                vec![Some(7), Some(2), Some(6), None, Some(10)],
+            ),
+        )?;
+        let left_bounds = (col("ts", &left.schema())?, col("ts2", &left.schema())?);
+        let right_bounds = (col("start", &right.schema())?, col("end", &right.schema())?);
+
+        let schema = build_join_schema(&left.schema(), &right.schema(), join_type)?;
+        let (left, right): (Arc<dyn ExecutionPlan>, Arc<dyn ExecutionPlan>) = match broadcast_side {
+            JoinSide::Left => (
+                Arc::new(BroadcastJoinBuildHashMapExec::new(left, vec![])),
+                right,
+            ),
+            JoinSide::Right => (
+                left,
+                Arc::new(BroadcastJoinBuildHashMapExec::new(right, vec![])),
+            ),
+        };
+        let join = IntervalJoinExec::try_new(
+            schema,
+            left,
+            right,
+            join_type,
+            broadcast_side,
+            left_bounds,
+            right_bounds,
+            true,
+            right_end_inclusive,
+        )?;
+        let stream = join.execute(0, SessionContext::new().task_ctx())?;
+        common::collect(stream).await
+    }
+
+    #[tokio::test]
+    async fn test_interval_join() -> Result<()> {
+        for broadcast_side in [JoinSide::Left, JoinSide::Right] {
+            // ts BETWEEN start AND end
+            let batches = join_collect(JoinType::Inner, broadcast_side, true).await?;
+            let expected = vec![
+                "+----+-----+-------+-----+",
+                "| ts | ts2 | start | end |",
+                "+----+-----+-------+-----+",
+                "| 1  | 1   | 0     | 2   |",
+                "| 10 | 10  | 8     | 10  |",
+                "| 5  | 5   | 4     | 7   |",
+                "| 7  | 7   | 4     | 7   |",
+                "+----+-----+-------+-----+",
+            ];
+            assert_batches_sorted_eq!(expected, &batches);
+
+            // ts >= start AND ts < end
+            let batches = join_collect(JoinType::Full, broadcast_side, false).await?;
+            let expected = vec![
+                "+----+-----+-------+-----+",
+                "| ts | ts2 | start | end |",
+                "+----+-----+-------+-----+",
+                "|    |     |       |     |",
+                "|    |     | 3     |     |",
+                "|    |     | 6     | 6   |",
+                "|    |     | 8     | 10  |",
+                "| 1  | 1   | 0     | 2   |",
+                "| 10 | 10  |       |     |",
+                "| 5  | 5   | 4     | 7   |",
+                "| 7  | 7   |       |     |",
+                "+----+-----+-------+-----+",
+            ];
+            assert_batches_sorted_eq!(expected, &batches);
+        }
+        Ok(())
+    }
+}
@@ -14,10 +14,15 @@
 
 use std::{
     any::Any,
+    collections::VecDeque,
     fmt::{Debug, Formatter},
     fs::File,
     io::{BufReader, Cursor, Read, Seek, SeekFrom},
-    sync::{mpsc::Receiver, Arc},
+    sync::{
+        atomic::{AtomicUsize, Ordering::SeqCst},
+        mpsc::Receiver,
+        Arc, Weak,
+    },
 };
 
 use arrow::{
@@ -26,8 +31,11 @@ use arrow::{
 };
 use async_trait::async_trait;
 use blaze_jni_bridge::{
-    is_task_running, jni_call, jni_call_static, jni_get_byte_array_region, jni_get_direct_buffer,
-    jni_get_string, jni_new_direct_byte_buffer, jni_new_global_ref, jni_new_string,
+    batched::BatchedScalaIterator,
+    conf::{self, IntConf},
+    is_task_running, jni_call, jni_call_static, jni_call_with_retry, jni_get_byte_array_region,
+    jni_get_direct_buffer, jni_get_string, jni_new_direct_byte_buffer, jni_new_global_ref,
+    jni_new_string,
 };
 use datafusion::{
     error::{DataFusionError, Result},
@@ -36,7 +44,7 @@ use datafusion::{
     physical_plan::{
         metrics::{ExecutionPlanMetricsSet, MetricsSet},
         DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan,
-        Partitioning::UnknownPartitioning,
+        Partitioning::{self, UnknownPartitioning},
         PlanProperties, SendableRecordBatchStream, Statistics,
     },
 };
@@ -48,9 +56,12 @@ use jni::objects::{GlobalRef, JObject};
 use once_cell::sync::OnceCell;
 use tokio::task::JoinHandle;
 
-use crate::common::{
-    execution_context::ExecutionContext, ipc_compression::IpcCompressionReader,
-    timer_helper::TimerHelper,
+use crate::{
+    common::{
+        execution_context::ExecutionContext, ipc_compression::IpcCompressionReader,
+        timer_helper::TimerHelper,
+    },
+    memmgr::{MemConsumer, MemConsumerInfo, MemManager},
 };
 
 #[derive(Debug, Clone)]
@@ -58,19 +69,25 @@ pub struct IpcReaderExec {
     pub num_partitions: usize,
     pub ipc_provider_resource_id: String,
     pub schema: SchemaRef,
+    pub partitioning: Option<Partitioning>,
     pub metrics: ExecutionPlanMetricsSet,
     props: OnceCell<PlanProperties>,
 }
 impl IpcReaderExec {
+    /// `partitioning` is the original spark-side partitioning of the data
+    /// being read (e.g. hash partitioning of a shuffle), reported as output
+    /// partitioning instead of UnknownPartitioning if present
     pub fn new(
         num_partitions: usize,
         ipc_provider_resource_id: String,
         schema: SchemaRef,
+        partitioning: Option<Partitioning>,
     ) -> IpcReaderExec {
         IpcReaderExec {
             num_partitions,
             ipc_provider_resource_id,
             schema,
+            partitioning,
             metrics: ExecutionPlanMetricsSet::new(),
             props: OnceCell::new(),
         }
@@ -101,7 +118,9 @@ impl ExecutionPlan for IpcReaderExec {
         self.props.get_or_init(|| {
             PlanProperties::new(
                 EquivalenceProperties::new(self.schema()),
-                UnknownPartitioning(self.num_partitions),
+                self.partitioning
+                    .clone()
+                    .unwrap_or(UnknownPartitioning(self.num_partitions)),
                 ExecutionMode::Bounded,
             )
         })
@@ -119,6 +138,7 @@ impl ExecutionPlan for IpcReaderExec {
             self.num_partitions,
             self.ipc_provider_resource_id.clone(),
             self.schema.clone(),
+            self.partitioning.clone(),
         )))
     }
 
@@ -131,19 +151,35 @@ impl ExecutionPlan for IpcReaderExec {
         let elapsed_compute = exec_ctx.baseline_metrics().elapsed_compute().clone();
         let _timer = elapsed_compute.timer();
 
-        let blocks_provider = jni_call_static!(
-            JniBridge.getResource(
-                jni_new_string!(&self.ipc_provider_resource_id)?.as_obj()
-            ) -> JObject
-        )?;
-        assert!(!blocks_provider.as_obj().is_null());
-
-        let blocks_local = jni_call!(ScalaFunction0(blocks_provider.as_obj()).apply() -> JObject)?;
-        assert!(!blocks_local.as_obj().is_null());
+        // the provider is removed from the resource map when taken, so it is
+        // taken only once. invoking the provider may fail on transient errors,
+        // which is retried only if the provider can be invoked again
+        let resource_id = jni_new_string!(&self.ipc_provider_resource_id)?;
+        let blocks_provider =
+            jni_call_static!(JniBridge.getResource(resource_id.as_obj()) -> JObject)?;
+        if blocks_provider.as_obj().is_null() {
+            return df_execution_err!(
+                "IpcReaderExec: blocks provider not found: {}",
+                self.ipc_provider_resource_id,
+            );
+        }
+        let provide_blocks =
+            || jni_call!(ScalaFunction0(blocks_provider.as_obj()).apply() -> JObject);
+        let blocks_local = if jni_call_static!(
+            JniBridge.isReinvocableProvider(blocks_provider.as_obj()) -> bool
+        )? {
+            jni_call_with_retry("IpcReaderExec: getting blocks", provide_blocks)?
+        } else {
+            provide_blocks()?
+        };
+        if blocks_local.as_obj().is_null() {
+            return df_execution_err!("IpcReaderExec: blocks provider returns null");
+        }
 
         // spawn a blocking thread for reading ipcs and providing batches
         let blocks = jni_new_global_ref!(blocks_local.as_obj())?;
-        let (rx, handle) = read_ipc_into_channel(blocks, exec_ctx.clone());
+        let prefetch_depth = conf::IPC_READER_PREFETCH_DEPTH.value()?.max(0) as usize;
+        let (rx, handle) = read_ipc_into_channel(blocks, exec_ctx.clone(), prefetch_depth);
         let output = exec_ctx.output_with_sender("IpcReader", move |sender| async move {
             loop {
                 match rx.recv() {
@@ -167,19 +203,38 @@ impl ExecutionPlan for IpcReaderExec {
     }
 
     fn statistics(&self) -> Result<Statistics> {
-        todo!()
+        Ok(Statistics::new_unknown(&self.schema()))
     }
 }
 
+type IpcBlockReader = IpcCompressionReader<Box<dyn Read + Send>>;
+type DecodedBatch = (usize, Vec<ArrayRef>);
+
+/// number of decoded batches buffered for each prefetching segment, decoding
+/// of the segment is paused when the buffer is full
+const PREFETCH_BATCHES_PER_SEGMENT: usize = 4;
+
+/// a block being read, blocks are either read in the consuming thread, or
+/// fetched and decoded in advance on background tasks.
+enum IpcSegment {
+    Streaming(IpcBlockReader),
+    Prefetching(Receiver<Result<DecodedBatch>>),
+}
+
 fn read_ipc_into_channel(
     blocks: GlobalRef,
     exec_ctx: Arc<ExecutionContext>,
+    prefetch_depth: usize,
 ) -> (Receiver<RecordBatch>, JoinHandle<Result<()>>) {
     let (tx, rx) = std::sync::mpsc::sync_channel(1);
     let handle = tokio::task::spawn_blocking(move || {
         let elapsed_compute = exec_ctx.baseline_metrics().elapsed_compute().clone();
         let _timer = elapsed_compute.timer();
-        log::info!("start ipc reading");
+        log::info!("start ipc reading (prefetch depth: {prefetch_depth})");
+
+        // block handles are fetched from the jvm in batches, as many as the
+        // segments being read at the same time
+        let mut blocks = BatchedScalaIterator::new(blocks, prefetch_depth + 1);
 
         let size_counter = exec_ctx.register_counter_metric("size");
         let batch_size = batch_size();
@@ -187,29 +242,30 @@ fn read_ipc_into_channel(
         let mut staging_cols: Vec<Vec<ArrayRef>> = vec![];
         let mut staging_num_rows = 0;
         let mut staging_mem_size = 0;
-
-        while is_task_running() {
-            // get next block
-            let blocks = blocks.clone();
-            if !jni_call!(ScalaIterator(blocks.as_obj()).hasNext() -> bool)? {
-                break;
-            }
-            let next_block = jni_new_global_ref!(
-                jni_call!(ScalaIterator(blocks.as_obj()).next() -> JObject)?.as_obj()
+        let flush_staging = |staging_cols: Vec<Vec<ArrayRef>>, num_rows: usize| {
+            let coalesced_cols = staging_cols
+                .into_iter()
+                .map(|cols| coalesce_arrays_unchecked(cols[0].data_type(), &cols))
+                .collect::<Vec<_>>();
+            let batch = RecordBatch::try_new_with_options(
+                exec_ctx.output_schema(),
+                coalesced_cols,
+                &RecordBatchOptions::new().with_row_count(Some(num_rows)),
             )?;
-
-            // get ipc reader
-            let mut reader = match next_block {
-                b if jni_call!(BlazeBlockObject(b.as_obj()).hasFileSegment() -> bool)? => {
-                    get_file_reader(b.as_obj())?
-                }
-                b if jni_call!(BlazeBlockObject(b.as_obj()).hasByteBuffer() -> bool)? => {
-                    get_byte_buffer_reader(b.as_obj())?
-                }
-                b => get_channel_reader(b.as_obj())?,
-            };
-
-            while let Some((num_rows, cols)) = reader.read_batch(&exec_ctx.output_schema())? {
+            size_counter.add(batch.get_array_mem_size());
+            exec_ctx.baseline_metrics().record_output(batch.num_rows());
+            Ok::<_, DataFusionError>(elapsed_compute.exclude_timer(|| tx.send(batch)).is_ok())
+        };
+
+        let prefetch_mem = Arc::new(IpcPrefetchMemConsumer::new(exec_ctx.partition_id()));
+        MemManager::register_consumer(prefetch_mem.clone(), false);
+        let mut receiver_dropped = false;
+        let read_result = read_segments(
+            || next_block_reader(&mut blocks),
+            exec_ctx.output_schema(),
+            prefetch_depth,
+            &prefetch_mem,
+            |(num_rows, cols)| {
                 let mut cols_mem_size = 0;
                 staging_cols.resize_with(cols.len(), || vec![]);
                 for (col_idx, col) in cols.into_iter().enumerate() {
@@ -220,46 +276,178 @@ fn read_ipc_into_channel(
                 staging_mem_size += cols_mem_size;
 
                 if staging_num_rows >= batch_size || staging_mem_size >= output_batch_mem_size {
-                    let coalesced_cols = std::mem::take(&mut staging_cols)
-                        .into_iter()
-                        .map(|cols| coalesce_arrays_unchecked(cols[0].data_type(), &cols))
-                        .collect::<Vec<_>>();
-                    let batch = RecordBatch::try_new_with_options(
-                        exec_ctx.output_schema(),
-                        coalesced_cols,
-                        &RecordBatchOptions::new().with_row_count(Some(staging_num_rows)),
-                    )?;
-                    staging_num_rows = 0;
+                    let num_rows = std::mem::take(&mut staging_num_rows);
                     staging_mem_size = 0;
-                    size_counter.add(batch.get_array_mem_size());
-                    exec_ctx.baseline_metrics().record_output(batch.num_rows());
-                    if !elapsed_compute.exclude_timer(|| tx.send(batch)).is_ok() {
-                        break;
+                    if !flush_staging(std::mem::take(&mut staging_cols), num_rows)? {
+                        receiver_dropped = true;
+                        return Ok(false);
                     }
                 }
+                Ok(true)
+            },
+        );
+        MemManager::deregister_consumer(prefetch_mem.as_ref());
+        read_result?;
+
+        if staging_num_rows > 0 && !receiver_dropped {
+            let _ = flush_staging(staging_cols, staging_num_rows)?;
+        }
+        Ok::<_, DataFusionError>(())
+    });
+    (rx, handle)
+}
+
+/// reads blocks segment by segment and calls `on_batch` with every decoded
+/// batch in order, until all blocks are read or `on_batch` returns false.
+///
+/// the current segment is followed by at most `prefetch_depth` segments being
+/// decoded in background, each of which buffers at most
+/// `PREFETCH_BATCHES_PER_SEGMENT` decoded batches. the buffered batches are
+/// accounted by `prefetch_mem`. must be called in a blocking thread.
+fn read_segments(
+    mut next_reader: impl FnMut() -> Result<Option<IpcBlockReader>>,
+    schema: SchemaRef,
+    prefetch_depth: usize,
+    prefetch_mem: &Arc<IpcPrefetchMemConsumer>,
+    mut on_batch: impl FnMut(DecodedBatch) -> Result<bool>,
+) -> Result<()> {
+    let mut segments: VecDeque<IpcSegment> = VecDeque::new();
+    let mut prefetch_handles = vec![];
+    let mut read_all = || -> Result<()> {
+        let mut has_more_blocks = true;
+        while is_task_running() {
+            while has_more_blocks && segments.len() <= prefetch_depth {
+                match next_reader()? {
+                    Some(reader) if prefetch_depth > 0 => {
+                        let (rx, handle) =
+                            prefetch_segment(reader, schema.clone(), prefetch_mem.clone());
+                        segments.push_back(IpcSegment::Prefetching(rx));
+                        prefetch_handles.push(handle);
+                    }
+                    Some(reader) => segments.push_back(IpcSegment::Streaming(reader)),
+                    None => has_more_blocks = false,
+                }
+            }
+            let Some(segment) = segments.pop_front() else {
+                break;
+            };
+
+            let decoded_batches: Box<dyn Iterator<Item = Result<DecodedBatch>>> = match segment {
+                IpcSegment::Streaming(mut reader) => {
+                    let schema = schema.clone();
+                    Box::new(std::iter::from_fn(move || {
+                        reader.read_batch(&schema).transpose()
+                    }))
+                }
+                IpcSegment::Prefetching(rx) => {
+                    let prefetch_mem = prefetch_mem.clone();
+                    Box::new(rx.into_iter().map(move |decoded| {
+                        prefetch_mem.update_with_diff(-(decoded_mem_size(&decoded) as isize))?;
+                        decoded
+                    }))
+                }
+            };
+            for decoded_batch in decoded_batches {
+                if !on_batch(decoded_batch?)? {
+                    return Ok(());
+                }
             }
         }
+        Ok(())
+    };
+    let result = read_all();
+
+    // stop all prefetching segments and wait for them, so that no memory is
+    // accounted after returning
+    drop(segments);
+    for handle in prefetch_handles {
+        let _ = tokio::runtime::Handle::current().block_on(handle);
+    }
+    result
+}
 
-        if staging_num_rows > 0 {
-            let coalesced_cols = staging_cols
-                .into_iter()
-                .map(|cols| coalesce_arrays_unchecked(cols[0].data_type(), &cols))
-                .collect::<Vec<_>>();
-            let batch = RecordBatch::try_new_with_options(
-                exec_ctx.output_schema(),
-                coalesced_cols,
-                &RecordBatchOptions::new().with_row_count(Some(staging_num_rows)),
-            )?;
-            size_counter.add(batch.get_array_mem_size());
-            exec_ctx.baseline_metrics().record_output(batch.num_rows());
-            let _ = elapsed_compute.exclude_timer(|| tx.send(batch));
+/// decodes the segment on a background task, decoded batches are sent through
+/// a bounded channel
+fn prefetch_segment(
+    mut reader: IpcBlockReader,
+    schema: SchemaRef,
+    prefetch_mem: Arc<IpcPrefetchMemConsumer>,
+) -> (Receiver<Result<DecodedBatch>>, JoinHandle<()>) {
+    let (tx, rx) = std::sync::mpsc::sync_channel(PREFETCH_BATCHES_PER_SEGMENT);
+    let handle = tokio::task::spawn_blocking(move || {
+        while let Some(decoded) = reader.read_batch(&schema).transpose() {
+            let is_err = decoded.is_err();
+            let mem_size = decoded_mem_size(&decoded) as isize;
+            if let Err(err) = prefetch_mem.update_with_diff(mem_size) {
+                let _ = tx.send(Err(err));
+                return;
+            }
+            if tx.send(decoded).is_err() {
+                // the segment is no longer read
+                let _ = prefetch_mem.update_with_diff(-mem_size);
+                return;
+            }
+            if is_err {
+                return;
+            }
         }
-        Ok::<_, DataFusionError>(())
     });
     (rx, handle)
 }
 
-fn get_channel_reader(block: JObject) -> Result<IpcCompressionReader<Box<dyn Read + Send>>> {
+fn decoded_mem_size(decoded: &Result<DecodedBatch>) -> usize {
+    match decoded {
+        Ok((_, cols)) => cols.iter().map(|col| col.get_array_mem_size()).sum(),
+        Err(_) => 0,
+    }
+}
+
+/// Memory consumer of the batches decoded in advance by prefetching segments.
+/// the batches cannot be spilled, they are accounted so that other consumers
+/// can spill in time.
+struct IpcPrefetchMemConsumer {
+    name: String,
+    mem_consumer_info: Option<Weak<MemConsumerInfo>>,
+    mem_used: AtomicUsize,
+}
+
+impl IpcPrefetchMemConsumer {
+    fn new(partition_id: usize) -> Self {
+        Self {
+            name: format!("IpcReaderPrefetch[partition={partition_id}]"),
+            mem_consumer_info: None,
+            mem_used: AtomicUsize::new(0),
+        }
+    }
+
+    fn update_with_diff(&self, diff_used: isize) -> Result<()> {
+        if diff_used >= 0 {
+            self.mem_used.fetch_add(diff_used as usize, SeqCst);
+        } else {
+            self.mem_used.fetch_sub(-diff_used as usize, SeqCst);
+        }
+        tokio::runtime::Handle::current().block_on(self.update_mem_used_with_diff(diff_used))
+    }
+}
+
+#[async_trait]
+impl MemConsumer for IpcPrefetchMemConsumer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn set_consumer_info(&mut self, consumer_info: Weak<MemConsumerInfo>) {
+        self.mem_consumer_info = Some(consumer_info);
+    }
+
+    fn get_consumer_info(&self) -> &Weak<MemConsumerInfo> {
+        self.mem_consumer_info
+            .as_ref()
+            .expect("consumer info not set")
+    }
+}
+
+fn get_channel_reader(block: JObject) -> Result<IpcBlockReader> {
     let channel_reader = ReadableByteChannelReader::try_new(block)?;
 
     Ok(IpcCompressionReader::new(Box::new(
@@ -267,7 +455,7 @@ fn get_channel_reader(block: JObject) -> Result<IpcCompressionReader<Box<dyn Rea
     )))
 }
 
-fn get_file_reader(block: JObject) -> Result<IpcCompressionReader<Box<dyn Read + Send>>> {
+fn get_file_reader(block: JObject) -> Result<IpcBlockReader> {
     let path = jni_call!(BlazeBlockObject(block).getFilePath() -> JObject)?;
     let path = jni_get_string!(path.as_obj().into())?;
     let offset = jni_call!(BlazeBlockObject(block).getFileOffset() -> i64)?;
@@ -280,7 +468,7 @@ fn get_file_reader(block: JObject) -> Result<IpcCompressionReader<Box<dyn Read +
     )))
 }
 
-fn get_byte_buffer_reader(block: JObject) -> Result<IpcCompressionReader<Box<dyn Read + Send>>> {
+fn get_byte_buffer_reader(block: JObject) -> Result<IpcBlockReader> {
     let byte_buffer = jni_call!(BlazeBlockObject(block).getByteBuffer() -> JObject)?;
     if jni_call!(JavaBuffer(byte_buffer.as_obj()).isDirect() -> bool)? {
         let reader = DirectByteBufferReader::try_new(block, byte_buffer.as_obj())?;
@@ -446,3 +634,138 @@ impl Drop for HeapByteBufferReader {
         let _ = self.block;
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::Cursor,
+        sync::{atomic::Ordering::SeqCst, Arc},
+        time::Duration,
+    };
+
+    use arrow::{
+        array::{ArrayRef, AsArray, Int32Array},
+        datatypes::{DataType, Field, Int32Type, Schema},
+    };
+    use datafusion::{common::Result, error::DataFusionError};
+    use datafusion_ext_commons::arrow::array_size::ArraySize;
+
+    use crate::{
+        common::ipc_compression::{IpcCompressionReader, IpcCompressionWriter},
+        ipc_reader_exec::{
+            read_segments, IpcBlockReader, IpcPrefetchMemConsumer, PREFETCH_BATCHES_PER_SEGMENT,
+        },
+        memmgr::MemManager,
+    };
+
+    const NUM_BLOCKS: usize = 3;
+    const NUM_BATCHES_PER_BLOCK: usize = 20;
+    const NUM_ROWS_PER_BATCH: usize = 100;
+
+    fn build_blocks() -> Result<Vec<Vec<u8>>> {
+        (0..NUM_BLOCKS)
+            .map(|block_idx| {
+                let mut buf = vec![];
+                let mut writer = IpcCompressionWriter::new(&mut buf);
+                for batch_idx in 0..NUM_BATCHES_PER_BLOCK {
+                    let start =
+                        (block_idx * NUM_BATCHES_PER_BLOCK + batch_idx) * NUM_ROWS_PER_BATCH;
+                    let start = start as i32;
+                    let values = start..start + NUM_ROWS_PER_BATCH as i32;
+                    let col: ArrayRef = Arc::new(Int32Array::from_iter_values(values));
+                    writer.write_batch(NUM_ROWS_PER_BATCH, &[col])?;
+                }
+                writer.finish_current_buf()?;
+                drop(writer);
+                Ok(buf)
+            })
+            .collect()
+    }
+
+    fn block_reader(block: Vec<u8>) -> IpcBlockReader {
+        IpcCompressionReader::new(Box::new(Cursor::new(block)))
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_read_segments() -> Result<()> {
+        MemManager::init(1000000);
+        let schema = Arc::new(Schema::new(vec![Field::new("", DataType::Int32, false)]));
+        let num_rows = NUM_BLOCKS * NUM_BATCHES_PER_BLOCK * NUM_ROWS_PER_BATCH;
+
+        for prefetch_depth in [0, 1, 2, 5] {
+            let schema = schema.clone();
+            let values = tokio::task::spawn_blocking(move || {
+                let mut blocks = build_blocks()?.into_iter();
+                let prefetch_mem = Arc::new(IpcPrefetchMemConsumer::new(0));
+                MemManager::register_consumer(prefetch_mem.clone(), false);
+
+                let mut values = vec![];
+                read_segments(
+                    || Ok(blocks.next().map(block_reader)),
+                    schema,
+                    prefetch_depth,
+                    &prefetch_mem,
+                    |(num_rows, cols)| {
+                        assert_eq!(num_rows, NUM_ROWS_PER_BATCH);
+                        values.extend(cols[0].as_primitive::<Int32Type>().values().iter());
+                        Ok(true)
+                    },
+                )?;
+
+                // all prefetched batches are consumed
+                assert_eq!(prefetch_mem.mem_used.load(SeqCst), 0);
+                MemManager::deregister_consumer(prefetch_mem.as_ref());
+                Ok::<_, DataFusionError>(values)
+            })
+            .await
+            .expect("tokio error")?;
+            assert_eq!(values, (0..num_rows as i32).collect::<Vec<_>>());
+        }
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_read_segments_bounded_prefetching() -> Result<()> {
+        MemManager::init(1000000);
+        let schema = Arc::new(Schema::new(vec![Field::new("", DataType::Int32, false)]));
+        let prefetch_depth = 2;
+
+        tokio::task::spawn_blocking(move || {
+            let mut blocks = build_blocks()?.into_iter();
+            let prefetch_mem = Arc::new(IpcPrefetchMemConsumer::new(0));
+            MemManager::register_consumer(prefetch_mem.clone(), false);
+
+            let mut num_batches = 0;
+            read_segments(
+                || Ok(blocks.next().map(block_reader)),
+                schema,
+                prefetch_depth,
+                &prefetch_mem,
+                |(_num_rows, cols)| {
+                    // let background segments decode as much as they can
+                    std::thread::sleep(Duration::from_millis(200));
+
+                    // all segments are being prefetched, but each of them buffers
+                    // only a bounded number of batches
+                    let batch_mem_size = cols[0].get_array_mem_size();
+                    let max_prefetched_batches =
+                        (prefetch_depth + 1) * (PREFETCH_BATCHES_PER_SEGMENT + 1);
+                    assert!(max_prefetched_batches < NUM_BLOCKS * NUM_BATCHES_PER_BLOCK);
+                    assert!(
+                        prefetch_mem.mem_used.load(SeqCst)
+                            <= max_prefetched_batches * batch_mem_size
+                    );
+
+                    // stop reading, the prefetching segments are cancelled
+                    num_batches += 1;
+                    Ok(num_batches < 2)
+                },
+            )?;
+            assert_eq!(num_batches, 2);
+            MemManager::deregister_consumer(prefetch_mem.as_ref());
+            Ok::<_, DataFusionError>(())
+        })
+        .await
+        .expect("tokio error")
+    }
+}
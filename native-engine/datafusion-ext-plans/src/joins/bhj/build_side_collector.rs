@@ -0,0 +1,313 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::{Arc, Weak};
+
+use arrow::{
+    array::{RecordBatch, RecordBatchOptions},
+    datatypes::{SchemaRef, UInt32Type},
+};
+use async_trait::async_trait;
+use datafusion::{common::Result, physical_expr::PhysicalExprRef};
+use datafusion_ext_commons::{
+    arrow::{array_size::ArraySize, selection::take_batch},
+    io::{read_one_batch, write_one_batch},
+    spark_hash::create_xxhash64_hashes,
+};
+use parking_lot::Mutex;
+
+use crate::{
+    common::execution_context::ExecutionContext,
+    memmgr::{
+        metrics::SpillMetrics,
+        quota::MemConsumerType,
+        spill::{try_new_spill, Spill},
+        MemConsumer, MemConsumerInfo, MemManager,
+    },
+};
+
+/// number of partitions of spilled hash join data
+pub const NUM_SPILLED_PARTITIONS: usize = 16;
+
+// xxhash64 is used for partitioning instead of murmur3, otherwise rows of a
+// shuffled partition (partitioned with murmur3 hashes) may fall into only a
+// few spilled partitions.
+const SPILLED_PARTITION_HASH_SEED: i64 = 0x1E39FA04;
+
+/// Collects build side data of hash join. the collector is a spillable memory
+/// consumer, under memory pressure the collected batches are partitioned by
+/// hashes of join keys and spilled, then the join is executed on each pair of
+/// build/probed partitions (like a grace hash join).
+pub struct BuildSideCollector {
+    name: String,
+    mem_consumer_info: Option<Weak<MemConsumerInfo>>,
+    key_exprs: Vec<PhysicalExprRef>,
+    spill_metrics: SpillMetrics,
+    data: Mutex<BuildSideData>,
+}
+
+#[derive(Default)]
+struct BuildSideData {
+    in_mem_batches: Vec<RecordBatch>,
+    partitioned_spills: Vec<Vec<Box<dyn Spill>>>,
+    mem_used: usize,
+}
+
+pub enum CollectedBuildSide {
+    InMem(Vec<RecordBatch>),
+    Spilled(Vec<Vec<Box<dyn Spill>>>),
+}
+
+impl BuildSideCollector {
+    pub fn new(exec_ctx: &Arc<ExecutionContext>, key_exprs: &[PhysicalExprRef]) -> Arc<Self> {
+        let collector = Arc::new(Self {
+            name: format!("HashJoin.BuildSide[partition={}]", exec_ctx.partition_id()),
+            mem_consumer_info: None,
+            key_exprs: key_exprs.to_vec(),
+            spill_metrics: exec_ctx.spill_metrics().clone(),
+            data: Mutex::default(),
+        });
+        MemManager::register_consumer(collector.clone(), true);
+        collector
+    }
+
+    pub fn is_spilled(&self) -> bool {
+        !self.data.lock().partitioned_spills.is_empty()
+    }
+
+    pub async fn push(&self, batch: RecordBatch) -> Result<()> {
+        let mem_used = {
+            let mut data = self.data.lock();
+            data.mem_used += batch.get_array_mem_size();
+            data.in_mem_batches.push(batch);
+            data.mem_used
+        };
+        self.update_mem_used(mem_used).await
+    }
+
+    /// finishes collecting. if any data has been spilled, the remaining
+    /// in-memory batches are also spilled so that all data is partitioned.
+    pub async fn finish(&self) -> Result<CollectedBuildSide> {
+        if self.is_spilled() {
+            self.spill().await?;
+            let partitioned_spills = std::mem::take(&mut self.data.lock().partitioned_spills);
+            return Ok(CollectedBuildSide::Spilled(partitioned_spills));
+        }
+        self.set_spillable(false);
+        let in_mem_batches = std::mem::take(&mut self.data.lock().in_mem_batches);
+        Ok(CollectedBuildSide::InMem(in_mem_batches))
+    }
+}
+
+#[async_trait]
+impl MemConsumer for BuildSideCollector {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn set_consumer_info(&mut self, consumer_info: Weak<MemConsumerInfo>) {
+        self.mem_consumer_info = Some(consumer_info);
+    }
+
+    fn get_consumer_info(&self) -> &Weak<MemConsumerInfo> {
+        self.mem_consumer_info
+            .as_ref()
+            .expect("consumer info not set")
+    }
+
+    fn consumer_type(&self) -> MemConsumerType {
+        MemConsumerType::Join
+    }
+
+    fn spill_metrics(&self) -> Option<&SpillMetrics> {
+        Some(&self.spill_metrics)
+    }
+
+    // spilled build side is read back and repartitioned again when joining
+    fn spill_cost(&self) -> f64 {
+        2.0
+    }
+
+    async fn spill(&self) -> Result<()> {
+        let batches = std::mem::take(&mut self.data.lock().in_mem_batches);
+        let key_exprs = self.key_exprs.clone();
+        let spill_metrics = self.spill_metrics.clone();
+        let spills = tokio::task::spawn_blocking(move || {
+            let mut partitioned_batches = vec![vec![]; NUM_SPILLED_PARTITIONS];
+            for batch in batches {
+                for (partition, batch) in partition_batch(batch, &key_exprs)? {
+                    partitioned_batches[partition].push(batch);
+                }
+            }
+            partitioned_batches
+                .into_iter()
+                .map(|batches| {
+                    if batches.is_empty() {
+                        return Ok(None);
+                    }
+                    let mut spill = try_new_spill(&spill_metrics)?;
+                    let mut writer = spill.get_compressed_writer();
+                    for batch in &batches {
+                        write_one_batch(batch.num_rows(), batch.columns(), &mut writer)?;
+                    }
+                    writer.finish()?;
+                    Ok(Some(spill))
+                })
+                .collect::<Result<Vec<_>>>()
+        })
+        .await
+        .expect("tokio error")?;
+
+        {
+            let mut data = self.data.lock();
+            data.partitioned_spills
+                .resize_with(NUM_SPILLED_PARTITIONS, Vec::new);
+            for (partition, spill) in spills.into_iter().enumerate() {
+                data.partitioned_spills[partition].extend(spill);
+            }
+            data.mem_used = 0;
+        }
+        self.update_mem_used(0).await?;
+        Ok(())
+    }
+}
+
+impl Drop for BuildSideCollector {
+    fn drop(&mut self) {
+        MemManager::deregister_consumer(self);
+    }
+}
+
+/// partitions rows of the batch by hashes of join keys, returns non-empty
+/// (partition, batch) pairs. rows with equal keys from both sides fall into
+/// the same partition.
+pub fn partition_batch(
+    batch: RecordBatch,
+    key_exprs: &[PhysicalExprRef],
+) -> Result<Vec<(usize, RecordBatch)>> {
+    let num_rows = batch.num_rows();
+    let key_columns = key_exprs
+        .iter()
+        .map(|expr| {
+            expr.evaluate(&batch)
+                .and_then(|key| key.into_array(num_rows))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let hashes = create_xxhash64_hashes(num_rows, &key_columns, SPILLED_PARTITION_HASH_SEED);
+
+    let mut partition_indices = vec![vec![]; NUM_SPILLED_PARTITIONS];
+    for (row_idx, hash) in hashes.into_iter().enumerate() {
+        let partition = hash.rem_euclid(NUM_SPILLED_PARTITIONS as i64) as usize;
+        partition_indices[partition].push(row_idx as u32);
+    }
+    partition_indices
+        .into_iter()
+        .enumerate()
+        .filter(|(_, indices)| !indices.is_empty())
+        .map(|(partition, indices)| {
+            Ok((partition, take_batch::<UInt32Type>(batch.clone(), indices)?))
+        })
+        .collect()
+}
+
+/// reads all batches in the spills
+pub fn read_spilled_batches(
+    spills: &[Box<dyn Spill>],
+    schema: &SchemaRef,
+) -> Result<Vec<RecordBatch>> {
+    let mut batches = vec![];
+    for spill in spills {
+        let mut reader = spill.get_compressed_reader()?;
+        while let Some((num_rows, cols)) = read_one_batch(&mut reader, schema)? {
+            batches.push(RecordBatch::try_new_with_options(
+                schema.clone(),
+                cols,
+                &RecordBatchOptions::new().with_row_count(Some(num_rows)),
+            )?);
+        }
+    }
+    Ok(batches)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{AsArray, Int32Array, RecordBatch},
+        datatypes::{DataType, Field, Int32Type, Schema},
+    };
+    use datafusion::{
+        common::Result,
+        physical_expr::{expressions::Column, PhysicalExprRef},
+        physical_plan::metrics::ExecutionPlanMetricsSet,
+        prelude::SessionContext,
+    };
+
+    use crate::{
+        common::execution_context::ExecutionContext,
+        joins::bhj::build_side_collector::{
+            partition_batch, read_spilled_batches, BuildSideCollector, CollectedBuildSide,
+        },
+        memmgr::{MemConsumer, MemManager},
+    };
+
+    #[tokio::test]
+    async fn test_build_side_collector() -> Result<()> {
+        MemManager::init(1000000);
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let exec_ctx = ExecutionContext::new(
+            SessionContext::new().task_ctx(),
+            0,
+            schema.clone(),
+            &ExecutionPlanMetricsSet::new(),
+        );
+        let key_exprs: Vec<PhysicalExprRef> = vec![Arc::new(Column::new("a", 0))];
+        let new_batch = |values: Vec<i32>| {
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from(values))])
+        };
+
+        // not spilled
+        let collector = BuildSideCollector::new(&exec_ctx, &key_exprs);
+        collector.push(new_batch(vec![1, 2, 3])?).await?;
+        match collector.finish().await? {
+            CollectedBuildSide::InMem(batches) => assert_eq!(batches.len(), 1),
+            CollectedBuildSide::Spilled(_) => unreachable!(),
+        }
+
+        // spilled, all rows are partitioned by keys
+        let collector = BuildSideCollector::new(&exec_ctx, &key_exprs);
+        collector.push(new_batch((0..100).collect())?).await?;
+        collector.spill().await?;
+        collector.push(new_batch((50..150).collect())?).await?;
+        let CollectedBuildSide::Spilled(partitioned_spills) = collector.finish().await? else {
+            unreachable!()
+        };
+
+        let mut values = vec![];
+        for (partition, spills) in partitioned_spills.iter().enumerate() {
+            for batch in read_spilled_batches(spills, &schema)? {
+                for (p, _) in partition_batch(batch.clone(), &key_exprs)? {
+                    assert_eq!(p, partition);
+                }
+                values.extend(batch.column(0).as_primitive::<Int32Type>().values());
+            }
+        }
+        values.sort();
+        let mut expected = (0..100).chain(50..150).collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(values, expected);
+        Ok(())
+    }
+}
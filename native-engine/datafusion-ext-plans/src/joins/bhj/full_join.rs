@@ -26,7 +26,10 @@ use arrow::{
 };
 use async_trait::async_trait;
 use bitvec::{bitvec, prelude::BitVec};
-use datafusion::{common::Result, physical_plan::metrics::Time};
+use datafusion::{
+    common::Result,
+    physical_plan::metrics::{Count, Time},
+};
 use datafusion_ext_commons::{
     arrow::{eq_comparator::EqComparator, selection::take_cols},
     likely,
@@ -87,6 +90,7 @@ pub struct FullJoiner<const P: JoinerParams> {
     map: Arc<JoinHashMap>,
     map_joined: BitVec,
     output_rows: AtomicUsize,
+    hash_collisions: Count,
 }
 
 impl<const P: JoinerParams> FullJoiner<P> {
@@ -96,12 +100,16 @@ impl<const P: JoinerParams> FullJoiner<P> {
         output_sender: Arc<WrappedRecordBatchSender>,
     ) -> Self {
         let map_joined = bitvec![0; map.data_batch().num_rows()];
+        let hash_collisions = output_sender
+            .exec_ctx()
+            .register_counter_metric("hash_collisions");
         Self {
             join_params,
             output_sender,
             map,
             map_joined,
             output_rows: AtomicUsize::new(0),
+            hash_collisions,
         }
     }
 
@@ -249,6 +257,7 @@ impl<const P: JoinerParams> Joiner for FullJoiner<P> {
 
         let _probed_side_compare_timer = probed_side_compare_time.timer();
         let mut hashes_idx = 0;
+        let mut num_hash_collisions = 0;
 
         for row_idx in 0..probed_batch.num_rows() {
             let mut joined = false;
@@ -285,6 +294,11 @@ impl<const P: JoinerParams> Joiner for FullJoiner<P> {
                     }
                     _ => {} // map_value.is_empty
                 }
+
+                // hash matched but no keys are equal
+                if !joined && !map_value.is_empty() {
+                    num_hash_collisions += 1;
+                }
             }
 
             if P.probe_side_outer && !joined {
@@ -305,6 +319,8 @@ impl<const P: JoinerParams> Joiner for FullJoiner<P> {
             }
         }
 
+        self.hash_collisions.add(num_hash_collisions);
+
         if !hash_joined_probe_indices.is_empty() {
             probed_side_compare_time
                 .exclude_timer_async(self.as_mut().flush_hash_joined(
@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod build_side_collector;
 pub mod full_join;
 pub mod semi_join;
 
@@ -26,7 +26,10 @@ use arrow::{
 };
 use async_trait::async_trait;
 use bitvec::{bitvec, prelude::BitVec};
-use datafusion::{common::Result, physical_plan::metrics::Time};
+use datafusion::{
+    common::Result,
+    physical_plan::metrics::{Count, Time},
+};
 use datafusion_ext_commons::{
     arrow::{eq_comparator::EqComparator, selection::take_cols},
     likely,
@@ -100,6 +103,7 @@ pub struct SemiJoiner<const P: JoinerParams> {
     map_joined: BitVec,
     map: Arc<JoinHashMap>,
     output_rows: AtomicUsize,
+    hash_collisions: Count,
 }
 
 impl<const P: JoinerParams> SemiJoiner<P> {
@@ -109,12 +113,16 @@ impl<const P: JoinerParams> SemiJoiner<P> {
         output_sender: Arc<WrappedRecordBatchSender>,
     ) -> Self {
         let map_joined = bitvec![0; map.data_batch().num_rows()];
+        let hash_collisions = output_sender
+            .exec_ctx()
+            .register_counter_metric("hash_collisions");
         Self {
             join_params,
             output_sender,
             map,
             map_joined,
             output_rows: AtomicUsize::new(0),
+            hash_collisions,
         }
     }
 
@@ -186,6 +194,7 @@ impl<const P: JoinerParams> Joiner for SemiJoiner<P> {
 
         let _probed_side_compare_timer = probed_side_compare_time.timer();
         let mut hashes_idx = 0;
+        let mut num_hash_collisions = 0;
 
         for row_idx in 0..probed_batch.num_rows() {
             if probed_valids
@@ -205,6 +214,8 @@ impl<const P: JoinerParams> Joiner for SemiJoiner<P> {
                             } else {
                                 map_joined.set(map_idx as usize, true);
                             }
+                        } else {
+                            num_hash_collisions += 1;
                         }
                     }
                     map_value if map_value.is_range() => {
@@ -227,12 +238,15 @@ impl<const P: JoinerParams> Joiner for SemiJoiner<P> {
                                 // should
                                 // have already been joined
                             }
+                        } else {
+                            num_hash_collisions += 1;
                         }
                     }
                     _ => {} // map_value.is_empty()
                 }
             }
         }
+        self.hash_collisions.add(num_hash_collisions);
 
         if P.probe_is_join_side {
             probed_side_compare_time
@@ -262,7 +276,9 @@ impl<const P: JoinerParams> Joiner for SemiJoiner<P> {
                             let exists_col = Arc::new(BooleanArray::from(
                                 probed_joined.into_iter().collect::<Vec<_>>(),
                             ));
-                            [pprojected, vec![exists_col]].concat()
+                            self.join_params
+                                .projection
+                                .project_exists(pprojected, exists_col)
                         }
                     };
                     build_output_time
@@ -302,7 +318,9 @@ impl<const P: JoinerParams> Joiner for SemiJoiner<P> {
                     let exists_col = Arc::new(BooleanArray::from(
                         map_joined.into_iter().collect::<Vec<_>>(),
                     ));
-                    [mprojected, vec![exists_col]].concat()
+                    self.join_params
+                        .projection
+                        .project_exists(mprojected, exists_col)
                 }
             };
             build_output_time
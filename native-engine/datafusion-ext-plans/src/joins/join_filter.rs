@@ -0,0 +1,235 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use arrow::{
+    array::{Array, ArrayRef, AsArray, BooleanArray, RecordBatch},
+    compute::{
+        and,
+        kernels::cmp::{distinct, eq, gt, gt_eq, lt, lt_eq, neq, not_distinct},
+        prep_null_mask_filter,
+    },
+    datatypes::{DataType, Schema},
+};
+use datafusion::{
+    common::Result,
+    logical_expr::Operator,
+    physical_expr::{
+        expressions::{BinaryExpr, Column},
+        utils::split_conjunction,
+        PhysicalExprRef,
+    },
+};
+use datafusion_ext_commons::df_execution_err;
+
+/// Evaluates a join filter on pair batches, which contain joined left columns
+/// followed by right columns.
+///
+/// filters which are conjunctions of simple comparisons between two columns
+/// (e.g. `l.a = r.a AND l.b < r.b`) are evaluated with arrow comparison
+/// kernels directly, without going through generic expression evaluation.
+/// other filters fall back to generic evaluation. in both cases rows with
+/// null filter results are treated as not matched.
+#[derive(Debug)]
+pub struct JoinFilterEvaluator {
+    filter: PhysicalExprRef,
+    kernels: Option<Vec<ComparisonKernel>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ComparisonKernel {
+    op: Operator,
+    lhs: usize,
+    rhs: usize,
+}
+
+impl ComparisonKernel {
+    fn try_new(expr: &PhysicalExprRef, pair_schema: &Schema) -> Option<Self> {
+        let binary = expr.as_any().downcast_ref::<BinaryExpr>()?;
+        let lhs = binary.left().as_any().downcast_ref::<Column>()?.index();
+        let rhs = binary.right().as_any().downcast_ref::<Column>()?.index();
+        let lhs_type = pair_schema.fields().get(lhs)?.data_type();
+        let rhs_type = pair_schema.fields().get(rhs)?.data_type();
+        let supported_type = !lhs_type.is_nested() && !matches!(lhs_type, DataType::Dictionary(..));
+        if lhs_type != rhs_type || !supported_type {
+            return None;
+        }
+        match binary.op() {
+            op @ (Operator::Eq
+            | Operator::NotEq
+            | Operator::Lt
+            | Operator::LtEq
+            | Operator::Gt
+            | Operator::GtEq
+            | Operator::IsDistinctFrom
+            | Operator::IsNotDistinctFrom) => Some(Self { op: *op, lhs, rhs }),
+            _ => None,
+        }
+    }
+
+    fn evaluate(&self, cols: &[ArrayRef]) -> Result<BooleanArray> {
+        let (lhs, rhs) = (&cols[self.lhs], &cols[self.rhs]);
+        Ok(match self.op {
+            Operator::Eq => eq(lhs, rhs)?,
+            Operator::NotEq => neq(lhs, rhs)?,
+            Operator::Lt => lt(lhs, rhs)?,
+            Operator::LtEq => lt_eq(lhs, rhs)?,
+            Operator::Gt => gt(lhs, rhs)?,
+            Operator::GtEq => gt_eq(lhs, rhs)?,
+            Operator::IsDistinctFrom => distinct(lhs, rhs)?,
+            Operator::IsNotDistinctFrom => not_distinct(lhs, rhs)?,
+            op => unreachable!("unsupported comparison operator: {op}"),
+        })
+    }
+}
+
+impl JoinFilterEvaluator {
+    pub fn new(filter: PhysicalExprRef, pair_schema: &Schema) -> Self {
+        let kernels = split_conjunction(&filter)
+            .into_iter()
+            .map(|expr| ComparisonKernel::try_new(expr, pair_schema))
+            .collect::<Option<Vec<_>>>();
+        Self { filter, kernels }
+    }
+
+    /// returns true if the filter is evaluated with comparison kernels
+    pub fn is_vectorized(&self) -> bool {
+        self.kernels.is_some()
+    }
+
+    /// evaluates the filter on the pair batch, returns a mask without nulls
+    pub fn evaluate(&self, pair_batch: &RecordBatch) -> Result<BooleanArray> {
+        let mask = match &self.kernels {
+            Some(kernels) => {
+                let mut mask: Option<BooleanArray> = None;
+                for kernel in kernels {
+                    let kernel_mask = kernel.evaluate(pair_batch.columns())?;
+                    mask = Some(match mask {
+                        Some(mask) => and(&mask, &kernel_mask)?,
+                        None => kernel_mask,
+                    });
+                }
+                mask.unwrap_or_else(|| BooleanArray::from(vec![true; pair_batch.num_rows()]))
+            }
+            None => {
+                let result = self
+                    .filter
+                    .evaluate(pair_batch)?
+                    .into_array(pair_batch.num_rows())?;
+                if result.data_type() != &DataType::Boolean {
+                    return df_execution_err!(
+                        "join filter must return boolean values, got {}",
+                        result.data_type()
+                    );
+                }
+                result.as_boolean().clone()
+            }
+        };
+        if mask.null_count() > 0 {
+            return Ok(prep_null_mask_filter(&mask));
+        }
+        Ok(mask)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{ArrayRef, BooleanArray, Int32Array, RecordBatch, StringArray},
+        datatypes::{DataType, Field, Schema},
+    };
+    use datafusion::{
+        common::Result,
+        logical_expr::Operator,
+        physical_expr::{
+            expressions::{binary, col, lit},
+            PhysicalExprRef,
+        },
+    };
+
+    use crate::joins::join_filter::JoinFilterEvaluator;
+
+    #[test]
+    fn test_join_filter_evaluator() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("l_a", DataType::Int32, true),
+            Field::new("l_b", DataType::Utf8, true),
+            Field::new("r_a", DataType::Int32, true),
+            Field::new("r_b", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![
+                    Some(1),
+                    Some(2),
+                    None,
+                    Some(4),
+                    None,
+                ])),
+                Arc::new(StringArray::from(vec!["a", "b", "c", "d", "e"])),
+                Arc::new(Int32Array::from(vec![
+                    Some(1),
+                    Some(3),
+                    Some(3),
+                    Some(4),
+                    None,
+                ])),
+                Arc::new(StringArray::from(vec!["z", "a", "z", "a", "z"])),
+            ] as Vec<ArrayRef>,
+        )?;
+        let compare = |l: &str, op: Operator, r: &str| -> Result<PhysicalExprRef> {
+            binary(col(l, &schema)?, op, col(r, &schema)?, &schema)
+        };
+
+        // l_a = r_a and l_b < r_b
+        let filter = binary(
+            compare("l_a", Operator::Eq, "r_a")?,
+            Operator::And,
+            compare("l_b", Operator::Lt, "r_b")?,
+            &schema,
+        )?;
+        let evaluator = JoinFilterEvaluator::new(filter, &schema);
+        assert!(evaluator.is_vectorized());
+        assert_eq!(
+            evaluator.evaluate(&batch)?,
+            BooleanArray::from(vec![true, false, false, false, false]),
+        );
+
+        // null-aware equality
+        let filter = compare("l_a", Operator::IsNotDistinctFrom, "r_a")?;
+        let evaluator = JoinFilterEvaluator::new(filter, &schema);
+        assert!(evaluator.is_vectorized());
+        assert_eq!(
+            evaluator.evaluate(&batch)?,
+            BooleanArray::from(vec![true, false, false, true, true]),
+        );
+
+        // falls back to generic evaluation, null results are not matched
+        let filter = binary(
+            compare("l_a", Operator::Lt, "r_a")?,
+            Operator::Or,
+            binary(col("l_b", &schema)?, Operator::Eq, lit("e"), &schema)?,
+            &schema,
+        )?;
+        let evaluator = JoinFilterEvaluator::new(filter, &schema);
+        assert!(!evaluator.is_vectorized());
+        assert_eq!(
+            evaluator.evaluate(&batch)?,
+            BooleanArray::from(vec![false, true, false, false, true]),
+        );
+        Ok(())
+    }
+}
@@ -12,6 +12,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::sync::Arc;
+
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
 use datafusion::common::{DataFusionError, Result};
 use datafusion_ext_commons::df_execution_err;
 
@@ -62,3 +65,19 @@ impl TryFrom<datafusion::prelude::JoinType> for JoinType {
         }
     }
 }
+
+/// Builds the output schema of a join. existence join outputs all left
+/// columns followed by a non-nullable boolean `exists` column, other join
+/// types are the same as datafusion's.
+pub fn build_join_schema(left: &Schema, right: &Schema, join_type: JoinType) -> Result<SchemaRef> {
+    if join_type == JoinType::Existence {
+        let exists_field = Arc::new(Field::new("exists", DataType::Boolean, false));
+        return Ok(Arc::new(Schema::new(
+            [left.fields().to_vec(), vec![exists_field]].concat(),
+        )));
+    }
+    let df_join_type = join_type.try_into()?;
+    Ok(Arc::new(
+        datafusion::physical_plan::joins::utils::build_join_schema(left, right, &df_join_type).0,
+    ))
+}
@@ -23,6 +23,7 @@ use datafusion::{common::Result, physical_expr::PhysicalExprRef};
 
 use crate::joins::{join_utils::JoinType, stream_cursor::StreamCursor};
 
+pub mod join_filter;
 pub mod join_hash_map;
 pub mod join_utils;
 pub mod stream_cursor;
@@ -42,6 +43,7 @@ pub struct JoinParams {
     pub right_keys: Vec<PhysicalExprRef>,
     pub key_data_types: Vec<DataType>,
     pub sort_options: Vec<SortOptions>,
+    pub null_equals_null: bool,
     pub projection: JoinProjection,
     pub batch_size: usize,
 }
@@ -53,6 +55,7 @@ pub struct JoinProjection {
     pub right_schema: SchemaRef,
     pub left: Vec<usize>,
     pub right: Vec<usize>,
+    pub exists: bool,
 }
 
 impl JoinProjection {
@@ -66,6 +69,7 @@ impl JoinProjection {
         let projected_schema = Arc::new(schema.project(projection)?);
         let mut left = vec![];
         let mut right = vec![];
+        let mut exists = false;
 
         match join_type {
             JoinType::Inner | JoinType::Left | JoinType::Right | JoinType::Full => {
@@ -87,6 +91,8 @@ impl JoinProjection {
                 for &i in projection {
                     if i < left_schema.fields().len() {
                         left.push(i);
+                    } else if i == left_schema.fields().len() {
+                        exists = true;
                     }
                 }
             }
@@ -97,6 +103,7 @@ impl JoinProjection {
             right_schema: Arc::new(right_schema.project(&right)?),
             left,
             right,
+            exists,
         })
     }
 
@@ -107,6 +114,14 @@ impl JoinProjection {
     pub fn project_right(&self, cols: &[ArrayRef]) -> Vec<ArrayRef> {
         self.right.iter().map(|&i| cols[i].clone()).collect()
     }
+
+    /// appends the `exists` column of existence join if it is projected
+    pub fn project_exists(&self, mut cols: Vec<ArrayRef>, exists_col: ArrayRef) -> Vec<ArrayRef> {
+        if self.exists {
+            cols.push(exists_col);
+        }
+        cols
+    }
 }
 
 pub type Idx = (usize, usize);
@@ -0,0 +1,270 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Weak},
+};
+
+use arrow::{
+    array::{RecordBatch, RecordBatchOptions},
+    datatypes::SchemaRef,
+};
+use async_trait::async_trait;
+use datafusion::common::{DataFusionError, Result};
+use datafusion_ext_commons::{
+    arrow::array_size::ArraySize,
+    io::{read_one_batch, write_one_batch},
+};
+use parking_lot::Mutex;
+
+use crate::{
+    common::execution_context::ExecutionContext,
+    memmgr::{
+        metrics::SpillMetrics,
+        quota::MemConsumerType,
+        spill::{try_new_file_spill, Spill},
+        MemConsumer, MemConsumerInfo, MemManager,
+    },
+};
+
+/// Buffers materialized rows of one side of an equal-key run in sort-merge
+/// join. used for heavily skewed keys, where the run is too large to be kept
+/// in stream cursors. the buffer is a spillable memory consumer, buffered
+/// batches are spilled into files under memory pressure and can be read for
+/// many times when expanding the cartesian product with the other side.
+pub struct EqualRunBuffer {
+    name: String,
+    mem_consumer_info: Option<Weak<MemConsumerInfo>>,
+    schema: SchemaRef,
+    spill_metrics: SpillMetrics,
+    data: Mutex<EqualRunBufferData>,
+}
+
+#[derive(Default)]
+struct EqualRunBufferData {
+    in_mem_batches: Vec<RecordBatch>,
+    spills: Vec<Arc<dyn Spill>>,
+    num_batches: usize,
+    mem_used: usize,
+}
+
+impl EqualRunBuffer {
+    pub fn new(exec_ctx: &Arc<ExecutionContext>, schema: SchemaRef) -> Arc<Self> {
+        let buffer = Arc::new(Self {
+            name: format!(
+                "SortMergeJoin.EqualRunBuffer[partition={}]",
+                exec_ctx.partition_id()
+            ),
+            mem_consumer_info: None,
+            schema,
+            spill_metrics: exec_ctx.spill_metrics().clone(),
+            data: Mutex::default(),
+        });
+        MemManager::register_consumer(buffer.clone(), true);
+        buffer
+    }
+
+    pub fn num_batches(&self) -> usize {
+        self.data.lock().num_batches
+    }
+
+    pub async fn push(&self, batch: RecordBatch) -> Result<()> {
+        let mem_used = {
+            let mut data = self.data.lock();
+            data.mem_used += batch.get_array_mem_size();
+            data.num_batches += 1;
+            data.in_mem_batches.push(batch);
+            data.mem_used
+        };
+        self.update_mem_used(mem_used).await
+    }
+
+    /// returns all buffered batches in the pushed order
+    pub fn batches(&self) -> EqualRunBatches {
+        let data = self.data.lock();
+        EqualRunBatches {
+            schema: self.schema.clone(),
+            spills: data.spills.iter().cloned().collect(),
+            loaded: VecDeque::new(),
+            in_mem_batches: data.in_mem_batches.iter().cloned().collect(),
+        }
+    }
+}
+
+#[async_trait]
+impl MemConsumer for EqualRunBuffer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn set_consumer_info(&mut self, consumer_info: Weak<MemConsumerInfo>) {
+        self.mem_consumer_info = Some(consumer_info);
+    }
+
+    fn get_consumer_info(&self) -> &Weak<MemConsumerInfo> {
+        self.mem_consumer_info
+            .as_ref()
+            .expect("consumer info not set")
+    }
+
+    fn consumer_type(&self) -> MemConsumerType {
+        MemConsumerType::Join
+    }
+
+    fn spill_metrics(&self) -> Option<&SpillMetrics> {
+        Some(&self.spill_metrics)
+    }
+
+    // spilled runs are read for many times when joining
+    fn spill_cost(&self) -> f64 {
+        4.0
+    }
+
+    async fn spill(&self) -> Result<()> {
+        let batches = std::mem::take(&mut self.data.lock().in_mem_batches);
+        if batches.is_empty() {
+            return Ok(());
+        }
+        let spill_metrics = self.spill_metrics.clone();
+        let spill = tokio::task::spawn_blocking(move || {
+            // file spill is used because it will be read for many times
+            let mut spill = try_new_file_spill(&spill_metrics)?;
+            let mut writer = spill.get_compressed_writer();
+            for batch in &batches {
+                write_one_batch(batch.num_rows(), batch.columns(), &mut writer)?;
+            }
+            writer.finish()?;
+            Ok::<_, DataFusionError>(spill)
+        })
+        .await
+        .expect("tokio error")?;
+
+        {
+            let mut data = self.data.lock();
+            data.spills.push(Arc::from(spill));
+            data.mem_used = 0;
+        }
+        self.update_mem_used(0).await?;
+        Ok(())
+    }
+}
+
+impl Drop for EqualRunBuffer {
+    fn drop(&mut self) {
+        MemManager::deregister_consumer(self);
+    }
+}
+
+/// Iterator of batches in [`EqualRunBuffer`]. spilled batches are loaded one
+/// spill at a time.
+pub struct EqualRunBatches {
+    schema: SchemaRef,
+    spills: VecDeque<Arc<dyn Spill>>,
+    loaded: VecDeque<RecordBatch>,
+    in_mem_batches: VecDeque<RecordBatch>,
+}
+
+impl EqualRunBatches {
+    fn load_next_spill(&mut self) -> Result<bool> {
+        let Some(spill) = self.spills.pop_front() else {
+            return Ok(false);
+        };
+        let mut reader = spill.get_compressed_reader()?;
+        while let Some((num_rows, cols)) = read_one_batch(&mut reader, &self.schema)? {
+            self.loaded.push_back(RecordBatch::try_new_with_options(
+                self.schema.clone(),
+                cols,
+                &RecordBatchOptions::new().with_row_count(Some(num_rows)),
+            )?);
+        }
+        Ok(true)
+    }
+}
+
+impl Iterator for EqualRunBatches {
+    type Item = Result<RecordBatch>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(batch) = self.loaded.pop_front() {
+                return Some(Ok(batch));
+            }
+            match self.load_next_spill() {
+                Ok(true) => continue,
+                Ok(false) => return self.in_mem_batches.pop_front().map(Ok),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{AsArray, Int32Array, RecordBatch},
+        datatypes::{DataType, Field, Int32Type, Schema},
+    };
+    use datafusion::{
+        common::Result, physical_plan::metrics::ExecutionPlanMetricsSet, prelude::SessionContext,
+    };
+
+    use crate::{
+        common::execution_context::ExecutionContext,
+        joins::smj::equal_run_buffer::EqualRunBuffer,
+        memmgr::{MemConsumer, MemManager},
+    };
+
+    #[tokio::test]
+    async fn test_equal_run_buffer() -> Result<()> {
+        MemManager::init(1000000);
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let exec_ctx = ExecutionContext::new(
+            SessionContext::new().task_ctx(),
+            0,
+            schema.clone(),
+            &ExecutionPlanMetricsSet::new(),
+        );
+        let buffer = EqualRunBuffer::new(&exec_ctx, schema.clone());
+        let new_batch = |values: Vec<i32>| {
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from(values))])
+        };
+
+        buffer.push(new_batch(vec![1, 2])?).await?;
+        buffer.push(new_batch(vec![3])?).await?;
+        buffer.spill().await?;
+        buffer.push(new_batch(vec![4, 5])?).await?;
+        buffer.spill().await?;
+        buffer.push(new_batch(vec![6])?).await?;
+        assert_eq!(buffer.num_batches(), 4);
+
+        // read twice
+        for _ in 0..2 {
+            let values = buffer
+                .batches()
+                .map(|batch| {
+                    Ok(batch?
+                        .column(0)
+                        .as_primitive::<Int32Type>()
+                        .values()
+                        .to_vec())
+                })
+                .collect::<Result<Vec<_>>>()?;
+            assert_eq!(values, vec![vec![1, 2], vec![3], vec![4, 5], vec![6]]);
+        }
+        Ok(())
+    }
+}
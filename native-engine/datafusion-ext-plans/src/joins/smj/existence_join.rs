@@ -74,8 +74,10 @@ impl ExistenceJoiner {
         let exists_col: ArrayRef = Arc::new(arrow::array::BooleanArray::from(exists));
 
         let output_batch = RecordBatch::try_new_with_options(
-            self.join_params.output_schema.clone(),
-            [cols.columns().to_vec(), vec![exists_col]].concat(),
+            self.join_params.projection.schema.clone(),
+            self.join_params
+                .projection
+                .project_exists(cols.columns().to_vec(), exists_col),
             &RecordBatchOptions::new().with_row_count(Some(num_rows)),
         )?;
 
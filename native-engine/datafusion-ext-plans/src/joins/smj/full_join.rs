@@ -14,27 +14,46 @@
 
 use std::{cmp::Ordering, pin::Pin, sync::Arc};
 
-use arrow::array::{RecordBatch, RecordBatchOptions};
+use arrow::array::{RecordBatch, RecordBatchOptions, UInt32Array};
 use async_trait::async_trait;
-use datafusion::common::Result;
+use blaze_jni_bridge::{conf, conf::IntConf, is_jni_bridge_inited};
+use datafusion::{
+    common::Result,
+    physical_plan::metrics::{Count, Gauge, MetricBuilder},
+};
 use datafusion_ext_commons::{
-    arrow::selection::create_batch_interleaver, suggested_output_batch_mem_size,
+    arrow::selection::{create_batch_interleaver, take_cols},
+    suggested_output_batch_mem_size,
 };
 use smallvec::{smallvec, SmallVec};
 
 use crate::{
     common::execution_context::WrappedRecordBatchSender,
     compare_cursor, cur_forward,
-    joins::{Idx, JoinParams, StreamCursors},
+    joins::{
+        smj::equal_run_buffer::EqualRunBuffer, stream_cursor::StreamCursor, Idx, JoinParams,
+        StreamCursors,
+    },
     sort_merge_join_exec::Joiner,
 };
 
+// an equal-key run spanning more than this number of batches on both sides
+// is joined with spillable buffers instead of being kept in stream cursors
+const SPILLABLE_EQUAL_RUN_MIN_BATCHES: usize = 2;
+
+// number of the hottest skewed keys reported in metrics and logs
+const NUM_REPORTED_HOTTEST_KEYS: usize = 5;
+
 pub struct FullJoiner<const L_OUTER: bool, const R_OUTER: bool> {
     join_params: JoinParams,
     output_sender: Arc<WrappedRecordBatchSender>,
     lindices: Vec<Idx>,
     rindices: Vec<Idx>,
     output_rows: usize,
+    skewed_key_min_rows: usize,
+    skewed_key_count: Count,
+    skewed_key_max_rows: Gauge,
+    hottest_keys: Vec<(usize, String)>,
 }
 
 pub type InnerJoiner = FullJoiner<false, false>;
@@ -44,15 +63,68 @@ pub type FullOuterJoiner = FullJoiner<true, true>;
 
 impl<const L_OUTER: bool, const R_OUTER: bool> FullJoiner<L_OUTER, R_OUTER> {
     pub fn new(join_params: JoinParams, output_sender: Arc<WrappedRecordBatchSender>) -> Self {
+        let exec_ctx = output_sender.exec_ctx().clone();
         Self {
             join_params,
             output_sender,
             lindices: vec![],
             rindices: vec![],
             output_rows: 0,
+            skewed_key_min_rows: skewed_key_min_rows(),
+            skewed_key_count: exec_ctx.register_counter_metric("skewed_key_count"),
+            skewed_key_max_rows: exec_ctx.register_gauge_metric("skewed_key_max_rows"),
+            hottest_keys: vec![],
+        }
+    }
+
+    fn is_spillable_equal_run(
+        &self,
+        equal_lindices: &[Idx],
+        equal_rindices: &[Idx],
+        last_lidx: Idx,
+        last_ridx: Idx,
+    ) -> bool {
+        let num_equal_rows = equal_lindices.len() + equal_rindices.len();
+        (self.skewed_key_min_rows > 0 && num_equal_rows >= self.skewed_key_min_rows)
+            || (last_lidx.0 - equal_lindices[0].0 >= SPILLABLE_EQUAL_RUN_MIN_BATCHES
+                && last_ridx.0 - equal_rindices[0].0 >= SPILLABLE_EQUAL_RUN_MIN_BATCHES)
+    }
+
+    /// updates skewed key metrics and keeps the hottest keys for reporting
+    fn record_skewed_key(&mut self, key: String, num_rows: usize) {
+        self.skewed_key_count.add(1);
+        self.skewed_key_max_rows.set_max(num_rows);
+
+        let pos = self
+            .hottest_keys
+            .partition_point(|(hot_num_rows, _)| *hot_num_rows >= num_rows);
+        if pos < NUM_REPORTED_HOTTEST_KEYS {
+            self.hottest_keys.insert(pos, (num_rows, key));
+            self.hottest_keys.truncate(NUM_REPORTED_HOTTEST_KEYS);
         }
     }
 
+    /// reports the hottest skewed keys as `skewed_key_rows` gauges labeled
+    /// with the keys, so they can be found in the metrics set of the join
+    fn report_hottest_keys(&self) {
+        if self.hottest_keys.is_empty() {
+            return;
+        }
+        let exec_ctx = self.output_sender.exec_ctx();
+        for (num_rows, key) in &self.hottest_keys {
+            MetricBuilder::new(exec_ctx.execution_plan_metrics())
+                .with_new_label("skewed_key", key.clone())
+                .gauge("skewed_key_rows", exec_ctx.partition_id())
+                .set(*num_rows);
+        }
+        log::warn!(
+            "SortMergeJoin[partition={}]: joined {} skewed keys, hottest (rows, key): {:?}",
+            exec_ctx.partition_id(),
+            self.skewed_key_count.value(),
+            self.hottest_keys,
+        );
+    }
+
     fn should_flush(&self, curs: &StreamCursors) -> bool {
         if self.lindices.len() >= self.join_params.batch_size {
             return true;
@@ -98,6 +170,139 @@ impl<const L_OUTER: bool, const R_OUTER: bool> FullJoiner<L_OUTER, R_OUTER> {
         }
         Ok(())
     }
+
+    /// joins the rest of a heavily skewed equal-key run. `seen_lindices` x
+    /// `seen_rindices` are already joined, and both cursors are pointing to
+    /// the next unseen rows of the run.
+    ///
+    /// all left rows of the run are materialized into a spillable buffer so
+    /// that cursor batches can be released, then right rows are materialized
+    /// chunk by chunk and joined with the buffered left rows.
+    async fn join_spillable_equal_run(
+        mut self: Pin<&mut Self>,
+        curs: &mut StreamCursors,
+        seen_lindices: &[Idx],
+        seen_rindices: &[Idx],
+        mut last_lidx: Idx,
+        mut last_ridx: Idx,
+    ) -> Result<()> {
+        // flush joined indices, which are pinning the cursor batches
+        self.as_mut().flush(curs).await?;
+        let batch_size = self.join_params.batch_size;
+        let key = curs.0.key_string(last_lidx)?;
+        let mut num_rows = seen_lindices.len() + seen_rindices.len();
+
+        let lbuffer = EqualRunBuffer::new(
+            self.output_sender.exec_ctx(),
+            curs.0.projected_batch_schema.clone(),
+        );
+        for lindices in seen_lindices.chunks(batch_size) {
+            lbuffer
+                .push(interleave_cursor_rows(&curs.0, lindices)?)
+                .await?;
+        }
+        let num_seen_lbatches = lbuffer.num_batches();
+        let seen_rbatches = seen_rindices
+            .chunks(batch_size)
+            .map(|rindices| interleave_cursor_rows(&curs.1, rindices))
+            .collect::<Result<Vec<_>>>()?;
+
+        // buffer unseen left rows
+        while let Some(lbatch) =
+            take_equal_run_rows(&mut curs.0, &mut last_lidx, batch_size).await?
+        {
+            num_rows += lbatch.num_rows();
+            lbuffer.push(lbatch).await?;
+        }
+
+        // join seen right rows with unseen left rows
+        for rbatch in &seen_rbatches {
+            for lbatch in lbuffer.batches().skip(num_seen_lbatches) {
+                self.as_mut().output_product(&lbatch?, rbatch).await?;
+            }
+        }
+        drop(seen_rbatches);
+
+        // join unseen right rows with all left rows
+        while let Some(rbatch) =
+            take_equal_run_rows(&mut curs.1, &mut last_ridx, batch_size).await?
+        {
+            num_rows += rbatch.num_rows();
+            for lbatch in lbuffer.batches() {
+                self.as_mut().output_product(&lbatch?, &rbatch).await?;
+            }
+        }
+        self.record_skewed_key(key, num_rows);
+        Ok(())
+    }
+
+    /// outputs the cartesian product of left and right rows
+    async fn output_product(
+        mut self: Pin<&mut Self>,
+        lbatch: &RecordBatch,
+        rbatch: &RecordBatch,
+    ) -> Result<()> {
+        let num_lrows = lbatch.num_rows();
+        let num_rows = num_lrows * rbatch.num_rows();
+        let batch_size = self.join_params.batch_size;
+
+        for start in (0..num_rows).step_by(batch_size) {
+            let end = num_rows.min(start + batch_size);
+            let lindices =
+                UInt32Array::from_iter_values((start..end).map(|i| (i % num_lrows) as u32));
+            let rindices =
+                UInt32Array::from_iter_values((start..end).map(|i| (i / num_lrows) as u32));
+            let lcols = take_cols(lbatch.columns(), lindices)?;
+            let rcols = take_cols(rbatch.columns(), rindices)?;
+            let output_batch = RecordBatch::try_new_with_options(
+                self.join_params.projection.schema.clone(),
+                [lcols, rcols].concat(),
+                &RecordBatchOptions::new().with_row_count(Some(end - start)),
+            )?;
+            self.output_rows += output_batch.num_rows();
+            self.output_sender.send(output_batch).await;
+        }
+        Ok(())
+    }
+}
+
+fn skewed_key_min_rows() -> usize {
+    if is_jni_bridge_inited() {
+        conf::SMJ_SKEWED_KEY_MIN_ROWS
+            .value()
+            .map(|min_rows| min_rows.max(0) as usize)
+            .unwrap_or(0)
+    } else {
+        0 // for testing
+    }
+}
+
+fn interleave_cursor_rows(cur: &StreamCursor, indices: &[Idx]) -> Result<RecordBatch> {
+    let batch_interleaver = create_batch_interleaver(&cur.projected_batches, false)?;
+    batch_interleaver(indices)
+}
+
+/// takes at most `batch_size` rows with the same key as `last_idx` from the
+/// cursor, returns None if there are no more rows of the run. taken rows are
+/// released from the cursor except the last one, which is used for comparing.
+async fn take_equal_run_rows(
+    cur: &mut StreamCursor,
+    last_idx: &mut Idx,
+    batch_size: usize,
+) -> Result<Option<RecordBatch>> {
+    let mut indices = vec![];
+    while indices.len() < batch_size && !cur.finished && cur.key(cur.cur_idx) == cur.key(*last_idx)
+    {
+        *last_idx = cur.cur_idx;
+        indices.push(cur.cur_idx);
+        cur_forward!(cur);
+    }
+    if indices.is_empty() {
+        return Ok(None);
+    }
+    let batch = interleave_cursor_rows(cur, &indices)?;
+    cur.set_min_reserved_idx(*last_idx);
+    Ok(Some(batch))
 }
 
 #[async_trait]
@@ -111,6 +316,9 @@ impl<const L_OUTER: bool, const R_OUTER: bool> Joiner for FullJoiner<L_OUTER, R_
                     if L_OUTER {
                         self.lindices.push(lidx);
                         self.rindices.push(Idx::default());
+                    } else if curs.0.is_null_key(lidx) {
+                        // null keys never match, skip the whole null run
+                        curs.0.skip_null_run();
                     }
                     cur_forward!(curs.0);
                     if self.should_flush(curs) {
@@ -123,6 +331,9 @@ impl<const L_OUTER: bool, const R_OUTER: bool> Joiner for FullJoiner<L_OUTER, R_
                     if R_OUTER {
                         self.lindices.push(Idx::default());
                         self.rindices.push(ridx);
+                    } else if curs.1.is_null_key(ridx) {
+                        // null keys never match, skip the whole null run
+                        curs.1.skip_null_run();
                     }
                     cur_forward!(curs.1);
                     if self.should_flush(curs) {
@@ -147,6 +358,27 @@ impl<const L_OUTER: bool, const R_OUTER: bool> Joiner for FullJoiner<L_OUTER, R_
                     let mut r_equal = !curs.1.finished && curs.1.key(ridx) == curs.1.key(last_ridx);
 
                     while l_equal || r_equal {
+                        if l_equal
+                            && r_equal
+                            && self.is_spillable_equal_run(
+                                &equal_lindices,
+                                &equal_rindices,
+                                last_lidx,
+                                last_ridx,
+                            )
+                        {
+                            self.as_mut()
+                                .join_spillable_equal_run(
+                                    curs,
+                                    &equal_lindices,
+                                    &equal_rindices,
+                                    last_lidx,
+                                    last_ridx,
+                                )
+                                .await?;
+                            break;
+                        }
+
                         if l_equal {
                             for &ridx in &equal_rindices {
                                 self.lindices.push(lidx);
@@ -225,8 +457,9 @@ impl<const L_OUTER: bool, const R_OUTER: bool> Joiner for FullJoiner<L_OUTER, R_
                 .set_min_reserved_idx(*self.rindices.first().unwrap_or(&ridx));
         }
         if !self.lindices.is_empty() {
-            self.flush(curs).await?;
+            self.as_mut().flush(curs).await?;
         }
+        self.report_hottest_keys();
         Ok(())
     }
 
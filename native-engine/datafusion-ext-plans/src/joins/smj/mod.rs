@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod equal_run_buffer;
 pub mod existence_join;
 pub mod full_join;
 pub mod semi_join;
@@ -135,6 +135,9 @@ impl<const P: JoinerParams> Joiner for SemiJoiner<P> {
                 Ordering::Less => {
                     if P.join_side == L && !P.semi {
                         self.indices.push(lidx);
+                    } else if curs.0.is_null_key(lidx) {
+                        // null keys never match, skip the whole null run
+                        curs.0.skip_null_run();
                     }
                     cur_forward!(curs.0);
                     if self.should_flush(curs) {
@@ -148,6 +151,9 @@ impl<const P: JoinerParams> Joiner for SemiJoiner<P> {
                 Ordering::Greater => {
                     if P.join_side == R && !P.semi {
                         self.indices.push(ridx);
+                    } else if curs.1.is_null_key(ridx) {
+                        // null keys never match, skip the whole null run
+                        curs.1.skip_null_run();
                     }
                     cur_forward!(curs.1);
                     if self.should_flush(curs) {
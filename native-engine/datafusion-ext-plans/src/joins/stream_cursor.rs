@@ -21,7 +21,7 @@ use arrow::{
     row::{Row, RowConverter, Rows, SortField},
 };
 use datafusion::{
-    common::{JoinSide, Result},
+    common::{JoinSide, Result, ScalarValue},
     execution::SendableRecordBatchStream,
     physical_expr::PhysicalExprRef,
     physical_plan::metrics::Time,
@@ -39,6 +39,7 @@ pub struct StreamCursor {
     stream: SendableRecordBatchStream,
     key_converter: Arc<Mutex<RowConverter>>,
     key_exprs: Vec<PhysicalExprRef>,
+    null_equals_null: bool,
     poll_time: Time,
 
     // IMPORTANT:
@@ -50,6 +51,7 @@ pub struct StreamCursor {
     min_reserved_idx: Idx,
     keys: Vec<Arc<Rows>>,
     key_has_nulls: Vec<Option<NullBuffer>>,
+    key_null_runs: Vec<NullRuns>,
     num_null_batches: usize,
     mem_size: usize,
     pub finished: bool,
@@ -101,6 +103,7 @@ impl StreamCursor {
             stream,
             key_exprs,
             key_converter,
+            null_equals_null: join_params.null_equals_null,
             poll_time,
             projection: projection.to_vec(),
             projected_batch_schema: projected_null_batch.schema(),
@@ -109,6 +112,7 @@ impl StreamCursor {
             min_reserved_idx: (0, 0),
             keys: vec![empty_keys],
             key_has_nulls: vec![Some(null_nb)],
+            key_null_runs: vec![NullRuns::default()],
             num_null_batches: 1,
             mem_size: 0,
             finished: false,
@@ -138,12 +142,19 @@ impl StreamCursor {
                         .iter()
                         .map(|key| Ok(key.evaluate(&batch)?.into_array(batch.num_rows())?))
                         .collect::<Result<Vec<_>>>()?;
-                    let key_has_nulls = key_columns
-                        .iter()
-                        .map(|c| c.logical_nulls())
-                        .reduce(|lhs, rhs| NullBuffer::union(lhs.as_ref(), rhs.as_ref()))
-                        .unwrap_or(None);
+                    // null keys are compared as normal values in null-safe joins,
+                    // so they are not marked as non-matching
+                    let key_has_nulls = if self.null_equals_null {
+                        None
+                    } else {
+                        key_columns
+                            .iter()
+                            .map(|c| c.logical_nulls())
+                            .reduce(|lhs, rhs| NullBuffer::union(lhs.as_ref(), rhs.as_ref()))
+                            .unwrap_or(None)
+                    };
                     let keys = Arc::new(self.key_converter.lock().convert_columns(&key_columns)?);
+                    let key_null_runs = NullRuns::new(key_has_nulls.as_ref(), batch.num_rows());
 
                     self.mem_size += batch.get_array_mem_size();
                     self.mem_size += key_has_nulls
@@ -162,6 +173,7 @@ impl StreamCursor {
                             &RecordBatchOptions::new().with_row_count(Some(batch.num_rows())),
                         )?);
                     self.key_has_nulls.push(key_has_nulls);
+                    self.key_null_runs.push(key_null_runs);
                     self.keys.push(keys);
 
                     // fill out-dated batches with null batches
@@ -177,6 +189,7 @@ impl StreamCursor {
                             self.projected_batches[i] = self.projected_batches[0].clone();
                             self.keys[i] = self.keys[0].clone();
                             self.key_has_nulls[i] = self.key_has_nulls[0].clone();
+                            self.key_null_runs[i] = self.key_null_runs[0];
                             self.num_null_batches += 1;
                         }
                     }
@@ -198,12 +211,40 @@ impl StreamCursor {
             .unwrap_or(false)
     }
 
+    /// moves to the last row of the null key run containing current row, so
+    /// that the following `next()` steps over the whole run at once instead
+    /// of comparing row by row. only null prefix/suffix of a batch (produced
+    /// by inputs sorted with nulls first/last) are taken into account.
+    #[inline]
+    pub fn skip_null_run(&mut self) {
+        let (batch_idx, row_idx) = self.cur_idx;
+        let null_runs = &self.key_null_runs[batch_idx];
+        if row_idx < null_runs.prefix_len {
+            self.cur_idx.1 = null_runs.prefix_len - 1;
+        } else if row_idx >= null_runs.suffix_start {
+            self.cur_idx.1 = self.projected_batches[batch_idx].num_rows() - 1;
+        }
+    }
+
     #[inline]
     pub fn key<'a>(&'a self, idx: Idx) -> Row<'a> {
         let keys = &self.keys[idx.0];
         keys.row(idx.1)
     }
 
+    /// formats the key of the row, used for logging
+    pub fn key_string(&self, idx: Idx) -> Result<String> {
+        let key_columns = self
+            .key_converter
+            .lock()
+            .convert_rows(std::iter::once(self.key(idx)))?;
+        let key_values = key_columns
+            .iter()
+            .map(|col| Ok(ScalarValue::try_from_array(col, 0)?.to_string()))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(format!("[{}]", key_values.join(", ")))
+    }
+
     #[inline]
     pub fn num_buffered_batches(&self) -> usize {
         self.projected_batches.len() - self.num_null_batches
@@ -220,6 +261,37 @@ impl StreamCursor {
     }
 }
 
+/// all-null key runs at the beginning and the end of a batch
+#[derive(Clone, Copy, Default)]
+struct NullRuns {
+    prefix_len: usize,
+    suffix_start: usize,
+}
+
+impl NullRuns {
+    fn new(key_has_nulls: Option<&NullBuffer>, num_rows: usize) -> Self {
+        match key_has_nulls {
+            Some(nb) if nb.null_count() > 0 => {
+                let mut valid_slices = nb.valid_slices();
+                match valid_slices.next() {
+                    Some((first_valid, end)) => Self {
+                        prefix_len: first_valid,
+                        suffix_start: valid_slices.last().map(|(_, end)| end).unwrap_or(end),
+                    },
+                    None => Self {
+                        prefix_len: num_rows, // all keys are null
+                        suffix_start: num_rows,
+                    },
+                }
+            }
+            _ => Self {
+                prefix_len: 0,
+                suffix_start: num_rows,
+            },
+        }
+    }
+}
+
 #[macro_export]
 macro_rules! cur_forward {
     ($cur:expr) => {{
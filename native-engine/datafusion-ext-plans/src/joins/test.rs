@@ -20,7 +20,7 @@ mod tests {
         self,
         array::*,
         compute::SortOptions,
-        datatypes::{DataType, Field, Schema, SchemaRef},
+        datatypes::{DataType, Field, Schema},
         record_batch::RecordBatch,
     };
     use datafusion::{
@@ -36,7 +36,9 @@ mod tests {
     use crate::{
         broadcast_join_build_hash_map_exec::BroadcastJoinBuildHashMapExec,
         broadcast_join_exec::BroadcastJoinExec,
-        joins::join_utils::{JoinType, JoinType::*},
+        common::column_pruning::ExecuteWithColumnPruning,
+        joins::join_utils::{build_join_schema, JoinType, JoinType::*},
+        memmgr::MemManager,
         sort_merge_join_exec::SortMergeJoinExec,
     };
 
@@ -163,22 +165,6 @@ mod tests {
         Arc::new(MemoryExec::try_new(&[vec![batch]], schema, None).unwrap())
     }
 
-    fn build_join_schema_for_test(
-        left: &Schema,
-        right: &Schema,
-        join_type: JoinType,
-    ) -> Result<SchemaRef> {
-        if join_type == Existence {
-            let exists_field = Arc::new(Field::new("exists#0", DataType::Boolean, false));
-            return Ok(Arc::new(Schema::new(
-                [left.fields().to_vec(), vec![exists_field]].concat(),
-            )));
-        }
-        Ok(Arc::new(
-            build_join_schema(left, right, &join_type.try_into()?).0,
-        ))
-    }
-
     async fn join_collect(
         test_type: TestType,
         left: Arc<dyn ExecutionPlan>,
@@ -188,7 +174,7 @@ mod tests {
     ) -> Result<(Vec<String>, Vec<RecordBatch>)> {
         let session_ctx = SessionContext::new();
         let task_ctx = session_ctx.task_ctx();
-        let schema = build_join_schema_for_test(&left.schema(), &right.schema(), join_type)?;
+        let schema = build_join_schema(&left.schema(), &right.schema(), join_type)?;
 
         let join: Arc<dyn ExecutionPlan> = match test_type {
             SMJ => {
@@ -200,6 +186,7 @@ mod tests {
                     on,
                     join_type,
                     sort_options,
+                    false,
                 )?)
             }
             BHJLeftProbed => {
@@ -425,6 +412,142 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn join_null_equals_null() -> Result<()> {
+        let build_tables = || {
+            let left = build_table_i32_nullable(
+                ("a1", &vec![Some(1), Some(1), Some(2), Some(2)]),
+                ("b2", &vec![None, Some(1), Some(2), Some(2)]), // null in key field
+                ("c1", &vec![Some(1), None, Some(8), Some(9)]),
+            );
+            let right = build_table_i32_nullable(
+                ("a1", &vec![Some(1), Some(1), Some(2), Some(3)]),
+                ("b2", &vec![None, Some(1), None, Some(2)]),
+                ("c2", &vec![Some(10), Some(70), Some(80), Some(90)]),
+            );
+            (left, right)
+        };
+        let session_ctx = SessionContext::new();
+        let mut outputs = vec![];
+        for join_type in [Inner, LeftAnti] {
+            let (left, right) = build_tables();
+            let on: JoinOn = vec![
+                (
+                    Arc::new(Column::new_with_schema("a1", &left.schema())?),
+                    Arc::new(Column::new_with_schema("a1", &right.schema())?),
+                ),
+                (
+                    Arc::new(Column::new_with_schema("b2", &left.schema())?),
+                    Arc::new(Column::new_with_schema("b2", &right.schema())?),
+                ),
+            ];
+            let schema = build_join_schema(&left.schema(), &right.schema(), join_type)?;
+            let join = SortMergeJoinExec::try_new(
+                schema,
+                left,
+                right,
+                on,
+                join_type,
+                vec![SortOptions::default(); 2],
+                true,
+            )?;
+            let stream = join.execute(0, session_ctx.task_ctx())?;
+            outputs.push(common::collect(stream).await?);
+        }
+
+        // (1, null) matches (1, null), while (2, null) on right side matches nothing
+        let expected = vec![
+            "+----+----+----+----+----+----+",
+            "| a1 | b2 | c1 | a1 | b2 | c2 |",
+            "+----+----+----+----+----+----+",
+            "| 1  |    | 1  | 1  |    | 10 |",
+            "| 1  | 1  |    | 1  | 1  | 70 |",
+            "+----+----+----+----+----+----+",
+        ];
+        assert_batches_sorted_eq!(expected, &outputs[0]);
+        let expected = vec![
+            "+----+----+----+",
+            "| a1 | b2 | c1 |",
+            "+----+----+----+",
+            "| 2  | 2  | 8  |",
+            "| 2  | 2  | 9  |",
+            "+----+----+----+",
+        ];
+        assert_batches_sorted_eq!(expected, &outputs[1]);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn join_with_null_key_runs() -> Result<()> {
+        let build_batch = |a: Vec<i32>, b: Vec<Option<i32>>| {
+            let schema = Arc::new(Schema::new(vec![
+                Field::new("a", DataType::Int32, false),
+                Field::new("b", DataType::Int32, true),
+            ]));
+            RecordBatch::try_new(
+                schema,
+                vec![Arc::new(Int32Array::from(a)), Arc::new(Int32Array::from(b))],
+            )
+            .unwrap()
+        };
+
+        for (join_type, expected) in [
+            (
+                Inner,
+                vec![
+                    "+---+---+---+---+",
+                    "| a | b | a | b |",
+                    "+---+---+---+---+",
+                    "| 4 | 1 | 3 | 1 |",
+                    "| 5 | 2 | 4 | 2 |",
+                    "+---+---+---+---+",
+                ],
+            ),
+            (
+                LeftSemi,
+                vec![
+                    "+---+---+",
+                    "| a | b |",
+                    "+---+---+",
+                    "| 4 | 1 |",
+                    "| 5 | 2 |",
+                    "+---+---+",
+                ],
+            ),
+            (
+                LeftAnti,
+                vec![
+                    "+---+---+",
+                    "| a | b |",
+                    "+---+---+",
+                    "| 1 |   |",
+                    "| 2 |   |",
+                    "| 3 |   |",
+                    "| 6 | 3 |",
+                    "+---+---+",
+                ],
+            ),
+        ] {
+            // null keys (sorted with nulls first) span over batches
+            let left = build_table_from_batches(vec![
+                build_batch(vec![1, 2], vec![None, None]),
+                build_batch(vec![3, 4, 5], vec![None, Some(1), Some(2)]),
+                build_batch(vec![6], vec![Some(3)]),
+            ]);
+            let right = build_table_from_batches(vec![
+                build_batch(vec![1, 2, 3], vec![None, None, Some(1)]),
+                build_batch(vec![4], vec![Some(2)]),
+            ]);
+            let on: JoinOn = vec![(
+                Arc::new(Column::new_with_schema("b", &left.schema())?),
+                Arc::new(Column::new_with_schema("b", &right.schema())?),
+            )];
+            let (_, batches) = join_collect(SMJ, left, right, on, join_type).await?;
+            assert_batches_sorted_eq!(expected, &batches);
+        }
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
     async fn join_left_one() -> Result<()> {
         for test_type in ALL_TEST_TYPE {
@@ -963,20 +1086,236 @@ mod tests {
 
             let (_, batches) = join_collect(test_type, left, right, on, Existence).await?;
             let expected = vec![
-                "+----+----+----+----------+",
-                "| a1 | b1 | c1 | exists#0 |",
-                "+----+----+----+----------+",
-                "| 0  | 3  | 4  | false    |",
-                "| 1  | 4  | 5  | true     |",
-                "| 2  | 5  | 6  | false    |",
-                "| 3  | 6  | 7  | true     |",
-                "| 4  | 6  | 8  | true     |",
-                "| 5  | 7  | 9  | false    |",
-                "| 6  | 9  | 9  | false    |",
-                "+----+----+----+----------+",
+                "+----+----+----+--------+",
+                "| a1 | b1 | c1 | exists |",
+                "+----+----+----+--------+",
+                "| 0  | 3  | 4  | false  |",
+                "| 1  | 4  | 5  | true   |",
+                "| 2  | 5  | 6  | false  |",
+                "| 3  | 6  | 7  | true   |",
+                "| 4  | 6  | 8  | true   |",
+                "| 5  | 7  | 9  | false  |",
+                "| 6  | 9  | 9  | false  |",
+                "+----+----+----+--------+",
             ];
             assert_batches_sorted_eq!(expected, &batches);
         }
         Ok(())
     }
+
+    #[tokio::test]
+    async fn join_existence_projected() -> Result<()> {
+        let left = build_table(
+            ("a1", &vec![1, 2, 3]),
+            ("b1", &vec![4, 5, 7]),
+            ("c1", &vec![7, 8, 9]),
+        );
+        let right = build_table(
+            ("a2", &vec![10, 20, 30]),
+            ("b1", &vec![4, 5, 6]),
+            ("c2", &vec![70, 80, 90]),
+        );
+        let on: JoinOn = vec![(
+            Arc::new(Column::new_with_schema("b1", &left.schema())?),
+            Arc::new(Column::new_with_schema("b1", &right.schema())?),
+        )];
+        let schema = build_join_schema(&left.schema(), &right.schema(), Existence)?;
+        let join = SortMergeJoinExec::try_new(
+            schema,
+            left,
+            right,
+            on,
+            Existence,
+            vec![SortOptions::default()],
+            false,
+        )?;
+        let session_ctx = SessionContext::new();
+
+        // keep a1 and exists
+        let stream = join.execute_projected(0, session_ctx.task_ctx(), &[0, 3])?;
+        let batches = common::collect(stream).await?;
+        let expected = vec![
+            "+----+--------+",
+            "| a1 | exists |",
+            "+----+--------+",
+            "| 1  | true   |",
+            "| 2  | true   |",
+            "| 3  | false  |",
+            "+----+--------+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+
+        // exists is pruned
+        let stream = join.execute_projected(0, session_ctx.task_ctx(), &[1])?;
+        let batches = common::collect(stream).await?;
+        let expected = vec![
+            "+----+", "| b1 |", "+----+", "| 4  |", "| 5  |", "| 7  |", "+----+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn join_skewed_key_multiple_batches() -> Result<()> {
+        MemManager::init(10000);
+
+        // key 1 spans all batches on both sides, so the equal-key run is joined
+        // with spillable buffers
+        let build_batches = |keys: &[Vec<i32>], first_id: i32, suffix: &str| {
+            let mut next_id = first_id;
+            keys.iter()
+                .map(|batch_keys| {
+                    let ids = (next_id..next_id + batch_keys.len() as i32).collect::<Vec<_>>();
+                    next_id += batch_keys.len() as i32;
+                    build_table_i32(
+                        (&format!("a{suffix}"), &ids),
+                        (&format!("b{suffix}"), batch_keys),
+                        (&format!("c{suffix}"), &ids),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        let lbatches = build_batches(&[vec![0, 1], vec![1, 1], vec![1, 1], vec![1, 2]], 0, "1");
+        let rbatches = build_batches(&[vec![1, 1], vec![1, 1], vec![1, 1], vec![3]], 100, "2");
+        let id_and_keys = |batches: &[RecordBatch]| {
+            batches
+                .iter()
+                .flat_map(|batch| {
+                    let ids = batch.column(0).as_primitive::<types::Int32Type>();
+                    let keys = batch.column(1).as_primitive::<types::Int32Type>();
+                    ids.values()
+                        .iter()
+                        .copied()
+                        .zip(keys.values().iter().copied())
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for join_type in [Inner, Full] {
+            let left = build_table_from_batches(lbatches.clone());
+            let right = build_table_from_batches(rbatches.clone());
+            let on: JoinOn = vec![(
+                Arc::new(Column::new_with_schema("b1", &left.schema())?),
+                Arc::new(Column::new_with_schema("b2", &right.schema())?),
+            )];
+            let (_, batches) = join_collect(SMJ, left, right, on, join_type).await?;
+            let mut output_pairs = batches
+                .iter()
+                .flat_map(|batch| {
+                    let a1 = batch.column(0).as_primitive::<types::Int32Type>();
+                    let a2 = batch.column(3).as_primitive::<types::Int32Type>();
+                    a1.iter().zip(a2.iter()).collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            output_pairs.sort();
+
+            // nested loop join as expected output
+            let lrows = id_and_keys(&lbatches);
+            let rrows = id_and_keys(&rbatches);
+            let mut expected_pairs = vec![];
+            for &(lid, lkey) in &lrows {
+                for &(rid, rkey) in &rrows {
+                    if lkey == rkey {
+                        expected_pairs.push((Some(lid), Some(rid)));
+                    }
+                }
+            }
+            if join_type == Full {
+                for &(lid, lkey) in &lrows {
+                    if !rrows.iter().any(|&(_, rkey)| rkey == lkey) {
+                        expected_pairs.push((Some(lid), None));
+                    }
+                }
+                for &(rid, rkey) in &rrows {
+                    if !lrows.iter().any(|&(_, lkey)| lkey == rkey) {
+                        expected_pairs.push((None, Some(rid)));
+                    }
+                }
+            }
+            expected_pairs.sort();
+
+            assert_eq!(output_pairs.len(), if join_type == Full { 39 } else { 36 });
+            assert_eq!(output_pairs, expected_pairs);
+        }
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn join_skewed_key_metrics() -> Result<()> {
+        MemManager::init(10000);
+        let build_batches = |keys: &[Vec<i32>], suffix: &str| {
+            keys.iter()
+                .map(|batch_keys| {
+                    build_table_i32(
+                        (&format!("a{suffix}"), batch_keys),
+                        (&format!("b{suffix}"), batch_keys),
+                        (&format!("c{suffix}"), batch_keys),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // key 1 (7 x 7 rows) and key 4 (7 x 8 rows) both span 4 batches on
+        // both sides, so they are joined as skewed keys
+        let left = build_table_from_batches(build_batches(
+            &[
+                vec![1, 1],
+                vec![1, 1],
+                vec![1, 1],
+                vec![1, 2],
+                vec![4, 4],
+                vec![4, 4],
+                vec![4, 4],
+                vec![4],
+            ],
+            "1",
+        ));
+        let right = build_table_from_batches(build_batches(
+            &[
+                vec![1, 1],
+                vec![1, 1],
+                vec![1, 1],
+                vec![1, 3],
+                vec![4, 4],
+                vec![4, 4],
+                vec![4, 4],
+                vec![4, 4],
+            ],
+            "2",
+        ));
+        let on: JoinOn = vec![(
+            Arc::new(Column::new_with_schema("b1", &left.schema())?),
+            Arc::new(Column::new_with_schema("b2", &right.schema())?),
+        )];
+        let schema = build_join_schema(&left.schema(), &right.schema(), Inner)?;
+        let join = Arc::new(SortMergeJoinExec::try_new(
+            schema,
+            left,
+            right,
+            on,
+            Inner,
+            vec![SortOptions::default()],
+            false,
+        )?);
+        let task_ctx = SessionContext::new().task_ctx();
+        let batches = common::collect(join.execute(0, task_ctx)?).await?;
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 49 + 56);
+
+        let metrics = join.metrics().expect("metrics not found");
+        let metric_value = |name: &str| metrics.sum_by_name(name).map(|v| v.as_usize());
+        assert_eq!(metric_value("skewed_key_count"), Some(2));
+        assert_eq!(metric_value("skewed_key_max_rows"), Some(15));
+
+        let hottest_keys = metrics
+            .iter()
+            .filter(|m| m.value().name() == "skewed_key_rows")
+            .map(|m| (m.labels()[0].value().to_owned(), m.value().as_usize()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            hottest_keys,
+            vec![("[4]".to_owned(), 15), ("[1]".to_owned(), 14)]
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,258 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{any::Any, fmt, fmt::Formatter, sync::Arc};
+
+use arrow::{datatypes::SchemaRef, error::ArrowError};
+use blaze_jni_bridge::{jni_call_static, jni_new_global_ref, jni_new_string};
+use datafusion::{
+    datasource::physical_plan::{FileMeta, FileOpenFuture, FileOpener, FileScanConfig, FileStream},
+    error::Result,
+    execution::context::TaskContext,
+    physical_expr::EquivalenceProperties,
+    physical_plan::{
+        metrics::{Count, ExecutionPlanMetricsSet, MetricsSet},
+        DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, Partitioning, PlanProperties,
+        SendableRecordBatchStream, Statistics,
+    },
+};
+use datafusion_ext_commons::{batch_size, df_execution_err, hadoop_fs::FsProvider};
+use futures::{stream, StreamExt};
+use once_cell::sync::OnceCell;
+
+use crate::{
+    common::execution_context::ExecutionContext,
+    scan::{
+        internal_file_reader::InternalFileReader,
+        json::{JsonBatchBuilder, JsonOptions},
+        partition_pruning::{prune_dynamic_partitions, DynamicPartitionFilter},
+        text::{read_line_batches, LineReader},
+    },
+};
+
+/// Execution plan for scanning one or more json lines partitions
+#[derive(Debug, Clone)]
+pub struct JsonExec {
+    fs_resource_id: String,
+    base_config: FileScanConfig,
+    options: JsonOptions,
+    projected_statistics: Statistics,
+    projected_schema: SchemaRef,
+    metrics: ExecutionPlanMetricsSet,
+    dynamic_partition_filters: Vec<DynamicPartitionFilter>,
+    props: OnceCell<PlanProperties>,
+}
+
+impl JsonExec {
+    pub fn try_new(
+        base_config: FileScanConfig,
+        fs_resource_id: String,
+        options: JsonOptions,
+    ) -> Result<Self> {
+        // check the schema and options
+        let projection = match base_config.file_column_projection_indices() {
+            Some(proj) => proj,
+            None => (0..base_config.file_schema.fields().len()).collect(),
+        };
+        JsonBatchBuilder::try_new(&base_config.file_schema, &projection, options.clone())?;
+
+        let (projected_schema, projected_statistics, _projected_output_ordering) =
+            base_config.project();
+
+        Ok(Self {
+            fs_resource_id,
+            base_config,
+            options,
+            projected_statistics,
+            projected_schema,
+            metrics: ExecutionPlanMetricsSet::new(),
+            dynamic_partition_filters: vec![],
+            props: OnceCell::new(),
+        })
+    }
+
+    pub fn with_dynamic_partition_filters(
+        mut self,
+        dynamic_partition_filters: Vec<DynamicPartitionFilter>,
+    ) -> Self {
+        self.dynamic_partition_filters = dynamic_partition_filters;
+        self
+    }
+}
+
+impl DisplayAs for JsonExec {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut Formatter) -> fmt::Result {
+        let limit = self.base_config.limit;
+        let projection = self.base_config.projection.clone();
+        let file_group = self
+            .base_config
+            .file_groups
+            .iter()
+            .flatten()
+            .cloned()
+            .collect::<Vec<_>>();
+
+        write!(
+            f,
+            "JsonExec: file_group={:?}, limit={:?}, projection={:?}, options={:?}",
+            file_group, limit, projection, self.options,
+        )
+    }
+}
+
+impl ExecutionPlan for JsonExec {
+    fn name(&self) -> &str {
+        "JsonExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        Arc::clone(&self.projected_schema)
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.props.get_or_init(|| {
+            PlanProperties::new(
+                EquivalenceProperties::new(self.schema()),
+                Partitioning::UnknownPartitioning(self.base_config.file_groups.len()),
+                ExecutionMode::Bounded,
+            )
+        })
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        _: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(self)
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let exec_ctx = ExecutionContext::new(context, partition, self.schema(), &self.metrics);
+        let io_time = exec_ctx.register_timer_metric("io_time");
+
+        // get fs object from jni bridge resource
+        let resource_id = jni_new_string!(&self.fs_resource_id)?;
+        // resources are removed from the resource map when taken, never retry it
+        let fs = jni_call_static!(JniBridge.getResource(resource_id.as_obj()) -> JObject)?;
+        if fs.as_obj().is_null() {
+            return df_execution_err!("JsonExec: fs resource not found: {}", self.fs_resource_id);
+        }
+        let fs_provider = Arc::new(FsProvider::new(jni_new_global_ref!(fs.as_obj())?, &io_time));
+
+        let projection = match self.base_config.file_column_projection_indices() {
+            Some(proj) => proj,
+            None => (0..self.base_config.file_schema.fields().len()).collect(),
+        };
+
+        let opener = JsonOpener {
+            projection,
+            batch_size: batch_size(),
+            file_schema: self.base_config.file_schema.clone(),
+            options: self.options.clone(),
+            fs_provider,
+            bytes_scanned: exec_ctx.register_counter_metric("bytes_scanned"),
+            malformed_records: exec_ctx.register_counter_metric("malformed_records"),
+        };
+
+        let base_config = prune_dynamic_partitions(
+            &self.base_config,
+            partition,
+            &self.dynamic_partition_filters,
+            &exec_ctx.register_counter_metric("dynamic_partition_pruned_files"),
+        )?;
+        let mut file_stream = Box::pin(FileStream::new(
+            &base_config,
+            partition,
+            opener,
+            exec_ctx.execution_plan_metrics(),
+        )?);
+        let timed_stream =
+            exec_ctx
+                .clone()
+                .output_with_sender("JsonScan", move |sender| async move {
+                    sender.exclude_time(exec_ctx.baseline_metrics().elapsed_compute());
+                    let _timer = exec_ctx.baseline_metrics().elapsed_compute().timer();
+                    while let Some(batch) = file_stream.next().await.transpose()? {
+                        sender.send(batch).await;
+                    }
+                    Ok(())
+                });
+        Ok(timed_stream)
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        Ok(self.projected_statistics.clone())
+    }
+}
+
+struct JsonOpener {
+    projection: Vec<usize>,
+    batch_size: usize,
+    file_schema: SchemaRef,
+    options: JsonOptions,
+    fs_provider: Arc<FsProvider>,
+    bytes_scanned: Count,
+    malformed_records: Count,
+}
+
+impl FileOpener for JsonOpener {
+    fn open(&self, file_meta: FileMeta) -> Result<FileOpenFuture> {
+        let reader = Arc::new(InternalFileReader::try_new(
+            self.fs_provider.clone(),
+            file_meta.object_meta.clone(),
+        )?);
+        let file_size = file_meta.object_meta.size;
+        let range = file_meta
+            .range
+            .as_ref()
+            .map(|range| range.start as usize..range.end as usize)
+            .unwrap_or(0..file_size);
+
+        // like spark, only projected fields are converted and checked
+        let batch_builder =
+            JsonBatchBuilder::try_new(&self.file_schema, &self.projection, self.options.clone())?;
+        let batch_size = self.batch_size;
+        let bytes_scanned = self.bytes_scanned.clone();
+        let malformed_records = self.malformed_records.clone();
+
+        Ok(Box::pin(async move {
+            let line_reader = LineReader::try_new(reader, range, bytes_scanned)?;
+            let batches = read_line_batches(
+                line_reader,
+                batch_builder,
+                batch_size,
+                false, // json lines have no header
+                malformed_records,
+            )
+            .map(|batch| batch.map_err(|err| ArrowError::ExternalError(Box::new(err))));
+            Ok(stream::iter(batches).boxed())
+        }))
+    }
+}
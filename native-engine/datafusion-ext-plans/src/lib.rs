@@ -25,25 +25,35 @@
 pub mod agg_exec;
 pub mod broadcast_join_build_hash_map_exec;
 pub mod broadcast_join_exec;
+pub mod broadcast_nested_loop_join_exec;
+pub mod coalesce_partitions_exec;
+pub mod csv_exec;
 pub mod debug_exec;
 pub mod empty_partitions_exec;
 pub mod expand_exec;
 pub mod ffi_reader_exec;
 pub mod filter_exec;
 pub mod generate_exec;
+pub mod interval_join_exec;
 pub mod ipc_reader_exec;
 pub mod ipc_writer_exec;
+pub mod json_exec;
 pub mod limit_exec;
 pub mod orc_exec;
 pub mod parquet_exec;
 pub mod parquet_sink_exec;
 pub mod project_exec;
+pub mod range_exec;
 pub mod rename_columns_exec;
 pub mod rss_shuffle_writer_exec;
 pub mod shuffle_writer_exec;
 pub mod sort_exec;
 pub mod sort_merge_join_exec;
+pub mod take_ordered_and_project_exec;
+pub mod top_k_frequent_exec;
+pub mod union_exec;
 pub mod window_exec;
+pub mod window_group_limit_exec;
 
 // memory management
 pub mod memmgr;
@@ -53,6 +63,6 @@ pub mod agg;
 pub mod common;
 pub mod generate;
 pub mod joins;
-mod scan;
-mod shuffle;
+pub mod scan;
+pub mod shuffle;
 pub mod window;
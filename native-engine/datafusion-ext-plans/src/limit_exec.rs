@@ -18,21 +18,47 @@ use datafusion::{
 use futures::StreamExt;
 use once_cell::sync::OnceCell;
 
-use crate::common::execution_context::ExecutionContext;
+use crate::{
+    common::{
+        execution_context::ExecutionContext,
+        statistics::{limit_statistics, scale_statistics, skip_statistics},
+    },
+    sort_exec::SortExec,
+};
 
 #[derive(Debug)]
 pub struct LimitExec {
     input: Arc<dyn ExecutionPlan>,
     limit: u64,
+    offset: u64,
     pub metrics: ExecutionPlanMetricsSet,
     props: OnceCell<PlanProperties>,
 }
 
 impl LimitExec {
-    pub fn new(input: Arc<dyn ExecutionPlan>, limit: u64) -> Self {
+    /// creates a LimitExec which skips the first `offset` rows and then
+    /// outputs at most `limit` rows
+    pub fn new(input: Arc<dyn ExecutionPlan>, limit: u64, offset: u64) -> Self {
+        // folds limit into the sort below, so that the sorter stops at the
+        // limit when sorting and merging spills. offset is also folded, so
+        // that skipped rows are discarded when merging.
+        let is_foldable = |sort: &SortExec| {
+            let sort_limit = sort.fetch().map(|f| f.saturating_sub(sort.offset()) as u64);
+            offset > 0 || !matches!(sort_limit, Some(sort_limit) if sort_limit <= limit)
+        };
+        let (input, offset) = match input.as_any().downcast_ref::<SortExec>() {
+            Some(sort) if is_foldable(sort) => {
+                let limit = limit.min(usize::MAX as u64) as usize;
+                let offset = offset.min(usize::MAX as u64) as usize;
+                let sort = sort.with_limit_and_offset(limit, offset);
+                (Arc::new(sort) as Arc<dyn ExecutionPlan>, 0)
+            }
+            _ => (input, offset),
+        };
         Self {
             input,
             limit,
+            offset,
             metrics: ExecutionPlanMetricsSet::new(),
             props: OnceCell::new(),
         }
@@ -41,7 +67,11 @@ impl LimitExec {
 
 impl DisplayAs for LimitExec {
     fn fmt_as(&self, _t: DisplayFormatType, f: &mut Formatter) -> std::fmt::Result {
-        write!(f, "LimitExec(limit={})", self.limit)
+        if self.offset > 0 {
+            write!(f, "LimitExec(limit={}, offset={})", self.limit, self.offset)
+        } else {
+            write!(f, "LimitExec(limit={})", self.limit)
+        }
     }
 }
 
@@ -76,7 +106,11 @@ impl ExecutionPlan for LimitExec {
         self: Arc<Self>,
         children: Vec<Arc<dyn ExecutionPlan>>,
     ) -> Result<Arc<dyn ExecutionPlan>> {
-        Ok(Arc::new(Self::new(children[0].clone(), self.limit)))
+        Ok(Arc::new(Self::new(
+            children[0].clone(),
+            self.limit,
+            self.offset,
+        )))
     }
 
     fn execute(
@@ -86,31 +120,52 @@ impl ExecutionPlan for LimitExec {
     ) -> Result<SendableRecordBatchStream> {
         let exec_ctx = ExecutionContext::new(context, partition, self.schema(), &self.metrics);
         let input = exec_ctx.execute_with_input_stats(&self.input)?;
-        execute_limit(input, self.limit, exec_ctx)
+        execute_limit(input, self.limit, self.offset, exec_ctx)
     }
 
     fn statistics(&self) -> Result<Statistics> {
-        todo!()
+        let limit = self.limit.min(usize::MAX as u64) as usize;
+        let offset = self.offset.min(usize::MAX as u64) as usize;
+        let input_stats = self.input.statistics()?;
+        let num_partitions = self.output_partitioning().partition_count();
+        if num_partitions <= 1 {
+            let fetched_stats = limit_statistics(input_stats, limit.saturating_add(offset));
+            return Ok(skip_statistics(fetched_stats, offset));
+        }
+
+        // limit is applied to each partition, whose number of rows is unknown
+        let fetch = limit.saturating_mul(num_partitions);
+        Ok(scale_statistics(limit_statistics(input_stats, fetch), 1.0))
     }
 }
 
 fn execute_limit(
     mut input: SendableRecordBatchStream,
     limit: u64,
+    offset: u64,
     exec_ctx: Arc<ExecutionContext>,
 ) -> Result<SendableRecordBatchStream> {
     Ok(exec_ctx
         .clone()
         .output_with_sender("Limit", move |sender| async move {
-            let mut cur = 0;
-            while let Some(batch) = input.next().await.transpose()? {
-                cur += batch.num_rows() as u64;
-                if cur >= limit {
-                    sender.send(batch.slice(0, limit as usize)).await;
-                    break;
+            let mut num_skipped = 0;
+            let mut num_output = 0;
+            while num_output < limit {
+                let batch = match input.next().await.transpose()? {
+                    Some(batch) => batch,
+                    None => break,
+                };
+
+                // skips offset rows
+                let num_rows = batch.num_rows() as u64;
+                let skip = (offset - num_skipped).min(num_rows);
+                num_skipped += skip;
+                let len = (num_rows - skip).min(limit - num_output);
+                if len == 0 {
+                    continue;
                 }
-                cur += batch.num_rows() as u64;
-                sender.send(batch).await;
+                num_output += len;
+                sender.send(batch.slice(skip as usize, len as usize)).await;
             }
             Ok(())
         }))
@@ -122,17 +177,19 @@ mod test {
 
     use arrow::{
         array::Int32Array,
+        compute::SortOptions,
         datatypes::{DataType, Field, Schema},
         record_batch::RecordBatch,
     };
     use datafusion::{
         assert_batches_eq,
         common::Result,
+        physical_expr::{expressions::Column, PhysicalSortExpr},
         physical_plan::{common, memory::MemoryExec, ExecutionPlan},
         prelude::SessionContext,
     };
 
-    use crate::{limit_exec::LimitExec, memmgr::MemManager};
+    use crate::{limit_exec::LimitExec, memmgr::MemManager, sort_exec::SortExec};
 
     fn build_table_i32(
         a: (&str, &Vec<i32>),
@@ -174,7 +231,7 @@ mod test {
             ("b", &vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9]),
             ("c", &vec![5, 6, 7, 8, 9, 0, 1, 2, 3, 4]),
         );
-        let limit_exec = LimitExec::new(input, 2_u64);
+        let limit_exec = LimitExec::new(input, 2_u64, 0);
         let session_ctx = SessionContext::new();
         let task_ctx = session_ctx.task_ctx();
         let output = limit_exec.execute(0, task_ctx).unwrap();
@@ -191,4 +248,87 @@ mod test {
         assert_batches_eq!(expected, &batches);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_limit_folded_into_sort() -> Result<()> {
+        MemManager::init(10000);
+        let input = build_table(
+            ("a", &vec![9, 8, 7, 6, 5, 4, 3, 2, 1, 0]),
+            ("b", &vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9]),
+            ("c", &vec![5, 6, 7, 8, 9, 0, 1, 2, 3, 4]),
+        );
+        let sort_exec = Arc::new(SortExec::new(
+            input,
+            vec![PhysicalSortExpr {
+                expr: Arc::new(Column::new("c", 2)),
+                options: SortOptions::default(),
+            }],
+            None,
+        ));
+        let limit_exec = LimitExec::new(sort_exec, 3_u64, 0);
+        let folded_sort_exec = limit_exec.children()[0]
+            .as_any()
+            .downcast_ref::<SortExec>()
+            .expect("SortExec");
+        assert_eq!(folded_sort_exec.fetch(), Some(3));
+
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+        let output = limit_exec.execute(0, task_ctx).unwrap();
+        let batches = common::collect(output).await?;
+
+        let expected = vec![
+            "+---+---+---+",
+            "| a | b | c |",
+            "+---+---+---+",
+            "| 4 | 5 | 0 |",
+            "| 3 | 6 | 1 |",
+            "| 2 | 7 | 2 |",
+            "+---+---+---+",
+        ];
+        assert_batches_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_limit_exec_with_offset() -> Result<()> {
+        MemManager::init(10000);
+        let input = build_table(
+            ("a", &vec![9, 8, 7, 6, 5, 4, 3, 2, 1, 0]),
+            ("b", &vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9]),
+            ("c", &vec![5, 6, 7, 8, 9, 0, 1, 2, 3, 4]),
+        );
+        let sort_exec = Arc::new(SortExec::new(
+            input,
+            vec![PhysicalSortExpr {
+                expr: Arc::new(Column::new("c", 2)),
+                options: SortOptions::default(),
+            }],
+            None,
+        ));
+        let limit_exec = LimitExec::new(sort_exec, 3_u64, 2_u64);
+        let folded_sort_exec = limit_exec.children()[0]
+            .as_any()
+            .downcast_ref::<SortExec>()
+            .expect("SortExec");
+        assert_eq!(folded_sort_exec.fetch(), Some(5));
+        assert_eq!(folded_sort_exec.offset(), 2);
+
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+        let output = limit_exec.execute(0, task_ctx).unwrap();
+        let batches = common::collect(output).await?;
+
+        let expected = vec![
+            "+---+---+---+",
+            "| a | b | c |",
+            "+---+---+---+",
+            "| 2 | 7 | 2 |",
+            "| 1 | 8 | 3 |",
+            "| 0 | 9 | 4 |",
+            "+---+---+---+",
+        ];
+        assert_batches_eq!(expected, &batches);
+        Ok(())
+    }
 }
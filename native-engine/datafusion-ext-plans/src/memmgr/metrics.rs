@@ -16,13 +16,15 @@ use datafusion::physical_plan::metrics::{
     Count, ExecutionPlanMetricsSet, Gauge, MetricBuilder, Time,
 };
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct SpillMetrics {
     pub mem_spill_count: Count,
     pub mem_spill_size: Gauge,
     pub mem_spill_iotime: Time,
     pub disk_spill_size: Gauge,
     pub disk_spill_iotime: Time,
+    pub spill_count: Count,   // number of spills triggered by mem manager
+    pub mem_used_peak: Gauge, // peak memory used of the consumer
 }
 
 impl SpillMetrics {
@@ -35,6 +37,8 @@ impl SpillMetrics {
             disk_spill_size: MetricBuilder::new(metrics).gauge("disk_spill_size", partition),
             disk_spill_iotime: MetricBuilder::new(metrics)
                 .subset_time("disk_spill_iotime", partition),
+            spill_count: MetricBuilder::new(metrics).counter("spill_count", partition),
+            mem_used_peak: MetricBuilder::new(metrics).gauge("mem_used_peak", partition),
         }
     }
 }
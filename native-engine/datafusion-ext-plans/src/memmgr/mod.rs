@@ -13,7 +13,10 @@
 // limitations under the License.
 
 pub mod metrics;
+pub mod off_heap;
+pub mod quota;
 pub mod spill;
+pub mod spill_manager;
 
 use std::{
     sync::{Arc, Weak},
@@ -27,6 +30,14 @@ use datafusion::common::Result;
 use once_cell::sync::OnceCell;
 use parking_lot::{Condvar, Mutex};
 
+use crate::{
+    common::resource_usage::TaskMemUsage,
+    memmgr::{
+        metrics::SpillMetrics,
+        quota::{consumer_shares, MemConsumerType},
+    },
+};
+
 static MEM_MANAGER: OnceCell<Arc<MemManager>> = OnceCell::new();
 
 // never triggers waiting/spilling for consumers which use very little memory
@@ -64,6 +75,17 @@ impl MemManager {
         MEM_MANAGER.get().expect("mem manager not initialized")
     }
 
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// total memory including data booked in the off-heap quota, which is
+    /// charged to spark's off-heap pool instead of the native memory pool
+    fn total_with_off_heap(&self) -> usize {
+        let booked = off_heap::OffHeapQuota::get().map(|quota| quota.used());
+        self.total + booked.unwrap_or(0)
+    }
+
     pub fn num_consumers(&self) -> usize {
         self.consumers.lock().len()
     }
@@ -82,7 +104,12 @@ impl MemManager {
             status: Mutex::new(MemConsumerStatus {
                 mem_used: 0,
                 spillable,
+                spill_requested: None,
             }),
+            consumer_type: consumer.consumer_type(),
+            spill_cost: consumer.spill_cost(),
+            spill_metrics: consumer.spill_metrics().cloned(),
+            task_mem_usage: TaskMemUsage::current(),
         });
         log::info!("mem manager registering consumer: {}", consumer.name());
 
@@ -115,6 +142,10 @@ impl MemManager {
         assert!(mm_status.total_used >= consumer_status.mem_used);
         mm_status.num_consumers -= 1;
         mm_status.update_total_used_with_diff(-(consumer_status.mem_used as isize));
+        mm_status.type_used[consumer_info.consumer_type.index()] -= consumer_status.mem_used;
+        if let Some(task_mem_usage) = &consumer_info.task_mem_usage {
+            task_mem_usage.update_with_diff(-(consumer_status.mem_used as isize));
+        }
 
         // update mm spillable status
         if consumer_status.spillable {
@@ -147,16 +178,83 @@ impl MemManager {
         );
         drop(mm_status);
 
+        if let Some(quota) = off_heap::OffHeapQuota::get() {
+            log::info!(
+                "off-heap booking quota status: capacity: {}, used: {}",
+                ByteSize(quota.capacity() as u64),
+                ByteSize(quota.used() as u64),
+            );
+        }
+
         for consumer in &*self.consumers.lock() {
             let consumer_status = consumer.status.lock();
             log::info!(
-                "* consumer: {}, spillable: {}, mem_used: {}",
+                "* consumer: {}, type: {:?}, spillable: {}, spill_cost: {}, mem_used: {}",
                 consumer.name,
+                consumer.consumer_type,
                 consumer_status.spillable,
+                consumer.spill_cost,
                 ByteSize(consumer_status.mem_used as u64),
             );
         }
     }
+
+    /// selects the spill victim among spillable consumers, which is the one
+    /// with the largest memory used weighted by the inverse of its spill cost.
+    /// consumers of types within their minimum shares are never selected.
+    /// returns None if the consumer itself is selected, otherwise the victim
+    /// is requested to spill on its next memory update.
+    fn select_spill_victim(
+        &self,
+        consumer_info: &Arc<MemConsumerInfo>,
+        spill_size: usize,
+    ) -> Option<Arc<MemConsumerInfo>> {
+        let weight = |info: &MemConsumerInfo, status: &MemConsumerStatus| {
+            status.mem_used as f64 / info.spill_cost.max(f64::EPSILON)
+        };
+        let self_weight = weight(consumer_info, &consumer_info.status.lock());
+        let type_used = self.status.lock().type_used;
+
+        let mm_consumers = self.consumers.lock();
+        let mut victim: Option<(&Arc<MemConsumerInfo>, f64)> = None;
+        for other in mm_consumers.iter() {
+            if Arc::ptr_eq(other, consumer_info) {
+                continue;
+            }
+            let other_status = other.status.lock();
+            if !other_status.spillable
+                || other_status.spill_requested.is_some()
+                || other_status.mem_used <= MIN_TRIGGER_SIZE.max(spill_size)
+                || self.is_type_reserved(other.consumer_type, type_used)
+            {
+                continue;
+            }
+            let other_weight = weight(other, &other_status);
+            if other_weight > victim.map(|(_, w)| w).unwrap_or(self_weight) {
+                victim = Some((other, other_weight));
+            }
+        }
+        let (victim, _) = victim?;
+        victim.status.lock().spill_requested = Some(spill_size);
+        Some(victim.clone())
+    }
+
+    /// returns the max memory of the consumer type
+    fn type_mem_max(&self, consumer_type: MemConsumerType) -> usize {
+        (self.total_with_off_heap() as f64 * consumer_shares()[consumer_type.index()].max) as usize
+    }
+
+    /// returns whether memory used of the consumer type is within its
+    /// minimum share
+    fn is_type_reserved(
+        &self,
+        consumer_type: MemConsumerType,
+        type_used: [usize; MemConsumerType::NUM_TYPES],
+    ) -> bool {
+        let type_mem_min =
+            self.total_with_off_heap() as f64 * consumer_shares()[consumer_type.index()].min;
+        type_used[consumer_type.index()] as f64 <= type_mem_min
+    }
 }
 
 #[derive(Default, Clone, Copy)]
@@ -165,6 +263,7 @@ struct MemManagerStatus {
     total_used: usize,
     num_spillables: usize,
     mem_spillables: usize,
+    type_used: [usize; MemConsumerType::NUM_TYPES], // memory used of each consumer type
 }
 
 impl MemManagerStatus {
@@ -186,12 +285,25 @@ impl MemManagerStatus {
 pub struct MemConsumerInfo {
     name: String,
     status: Mutex<MemConsumerStatus>,
+    consumer_type: MemConsumerType,
+    spill_cost: f64,
+    spill_metrics: Option<SpillMetrics>, // memory usage and spills are published to spark ui
+    task_mem_usage: Option<Arc<TaskMemUsage>>, // usage of the task registering the consumer
+}
+
+impl MemConsumerInfo {
+    fn record_spill(&self) {
+        if let Some(spill_metrics) = &self.spill_metrics {
+            spill_metrics.spill_count.add(1);
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
 struct MemConsumerStatus {
     mem_used: usize,
     spillable: bool,
+    spill_requested: Option<usize>, // requested by other consumers to spill
 }
 
 #[async_trait]
@@ -208,7 +320,7 @@ pub trait MemConsumer: Send + Sync {
 
     fn mem_used_percent(&self) -> f64 {
         let mm = MemManager::get();
-        let total = mm.total;
+        let total = mm.total_with_off_heap();
         let mm_status = *mm.status.lock();
 
         let mem_unspillable = mm_status.total_used - mm_status.mem_spillables;
@@ -266,10 +378,33 @@ pub trait MemConsumer: Send + Sync {
         .await
     }
 
+    /// type of the consumer, which memory shares are applied to
+    fn consumer_type(&self) -> MemConsumerType {
+        MemConsumerType::Other
+    }
+
+    /// metrics of the operator owning this consumer, which memory usage and
+    /// spill counts are recorded to
+    fn spill_metrics(&self) -> Option<&SpillMetrics> {
+        None
+    }
+
+    /// relative cost of reading spilled data back, consumers with lower cost
+    /// are preferred when selecting spill victims
+    fn spill_cost(&self) -> f64 {
+        1.0
+    }
+
     /// spills this consumer and returns used memory after spilling
     async fn spill(&self) -> Result<()> {
         unimplemented!()
     }
+
+    /// spills at least the given size of this consumer, consumers that cannot
+    /// spill partially spill all their data
+    async fn spill_partial(&self, _spill_size: usize) -> Result<()> {
+        self.spill().await
+    }
 }
 
 async fn update_consumer_mem_used_with_custom_updater(
@@ -279,13 +414,13 @@ async fn update_consumer_mem_used_with_custom_updater(
     let consumer_name = consumer.name();
     let mm = MemManager::get();
     let consumer_info = consumer.consumer_info();
-    let total = mm.total;
+    let total = mm.total_with_off_heap();
 
     #[derive(Clone, Copy, PartialEq)]
     enum Operation {
-        Spill,   // spill this consumer
-        Wait,    // wait other consumers to spill
-        Nothing, // do nothing
+        Spill(usize), // spill at least the given size of this consumer
+        Wait,         // wait other consumers to spill
+        Nothing,      // do nothing
     }
 
     let (mem_unspillable, mem_jvm_direct_used);
@@ -296,10 +431,21 @@ async fn update_consumer_mem_used_with_custom_updater(
         // update consumer info
         let (old_used, new_used) = updater(&mut consumer_status);
         let spillable = consumer_status.spillable;
+        let spill_requested = consumer_status.spill_requested.take();
         let diff_used = new_used as isize - old_used as isize;
 
         // update mm status
         let total_used = mm_status.update_total_used_with_diff(diff_used);
+        let type_idx = consumer_info.consumer_type.index();
+        assert!(mm_status.type_used[type_idx] as isize + diff_used >= 0);
+        mm_status.type_used[type_idx] =
+            (mm_status.type_used[type_idx] as isize + diff_used) as usize;
+        if let Some(task_mem_usage) = &consumer_info.task_mem_usage {
+            task_mem_usage.update_with_diff(diff_used);
+        }
+        if let Some(spill_metrics) = &consumer_info.spill_metrics {
+            spill_metrics.mem_used_peak.set_max(new_used);
+        }
 
         // update mm spillable status
         if consumer_status.spillable {
@@ -312,9 +458,23 @@ async fn update_consumer_mem_used_with_custom_updater(
             return Ok(());
         }
 
+        // spill as requested by other consumers
+        if let Some(spill_size) = spill_requested {
+            drop(consumer_status);
+            drop(mm_status);
+            log::info!(
+                "mem manager spilling {consumer_name} (mem_used: {}) as requested, spill_size: {}",
+                ByteSize(new_used as u64),
+                ByteSize(spill_size as u64),
+            );
+            consumer_info.record_spill();
+            return consumer.spill_partial(spill_size).await;
+        }
+
         // unlock
         let num_spillables = mm_status.num_spillables;
         let mem_spillables = mm_status.mem_spillables;
+        let type_used = mm_status.type_used;
         drop(consumer_status);
         drop(mm_status);
 
@@ -330,14 +490,48 @@ async fn update_consumer_mem_used_with_custom_updater(
         let consumer_mem_max = total_managed / num_spillables;
         let consumer_mem_min = consumer_mem_max / 8;
 
+        // consumers of types exceeding their maximum shares are always overflowed,
+        // and those within their minimum shares are not limited by fair shares
+        let consumer_type = consumer_info.consumer_type;
+        let type_mem_max = mm.type_mem_max(consumer_type);
+        let type_overflowed = type_used[type_idx] > type_mem_max;
+        let type_reserved = mm.is_type_reserved(consumer_type, type_used);
+
         let total_overflowed = total_used > total_managed;
-        let consumer_overflowed = new_used > consumer_mem_max;
+        let consumer_overflowed =
+            type_overflowed || (new_used > consumer_mem_max && !type_reserved);
         let operation = if (total_overflowed || consumer_overflowed)
             && new_used > MIN_TRIGGER_SIZE
             && new_used > old_used
         {
+            // spill at least half of the memory used, avoiding too many small spills
+            let overflowed_size = if type_overflowed {
+                type_used[type_idx] - type_mem_max
+            } else if consumer_overflowed {
+                new_used - consumer_mem_max
+            } else {
+                total_used - total_managed
+            };
+            let spill_size = overflowed_size.max(new_used / 2);
+
             if spillable && new_used > consumer_mem_min {
-                Operation::Spill
+                // other consumers may be better victims if this one is not overflowed
+                let victim = if consumer_overflowed {
+                    None
+                } else {
+                    mm.select_spill_victim(&consumer_info, spill_size)
+                };
+                match victim {
+                    Some(victim) => {
+                        log::info!(
+                            "mem manager: {consumer_name} requests {} to spill {}",
+                            victim.name,
+                            ByteSize(spill_size as u64),
+                        );
+                        Operation::Wait
+                    }
+                    None => Operation::Spill(spill_size),
+                }
             } else {
                 Operation::Wait
             }
@@ -359,21 +553,23 @@ async fn update_consumer_mem_used_with_custom_updater(
 
         if wait.timed_out() {
             log::warn!("mem manager: consumer {consumer_name} timeout waiting for resources");
-            operation = Operation::Spill;
+            operation = Operation::Spill(mem_used);
         }
     }
 
     // trigger spilling
-    if operation == Operation::Spill {
+    if let Operation::Spill(spill_size) = operation {
         log::info!(
-            "mem manager spilling {consumer_name} (mem_used: {}), total: {}/{}, unspillable: {}, jvm_direct: {}",
+            "mem manager spilling {consumer_name} (mem_used: {}, spill_size: {}), total: {}/{}, unspillable: {}, jvm_direct: {}",
             ByteSize(mem_used as u64),
+            ByteSize(spill_size as u64),
             ByteSize(total_used as u64),
-            ByteSize(mm.total as u64),
+            ByteSize(total as u64),
             ByteSize(mem_unspillable as u64),
             ByteSize(mem_jvm_direct_used as u64),
         );
-        consumer.spill().await?;
+        consumer_info.record_spill();
+        consumer.spill_partial(spill_size).await?;
         return Ok(());
     }
     Ok(())
@@ -0,0 +1,322 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+
+use blaze_jni_bridge::{
+    conf,
+    conf::{BooleanConf, DoubleConf, LongConf},
+    is_jni_bridge_inited, jni_call, jni_call_static, jni_new_global_ref,
+};
+use bytesize::ByteSize;
+use datafusion::common::Result;
+use jni::{objects::GlobalRef, sys::jlong};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+
+use crate::memmgr::MemConsumer;
+
+static OFF_HEAP_QUOTA: OnceCell<OffHeapQuota> = OnceCell::new();
+
+/// An accounting-only quota of spark.memory.offHeap.size for buffered shuffle
+/// data. no memory is allocated here: buffered data stays in native buffers,
+/// and only its size is booked against the quota and spark's off-heap
+/// execution pool of the task, so it is never counted twice by spark and
+/// blaze. booked bytes are still reported to the MemManager, which extends the
+/// native memory pool by the bytes booked here instead of charging them to the
+/// pool sized by executor memory overhead.
+pub struct OffHeapQuota {
+    capacity: usize,
+    used: AtomicUsize,
+}
+
+impl OffHeapQuota {
+    /// initializes the global quota, off-heap booking is disabled if capacity
+    /// is zero
+    pub fn init(capacity: usize) {
+        if capacity == 0 {
+            return;
+        }
+        OFF_HEAP_QUOTA.get_or_init(|| {
+            log::info!(
+                "off-heap booking quota initialized with capacity: {}",
+                ByteSize(capacity as u64),
+            );
+            OffHeapQuota::new(capacity)
+        });
+    }
+
+    /// returns the global quota, or None if off-heap booking is disabled
+    pub fn get() -> Option<&'static OffHeapQuota> {
+        OFF_HEAP_QUOTA.get()
+    }
+
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            used: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn capacity_from_conf() -> Result<usize> {
+        if !is_jni_bridge_inited()
+            || !conf::SHUFFLE_OFF_HEAP_STAGING_ENABLE.value()?
+            || !conf::OFF_HEAP_MEMORY_ENABLED.value()?
+        {
+            return Ok(0);
+        }
+        let size = conf::OFF_HEAP_MEMORY_SIZE.value()?.max(0) as usize;
+        let fraction = conf::SHUFFLE_OFF_HEAP_STAGING_FRACTION.value()?;
+        Ok((size as f64 * fraction) as usize)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn used(&self) -> usize {
+        self.used.load(SeqCst)
+    }
+
+    /// creates an empty reservation of the current task, which can be grown
+    /// with `try_resize()`
+    pub fn new_reservation(&'static self) -> Result<OffHeapReservation> {
+        let jvm_memory = if is_jni_bridge_inited() {
+            let jvm_memory = jni_call_static!(JniBridge.getTaskOffHeapStagingMemory() -> JObject)?;
+            Some(jni_new_global_ref!(jvm_memory.as_obj())?)
+        } else {
+            None
+        };
+        Ok(OffHeapReservation {
+            quota: self,
+            jvm_memory,
+            size: 0,
+        })
+    }
+
+    fn try_grow(&self, diff: usize) -> bool {
+        self.used
+            .fetch_update(SeqCst, SeqCst, |used| {
+                used.checked_add(diff)
+                    .filter(|&new_used| new_used <= self.capacity)
+            })
+            .is_ok()
+    }
+
+    fn shrink(&self, diff: usize) {
+        let old_used = self.used.fetch_sub(diff, SeqCst);
+        assert!(old_used >= diff);
+    }
+}
+
+/// Bytes booked in the off-heap quota and spark's off-heap execution pool,
+/// released on drop
+pub struct OffHeapReservation {
+    quota: &'static OffHeapQuota,
+    jvm_memory: Option<GlobalRef>,
+    size: usize,
+}
+
+impl OffHeapReservation {
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// resizes the reservation, returns false (and keeps the old size) if the
+    /// quota or spark's off-heap pool does not have enough free memory
+    pub fn try_resize(&mut self, new_size: usize) -> Result<bool> {
+        if new_size <= self.size {
+            let diff = self.size - new_size;
+            self.quota.shrink(diff);
+            self.size = new_size;
+            if let Some(jvm_memory) = self.jvm_memory.as_ref().filter(|_| diff > 0) {
+                jni_call!(BlazeOffHeapStagingMemory(jvm_memory.as_obj())
+                    .release(diff as jlong) -> ())?;
+            }
+            return Ok(true);
+        }
+
+        let diff = new_size - self.size;
+        if !self.quota.try_grow(diff) {
+            return Ok(false);
+        }
+        if let Some(jvm_memory) = &self.jvm_memory {
+            let acquired = jni_call!(BlazeOffHeapStagingMemory(jvm_memory.as_obj())
+                .acquire(diff as jlong) -> jlong)
+            .inspect_err(|_| self.quota.shrink(diff))?;
+            if (acquired as usize) < diff {
+                self.quota.shrink(diff);
+                jni_call!(BlazeOffHeapStagingMemory(jvm_memory.as_obj())
+                    .release(acquired) -> ())?;
+                return Ok(false);
+            }
+        }
+        self.size = new_size;
+        Ok(true)
+    }
+
+    pub fn free(&mut self) -> Result<()> {
+        self.try_resize(0)?;
+        Ok(())
+    }
+}
+
+impl Drop for OffHeapReservation {
+    fn drop(&mut self) {
+        if let Err(e) = self.free() {
+            log::warn!("error releasing off-heap booked memory: {e}");
+        }
+    }
+}
+
+/// Books the size of a consumer's buffered data in the off-heap quota
+#[derive(Default)]
+pub struct OffHeapBooking {
+    reservation: Mutex<Option<OffHeapReservation>>,
+}
+
+impl OffHeapBooking {
+    /// updates memory used by the consumer's buffered data, which is booked in
+    /// the off-heap quota if possible, or in the native memory pool when the
+    /// quota is exhausted. the memory used is always reported to the
+    /// MemManager so that the consumer is spilled like other consumers.
+    pub async fn update_mem_used(
+        &self,
+        consumer: &impl MemConsumer,
+        mem_used: usize,
+    ) -> Result<()> {
+        self.book(mem_used)?;
+        consumer.update_mem_used(mem_used).await
+    }
+
+    fn book(&self, mem_used: usize) -> Result<()> {
+        let Some(quota) = OffHeapQuota::get() else {
+            return Ok(());
+        };
+        let mut reservation = self.reservation.lock();
+        if reservation.is_none() {
+            *reservation = Some(quota.new_reservation()?);
+        }
+        let reservation = reservation.as_mut().expect("reservation not initialized");
+        if !reservation.try_resize(mem_used)? {
+            reservation.free()?;
+        }
+        Ok(())
+    }
+
+    /// returns memory currently booked in the off-heap quota
+    pub fn booked(&self) -> usize {
+        self.reservation
+            .lock()
+            .as_ref()
+            .map(|r| r.size())
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Weak};
+
+    use async_trait::async_trait;
+    use datafusion::common::Result;
+
+    use crate::memmgr::{
+        off_heap::{OffHeapBooking, OffHeapQuota},
+        MemConsumer, MemConsumerInfo, MemManager,
+    };
+
+    #[test]
+    fn test_off_heap_reservation() -> Result<()> {
+        let quota: &'static OffHeapQuota = Box::leak(Box::new(OffHeapQuota::new(100)));
+        let mut r1 = quota.new_reservation()?;
+        let mut r2 = quota.new_reservation()?;
+
+        assert!(r1.try_resize(60)?);
+        assert!(!r2.try_resize(50)?);
+        assert_eq!(r2.size(), 0);
+        assert!(r2.try_resize(40)?);
+        assert_eq!(quota.used(), 100);
+
+        assert!(r1.try_resize(10)?);
+        assert_eq!(quota.used(), 50);
+        drop(r1);
+        assert_eq!(quota.used(), 40);
+        drop(r2);
+        assert_eq!(quota.used(), 0);
+        Ok(())
+    }
+
+    struct TestConsumer {
+        consumer_info: Option<Weak<MemConsumerInfo>>,
+        booking: OffHeapBooking,
+    }
+
+    #[async_trait]
+    impl MemConsumer for TestConsumer {
+        fn name(&self) -> &str {
+            "TestConsumer"
+        }
+
+        fn set_consumer_info(&mut self, consumer_info: Weak<MemConsumerInfo>) {
+            self.consumer_info = Some(consumer_info);
+        }
+
+        fn get_consumer_info(&self) -> &Weak<MemConsumerInfo> {
+            self.consumer_info.as_ref().expect("consumer info not set")
+        }
+    }
+
+    impl Drop for TestConsumer {
+        fn drop(&mut self) {
+            MemManager::deregister_consumer(self);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_off_heap_booking() -> Result<()> {
+        MemManager::init(10000);
+        OffHeapQuota::init(1000);
+        let mm = MemManager::get();
+        let quota = OffHeapQuota::get().expect("off-heap quota not initialized");
+
+        let consumer = Arc::new(TestConsumer {
+            consumer_info: None,
+            booking: OffHeapBooking::default(),
+        });
+        MemManager::register_consumer(consumer.clone(), true);
+        let consumer_mem_used = || consumer.consumer_info().status.lock().mem_used;
+
+        // booked memory is visible to the mem manager, and extends its pool
+        consumer.booking.update_mem_used(&*consumer, 600).await?;
+        assert_eq!(consumer_mem_used(), 600);
+        assert_eq!(consumer.booking.booked(), 600);
+        assert_eq!(quota.used(), 600);
+        assert_eq!(mm.total_with_off_heap(), mm.total() + 600);
+
+        // falls back to the native pool when the quota is exhausted
+        consumer.booking.update_mem_used(&*consumer, 1200).await?;
+        assert_eq!(consumer_mem_used(), 1200);
+        assert_eq!(consumer.booking.booked(), 0);
+        assert_eq!(quota.used(), 0);
+        assert_eq!(mm.total_with_off_heap(), mm.total());
+
+        consumer.booking.update_mem_used(&*consumer, 300).await?;
+        assert_eq!(quota.used(), 300);
+        consumer.booking.update_mem_used(&*consumer, 0).await?;
+        assert_eq!(consumer_mem_used(), 0);
+        assert_eq!(quota.used(), 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,106 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Memory shares of consumer types. a consumer type exceeding its maximum
+//! share spills even if the total memory is not used up, and a consumer type
+//! within its minimum share is never chosen to spill for other consumers.
+//!
+//! shares are fractions of the managed memory, configured like
+//! `shuffle:0:0.5,join:0.2:1`.
+
+use blaze_jni_bridge::{conf, conf::StringConf, is_jni_bridge_inited};
+use once_cell::sync::OnceCell;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemConsumerType {
+    Sort,
+    Agg,
+    Join,
+    Shuffle,
+    Cache,
+    Other,
+}
+
+impl MemConsumerType {
+    pub const NUM_TYPES: usize = 6;
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name.trim().to_lowercase().as_str() {
+            "sort" => Self::Sort,
+            "agg" => Self::Agg,
+            "join" => Self::Join,
+            "shuffle" => Self::Shuffle,
+            "cache" => Self::Cache,
+            "other" => Self::Other,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MemConsumerShare {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Default for MemConsumerShare {
+    fn default() -> Self {
+        Self { min: 0.0, max: 1.0 }
+    }
+}
+
+/// returns memory shares of all consumer types, indexed by type
+pub fn consumer_shares() -> &'static [MemConsumerShare; MemConsumerType::NUM_TYPES] {
+    static SHARES: OnceCell<[MemConsumerShare; MemConsumerType::NUM_TYPES]> = OnceCell::new();
+    SHARES.get_or_init(|| {
+        if !is_jni_bridge_inited() {
+            // for testing
+            return Default::default();
+        }
+        let shares_conf = conf::MEMORY_CONSUMER_SHARES
+            .value()
+            .expect("error reading memory consumer shares");
+        parse_consumer_shares(&shares_conf)
+    })
+}
+
+/// parses shares like `shuffle:0:0.5,join:0.2:1`, invalid entries are ignored
+fn parse_consumer_shares(shares_conf: &str) -> [MemConsumerShare; MemConsumerType::NUM_TYPES] {
+    let mut shares: [MemConsumerShare; MemConsumerType::NUM_TYPES] = Default::default();
+    for entry in shares_conf
+        .split(',')
+        .filter(|entry| !entry.trim().is_empty())
+    {
+        let parsed = match entry.split(':').collect::<Vec<_>>().as_slice() {
+            &[name, min, max] => MemConsumerType::from_name(name).zip(
+                min.trim()
+                    .parse::<f64>()
+                    .ok()
+                    .zip(max.trim().parse::<f64>().ok()),
+            ),
+            _ => None,
+        };
+        match parsed {
+            Some((consumer_type, (min, max))) if 0.0 <= min && min <= max && max <= 1.0 => {
+                shares[consumer_type.index()] = MemConsumerShare { min, max };
+            }
+            _ => log::warn!("ignored invalid memory consumer share: {entry}"),
+        }
+    }
+    shares
+}
@@ -14,25 +14,41 @@
 
 use std::{
     any::Any,
-    fs,
-    fs::{File, OpenOptions},
-    io::{BufReader, BufWriter, Cursor, Read, Seek, Write},
-    sync::Arc,
+    io::{BufReader, BufWriter, Cursor, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering::SeqCst},
+        Arc,
+    },
     time::Duration,
 };
 
 use blaze_jni_bridge::{
-    conf, conf::StringConf, is_jni_bridge_inited, jni_bridge::LocalRef, jni_call, jni_call_static,
-    jni_get_string, jni_new_direct_byte_buffer, jni_new_global_ref,
+    conf,
+    conf::{IntConf, LongConf, StringConf},
+    is_jni_bridge_inited,
+    jni_bridge::LocalRef,
+    jni_call, jni_call_static, jni_get_string, jni_new_direct_byte_buffer, jni_new_global_ref,
+};
+use datafusion::{
+    common::{DataFusionError, Result},
+    physical_plan::metrics::Time,
+};
+use datafusion_ext_commons::fs::{
+    get_fs, local_fs, register_fs_for_url, FileReaderStream, FileSystem, FileWriter,
 };
-use datafusion::{common::Result, parquet::file::reader::Length, physical_plan::metrics::Time};
 use jni::{objects::GlobalRef, sys::jlong};
 use log::warn;
 use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use tempfile::TempPath;
 
 use crate::{
-    common::ipc_compression::{IoCompressionReader, IoCompressionWriter},
-    memmgr::metrics::SpillMetrics,
+    common::ipc_compression::{IoCompressionReader, IoCompressionWriter, DEFAULT_ZSTD_LEVEL},
+    memmgr::{
+        metrics::SpillMetrics,
+        spill_manager::{executor_disk_usage, SpillFileHandle, SpillManager},
+    },
 };
 
 pub type SpillCompressedReader<'a> = IoCompressionReader<BufReader<Box<dyn Read + Send + 'a>>>;
@@ -41,20 +57,95 @@ pub type SpillCompressedWriter<'a> = IoCompressionWriter<BufWriter<Box<dyn Write
 pub trait Spill: Send + Sync {
     fn as_any(&self) -> &dyn Any;
     fn as_any_mut(&mut self) -> &mut dyn Any;
-    fn get_buf_reader<'a>(&'a self) -> BufReader<Box<dyn Read + Send + 'a>>;
+    fn get_buf_reader<'a>(&'a self) -> Result<BufReader<Box<dyn Read + Send + 'a>>>;
     fn get_buf_writer<'a>(&'a mut self) -> BufWriter<Box<dyn Write + Send + 'a>>;
 
-    fn get_compressed_reader(&self) -> SpillCompressedReader<'_> {
-        IoCompressionReader::try_new(spill_compression_codec(), self.get_buf_reader())
-            .expect("error creating compression reader")
+    fn get_compressed_reader(&self) -> Result<SpillCompressedReader<'_>> {
+        self.get_compressed_reader_with(spill_compression())
     }
 
     fn get_compressed_writer(&mut self) -> SpillCompressedWriter<'_> {
-        IoCompressionWriter::try_new(spill_compression_codec(), self.get_buf_writer())
-            .expect("error creating compression writer")
+        self.get_compressed_writer_with(spill_compression())
+    }
+
+    /// creates a reader of data written with the same compression
+    fn get_compressed_reader_with(
+        &self,
+        compression: &SpillCompression,
+    ) -> Result<SpillCompressedReader<'_>> {
+        IoCompressionReader::try_new(&compression.codec, self.get_buf_reader()?)
+    }
+
+    fn get_compressed_writer_with(
+        &mut self,
+        compression: &SpillCompression,
+    ) -> SpillCompressedWriter<'_> {
+        IoCompressionWriter::try_new_with_level(
+            &compression.codec,
+            compression.level,
+            self.get_buf_writer(),
+        )
+        .expect("error creating compression writer")
+    }
+}
+
+/// Compression codec and level of spills, configurable per operator
+#[derive(Debug, Clone)]
+pub struct SpillCompression {
+    pub codec: String, // lz4, zstd or none
+    pub level: i32,    // only used by zstd
+}
+
+impl SpillCompression {
+    fn from_conf(codec_conf: impl StringConf, level_conf: impl IntConf) -> Self {
+        if !is_jni_bridge_inited() {
+            // for testing
+            return Self {
+                codec: format!("lz4"),
+                level: DEFAULT_ZSTD_LEVEL,
+            };
+        }
+        let codec = codec_conf
+            .value()
+            .expect("error reading spill compression codec")
+            .to_lowercase();
+        let level = level_conf
+            .value()
+            .expect("error reading spill compression level");
+        Self { codec, level }
     }
 }
 
+/// returns the default compression of spills
+pub fn spill_compression() -> &'static SpillCompression {
+    static COMPRESSION: OnceCell<SpillCompression> = OnceCell::new();
+    COMPRESSION.get_or_init(|| {
+        SpillCompression::from_conf(conf::SPILL_COMPRESSION_CODEC, conf::SPILL_COMPRESSION_LEVEL)
+    })
+}
+
+/// returns the compression of sort spills
+pub fn sort_spill_compression() -> &'static SpillCompression {
+    static COMPRESSION: OnceCell<SpillCompression> = OnceCell::new();
+    COMPRESSION.get_or_init(|| {
+        SpillCompression::from_conf(
+            conf::SORT_SPILL_COMPRESSION_CODEC,
+            conf::SORT_SPILL_COMPRESSION_LEVEL,
+        )
+    })
+}
+
+/// returns the compression of aggregation spills
+pub fn agg_spill_compression() -> &'static SpillCompression {
+    static COMPRESSION: OnceCell<SpillCompression> = OnceCell::new();
+    COMPRESSION.get_or_init(|| {
+        SpillCompression::from_conf(
+            conf::AGG_SPILL_COMPRESSION_CODEC,
+            conf::AGG_SPILL_COMPRESSION_LEVEL,
+        )
+    })
+}
+
 impl Spill for Vec<u8> {
     fn as_any(&self) -> &dyn Any {
         self
@@ -64,8 +155,8 @@ impl Spill for Vec<u8> {
         self
     }
 
-    fn get_buf_reader<'a>(&'a self) -> BufReader<Box<dyn Read + Send + 'a>> {
-        BufReader::new(Box::new(Cursor::new(self)))
+    fn get_buf_reader<'a>(&'a self) -> Result<BufReader<Box<dyn Read + Send + 'a>>> {
+        Ok(BufReader::new(Box::new(Cursor::new(self))))
     }
 
     fn get_buf_writer<'a>(&'a mut self) -> BufWriter<Box<dyn Write + Send + 'a>> {
@@ -73,18 +164,51 @@ impl Spill for Vec<u8> {
     }
 }
 
-fn spill_compression_codec() -> &'static str {
-    static CODEC: OnceCell<String> = OnceCell::new();
-    CODEC
-        .get_or_try_init(|| {
-            if is_jni_bridge_inited() {
-                conf::SPILL_COMPRESSION_CODEC.value()
-            } else {
-                Ok(format!("lz4")) // for testing
-            }
-        })
-        .expect("error reading spark.blaze.spill.compression.codec")
-        .as_str()
+/// In-memory spill always compressed with lz4 frame regardless of the
+/// configured spill codec. used for buffering data under memory pressure
+/// before resorting to real spills, lz4 is fast enough that the buffered data
+/// can be compressed and read again with little cost.
+#[derive(Default)]
+pub struct Lz4InMemSpill(Vec<u8>);
+
+impl Lz4InMemSpill {
+    pub fn size(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.0.shrink_to_fit();
+    }
+}
+
+impl Spill for Lz4InMemSpill {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn get_buf_reader<'a>(&'a self) -> Result<BufReader<Box<dyn Read + Send + 'a>>> {
+        self.0.get_buf_reader()
+    }
+
+    fn get_buf_writer<'a>(&'a mut self) -> BufWriter<Box<dyn Write + Send + 'a>> {
+        self.0.get_buf_writer()
+    }
+
+    fn get_compressed_reader_with(
+        &self,
+        _: &SpillCompression,
+    ) -> Result<SpillCompressedReader<'_>> {
+        IoCompressionReader::try_new("lz4", self.get_buf_reader()?)
+    }
+
+    fn get_compressed_writer_with(&mut self, _: &SpillCompression) -> SpillCompressedWriter<'_> {
+        IoCompressionWriter::try_new("lz4", self.get_buf_writer())
+            .expect("error creating compression writer")
+    }
 }
 
 pub fn try_new_spill(spill_metrics: &SpillMetrics) -> Result<Box<dyn Spill>> {
@@ -101,29 +225,197 @@ pub fn try_new_spill(spill_metrics: &SpillMetrics) -> Result<Box<dyn Spill>> {
     }
 }
 
+/// creates a spill backed by temporary file, which can be read for many times
+pub fn try_new_file_spill(spill_metrics: &SpillMetrics) -> Result<Box<dyn Spill>> {
+    Ok(Box::new(FileSpill::try_new(spill_metrics)?))
+}
+
+/// copies all data of a spill into another spill, the data is recompressed
+/// with the compression of the target spill
+pub fn copy_spill(
+    from: &dyn Spill,
+    to: &mut dyn Spill,
+    compression: &SpillCompression,
+) -> Result<()> {
+    let mut reader = from.get_compressed_reader_with(compression)?;
+    let mut writer = to.get_compressed_writer_with(compression);
+    std::io::copy(&mut reader, &mut writer)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// creates a file spill in a local dir other than `excluded_dirs` if possible,
+/// used for retrying a spill write failed on a bad disk. temporary files are
+/// distributed to local dirs by spark's DiskBlockManager, so we just retry for
+/// a few times until an alternate dir is hit.
+pub fn try_new_file_spill_excluding_dirs(
+    spill_metrics: &SpillMetrics,
+    excluded_dirs: &[PathBuf],
+) -> Result<Box<dyn Spill>> {
+    const MAX_ATTEMPTS: usize = 16;
+    let mut spill = FileSpill::try_new(spill_metrics)?;
+    for _ in 1..MAX_ATTEMPTS {
+        match spill.local_dir() {
+            Some(dir) if excluded_dirs.contains(&dir) => {
+                spill = FileSpill::try_new(spill_metrics)?;
+            }
+            _ => break,
+        }
+    }
+    Ok(Box::new(spill))
+}
+
+/// returns the local dir of a file spill, None for other spills
+pub fn spill_local_dir(spill: &dyn Spill) -> Option<PathBuf> {
+    spill.as_any().downcast_ref::<FileSpill>()?.local_dir()
+}
+
+/// Remote storage for spills exceeding the local disk quota. the dir is served
+/// by the filesystem registered in [`datafusion_ext_commons::fs`], or by an
+/// object store (like S3 or HDFS) registered for the dir on initializing.
+struct RemoteSpillConf {
+    fs: Arc<dyn FileSystem>,
+    dir: String,
+    local_disk_quota: u64,
+}
+
+static REMOTE_SPILL_CONF: OnceCell<Option<RemoteSpillConf>> = OnceCell::new();
+
+/// initializes remote storage of spills from `spark.blaze.spill.remote.dir`,
+/// called when initializing native environment
+pub fn init_remote_spill() -> Result<()> {
+    REMOTE_SPILL_CONF.get_or_try_init(|| {
+        if !is_jni_bridge_inited() {
+            return Ok(None);
+        }
+        let dir = conf::SPILL_REMOTE_DIR.value()?;
+        let local_disk_quota = conf::SPILL_LOCAL_DISK_QUOTA.value()?;
+        if dir.is_empty() || local_disk_quota <= 0 {
+            return Ok(None);
+        }
+        let fs = match get_fs(&dir) {
+            Some(fs) => fs,
+            None => register_fs_for_url(&dir)?,
+        };
+        log::info!(
+            "spills beyond local disk quota ({local_disk_quota} bytes) are written to: {dir}"
+        );
+        Ok::<_, DataFusionError>(Some(RemoteSpillConf {
+            fs,
+            dir: dir.trim_end_matches('/').to_string(),
+            local_disk_quota: local_disk_quota as u64,
+        }))
+    })?;
+    Ok(())
+}
+
+fn remote_spill_conf() -> Option<&'static RemoteSpillConf> {
+    REMOTE_SPILL_CONF.get()?.as_ref()
+}
+
 /// A spill structure which write data to temporary files
-/// used in driver side or executor side with on-heap memory is full
-struct FileSpill(File, SpillMetrics, Option<String>);
+/// used in driver side or executor side with on-heap memory is full.
+/// files are written to remote storage if local spills exceed the quota.
+/// files are tracked by the spill manager of current task.
+struct FileSpill {
+    fs: Arc<dyn FileSystem>,
+    path: String,
+    writer: Mutex<Option<Box<dyn FileWriter>>>, // remote writer is closed before reading
+    closed_len: AtomicU64,
+    file_handle: SpillFileHandle,
+    spill_metrics: SpillMetrics,
+    is_spark_local_file: bool,
+    is_remote: bool,
+    _temp_path: Option<TempPath>,
+}
+
 impl FileSpill {
     fn try_new(spill_metrics: &SpillMetrics) -> Result<Self> {
-        if is_jni_bridge_inited() {
+        if let Some(remote) = remote_spill_conf() {
+            if executor_disk_usage() >= remote.local_disk_quota {
+                return Self::try_new_remote(spill_metrics, remote);
+            }
+        }
+
+        let fs = local_fs();
+        let (path, is_spark_local_file, temp_path) = if is_jni_bridge_inited() {
             let file_name = jni_get_string!(
                 jni_call_static!(JniBridge.getDirectWriteSpillToDiskFile() -> JObject)?
                     .as_obj()
                     .into()
             )?;
-            let file = OpenOptions::new() // create file and open under rw mode
-                .create(true)
-                .truncate(true)
-                .write(true)
-                .read(true)
-                .open(&file_name)?;
-            Ok(Self(file, spill_metrics.clone(), Some(file_name)))
+            (file_name, true, None)
         } else {
-            let file = tempfile::tempfile()?;
-            Ok(Self(file, spill_metrics.clone(), None))
+            let temp_path = tempfile::Builder::new()
+                .prefix("blaze-spill-")
+                .tempfile()?
+                .into_temp_path();
+            (
+                temp_path.to_string_lossy().to_string(),
+                false,
+                Some(temp_path),
+            )
+        };
+        let writer = fs.create(&path)?;
+        let file_handle = SpillManager::current().register_file(fs.clone(), &path);
+        Ok(Self {
+            fs,
+            path,
+            writer: Mutex::new(Some(writer)),
+            closed_len: AtomicU64::new(0),
+            file_handle,
+            spill_metrics: spill_metrics.clone(),
+            is_spark_local_file,
+            is_remote: false,
+            _temp_path: temp_path,
+        })
+    }
+
+    fn try_new_remote(spill_metrics: &SpillMetrics, remote: &RemoteSpillConf) -> Result<Self> {
+        let path = format!("{}/blaze-spill-{}", remote.dir, uuid::Uuid::new_v4());
+        let writer = remote.fs.create(&path)?;
+        let file_handle = SpillManager::current().register_file(remote.fs.clone(), &path);
+        Ok(Self {
+            fs: remote.fs.clone(),
+            path,
+            writer: Mutex::new(Some(writer)),
+            closed_len: AtomicU64::new(0),
+            file_handle,
+            spill_metrics: spill_metrics.clone(),
+            is_spark_local_file: false,
+            is_remote: true,
+            _temp_path: None,
+        })
+    }
+
+    fn len(&self) -> u64 {
+        match &*self.writer.lock() {
+            Some(writer) => writer.position(),
+            None => self.closed_len.load(SeqCst),
         }
     }
+
+    /// closes the remote writer so that written data becomes visible, local
+    /// files are readable without closing
+    fn close_remote_writer(&self) -> Result<()> {
+        if self.is_remote {
+            if let Some(writer) = self.writer.lock().take() {
+                self.closed_len.store(writer.position(), SeqCst);
+                writer.close()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// returns the spark local dir containing the spill file, which is in the
+    /// form of `<local_dir>/<sub_dir>/<file_name>`
+    fn local_dir(&self) -> Option<PathBuf> {
+        if !self.is_spark_local_file {
+            return None;
+        }
+        let file_path = Path::new(&self.path);
+        Some(file_path.parent()?.parent()?.to_path_buf())
+    }
 }
 
 impl Spill for FileSpill {
@@ -135,42 +427,56 @@ impl Spill for FileSpill {
         self
     }
 
-    fn get_buf_reader<'a>(&'a self) -> BufReader<Box<dyn Read + Send + 'a>> {
-        let mut file_cloned = self.0.try_clone().expect("File.try_clone() returns error");
-        file_cloned.sync_data().expect("error synchronizing data");
-        file_cloned.rewind().expect("error rewinding");
-        BufReader::with_capacity(
-            65536,
+    fn get_buf_reader<'a>(&'a self) -> Result<BufReader<Box<dyn Read + Send + 'a>>> {
+        self.close_remote_writer()?;
+        let reader = self.fs.open(&self.path)?;
+
+        // use larger buffer for remote files to reduce number of requests
+        let buf_size = if self.is_remote { 4194304 } else { 65536 };
+        Ok(BufReader::with_capacity(
+            buf_size,
             Box::new(IoTimeReadWrapper(
-                file_cloned,
-                self.1.mem_spill_iotime.clone(),
+                FileReaderStream::new(reader, self.len()),
+                self.spill_metrics.mem_spill_iotime.clone(),
             )),
-        )
+        ))
     }
 
     fn get_buf_writer<'a>(&'a mut self) -> BufWriter<Box<dyn Write + Send + 'a>> {
-        let file_cloned = self.0.try_clone().expect("File.try_clone() returns error");
-        BufWriter::with_capacity(
-            65536,
-            Box::new(IoTimeWriteWrapper(
-                file_cloned,
-                self.1.mem_spill_iotime.clone(),
-            )),
-        )
+        let writer = self
+            .writer
+            .get_mut()
+            .as_mut()
+            .expect("writing to closed spill file");
+        let writer = IoTimeWriteWrapper(writer, self.spill_metrics.mem_spill_iotime.clone());
+        if self.is_remote {
+            BufWriter::with_capacity(65536, Box::new(writer))
+        } else {
+            let writer = LocalDiskUsageWriteWrapper(writer, &self.file_handle);
+            BufWriter::with_capacity(65536, Box::new(writer))
+        }
     }
 }
 
 impl Drop for FileSpill {
     fn drop(&mut self) {
-        self.1.disk_spill_size.add(self.0.len() as usize);
-        self.1
+        let len = self.len();
+        let is_registered = self.file_handle.unregister();
+        let spill_metrics = &self.spill_metrics;
+        spill_metrics.disk_spill_size.add(len as usize);
+        spill_metrics
             .disk_spill_iotime
-            .add_duration(Duration::from_nanos(self.1.mem_spill_iotime.value() as u64));
-        if let Some(file_path) = &self.2 {
-            if let Err(e) = fs::remove_file(file_path) {
+            .add_duration(Duration::from_nanos(
+                spill_metrics.mem_spill_iotime.value() as u64
+            ));
+        // unclosed remote files are never completed, so there is nothing to remove.
+        // unregistered files are already removed by the spill manager.
+        let is_remote_written = self.is_remote && self.writer.get_mut().is_none();
+        if is_registered && (self.is_spark_local_file || is_remote_written) {
+            if let Err(e) = self.fs.remove(&self.path) {
                 warn!(
                     "Was unable to delete spill file: {}. error: {}",
-                    file_path, e
+                    self.path, e
                 );
             }
         }
@@ -214,9 +520,9 @@ impl Spill for OnHeapSpill {
         self
     }
 
-    fn get_buf_reader<'a>(&'a self) -> BufReader<Box<dyn Read + Send + 'a>> {
+    fn get_buf_reader<'a>(&'a self) -> Result<BufReader<Box<dyn Read + Send + 'a>>> {
         let cloned = Self(self.0.clone(), self.1.clone());
-        BufReader::with_capacity(65536, Box::new(cloned))
+        Ok(BufReader::with_capacity(65536, Box::new(cloned)))
     }
 
     fn get_buf_writer<'a>(&'a mut self) -> BufWriter<Box<dyn Write + Send + 'a>> {
@@ -280,6 +586,7 @@ impl Drop for RawOnHeapSpill {
 
 struct IoTimeReadWrapper<R: Read>(R, Time);
 struct IoTimeWriteWrapper<W: Write>(W, Time);
+struct LocalDiskUsageWriteWrapper<'a, W: Write>(W, &'a SpillFileHandle);
 
 impl<R: Read> Read for IoTimeReadWrapper<R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
@@ -299,3 +606,65 @@ impl<W: Write> Write for IoTimeWriteWrapper<W> {
         self.0.flush()
     }
 }
+
+impl<W: Write> Write for LocalDiskUsageWriteWrapper<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.1.acquire_disk_usage(buf.len() as u64)?;
+        let len = self.0.write(buf).inspect_err(|_| {
+            self.1.release_disk_usage(buf.len() as u64);
+        })?;
+        self.1.release_disk_usage((buf.len() - len) as u64);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{Read, Write},
+        sync::Arc,
+    };
+
+    use datafusion::{common::Result, physical_plan::metrics::ExecutionPlanMetricsSet};
+    use datafusion_ext_commons::fs::{object_store_fs::ObjectStoreFileSystem, FileSystem};
+    use object_store::memory::InMemory;
+
+    use crate::memmgr::{
+        metrics::SpillMetrics,
+        spill::{FileSpill, RemoteSpillConf, Spill},
+    };
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_remote_spill() -> Result<()> {
+        let fs: Arc<dyn FileSystem> =
+            Arc::new(ObjectStoreFileSystem::new(Arc::new(InMemory::new())));
+        let remote = RemoteSpillConf {
+            fs: fs.clone(),
+            dir: "memory://bucket/spills".to_string(),
+            local_disk_quota: 0,
+        };
+        let spill_metrics = SpillMetrics::new(&ExecutionPlanMetricsSet::new(), 0);
+        let mut spill = FileSpill::try_new_remote(&spill_metrics, &remote)?;
+        let path = spill.path.clone();
+        assert!(path.starts_with("memory://bucket/spills/"));
+
+        let data = (0..100000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        let mut writer = spill.get_compressed_writer();
+        writer.write_all(&data)?;
+        writer.finish()?;
+
+        let mut read = vec![];
+        spill.get_compressed_reader()?.read_to_end(&mut read)?;
+        assert_eq!(read, data);
+        assert!(fs.exists(&path)?);
+
+        // remote spill files are removed on dropping
+        drop(spill);
+        assert!(!fs.exists(&path)?);
+        Ok(())
+    }
+}
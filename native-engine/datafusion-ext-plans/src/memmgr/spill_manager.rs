@@ -0,0 +1,325 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Lifecycle of spill files. all live spill files of a task attempt are
+//! tracked by its spill manager, and the files left by aborted or panicked
+//! tasks are removed when the task is finalized.
+//!
+//! local disk usage of spill files is limited by per-task and per-executor
+//! quotas, writing beyond the quotas fails with an error instead of filling
+//! the disks.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering::SeqCst},
+        Arc,
+    },
+};
+
+use blaze_jni_bridge::{conf, conf::LongConf, is_jni_bridge_inited};
+use bytesize::ByteSize;
+use datafusion_ext_commons::{blaze_error::BlazeError, fs::FileSystem};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+
+thread_local! {
+    static THREAD_SPILL_MANAGER: RefCell<Option<Arc<SpillManager>>> = const { RefCell::new(None) };
+}
+
+/// bytes of native spill files currently on local disks of the executor
+static EXECUTOR_DISK_USAGE: AtomicU64 = AtomicU64::new(0);
+
+pub fn executor_disk_usage() -> u64 {
+    EXECUTOR_DISK_USAGE.load(SeqCst)
+}
+
+/// returns (task quota, executor quota) of local disk usage, 0 for unlimited
+fn disk_quotas() -> (u64, u64) {
+    static DISK_QUOTAS: OnceCell<(u64, u64)> = OnceCell::new();
+    *DISK_QUOTAS.get_or_init(|| {
+        if !is_jni_bridge_inited() {
+            return (0, 0);
+        }
+        let task_quota = conf::SPILL_TASK_DISK_QUOTA.value().unwrap_or(0).max(0);
+        let executor_quota = conf::SPILL_EXECUTOR_DISK_QUOTA.value().unwrap_or(0).max(0);
+        (task_quota as u64, executor_quota as u64)
+    })
+}
+
+/// Tracks live spill files of a task attempt
+pub struct SpillManager {
+    files: Mutex<HashMap<usize, SpillFile>>,
+    next_file_id: AtomicUsize,
+    disk_usage: AtomicU64,
+    task_disk_quota: u64,
+    executor_disk_quota: u64,
+}
+
+impl Default for SpillManager {
+    fn default() -> Self {
+        let (task_disk_quota, executor_disk_quota) = disk_quotas();
+        Self {
+            files: Mutex::default(),
+            next_file_id: AtomicUsize::new(0),
+            disk_usage: AtomicU64::new(0),
+            task_disk_quota,
+            executor_disk_quota,
+        }
+    }
+}
+
+struct SpillFile {
+    fs: Arc<dyn FileSystem>,
+    path: String,
+    disk_usage: u64,
+}
+
+impl SpillManager {
+    /// overrides disk quotas from spark confs, 0 for unlimited
+    pub fn with_disk_quotas(mut self, task_disk_quota: u64, executor_disk_quota: u64) -> Self {
+        self.task_disk_quota = task_disk_quota;
+        self.executor_disk_quota = executor_disk_quota;
+        self
+    }
+
+    /// sets the spill manager of current thread, called when starting threads
+    /// of the task's runtime
+    pub fn set_current(spill_manager: Option<Arc<Self>>) {
+        THREAD_SPILL_MANAGER.with(|cur| *cur.borrow_mut() = spill_manager);
+    }
+
+    /// returns the spill manager of current task, or a shared one never
+    /// cleaned up if not running in a task (like in driver side)
+    pub fn current() -> Arc<Self> {
+        static UNTRACKED: OnceCell<Arc<SpillManager>> = OnceCell::new();
+        THREAD_SPILL_MANAGER
+            .with(|cur| cur.borrow().clone())
+            .unwrap_or_else(|| UNTRACKED.get_or_init(Arc::default).clone())
+    }
+
+    /// local disk usage of spill files of the task
+    pub fn disk_usage(&self) -> u64 {
+        self.disk_usage.load(SeqCst)
+    }
+
+    pub fn num_files(&self) -> usize {
+        self.files.lock().len()
+    }
+
+    pub fn register_file(self: &Arc<Self>, fs: Arc<dyn FileSystem>, path: &str) -> SpillFileHandle {
+        let id = self.next_file_id.fetch_add(1, SeqCst);
+        self.files.lock().insert(
+            id,
+            SpillFile {
+                fs,
+                path: path.to_string(),
+                disk_usage: 0,
+            },
+        );
+        SpillFileHandle {
+            spill_manager: self.clone(),
+            id,
+        }
+    }
+
+    /// removes all live spill files, called when the task is finalized. the
+    /// files are no longer usable even if their spills are still alive.
+    pub fn cleanup(&self) {
+        let files = std::mem::take(&mut *self.files.lock());
+        if files.is_empty() {
+            return;
+        }
+        log::warn!("removing {} spill files left by the task", files.len());
+        for file in files.into_values() {
+            self.disk_usage.fetch_sub(file.disk_usage, SeqCst);
+            EXECUTOR_DISK_USAGE.fetch_sub(file.disk_usage, SeqCst);
+            if let Err(e) = file.fs.remove(&file.path) {
+                log::warn!("error removing spill file {}: {e}", file.path);
+            }
+        }
+    }
+}
+
+/// Removes live spill files of the task when dropped, which is held by the
+/// task runtime so that files are removed even if the task is aborted or
+/// panicked
+pub struct SpillCleanupGuard(pub Arc<SpillManager>);
+
+impl Drop for SpillCleanupGuard {
+    fn drop(&mut self) {
+        self.0.cleanup();
+    }
+}
+
+/// Handle of a spill file registered in the spill manager
+pub struct SpillFileHandle {
+    spill_manager: Arc<SpillManager>,
+    id: usize,
+}
+
+impl SpillFileHandle {
+    /// accounts bytes to be written to local disk, fails if the task or
+    /// executor disk quota is exceeded
+    pub fn acquire_disk_usage(&self, len: u64) -> std::io::Result<()> {
+        let spill_manager = &self.spill_manager;
+        let mut files = spill_manager.files.lock();
+        let Some(file) = files.get_mut(&self.id) else {
+            return Err(std::io::Error::other(
+                "spill file is already removed because the task is finalized",
+            ));
+        };
+
+        let task_quota = spill_manager.task_disk_quota;
+        let executor_quota = spill_manager.executor_disk_quota;
+        let task_usage = spill_manager.disk_usage();
+        if task_quota > 0 && task_usage + len > task_quota {
+            return Err(std::io::Error::other(BlazeError::SpillIoFailure(format!(
+                "native spills exceed the disk quota of the task: {} (used) + {} > {}, \
+                 consider increasing spark.blaze.spill.taskDiskQuota",
+                ByteSize(task_usage),
+                ByteSize(len),
+                ByteSize(task_quota),
+            ))));
+        }
+        let executor_usage = EXECUTOR_DISK_USAGE.fetch_add(len, SeqCst);
+        if executor_quota > 0 && executor_usage + len > executor_quota {
+            EXECUTOR_DISK_USAGE.fetch_sub(len, SeqCst);
+            return Err(std::io::Error::other(BlazeError::SpillIoFailure(format!(
+                "native spills exceed the disk quota of the executor: {} (used) + {} > {}, \
+                 consider increasing spark.blaze.spill.executorDiskQuota",
+                ByteSize(executor_usage),
+                ByteSize(len),
+                ByteSize(executor_quota),
+            ))));
+        }
+        spill_manager.disk_usage.fetch_add(len, SeqCst);
+        file.disk_usage += len;
+        Ok(())
+    }
+
+    /// releases bytes accounted but not written
+    pub fn release_disk_usage(&self, len: u64) {
+        let spill_manager = &self.spill_manager;
+        if let Some(file) = spill_manager.files.lock().get_mut(&self.id) {
+            let len = len.min(file.disk_usage);
+            file.disk_usage -= len;
+            spill_manager.disk_usage.fetch_sub(len, SeqCst);
+            EXECUTOR_DISK_USAGE.fetch_sub(len, SeqCst);
+        }
+    }
+
+    /// unregisters the file and releases its disk usage, returns false if the
+    /// file is already removed by the spill manager
+    pub fn unregister(&self) -> bool {
+        let spill_manager = &self.spill_manager;
+        match spill_manager.files.lock().remove(&self.id) {
+            Some(file) => {
+                spill_manager.disk_usage.fetch_sub(file.disk_usage, SeqCst);
+                EXECUTOR_DISK_USAGE.fetch_sub(file.disk_usage, SeqCst);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use datafusion::common::Result;
+    use datafusion_ext_commons::fs::{local_fs::LocalFileSystem, FileSystem};
+
+    use crate::memmgr::spill_manager::{SpillCleanupGuard, SpillManager};
+
+    #[test]
+    fn test_disk_quotas() {
+        let fs: Arc<dyn FileSystem> = Arc::new(LocalFileSystem);
+        let spill_manager = Arc::new(SpillManager::default().with_disk_quotas(100, 0));
+        let handle1 = spill_manager.register_file(fs.clone(), "/tmp/blaze-spill-1");
+        let handle2 = spill_manager.register_file(fs.clone(), "/tmp/blaze-spill-2");
+
+        // task quota is shared by all files of the task
+        handle1
+            .acquire_disk_usage(60)
+            .expect("acquire disk usage error");
+        let err = handle2
+            .acquire_disk_usage(50)
+            .expect_err("task quota is not exceeded");
+        assert!(err.to_string().contains("disk quota of the task"), "{err}");
+        assert_eq!(spill_manager.disk_usage(), 60);
+
+        handle1.release_disk_usage(20);
+        handle2
+            .acquire_disk_usage(50)
+            .expect("acquire disk usage error");
+        assert_eq!(spill_manager.disk_usage(), 90);
+        assert!(handle1.unregister());
+        assert!(handle2.unregister());
+        assert_eq!(spill_manager.disk_usage(), 0);
+
+        // executor quota is exceeded regardless of other tasks' usage
+        let spill_manager = Arc::new(SpillManager::default().with_disk_quotas(0, 1));
+        let handle = spill_manager.register_file(fs, "/tmp/blaze-spill-3");
+        let err = handle
+            .acquire_disk_usage(100)
+            .expect_err("executor quota is not exceeded");
+        assert!(
+            err.to_string().contains("disk quota of the executor"),
+            "{err}"
+        );
+        assert_eq!(spill_manager.disk_usage(), 0);
+        assert!(handle.unregister());
+    }
+
+    #[test]
+    fn test_cleanup_guard() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let fs: Arc<dyn FileSystem> = Arc::new(LocalFileSystem);
+        let spill_manager = Arc::new(SpillManager::default());
+        let paths = (0..3)
+            .map(|i| dir.path().join(format!("spill-{i}")).display().to_string())
+            .collect::<Vec<_>>();
+        let handles = paths
+            .iter()
+            .map(|path| {
+                fs.create(path)?.close()?;
+                let handle = spill_manager.register_file(fs.clone(), path);
+                handle.acquire_disk_usage(10)?;
+                Ok(handle)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // unregistered files are owned by their spills and not removed
+        assert!(handles[0].unregister());
+        assert_eq!(spill_manager.num_files(), 2);
+        assert_eq!(spill_manager.disk_usage(), 20);
+
+        drop(SpillCleanupGuard(spill_manager.clone()));
+        assert_eq!(spill_manager.num_files(), 0);
+        assert_eq!(spill_manager.disk_usage(), 0);
+        assert!(fs.exists(&paths[0])?);
+        assert!(!fs.exists(&paths[1])?);
+        assert!(!fs.exists(&paths[2])?);
+
+        // removed files are no longer usable
+        let err = handles[1]
+            .acquire_disk_usage(10)
+            .expect_err("file is not removed");
+        assert!(err.to_string().contains("already removed"), "{err}");
+        Ok(())
+    }
+}
@@ -15,12 +15,21 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use std::{any::Any, fmt, fmt::Formatter, sync::Arc};
+use std::{any::Any, collections::HashSet, fmt, fmt::Formatter, ops::Range, sync::Arc};
 
-use arrow::{datatypes::SchemaRef, error::ArrowError};
-use blaze_jni_bridge::{jni_call_static, jni_new_global_ref, jni_new_string};
+use arrow::{
+    array::{
+        ArrayRef, BooleanArray, Date32Array, Float64Array, Int64Array, StringArray, UInt64Array,
+    },
+    datatypes::{DataType, SchemaRef},
+    error::ArrowError,
+};
+use blaze_jni_bridge::{
+    conf, conf::BooleanConf, jni_call_static, jni_new_global_ref, jni_new_string,
+};
 use bytes::Bytes;
 use datafusion::{
+    common::{Column, ScalarValue},
     datasource::{
         physical_plan::{FileMeta, FileOpenFuture, FileOpener, FileScanConfig, FileStream},
         schema_adapter::SchemaMapper,
@@ -28,25 +37,35 @@ use datafusion::{
     error::Result,
     execution::context::TaskContext,
     physical_expr::EquivalenceProperties,
+    physical_optimizer::pruning::{PruningPredicate, PruningStatistics},
     physical_plan::{
-        metrics::{ExecutionPlanMetricsSet, MetricsSet},
+        metrics::{Count, ExecutionPlanMetricsSet, MetricsSet},
         DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, Partitioning, PhysicalExpr,
         PlanProperties, SendableRecordBatchStream, Statistics,
     },
 };
 use datafusion_ext_commons::{batch_size, df_execution_err, hadoop_fs::FsProvider};
-use futures::{future::BoxFuture, FutureExt, StreamExt};
+use futures::{future::BoxFuture, stream, FutureExt, StreamExt};
 use futures_util::TryStreamExt;
 use once_cell::sync::OnceCell;
 use orc_rust::{
     arrow_reader::ArrowReaderBuilder,
     projection::ProjectionMask,
     reader::{metadata::FileMetadata, AsyncChunkReader},
+    statistics::TypeStatistics,
+    stripe::StripeMetadata,
 };
 
 use crate::{
     common::execution_context::ExecutionContext,
-    scan::{internal_file_reader::InternalFileReader, BlazeSchemaMapping},
+    scan::{
+        aggregate_pushdown::{
+            pushed_aggregates_schema, PushedAggregate, PushedAggregatesAccumulator,
+        },
+        internal_file_reader::{track_input_file_name, InternalFileReader},
+        partition_pruning::{prune_dynamic_partitions, DynamicPartitionFilter},
+        BlazeSchemaMapping,
+    },
 };
 
 /// Execution plan for scanning one or more Orc partitions
@@ -57,7 +76,9 @@ pub struct OrcExec {
     projected_statistics: Statistics,
     projected_schema: SchemaRef,
     metrics: ExecutionPlanMetricsSet,
-    _predicate: Option<Arc<dyn PhysicalExpr>>,
+    pruning_predicate: Option<Arc<PruningPredicate>>,
+    dynamic_partition_filters: Vec<DynamicPartitionFilter>,
+    pushed_aggregates: Vec<PushedAggregate>,
     props: OnceCell<PlanProperties>,
 }
 
@@ -67,9 +88,20 @@ impl OrcExec {
     pub fn new(
         base_config: FileScanConfig,
         fs_resource_id: String,
-        _predicate: Option<Arc<dyn PhysicalExpr>>,
+        predicate: Option<Arc<dyn PhysicalExpr>>,
     ) -> Self {
         let metrics = ExecutionPlanMetricsSet::new();
+        let pruning_predicate = predicate
+            .and_then(|predicate_expr| {
+                match PruningPredicate::try_new(predicate_expr, base_config.file_schema.clone()) {
+                    Ok(pruning_predicate) => Some(Arc::new(pruning_predicate)),
+                    Err(e) => {
+                        log::warn!("Could not create orc pruning predicate: {e}");
+                        None
+                    }
+                }
+            })
+            .filter(|p| !p.always_true());
 
         let (projected_schema, projected_statistics, _projected_output_ordering) =
             base_config.project();
@@ -80,10 +112,31 @@ impl OrcExec {
             projected_statistics,
             projected_schema,
             metrics,
-            _predicate,
+            pruning_predicate,
+            dynamic_partition_filters: vec![],
+            pushed_aggregates: vec![],
             props: OnceCell::new(),
         }
     }
+
+    pub fn with_dynamic_partition_filters(
+        mut self,
+        dynamic_partition_filters: Vec<DynamicPartitionFilter>,
+    ) -> Self {
+        self.dynamic_partition_filters = dynamic_partition_filters;
+        self
+    }
+
+    /// outputs a single row of partial aggregate values per partition instead
+    /// of the scanned rows, which are answered from stripe statistics if
+    /// possible. the scan must have no predicates.
+    pub fn with_pushed_aggregates(mut self, pushed_aggregates: Vec<PushedAggregate>) -> Self {
+        self.projected_schema =
+            pushed_aggregates_schema(&pushed_aggregates, &self.base_config.file_schema);
+        self.projected_statistics = Statistics::new_unknown(&self.projected_schema);
+        self.pushed_aggregates = pushed_aggregates;
+        self
+    }
 }
 
 impl DisplayAs for OrcExec {
@@ -100,9 +153,19 @@ impl DisplayAs for OrcExec {
 
         write!(
             f,
-            "OrcExec: file_group={:?}, limit={:?}, projection={:?}",
-            file_group, limit, projection
-        )
+            "OrcExec: file_group={:?}, limit={:?}, projection={:?}, predicate={}",
+            file_group,
+            limit,
+            projection,
+            self.pruning_predicate
+                .as_ref()
+                .map(|pre| format!("{}", pre.predicate_expr()))
+                .unwrap_or(format!("<empty>")),
+        )?;
+        if !self.pushed_aggregates.is_empty() {
+            write!(f, ", pushed_aggregates={:?}", self.pushed_aggregates)?;
+        }
+        Ok(())
     }
 }
 
@@ -150,7 +213,11 @@ impl ExecutionPlan for OrcExec {
 
         // get fs object from jni bridge resource
         let resource_id = jni_new_string!(&self.fs_resource_id)?;
+        // resources are removed from the resource map when taken, never retry it
         let fs = jni_call_static!(JniBridge.getResource(resource_id.as_obj()) -> JObject)?;
+        if fs.as_obj().is_null() {
+            return df_execution_err!("OrcExec: fs resource not found: {}", self.fs_resource_id);
+        }
         let fs_provider = Arc::new(FsProvider::new(jni_new_global_ref!(fs.as_obj())?, &io_time));
 
         let projection = match self.base_config.file_column_projection_indices() {
@@ -158,16 +225,43 @@ impl ExecutionPlan for OrcExec {
             None => (0..self.base_config.file_schema.fields().len()).collect(),
         };
 
+        let stripe_pruning_enabled = conf::ORC_ENABLE_STRIPE_PRUNING.value()?;
         let opener = OrcOpener {
-            projection,
+            projection: projection.clone(),
             batch_size: batch_size(),
             table_schema: self.base_config.file_schema.clone(),
             fs_provider,
+            force_positional_evolution: conf::ORC_FORCE_POSITIONAL_EVOLUTION.value()?,
+            pruning_predicate: self
+                .pruning_predicate
+                .clone()
+                .filter(|_| stripe_pruning_enabled),
+            stripes_pruned: exec_ctx.register_counter_metric("stripes_pruned"),
         };
 
-        let mut file_stream = Box::pin(FileStream::new(
+        let base_config = prune_dynamic_partitions(
             &self.base_config,
             partition,
+            &self.dynamic_partition_filters,
+            &exec_ctx.register_counter_metric("dynamic_partition_pruned_files"),
+        )?;
+        if !self.pushed_aggregates.is_empty() {
+            if self.pruning_predicate.is_some() {
+                return df_execution_err!("cannot push down aggregates into a filtered scan");
+            }
+            return execute_pushed_aggregates(
+                base_config,
+                partition,
+                opener,
+                projection,
+                self.pushed_aggregates.clone(),
+                exec_ctx,
+            );
+        }
+
+        let mut file_stream = Box::pin(FileStream::new(
+            &base_config,
+            partition,
             opener,
             exec_ctx.execution_plan_metrics(),
         )?);
@@ -194,11 +288,88 @@ impl ExecutionPlan for OrcExec {
     }
 }
 
+/// answers pushed aggregates of all files in the partition, from stripe
+/// statistics if all statistics are available, or else by reading the file
+fn execute_pushed_aggregates(
+    base_config: FileScanConfig,
+    partition: usize,
+    opener: OrcOpener,
+    projection: Vec<usize>,
+    pushed_aggregates: Vec<PushedAggregate>,
+    exec_ctx: Arc<ExecutionContext>,
+) -> Result<SendableRecordBatchStream> {
+    let files = base_config
+        .file_groups
+        .get(partition)
+        .cloned()
+        .unwrap_or_default();
+    let file_schema = base_config.file_schema.clone();
+    let metadata_aggregated_files = exec_ctx.register_counter_metric("metadata_aggregated_files");
+
+    Ok(exec_ctx
+        .clone()
+        .output_with_sender("OrcScan", move |sender| async move {
+            sender.exclude_time(exec_ctx.baseline_metrics().elapsed_compute());
+            let _timer = exec_ctx.baseline_metrics().elapsed_compute().timer();
+            let mut accumulator =
+                PushedAggregatesAccumulator::try_new(pushed_aggregates, file_schema.clone())?;
+            let schema_adapter = SchemaAdapter::new(
+                file_schema,
+                projection.clone(),
+                opener.force_positional_evolution,
+            );
+
+            for file in files {
+                let reader = OrcFileReaderRef(Arc::new(InternalFileReader::try_new(
+                    opener.fs_provider.clone(),
+                    file.object_meta.clone(),
+                )?));
+                let builder = ArrowReaderBuilder::try_new_async(reader)
+                    .await
+                    .or_else(|err| df_execution_err!("create orc reader error: {err}"))?;
+                let stripes = builder.file_metadata().stripe_metadatas();
+                let stripe_columns = schema_adapter.map_stripe_columns(builder.file_metadata());
+                let selected = stripes
+                    .iter()
+                    .map(|stripe| {
+                        file.range.as_ref().map_or(true, |range| {
+                            (range.start as usize..range.end as usize)
+                                .contains(&(stripe.offset() as usize))
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                let statistics = OrcStripeStatistics {
+                    stripes,
+                    table_schema: &schema_adapter.table_schema,
+                    stripe_columns: &stripe_columns,
+                };
+                if accumulator.update_with_statistics(&statistics, &selected)? {
+                    metadata_aggregated_files.add(1);
+                    continue;
+                }
+
+                let mut file_meta = FileMeta::from(file.object_meta);
+                file_meta.range = file.range;
+                let mut stream = opener.open(file_meta)?.await?;
+                while let Some(batch) = stream.next().await.transpose()? {
+                    accumulator.update_batch(&batch, &projection)?;
+                }
+            }
+            sender
+                .send(accumulator.finish(&exec_ctx.output_schema())?)
+                .await;
+            Ok(())
+        }))
+}
+
 struct OrcOpener {
     projection: Vec<usize>,
     batch_size: usize,
     table_schema: SchemaRef,
     fs_provider: Arc<FsProvider>,
+    force_positional_evolution: bool,
+    pruning_predicate: Option<Arc<PruningPredicate>>,
+    stripes_pruned: Count,
 }
 
 impl FileOpener for OrcOpener {
@@ -208,40 +379,109 @@ impl FileOpener for OrcOpener {
             file_meta.object_meta.clone(),
         )?));
         let batch_size = self.batch_size;
-        let projection = self.projection.clone();
-        let projected_schema = SchemaRef::from(self.table_schema.project(&projection)?);
-        let schema_adapter = SchemaAdapter::new(projected_schema);
+        let schema_adapter = SchemaAdapter::new(
+            self.table_schema.clone(),
+            self.projection.clone(),
+            self.force_positional_evolution,
+        );
+        let pruning_predicate = self.pruning_predicate.clone();
+        let stripes_pruned = self.stripes_pruned.clone();
 
         Ok(Box::pin(async move {
-            let mut builder = ArrowReaderBuilder::try_new_async(reader)
+            let builder = ArrowReaderBuilder::try_new_async(reader.clone())
                 .await
                 .or_else(|err| df_execution_err!("create orc reader error: {err}"))?;
-            if let Some(range) = file_meta.range.clone() {
-                let range = range.start as usize..range.end as usize;
-                builder = builder.with_file_byte_range(range);
-            }
-
+            let file_range = file_meta
+                .range
+                .as_ref()
+                .map(|range| range.start as usize..range.end as usize);
             let (schema_mapping, projection) =
                 schema_adapter.map_schema(builder.file_metadata())?;
 
-            let projection_mask =
-                ProjectionMask::roots(builder.file_metadata().root_data_type(), projection);
-            let stream = builder
-                .with_batch_size(batch_size)
-                .with_projection(projection_mask)
-                .build_async();
+            // select stripes in the file range, skipping stripes pruned by statistics
+            let stripe_ranges: Vec<Option<Range<usize>>> = match &pruning_predicate {
+                Some(pruning_predicate) => {
+                    let stripes = builder.file_metadata().stripe_metadatas();
+                    let stripe_columns = schema_adapter.map_stripe_columns(builder.file_metadata());
+                    let mut selected = pruning_predicate.prune(&OrcStripeStatistics {
+                        stripes,
+                        table_schema: &schema_adapter.table_schema,
+                        stripe_columns: &stripe_columns,
+                    })?;
+                    for (stripe, selected) in stripes.iter().zip(&mut selected) {
+                        let in_range = file_range
+                            .as_ref()
+                            .map(|range| range.contains(&(stripe.offset() as usize)))
+                            .unwrap_or(true);
+                        if in_range && !*selected {
+                            stripes_pruned.add(1);
+                        }
+                        *selected &= in_range;
+                    }
+                    selected_stripe_ranges(stripes, &selected)
+                        .into_iter()
+                        .map(Some)
+                        .collect()
+                }
+                None => vec![file_range],
+            };
+
+            // the opened reader is used for the first range, other ranges are read
+            // with new readers
+            let mut opened_builder = Some(builder);
+            let mut builders = vec![];
+            for range in stripe_ranges {
+                let mut builder = match opened_builder.take() {
+                    Some(builder) => builder,
+                    None => ArrowReaderBuilder::try_new_async(reader.clone())
+                        .await
+                        .or_else(|err| df_execution_err!("create orc reader error: {err}"))?,
+                };
+                if let Some(range) = range {
+                    builder = builder.with_file_byte_range(range);
+                }
+                builders.push(builder);
+            }
 
-            let adapted = stream
+            let adapted = stream::iter(builders)
+                .flat_map(move |builder| {
+                    let projection_mask = ProjectionMask::roots(
+                        builder.file_metadata().root_data_type(),
+                        projection.clone(),
+                    );
+                    builder
+                        .with_batch_size(batch_size)
+                        .with_projection(projection_mask)
+                        .build_async()
+                })
                 .map_err(|e| ArrowError::ExternalError(Box::new(e)))
                 .map(move |maybe_batch| {
                     maybe_batch.and_then(|b| schema_mapping.map_batch(b).map_err(Into::into))
                 });
 
-            Ok(adapted.boxed())
+            track_input_file_name(adapted.boxed(), &file_meta.object_meta)
         }))
     }
 }
 
+/// groups consecutive selected stripes into byte ranges, each range contains
+/// the starting offsets of its stripes
+fn selected_stripe_ranges(stripes: &[StripeMetadata], selected: &[bool]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = vec![];
+    let mut last_selected = false;
+    for (stripe, &selected) in stripes.iter().zip(selected) {
+        let offset = stripe.offset() as usize;
+        if selected {
+            match ranges.last_mut() {
+                Some(range) if last_selected => range.end = offset + 1,
+                _ => ranges.push(offset..offset + 1),
+            }
+        }
+        last_selected = selected;
+    }
+    ranges
+}
+
 #[derive(Clone)]
 struct OrcFileReaderRef(Arc<InternalFileReader>);
 
@@ -264,35 +504,200 @@ impl AsyncChunkReader for OrcFileReaderRef {
 
 struct SchemaAdapter {
     table_schema: SchemaRef,
+    projection: Vec<usize>,
+    force_positional_evolution: bool,
 }
 
 impl SchemaAdapter {
-    pub fn new(table_schema: SchemaRef) -> Self {
-        Self { table_schema }
+    pub fn new(
+        table_schema: SchemaRef,
+        projection: Vec<usize>,
+        force_positional_evolution: bool,
+    ) -> Self {
+        Self {
+            table_schema,
+            projection,
+            force_positional_evolution,
+        }
+    }
+
+    /// returns the index of file column for each table column. columns are
+    /// matched by position if the file is written by hive with physical names
+    /// like `_col0`, or by case insensitive names otherwise.
+    fn map_table_columns(&self, orc_file_meta: &FileMetadata) -> Vec<Option<usize>> {
+        let file_columns = orc_file_meta.root_data_type().children();
+        let positional = self.force_positional_evolution
+            || (!file_columns.is_empty()
+                && file_columns
+                    .iter()
+                    .all(|column| is_hive_physical_column_name(column.name())));
+
+        self.table_schema
+            .fields()
+            .iter()
+            .enumerate()
+            .map(|(table_idx, table_field)| {
+                if positional {
+                    return (table_idx < file_columns.len()).then_some(table_idx);
+                }
+                file_columns
+                    .iter()
+                    .position(|column| column.name().eq_ignore_ascii_case(table_field.name()))
+            })
+            .collect()
+    }
+
+    /// returns the index of column statistics in stripes for each table column
+    fn map_stripe_columns(&self, orc_file_meta: &FileMetadata) -> Vec<Option<usize>> {
+        let file_columns = orc_file_meta.root_data_type().children();
+        self.map_table_columns(orc_file_meta)
+            .into_iter()
+            .map(|file_idx| file_idx.map(|idx| file_columns[idx].data_type().column_index()))
+            .collect()
     }
 
     fn map_schema(
         &self,
         orc_file_meta: &FileMetadata,
     ) -> Result<(Arc<dyn SchemaMapper>, Vec<usize>)> {
-        let mut projection = Vec::with_capacity(self.table_schema.fields().len());
-        let mut field_mappings = vec![None; self.table_schema.fields().len()];
-
-        for named_column in orc_file_meta.root_data_type().children() {
-            if let Some((table_idx, _table_field)) =
-                self.table_schema.fields().find(named_column.name())
-            {
-                field_mappings[table_idx] = Some(projection.len());
-                projection.push(named_column.data_type().column_index());
+        let file_columns = orc_file_meta.root_data_type().children();
+        let table_columns = self.map_table_columns(orc_file_meta);
+        let mut projection = Vec::with_capacity(self.projection.len());
+        let mut field_mappings = vec![None; self.projection.len()];
+
+        for (projected_idx, &table_idx) in self.projection.iter().enumerate() {
+            if let Some(file_idx) = table_columns[table_idx] {
+                field_mappings[projected_idx] = Some(projection.len());
+                projection.push(file_columns[file_idx].data_type().column_index());
             }
         }
 
+        // the projected columns are read in the order of the file
+        let mut sorted_projection = projection.clone();
+        sorted_projection.sort_unstable();
+        for mapping in field_mappings.iter_mut().flatten() {
+            *mapping = sorted_projection
+                .iter()
+                .position(|&column_index| column_index == projection[*mapping])
+                .expect("projected column not found");
+        }
+
         Ok((
             Arc::new(BlazeSchemaMapping::new(
-                self.table_schema.clone(),
+                Arc::new(self.table_schema.project(&self.projection)?),
                 field_mappings,
             )),
-            projection,
+            sorted_projection,
         ))
     }
 }
+
+fn is_hive_physical_column_name(name: &str) -> bool {
+    name.strip_prefix("_col")
+        .map(|id| !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit()))
+        .unwrap_or(false)
+}
+
+/// Statistics of stripes in an orc file, used for pruning stripes and answering
+/// pushed aggregates
+struct OrcStripeStatistics<'a> {
+    stripes: &'a [StripeMetadata],
+    table_schema: &'a SchemaRef,
+    stripe_columns: &'a [Option<usize>],
+}
+
+impl OrcStripeStatistics<'_> {
+    fn column_statistics_index(&self, column: &Column) -> Option<(usize, &DataType)> {
+        let (table_idx, field) = self.table_schema.column_with_name(&column.name)?;
+        Some((self.stripe_columns[table_idx]?, field.data_type()))
+    }
+
+    fn min_max_values(&self, column: &Column, is_min: bool) -> Option<ArrayRef> {
+        let (stats_idx, data_type) = self.column_statistics_index(column)?;
+        let type_stats = |stripe: &StripeMetadata| {
+            stripe
+                .column_statistics()
+                .get(stats_idx)
+                .and_then(|stats| stats.type_statistics())
+        };
+        let values: ArrayRef = match data_type {
+            DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64 => Arc::new(
+                Int64Array::from_iter(self.stripes.iter().map(|stripe| match type_stats(stripe) {
+                    Some(TypeStatistics::Integer { min, max, .. }) => {
+                        Some(if is_min { *min } else { *max })
+                    }
+                    _ => None,
+                })),
+            ),
+            DataType::Float32 | DataType::Float64 => Arc::new(Float64Array::from_iter(
+                self.stripes.iter().map(|stripe| match type_stats(stripe) {
+                    Some(TypeStatistics::Double { min, max, .. }) => {
+                        Some(if is_min { *min } else { *max })
+                    }
+                    _ => None,
+                }),
+            )),
+            DataType::Utf8 => {
+                Arc::new(StringArray::from_iter(self.stripes.iter().map(
+                    |stripe| match type_stats(stripe) {
+                        Some(TypeStatistics::String { min, max, .. }) => {
+                            Some(if is_min { min.clone() } else { max.clone() })
+                        }
+                        _ => None,
+                    },
+                )))
+            }
+            DataType::Date32 => Arc::new(Date32Array::from_iter(self.stripes.iter().map(
+                |stripe| match type_stats(stripe) {
+                    Some(TypeStatistics::Date { min, max }) => {
+                        Some(if is_min { *min } else { *max })
+                    }
+                    _ => None,
+                },
+            ))),
+            _ => return None,
+        };
+        arrow::compute::cast(&values, data_type).ok()
+    }
+}
+
+impl PruningStatistics for OrcStripeStatistics<'_> {
+    fn min_values(&self, column: &Column) -> Option<ArrayRef> {
+        self.min_max_values(column, true)
+    }
+
+    fn max_values(&self, column: &Column) -> Option<ArrayRef> {
+        self.min_max_values(column, false)
+    }
+
+    fn num_containers(&self) -> usize {
+        self.stripes.len()
+    }
+
+    fn null_counts(&self, column: &Column) -> Option<ArrayRef> {
+        let (stats_idx, _) = self.column_statistics_index(column)?;
+        Some(Arc::new(UInt64Array::from_iter(self.stripes.iter().map(
+            |stripe| {
+                let stats = stripe.column_statistics().get(stats_idx)?;
+                if !stats.has_null() {
+                    return Some(0);
+                }
+                Some(
+                    stripe
+                        .number_of_rows()
+                        .saturating_sub(stats.number_of_values()),
+                )
+            },
+        ))))
+    }
+
+    fn row_counts(&self, _column: &Column) -> Option<ArrayRef> {
+        Some(Arc::new(UInt64Array::from_iter_values(
+            self.stripes.iter().map(|stripe| stripe.number_of_rows()),
+        )))
+    }
+
+    fn contained(&self, _column: &Column, _values: &HashSet<ScalarValue>) -> Option<BooleanArray> {
+        None
+    }
+}
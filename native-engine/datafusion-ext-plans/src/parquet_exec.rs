@@ -17,35 +17,59 @@
 
 //! Execution plan for reading Parquet files
 
-use std::{any::Any, fmt, fmt::Formatter, ops::Range, pin::Pin, sync::Arc};
+use std::{any::Any, collections::HashSet, fmt, fmt::Formatter, ops::Range, pin::Pin, sync::Arc};
 
-use arrow::datatypes::SchemaRef;
+use arrow::{
+    array::{new_null_array, ArrayRef, BooleanArray, Int8Array, RecordBatch, UInt64Array},
+    compute::cast,
+    datatypes::{Schema, SchemaRef},
+};
 use blaze_jni_bridge::{
     conf, conf::BooleanConf, jni_call_static, jni_new_global_ref, jni_new_string,
 };
 use bytes::Bytes;
 use datafusion::{
+    common::{
+        self,
+        tree_node::{Transformed, TreeNode},
+        ScalarValue,
+    },
     datasource::physical_plan::{
         parquet::{page_filter::PagePruningAccessPlanFilter, ParquetOpener},
-        FileMeta, FileScanConfig, FileStream, OnError, ParquetFileMetrics,
-        ParquetFileReaderFactory,
+        FileMeta, FileOpenFuture, FileOpener, FileScanConfig, FileStream, OnError,
+        ParquetFileMetrics, ParquetFileReaderFactory,
     },
     error::{DataFusionError, Result},
     execution::context::TaskContext,
+    logical_expr::Operator,
     parquet::{
-        arrow::async_reader::{fetch_parquet_metadata, AsyncFileReader},
+        arrow::{
+            arrow_reader::statistics::StatisticsConverter,
+            async_reader::{fetch_parquet_metadata, AsyncFileReader},
+            parquet_to_arrow_schema,
+        },
         errors::ParquetError,
         file::metadata::ParquetMetaData,
     },
-    physical_expr::EquivalenceProperties,
-    physical_optimizer::pruning::PruningPredicate,
+    physical_expr::{
+        expressions::{BinaryExpr, Column, InListExpr, Literal},
+        utils::collect_columns,
+        EquivalenceProperties, PhysicalExprRef,
+    },
+    physical_optimizer::pruning::{PruningPredicate, PruningStatistics},
     physical_plan::{
-        metrics::{ExecutionPlanMetricsSet, MetricBuilder, MetricsSet},
+        metrics::{Count, ExecutionPlanMetricsSet, MetricBuilder, MetricsSet},
         DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, Partitioning, PhysicalExpr,
         PlanProperties, SendableRecordBatchStream, Statistics,
     },
 };
-use datafusion_ext_commons::{batch_size, hadoop_fs::FsProvider};
+use datafusion_ext_commons::{batch_size, df_execution_err, hadoop_fs::FsProvider};
+use datafusion_ext_exprs::{
+    input_file_name::InputFileNameExpr, monotonically_increasing_id::MonotonicallyIncreasingIdExpr,
+    raise_error::RaiseErrorExpr, row_num::RowNumExpr,
+    spark_scalar_subquery_wrapper::SparkScalarSubqueryWrapperExpr,
+    spark_udf_wrapper::SparkUDFWrapperExpr,
+};
 use fmt::Debug;
 use futures::{future::BoxFuture, FutureExt, StreamExt};
 use itertools::Itertools;
@@ -54,8 +78,19 @@ use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
 
 use crate::{
-    common::execution_context::ExecutionContext,
-    scan::{internal_file_reader::InternalFileReader, BlazeSchemaAdapterFactory},
+    common::{
+        cached_exprs_evaluator::is_reorderable_predicate, execution_context::ExecutionContext,
+    },
+    scan::{
+        aggregate_pushdown::{
+            pushed_aggregates_schema, PushedAggregate, PushedAggregatesAccumulator,
+        },
+        column_chunk_cache::ColumnChunkCache,
+        deletion_vector::{DeletionVectorDescriptor, DELTA_IS_ROW_DELETED_COLUMN},
+        internal_file_reader::{track_input_file_name, InternalFileReader},
+        partition_pruning::{prune_dynamic_partitions, DynamicPartitionFilter},
+        BlazeSchemaAdapterFactory, ColumnResolver,
+    },
 };
 
 /// Execution plan for scanning one or more Parquet partitions
@@ -69,6 +104,10 @@ pub struct ParquetExec {
     predicate: Option<Arc<dyn PhysicalExpr>>,
     pruning_predicate: Option<Arc<PruningPredicate>>,
     page_pruning_predicate: Option<Arc<PagePruningAccessPlanFilter>>,
+    is_point_lookup: bool,
+    late_materialization: bool,
+    dynamic_partition_filters: Vec<DynamicPartitionFilter>,
+    pushed_aggregates: Vec<PushedAggregate>,
     props: OnceCell<PlanProperties>,
 }
 
@@ -80,12 +119,35 @@ impl ParquetExec {
         fs_resource_id: String,
         predicate: Option<Arc<dyn PhysicalExpr>>,
     ) -> Self {
-        let metrics = ExecutionPlanMetricsSet::new();
+        let (projected_schema, projected_statistics, _projected_output_ordering) =
+            base_config.project();
+
+        Self {
+            fs_resource_id,
+            base_config,
+            projected_schema,
+            projected_statistics,
+            metrics: ExecutionPlanMetricsSet::new(),
+            predicate: None,
+            pruning_predicate: None,
+            page_pruning_predicate: None,
+            is_point_lookup: false,
+            late_materialization: false,
+            dynamic_partition_filters: vec![],
+            pushed_aggregates: vec![],
+            props: OnceCell::new(),
+        }
+        .with_predicate(predicate)
+    }
+
+    /// replaces the predicate and recomputes everything derived from it, other
+    /// states of the plan are kept.
+    fn with_predicate(mut self, predicate: Option<Arc<dyn PhysicalExpr>>) -> Self {
         let predicate_creation_errors =
-            MetricBuilder::new(&metrics).global_counter("num_predicate_creation_errors");
+            MetricBuilder::new(&self.metrics).global_counter("num_predicate_creation_errors");
 
-        let file_schema = &base_config.file_schema;
-        let pruning_predicate = predicate
+        let file_schema = &self.base_config.file_schema;
+        self.pruning_predicate = predicate
             .clone()
             .and_then(|predicate_expr| {
                 match PruningPredicate::try_new(predicate_expr, file_schema.clone()) {
@@ -99,24 +161,115 @@ impl ParquetExec {
             })
             .filter(|p| !p.always_true());
 
-        let page_pruning_predicate = predicate
+        self.page_pruning_predicate = predicate
             .as_ref()
             .map(|p| Arc::new(PagePruningAccessPlanFilter::new(p, file_schema.clone())));
 
-        let (projected_schema, projected_statistics, _projected_output_ordering) =
-            base_config.project();
+        self.is_point_lookup = predicate
+            .as_ref()
+            .is_some_and(|predicate| is_point_lookup_predicate(predicate));
 
-        Self {
-            fs_resource_id,
-            base_config,
-            projected_schema,
-            projected_statistics,
-            metrics,
-            predicate,
-            pruning_predicate,
-            page_pruning_predicate,
-            props: OnceCell::new(),
+        // predicate columns are decoded and filtered before other columns, which
+        // is only worthwhile if some projected columns are not used by predicates
+        self.late_materialization = predicate.as_ref().is_some_and(|predicate| {
+            let predicate_columns = collect_columns(predicate)
+                .into_iter()
+                .map(|column| column.index())
+                .collect::<HashSet<_>>();
+            !predicate_columns.is_empty()
+                && self
+                    .base_config
+                    .file_column_projection_indices()
+                    .unwrap_or_else(|| (0..file_schema.fields().len()).collect())
+                    .into_iter()
+                    .any(|idx| !predicate_columns.contains(&idx))
+        });
+
+        self.predicate = predicate;
+        self
+    }
+
+    /// returns the predicate used in pruning and row-level filtering, bound
+    /// to the file schema
+    pub fn predicate(&self) -> Option<&Arc<dyn PhysicalExpr>> {
+        self.predicate.as_ref()
+    }
+
+    /// pushes down predicates of the parent filter, which are bound to the
+    /// output schema of this scan. the predicates are used in row group/page
+    /// pruning and row-level filtering, predicates on partition columns or
+    /// with nondeterministic expressions are not pushed down.
+    ///
+    /// row-level filters are reordered by the parquet reader, so predicates
+    /// which may fail on rows guarded by other predicates (like `a / b > 1`
+    /// guarded by `b <> 0`) are not pushed down either. the parent filter
+    /// still evaluates all predicates in order.
+    pub fn with_filter_predicates(&self, predicates: &[PhysicalExprRef]) -> Self {
+        if !self.pushed_aggregates.is_empty() {
+            return self.clone();
+        }
+        let pushed = predicates
+            .iter()
+            .filter(|predicate| is_reorderable_predicate(predicate))
+            .filter_map(|predicate| self.to_file_schema_predicate(predicate))
+            .collect::<Vec<_>>();
+        if pushed.is_empty() {
+            return self.clone();
         }
+        log::info!("pushing down filter predicates to parquet scan: {pushed:?}");
+        let predicate = self
+            .predicate
+            .iter()
+            .cloned()
+            .chain(pushed)
+            .reduce(|a, b| Arc::new(BinaryExpr::new(a, Operator::And, b)))
+            .expect("non-empty predicates");
+        self.clone().with_predicate(Some(predicate))
+    }
+
+    fn to_file_schema_predicate(&self, predicate: &PhysicalExprRef) -> Option<PhysicalExprRef> {
+        let file_schema = &self.base_config.file_schema;
+        predicate
+            .clone()
+            .transform_down(&|node: PhysicalExprRef| {
+                let node_any = node.as_any();
+                if node_any.is::<RowNumExpr>()
+                    || node_any.is::<MonotonicallyIncreasingIdExpr>()
+                    || node_any.is::<InputFileNameExpr>()
+                    || node_any.is::<RaiseErrorExpr>()
+                    || node_any.is::<SparkUDFWrapperExpr>()
+                    || node_any.is::<SparkScalarSubqueryWrapperExpr>()
+                {
+                    return df_execution_err!("cannot push down expression: {node:?}");
+                }
+                if let Some(column) = node_any.downcast_ref::<Column>() {
+                    let name = self.projected_schema.field(column.index()).name();
+                    let file_idx = file_schema.index_of(name)?;
+                    return Ok(Transformed::yes(Arc::new(Column::new(name, file_idx))));
+                }
+                Ok(Transformed::no(node))
+            })
+            .map(|transformed| transformed.data)
+            .ok()
+    }
+
+    pub fn with_dynamic_partition_filters(
+        mut self,
+        dynamic_partition_filters: Vec<DynamicPartitionFilter>,
+    ) -> Self {
+        self.dynamic_partition_filters = dynamic_partition_filters;
+        self
+    }
+
+    /// outputs a single row of partial aggregate values per partition instead
+    /// of the scanned rows, which are answered from row group statistics if
+    /// possible. the scan must have no predicates.
+    pub fn with_pushed_aggregates(mut self, pushed_aggregates: Vec<PushedAggregate>) -> Self {
+        self.projected_schema =
+            pushed_aggregates_schema(&pushed_aggregates, &self.base_config.file_schema);
+        self.projected_statistics = Statistics::new_unknown(&self.projected_schema);
+        self.pushed_aggregates = pushed_aggregates;
+        self
     }
 }
 
@@ -140,7 +293,11 @@ impl DisplayAs for ParquetExec {
                 .as_ref()
                 .map(|pre| format!("{}", pre.predicate_expr()))
                 .unwrap_or(format!("<empty>")),
-        )
+        )?;
+        if !self.pushed_aggregates.is_empty() {
+            write!(f, ", pushed_aggregates={:?}", self.pushed_aggregates)?;
+        }
+        Ok(())
     }
 }
 
@@ -190,38 +347,87 @@ impl ExecutionPlan for ParquetExec {
 
         // get fs object from jni bridge resource
         let resource_id = jni_new_string!(&self.fs_resource_id)?;
+        // resources are removed from the resource map when taken, never retry it
         let fs = jni_call_static!(JniBridge.getResource(resource_id.as_obj()) -> JObject)?;
+        if fs.as_obj().is_null() {
+            return df_execution_err!(
+                "ParquetExec: fs resource not found: {}",
+                self.fs_resource_id
+            );
+        }
         let fs_provider = Arc::new(FsProvider::new(jni_new_global_ref!(fs.as_obj())?, &io_time));
 
-        let schema_adapter_factory = Arc::new(BlazeSchemaAdapterFactory);
+        let column_resolver = ColumnResolver {
+            case_sensitive: conf::CASE_SENSITIVE.value()?,
+            field_id_enabled: conf::PARQUET_FIELD_ID_READ_ENABLED.value()?,
+        };
+        let schema_adapter_factory = Arc::new(BlazeSchemaAdapterFactory::new(column_resolver));
         let projection = match self.base_config.file_column_projection_indices() {
             Some(proj) => proj,
             None => (0..self.base_config.file_schema.fields().len()).collect(),
         };
+        let file_projection = projection.clone();
 
-        let page_filtering_enabled = conf::PARQUET_ENABLE_PAGE_FILTERING.value()?;
-        let bloom_filter_enabled = conf::PARQUET_ENABLE_BLOOM_FILTER.value()?;
+        let base_config = prune_dynamic_partitions(
+            &self.base_config,
+            partition,
+            &self.dynamic_partition_filters,
+            &exec_ctx.register_counter_metric("dynamic_partition_pruned_files"),
+        )?;
 
+        let opener_flags = self.opener_flags(ParquetReadConfs {
+            page_filtering: conf::PARQUET_ENABLE_PAGE_FILTERING.value()?,
+            bloom_filter: conf::PARQUET_ENABLE_BLOOM_FILTER.value()?,
+            filter_pushdown: conf::PARQUET_ENABLE_FILTER_PUSHDOWN.value()?,
+            point_lookup_pruning: conf::PARQUET_ENABLE_POINT_LOOKUP_PRUNING.value()?,
+        });
+        let reader_factory = Arc::new(FsReaderFactory::new(fs_provider.clone()));
         let opener = ParquetOpener {
             partition_index: partition,
             projection: Arc::from(projection),
             batch_size: batch_size(),
-            limit: self.base_config.limit,
+            limit: base_config.limit,
             predicate: self.predicate.clone(),
             pruning_predicate: self.pruning_predicate.clone(),
             page_pruning_predicate: self.page_pruning_predicate.clone(),
             table_schema: self.base_config.file_schema.clone(),
             metadata_size_hint: None,
             metrics: self.metrics.clone(),
-            parquet_file_reader_factory: Arc::new(FsReaderFactory::new(fs_provider)),
-            pushdown_filters: page_filtering_enabled,
-            reorder_filters: page_filtering_enabled,
-            enable_page_index: page_filtering_enabled,
-            enable_bloom_filter: bloom_filter_enabled,
+            parquet_file_reader_factory: reader_factory.clone(),
+            pushdown_filters: opener_flags.pushdown_filters,
+            reorder_filters: opener_flags.pushdown_filters,
+            enable_page_index: opener_flags.enable_page_index,
+            enable_bloom_filter: opener_flags.enable_bloom_filter,
             schema_adapter_factory,
         };
+        let opener = RowDeletesOpener {
+            inner: Arc::new(opener),
+            partition_index: partition,
+            fs_provider,
+            reader_factory,
+            metrics: self.metrics.clone(),
+            column_resolver,
+            deleted_rows: exec_ctx.register_counter_metric("deletion_vector_deleted_rows"),
+            is_row_deleted_column: self
+                .projected_schema
+                .index_of(DELTA_IS_ROW_DELETED_COLUMN)
+                .ok(),
+        };
+
+        if !self.pushed_aggregates.is_empty() {
+            if self.pruning_predicate.is_some() {
+                return df_execution_err!("cannot push down aggregates into a filtered scan");
+            }
+            return execute_pushed_aggregates(
+                base_config,
+                opener,
+                file_projection,
+                self.pushed_aggregates.clone(),
+                exec_ctx,
+            );
+        }
 
-        let mut file_stream = FileStream::new(&self.base_config, partition, opener, &self.metrics)?;
+        let mut file_stream = FileStream::new(&base_config, partition, opener, &self.metrics)?;
         if conf::IGNORE_CORRUPTED_FILES.value()? {
             file_stream = file_stream.with_on_error(OnError::Skip);
         }
@@ -239,8 +445,65 @@ impl ExecutionPlan for ParquetExec {
     }
 }
 
+/// parquet reading features enabled by confs
+#[derive(Debug, Clone, Copy)]
+struct ParquetReadConfs {
+    page_filtering: bool,
+    bloom_filter: bool,
+    filter_pushdown: bool,
+    point_lookup_pruning: bool,
+}
+
+/// parquet reading features of the opener, derived from confs and the
+/// predicate of the scan
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ParquetOpenerFlags {
+    pushdown_filters: bool,
+    enable_page_index: bool,
+    enable_bloom_filter: bool,
+}
+
+impl ParquetExec {
+    fn opener_flags(&self, confs: ParquetReadConfs) -> ParquetOpenerFlags {
+        let filter_pushdown = confs.page_filtering || confs.filter_pushdown;
+
+        // point lookups are highly selective, most pages and row groups can be
+        // skipped with page index and bloom filters
+        let point_lookup_pruning = self.is_point_lookup && confs.point_lookup_pruning;
+        ParquetOpenerFlags {
+            pushdown_filters: filter_pushdown && self.late_materialization,
+            enable_page_index: confs.page_filtering || point_lookup_pruning,
+            enable_bloom_filter: confs.bloom_filter || point_lookup_pruning,
+        }
+    }
+}
+
+/// returns whether the predicate contains equality comparisons between
+/// columns and literals (like `a = 1` or `a in (1, 2)`)
+fn is_point_lookup_predicate(predicate: &PhysicalExprRef) -> bool {
+    let is_column_or_literal =
+        |expr: &PhysicalExprRef| expr.as_any().is::<Column>() || expr.as_any().is::<Literal>();
+    predicate
+        .exists(|expr| {
+            if let Some(binary) = expr.as_any().downcast_ref::<BinaryExpr>() {
+                return Ok(*binary.op() == Operator::Eq
+                    && is_column_or_literal(binary.left())
+                    && is_column_or_literal(binary.right())
+                    && (binary.left().as_any().is::<Column>()
+                        != binary.right().as_any().is::<Column>()));
+            }
+            if let Some(in_list) = expr.as_any().downcast_ref::<InListExpr>() {
+                return Ok(!in_list.negated()
+                    && in_list.expr().as_any().is::<Column>()
+                    && in_list.list().iter().all(|v| v.as_any().is::<Literal>()));
+            }
+            Ok(false)
+        })
+        .unwrap_or(false)
+}
+
 fn execute_parquet_scan(
-    mut stream: Pin<Box<FileStream<ParquetOpener>>>,
+    mut stream: Pin<Box<FileStream<RowDeletesOpener>>>,
     exec_ctx: Arc<ExecutionContext>,
 ) -> Result<SendableRecordBatchStream> {
     Ok(exec_ctx
@@ -255,6 +518,262 @@ fn execute_parquet_scan(
         }))
 }
 
+/// answers pushed aggregates of all files in the partition, from row group
+/// statistics if the file has no row-level deletes and all statistics are
+/// available, or else by reading the file
+fn execute_pushed_aggregates(
+    base_config: FileScanConfig,
+    opener: RowDeletesOpener,
+    projection: Vec<usize>,
+    pushed_aggregates: Vec<PushedAggregate>,
+    exec_ctx: Arc<ExecutionContext>,
+) -> Result<SendableRecordBatchStream> {
+    let files = base_config
+        .file_groups
+        .get(opener.partition_index)
+        .cloned()
+        .unwrap_or_default();
+    let file_schema = base_config.file_schema.clone();
+    let metadata_aggregated_files = exec_ctx.register_counter_metric("metadata_aggregated_files");
+
+    Ok(exec_ctx
+        .clone()
+        .output_with_sender("ParquetScan", move |sender| async move {
+            sender.exclude_time(exec_ctx.baseline_metrics().elapsed_compute());
+            let _timer = exec_ctx.baseline_metrics().elapsed_compute().timer();
+            let mut accumulator =
+                PushedAggregatesAccumulator::try_new(pushed_aggregates, file_schema.clone())?;
+
+            for file in files {
+                if file.extensions.is_none() {
+                    let mut reader = opener.create_reader(file.object_meta.clone())?;
+                    let metadata = reader.get_metadata().await?;
+                    let statistics = ParquetRowGroupStatistics::try_new(
+                        &metadata,
+                        &file_schema,
+                        opener.column_resolver,
+                    )?;
+
+                    // row groups are assigned to the file range containing their
+                    // first bytes, like the parquet reader does
+                    let selected = metadata
+                        .row_groups()
+                        .iter()
+                        .map(|row_group| {
+                            let column = row_group.column(0);
+                            let offset = column
+                                .dictionary_page_offset()
+                                .unwrap_or_else(|| column.data_page_offset());
+                            file.range
+                                .as_ref()
+                                .map_or(true, |range| offset >= range.start && offset < range.end)
+                        })
+                        .collect::<Vec<_>>();
+                    if accumulator.update_with_statistics(&statistics, &selected)? {
+                        metadata_aggregated_files.add(1);
+                        continue;
+                    }
+                }
+
+                let mut file_meta = FileMeta::from(file.object_meta);
+                file_meta.range = file.range;
+                file_meta.extensions = file.extensions;
+                let mut stream = opener.open(file_meta)?.await?;
+                while let Some(batch) = stream.next().await.transpose()? {
+                    accumulator.update_batch(&batch, &projection)?;
+                }
+            }
+            sender
+                .send(accumulator.finish(&exec_ctx.output_schema())?)
+                .await;
+            Ok(())
+        }))
+}
+
+/// Row group statistics of a parquet file converted to the table schema,
+/// table columns missing in the file are treated as all nulls
+struct ParquetRowGroupStatistics<'a> {
+    metadata: &'a ParquetMetaData,
+    file_arrow_schema: Schema,
+    table_schema: &'a SchemaRef,
+    column_resolver: ColumnResolver,
+}
+
+impl<'a> ParquetRowGroupStatistics<'a> {
+    fn try_new(
+        metadata: &'a ParquetMetaData,
+        table_schema: &'a SchemaRef,
+        column_resolver: ColumnResolver,
+    ) -> Result<Self> {
+        let file_metadata = metadata.file_metadata();
+        let file_arrow_schema = parquet_to_arrow_schema(
+            file_metadata.schema_descr(),
+            file_metadata.key_value_metadata(),
+        )?;
+        Ok(Self {
+            metadata,
+            file_arrow_schema,
+            table_schema,
+            column_resolver,
+        })
+    }
+
+    /// returns the statistics converter of a table column, or None if the
+    /// column is missing in the file
+    fn converter(&self, column: &common::Column) -> Result<Option<StatisticsConverter<'_>>> {
+        let table_field = self.table_schema.field_with_name(&column.name)?;
+        let Some(file_idx) = self
+            .column_resolver
+            .resolve(table_field, &self.file_arrow_schema)?
+        else {
+            return Ok(None);
+        };
+        Ok(Some(StatisticsConverter::try_new(
+            self.file_arrow_schema.field(file_idx).name(),
+            &self.file_arrow_schema,
+            self.metadata.file_metadata().schema_descr(),
+        )?))
+    }
+
+    fn min_max_values(&self, column: &common::Column, is_min: bool) -> Option<ArrayRef> {
+        let row_groups = self.metadata.row_groups();
+        let data_type = self
+            .table_schema
+            .field_with_name(&column.name)
+            .ok()?
+            .data_type();
+        let values = match self.converter(column).ok()? {
+            Some(converter) if is_min => converter.row_group_mins(row_groups).ok()?,
+            Some(converter) => converter.row_group_maxes(row_groups).ok()?,
+            None => new_null_array(data_type, row_groups.len()),
+        };
+        cast(&values, data_type).ok()
+    }
+}
+
+impl PruningStatistics for ParquetRowGroupStatistics<'_> {
+    fn min_values(&self, column: &common::Column) -> Option<ArrayRef> {
+        self.min_max_values(column, true)
+    }
+
+    fn max_values(&self, column: &common::Column) -> Option<ArrayRef> {
+        self.min_max_values(column, false)
+    }
+
+    fn num_containers(&self) -> usize {
+        self.metadata.num_row_groups()
+    }
+
+    fn null_counts(&self, column: &common::Column) -> Option<ArrayRef> {
+        match self.converter(column).ok()? {
+            Some(converter) => {
+                let null_counts = converter
+                    .row_group_null_counts(self.metadata.row_groups())
+                    .ok()?;
+                Some(Arc::new(null_counts))
+            }
+            None => self.row_counts(column),
+        }
+    }
+
+    fn row_counts(&self, _column: &common::Column) -> Option<ArrayRef> {
+        let row_counts = self
+            .metadata
+            .row_groups()
+            .iter()
+            .map(|row_group| row_group.num_rows() as u64);
+        Some(Arc::new(UInt64Array::from_iter_values(row_counts)))
+    }
+
+    fn contained(
+        &self,
+        _column: &common::Column,
+        _values: &HashSet<ScalarValue>,
+    ) -> Option<BooleanArray> {
+        None
+    }
+}
+
+/// Opens parquet files with their row-level deletes (delta deletion vectors)
+/// applied, deleted rows are skipped with row selections of the parquet access
+/// plan
+struct RowDeletesOpener {
+    inner: Arc<ParquetOpener>,
+    partition_index: usize,
+    fs_provider: Arc<FsProvider>,
+    reader_factory: Arc<FsReaderFactory>,
+    metrics: ExecutionPlanMetricsSet,
+    column_resolver: ColumnResolver,
+    deleted_rows: Count,
+    is_row_deleted_column: Option<usize>,
+}
+
+impl RowDeletesOpener {
+    fn create_reader(&self, object_meta: ObjectMeta) -> Result<Box<dyn AsyncFileReader + Send>> {
+        self.reader_factory.create_reader(
+            self.partition_index,
+            FileMeta::from(object_meta),
+            None,
+            &self.metrics,
+        )
+    }
+}
+
+impl FileOpener for RowDeletesOpener {
+    fn open(&self, mut file_meta: FileMeta) -> Result<FileOpenFuture> {
+        let descriptor = file_meta
+            .extensions
+            .as_ref()
+            .and_then(|extensions| extensions.downcast_ref::<DeletionVectorDescriptor>())
+            .cloned();
+        let metadata_reader = match descriptor {
+            Some(_) => Some(self.create_reader(file_meta.object_meta.clone())?),
+            None => None,
+        };
+        let inner = self.inner.clone();
+        let fs_provider = self.fs_provider.clone();
+        let deleted_rows = self.deleted_rows.clone();
+        let is_row_deleted_column = self.is_row_deleted_column;
+
+        Ok(Box::pin(async move {
+            if let (Some(descriptor), Some(mut reader)) = (descriptor, metadata_reader) {
+                let metadata = reader.get_metadata().await?;
+                let deletion_vector =
+                    tokio::task::spawn_blocking(move || descriptor.load(&fs_provider))
+                        .await
+                        .expect("tokio spawn_blocking error")?;
+                let row_group_num_rows = metadata
+                    .row_groups()
+                    .iter()
+                    .map(|row_group| row_group.num_rows() as usize)
+                    .collect::<Vec<_>>();
+                deleted_rows.add(deletion_vector.num_deleted_rows());
+                file_meta.extensions =
+                    Some(Arc::new(deletion_vector.access_plan(&row_group_num_rows)));
+            }
+            let object_meta = file_meta.object_meta.clone();
+            let stream = inner.open(file_meta)?.await?;
+            let stream = track_input_file_name(stream, &object_meta)?;
+
+            // deleted rows are already skipped, so the delta internal column
+            // is filled with zeros
+            let Some(is_row_deleted_column) = is_row_deleted_column else {
+                return Ok(stream);
+            };
+            Ok(stream
+                .map(move |batch| {
+                    let batch = batch?;
+                    let mut columns = batch.columns().to_vec();
+                    let data_type = columns[is_row_deleted_column].data_type().clone();
+                    columns[is_row_deleted_column] =
+                        cast(&Int8Array::from(vec![0; batch.num_rows()]), &data_type)?;
+                    RecordBatch::try_new(batch.schema(), columns)
+                })
+                .boxed())
+        }))
+    }
+}
+
 #[derive(Clone)]
 pub struct FsReaderFactory {
     fs_provider: Arc<FsProvider>,
@@ -352,76 +871,45 @@ impl AsyncFileReader for ParquetFileReaderRef {
         &mut self,
         ranges: Vec<Range<usize>>,
     ) -> BoxFuture<'_, datafusion::parquet::errors::Result<Vec<Bytes>>> {
-        const MAX_OVER_READ_SIZE: usize = 16384; // TODO: make it configurable
-        let num_ranges = ranges.len();
-        let (sorted_range_indices, sorted_ranges): (Vec<usize>, Vec<Range<usize>>) = ranges
-            .into_iter()
-            .enumerate()
-            .sorted_unstable_by_key(|(_, r)| r.start)
-            .unzip();
-        let mut merged_ranges = vec![];
-
-        for range in sorted_ranges.iter().cloned() {
-            if merged_ranges.is_empty() {
-                merged_ranges.push(range);
-                continue;
-            }
-
-            let last_merged_range = merged_ranges.last_mut().unwrap();
-            if range.start <= last_merged_range.end + MAX_OVER_READ_SIZE {
-                last_merged_range.end = range.end.max(last_merged_range.end);
-            } else {
-                merged_ranges.push(range);
-            }
-        }
-
+        let inner = self.0.clone();
+        let cache = ColumnChunkCache::get();
         async move {
-            let inner = self.0.clone();
-            let merged_bytes = Arc::new(Mutex::new(Vec::with_capacity(merged_ranges.len())));
-            let merged_bytes_cloned = merged_bytes.clone();
-            let merged_ranges_cloned = merged_ranges.clone();
-            tokio::task::spawn_blocking(move || {
-                let merged_bytes = &mut *merged_bytes_cloned.lock();
-                for range in merged_ranges_cloned {
-                    inner.metrics.bytes_scanned.add(range.len());
-                    if range.is_empty() {
-                        merged_bytes.push(Bytes::new());
-                        continue;
-                    }
-                    let bytes = inner.get_internal_reader().read_fully(range)?;
-                    merged_bytes.push(bytes);
-                }
-                Ok::<_, DataFusionError>(())
-            })
-            .await
-            .expect("tokio spawn_blocking error")
-            .map_err(|e| ParquetError::External(Box::new(e)))?;
-
-            let merged_bytes = &*merged_bytes.lock();
-            let mut sorted_range_bytes = Vec::with_capacity(num_ranges);
-            let mut m = 0;
-            for range in sorted_ranges {
-                if range.is_empty() {
-                    sorted_range_bytes.push(Bytes::new());
-                    continue;
-                }
-                while merged_ranges[m].end <= range.start {
-                    m += 1;
+            let meta = inner.get_meta();
+
+            // serve cached column chunks and only read the missing ones
+            let mut cached = vec![None; ranges.len()];
+            if let Some(cache) = &cache {
+                for (range, cached) in ranges.iter().zip(&mut cached) {
+                    *cached = cache.lookup(&meta, range);
                 }
-                let len = range.len();
-                if len < merged_ranges[m].len() {
-                    let offset = range.start - merged_ranges[m].start;
-                    sorted_range_bytes.push(merged_bytes[m].slice(offset..offset + len));
-                } else {
-                    sorted_range_bytes.push(merged_bytes[m].clone());
+            }
+            let missing_ranges = ranges
+                .iter()
+                .zip(&cached)
+                .filter(|(_, cached)| cached.is_none())
+                .map(|(range, _)| range.clone())
+                .collect::<Vec<_>>();
+            let missing_bytes = if !missing_ranges.is_empty() {
+                read_byte_ranges(inner, missing_ranges.clone()).await?
+            } else {
+                vec![]
+            };
+            if let Some(cache) = &cache {
+                for (range, bytes) in missing_ranges.iter().zip(&missing_bytes) {
+                    cache
+                        .insert(&meta, range, bytes.clone())
+                        .await
+                        .map_err(|e| ParquetError::External(Box::new(e)))?;
                 }
             }
 
-            let mut range_bytes = Vec::with_capacity(num_ranges);
-            for i in 0..num_ranges {
-                range_bytes.push(sorted_range_bytes[sorted_range_indices[i]].clone());
-            }
-            Ok(range_bytes)
+            let mut missing_bytes = missing_bytes.into_iter();
+            Ok(cached
+                .into_iter()
+                .map(|cached| {
+                    cached.unwrap_or_else(|| missing_bytes.next().expect("missing bytes"))
+                })
+                .collect())
         }
         .boxed()
     }
@@ -488,3 +976,310 @@ impl AsyncFileReader for ParquetFileReaderRef {
         .boxed()
     }
 }
+
+async fn read_byte_ranges(
+    inner: Arc<ParquetFileReader>,
+    ranges: Vec<Range<usize>>,
+) -> datafusion::parquet::errors::Result<Vec<Bytes>> {
+    const MAX_OVER_READ_SIZE: usize = 16384; // TODO: make it configurable
+    let num_ranges = ranges.len();
+    let (sorted_range_indices, sorted_ranges): (Vec<usize>, Vec<Range<usize>>) = ranges
+        .into_iter()
+        .enumerate()
+        .sorted_unstable_by_key(|(_, r)| r.start)
+        .unzip();
+    let mut merged_ranges = vec![];
+
+    for range in sorted_ranges.iter().cloned() {
+        if merged_ranges.is_empty() {
+            merged_ranges.push(range);
+            continue;
+        }
+
+        let last_merged_range = merged_ranges.last_mut().unwrap();
+        if range.start <= last_merged_range.end + MAX_OVER_READ_SIZE {
+            last_merged_range.end = range.end.max(last_merged_range.end);
+        } else {
+            merged_ranges.push(range);
+        }
+    }
+
+    let merged_bytes = Arc::new(Mutex::new(Vec::with_capacity(merged_ranges.len())));
+    let merged_bytes_cloned = merged_bytes.clone();
+    let merged_ranges_cloned = merged_ranges.clone();
+    tokio::task::spawn_blocking(move || {
+        let merged_bytes = &mut *merged_bytes_cloned.lock();
+        for range in merged_ranges_cloned {
+            inner.metrics.bytes_scanned.add(range.len());
+            if range.is_empty() {
+                merged_bytes.push(Bytes::new());
+                continue;
+            }
+            let bytes = inner.get_internal_reader().read_fully(range)?;
+            merged_bytes.push(bytes);
+        }
+        Ok::<_, DataFusionError>(())
+    })
+    .await
+    .expect("tokio spawn_blocking error")
+    .map_err(|e| ParquetError::External(Box::new(e)))?;
+
+    let merged_bytes = &*merged_bytes.lock();
+    let mut sorted_range_bytes = Vec::with_capacity(num_ranges);
+    let mut m = 0;
+    for range in sorted_ranges {
+        if range.is_empty() {
+            sorted_range_bytes.push(Bytes::new());
+            continue;
+        }
+        while merged_ranges[m].end <= range.start {
+            m += 1;
+        }
+        let len = range.len();
+        if len < merged_ranges[m].len() {
+            let offset = range.start - merged_ranges[m].start;
+            sorted_range_bytes.push(merged_bytes[m].slice(offset..offset + len));
+        } else {
+            sorted_range_bytes.push(merged_bytes[m].clone());
+        }
+    }
+
+    let mut range_bytes = Vec::with_capacity(num_ranges);
+    for i in 0..num_ranges {
+        range_bytes.push(sorted_range_bytes[sorted_range_indices[i]].clone());
+    }
+    Ok(range_bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::{
+        common::ScalarValue,
+        datasource::physical_plan::FileScanConfig,
+        execution::object_store::ObjectStoreUrl,
+        logical_expr::Operator,
+        physical_expr::{
+            expressions::{BinaryExpr, Column, InListExpr, Literal},
+            PhysicalExprRef,
+        },
+        physical_plan::ExecutionPlan,
+    };
+
+    use crate::{
+        parquet_exec::{
+            is_point_lookup_predicate, ParquetExec, ParquetOpenerFlags, ParquetReadConfs,
+        },
+        scan::{
+            aggregate_pushdown::{PushedAggFunction, PushedAggregate},
+            partition_pruning::DynamicPartitionFilter,
+        },
+    };
+
+    fn build_parquet_exec() -> ParquetExec {
+        build_parquet_exec_with_projection(vec![2, 1, 0])
+    }
+
+    fn build_parquet_exec_with_projection(projection: Vec<usize>) -> ParquetExec {
+        let file_schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, true),
+            Field::new("b", DataType::Int32, true),
+            Field::new("c", DataType::Utf8, true),
+        ]));
+        let scan_config = FileScanConfig::new(ObjectStoreUrl::local_filesystem(), file_schema)
+            .with_file_groups(vec![vec![]])
+            .with_projection(Some(projection));
+        ParquetExec::new(scan_config, "fs".to_string(), None)
+    }
+
+    fn col(exec: &ParquetExec, name: &str) -> PhysicalExprRef {
+        Arc::new(Column::new_with_schema(name, &exec.schema()).unwrap())
+    }
+
+    fn lit(v: i32) -> PhysicalExprRef {
+        Arc::new(Literal::new(ScalarValue::Int32(Some(v))))
+    }
+
+    fn binary(l: PhysicalExprRef, op: Operator, r: PhysicalExprRef) -> PhysicalExprRef {
+        Arc::new(BinaryExpr::new(l, op, r))
+    }
+
+    #[test]
+    fn test_push_down_filter_predicates() {
+        let exec =
+            build_parquet_exec().with_dynamic_partition_filters(vec![DynamicPartitionFilter {
+                partition_column: 0,
+                resource_id: "dpp".to_string(),
+            }]);
+        let pushed = exec.with_filter_predicates(&[
+            binary(col(&exec, "a"), Operator::Gt, lit(1)),
+            binary(col(&exec, "b"), Operator::Eq, lit(2)),
+        ]);
+
+        // columns are remapped from the output schema to the file schema
+        assert_eq!(
+            format!("{}", pushed.predicate.as_ref().unwrap()),
+            "a@0 > 1 AND b@1 = 2",
+        );
+        assert!(pushed.pruning_predicate.is_some());
+        assert!(pushed.is_point_lookup);
+        assert!(pushed.late_materialization);
+        assert_eq!(pushed.dynamic_partition_filters.len(), 1);
+        assert_eq!(pushed.schema(), exec.schema());
+    }
+
+    #[test]
+    fn test_push_down_guarded_division() {
+        let exec = build_parquet_exec();
+
+        // `b <> 0 AND a / b > 1`: the division must not be evaluated before
+        // its guard, which is not guaranteed by the parquet row filters
+        let pushed = exec.with_filter_predicates(&[
+            binary(col(&exec, "b"), Operator::NotEq, lit(0)),
+            binary(
+                binary(col(&exec, "a"), Operator::Divide, col(&exec, "b")),
+                Operator::Gt,
+                lit(1),
+            ),
+        ]);
+        assert_eq!(
+            format!("{}", pushed.predicate.as_ref().unwrap()),
+            "b@1 != 0"
+        );
+
+        let pushed = exec.with_filter_predicates(&[binary(
+            binary(col(&exec, "a"), Operator::Divide, col(&exec, "b")),
+            Operator::Gt,
+            lit(1),
+        )]);
+        assert!(pushed.predicate.is_none());
+        assert!(!pushed.late_materialization);
+    }
+
+    #[test]
+    fn test_push_down_with_pushed_aggregates() {
+        let exec = build_parquet_exec().with_pushed_aggregates(vec![PushedAggregate {
+            function: PushedAggFunction::CountRows,
+            column: 0,
+        }]);
+        let predicate = binary(Arc::new(Column::new("agg#0", 0)), Operator::Gt, lit(1));
+        let pushed = exec.with_filter_predicates(&[predicate]);
+        assert!(pushed.predicate.is_none());
+        assert_eq!(pushed.pushed_aggregates.len(), 1);
+        assert_eq!(pushed.schema(), exec.schema());
+    }
+
+    #[test]
+    fn test_late_materialization() {
+        // projected column c is not used by predicates
+        let exec = build_parquet_exec();
+        let pushed = exec.with_filter_predicates(&[binary(col(&exec, "a"), Operator::Gt, lit(1))]);
+        assert!(pushed.late_materialization);
+
+        // all projected columns are used by predicates
+        let pushed = exec.with_filter_predicates(&[
+            binary(col(&exec, "a"), Operator::Gt, lit(1)),
+            binary(col(&exec, "b"), Operator::Eq, lit(2)),
+            binary(col(&exec, "c"), Operator::Eq, col(&exec, "c")),
+        ]);
+        assert!(!pushed.late_materialization);
+
+        let exec = build_parquet_exec_with_projection(vec![1, 0]);
+        let pushed = exec.with_filter_predicates(&[
+            binary(col(&exec, "a"), Operator::Gt, lit(1)),
+            binary(col(&exec, "b"), Operator::Eq, lit(2)),
+        ]);
+        assert!(!pushed.late_materialization);
+        let pushed = exec.with_filter_predicates(&[binary(col(&exec, "b"), Operator::Eq, lit(2))]);
+        assert!(pushed.late_materialization);
+
+        // no predicates
+        assert!(!exec.late_materialization);
+    }
+
+    #[test]
+    fn test_is_point_lookup_predicate() {
+        let exec = build_parquet_exec();
+        let in_list = |negated: bool, list: Vec<PhysicalExprRef>| -> PhysicalExprRef {
+            Arc::new(InListExpr::new(col(&exec, "a"), list, negated, None))
+        };
+
+        let point_lookups = [
+            binary(col(&exec, "a"), Operator::Eq, lit(1)),
+            binary(lit(1), Operator::Eq, col(&exec, "a")),
+            in_list(false, vec![lit(1), lit(2)]),
+            binary(
+                binary(col(&exec, "a"), Operator::Gt, lit(1)),
+                Operator::And,
+                binary(col(&exec, "b"), Operator::Eq, lit(2)),
+            ),
+        ];
+        for predicate in &point_lookups {
+            assert!(is_point_lookup_predicate(predicate), "{predicate}");
+        }
+
+        let non_point_lookups = [
+            binary(col(&exec, "a"), Operator::Gt, lit(1)),
+            binary(col(&exec, "a"), Operator::Eq, col(&exec, "b")),
+            binary(lit(1), Operator::Eq, lit(1)),
+            in_list(true, vec![lit(1), lit(2)]),
+            in_list(false, vec![lit(1), col(&exec, "b")]),
+        ];
+        for predicate in &non_point_lookups {
+            assert!(!is_point_lookup_predicate(predicate), "{predicate}");
+        }
+    }
+
+    #[test]
+    fn test_opener_flags() {
+        let exec = build_parquet_exec();
+        let point_lookup =
+            exec.with_filter_predicates(&[binary(col(&exec, "a"), Operator::Eq, lit(1))]);
+        let range_scan =
+            exec.with_filter_predicates(&[binary(col(&exec, "a"), Operator::Gt, lit(1))]);
+        let confs = |page_filtering, bloom_filter, point_lookup_pruning| ParquetReadConfs {
+            page_filtering,
+            bloom_filter,
+            filter_pushdown: true,
+            point_lookup_pruning,
+        };
+        let flags = |enable_page_index, enable_bloom_filter| ParquetOpenerFlags {
+            pushdown_filters: true,
+            enable_page_index,
+            enable_bloom_filter,
+        };
+
+        // explicit confs are respected if point lookup pruning is disabled
+        assert_eq!(
+            point_lookup.opener_flags(confs(false, false, false)),
+            flags(false, false)
+        );
+        assert_eq!(
+            point_lookup.opener_flags(confs(true, false, false)),
+            flags(true, false)
+        );
+        assert_eq!(
+            point_lookup.opener_flags(confs(false, true, false)),
+            flags(false, true)
+        );
+
+        // point lookup pruning only applies to point lookups
+        assert_eq!(
+            point_lookup.opener_flags(confs(false, false, true)),
+            flags(true, true)
+        );
+        assert_eq!(
+            range_scan.opener_flags(confs(false, false, true)),
+            flags(false, false)
+        );
+
+        // filters are not pushed down without late materialization
+        assert!(
+            !build_parquet_exec()
+                .opener_flags(confs(true, true, true))
+                .pushdown_filters
+        );
+    }
+}
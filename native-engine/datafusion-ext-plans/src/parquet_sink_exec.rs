@@ -15,45 +15,73 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use std::{any::Any, fmt::Formatter, io::Write, sync::Arc};
+use std::{any::Any, collections::HashMap, fmt::Formatter, io::Write, sync::Arc};
 
 use arrow::{
-    datatypes::SchemaRef,
+    array::{AsArray, Int32Array, UInt32Array},
+    compute::SortOptions,
+    datatypes::{DataType, Field, FieldRef, Schema, SchemaRef, TimeUnit, TimestampMicrosecondType},
     record_batch::{RecordBatch, RecordBatchOptions},
+    row::{RowConverter, SortField},
 };
 use blaze_jni_bridge::{jni_call_static, jni_get_string, jni_new_global_ref, jni_new_string};
 use datafusion::{
     common::{Result, ScalarValue, Statistics},
     execution::context::TaskContext,
     parquet::{
-        arrow::{parquet_to_arrow_schema, ArrowWriter},
-        basic::{BrotliLevel, Compression, GzipLevel, ZstdLevel},
-        file::properties::{EnabledStatistics, WriterProperties, WriterVersion},
-        schema::{parser::parse_message_type, types::SchemaDescriptor},
+        arrow::{
+            arrow_to_parquet_schema,
+            arrow_writer::{compute_leaves, get_column_writers, ArrowColumnWriter},
+            parquet_to_arrow_schema,
+        },
+        basic::{BrotliLevel, Compression, GzipLevel, Type as PhysicalType, ZstdLevel},
+        data_type::{Int96, Int96Type},
+        file::{
+            properties::{
+                EnabledStatistics, WriterProperties, WriterPropertiesBuilder, WriterPropertiesPtr,
+                WriterVersion,
+            },
+            writer::SerializedFileWriter,
+        },
+        schema::{
+            parser::parse_message_type,
+            types::{SchemaDescPtr, SchemaDescriptor, Type},
+        },
     },
-    physical_expr::EquivalenceProperties,
+    physical_expr::{expressions::Column, EquivalenceProperties, PhysicalSortExpr},
     physical_plan::{
         metrics::{Count, ExecutionPlanMetricsSet, MetricsSet, Time},
+        stream::RecordBatchStreamAdapter,
         DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, ExecutionPlanProperties,
         PlanProperties, SendableRecordBatchStream,
     },
 };
 use datafusion_ext_commons::{
-    arrow::{array_size::ArraySize, cast::cast},
+    arrow::{array_size::ArraySize, cast::cast, selection::take_batch},
     df_execution_err,
     hadoop_fs::{FsDataOutputWrapper, FsProvider},
+    spark_hash::create_murmur3_hashes,
 };
-use futures::StreamExt;
+use futures::{stream, StreamExt};
 use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
 
-use crate::common::execution_context::ExecutionContext;
+use crate::{
+    common::{
+        execution_context::{ExecutionContext, WrappedRecordBatchSender},
+        stream_exec::RecordBatchStreamExec,
+    },
+    sort_exec::SortExec,
+};
 
 #[derive(Debug)]
 pub struct ParquetSinkExec {
     fs_resource_id: String,
     input: Arc<dyn ExecutionPlan>,
     num_dyn_parts: usize,
+    bucket_columns: Vec<usize>,
+    num_buckets: usize,
+    max_concurrent_writers: usize,
     props: Vec<(String, String)>,
     metrics: ExecutionPlanMetricsSet,
     plan_props: OnceCell<PlanProperties>,
@@ -70,11 +98,29 @@ impl ParquetSinkExec {
             input,
             fs_resource_id,
             num_dyn_parts,
+            bucket_columns: vec![],
+            num_buckets: 0,
+            max_concurrent_writers: 0,
             props,
             metrics: ExecutionPlanMetricsSet::new(),
             plan_props: OnceCell::new(),
         }
     }
+
+    /// writes rows of different buckets into different files, bucket ids are
+    /// computed from the bucket columns like spark's bucketing
+    pub fn with_bucketing(mut self, bucket_columns: Vec<usize>, num_buckets: usize) -> Self {
+        self.bucket_columns = bucket_columns;
+        self.num_buckets = num_buckets;
+        self
+    }
+
+    /// writes unsorted input with at most `max_concurrent_writers` opened part
+    /// writers, 0 means the input is sorted by dynamic partitions and buckets
+    pub fn with_max_concurrent_writers(mut self, max_concurrent_writers: usize) -> Self {
+        self.max_concurrent_writers = max_concurrent_writers;
+        self
+    }
 }
 
 impl DisplayAs for ParquetSinkExec {
@@ -114,12 +160,16 @@ impl ExecutionPlan for ParquetSinkExec {
         self: Arc<Self>,
         children: Vec<Arc<dyn ExecutionPlan>>,
     ) -> Result<Arc<dyn ExecutionPlan>> {
-        Ok(Arc::new(Self::new(
-            children[0].clone(),
-            self.fs_resource_id.clone(),
-            self.num_dyn_parts,
-            self.props.clone(),
-        )))
+        Ok(Arc::new(
+            Self::new(
+                children[0].clone(),
+                self.fs_resource_id.clone(),
+                self.num_dyn_parts,
+                self.props.clone(),
+            )
+            .with_bucketing(self.bucket_columns.clone(), self.num_buckets)
+            .with_max_concurrent_writers(self.max_concurrent_writers),
+        ))
     }
 
     fn execute(
@@ -140,7 +190,14 @@ impl ExecutionPlan for ParquetSinkExec {
         )?);
 
         let input = exec_ctx.execute_with_input_stats(&self.input)?;
-        execute_parquet_sink(parquet_sink_context, input, exec_ctx)
+        execute_parquet_sink(
+            parquet_sink_context,
+            input,
+            exec_ctx,
+            self.bucket_columns.clone(),
+            self.num_buckets,
+            self.max_concurrent_writers,
+        )
     }
 
     fn metrics(&self) -> Option<MetricsSet> {
@@ -148,16 +205,19 @@ impl ExecutionPlan for ParquetSinkExec {
     }
 
     fn statistics(&self) -> Result<Statistics> {
-        todo!()
+        // outputs one row for each written file
+        Ok(Statistics::new_unknown(&self.schema()))
     }
 }
 
 struct ParquetSinkContext {
     fs_provider: FsProvider,
     hive_schema: SchemaRef,
+    parquet_schema: SchemaDescPtr,
     num_dyn_parts: usize,
     row_group_block_size: usize,
-    props: WriterProperties,
+    max_records_per_file: usize,
+    props: WriterPropertiesPtr,
 }
 
 impl ParquetSinkContext {
@@ -172,136 +232,420 @@ impl ParquetSinkContext {
             let fs = jni_call_static!(JniBridge.getResource(resource_id.as_obj()) -> JObject)?;
             FsProvider::new(jni_new_global_ref!(fs.as_obj())?, io_time)
         };
+        let get_prop = |name: &str| {
+            props
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
 
         // parse hive schema from props
-        let hive_schema = match props
-            .iter()
-            .find(|(key, _)| key == "parquet.hive.schema")
-            .map(|(_, value)| value)
-            .and_then(|value| parse_message_type(value.as_str()).ok())
+        let hive_schema = match get_prop("parquet.hive.schema")
+            .and_then(|value| parse_message_type(value).ok())
             .and_then(|tp| parquet_to_arrow_schema(&SchemaDescriptor::new(Arc::new(tp)), None).ok())
-            .map(Arc::new)
         {
             Some(hive_schema) => hive_schema,
             _ => df_execution_err!("missing parquet.hive.schema")?,
         };
 
+        // timestamps are written as INT96 or INT64 like spark's outputTimestampType
+        let timestamp_type = get_prop("spark.sql.parquet.outputTimestampType").unwrap_or("INT96");
+        let timestamp_unit = match timestamp_type.to_ascii_uppercase().as_ref() {
+            "TIMESTAMP_MILLIS" => TimeUnit::Millisecond,
+            "INT96" | "TIMESTAMP_MICROS" => TimeUnit::Microsecond,
+            _ => df_execution_err!("unsupported parquet output timestamp type: {timestamp_type}")?,
+        };
+        let hive_schema = Arc::new(Schema::new(
+            hive_schema
+                .fields()
+                .iter()
+                .map(|field| adapt_timestamp_field(field, timestamp_unit))
+                .collect::<Vec<_>>(),
+        ));
+        let parquet_schema = Arc::new(to_parquet_schema(
+            &hive_schema,
+            timestamp_type.eq_ignore_ascii_case("INT96"),
+        )?);
+
         // parse row group byte size from props
-        let row_group_block_size = props
-            .iter()
-            .find(|(key, _)| key == "parquet.block.size")
-            .and_then(|(_, value)| value.parse::<usize>().ok())
+        let row_group_block_size = get_prop("parquet.block.size")
+            .and_then(|value| value.parse::<usize>().ok())
             .unwrap_or(128 * 1024 * 1024);
 
+        // non-positive values mean no limit
+        let max_records_per_file = get_prop("spark.sql.files.maxRecordsPerFile")
+            .and_then(|value| value.parse::<i64>().ok())
+            .filter(|&value| value > 0)
+            .map(|value| value as usize)
+            .unwrap_or(usize::MAX);
+
+        // int96 values have no defined sort order, so statistics are not written
+        let mut props_builder = parse_writer_props(props);
+        for column in parquet_schema.columns() {
+            if column.physical_type() == PhysicalType::INT96 {
+                props_builder = props_builder
+                    .set_column_statistics_enabled(column.path().clone(), EnabledStatistics::None);
+            }
+        }
+
         Ok(Self {
             fs_provider,
             hive_schema,
+            parquet_schema,
             num_dyn_parts,
             row_group_block_size,
-            props: parse_writer_props(props),
+            max_records_per_file,
+            props: Arc::new(props_builder.build()),
         })
     }
 }
 
+fn adapt_timestamp_field(field: &FieldRef, unit: TimeUnit) -> FieldRef {
+    let data_type = match field.data_type() {
+        DataType::Timestamp(..) => DataType::Timestamp(unit, Some("UTC".into())),
+        DataType::List(item) => DataType::List(adapt_timestamp_field(item, unit)),
+        DataType::Map(entries, sorted) => {
+            DataType::Map(adapt_timestamp_field(entries, unit), *sorted)
+        }
+        DataType::Struct(fields) => DataType::Struct(
+            fields
+                .iter()
+                .map(|field| adapt_timestamp_field(field, unit))
+                .collect(),
+        ),
+        other => other.clone(),
+    };
+    Arc::new(field.as_ref().clone().with_data_type(data_type))
+}
+
+/// converts the arrow schema to parquet schema, top-level timestamp columns are
+/// converted to INT96 if `int96_timestamps` is set (nested timestamps are
+/// always written as INT64)
+fn to_parquet_schema(schema: &Schema, int96_timestamps: bool) -> Result<SchemaDescriptor> {
+    let parquet_schema = arrow_to_parquet_schema(schema)?;
+    if !int96_timestamps {
+        return Ok(parquet_schema);
+    }
+    let root = parquet_schema.root_schema();
+    let fields = root
+        .get_fields()
+        .iter()
+        .zip(schema.fields())
+        .map(|(parquet_field, field)| {
+            if !matches!(field.data_type(), DataType::Timestamp(..)) {
+                return Ok(parquet_field.clone());
+            }
+            let basic_info = parquet_field.get_basic_info();
+            Ok(Arc::new(
+                Type::primitive_type_builder(basic_info.name(), PhysicalType::INT96)
+                    .with_repetition(basic_info.repetition())
+                    .build()?,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let root = Type::group_type_builder(root.name())
+        .with_fields(fields)
+        .build()?;
+    Ok(SchemaDescriptor::new(Arc::new(root)))
+}
+
 fn execute_parquet_sink(
     parquet_sink_context: Arc<ParquetSinkContext>,
-    mut input: SendableRecordBatchStream,
+    input: SendableRecordBatchStream,
     exec_ctx: Arc<ExecutionContext>,
+    bucket_columns: Vec<usize>,
+    num_buckets: usize,
+    max_concurrent_writers: usize,
 ) -> Result<SendableRecordBatchStream> {
-    let part_writer: Arc<Mutex<Option<PartWriter>>> = Arc::default();
-    let bytes_written = exec_ctx.register_counter_metric("bytes_written");
+    let input_schema = input.schema();
+    let num_input_columns = input_schema.fields().len();
+    let num_dyn_parts = parquet_sink_context.num_dyn_parts;
+
+    // rows are written to part writers keyed by dynamic partition values and
+    // bucket id, bucket ids are computed and appended to the input columns
+    let mut key_columns: Vec<usize> =
+        (num_input_columns - num_dyn_parts..num_input_columns).collect();
+    let mut input = input;
+    if num_buckets > 0 {
+        let mut fields = input_schema.fields().to_vec();
+        fields.push(Arc::new(Field::new("__bucket_id", DataType::Int32, false)));
+        let schema = Arc::new(Schema::new(fields));
+        key_columns.push(num_input_columns);
+        input = Box::pin(RecordBatchStreamAdapter::new(
+            schema.clone(),
+            input
+                .map(move |batch| append_bucket_ids(batch?, &schema, &bucket_columns, num_buckets)),
+        ));
+    }
+    let key_converter = RowConverter::new(
+        key_columns
+            .iter()
+            .map(|&idx| SortField::new(input.schema().field(idx).data_type().clone()))
+            .collect(),
+    )?;
 
     Ok(exec_ctx
         .clone()
         .output_with_sender("ParquetSink", move |sender| async move {
-            macro_rules! part_writer_init {
-                ($batch:expr, $part_values:expr) => {{
-                    log::info!("starts writing partition: {:?}", $part_values);
-                    let parquet_sink_context_cloned = parquet_sink_context.clone();
-                    *part_writer.lock() = Some({
-                        // send identity batch, after that we can achieve a new output file
-                        sender.send(($batch.slice(0, 1))).await;
-                        tokio::task::spawn_blocking(move || {
-                            PartWriter::try_new(parquet_sink_context_cloned, $part_values)
-                        })
-                        .await
-                        .or_else(|e| df_execution_err!("closing parquet file error: {e}"))??
-                    });
-                }};
-            }
-            macro_rules! part_writer_close {
-            () => {{
-                let maybe_writer = part_writer.lock().take();
-                if let Some(w) = maybe_writer {
-                    let file_stat = tokio::task::spawn_blocking(move || w.close())
-                        .await
-                        .or_else(|e| df_execution_err!("closing parquet file error: {e}"))??;
-                    jni_call_static!(
-                        BlazeNativeParquetSinkUtils.completeOutput(
-                            jni_new_string!(&file_stat.path)?.as_obj(),
-                            file_stat.num_rows as i64,
-                            file_stat.num_bytes as i64,
-                        ) -> ()
-                    )?;
-                    exec_ctx.baseline_metrics().output_rows().add(file_stat.num_rows);
-                    bytes_written.add(file_stat.num_bytes);
-                }
-            }}
-        }
+            let mut writers = PartWriters {
+                parquet_sink_context,
+                exec_ctx: exec_ctx.clone(),
+                sender,
+                bytes_written: exec_ctx.register_counter_metric("bytes_written"),
+                num_input_columns,
+                concurrent_writers: HashMap::new(),
+                sorted_writer: None,
+            };
+            let mut sorted = max_concurrent_writers == 0;
 
-            // write parquet data
-            while let Some(mut batch) = input.next().await.transpose()? {
-                let _timer = exec_ctx.baseline_metrics().elapsed_compute().timer();
-                if batch.num_rows() == 0 {
-                    continue;
-                }
-
-                while batch.num_rows() > 0 {
-                    let part_values =
-                        get_dyn_part_values(&batch, parquet_sink_context.num_dyn_parts, 0)?;
-                    let part_writer_outdated =
-                        part_writer.lock().as_ref().map(|w| &w.part_values) != Some(&part_values);
+            loop {
+                // rows left after the number of concurrent writers reaches the
+                // limit, which are sorted with the remaining input
+                let mut unwritten_batches = vec![];
 
-                    if part_writer_outdated {
-                        part_writer_close!();
-                        part_writer_init!(batch, &part_values);
+                while let Some(batch) = input.next().await.transpose()? {
+                    let _timer = exec_ctx.baseline_metrics().elapsed_compute().timer();
+                    if batch.num_rows() == 0 {
                         continue;
                     }
 
-                    // compute sub batch size
-                    let batch_mem_size = batch.get_array_mem_size();
-                    let num_sub_batches = (batch_mem_size / 1048576).max(1);
-                    let num_sub_batch_rows = (batch.num_rows() / num_sub_batches).max(16);
-
-                    // split batch into current part and rest parts, then write current part
-                    let m = rfind_part_values(&batch, &part_values)?;
-                    let cur_batch = batch.slice(0, m);
-                    batch = batch.slice(m, batch.num_rows() - m);
-
-                    // write cur batch
-                    let cur_batch = adapt_schema(&cur_batch, &parquet_sink_context.hive_schema)?;
-                    let mut offset = 0;
-                    while offset < cur_batch.num_rows() {
-                        let part_writer = part_writer.clone();
-                        let sub_batch_size = num_sub_batch_rows.min(cur_batch.num_rows() - offset);
-                        let sub_batch = cur_batch.slice(offset, sub_batch_size);
-                        offset += sub_batch_size;
-
-                        tokio::task::spawn_blocking(move || {
-                            let mut part_writer = part_writer.lock();
-                            let w = part_writer.as_mut().unwrap();
-                            w.write(&sub_batch)
-                        })
-                        .await
-                        .or_else(|e| df_execution_err!("writing parquet file error: {e}"))??;
+                    for (key, rows) in group_rows_by_key(&batch, &key_columns, &key_converter)? {
+                        if !unwritten_batches.is_empty() {
+                            unwritten_batches.push(rows);
+                            continue;
+                        }
+                        let part_writer = match writers.get(&key) {
+                            Some(part_writer) => part_writer,
+                            None if sorted => {
+                                // input is sorted, so the previous key never occurs again
+                                if let Some((_, part_writer)) = writers.sorted_writer.take() {
+                                    writers.close(part_writer).await?;
+                                }
+                                let part_writer = writers.open(&rows).await?;
+                                writers.sorted_writer = Some((key, part_writer.clone()));
+                                part_writer
+                            }
+                            None if writers.concurrent_writers.len() < max_concurrent_writers => {
+                                let part_writer = writers.open(&rows).await?;
+                                writers.concurrent_writers.insert(key, part_writer.clone());
+                                part_writer
+                            }
+                            None => {
+                                log::info!(
+                                    "number of concurrent writers reaches limit \
+                                    {max_concurrent_writers}, sorting the remaining input"
+                                );
+                                unwritten_batches.push(rows);
+                                continue;
+                            }
+                        };
+                        writers.write(part_writer, rows).await?;
+                    }
+                    if !unwritten_batches.is_empty() {
+                        break;
                     }
                 }
+                if unwritten_batches.is_empty() {
+                    break;
+                }
+
+                // fall back to sort-based writing, opened concurrent writers are
+                // still used for their keys
+                let schema = input.schema();
+                let remaining = Box::pin(RecordBatchStreamAdapter::new(
+                    schema.clone(),
+                    stream::iter(unwritten_batches.into_iter().map(Ok)).chain(input),
+                ));
+                let sort_exprs = key_columns
+                    .iter()
+                    .map(|&idx| PhysicalSortExpr {
+                        expr: Arc::new(Column::new(schema.field(idx).name(), idx)),
+                        options: SortOptions::default(),
+                    })
+                    .collect();
+                input = SortExec::new(
+                    Arc::new(RecordBatchStreamExec::new(remaining)),
+                    sort_exprs,
+                    None,
+                )
+                .execute(exec_ctx.partition_id(), exec_ctx.task_ctx())?;
+                sorted = true;
             }
-            part_writer_close!();
-            Ok(())
+            writers.close_all().await
         }))
 }
 
+/// appends bucket ids evaluated like spark's `pmod(murmur3_hash(cols), n)`
+fn append_bucket_ids(
+    batch: RecordBatch,
+    schema: &SchemaRef,
+    bucket_columns: &[usize],
+    num_buckets: usize,
+) -> Result<RecordBatch> {
+    let num_rows = batch.num_rows();
+    let bucket_arrays = bucket_columns
+        .iter()
+        .map(|&idx| batch.column(idx).clone())
+        .collect::<Vec<_>>();
+    let bucket_ids = create_murmur3_hashes(num_rows, &bucket_arrays, 42)
+        .into_iter()
+        .map(|hash| hash.rem_euclid(num_buckets as i32))
+        .collect::<Vec<_>>();
+
+    let mut columns = batch.columns().to_vec();
+    columns.push(Arc::new(Int32Array::from(bucket_ids)));
+    Ok(RecordBatch::try_new_with_options(
+        schema.clone(),
+        columns,
+        &RecordBatchOptions::new().with_row_count(Some(num_rows)),
+    )?)
+}
+
+/// splits a batch into rows of each key, in the order of first occurrence
+fn group_rows_by_key(
+    batch: &RecordBatch,
+    key_columns: &[usize],
+    key_converter: &RowConverter,
+) -> Result<Vec<(Box<[u8]>, RecordBatch)>> {
+    if key_columns.is_empty() {
+        return Ok(vec![(Box::default(), batch.clone())]);
+    }
+    let key_arrays = key_columns
+        .iter()
+        .map(|&idx| batch.column(idx).clone())
+        .collect::<Vec<_>>();
+    let keys = key_converter.convert_columns(&key_arrays)?;
+    let keys = keys.iter().collect::<Vec<_>>();
+
+    let mut group_indices: HashMap<&[u8], usize> = HashMap::new();
+    let mut groups: Vec<(&[u8], Vec<u32>)> = vec![];
+    for (row_idx, key) in keys.iter().enumerate() {
+        let key = key.as_ref();
+        let group_idx = *group_indices.entry(key).or_insert_with(|| {
+            groups.push((key, vec![]));
+            groups.len() - 1
+        });
+        groups[group_idx].1.push(row_idx as u32);
+    }
+
+    if groups.len() == 1 {
+        return Ok(vec![(Box::from(groups[0].0), batch.clone())]);
+    }
+    groups
+        .into_iter()
+        .map(|(key, indices)| {
+            let rows = take_batch(batch.clone(), UInt32Array::from(indices))?;
+            Ok((Box::from(key), rows))
+        })
+        .collect()
+}
+
+type SharedPartWriter = Arc<Mutex<Option<PartWriter>>>;
+
+/// Part writers opened by the sink, keyed by row-encoded dynamic partition
+/// values and bucket id.
+///
+/// with sorted input, only one writer is opened at a time. otherwise rows are
+/// written to concurrent writers of their keys, and the remaining input is
+/// sorted once the number of writers reaches the limit, like spark's
+/// concurrent output writers. every writer is opened only once, so the jvm
+/// side gets exactly one output file for each key.
+struct PartWriters {
+    parquet_sink_context: Arc<ParquetSinkContext>,
+    exec_ctx: Arc<ExecutionContext>,
+    sender: Arc<WrappedRecordBatchSender>,
+    bytes_written: Count,
+    num_input_columns: usize,
+    concurrent_writers: HashMap<Box<[u8]>, SharedPartWriter>,
+    sorted_writer: Option<(Box<[u8]>, SharedPartWriter)>,
+}
+
+impl PartWriters {
+    fn get(&self, key: &[u8]) -> Option<SharedPartWriter> {
+        if let Some(part_writer) = self.concurrent_writers.get(key) {
+            return Some(part_writer.clone());
+        }
+        self.sorted_writer
+            .as_ref()
+            .filter(|(sorted_key, _)| sorted_key.as_ref() == key)
+            .map(|(_, part_writer)| part_writer.clone())
+    }
+
+    async fn open(&self, rows: &RecordBatch) -> Result<SharedPartWriter> {
+        let num_dyn_parts = self.parquet_sink_context.num_dyn_parts;
+        let input_rows = rows.project(&(0..self.num_input_columns).collect::<Vec<_>>())?;
+        let part_values = get_dyn_part_values(&input_rows, num_dyn_parts, 0)?;
+        log::info!("starts writing partition: {part_values:?}");
+
+        // send identity row, after that we can achieve a new output file
+        self.sender.send(input_rows.slice(0, 1)).await;
+        let parquet_sink_context = self.parquet_sink_context.clone();
+        let part_writer = tokio::task::spawn_blocking(move || {
+            PartWriter::try_new(parquet_sink_context, &part_values)
+        })
+        .await
+        .or_else(|e| df_execution_err!("opening parquet file error: {e}"))??;
+        Ok(Arc::new(Mutex::new(Some(part_writer))))
+    }
+
+    async fn write(&self, part_writer: SharedPartWriter, rows: RecordBatch) -> Result<()> {
+        // compute sub batch size
+        let batch_mem_size = rows.get_array_mem_size();
+        let num_sub_batches = (batch_mem_size / 1048576).max(1);
+        let num_sub_batch_rows = (rows.num_rows() / num_sub_batches).max(16);
+
+        let rows = adapt_schema(&rows, &self.parquet_sink_context.hive_schema)?;
+        let mut offset = 0;
+        while offset < rows.num_rows() {
+            let part_writer = part_writer.clone();
+            let sub_batch_size = num_sub_batch_rows.min(rows.num_rows() - offset);
+            let sub_batch = rows.slice(offset, sub_batch_size);
+            offset += sub_batch_size;
+
+            tokio::task::spawn_blocking(move || {
+                let mut part_writer = part_writer.lock();
+                let w = part_writer.as_mut().unwrap();
+                w.write(&sub_batch)
+            })
+            .await
+            .or_else(|e| df_execution_err!("writing parquet file error: {e}"))??;
+        }
+        Ok(())
+    }
+
+    async fn close(&self, part_writer: SharedPartWriter) -> Result<()> {
+        let maybe_writer = part_writer.lock().take();
+        if let Some(w) = maybe_writer {
+            let file_stat = tokio::task::spawn_blocking(move || w.close())
+                .await
+                .or_else(|e| df_execution_err!("closing parquet file error: {e}"))??;
+            jni_call_static!(
+                BlazeNativeParquetSinkUtils.completeOutput(
+                    jni_new_string!(&file_stat.path)?.as_obj(),
+                    file_stat.num_rows as i64,
+                    file_stat.num_bytes as i64,
+                ) -> ()
+            )?;
+            self.exec_ctx
+                .baseline_metrics()
+                .output_rows()
+                .add(file_stat.num_rows);
+            self.bytes_written.add(file_stat.num_bytes);
+        }
+        Ok(())
+    }
+
+    async fn close_all(mut self) -> Result<()> {
+        if let Some((_, part_writer)) = self.sorted_writer.take() {
+            self.close(part_writer).await?;
+        }
+        for (_, part_writer) in std::mem::take(&mut self.concurrent_writers) {
+            self.close(part_writer).await?;
+        }
+        Ok(())
+    }
+}
+
 fn adapt_schema(batch: &RecordBatch, schema: &SchemaRef) -> Result<RecordBatch> {
     let num_rows = batch.num_rows();
     let mut casted_cols = vec![];
@@ -316,16 +660,7 @@ fn adapt_schema(batch: &RecordBatch, schema: &SchemaRef) -> Result<RecordBatch>
     )?)
 }
 
-fn rfind_part_values(batch: &RecordBatch, part_values: &[ScalarValue]) -> Result<usize> {
-    for row_idx in (0..batch.num_rows()).rev() {
-        if get_dyn_part_values(batch, part_values.len(), row_idx)? == part_values {
-            return Ok(row_idx + 1);
-        }
-    }
-    Ok(0)
-}
-
-fn parse_writer_props(prop_kvs: &[(String, String)]) -> WriterProperties {
+fn parse_writer_props(prop_kvs: &[(String, String)]) -> WriterPropertiesBuilder {
     let mut builder = WriterProperties::builder();
 
     macro_rules! setprop {
@@ -395,7 +730,7 @@ fn parse_writer_props(prop_kvs: &[(String, String)]) -> WriterProperties {
             _ => builder,
         }
     }
-    builder.build()
+    builder
 }
 
 #[derive(Debug)]
@@ -408,10 +743,11 @@ struct PartFileStat {
 struct PartWriter {
     path: String,
     parquet_sink_context: Arc<ParquetSinkContext>,
-    parquet_writer: ArrowWriter<FSDataWriter>,
+    file_writer: Option<ParquetFileWriter>,
+    num_files: usize,
     part_values: Vec<ScalarValue>,
-    rows_written: Count,
-    bytes_written: Count,
+    rows_written: usize,
+    bytes_written: usize,
 }
 
 impl PartWriter {
@@ -427,61 +763,237 @@ impl PartWriter {
                 .as_obj()
                 .into()
         )?;
-        log::info!("starts writing parquet file: {part_file}");
-
-        let fs = parquet_sink_context.fs_provider.provide(&part_file)?;
-        let bytes_written = Count::new();
-        let rows_written = Count::new();
-        let fout = Arc::into_inner(fs.create(&part_file)?).expect("Arc::into_inner");
-        let data_writer = FSDataWriter::new(fout, &bytes_written);
-        let parquet_writer = ArrowWriter::try_new(
-            data_writer,
-            parquet_sink_context.hive_schema.clone(),
-            Some(parquet_sink_context.props.clone()),
-        )?;
-        Ok(Self {
+
+        let mut part_writer = Self {
             path: part_file,
             parquet_sink_context,
-            parquet_writer,
+            file_writer: None,
+            num_files: 0,
             part_values: part_values.to_vec(),
-            rows_written,
-            bytes_written,
-        })
+            rows_written: 0,
+            bytes_written: 0,
+        };
+        part_writer.open_file()?;
+        Ok(part_writer)
+    }
+
+    /// opens a new file for writing, files exceeding max records are written
+    /// next to the output path given by the jvm side with a sequence suffix
+    fn open_file(&mut self) -> Result<&mut ParquetFileWriter> {
+        let path = match self.num_files {
+            0 => self.path.clone(),
+            n => format!("{}-{n:05}", self.path),
+        };
+        log::info!("starts writing parquet file: {path}");
+        self.num_files += 1;
+        Ok(self.file_writer.insert(ParquetFileWriter::try_new(
+            &self.parquet_sink_context,
+            path,
+        )?))
+    }
+
+    fn close_file(&mut self) -> Result<()> {
+        if let Some(file_writer) = self.file_writer.take() {
+            let stat = file_writer.close()?;
+            log::info!("finished writing parquet file: {stat:?}");
+            self.rows_written += stat.num_rows;
+            self.bytes_written += stat.num_bytes;
+        }
+        Ok(())
     }
 
     fn write(&mut self, batch: &RecordBatch) -> Result<()> {
-        let row_group_block_size = self.parquet_sink_context.row_group_block_size;
-        self.parquet_writer.write(&batch)?;
-        if self.parquet_writer.in_progress_size() >= row_group_block_size {
-            self.parquet_writer.flush()?;
+        let max_records_per_file = self.parquet_sink_context.max_records_per_file;
+        let mut offset = 0;
+        while offset < batch.num_rows() {
+            let file_writer = match self.file_writer.take() {
+                Some(file_writer) => self.file_writer.insert(file_writer),
+                None => self.open_file()?,
+            };
+            let num_rows =
+                (batch.num_rows() - offset).min(max_records_per_file - file_writer.num_rows);
+            file_writer.write(&batch.slice(offset, num_rows))?;
+            offset += num_rows;
+
+            if file_writer.num_rows >= max_records_per_file {
+                self.close_file()?;
+            }
         }
         Ok(())
     }
 
-    fn close(self) -> Result<PartFileStat> {
-        let mut parquet_writer = self.parquet_writer;
-        parquet_writer.flush()?;
-        let rows_written = parquet_writer
-            .flushed_row_groups()
+    /// closes the writer and returns the total stat of all written files, which
+    /// is reported as a single output file to the jvm side
+    fn close(mut self) -> Result<PartFileStat> {
+        self.close_file()?;
+        Ok(PartFileStat {
+            path: self.path,
+            num_rows: self.rows_written,
+            num_bytes: self.bytes_written,
+        })
+    }
+}
+
+/// Writes record batches into a parquet file with the parquet schema of the
+/// sink context. unlike `ArrowWriter`, INT96 timestamp columns are supported.
+struct ParquetFileWriter {
+    path: String,
+    file_writer: SerializedFileWriter<FSDataWriter>,
+    arrow_schema: SchemaRef,
+    props: WriterPropertiesPtr,
+    row_group_block_size: usize,
+    column_writers: Vec<ColumnChunkWriter>,
+    num_rows: usize,
+    num_buffered_rows: usize,
+}
+
+enum ColumnChunkWriter {
+    Arrow(ArrowColumnWriter),
+    Int96 {
+        values: Vec<Int96>,
+        def_levels: Option<Vec<i16>>,
+    },
+}
+
+impl ParquetFileWriter {
+    fn try_new(parquet_sink_context: &ParquetSinkContext, path: String) -> Result<Self> {
+        let fs = parquet_sink_context.fs_provider.provide(&path)?;
+        let fout = Arc::into_inner(fs.create(&path)?).expect("Arc::into_inner");
+        let data_writer = FSDataWriter::new(fout, &Count::new());
+        let file_writer = SerializedFileWriter::new(
+            data_writer,
+            parquet_sink_context.parquet_schema.root_schema_ptr(),
+            parquet_sink_context.props.clone(),
+        )?;
+        Ok(Self {
+            path,
+            file_writer,
+            arrow_schema: parquet_sink_context.hive_schema.clone(),
+            props: parquet_sink_context.props.clone(),
+            row_group_block_size: parquet_sink_context.row_group_block_size,
+            column_writers: vec![],
+            num_rows: 0,
+            num_buffered_rows: 0,
+        })
+    }
+
+    fn write(&mut self, batch: &RecordBatch) -> Result<()> {
+        if self.column_writers.is_empty() {
+            let parquet_schema = self.file_writer.schema_descr();
+            self.column_writers =
+                get_column_writers(parquet_schema, &self.props, &self.arrow_schema)?
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, writer)| {
+                        let column = parquet_schema.column(i);
+                        match column.physical_type() {
+                            PhysicalType::INT96 => ColumnChunkWriter::Int96 {
+                                values: vec![],
+                                def_levels: (column.max_def_level() > 0).then(Vec::new),
+                            },
+                            _ => ColumnChunkWriter::Arrow(writer),
+                        }
+                    })
+                    .collect();
+        }
+
+        let mut column_writers = self.column_writers.iter_mut();
+        for (field, column) in self.arrow_schema.fields().iter().zip(batch.columns()) {
+            for leaf in compute_leaves(field, column)? {
+                match column_writers.next().expect("missing column writer") {
+                    ColumnChunkWriter::Arrow(writer) => writer.write(&leaf)?,
+                    ColumnChunkWriter::Int96 { values, def_levels } => {
+                        // int96 columns are always top-level timestamps
+                        let timestamps = column.as_primitive::<TimestampMicrosecondType>();
+                        for timestamp in timestamps {
+                            if let Some(micros) = timestamp {
+                                values.push(micros_to_int96(micros));
+                            }
+                            if let Some(def_levels) = def_levels {
+                                def_levels.push(timestamp.is_some() as i16);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        self.num_rows += batch.num_rows();
+        self.num_buffered_rows += batch.num_rows();
+
+        let buffered_size = self
+            .column_writers
             .iter()
-            .map(|rg| rg.num_rows() as usize)
-            .sum();
-        let data_writer = parquet_writer.into_inner()?;
-        let bytes_written = data_writer.bytes_written.value();
-        data_writer.close()?;
+            .map(|writer| match writer {
+                ColumnChunkWriter::Arrow(writer) => writer.get_estimated_total_bytes(),
+                ColumnChunkWriter::Int96 { values, .. } => values.len() * 12,
+            })
+            .sum::<usize>();
+        if buffered_size >= self.row_group_block_size
+            || self.num_buffered_rows >= self.props.max_row_group_size()
+        {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if self.column_writers.is_empty() {
+            return Ok(());
+        }
+        let mut row_group_writer = self.file_writer.next_row_group()?;
+        for writer in std::mem::take(&mut self.column_writers) {
+            match writer {
+                ColumnChunkWriter::Arrow(writer) => {
+                    writer.close()?.append_to_row_group(&mut row_group_writer)?;
+                }
+                ColumnChunkWriter::Int96 { values, def_levels } => {
+                    let mut column_writer = row_group_writer
+                        .next_column()?
+                        .expect("missing int96 column writer");
+                    column_writer.typed::<Int96Type>().write_batch(
+                        &values,
+                        def_levels.as_deref(),
+                        None,
+                    )?;
+                    column_writer.close()?;
+                }
+            }
+        }
+        row_group_writer.close()?;
+        self.num_buffered_rows = 0;
+        Ok(())
+    }
 
-        self.rows_written.add(rows_written);
-        self.bytes_written.add(bytes_written);
-        let stat = PartFileStat {
+    fn close(mut self) -> Result<PartFileStat> {
+        self.flush()?;
+        let data_writer = self.file_writer.into_inner()?;
+        let num_bytes = data_writer.bytes_written.value();
+        data_writer.close()?;
+        Ok(PartFileStat {
             path: self.path,
-            num_rows: rows_written,
-            num_bytes: bytes_written,
-        };
-        log::info!("finished writing parquet file: {stat:?}");
-        Ok(stat)
+            num_rows: self.num_rows,
+            num_bytes,
+        })
     }
 }
 
+/// converts microseconds since epoch to spark's INT96 timestamp, which stores
+/// nanoseconds of the day and julian day
+fn micros_to_int96(micros: i64) -> Int96 {
+    const MICROS_PER_DAY: i64 = 86_400_000_000;
+    const JULIAN_DAY_OF_EPOCH: i64 = 2_440_588;
+
+    let julian_day = micros.div_euclid(MICROS_PER_DAY) + JULIAN_DAY_OF_EPOCH;
+    let nanos_of_day = micros.rem_euclid(MICROS_PER_DAY) * 1000;
+    let mut value = Int96::new();
+    value.set_data(
+        nanos_of_day as u32,
+        (nanos_of_day >> 32) as u32,
+        julian_day as u32,
+    );
+    value
+}
+
 fn get_dyn_part_values(
     batch: &RecordBatch,
     num_dyn_parts: usize,
@@ -526,3 +1038,60 @@ impl Write for FSDataWriter {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{ArrayRef, Int32Array, RecordBatch, StringArray},
+        datatypes::DataType,
+        row::{RowConverter, SortField},
+    };
+    use datafusion::{assert_batches_eq, common::Result};
+
+    use crate::parquet_sink_exec::{group_rows_by_key, micros_to_int96};
+
+    #[test]
+    fn test_micros_to_int96() {
+        assert_eq!(micros_to_int96(0).data(), &[0, 0, 2_440_588]);
+
+        let nanos = 86_399_999_999_000i64;
+        assert_eq!(
+            micros_to_int96(-1).data(),
+            &[nanos as u32, (nanos >> 32) as u32, 2_440_587],
+        );
+    }
+
+    #[test]
+    fn test_group_rows_by_key() -> Result<()> {
+        let batch = RecordBatch::try_from_iter([
+            (
+                "v",
+                Arc::new(Int32Array::from(vec![1, 2, 3, 4, 5])) as ArrayRef,
+            ),
+            (
+                "p",
+                Arc::new(StringArray::from(vec!["b", "a", "b", "c", "a"])) as ArrayRef,
+            ),
+        ])?;
+        let key_converter = RowConverter::new(vec![SortField::new(DataType::Utf8)])?;
+        let groups = group_rows_by_key(&batch, &[1], &key_converter)?;
+        let groups = groups.into_iter().map(|(_, rows)| rows).collect::<Vec<_>>();
+        assert_batches_eq!(
+            vec![
+                "+---+---+",
+                "| v | p |",
+                "+---+---+",
+                "| 1 | b |",
+                "| 3 | b |",
+                "| 2 | a |",
+                "| 5 | a |",
+                "| 4 | c |",
+                "+---+---+",
+            ],
+            &groups
+        );
+        Ok(())
+    }
+}
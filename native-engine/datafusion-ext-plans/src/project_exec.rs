@@ -28,7 +28,6 @@ use datafusion::{
         PlanProperties, SendableRecordBatchStream,
     },
 };
-use datafusion_ext_commons::downcast_any;
 use futures::StreamExt;
 use itertools::Itertools;
 use once_cell::sync::OnceCell;
@@ -38,6 +37,9 @@ use crate::{
         cached_exprs_evaluator::CachedExprsEvaluator,
         column_pruning::{prune_columns, ExecuteWithColumnPruning},
         execution_context::ExecutionContext,
+        partitioning::derive_projected_partitioning,
+        runtime_filter::RuntimeFilterPruner,
+        statistics::project_statistics,
         timer_helper::TimerHelper,
     },
     filter_exec::FilterExec,
@@ -110,7 +112,7 @@ impl ExecutionPlan for ProjectExec {
         self.props.get_or_init(|| {
             PlanProperties::new(
                 EquivalenceProperties::new(self.schema()),
-                self.input.output_partitioning().clone(),
+                derive_projected_partitioning(self.input.output_partitioning(), &self.expr),
                 ExecutionMode::Bounded,
             )
         })
@@ -135,19 +137,34 @@ impl ExecutionPlan for ProjectExec {
         partition: usize,
         context: Arc<TaskContext>,
     ) -> Result<SendableRecordBatchStream> {
-        let exec_ctx = ExecutionContext::new(context, partition, self.schema(), &self.metrics);
+        let exec_ctx =
+            ExecutionContext::new(context.clone(), partition, self.schema(), &self.metrics);
         let exprs: Vec<PhysicalExprRef> = self.expr.iter().map(|(e, _name)| e.clone()).collect();
 
-        let output = if let Ok(filter_exec) = downcast_any!(self.input, FilterExec) {
-            execute_project_with_filtering(
-                filter_exec.children()[0].clone(),
-                exec_ctx.clone(),
-                filter_exec.predicates().to_vec(),
-                exprs,
-            )?
-        } else {
-            execute_project_with_filtering(self.input.clone(), exec_ctx.clone(), vec![], exprs)?
-        };
+        // fuse all filters directly under the projection, so that predicates and
+        // projection exprs share one evaluator. predicates of lower filters are
+        // evaluated first.
+        let mut input = self.input.clone();
+        let mut filters = vec![];
+        let mut runtime_filters = vec![];
+        let mut filter_exec_ctx = None;
+        while let Some(filter_exec) = input.as_any().downcast_ref::<FilterExec>() {
+            filter_exec_ctx.get_or_insert_with(|| {
+                filter_exec.fused_execution_context(partition, context.clone())
+            });
+            filters.splice(0..0, filter_exec.predicates().iter().cloned());
+            runtime_filters.splice(0..0, filter_exec.runtime_filters().iter().cloned());
+            let filter_input = filter_exec.children()[0].clone();
+            input = filter_input;
+        }
+        let output = execute_project_with_filtering(
+            input,
+            exec_ctx.clone(),
+            filter_exec_ctx,
+            filters,
+            runtime_filters,
+            exprs,
+        )?;
         Ok(exec_ctx.coalesce_with_default_batch_size(output))
     }
 
@@ -156,7 +173,7 @@ impl ExecutionPlan for ProjectExec {
     }
 
     fn statistics(&self) -> Result<Statistics> {
-        todo!()
+        Ok(project_statistics(self.input.statistics()?, &self.schema))
     }
 }
 
@@ -178,15 +195,24 @@ impl ExecuteWithColumnPruning for ProjectExec {
     }
 }
 
+/// executes the projection with fused filters. `filter_exec_ctx` is the
+/// context of the topmost fused filter, on which input/output rows of the
+/// filters and the runtime filter pruned rows are recorded.
 fn execute_project_with_filtering(
     input: Arc<dyn ExecutionPlan>,
     exec_ctx: Arc<ExecutionContext>,
+    filter_exec_ctx: Option<Arc<ExecutionContext>>,
     filters: Vec<PhysicalExprRef>,
+    runtime_filters: Vec<(PhysicalExprRef, String)>,
     exprs: Vec<PhysicalExprRef>,
 ) -> Result<SendableRecordBatchStream> {
     // execute input with pruning
     let num_exprs = exprs.len();
-    let (pruned_exprs, projection) = prune_columns(&[exprs, filters].concat())?;
+    let num_filters = filters.len();
+    let (runtime_filter_keys, runtime_filter_ids): (Vec<PhysicalExprRef>, Vec<String>) =
+        runtime_filters.into_iter().unzip();
+    let (pruned_exprs, projection) =
+        prune_columns(&[exprs, filters, runtime_filter_keys].concat())?;
     let exprs = pruned_exprs
         .iter()
         .take(num_exprs)
@@ -195,14 +221,30 @@ fn execute_project_with_filtering(
     let filters = pruned_exprs
         .iter()
         .skip(num_exprs)
+        .take(num_filters)
         .cloned()
         .collect::<Vec<PhysicalExprRef>>();
+    let runtime_filter_pruner = (!runtime_filter_ids.is_empty()).then(|| {
+        let runtime_filters = pruned_exprs
+            .iter()
+            .skip(num_exprs + num_filters)
+            .cloned()
+            .zip(runtime_filter_ids)
+            .collect();
+        RuntimeFilterPruner::new(
+            runtime_filters,
+            filter_exec_ctx.as_ref().unwrap_or(&exec_ctx),
+        )
+    });
+    let filter_metrics = filter_exec_ctx.map(|filter_exec_ctx| {
+        let input_rows = filter_exec_ctx.register_counter_metric("filter_input_rows");
+        (input_rows, filter_exec_ctx)
+    });
 
-    let cached_expr_evaluator = Arc::new(CachedExprsEvaluator::try_new(
-        filters,
-        exprs,
-        exec_ctx.output_schema(),
-    )?);
+    let cached_expr_evaluator = Arc::new(
+        CachedExprsEvaluator::try_new(filters, exprs, exec_ctx.output_schema())?
+            .with_expr_metrics(&exec_ctx)?,
+    );
 
     let mut input = exec_ctx.execute_projected_with_input_stats(&input, &projection)?;
     Ok(exec_ctx
@@ -217,7 +259,20 @@ fn execute_project_with_filtering(
                 .await
                 .transpose()?
             {
+                if let Some((input_rows, _)) = &filter_metrics {
+                    input_rows.add(batch.num_rows());
+                }
+                if let Some(pruner) = &runtime_filter_pruner {
+                    if pruner.can_prune(&batch)? {
+                        continue;
+                    }
+                }
                 let output_batch = cached_expr_evaluator.filter_project(&batch)?;
+                if let Some((_, filter_exec_ctx)) = &filter_metrics {
+                    filter_exec_ctx
+                        .baseline_metrics()
+                        .record_output(output_batch.num_rows());
+                }
                 exec_ctx
                     .baseline_metrics()
                     .record_output(output_batch.num_rows());
@@ -226,3 +281,147 @@ fn execute_project_with_filtering(
             Ok(())
         }))
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{ArrayRef, Int32Array},
+        datatypes::{DataType, Field, Schema},
+        record_batch::RecordBatch,
+    };
+    use datafusion::{
+        assert_batches_eq,
+        common::Result,
+        logical_expr::Operator,
+        physical_expr::{
+            expressions::{binary, col, lit},
+            PhysicalExprRef,
+        },
+        physical_plan::{common, memory::MemoryExec, projection::ProjectionExec, ExecutionPlan},
+        prelude::SessionContext,
+    };
+
+    use crate::{
+        common::runtime_filter::publish_runtime_filters, filter_exec::FilterExec,
+        project_exec::ProjectExec,
+    };
+
+    fn build_batch(a: Vec<i32>, b: Vec<i32>, c: Vec<i32>) -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, false),
+            Field::new("b", DataType::Int32, false),
+            Field::new("c", DataType::Int32, false),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from(a)),
+                Arc::new(Int32Array::from(b)),
+                Arc::new(Int32Array::from(c)),
+            ],
+        )
+        .unwrap()
+    }
+
+    // filter(b != 7) <- filter(a >= 1, runtime filter on a) <- memory
+    fn build_filters() -> Result<Arc<FilterExec>> {
+        let batches = vec![
+            build_batch(
+                vec![0, 1, 2, 3, 4],
+                vec![9, 8, 7, 6, 5],
+                vec![0, 10, 20, 30, 40],
+            ),
+            build_batch(
+                vec![5, 6, 7, 8, 9],
+                vec![4, 3, 2, 1, 0],
+                vec![50, 60, 70, 80, 90],
+            ),
+        ];
+        let schema = batches[0].schema();
+        let input = Arc::new(MemoryExec::try_new(&[batches], schema.clone(), None)?);
+        let lower_filter = FilterExec::try_new(
+            vec![binary(col("a", &schema)?, Operator::GtEq, lit(1), &schema)?],
+            input,
+        )?
+        .with_runtime_filters(vec![(col("a", &schema)?, "rf-project-fused".to_string())]);
+        let upper_filter = FilterExec::try_new(
+            vec![binary(
+                col("b", &schema)?,
+                Operator::NotEq,
+                lit(7),
+                &schema,
+            )?],
+            Arc::new(lower_filter),
+        )?;
+        Ok(Arc::new(upper_filter))
+    }
+
+    fn build_exprs(filters: &FilterExec) -> Result<Vec<(PhysicalExprRef, String)>> {
+        let schema = filters.schema();
+        Ok(vec![
+            (col("c", &schema)?, "c".to_string()),
+            (
+                binary(
+                    col("a", &schema)?,
+                    Operator::Plus,
+                    col("c", &schema)?,
+                    &schema,
+                )?,
+                "a_plus_c".to_string(),
+            ),
+        ])
+    }
+
+    #[tokio::test]
+    async fn test_project_with_fused_filters() -> Result<()> {
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+
+        // publish a runtime filter of keys in [0, 4], the second batch is pruned
+        let keys: ArrayRef = Arc::new(Int32Array::from(vec![0, 4]));
+        let _runtime_filters =
+            publish_runtime_filters(&[Some("rf-project-fused".to_string())], &[keys], 0)?;
+
+        let fused_filters = build_filters()?;
+        let fused = ProjectExec::try_new(build_exprs(&fused_filters)?, fused_filters.clone())?;
+        let fused_output = common::collect(fused.execute(0, task_ctx.clone())?).await?;
+
+        let unfused_filters = build_filters()?;
+        let unfused =
+            ProjectionExec::try_new(build_exprs(&unfused_filters)?, unfused_filters.clone())?;
+        let unfused_output = common::collect(unfused.execute(0, task_ctx.clone())?).await?;
+
+        let expected = vec![
+            "+----+----------+",
+            "| c  | a_plus_c |",
+            "+----+----------+",
+            "| 10 | 11       |",
+            "| 30 | 33       |",
+            "| 40 | 44       |",
+            "+----+----------+",
+        ];
+        assert_batches_eq!(expected, &fused_output);
+        assert_batches_eq!(expected, &unfused_output);
+
+        // metrics of the fused filters are recorded on the topmost filter
+        let fused_metrics = fused_filters.metrics().unwrap();
+        let unfused_metrics = unfused_filters.metrics().unwrap();
+        assert_eq!(fused_metrics.output_rows(), Some(3));
+        assert_eq!(unfused_metrics.output_rows(), Some(3));
+        assert_eq!(
+            fused_metrics
+                .sum_by_name("filter_input_rows")
+                .map(|v| v.as_usize()),
+            Some(10),
+        );
+        assert_eq!(
+            fused_metrics
+                .sum_by_name("runtime_filter_pruned_rows")
+                .map(|v| v.as_usize()),
+            Some(5),
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,317 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{any::Any, fmt::Formatter, sync::Arc};
+
+use arrow::{array::Int64Array, datatypes::SchemaRef, record_batch::RecordBatch};
+use datafusion::{
+    common::{stats::Precision, Result, Statistics},
+    execution::context::TaskContext,
+    physical_expr::EquivalenceProperties,
+    physical_plan::{
+        metrics::{ExecutionPlanMetricsSet, MetricsSet},
+        DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, Partitioning, PlanProperties,
+        SendableRecordBatchStream,
+    },
+};
+use datafusion_ext_commons::{batch_size, df_execution_err};
+use once_cell::sync::OnceCell;
+
+use crate::common::execution_context::ExecutionContext;
+
+/// Generates int64 values in [start, end) with the given step, which is the
+/// native implementation of spark's RangeExec (`spark.range()`). values are
+/// split into `num_slices` partitions exactly the same way as spark does.
+#[derive(Debug)]
+pub struct RangeExec {
+    start: i64,
+    end: i64,
+    step: i64,
+    num_slices: usize,
+    schema: SchemaRef,
+    metrics: ExecutionPlanMetricsSet,
+    props: OnceCell<PlanProperties>,
+}
+
+impl RangeExec {
+    pub fn try_new(
+        start: i64,
+        end: i64,
+        step: i64,
+        num_slices: usize,
+        schema: SchemaRef,
+    ) -> Result<Self> {
+        if step == 0 {
+            return df_execution_err!("RangeExec: step cannot be 0");
+        }
+        if num_slices == 0 {
+            return df_execution_err!("RangeExec: num_slices cannot be 0");
+        }
+        Ok(Self {
+            start,
+            end,
+            step,
+            num_slices,
+            schema,
+            metrics: ExecutionPlanMetricsSet::new(),
+            props: OnceCell::new(),
+        })
+    }
+
+    /// returns the [start, end) bound of the partition, computed in the same
+    /// way as spark's RangeExec, bounds are clamped into the range of i64
+    fn partition_bounds(&self, partition: usize) -> (i64, i64) {
+        let start = self.start as i128;
+        let end = self.end as i128;
+        let step = self.step as i128;
+        let num_slices = self.num_slices as i128;
+        let num_elements = if (end - start) % step == 0 || (end > start) != (step > 0) {
+            (end - start) / step
+        } else {
+            // the remainder has the same sign with range, could add 1 more
+            (end - start) / step + 1
+        };
+
+        let i = partition as i128;
+        let partition_start = i * num_elements / num_slices * step + start;
+        let partition_end = (i + 1) * num_elements / num_slices * step + start;
+        let safe = |v: i128| v.clamp(i64::MIN as i128, i64::MAX as i128) as i64;
+        (safe(partition_start), safe(partition_end))
+    }
+
+    /// returns number of values of all partitions
+    fn num_rows(&self) -> usize {
+        (0..self.num_slices)
+            .map(|partition| {
+                let (start, end) = self.partition_bounds(partition);
+                let (len, step) = (end as i128 - start as i128, self.step as i128);
+                if len == 0 || (len > 0) != (step > 0) {
+                    return 0;
+                }
+                (len / step + (len % step != 0) as i128) as usize
+            })
+            .sum()
+    }
+}
+
+impl DisplayAs for RangeExec {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "RangeExec(start={}, end={}, step={}, num_slices={})",
+            self.start, self.end, self.step, self.num_slices
+        )
+    }
+}
+
+impl ExecutionPlan for RangeExec {
+    fn name(&self) -> &str {
+        "RangeExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.props.get_or_init(|| {
+            PlanProperties::new(
+                EquivalenceProperties::new(self.schema()),
+                Partitioning::UnknownPartitioning(self.num_slices),
+                ExecutionMode::Bounded,
+            )
+        })
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        _children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(self)
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        if partition >= self.num_slices {
+            return df_execution_err!(
+                "RangeExec: invalid partition {partition}, num_slices={}",
+                self.num_slices
+            );
+        }
+        let exec_ctx = ExecutionContext::new(context, partition, self.schema(), &self.metrics);
+        let (partition_start, partition_end) = self.partition_bounds(partition);
+        let step = self.step;
+
+        Ok(exec_ctx
+            .clone()
+            .output_with_sender("Range", move |sender| async move {
+                sender.exclude_time(exec_ctx.baseline_metrics().elapsed_compute());
+                let batch_size = batch_size();
+                let in_range = |v: i64| {
+                    if step > 0 {
+                        v < partition_end
+                    } else {
+                        v > partition_end
+                    }
+                };
+
+                let mut next = Some(partition_start).filter(|&v| in_range(v));
+                while let Some(batch_start) = next {
+                    let _timer = exec_ctx.baseline_metrics().elapsed_compute().timer();
+                    let mut values = Vec::with_capacity(batch_size);
+                    let mut cur = Some(batch_start);
+                    while let Some(v) = cur.filter(|&v| in_range(v)) {
+                        if values.len() >= batch_size {
+                            break;
+                        }
+                        values.push(v);
+                        cur = v.checked_add(step); // stop on overflow
+                    }
+                    next = cur.filter(|&v| in_range(v));
+
+                    let output_batch = RecordBatch::try_new(
+                        exec_ctx.output_schema(),
+                        vec![Arc::new(Int64Array::from(values))],
+                    )?;
+                    exec_ctx
+                        .baseline_metrics()
+                        .record_output(output_batch.num_rows());
+                    sender.send(output_batch).await;
+                }
+                Ok(())
+            }))
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        let num_rows = self.num_rows();
+        Ok(Statistics {
+            num_rows: Precision::Exact(num_rows),
+            total_byte_size: Precision::Exact(num_rows * size_of::<i64>()),
+            column_statistics: Statistics::unknown_column(&self.schema),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::AsArray,
+        datatypes::{DataType, Field, Int64Type, Schema},
+    };
+    use datafusion::{
+        common::{stats::Precision, Result},
+        physical_plan::{common, ExecutionPlan},
+        prelude::SessionContext,
+    };
+
+    use crate::range_exec::RangeExec;
+
+    async fn collect_range(
+        start: i64,
+        end: i64,
+        step: i64,
+        num_slices: usize,
+    ) -> Result<Vec<Vec<i64>>> {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let range = RangeExec::try_new(start, end, step, num_slices, schema)?;
+        let session_ctx = SessionContext::new();
+        let mut partitions = vec![];
+        for partition in 0..num_slices {
+            let output = range.execute(partition, session_ctx.task_ctx())?;
+            let values = common::collect(output)
+                .await?
+                .iter()
+                .flat_map(|batch| {
+                    batch
+                        .column(0)
+                        .as_primitive::<Int64Type>()
+                        .values()
+                        .to_vec()
+                })
+                .collect();
+            partitions.push(values);
+        }
+        Ok(partitions)
+    }
+
+    #[tokio::test]
+    async fn test_range() -> Result<()> {
+        // spark.range(0, 10, 3, 3)
+        assert_eq!(
+            collect_range(0, 10, 3, 3).await?,
+            vec![vec![0], vec![3], vec![6, 9]],
+        );
+
+        // spark.range(10, 0, -4, 2)
+        assert_eq!(
+            collect_range(10, 0, -4, 2).await?,
+            vec![vec![10], vec![6, 2]]
+        );
+
+        // empty ranges
+        assert_eq!(collect_range(0, 10, -1, 2).await?, vec![vec![], vec![]]);
+        assert_eq!(collect_range(5, 5, 1, 1).await?, vec![Vec::<i64>::new()]);
+
+        // near the bound of i64
+        assert_eq!(
+            collect_range(i64::MAX - 2, i64::MAX, 1, 2).await?,
+            vec![vec![i64::MAX - 2], vec![i64::MAX - 1]],
+        );
+        assert_eq!(
+            collect_range(i64::MIN, i64::MAX, i64::MAX, 1).await?,
+            vec![vec![i64::MIN, -1, i64::MAX - 1]],
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_range_statistics() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        for (start, end, step, num_slices) in [
+            (0, 10, 3, 3),
+            (10, 0, -4, 2),
+            (0, 10, -1, 2),
+            (5, 5, 1, 1),
+            (i64::MIN, i64::MAX, i64::MAX, 1),
+        ] {
+            let range = RangeExec::try_new(start, end, step, num_slices, schema.clone())?;
+            let num_rows = collect_range(start, end, step, num_slices)
+                .await?
+                .iter()
+                .map(|values| values.len())
+                .sum::<usize>();
+            let stats = range.statistics()?;
+            assert_eq!(stats.num_rows, Precision::Exact(num_rows));
+            assert_eq!(stats.total_byte_size, Precision::Exact(num_rows * 8));
+        }
+        Ok(())
+    }
+}
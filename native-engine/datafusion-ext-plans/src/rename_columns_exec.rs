@@ -22,7 +22,7 @@ use async_trait::async_trait;
 use datafusion::{
     error::Result,
     execution::context::TaskContext,
-    physical_expr::EquivalenceProperties,
+    physical_expr::{expressions::Column, EquivalenceProperties, PhysicalExprRef},
     physical_plan::{
         metrics::{ExecutionPlanMetricsSet, MetricsSet},
         stream::RecordBatchStreamAdapter,
@@ -33,7 +33,10 @@ use datafusion::{
 use futures::StreamExt;
 use once_cell::sync::OnceCell;
 
-use crate::{agg::AGG_BUF_COLUMN_NAME, common::execution_context::ExecutionContext};
+use crate::{
+    agg::AGG_BUF_COLUMN_NAME,
+    common::{execution_context::ExecutionContext, partitioning::derive_projected_partitioning},
+};
 
 #[derive(Debug, Clone)]
 pub struct RenameColumnsExec {
@@ -114,7 +117,21 @@ impl ExecutionPlan for RenameColumnsExec {
         self.props.get_or_init(|| {
             PlanProperties::new(
                 EquivalenceProperties::new(self.schema()),
-                self.input.output_partitioning().clone(),
+                derive_projected_partitioning(
+                    self.input.output_partitioning(),
+                    &self
+                        .input
+                        .schema()
+                        .fields()
+                        .iter()
+                        .zip(self.renamed_schema.fields())
+                        .enumerate()
+                        .map(|(i, (field, renamed_field))| {
+                            let col: PhysicalExprRef = Arc::new(Column::new(field.name(), i));
+                            (col, renamed_field.name().clone())
+                        })
+                        .collect::<Vec<_>>(),
+                ),
                 ExecutionMode::Bounded,
             )
         })
@@ -164,6 +181,6 @@ impl ExecutionPlan for RenameColumnsExec {
     }
 
     fn statistics(&self) -> Result<Statistics> {
-        todo!()
+        self.input.statistics()
     }
 }
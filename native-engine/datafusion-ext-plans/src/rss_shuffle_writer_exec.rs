@@ -25,7 +25,7 @@ use datafusion::{
     physical_expr::EquivalenceProperties,
     physical_plan::{
         metrics::{ExecutionPlanMetricsSet, MetricsSet},
-        DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, Partitioning, PlanProperties,
+        DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, PlanProperties,
         SendableRecordBatchStream, Statistics,
     },
 };
@@ -36,7 +36,8 @@ use crate::{
     memmgr::MemManager,
     shuffle::{
         rss_single_repartitioner::RssSingleShuffleRepartitioner,
-        rss_sort_repartitioner::RssSortShuffleRepartitioner, ShuffleRepartitioner,
+        rss_sort_repartitioner::RssSortShuffleRepartitioner, ShufflePartitioning,
+        ShuffleRepartitioner,
     },
 };
 
@@ -46,7 +47,7 @@ use crate::{
 #[derive(Debug)]
 pub struct RssShuffleWriterExec {
     input: Arc<dyn ExecutionPlan>,
-    partitioning: Partitioning,
+    partitioning: ShufflePartitioning,
     pub rss_partition_writer_resource_id: String,
     metrics: ExecutionPlanMetricsSet,
     props: OnceCell<PlanProperties>,
@@ -80,7 +81,7 @@ impl ExecutionPlan for RssShuffleWriterExec {
         self.props.get_or_init(|| {
             PlanProperties::new(
                 EquivalenceProperties::new(self.schema()),
-                self.partitioning.clone(),
+                self.partitioning.to_df_partitioning(),
                 ExecutionMode::Bounded,
             )
         })
@@ -122,7 +123,9 @@ impl ExecutionPlan for RssShuffleWriterExec {
             p if p.partition_count() == 1 => {
                 Arc::new(RssSingleShuffleRepartitioner::new(rss_partition_writer))
             }
-            Partitioning::Hash(..) | Partitioning::RoundRobinBatch(..) => {
+            ShufflePartitioning::Hash(..)
+            | ShufflePartitioning::RoundRobin(..)
+            | ShufflePartitioning::Range(..) => {
                 let sort_time = exec_ctx.register_timer_metric("sort_time");
                 let partitioner = Arc::new(RssSortShuffleRepartitioner::new(
                     partition,
@@ -152,7 +155,7 @@ impl RssShuffleWriterExec {
     /// Create a new RssShuffleWriterExec
     pub fn try_new(
         input: Arc<dyn ExecutionPlan>,
-        partitioning: Partitioning,
+        partitioning: ShufflePartitioning,
         rss_partition_writer_resource_id: String,
     ) -> Result<Self> {
         Ok(RssShuffleWriterExec {
@@ -0,0 +1,377 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Aggregates pushed down into file scans.
+//!
+//! global `count`, `min` and `max` aggregates over a scan without data filters
+//! are answered from file metadata (row group statistics of parquet files or
+//! stripe statistics of orc files) instead of reading data pages. files
+//! without the required statistics are read and aggregated as usual. every
+//! scan partition outputs a single row of partial aggregate values, which are
+//! merged by the final aggregate.
+
+use std::{cmp::Ordering, sync::Arc};
+
+use arrow::{
+    array::{Array, ArrayRef, AsArray, RecordBatch, RecordBatchOptions},
+    compute::{max, max_boolean, min, min_boolean},
+    datatypes::{
+        DataType, Date32Type, Field, Float32Type, Float64Type, Int16Type, Int32Type, Int64Type,
+        Int8Type, Schema, SchemaRef, UInt64Type,
+    },
+};
+use datafusion::{
+    common::{Column, Result, ScalarValue},
+    physical_optimizer::pruning::PruningStatistics,
+};
+use datafusion_ext_commons::df_execution_err;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PushedAggFunction {
+    /// `count(*)`
+    CountRows,
+    Count,
+    Min,
+    Max,
+}
+
+/// An aggregate pushed down into a file scan, `column` is the index of the
+/// aggregated column in the file schema (not used by `CountRows`)
+#[derive(Debug, Clone, Copy)]
+pub struct PushedAggregate {
+    pub function: PushedAggFunction,
+    pub column: usize,
+}
+
+/// returns the output schema of a scan with pushed aggregates, which contains
+/// the partial aggregate values in the same order as the aggregates
+pub fn pushed_aggregates_schema(aggs: &[PushedAggregate], file_schema: &SchemaRef) -> SchemaRef {
+    Arc::new(Schema::new(
+        aggs.iter()
+            .enumerate()
+            .map(|(i, agg)| match agg.function {
+                PushedAggFunction::CountRows | PushedAggFunction::Count => {
+                    Field::new(format!("agg#{i}"), DataType::Int64, false)
+                }
+                PushedAggFunction::Min | PushedAggFunction::Max => Field::new(
+                    format!("agg#{i}"),
+                    file_schema.field(agg.column).data_type().clone(),
+                    true,
+                ),
+            })
+            .collect::<Vec<_>>(),
+    ))
+}
+
+/// Accumulates pushed aggregates of all files in a scan partition
+pub struct PushedAggregatesAccumulator {
+    aggs: Vec<PushedAggregate>,
+    file_schema: SchemaRef,
+    values: Vec<ScalarValue>,
+}
+
+impl PushedAggregatesAccumulator {
+    pub fn try_new(aggs: Vec<PushedAggregate>, file_schema: SchemaRef) -> Result<Self> {
+        let values = aggs
+            .iter()
+            .map(|agg| match agg.function {
+                PushedAggFunction::CountRows | PushedAggFunction::Count => {
+                    Ok(ScalarValue::Int64(Some(0)))
+                }
+                PushedAggFunction::Min | PushedAggFunction::Max => {
+                    ScalarValue::try_from(file_schema.field(agg.column).data_type())
+                }
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            aggs,
+            file_schema,
+            values,
+        })
+    }
+
+    /// updates with statistics of the selected containers (row groups or
+    /// stripes) of a file, returns false without updating if some of the
+    /// required statistics are missing
+    pub fn update_with_statistics(
+        &mut self,
+        statistics: &dyn PruningStatistics,
+        selected: &[bool],
+    ) -> Result<bool> {
+        let column = |idx: usize| Column::from_name(self.file_schema.field(idx).name());
+        let Some(row_counts) = self
+            .aggs
+            .first()
+            .and_then(|agg| statistics.row_counts(&column(agg.column)))
+        else {
+            return Ok(false);
+        };
+        let row_counts = row_counts.as_primitive::<UInt64Type>();
+        let num_containers = row_counts.len();
+        let selected_containers = || (0..num_containers).filter(move |&i| selected[i]);
+        if selected_containers().any(|i| row_counts.is_null(i)) {
+            return Ok(false);
+        }
+
+        let mut values = self.values.clone();
+        for (agg, value) in self.aggs.iter().zip(&mut values) {
+            if agg.function == PushedAggFunction::CountRows {
+                let num_rows: u64 = selected_containers().map(|i| row_counts.value(i)).sum();
+                add_count(value, num_rows);
+                continue;
+            }
+
+            let Some(null_counts) = statistics.null_counts(&column(agg.column)) else {
+                return Ok(false);
+            };
+            let null_counts = null_counts.as_primitive::<UInt64Type>();
+            if selected_containers().any(|i| null_counts.is_null(i)) {
+                return Ok(false);
+            }
+
+            match agg.function {
+                PushedAggFunction::Count => {
+                    let num_values: u64 = selected_containers()
+                        .map(|i| row_counts.value(i).saturating_sub(null_counts.value(i)))
+                        .sum();
+                    add_count(value, num_values);
+                }
+                PushedAggFunction::Min | PushedAggFunction::Max => {
+                    let is_min = agg.function == PushedAggFunction::Min;
+                    let values = if is_min {
+                        statistics.min_values(&column(agg.column))
+                    } else {
+                        statistics.max_values(&column(agg.column))
+                    };
+                    let Some(values) = values else {
+                        return Ok(false);
+                    };
+
+                    // containers with only nulls have no min/max values
+                    for i in selected_containers() {
+                        if null_counts.value(i) >= row_counts.value(i) {
+                            continue;
+                        }
+                        if values.is_null(i) {
+                            return Ok(false);
+                        }
+                        merge_min_max(value, ScalarValue::try_from_array(&values, i)?, is_min)?;
+                    }
+                }
+                PushedAggFunction::CountRows => unreachable!(),
+            }
+        }
+        self.values = values;
+        Ok(true)
+    }
+
+    /// updates with a batch read from a file, `projection` contains the file
+    /// schema indices of the batch columns
+    pub fn update_batch(&mut self, batch: &RecordBatch, projection: &[usize]) -> Result<()> {
+        for (agg, value) in self.aggs.iter().zip(&mut self.values) {
+            let array = || match projection.iter().position(|&idx| idx == agg.column) {
+                Some(i) => Ok(batch.column(i)),
+                None => df_execution_err!("pushed aggregate column {} not read", agg.column),
+            };
+            match agg.function {
+                PushedAggFunction::CountRows => add_count(value, batch.num_rows() as u64),
+                PushedAggFunction::Count => {
+                    let array = array()?;
+                    add_count(value, (array.len() - array.null_count()) as u64);
+                }
+                PushedAggFunction::Min | PushedAggFunction::Max => {
+                    let is_min = agg.function == PushedAggFunction::Min;
+                    merge_min_max(value, array_min_max(array()?, is_min)?, is_min)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// returns the single row of partial aggregate values
+    pub fn finish(&self, schema: &SchemaRef) -> Result<RecordBatch> {
+        let columns = self
+            .values
+            .iter()
+            .map(|value| value.to_array())
+            .collect::<Result<Vec<_>>>()?;
+        Ok(RecordBatch::try_new_with_options(
+            schema.clone(),
+            columns,
+            &RecordBatchOptions::new().with_row_count(Some(1)),
+        )?)
+    }
+}
+
+fn add_count(value: &mut ScalarValue, n: u64) {
+    if let ScalarValue::Int64(Some(count)) = value {
+        *count += n as i64;
+    }
+}
+
+fn merge_min_max(value: &mut ScalarValue, other: ScalarValue, is_min: bool) -> Result<()> {
+    if other.is_null() {
+        return Ok(());
+    }
+    let other = other.cast_to(&value.data_type())?;
+    let replace = value.is_null()
+        || match other.partial_cmp(value) {
+            Some(Ordering::Less) => is_min,
+            Some(Ordering::Greater) => !is_min,
+            _ => false,
+        };
+    if replace {
+        *value = other;
+    }
+    Ok(())
+}
+
+fn array_min_max(array: &ArrayRef, is_min: bool) -> Result<ScalarValue> {
+    macro_rules! primitive_min_max {
+        ($t:ty) => {{
+            let array = array.as_primitive::<$t>();
+            let value = if is_min { min(array) } else { max(array) };
+            ScalarValue::new_primitive::<$t>(value, array.data_type())?
+        }};
+    }
+    Ok(match array.data_type() {
+        DataType::Boolean => {
+            let array = array.as_boolean();
+            ScalarValue::Boolean(if is_min {
+                min_boolean(array)
+            } else {
+                max_boolean(array)
+            })
+        }
+        DataType::Int8 => primitive_min_max!(Int8Type),
+        DataType::Int16 => primitive_min_max!(Int16Type),
+        DataType::Int32 => primitive_min_max!(Int32Type),
+        DataType::Int64 => primitive_min_max!(Int64Type),
+        DataType::Float32 => primitive_min_max!(Float32Type),
+        DataType::Float64 => primitive_min_max!(Float64Type),
+        DataType::Date32 => primitive_min_max!(Date32Type),
+        other => df_execution_err!("unsupported data type of pushed min/max aggregate: {other}")?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashSet, sync::Arc};
+
+    use arrow::{
+        array::{ArrayRef, BooleanArray, Int32Array, RecordBatch, UInt64Array},
+        datatypes::{DataType, Field, Schema},
+    };
+    use datafusion::{
+        assert_batches_eq,
+        common::{Column, Result, ScalarValue},
+        physical_optimizer::pruning::PruningStatistics,
+    };
+
+    use crate::scan::aggregate_pushdown::{
+        pushed_aggregates_schema, PushedAggFunction, PushedAggregate, PushedAggregatesAccumulator,
+    };
+
+    struct TestStatistics {
+        mins: Vec<Option<i32>>,
+        maxes: Vec<Option<i32>>,
+        null_counts: Vec<u64>,
+        row_counts: Vec<u64>,
+    }
+
+    impl PruningStatistics for TestStatistics {
+        fn min_values(&self, _column: &Column) -> Option<ArrayRef> {
+            Some(Arc::new(Int32Array::from(self.mins.clone())))
+        }
+
+        fn max_values(&self, _column: &Column) -> Option<ArrayRef> {
+            Some(Arc::new(Int32Array::from(self.maxes.clone())))
+        }
+
+        fn num_containers(&self) -> usize {
+            self.row_counts.len()
+        }
+
+        fn null_counts(&self, _column: &Column) -> Option<ArrayRef> {
+            Some(Arc::new(UInt64Array::from(self.null_counts.clone())))
+        }
+
+        fn row_counts(&self, _column: &Column) -> Option<ArrayRef> {
+            Some(Arc::new(UInt64Array::from(self.row_counts.clone())))
+        }
+
+        fn contained(
+            &self,
+            _column: &Column,
+            _values: &HashSet<ScalarValue>,
+        ) -> Option<BooleanArray> {
+            None
+        }
+    }
+
+    #[test]
+    fn test_pushed_aggregates() -> Result<()> {
+        let file_schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+        let aggs = [
+            PushedAggFunction::CountRows,
+            PushedAggFunction::Count,
+            PushedAggFunction::Min,
+            PushedAggFunction::Max,
+        ]
+        .into_iter()
+        .map(|function| PushedAggregate {
+            function,
+            column: 0,
+        })
+        .collect::<Vec<_>>();
+        let schema = pushed_aggregates_schema(&aggs, &file_schema);
+        let mut accumulator = PushedAggregatesAccumulator::try_new(aggs, file_schema)?;
+
+        // the third row group is not selected, the second one has only nulls
+        let statistics = TestStatistics {
+            mins: vec![Some(3), None, Some(-100)],
+            maxes: vec![Some(10), None, Some(100)],
+            null_counts: vec![1, 5, 0],
+            row_counts: vec![10, 5, 10],
+        };
+        assert!(accumulator.update_with_statistics(&statistics, &[true, true, false])?);
+
+        // statistics are missing
+        let statistics = TestStatistics {
+            mins: vec![None],
+            maxes: vec![None],
+            null_counts: vec![0],
+            row_counts: vec![10],
+        };
+        assert!(!accumulator.update_with_statistics(&statistics, &[true])?);
+
+        let batch = RecordBatch::try_from_iter([(
+            "a",
+            Arc::new(Int32Array::from(vec![Some(2), None, Some(7)])) as ArrayRef,
+        )])?;
+        accumulator.update_batch(&batch, &[0])?;
+
+        assert_batches_eq!(
+            vec![
+                "+-------+-------+-------+-------+",
+                "| agg#0 | agg#1 | agg#2 | agg#3 |",
+                "+-------+-------+-------+-------+",
+                "| 18    | 11    | 2     | 10    |",
+                "+-------+-------+-------+-------+",
+            ],
+            &[accumulator.finish(&schema)?]
+        );
+        Ok(())
+    }
+}
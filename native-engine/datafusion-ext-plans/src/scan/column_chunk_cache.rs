@@ -0,0 +1,253 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Range,
+    sync::{Arc, Weak},
+};
+
+use async_trait::async_trait;
+use blaze_jni_bridge::{
+    conf::{self, DoubleConf},
+    is_jni_bridge_inited,
+};
+use bytes::Bytes;
+use bytesize::ByteSize;
+use datafusion::common::Result;
+use object_store::ObjectMeta;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+
+use crate::memmgr::{quota::MemConsumerType, MemConsumer, MemConsumerInfo, MemManager};
+
+/// Executor-level LRU cache of column chunks read by parquet scans, shared by
+/// all scan partitions. when partitions read overlapping row groups (e.g.
+/// after AQE splitting), the same column chunks are served from the cache
+/// instead of being read from the file system again.
+///
+/// the cache is registered as a spillable memory consumer, cached bytes are
+/// evicted when its capacity or the memory manager's quota is exceeded.
+pub struct ColumnChunkCache {
+    name: String,
+    mem_consumer_info: Option<Weak<MemConsumerInfo>>,
+    capacity: usize,
+    inner: Mutex<CacheInner>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    location: String,
+    file_size: usize,
+    last_modified: i64,
+    range: Range<usize>,
+}
+
+impl CacheKey {
+    fn new(meta: &ObjectMeta, range: &Range<usize>) -> Self {
+        Self {
+            location: meta.location.to_string(),
+            file_size: meta.size,
+            last_modified: meta.last_modified.timestamp_millis(),
+            range: range.clone(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct CacheInner {
+    entries: HashMap<CacheKey, (Bytes, u64)>,
+    lru: BTreeMap<u64, CacheKey>,
+    next_tick: u64,
+    mem_used: usize,
+}
+
+impl CacheInner {
+    fn touch(&mut self, key: &CacheKey) -> Option<Bytes> {
+        let tick = self.next_tick;
+        let (bytes, entry_tick) = self.entries.get_mut(key)?;
+        let old_tick = std::mem::replace(entry_tick, tick);
+        let bytes = bytes.clone();
+        self.next_tick += 1;
+        let key = self.lru.remove(&old_tick).expect("missing lru entry");
+        self.lru.insert(tick, key);
+        Some(bytes)
+    }
+
+    fn insert(&mut self, key: CacheKey, bytes: Bytes) {
+        if self.touch(&key).is_some() {
+            return;
+        }
+        let tick = self.next_tick;
+        self.next_tick += 1;
+        self.mem_used += bytes.len();
+        self.lru.insert(tick, key.clone());
+        self.entries.insert(key, (bytes, tick));
+    }
+
+    fn evict_until(&mut self, mem_used: usize) {
+        while self.mem_used > mem_used {
+            let Some((_, key)) = self.lru.pop_first() else {
+                break;
+            };
+            let (bytes, _) = self.entries.remove(&key).expect("missing cache entry");
+            self.mem_used -= bytes.len();
+        }
+    }
+}
+
+impl ColumnChunkCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            name: "ColumnChunkCache".to_string(),
+            mem_consumer_info: None,
+            capacity,
+            inner: Mutex::default(),
+        }
+    }
+
+    /// returns the executor-level cache, or None if the cache is disabled
+    pub fn get() -> Option<Arc<Self>> {
+        static CACHE: OnceCell<Option<Arc<ColumnChunkCache>>> = OnceCell::new();
+        CACHE
+            .get_or_init(|| {
+                if !is_jni_bridge_inited() || !MemManager::initialized() {
+                    return None;
+                }
+                let fraction = conf::PARQUET_COLUMN_CHUNK_CACHE_FRACTION
+                    .value()
+                    .unwrap_or(0.0);
+                let capacity = (MemManager::get().total() as f64 * fraction) as usize;
+                if capacity == 0 {
+                    return None;
+                }
+                log::info!(
+                    "parquet column chunk cache enabled, capacity: {}",
+                    ByteSize(capacity as u64)
+                );
+                let cache = Arc::new(Self::new(capacity));
+                MemManager::register_consumer(cache.clone(), true);
+                Some(cache)
+            })
+            .clone()
+    }
+
+    pub fn lookup(&self, meta: &ObjectMeta, range: &Range<usize>) -> Option<Bytes> {
+        self.inner.lock().touch(&CacheKey::new(meta, range))
+    }
+
+    pub async fn insert(
+        &self,
+        meta: &ObjectMeta,
+        range: &Range<usize>,
+        bytes: Bytes,
+    ) -> Result<()> {
+        if bytes.len() > self.capacity / 4 {
+            return Ok(()); // do not let a single huge chunk flush the whole
+                           // cache
+        }
+
+        // copy bytes, since the given bytes may be a slice of a larger buffer
+        let mem_used = {
+            let mut inner = self.inner.lock();
+            inner.insert(CacheKey::new(meta, range), Bytes::copy_from_slice(&bytes));
+            inner.evict_until(self.capacity);
+            inner.mem_used
+        };
+        self.update_mem_used(mem_used).await
+    }
+
+    pub fn mem_used(&self) -> usize {
+        self.inner.lock().mem_used
+    }
+}
+
+#[async_trait]
+impl MemConsumer for ColumnChunkCache {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn set_consumer_info(&mut self, consumer_info: Weak<MemConsumerInfo>) {
+        self.mem_consumer_info = Some(consumer_info);
+    }
+
+    fn get_consumer_info(&self) -> &Weak<MemConsumerInfo> {
+        self.mem_consumer_info
+            .as_ref()
+            .expect("consumer info not set")
+    }
+
+    fn consumer_type(&self) -> MemConsumerType {
+        MemConsumerType::Cache
+    }
+
+    // evicted chunks are cheap to read again from file system
+    fn spill_cost(&self) -> f64 {
+        0.5
+    }
+
+    async fn spill(&self) -> Result<()> {
+        // cached chunks can be read again from file system, so just evict the
+        // least recently used half
+        let mem_used = {
+            let mut inner = self.inner.lock();
+            let target = inner.mem_used / 2;
+            inner.evict_until(target);
+            inner.mem_used
+        };
+        self.update_mem_used(mem_used).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+    use object_store::{path::Path, ObjectMeta};
+
+    use crate::scan::column_chunk_cache::{CacheInner, CacheKey};
+
+    #[test]
+    fn test_column_chunk_cache_lru() {
+        let meta = ObjectMeta {
+            location: Path::from("test.parquet"),
+            last_modified: Default::default(),
+            size: 1000,
+            e_tag: None,
+            version: None,
+        };
+        let key = |range: std::ops::Range<usize>| CacheKey::new(&meta, &range);
+        let mut inner = CacheInner::default();
+
+        inner.insert(key(0..100), Bytes::from(vec![1u8; 100]));
+        inner.insert(key(100..200), Bytes::from(vec![2u8; 100]));
+        inner.insert(key(200..300), Bytes::from(vec![3u8; 100]));
+        assert_eq!(inner.mem_used, 300);
+
+        // touch the first chunk, so the second one becomes the eldest
+        assert_eq!(inner.touch(&key(0..100)).map(|b| b[0]), Some(1));
+        inner.evict_until(200);
+        assert_eq!(inner.mem_used, 200);
+        assert!(inner.touch(&key(100..200)).is_none());
+        assert!(inner.touch(&key(0..100)).is_some());
+        assert!(inner.touch(&key(200..300)).is_some());
+
+        // re-inserting an existing chunk does not change memory usage
+        inner.insert(key(0..100), Bytes::from(vec![1u8; 100]));
+        assert_eq!(inner.mem_used, 200);
+        inner.evict_until(0);
+        assert_eq!(inner.mem_used, 0);
+        assert!(inner.entries.is_empty() && inner.lru.is_empty());
+    }
+}
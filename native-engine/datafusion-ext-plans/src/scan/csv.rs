@@ -0,0 +1,432 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Parsing of csv records compatible with spark's csv data source (with
+//! `multiLine=false`). records are parsed by position into the data columns,
+//! values failed to parse are treated as malformed records and handled
+//! according to the parse mode.
+
+use std::sync::Arc;
+
+use arrow::{
+    array::{
+        new_null_array, ArrayRef, BooleanArray, Date32Array, Decimal128Array, Float32Array,
+        Float64Array, Int16Array, Int32Array, Int64Array, Int8Array, RecordBatch,
+        RecordBatchOptions, StringArray,
+    },
+    compute::filter_record_batch,
+    datatypes::{DataType, SchemaRef},
+};
+use datafusion::common::Result;
+use datafusion_ext_commons::df_execution_err;
+
+use crate::scan::text::{
+    parse_decimal, parse_float, DateFormat, LineBatchBuilder, ParseMode, DEFAULT_DATE_FORMAT,
+};
+
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    pub delimiter: String,
+    pub quote: Option<char>,
+    pub escape: Option<char>,
+    pub header: bool,
+    pub null_value: String,
+    pub date_format: String,
+    pub mode: ParseMode,
+    pub column_name_of_corrupt_record: String,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: ",".to_string(),
+            quote: Some('"'),
+            escape: Some('\\'),
+            header: false,
+            null_value: String::new(),
+            date_format: DEFAULT_DATE_FORMAT.to_string(),
+            mode: ParseMode::Permissive,
+            column_name_of_corrupt_record: "_corrupt_record".to_string(),
+        }
+    }
+}
+
+/// splits a line into tokens, unquoted empty tokens are `None`. returns
+/// `None` if the line is malformed (like having an unclosed quote).
+pub fn tokenize_line(line: &str, options: &CsvOptions) -> Option<Vec<Option<String>>> {
+    let delimiter = options.delimiter.as_str();
+    let mut tokens = vec![];
+    let mut rest = line;
+
+    loop {
+        let mut token = String::new();
+        let mut quoted = false;
+        if let Some(quote) = options.quote.filter(|&quote| rest.starts_with(quote)) {
+            quoted = true;
+            let mut chars = rest[quote.len_utf8()..].char_indices();
+            let mut closed_at = None;
+            while let Some((i, ch)) = chars.next() {
+                let next_ch = rest[quote.len_utf8() + i + ch.len_utf8()..].chars().next();
+                if Some(ch) == options.escape && (next_ch == Some(quote) || next_ch == Some(ch)) {
+                    token.push(chars.next()?.1);
+                } else if ch == quote && next_ch == Some(quote) {
+                    token.push(chars.next()?.1);
+                } else if ch == quote {
+                    closed_at = Some(quote.len_utf8() + i + ch.len_utf8());
+                    break;
+                } else {
+                    token.push(ch);
+                }
+            }
+            rest = &rest[closed_at?..];
+        }
+
+        // unquoted part, or the remaining characters after the closing quote
+        let token_end = rest.find(delimiter).unwrap_or(rest.len());
+        token.push_str(&rest[..token_end]);
+        tokens.push((quoted || !token.is_empty()).then_some(token));
+
+        if token_end == rest.len() {
+            return Some(tokens);
+        }
+        rest = &rest[token_end + delimiter.len()..];
+    }
+}
+
+/// Builds record batches from csv lines
+pub struct CsvBatchBuilder {
+    schema: SchemaRef,
+    options: CsvOptions,
+    date_format: DateFormat,
+    num_data_columns: usize,
+    data_column_indices: Vec<Option<usize>>, // position of each field in the tokens
+    lines: Vec<String>,
+    tokens: Vec<Option<Vec<Option<String>>>>,
+}
+
+impl CsvBatchBuilder {
+    /// creates a builder producing batches of projected columns of the file
+    /// schema. fields of the file schema are data columns in order of the csv
+    /// tokens, except the corrupt record column.
+    pub fn try_new(
+        file_schema: &SchemaRef,
+        projection: &[usize],
+        options: CsvOptions,
+    ) -> Result<Self> {
+        let date_format = DateFormat::try_new(&options.date_format)?;
+        let corrupt_record_column = file_schema
+            .column_with_name(&options.column_name_of_corrupt_record)
+            .filter(|(_, field)| field.data_type() == &DataType::Utf8)
+            .map(|(idx, _)| idx);
+        let num_data_columns = file_schema.fields().len() - corrupt_record_column.iter().count();
+
+        let mut data_column_indices = vec![];
+        for &idx in projection {
+            let field = file_schema.field(idx);
+            if Some(idx) == corrupt_record_column {
+                data_column_indices.push(None);
+                continue;
+            }
+            if !is_supported_data_type(field.data_type()) {
+                return df_execution_err!("unsupported csv column type: {}", field.data_type());
+            }
+            let skipped = corrupt_record_column.filter(|&c| c < idx).iter().count();
+            data_column_indices.push(Some(idx - skipped));
+        }
+
+        Ok(Self {
+            schema: Arc::new(file_schema.project(projection)?),
+            options,
+            date_format,
+            num_data_columns,
+            data_column_indices,
+            lines: vec![],
+            tokens: vec![],
+        })
+    }
+
+    /// parses non-null values of a column, records with values failed to
+    /// parse are marked malformed
+    fn parse_column(
+        &self,
+        values: &[Option<&str>],
+        data_type: &DataType,
+        malformed: &mut [bool],
+    ) -> Result<ArrayRef> {
+        macro_rules! parse {
+            ($array:ty, $parse:expr) => {{
+                let parse = $parse;
+                Arc::new(<$array>::from_iter(values.iter().enumerate().map(
+                    |(row_idx, value)| {
+                        let parsed = value.and_then(|v| parse(v));
+                        malformed[row_idx] |= value.is_some() && parsed.is_none();
+                        parsed
+                    },
+                ))) as ArrayRef
+            }};
+        }
+
+        Ok(match data_type {
+            DataType::Null => new_null_array(data_type, values.len()),
+            DataType::Utf8 => Arc::new(StringArray::from_iter(values.iter().copied())),
+            DataType::Boolean => parse!(BooleanArray, |v: &str| {
+                if v.eq_ignore_ascii_case("true") {
+                    Some(true)
+                } else if v.eq_ignore_ascii_case("false") {
+                    Some(false)
+                } else {
+                    None
+                }
+            }),
+            DataType::Int8 => parse!(Int8Array, |v: &str| v.parse::<i8>().ok()),
+            DataType::Int16 => parse!(Int16Array, |v: &str| v.parse::<i16>().ok()),
+            DataType::Int32 => parse!(Int32Array, |v: &str| v.parse::<i32>().ok()),
+            DataType::Int64 => parse!(Int64Array, |v: &str| v.parse::<i64>().ok()),
+            DataType::Float32 => parse!(Float32Array, |v: &str| parse_float(v).map(|v| v as f32)),
+            DataType::Float64 => parse!(Float64Array, parse_float),
+            DataType::Date32 => parse!(Date32Array, |v: &str| self.date_format.parse(v)),
+            &DataType::Decimal128(precision, scale) => {
+                let array = parse!(Decimal128Array, |v: &str| parse_decimal(
+                    v, precision, scale
+                ));
+                Arc::new(
+                    Decimal128Array::from(array.to_data())
+                        .with_precision_and_scale(precision, scale)?,
+                )
+            }
+            other => df_execution_err!("unsupported csv column type: {other}")?,
+        })
+    }
+}
+
+impl LineBatchBuilder for CsvBatchBuilder {
+    fn num_rows(&self) -> usize {
+        self.lines.len()
+    }
+
+    fn append_line(&mut self, line: String) {
+        let tokens = tokenize_line(&line, &self.options);
+        self.tokens.push(tokens);
+        self.lines.push(line);
+    }
+
+    fn build(&mut self) -> Result<(RecordBatch, usize)> {
+        let lines = std::mem::take(&mut self.lines);
+        let tokens = std::mem::take(&mut self.tokens);
+        let num_rows = lines.len();
+
+        // records with unmatched number of tokens are malformed, extra tokens
+        // are ignored and missing tokens are null. like spark, the number of
+        // tokens is not checked if some data columns are pruned.
+        let check_num_tokens =
+            self.data_column_indices.iter().flatten().count() == self.num_data_columns;
+        let mut malformed: Vec<bool> = tokens
+            .iter()
+            .map(|tokens| match tokens {
+                Some(tokens) => check_num_tokens && tokens.len() != self.num_data_columns,
+                None => true,
+            })
+            .collect();
+
+        let mut columns = Vec::with_capacity(self.schema.fields().len());
+        for (field, column_idx) in self.schema.fields().iter().zip(&self.data_column_indices) {
+            let Some(column_idx) = *column_idx else {
+                columns.push(None); // corrupt record column
+                continue;
+            };
+            let values = tokens
+                .iter()
+                .map(|tokens| {
+                    tokens
+                        .as_ref()
+                        .and_then(|tokens| tokens.get(column_idx))
+                        .and_then(|token| token.as_deref())
+                        .filter(|&token| token != self.options.null_value)
+                })
+                .collect::<Vec<_>>();
+            columns.push(Some(self.parse_column(
+                &values,
+                field.data_type(),
+                &mut malformed,
+            )?));
+        }
+
+        let num_malformed = malformed.iter().filter(|&&m| m).count();
+        if num_malformed > 0 && self.options.mode == ParseMode::FailFast {
+            let line = &lines[malformed.iter().position(|&m| m).unwrap()];
+            return df_execution_err!(
+                "malformed csv record found in FAILFAST mode: {line}, \
+                 set mode to PERMISSIVE to parse it as null"
+            );
+        }
+
+        let columns = columns
+            .into_iter()
+            .map(|column| {
+                column.unwrap_or_else(|| {
+                    Arc::new(StringArray::from_iter(
+                        lines
+                            .iter()
+                            .zip(&malformed)
+                            .map(|(line, &malformed)| malformed.then_some(line.as_str())),
+                    ))
+                })
+            })
+            .collect();
+        let options = RecordBatchOptions::new().with_row_count(Some(num_rows));
+        let batch = RecordBatch::try_new_with_options(self.schema.clone(), columns, &options)?;
+
+        if num_malformed > 0 && self.options.mode == ParseMode::DropMalformed {
+            let selected = BooleanArray::from_iter(malformed.iter().map(|&m| Some(!m)));
+            return Ok((filter_record_batch(&batch, &selected)?, num_malformed));
+        }
+        Ok((batch, num_malformed))
+    }
+}
+
+fn is_supported_data_type(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Null
+            | DataType::Utf8
+            | DataType::Boolean
+            | DataType::Int8
+            | DataType::Int16
+            | DataType::Int32
+            | DataType::Int64
+            | DataType::Float32
+            | DataType::Float64
+            | DataType::Date32
+            | DataType::Decimal128(..)
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{AsArray, RecordBatch},
+        datatypes::{DataType, Date32Type, Decimal128Type, Field, Int32Type, Schema},
+    };
+    use datafusion::{assert_batches_eq, common::Result};
+
+    use crate::scan::{
+        csv::{tokenize_line, CsvBatchBuilder, CsvOptions},
+        text::{LineBatchBuilder, ParseMode},
+    };
+
+    #[test]
+    fn test_tokenize_line() {
+        let options = CsvOptions::default();
+        let tokens = |line| tokenize_line(line, &options);
+        assert_eq!(
+            tokens("a,,\"\",\"b,c\",\"x\\\"y\"\"z\""),
+            Some(vec![
+                Some("a".to_string()),
+                None,
+                Some("".to_string()),
+                Some("b,c".to_string()),
+                Some("x\"y\"z".to_string()),
+            ]),
+        );
+        assert_eq!(tokens(""), Some(vec![None]));
+        assert_eq!(tokens("a,\"b"), None);
+
+        let options = CsvOptions {
+            delimiter: "||".to_string(),
+            quote: None,
+            ..Default::default()
+        };
+        assert_eq!(
+            tokenize_line("\"a||b||", &options),
+            Some(vec![Some("\"a".to_string()), Some("b".to_string()), None]),
+        );
+    }
+
+    fn build_batch(options: CsvOptions, lines: &[&str]) -> Result<(RecordBatch, usize)> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, true),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("_corrupt_record", DataType::Utf8, true),
+            Field::new("date", DataType::Date32, true),
+            Field::new("price", DataType::Decimal128(5, 2), true),
+        ]));
+        let projection = (0..schema.fields().len()).collect::<Vec<_>>();
+        let mut builder = CsvBatchBuilder::try_new(&schema, &projection, options)?;
+        for line in lines {
+            builder.append_line(line.to_string());
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn test_parse_modes() -> Result<()> {
+        let lines = [
+            "1,a,2020-01-01,1.5",
+            "x,b,2020-01-02,2.5",
+            "3,NA,,",
+            "4,d,2020-01-04",
+        ];
+        let options = CsvOptions {
+            null_value: "NA".to_string(),
+            ..Default::default()
+        };
+
+        let (batch, num_malformed) = build_batch(options.clone(), &lines)?;
+        assert_eq!(num_malformed, 2);
+        assert_batches_eq!(
+            vec![
+                "+----+------+--------------------+------------+-------+",
+                "| id | name | _corrupt_record    | date       | price |",
+                "+----+------+--------------------+------------+-------+",
+                "| 1  | a    |                    | 2020-01-01 | 1.50  |",
+                "|    | b    | x,b,2020-01-02,2.5 | 2020-01-02 | 2.50  |",
+                "| 3  |      |                    |            |       |",
+                "| 4  | d    | 4,d,2020-01-04     | 2020-01-04 |       |",
+                "+----+------+--------------------+------------+-------+",
+            ],
+            &[batch]
+        );
+
+        let (batch, num_malformed) = build_batch(
+            CsvOptions {
+                mode: ParseMode::DropMalformed,
+                ..options.clone()
+            },
+            &lines,
+        )?;
+        assert_eq!(num_malformed, 2);
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(
+            batch.column(0).as_primitive::<Int32Type>().values(),
+            &[1, 3]
+        );
+        assert_eq!(batch.column(3).as_primitive::<Date32Type>().value(0), 18262);
+        assert_eq!(
+            batch.column(4).as_primitive::<Decimal128Type>().value(0),
+            150
+        );
+
+        let result = build_batch(
+            CsvOptions {
+                mode: ParseMode::FailFast,
+                ..options
+            },
+            &lines,
+        );
+        assert!(result.is_err());
+        Ok(())
+    }
+}
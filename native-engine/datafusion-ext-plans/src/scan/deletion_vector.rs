@@ -0,0 +1,316 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Delta lake deletion vectors.
+//!
+//! a deletion vector is a 64-bit roaring bitmap of the deleted row indices of
+//! a data file, serialized in the portable format with a leading magic number.
+//! it is either inlined in the delta log or stored in a separated file, where
+//! the serialized bitmap is prefixed by its size and followed by a checksum.
+
+use std::io::Read;
+
+use bytes::Bytes;
+use datafusion::{
+    common::Result,
+    datasource::physical_plan::parquet::ParquetAccessPlan,
+    parquet::arrow::arrow_reader::{RowSelection, RowSelector},
+};
+use datafusion_ext_commons::{
+    df_execution_err,
+    fs::{resolve_fs, FileReader, FileSystem},
+    hadoop_fs::FsProvider,
+};
+
+/// internal column added by delta for filtering deleted rows, the native scan
+/// removes deleted rows itself and always fills this column with zeros
+pub const DELTA_IS_ROW_DELETED_COLUMN: &str = "__delta_internal_is_row_deleted";
+
+const MAGIC_NUMBER: u32 = 1681511377;
+const SERIAL_COOKIE_NO_RUN_CONTAINER: u32 = 12346;
+const SERIAL_COOKIE: u32 = 12347;
+const NO_OFFSET_THRESHOLD: usize = 4;
+const ARRAY_CONTAINER_MAX_CARDINALITY: usize = 4096;
+const BITMAP_CONTAINER_NUM_WORDS: u32 = 1024;
+
+/// Location of the deletion vector of a data file, attached to the file as
+/// an extension of `PartitionedFile`
+#[derive(Debug, Clone)]
+pub enum DeletionVectorDescriptor {
+    Inline(Bytes),
+    Stored {
+        path: String,
+        offset: usize,
+        size: usize,
+    },
+}
+
+impl DeletionVectorDescriptor {
+    pub fn load(&self, fs_provider: &FsProvider) -> Result<DeletionVector> {
+        match self {
+            Self::Inline(data) => DeletionVector::deserialize(data),
+            Self::Stored { path, offset, size } => {
+                let fs = resolve_fs(path, fs_provider)?;
+                let mut buf = vec![0u8; size + 4];
+                fs.open(path)?.read_fully(*offset as u64, &mut buf)?;
+
+                let stored_size = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize;
+                if stored_size != *size {
+                    return df_execution_err!(
+                        "deletion vector size mismatched: {path}, offset={offset}, \
+                        expected={size}, stored={stored_size}"
+                    );
+                }
+                DeletionVector::deserialize(&buf[4..])
+            }
+        }
+    }
+}
+
+/// Sorted row indices deleted from a data file
+#[derive(Debug, Default, PartialEq)]
+pub struct DeletionVector {
+    deleted_rows: Vec<u64>,
+}
+
+impl DeletionVector {
+    pub fn deserialize(data: &[u8]) -> Result<Self> {
+        let mut input = data;
+        let magic_number = read_u32(&mut input)?;
+        if magic_number != MAGIC_NUMBER {
+            return df_execution_err!("invalid deletion vector magic number: {magic_number}");
+        }
+
+        let mut deleted_rows = vec![];
+        let num_bitmaps = read_u64(&mut input)?;
+        for _ in 0..num_bitmaps {
+            let high_bits = (read_u32(&mut input)? as u64) << 32;
+            read_roaring_bitmap(&mut input, |low_bits| {
+                deleted_rows.push(high_bits | low_bits as u64)
+            })?;
+        }
+
+        // bitmaps and containers are serialized in ascending order of keys, so
+        // sorting is normally not needed
+        if !deleted_rows.windows(2).all(|w| w[0] < w[1]) {
+            deleted_rows.sort_unstable();
+            deleted_rows.dedup();
+        }
+        Ok(Self { deleted_rows })
+    }
+
+    pub fn num_deleted_rows(&self) -> usize {
+        self.deleted_rows.len()
+    }
+
+    /// creates a parquet access plan skipping the deleted rows, given the
+    /// number of rows in each row group of the data file
+    pub fn access_plan(&self, row_group_num_rows: &[usize]) -> ParquetAccessPlan {
+        let mut access_plan = ParquetAccessPlan::new_all(row_group_num_rows.len());
+        let mut deleted_rows = self.deleted_rows.iter().copied().peekable();
+        let mut row_group_start = 0u64;
+
+        for (row_group_idx, &num_rows) in row_group_num_rows.iter().enumerate() {
+            let row_group_end = row_group_start + num_rows as u64;
+            let mut selectors = vec![];
+            let mut num_deleted = 0;
+            let mut pos = row_group_start;
+
+            while let Some(row) = deleted_rows.next_if(|&row| row < row_group_end) {
+                if row > pos {
+                    selectors.push(RowSelector::select((row - pos) as usize));
+                }
+                selectors.push(RowSelector::skip(1));
+                num_deleted += 1;
+                pos = row + 1;
+            }
+
+            if num_deleted == num_rows {
+                access_plan.skip(row_group_idx);
+            } else if num_deleted > 0 {
+                if pos < row_group_end {
+                    selectors.push(RowSelector::select((row_group_end - pos) as usize));
+                }
+                access_plan.scan_selection(row_group_idx, RowSelection::from(selectors));
+            }
+            row_group_start = row_group_end;
+        }
+        access_plan
+    }
+}
+
+/// reads a 32-bit roaring bitmap in portable format, calling `f` with every
+/// value in ascending order
+fn read_roaring_bitmap(input: &mut &[u8], mut f: impl FnMut(u32)) -> Result<()> {
+    let cookie = read_u32(input)?;
+    let (num_containers, run_container_bitset) = if cookie & 0xffff == SERIAL_COOKIE {
+        let num_containers = (cookie >> 16) as usize + 1;
+        let bitset = read_bytes(input, (num_containers + 7) / 8)?.to_vec();
+        (num_containers, Some(bitset))
+    } else if cookie == SERIAL_COOKIE_NO_RUN_CONTAINER {
+        (read_u32(input)? as usize, None)
+    } else {
+        return df_execution_err!("invalid roaring bitmap cookie: {cookie}");
+    };
+
+    let mut keys_and_cardinalities = Vec::with_capacity(num_containers);
+    for _ in 0..num_containers {
+        let key = read_u16(input)? as u32;
+        let cardinality = read_u16(input)? as usize + 1;
+        keys_and_cardinalities.push((key, cardinality));
+    }
+
+    // container offsets are not needed for sequential reading
+    if run_container_bitset.is_none() || num_containers >= NO_OFFSET_THRESHOLD {
+        read_bytes(input, num_containers * 4)?;
+    }
+
+    for (i, (key, cardinality)) in keys_and_cardinalities.into_iter().enumerate() {
+        let high_bits = key << 16;
+        let is_run_container = run_container_bitset
+            .as_ref()
+            .map(|bitset| bitset[i / 8] & (1 << (i % 8)) != 0)
+            .unwrap_or(false);
+
+        if is_run_container {
+            let num_runs = read_u16(input)?;
+            for _ in 0..num_runs {
+                let start = read_u16(input)? as u32;
+                let length = read_u16(input)? as u32;
+                for low_bits in start..=start + length {
+                    f(high_bits | low_bits);
+                }
+            }
+        } else if cardinality <= ARRAY_CONTAINER_MAX_CARDINALITY {
+            for _ in 0..cardinality {
+                f(high_bits | read_u16(input)? as u32);
+            }
+        } else {
+            for word_idx in 0..BITMAP_CONTAINER_NUM_WORDS {
+                let mut word = read_u64(input)?;
+                while word != 0 {
+                    f(high_bits | (word_idx * 64 + word.trailing_zeros()));
+                    word &= word - 1;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_bytes<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if input.len() < len {
+        return df_execution_err!("unexpected end of deletion vector");
+    }
+    let (bytes, remaining) = input.split_at(len);
+    *input = remaining;
+    Ok(bytes)
+}
+
+fn read_u16(input: &mut &[u8]) -> Result<u16> {
+    let mut buf = [0u8; 2];
+    input.read_exact(&mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
+
+fn read_u32(input: &mut &[u8]) -> Result<u32> {
+    let mut buf = [0u8; 4];
+    input.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(input: &mut &[u8]) -> Result<u64> {
+    let mut buf = [0u8; 8];
+    input.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod test {
+    use datafusion::{
+        common::Result,
+        datasource::physical_plan::parquet::RowGroupAccess,
+        parquet::arrow::arrow_reader::{RowSelection, RowSelector},
+    };
+
+    use crate::scan::deletion_vector::{
+        DeletionVector, MAGIC_NUMBER, SERIAL_COOKIE, SERIAL_COOKIE_NO_RUN_CONTAINER,
+    };
+
+    #[test]
+    fn test_deserialize() -> Result<()> {
+        fn put_u16(data: &mut Vec<u8>, v: u16) {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+
+        let mut data = vec![];
+        data.extend_from_slice(&MAGIC_NUMBER.to_le_bytes());
+        data.extend_from_slice(&2u64.to_le_bytes());
+
+        // high bits = 0, no run containers, array containers [1, 2, 5] and [65539]
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&SERIAL_COOKIE_NO_RUN_CONTAINER.to_le_bytes());
+        data.extend_from_slice(&2u32.to_le_bytes());
+        for (key, cardinality) in [(0, 3), (1, 1)] {
+            put_u16(&mut data, key);
+            put_u16(&mut data, cardinality - 1);
+        }
+        data.extend_from_slice(&[0u8; 8]); // offsets
+        for v in [1, 2, 5, 3] {
+            put_u16(&mut data, v);
+        }
+
+        // high bits = 1, a run container [10, 19]
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&SERIAL_COOKIE.to_le_bytes()); // one container
+        data.push(0b1);
+        put_u16(&mut data, 0);
+        put_u16(&mut data, 9);
+        for v in [1, 10, 9] {
+            put_u16(&mut data, v);
+        }
+
+        let dv = DeletionVector::deserialize(&data)?;
+        let mut expected = vec![1, 2, 5, 65539];
+        expected.extend((10..20).map(|v| (1u64 << 32) + v));
+        assert_eq!(dv.deleted_rows, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_access_plan() -> Result<()> {
+        let dv = DeletionVector {
+            deleted_rows: vec![1, 2, 5, 8, 9, 10, 11, 100],
+        };
+        let access_plan = dv.access_plan(&[4, 4, 4, 4]);
+        assert_eq!(
+            access_plan.inner(),
+            &[
+                RowGroupAccess::Selection(RowSelection::from(vec![
+                    RowSelector::select(1),
+                    RowSelector::skip(2),
+                    RowSelector::select(1),
+                ])),
+                RowGroupAccess::Selection(RowSelection::from(vec![
+                    RowSelector::select(1),
+                    RowSelector::skip(1),
+                    RowSelector::select(2),
+                ])),
+                RowGroupAccess::Skip,
+                RowGroupAccess::Scan,
+            ]
+        );
+        Ok(())
+    }
+}
@@ -17,33 +17,61 @@
 
 use std::{ops::Range, sync::Arc};
 
+use arrow::{error::ArrowError, record_batch::RecordBatch};
 use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
 use bytes::Bytes;
 use datafusion::common::Result;
 use datafusion_ext_commons::{
     df_execution_err,
-    hadoop_fs::{Fs, FsDataInputWrapper, FsProvider},
+    fs::{resolve_fs, FileReader, FileSystem},
+    hadoop_fs::FsProvider,
+    spark_task_context::SparkTaskContext,
 };
+use futures::{stream::BoxStream, StreamExt};
 use object_store::ObjectMeta;
 use once_cell::sync::OnceCell;
 
+/// decodes the original file path from the base64-encoded object location
+pub fn decode_file_path(meta: &ObjectMeta) -> Result<String> {
+    BASE64_URL_SAFE_NO_PAD
+        .decode(meta.location.filename().expect("missing filename"))
+        .map(|bytes| String::from_utf8_lossy(&bytes).to_string())
+        .or_else(|_| {
+            let filename = meta.location.filename();
+            df_execution_err!("cannot decode filename: {filename:?}")
+        })
+}
+
+/// updates the input file name of the current task when batches of the file
+/// are produced, used by input_file_name()
+pub fn track_input_file_name(
+    stream: BoxStream<'static, std::result::Result<RecordBatch, ArrowError>>,
+    meta: &ObjectMeta,
+) -> Result<BoxStream<'static, std::result::Result<RecordBatch, ArrowError>>> {
+    let Some(task_context) = SparkTaskContext::current() else {
+        return Ok(stream);
+    };
+    let path = decode_file_path(meta)?;
+    Ok(stream
+        .inspect(move |batch| {
+            if batch.is_ok() {
+                task_context.set_input_file_name(&path);
+            }
+        })
+        .boxed())
+}
+
 pub struct InternalFileReader {
-    fs: Fs,
+    fs: Arc<dyn FileSystem>,
     meta: ObjectMeta,
     path: String,
-    input: OnceCell<Arc<FsDataInputWrapper>>,
+    input: OnceCell<Arc<dyn FileReader>>,
 }
 
 impl InternalFileReader {
     pub fn try_new(fs_provider: Arc<FsProvider>, meta: ObjectMeta) -> Result<Self> {
-        let path = BASE64_URL_SAFE_NO_PAD
-            .decode(meta.location.filename().expect("missing filename"))
-            .map(|bytes| String::from_utf8_lossy(&bytes).to_string())
-            .or_else(|_| {
-                let filename = meta.location.filename();
-                df_execution_err!("cannot decode filename: {filename:?}")
-            })?;
-        let fs = fs_provider.provide(&path)?;
+        let path = decode_file_path(&meta)?;
+        let fs = resolve_fs(&path, &fs_provider)?;
 
         Ok(Self {
             fs,
@@ -53,15 +81,16 @@ impl InternalFileReader {
         })
     }
 
-    fn get_input(&self) -> Result<Arc<FsDataInputWrapper>> {
+    fn get_input(&self) -> Result<Arc<dyn FileReader>> {
         let input = self
             .input
             .get_or_try_init(|| self.fs.open(&self.path))
-            .or_else(|e| df_execution_err!("cannot get FSDataInputStream: ${e:?}"))?;
+            .or_else(|e| df_execution_err!("cannot open file {}: {e:?}", self.path))?;
         Ok(input.clone())
     }
 
     pub fn read_fully(&self, range: Range<usize>) -> Result<Bytes> {
+        SparkTaskContext::check_cancelled()?;
         let mut bytes = vec![0u8; range.len()];
         self.get_input()?
             .read_fully(range.start as u64, &mut bytes)?;
@@ -0,0 +1,467 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Parsing of json lines compatible with spark's json data source (with
+//! `multiLine=false`). fields are matched by name, values not convertible to
+//! the field types are treated as malformed records and handled according to
+//! the parse mode.
+
+use std::sync::Arc;
+
+use arrow::{
+    array::{
+        new_null_array, ArrayRef, BooleanArray, Date32Array, Decimal128Array, Float32Array,
+        Float64Array, Int16Array, Int32Array, Int64Array, Int8Array, ListArray, MapArray,
+        RecordBatch, RecordBatchOptions, StringArray, StructArray,
+    },
+    buffer::{NullBuffer, OffsetBuffer},
+    compute::filter_record_batch,
+    datatypes::{DataType, SchemaRef},
+};
+use datafusion::common::Result;
+use datafusion_ext_commons::df_execution_err;
+use serde_json::Value;
+
+use crate::scan::text::{
+    parse_decimal, DateFormat, LineBatchBuilder, ParseMode, DEFAULT_DATE_FORMAT,
+};
+
+#[derive(Debug, Clone)]
+pub struct JsonOptions {
+    pub date_format: String,
+    pub mode: ParseMode,
+    pub column_name_of_corrupt_record: String,
+}
+
+impl Default for JsonOptions {
+    fn default() -> Self {
+        Self {
+            date_format: DEFAULT_DATE_FORMAT.to_string(),
+            mode: ParseMode::Permissive,
+            column_name_of_corrupt_record: "_corrupt_record".to_string(),
+        }
+    }
+}
+
+/// Builds record batches from json lines
+pub struct JsonBatchBuilder {
+    schema: SchemaRef,
+    options: JsonOptions,
+    date_format: DateFormat,
+    corrupt_record_column: Option<usize>,
+    lines: Vec<String>,
+}
+
+impl JsonBatchBuilder {
+    /// creates a builder producing batches of projected columns of the file
+    /// schema
+    pub fn try_new(
+        file_schema: &SchemaRef,
+        projection: &[usize],
+        options: JsonOptions,
+    ) -> Result<Self> {
+        let schema = Arc::new(file_schema.project(projection)?);
+        let date_format = DateFormat::try_new(&options.date_format)?;
+        let corrupt_record_column = schema
+            .column_with_name(&options.column_name_of_corrupt_record)
+            .filter(|(_, field)| field.data_type() == &DataType::Utf8)
+            .map(|(idx, _)| idx);
+
+        for field in schema.fields() {
+            if !is_supported_data_type(field.data_type()) {
+                return df_execution_err!("unsupported json column type: {}", field.data_type());
+            }
+        }
+
+        Ok(Self {
+            schema,
+            options,
+            date_format,
+            corrupt_record_column,
+            lines: vec![],
+        })
+    }
+}
+
+impl LineBatchBuilder for JsonBatchBuilder {
+    fn num_rows(&self) -> usize {
+        self.lines.len()
+    }
+
+    fn append_line(&mut self, line: String) {
+        self.lines.push(line);
+    }
+
+    fn build(&mut self) -> Result<(RecordBatch, usize)> {
+        let lines = std::mem::take(&mut self.lines);
+
+        // each line is a record, or multiple records if it is an array of
+        // objects like spark. other values are malformed records.
+        let mut records: Vec<(usize, Option<Value>)> = vec![];
+        for (line_idx, line) in lines.iter().enumerate() {
+            match serde_json::from_str::<Value>(line) {
+                Ok(Value::Object(object)) => records.push((line_idx, Some(Value::Object(object)))),
+                Ok(Value::Array(array)) if array.iter().all(Value::is_object) => {
+                    records.extend(array.into_iter().map(|v| (line_idx, Some(v))));
+                }
+                _ => records.push((line_idx, None)),
+            }
+        }
+        let num_rows = records.len();
+        let mut malformed: Vec<bool> = records.iter().map(|(_, v)| v.is_none()).collect();
+
+        let mut columns = Vec::with_capacity(self.schema.fields().len());
+        for (idx, field) in self.schema.fields().iter().enumerate() {
+            if Some(idx) == self.corrupt_record_column {
+                columns.push(None);
+                continue;
+            }
+            let values = records
+                .iter()
+                .map(|(_, record)| record.as_ref().and_then(|v| v.get(field.name())))
+                .collect::<Vec<_>>();
+            let (column, failed) = convert_values(&values, field.data_type(), &self.date_format)?;
+            for (malformed, failed) in malformed.iter_mut().zip(failed) {
+                *malformed |= failed;
+            }
+            columns.push(Some(column));
+        }
+
+        let num_malformed = malformed.iter().filter(|&&m| m).count();
+        if num_malformed > 0 && self.options.mode == ParseMode::FailFast {
+            let line_idx = records[malformed.iter().position(|&m| m).unwrap()].0;
+            return df_execution_err!(
+                "malformed json record found in FAILFAST mode: {}, \
+                 set mode to PERMISSIVE to parse it as null",
+                lines[line_idx],
+            );
+        }
+
+        let columns = columns
+            .into_iter()
+            .map(|column| {
+                column.unwrap_or_else(|| {
+                    Arc::new(StringArray::from_iter(records.iter().zip(&malformed).map(
+                        |(&(line_idx, _), &m)| m.then_some(lines[line_idx].as_str()),
+                    )))
+                })
+            })
+            .collect();
+        let options = RecordBatchOptions::new().with_row_count(Some(num_rows));
+        let batch = RecordBatch::try_new_with_options(self.schema.clone(), columns, &options)?;
+
+        if num_malformed > 0 && self.options.mode == ParseMode::DropMalformed {
+            let selected = BooleanArray::from_iter(malformed.iter().map(|&m| Some(!m)));
+            return Ok((filter_record_batch(&batch, &selected)?, num_malformed));
+        }
+        Ok((batch, num_malformed))
+    }
+}
+
+fn is_supported_data_type(data_type: &DataType) -> bool {
+    match data_type {
+        DataType::Null
+        | DataType::Utf8
+        | DataType::Boolean
+        | DataType::Int8
+        | DataType::Int16
+        | DataType::Int32
+        | DataType::Int64
+        | DataType::Float32
+        | DataType::Float64
+        | DataType::Date32
+        | DataType::Decimal128(..) => true,
+        DataType::Struct(fields) => fields
+            .iter()
+            .all(|field| is_supported_data_type(field.data_type())),
+        DataType::List(field) => is_supported_data_type(field.data_type()),
+        DataType::Map(entries_field, _) => match entries_field.data_type() {
+            DataType::Struct(fields) if fields.len() == 2 => {
+                fields[0].data_type() == &DataType::Utf8
+                    && is_supported_data_type(fields[1].data_type())
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// converts json values to the data type, returns the converted array and
+/// whether each value failed to convert. values failed to convert are null.
+fn convert_values(
+    values: &[Option<&Value>],
+    data_type: &DataType,
+    date_format: &DateFormat,
+) -> Result<(ArrayRef, Vec<bool>)> {
+    let values = values
+        .iter()
+        .map(|value| value.filter(|v| !v.is_null()))
+        .collect::<Vec<_>>();
+    let mut failed = vec![false; values.len()];
+    let nulls = || NullBuffer::from_iter(values.iter().map(|v| v.is_some()));
+
+    macro_rules! convert {
+        ($array:ty, $convert:expr) => {{
+            let convert = $convert;
+            Arc::new(<$array>::from_iter(values.iter().zip(&mut failed).map(
+                |(value, failed)| {
+                    let converted = value.and_then(|v| convert(v));
+                    *failed = value.is_some() && converted.is_none();
+                    converted
+                },
+            ))) as ArrayRef
+        }};
+    }
+
+    let array = match data_type {
+        DataType::Null => new_null_array(data_type, values.len()),
+        DataType::Boolean => convert!(BooleanArray, Value::as_bool),
+        DataType::Int8 => convert!(Int8Array, |v: &Value| v
+            .as_i64()
+            .and_then(|v| v.try_into().ok())),
+        DataType::Int16 => convert!(Int16Array, |v: &Value| v
+            .as_i64()
+            .and_then(|v| v.try_into().ok())),
+        DataType::Int32 => convert!(Int32Array, |v: &Value| v
+            .as_i64()
+            .and_then(|v| v.try_into().ok())),
+        DataType::Int64 => convert!(Int64Array, Value::as_i64),
+        DataType::Float32 => convert!(Float32Array, |v: &Value| to_float(v).map(|v| v as f32)),
+        DataType::Float64 => convert!(Float64Array, to_float),
+        DataType::Utf8 => convert!(StringArray, |v: &Value| match v {
+            Value::String(s) => Some(s.clone()),
+            other => Some(other.to_string()), // raw json of non-string values
+        }),
+        DataType::Date32 => convert!(Date32Array, |v: &Value| v
+            .as_str()
+            .and_then(|s| date_format.parse(s))),
+        &DataType::Decimal128(precision, scale) => {
+            let array = convert!(Decimal128Array, |v: &Value| match v {
+                Value::Number(n) => parse_decimal(&n.to_string(), precision, scale),
+                Value::String(s) => parse_decimal(s, precision, scale),
+                _ => None,
+            });
+            Arc::new(
+                Decimal128Array::from(array.to_data())
+                    .with_precision_and_scale(precision, scale)?,
+            )
+        }
+        DataType::Struct(fields) => {
+            let objects = values
+                .iter()
+                .zip(&mut failed)
+                .map(|(value, failed)| {
+                    let object = value.and_then(|v| v.as_object());
+                    *failed = value.is_some() && object.is_none();
+                    object
+                })
+                .collect::<Vec<_>>();
+            let mut children = Vec::with_capacity(fields.len());
+            for field in fields {
+                let child_values = objects
+                    .iter()
+                    .map(|object| object.and_then(|object| object.get(field.name())))
+                    .collect::<Vec<_>>();
+                let (child, child_failed) =
+                    convert_values(&child_values, field.data_type(), date_format)?;
+                for (failed, child_failed) in failed.iter_mut().zip(child_failed) {
+                    *failed |= child_failed;
+                }
+                children.push(child);
+            }
+            let nulls = NullBuffer::from_iter(objects.iter().map(|object| object.is_some()));
+            Arc::new(StructArray::try_new(fields.clone(), children, Some(nulls))?)
+        }
+        DataType::List(field) => {
+            let mut offsets = vec![0i32];
+            let mut elements: Vec<Option<&Value>> = vec![];
+            for (value, failed) in values.iter().zip(&mut failed) {
+                match value.map(|v| v.as_array()) {
+                    Some(Some(array)) => elements.extend(array.iter().map(Some)),
+                    Some(None) => *failed = true,
+                    None => {}
+                }
+                offsets.push(elements.len() as i32);
+            }
+            let (child, child_failed) = convert_values(&elements, field.data_type(), date_format)?;
+            propagate_child_failures(&mut failed, &offsets, &child_failed);
+            Arc::new(ListArray::try_new(
+                field.clone(),
+                OffsetBuffer::new(offsets.into()),
+                child,
+                Some(nulls()),
+            )?)
+        }
+        DataType::Map(entries_field, sorted) => {
+            let DataType::Struct(entry_fields) = entries_field.data_type() else {
+                unreachable!("invalid map entries type: {}", entries_field.data_type());
+            };
+            let mut offsets = vec![0i32];
+            let mut keys: Vec<&str> = vec![];
+            let mut entry_values: Vec<Option<&Value>> = vec![];
+            for (value, failed) in values.iter().zip(&mut failed) {
+                match value.map(|v| v.as_object()) {
+                    Some(Some(object)) => {
+                        for (k, v) in object {
+                            keys.push(k);
+                            entry_values.push(Some(v));
+                        }
+                    }
+                    Some(None) => *failed = true,
+                    None => {}
+                }
+                offsets.push(keys.len() as i32);
+            }
+            let (map_values, child_failed) =
+                convert_values(&entry_values, entry_fields[1].data_type(), date_format)?;
+            propagate_child_failures(&mut failed, &offsets, &child_failed);
+            let entries = StructArray::try_new(
+                entry_fields.clone(),
+                vec![Arc::new(StringArray::from(keys)), map_values],
+                None,
+            )?;
+            Arc::new(MapArray::try_new(
+                entries_field.clone(),
+                OffsetBuffer::new(offsets.into()),
+                entries,
+                Some(nulls()),
+                *sorted,
+            )?)
+        }
+        other => df_execution_err!("unsupported json column type: {other}")?,
+    };
+    Ok((array, failed))
+}
+
+/// marks list values failed if any of their elements failed
+fn propagate_child_failures(failed: &mut [bool], offsets: &[i32], child_failed: &[bool]) {
+    for (i, failed) in failed.iter_mut().enumerate() {
+        let range = offsets[i] as usize..offsets[i + 1] as usize;
+        *failed |= child_failed[range].iter().any(|&f| f);
+    }
+}
+
+/// converts numbers and spark's non-numeric strings (like `NaN`) to floats
+fn to_float(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => match s.as_str() {
+            "NaN" => Some(f64::NAN),
+            "+INF" | "+Infinity" | "Infinity" => Some(f64::INFINITY),
+            "-INF" | "-Infinity" => Some(f64::NEG_INFINITY),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{Array, AsArray},
+        datatypes::{DataType, Field, Fields, Float64Type, Int32Type, Schema},
+    };
+    use datafusion::{assert_batches_eq, common::Result};
+
+    use crate::scan::{
+        json::{JsonBatchBuilder, JsonOptions},
+        text::{LineBatchBuilder, ParseMode},
+    };
+
+    #[test]
+    fn test_json_batch_builder() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, true),
+            Field::new("tags", DataType::new_list(DataType::Utf8, true), true),
+            Field::new(
+                "info",
+                DataType::Struct(Fields::from(vec![
+                    Field::new("score", DataType::Float64, true),
+                    Field::new("extra", DataType::Utf8, true),
+                ])),
+                true,
+            ),
+            Field::new("_corrupt_record", DataType::Utf8, true),
+        ]));
+        let lines = [
+            r#"{"id": 1, "tags": ["a", "b"], "info": {"score": 1.5, "extra": {"k": 1}}}"#,
+            r#"{"id": "x", "info": {"score": "NaN"}}"#,
+            r#"[{"id": 3, "tags": null}, {"id": 4, "unknown": true}]"#,
+            r#"{"id": 5"#,
+            r#"{"id": 6, "tags": [1], "info": {"score": true}}"#,
+        ];
+        let build = |mode, projection: &[usize]| {
+            let options = JsonOptions {
+                mode,
+                ..Default::default()
+            };
+            let mut builder = JsonBatchBuilder::try_new(&schema, projection, options)?;
+            for line in lines {
+                builder.append_line(line.to_string());
+            }
+            builder.build()
+        };
+
+        let (batch, num_malformed) = build(ParseMode::Permissive, &[0, 1, 2, 3])?;
+        assert_eq!(num_malformed, 3);
+        assert_eq!(batch.num_rows(), 6);
+        let ids = batch.column(0).as_primitive::<Int32Type>();
+        assert_eq!(
+            ids.iter().collect::<Vec<_>>(),
+            vec![Some(1), None, Some(3), Some(4), None, Some(6)],
+        );
+        let tags = batch.column(1).as_list::<i32>();
+        assert_eq!(
+            tags.value(0).as_string::<i32>().iter().collect::<Vec<_>>(),
+            [Some("a"), Some("b")]
+        );
+        assert_eq!(tags.value(5).as_string::<i32>().value(0), "1");
+        let info = batch.column(2).as_struct();
+        assert_eq!(info.column(0).as_primitive::<Float64Type>().value(0), 1.5);
+        assert!(info
+            .column(0)
+            .as_primitive::<Float64Type>()
+            .value(1)
+            .is_nan());
+        assert_eq!(info.column(1).as_string::<i32>().value(0), r#"{"k":1}"#);
+        assert!(info.column(0).is_null(5));
+        let corrupt_records = batch.column(3).as_string::<i32>();
+        assert_eq!(
+            corrupt_records.iter().collect::<Vec<_>>(),
+            vec![
+                None,
+                Some(lines[1]),
+                None,
+                None,
+                Some(lines[3]),
+                Some(lines[4]),
+            ]
+        );
+
+        let (batch, num_malformed) = build(ParseMode::DropMalformed, &[0])?;
+        assert_eq!(num_malformed, 3);
+        assert_batches_eq!(
+            vec![
+                "+----+", //
+                "| id |", "+----+", "| 1  |", "| 3  |", "| 4  |", "| 6  |", "+----+",
+            ],
+            &[batch]
+        );
+
+        assert!(build(ParseMode::FailFast, &[0]).is_err());
+        Ok(())
+    }
+}
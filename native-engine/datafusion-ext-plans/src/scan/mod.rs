@@ -15,64 +15,149 @@
 use std::{fmt::Debug, sync::Arc};
 
 use arrow::{
-    array::{new_null_array, Array, ArrayRef, AsArray, ListArray, RecordBatch, RecordBatchOptions},
-    datatypes::{DataType, Schema, SchemaRef},
+    array::{
+        new_null_array, Array, ArrayRef, AsArray, Int64Array, ListArray, RecordBatch,
+        RecordBatchOptions,
+    },
+    datatypes::{DataType, Field, Int64Type, Schema, SchemaRef, TimeUnit},
 };
 use datafusion::{
     common::Result,
     datasource::schema_adapter::{SchemaAdapter, SchemaAdapterFactory, SchemaMapper},
+    parquet::arrow::PARQUET_FIELD_ID_META_KEY,
 };
 use datafusion_ext_commons::df_execution_err;
 
+pub mod aggregate_pushdown;
+pub mod column_chunk_cache;
+pub mod csv;
+pub mod deletion_vector;
 pub mod internal_file_reader;
+pub mod json;
+pub mod partition_pruning;
+pub mod text;
 
 #[derive(Debug)]
-pub struct BlazeSchemaAdapterFactory;
+pub struct BlazeSchemaAdapterFactory {
+    column_resolver: ColumnResolver,
+}
+
+impl BlazeSchemaAdapterFactory {
+    pub fn new(column_resolver: ColumnResolver) -> Self {
+        Self { column_resolver }
+    }
+}
 
 impl SchemaAdapterFactory for BlazeSchemaAdapterFactory {
     fn create(&self, schema: SchemaRef) -> Box<dyn SchemaAdapter> {
-        Box::new(BlazeSchemaAdapter::new(schema))
+        Box::new(BlazeSchemaAdapter::new(schema, self.column_resolver))
+    }
+}
+
+/// Resolves file columns of table fields like spark's parquet reader. fields
+/// with parquet field ids are matched by id if `field_id_enabled`, others are
+/// matched by name.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ColumnResolver {
+    pub case_sensitive: bool,
+    pub field_id_enabled: bool,
+}
+
+impl ColumnResolver {
+    pub fn resolve(&self, table_field: &Field, file_schema: &Schema) -> Result<Option<usize>> {
+        if self.field_id_enabled {
+            if let Some(id) = parquet_field_id(table_field) {
+                return Ok(file_schema
+                    .fields()
+                    .iter()
+                    .position(|f| parquet_field_id(f) == Some(id)));
+            }
+        }
+        if self.case_sensitive {
+            return Ok(file_schema.index_of(table_field.name()).ok());
+        }
+
+        let mut matched = file_schema
+            .fields()
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| f.name().eq_ignore_ascii_case(table_field.name()));
+        match (matched.next(), matched.next()) {
+            (Some((idx, _)), None) => Ok(Some(idx)),
+            (None, _) => Ok(None),
+            (Some((_, f1)), Some((_, f2))) => df_execution_err!(
+                "found duplicate field(s) \"{}\": [{}, {}] in case-insensitive mode",
+                table_field.name(),
+                f1.name(),
+                f2.name(),
+            ),
+        }
     }
 }
 
+fn parquet_field_id(field: &Field) -> Option<i32> {
+    field
+        .metadata()
+        .get(PARQUET_FIELD_ID_META_KEY)
+        .and_then(|id| id.parse().ok())
+}
+
 pub struct BlazeSchemaAdapter {
     table_schema: SchemaRef,
+    column_resolver: ColumnResolver,
 }
 
 impl BlazeSchemaAdapter {
-    pub fn new(table_schema: SchemaRef) -> Self {
-        Self { table_schema }
+    pub fn new(table_schema: SchemaRef, column_resolver: ColumnResolver) -> Self {
+        Self {
+            table_schema,
+            column_resolver,
+        }
     }
 }
 
 impl SchemaAdapter for BlazeSchemaAdapter {
     fn map_column_index(&self, index: usize, file_schema: &Schema) -> Option<usize> {
         let field = self.table_schema.field(index);
-
-        // use case insensitive matching
-        file_schema
-            .fields
-            .iter()
-            .position(|f| f.name().eq_ignore_ascii_case(field.name()))
+        self.column_resolver
+            .resolve(field, file_schema)
+            .ok()
+            .flatten()
     }
 
     fn map_schema(&self, file_schema: &Schema) -> Result<(Arc<dyn SchemaMapper>, Vec<usize>)> {
-        let mut projection = Vec::with_capacity(file_schema.fields().len());
-        let mut field_mappings = vec![None; self.table_schema.fields().len()];
+        let mut mapped = vec![];
+        for (table_idx, table_field) in self.table_schema.fields().iter().enumerate() {
+            if let Some(file_idx) = self.column_resolver.resolve(table_field, file_schema)? {
+                let file_field = file_schema.field(file_idx);
+                if !is_supported_type_evolution(file_field.data_type(), table_field.data_type()) {
+                    return df_execution_err!(
+                        "column cannot be converted, column: {}, expected: {}, found: {}",
+                        table_field.name(),
+                        table_field.data_type(),
+                        file_field.data_type(),
+                    );
+                }
+                mapped.push((file_idx, table_idx));
+            }
+        }
 
-        for (file_idx, file_field) in file_schema.fields.iter().enumerate() {
-            if let Some((table_idx, _table_field)) =
-                self.table_schema.fields().find(file_field.name())
-            {
-                field_mappings[table_idx] = Some(projection.len());
+        // projection must be in the order of file columns
+        mapped.sort_unstable();
+        let mut projection = Vec::with_capacity(mapped.len());
+        let mut field_mappings = vec![None; self.table_schema.fields().len()];
+        for (file_idx, table_idx) in mapped {
+            if projection.last() != Some(&file_idx) {
                 projection.push(file_idx);
             }
+            field_mappings[table_idx] = Some(projection.len() - 1);
         }
 
         Ok((
             Arc::new(BlazeSchemaMapping {
                 table_schema: self.table_schema.clone(),
                 field_mappings,
+                column_resolver: self.column_resolver,
             }),
             projection,
         ))
@@ -83,6 +168,7 @@ impl SchemaAdapter for BlazeSchemaAdapter {
 pub struct BlazeSchemaMapping {
     table_schema: SchemaRef,
     field_mappings: Vec<Option<usize>>,
+    column_resolver: ColumnResolver,
 }
 
 impl BlazeSchemaMapping {
@@ -90,6 +176,7 @@ impl BlazeSchemaMapping {
         Self {
             table_schema,
             field_mappings,
+            column_resolver: ColumnResolver::default(),
         }
     }
 }
@@ -122,13 +209,19 @@ impl SchemaMapper for BlazeSchemaMapping {
         let batch_cols = batch.columns().to_vec();
         let schema = batch.schema();
 
+        // keep the order of file columns, columns not in table schema are dropped
         let mut cols = vec![];
         let mut fields = vec![];
-        for (i, f) in schema.fields().iter().enumerate() {
-            let table_field = self.table_schema.field_with_name(f.name());
-            if let Ok(tf) = table_field {
-                cols.push(schema_adapter_cast_column(&batch_cols[i], tf.data_type())?);
-                fields.push(tf.clone());
+        for i in 0..schema.fields().len() {
+            for table_field in self.table_schema.fields() {
+                if self.column_resolver.resolve(table_field, &schema)? == Some(i) {
+                    cols.push(schema_adapter_cast_column(
+                        &batch_cols[i],
+                        table_field.data_type(),
+                    )?);
+                    fields.push(table_field.clone());
+                    break;
+                }
             }
         }
 
@@ -139,6 +232,68 @@ impl SchemaMapper for BlazeSchemaMapping {
     }
 }
 
+/// returns whether file columns of the type can be read as the table type,
+/// like spark's vectorized parquet reader only lossless widening is supported.
+fn is_supported_type_evolution(from: &DataType, to: &DataType) -> bool {
+    // number of integral digits of integer types
+    let int_digits = |data_type: &DataType| match data_type {
+        DataType::Int8 | DataType::UInt8 => Some(3),
+        DataType::Int16 | DataType::UInt16 => Some(5),
+        DataType::Int32 | DataType::UInt32 => Some(10),
+        DataType::Int64 => Some(19),
+        DataType::UInt64 => Some(20),
+        _ => None,
+    };
+    let is_string_or_binary = |data_type: &DataType| {
+        matches!(
+            data_type,
+            DataType::Utf8 | DataType::LargeUtf8 | DataType::Binary | DataType::LargeBinary
+        )
+    };
+
+    match (from, to) {
+        (from, to) if from == to => true,
+        (_, DataType::Null) | (DataType::Null, _) => true,
+        (DataType::Dictionary(_, value_type), to) => is_supported_type_evolution(value_type, to),
+
+        // int32 columns are read as byte/short like spark
+        (DataType::Int32, DataType::Int8 | DataType::Int16) => true,
+        (DataType::Int8, DataType::Int16 | DataType::Int32 | DataType::Int64)
+        | (DataType::Int16, DataType::Int32 | DataType::Int64)
+        | (DataType::Int32, DataType::Int64)
+        | (DataType::UInt8, DataType::Int16 | DataType::Int32 | DataType::Int64)
+        | (DataType::UInt16, DataType::Int32 | DataType::Int64)
+        | (DataType::UInt32, DataType::Int64) => true,
+        (DataType::Float32, DataType::Float64) => true,
+        (from, &DataType::Decimal128(precision, scale)) if int_digits(from).is_some() => {
+            precision as i32 - scale as i32 >= int_digits(from).unwrap()
+        }
+        (&DataType::Decimal128(p1, s1), &DataType::Decimal128(p2, s2)) => {
+            s2 >= s1 && p2 as i32 - s2 as i32 >= p1 as i32 - s1 as i32
+        }
+        (from, to) if is_string_or_binary(from) && is_string_or_binary(to) => true,
+        (DataType::Timestamp(..), DataType::Timestamp(..)) => true,
+
+        (DataType::List(from) | DataType::LargeList(from), DataType::List(to)) => {
+            is_supported_type_evolution(from.data_type(), to.data_type())
+        }
+        (DataType::Map(from, _), DataType::Map(to, _)) => {
+            is_supported_type_evolution(from.data_type(), to.data_type())
+        }
+        // nested fields are matched by name, missing fields are filled with nulls
+        (DataType::Struct(from_fields), DataType::Struct(to_fields)) => {
+            to_fields.iter().all(|to| {
+                from_fields
+                    .iter()
+                    .find(|from| from.name() == to.name())
+                    .map(|from| is_supported_type_evolution(from.data_type(), to.data_type()))
+                    .unwrap_or(true)
+            })
+        }
+        _ => false,
+    }
+}
+
 fn schema_adapter_cast_column(col: &ArrayRef, data_type: &DataType) -> Result<ArrayRef> {
     macro_rules! handle_decimal {
         ($s:ident, $t:ident, $tnative:ty, $prec:expr, $scale:expr) => {{
@@ -147,10 +302,12 @@ fn schema_adapter_cast_column(col: &ArrayRef, data_type: &DataType) -> Result<Ar
             type IntType = paste::paste! {[<$s Type>]};
 
             let col = col.as_primitive::<IntType>();
+            let scale_factor = (10 as $tnative).pow($scale.max(0) as u32);
             let mut decimal_builder = DecimalBuilder::new();
             for i in 0..col.len() {
                 if col.is_valid(i) {
-                    decimal_builder.append_value(col.value(i) as $tnative);
+                    decimal_builder
+                        .append_option((col.value(i) as $tnative).checked_mul(scale_factor));
                 } else {
                     decimal_builder.append_null();
                 }
@@ -168,7 +325,13 @@ fn schema_adapter_cast_column(col: &ArrayRef, data_type: &DataType) -> Result<Ar
             DataType::Int16 => handle_decimal!(Int16, Decimal128, i128, *prec, *scale),
             DataType::Int32 => handle_decimal!(Int32, Decimal128, i128, *prec, *scale),
             DataType::Int64 => handle_decimal!(Int64, Decimal128, i128, *prec, *scale),
+            DataType::UInt8 => handle_decimal!(UInt8, Decimal128, i128, *prec, *scale),
+            DataType::UInt16 => handle_decimal!(UInt16, Decimal128, i128, *prec, *scale),
+            DataType::UInt32 => handle_decimal!(UInt32, Decimal128, i128, *prec, *scale),
+            DataType::UInt64 => handle_decimal!(UInt64, Decimal128, i128, *prec, *scale),
             DataType::Decimal128(p, s) if p == prec && s == scale => Ok(col.clone()),
+            // rescaled with HALF_UP rounding, null on overflow like spark
+            DataType::Decimal128(..) => Ok(arrow::compute::cast(col, data_type)?),
             _ => df_execution_err!(
                 "schema_adapter_cast_column unsupported type: {:?} => {:?}",
                 col.data_type(),
@@ -193,6 +356,151 @@ fn schema_adapter_cast_column(col: &ArrayRef, data_type: &DataType) -> Result<Ar
                 data_type,
             ),
         },
+        DataType::Timestamp(to_unit, _) => match col.data_type() {
+            DataType::Timestamp(unit, _) if unit != to_unit => {
+                cast_timestamp_unit(col, unit, to_unit, data_type)
+            }
+            _ => {
+                datafusion_ext_commons::arrow::cast::cast_scan_input_array(col.as_ref(), data_type)
+            }
+        },
         _ => datafusion_ext_commons::arrow::cast::cast_scan_input_array(col.as_ref(), data_type),
     }
 }
+
+/// converts timestamps to another unit, finer values are truncated with floor
+/// division like spark (arrow truncates towards zero)
+fn cast_timestamp_unit(
+    col: &ArrayRef,
+    unit: &TimeUnit,
+    to_unit: &TimeUnit,
+    data_type: &DataType,
+) -> Result<ArrayRef> {
+    let units_per_second = |unit: &TimeUnit| match unit {
+        TimeUnit::Second => 1i64,
+        TimeUnit::Millisecond => 1_000,
+        TimeUnit::Microsecond => 1_000_000,
+        TimeUnit::Nanosecond => 1_000_000_000,
+    };
+    let (units, to_units) = (units_per_second(unit), units_per_second(to_unit));
+    let values = arrow::compute::cast(col, &DataType::Int64)?;
+    let values = values.as_primitive::<Int64Type>();
+    let converted: Int64Array = if to_units >= units {
+        values.unary_opt(|v| v.checked_mul(to_units / units))
+    } else {
+        values.unary(|v| v.div_euclid(units / to_units))
+    };
+    Ok(arrow::compute::cast(&converted, data_type)?)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, sync::Arc};
+
+    use arrow::{
+        array::{Decimal128Array, Float32Array, Int32Array, RecordBatch},
+        datatypes::{DataType, Field, Schema},
+    };
+    use datafusion::{
+        assert_batches_eq, common::Result, datasource::schema_adapter::SchemaAdapter,
+        parquet::arrow::PARQUET_FIELD_ID_META_KEY,
+    };
+
+    use crate::scan::{BlazeSchemaAdapter, ColumnResolver};
+
+    #[test]
+    fn test_schema_evolution() -> Result<()> {
+        let file_schema = Arc::new(Schema::new(vec![
+            Field::new("ID", DataType::Int32, true),
+            Field::new("score", DataType::Float32, true),
+            Field::new("amount", DataType::Decimal128(10, 2), true),
+            Field::new("unused", DataType::Utf8, true),
+        ]));
+        let table_schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, true),
+            Field::new("amount", DataType::Decimal128(12, 3), true),
+            Field::new("score", DataType::Float64, true),
+            Field::new("added", DataType::Utf8, true),
+        ]));
+
+        // case insensitive by default, missing columns are filled with nulls
+        let adapter = BlazeSchemaAdapter::new(table_schema.clone(), ColumnResolver::default());
+        let (mapping, projection) = adapter.map_schema(&file_schema)?;
+        assert_eq!(projection, vec![0, 1, 2]);
+        let batch = RecordBatch::try_new(
+            Arc::new(file_schema.project(&projection)?),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2])),
+                Arc::new(Float32Array::from(vec![0.5, 1.5])),
+                Arc::new(Decimal128Array::from(vec![123, -45]).with_precision_and_scale(10, 2)?),
+            ],
+        )?;
+        assert_batches_eq!(
+            vec![
+                "+----+--------+-------+-------+",
+                "| id | amount | score | added |",
+                "+----+--------+-------+-------+",
+                "| 1  | 1.230  | 0.5   |       |",
+                "| 2  | -0.450 | 1.5   |       |",
+                "+----+--------+-------+-------+",
+            ],
+            &[mapping.map_batch(batch)?]
+        );
+
+        // case sensitive
+        let column_resolver = ColumnResolver {
+            case_sensitive: true,
+            field_id_enabled: false,
+        };
+        let adapter = BlazeSchemaAdapter::new(table_schema.clone(), column_resolver);
+        assert_eq!(adapter.map_schema(&file_schema)?.1, vec![1, 2]);
+
+        // duplicated fields in case insensitive mode
+        let duplicated_schema = Schema::new(vec![
+            Field::new("id", DataType::Int32, true),
+            Field::new("Id", DataType::Int32, true),
+        ]);
+        let adapter = BlazeSchemaAdapter::new(table_schema.clone(), ColumnResolver::default());
+        assert!(adapter.map_schema(&duplicated_schema).is_err());
+
+        // narrowing is not supported
+        let narrowed_schema =
+            Schema::new(vec![Field::new("id", DataType::Decimal128(20, 0), true)]);
+        assert!(adapter.map_schema(&narrowed_schema).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_by_field_id() -> Result<()> {
+        let with_id = |field: Field, id: i32| {
+            field.with_metadata(HashMap::from([(
+                PARQUET_FIELD_ID_META_KEY.to_string(),
+                id.to_string(),
+            )]))
+        };
+        let file_schema = Schema::new(vec![
+            with_id(Field::new("a", DataType::Int64, true), 1),
+            with_id(Field::new("b", DataType::Int64, true), 2),
+            Field::new("c", DataType::Int64, true),
+        ]);
+        let table_schema = Arc::new(Schema::new(vec![
+            with_id(Field::new("renamed_a", DataType::Int64, true), 1),
+            with_id(Field::new("b", DataType::Int64, true), 3),
+            Field::new("c", DataType::Int64, true),
+        ]));
+        let column_resolver = ColumnResolver {
+            case_sensitive: false,
+            field_id_enabled: true,
+        };
+        let adapter = BlazeSchemaAdapter::new(table_schema.clone(), column_resolver);
+        assert_eq!(adapter.map_column_index(0, &file_schema), Some(0));
+        assert_eq!(adapter.map_column_index(1, &file_schema), None);
+        assert_eq!(adapter.map_column_index(2, &file_schema), Some(2));
+        assert_eq!(adapter.map_schema(&file_schema)?.1, vec![0, 2]);
+
+        // matched by name if disabled
+        let adapter = BlazeSchemaAdapter::new(table_schema, ColumnResolver::default());
+        assert_eq!(adapter.map_schema(&file_schema)?.1, vec![1, 2]);
+        Ok(())
+    }
+}
@@ -0,0 +1,291 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+
+use arrow::{
+    array::{new_empty_array, Array, ArrayRef, AsArray, RecordBatch, StructArray, UInt32Array},
+    compute::{concat, filter, filter_record_batch, prep_null_mask_filter},
+    datatypes::{DataType, Field, Fields, UInt32Type},
+    ffi::{from_ffi_and_data_type, FFI_ArrowArray},
+};
+use blaze_jni_bridge::{jni_call, jni_call_static, jni_new_global_ref, jni_new_string};
+use datafusion::{
+    common::{Result, ScalarValue},
+    datasource::{listing::PartitionedFile, physical_plan::FileScanConfig},
+    physical_expr::PhysicalExprRef,
+    physical_plan::metrics::Count,
+};
+use datafusion_ext_commons::df_execution_err;
+
+/// Evaluates partition pruning predicates on partition values, each row of
+/// `partition_values` is the values of one partition. returns indices of the
+/// partitions satisfying all predicates.
+///
+/// like spark's partition pruning, a partition is pruned if any predicate is
+/// evaluated to null. predicates are evaluated one by one, only on the
+/// partitions surviving the previous predicates.
+pub fn prune_partitions(
+    pruning_predicates: &[PhysicalExprRef],
+    partition_values: &RecordBatch,
+) -> Result<Vec<u32>> {
+    let mut survived_indices = UInt32Array::from_iter_values(0..partition_values.num_rows() as u32);
+    let mut survived_values = partition_values.clone();
+
+    for predicate in pruning_predicates {
+        if survived_values.num_rows() == 0 {
+            break;
+        }
+        let selected = predicate
+            .evaluate(&survived_values)?
+            .into_array(survived_values.num_rows())?;
+        if selected.data_type() != &DataType::Boolean {
+            return df_execution_err!(
+                "partition pruning predicate must return boolean values, got {}",
+                selected.data_type()
+            );
+        }
+        let selected = prep_null_mask_filter(selected.as_boolean());
+        if selected.true_count() == selected.len() {
+            continue;
+        }
+        survived_values = filter_record_batch(&survived_values, &selected)?;
+        survived_indices = filter(&survived_indices, &selected)?
+            .as_primitive::<UInt32Type>()
+            .clone();
+    }
+    Ok(survived_indices.values().to_vec())
+}
+
+/// Dynamic partition pruning filter. partitions are pruned if the value of
+/// the partition column is not one of the surviving values, which are known
+/// only at runtime (typically the join keys of a broadcast build side) and
+/// exported from jvm through an `ArrowFFIExporter` jni bridge resource.
+#[derive(Debug, Clone)]
+pub struct DynamicPartitionFilter {
+    pub partition_column: usize,
+    pub resource_id: String,
+}
+
+/// Prunes files of the partition with dynamic partition filters, returns the
+/// scan config with only the surviving files.
+pub fn prune_dynamic_partitions(
+    base_config: &FileScanConfig,
+    partition: usize,
+    filters: &[DynamicPartitionFilter],
+    pruned_files: &Count,
+) -> Result<FileScanConfig> {
+    let mut base_config = base_config.clone();
+    for filter in filters {
+        let Some(files) = base_config.file_groups.get_mut(partition) else {
+            break;
+        };
+        if files.is_empty() {
+            break;
+        }
+        let Some(field) = base_config
+            .table_partition_cols
+            .get(filter.partition_column)
+        else {
+            return df_execution_err!(
+                "dynamic partition filter column out of range: {}",
+                filter.partition_column
+            );
+        };
+        let surviving_values = read_surviving_values(&filter.resource_id, field.data_type())?;
+        let num_files = files.len();
+        *files = filter_files_by_partition_values(
+            std::mem::take(files),
+            filter.partition_column,
+            &surviving_values,
+        )?;
+        pruned_files.add(num_files - files.len());
+        log::info!(
+            "dynamic partition pruning on {}: {} of {num_files} files survived",
+            field.name(),
+            files.len(),
+        );
+    }
+    Ok(base_config)
+}
+
+/// imports all surviving values from the jvm exporter
+fn read_surviving_values(resource_id: &str, data_type: &DataType) -> Result<ArrayRef> {
+    let resource_id = jni_new_string!(resource_id)?;
+    let exporter = jni_new_global_ref!(
+        jni_call_static!(JniBridge.getResource(resource_id.as_obj()) -> JObject)?.as_obj()
+    )?;
+    let import_data_type =
+        DataType::Struct(Fields::from(vec![Field::new("v", data_type.clone(), true)]));
+
+    let mut arrays = vec![];
+    loop {
+        let mut ffi_arrow_array = FFI_ArrowArray::empty();
+        let ffi_arrow_array_ptr = &mut ffi_arrow_array as *mut FFI_ArrowArray as i64;
+        let has_next = jni_call!(
+            BlazeArrowFFIExporter(exporter.as_obj()).exportNextBatch(ffi_arrow_array_ptr) -> bool
+        )?;
+        if !has_next {
+            break;
+        }
+        let imported =
+            unsafe { from_ffi_and_data_type(ffi_arrow_array, import_data_type.clone())? };
+        arrays.push(StructArray::from(imported).column(0).clone());
+    }
+    if arrays.is_empty() {
+        return Ok(new_empty_array(data_type));
+    }
+    Ok(concat(
+        &arrays
+            .iter()
+            .map(|array| array.as_ref())
+            .collect::<Vec<_>>(),
+    )?)
+}
+
+/// keeps files whose value of the partition column is one of the surviving
+/// values. like spark's dynamic pruning, null partition values never survive.
+fn filter_files_by_partition_values(
+    files: Vec<PartitionedFile>,
+    partition_column: usize,
+    surviving_values: &ArrayRef,
+) -> Result<Vec<PartitionedFile>> {
+    let mut surviving = HashSet::new();
+    for i in 0..surviving_values.len() {
+        if surviving_values.is_valid(i) {
+            surviving.insert(ScalarValue::try_from_array(surviving_values, i)?);
+        }
+    }
+    Ok(files
+        .into_iter()
+        .filter(|file| {
+            let value = &file.partition_values[partition_column];
+            !value.is_null() && surviving.contains(value)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{ArrayRef, Int32Array, RecordBatch, StringArray},
+        datatypes::{DataType, Field, Schema},
+    };
+    use datafusion::{
+        common::{Result, ScalarValue},
+        datasource::listing::PartitionedFile,
+        logical_expr::Operator,
+        physical_expr::expressions::{binary, col, lit},
+    };
+
+    use crate::scan::partition_pruning::{filter_files_by_partition_values, prune_partitions};
+
+    #[test]
+    fn test_prune_partitions() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("dt", DataType::Utf8, true),
+            Field::new("hour", DataType::Int32, true),
+        ]));
+        let partition_values = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec![
+                    Some("2024-01-01"),
+                    Some("2024-01-01"),
+                    Some("2024-01-02"),
+                    Some("2024-01-02"),
+                    None,
+                ])),
+                Arc::new(Int32Array::from(vec![
+                    Some(1),
+                    Some(12),
+                    Some(5),
+                    None,
+                    Some(20),
+                ])),
+            ] as Vec<ArrayRef>,
+        )?;
+
+        // dt = '2024-01-02' and hour < 10
+        let predicates = vec![
+            binary(
+                col("dt", &schema)?,
+                Operator::Eq,
+                lit("2024-01-02"),
+                &schema,
+            )?,
+            binary(col("hour", &schema)?, Operator::Lt, lit(10), &schema)?,
+        ];
+        assert_eq!(prune_partitions(&predicates, &partition_values)?, vec![2]);
+
+        // hour > 3, null values are pruned
+        let predicates = vec![binary(
+            col("hour", &schema)?,
+            Operator::Gt,
+            lit(3),
+            &schema,
+        )?];
+        assert_eq!(
+            prune_partitions(&predicates, &partition_values)?,
+            vec![1, 2, 4]
+        );
+
+        // no predicates
+        assert_eq!(
+            prune_partitions(&[], &partition_values)?,
+            vec![0, 1, 2, 3, 4]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter_files_by_partition_values() -> Result<()> {
+        let file = |path: &str, dt: Option<&str>, hour: i32| {
+            let mut file = PartitionedFile::new(path, 100);
+            file.partition_values = vec![ScalarValue::from(dt), ScalarValue::from(hour)];
+            file
+        };
+        let files = vec![
+            file("f0", Some("2024-01-01"), 1),
+            file("f1", Some("2024-01-02"), 2),
+            file("f2", None, 3),
+            file("f3", Some("2024-01-03"), 2),
+        ];
+        let paths = |files: Vec<PartitionedFile>| {
+            files
+                .iter()
+                .map(|file| file.object_meta.location.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let surviving_dts: ArrayRef = Arc::new(StringArray::from(vec![
+            Some("2024-01-02"),
+            None,
+            Some("2024-01-03"),
+            Some("2024-01-05"),
+        ]));
+        let survived = filter_files_by_partition_values(files.clone(), 0, &surviving_dts)?;
+        assert_eq!(paths(survived), vec!["f1", "f3"]);
+
+        let surviving_hours: ArrayRef = Arc::new(Int32Array::from(vec![1, 3]));
+        let survived = filter_files_by_partition_values(files.clone(), 1, &surviving_hours)?;
+        assert_eq!(paths(survived), vec!["f0", "f2"]);
+
+        let no_hours: ArrayRef = Arc::new(Int32Array::from(Vec::<i32>::new()));
+        assert!(filter_files_by_partition_values(files, 1, &no_hours)?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,369 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Shared parsing of line-delimited text formats (csv and json lines),
+//! compatible with spark's text-based data sources.
+
+use std::{ops::Range, sync::Arc};
+
+use arrow::array::RecordBatch;
+use bytes::Bytes;
+use datafusion::{common::Result, physical_plan::metrics::Count};
+use datafusion_ext_commons::df_execution_err;
+
+use crate::scan::internal_file_reader::InternalFileReader;
+
+/// size of blocks read from text files
+const READ_BLOCK_SIZE: usize = 4 << 20;
+
+pub const DEFAULT_DATE_FORMAT: &str = "yyyy-MM-dd";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseMode {
+    /// sets malformed fields to null and puts malformed records into the
+    /// corrupt record column
+    Permissive,
+    /// drops malformed records
+    DropMalformed,
+    /// fails on malformed records
+    FailFast,
+}
+
+/// parses floats like java's `toDouble`, with spark's default nan/inf values
+pub fn parse_float(value: &str) -> Option<f64> {
+    match value {
+        "NaN" => Some(f64::NAN),
+        "Inf" | "+Inf" | "Infinity" | "+Infinity" => Some(f64::INFINITY),
+        "-Inf" | "-Infinity" => Some(f64::NEG_INFINITY),
+        _ if value
+            .bytes()
+            .any(|b| b.is_ascii_alphabetic() && !matches!(b, b'e' | b'E')) =>
+        {
+            None
+        }
+        _ => value.parse().ok(),
+    }
+}
+
+/// parses decimals like java's `BigDecimal`, rounded to the scale with
+/// HALF_UP rounding. returns `None` if the value exceeds the precision.
+pub fn parse_decimal(value: &str, precision: u8, scale: i8) -> Option<i128> {
+    let (negative, value) = match value.as_bytes().first() {
+        Some(b'-') => (true, &value[1..]),
+        Some(b'+') => (false, &value[1..]),
+        _ => (false, value),
+    };
+    let (mantissa, exponent) = match value.find(['e', 'E']) {
+        Some(pos) => (&value[..pos], value[pos + 1..].parse::<i32>().ok()?),
+        None => (value, 0),
+    };
+    let (int_part, frac_part) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    if int_part.is_empty() && frac_part.is_empty() {
+        return None;
+    }
+
+    let mut unscaled: i128 = 0;
+    for b in int_part.bytes().chain(frac_part.bytes()) {
+        if !b.is_ascii_digit() {
+            return None;
+        }
+        unscaled = unscaled.checked_mul(10)?.checked_add((b - b'0') as i128)?;
+    }
+
+    // unscaled * 10^shift is the value at the target scale
+    let shift = exponent as i64 - frac_part.len() as i64 + scale as i64;
+    let unscaled = if shift >= 0 {
+        unscaled.checked_mul(10i128.checked_pow(shift as u32)?)?
+    } else {
+        match 10i128.checked_pow((-shift) as u32) {
+            Some(factor) => unscaled / factor + (unscaled % factor >= (factor + 1) / 2) as i128,
+            None => 0,
+        }
+    };
+    if unscaled >= 10i128.pow(precision as u32) {
+        return None;
+    }
+    Some(if negative { -unscaled } else { unscaled })
+}
+
+/// Date patterns of spark's `dateFormat`, only year, month and day fields
+/// are supported
+#[derive(Debug)]
+pub struct DateFormat {
+    items: Vec<DateFormatItem>,
+}
+
+#[derive(Debug, PartialEq)]
+enum DateFormatItem {
+    Year(usize),
+    Month(usize),
+    Day(usize),
+    Literal(String),
+}
+
+impl DateFormat {
+    pub fn try_new(pattern: &str) -> Result<Self> {
+        let lenient = pattern == DEFAULT_DATE_FORMAT;
+        let mut items = vec![];
+        let mut chars = pattern.chars().peekable();
+        while let Some(ch) = chars.next() {
+            match ch {
+                'y' | 'M' | 'd' => {
+                    let mut count = 1;
+                    while chars.next_if_eq(&ch).is_some() {
+                        count += 1;
+                    }
+                    // the default format accepts single-digit month and day
+                    let count = if lenient && ch != 'y' { 1 } else { count };
+                    items.push(match ch {
+                        'y' => DateFormatItem::Year(count),
+                        'M' => DateFormatItem::Month(count),
+                        _ => DateFormatItem::Day(count),
+                    });
+                }
+                '\'' => {
+                    let mut literal = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('\'') if chars.next_if_eq(&'\'').is_some() => literal.push('\''),
+                            Some('\'') => break,
+                            Some(ch) => literal.push(ch),
+                            None => return df_execution_err!("unclosed quote in date format"),
+                        }
+                    }
+                    items.push(DateFormatItem::Literal(literal));
+                }
+                ch if ch.is_ascii_alphabetic() => {
+                    return df_execution_err!("unsupported csv date format: {pattern}");
+                }
+                ch => items.push(DateFormatItem::Literal(ch.to_string())),
+            }
+        }
+        Ok(Self { items })
+    }
+
+    /// parses date to days since epoch
+    pub fn parse(&self, value: &str) -> Option<i32> {
+        let (mut year, mut month, mut day) = (1970i32, 1i32, 1i32);
+        let mut rest = value;
+        for item in &self.items {
+            let (min_digits, max_digits, field) = match item {
+                DateFormatItem::Literal(literal) => {
+                    rest = rest.strip_prefix(literal.as_str())?;
+                    continue;
+                }
+                DateFormatItem::Year(2) => (2, 2, &mut year),
+                DateFormatItem::Year(count) => (*count, (*count).max(4), &mut year),
+                DateFormatItem::Month(count) => (*count, 2, &mut month),
+                DateFormatItem::Day(count) => (*count, 2, &mut day),
+            };
+            let num_digits = rest
+                .bytes()
+                .take(max_digits)
+                .take_while(|b| b.is_ascii_digit())
+                .count();
+            if num_digits < min_digits {
+                return None;
+            }
+            *field = rest[..num_digits].parse().ok()?;
+            rest = &rest[num_digits..];
+        }
+        if !rest.is_empty() {
+            return None;
+        }
+        if self.items.contains(&DateFormatItem::Year(2)) {
+            year += 2000;
+        }
+        days_from_civil(year, month as u32, day as u32)
+    }
+}
+
+/// returns days since epoch of the date in proleptic gregorian calendar
+fn days_from_civil(year: i32, month: u32, day: u32) -> Option<i32> {
+    let is_leap_year = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap_year => 29,
+        2 => 28,
+        _ => return None,
+    };
+    if day == 0 || day > days_in_month {
+        return None;
+    }
+
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month_from_march = (month as i32 + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day as i32 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Some(era * 146097 + day_of_era - 719468)
+}
+
+/// Builds record batches from lines of a text format
+pub trait LineBatchBuilder: Send + 'static {
+    fn num_rows(&self) -> usize;
+
+    fn append_line(&mut self, line: String);
+
+    /// builds a batch of appended lines, returns the batch and number of
+    /// malformed records
+    fn build(&mut self) -> Result<(RecordBatch, usize)>;
+}
+
+/// reads lines of the file split into batches, empty lines are ignored like
+/// spark
+pub fn read_line_batches(
+    mut line_reader: LineReader,
+    mut batch_builder: impl LineBatchBuilder,
+    batch_size: usize,
+    mut skip_header: bool,
+    malformed_records: Count,
+) -> impl Iterator<Item = Result<RecordBatch>> + Send {
+    let mut finished = false;
+    let mut next_batch = move || -> Result<Option<RecordBatch>> {
+        while !finished && batch_builder.num_rows() < batch_size {
+            let Some(line) = line_reader.next_split_line()? else {
+                finished = true;
+                break;
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            if std::mem::take(&mut skip_header) {
+                continue;
+            }
+            batch_builder.append_line(line);
+        }
+        if batch_builder.num_rows() == 0 {
+            return Ok(None);
+        }
+        let (batch, num_malformed) = batch_builder.build()?;
+        malformed_records.add(num_malformed);
+        Ok(Some(batch))
+    };
+    std::iter::from_fn(move || next_batch().transpose())
+}
+
+/// Reads lines of a file split. like hadoop's line reader, a split reads
+/// lines starting within or at the end of the split.
+pub struct LineReader {
+    reader: Arc<InternalFileReader>,
+    file_size: usize,
+    split_end: usize,
+    next_read_pos: usize,
+    next_line_pos: usize,
+    buf: Bytes,
+    pending: Vec<u8>,
+    bytes_scanned: Count,
+}
+
+impl LineReader {
+    /// creates a reader of the file split, the first partial line is skipped
+    /// since it is read by the previous split
+    pub fn try_new(
+        reader: Arc<InternalFileReader>,
+        range: Range<usize>,
+        bytes_scanned: Count,
+    ) -> Result<Self> {
+        let mut line_reader = Self {
+            file_size: reader.get_meta().size,
+            reader,
+            split_end: range.end,
+            next_read_pos: range.start,
+            next_line_pos: range.start,
+            buf: Bytes::new(),
+            pending: vec![],
+            bytes_scanned,
+        };
+        if range.start > 0 {
+            line_reader.next_line()?;
+        }
+        Ok(line_reader)
+    }
+
+    /// reads the next line starting in the split
+    pub fn next_split_line(&mut self) -> Result<Option<String>> {
+        if self.next_line_pos > self.split_end {
+            return Ok(None);
+        }
+        self.next_line()
+    }
+
+    /// reads the next line, trailing `\r` is removed
+    fn next_line(&mut self) -> Result<Option<String>> {
+        loop {
+            if let Some(newline_pos) = self.buf.iter().position(|&b| b == b'\n') {
+                let line_bytes = self.buf.split_to(newline_pos + 1);
+                self.pending.extend_from_slice(&line_bytes[..newline_pos]);
+                return Ok(Some(self.take_pending_line()));
+            }
+            self.pending
+                .extend_from_slice(&std::mem::take(&mut self.buf));
+
+            if self.next_read_pos >= self.file_size {
+                if self.pending.is_empty() {
+                    return Ok(None);
+                }
+                return Ok(Some(self.take_pending_line()));
+            }
+            let read_end = (self.next_read_pos + READ_BLOCK_SIZE).min(self.file_size);
+            self.buf = self.reader.read_fully(self.next_read_pos..read_end)?;
+            self.bytes_scanned.add(self.buf.len());
+            self.next_read_pos = read_end;
+        }
+    }
+
+    fn take_pending_line(&mut self) -> String {
+        let mut line = std::mem::take(&mut self.pending);
+        self.next_line_pos += line.len() + 1;
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        String::from_utf8(line)
+            .unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use datafusion::common::Result;
+
+    use crate::scan::text::{parse_decimal, DateFormat};
+
+    #[test]
+    fn test_parse_values() -> Result<()> {
+        assert_eq!(parse_decimal("123.456", 10, 2), Some(12346));
+        assert_eq!(parse_decimal("-1.005", 10, 2), Some(-101));
+        assert_eq!(parse_decimal("1.2e3", 10, 0), Some(1200));
+        assert_eq!(parse_decimal(".5", 10, 0), Some(1));
+        assert_eq!(parse_decimal("1000", 3, 0), None);
+        assert_eq!(parse_decimal("1a", 10, 0), None);
+        assert_eq!(parse_decimal("-", 10, 0), None);
+
+        let date_format = DateFormat::try_new("yyyy-MM-dd")?;
+        assert_eq!(date_format.parse("1970-01-01"), Some(0));
+        assert_eq!(date_format.parse("2000-3-1"), Some(11017));
+        assert_eq!(date_format.parse("1969-12-31"), Some(-1));
+        assert_eq!(date_format.parse("2023-02-29"), None);
+        assert_eq!(date_format.parse("2023-01-01x"), None);
+
+        let date_format = DateFormat::try_new("dd/MM/yyyy 'day'")?;
+        assert_eq!(date_format.parse("01/03/2000 day"), Some(11017));
+        assert_eq!(date_format.parse("1/03/2000 day"), None);
+        assert!(DateFormat::try_new("yyyy-MM-dd HH:mm").is_err());
+        Ok(())
+    }
+}
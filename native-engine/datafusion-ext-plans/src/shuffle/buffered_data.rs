@@ -18,10 +18,7 @@ use arrow::record_batch::RecordBatch;
 use blaze_jni_bridge::{is_task_running, jni_call};
 use bytesize::ByteSize;
 use count_write::CountWrite;
-use datafusion::{
-    common::Result,
-    physical_plan::{metrics::Time, Partitioning},
-};
+use datafusion::{common::Result, physical_plan::metrics::Time};
 use datafusion_ext_commons::{
     algorithm::{
         rdx_tournament_tree::{KeyForRadixTournamentTree, RadixTournamentTree},
@@ -39,12 +36,14 @@ use crate::{
     common::{ipc_compression::IpcCompressionWriter, timer_helper::TimerHelper},
     shuffle::{
         evaluate_hashes, evaluate_partition_ids, evaluate_robin_partition_ids, rss::RssWriter,
+        ShufflePartitioning,
     },
 };
 
+#[derive(Clone)]
 pub struct BufferedData {
     partition_id: usize,
-    partitioning: Partitioning,
+    partitioning: ShufflePartitioning,
     staging_batches: Vec<RecordBatch>,
     staging_num_rows: usize,
     staging_mem_used: usize,
@@ -56,7 +55,7 @@ pub struct BufferedData {
 }
 
 impl BufferedData {
-    pub fn new(partitioning: Partitioning, partition_id: usize, sort_time: Time) -> Self {
+    pub fn new(partitioning: ShufflePartitioning, partition_id: usize, sort_time: Time) -> Self {
         Self {
             partition_id,
             partitioning,
@@ -96,6 +95,15 @@ impl BufferedData {
         Ok(())
     }
 
+    /// sorts all staging batches, after which the data can be cheaply cloned
+    /// for rewriting
+    pub fn flush_all_staging(&mut self) -> Result<()> {
+        if !self.staging_batches.is_empty() {
+            self.flush_staging()?;
+        }
+        Ok(())
+    }
+
     fn flush_staging(&mut self) -> Result<()> {
         let sorted_num_rows = self.num_rows - self.staging_num_rows;
         let staging_batches = std::mem::take(&mut self.staging_batches);
@@ -310,7 +318,7 @@ impl KeyForRadixTournamentTree for PartCursor {
 
 fn sort_batches_by_partition_id(
     batches: Vec<RecordBatch>,
-    partitioning: &Partitioning,
+    partitioning: &ShufflePartitioning,
     current_num_rows: usize,
     partition_id: usize,
 ) -> Result<(Vec<u32>, RecordBatch)> {
@@ -326,13 +334,18 @@ fn sort_batches_by_partition_id(
             let part_ids: Vec<u32>;
 
             match partitioning {
-                Partitioning::Hash(..) => {
+                ShufflePartitioning::Hash(..) => {
                     // compute partition indices
                     let hashes = evaluate_hashes(partitioning, &batch)
-                        .expect(&format!("error evaluating hashes with {partitioning}"));
+                        .expect(&format!("error evaluating hashes with {partitioning:?}"));
                     part_ids = evaluate_partition_ids(hashes, partitioning.partition_count());
                 }
-                Partitioning::RoundRobinBatch(..) => {
+                ShufflePartitioning::Range(range) => {
+                    part_ids = range
+                        .evaluate_partition_ids(&batch)
+                        .expect("error evaluating range partition ids");
+                }
+                ShufflePartitioning::RoundRobin(..) => {
                     part_ids =
                         evaluate_robin_partition_ids(partitioning, &batch, round_robin_start_rows);
                     round_robin_start_rows += batch.num_rows();
@@ -384,7 +397,7 @@ mod test {
         datatypes::{DataType, Field, Schema},
         record_batch::RecordBatch,
     };
-    use datafusion::{assert_batches_eq, common::Result, physical_expr::Partitioning};
+    use datafusion::{assert_batches_eq, common::Result};
 
     use super::*;
 
@@ -418,7 +431,7 @@ mod test {
             ("c", &vec![5, 6, 7, 8, 9, 0, 1, 2, 3, 4]),
         );
 
-        let round_robin_partitioning = Partitioning::RoundRobinBatch(4);
+        let round_robin_partitioning = ShufflePartitioning::RoundRobin(4);
         let (_parts, sorted_batch) =
             sort_batches_by_partition_id(vec![record_batch], &round_robin_partitioning, 3, 0)?;
 
@@ -0,0 +1,386 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Registry of shuffle outputs and task results served by the native flight
+//! server.
+//!
+//! shuffle outputs are registered by shuffle writers and looked up by
+//! shuffle/map ids, so fetch requests never contain file paths. an output
+//! becomes fetchable after spark commits it to the final data/index files.
+//!
+//! result streams are registered by tasks whose outputs are streamed to flight
+//! clients instead of the jvm, each stream can be taken only once.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering::SeqCst},
+        Arc,
+    },
+    time::Duration,
+};
+
+use arrow::{
+    array::{RecordBatch, RecordBatchOptions},
+    datatypes::SchemaRef,
+};
+use byteorder::{BigEndian, ReadBytesExt};
+use datafusion::common::Result;
+use datafusion_ext_commons::df_execution_err;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+
+use crate::common::ipc_compression::IpcCompressionReader;
+
+/// stale entries are pruned when the registry grows over this size
+const PRUNE_THRESHOLD: usize = 4096;
+
+/// number of result batches buffered before a flight client fetches them
+const RESULT_STREAM_BUFFER_SIZE: usize = 4;
+
+/// result streams fail if no batch is fetched by flight clients in this time
+const RESULT_STREAM_FETCH_TIMEOUT: Duration = Duration::from_secs(600);
+
+#[derive(Debug)]
+pub struct RegisteredShuffleOutput {
+    pub schema: SchemaRef,
+    pub data_file: PathBuf,
+    pub index_file: PathBuf,
+}
+
+impl RegisteredShuffleOutput {
+    fn is_committed(&self) -> bool {
+        self.data_file.exists() && self.index_file.exists()
+    }
+
+    /// opens a reader of a reduce partition, which reads batches lazily.
+    pub fn read_partition(&self, reduce_id: usize) -> Result<ShufflePartitionReader> {
+        let (offset, len) = read_partition_range(&self.index_file, reduce_id)?;
+        let mut data_file = File::open(&self.data_file)?;
+        data_file.seek(SeekFrom::Start(offset))?;
+
+        let input: Box<dyn Read + Send> = Box::new(BufReader::new(data_file.take(len)));
+        Ok(ShufflePartitionReader {
+            schema: self.schema.clone(),
+            reader: IpcCompressionReader::new(input),
+        })
+    }
+}
+
+pub struct ShufflePartitionReader {
+    schema: SchemaRef,
+    reader: IpcCompressionReader<Box<dyn Read + Send>>,
+}
+
+impl ShufflePartitionReader {
+    fn read_batch(&mut self) -> Result<Option<RecordBatch>> {
+        let Some((num_rows, cols)) = self.reader.read_batch(&self.schema)? else {
+            return Ok(None);
+        };
+        let batch = RecordBatch::try_new_with_options(
+            self.schema.clone(),
+            cols,
+            &RecordBatchOptions::new().with_row_count(Some(num_rows)),
+        )?;
+        Ok(Some(batch))
+    }
+}
+
+impl Iterator for ShufflePartitionReader {
+    type Item = Result<RecordBatch>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_batch().transpose()
+    }
+}
+
+fn registry() -> &'static Mutex<HashMap<(u64, u64), Arc<RegisteredShuffleOutput>>> {
+    static REGISTRY: OnceCell<Mutex<HashMap<(u64, u64), Arc<RegisteredShuffleOutput>>>> =
+        OnceCell::new();
+    REGISTRY.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// registers the output of a shuffle writer. `output_data_file` is the
+/// temporary data file written by the native writer, named after the final
+/// data file `shuffle_<shuffle_id>_<map_id>_0.data` with a unique suffix.
+pub fn register_shuffle_output(schema: SchemaRef, output_data_file: &str) {
+    let Some((shuffle_id, map_id, data_file)) = parse_data_file_name(Path::new(output_data_file))
+    else {
+        log::warn!("cannot serve shuffle output with unrecognized file name: {output_data_file}");
+        return;
+    };
+    let index_file = data_file.with_extension("index");
+
+    let mut registry = registry().lock();
+    if registry.len() >= PRUNE_THRESHOLD {
+        // outputs of removed shuffles are no longer servable
+        registry.retain(|_, output| output.is_committed());
+    }
+    registry.insert(
+        (shuffle_id, map_id),
+        Arc::new(RegisteredShuffleOutput {
+            schema,
+            data_file,
+            index_file,
+        }),
+    );
+}
+
+/// returns a committed shuffle output.
+pub fn get_shuffle_output(shuffle_id: u64, map_id: u64) -> Option<Arc<RegisteredShuffleOutput>> {
+    let output = registry().lock().get(&(shuffle_id, map_id)).cloned()?;
+    output.is_committed().then_some(output)
+}
+
+/// parses shuffle/map ids and the final data file from a (temporary) data file
+fn parse_data_file_name(data_file: &Path) -> Option<(u64, u64, PathBuf)> {
+    let file_name = data_file.file_name()?.to_str()?;
+    let (ids, suffix) = file_name.strip_prefix("shuffle_")?.split_once("_0.data")?;
+    if !suffix.is_empty() && !suffix.starts_with('.') {
+        return None;
+    }
+    let mut parts = ids.split('_');
+    let shuffle_id = parts.next()?.parse().ok()?;
+    let map_id = parts.next()?.parse().ok()?;
+    let final_data_file = data_file.with_file_name(format!("shuffle_{ids}_0.data"));
+    parts
+        .next()
+        .is_none()
+        .then_some((shuffle_id, map_id, final_data_file))
+}
+
+/// reads offset and length of a reduce partition from a committed index file,
+/// which contains big-endian offsets of all partitions.
+fn read_partition_range(index_file: &Path, reduce_id: usize) -> Result<(u64, u64)> {
+    let mut index_file = File::open(index_file)?;
+    let num_partitions = (index_file.metadata()?.len() / 8).saturating_sub(1) as usize;
+    if reduce_id >= num_partitions {
+        return df_execution_err!(
+            "reduce id {reduce_id} out of range, number of partitions: {num_partitions}"
+        );
+    }
+    index_file.seek(SeekFrom::Start(reduce_id as u64 * 8))?;
+    let start = index_file.read_u64::<BigEndian>()?;
+    let end = index_file.read_u64::<BigEndian>()?;
+    let Some(len) = end.checked_sub(start) else {
+        return df_execution_err!("corrupted index file: partition {reduce_id} ends before start");
+    };
+    Ok((start, len))
+}
+
+/// A task result streamed to a flight client
+pub struct ResultStream {
+    pub schema: SchemaRef,
+    receiver: Receiver<RecordBatch>,
+    finished: Arc<AtomicBool>,
+}
+
+impl ResultStream {
+    /// receives the next batch, fails if the producing task terminates before
+    /// finishing the stream
+    pub async fn next(&mut self) -> Option<Result<RecordBatch>> {
+        match self.receiver.recv().await {
+            Some(batch) => Some(Ok(batch)),
+            None if self.finished.load(SeqCst) => None,
+            None => Some(df_execution_err!(
+                "result stream terminated before finishing"
+            )),
+        }
+    }
+}
+
+fn result_registry() -> &'static Mutex<HashMap<(String, usize), ResultStream>> {
+    static RESULT_REGISTRY: OnceCell<Mutex<HashMap<(String, usize), ResultStream>>> =
+        OnceCell::new();
+    RESULT_REGISTRY.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Producer of a result stream served with the ticket
+/// `result/<result_id>/<partition>`. clients see an error if the writer is
+/// dropped without finishing.
+pub struct ResultStreamWriter {
+    result_id: String,
+    partition: usize,
+    sender: Sender<RecordBatch>,
+    finished: Arc<AtomicBool>,
+}
+
+impl ResultStreamWriter {
+    pub fn new(result_id: &str, partition: usize, schema: SchemaRef) -> Self {
+        let (sender, receiver) = channel(RESULT_STREAM_BUFFER_SIZE);
+        let finished = Arc::new(AtomicBool::new(false));
+        let mut result_registry = result_registry().lock();
+        if result_registry.len() >= PRUNE_THRESHOLD {
+            // finished streams without buffered batches are no longer useful
+            result_registry.retain(|_, s| !s.receiver.is_closed() || !s.receiver.is_empty());
+        }
+        result_registry.insert(
+            (result_id.to_string(), partition),
+            ResultStream {
+                schema,
+                receiver,
+                finished: finished.clone(),
+            },
+        );
+        Self {
+            result_id: result_id.to_string(),
+            partition,
+            sender,
+            finished,
+        }
+    }
+
+    /// writes a batch, waiting for flight clients to fetch if the buffer is
+    /// full.
+    pub async fn write(&self, batch: RecordBatch) -> Result<()> {
+        match tokio::time::timeout(RESULT_STREAM_FETCH_TIMEOUT, self.sender.send(batch)).await {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(_)) => df_execution_err!("result stream closed by flight client"),
+            Err(_) => df_execution_err!("timeout waiting for flight client to fetch results"),
+        }
+    }
+
+    pub fn finish(self) {
+        self.finished.store(true, SeqCst);
+    }
+}
+
+impl Drop for ResultStreamWriter {
+    fn drop(&mut self) {
+        // streams never taken by clients are no longer servable
+        if !self.finished.load(SeqCst) {
+            take_result_stream(&self.result_id, self.partition);
+        }
+    }
+}
+
+/// takes a result stream, which can be served only once.
+pub fn take_result_stream(result_id: &str, partition: usize) -> Option<ResultStream> {
+    result_registry()
+        .lock()
+        .remove(&(result_id.to_string(), partition))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{io::Write, path::Path, sync::Arc};
+
+    use arrow::{
+        array::{ArrayRef, Int32Array, RecordBatch},
+        datatypes::{DataType, Field, Schema},
+    };
+    use byteorder::{BigEndian, WriteBytesExt};
+    use datafusion::common::Result;
+
+    use crate::{
+        common::ipc_compression::IpcCompressionWriter,
+        shuffle::flight_registry::{
+            get_shuffle_output, parse_data_file_name, register_shuffle_output, take_result_stream,
+            ResultStreamWriter,
+        },
+    };
+
+    #[test]
+    fn test_parse_data_file_name() {
+        let parse = |name: &str| parse_data_file_name(Path::new(name));
+        let data_file = Path::new("/tmp/blockmgr/0c/shuffle_3_12_0.data").to_path_buf();
+        assert_eq!(
+            parse("/tmp/blockmgr/0c/shuffle_3_12_0.data"),
+            Some((3, 12, data_file.clone())),
+        );
+        assert_eq!(
+            parse("/tmp/blockmgr/0c/shuffle_3_12_0.data.6f1c2e0a"),
+            Some((3, 12, data_file)),
+        );
+        assert_eq!(parse("shuffle_3_12_0.index"), None);
+        assert_eq!(parse("shuffle_3_12_0.datax"), None);
+        assert_eq!(parse("shuffle_3_x_0.data"), None);
+        assert_eq!(parse("shuffle_3_12_4_0.data"), None);
+    }
+
+    #[test]
+    fn test_read_registered_shuffle_output() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+        let dir = tempfile::tempdir()?;
+        let data_path = dir.path().join("shuffle_1_2_0.data");
+
+        // two partitions, the first one is empty
+        let mut writer = IpcCompressionWriter::new(vec![]);
+        let col: ArrayRef = Arc::new(Int32Array::from(vec![1, 2, 3]));
+        writer.write_batch(3, &[col.clone()])?;
+        writer.finish_current_buf()?;
+        let data = writer.into_inner();
+        std::fs::write(&data_path, &data)?;
+        let mut index_file = std::fs::File::create(data_path.with_extension("index"))?;
+        for offset in [0, 0, data.len() as u64, 0] {
+            index_file.write_u64::<BigEndian>(offset)?;
+        }
+        index_file.flush()?;
+
+        // registered with the temporary data file name
+        let tmp_data_path = format!("{}.6f1c2e0a", data_path.display());
+        register_shuffle_output(schema.clone(), &tmp_data_path);
+        let output = get_shuffle_output(1, 2).expect("output not registered");
+        assert_eq!(output.read_partition(0)?.count(), 0);
+        assert_eq!(
+            output.read_partition(1)?.collect::<Result<Vec<_>>>()?,
+            vec![RecordBatch::try_new(schema, vec![col])?],
+        );
+
+        // the last partition ends before its start in the corrupted index
+        assert!(output.read_partition(2).is_err());
+        assert!(output.read_partition(3).is_err());
+        assert!(get_shuffle_output(1, 3).is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_result_stream() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from(vec![1, 2, 3]))],
+        )?;
+
+        let writer = ResultStreamWriter::new("q1", 0, schema.clone());
+        writer.write(batch.clone()).await?;
+        writer.finish();
+
+        let mut stream = take_result_stream("q1", 0).expect("result stream not registered");
+        assert_eq!(stream.schema, schema);
+        assert_eq!(stream.next().await.transpose()?, Some(batch.clone()));
+        assert!(stream.next().await.is_none());
+
+        // result streams are served only once
+        assert!(take_result_stream("q1", 0).is_none());
+
+        // clients see an error if the task fails before finishing
+        let writer = ResultStreamWriter::new("q1", 1, schema.clone());
+        let mut stream = take_result_stream("q1", 1).expect("result stream not registered");
+        writer.write(batch.clone()).await?;
+        drop(writer);
+        assert_eq!(stream.next().await.transpose()?, Some(batch.clone()));
+        assert!(stream.next().await.expect("missing error").is_err());
+
+        // writing fails if the flight client is gone
+        let writer = ResultStreamWriter::new("q1", 2, schema);
+        drop(take_result_stream("q1", 2));
+        assert!(writer.write(batch).await.is_err());
+        Ok(())
+    }
+}
@@ -12,32 +12,88 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::sync::{
-    atomic::{AtomicUsize, Ordering::SeqCst},
-    Arc,
+use std::{
+    io::Write,
+    sync::{
+        atomic::{AtomicUsize, Ordering::SeqCst},
+        Arc,
+    },
 };
 
-use arrow::{error::Result as ArrowResult, record_batch::RecordBatch};
+use arrow::{
+    error::{ArrowError, Result as ArrowResult},
+    record_batch::RecordBatch,
+};
 use async_trait::async_trait;
+use blaze_jni_bridge::{
+    conf::{self, IntConf},
+    is_jni_bridge_inited,
+};
 use bytesize::ByteSize;
 use datafusion::{
     common::Result,
     error::DataFusionError,
-    physical_plan::{Partitioning, SendableRecordBatchStream},
+    physical_expr::PhysicalExprRef,
+    physical_plan::{metrics::Count, Partitioning, SendableRecordBatchStream},
 };
 use datafusion_ext_commons::{arrow::array_size::ArraySize, spark_hash::create_murmur3_hashes};
 use futures::StreamExt;
 
-use crate::{common::execution_context::ExecutionContext, memmgr::spill::Spill};
+use crate::{
+    common::execution_context::ExecutionContext,
+    memmgr::{
+        metrics::SpillMetrics,
+        spill::{spill_local_dir, try_new_file_spill_excluding_dirs, try_new_spill, Spill},
+    },
+    shuffle::{buffered_data::BufferedData, range_partitioning::RangePartitioning},
+};
 
+pub mod flight_registry;
+pub mod range_partitioning;
 pub mod single_repartitioner;
 pub mod sort_repartitioner;
 
 mod buffered_data;
+mod output_committer;
 mod rss;
 pub mod rss_single_repartitioner;
 pub mod rss_sort_repartitioner;
 
+/// Partitioning of shuffle output, which extends datafusion's partitioning
+/// with spark's range partitioning.
+#[derive(Debug, Clone)]
+pub enum ShufflePartitioning {
+    Single,
+    Hash(Vec<PhysicalExprRef>, usize),
+    RoundRobin(usize),
+    Range(Arc<RangePartitioning>),
+}
+
+impl ShufflePartitioning {
+    pub fn partition_count(&self) -> usize {
+        match self {
+            ShufflePartitioning::Single => 1,
+            ShufflePartitioning::Hash(_, num_partitions) => *num_partitions,
+            ShufflePartitioning::RoundRobin(num_partitions) => *num_partitions,
+            ShufflePartitioning::Range(range) => range.partition_count(),
+        }
+    }
+
+    /// returns the datafusion partitioning reported as plan's output
+    /// partitioning
+    pub fn to_df_partitioning(&self) -> Partitioning {
+        match self {
+            ShufflePartitioning::Hash(exprs, num_partitions) => {
+                Partitioning::Hash(exprs.clone(), *num_partitions)
+            }
+            ShufflePartitioning::RoundRobin(num_partitions) => {
+                Partitioning::RoundRobinBatch(*num_partitions)
+            }
+            other => Partitioning::UnknownPartitioning(other.partition_count()),
+        }
+    }
+}
+
 #[async_trait]
 pub trait ShuffleRepartitioner: Send + Sync {
     async fn insert_batch(&self, input: RecordBatch) -> Result<()>;
@@ -99,9 +155,74 @@ struct ShuffleSpill {
     offsets: Vec<u64>,
 }
 
-fn evaluate_hashes(partitioning: &Partitioning, batch: &RecordBatch) -> ArrowResult<Vec<i32>> {
+impl ShuffleSpill {
+    /// writes buffered data into a new spill. if the write fails with an io
+    /// error (e.g. disk error on one local dir), it is retried on a file spill
+    /// in an alternate local dir before failing the whole task. each retry is
+    /// recorded in `failover_count`.
+    fn try_write(
+        mut data: BufferedData,
+        spill_metrics: &SpillMetrics,
+        failover_count: &Count,
+    ) -> Result<Self> {
+        let max_retries = shuffle_write_failover_max_retries()?;
+        let mut failed_dirs = vec![];
+        let mut spill = try_new_spill(spill_metrics)?;
+
+        // sort staging data before cloning, so it is not sorted in every attempt
+        data.flush_all_staging()?;
+        let mut num_retries = 0;
+        loop {
+            let written = {
+                let mut writer = spill.get_buf_writer();
+                data.clone().write(&mut writer).and_then(|offsets| {
+                    writer.flush()?;
+                    Ok(offsets)
+                })
+            };
+            match written {
+                Ok(offsets) => return Ok(Self { spill, offsets }),
+                Err(err) if num_retries < max_retries && is_io_error(&err) => {
+                    failed_dirs.extend(spill_local_dir(spill.as_ref()));
+                    failover_count.add(1);
+                    log::warn!(
+                        "shuffle: error writing spill, retrying in alternate dir ({}/{}): {}",
+                        num_retries + 1,
+                        max_retries,
+                        err,
+                    );
+                    spill = try_new_file_spill_excluding_dirs(spill_metrics, &failed_dirs)?;
+                    num_retries += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+fn shuffle_write_failover_max_retries() -> Result<usize> {
+    if is_jni_bridge_inited() {
+        Ok(conf::SHUFFLE_WRITE_FAILOVER_MAX_RETRIES.value()?.max(0) as usize)
+    } else {
+        Ok(0) // for testing
+    }
+}
+
+fn is_io_error(err: &DataFusionError) -> bool {
+    match err {
+        DataFusionError::IoError(_) => true,
+        DataFusionError::ArrowError(ArrowError::IoError(..), _) => true,
+        DataFusionError::Context(_, err) => is_io_error(err),
+        _ => false,
+    }
+}
+
+fn evaluate_hashes(
+    partitioning: &ShufflePartitioning,
+    batch: &RecordBatch,
+) -> ArrowResult<Vec<i32>> {
     match partitioning {
-        Partitioning::Hash(exprs, _) => {
+        ShufflePartitioning::Hash(exprs, _) => {
             let arrays = exprs
                 .iter()
                 .map(|expr| Ok(expr.evaluate(batch)?.into_array(batch.num_rows())?))
@@ -127,7 +248,7 @@ fn evaluate_partition_ids(mut hashes: Vec<i32>, num_partitions: usize) -> Vec<u3
 }
 
 fn evaluate_robin_partition_ids(
-    partitioning: &Partitioning,
+    partitioning: &ShufflePartitioning,
     batch: &RecordBatch,
     start_rows: usize,
 ) -> Vec<u32> {
@@ -139,3 +260,29 @@ fn evaluate_robin_partition_ids(
     }
     vec_u32
 }
+
+#[cfg(test)]
+mod test {
+    use std::io::ErrorKind;
+
+    use arrow::error::ArrowError;
+    use datafusion::error::DataFusionError;
+
+    use crate::shuffle::is_io_error;
+
+    #[test]
+    fn test_is_io_error() {
+        let io_error = || std::io::Error::new(ErrorKind::Other, "disk error");
+        assert!(is_io_error(&DataFusionError::IoError(io_error())));
+        assert!(is_io_error(&DataFusionError::ArrowError(
+            ArrowError::IoError("write".to_string(), io_error()),
+            None,
+        )));
+        assert!(is_io_error(
+            &DataFusionError::IoError(io_error()).context("writing spill")
+        ));
+        assert!(!is_io_error(&DataFusionError::Execution(
+            "task killed".to_string()
+        )));
+    }
+}
@@ -0,0 +1,142 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Output data/index files of a shuffle map attempt.
+//!
+//! output files are named uniquely for each attempt by the jvm side, so
+//! speculative attempts of the same map task never write the same files.
+//! duplicate outputs are resolved by spark's IndexShuffleBlockResolver when
+//! committing them. files written by failed or cancelled attempts are removed
+//! here, so that they are not left in the local dirs.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering::SeqCst},
+    Arc,
+};
+
+use datafusion::common::Result;
+use datafusion_ext_commons::{
+    df_execution_err,
+    fs::{get_fs, FileSystem, FileWriter},
+};
+
+pub struct ShuffleOutputCommitter {
+    fs: Arc<dyn FileSystem>,
+    data_file: String,
+    index_file: String,
+    finished: AtomicBool,
+}
+
+impl ShuffleOutputCommitter {
+    pub fn try_new(data_file: &str, index_file: &str) -> Result<Self> {
+        let Some(fs) = get_fs(data_file) else {
+            return df_execution_err!("no filesystem registered for shuffle output: {data_file}");
+        };
+        Ok(Self {
+            fs,
+            data_file: data_file.to_string(),
+            index_file: index_file.to_string(),
+            finished: AtomicBool::new(false),
+        })
+    }
+
+    pub fn create_data_file(&self) -> Result<Box<dyn FileWriter>> {
+        self.fs.create(&self.data_file)
+    }
+
+    pub fn create_index_file(&self) -> Result<Box<dyn FileWriter>> {
+        self.fs.create(&self.index_file)
+    }
+
+    /// marks the written data/index files as finished, which are then
+    /// committed by the jvm side. must be called after both files are closed.
+    pub fn commit(&self) -> Result<()> {
+        if self.finished.swap(true, SeqCst) {
+            return df_execution_err!("shuffle output already committed: {}", self.data_file);
+        }
+        Ok(())
+    }
+
+    fn remove_output_files(&self) {
+        for path in [&self.data_file, &self.index_file] {
+            if self.fs.exists(path).unwrap_or(true) {
+                if let Err(err) = self.fs.remove(path) {
+                    log::warn!("error removing unfinished shuffle output {path}: {err}");
+                }
+            }
+        }
+    }
+}
+
+impl Drop for ShuffleOutputCommitter {
+    fn drop(&mut self) {
+        // removes files written by failed or cancelled attempts
+        if !self.finished.load(SeqCst) {
+            self.remove_output_files();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use datafusion::common::Result;
+
+    use crate::shuffle::output_committer::ShuffleOutputCommitter;
+
+    fn write_output(committer: &ShuffleOutputCommitter, data: &[u8], index: &[u8]) -> Result<()> {
+        let mut data_writer = committer.create_data_file()?;
+        data_writer.write_all(data)?;
+        data_writer.close()?;
+        let mut index_writer = committer.create_index_file()?;
+        index_writer.write_all(index)?;
+        index_writer.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_shuffle_output_committer() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output_file = |name: &str, attempt: usize| {
+            format!(
+                "{}/shuffle_0_1_0.{name}.{attempt}",
+                dir.path().to_string_lossy()
+            )
+        };
+        let num_files = || Ok::<_, std::io::Error>(std::fs::read_dir(dir.path())?.count());
+
+        // concurrent attempts write their own files
+        let attempt1 =
+            ShuffleOutputCommitter::try_new(&output_file("data", 1), &output_file("index", 1))?;
+        let attempt2 =
+            ShuffleOutputCommitter::try_new(&output_file("data", 2), &output_file("index", 2))?;
+        write_output(&attempt1, b"attempt1", &[1; 16])?;
+        write_output(&attempt2, b"attempt2", &[2; 16])?;
+        assert_eq!(num_files()?, 4);
+
+        // files of finished attempts are kept for committing
+        attempt2.commit()?;
+        assert!(attempt2.commit().is_err());
+        drop(attempt2);
+        assert_eq!(std::fs::read(output_file("data", 2))?, b"attempt2");
+        assert_eq!(std::fs::read(output_file("index", 2))?, [2; 16]);
+
+        // files of failed attempts are removed
+        drop(attempt1);
+        assert_eq!(num_files()?, 2);
+        assert!(std::fs::metadata(output_file("data", 1)).is_err());
+        Ok(())
+    }
+}
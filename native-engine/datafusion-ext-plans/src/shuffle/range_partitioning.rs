@@ -0,0 +1,213 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use arrow::{
+    array::ArrayRef,
+    datatypes::SchemaRef,
+    record_batch::RecordBatch,
+    row::{RowConverter, Rows, SortField},
+};
+use datafusion::{common::Result, physical_expr::PhysicalSortExpr};
+use datafusion_ext_commons::{arrow::cast::cast, df_execution_err};
+use itertools::Itertools;
+
+/// Spark's range partitioning. sort keys of each row are compared with the
+/// bounds sampled by spark's RangePartitioner, the partition id is the number
+/// of bounds less than the key.
+///
+/// keys and bounds are converted into arrow-row format, so that multiple
+/// sort columns with descending/nulls-first options are compared as plain
+/// bytes.
+#[derive(Debug)]
+pub struct RangePartitioning {
+    sort_exprs: Vec<PhysicalSortExpr>,
+    num_partitions: usize,
+    row_converter: RowConverter,
+    bounds: Rows,
+}
+
+impl RangePartitioning {
+    /// creates range partitioning with bounds, each array in `bounds` is a
+    /// column of the corresponding sort expr, and the i-th row of all arrays
+    /// is the upper bound (inclusive) of the i-th partition.
+    pub fn try_new(
+        sort_exprs: Vec<PhysicalSortExpr>,
+        num_partitions: usize,
+        bounds: Vec<ArrayRef>,
+        input_schema: &SchemaRef,
+    ) -> Result<Self> {
+        if bounds.len() != sort_exprs.len() {
+            return df_execution_err!(
+                "range partitioning: expect {} bound columns, got {}",
+                sort_exprs.len(),
+                bounds.len()
+            );
+        }
+        if !bounds.iter().map(|bound| bound.len()).all_equal() {
+            return df_execution_err!("range partitioning: bound columns have different lengths");
+        }
+        let num_bounds = bounds.first().map(|bound| bound.len()).unwrap_or(0);
+        if num_bounds >= num_partitions.max(1) {
+            return df_execution_err!(
+                "range partitioning: too many bounds ({num_bounds}) for {num_partitions} partitions"
+            );
+        }
+
+        let key_types = sort_exprs
+            .iter()
+            .map(|sort_expr| sort_expr.expr.data_type(input_schema))
+            .collect::<Result<Vec<_>>>()?;
+        let row_converter = RowConverter::new(
+            sort_exprs
+                .iter()
+                .zip(&key_types)
+                .map(|(sort_expr, key_type)| {
+                    SortField::new_with_options(key_type.clone(), sort_expr.options)
+                })
+                .collect(),
+        )?;
+        let bounds = bounds
+            .iter()
+            .zip(&key_types)
+            .map(|(bound, key_type)| {
+                if bound.data_type() == key_type {
+                    Ok(bound.clone())
+                } else {
+                    cast(bound, key_type)
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let bounds = row_converter.convert_columns(&bounds)?;
+
+        Ok(Self {
+            sort_exprs,
+            num_partitions,
+            row_converter,
+            bounds,
+        })
+    }
+
+    pub fn partition_count(&self) -> usize {
+        self.num_partitions
+    }
+
+    pub fn evaluate_partition_ids(&self, batch: &RecordBatch) -> Result<Vec<u32>> {
+        let keys = self
+            .sort_exprs
+            .iter()
+            .map(|sort_expr| {
+                sort_expr
+                    .expr
+                    .evaluate(batch)
+                    .and_then(|key| key.into_array(batch.num_rows()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let rows = self.row_converter.convert_columns(&keys)?;
+
+        // binary search the first bound >= key, bounds are strictly increasing
+        // so this is equivalent to spark's linear/binary searching
+        let num_bounds = self.bounds.num_rows();
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let (mut lo, mut hi) = (0, num_bounds);
+                while lo < hi {
+                    let mid = (lo + hi) / 2;
+                    if self.bounds.row(mid) < row {
+                        lo = mid + 1;
+                    } else {
+                        hi = mid;
+                    }
+                }
+                lo as u32
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{ArrayRef, Int32Array, StringArray},
+        compute::SortOptions,
+        record_batch::RecordBatch,
+    };
+    use datafusion::{
+        common::Result,
+        physical_expr::{expressions::Column, PhysicalSortExpr},
+    };
+
+    use crate::shuffle::range_partitioning::RangePartitioning;
+
+    #[test]
+    fn test_range_partitioning() -> Result<()> {
+        let batch = RecordBatch::try_from_iter([
+            (
+                "a",
+                Arc::new(Int32Array::from(vec![
+                    Some(1),
+                    Some(5),
+                    Some(5),
+                    Some(5),
+                    None,
+                    Some(9),
+                ])) as ArrayRef,
+            ),
+            (
+                "b",
+                Arc::new(StringArray::from(vec![
+                    Some("x"),
+                    Some("z"),
+                    Some("m"),
+                    None,
+                    Some("a"),
+                    Some("a"),
+                ])) as ArrayRef,
+            ),
+        ])?;
+        let schema = batch.schema();
+
+        // order by a asc nulls first, b desc nulls last
+        let sort_exprs = vec![
+            PhysicalSortExpr {
+                expr: Arc::new(Column::new("a", 0)),
+                options: SortOptions {
+                    descending: false,
+                    nulls_first: true,
+                },
+            },
+            PhysicalSortExpr {
+                expr: Arc::new(Column::new("b", 1)),
+                options: SortOptions {
+                    descending: true,
+                    nulls_first: false,
+                },
+            },
+        ];
+
+        // bounds: (5, "m"), (5, null)
+        let bounds: Vec<ArrayRef> = vec![
+            Arc::new(Int32Array::from(vec![5, 5])),
+            Arc::new(StringArray::from(vec![Some("m"), None])),
+        ];
+        let range_partitioning = RangePartitioning::try_new(sort_exprs, 3, bounds, &schema)?;
+        assert_eq!(
+            range_partitioning.evaluate_partition_ids(&batch)?,
+            vec![0, 0, 0, 1, 0, 2],
+        );
+        Ok(())
+    }
+}
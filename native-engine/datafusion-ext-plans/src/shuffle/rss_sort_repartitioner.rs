@@ -16,23 +16,23 @@ use std::sync::Weak;
 
 use arrow::record_batch::RecordBatch;
 use async_trait::async_trait;
-use datafusion::{
-    common::Result,
-    physical_plan::{metrics::Time, Partitioning},
-};
+use datafusion::{common::Result, physical_plan::metrics::Time};
 use datafusion_ext_commons::arrow::array_size::ArraySize;
 use futures::lock::Mutex;
 use jni::objects::GlobalRef;
 
 use crate::{
-    memmgr::{MemConsumer, MemConsumerInfo, MemManager},
-    shuffle::{buffered_data::BufferedData, ShuffleRepartitioner},
+    memmgr::{
+        off_heap::OffHeapBooking, quota::MemConsumerType, MemConsumer, MemConsumerInfo, MemManager,
+    },
+    shuffle::{buffered_data::BufferedData, ShufflePartitioning, ShuffleRepartitioner},
 };
 
 pub struct RssSortShuffleRepartitioner {
     name: String,
     mem_consumer_info: Option<Weak<MemConsumerInfo>>,
     data: Mutex<BufferedData>,
+    off_heap_booking: OffHeapBooking,
     rss: GlobalRef,
 }
 
@@ -40,16 +40,23 @@ impl RssSortShuffleRepartitioner {
     pub fn new(
         partition_id: usize,
         rss_partition_writer: GlobalRef,
-        partitioning: Partitioning,
+        partitioning: ShufflePartitioning,
         sort_time: Time,
     ) -> Self {
         Self {
             name: format!("RssSortShufflePartitioner[partition={}]", partition_id),
             mem_consumer_info: None,
             data: Mutex::new(BufferedData::new(partitioning, partition_id, sort_time)),
+            off_heap_booking: OffHeapBooking::default(),
             rss: rss_partition_writer,
         }
     }
+
+    /// updates memory used by buffered data, booked in the off-heap quota if
+    /// off-heap staging is enabled
+    async fn update_booked_mem_used(&self, mem_used: usize) -> Result<()> {
+        self.off_heap_booking.update_mem_used(self, mem_used).await
+    }
 }
 
 #[async_trait]
@@ -68,6 +75,10 @@ impl MemConsumer for RssSortShuffleRepartitioner {
             .expect("consumer info not set")
     }
 
+    fn consumer_type(&self) -> MemConsumerType {
+        MemConsumerType::Shuffle
+    }
+
     async fn spill(&self) -> Result<()> {
         let data = self.data.lock().await.drain();
         let rss = self.rss.clone();
@@ -75,7 +86,7 @@ impl MemConsumer for RssSortShuffleRepartitioner {
         tokio::task::spawn_blocking(move || data.write_rss(rss))
             .await
             .expect("tokio error")?;
-        self.update_mem_used(0).await?;
+        self.update_booked_mem_used(0).await?;
         Ok(())
     }
 }
@@ -91,7 +102,7 @@ impl ShuffleRepartitioner for RssSortShuffleRepartitioner {
     async fn insert_batch(&self, input: RecordBatch) -> Result<()> {
         // update memory usage before adding to buffered data
         let mem_used = self.data.lock().await.mem_used() + input.get_array_mem_size() * 2;
-        self.update_mem_used(mem_used).await?;
+        self.update_booked_mem_used(mem_used).await?;
 
         // add batch to buffered data
         let mem_used = {
@@ -99,7 +110,7 @@ impl ShuffleRepartitioner for RssSortShuffleRepartitioner {
             data.add_batch(input)?;
             data.mem_used()
         };
-        self.update_mem_used(mem_used).await?;
+        self.update_booked_mem_used(mem_used).await?;
 
         // we are likely to spill more frequently because the cost of spilling a shuffle
         // repartition is lower than other consumers.
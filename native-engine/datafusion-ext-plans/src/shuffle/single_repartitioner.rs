@@ -12,15 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{
-    fs::{File, OpenOptions},
-    io::{Seek, Write},
-    sync::Arc,
-};
+use std::{io::Write, sync::Arc};
 
 use arrow::record_batch::RecordBatch;
 use async_trait::async_trait;
 use datafusion::{common::Result, physical_plan::metrics::Time};
+use datafusion_ext_commons::fs::FileWriter;
 use tokio::sync::Mutex;
 
 use crate::{
@@ -28,39 +25,39 @@ use crate::{
         ipc_compression::IpcCompressionWriter,
         timer_helper::{TimedWriter, TimerHelper},
     },
-    shuffle::ShuffleRepartitioner,
+    shuffle::{output_committer::ShuffleOutputCommitter, ShuffleRepartitioner},
 };
 
 pub struct SingleShuffleRepartitioner {
-    output_data_file: String,
-    output_index_file: String,
-    output_data: Arc<Mutex<Option<IpcCompressionWriter<TimedWriter<File>>>>>,
+    output_committer: ShuffleOutputCommitter,
+    output_data: Arc<Mutex<Option<IpcCompressionWriter<TimedWriter<Box<dyn FileWriter>>>>>>,
     output_io_time: Time,
 }
 
 impl SingleShuffleRepartitioner {
-    pub fn new(output_data_file: String, output_index_file: String, output_io_time: Time) -> Self {
-        Self {
-            output_data_file,
-            output_index_file,
+    pub fn try_new(
+        output_data_file: String,
+        output_index_file: String,
+        output_io_time: Time,
+    ) -> Result<Self> {
+        Ok(Self {
+            output_committer: ShuffleOutputCommitter::try_new(
+                &output_data_file,
+                &output_index_file,
+            )?,
             output_data: Arc::new(Mutex::default()),
             output_io_time,
-        }
+        })
     }
 
     fn get_output_writer<'a>(
         &self,
-        output_data: &'a mut Option<IpcCompressionWriter<TimedWriter<File>>>,
-    ) -> Result<&'a mut IpcCompressionWriter<TimedWriter<File>>> {
+        output_data: &'a mut Option<IpcCompressionWriter<TimedWriter<Box<dyn FileWriter>>>>,
+    ) -> Result<&'a mut IpcCompressionWriter<TimedWriter<Box<dyn FileWriter>>>> {
         if output_data.is_none() {
             *output_data = Some(IpcCompressionWriter::new(
-                self.output_io_time.wrap_writer(
-                    OpenOptions::new()
-                        .write(true)
-                        .create(true)
-                        .truncate(true)
-                        .open(&self.output_data_file)?,
-                ),
+                self.output_io_time
+                    .wrap_writer(self.output_committer.create_data_file()?),
             ));
         }
         Ok(output_data.as_mut().unwrap())
@@ -77,39 +74,32 @@ impl ShuffleRepartitioner for SingleShuffleRepartitioner {
     }
 
     async fn shuffle_write(&self) -> Result<()> {
-        let mut output_data = std::mem::take(&mut *self.output_data.lock().await);
+        let output_data = std::mem::take(&mut *self.output_data.lock().await);
 
         // write index file
-        if let Some(output_writer) = output_data.as_mut() {
-            let mut output_index = self.output_io_time.wrap_writer(
-                OpenOptions::new()
-                    .write(true)
-                    .create(true)
-                    .truncate(true)
-                    .open(&self.output_index_file)?,
-            );
+        if let Some(mut output_writer) = output_data {
+            let mut output_index = self
+                .output_io_time
+                .wrap_writer(self.output_committer.create_index_file()?);
             output_writer.finish_current_buf()?;
-            let offset = output_writer.inner_mut().0.stream_position()?;
+            let offset = output_writer.inner().0.position();
             output_index.write_all(&[0u8; 8])?;
             output_index.write_all(&(offset as i64).to_le_bytes()[..])?;
+            output_writer.into_inner().0.close()?;
+            output_index.0.close()?;
         } else {
             // write empty data file and index file
-            let _output_data = self.output_io_time.wrap_writer(
-                OpenOptions::new()
-                    .write(true)
-                    .create(true)
-                    .truncate(true)
-                    .open(&self.output_data_file)?,
-            );
-            let mut output_index = self.output_io_time.wrap_writer(
-                OpenOptions::new()
-                    .write(true)
-                    .create(true)
-                    .truncate(true)
-                    .open(&self.output_index_file)?,
-            );
+            let output_data = self
+                .output_io_time
+                .wrap_writer(self.output_committer.create_data_file()?);
+            let mut output_index = self
+                .output_io_time
+                .wrap_writer(self.output_committer.create_index_file()?);
             output_index.write_all(&[0u8; 16])?;
+            output_data.0.close()?;
+            output_index.0.close()?;
         }
+        self.output_committer.commit()?;
         Ok(())
     }
 }
@@ -13,8 +13,7 @@
 // limitations under the License.
 
 use std::{
-    fs::OpenOptions,
-    io::{BufReader, Read, Seek, Write},
+    io::{BufReader, Read, Write},
     sync::{Arc, Weak},
 };
 
@@ -23,7 +22,7 @@ use async_trait::async_trait;
 use bytesize::ByteSize;
 use datafusion::{
     common::{DataFusionError, Result},
-    physical_plan::{metrics::Time, Partitioning},
+    physical_plan::metrics::{Count, Time},
 };
 use datafusion_ext_commons::{
     algorithm::rdx_tournament_tree::{KeyForRadixTournamentTree, RadixTournamentTree},
@@ -35,46 +34,62 @@ use futures::lock::Mutex;
 use crate::{
     common::{execution_context::ExecutionContext, timer_helper::TimerHelper},
     memmgr::{
-        spill::{try_new_spill, Spill},
+        metrics::SpillMetrics, off_heap::OffHeapBooking, quota::MemConsumerType, spill::Spill,
         MemConsumer, MemConsumerInfo, MemManager,
     },
-    shuffle::{buffered_data::BufferedData, ShuffleRepartitioner, ShuffleSpill},
+    shuffle::{
+        buffered_data::BufferedData, output_committer::ShuffleOutputCommitter, ShufflePartitioning,
+        ShuffleRepartitioner, ShuffleSpill,
+    },
 };
 
 pub struct SortShuffleRepartitioner {
     exec_ctx: Arc<ExecutionContext>,
     name: String,
     mem_consumer_info: Option<Weak<MemConsumerInfo>>,
-    output_data_file: String,
-    output_index_file: String,
+    output_committer: Arc<ShuffleOutputCommitter>,
     data: Mutex<BufferedData>,
     spills: Mutex<Vec<ShuffleSpill>>,
+    off_heap_booking: OffHeapBooking,
     num_output_partitions: usize,
     output_io_time: Time,
+    failover_count: Count,
 }
 
 impl SortShuffleRepartitioner {
-    pub fn new(
+    pub fn try_new(
         exec_ctx: Arc<ExecutionContext>,
         output_data_file: String,
         output_index_file: String,
-        partitioning: Partitioning,
+        partitioning: ShufflePartitioning,
         output_io_time: Time,
-    ) -> Self {
+    ) -> Result<Self> {
         let partition_id = exec_ctx.partition_id();
         let sort_time = exec_ctx.register_timer_metric("sort_time");
+        let failover_count = exec_ctx.register_counter_metric("shuffle_write_failover_count");
         let num_output_partitions = partitioning.partition_count();
-        Self {
+        let output_committer = Arc::new(ShuffleOutputCommitter::try_new(
+            &output_data_file,
+            &output_index_file,
+        )?);
+        Ok(Self {
             exec_ctx,
             name: format!("SortShufflePartitioner[partition={partition_id}]"),
             mem_consumer_info: None,
-            output_data_file,
-            output_index_file,
+            output_committer,
             data: Mutex::new(BufferedData::new(partitioning, partition_id, sort_time)),
             spills: Mutex::default(),
+            off_heap_booking: OffHeapBooking::default(),
             num_output_partitions,
             output_io_time,
-        }
+            failover_count,
+        })
+    }
+
+    /// updates memory used by buffered data, booked in the off-heap quota if
+    /// off-heap staging is enabled
+    async fn update_booked_mem_used(&self, mem_used: usize) -> Result<()> {
+        self.off_heap_booking.update_mem_used(self, mem_used).await
     }
 }
 
@@ -94,16 +109,20 @@ impl MemConsumer for SortShuffleRepartitioner {
             .expect("consumer info not set")
     }
 
+    fn consumer_type(&self) -> MemConsumerType {
+        MemConsumerType::Shuffle
+    }
+
+    fn spill_metrics(&self) -> Option<&SpillMetrics> {
+        Some(self.exec_ctx.spill_metrics())
+    }
+
     async fn spill(&self) -> Result<()> {
         let data = self.data.lock().await.drain();
-        let mut spill = try_new_spill(self.exec_ctx.spill_metrics())?;
-
-        let offsets = data.write(spill.get_buf_writer())?;
-        self.spills
-            .lock()
-            .await
-            .push(ShuffleSpill { spill, offsets });
-        self.update_mem_used(0).await?;
+        let spill =
+            ShuffleSpill::try_write(data, self.exec_ctx.spill_metrics(), &self.failover_count)?;
+        self.spills.lock().await.push(spill);
+        self.update_booked_mem_used(0).await?;
         Ok(())
     }
 }
@@ -119,7 +138,7 @@ impl ShuffleRepartitioner for SortShuffleRepartitioner {
     async fn insert_batch(&self, input: RecordBatch) -> Result<()> {
         // update memory usage before adding to buffered data
         let mem_used = self.data.lock().await.mem_used() + input.get_array_mem_size() * 2;
-        self.update_mem_used(mem_used).await?;
+        self.update_booked_mem_used(mem_used).await?;
 
         // add batch to buffered data
         let mem_used = {
@@ -127,7 +146,7 @@ impl ShuffleRepartitioner for SortShuffleRepartitioner {
             data.add_batch(input)?;
             data.mem_used()
         };
-        self.update_mem_used(mem_used).await?;
+        self.update_booked_mem_used(mem_used).await?;
 
         // we are likely to spill more frequently because the cost of spilling a shuffle
         // repartition is lower than other consumers.
@@ -156,27 +175,16 @@ impl ShuffleRepartitioner for SortShuffleRepartitioner {
             ByteSize(data.mem_used() as u64)
         );
 
-        let data_file = self.output_data_file.clone();
-        let index_file = self.output_index_file.clone();
+        let output_committer = self.output_committer.clone();
 
         // no spills - directly write current batches into final file
         if spills.is_empty() {
             let output_io_time = self.output_io_time.clone();
             tokio::task::spawn_blocking(move || {
-                let mut output_data = output_io_time.wrap_writer(
-                    OpenOptions::new()
-                        .write(true)
-                        .create(true)
-                        .truncate(true)
-                        .open(&data_file)?,
-                );
-                let mut output_index = output_io_time.wrap_writer(
-                    OpenOptions::new()
-                        .write(true)
-                        .create(true)
-                        .truncate(true)
-                        .open(&index_file)?,
-                );
+                let mut output_data =
+                    output_io_time.wrap_writer(output_committer.create_data_file()?);
+                let mut output_index =
+                    output_io_time.wrap_writer(output_committer.create_index_file()?);
 
                 // write data file
                 let offsets = data.write(&mut output_data)?;
@@ -187,12 +195,15 @@ impl ShuffleRepartitioner for SortShuffleRepartitioner {
                     offsets_data.extend_from_slice(&(offset as i64).to_le_bytes()[..]);
                 }
                 output_index.write_all(&offsets_data)?;
+                output_data.0.close()?;
+                output_index.0.close()?;
+                output_committer.commit()?;
 
                 Ok::<(), DataFusionError>(())
             })
             .await
             .or_else(|e| df_execution_err!("shuffle write error: {e:?}"))??;
-            self.update_mem_used(0).await?;
+            self.update_booked_mem_used(0).await?;
             return Ok(());
         }
 
@@ -222,7 +233,7 @@ impl ShuffleRepartitioner for SortShuffleRepartitioner {
             let mut spill = Box::new(vec![]);
             let writer = spill.get_buf_writer();
             let offsets = data.write(writer)?;
-            self.update_mem_used(spill.len()).await?;
+            self.update_booked_mem_used(spill.len()).await?;
             spills.push(ShuffleSpill { spill, offsets });
         }
 
@@ -232,39 +243,25 @@ impl ShuffleRepartitioner for SortShuffleRepartitioner {
         // append partition in each spills
         let output_io_time = self.output_io_time.clone();
         tokio::task::spawn_blocking(move || {
-            let mut output_data = output_io_time.wrap_writer(
-                OpenOptions::new()
-                    .write(true)
-                    .create(true)
-                    .truncate(true)
-                    .open(&data_file)?,
-            );
-            let mut output_index = output_io_time.wrap_writer(
-                OpenOptions::new()
-                    .write(true)
-                    .create(true)
-                    .truncate(true)
-                    .open(&index_file)?,
-            );
+            let mut output_data = output_io_time.wrap_writer(output_committer.create_data_file()?);
+            let mut output_index =
+                output_io_time.wrap_writer(output_committer.create_index_file()?);
 
             if !spills.is_empty() {
                 // select partitions from spills
-                let mut cursors = RadixTournamentTree::new(
-                    spills
-                        .iter_mut()
-                        .map(|spill| SpillCursor {
-                            cur: 0,
-                            reader: spill.spill.get_buf_reader(),
-                            offsets: std::mem::take(&mut spill.offsets),
-                        })
-                        .map(|mut spill| {
-                            spill.skip_empty_partitions();
-                            spill
-                        })
-                        .filter(|spill| spill.cur < spill.offsets.len())
-                        .collect(),
-                    num_output_partitions,
-                );
+                let mut spill_cursors = vec![];
+                for spill in &mut spills {
+                    let mut spill_cursor = SpillCursor {
+                        cur: 0,
+                        reader: spill.spill.get_buf_reader()?,
+                        offsets: std::mem::take(&mut spill.offsets),
+                    };
+                    spill_cursor.skip_empty_partitions();
+                    if spill_cursor.cur < spill_cursor.offsets.len() {
+                        spill_cursors.push(spill_cursor);
+                    }
+                }
+                let mut cursors = RadixTournamentTree::new(spill_cursors, num_output_partitions);
 
                 let mut cur_partition_id = 0;
                 loop {
@@ -274,7 +271,7 @@ impl ShuffleRepartitioner for SortShuffleRepartitioner {
                     }
 
                     while cur_partition_id < min_spill.cur {
-                        offsets.push(output_data.0.stream_position()?);
+                        offsets.push(output_data.0.position());
                         cur_partition_id += 1;
                     }
                     let (spill_offset_start, spill_offset_end) = (
@@ -293,7 +290,7 @@ impl ShuffleRepartitioner for SortShuffleRepartitioner {
             }
 
             // add one extra offset at last to ease partition length computation
-            offsets.resize(num_output_partitions + 1, output_data.0.stream_position()?);
+            offsets.resize(num_output_partitions + 1, output_data.0.position());
 
             // write index file
             let mut offsets_data = vec![];
@@ -301,13 +298,16 @@ impl ShuffleRepartitioner for SortShuffleRepartitioner {
                 offsets_data.extend_from_slice(&(offset as i64).to_le_bytes()[..]);
             }
             output_index.write_all(&offsets_data)?;
+            output_data.0.close()?;
+            output_index.0.close()?;
+            output_committer.commit()?;
 
             Ok::<(), DataFusionError>(())
         })
         .await
         .or_else(|e| df_execution_err!("shuffle write error: {e:?}"))??;
 
-        self.update_mem_used(0).await?;
+        self.update_booked_mem_used(0).await?;
         Ok(())
     }
 }
@@ -18,13 +18,17 @@ use std::{any::Any, fmt::Debug, sync::Arc};
 
 use arrow::datatypes::SchemaRef;
 use async_trait::async_trait;
+use blaze_jni_bridge::{
+    conf::{self, IntConf},
+    is_jni_bridge_inited,
+};
 use datafusion::{
     error::Result,
     execution::context::TaskContext,
     physical_expr::{expressions::Column, EquivalenceProperties, PhysicalSortExpr},
     physical_plan::{
         metrics::{ExecutionPlanMetricsSet, MetricsSet},
-        DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, Partitioning, PlanProperties,
+        DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, PlanProperties,
         SendableRecordBatchStream, Statistics,
     },
 };
@@ -35,8 +39,8 @@ use crate::{
     common::execution_context::ExecutionContext,
     memmgr::MemManager,
     shuffle::{
-        single_repartitioner::SingleShuffleRepartitioner,
-        sort_repartitioner::SortShuffleRepartitioner, ShuffleRepartitioner,
+        flight_registry::register_shuffle_output, single_repartitioner::SingleShuffleRepartitioner,
+        sort_repartitioner::SortShuffleRepartitioner, ShufflePartitioning, ShuffleRepartitioner,
     },
     sort_exec::SortExec,
 };
@@ -47,7 +51,7 @@ use crate::{
 #[derive(Debug)]
 pub struct ShuffleWriterExec {
     input: Arc<dyn ExecutionPlan>,
-    partitioning: Partitioning,
+    partitioning: ShufflePartitioning,
     output_data_file: String,
     output_index_file: String,
     metrics: ExecutionPlanMetricsSet,
@@ -78,7 +82,7 @@ impl ExecutionPlan for ShuffleWriterExec {
         self.props.get_or_init(|| {
             PlanProperties::new(
                 EquivalenceProperties::new(self.schema()),
-                self.partitioning.clone(),
+                self.partitioning.to_df_partitioning(),
                 ExecutionMode::Bounded,
             )
         })
@@ -116,23 +120,23 @@ impl ExecutionPlan for ShuffleWriterExec {
         let mut input = self.input.clone();
 
         let repartitioner: Arc<dyn ShuffleRepartitioner> = match &self.partitioning {
-            p if p.partition_count() == 1 => Arc::new(SingleShuffleRepartitioner::new(
+            p if p.partition_count() == 1 => Arc::new(SingleShuffleRepartitioner::try_new(
                 self.output_data_file.clone(),
                 self.output_index_file.clone(),
                 output_time,
-            )),
-            Partitioning::Hash(..) => {
-                let partitioner = Arc::new(SortShuffleRepartitioner::new(
+            )?),
+            ShufflePartitioning::Hash(..) | ShufflePartitioning::Range(..) => {
+                let partitioner = Arc::new(SortShuffleRepartitioner::try_new(
                     exec_ctx.clone(),
                     self.output_data_file.clone(),
                     self.output_index_file.clone(),
                     self.partitioning.clone(),
                     output_time,
-                ));
+                )?);
                 MemManager::register_consumer(partitioner.clone(), true);
                 partitioner
             }
-            Partitioning::RoundRobinBatch(..) => {
+            ShufflePartitioning::RoundRobin(..) => {
                 let sort_expr: Vec<PhysicalSortExpr> = self
                     .input
                     .schema()
@@ -146,19 +150,24 @@ impl ExecutionPlan for ShuffleWriterExec {
                     .collect();
                 input = Arc::new(SortExec::new(input, sort_expr, None));
 
-                let partitioner = Arc::new(SortShuffleRepartitioner::new(
+                let partitioner = Arc::new(SortShuffleRepartitioner::try_new(
                     exec_ctx.clone(),
                     self.output_data_file.clone(),
                     self.output_index_file.clone(),
                     self.partitioning.clone(),
                     output_time,
-                ));
+                )?);
                 MemManager::register_consumer(partitioner.clone(), true);
                 partitioner
             }
             p => unreachable!("unsupported partitioning: {:?}", p),
         };
 
+        // serve the output through the native flight server if enabled
+        if is_jni_bridge_inited() && conf::FLIGHT_SERVER_PORT.value()? > 0 {
+            register_shuffle_output(self.schema(), &self.output_data_file);
+        }
+
         let input = exec_ctx.execute_with_input_stats(&input)?;
         repartitioner.execute(exec_ctx, input)
     }
@@ -176,7 +185,7 @@ impl ShuffleWriterExec {
     /// Create a new ShuffleWriterExec
     pub fn try_new(
         input: Arc<dyn ExecutionPlan>,
-        partitioning: Partitioning,
+        partitioning: ShufflePartitioning,
         output_data_file: String,
         output_index_file: String,
     ) -> Result<Self> {
@@ -16,12 +16,13 @@
 
 use std::{
     any::Any,
-    collections::HashSet,
+    collections::{BinaryHeap, HashSet},
     fmt::Formatter,
     io::{Cursor, Read, Write},
     marker::PhantomData,
     sync::{
         atomic::{AtomicUsize, Ordering::SeqCst},
+        mpsc::{sync_channel, Receiver},
         Arc, Weak,
     },
 };
@@ -30,7 +31,7 @@ use arrow::{
     array::ArrayRef,
     datatypes::{Schema, SchemaRef},
     record_batch::{RecordBatch, RecordBatchOptions},
-    row::{Row, RowConverter, RowParser, Rows, SortField},
+    row::{OwnedRow, Row, RowConverter, RowParser, Rows, SortField},
 };
 use async_trait::async_trait;
 use bytesize::ByteSize;
@@ -49,10 +50,12 @@ use datafusion_ext_commons::{
     arrow::{
         array_size::ArraySize,
         selection::{create_batch_interleaver, take_batch, BatchInterleaver},
+        view::is_view_type,
     },
-    compute_suggested_batch_size_for_kway_merge, compute_suggested_batch_size_for_output,
-    downcast_any,
+    batch_size, compute_suggested_batch_size_for_kway_merge,
+    compute_suggested_batch_size_for_output, df_execution_err, downcast_any,
     io::{read_len, read_one_batch, write_len, write_one_batch},
+    spark_task_context::SparkTaskContext,
 };
 use futures::{lock::Mutex, StreamExt};
 use once_cell::sync::OnceCell;
@@ -62,11 +65,14 @@ use crate::{
     common::{
         column_pruning::ExecuteWithColumnPruning,
         execution_context::{ExecutionContext, WrappedRecordBatchSender},
+        replay_log::ReplayEvent,
+        statistics::limit_statistics,
         timer_helper::TimerHelper,
     },
     memmgr::{
         metrics::SpillMetrics,
-        spill::{try_new_spill, Spill, SpillCompressedReader},
+        quota::MemConsumerType,
+        spill::{copy_spill, sort_spill_compression, try_new_spill, Lz4InMemSpill, Spill},
         MemConsumer, MemConsumerInfo, MemManager,
     },
 };
@@ -76,11 +82,24 @@ use crate::{
 const SPILL_OFFHEAP_MEM_COST: usize = 200000;
 const SPILL_MERGING_SIZE: usize = 32;
 
+// number of batches read ahead by the background prefetcher of each spill
+const SPILL_PREFETCH_NUM_BATCHES: usize = 1;
+
+// buffered data is compressed in memory when spilling, and only moved into
+// real spills if the compressed data is larger than this ratio of the memory
+// used before spilling
+const IN_MEM_COMPRESSED_MAX_RATIO: f64 = 0.5;
+
+// sorts with a bounded heap instead of the external sorter if fetch is not
+// larger than this, so that input is never fully sorted or spilled
+const TOP_K_MAX_FETCH: usize = 65536;
+
 #[derive(Debug)]
 pub struct SortExec {
     input: Arc<dyn ExecutionPlan>,
     exprs: Vec<PhysicalSortExpr>,
     fetch: Option<usize>,
+    offset: usize,
     metrics: ExecutionPlanMetricsSet,
     props: OnceCell<PlanProperties>,
 }
@@ -96,10 +115,42 @@ impl SortExec {
             input,
             exprs,
             fetch,
+            offset: 0,
             metrics,
             props: OnceCell::new(),
         }
     }
+
+    /// returns the number of sorted rows to keep, including the skipped
+    /// leading rows
+    pub fn fetch(&self) -> Option<usize> {
+        self.fetch
+    }
+
+    /// returns the number of leading sorted rows skipped in output
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// creates a SortExec outputting at most `fetch` rows, used for folding a
+    /// limit (offset + fetch rows if the limit has offset) into the sorter
+    pub fn with_fetch(&self, fetch: usize) -> Self {
+        let fetch = self.fetch.map(|f| f.min(fetch)).unwrap_or(fetch);
+        let mut sort = Self::new(self.input.clone(), self.exprs.clone(), Some(fetch));
+        sort.offset = self.offset;
+        sort
+    }
+
+    /// creates a SortExec which additionally skips `offset` rows and then
+    /// outputs at most `limit` rows, used for folding a limit with offset
+    /// into the sorter. skipped rows are discarded when merging and never
+    /// materialized.
+    pub fn with_limit_and_offset(&self, limit: usize, offset: usize) -> Self {
+        let offset = self.offset.saturating_add(offset);
+        let mut sort = self.with_fetch(offset.saturating_add(limit));
+        sort.offset = offset;
+        sort
+    }
 }
 
 impl DisplayAs for SortExec {
@@ -130,7 +181,7 @@ impl ExecutionPlan for SortExec {
     fn properties(&self) -> &PlanProperties {
         self.props.get_or_init(|| {
             PlanProperties::new(
-                EquivalenceProperties::new(self.schema()),
+                EquivalenceProperties::new_with_orderings(self.schema(), &[self.exprs.clone()]),
                 self.input.output_partitioning().clone(),
                 ExecutionMode::Bounded,
             )
@@ -145,11 +196,9 @@ impl ExecutionPlan for SortExec {
         self: Arc<Self>,
         children: Vec<Arc<dyn ExecutionPlan>>,
     ) -> Result<Arc<dyn ExecutionPlan>> {
-        Ok(Arc::new(Self::new(
-            children[0].clone(),
-            self.exprs.clone(),
-            self.fetch,
-        )))
+        let mut sort = Self::new(children[0].clone(), self.exprs.clone(), self.fetch);
+        sort.offset = self.offset;
+        Ok(Arc::new(sort))
     }
 
     fn execute(
@@ -166,7 +215,11 @@ impl ExecutionPlan for SortExec {
     }
 
     fn statistics(&self) -> Result<Statistics> {
-        todo!()
+        let input_stats = self.input.statistics()?;
+        Ok(match self.fetch {
+            Some(fetch) => limit_statistics(input_stats, fetch.saturating_sub(self.offset)),
+            None => input_stats,
+        })
     }
 }
 
@@ -181,8 +234,10 @@ struct ExternalSorter {
     mem_consumer_info: Option<Weak<MemConsumerInfo>>,
     prune_sort_keys_from_batch: Arc<PruneSortKeysFromBatch>,
     limit: usize,
+    offset: usize,
     data: Arc<Mutex<BufferedData>>,
     spills: Mutex<Vec<LevelSpill>>,
+    in_mem_spills: Mutex<Vec<Lz4InMemSpill>>,
     num_total_rows: AtomicUsize,
     mem_total_size: AtomicUsize,
 }
@@ -203,28 +258,84 @@ impl MemConsumer for ExternalSorter {
             .expect("consumer info not set")
     }
 
+    fn consumer_type(&self) -> MemConsumerType {
+        MemConsumerType::Sort
+    }
+
+    fn spill_metrics(&self) -> Option<&SpillMetrics> {
+        Some(self.exec_ctx.spill_metrics())
+    }
+
     async fn spill(&self) -> Result<()> {
-        let data = std::mem::take(&mut *self.data.lock().await);
+        self.spill_partial(usize::MAX).await
+    }
+
+    /// spills the oldest sorted runs until at least `spill_size` bytes of
+    /// buffered data are spilled, the newer runs are kept in memory
+    async fn spill_partial(&self, spill_size: usize) -> Result<()> {
+        let (data, remaining_mem_used) = {
+            let mut data = self.data.lock().await;
+            let spilled = data.split_oldest_runs(spill_size);
+            (spilled, data.mem_used())
+        };
+        let data_mem_used = data.mem_used();
+        self.exec_ctx.log_replay_event(|| ReplayEvent::Spill {
+            mem_size: data_mem_used,
+        });
         let sub_batch_size = compute_suggested_batch_size_for_kway_merge(
             self.mem_total_size(),
             self.num_total_rows(),
         );
 
-        let limit = self.limit;
+        // compress buffered data in memory first, which avoids disk io entirely
+        // if the compressed data is small enough
+        let mut in_mem_spills = self.in_mem_spills.lock().await;
+        let old_in_mem_spills_size = in_mem_spills.iter().map(|s| s.size()).sum::<usize>();
+        let mut in_mem_spills_size = old_in_mem_spills_size;
+        if data.num_rows > 0 {
+            let limit = self.limit;
+            let in_mem_spill = tokio::task::spawn_blocking(move || {
+                let mut spill: Box<dyn Spill> = Box::new(Lz4InMemSpill::default());
+                data.try_into_spill(&mut spill, sub_batch_size, limit)?;
+                let mut in_mem_spill = std::mem::take(downcast_any!(spill, mut Lz4InMemSpill)?);
+                in_mem_spill.shrink_to_fit();
+                Ok::<_, DataFusionError>(in_mem_spill)
+            })
+            .await
+            .expect("tokio error")?;
+            in_mem_spills_size += in_mem_spill.size();
+            in_mem_spills.push(in_mem_spill);
+        }
+        let old_mem_used = data_mem_used + old_in_mem_spills_size;
+        if (in_mem_spills_size as f64) <= old_mem_used as f64 * IN_MEM_COMPRESSED_MAX_RATIO {
+            drop(in_mem_spills);
+            self.update_mem_used(in_mem_spills_size + remaining_mem_used)
+                .await?;
+            return Ok(());
+        }
+
+        // compressed data is still too large, move all of them into spills
+        let in_mem_spills = std::mem::take(&mut *in_mem_spills);
         let spill_metrics = self.exec_ctx.spill_metrics().clone();
-        let spill = tokio::task::spawn_blocking(move || {
-            let mut spill = try_new_spill(&spill_metrics)?;
-            data.try_into_spill(&mut spill, sub_batch_size, limit)?;
-            Ok::<_, DataFusionError>(spill)
+        let new_spills = tokio::task::spawn_blocking(move || {
+            in_mem_spills
+                .into_iter()
+                .map(|in_mem_spill| {
+                    let mut spill = try_new_spill(&spill_metrics)?;
+                    copy_spill(&in_mem_spill, spill.as_mut(), sort_spill_compression())?;
+                    Ok(spill)
+                })
+                .collect::<Result<Vec<_>>>()
         })
         .await
         .expect("tokio error")?;
 
-        self.spills
-            .lock()
-            .await
-            .push(LevelSpill { spill, level: 0 });
-        self.update_mem_used(0).await?;
+        self.spills.lock().await.extend(
+            new_spills
+                .into_iter()
+                .map(|spill| LevelSpill { spill, level: 0 }),
+        );
+        self.update_mem_used(remaining_mem_used).await?;
 
         // merge if there are too many spills
         let mut spills = self.spills.lock().await;
@@ -234,6 +345,10 @@ impl MemConsumer for ExternalSorter {
         }
         for level in 0..levels.len() {
             if levels[level].len() >= SPILL_MERGING_SIZE {
+                self.exec_ctx.log_replay_event(|| ReplayEvent::MergeSpills {
+                    num_spills: levels[level].len(),
+                    output: false,
+                });
                 let merged = merge_spills(
                     std::mem::take(&mut levels[level]),
                     self.exec_ctx.spill_metrics(),
@@ -270,9 +385,9 @@ impl BufferedData {
         sub_batch_size: usize,
         limit: usize,
     ) -> Result<()> {
-        let mut writer = spill.get_compressed_writer();
+        let mut writer = spill.get_compressed_writer_with(sort_spill_compression());
         for (key_collector, batch) in
-            self.into_sorted_batches::<SqueezeKeyCollector>(sub_batch_size, limit)?
+            self.into_sorted_batches::<SqueezeKeyCollector>(sub_batch_size, limit, 0)?
         {
             write_one_batch(batch.num_rows(), batch.columns(), &mut writer)?;
             writer.write_all(&key_collector.store)?;
@@ -285,6 +400,44 @@ impl BufferedData {
         self.sorted_batches_mem_used + self.sorted_key_stores_mem_used
     }
 
+    /// splits out the oldest sorted runs whose memory used reaches
+    /// `spill_size`, or all runs if there is not enough data
+    fn split_oldest_runs(&mut self, spill_size: usize) -> Self {
+        let mut num_runs = 0;
+        let mut split_mem_used = 0;
+        while num_runs < self.sorted_batches.len() && split_mem_used < spill_size {
+            split_mem_used += self.sorted_batches[num_runs].get_array_mem_size();
+            split_mem_used += self.sorted_key_stores[num_runs].len();
+            num_runs += 1;
+        }
+        if num_runs == self.sorted_batches.len() {
+            return std::mem::take(self);
+        }
+
+        let sorted_batches = self.sorted_batches.drain(..num_runs).collect::<Vec<_>>();
+        let sorted_key_stores = self.sorted_key_stores.drain(..num_runs).collect::<Vec<_>>();
+        let sorted_batches_mem_used = sorted_batches
+            .iter()
+            .map(|batch| batch.get_array_mem_size())
+            .sum::<usize>();
+        let sorted_key_stores_mem_used = sorted_key_stores
+            .iter()
+            .map(|key_store| key_store.len())
+            .sum::<usize>();
+        let num_rows = sorted_batches.iter().map(|batch| batch.num_rows()).sum();
+
+        self.sorted_batches_mem_used -= sorted_batches_mem_used;
+        self.sorted_key_stores_mem_used -= sorted_key_stores_mem_used;
+        self.num_rows = self.num_rows.saturating_sub(num_rows);
+        Self {
+            sorted_key_stores,
+            sorted_key_stores_mem_used,
+            sorted_batches,
+            sorted_batches_mem_used,
+            num_rows,
+        }
+    }
+
     fn add_batch(&mut self, batch: RecordBatch, sorter: &ExternalSorter) -> Result<()> {
         self.num_rows += batch.num_rows();
         let (key_rows, batch) = sorter.prune_sort_keys_from_batch.prune(batch)?;
@@ -328,10 +481,13 @@ impl BufferedData {
         Ok(())
     }
 
+    /// merges sorted batches, the first `offset` rows within `limit` are
+    /// skipped
     fn into_sorted_batches<'a, KC: KeyCollector>(
         self,
         batch_size: usize,
         limit: usize,
+        offset: usize,
     ) -> Result<impl Iterator<Item = (KC, RecordBatch)>> {
         struct Cursor {
             idx: usize,
@@ -391,7 +547,7 @@ impl BufferedData {
                 if other.finished() {
                     return true;
                 }
-                self.cur_key() < other.cur_key()
+                self.key_reader.cur_key_lt(&other.key_reader)
             }
         }
 
@@ -401,6 +557,7 @@ impl BufferedData {
             is_all_pruned: bool,
             batch_size: usize,
             num_output_rows: usize,
+            num_skipping_rows: usize,
             limit: usize,
             _phantom: PhantomData<KC>,
         }
@@ -409,6 +566,23 @@ impl BufferedData {
             type Item = (KC, RecordBatch);
 
             fn next(&mut self) -> Option<Self::Item> {
+                // skip leading rows, skipped rows are counted in output rows
+                if self.num_skipping_rows > 0 {
+                    let num_skipped = self
+                        .num_skipping_rows
+                        .min(self.limit.saturating_sub(self.num_output_rows));
+                    let mut min_cursor = self.cursors.peek_mut();
+                    for _ in 0..num_skipped {
+                        min_cursor.forward();
+                        if min_cursor.finished() || !min_cursor.is_equal_to_prev_key() {
+                            min_cursor.adjust();
+                        }
+                    }
+                    drop(min_cursor);
+                    self.num_skipping_rows = 0;
+                    self.num_output_rows += num_skipped;
+                }
+
                 if self.num_output_rows >= self.limit {
                     return None;
                 }
@@ -462,6 +636,7 @@ impl BufferedData {
             is_all_pruned,
             limit: limit.min(self.num_rows),
             num_output_rows: 0,
+            num_skipping_rows: offset,
             _phantom: PhantomData,
         }))
     }
@@ -475,6 +650,13 @@ impl ExecuteWithColumnPruning for SortExec {
         projection: &[usize],
     ) -> Result<SendableRecordBatchStream> {
         let exec_ctx = ExecutionContext::new(context, partition, self.schema(), &self.metrics);
+        if self.is_input_sorted() {
+            return self.execute_sorted_input(exec_ctx, projection);
+        }
+        if let Some(fetch) = self.fetch.filter(|&fetch| fetch <= TOP_K_MAX_FETCH) {
+            return self.execute_top_k(exec_ctx, projection, fetch);
+        }
+
         let prune_sort_keys_from_batch = Arc::new(PruneSortKeysFromBatch::try_new(
             self.input.schema(),
             projection,
@@ -486,8 +668,10 @@ impl ExecuteWithColumnPruning for SortExec {
             mem_consumer_info: None,
             prune_sort_keys_from_batch,
             limit: self.fetch.unwrap_or(usize::MAX),
+            offset: self.offset,
             data: Default::default(),
             spills: Default::default(),
+            in_mem_spills: Default::default(),
             num_total_rows: Default::default(),
             mem_total_size: Default::default(),
         });
@@ -517,6 +701,317 @@ impl ExecuteWithColumnPruning for SortExec {
     }
 }
 
+impl SortExec {
+    /// returns whether the input already produces the required ordering
+    fn is_input_sorted(&self) -> bool {
+        !self.exprs.is_empty()
+            && self
+                .input
+                .equivalence_properties()
+                .ordering_satisfy(&self.exprs)
+    }
+
+    /// streams the already sorted input directly, verifying that rows are
+    /// really in order
+    fn execute_sorted_input(
+        &self,
+        exec_ctx: Arc<ExecutionContext>,
+        projection: &[usize],
+    ) -> Result<SendableRecordBatchStream> {
+        log::info!(
+            "[partition={}] input of SortExec is already sorted, skip sorting",
+            exec_ctx.partition_id(),
+        );
+        let mut verifier = SortOrderVerifier::try_new(&self.input.schema(), &self.exprs)?;
+        let projection = projection.to_vec();
+        let limit = self.fetch.unwrap_or(usize::MAX);
+        let offset = self.offset;
+        let elapsed_compute = exec_ctx.baseline_metrics().elapsed_compute().clone();
+        let mut input = exec_ctx.execute_with_input_stats(&self.input)?;
+
+        let output =
+            exec_ctx
+                .clone()
+                .output_with_sender("SortedPassthrough", move |sender| async move {
+                    let _timer = elapsed_compute.timer();
+                    sender.exclude_time(&elapsed_compute);
+
+                    let mut num_output_rows = 0;
+                    while num_output_rows < limit {
+                        let Some(batch) = elapsed_compute
+                            .exclude_timer_async(input.next())
+                            .await
+                            .transpose()?
+                        else {
+                            break;
+                        };
+                        verifier.verify(&batch)?;
+                        let num_rows = batch.num_rows().min(limit - num_output_rows);
+                        let skip = offset.saturating_sub(num_output_rows).min(num_rows);
+                        num_output_rows += num_rows;
+                        if skip == num_rows {
+                            continue;
+                        }
+                        let batch = batch.project(&projection)?.slice(skip, num_rows - skip);
+                        sender
+                            .exec_ctx()
+                            .baseline_metrics()
+                            .record_output(batch.num_rows());
+                        sender.send(batch).await;
+                    }
+                    Ok(())
+                });
+        Ok(exec_ctx.coalesce_with_default_batch_size(output))
+    }
+
+    fn execute_top_k(
+        &self,
+        exec_ctx: Arc<ExecutionContext>,
+        projection: &[usize],
+        k: usize,
+    ) -> Result<SendableRecordBatchStream> {
+        let mut top_k_sorter =
+            TopKSorter::try_new(&self.input.schema(), &self.exprs, projection, k)?;
+        let mut num_skipping_rows = self.offset;
+        let elapsed_compute = exec_ctx.baseline_metrics().elapsed_compute().clone();
+        let mut input = exec_ctx.execute_with_input_stats(&self.input)?;
+
+        let output = exec_ctx
+            .clone()
+            .output_with_sender("TopKSort", move |sender| async move {
+                let _timer = elapsed_compute.timer();
+                sender.exclude_time(&elapsed_compute);
+
+                while let Some(batch) = elapsed_compute
+                    .exclude_timer_async(input.next())
+                    .await
+                    .transpose()?
+                {
+                    top_k_sorter.insert_batch(batch)?;
+                }
+                for batch in top_k_sorter.into_sorted_batches()? {
+                    let skip = num_skipping_rows.min(batch.num_rows());
+                    num_skipping_rows -= skip;
+                    if skip == batch.num_rows() {
+                        continue;
+                    }
+                    let batch = batch.slice(skip, batch.num_rows() - skip);
+                    sender
+                        .exec_ctx()
+                        .baseline_metrics()
+                        .record_output(batch.num_rows());
+                    sender.send(batch).await;
+                }
+                Ok(())
+            });
+        Ok(exec_ctx.coalesce_with_default_batch_size(output))
+    }
+}
+
+/// Verifies rows of consecutive batches are in the sort order
+struct SortOrderVerifier {
+    key_exprs: Vec<PhysicalSortExpr>,
+    row_converter: RowConverter,
+    last_key: Option<OwnedRow>,
+}
+
+impl SortOrderVerifier {
+    fn try_new(input_schema: &SchemaRef, exprs: &[PhysicalSortExpr]) -> Result<Self> {
+        let row_converter = RowConverter::new(
+            exprs
+                .iter()
+                .map(|expr| {
+                    Ok(SortField::new_with_options(
+                        expr.expr.data_type(input_schema)?,
+                        expr.options,
+                    ))
+                })
+                .collect::<Result<Vec<_>>>()?,
+        )?;
+        Ok(Self {
+            key_exprs: exprs.to_vec(),
+            row_converter,
+            last_key: None,
+        })
+    }
+
+    fn verify(&mut self, batch: &RecordBatch) -> Result<()> {
+        if batch.num_rows() == 0 {
+            return Ok(());
+        }
+        let key_cols: Vec<ArrayRef> = self
+            .key_exprs
+            .iter()
+            .map(|expr| {
+                expr.expr
+                    .evaluate(batch)
+                    .and_then(|cv| cv.into_array(batch.num_rows()))
+            })
+            .collect::<Result<_>>()?;
+        let key_rows = self.row_converter.convert_columns(&key_cols)?;
+
+        let mut prev_key = self.last_key.as_ref().map(|key| key.row());
+        for (row_idx, key) in key_rows.iter().enumerate() {
+            if prev_key.is_some_and(|prev_key| prev_key > key) {
+                return df_execution_err!(
+                    "input of SortExec is declared sorted but row {row_idx} is out of order"
+                );
+            }
+            prev_key = Some(key);
+        }
+        let last_key = prev_key.map(|key| key.owned());
+        self.last_key = last_key;
+        Ok(())
+    }
+}
+
+/// Keeps the smallest k rows in a bounded max-heap of sort keys, heap entries
+/// refer to rows of the retained input batches, which are compacted when they
+/// grow much larger than k rows.
+struct TopKSorter {
+    k: usize,
+    key_exprs: Vec<PhysicalSortExpr>,
+    row_converter: RowConverter,
+    projection: Vec<usize>,
+    heap: BinaryHeap<TopKEntry>,
+    batches: Vec<RecordBatch>,
+    num_batch_rows: usize,
+}
+
+struct TopKEntry {
+    key: OwnedRow,
+    batch_idx: usize,
+    row_idx: usize,
+}
+
+impl PartialEq for TopKEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for TopKEntry {}
+
+impl PartialOrd for TopKEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TopKEntry {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+impl TopKSorter {
+    fn try_new(
+        input_schema: &SchemaRef,
+        exprs: &[PhysicalSortExpr],
+        projection: &[usize],
+        k: usize,
+    ) -> Result<Self> {
+        let row_converter = RowConverter::new(
+            exprs
+                .iter()
+                .map(|expr| {
+                    Ok(SortField::new_with_options(
+                        expr.expr.data_type(input_schema)?,
+                        expr.options,
+                    ))
+                })
+                .collect::<Result<Vec<_>>>()?,
+        )?;
+        Ok(Self {
+            k,
+            key_exprs: exprs.to_vec(),
+            row_converter,
+            projection: projection.to_vec(),
+            heap: BinaryHeap::with_capacity(k),
+            batches: vec![],
+            num_batch_rows: 0,
+        })
+    }
+
+    fn insert_batch(&mut self, batch: RecordBatch) -> Result<()> {
+        if self.k == 0 || batch.num_rows() == 0 {
+            return Ok(());
+        }
+        let key_cols: Vec<ArrayRef> = self
+            .key_exprs
+            .iter()
+            .map(|expr| {
+                expr.expr
+                    .evaluate(&batch)
+                    .and_then(|cv| cv.into_array(batch.num_rows()))
+            })
+            .collect::<Result<_>>()?;
+        let key_rows = self.row_converter.convert_columns(&key_cols)?;
+
+        let batch_idx = self.batches.len();
+        let mut num_inserted = 0;
+        for (row_idx, key) in key_rows.iter().enumerate() {
+            let entry = || TopKEntry {
+                key: key.owned(),
+                batch_idx,
+                row_idx,
+            };
+            if self.heap.len() < self.k {
+                self.heap.push(entry());
+            } else if let Some(mut top) = self.heap.peek_mut().filter(|top| key < top.key.row()) {
+                *top = entry();
+            } else {
+                continue;
+            }
+            num_inserted += 1;
+        }
+
+        // retain the batch only if some of its rows are in the heap
+        if num_inserted > 0 {
+            self.num_batch_rows += batch.num_rows();
+            self.batches.push(batch.project(&self.projection)?);
+            if self.num_batch_rows > self.k * 2 {
+                self.compact()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn compact(&mut self) -> Result<()> {
+        let entries = std::mem::take(&mut self.heap).into_vec();
+        let compacted = self.take_rows(&entries)?;
+        self.num_batch_rows = compacted.num_rows();
+        self.batches = vec![compacted];
+        self.heap = entries
+            .into_iter()
+            .enumerate()
+            .map(|(row_idx, entry)| TopKEntry {
+                key: entry.key,
+                batch_idx: 0,
+                row_idx,
+            })
+            .collect();
+        Ok(())
+    }
+
+    fn into_sorted_batches(mut self) -> Result<Vec<RecordBatch>> {
+        let entries = std::mem::take(&mut self.heap).into_sorted_vec();
+        entries
+            .chunks(batch_size())
+            .map(|chunk| self.take_rows(chunk))
+            .collect()
+    }
+
+    fn take_rows(&self, entries: &[TopKEntry]) -> Result<RecordBatch> {
+        let indices = entries
+            .iter()
+            .map(|entry| (entry.batch_idx, entry.row_idx))
+            .collect::<Vec<_>>();
+        let interleaver = create_batch_interleaver(&self.batches, false)?;
+        interleaver(&indices)
+    }
+}
+
 impl Drop for ExternalSorter {
     fn drop(&mut self) {
         MemManager::deregister_consumer(self);
@@ -551,15 +1046,19 @@ impl ExternalSorter {
 
         let data = std::mem::take(&mut *self.data.lock().await);
         let spills = std::mem::take(&mut *self.spills.lock().await);
+        let in_mem_spills = std::mem::take(&mut *self.in_mem_spills.lock().await);
+        let in_mem_spills_size = in_mem_spills.iter().map(|s| s.size()).sum::<usize>();
         log::info!(
-            "{} starts outputting ({} spills + in_mem: {})",
+            "{} starts outputting ({} spills + {} compressed in-mem spills: {} + in_mem: {})",
             self.name(),
             spills.len(),
+            in_mem_spills.len(),
+            ByteSize(in_mem_spills_size as u64),
             ByteSize(data.mem_used() as u64)
         );
 
         // no spills -- output in-mem batches
-        if spills.is_empty() {
+        if spills.is_empty() && in_mem_spills.is_empty() {
             if data.num_rows == 0 {
                 // no data
                 return Ok(());
@@ -569,9 +1068,11 @@ impl ExternalSorter {
                 self.num_total_rows(),
             );
 
-            for (key_store, pruned_batch) in
-                data.into_sorted_batches::<SimpleKeyCollector>(sub_batch_size, self.limit)?
-            {
+            for (key_store, pruned_batch) in data.into_sorted_batches::<SimpleKeyCollector>(
+                sub_batch_size,
+                self.limit,
+                self.offset,
+            )? {
                 let batch = self
                     .prune_sort_keys_from_batch
                     .restore(pruned_batch, key_store)?;
@@ -590,6 +1091,11 @@ impl ExternalSorter {
             self.num_total_rows(),
         );
         let mut spills: Vec<Box<dyn Spill>> = spills.into_iter().map(|spill| spill.spill).collect();
+        spills.extend(
+            in_mem_spills
+                .into_iter()
+                .map(|spill| Box::new(spill) as Box<dyn Spill>),
+        );
 
         if self.mem_used_percent() < 0.25 {
             // if in-mem data is small, try to spill it into native raw bytes
@@ -607,8 +1113,10 @@ impl ExternalSorter {
 
             let in_mem_spill_size = in_mem_spill.len();
             spills.push(spill);
-            self.update_mem_used(in_mem_spill_size + spills.len() * SPILL_OFFHEAP_MEM_COST)
-                .await?;
+            self.update_mem_used(
+                in_mem_spill_size + in_mem_spills_size + spills.len() * SPILL_OFFHEAP_MEM_COST,
+            )
+            .await?;
         } else {
             let limit = self.limit;
             let spill_metrics = self.exec_ctx.spill_metrics().clone();
@@ -620,15 +1128,20 @@ impl ExternalSorter {
             .await
             .expect("tokio error")?;
             spills.push(spill);
-            self.update_mem_used(spills.len() * SPILL_OFFHEAP_MEM_COST)
+            self.update_mem_used(in_mem_spills_size + spills.len() * SPILL_OFFHEAP_MEM_COST)
                 .await?;
         }
 
+        self.exec_ctx.log_replay_event(|| ReplayEvent::MergeSpills {
+            num_spills: spills.len(),
+            output: true,
+        });
         let mut merger = ExternalMerger::<SimpleKeyCollector>::try_new(
-            &mut spills,
+            spills,
             self.prune_sort_keys_from_batch.pruned_schema(),
             sub_batch_size,
             self.limit,
+            self.offset,
         )?;
         while let Some((key_collector, pruned_batch)) = merger.next().transpose()? {
             let batch = self
@@ -655,13 +1168,79 @@ impl ExternalSorter {
     }
 }
 
-struct SpillCursor<'a> {
-    id: usize,
+/// Batch read from a spill in background, with the encoded sorted keys of
+/// its rows
+struct PrefetchedBatch {
+    batch: RecordBatch,
+    key_store: Vec<u8>,
+}
+
+impl PrefetchedBatch {
+    fn mem_size(&self) -> usize {
+        self.batch.get_array_mem_size() + self.key_store.capacity()
+    }
+}
+
+/// reads and decompresses batches of the spill in a background blocking
+/// task. at most `SPILL_PREFETCH_NUM_BATCHES` batches are buffered ahead of
+/// the cursor, the task stops once the receiver is dropped.
+fn spawn_spill_prefetcher(
+    spill: Box<dyn Spill>,
     pruned_schema: SchemaRef,
-    input: SpillCompressedReader<'a>,
+    prefetched_mem_used: Arc<AtomicUsize>,
+) -> Receiver<Result<PrefetchedBatch>> {
+    let (sender, receiver) = sync_channel(SPILL_PREFETCH_NUM_BATCHES);
+    tokio::task::spawn_blocking(move || {
+        let mut input = match spill.get_compressed_reader_with(sort_spill_compression()) {
+            Ok(input) => input,
+            Err(err) => {
+                let _ = sender.send(Err(err));
+                return;
+            }
+        };
+        loop {
+            let prefetched = match read_spilled_batch(&mut input, &pruned_schema) {
+                Ok(Some(prefetched)) => {
+                    prefetched_mem_used.fetch_add(prefetched.mem_size(), SeqCst);
+                    Ok(prefetched)
+                }
+                Ok(None) => break,
+                Err(err) => Err(err),
+            };
+            let is_err = prefetched.is_err();
+            if sender.send(prefetched).is_err() || is_err {
+                break; // cursor is dropped or error occurred
+            }
+        }
+    });
+    receiver
+}
+
+fn read_spilled_batch(
+    input: &mut impl Read,
+    pruned_schema: &SchemaRef,
+) -> Result<Option<PrefetchedBatch>> {
+    let Some((num_rows, cols)) = read_one_batch(&mut *input, pruned_schema)? else {
+        return Ok(None);
+    };
+    let batch = RecordBatch::try_new_with_options(
+        pruned_schema.clone(),
+        cols,
+        &RecordBatchOptions::new().with_row_count(Some(num_rows)),
+    )?;
+    let mut key_store = vec![];
+    SortedKeysReader::copy_encoded_keys(num_rows, input, &mut key_store)?;
+    Ok(Some(PrefetchedBatch { batch, key_store }))
+}
+
+struct SpillCursor {
+    id: usize,
+    prefetched: Receiver<Result<PrefetchedBatch>>,
+    prefetched_mem_used: Arc<AtomicUsize>,
     cur_batch_num_rows: usize,
     cur_loaded_num_rows: usize,
     cur_batches: Vec<RecordBatch>,
+    cur_key_store_cursor: Cursor<Vec<u8>>,
     cur_key_reader: SortedKeysReader,
     cur_key_row_idx: usize,
     cur_batch_idx: usize,
@@ -670,7 +1249,7 @@ struct SpillCursor<'a> {
     finished: bool,
 }
 
-impl<'a> ComparableForLoserTree for SpillCursor<'a> {
+impl ComparableForLoserTree for SpillCursor {
     #[inline(always)]
     fn lt(&self, other: &Self) -> bool {
         if self.finished {
@@ -679,23 +1258,21 @@ impl<'a> ComparableForLoserTree for SpillCursor<'a> {
         if other.finished {
             return true;
         }
-        self.cur_key() < other.cur_key()
+        self.cur_key_reader.cur_key_lt(&other.cur_key_reader)
     }
 }
 
-impl<'a> SpillCursor<'a> {
-    fn try_from_spill(
-        id: usize,
-        pruned_schema: SchemaRef,
-        spill: &'a mut Box<dyn Spill>,
-    ) -> Result<Self> {
+impl SpillCursor {
+    fn try_from_spill(id: usize, pruned_schema: SchemaRef, spill: Box<dyn Spill>) -> Result<Self> {
+        let prefetched_mem_used = Arc::new(AtomicUsize::new(0));
         let mut iter = SpillCursor {
             id,
-            pruned_schema,
-            input: spill.get_compressed_reader(),
+            prefetched: spawn_spill_prefetcher(spill, pruned_schema, prefetched_mem_used.clone()),
+            prefetched_mem_used,
             cur_batch_num_rows: 0,
             cur_loaded_num_rows: 0,
             cur_batches: vec![],
+            cur_key_store_cursor: Cursor::default(),
             cur_key_reader: SortedKeysReader::default(),
             cur_key_row_idx: 0,
             cur_batch_idx: 0,
@@ -725,7 +1302,7 @@ impl<'a> SpillCursor<'a> {
             }
         }
         self.cur_key_reader
-            .next_key(&mut self.input)
+            .next_key(&mut self.cur_key_store_cursor)
             .expect("error reading next key");
         self.cur_key_row_idx += 1;
         Ok(())
@@ -736,16 +1313,17 @@ impl<'a> SpillCursor<'a> {
     }
 
     fn load_next_batch(&mut self) -> Result<bool> {
-        if let Some((num_rows, cols)) = read_one_batch(&mut self.input, &self.pruned_schema)? {
-            let batch = RecordBatch::try_new_with_options(
-                self.pruned_schema.clone(),
-                cols,
-                &RecordBatchOptions::new().with_row_count(Some(num_rows)),
-            )?;
+        // receiving fails only if the prefetcher has finished
+        if let Ok(prefetched) = self.prefetched.recv() {
+            let prefetched = prefetched?;
+            self.prefetched_mem_used
+                .fetch_sub(prefetched.mem_size(), SeqCst);
+            let PrefetchedBatch { batch, key_store } = prefetched;
             self.cur_mem_used += batch.get_array_mem_size();
             self.cur_batch_num_rows = batch.num_rows();
             self.cur_loaded_num_rows = 0;
             self.cur_batches.push(batch);
+            self.cur_key_store_cursor = Cursor::new(key_store);
             self.cur_key_reader = SortedKeysReader::default();
             self.cur_key_row_idx = 0;
             return Ok(true);
@@ -776,28 +1354,30 @@ impl<'a> SpillCursor<'a> {
     }
 }
 
-struct ExternalMerger<'a, KC: KeyCollector> {
-    cursors: LoserTree<SpillCursor<'a>>,
+struct ExternalMerger<KC: KeyCollector> {
+    cursors: LoserTree<SpillCursor>,
     pruned_schema: SchemaRef,
     sub_batch_size: usize,
     limit: usize,
-    num_total_output_rows: usize,
+    num_total_output_rows: usize, // including skipped rows
+    num_skipping_rows: usize,
     staging_cursor_ids: Vec<usize>,
     staging_key_collector: KC,
     staging_num_rows: usize,
 }
 
-impl<'a, KC: KeyCollector> ExternalMerger<'a, KC> {
+impl<KC: KeyCollector> ExternalMerger<KC> {
     fn try_new(
-        spills: &'a mut [Box<dyn Spill>],
+        spills: Vec<Box<dyn Spill>>,
         pruned_schema: SchemaRef,
         sub_batch_size: usize,
         limit: usize,
+        offset: usize,
     ) -> Result<Self> {
         Ok(Self {
             cursors: LoserTree::new(
                 spills
-                    .iter_mut()
+                    .into_iter()
                     .enumerate()
                     .map(|(id, spill)| {
                         SpillCursor::try_from_spill(id, pruned_schema.clone(), spill)
@@ -808,6 +1388,7 @@ impl<'a, KC: KeyCollector> ExternalMerger<'a, KC> {
             sub_batch_size,
             limit,
             num_total_output_rows: 0,
+            num_skipping_rows: offset,
             staging_cursor_ids: Vec::with_capacity(sub_batch_size),
             staging_key_collector: KC::default(),
             staging_num_rows: 0,
@@ -815,7 +1396,7 @@ impl<'a, KC: KeyCollector> ExternalMerger<'a, KC> {
     }
 }
 
-impl<KC: KeyCollector> Iterator for ExternalMerger<'_, KC> {
+impl<KC: KeyCollector> Iterator for ExternalMerger<KC> {
     type Item = Result<(KC, RecordBatch)>;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -823,19 +1404,21 @@ impl<KC: KeyCollector> Iterator for ExternalMerger<'_, KC> {
     }
 }
 
-impl<KC: KeyCollector> ExternalMerger<'_, KC> {
+impl<KC: KeyCollector> ExternalMerger<KC> {
     fn cursors_mem_used(&self) -> usize {
         self.cursors.len() * SPILL_OFFHEAP_MEM_COST
             + self
                 .cursors
                 .values()
                 .iter()
-                .map(|cursor| cursor.cur_mem_used)
+                .map(|cursor| cursor.cur_mem_used + cursor.prefetched_mem_used.load(SeqCst))
                 .sum::<usize>()
     }
 
     fn merge_one(&mut self) -> Result<Option<(KC, RecordBatch)>> {
+        SparkTaskContext::check_cancelled()?;
         let pruned_schema = self.pruned_schema.clone();
+        self.skip_rows()?;
 
         // collect merged records to staging
         if self.num_total_output_rows < self.limit {
@@ -870,6 +1453,38 @@ impl<KC: KeyCollector> ExternalMerger<'_, KC> {
         Ok(None)
     }
 
+    /// skips leading rows without collecting them into staging. rows are
+    /// skipped by sub batches so that finished batches are released in time.
+    fn skip_rows(&mut self) -> Result<()> {
+        let has_pruned_cols = !self.pruned_schema.fields().is_empty();
+        while self.num_skipping_rows > 0 && self.num_total_output_rows < self.limit {
+            let num_rows = self
+                .num_skipping_rows
+                .min(self.sub_batch_size)
+                .min(self.limit - self.num_total_output_rows);
+            let mut min_cursor = self.cursors.peek_mut();
+            for _ in 0..num_rows {
+                if min_cursor.finished {
+                    break;
+                }
+                if has_pruned_cols {
+                    min_cursor.next_row();
+                }
+                min_cursor.next_key()?;
+                if min_cursor.finished || !min_cursor.is_equal_to_prev_key() {
+                    min_cursor.adjust();
+                }
+            }
+            drop(min_cursor);
+            self.num_skipping_rows -= num_rows;
+            self.num_total_output_rows += num_rows;
+            for cursor in self.cursors.values_mut() {
+                cursor.clear_finished_batches();
+            }
+        }
+        Ok(())
+    }
+
     fn flush_staging(&mut self) -> Result<(KC, RecordBatch)> {
         let num_rows = self
             .staging_num_rows
@@ -919,7 +1534,7 @@ impl<KC: KeyCollector> ExternalMerger<'_, KC> {
 }
 
 fn merge_spills(
-    mut spills: Vec<Box<dyn Spill>>,
+    spills: Vec<Box<dyn Spill>>,
     spill_metrics: &SpillMetrics,
     sub_batch_size: usize,
     limit: usize,
@@ -931,12 +1546,13 @@ fn merge_spills(
     }
 
     let mut output_spill = try_new_spill(spill_metrics)?;
-    let mut output_writer = output_spill.get_compressed_writer();
+    let mut output_writer = output_spill.get_compressed_writer_with(sort_spill_compression());
     let mut merger = ExternalMerger::<SqueezeKeyCollector>::try_new(
-        &mut spills,
+        spills,
         pruned_schema,
         sub_batch_size,
         limit,
+        0,
     )?;
 
     while let Some((key_collector, pruned_batch)) = merger.next().transpose()? {
@@ -1001,10 +1617,14 @@ impl PruneSortKeysFromBatch {
         let sort_row_parser = sort_row_converter.lock().parser();
         let input_projected_schema = Arc::new(input_schema.project(input_projection)?);
 
+        // view columns are not pruned, interleaving them is cheaper than
+        // restoring them from key rows, which copies all values
         let mut relation = vec![];
         for (expr_idx, expr) in exprs.iter().enumerate() {
             if let Some(col) = expr.expr.as_any().downcast_ref::<Column>() {
-                relation.push((expr_idx, col.index()));
+                if !is_view_type(input_schema.field(col.index()).data_type()) {
+                    relation.push((expr_idx, col.index()));
+                }
             }
         }
 
@@ -1244,6 +1864,7 @@ impl SortedKeysWriter {
 #[derive(Default)]
 struct SortedKeysReader {
     cur_key: Vec<u8>,
+    cur_key_prefix: u64, // inlined prefix of cur_key for fast comparison
     is_equal_to_prev: bool,
 }
 
@@ -1256,11 +1877,60 @@ impl SortedKeysReader {
             let prefix_len = read_len(r)?;
             self.cur_key.resize(prefix_len + suffix_len, 0);
             r.read_exact(&mut self.cur_key[prefix_len..][..suffix_len])?;
+
+            // inlined prefix is unchanged if it is shared with previous key
+            if prefix_len < KEY_PREFIX_LEN || self.cur_key.len() < KEY_PREFIX_LEN {
+                self.cur_key_prefix = key_prefix(&self.cur_key);
+            }
         } else {
             self.is_equal_to_prev = true;
         }
         Ok(())
     }
+
+    /// copies encoded keys of the given number of rows without decoding
+    fn copy_encoded_keys(
+        num_keys: usize,
+        r: &mut impl Read,
+        w: &mut Vec<u8>,
+    ) -> std::io::Result<()> {
+        for _ in 0..num_keys {
+            let b = read_len(r)?;
+            write_len(b, w)?;
+            if b > 0 {
+                write_len(read_len(r)?, w)?;
+                let suffix_start = w.len();
+                w.resize(suffix_start + b - 1, 0);
+                r.read_exact(&mut w[suffix_start..])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// compares current keys of two readers. most comparisons are resolved
+    /// on the inlined prefixes, full keys are compared only if the prefixes
+    /// are equal.
+    #[inline(always)]
+    fn cur_key_lt(&self, other: &Self) -> bool {
+        if self.cur_key_prefix != other.cur_key_prefix {
+            return self.cur_key_prefix < other.cur_key_prefix;
+        }
+        self.cur_key < other.cur_key
+    }
+}
+
+const KEY_PREFIX_LEN: usize = 8;
+
+/// returns the first 8 bytes of key as a big-endian u64, zero-padded if the
+/// key is shorter. ordering of prefixes is consistent with the lexicographic
+/// ordering of keys, so keys with different prefixes are ordered by their
+/// prefixes.
+#[inline]
+fn key_prefix(key: &[u8]) -> u64 {
+    let mut prefix = [0u8; KEY_PREFIX_LEN];
+    let len = key.len().min(KEY_PREFIX_LEN);
+    prefix[..len].copy_from_slice(&key[..len]);
+    u64::from_be_bytes(prefix)
 }
 
 fn common_prefix_len(a: &[u8], b: &[u8]) -> usize {
@@ -1281,20 +1951,30 @@ mod test {
     use std::sync::Arc;
 
     use arrow::{
-        array::Int32Array,
+        array::{AsArray, Int32Array},
         compute::SortOptions,
-        datatypes::{DataType, Field, Schema},
+        datatypes::{DataType, Field, Int32Type, Schema},
         record_batch::RecordBatch,
     };
     use datafusion::{
         assert_batches_eq,
         common::Result,
         physical_expr::{expressions::Column, PhysicalSortExpr},
-        physical_plan::{common, memory::MemoryExec, ExecutionPlan},
+        physical_plan::{
+            common, memory::MemoryExec, metrics::ExecutionPlanMetricsSet, ExecutionPlan,
+        },
         prelude::SessionContext,
     };
-
-    use crate::{memmgr::MemManager, sort_exec::SortExec};
+    use datafusion_ext_commons::spark_task_context::SparkTaskContext;
+
+    use crate::{
+        common::{column_pruning::ExecuteWithColumnPruning, execution_context::ExecutionContext},
+        memmgr::{MemConsumer, MemManager},
+        sort_exec::{
+            key_prefix, ExternalSorter, PruneSortKeysFromBatch, SortExec, SortedKeysReader,
+            SortedKeysWriter,
+        },
+    };
 
     fn build_table_i32(
         a: (&str, &Vec<i32>),
@@ -1362,6 +2042,413 @@ mod test {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_sort_top_k() -> Result<()> {
+        MemManager::init(100);
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+        let n = 1000;
+        let batches = (0..n)
+            .step_by(100)
+            .map(|offset| {
+                build_table_i32(
+                    (
+                        "a",
+                        &(offset..offset + 100).map(|i| (i * 37) % 101).collect(),
+                    ),
+                    ("b", &(offset..offset + 100).collect()),
+                    ("c", &vec![0; 100]),
+                )
+            })
+            .collect::<Vec<_>>();
+        let schema = batches[0].schema();
+        let input = Arc::new(MemoryExec::try_new(&[batches], schema, None)?);
+        let sort_exprs = vec![PhysicalSortExpr {
+            expr: Arc::new(Column::new("a", 0)),
+            options: SortOptions {
+                descending: true,
+                nulls_first: false,
+            },
+        }];
+
+        // sort by a, output only b and a
+        let sort = SortExec::new(input, sort_exprs, Some(25));
+        let output = sort.execute_projected(0, task_ctx, &[1, 0])?;
+        let batches = common::collect(output).await?;
+        let sorted_keys = batches
+            .iter()
+            .flat_map(|batch| {
+                assert_eq!(batch.num_columns(), 2);
+                batch
+                    .column(1)
+                    .as_primitive::<Int32Type>()
+                    .values()
+                    .to_vec()
+            })
+            .collect::<Vec<_>>();
+        let mut expected_keys = (0..n).map(|i| (i * 37) % 101).collect::<Vec<_>>();
+        expected_keys.sort_by(|a, b| b.cmp(a));
+        expected_keys.truncate(25);
+        assert_eq!(sorted_keys, expected_keys);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sort_with_offset() -> Result<()> {
+        MemManager::init(100);
+        let n = 1000;
+        let batch = build_table_i32(
+            ("a", &(0..n).map(|i| (i * 37) % 1000).collect()),
+            ("b", &vec![1; n as usize]),
+            ("c", &(0..n).map(|i| i % 3).collect()),
+        );
+        let schema = batch.schema();
+        let sort_exprs = vec![PhysicalSortExpr {
+            expr: Arc::new(Column::new("a", 0)),
+            options: SortOptions::default(),
+        }];
+
+        for (limit, offset, with_spills) in [(600, 500, true), (600, 500, false), (900, 950, true)]
+        {
+            let exec_ctx = ExecutionContext::new(
+                SessionContext::new().task_ctx(),
+                0,
+                schema.clone(),
+                &ExecutionPlanMetricsSet::new(),
+            );
+            let sorter = Arc::new(ExternalSorter {
+                exec_ctx: exec_ctx.clone(),
+                name: "ExternalSorter[partition=0]".to_string(),
+                mem_consumer_info: None,
+                prune_sort_keys_from_batch: Arc::new(PruneSortKeysFromBatch::try_new(
+                    schema.clone(),
+                    &[0, 1],
+                    &sort_exprs,
+                )?),
+                limit,
+                offset,
+                data: Default::default(),
+                spills: Default::default(),
+                in_mem_spills: Default::default(),
+                num_total_rows: Default::default(),
+                mem_total_size: Default::default(),
+            });
+            MemManager::register_consumer(sorter.clone(), false);
+
+            let batch = batch.clone();
+            let output = exec_ctx
+                .clone()
+                .output_with_sender("Sort", move |sender| async move {
+                    for offset in (0..n as usize).step_by(100) {
+                        sorter.insert_batch(batch.slice(offset, 100)).await?;
+                        if with_spills {
+                            sorter.spill().await?;
+                        }
+                    }
+                    sorter.output(sender).await
+                });
+            let batches = common::collect(output).await?;
+            let sorted_keys = batches
+                .iter()
+                .flat_map(|batch| {
+                    assert_eq!(batch.num_columns(), 2);
+                    batch
+                        .column(0)
+                        .as_primitive::<Int32Type>()
+                        .values()
+                        .to_vec()
+                })
+                .collect::<Vec<_>>();
+            let expected_keys = (offset as i32..limit.min(n as usize) as i32).collect::<Vec<_>>();
+            assert_eq!(sorted_keys, expected_keys);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sort_sorted_input() -> Result<()> {
+        MemManager::init(100);
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+        let sort_exprs = vec![PhysicalSortExpr {
+            expr: Arc::new(Column::new("a", 0)),
+            options: SortOptions::default(),
+        }];
+        let batches = vec![
+            build_table_i32(
+                ("a", &vec![1, 2, 2]),
+                ("b", &vec![3, 2, 1]),
+                ("c", &vec![0; 3]),
+            ),
+            build_table_i32(
+                ("a", &vec![2, 5, 7]),
+                ("b", &vec![6, 5, 4]),
+                ("c", &vec![0; 3]),
+            ),
+        ];
+        let schema = batches[0].schema();
+        let input = Arc::new(
+            MemoryExec::try_new(&[batches], schema.clone(), None)?
+                .with_sort_information(vec![sort_exprs.clone()]),
+        );
+        let sort = SortExec::new(input, sort_exprs.clone(), Some(5));
+        assert!(sort.is_input_sorted());
+        let output = sort.execute_projected(0, task_ctx.clone(), &[1, 0])?;
+        let batches = common::collect(output).await?;
+        let expected = vec![
+            "+---+---+",
+            "| b | a |",
+            "+---+---+",
+            "| 3 | 1 |",
+            "| 2 | 2 |",
+            "| 1 | 2 |",
+            "| 6 | 2 |",
+            "| 5 | 5 |",
+            "+---+---+",
+        ];
+        assert_batches_eq!(expected, &batches);
+
+        // input declared sorted but actually not
+        let batches = vec![
+            build_table_i32(("a", &vec![1, 3]), ("b", &vec![0; 2]), ("c", &vec![0; 2])),
+            build_table_i32(("a", &vec![2, 4]), ("b", &vec![0; 2]), ("c", &vec![0; 2])),
+        ];
+        let input = Arc::new(
+            MemoryExec::try_new(&[batches], schema, None)?
+                .with_sort_information(vec![sort_exprs.clone()]),
+        );
+        let sort = SortExec::new(input, sort_exprs, None);
+        let output = sort.execute(0, task_ctx)?;
+        assert!(common::collect(output).await.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sorted_keys_prefix() -> Result<()> {
+        let keys: Vec<&[u8]> = vec![
+            b"",
+            b"\x00",
+            b"\x00\x00",
+            b"abc",
+            b"abcdefgh",
+            b"abcdefgh\x00",
+            b"abcdefghij",
+            b"abcdefghij",
+            b"abcdefgz",
+            b"b",
+        ];
+        let mut writer = SortedKeysWriter::default();
+        let mut buf = vec![];
+        for key in &keys {
+            writer.write_key(key, &mut buf)?;
+        }
+
+        let mut cursor = std::io::Cursor::new(buf);
+        let mut readers: Vec<SortedKeysReader> = vec![];
+        let mut reader = SortedKeysReader::default();
+        for key in &keys {
+            reader.next_key(&mut cursor)?;
+            assert_eq!(reader.cur_key, *key);
+            assert_eq!(reader.cur_key_prefix, key_prefix(key));
+            readers.push(SortedKeysReader {
+                cur_key: reader.cur_key.clone(),
+                cur_key_prefix: reader.cur_key_prefix,
+                is_equal_to_prev: reader.is_equal_to_prev,
+            });
+        }
+
+        for (i, r1) in readers.iter().enumerate() {
+            for (j, r2) in readers.iter().enumerate() {
+                assert_eq!(r1.cur_key_lt(r2), keys[i] < keys[j], "{i} vs {j}");
+            }
+        }
+        Ok(())
+    }
+
+    fn build_sorter(
+        exec_ctx: Arc<ExecutionContext>,
+        sort_exprs: &[PhysicalSortExpr],
+    ) -> Result<Arc<ExternalSorter>> {
+        let schema = exec_ctx.output_schema();
+        Ok(Arc::new(ExternalSorter {
+            exec_ctx,
+            name: "ExternalSorter[partition=0]".to_string(),
+            mem_consumer_info: None,
+            prune_sort_keys_from_batch: Arc::new(PruneSortKeysFromBatch::try_new(
+                schema,
+                &[0, 1, 2],
+                sort_exprs,
+            )?),
+            limit: usize::MAX,
+            offset: 0,
+            data: Default::default(),
+            spills: Default::default(),
+            in_mem_spills: Default::default(),
+            num_total_rows: Default::default(),
+            mem_total_size: Default::default(),
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_sort_with_compressed_in_mem_spills() -> Result<()> {
+        MemManager::init(100);
+        let n = 1000;
+        let batch = build_table_i32(
+            ("a", &(0..n).map(|i| i % 7).collect()),
+            ("b", &vec![1; n as usize]),
+            ("c", &(0..n).map(|i| i % 3).collect()),
+        );
+        let schema = batch.schema();
+        let sort_exprs = vec![PhysicalSortExpr {
+            expr: Arc::new(Column::new("a", 0)),
+            options: SortOptions::default(),
+        }];
+
+        let exec_ctx = ExecutionContext::new(
+            SessionContext::new().task_ctx(),
+            0,
+            schema.clone(),
+            &ExecutionPlanMetricsSet::new(),
+        );
+        let sorter = build_sorter(exec_ctx.clone(), &sort_exprs)?;
+        MemManager::register_consumer(sorter.clone(), false);
+
+        // compressible data is kept in memory after spilling
+        let output = exec_ctx
+            .clone()
+            .output_with_sender("Sort", move |sender| async move {
+                for offset in (0..n as usize).step_by(250) {
+                    sorter.insert_batch(batch.slice(offset, 250)).await?;
+                    sorter.spill().await?;
+                }
+                assert_eq!(sorter.in_mem_spills.lock().await.len(), 4);
+                assert!(sorter.spills.lock().await.is_empty());
+                sorter.output(sender).await
+            });
+        let batches = common::collect(output).await?;
+
+        let sorted_keys = batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column(0)
+                    .as_primitive::<Int32Type>()
+                    .values()
+                    .to_vec()
+            })
+            .collect::<Vec<_>>();
+        let mut expected_keys = (0..n).map(|i| i % 7).collect::<Vec<_>>();
+        expected_keys.sort();
+        assert_eq!(sorted_keys, expected_keys);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sort_with_partial_spills() -> Result<()> {
+        MemManager::init(100);
+        let n = 1000;
+        let batch = build_table_i32(
+            ("a", &(0..n).map(|i| (n - i) % 7).collect()),
+            ("b", &vec![1; n as usize]),
+            ("c", &(0..n).map(|i| i % 3).collect()),
+        );
+        let schema = batch.schema();
+        let sort_exprs = vec![PhysicalSortExpr {
+            expr: Arc::new(Column::new("a", 0)),
+            options: SortOptions::default(),
+        }];
+
+        let exec_ctx = ExecutionContext::new(
+            SessionContext::new().task_ctx(),
+            0,
+            schema.clone(),
+            &ExecutionPlanMetricsSet::new(),
+        );
+        let sorter = build_sorter(exec_ctx.clone(), &sort_exprs)?;
+        MemManager::register_consumer(sorter.clone(), false);
+
+        // only the oldest runs are spilled
+        let output = exec_ctx
+            .clone()
+            .output_with_sender("Sort", move |sender| async move {
+                for offset in (0..n as usize).step_by(250) {
+                    sorter.insert_batch(batch.slice(offset, 250)).await?;
+                }
+                sorter.spill_partial(1).await?;
+                assert_eq!(sorter.in_mem_spills.lock().await.len(), 1);
+                assert_eq!(sorter.data.lock().await.sorted_batches.len(), 3);
+                assert_eq!(sorter.data.lock().await.num_rows, 750);
+
+                let data_mem_used = sorter.data.lock().await.mem_used();
+                sorter.spill_partial(data_mem_used).await?;
+                assert_eq!(sorter.in_mem_spills.lock().await.len(), 2);
+                assert!(sorter.data.lock().await.sorted_batches.is_empty());
+                sorter.output(sender).await
+            });
+        let batches = common::collect(output).await?;
+
+        let sorted_keys = batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column(0)
+                    .as_primitive::<Int32Type>()
+                    .values()
+                    .to_vec()
+            })
+            .collect::<Vec<_>>();
+        let mut expected_keys = (0..n).map(|i| i % 7).collect::<Vec<_>>();
+        expected_keys.sort();
+        assert_eq!(sorted_keys, expected_keys);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sort_cancelled() -> Result<()> {
+        MemManager::init(100);
+        let n = 1000;
+        let batch = build_table_i32(
+            ("a", &(0..n).map(|i| (n - i) % 7).collect()),
+            ("b", &vec![1; n as usize]),
+            ("c", &(0..n).map(|i| i % 3).collect()),
+        );
+        let schema = batch.schema();
+        let sort_exprs = vec![PhysicalSortExpr {
+            expr: Arc::new(Column::new("a", 0)),
+            options: SortOptions::default(),
+        }];
+
+        let exec_ctx = ExecutionContext::new(
+            SessionContext::new().task_ctx(),
+            0,
+            schema.clone(),
+            &ExecutionPlanMetricsSet::new(),
+        );
+        let sorter = build_sorter(exec_ctx.clone(), &sort_exprs)?;
+        MemManager::register_consumer(sorter.clone(), false);
+
+        // the task is cancelled before merging spills
+        let task_context = Arc::new(SparkTaskContext::new(0, 0, 0));
+        SparkTaskContext::set_current(Some(task_context.clone()));
+        let output = exec_ctx
+            .clone()
+            .output_with_sender("Sort", move |sender| async move {
+                for offset in (0..n as usize).step_by(250) {
+                    sorter.insert_batch(batch.slice(offset, 250)).await?;
+                }
+                let data_mem_used = sorter.data.lock().await.mem_used();
+                sorter.spill_partial(data_mem_used).await?;
+                task_context.cancel();
+                sorter.output(sender).await
+            });
+        let result = common::collect(output).await;
+        SparkTaskContext::set_current(None);
+
+        let err = result.expect_err("sort is not cancelled");
+        assert!(err.to_string().contains("task cancelled"), "{err}");
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -14,8 +14,15 @@
 
 use std::{any::Any, fmt::Formatter, pin::Pin, sync::Arc};
 
-use arrow::{compute::SortOptions, datatypes::SchemaRef};
+use arrow::{
+    compute::SortOptions,
+    datatypes::{DataType, Schema, SchemaRef},
+};
 use async_trait::async_trait;
+use blaze_jni_bridge::{
+    conf::{self, IntConf},
+    is_jni_bridge_inited,
+};
 use datafusion::{
     common::{DataFusionError, JoinSide},
     error::Result,
@@ -28,13 +35,16 @@ use datafusion::{
         PlanProperties, SendableRecordBatchStream, Statistics,
     },
 };
-use datafusion_ext_commons::{batch_size, df_execution_err};
+use datafusion_ext_commons::{batch_size, df_execution_err, suggested_output_batch_mem_size};
 use once_cell::sync::OnceCell;
 
 use crate::{
     common::{
         column_pruning::ExecuteWithColumnPruning,
         execution_context::{ExecutionContext, WrappedRecordBatchSender},
+        partitioning::derive_join_partitioning,
+        runtime_filter::publish_runtime_filters_on_finish,
+        statistics::estimate_join_statistics,
         timer_helper::TimerHelper,
     },
     cur_forward,
@@ -57,6 +67,9 @@ pub struct SortMergeJoinExec {
     on: JoinOn,
     join_type: JoinType,
     sort_options: Vec<SortOptions>,
+    null_equals_null: bool,
+    left_runtime_filter_ids: Vec<Option<String>>,
+    right_runtime_filter_ids: Vec<Option<String>>,
     schema: SchemaRef,
     metrics: ExecutionPlanMetricsSet,
     props: OnceCell<PlanProperties>,
@@ -70,6 +83,7 @@ impl SortMergeJoinExec {
         on: JoinOn,
         join_type: JoinType,
         sort_options: Vec<SortOptions>,
+        null_equals_null: bool,
     ) -> Result<Self> {
         Ok(Self {
             schema,
@@ -78,11 +92,28 @@ impl SortMergeJoinExec {
             on,
             join_type,
             sort_options,
+            null_equals_null,
+            left_runtime_filter_ids: vec![],
+            right_runtime_filter_ids: vec![],
             metrics: ExecutionPlanMetricsSet::new(),
             props: OnceCell::new(),
         })
     }
 
+    /// publishes min/max runtime filters of the keys of each side once the
+    /// side is finished, with one optional filter id for each key. the planner
+    /// must ensure that rows not matching the other side are not needed in
+    /// the output.
+    pub fn with_runtime_filter_ids(
+        mut self,
+        left_runtime_filter_ids: Vec<Option<String>>,
+        right_runtime_filter_ids: Vec<Option<String>>,
+    ) -> Self {
+        self.left_runtime_filter_ids = left_runtime_filter_ids;
+        self.right_runtime_filter_ids = right_runtime_filter_ids;
+        self
+    }
+
     fn create_join_params(&self, projection: &[usize]) -> Result<JoinParams> {
         let left_schema = self.left.schema();
         let right_schema = self.right.schema();
@@ -112,6 +143,7 @@ impl SortMergeJoinExec {
             &right_schema,
             projection,
         )?;
+        let batch_size = compute_sub_batch_size(&projection.schema);
         Ok(JoinParams {
             join_type: self.join_type,
             left_schema,
@@ -121,8 +153,9 @@ impl SortMergeJoinExec {
             right_keys,
             key_data_types,
             sort_options: self.sort_options.clone(),
+            null_equals_null: self.null_equals_null,
             projection,
-            batch_size: batch_size(),
+            batch_size,
         })
     }
 
@@ -140,8 +173,18 @@ impl SortMergeJoinExec {
             &self.metrics,
         );
         let exec_ctx_cloned = exec_ctx.clone();
-        let left = exec_ctx.execute(&self.left)?;
-        let right = exec_ctx.execute(&self.right)?;
+        let left = publish_runtime_filters_on_finish(
+            exec_ctx.execute(&self.left)?,
+            &self.left_runtime_filter_ids,
+            &join_params.left_keys,
+            partition,
+        )?;
+        let right = publish_runtime_filters_on_finish(
+            exec_ctx.execute(&self.right)?,
+            &self.right_runtime_filter_ids,
+            &join_params.right_keys,
+            partition,
+        )?;
         let output = exec_ctx_cloned
             .clone()
             .output_with_sender("SortMergeJoin", move |sender| {
@@ -155,8 +198,8 @@ impl DisplayAs for SortMergeJoinExec {
     fn fmt_as(&self, _t: DisplayFormatType, f: &mut Formatter) -> std::fmt::Result {
         write!(
             f,
-            "SortMergeJoin: join_type={:?}, on={:?}, schema={:?}",
-            self.join_type, self.on, self.schema,
+            "SortMergeJoin: join_type={:?}, on={:?}, null_equals_null={}, schema={:?}",
+            self.join_type, self.on, self.null_equals_null, self.schema,
         )
     }
 }
@@ -189,7 +232,7 @@ impl ExecutionPlan for SortMergeJoinExec {
         self.props.get_or_init(|| {
             PlanProperties::new(
                 EquivalenceProperties::new(self.schema()),
-                self.right.output_partitioning().clone(),
+                derive_join_partitioning(&self.left, &self.right, &self.schema),
                 ExecutionMode::Bounded,
             )
         })
@@ -203,14 +246,21 @@ impl ExecutionPlan for SortMergeJoinExec {
         self: Arc<Self>,
         children: Vec<Arc<dyn ExecutionPlan>>,
     ) -> Result<Arc<dyn ExecutionPlan>> {
-        Ok(Arc::new(SortMergeJoinExec::try_new(
-            self.schema(),
-            children[0].clone(),
-            children[1].clone(),
-            self.on.clone(),
-            self.join_type,
-            self.sort_options.clone(),
-        )?))
+        Ok(Arc::new(
+            SortMergeJoinExec::try_new(
+                self.schema(),
+                children[0].clone(),
+                children[1].clone(),
+                self.on.clone(),
+                self.join_type,
+                self.sort_options.clone(),
+                self.null_equals_null,
+            )?
+            .with_runtime_filter_ids(
+                self.left_runtime_filter_ids.clone(),
+                self.right_runtime_filter_ids.clone(),
+            ),
+        ))
     }
 
     fn execute(
@@ -227,7 +277,12 @@ impl ExecutionPlan for SortMergeJoinExec {
     }
 
     fn statistics(&self) -> Result<Statistics> {
-        todo!()
+        Ok(estimate_join_statistics(
+            &self.left.statistics()?,
+            &self.right.statistics()?,
+            self.join_type,
+            &self.schema,
+        ))
     }
 }
 
@@ -289,6 +344,59 @@ pub async fn execute_join(
     Ok(())
 }
 
+/// computes number of rows of each sub-batch produced by joiners. the
+/// configured size is capped by the estimated output row width, so that very
+/// wide outputs are not produced in oversized batches.
+fn compute_sub_batch_size(output_schema: &Schema) -> usize {
+    let sub_batch_size_min = 20;
+    let sub_batch_size = match smj_sub_batch_size() {
+        0 => batch_size(),
+        sub_batch_size => sub_batch_size,
+    };
+    let est_row_width = output_schema
+        .fields()
+        .iter()
+        .map(|field| estimate_value_width(field.data_type()))
+        .sum::<usize>();
+    let max_sub_batch_size = suggested_output_batch_mem_size() / est_row_width.max(1);
+    sub_batch_size
+        .min(max_sub_batch_size)
+        .max(sub_batch_size_min)
+}
+
+/// estimates memory size of a value of the data type, variable-length values
+/// are assumed to be of an average size
+fn estimate_value_width(data_type: &DataType) -> usize {
+    const EST_VAR_LEN_VALUE_WIDTH: usize = 32;
+    match data_type {
+        DataType::Null => 0,
+        DataType::Boolean => 1,
+        DataType::Struct(fields) => fields
+            .iter()
+            .map(|field| estimate_value_width(field.data_type()))
+            .sum(),
+        DataType::List(field) | DataType::LargeList(field) => {
+            // assume several elements in each list
+            4 * estimate_value_width(field.data_type()) + 8
+        }
+        DataType::Map(field, _) => 4 * estimate_value_width(field.data_type()) + 8,
+        data_type => data_type
+            .primitive_width()
+            .unwrap_or(EST_VAR_LEN_VALUE_WIDTH + 8),
+    }
+}
+
+fn smj_sub_batch_size() -> usize {
+    if is_jni_bridge_inited() {
+        conf::SMJ_SUB_BATCH_SIZE
+            .value()
+            .map(|sub_batch_size| sub_batch_size.max(0) as usize)
+            .unwrap_or(0)
+    } else {
+        0 // for testing
+    }
+}
+
 #[macro_export]
 macro_rules! compare_cursor {
     ($curs:expr) => {{
@@ -305,3 +413,35 @@ pub trait Joiner {
     async fn join(self: Pin<&mut Self>, curs: &mut StreamCursors) -> Result<()>;
     fn num_output_rows(&self) -> usize;
 }
+
+#[cfg(test)]
+mod test {
+    use arrow::datatypes::{DataType, Field, Fields, Schema};
+    use datafusion_ext_commons::batch_size;
+
+    use crate::sort_merge_join_exec::compute_sub_batch_size;
+
+    #[test]
+    fn test_compute_sub_batch_size() {
+        // narrow outputs use the configured size
+        let narrow_schema = Schema::new(vec![
+            Field::new("a", DataType::Int32, true),
+            Field::new("b", DataType::Utf8, true),
+        ]);
+        assert_eq!(compute_sub_batch_size(&narrow_schema), batch_size());
+
+        // wide outputs are capped by the estimated row width
+        let wide_struct = DataType::Struct(Fields::from(
+            (0..1000)
+                .map(|i| Field::new(format!("f{i}"), DataType::Utf8, true))
+                .collect::<Vec<_>>(),
+        ));
+        let wide_schema = Schema::new(vec![
+            Field::new("a", wide_struct.clone(), true),
+            Field::new("b", wide_struct, true),
+        ]);
+        let sub_batch_size = compute_sub_batch_size(&wide_schema);
+        assert!(sub_batch_size < batch_size());
+        assert!(sub_batch_size >= 20);
+    }
+}
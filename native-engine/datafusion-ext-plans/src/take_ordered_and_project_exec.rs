@@ -0,0 +1,331 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{any::Any, fmt::Formatter, sync::Arc};
+
+use arrow::datatypes::{Field, Fields, Schema, SchemaRef};
+use datafusion::{
+    common::{stats::Precision, Result, Statistics},
+    execution::context::TaskContext,
+    physical_expr::{EquivalenceProperties, PhysicalExprRef, PhysicalSortExpr},
+    physical_plan::{
+        metrics::{ExecutionPlanMetricsSet, MetricsSet},
+        DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, ExecutionPlanProperties,
+        PlanProperties, SendableRecordBatchStream,
+    },
+};
+use futures::StreamExt;
+use itertools::Itertools;
+use once_cell::sync::OnceCell;
+
+use crate::{
+    common::{
+        cached_exprs_evaluator::CachedExprsEvaluator,
+        column_pruning::{prune_columns, ExecuteWithColumnPruning},
+        execution_context::ExecutionContext,
+        partitioning::derive_projected_partitioning,
+        statistics::limit_statistics,
+        timer_helper::TimerHelper,
+    },
+    sort_exec::SortExec,
+};
+
+/// Sorts the input, skips the first `offset` rows and outputs at most `limit`
+/// rows projected with the given expressions. the top-k sorting is done by a
+/// SortExec with fetch=offset+limit, which only reads the columns needed by
+/// the projection.
+#[derive(Debug)]
+pub struct TakeOrderedAndProjectExec {
+    input: Arc<dyn ExecutionPlan>,
+    sort_exprs: Vec<PhysicalSortExpr>,
+    sort: Arc<SortExec>,
+    limit: usize,
+    offset: usize,
+    projection: Vec<(PhysicalExprRef, String)>,
+    schema: SchemaRef,
+    metrics: ExecutionPlanMetricsSet,
+    props: OnceCell<PlanProperties>,
+}
+
+impl TakeOrderedAndProjectExec {
+    pub fn try_new(
+        input: Arc<dyn ExecutionPlan>,
+        sort_exprs: Vec<PhysicalSortExpr>,
+        limit: usize,
+        offset: usize,
+        projection: Vec<(PhysicalExprRef, String)>,
+    ) -> Result<Self> {
+        let input_schema = input.schema();
+        let schema = Arc::new(Schema::new(
+            projection
+                .iter()
+                .map(|(e, name)| {
+                    Ok(Field::new(
+                        name,
+                        e.data_type(&input_schema)?,
+                        e.nullable(&input_schema)?,
+                    ))
+                })
+                .collect::<Result<Fields>>()?,
+        ));
+        let sort = Arc::new(SortExec::new(
+            input.clone(),
+            sort_exprs.clone(),
+            Some(limit.saturating_add(offset)),
+        ));
+
+        Ok(Self {
+            input,
+            sort_exprs,
+            sort,
+            limit,
+            offset,
+            projection,
+            schema,
+            metrics: ExecutionPlanMetricsSet::new(),
+            props: OnceCell::new(),
+        })
+    }
+}
+
+impl DisplayAs for TakeOrderedAndProjectExec {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "TakeOrderedAndProjectExec(limit={}, offset={}, order=[{}], projection=[{}])",
+            self.limit,
+            self.offset,
+            self.sort_exprs.iter().join(", "),
+            self.projection
+                .iter()
+                .map(|(e, name)| format!("{e} AS {name}"))
+                .join(", "),
+        )
+    }
+}
+
+impl ExecutionPlan for TakeOrderedAndProjectExec {
+    fn name(&self) -> &str {
+        "TakeOrderedAndProjectExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.props.get_or_init(|| {
+            PlanProperties::new(
+                EquivalenceProperties::new(self.schema()),
+                derive_projected_partitioning(self.input.output_partitioning(), &self.projection),
+                ExecutionMode::Bounded,
+            )
+        })
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.input]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(Self::try_new(
+            children[0].clone(),
+            self.sort_exprs.clone(),
+            self.limit,
+            self.offset,
+            self.projection.clone(),
+        )?))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let exec_ctx =
+            ExecutionContext::new(context.clone(), partition, self.schema(), &self.metrics);
+
+        // only columns used by projection are carried through the sorter
+        let exprs = self.projection.iter().map(|(e, _)| e.clone()).collect_vec();
+        let (pruned_exprs, projection) = prune_columns(&exprs)?;
+        let cached_expr_evaluator = Arc::new(
+            CachedExprsEvaluator::try_new(vec![], pruned_exprs, exec_ctx.output_schema())?
+                .with_expr_metrics(&exec_ctx)?,
+        );
+        let mut sorted = self
+            .sort
+            .execute_projected(partition, context, &projection)?;
+        let limit = self.limit;
+        let offset = self.offset;
+
+        let output = exec_ctx.clone().output_with_sender(
+            "TakeOrderedAndProject",
+            move |sender| async move {
+                let elapsed_compute = exec_ctx.baseline_metrics().elapsed_compute().clone();
+                let _timer = elapsed_compute.timer();
+                sender.exclude_time(&elapsed_compute);
+
+                let mut num_skipped = 0;
+                let mut num_output = 0;
+                while num_output < limit {
+                    let batch = match elapsed_compute
+                        .exclude_timer_async(sorted.next())
+                        .await
+                        .transpose()?
+                    {
+                        Some(batch) => batch,
+                        None => break,
+                    };
+
+                    // skips offset rows
+                    let skip = (offset - num_skipped).min(batch.num_rows());
+                    num_skipped += skip;
+                    let len = (batch.num_rows() - skip).min(limit - num_output);
+                    if len == 0 {
+                        continue;
+                    }
+                    num_output += len;
+
+                    let output_batch =
+                        cached_expr_evaluator.filter_project(&batch.slice(skip, len))?;
+                    exec_ctx
+                        .baseline_metrics()
+                        .record_output(output_batch.num_rows());
+                    sender.send(output_batch).await;
+                }
+                Ok(())
+            },
+        );
+        Ok(output)
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        // includes metrics of the inner sorter, except its output rows which
+        // also count the skipped offset rows
+        let mut metrics = self.metrics.clone_inner();
+        for metric in self.sort.metrics().unwrap_or_default().iter() {
+            if metric.value().name() != "output_rows" {
+                metrics.push(metric.clone());
+            }
+        }
+        Some(metrics)
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        // byte size and column statistics are unknown after projection
+        let fetch = self.limit.saturating_add(self.offset);
+        let fetched_stats = limit_statistics(self.input.statistics()?, fetch);
+        Ok(Statistics {
+            num_rows: fetched_stats
+                .num_rows
+                .map(|num_rows| num_rows.saturating_sub(self.offset)),
+            total_byte_size: Precision::Absent,
+            column_statistics: Statistics::unknown_column(&self.schema),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::Int32Array,
+        compute::SortOptions,
+        datatypes::{DataType, Field, Schema},
+        record_batch::RecordBatch,
+    };
+    use datafusion::{
+        assert_batches_eq,
+        common::{stats::Precision, Result},
+        logical_expr::Operator,
+        physical_expr::{
+            expressions::{BinaryExpr, Column},
+            PhysicalSortExpr,
+        },
+        physical_plan::{common, memory::MemoryExec, ExecutionPlan},
+        prelude::SessionContext,
+    };
+
+    use crate::{memmgr::MemManager, take_ordered_and_project_exec::TakeOrderedAndProjectExec};
+
+    #[tokio::test]
+    async fn test_take_ordered_and_project() -> Result<()> {
+        MemManager::init(10000);
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, false),
+            Field::new("b", DataType::Int32, false),
+            Field::new("c", DataType::Int32, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![9, 8, 7, 6, 5, 4, 3, 2, 1, 0])),
+                Arc::new(Int32Array::from(vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9])),
+                Arc::new(Int32Array::from(vec![5, 6, 7, 8, 9, 0, 1, 2, 3, 4])),
+            ],
+        )?;
+        let input = Arc::new(MemoryExec::try_new(&[vec![batch]], schema, None)?);
+
+        // SELECT a + b AS s, a FROM t ORDER BY c DESC LIMIT 3 OFFSET 2
+        let take_ordered = TakeOrderedAndProjectExec::try_new(
+            input,
+            vec![PhysicalSortExpr {
+                expr: Arc::new(Column::new("c", 2)),
+                options: SortOptions {
+                    descending: true,
+                    nulls_first: false,
+                },
+            }],
+            3,
+            2,
+            vec![
+                (
+                    Arc::new(BinaryExpr::new(
+                        Arc::new(Column::new("a", 0)),
+                        Operator::Plus,
+                        Arc::new(Column::new("b", 1)),
+                    )),
+                    "s".to_string(),
+                ),
+                (Arc::new(Column::new("a", 0)), "a".to_string()),
+            ],
+        )?;
+        assert_eq!(take_ordered.statistics()?.num_rows, Precision::Exact(3));
+
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+        let output = take_ordered.execute(0, task_ctx)?;
+        let batches = common::collect(output).await?;
+
+        let expected = vec![
+            "+----+---+",
+            "| s  | a |",
+            "+----+---+",
+            "| 9  | 7 |",
+            "| 11 | 8 |",
+            "| 9  | 9 |",
+            "+----+---+",
+        ];
+        assert_batches_eq!(expected, &batches);
+        Ok(())
+    }
+}
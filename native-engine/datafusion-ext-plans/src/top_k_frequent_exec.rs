@@ -0,0 +1,353 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    fmt::Formatter,
+    hash::BuildHasher,
+    sync::Arc,
+};
+
+use arrow::{
+    array::{ArrayRef, Int64Array},
+    datatypes::{DataType, Field, Schema, SchemaRef},
+    record_batch::RecordBatch,
+    row::{RowConverter, SortField},
+};
+use datafusion::{
+    common::{Result, Statistics},
+    execution::context::TaskContext,
+    physical_expr::{EquivalenceProperties, PhysicalExpr},
+    physical_plan::{
+        metrics::{ExecutionPlanMetricsSet, MetricsSet},
+        DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, ExecutionPlanProperties,
+        Partitioning, PlanProperties, SendableRecordBatchStream,
+    },
+};
+use datafusion_ext_commons::df_execution_err;
+use futures::StreamExt;
+use once_cell::sync::OnceCell;
+
+use crate::common::execution_context::ExecutionContext;
+
+/// Returns the K most frequent values of an expression in each partition,
+/// together with their estimated counts, sorted by count descending.
+/// frequencies are estimated with a count-min sketch and candidates are kept
+/// in a bounded min-heap, so memory usage does not grow with the number of
+/// distinct values. estimated counts are never less than the real counts.
+#[derive(Debug)]
+pub struct TopKFrequentExec {
+    input: Arc<dyn ExecutionPlan>,
+    expr: Arc<dyn PhysicalExpr>,
+    k: usize,
+    schema: SchemaRef,
+    metrics: ExecutionPlanMetricsSet,
+    props: OnceCell<PlanProperties>,
+}
+
+impl TopKFrequentExec {
+    pub fn try_new(
+        input: Arc<dyn ExecutionPlan>,
+        expr: Arc<dyn PhysicalExpr>,
+        k: usize,
+    ) -> Result<Self> {
+        if k == 0 {
+            return df_execution_err!("TopKFrequentExec requires k > 0");
+        }
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("value", expr.data_type(&input.schema())?, true),
+            Field::new("count", DataType::Int64, false),
+        ]));
+        Ok(Self {
+            input,
+            expr,
+            k,
+            schema,
+            metrics: ExecutionPlanMetricsSet::new(),
+            props: OnceCell::new(),
+        })
+    }
+}
+
+impl DisplayAs for TopKFrequentExec {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "TopKFrequentExec(expr={}, k={})", self.expr, self.k)
+    }
+}
+
+impl ExecutionPlan for TopKFrequentExec {
+    fn name(&self) -> &str {
+        "TopKFrequentExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.props.get_or_init(|| {
+            PlanProperties::new(
+                EquivalenceProperties::new(self.schema()),
+                // output columns are not derived from input columns
+                Partitioning::UnknownPartitioning(
+                    self.input.output_partitioning().partition_count(),
+                ),
+                ExecutionMode::Bounded,
+            )
+        })
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.input]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(Self::try_new(
+            children[0].clone(),
+            self.expr.clone(),
+            self.k,
+        )?))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let exec_ctx = ExecutionContext::new(context, partition, self.schema(), &self.metrics);
+        let input = exec_ctx.execute_with_input_stats(&self.input)?;
+        execute_top_k_frequent(input, self.expr.clone(), self.k, exec_ctx)
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        Ok(Statistics::new_unknown(&self.schema()))
+    }
+}
+
+fn execute_top_k_frequent(
+    mut input: SendableRecordBatchStream,
+    expr: Arc<dyn PhysicalExpr>,
+    k: usize,
+    exec_ctx: Arc<ExecutionContext>,
+) -> Result<SendableRecordBatchStream> {
+    const HASHER: foldhash::fast::FixedState = foldhash::fast::FixedState::with_seed(0x9E3779B9);
+    let value_type = exec_ctx.output_schema().field(0).data_type().clone();
+    let mut row_converter = RowConverter::new(vec![SortField::new(value_type)])?;
+
+    Ok(exec_ctx
+        .clone()
+        .output_with_sender("TopKFrequent", move |sender| async move {
+            sender.exclude_time(exec_ctx.baseline_metrics().elapsed_compute());
+            let mut sketch = CountMinSketch::new(k);
+            let mut candidates = TopKCandidates::new(k);
+
+            while let Some(batch) = input.next().await.transpose()? {
+                let _timer = exec_ctx.baseline_metrics().elapsed_compute().timer();
+                let values = expr
+                    .evaluate(&batch)
+                    .and_then(|v| v.into_array(batch.num_rows()))?;
+                let rows = row_converter.convert_columns(&[values])?;
+                for row in &rows {
+                    let key = row.as_ref();
+                    let count = sketch.add(HASHER.hash_one(key));
+                    candidates.offer(key, count);
+                }
+            }
+
+            let _timer = exec_ctx.baseline_metrics().elapsed_compute().timer();
+            let top_k = candidates.into_sorted_vec();
+            let parser = row_converter.parser();
+            let value_cols =
+                row_converter.convert_rows(top_k.iter().map(|(key, _)| parser.parse(key)))?;
+            let count_col: ArrayRef = Arc::new(Int64Array::from_iter_values(
+                top_k.iter().map(|&(_, count)| count as i64),
+            ));
+            let output_batch = RecordBatch::try_new(
+                exec_ctx.output_schema(),
+                vec![value_cols[0].clone(), count_col],
+            )?;
+            exec_ctx
+                .baseline_metrics()
+                .record_output(output_batch.num_rows());
+            sender.send(output_batch).await;
+            Ok(())
+        }))
+}
+
+/// count-min sketch, returns estimated count of a value after adding it
+struct CountMinSketch {
+    width: usize,
+    counters: Vec<u64>,
+}
+
+impl CountMinSketch {
+    const DEPTH: usize = 4;
+
+    fn new(k: usize) -> Self {
+        let width = (k * 64).clamp(1024, 65536).next_power_of_two();
+        Self {
+            width,
+            counters: vec![0; width * Self::DEPTH],
+        }
+    }
+
+    fn add(&mut self, hash: u64) -> u64 {
+        // derives hashes of each row with double hashing
+        let h1 = hash as u32 as usize;
+        let h2 = (hash >> 32) as u32 as usize | 1;
+        let mut estimated = u64::MAX;
+        for i in 0..Self::DEPTH {
+            let col = h1.wrapping_add(i.wrapping_mul(h2)) & (self.width - 1);
+            let counter = &mut self.counters[i * self.width + col];
+            *counter += 1;
+            estimated = estimated.min(*counter);
+        }
+        estimated
+    }
+}
+
+/// most frequent values with their estimated counts. the heap contains exactly
+/// one entry for each candidate, whose count may be stale (less than the
+/// latest count) and is fixed when the entry reaches the top.
+struct TopKCandidates {
+    k: usize,
+    counts: HashMap<Box<[u8]>, u64>,
+    heap: BinaryHeap<Reverse<(u64, Box<[u8]>)>>,
+}
+
+impl TopKCandidates {
+    fn new(k: usize) -> Self {
+        Self {
+            k,
+            counts: HashMap::new(),
+            heap: BinaryHeap::new(),
+        }
+    }
+
+    fn offer(&mut self, key: &[u8], count: u64) {
+        if let Some(cur_count) = self.counts.get_mut(key) {
+            *cur_count = count;
+            return;
+        }
+        if self.counts.len() < self.k {
+            self.counts.insert(key.into(), count);
+            self.heap.push(Reverse((count, key.into())));
+            return;
+        }
+
+        // fix stale entries until the top entry has the latest count
+        while let Some(mut top) = self.heap.peek_mut() {
+            let latest_count = self.counts[&top.0 .1];
+            if top.0 .0 == latest_count {
+                break;
+            }
+            top.0 .0 = latest_count;
+        }
+        let mut top = self.heap.peek_mut().expect("non-empty heap");
+        if count > top.0 .0 {
+            self.counts.remove(&top.0 .1);
+            self.counts.insert(key.into(), count);
+            *top = Reverse((count, key.into()));
+        }
+    }
+
+    fn into_sorted_vec(self) -> Vec<(Box<[u8]>, u64)> {
+        let mut top_k = self.counts.into_iter().collect::<Vec<_>>();
+        top_k.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        top_k
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::Int32Array,
+        datatypes::{DataType, Field, Schema},
+        record_batch::RecordBatch,
+    };
+    use datafusion::{
+        assert_batches_eq,
+        common::Result,
+        physical_expr::expressions::Column,
+        physical_plan::{common, memory::MemoryExec, ExecutionPlan},
+        prelude::SessionContext,
+    };
+
+    use crate::top_k_frequent_exec::TopKFrequentExec;
+
+    #[tokio::test]
+    async fn test_top_k_frequent() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+        let batch1 = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from(vec![
+                Some(1),
+                Some(4),
+                Some(2),
+                None,
+                Some(4),
+                Some(3),
+            ]))],
+        )?;
+        let batch2 = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from(vec![
+                Some(3),
+                Some(4),
+                None,
+                Some(3),
+                Some(4),
+                None,
+                None,
+            ]))],
+        )?;
+        let input = Arc::new(MemoryExec::try_new(
+            &[vec![batch1, batch2]],
+            schema.clone(),
+            None,
+        )?);
+        let top_k = TopKFrequentExec::try_new(input, Arc::new(Column::new("a", 0)), 3)?;
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+        let output = top_k.execute(0, task_ctx)?;
+        let batches = common::collect(output).await?;
+
+        let expected = vec![
+            "+-------+-------+",
+            "| value | count |",
+            "+-------+-------+",
+            "|       | 4     |",
+            "| 4     | 4     |",
+            "| 3     | 3     |",
+            "+-------+-------+",
+        ];
+        assert_batches_eq!(expected, &batches);
+        Ok(())
+    }
+}
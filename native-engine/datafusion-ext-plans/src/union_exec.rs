@@ -0,0 +1,280 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{any::Any, fmt::Formatter, sync::Arc};
+
+use arrow::{array::RecordBatchOptions, datatypes::SchemaRef, record_batch::RecordBatch};
+use datafusion::{
+    common::{Result, Statistics},
+    execution::context::TaskContext,
+    physical_expr::EquivalenceProperties,
+    physical_plan::{
+        metrics::{ExecutionPlanMetricsSet, MetricsSet},
+        DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, Partitioning, PlanProperties,
+        SendableRecordBatchStream,
+    },
+};
+use datafusion_ext_commons::{arrow::cast::cast, df_execution_err};
+use futures::StreamExt;
+use itertools::Itertools;
+use once_cell::sync::OnceCell;
+
+use crate::common::{
+    execution_context::ExecutionContext, statistics::sum_statistics, timer_helper::TimerHelper,
+};
+
+/// an input of union, which is executed with the specified partition
+#[derive(Debug, Clone)]
+pub struct UnionInput(pub Arc<dyn ExecutionPlan>, pub usize);
+
+/// Concatenates streams of the inputs into the current output partition.
+/// unlike datafusion's UnionExec which maps output partitions to inputs by
+/// counting partitions of each input, every input here is bound to its own
+/// partition slot, so the task only needs to carry the inputs it really reads.
+#[derive(Debug)]
+pub struct UnionExec {
+    inputs: Vec<UnionInput>,
+    schema: SchemaRef,
+    num_partitions: usize,
+    cur_partition: usize,
+    metrics: ExecutionPlanMetricsSet,
+    props: OnceCell<PlanProperties>,
+}
+
+impl UnionExec {
+    pub fn new(
+        inputs: Vec<UnionInput>,
+        schema: SchemaRef,
+        num_partitions: usize,
+        cur_partition: usize,
+    ) -> Self {
+        Self {
+            inputs,
+            schema,
+            num_partitions,
+            cur_partition,
+            metrics: ExecutionPlanMetricsSet::new(),
+            props: OnceCell::new(),
+        }
+    }
+}
+
+impl DisplayAs for UnionExec {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "UnionExec(partition={}/{}, input_partitions=[{}])",
+            self.cur_partition,
+            self.num_partitions,
+            self.inputs.iter().map(|input| input.1).join(", "),
+        )
+    }
+}
+
+impl ExecutionPlan for UnionExec {
+    fn name(&self) -> &str {
+        "UnionExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.props.get_or_init(|| {
+            PlanProperties::new(
+                EquivalenceProperties::new(self.schema()),
+                Partitioning::UnknownPartitioning(self.num_partitions),
+                ExecutionMode::Bounded,
+            )
+        })
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        self.inputs.iter().map(|input| &input.0).collect()
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(Self::new(
+            children
+                .into_iter()
+                .zip(&self.inputs)
+                .map(|(child, input)| UnionInput(child, input.1))
+                .collect(),
+            self.schema.clone(),
+            self.num_partitions,
+            self.cur_partition,
+        )))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        if partition != self.cur_partition {
+            return df_execution_err!(
+                "UnionExec: executing partition {partition}, expected {}",
+                self.cur_partition
+            );
+        }
+        let exec_ctx =
+            ExecutionContext::new(context.clone(), partition, self.schema(), &self.metrics);
+        let inputs = self
+            .inputs
+            .iter()
+            .map(|UnionInput(input, input_partition)| {
+                Ok(exec_ctx.stat_input(input.execute(*input_partition, context.clone())?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        execute_union(inputs, exec_ctx)
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        let input_stats = self
+            .inputs
+            .iter()
+            .map(|UnionInput(input, _)| input.statistics())
+            .collect::<Result<Vec<_>>>()?;
+        Ok(sum_statistics(input_stats, &self.schema))
+    }
+}
+
+/// concatenates the input streams one by one, adapting batches to the output
+/// schema (inputs may differ in nullability)
+pub fn execute_union(
+    inputs: Vec<SendableRecordBatchStream>,
+    exec_ctx: Arc<ExecutionContext>,
+) -> Result<SendableRecordBatchStream> {
+    Ok(exec_ctx
+        .clone()
+        .output_with_sender("Union", move |sender| async move {
+            let elapsed_compute = exec_ctx.baseline_metrics().elapsed_compute().clone();
+            let _timer = elapsed_compute.timer();
+            sender.exclude_time(&elapsed_compute);
+
+            let output_schema = exec_ctx.output_schema();
+            for mut input in inputs {
+                while let Some(batch) = elapsed_compute
+                    .exclude_timer_async(input.next())
+                    .await
+                    .transpose()?
+                {
+                    let output_batch = adapt_batch(batch, &output_schema)?;
+                    exec_ctx
+                        .baseline_metrics()
+                        .record_output(output_batch.num_rows());
+                    sender.send(output_batch).await;
+                }
+            }
+            Ok(())
+        }))
+}
+
+fn adapt_batch(batch: RecordBatch, schema: &SchemaRef) -> Result<RecordBatch> {
+    if batch.schema() == *schema {
+        return Ok(batch);
+    }
+    let columns = batch
+        .columns()
+        .iter()
+        .zip(schema.fields())
+        .map(|(col, field)| {
+            if col.data_type() == field.data_type() {
+                Ok(col.clone())
+            } else {
+                cast(col.as_ref(), field.data_type())
+            }
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(RecordBatch::try_new_with_options(
+        schema.clone(),
+        columns,
+        &RecordBatchOptions::new().with_row_count(Some(batch.num_rows())),
+    )?)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::Int32Array,
+        datatypes::{DataType, Field, Schema},
+        record_batch::RecordBatch,
+    };
+    use datafusion::{
+        assert_batches_eq,
+        common::Result,
+        physical_plan::{common, memory::MemoryExec, ExecutionPlan},
+        prelude::SessionContext,
+    };
+
+    use crate::union_exec::{UnionExec, UnionInput};
+
+    fn build_input(partitions: Vec<Vec<i32>>, nullable: bool) -> Result<Arc<dyn ExecutionPlan>> {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "a",
+            DataType::Int32,
+            nullable,
+        )]));
+        let partitions = partitions
+            .into_iter()
+            .map(|values| {
+                Ok(vec![RecordBatch::try_new(
+                    schema.clone(),
+                    vec![Arc::new(Int32Array::from(values))],
+                )?])
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Arc::new(MemoryExec::try_new(&partitions, schema, None)?))
+    }
+
+    #[tokio::test]
+    async fn test_union() -> Result<()> {
+        let input1 = build_input(vec![vec![1, 2], vec![3, 4]], false)?;
+        let input2 = build_input(vec![vec![5], vec![6], vec![7, 8]], true)?;
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+
+        // output partition 3 reads partition 1 of input1 and partition 2 of input2
+        let union = UnionExec::new(
+            vec![UnionInput(input1, 1), UnionInput(input2, 2)],
+            schema,
+            5,
+            3,
+        );
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+        assert!(union.execute(0, task_ctx.clone()).is_err());
+
+        let output = union.execute(3, task_ctx)?;
+        let batches = common::collect(output).await?;
+        let expected = vec![
+            "+---+", "| a |", "+---+", "| 3 |", "| 4 |", "| 7 |", "| 8 |", "+---+",
+        ];
+        assert_batches_eq!(expected, &batches);
+        Ok(())
+    }
+}
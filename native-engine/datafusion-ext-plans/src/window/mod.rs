@@ -21,7 +21,11 @@ use crate::{
     agg::{agg::create_agg, AggFunction},
     window::{
         processors::{
-            agg_processor::AggProcessor, rank_processor::RankProcessor,
+            agg_frame_processor::{is_range_frame_end_ready, AggFrameProcessor},
+            agg_processor::AggProcessor,
+            frame_value_processor::FrameValueProcessor,
+            offset_processor::OffsetProcessor,
+            rank_processor::RankProcessor,
             row_number_processor::RowNumberProcessor,
         },
         window_context::WindowContext,
@@ -34,6 +38,8 @@ pub mod window_context;
 #[derive(Debug, Clone, Copy)]
 pub enum WindowFunction {
     RankLike(WindowRankType),
+    Offset(WindowOffsetType),
+    Value(WindowValueType),
     Agg(AggFunction),
 }
 
@@ -44,8 +50,127 @@ pub enum WindowRankType {
     DenseRank,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WindowOffsetType {
+    Lag,
+    Lead,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WindowValueType {
+    FirstValue,
+    LastValue,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WindowFrameBound {
+    UnboundedPreceding,
+    Preceding(usize),
+    CurrentRow,
+    Following(usize),
+    UnboundedFollowing,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WindowFrameUnits {
+    Rows,
+    Range,
+}
+
+/// ROWS/RANGE BETWEEN start AND end. offsets of RANGE frames are differences
+/// of the (only) order key value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WindowFrame {
+    pub units: WindowFrameUnits,
+    pub start: WindowFrameBound,
+    pub end: WindowFrameBound,
+}
+
+impl Default for WindowFrame {
+    fn default() -> Self {
+        Self {
+            units: WindowFrameUnits::Rows,
+            start: WindowFrameBound::UnboundedPreceding,
+            end: WindowFrameBound::CurrentRow,
+        }
+    }
+}
+
+impl WindowFrame {
+    pub fn new(start: WindowFrameBound, end: WindowFrameBound) -> Self {
+        Self {
+            units: WindowFrameUnits::Rows,
+            start,
+            end,
+        }
+    }
+
+    pub fn new_range(start: WindowFrameBound, end: WindowFrameBound) -> Self {
+        Self {
+            units: WindowFrameUnits::Range,
+            start,
+            end,
+        }
+    }
+
+    /// returns true if any bound is N PRECEDING/FOLLOWING
+    pub fn has_offset(&self) -> bool {
+        [self.start, self.end].into_iter().any(|bound| {
+            matches!(
+                bound,
+                WindowFrameBound::Preceding(_) | WindowFrameBound::Following(_)
+            )
+        })
+    }
+
+    /// number of rows before the current row needed to evaluate the frame
+    /// (excluding unbounded preceding), only meaningful for ROWS frames
+    pub fn num_preceding_rows(&self) -> usize {
+        if self.units == WindowFrameUnits::Range {
+            return 0;
+        }
+        [self.start, self.end]
+            .into_iter()
+            .map(|bound| match bound {
+                WindowFrameBound::Preceding(n) => n,
+                _ => 0,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// number of rows after the current row needed to evaluate the frame, only
+    /// meaningful for ROWS frames
+    pub fn num_following_rows(&self) -> usize {
+        if self.units == WindowFrameUnits::Range {
+            return 0;
+        }
+        [self.start, self.end]
+            .into_iter()
+            .map(|bound| match bound {
+                WindowFrameBound::Following(n) => n,
+                _ => 0,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+}
+
 pub trait WindowFunctionProcessor: Send {
     fn process_batch(&mut self, context: &WindowContext, batch: &RecordBatch) -> Result<ArrayRef>;
+
+    /// processes a batch with following rows from the next batches, the
+    /// lookahead batch contains at least `WindowExpr::num_following_rows()`
+    /// rows (or all following rows of RANGE frames), or less if reaching the
+    /// end of input.
+    fn process_batch_with_lookahead(
+        &mut self,
+        context: &WindowContext,
+        batch: &RecordBatch,
+        _lookahead: &RecordBatch,
+    ) -> Result<ArrayRef> {
+        self.process_batch(context, batch)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -53,6 +178,9 @@ pub struct WindowExpr {
     field: FieldRef,
     func: WindowFunction,
     children: Vec<Arc<dyn PhysicalExpr>>,
+    frame: WindowFrame,
+    offset: i64,
+    ignore_nulls: bool,
 }
 
 impl WindowExpr {
@@ -65,6 +193,68 @@ impl WindowExpr {
             field,
             func,
             children,
+            frame: WindowFrame::default(),
+            offset: 1,
+            ignore_nulls: false,
+        }
+    }
+
+    /// sets frame of first_value/last_value and aggregate functions
+    pub fn with_frame(mut self, frame: WindowFrame) -> Self {
+        self.frame = frame;
+        self
+    }
+
+    /// sets offset of lag/lead
+    pub fn with_offset(mut self, offset: i64) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn with_ignore_nulls(mut self, ignore_nulls: bool) -> Self {
+        self.ignore_nulls = ignore_nulls;
+        self
+    }
+
+    /// number of rows after the current row needed to evaluate this expr
+    pub fn num_following_rows(&self) -> usize {
+        match self.func {
+            WindowFunction::Offset(WindowOffsetType::Lead) => self.offset.max(0) as usize,
+            WindowFunction::Offset(WindowOffsetType::Lag) => (-self.offset).max(0) as usize,
+            WindowFunction::Value(_) | WindowFunction::Agg(_) => self.frame.num_following_rows(),
+            _ => 0,
+        }
+    }
+
+    /// returns true if following rows needed by this expr are determined by
+    /// order key values instead of a fixed number of rows
+    pub fn has_range_following(&self) -> bool {
+        matches!(self.func, WindowFunction::Agg(_))
+            && self.frame.units == WindowFrameUnits::Range
+            && matches!(
+                self.frame.end,
+                WindowFrameBound::CurrentRow | WindowFrameBound::Following(_)
+            )
+    }
+
+    /// returns whether the staged following rows (`num_following` rows, the
+    /// last of which is `last_following`) are enough to evaluate all rows in
+    /// the batch
+    pub fn is_following_ready(
+        &self,
+        context: &WindowContext,
+        batch: &RecordBatch,
+        num_following: usize,
+        last_following: Option<&RecordBatch>,
+    ) -> Result<bool> {
+        if !self.has_range_following() {
+            return Ok(num_following >= self.num_following_rows());
+        }
+        match last_following {
+            Some(last_following) => {
+                is_range_frame_end_ready(context, &self.frame, batch, last_following)
+            }
+            None => Ok(batch.num_rows() == 0),
         }
     }
 
@@ -82,9 +272,26 @@ impl WindowExpr {
             WindowFunction::RankLike(WindowRankType::DenseRank) => {
                 Ok(Box::new(RankProcessor::new(true)))
             }
+            WindowFunction::Offset(offset_type) => Ok(Box::new(OffsetProcessor::try_new(
+                offset_type,
+                self.offset,
+                self.ignore_nulls,
+                &self.children,
+            )?)),
+            WindowFunction::Value(value_type) => Ok(Box::new(FrameValueProcessor::try_new(
+                value_type,
+                self.frame,
+                self.ignore_nulls,
+                &self.children,
+            )?)),
             WindowFunction::Agg(agg_func) => {
                 let agg = create_agg(agg_func, &self.children, &context.input_schema)?;
-                Ok(Box::new(AggProcessor::try_new(agg)?))
+                if self.frame == WindowFrame::default() {
+                    return Ok(Box::new(AggProcessor::try_new(agg)?));
+                }
+                Ok(Box::new(AggFrameProcessor::try_new(
+                    agg, self.frame, context,
+                )?))
             }
         }
     }
@@ -0,0 +1,402 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{ops::Range, sync::Arc};
+
+use arrow::{
+    array::{Array, ArrayRef, AsArray},
+    compute::concat_batches,
+    datatypes::{DataType, Int64Type},
+    record_batch::RecordBatch,
+    row::Rows,
+};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_expr::{expressions::Literal, PhysicalExpr},
+};
+use datafusion_ext_commons::{
+    arrow::{cast::cast, coalesce::coalesce_arrays_unchecked},
+    df_unimplemented_err,
+};
+
+use crate::{
+    agg::{
+        acc::AccColumnRef,
+        agg::{Agg, IdxSelection},
+    },
+    window::{
+        processors::window_buffer::{BufferedRows, WindowBuffer},
+        window_context::WindowContext,
+        WindowFrame, WindowFrameBound, WindowFrameUnits, WindowFunctionProcessor,
+    },
+};
+
+/// Processor of aggregate functions over ROWS/RANGE frames.
+///  * frames starting at unbounded preceding keep a running accumulator, which
+///    is advanced to the end of each frame (frame ends never move backward).
+///  * other frames are sliding and recomputed with a fresh accumulator.
+///  * RANGE frames with offsets require a single integral/date order key, null
+///    order values form their own peer group.
+pub struct AggFrameProcessor {
+    agg: Arc<dyn Agg>,
+    frame: WindowFrame,
+    buffer: WindowBuffer,
+
+    // state of running accumulator, next row to accumulate is relative to the
+    // first buffered row
+    acc_col: AccColumnRef,
+    acc_partition: Option<Vec<u8>>,
+    acc_next: usize,
+}
+
+impl AggFrameProcessor {
+    pub fn try_new(agg: Arc<dyn Agg>, frame: WindowFrame, context: &WindowContext) -> Result<Self> {
+        if matches!(frame.start, WindowFrameBound::UnboundedFollowing)
+            || matches!(frame.end, WindowFrameBound::UnboundedPreceding)
+        {
+            return df_unimplemented_err!("invalid window frame: {frame:?}");
+        }
+        if matches!(frame.end, WindowFrameBound::UnboundedFollowing) {
+            return df_unimplemented_err!("window frame not supported: {frame:?}");
+        }
+        if frame.units == WindowFrameUnits::Range && frame.has_offset() {
+            if context.order_spec.len() != 1 {
+                return df_unimplemented_err!(
+                    "RANGE frame with offset requires exactly one order key: {frame:?}"
+                );
+            }
+            let order_type = context.order_spec[0]
+                .expr
+                .data_type(&context.input_schema)?;
+            if !order_type.is_integer() && !matches!(order_type, DataType::Date32) {
+                return df_unimplemented_err!(
+                    "RANGE frame with offset not supported on order key type: {order_type}"
+                );
+            }
+        }
+
+        // values are not used since aggregate children are evaluated separately
+        let value_expr: Arc<dyn PhysicalExpr> = Arc::new(Literal::new(ScalarValue::Null));
+        let acc_col = agg.create_acc_column(1);
+        Ok(Self {
+            agg,
+            frame,
+            buffer: WindowBuffer::new(value_expr, frame.num_preceding_rows(), 0),
+            acc_col,
+            acc_partition: None,
+            acc_next: 0,
+        })
+    }
+
+    fn is_running(&self) -> bool {
+        self.frame.start == WindowFrameBound::UnboundedPreceding
+    }
+}
+
+impl WindowFunctionProcessor for AggFrameProcessor {
+    fn process_batch(&mut self, context: &WindowContext, batch: &RecordBatch) -> Result<ArrayRef> {
+        let lookahead = RecordBatch::new_empty(batch.schema());
+        self.process_batch_with_lookahead(context, batch, &lookahead)
+    }
+
+    fn process_batch_with_lookahead(
+        &mut self,
+        context: &WindowContext,
+        batch: &RecordBatch,
+        lookahead: &RecordBatch,
+    ) -> Result<ArrayRef> {
+        let rows = self.buffer.prepare(context, batch, lookahead)?;
+        let num_rows = rows.combined.num_rows();
+        let children_cols: Vec<ArrayRef> = self
+            .agg
+            .exprs()
+            .iter()
+            .map(|expr| {
+                expr.evaluate(&rows.combined)
+                    .and_then(|v| v.into_array(num_rows))
+            })
+            .collect::<Result<_>>()?;
+        let partial_args = self.agg.prepare_partial_args(&children_cols)?;
+        let frame_rows = FrameRows::try_new(context, &self.frame, &rows)?;
+
+        let mut output = Vec::with_capacity(batch.num_rows());
+        let keep_from;
+        if self.is_running() {
+            for c in rows.cur_rows.clone() {
+                let partition = rows.partition_key(c);
+                if self.acc_partition.as_deref() != Some(partition) {
+                    self.acc_col = self.agg.create_acc_column(1);
+                    self.acc_partition = Some(partition.to_vec());
+                    self.acc_next = rows.partition_starts[c];
+                }
+                let upper = frame_rows.upper(c);
+                if upper > self.acc_next {
+                    self.agg.partial_update(
+                        &mut self.acc_col,
+                        IdxSelection::Single(0),
+                        &partial_args,
+                        IdxSelection::Range(self.acc_next, upper),
+                    )?;
+                    self.acc_next = upper;
+                }
+                output.push(
+                    self.agg
+                        .final_merge(&mut self.acc_col, IdxSelection::Single(0))?,
+                );
+            }
+            keep_from = self.acc_next.min(rows.cur_rows.end);
+            self.acc_next -= keep_from;
+        } else {
+            let mut last_lower = rows.cur_rows.start;
+            for c in rows.cur_rows.clone() {
+                let lower = frame_rows.lower(c);
+                let upper = frame_rows.upper(c);
+                let mut acc_col = self.agg.create_acc_column(1);
+                if upper > lower {
+                    self.agg.partial_update(
+                        &mut acc_col,
+                        IdxSelection::Single(0),
+                        &partial_args,
+                        IdxSelection::Range(lower, upper),
+                    )?;
+                }
+                output.push(
+                    self.agg
+                        .final_merge(&mut acc_col, IdxSelection::Single(0))?,
+                );
+                last_lower = lower;
+            }
+            keep_from = last_lower.min(rows.cur_rows.end);
+        }
+        self.buffer.advance_from(rows, keep_from);
+
+        Ok(Arc::new(coalesce_arrays_unchecked(
+            self.agg.data_type(),
+            &output,
+        )))
+    }
+}
+
+/// returns whether the frame end of the last row in batch can be determined
+/// by the staged following rows, the last of which is `last_following`.
+/// only used for RANGE frames ending at current row or N following.
+pub fn is_range_frame_end_ready(
+    context: &WindowContext,
+    frame: &WindowFrame,
+    batch: &RecordBatch,
+    last_following: &RecordBatch,
+) -> Result<bool> {
+    if batch.num_rows() == 0 {
+        return Ok(true);
+    }
+    let last_batch_row = batch.slice(batch.num_rows() - 1, 1);
+    let rows = concat_batches(&batch.schema(), [&last_batch_row, last_following])?;
+    if context.has_partition() {
+        let partition_rows = context.get_partition_rows(&rows)?;
+        if partition_rows.row(0) != partition_rows.row(1) {
+            return Ok(true);
+        }
+    }
+    match frame.end {
+        WindowFrameBound::CurrentRow => {
+            let order_rows = context.get_order_rows(&rows)?;
+            Ok(order_rows.row(0) != order_rows.row(1))
+        }
+        WindowFrameBound::Following(n) => {
+            let ranks = range_order_ranks(context, &rows)?;
+            if is_null_rank(ranks[0]) {
+                return Ok(ranks[0] != ranks[1]);
+            }
+            Ok(ranks[1] > ranks[0] + n as i128)
+        }
+        _ => Ok(true),
+    }
+}
+
+/// evaluates the only order key as i128 ranks which are ascending in sort
+/// order, nulls are ranked as i128::MIN/MAX according to nulls_first.
+fn range_order_ranks(context: &WindowContext, batch: &RecordBatch) -> Result<Vec<i128>> {
+    let order = &context.order_spec[0];
+    let values = order
+        .expr
+        .evaluate(batch)
+        .and_then(|v| v.into_array(batch.num_rows()))?;
+    let values = cast(&values, &DataType::Int64)?;
+    let values = values.as_primitive::<Int64Type>();
+    let null_rank = if order.options.nulls_first {
+        i128::MIN
+    } else {
+        i128::MAX
+    };
+    Ok((0..values.len())
+        .map(|i| {
+            if values.is_null(i) {
+                null_rank
+            } else if order.options.descending {
+                -(values.value(i) as i128)
+            } else {
+                values.value(i) as i128
+            }
+        })
+        .collect())
+}
+
+fn is_null_rank(rank: i128) -> bool {
+    rank == i128::MIN || rank == i128::MAX
+}
+
+/// computes frame boundaries (in buffered rows) of each row
+struct FrameRows<'a> {
+    frame: &'a WindowFrame,
+    rows: &'a BufferedRows,
+    peer_starts: Vec<usize>,
+    peer_ends: Vec<usize>,
+    ranks: Vec<i128>,
+}
+
+impl<'a> FrameRows<'a> {
+    fn try_new(
+        context: &WindowContext,
+        frame: &'a WindowFrame,
+        rows: &'a BufferedRows,
+    ) -> Result<Self> {
+        let mut frame_rows = Self {
+            frame,
+            rows,
+            peer_starts: vec![],
+            peer_ends: vec![],
+            ranks: vec![],
+        };
+        if frame.units == WindowFrameUnits::Range {
+            let order_rows = context.get_order_rows(&rows.combined)?;
+            (frame_rows.peer_starts, frame_rows.peer_ends) = peer_bounds(rows, &order_rows);
+            if frame.has_offset() {
+                frame_rows.ranks = range_order_ranks(context, &rows.combined)?;
+            }
+        }
+        Ok(frame_rows)
+    }
+
+    /// start of frame
+    fn lower(&self, c: usize) -> usize {
+        let partition = self.partition_range(c);
+        match (self.frame.units, self.frame.start) {
+            (_, WindowFrameBound::UnboundedPreceding) => partition.start,
+            (WindowFrameUnits::Rows, WindowFrameBound::Preceding(n)) => {
+                c.saturating_sub(n).max(partition.start)
+            }
+            (WindowFrameUnits::Rows, WindowFrameBound::CurrentRow) => c,
+            (WindowFrameUnits::Rows, WindowFrameBound::Following(n)) => (c + n).min(partition.end),
+            (WindowFrameUnits::Range, WindowFrameBound::CurrentRow) => self.peer_starts[c],
+            (WindowFrameUnits::Range, WindowFrameBound::Preceding(n)) => {
+                self.range_search(c, partition, |rank| rank - n as i128, false)
+            }
+            (WindowFrameUnits::Range, WindowFrameBound::Following(n)) => {
+                self.range_search(c, partition, |rank| rank + n as i128, false)
+            }
+            (_, WindowFrameBound::UnboundedFollowing) => unreachable!(),
+        }
+    }
+
+    /// end (exclusive) of frame
+    fn upper(&self, c: usize) -> usize {
+        let partition = self.partition_range(c);
+        match (self.frame.units, self.frame.end) {
+            (WindowFrameUnits::Rows, WindowFrameBound::Preceding(n)) => {
+                (c + 1).saturating_sub(n).max(partition.start)
+            }
+            (WindowFrameUnits::Rows, WindowFrameBound::CurrentRow) => c + 1,
+            (WindowFrameUnits::Rows, WindowFrameBound::Following(n)) => {
+                (c + 1 + n).min(partition.end)
+            }
+            (WindowFrameUnits::Range, WindowFrameBound::CurrentRow) => self.peer_ends[c],
+            (WindowFrameUnits::Range, WindowFrameBound::Preceding(n)) => {
+                self.range_search(c, partition, |rank| rank - n as i128, true)
+            }
+            (WindowFrameUnits::Range, WindowFrameBound::Following(n)) => {
+                self.range_search(c, partition, |rank| rank + n as i128, true)
+            }
+            (_, WindowFrameBound::UnboundedPreceding | WindowFrameBound::UnboundedFollowing) => {
+                unreachable!()
+            }
+        }
+    }
+
+    fn partition_range(&self, c: usize) -> Range<usize> {
+        self.rows.partition_starts[c]..self.rows.partition_ends[c]
+    }
+
+    /// finds the first row in partition whose rank is not less than (or
+    /// greater than if inclusive) the target rank. rows with null order values
+    /// are only peers of each other.
+    fn range_search(
+        &self,
+        c: usize,
+        partition: Range<usize>,
+        target: impl Fn(i128) -> i128,
+        inclusive: bool,
+    ) -> usize {
+        let rank = self.ranks[c];
+        if is_null_rank(rank) {
+            return if inclusive {
+                self.peer_ends[c]
+            } else {
+                self.peer_starts[c]
+            };
+        }
+        let target = target(rank);
+        let (mut lo, mut hi) = (partition.start, partition.end);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let before_target = if inclusive {
+                self.ranks[mid] <= target
+            } else {
+                self.ranks[mid] < target
+            };
+            if before_target {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
+    }
+}
+
+/// start/end (exclusive) of peer group (rows with same partition and order
+/// values) of each row
+fn peer_bounds(rows: &BufferedRows, order_rows: &Rows) -> (Vec<usize>, Vec<usize>) {
+    let num_rows = order_rows.num_rows();
+    let is_peer = |i: usize, j: usize| {
+        rows.partition_starts[i] == rows.partition_starts[j]
+            && order_rows.row(i) == order_rows.row(j)
+    };
+    let mut peer_starts = vec![0; num_rows];
+    let mut peer_ends = vec![num_rows; num_rows];
+    for i in 1..num_rows {
+        peer_starts[i] = if is_peer(i, i - 1) {
+            peer_starts[i - 1]
+        } else {
+            i
+        };
+    }
+    for i in (0..num_rows.saturating_sub(1)).rev() {
+        peer_ends[i] = if is_peer(i, i + 1) {
+            peer_ends[i + 1]
+        } else {
+            i + 1
+        };
+    }
+    (peer_starts, peer_ends)
+}
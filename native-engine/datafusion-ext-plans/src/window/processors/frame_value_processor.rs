@@ -0,0 +1,170 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use arrow::{
+    array::{new_null_array, Array, ArrayRef},
+    compute::interleave,
+    record_batch::RecordBatch,
+};
+use datafusion::{common::Result, physical_expr::PhysicalExpr};
+use datafusion_ext_commons::df_unimplemented_err;
+
+use crate::window::{
+    processors::window_buffer::WindowBuffer, window_context::WindowContext, WindowFrame,
+    WindowFrameBound, WindowFrameUnits, WindowFunctionProcessor, WindowValueType,
+};
+
+/// Processor of first_value/last_value over a ROWS frame. frames may span
+/// batch boundaries: preceding rows are kept in the window buffer and
+/// following rows are read from the lookahead batch.
+pub struct FrameValueProcessor {
+    value_type: WindowValueType,
+    frame: WindowFrame,
+    ignore_nulls: bool,
+    buffer: WindowBuffer,
+}
+
+impl FrameValueProcessor {
+    pub fn try_new(
+        value_type: WindowValueType,
+        frame: WindowFrame,
+        ignore_nulls: bool,
+        children: &[Arc<dyn PhysicalExpr>],
+    ) -> Result<Self> {
+        if matches!(frame.start, WindowFrameBound::UnboundedFollowing)
+            || matches!(frame.end, WindowFrameBound::UnboundedPreceding)
+        {
+            return df_unimplemented_err!("invalid window frame: {frame:?}");
+        }
+        if matches!(frame.end, WindowFrameBound::UnboundedFollowing)
+            || frame.units == WindowFrameUnits::Range
+        {
+            return df_unimplemented_err!("window frame not supported: {frame:?}");
+        }
+        let num_evicted_non_nulls = match value_type {
+            WindowValueType::LastValue if ignore_nulls => 1,
+            _ => 0,
+        };
+        Ok(Self {
+            value_type,
+            frame,
+            ignore_nulls,
+            buffer: WindowBuffer::new(
+                children[0].clone(),
+                frame.num_preceding_rows(),
+                num_evicted_non_nulls,
+            ),
+        })
+    }
+}
+
+impl WindowFunctionProcessor for FrameValueProcessor {
+    fn process_batch(&mut self, context: &WindowContext, batch: &RecordBatch) -> Result<ArrayRef> {
+        let lookahead = RecordBatch::new_empty(batch.schema());
+        self.process_batch_with_lookahead(context, batch, &lookahead)
+    }
+
+    fn process_batch_with_lookahead(
+        &mut self,
+        context: &WindowContext,
+        batch: &RecordBatch,
+        lookahead: &RecordBatch,
+    ) -> Result<ArrayRef> {
+        let rows = self.buffer.prepare(context, batch, lookahead)?;
+        let values = &rows.values;
+        let nulls = new_null_array(values.data_type(), 1);
+
+        // sources of output values: [values, null, evicted value]
+        const VALUES: usize = 0;
+        const NULL: (usize, usize) = (1, 0);
+        const EVICTED: (usize, usize) = (2, 0);
+        let mut sources: Vec<&dyn Array> = vec![values.as_ref(), nulls.as_ref()];
+
+        // evicted rows are only used for unbounded preceding frames, since the
+        // window buffer always keeps enough rows for bounded frames.
+        let unbounded_preceding = self.frame.start == WindowFrameBound::UnboundedPreceding;
+        let evicted_value = match &rows.evicted {
+            Some(evicted) if unbounded_preceding => match (self.value_type, self.ignore_nulls) {
+                (WindowValueType::FirstValue, false) => Some(&evicted.first),
+                (WindowValueType::FirstValue, true) => evicted.first_non_null.as_ref(),
+                (WindowValueType::LastValue, _) => evicted.last_non_nulls.back(),
+            },
+            _ => None,
+        };
+        let evicted = evicted_value.map(|value| {
+            sources.push(value.as_ref());
+            EVICTED
+        });
+        let has_evicted = unbounded_preceding && rows.evicted.is_some();
+        let next_valid = match (self.value_type, self.ignore_nulls) {
+            (WindowValueType::FirstValue, true) => rows.next_valid_indices(),
+            _ => vec![],
+        };
+        let prev_valid = match (self.value_type, self.ignore_nulls) {
+            (WindowValueType::LastValue, true) => rows.prev_valid_indices(),
+            _ => vec![],
+        };
+
+        let mut indices = Vec::with_capacity(batch.num_rows());
+        for c in rows.cur_rows.clone() {
+            let partition_start = rows.partition_starts[c];
+            let partition_end = rows.partition_ends[c];
+            let lower = match self.frame.start {
+                WindowFrameBound::UnboundedPreceding => partition_start,
+                WindowFrameBound::Preceding(n) => c.saturating_sub(n).max(partition_start),
+                WindowFrameBound::CurrentRow => c,
+                WindowFrameBound::Following(n) => c + n,
+                WindowFrameBound::UnboundedFollowing => unreachable!(),
+            };
+            let upper = match self.frame.end {
+                WindowFrameBound::Preceding(n) => (c + 1).saturating_sub(n),
+                WindowFrameBound::CurrentRow => c + 1,
+                WindowFrameBound::Following(n) => (c + 1 + n).min(partition_end),
+                _ => unreachable!(),
+            };
+            // rows in partition before buffered rows are also in the frame
+            let with_evicted = has_evicted && partition_start == 0;
+
+            indices.push(match self.value_type {
+                WindowValueType::FirstValue if with_evicted && !self.ignore_nulls => {
+                    evicted.unwrap_or(NULL)
+                }
+                WindowValueType::FirstValue if with_evicted => match evicted {
+                    Some(evicted) => evicted,
+                    None => next_valid[0]
+                        .filter(|&i| i < upper)
+                        .map(|i| (VALUES, i))
+                        .unwrap_or(NULL),
+                },
+                _ if lower >= upper && !with_evicted => NULL,
+                WindowValueType::FirstValue if self.ignore_nulls => next_valid[lower]
+                    .filter(|&i| i < upper)
+                    .map(|i| (VALUES, i))
+                    .unwrap_or(NULL),
+                WindowValueType::FirstValue => (VALUES, lower),
+                WindowValueType::LastValue if self.ignore_nulls => prev_valid[upper - 1]
+                    .filter(|&i| i >= lower)
+                    .map(|i| (VALUES, i))
+                    .or(evicted.filter(|_| with_evicted))
+                    .unwrap_or(NULL),
+                WindowValueType::LastValue => (VALUES, upper - 1),
+            });
+        }
+        let output = interleave(&sources, &indices)?;
+        self.buffer.advance(rows);
+        Ok(output)
+    }
+}
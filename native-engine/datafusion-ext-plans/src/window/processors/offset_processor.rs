@@ -0,0 +1,171 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::VecDeque, sync::Arc};
+
+use arrow::{
+    array::{new_null_array, Array, ArrayRef},
+    compute::interleave,
+    record_batch::RecordBatch,
+};
+use datafusion::{common::Result, physical_expr::PhysicalExpr};
+use datafusion_ext_commons::{arrow::cast::cast, df_unimplemented_err};
+
+use crate::window::{
+    processors::window_buffer::WindowBuffer, window_context::WindowContext,
+    WindowFunctionProcessor, WindowOffsetType,
+};
+
+/// Processor of lag/lead, following spark's semantics:
+///  * the value of the offset-th row before (lag) or after (lead) the current
+///    row is returned, even if it is null.
+///  * default value (evaluated on the current row) is returned if the offset
+///    row is out of the partition.
+///  * with ignore_nulls, lag returns the offset-th non-null value before the
+///    current row.
+pub struct OffsetProcessor {
+    offset_type: WindowOffsetType,
+    offset: usize,
+    ignore_nulls: bool,
+    default_expr: Option<Arc<dyn PhysicalExpr>>,
+    buffer: WindowBuffer,
+}
+
+impl OffsetProcessor {
+    pub fn try_new(
+        offset_type: WindowOffsetType,
+        offset: i64,
+        ignore_nulls: bool,
+        children: &[Arc<dyn PhysicalExpr>],
+    ) -> Result<Self> {
+        // negative offset is equivalent to the opposite function
+        let (offset_type, offset) = match (offset_type, offset) {
+            (WindowOffsetType::Lag, o) if o < 0 => (WindowOffsetType::Lead, -o as usize),
+            (WindowOffsetType::Lead, o) if o < 0 => (WindowOffsetType::Lag, -o as usize),
+            (offset_type, o) => (offset_type, o as usize),
+        };
+        if ignore_nulls && offset_type == WindowOffsetType::Lead && offset > 0 {
+            return df_unimplemented_err!("lead() with ignore nulls is not supported");
+        }
+
+        let num_preceding_rows = match offset_type {
+            WindowOffsetType::Lag => offset,
+            WindowOffsetType::Lead => 0,
+        };
+        let num_evicted_non_nulls = if ignore_nulls { offset } else { 0 };
+        Ok(Self {
+            offset_type,
+            offset,
+            ignore_nulls,
+            default_expr: children.get(1).cloned(),
+            buffer: WindowBuffer::new(
+                children[0].clone(),
+                num_preceding_rows,
+                num_evicted_non_nulls,
+            ),
+        })
+    }
+}
+
+impl WindowFunctionProcessor for OffsetProcessor {
+    fn process_batch(&mut self, context: &WindowContext, batch: &RecordBatch) -> Result<ArrayRef> {
+        let lookahead = RecordBatch::new_empty(batch.schema());
+        self.process_batch_with_lookahead(context, batch, &lookahead)
+    }
+
+    fn process_batch_with_lookahead(
+        &mut self,
+        context: &WindowContext,
+        batch: &RecordBatch,
+        lookahead: &RecordBatch,
+    ) -> Result<ArrayRef> {
+        let rows = self.buffer.prepare(context, batch, lookahead)?;
+        let values = &rows.values;
+        let data_type = values.data_type().clone();
+
+        let defaults = match &self.default_expr {
+            Some(default_expr) => {
+                let defaults = default_expr
+                    .evaluate(batch)
+                    .and_then(|v| v.into_array(batch.num_rows()))?;
+                if defaults.data_type() != &data_type {
+                    cast(&defaults, &data_type)?
+                } else {
+                    defaults
+                }
+            }
+            None => new_null_array(&data_type, batch.num_rows()),
+        };
+
+        // sources of output values: [values, defaults, evicted non-nulls...]
+        const VALUES: usize = 0;
+        const DEFAULTS: usize = 1;
+        const EVICTED: usize = 2;
+        let mut sources: Vec<&dyn Array> = vec![values.as_ref(), defaults.as_ref()];
+        let mut indices = Vec::with_capacity(batch.num_rows());
+        let cur_start = rows.cur_rows.start;
+        let n = self.offset;
+
+        match self.offset_type {
+            WindowOffsetType::Lag if self.ignore_nulls && n > 0 => {
+                // the last n non-null values before current row in the same partition
+                let mut non_nulls: VecDeque<(usize, usize)> = VecDeque::new();
+                if let Some(evicted) = &rows.evicted {
+                    for (i, value) in evicted.last_non_nulls.iter().enumerate() {
+                        sources.push(value.as_ref());
+                        non_nulls.push_back((EVICTED + i, 0));
+                    }
+                }
+                for c in 0..rows.cur_rows.end {
+                    if rows.partition_starts[c] == c && c > 0 {
+                        non_nulls.clear();
+                    }
+                    if c >= cur_start {
+                        indices.push(match non_nulls.len() {
+                            len if len >= n => non_nulls[len - n],
+                            _ => (DEFAULTS, c - cur_start),
+                        });
+                    }
+                    if values.is_valid(c) {
+                        if non_nulls.len() == n {
+                            non_nulls.pop_front();
+                        }
+                        non_nulls.push_back((VALUES, c));
+                    }
+                }
+            }
+            WindowOffsetType::Lag => {
+                for c in rows.cur_rows.clone() {
+                    indices.push(match c.checked_sub(n) {
+                        Some(target) if target >= rows.partition_starts[c] => (VALUES, target),
+                        _ => (DEFAULTS, c - cur_start),
+                    });
+                }
+            }
+            WindowOffsetType::Lead => {
+                for c in rows.cur_rows.clone() {
+                    let target = c + n;
+                    indices.push(if target < rows.partition_ends[c] {
+                        (VALUES, target)
+                    } else {
+                        (DEFAULTS, c - cur_start)
+                    });
+                }
+            }
+        }
+        let output = interleave(&sources, &indices)?;
+        self.buffer.advance(rows);
+        Ok(output)
+    }
+}
@@ -0,0 +1,226 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::VecDeque, ops::Range, sync::Arc};
+
+use arrow::{
+    array::{Array, ArrayRef},
+    compute::concat_batches,
+    record_batch::RecordBatch,
+    row::Rows,
+};
+use datafusion::{common::Result, physical_expr::PhysicalExpr};
+
+use crate::window::window_context::WindowContext;
+
+/// Buffers rows around the current batch for window functions which access
+/// preceding/following rows. preceding rows are kept from previous batches
+/// (history), following rows are provided by the caller (lookahead). rows
+/// evicted from history are summarized so that unbounded preceding frames
+/// can still be evaluated.
+pub struct WindowBuffer {
+    value_expr: Arc<dyn PhysicalExpr>,
+    num_preceding_rows: usize,
+    num_evicted_non_nulls: usize,
+    history: Option<RecordBatch>,
+    evicted: Option<EvictedRows>,
+}
+
+/// summary of rows which are evicted from history, only rows belonging to the
+/// same partition as the first row in history are summarized
+pub struct EvictedRows {
+    partition: Vec<u8>,
+    pub first: ArrayRef,
+    pub first_non_null: Option<ArrayRef>,
+    pub last_non_nulls: VecDeque<ArrayRef>,
+}
+
+pub struct BufferedRows {
+    /// evaluated values of history + current batch + lookahead rows
+    pub values: ArrayRef,
+
+    /// range of current batch rows in values
+    pub cur_rows: Range<usize>,
+
+    /// start/end (exclusive) of partition of each row, bounded by buffered rows
+    pub partition_starts: Vec<usize>,
+    pub partition_ends: Vec<usize>,
+
+    /// summary of evicted rows, only available when the first buffered row
+    /// belongs to the same partition
+    pub evicted: Option<EvictedRows>,
+
+    /// history + current batch + lookahead rows
+    pub combined: RecordBatch,
+
+    partition_rows: Rows,
+    has_partition: bool,
+}
+
+impl WindowBuffer {
+    pub fn new(
+        value_expr: Arc<dyn PhysicalExpr>,
+        num_preceding_rows: usize,
+        num_evicted_non_nulls: usize,
+    ) -> Self {
+        Self {
+            value_expr,
+            num_preceding_rows,
+            num_evicted_non_nulls,
+            history: None,
+            evicted: None,
+        }
+    }
+
+    pub fn prepare(
+        &mut self,
+        context: &WindowContext,
+        batch: &RecordBatch,
+        lookahead: &RecordBatch,
+    ) -> Result<BufferedRows> {
+        let num_history_rows = self.history.as_ref().map(|h| h.num_rows()).unwrap_or(0);
+        let combined = concat_batches(
+            &batch.schema(),
+            self.history.iter().chain([batch, lookahead]),
+        )?;
+        let num_rows = combined.num_rows();
+        let values = self
+            .value_expr
+            .evaluate(&combined)
+            .and_then(|v| v.into_array(num_rows))?;
+
+        let has_partition = context.has_partition();
+        let partition_rows = context.get_partition_rows(&combined)?;
+        let mut partition_starts = vec![0; num_rows];
+        let mut partition_ends = vec![num_rows; num_rows];
+        if has_partition {
+            for i in 1..num_rows {
+                partition_starts[i] = if partition_rows.row(i) == partition_rows.row(i - 1) {
+                    partition_starts[i - 1]
+                } else {
+                    i
+                };
+            }
+            for i in (0..num_rows.saturating_sub(1)).rev() {
+                partition_ends[i] = if partition_rows.row(i) == partition_rows.row(i + 1) {
+                    partition_ends[i + 1]
+                } else {
+                    i + 1
+                };
+            }
+        }
+
+        let evicted = self.evicted.take().filter(|evicted| {
+            num_rows > 0
+                && (!has_partition
+                    || evicted.partition.as_slice() == partition_rows.row(0).as_ref())
+        });
+
+        Ok(BufferedRows {
+            values,
+            cur_rows: num_history_rows..num_history_rows + batch.num_rows(),
+            partition_starts,
+            partition_ends,
+            evicted,
+            combined,
+            partition_rows,
+            has_partition,
+        })
+    }
+
+    /// keeps preceding rows of the processed batch for the next batch, and
+    /// summarizes the evicted rows
+    pub fn advance(&mut self, rows: BufferedRows) {
+        let keep_from = rows.cur_rows.end.saturating_sub(self.num_preceding_rows);
+        self.advance_from(rows, keep_from);
+    }
+
+    /// like `advance()`, but keeps all rows starting from `keep_from`, used by
+    /// frames whose preceding rows are not bounded by a fixed number (RANGE)
+    pub fn advance_from(&mut self, mut rows: BufferedRows, keep_from: usize) {
+        let num_rows = rows.cur_rows.end; // lookahead rows are excluded
+        let num_evicted = keep_from.min(num_rows);
+        let num_kept = num_rows - num_evicted;
+
+        let mut evicted = rows.evicted.take();
+        for i in 0..num_evicted {
+            let partition = rows.partition_key(i);
+            // starts summarizing a new partition
+            if evicted.as_ref().map(|e| e.partition.as_slice()) != Some(partition) {
+                evicted = Some(EvictedRows {
+                    partition: partition.to_vec(),
+                    first: rows.values.slice(i, 1),
+                    first_non_null: None,
+                    last_non_nulls: VecDeque::new(),
+                });
+            }
+            let summary = evicted.as_mut().expect("evicted rows");
+
+            if rows.values.is_valid(i) {
+                let value = rows.values.slice(i, 1);
+                if summary.first_non_null.is_none() {
+                    summary.first_non_null = Some(value.clone());
+                }
+                if self.num_evicted_non_nulls > 0 {
+                    if summary.last_non_nulls.len() == self.num_evicted_non_nulls {
+                        summary.last_non_nulls.pop_front();
+                    }
+                    summary.last_non_nulls.push_back(value);
+                }
+            }
+        }
+        self.evicted = evicted;
+        self.history = (num_kept > 0).then(|| rows.combined.slice(num_evicted, num_kept));
+    }
+}
+
+impl BufferedRows {
+    /// returns the encoded partition key of a row, or empty if there is no
+    /// partition spec
+    pub fn partition_key(&self, i: usize) -> &[u8] {
+        if self.has_partition {
+            self.partition_rows.row(i).as_ref()
+        } else {
+            &[]
+        }
+    }
+
+    /// returns the nearest valid row index at or after each row
+    pub fn next_valid_indices(&self) -> Vec<Option<usize>> {
+        let mut next_valid = vec![None; self.values.len()];
+        for i in (0..self.values.len()).rev() {
+            next_valid[i] = if self.values.is_valid(i) {
+                Some(i)
+            } else {
+                next_valid.get(i + 1).cloned().flatten()
+            };
+        }
+        next_valid
+    }
+
+    /// returns the nearest valid row index at or before each row
+    pub fn prev_valid_indices(&self) -> Vec<Option<usize>> {
+        let mut prev_valid = vec![None; self.values.len()];
+        for i in 0..self.values.len() {
+            prev_valid[i] = if self.values.is_valid(i) {
+                Some(i)
+            } else if i > 0 {
+                prev_valid[i - 1]
+            } else {
+                None
+            };
+        }
+        prev_valid
+    }
+}
@@ -12,10 +12,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{any::Any, fmt::Formatter, sync::Arc};
+use std::{any::Any, collections::VecDeque, fmt::Formatter, sync::Arc};
 
 use arrow::{
     array::{Array, ArrayRef},
+    compute::concat_batches,
     datatypes::SchemaRef,
     record_batch::{RecordBatch, RecordBatchOptions},
 };
@@ -34,7 +35,7 @@ use futures::StreamExt;
 use once_cell::sync::OnceCell;
 
 use crate::{
-    common::execution_context::ExecutionContext,
+    common::{execution_context::ExecutionContext, statistics::project_statistics},
     window::{window_context::WindowContext, WindowExpr},
 };
 
@@ -118,7 +119,6 @@ impl ExecutionPlan for WindowExec {
         partition: usize,
         context: Arc<TaskContext>,
     ) -> Result<SendableRecordBatchStream> {
-        // at this moment only supports ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW
         let exec_ctx = ExecutionContext::new(context, partition, self.schema(), &self.metrics);
         let input = exec_ctx.execute_with_input_stats(&self.input)?;
         let coalesced = exec_ctx.coalesce_with_default_batch_size(input);
@@ -131,7 +131,7 @@ impl ExecutionPlan for WindowExec {
     }
 
     fn statistics(&self) -> Result<Statistics> {
-        todo!()
+        Ok(project_statistics(self.input.statistics()?, &self.schema()))
     }
 }
 
@@ -140,6 +140,20 @@ fn execute_window(
     exec_ctx: Arc<ExecutionContext>,
     window_ctx: Arc<WindowContext>,
 ) -> Result<SendableRecordBatchStream> {
+    // number of rows from next batches needed by lead/following frames
+    let num_following_rows = window_ctx
+        .window_exprs
+        .iter()
+        .map(|expr| expr.num_following_rows())
+        .max()
+        .unwrap_or(0);
+
+    // RANGE frames need following rows until the frame end is determined
+    let has_range_following = window_ctx
+        .window_exprs
+        .iter()
+        .any(|expr| expr.has_range_following());
+
     // start processing input batches
     Ok(exec_ctx
         .clone()
@@ -152,39 +166,114 @@ fn execute_window(
                 .map(|expr: &WindowExpr| expr.create_processor(&window_ctx))
                 .collect::<Result<Vec<_>>>()?;
 
-            while let Some(batch) = input.next().await.transpose()? {
-                let _timer = exec_ctx.baseline_metrics().elapsed_compute().timer();
-                let window_cols: Vec<ArrayRef> = processors
-                    .iter_mut()
-                    .map(|processor| processor.process_batch(&window_ctx, &batch))
-                    .collect::<Result<_>>()?;
-
-                let outputs: Vec<ArrayRef> = batch
-                    .columns()
-                    .iter()
-                    .chain(&window_cols)
-                    .zip(window_ctx.output_schema.fields())
-                    .map(|(array, field)| {
-                        if array.data_type() != field.data_type() {
-                            return cast(&array, field.data_type());
+            // batches waiting for enough following rows
+            let mut staging_batches: VecDeque<RecordBatch> = VecDeque::new();
+            let mut staging_num_following_rows = 0;
+            let mut input_finished = false;
+
+            while !input_finished || !staging_batches.is_empty() {
+                if !input_finished {
+                    match input.next().await.transpose()? {
+                        Some(batch) => {
+                            if !staging_batches.is_empty() {
+                                staging_num_following_rows += batch.num_rows();
+                            }
+                            staging_batches.push_back(batch);
                         }
-                        Ok(array.clone())
-                    })
-                    .collect::<Result<_>>()?;
-                let output_batch = RecordBatch::try_new_with_options(
-                    window_ctx.output_schema.clone(),
-                    outputs,
-                    &RecordBatchOptions::new().with_row_count(Some(batch.num_rows())),
-                )?;
-                exec_ctx
-                    .baseline_metrics()
-                    .record_output(output_batch.num_rows());
-                sender.send(output_batch).await;
+                        None => input_finished = true,
+                    }
+                }
+
+                // process the first staging batch if it has enough following rows
+                while !staging_batches.is_empty()
+                    && (input_finished
+                        || is_following_ready(
+                            &window_ctx,
+                            &staging_batches,
+                            staging_num_following_rows,
+                        )?)
+                {
+                    let _timer = exec_ctx.baseline_metrics().elapsed_compute().timer();
+                    let batch = staging_batches.pop_front().expect("staging batch");
+                    staging_num_following_rows -= staging_batches
+                        .front()
+                        .map(|batch| batch.num_rows())
+                        .unwrap_or(0);
+
+                    let window_cols: Vec<ArrayRef> =
+                        if num_following_rows > 0 || has_range_following {
+                            let lookahead = concat_batches(&batch.schema(), &staging_batches)?;
+                            let lookahead = if has_range_following {
+                                lookahead
+                            } else {
+                                lookahead.slice(0, num_following_rows.min(lookahead.num_rows()))
+                            };
+                            processors
+                                .iter_mut()
+                                .map(|processor| {
+                                    processor.process_batch_with_lookahead(
+                                        &window_ctx,
+                                        &batch,
+                                        &lookahead,
+                                    )
+                                })
+                                .collect::<Result<_>>()?
+                        } else {
+                            processors
+                                .iter_mut()
+                                .map(|processor| processor.process_batch(&window_ctx, &batch))
+                                .collect::<Result<_>>()?
+                        };
+
+                    let outputs: Vec<ArrayRef> = batch
+                        .columns()
+                        .iter()
+                        .chain(&window_cols)
+                        .zip(window_ctx.output_schema.fields())
+                        .map(|(array, field)| {
+                            if array.data_type() != field.data_type() {
+                                return cast(&array, field.data_type());
+                            }
+                            Ok(array.clone())
+                        })
+                        .collect::<Result<_>>()?;
+                    let output_batch = RecordBatch::try_new_with_options(
+                        window_ctx.output_schema.clone(),
+                        outputs,
+                        &RecordBatchOptions::new().with_row_count(Some(batch.num_rows())),
+                    )?;
+                    exec_ctx
+                        .baseline_metrics()
+                        .record_output(output_batch.num_rows());
+                    sender.send(output_batch).await;
+                }
             }
             Ok(())
         }))
 }
 
+/// returns whether the first staging batch has enough following rows for all
+/// window exprs
+fn is_following_ready(
+    window_ctx: &WindowContext,
+    staging_batches: &VecDeque<RecordBatch>,
+    num_following: usize,
+) -> Result<bool> {
+    let batch = &staging_batches[0];
+    let last_following = staging_batches
+        .iter()
+        .skip(1)
+        .rev()
+        .find(|batch| batch.num_rows() > 0)
+        .map(|batch| batch.slice(batch.num_rows() - 1, 1));
+    for expr in &window_ctx.window_exprs {
+        if !expr.is_following_ready(window_ctx, batch, num_following, last_following.as_ref())? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
 #[cfg(test)]
 mod test {
     use std::sync::Arc;
@@ -192,14 +281,21 @@ mod test {
     use arrow::{array::*, datatypes::*, record_batch::RecordBatch};
     use datafusion::{
         assert_batches_eq,
-        physical_expr::{expressions::Column, PhysicalSortExpr},
+        common::ScalarValue,
+        physical_expr::{
+            expressions::{Column, Literal},
+            PhysicalExpr, PhysicalSortExpr,
+        },
         physical_plan::{memory::MemoryExec, ExecutionPlan},
         prelude::SessionContext,
     };
 
     use crate::{
         agg::AggFunction,
-        window::{WindowExpr, WindowFunction, WindowRankType},
+        window::{
+            window_context::WindowContext, WindowExpr, WindowFrame, WindowFrameBound,
+            WindowFunction, WindowOffsetType, WindowRankType, WindowValueType,
+        },
         window_exec::WindowExec,
     };
 
@@ -347,4 +443,181 @@ mod test {
         assert_batches_eq!(expected, &batches);
         Ok(())
     }
+
+    #[test]
+    fn test_window_offset_and_frame_value() -> Result<(), Box<dyn std::error::Error>> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a1", DataType::Int32, false),
+            Field::new("b1", DataType::Int32, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 1, 1, 1, 2, 2, 2])),
+                Arc::new(Int32Array::from(vec![
+                    None,
+                    Some(2),
+                    Some(3),
+                    None,
+                    Some(5),
+                    None,
+                    Some(7),
+                ])),
+            ],
+        )?;
+        let b1: Arc<dyn PhysicalExpr> = Arc::new(Column::new("b1", 1));
+        let window_ctx = Arc::new(WindowContext::try_new(
+            schema,
+            vec![
+                WindowExpr::new(
+                    WindowFunction::Offset(WindowOffsetType::Lag),
+                    vec![
+                        b1.clone(),
+                        Arc::new(Literal::new(ScalarValue::Int32(Some(-1)))),
+                    ],
+                    Arc::new(Field::new("b1_lag", DataType::Int32, true)),
+                ),
+                WindowExpr::new(
+                    WindowFunction::Offset(WindowOffsetType::Lead),
+                    vec![b1.clone()],
+                    Arc::new(Field::new("b1_lead", DataType::Int32, true)),
+                )
+                .with_offset(2),
+                WindowExpr::new(
+                    WindowFunction::Value(WindowValueType::FirstValue),
+                    vec![b1.clone()],
+                    Arc::new(Field::new("b1_first_value", DataType::Int32, true)),
+                )
+                .with_ignore_nulls(true),
+                WindowExpr::new(
+                    WindowFunction::Value(WindowValueType::LastValue),
+                    vec![b1.clone()],
+                    Arc::new(Field::new("b1_last_value", DataType::Int32, true)),
+                )
+                .with_frame(WindowFrame::new(
+                    WindowFrameBound::Preceding(1),
+                    WindowFrameBound::Following(1),
+                )),
+            ],
+            vec![Arc::new(Column::new("a1", 0))],
+            vec![],
+        )?);
+
+        // split input into two batches, so that frames span batch boundaries
+        let (batch1, batch2) = (batch.slice(0, 3), batch.slice(3, 4));
+        let mut outputs = vec![];
+        for expr in &window_ctx.window_exprs {
+            let mut processor = expr.create_processor(&window_ctx)?;
+            let lookahead = batch2.slice(0, expr.num_following_rows());
+            let output1 =
+                processor.process_batch_with_lookahead(&window_ctx, &batch1, &lookahead)?;
+            let output2 = processor.process_batch_with_lookahead(
+                &window_ctx,
+                &batch2,
+                &RecordBatch::new_empty(batch2.schema()),
+            )?;
+            outputs.push(arrow::compute::concat(&[&output1, &output2])?);
+        }
+
+        let expected = [
+            vec![Some(-1), None, Some(2), Some(3), Some(-1), Some(5), None],
+            vec![Some(3), None, None, None, Some(7), None, None],
+            vec![None, Some(2), Some(2), Some(2), Some(5), Some(5), Some(5)],
+            vec![Some(2), Some(3), None, None, None, Some(7), Some(7)],
+        ];
+        for (output, expected) in outputs.iter().zip(expected) {
+            assert_eq!(
+                output.as_primitive::<Int32Type>(),
+                &Int32Array::from(expected)
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_window_agg_frames() -> Result<(), Box<dyn std::error::Error>> {
+        let input = build_table_i32(
+            ("a1", &vec![1, 1, 1, 1, 1, 2, 2]),
+            ("b1", &vec![1, 2, 2, 4, 5, 1, 3]),
+            ("c1", &vec![1, 2, 3, 4, 5, 6, 7]),
+        );
+        let c1: Arc<dyn PhysicalExpr> = Arc::new(Column::new("c1", 2));
+        let window_ctx = Arc::new(WindowContext::try_new(
+            input.schema(),
+            vec![
+                WindowExpr::new(
+                    WindowFunction::Agg(AggFunction::Sum),
+                    vec![c1.clone()],
+                    Arc::new(Field::new("c1_range_running_sum", DataType::Int64, true)),
+                )
+                .with_frame(WindowFrame::new_range(
+                    WindowFrameBound::UnboundedPreceding,
+                    WindowFrameBound::CurrentRow,
+                )),
+                WindowExpr::new(
+                    WindowFunction::Agg(AggFunction::Sum),
+                    vec![c1.clone()],
+                    Arc::new(Field::new("c1_range_sliding_sum", DataType::Int64, true)),
+                )
+                .with_frame(WindowFrame::new_range(
+                    WindowFrameBound::Preceding(1),
+                    WindowFrameBound::Following(1),
+                )),
+                WindowExpr::new(
+                    WindowFunction::Agg(AggFunction::Sum),
+                    vec![c1.clone()],
+                    Arc::new(Field::new("c1_rows_running_sum", DataType::Int64, true)),
+                )
+                .with_frame(WindowFrame::new(
+                    WindowFrameBound::UnboundedPreceding,
+                    WindowFrameBound::Following(1),
+                )),
+                WindowExpr::new(
+                    WindowFunction::Agg(AggFunction::Max),
+                    vec![c1.clone()],
+                    Arc::new(Field::new("c1_rows_sliding_max", DataType::Int32, true)),
+                )
+                .with_frame(WindowFrame::new(
+                    WindowFrameBound::Preceding(1),
+                    WindowFrameBound::Following(1),
+                )),
+            ],
+            vec![Arc::new(Column::new("a1", 0))],
+            vec![PhysicalSortExpr {
+                expr: Arc::new(Column::new("b1", 1)),
+                options: Default::default(),
+            }],
+        )?);
+
+        // split input into two batches, so that frames span batch boundaries
+        let (batch1, batch2) = (input.slice(0, 3), input.slice(3, 4));
+        let mut outputs = vec![];
+        for expr in &window_ctx.window_exprs {
+            let mut processor = expr.create_processor(&window_ctx)?;
+            let output1 = processor.process_batch_with_lookahead(&window_ctx, &batch1, &batch2)?;
+            let output2 = processor.process_batch_with_lookahead(
+                &window_ctx,
+                &batch2,
+                &RecordBatch::new_empty(batch2.schema()),
+            )?;
+            outputs.push(arrow::compute::concat(&[&output1, &output2])?);
+        }
+
+        let expected_sums = [
+            vec![1, 6, 6, 10, 15, 6, 13],
+            vec![6, 6, 6, 9, 9, 6, 7],
+            vec![3, 6, 10, 15, 15, 13, 13],
+        ];
+        for (output, expected) in outputs.iter().zip(expected_sums) {
+            assert_eq!(
+                output.as_primitive::<Int64Type>(),
+                &Int64Array::from(expected)
+            );
+        }
+        assert_eq!(
+            outputs[3].as_primitive::<Int32Type>(),
+            &Int32Array::from(vec![2, 3, 4, 5, 5, 7, 7])
+        );
+        Ok(())
+    }
 }
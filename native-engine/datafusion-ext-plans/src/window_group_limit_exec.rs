@@ -0,0 +1,438 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{any::Any, collections::BTreeMap, fmt::Formatter, sync::Arc};
+
+use arrow::{datatypes::SchemaRef, record_batch::RecordBatch};
+use datafusion::{
+    common::{Result, Statistics},
+    execution::context::TaskContext,
+    physical_expr::{EquivalenceProperties, PhysicalSortExpr},
+    physical_plan::{
+        metrics::{ExecutionPlanMetricsSet, MetricsSet},
+        DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, ExecutionPlanProperties,
+        PhysicalExpr, PlanProperties, SendableRecordBatchStream,
+    },
+};
+use datafusion_ext_commons::{arrow::selection::create_batch_interleaver, df_execution_err};
+use futures::StreamExt;
+use once_cell::sync::OnceCell;
+
+use crate::{
+    common::{execution_context::ExecutionContext, statistics::scale_statistics},
+    window::{window_context::WindowContext, WindowRankType},
+};
+
+/// Keeps only rows whose row_number/rank/dense_rank is not greater than limit
+/// in each window partition, the equivalent of spark's WindowGroupLimitExec.
+/// input rows must be clustered by partition spec, but are not required to be
+/// sorted by order spec: rows of the current partition are kept in a bounded
+/// ordered heap, so the whole partition is never buffered. output rows are
+/// sorted by order spec within each partition.
+#[derive(Debug)]
+pub struct WindowGroupLimitExec {
+    input: Arc<dyn ExecutionPlan>,
+    context: Arc<WindowContext>,
+    rank_type: WindowRankType,
+    limit: usize,
+    metrics: ExecutionPlanMetricsSet,
+    props: OnceCell<PlanProperties>,
+}
+
+impl WindowGroupLimitExec {
+    pub fn try_new(
+        input: Arc<dyn ExecutionPlan>,
+        partition_spec: Vec<Arc<dyn PhysicalExpr>>,
+        order_spec: Vec<PhysicalSortExpr>,
+        rank_type: WindowRankType,
+        limit: usize,
+    ) -> Result<Self> {
+        if limit == 0 {
+            return df_execution_err!("WindowGroupLimitExec requires limit > 0");
+        }
+        let context = Arc::new(WindowContext::try_new(
+            input.schema(),
+            vec![],
+            partition_spec,
+            order_spec,
+        )?);
+        Ok(Self {
+            input,
+            context,
+            rank_type,
+            limit,
+            metrics: ExecutionPlanMetricsSet::new(),
+            props: OnceCell::new(),
+        })
+    }
+}
+
+impl DisplayAs for WindowGroupLimitExec {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "WindowGroupLimitExec({:?}, limit={})",
+            self.rank_type, self.limit
+        )
+    }
+}
+
+impl ExecutionPlan for WindowGroupLimitExec {
+    fn name(&self) -> &str {
+        "WindowGroupLimitExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.props.get_or_init(|| {
+            PlanProperties::new(
+                EquivalenceProperties::new(self.schema()),
+                self.input.output_partitioning().clone(),
+                ExecutionMode::Bounded,
+            )
+        })
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.input]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(Self::try_new(
+            children[0].clone(),
+            self.context.partition_spec.clone(),
+            self.context.order_spec.clone(),
+            self.rank_type,
+            self.limit,
+        )?))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let exec_ctx = ExecutionContext::new(context, partition, self.schema(), &self.metrics);
+        let input = exec_ctx.execute_with_input_stats(&self.input)?;
+        execute_window_group_limit(
+            input,
+            self.context.clone(),
+            self.rank_type,
+            self.limit,
+            exec_ctx,
+        )
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        // number of window partitions is unknown, so the input is an upper bound
+        Ok(scale_statistics(self.input.statistics()?, 1.0))
+    }
+}
+
+fn execute_window_group_limit(
+    mut input: SendableRecordBatchStream,
+    window_ctx: Arc<WindowContext>,
+    rank_type: WindowRankType,
+    limit: usize,
+    exec_ctx: Arc<ExecutionContext>,
+) -> Result<SendableRecordBatchStream> {
+    Ok(exec_ctx
+        .clone()
+        .output_with_sender("WindowGroupLimit", move |sender| async move {
+            sender.exclude_time(exec_ctx.baseline_metrics().elapsed_compute());
+
+            // batches referenced by the current group and output indices, the
+            // first batch holds compacted rows of the current group
+            let mut staging_batches: Vec<RecordBatch> = vec![];
+            let mut output_indices: Vec<(usize, usize)> = vec![];
+            let mut cur_partition: Option<Box<[u8]>> = None;
+            let mut group = GroupTopK::new(rank_type, limit);
+
+            loop {
+                let batch = input.next().await.transpose()?;
+                let _timer = exec_ctx.baseline_metrics().elapsed_compute().timer();
+                let input_finished = batch.is_none();
+
+                if let Some(batch) = batch {
+                    let partition_rows = window_ctx
+                        .has_partition()
+                        .then(|| window_ctx.get_partition_rows(&batch))
+                        .transpose()?;
+                    let order_rows = window_ctx.get_order_rows(&batch)?;
+                    let batch_idx = staging_batches.len();
+                    staging_batches.push(batch);
+
+                    for (row_idx, order_row) in order_rows.iter().enumerate() {
+                        let partition = match &partition_rows {
+                            Some(partition_rows) => partition_rows.row(row_idx).as_ref(),
+                            None => &[],
+                        };
+                        if cur_partition.as_deref() != Some(partition) {
+                            output_indices.extend(group.take_rows());
+                            cur_partition = Some(partition.into());
+                        }
+                        group.insert(order_row.as_ref(), (batch_idx, row_idx));
+                    }
+                } else {
+                    output_indices.extend(group.take_rows());
+                }
+
+                if !output_indices.is_empty() {
+                    let batch_interleaver = create_batch_interleaver(&staging_batches, false)?;
+                    let output_batch = batch_interleaver(&output_indices)?;
+                    output_indices.clear();
+                    exec_ctx
+                        .baseline_metrics()
+                        .record_output(output_batch.num_rows());
+                    sender.send(output_batch).await;
+                }
+                if input_finished {
+                    break;
+                }
+
+                // compact rows of current group, so that input batches are not
+                // retained by a few candidate rows
+                let batch_interleaver = create_batch_interleaver(&staging_batches, false)?;
+                let compacted = batch_interleaver(&group.row_refs())?;
+                group.remap_row_refs();
+                staging_batches = vec![compacted];
+            }
+            Ok(())
+        }))
+}
+
+/// bounded ordered heap of rows in a window partition, rows whose rank exceeds
+/// the limit are pruned as soon as they are determined.
+struct GroupTopK {
+    rank_type: WindowRankType,
+    limit: usize,
+    rows: BTreeMap<Box<[u8]>, Vec<(usize, usize)>>,
+    num_rows: usize,
+}
+
+impl GroupTopK {
+    fn new(rank_type: WindowRankType, limit: usize) -> Self {
+        Self {
+            rank_type,
+            limit,
+            rows: BTreeMap::new(),
+            num_rows: 0,
+        }
+    }
+
+    fn insert(&mut self, order_key: &[u8], row_ref: (usize, usize)) {
+        // fast path: rows after the last kept row cannot be kept
+        if let Some((last_key, _)) = self.rows.last_key_value() {
+            let rejected = match self.rank_type {
+                WindowRankType::RowNumber => {
+                    self.num_rows >= self.limit && order_key >= last_key.as_ref()
+                }
+                WindowRankType::Rank => {
+                    self.num_rows >= self.limit && order_key > last_key.as_ref()
+                }
+                WindowRankType::DenseRank => {
+                    self.rows.len() >= self.limit && order_key > last_key.as_ref()
+                }
+            };
+            if rejected {
+                return;
+            }
+        }
+        match self.rows.get_mut(order_key) {
+            Some(peers) => peers.push(row_ref),
+            None => {
+                self.rows.insert(order_key.into(), vec![row_ref]);
+            }
+        }
+        self.num_rows += 1;
+
+        // prune rows with the greatest order key
+        match self.rank_type {
+            WindowRankType::RowNumber => {
+                while self.num_rows > self.limit {
+                    let mut last = self.rows.last_entry().expect("non-empty group");
+                    last.get_mut().pop();
+                    if last.get().is_empty() {
+                        last.remove();
+                    }
+                    self.num_rows -= 1;
+                }
+            }
+            WindowRankType::Rank => {
+                // rows with the last key are pruned if there are enough rows
+                // before them, ties at the limit are all kept
+                loop {
+                    let num_last = self.rows.last_key_value().map(|(_, p)| p.len());
+                    match num_last {
+                        Some(n) if self.num_rows - n >= self.limit => {
+                            self.rows.pop_last();
+                            self.num_rows -= n;
+                        }
+                        _ => break,
+                    }
+                }
+            }
+            WindowRankType::DenseRank => {
+                while self.rows.len() > self.limit {
+                    let (_, peers) = self.rows.pop_last().expect("non-empty group");
+                    self.num_rows -= peers.len();
+                }
+            }
+        }
+    }
+
+    /// references of kept rows, sorted by order key
+    fn row_refs(&self) -> Vec<(usize, usize)> {
+        self.rows.values().flatten().cloned().collect()
+    }
+
+    /// updates row references after rows are compacted into the first batch
+    fn remap_row_refs(&mut self) {
+        let mut idx = 0;
+        for row_ref in self.rows.values_mut().flatten() {
+            *row_ref = (0, idx);
+            idx += 1;
+        }
+    }
+
+    fn take_rows(&mut self) -> Vec<(usize, usize)> {
+        let row_refs = self.row_refs();
+        self.rows.clear();
+        self.num_rows = 0;
+        row_refs
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::Int32Array,
+        datatypes::{DataType, Field, Schema},
+        record_batch::RecordBatch,
+    };
+    use datafusion::{
+        assert_batches_eq,
+        common::Result,
+        physical_expr::{expressions::Column, PhysicalSortExpr},
+        physical_plan::{common, memory::MemoryExec, ExecutionPlan},
+        prelude::SessionContext,
+    };
+
+    use crate::{window::WindowRankType, window_group_limit_exec::WindowGroupLimitExec};
+
+    #[tokio::test]
+    async fn test_window_group_limit() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, false),
+            Field::new("b", DataType::Int32, false),
+            Field::new("c", DataType::Int32, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 1, 1, 1, 1, 2, 2, 3])),
+                Arc::new(Int32Array::from(vec![5, 3, 3, 1, 4, 2, 2, 9])),
+                Arc::new(Int32Array::from(vec![0, 1, 2, 3, 4, 5, 6, 7])),
+            ],
+        )?;
+        // rows are clustered by partition but not sorted by order key
+        let input = Arc::new(MemoryExec::try_new(
+            &[vec![batch.slice(0, 3), batch.slice(3, 5)]],
+            schema.clone(),
+            None,
+        )?);
+
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+        let run = |rank_type, limit| {
+            let input = input.clone();
+            let task_ctx = task_ctx.clone();
+            async move {
+                let group_limit = WindowGroupLimitExec::try_new(
+                    input,
+                    vec![Arc::new(Column::new("a", 0))],
+                    vec![PhysicalSortExpr {
+                        expr: Arc::new(Column::new("b", 1)),
+                        options: Default::default(),
+                    }],
+                    rank_type,
+                    limit,
+                )?;
+                common::collect(group_limit.execute(0, task_ctx)?).await
+            }
+        };
+
+        let batches = run(WindowRankType::RowNumber, 2).await?;
+        let expected = vec![
+            "+---+---+---+",
+            "| a | b | c |",
+            "+---+---+---+",
+            "| 1 | 1 | 3 |",
+            "| 1 | 3 | 1 |",
+            "| 2 | 2 | 5 |",
+            "| 2 | 2 | 6 |",
+            "| 3 | 9 | 7 |",
+            "+---+---+---+",
+        ];
+        assert_batches_eq!(expected, &batches);
+
+        let batches = run(WindowRankType::Rank, 3).await?;
+        let expected = vec![
+            "+---+---+---+",
+            "| a | b | c |",
+            "+---+---+---+",
+            "| 1 | 1 | 3 |",
+            "| 1 | 3 | 1 |",
+            "| 1 | 3 | 2 |",
+            "| 2 | 2 | 5 |",
+            "| 2 | 2 | 6 |",
+            "| 3 | 9 | 7 |",
+            "+---+---+---+",
+        ];
+        assert_batches_eq!(expected, &batches);
+
+        let batches = run(WindowRankType::DenseRank, 3).await?;
+        let expected = vec![
+            "+---+---+---+",
+            "| a | b | c |",
+            "+---+---+---+",
+            "| 1 | 1 | 3 |",
+            "| 1 | 3 | 1 |",
+            "| 1 | 3 | 2 |",
+            "| 1 | 4 | 4 |",
+            "| 2 | 2 | 5 |",
+            "| 2 | 2 | 6 |",
+            "| 3 | 9 | 7 |",
+            "+---+---+---+",
+        ];
+        assert_batches_eq!(expected, &batches);
+        Ok(())
+    }
+}
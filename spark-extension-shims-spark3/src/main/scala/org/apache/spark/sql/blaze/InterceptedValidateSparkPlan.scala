@@ -27,6 +27,9 @@ object InterceptedValidateSparkPlan extends Logging {
       System.getProperty("blaze.shim")))
   def validate(plan: SparkPlan): Unit = {
     import org.apache.spark.sql.execution.adaptive.BroadcastQueryStageExec
+    import org.apache.spark.sql.execution.blaze.plan.BroadcastLeft
+    import org.apache.spark.sql.execution.blaze.plan.BroadcastRight
+    import org.apache.spark.sql.execution.blaze.plan.NativeBroadcastNestedLoopJoinExec
     import org.apache.spark.sql.execution.blaze.plan.NativeRenameColumnsBase
     import org.apache.spark.sql.execution.joins.BroadcastNestedLoopJoinExec
     import org.apache.spark.sql.execution.joins.blaze.plan.NativeBroadcastJoinExec
@@ -67,6 +70,19 @@ object InterceptedValidateSparkPlan extends Logging {
           validate(buildPlan)
         }
         validate(probePlan)
+
+      case b: NativeBroadcastNestedLoopJoinExec => // same as non-native BNLJ
+        var (buildPlan, probePlan) = b.broadcastSide match {
+          case BroadcastLeft => (b.left, b.right)
+          case BroadcastRight => (b.right, b.left)
+        }
+        if (buildPlan.isInstanceOf[NativeRenameColumnsBase]) {
+          buildPlan = buildPlan.children.head
+        }
+        if (!buildPlan.isInstanceOf[BroadcastQueryStageExec]) {
+          validate(buildPlan)
+        }
+        validate(probePlan)
       case q: BroadcastQueryStageExec => errorOnInvalidBroadcastQueryStage(q)
       case _ => plan.children.foreach(validate)
     }
@@ -36,6 +36,7 @@ import org.apache.spark.sql.blaze.NativeConverters.NativeExprWrapperBase
 import org.apache.spark.sql.catalyst.InternalRow
 import org.apache.spark.sql.catalyst.catalog.CatalogTable
 import org.apache.spark.sql.catalyst.expressions.Attribute
+import org.apache.spark.sql.catalyst.expressions.Cast
 import org.apache.spark.sql.catalyst.expressions.Expression
 import org.apache.spark.sql.catalyst.expressions.Generator
 import org.apache.spark.sql.catalyst.expressions.Like
@@ -78,11 +79,15 @@ import org.apache.spark.sql.execution.blaze.plan.NativeGlobalLimitExec
 import org.apache.spark.sql.execution.blaze.plan.NativeLocalLimitBase
 import org.apache.spark.sql.execution.blaze.plan.NativeLocalLimitExec
 import org.apache.spark.sql.execution.blaze.plan.NativeOrcScanExec
+import org.apache.spark.sql.execution.blaze.plan.NativeCsvScanExec
+import org.apache.spark.sql.execution.blaze.plan.NativeJsonScanExec
 import org.apache.spark.sql.execution.blaze.plan.NativeParquetInsertIntoHiveTableBase
 import org.apache.spark.sql.execution.blaze.plan.NativeParquetInsertIntoHiveTableExec
 import org.apache.spark.sql.execution.blaze.plan.NativeParquetScanBase
 import org.apache.spark.sql.execution.blaze.plan.NativeParquetScanExec
 import org.apache.spark.sql.execution.blaze.plan.NativeProjectBase
+import org.apache.spark.sql.execution.blaze.plan.NativeRangeBase
+import org.apache.spark.sql.execution.blaze.plan.NativeRangeExec
 import org.apache.spark.sql.execution.blaze.plan.NativeRenameColumnsBase
 import org.apache.spark.sql.execution.blaze.plan.NativeShuffleExchangeBase
 import org.apache.spark.sql.execution.blaze.plan.NativeShuffleExchangeExec
@@ -105,6 +110,7 @@ import org.apache.spark.sql.execution.joins.blaze.plan.NativeShuffledHashJoinExe
 import org.apache.spark.sql.execution.joins.blaze.plan.NativeSortMergeJoinExecProvider
 import org.apache.spark.sql.execution.metric.SQLMetric
 import org.apache.spark.sql.hive.execution.InsertIntoHiveTable
+import org.apache.spark.sql.internal.SQLConf
 import org.apache.spark.sql.types.DataType
 import org.apache.spark.sql.types.IntegerType
 import org.apache.spark.sql.types.StringType
@@ -194,6 +200,21 @@ class ShimsImpl extends Shims with Logging {
       joinType,
       broadcastSide)
 
+  override def createNativeBroadcastNestedLoopJoinExec(
+      left: SparkPlan,
+      right: SparkPlan,
+      outputPartitioning: Partitioning,
+      joinType: JoinType,
+      broadcastSide: BroadcastSide,
+      condition: Option[Expression]): NativeBroadcastNestedLoopJoinBase =
+    NativeBroadcastNestedLoopJoinExec(
+      left,
+      right,
+      outputPartitioning,
+      joinType,
+      broadcastSide,
+      condition)
+
   override def createNativeSortMergeJoinExec(
       left: SparkPlan,
       right: SparkPlan,
@@ -234,8 +255,11 @@ class ShimsImpl extends Shims with Logging {
       child: SparkPlan): NativeGenerateBase =
     NativeGenerateExec(generator, requiredChildOutput, outer, generatorOutput, child)
 
-  override def createNativeGlobalLimitExec(limit: Long, child: SparkPlan): NativeGlobalLimitBase =
-    NativeGlobalLimitExec(limit, child)
+  override def createNativeGlobalLimitExec(
+      limit: Long,
+      offset: Long,
+      child: SparkPlan): NativeGlobalLimitBase =
+    NativeGlobalLimitExec(limit, offset, child)
 
   override def createNativeLocalLimitExec(limit: Long, child: SparkPlan): NativeLocalLimitBase =
     NativeLocalLimitExec(limit, child)
@@ -252,12 +276,32 @@ class ShimsImpl extends Shims with Logging {
   override def createNativeOrcScanExec(basedFileScan: FileSourceScanExec): NativeOrcScanBase =
     NativeOrcScanExec(basedFileScan)
 
+  override def createNativeCsvScanExec(basedFileScan: FileSourceScanExec): NativeCsvScanBase =
+    NativeCsvScanExec(basedFileScan)
+
+  override def createNativeJsonScanExec(basedFileScan: FileSourceScanExec): NativeJsonScanBase =
+    NativeJsonScanExec(basedFileScan)
+
+  override def createNativeScanAggregatePushdownExec(
+      basedScan: NativeFileSourceScanBase,
+      output: Seq[Attribute],
+      pushedAggregates: Seq[pb.PushedAggregate]): NativeScanAggregatePushdownBase =
+    NativeScanAggregatePushdownExec(basedScan, output, pushedAggregates)
+
   override def createNativeProjectExec(
       projectList: Seq[NamedExpression],
       child: SparkPlan,
       addTypeCast: Boolean = false): NativeProjectBase =
     NativeProjectExecProvider.provide(projectList, child, addTypeCast)
 
+  override def createNativeRangeExec(
+      start: Long,
+      end: Long,
+      step: Long,
+      numSlices: Int,
+      output: Seq[Attribute]): NativeRangeBase =
+    NativeRangeExec(start, end, step, numSlices, output)
+
   override def createNativeRenameColumnsExec(
       child: SparkPlan,
       newColumnNames: Seq[String]): NativeRenameColumnsBase =
@@ -275,11 +319,19 @@ class ShimsImpl extends Shims with Logging {
       child: SparkPlan): NativeSortBase =
     NativeSortExec(sortOrder, global, child)
 
+  override def createNativeTopKFrequentExec(
+      expr: Expression,
+      k: Int,
+      child: SparkPlan): NativeTopKFrequentBase =
+    NativeTopKFrequentExec(expr, k, NativeTopKFrequentBase.outputAttributes(expr), child)
+
   override def createNativeTakeOrderedExec(
       limit: Long,
+      offset: Long,
       sortOrder: Seq[SortOrder],
+      projectList: Seq[NamedExpression],
       child: SparkPlan): NativeTakeOrderedBase =
-    NativeTakeOrderedExec(limit, sortOrder, child)
+    NativeTakeOrderedExec(limit, offset, sortOrder, projectList, child)
 
   override def createNativePartialTakeOrderedExec(
       limit: Long,
@@ -298,6 +350,15 @@ class ShimsImpl extends Shims with Logging {
       child: SparkPlan): NativeWindowBase =
     NativeWindowExec(windowExpression, partitionSpec, orderSpec, child)
 
+  override def createNativeWindowGroupLimitExec(
+      partitionSpec: Seq[Expression],
+      orderSpec: Seq[SortOrder],
+      rankLikeFunction: Expression,
+      limit: Int,
+      partial: Boolean,
+      child: SparkPlan): NativeWindowGroupLimitBase =
+    NativeWindowGroupLimitExec(partitionSpec, orderSpec, rankLikeFunction, limit, partial, child)
+
   override def createNativeParquetSinkExec(
       sparkSession: SparkSession,
       table: CatalogTable,
@@ -493,6 +554,14 @@ class ShimsImpl extends Shims with Logging {
           case Some(v) => return Some(v)
           case None =>
         }
+        convertTryCast(e, isPruningExpr, fallback) match {
+          case Some(v) => return Some(v)
+          case None =>
+        }
+        convertRegExpExtractAll(e, isPruningExpr, fallback) match {
+          case Some(v) => return Some(v)
+          case None =>
+        }
         None
     }
   }
@@ -501,6 +570,44 @@ class ShimsImpl extends Shims with Logging {
     expr.asInstanceOf[Like].escapeChar
   }
 
+  @enableIf(Seq("spark-3.0", "spark-3.1").contains(System.getProperty("blaze.shim")))
+  override def getCastMode(cast: Cast): pb.CastMode = {
+    if (SQLConf.get.ansiEnabled) pb.CastMode.ANSI else pb.CastMode.LEGACY
+  }
+
+  @enableIf(Seq("spark-3.2", "spark-3.3").contains(System.getProperty("blaze.shim")))
+  override def getCastMode(cast: Cast): pb.CastMode = {
+    if (cast.ansiEnabled) pb.CastMode.ANSI else pb.CastMode.LEGACY
+  }
+
+  @enableIf(Seq("spark-3.4", "spark-3.5").contains(System.getProperty("blaze.shim")))
+  override def getCastMode(cast: Cast): pb.CastMode = {
+    import org.apache.spark.sql.catalyst.expressions.EvalMode
+    cast.evalMode match {
+      case EvalMode.ANSI => pb.CastMode.ANSI
+      case EvalMode.TRY => pb.CastMode.TRY
+      case _ => pb.CastMode.LEGACY
+    }
+  }
+
+  @enableIf(
+    Seq("spark-3.0", "spark-3.1", "spark-3.2", "spark-3.3").contains(
+      System.getProperty("blaze.shim")))
+  override def isAnsiArithmetic(e: Expression): Boolean = SQLConf.get.ansiEnabled
+
+  @enableIf(Seq("spark-3.4", "spark-3.5").contains(System.getProperty("blaze.shim")))
+  override def isAnsiArithmetic(e: Expression): Boolean = {
+    import org.apache.spark.sql.catalyst.expressions._
+    e match {
+      case e: Add => e.evalMode == EvalMode.ANSI
+      case e: Subtract => e.evalMode == EvalMode.ANSI
+      case e: Multiply => e.evalMode == EvalMode.ANSI
+      case e: Divide => e.evalMode == EvalMode.ANSI
+      case e: Remainder => e.evalMode == EvalMode.ANSI
+      case _ => SQLConf.get.ansiEnabled
+    }
+  }
+
   override def convertMoreAggregateExpr(e: AggregateExpression): Option[pb.PhysicalExprNode] = {
     assert(getAggregateExpressionFilter(e).isEmpty)
 
@@ -930,6 +1037,66 @@ class ShimsImpl extends Shims with Logging {
       isPruningExpr: Boolean,
       fallback: Expression => pb.PhysicalExprNode): Option[pb.PhysicalExprNode] = None
 
+  // try_cast is a separated expression before spark 3.4
+  @enableIf(Seq("spark-3.2", "spark-3.3").contains(System.getProperty("blaze.shim")))
+  private def convertTryCast(
+      e: Expression,
+      isPruningExpr: Boolean,
+      fallback: Expression => pb.PhysicalExprNode): Option[pb.PhysicalExprNode] = {
+    import org.apache.spark.sql.catalyst.expressions.TryCast
+    e match {
+      case e: TryCast =>
+        val timeZone = NativeConverters.timeZoneId(e)
+        Some(
+          NativeConverters.buildCastExprNode(
+            e.child,
+            e.dataType,
+            pb.CastMode.TRY,
+            timeZone,
+            isPruningExpr,
+            fallback))
+      case _ => None
+    }
+  }
+
+  @enableIf(
+    Seq("spark-3.0", "spark-3.1", "spark-3.4", "spark-3.5").contains(
+      System.getProperty("blaze.shim")))
+  private def convertTryCast(
+      e: Expression,
+      isPruningExpr: Boolean,
+      fallback: Expression => pb.PhysicalExprNode): Option[pb.PhysicalExprNode] = None
+
+  @enableIf(
+    Seq("spark-3.1", "spark-3.2", "spark-3.3", "spark-3.4", "spark-3.5").contains(
+      System.getProperty("blaze.shim")))
+  private def convertRegExpExtractAll(
+      e: Expression,
+      isPruningExpr: Boolean,
+      fallback: Expression => pb.PhysicalExprNode): Option[pb.PhysicalExprNode] = {
+    import org.apache.spark.sql.catalyst.expressions.RegExpExtractAll
+    e match {
+      case e: RegExpExtractAll
+          if BlazeConf.REGEXP_FUNCTIONS_ENABLE.booleanConf()
+            && NativeConverters.isNativeRegexpSupported(e.regexp)
+            && e.idx.isInstanceOf[Literal] =>
+        Some(
+          NativeConverters.buildExtScalarFunctionNode(
+            "RegExpExtractAll",
+            e.children,
+            e.dataType,
+            isPruningExpr,
+            fallback))
+      case _ => None
+    }
+  }
+
+  @enableIf(Seq("spark-3.0").contains(System.getProperty("blaze.shim")))
+  private def convertRegExpExtractAll(
+      e: Expression,
+      isPruningExpr: Boolean,
+      fallback: Expression => pb.PhysicalExprNode): Option[pb.PhysicalExprNode] = None
+
 }
 
 case class ForceNativeExecutionWrapper(override val child: SparkPlan)
@@ -0,0 +1,51 @@
+/*
+ * Copyright 2022 The Blaze Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package org.apache.spark.sql.execution.blaze.plan
+
+import org.apache.spark.sql.catalyst.expressions.Expression
+import org.apache.spark.sql.catalyst.plans.JoinType
+import org.apache.spark.sql.catalyst.plans.physical.Partitioning
+import org.apache.spark.sql.execution.SparkPlan
+
+import com.thoughtworks.enableIf
+
+case class NativeBroadcastNestedLoopJoinExec(
+    override val left: SparkPlan,
+    override val right: SparkPlan,
+    override val outputPartitioning: Partitioning,
+    joinType: JoinType,
+    broadcastSide: BroadcastSide,
+    condition: Option[Expression])
+    extends NativeBroadcastNestedLoopJoinBase(
+      left,
+      right,
+      outputPartitioning,
+      joinType,
+      broadcastSide,
+      condition) {
+
+  @enableIf(
+    Seq("spark-3.2", "spark-3.3", "spark-3.4", "spark-3.5").contains(
+      System.getProperty("blaze.shim")))
+  override protected def withNewChildrenInternal(
+      newLeft: SparkPlan,
+      newRight: SparkPlan): SparkPlan =
+    copy(left = newLeft, right = newRight)
+
+  @enableIf(Seq("spark-3.0", "spark-3.1").contains(System.getProperty("blaze.shim")))
+  override def withNewChildren(newChildren: Seq[SparkPlan]): SparkPlan =
+    copy(left = newChildren(0), right = newChildren(1))
+}
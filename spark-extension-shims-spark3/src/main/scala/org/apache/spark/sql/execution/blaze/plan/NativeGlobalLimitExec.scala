@@ -19,8 +19,8 @@ import org.apache.spark.sql.execution.SparkPlan
 
 import com.thoughtworks.enableIf
 
-case class NativeGlobalLimitExec(limit: Long, override val child: SparkPlan)
-    extends NativeGlobalLimitBase(limit, child) {
+case class NativeGlobalLimitExec(limit: Long, offset: Long, override val child: SparkPlan)
+    extends NativeGlobalLimitBase(limit, offset, child) {
 
   @enableIf(
     Seq("spark-3.2", "spark-3.3", "spark-3.4", "spark-3.5").contains(
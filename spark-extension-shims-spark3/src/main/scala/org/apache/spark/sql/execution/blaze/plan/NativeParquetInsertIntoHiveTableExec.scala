@@ -15,6 +15,8 @@
  */
 package org.apache.spark.sql.execution.blaze.plan
 
+import scala.collection.JavaConverters._
+
 import org.apache.spark.sql.blaze.Shims
 import org.apache.spark.sql.execution.SparkPlan
 import org.apache.spark.sql.hive.execution.InsertIntoHiveTable
@@ -113,6 +115,7 @@ case class NativeParquetInsertIntoHiveTableExec(
       import org.apache.spark.sql.catalyst.InternalRow
       import org.apache.spark.sql.execution.datasources.BasicWriteJobStatsTracker
       import org.apache.spark.sql.execution.datasources.BasicWriteTaskStatsTracker
+      import org.apache.spark.sql.execution.datasources.WriteTaskStats
       import org.apache.spark.sql.execution.datasources.WriteTaskStatsTracker
       import org.apache.spark.util.SerializableConfiguration
 
@@ -122,12 +125,19 @@ case class NativeParquetInsertIntoHiveTableExec(
           new BasicWriteTaskStatsTracker(serializableHadoopConf.value) {
             override def newRow(_filePath: String, _row: InternalRow): Unit = {}
 
-            override def closeFile(filePath: String): Unit = {
-              val outputFileStat = ParquetSinkTaskContext.get.processedOutputFiles.remove()
-              for (_ <- 0L until outputFileStat.numRows) {
-                super.newRow(filePath, null)
+            // output files may be completed by native concurrent writers after spark closes
+            // them, so file stats are updated with the final stats
+            override def closeFile(filePath: String): Unit = {}
+
+            override def getFinalStats(taskCommitTime: Long): WriteTaskStats = {
+              val processedOutputFiles = ParquetSinkTaskContext.get.processedOutputFiles
+              while (!processedOutputFiles.isEmpty) {
+                val outputFileStat = processedOutputFiles.remove()
+                for (_ <- 0L until outputFileStat.numRows) {
+                  super.newRow(outputFileStat.path, null)
+                }
               }
-              super.closeFile(filePath)
+              super.getFinalStats(taskCommitTime)
             }
           }
         }
@@ -160,12 +170,12 @@ case class NativeParquetInsertIntoHiveTableExec(
             override def newRow(_row: InternalRow): Unit = {}
 
             override def getFinalStats(): WriteTaskStats = {
-              val outputFileStat = ParquetSinkTaskContext.get.processedOutputFiles.remove()
+              val outputFileStats = ParquetSinkTaskContext.get.processedOutputFiles.asScala
               BasicWriteTaskStats(
                 partitions = partitions,
-                numFiles = 1,
-                numBytes = outputFileStat.numBytes,
-                numRows = outputFileStat.numRows)
+                numFiles = outputFileStats.size,
+                numBytes = outputFileStats.map(_.numBytes).sum,
+                numRows = outputFileStats.map(_.numRows).sum)
             }
           }
         }
@@ -189,12 +199,12 @@ case class NativeParquetInsertIntoHiveTableExec(
             override def newRow(_row: InternalRow): Unit = {}
 
             override def getFinalStats(): WriteTaskStats = {
-              val outputFileStat = ParquetSinkTaskContext.get.processedOutputFiles.remove()
+              val outputFileStats = ParquetSinkTaskContext.get.processedOutputFiles.asScala
               BasicWriteTaskStats(
                 numPartitions = 1,
-                numFiles = 1,
-                numBytes = outputFileStat.numBytes,
-                numRows = outputFileStat.numRows)
+                numFiles = outputFileStats.size,
+                numBytes = outputFileStats.map(_.numBytes).sum,
+                numRows = outputFileStats.map(_.numRows).sum)
             }
           }
         }
@@ -15,6 +15,7 @@
  */
 package org.apache.spark.sql.execution.blaze.plan
 
+import org.apache.spark.sql.catalyst.expressions.NamedExpression
 import org.apache.spark.sql.catalyst.expressions.SortOrder
 import org.apache.spark.sql.execution.SparkPlan
 
@@ -22,9 +23,11 @@ import com.thoughtworks.enableIf
 
 case class NativeTakeOrderedExec(
     limit: Long,
+    offset: Long,
     sortOrder: Seq[SortOrder],
+    projectList: Seq[NamedExpression],
     override val child: SparkPlan)
-    extends NativeTakeOrderedBase(limit, sortOrder, child) {
+    extends NativeTakeOrderedBase(limit, offset, sortOrder, projectList, child) {
 
   @enableIf(
     Seq("spark-3.2", "spark-3.3", "spark-3.4", "spark-3.5").contains(
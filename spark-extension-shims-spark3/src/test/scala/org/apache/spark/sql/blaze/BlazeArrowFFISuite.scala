@@ -0,0 +1,171 @@
+/*
+ * Copyright 2022 The Blaze Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package org.apache.spark.sql.blaze
+
+import scala.util.Try
+
+import org.apache.arrow.c.ArrowArray
+import org.apache.arrow.c.ArrowSchema
+import org.apache.arrow.c.CDataDictionaryProvider
+import org.apache.arrow.c.Data
+import org.apache.arrow.vector.IntVector
+import org.apache.arrow.vector.VectorSchemaRoot
+import org.apache.spark.TaskContext
+import org.apache.spark.TaskContextImpl
+import org.apache.spark.sql.blaze.util.Using
+import org.apache.spark.sql.execution.blaze.arrowio.ArrowFFIExporter
+import org.apache.spark.sql.execution.blaze.arrowio.util.{ArrowColumnVector => BlazeArrowColumnVector}
+import org.apache.spark.sql.execution.blaze.arrowio.util.ArrowUtils
+import org.apache.spark.sql.execution.vectorized.OffHeapColumnVector
+import org.apache.spark.sql.execution.vectorized.OnHeapColumnVector
+import org.apache.spark.sql.types._
+import org.apache.spark.sql.vectorized.{ArrowColumnVector => SparkArrowColumnVector}
+import org.apache.spark.sql.vectorized.ColumnarBatch
+import org.apache.spark.sql.vectorized.ColumnVector
+
+class BlazeArrowFFISuite extends org.apache.spark.sql.QueryTest with BaseBlazeSQLSuite {
+
+  // exports the batch through the C data interface and imports it back
+  private def exportAndImport[T](batch: ColumnarBatch, schema: StructType)(
+      check: VectorSchemaRoot => T): T = {
+    Using.resource(ArrowUtils.newChildAllocator(getClass.getName)) { allocator =>
+      Using.resources(ArrowSchema.allocateNew(allocator), ArrowArray.allocateNew(allocator)) {
+        case (ffiSchema, ffiArray) =>
+          val exporter = ArrowFFIExporter.fromColumnarBatches(Iterator(batch), schema)
+          exporter.exportSchema(ffiSchema.memoryAddress())
+          assert(exporter.exportNextBatch(ffiArray.memoryAddress()))
+          assert(!exporter.exportNextBatch(ffiArray.memoryAddress()))
+
+          Using.resource(new CDataDictionaryProvider()) { dictionaryProvider =>
+            Using.resource(
+              Data.importVectorSchemaRoot(allocator, ffiArray, ffiSchema, dictionaryProvider)) {
+              root => check(root)
+            }
+          }
+      }
+    }
+  }
+
+  private def values(root: VectorSchemaRoot, ordinal: Int): Seq[Any] =
+    (0 until root.getRowCount).map(i => root.getVector(ordinal).getObject(i) match {
+      case null => null
+      case v: java.lang.Integer => v.intValue()
+      case v: java.lang.Long => v.longValue()
+      case v => v.toString
+    })
+
+  test("export batches of spark column vectors") {
+    val schema = StructType(
+      Seq(
+        StructField("i", IntegerType),
+        StructField("l", LongType),
+        StructField("s", StringType)))
+    val ints = new OnHeapColumnVector(3, IntegerType)
+    ints.putInt(0, 1)
+    ints.putNull(1)
+    ints.putInt(2, 3)
+    val longs = new OffHeapColumnVector(3, LongType)
+    longs.putLong(0, 10L)
+    longs.putLong(1, 20L)
+    longs.putNull(2)
+    val strings = new OnHeapColumnVector(3, StringType)
+    strings.putByteArray(0, "a".getBytes)
+    strings.putNull(1)
+    strings.putByteArray(2, "c".getBytes)
+
+    val batch = new ColumnarBatch(Array[ColumnVector](ints, longs, strings))
+    batch.setNumRows(3)
+    try {
+      exportAndImport(batch, schema) { root =>
+        assert(root.getRowCount == 3)
+        assert(values(root, 0) == Seq(1, null, 3))
+        assert(values(root, 1) == Seq(10L, 20L, null))
+        assert(values(root, 2) == Seq("a", null, "c"))
+      }
+    } finally {
+      batch.close()
+    }
+  }
+
+  test("export batches of spark arrow column vectors") {
+    val schema = StructType(Seq(StructField("i", IntegerType)))
+    Using.resource(ArrowUtils.newChildAllocator(getClass.getName)) { allocator =>
+      val vector = new IntVector("i", allocator)
+      vector.allocateNew(3)
+      vector.set(0, 1)
+      vector.setNull(1)
+      vector.set(2, 3)
+      vector.setValueCount(3)
+
+      val batch = new ColumnarBatch(Array[ColumnVector](new SparkArrowColumnVector(vector)))
+      batch.setNumRows(3)
+      try {
+        exportAndImport(batch, schema) { root =>
+          assert(values(root, 0) == Seq(1, null, 3))
+        }
+      } finally {
+        batch.close()
+      }
+    }
+  }
+
+  test("export batches of blaze arrow column vectors without copying") {
+    val schema = StructType(Seq(StructField("i", IntegerType)))
+    Using.resource(ArrowUtils.newChildAllocator(getClass.getName)) { allocator =>
+      val vector = new IntVector("i", allocator)
+      vector.allocateNew(3)
+      vector.set(0, 1)
+      vector.set(1, 2)
+      vector.setNull(2)
+      vector.setValueCount(3)
+
+      val batch = new ColumnarBatch(Array[ColumnVector](new BlazeArrowColumnVector(vector)))
+      batch.setNumRows(3)
+      try {
+        exportAndImport(batch, schema) { root =>
+          assert(values(root, 0) == Seq(1, 2, null))
+        }
+        // buffers are transferred out of the batch
+        assert(vector.getValueCount == 0)
+      } finally {
+        batch.close()
+      }
+    }
+  }
+
+  test("native rows iterated while the task is completed in another thread") {
+    withTable("t1") {
+      sql("create table t1 using parquet as select id as c1, cast(id as string) as c2 from range(100000)")
+      val rdd = sql("select c1, c2 from t1 where c1 >= 0").queryExecution.executedPlan.execute()
+      val numRows = rdd
+        .mapPartitions { rows =>
+          // task completion listeners close the native wrapper while rows are iterated
+          val tc = TaskContext.get().asInstanceOf[TaskContextImpl]
+          val completer = new Thread(() => Try(tc.markTaskCompleted(None)))
+          var count = 0L
+          completer.start()
+          while (rows.hasNext) {
+            rows.next()
+            count += 1
+          }
+          completer.join()
+          Iterator.single(count)
+        }
+        .collect()
+      assert(numRows.sum <= 100000)
+    }
+  }
+}
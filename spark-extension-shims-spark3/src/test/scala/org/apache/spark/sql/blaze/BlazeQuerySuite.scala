@@ -39,6 +39,22 @@ class BlazeQuerySuite extends org.apache.spark.sql.QueryTest with BaseBlazeSQLSu
     }
   }
 
+  test("aggregate window function without order by falls back") {
+    withSQLConf("spark.sql.adaptive.enabled" -> "false") {
+      withTable("t1") {
+        sql("create table t1 using parquet as select * from values (1, 1), (1, 2), (2, 3) as (k, v)")
+        val df = sql("select k, v, sum(v) over (partition by k) from t1")
+        checkAnswer(df, Seq(Row(1, 1, 3L), Row(1, 2, 3L), Row(2, 3, 3L)))
+
+        // the frame ends in UNBOUNDED FOLLOWING, which is not converted
+        val nativeWindows = df.queryExecution.executedPlan.collect {
+          case p if p.getClass.getSimpleName.startsWith("NativeWindow") => p
+        }
+        assert(nativeWindows.isEmpty)
+      }
+    }
+  }
+
   test("test filter with year function") {
     withTable("t1") {
       sql("create table t1 using parquet as select '2024-12-18' as event_time")
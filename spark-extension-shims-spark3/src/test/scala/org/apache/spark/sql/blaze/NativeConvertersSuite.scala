@@ -0,0 +1,46 @@
+/*
+ * Copyright 2022 The Blaze Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package org.apache.spark.sql.blaze
+
+import org.apache.spark.sql.catalyst.InternalRow
+import org.apache.spark.sql.catalyst.expressions.AttributeReference
+import org.apache.spark.sql.catalyst.expressions.AttributeSet
+import org.apache.spark.sql.catalyst.expressions.LeafExpression
+import org.apache.spark.sql.catalyst.expressions.codegen.CodegenFallback
+import org.apache.spark.sql.types.DataType
+import org.apache.spark.sql.types.IntegerType
+
+class NativeConvertersSuite extends org.apache.spark.sql.QueryTest with BaseBlazeSQLSuite {
+
+  test("fallback expression with unbound references is not converted") {
+    val c1 = AttributeReference("c1", IntegerType)()
+    val e = intercept[NotImplementedError] {
+      NativeConverters.convertExpr(UnboundAttributeExpr(c1))
+    }
+    assert(e.getMessage.contains("unbound references"))
+  }
+}
+
+/** an inconvertible expression referencing an attribute which is not its child */
+case class UnboundAttributeExpr(attr: AttributeReference)
+    extends LeafExpression
+    with CodegenFallback {
+
+  override lazy val references: AttributeSet = AttributeSet(attr)
+  override def dataType: DataType = attr.dataType
+  override def nullable: Boolean = attr.nullable
+  override def eval(input: InternalRow): Any = throw new UnsupportedOperationException
+}
@@ -17,6 +17,7 @@ package org.apache.spark.sql.blaze;
 
 import org.apache.spark.SparkConf;
 import org.apache.spark.SparkEnv$;
+import org.apache.spark.TaskContext;
 
 @SuppressWarnings("unused")
 public enum BlazeConf {
@@ -27,16 +28,54 @@ public enum BlazeConf {
     /// actual off-heap memory usage is expected to be spark.executor.memoryOverhead * fraction.
     MEMORY_FRACTION("spark.blaze.memoryFraction", 0.6),
 
+    /// min/max shares of native memory of consumer types (sort, agg, join, shuffle, cache, other),
+    /// like "shuffle:0:0.5,join:0.2:1". consumers exceeding the max share of their type are
+    /// spilled, and consumers within the min share of their type are not spilled for others.
+    MEMORY_CONSUMER_SHARES("spark.blaze.memory.consumerShares", ""),
+
     /// enable converting upper/lower functions to native, special cases may provide different
     /// outputs from spark due to different unicode versions.
     CASE_CONVERT_FUNCTIONS_ENABLE("spark.blaze.enable.caseconvert.functions", true),
 
+    /// enable converting rlike/regexp_extract/regexp_extract_all/regexp_replace with literal
+    /// patterns to native. patterns not supported by the native regex engine fall back to spark.
+    REGEXP_FUNCTIONS_ENABLE("spark.blaze.enable.regexp.functions", true),
+
+    /// enable converting datetime functions like date_format/unix_timestamp/date_trunc with
+    /// literal patterns to native. patterns with unsupported letters fall back to spark.
+    DATETIME_FUNCTIONS_ENABLE("spark.blaze.enable.datetime.functions", true),
+
+    /// enable converting transform/filter/exists/forall/aggregate/zip_with with lambda functions
+    /// to native. lambda bodies with inconvertible expressions fall back to spark.
+    HIGHER_ORDER_FUNCTIONS_ENABLE("spark.blaze.enable.higherOrder.functions", true),
+
+    /// enable evaluating hive UDAFs and other typed imperative aggregates in native aggregations
+    /// through the jvm, instead of falling back the whole aggregation to spark.
+    UDAF_WRAPPER_ENABLE("spark.blaze.enable.udafWrapper", true),
+
     /// enable extra metrics of input batch statistics
     INPUT_BATCH_STATISTICS_ENABLE("spark.blaze.enableInputBatchStatistics", true),
 
+    /// enable extra metrics of sampled evaluation time of each filter/projection expression
+    EXPR_METRICS_ENABLE("spark.blaze.exprMetrics.enable", false),
+
+    /// expression metrics are measured on one of every N evaluated batches
+    EXPR_METRICS_SAMPLE_INTERVAL("spark.blaze.exprMetrics.sampleInterval", 16),
+
+    /// max number of filter masks cached in a task, identical filters on the same batch in
+    /// different operators are evaluated only once. 0 to disable.
+    PREDICATE_CACHE_CAPACITY("spark.blaze.predicateCache.capacity", 16),
+
+    /// reorder conjunctive filter predicates by observed selectivity and cost, so that cheap and
+    /// selective predicates are evaluated first
+    FILTER_ADAPTIVE_PREDICATE_ORDER_ENABLE("spark.blaze.filter.adaptivePredicateOrder.enable", true),
+
     /// ignore corrupted input files
     IGNORE_CORRUPTED_FILES("spark.files.ignoreCorruptFiles", false),
 
+    /// match scanned columns case sensitively
+    CASE_SENSITIVE("spark.sql.caseSensitive", false),
+
     /// enable partial aggregate skipping (see https://github.com/blaze-init/blaze/issues/327)
     PARTIAL_AGG_SKIPPING_ENABLE("spark.blaze.partialAggSkipping.enable", true),
 
@@ -46,23 +85,280 @@ public enum BlazeConf {
     /// mininum number of rows to trigger partial aggregate skipping
     PARTIAL_AGG_SKIPPING_MIN_ROWS("spark.blaze.partialAggSkipping.minRows", BATCH_SIZE.intConf() * 2),
 
+    /// hash aggregates with a single int/long/date/timestamp grouping key directly hash the key
+    /// values instead of converting keys to rows
+    AGG_FIXED_KEY_HASHING_ENABLE("spark.blaze.agg.fixedKeyHashing.enable", true),
+
     // parquet enable page filtering
     PARQUET_ENABLE_PAGE_FILTERING("spark.blaze.parquet.enable.pageFiltering", false),
 
     // parqeut enable bloom filter
     PARQUET_ENABLE_BLOOM_FILTER("spark.blaze.parquet.enable.bloomFilter", false),
 
+    /// push down predicates of filters on parquet scans (late materialization). predicate columns
+    /// are decoded and filtered first, and other projected columns are decoded only for the
+    /// remaining rows. not used if all projected columns are predicate columns.
+    PARQUET_ENABLE_FILTER_PUSHDOWN("spark.blaze.parquet.enable.filterPushdown", true),
+
+    /// use page index and bloom filters for parquet scans with point lookup predicates (like
+    /// `a = 1` or `a in (1, 2)`), even if page filtering and bloom filters are disabled. when this
+    /// is disabled, page index and bloom filters are only used as the confs above specify.
+    PARQUET_ENABLE_POINT_LOOKUP_PRUNING("spark.blaze.parquet.enable.pointLookupPruning", false),
+
+    /// match parquet columns by field ids if the fields of the read schema have ids.
+    PARQUET_FIELD_ID_READ_ENABLED("spark.sql.parquet.fieldId.read.enabled", false),
+
+    /// apply deletion vectors of delta tables in native parquet scan. delta scans with deletion
+    /// vectors fall back to spark if disabled.
+    DELTA_DELETION_VECTOR_ENABLE("spark.blaze.delta.deletionVectors.enable", true),
+
+    // fraction of native memory used for caching parquet column chunks shared by all scan
+    // partitions in the executor, 0 to disable
+    PARQUET_COLUMN_CHUNK_CACHE_FRACTION("spark.blaze.parquet.columnChunkCache.fraction", 0.0),
+
+    /// max number of parquet files written concurrently by a native parquet sink task, so that
+    /// rows need not be sorted by dynamic partitions and buckets before writing. the remaining
+    /// rows are sorted when the limit is reached. 0 to always sort before writing.
+    PARQUET_SINK_MAX_CONCURRENT_WRITERS("spark.blaze.parquet.sink.maxConcurrentWriters", 0),
+
+    /// skip orc stripes whose column statistics cannot match the pushed down predicates
+    ORC_ENABLE_STRIPE_PRUNING("spark.blaze.orc.enable.stripePruning", true),
+
+    /// match orc columns by position instead of by name. files written by hive with physical
+    /// column names like `_col0` are always matched by position.
+    ORC_FORCE_POSITIONAL_EVOLUTION("spark.sql.orc.forcePositionalEvolution", false),
+
+    /// answer partial count/min/max aggregates without grouping keys over parquet/orc scans from
+    /// row group or stripe statistics, instead of reading data pages.
+    SCAN_AGGREGATE_PUSHDOWN_ENABLE("spark.blaze.scan.aggregatePushdown.enable", true),
+
+    /// aggregate in streaming mode if the input is already ordered on the grouping keys, groups
+    /// are emitted as soon as the keys change, without building a hash table.
+    AGG_STREAMING_ENABLE("spark.blaze.agg.streaming.enable", true),
+
     // spark io compression codec
     SPARK_IO_COMPRESSION_CODEC("spark.io.compression.codec", "lz4"),
 
+    // spark io compression zstd level, used for shuffle and broadcast data
+    SPARK_IO_COMPRESSION_ZSTD_LEVEL("spark.io.compression.zstd.level", 1),
+
+    /// compression codec of shuffle and broadcast ipc blocks: lz4, zstd or none, defaults to
+    /// spark.io.compression.codec. the codec is recorded in each block, readers do not need it.
+    IPC_COMPRESSION_CODEC("spark.blaze.ipc.compression.codec", null) {
+        @Override
+        public String stringConf() {
+            return inherited(this, SPARK_IO_COMPRESSION_CODEC).stringConf();
+        }
+    },
+
+    /// ipc blocks compressed to more than this ratio of their raw size (e.g. already compressed
+    /// binary columns) are written uncompressed for a while. set to 1.0 or more to disable.
+    IPC_COMPRESSION_SKIP_RATIO("spark.blaze.ipc.compression.skipRatio", 0.9),
+
     // replace all sort-merge join to shuffled-hash join, only used for benchmarking
     FORCE_SHUFFLED_HASH_JOIN("spark.blaze.forceShuffledHashJoin", false),
 
-    // spark spill compression codec
-    SPILL_COMPRESSION_CODEC("spark.blaze.spill.compression.codec", "lz4");
+    // spark spill compression codec: lz4, zstd or none
+    SPILL_COMPRESSION_CODEC("spark.blaze.spill.compression.codec", "lz4"),
+
+    /// zstd level of spills, ignored by other codecs
+    SPILL_COMPRESSION_LEVEL("spark.blaze.spill.compression.level", 1),
+
+    /// compression codec of sort spills, defaults to spark.blaze.spill.compression.codec
+    SORT_SPILL_COMPRESSION_CODEC("spark.blaze.sort.spill.compression.codec", null) {
+        @Override
+        public String stringConf() {
+            return inherited(this, SPILL_COMPRESSION_CODEC).stringConf();
+        }
+    },
+
+    /// zstd level of sort spills, defaults to spark.blaze.spill.compression.level
+    SORT_SPILL_COMPRESSION_LEVEL("spark.blaze.sort.spill.compression.level", null) {
+        @Override
+        public int intConf() {
+            return inherited(this, SPILL_COMPRESSION_LEVEL).intConf();
+        }
+    },
+
+    /// compression codec of aggregation spills, defaults to spark.blaze.spill.compression.codec
+    AGG_SPILL_COMPRESSION_CODEC("spark.blaze.agg.spill.compression.codec", null) {
+        @Override
+        public String stringConf() {
+            return inherited(this, SPILL_COMPRESSION_CODEC).stringConf();
+        }
+    },
+
+    /// zstd level of aggregation spills, defaults to spark.blaze.spill.compression.level
+    AGG_SPILL_COMPRESSION_LEVEL("spark.blaze.agg.spill.compression.level", null) {
+        @Override
+        public int intConf() {
+            return inherited(this, SPILL_COMPRESSION_LEVEL).intConf();
+        }
+    },
+
+    /// remote dir (like s3://bucket/path) for native spills exceeding the local disk quota, the
+    /// scheme must be served by a natively registered filesystem. empty to disable.
+    SPILL_REMOTE_DIR("spark.blaze.spill.remote.dir", ""),
+
+    /// max bytes of native spill files on local disks of an executor, spills are written to
+    /// spark.blaze.spill.remote.dir once exceeded. supports size suffixes like "100g".
+    SPILL_LOCAL_DISK_QUOTA("spark.blaze.spill.localDiskQuota", 0L) {
+        @Override
+        public long longConf() {
+            return conf().getSizeAsBytes(key, (long) defaultValue);
+        }
+    },
+
+    /// max bytes of native spill files on local disks of a task, exceeding spills fail the task
+    /// instead of filling the disks. 0 for unlimited, supports size suffixes like "100g".
+    SPILL_TASK_DISK_QUOTA("spark.blaze.spill.taskDiskQuota", 0L) {
+        @Override
+        public long longConf() {
+            return conf().getSizeAsBytes(key, (long) defaultValue);
+        }
+    },
+
+    /// max bytes of native spill files on local disks of an executor, exceeding spills fail the
+    /// task instead of filling the disks. 0 for unlimited, supports size suffixes like "100g".
+    SPILL_EXECUTOR_DISK_QUOTA("spark.blaze.spill.executorDiskQuota", 0L) {
+        @Override
+        public long longConf() {
+            return conf().getSizeAsBytes(key, (long) defaultValue);
+        }
+    },
+
+    // spark off-heap memory enabled
+    OFF_HEAP_MEMORY_ENABLED("spark.memory.offHeap.enabled", false),
+
+    // spark off-heap memory size in bytes, supports size suffixes like "1g"
+    OFF_HEAP_MEMORY_SIZE("spark.memory.offHeap.size", 0L) {
+        @Override
+        public long longConf() {
+            return conf().getSizeAsBytes(key, (long) defaultValue);
+        }
+    },
+
+    // book the size of buffered shuffle data in spark's off-heap execution memory instead of the
+    // native memory pool. accounting only: the data itself stays in native buffers.
+    // requires spark.memory.offHeap.enabled.
+    SHUFFLE_OFF_HEAP_STAGING_ENABLE("spark.blaze.shuffle.offHeapStaging.enable", false),
+
+    // max fraction of spark.memory.offHeap.size booked for buffered shuffle data
+    SHUFFLE_OFF_HEAP_STAGING_FRACTION("spark.blaze.shuffle.offHeapStaging.fraction", 0.5),
+
+    /// record input batch digests and operator decisions of one partition for offline replaying,
+    /// -1 to disable. only used for debugging non-deterministic results.
+    REPLAY_LOG_PARTITION("spark.blaze.replayLog.partition", -1),
+
+    /// stage of the replay logged partition, -1 for all stages
+    REPLAY_LOG_STAGE("spark.blaze.replayLog.stage", -1),
+
+    /// local directory of replay logs
+    REPLAY_LOG_DIR("spark.blaze.replayLog.dir", "/tmp/blaze-replay-logs"),
+
+    /// interval of publishing native metrics (including memory usage and spills) of running tasks
+    /// to spark ui, metrics are only published when tasks finish if set to 0.
+    METRICS_UPDATE_INTERVAL_MS("spark.blaze.metrics.updateIntervalMs", 10000),
+
+    /// interval of checking whether the task is killed (by speculation or stage cancellation),
+    /// native operators of killed tasks are cancelled promptly. 0 to disable.
+    TASK_CANCELLATION_CHECK_INTERVAL_MS("spark.blaze.task.cancellationCheckIntervalMs", 1000),
+
+    /// max number of retries of recoverable jni calls (like fetching resources of ipc readers)
+    /// before failing the task. 0 to disable retrying.
+    JNI_CALL_MAX_RETRIES("spark.blaze.jniCall.maxRetries", 3),
+
+    /// initial backoff between retries of jni calls, doubled after each retry
+    JNI_CALL_RETRY_BACKOFF_MS("spark.blaze.jniCall.retryBackoffMs", 100),
+
+    /// max number of rows in a batch passed from native to jvm, larger batches are split.
+    /// 0 for unlimited.
+    FFI_OUTPUT_MAX_ROWS("spark.blaze.ffiOutput.maxRows", 0),
+
+    /// max memory size in bytes of a batch passed from native to jvm, larger batches are split.
+    /// 0 for unlimited.
+    FFI_OUTPUT_MAX_BYTES("spark.blaze.ffiOutput.maxBytes", 0L),
+
+    /// max number of retries of a failed shuffle spill write, each retry writes to an
+    /// alternate local dir. 0 to disable retrying.
+    SHUFFLE_WRITE_FAILOVER_MAX_RETRIES("spark.blaze.shuffle.writeFailover.maxRetries", 2),
+
+    /// evaluate partition pruning predicates natively if the number of listed partitions
+    /// is at least this value. 0 to disable native partition pruning.
+    NATIVE_PARTITION_PRUNING_MIN_PARTITIONS("spark.blaze.partitionPruning.native.minPartitions", 10000),
+
+    /// evaluate dynamic partition pruning filters in native scans at runtime, so that planning
+    /// the scan does not wait for the broadcast results of the pruning subqueries.
+    NATIVE_DYNAMIC_PARTITION_PRUNING_ENABLE("spark.blaze.dynamicPartitionPruning.native.enable", true),
+
+    /// fall back to sort-merge join if the build side of shuffled hash join does not fit in
+    /// memory. otherwise the join is executed on hash-partitioned spills of both sides.
+    SHJ_FALLBACK_TO_SMJ_ENABLE("spark.blaze.shj.fallbackToSmj.enable", true),
+
+    /// max memory size in bytes of a batch produced by any native operator, larger batches are
+    /// split before sending downstream. protects against OOM with very wide rows. 0 for unlimited.
+    OUTPUT_BATCH_MAX_BYTES("spark.blaze.outputBatch.maxBytes", 134217728L),
+
+    /// equal-key runs of sort-merge join with at least this number of rows are treated as skewed
+    /// keys, which are joined block by block with spillable buffers. runs spanning multiple
+    /// batches on both sides are always treated as skewed. 0 to disable the row-count threshold.
+    SMJ_SKEWED_KEY_MIN_ROWS("spark.blaze.smj.skewedKey.minRows", 100000),
+
+    /// number of rows of sub-batches produced by sort-merge join, capped by the estimated output
+    /// row width so that wide outputs are produced in smaller batches. 0 for spark.blaze.batchSize.
+    SMJ_SUB_BATCH_SIZE("spark.blaze.smj.subBatchSize", 0),
+
+    /// execute broadcast nested loop joins with interval conditions (point-in-interval or
+    /// interval-overlap comparisons) by sweeping intervals sorted by start.
+    INTERVAL_JOIN_ENABLE("spark.blaze.intervalJoin.enable", true),
+
+    /// let joins publish min/max of join keys as runtime filters, so that native filters on the
+    /// other side skip batches whose key range cannot match.
+    RUNTIME_FILTER_ENABLE("spark.blaze.runtimeFilter.enable", true),
+
+    /// target memory size in bytes of batches coalesced from small ones. the number of rows of a
+    /// coalesced batch is computed from this size and the observed average row size.
+    COALESCE_TARGET_BATCH_BYTES("spark.blaze.coalesce.targetBatchBytes", 16777216L),
+
+    /// min number of rows of a coalesced batch, used when rows are very wide.
+    COALESCE_MIN_BATCH_ROWS("spark.blaze.coalesce.minBatchRows", 128),
+
+    /// max number of rows of a coalesced batch, used when rows are very narrow.
+    COALESCE_MAX_BATCH_ROWS("spark.blaze.coalesce.maxBatchRows", 65536),
+
+    /// number of shuffle/broadcast blocks fetched and decompressed in background while the
+    /// current one is consumed, each of them buffers a few decoded batches in native memory.
+    /// 0 to read blocks one by one.
+    IPC_READER_PREFETCH_DEPTH("spark.blaze.ipcReader.prefetchDepth", 0),
+
+    /// port of the native arrow flight server serving shuffle outputs of the executor to remote
+    /// peers. the native library must be built with the "flight" feature. 0 to disable.
+    /// requires spark.authenticate, clients authenticate with the spark auth secret.
+    FLIGHT_SERVER_PORT("spark.blaze.flight.server.port", 0),
+
+    // id of results streamed to flight clients instead of the jvm, set in the sql session to
+    // serve outputs of the query with tickets "result/<id>/<partition>". empty to disable.
+    FLIGHT_RESULT_ID("spark.blaze.flight.resultId", ""),
+
+    /// dump the executed native plan with metrics of each node when tasks finish, the dump is
+    /// written to executor logs for debugging slow stages.
+    EXPLAIN_ANALYZE_ENABLE("spark.blaze.explainAnalyze.enable", false),
+
+    /// format of the executed native plan dump, "text" or "json".
+    EXPLAIN_ANALYZE_FORMAT("spark.blaze.explainAnalyze.format", "text"),
+
+    /// endpoint of the OTLP collector (like http://localhost:4317) receiving tracing spans of native
+    /// tasks and operators. the native library must be built with the "otlp" feature. empty to
+    /// disable.
+    TRACING_OTLP_ENDPOINT("spark.blaze.tracing.otlpEndpoint", ""),
+
+    // comma separated storage urls (like hdfs://nameservice,s3://bucket) served by native
+    // filesystems instead of hadoop filesystems of jvm. the native library must be built with
+    // the "hdfs" or "s3" feature of the storage.
+    FS_NATIVE_URLS("spark.blaze.fs.nativeUrls", "");
 
     public final String key;
-    private final Object defaultValue;
+    final Object defaultValue;
 
     BlazeConf(String key, Object defaultValue) {
         this.key = key;
@@ -70,22 +366,42 @@ public enum BlazeConf {
     }
 
     public boolean booleanConf() {
+        String sessionValue = sessionValue();
+        if (sessionValue != null) {
+            return Boolean.parseBoolean(sessionValue.trim());
+        }
         return conf().getBoolean(key, (boolean) defaultValue);
     }
 
     public int intConf() {
+        String sessionValue = sessionValue();
+        if (sessionValue != null) {
+            return Integer.parseInt(sessionValue.trim());
+        }
         return conf().getInt(key, (int) defaultValue);
     }
 
     public long longConf() {
+        String sessionValue = sessionValue();
+        if (sessionValue != null) {
+            return Long.parseLong(sessionValue.trim());
+        }
         return conf().getLong(key, (long) defaultValue);
     }
 
     public double doubleConf() {
+        String sessionValue = sessionValue();
+        if (sessionValue != null) {
+            return Double.parseDouble(sessionValue.trim());
+        }
         return conf().getDouble(key, (double) defaultValue);
     }
 
     public String stringConf() {
+        String sessionValue = sessionValue();
+        if (sessionValue != null) {
+            return sessionValue;
+        }
         return conf().get(key, (String) defaultValue);
     }
 
@@ -109,6 +425,19 @@ public enum BlazeConf {
         return BlazeConf.valueOf(confName).stringConf();
     }
 
+    /// returns the conf itself if it is set, otherwise the parent conf whose value is inherited
+    private static BlazeConf inherited(BlazeConf conf, BlazeConf parent) {
+        boolean isSet = conf.sessionValue() != null || conf().contains(conf.key);
+        return isSet ? conf : parent;
+    }
+
+    /// confs set in the sql session are propagated to tasks as local properties, they take
+    /// precedence over the spark conf so that they can be configured per query.
+    private String sessionValue() {
+        TaskContext taskContext = TaskContext.get();
+        return taskContext != null ? taskContext.getLocalProperty(key) : null;
+    }
+
     private static SparkConf conf() {
         return SparkEnv$.MODULE$.get().conf();
     }
@@ -18,10 +18,12 @@ package org.apache.spark.sql.blaze;
 import java.lang.management.BufferPoolMXBean;
 import java.lang.management.ManagementFactory;
 import java.net.URI;
+import java.util.ArrayList;
 import java.util.List;
 import java.util.concurrent.ConcurrentHashMap;
 import org.apache.hadoop.fs.FileSystem;
 import org.apache.hadoop.fs.Path;
+import org.apache.spark.SecurityManager;
 import org.apache.spark.SparkEnv;
 import org.apache.spark.TaskContext;
 import org.apache.spark.TaskContext$;
@@ -29,8 +31,11 @@ import org.apache.spark.blaze.FSDataInputWrapper;
 import org.apache.spark.blaze.FSDataInputWrapper$;
 import org.apache.spark.blaze.FSDataOutputWrapper;
 import org.apache.spark.blaze.FSDataOutputWrapper$;
+import org.apache.spark.sql.blaze.memory.OffHeapStagingMemory;
+import org.apache.spark.sql.blaze.memory.OffHeapStagingMemory$;
 import org.apache.spark.sql.blaze.memory.OnHeapSpillManager;
 import org.apache.spark.sql.blaze.memory.OnHeapSpillManager$;
+import scala.collection.Iterator;
 
 @SuppressWarnings("unused")
 public class JniBridge {
@@ -44,6 +49,13 @@ public class JniBridge {
 
     public static native void onExit();
 
+    public static native int[] prunePartitions(byte[] request, long partitionValuesFFIArrayPtr);
+
+    public static native long[] getStageResourceUsage(int stageId);
+
+    /// returns definitions of native metrics in the format of "name:kind:displayName"
+    public static native String[] getNativeMetricDefinitions();
+
     public static ClassLoader getContextClassLoader() {
         return Thread.currentThread().getContextClassLoader();
     }
@@ -72,6 +84,16 @@ public class JniBridge {
         return OnHeapSpillManager$.MODULE$.current();
     }
 
+    public static OffHeapStagingMemory getTaskOffHeapStagingMemory() {
+        return OffHeapStagingMemory$.MODULE$.current();
+    }
+
+    /// returns true if the resource provider can be invoked again after failing, native callers
+    /// only retry such providers.
+    public static boolean isReinvocableProvider(Object provider) {
+        return provider instanceof ReinvocableProvider;
+    }
+
     public static boolean isTaskRunning() {
         TaskContext tc = getTaskContext();
         if (tc == null) { // driver is always running
@@ -80,6 +102,16 @@ public class JniBridge {
         return !tc.isCompleted() && !tc.isInterrupted();
     }
 
+    /// returns at most maxCount next elements of the iterator, fewer elements are returned only if
+    /// the iterator is exhausted. used by native to fetch elements in batches.
+    public static Object[] nextElements(Iterator<?> iterator, int maxCount) {
+        ArrayList<Object> elements = new ArrayList<>(maxCount);
+        while (elements.size() < maxCount && iterator.hasNext()) {
+            elements.add(iterator.next());
+        }
+        return elements.toArray();
+    }
+
     public static boolean isDriverSide() {
         TaskContext tc = getTaskContext();
         return tc == null;
@@ -112,4 +144,13 @@ public class JniBridge {
                 ._2
                 .getPath();
     }
+
+    public static String getExecutorHost() {
+        return SparkEnv.get().blockManager().blockManagerId().host();
+    }
+
+    public static String getAuthSecret() {
+        SecurityManager securityManager = SparkEnv.get().securityManager();
+        return securityManager.isAuthenticationEnabled() ? securityManager.getSecretKey() : null;
+    }
 }
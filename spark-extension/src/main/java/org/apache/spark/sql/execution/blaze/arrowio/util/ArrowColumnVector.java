@@ -151,6 +151,11 @@ public final class ArrowColumnVector extends ColumnVector {
         return childColumns[ordinal];
     }
 
+    /** Returns the underlying arrow vector. */
+    public ValueVector getValueVector() {
+        return accessor.vector;
+    }
+
     public ArrowColumnVector(ValueVector vector) {
         super(ArrowUtils.fromArrowField(vector.getField()));
 
@@ -21,17 +21,20 @@ import java.nio.file.Files
 import java.nio.file.StandardCopyOption
 import java.util.concurrent.atomic.AtomicReference
 
-import scala.collection.mutable.ArrayBuffer
+import scala.collection.JavaConverters._
 
 import org.apache.arrow.c.ArrowArray
 import org.apache.arrow.c.ArrowSchema
 import org.apache.arrow.c.CDataDictionaryProvider
 import org.apache.arrow.c.Data
+import org.apache.arrow.memory.BufferAllocator
 import org.apache.arrow.vector.VectorSchemaRoot
 import org.apache.arrow.vector.types.pojo.Schema
 import org.apache.spark.Partition
+import org.apache.spark.SparkException
 import org.apache.spark.TaskContext
 import org.apache.spark.internal.Logging
+import org.apache.spark.memory.SparkOutOfMemoryError
 import org.apache.spark.sql.blaze.util.Using
 import org.apache.spark.sql.catalyst.InternalRow
 import org.apache.spark.sql.catalyst.expressions.UnsafeProjection
@@ -55,39 +58,40 @@ case class BlazeCallNativeWrapper(
   BlazeCallNativeWrapper.initNative()
 
   private val error: AtomicReference[Throwable] = new AtomicReference(null)
+  @volatile private var planDump: Option[String] = None
   private val dictionaryProvider = new CDataDictionaryProvider()
   private var arrowSchema: Schema = _
   private var schema: StructType = _
   private var toUnsafe: UnsafeProjection = _
-  private val batchRows: ArrayBuffer[InternalRow] = ArrayBuffer()
-  private var batchCurRowIdx = 0
+
+  // the imported batch references buffers exported by native side, which are released
+  // when the batch is closed, so rows are read without copying the batch
+  private var batchAllocator: BufferAllocator = _
+  private var batchRoot: VectorSchemaRoot = _
+  private var batchRows: Iterator[InternalRow] = Iterator.empty
 
   logInfo(s"Start executing native plan")
   private var nativeRuntimePtr = JniBridge.callNative(NativeHelper.nativeMemory, this)
 
+  // the wrapper may be closed by task completion/failure listeners running in other threads
+  // (for example when the task is killed), so the iterator holds the same lock as close()
+  // while accessing the current batch or the native runtime. rows are copied into unsafe
+  // rows, which do not reference the batch after next() returns.
   private lazy val rowIterator = new Iterator[InternalRow] {
-    override def hasNext: Boolean = {
+    override def hasNext: Boolean = BlazeCallNativeWrapper.this.synchronized {
       checkError()
-      if (batchCurRowIdx < batchRows.length) {
-        return true
-      }
+      batchRows.hasNext || {
+        // release current batch
+        closeBatch()
 
-      // clear current batch
-      batchRows.clear()
-      batchCurRowIdx = 0
-
-      // load next batch
-      if (nativeRuntimePtr != 0 && JniBridge.nextBatch(nativeRuntimePtr)) {
-        return hasNext
+        // load next batch
+        nativeRuntimePtr != 0 && JniBridge.nextBatch(nativeRuntimePtr) && hasNext
       }
-      false
     }
 
-    override def next(): InternalRow = {
+    override def next(): InternalRow = BlazeCallNativeWrapper.this.synchronized {
       checkError()
-      val batchRow = batchRows(batchCurRowIdx)
-      batchCurRowIdx += 1
-      batchRow
+      toUnsafe(batchRows.next())
     }
   }
 
@@ -116,19 +120,26 @@ case class BlazeCallNativeWrapper(
       throw new RuntimeException("Native runtime is finalized")
     }
 
-    Using.resource(ArrowUtils.newChildAllocator(getClass.getName)) { batchAllocator =>
-      Using.resources(
-        ArrowArray.wrap(ffiArrayPtr),
-        VectorSchemaRoot.create(arrowSchema, batchAllocator)) { case (ffiArray, root) =>
-        Data.importIntoVectorSchemaRoot(batchAllocator, ffiArray, root, dictionaryProvider)
-        val batch = ColumnarHelper.rootAsBatch(root)
-
-        batchRows.append(
-          ColumnarHelper
-            .batchAsRowIter(batch)
-            .map(row => toUnsafe(row).copy().asInstanceOf[InternalRow])
-            .toSeq: _*)
-      }
+    closeBatch()
+    batchAllocator = ArrowUtils.newChildAllocator(getClass.getName)
+    batchRoot = VectorSchemaRoot.create(arrowSchema, batchAllocator)
+    Using.resource(ArrowArray.wrap(ffiArrayPtr)) { ffiArray =>
+      // moves the ownership of the exported array, its release callback is called
+      // after the root is closed
+      Data.importIntoVectorSchemaRoot(batchAllocator, ffiArray, batchRoot, dictionaryProvider)
+    }
+    batchRows = ColumnarHelper.rootAsBatch(batchRoot).rowIterator().asScala
+  }
+
+  private def closeBatch(): Unit = {
+    batchRows = Iterator.empty
+    if (batchRoot != null) {
+      batchRoot.close()
+      batchRoot = null
+    }
+    if (batchAllocator != null) {
+      batchAllocator.close()
+      batchAllocator = null
     }
   }
 
@@ -136,6 +147,43 @@ case class BlazeCallNativeWrapper(
     this.error.set(error)
   }
 
+  // errors raised by native side with error codes (including spark's error classes),
+  // thrown as the same exceptions spark throws
+  protected def setSparkError(errorClass: String, message: String): Unit = {
+    val formattedMessage = s"[$errorClass] $message"
+    setError(errorClass match {
+      case "MEMORY_EXCEEDED" =>
+        new SparkOutOfMemoryError(formattedMessage)
+      case "SPILL_IO_FAILURE" =>
+        new IOException(formattedMessage)
+      case "UNSUPPORTED_EXPRESSION" =>
+        new UnsupportedOperationException(formattedMessage)
+      case "DATA_CORRUPTION" =>
+        new SparkException(formattedMessage)
+      case "ARITHMETIC_OVERFLOW" | "CAST_OVERFLOW" | "DIVIDE_BY_ZERO" |
+          "NUMERIC_VALUE_OUT_OF_RANGE" =>
+        new ArithmeticException(formattedMessage)
+      case "CAST_INVALID_INPUT" =>
+        new NumberFormatException(formattedMessage)
+      case "INVALID_ARRAY_INDEX" =>
+        new ArrayIndexOutOfBoundsException(formattedMessage)
+      case "USER_RAISED_EXCEPTION" =>
+        new RuntimeException(message)
+      case _ =>
+        new RuntimeException(formattedMessage)
+    })
+  }
+
+  // executed native plan with metrics, dumped by native side when the task finishes
+  // if spark.blaze.explainAnalyze.enable is set
+  protected def setPlanDump(planDump: String): Unit = {
+    val stageId = context.map(_.stageId()).getOrElse(-1)
+    logInfo(s"Executed native plan (stage=$stageId, partition=${partition.index}):\n$planDump")
+    this.planDump = Some(planDump)
+  }
+
+  def getPlanDump: Option[String] = planDump
+
   protected def checkError(): Unit = {
     val throwable = error.getAndSet(null)
     if (throwable != null) {
@@ -150,6 +198,7 @@ case class BlazeCallNativeWrapper(
       .setPartitionId(partition.index)
       .setStageId(context.map(_.stageId()).getOrElse(0))
       .setJobId(partition.index.toString)
+      .setTaskAttemptId(context.map(_.taskAttemptId()).getOrElse(0L))
       .build()
 
     val taskDefinition = TaskDefinition
@@ -162,8 +211,7 @@ case class BlazeCallNativeWrapper(
 
   private def close(): Unit = {
     synchronized {
-      batchRows.clear()
-      batchCurRowIdx = 0
+      closeBatch()
 
       if (nativeRuntimePtr != 0) {
         JniBridge.finalizeNative(nativeRuntimePtr)
@@ -40,16 +40,25 @@ import org.apache.spark.sql.catalyst.expressions.aggregate.Partial
 import org.apache.spark.sql.catalyst.expressions.Literal
 import org.apache.spark.sql.catalyst.expressions.aggregate.AggregateExpression
 import org.apache.spark.sql.catalyst.expressions.aggregate.AggregateFunction
+import org.apache.spark.sql.catalyst.expressions.aggregate.Count
+import org.apache.spark.sql.catalyst.expressions.aggregate.Max
+import org.apache.spark.sql.catalyst.expressions.aggregate.Min
 import org.apache.spark.sql.catalyst.expressions.Ascending
 import org.apache.spark.sql.catalyst.expressions.SortOrder
+import org.apache.spark.sql.catalyst.plans.InnerLike
+import org.apache.spark.sql.catalyst.plans.LeftOuter
+import org.apache.spark.sql.catalyst.plans.LeftSemi
+import org.apache.spark.sql.catalyst.plans.RightOuter
 import org.apache.spark.sql.catalyst.plans.physical.HashPartitioning
 import org.apache.spark.sql.catalyst.plans.physical.RoundRobinPartitioning
+import org.apache.spark.sql.catalyst.plans.physical.RangePartitioning
 import org.apache.spark.sql.catalyst.plans.physical.Partitioning
 import org.apache.spark.sql.execution.FileSourceScanExec
 import org.apache.spark.sql.execution.FilterExec
 import org.apache.spark.sql.execution.GlobalLimitExec
 import org.apache.spark.sql.execution.LocalLimitExec
 import org.apache.spark.sql.execution.ProjectExec
+import org.apache.spark.sql.execution.RangeExec
 import org.apache.spark.sql.execution.SortExec
 import org.apache.spark.sql.execution.SparkPlan
 import org.apache.spark.sql.execution.TakeOrderedAndProjectExec
@@ -73,17 +82,33 @@ import org.apache.spark.sql.execution.UnaryExecNode
 import org.apache.spark.sql.execution.blaze.plan.BroadcastLeft
 import org.apache.spark.sql.execution.blaze.plan.BroadcastRight
 import org.apache.spark.sql.execution.blaze.plan.ConvertToNativeBase
+import org.apache.spark.sql.execution.blaze.plan.NativeCsvScanBase
+import org.apache.spark.sql.execution.blaze.plan.NativeJsonScanBase
 import org.apache.spark.sql.execution.blaze.plan.NativeOrcScanBase
+import org.apache.spark.sql.execution.blaze.plan.NativeFileSourceScanBase
 import org.apache.spark.sql.execution.blaze.plan.NativeParquetScanBase
+import org.apache.spark.sql.execution.blaze.plan.NativeRenameColumnsBase
+import org.apache.spark.sql.execution.blaze.plan.NativeScanAggregatePushdownBase
 import org.apache.spark.sql.execution.blaze.plan.NativeSortBase
 import org.apache.spark.sql.hive.blaze.BlazeHiveConverters
 import org.apache.spark.sql.hive.execution.InsertIntoHiveTable
 import org.apache.spark.sql.hive.execution.blaze.plan.NativeHiveTableScanBase
+import org.apache.spark.sql.types.BooleanType
+import org.apache.spark.sql.types.ByteType
+import org.apache.spark.sql.types.DataType
+import org.apache.spark.sql.types.DateType
+import org.apache.spark.sql.types.IntegerType
 import org.apache.spark.sql.types.LongType
+import org.apache.spark.sql.types.ShortType
+import org.blaze.{protobuf => pb}
 
 object BlazeConverters extends Logging {
   val enableScan: Boolean =
     SparkEnv.get.conf.getBoolean("spark.blaze.enable.scan", defaultValue = true)
+  val enableCsvScan: Boolean =
+    SparkEnv.get.conf.getBoolean("spark.blaze.enable.csv.scan", defaultValue = true)
+  val enableJsonScan: Boolean =
+    SparkEnv.get.conf.getBoolean("spark.blaze.enable.json.scan", defaultValue = true)
   val enablePaimonScan: Boolean =
     SparkEnv.get.conf.getBoolean("spark.blaze.enable.paimon.scan", defaultValue = false)
   val enableProject: Boolean =
@@ -115,10 +140,14 @@ object BlazeConverters extends Logging {
     SparkEnv.get.conf.getBoolean("spark.blaze.enable.expand", defaultValue = true)
   val enableWindow: Boolean =
     SparkEnv.get.conf.getBoolean("spark.blaze.enable.window", defaultValue = true)
+  val enableWindowGroupLimit: Boolean =
+    SparkEnv.get.conf.getBoolean("spark.blaze.enable.window.group.limit", defaultValue = true)
   val enableGenerate: Boolean =
     SparkEnv.get.conf.getBoolean("spark.blaze.enable.generate", defaultValue = true)
   val enableLocalTableScan: Boolean =
     SparkEnv.get.conf.getBoolean("spark.blaze.enable.local.table.scan", defaultValue = true)
+  val enableRange: Boolean =
+    SparkEnv.get.conf.getBoolean("spark.blaze.enable.range", defaultValue = true)
   val enableDataWriting: Boolean =
     SparkEnv.get.conf.getBoolean("spark.blaze.enable.data.writing", defaultValue = false)
 
@@ -219,10 +248,14 @@ object BlazeConverters extends Logging {
         tryConvert(e, convertExpandExec)
       case e: WindowExec if enableWindow => // window
         tryConvert(e, convertWindowExec)
+      case e if enableWindowGroupLimit && isWindowGroupLimitExec(e) => // window group limit
+        tryConvert(e, convertWindowGroupLimitExec)
       case e: GenerateExec if enableGenerate => // generate
         tryConvert(e, convertGenerateExec)
       case e: LocalTableScanExec if enableLocalTableScan => // local table scan
         tryConvert(e, convertLocalTableScanExec)
+      case e: RangeExec if enableRange => // range
+        tryConvert(e, convertRangeExec)
       case e: DataWritingCommandExec if enableDataWriting => // data writing
         tryConvert(e, convertDataWritingCommandExec)
 
@@ -267,13 +300,15 @@ object BlazeConverters extends Logging {
     assert(
       exec.outputPartitioning.numPartitions == 1 || exec.outputPartitioning
         .isInstanceOf[HashPartitioning] || exec.outputPartitioning
-        .isInstanceOf[RoundRobinPartitioning],
+        .isInstanceOf[RoundRobinPartitioning] || exec.outputPartitioning
+        .isInstanceOf[RangePartitioning],
       s"partitioning not supported: ${exec.outputPartitioning}")
 
     val convertedChild = outputPartitioning match {
       case p
           if p.isInstanceOf[HashPartitioning] || p
-            .isInstanceOf[RoundRobinPartitioning] || p.numPartitions == 1 =>
+            .isInstanceOf[RoundRobinPartitioning] || p
+            .isInstanceOf[RangePartitioning] || p.numPartitions == 1 =>
         convertToNative(child)
       case _ => child
     }
@@ -321,7 +356,12 @@ object BlazeConverters extends Logging {
         addRenameColumnsExec(Shims.get.createNativeParquetScanExec(exec))
       case p if p.getClass().getName().endsWith("OrcFileFormat") =>
         addRenameColumnsExec(Shims.get.createNativeOrcScanExec(exec))
-      case _ => throw new NotImplementedError("Cannot convert non parquet/orc scan exec")
+      case p if p.getClass().getName().endsWith("CSVFileFormat") && enableCsvScan =>
+        addRenameColumnsExec(Shims.get.createNativeCsvScanExec(exec))
+      case p if p.getClass().getName().endsWith("JsonFileFormat") && enableJsonScan =>
+        addRenameColumnsExec(Shims.get.createNativeJsonScanExec(exec))
+      case _ =>
+        throw new NotImplementedError("Cannot convert non parquet/orc/csv/json scan exec")
     }
   }
 
@@ -407,12 +447,31 @@ object BlazeConverters extends Logging {
     logDebug(s"  condition: $condition")
     assert(condition.isEmpty, "join condition is not supported")
 
-    Shims.get.createNativeSortMergeJoinExec(
-      addRenameColumnsExec(convertToNative(left)),
-      addRenameColumnsExec(convertToNative(right)),
+    val (nativeLeft, nativeRight) = (convertToNative(left), convertToNative(right))
+    val nativeJoin = Shims.get.createNativeSortMergeJoinExec(
+      addRenameColumnsExec(nativeLeft),
+      addRenameColumnsExec(nativeRight),
       leftKeys,
       rightKeys,
       joinType)
+
+    // runtime filters published by the finished side are applied on the other side, if its
+    // unmatched rows are not needed
+    joinType match {
+      case _: InnerLike | LeftSemi | LeftOuter =>
+        nativeJoin.setTagValue(
+          NativeRuntimeFilters.leftProducerTag,
+          NativeRuntimeFilters.assign(leftKeys, rightKeys, nativeRight))
+      case _ =>
+    }
+    joinType match {
+      case _: InnerLike | LeftSemi | RightOuter =>
+        nativeJoin.setTagValue(
+          NativeRuntimeFilters.rightProducerTag,
+          NativeRuntimeFilters.assign(rightKeys, leftKeys, nativeLeft))
+      case _ =>
+    }
+    nativeJoin
   }
 
   def convertShuffledHashJoinExec(exec: ShuffledHashJoinExec): SparkPlan = {
@@ -508,9 +567,10 @@ object BlazeConverters extends Logging {
           assert(NativeHelper.isNative(left), "broadcast join build side is not native")
       }
 
-      Shims.get.createNativeBroadcastJoinExec(
-        addRenameColumnsExec(convertToNative(left)),
-        addRenameColumnsExec(convertToNative(right)),
+      val (nativeLeft, nativeRight) = (convertToNative(left), convertToNative(right))
+      val nativeJoin = Shims.get.createNativeBroadcastJoinExec(
+        addRenameColumnsExec(nativeLeft),
+        addRenameColumnsExec(nativeRight),
         exec.outputPartitioning,
         leftKeys,
         rightKeys,
@@ -520,6 +580,21 @@ object BlazeConverters extends Logging {
           case BuildRight => BroadcastRight
         })
 
+      // runtime filters published by the built side are applied on the probed side, if its
+      // unmatched rows are not needed
+      (joinType, buildSide) match {
+        case (_: InnerLike, BuildLeft) =>
+          nativeJoin.setTagValue(
+            NativeRuntimeFilters.leftProducerTag,
+            NativeRuntimeFilters.assign(leftKeys, rightKeys, nativeRight))
+        case (_: InnerLike | LeftSemi, BuildRight) =>
+          nativeJoin.setTagValue(
+            NativeRuntimeFilters.rightProducerTag,
+            NativeRuntimeFilters.assign(rightKeys, leftKeys, nativeLeft))
+        case _ =>
+      }
+      nativeJoin
+
     } catch {
       case e @ (_: NotImplementedError | _: Exception) =>
         val underlyingBroadcast = exec.buildSide match {
@@ -540,7 +615,6 @@ object BlazeConverters extends Logging {
       logDebug(s"  joinType: ${exec.joinType}")
       logDebug(s"  buildSide: ${exec.buildSide}")
       logDebug(s"  condition: ${exec.condition}")
-      assert(condition.isEmpty, "join condition is not supported")
 
       // verify build side is native
       buildSide match {
@@ -550,18 +624,16 @@ object BlazeConverters extends Logging {
           assert(NativeHelper.isNative(left), "broadcast join build side is not native")
       }
 
-      // reuse NativeBroadcastJoin with empty equility keys
-      Shims.get.createNativeBroadcastJoinExec(
+      Shims.get.createNativeBroadcastNestedLoopJoinExec(
         addRenameColumnsExec(convertToNative(left)),
         addRenameColumnsExec(convertToNative(right)),
         exec.outputPartitioning,
-        Nil,
-        Nil,
         joinType,
         buildSide match {
           case BuildLeft => BroadcastLeft
           case BuildRight => BroadcastRight
-        })
+        },
+        condition)
 
     } catch {
       case e @ (_: NotImplementedError | _: Exception) =>
@@ -593,25 +665,49 @@ object BlazeConverters extends Logging {
 
   def convertGlobalLimitExec(exec: GlobalLimitExec): SparkPlan = {
     logDebug(s"Converting GlobalLimitExec: ${Shims.get.simpleStringWithNodeId(exec)}")
-    Shims.get.createNativeGlobalLimitExec(exec.limit.toLong, exec.child)
+    val (limit, offset) = getGlobalLimitAndOffset(exec)
+    Shims.get.createNativeGlobalLimitExec(limit, offset, exec.child)
+  }
+
+  @enableIf(Seq("spark-3.4", "spark-3.5").contains(System.getProperty("blaze.shim")))
+  def getGlobalLimitAndOffset(exec: GlobalLimitExec): (Long, Long) = {
+    // limit=-1 means there is only an offset
+    val limit = if (exec.limit >= 0) exec.limit.toLong else Long.MaxValue
+    (limit, exec.offset.toLong)
   }
 
+  @enableIf(
+    Seq("spark-3.0", "spark-3.1", "spark-3.2", "spark-3.3").contains(
+      System.getProperty("blaze.shim")))
+  def getGlobalLimitAndOffset(exec: GlobalLimitExec): (Long, Long) = (exec.limit.toLong, 0L)
+
   def convertTakeOrderedAndProjectExec(exec: TakeOrderedAndProjectExec): SparkPlan = {
     logDebug(s"Converting TakeOrderedAndProjectExec: ${Shims.get.simpleStringWithNodeId(exec)}")
-    val nativeTakeOrdered = Shims.get.createNativeTakeOrderedExec(
+    Shims.get.createNativeTakeOrderedExec(
       exec.limit,
+      getTakeOrderedOffset(exec),
       exec.sortOrder,
+      exec.projectList,
       addRenameColumnsExec(convertToNative(exec.child)))
-
-    if (exec.projectList != exec.child.output) {
-      val project = ProjectExec(exec.projectList, nativeTakeOrdered)
-      tryConvert(project, convertProjectExec)
-    } else {
-      nativeTakeOrdered
-    }
   }
 
+  @enableIf(Seq("spark-3.4", "spark-3.5").contains(System.getProperty("blaze.shim")))
+  def getTakeOrderedOffset(exec: TakeOrderedAndProjectExec): Long = exec.offset.toLong
+
+  @enableIf(
+    Seq("spark-3.0", "spark-3.1", "spark-3.2", "spark-3.3").contains(
+      System.getProperty("blaze.shim")))
+  def getTakeOrderedOffset(exec: TakeOrderedAndProjectExec): Long = 0L
+
   def convertHashAggregateExec(exec: HashAggregateExec): SparkPlan = {
+    // answer count/min/max without grouping keys from file statistics in native scans
+    if (BlazeConf.SCAN_AGGREGATE_PUSHDOWN_ENABLE.booleanConf()) {
+      pushDownAggregateToScan(exec) match {
+        case Some(pushed) => return addRenameColumnsExec(pushed)
+        case None =>
+      }
+    }
+
     // split non-trivial children exprs in partial-agg to a ProjectExec
     // for enabling filter-project optimization in native side
     getPartialAggProjection(exec.aggregateExpressions, exec.groupingExpressions) match {
@@ -656,7 +752,7 @@ object BlazeConverters extends Logging {
         "partial AggregateExec is not native")
     }
     val nativeAggr = Shims.get.createNativeAggExec(
-      NativeAggBase.HashAgg,
+      hashAggExecMode(exec.groupingExpressions, exec.child),
       exec.requiredChildDistributionExpressions,
       exec.groupingExpressions,
       exec.aggregateExpressions,
@@ -693,6 +789,95 @@ object BlazeConverters extends Logging {
     nativeAggr
   }
 
+  /**
+   * Returns the exec mode of a hash aggregate. if the input is already ordered on the grouping
+   * keys (like the output of sort-merge joins or sorted scans), groups are aggregated in
+   * streaming mode and emitted as soon as the keys change, without building a hash table.
+   */
+  private def hashAggExecMode(
+      groupingExpressions: Seq[NamedExpression],
+      child: SparkPlan): NativeAggBase.AggExecMode = {
+    val requiredOrdering = groupingExpressions.map(SortOrder(_, Ascending))
+    if (BlazeConf.AGG_STREAMING_ENABLE.booleanConf()
+      && groupingExpressions.nonEmpty
+      && SortOrder.orderingSatisfies(child.outputOrdering, requiredOrdering)) {
+      logDebug(s"Using streaming aggregation on ordered input: ${child.nodeName}")
+      NativeAggBase.SortAgg
+    } else {
+      NativeAggBase.HashAgg
+    }
+  }
+
+  /**
+   * Pushes down a partial aggregate without grouping keys into its child native parquet/orc
+   * scan, if the scan has no data filters and all aggregates are `count`, `min` or `max` of data
+   * columns. the scan outputs the aggregate buffers in spark's layout, so the final aggregate
+   * runs in spark.
+   */
+  private def pushDownAggregateToScan(exec: HashAggregateExec): Option[SparkPlan] = {
+    if (exec.requiredChildDistributionExpressions.isDefined || exec.groupingExpressions.nonEmpty) {
+      return None
+    }
+    val scan = exec.child match {
+      case e: NativeRenameColumnsBase => e.child
+      case e => e
+    }
+    val nativeScan = scan match {
+      case e: NativeParquetScanBase => e
+      case e: NativeOrcScanBase => e
+      case _ => return None
+    }
+    val fileScan = nativeScan.fileSourceScan
+    if (fileScan.dataFilters.nonEmpty) {
+      return None
+    }
+
+    // min/max of floating-point and string columns may differ from spark's results because of
+    // NaNs and truncated statistics, so they are not pushed down
+    val dataSchema = fileScan.relation.dataSchema
+    val partitionSchema = fileScan.relation.partitionSchema
+    val isMinMaxSupportedType = (dataType: DataType) =>
+      dataType match {
+        case BooleanType | ByteType | ShortType | IntegerType | LongType | DateType => true
+        case _ => false
+      }
+    def dataColumnIndex(expr: Expression): Option[Int] = expr match {
+      case attr: AttributeReference if !partitionSchema.exists(_.name == attr.name) =>
+        Some(dataSchema.indexWhere(_.name == attr.name)).filter(_ >= 0)
+      case _ => None
+    }
+    def pushedAggregate(function: pb.PushedAggFunction, column: Int): pb.PushedAggregate =
+      pb.PushedAggregate.newBuilder().setFunction(function).setColumn(column).build()
+
+    val pushedAggregates = exec.aggregateExpressions.map {
+      case e if e.mode != Partial || e.isDistinct || e.filter.isDefined => None
+      case e =>
+        e.aggregateFunction match {
+          case Count(Seq(child: Literal)) if !child.nullable =>
+            Some(pushedAggregate(pb.PushedAggFunction.PUSHED_AGG_FUNCTION_COUNT_ROWS, 0))
+          case Count(Seq(child)) =>
+            dataColumnIndex(child).map(
+              pushedAggregate(pb.PushedAggFunction.PUSHED_AGG_FUNCTION_COUNT, _))
+          case Min(child) if isMinMaxSupportedType(child.dataType) =>
+            dataColumnIndex(child).map(
+              pushedAggregate(pb.PushedAggFunction.PUSHED_AGG_FUNCTION_MIN, _))
+          case Max(child) if isMinMaxSupportedType(child.dataType) =>
+            dataColumnIndex(child).map(
+              pushedAggregate(pb.PushedAggFunction.PUSHED_AGG_FUNCTION_MAX, _))
+          case _ => None
+        }
+    }
+    if (pushedAggregates.isEmpty || pushedAggregates.exists(_.isEmpty)) {
+      return None
+    }
+    logDebug(s"Pushing down HashAggregateExec to scan: ${Shims.get.simpleStringWithNodeId(exec)}")
+    Some(
+      Shims.get.createNativeScanAggregatePushdownExec(
+        nativeScan,
+        exec.output,
+        pushedAggregates.flatten))
+  }
+
   def convertObjectHashAggregateExec(exec: ObjectHashAggregateExec): SparkPlan = {
     // split non-trivial children exprs in partial-agg to a ProjectExec
     // for enabling filter-project optimization in native side
@@ -713,7 +898,7 @@ object BlazeConverters extends Logging {
       assert(NativeAggBase.findPreviousNativeAggrExec(exec).isDefined)
     }
     val nativeAggr = Shims.get.createNativeAggExec(
-      NativeAggBase.HashAgg,
+      hashAggExecMode(exec.groupingExpressions, exec.child),
       exec.requiredChildDistributionExpressions,
       exec.groupingExpressions,
       exec.aggregateExpressions,
@@ -825,6 +1010,42 @@ object BlazeConverters extends Logging {
       addRenameColumnsExec(convertToNative(exec.child)))
   }
 
+  @enableIf(Seq("spark-3.5").contains(System.getProperty("blaze.shim")))
+  def isWindowGroupLimitExec(exec: SparkPlan): Boolean = {
+    import org.apache.spark.sql.execution.window.WindowGroupLimitExec
+    exec.isInstanceOf[WindowGroupLimitExec]
+  }
+
+  @enableIf(
+    Seq("spark-3.0", "spark-3.1", "spark-3.2", "spark-3.3", "spark-3.4").contains(
+      System.getProperty("blaze.shim")))
+  def isWindowGroupLimitExec(exec: SparkPlan): Boolean = false
+
+  @enableIf(Seq("spark-3.5").contains(System.getProperty("blaze.shim")))
+  def convertWindowGroupLimitExec(exec: SparkPlan): SparkPlan = {
+    import org.apache.spark.sql.execution.window.Partial
+    import org.apache.spark.sql.execution.window.WindowGroupLimitExec
+    val e = exec.asInstanceOf[WindowGroupLimitExec]
+    logDebug(s"Converting WindowGroupLimitExec: ${Shims.get.simpleStringWithNodeId(e)}")
+    logDebug(s"  partition spec: ${e.partitionSpec}")
+    logDebug(s"  order spec: ${e.orderSpec}")
+    logDebug(s"  rank like function: ${e.rankLikeFunction}")
+    logDebug(s"  limit: ${e.limit}, mode: ${e.mode}")
+    Shims.get.createNativeWindowGroupLimitExec(
+      e.partitionSpec,
+      e.orderSpec,
+      e.rankLikeFunction,
+      e.limit,
+      e.mode == Partial,
+      addRenameColumnsExec(convertToNative(e.child)))
+  }
+
+  @enableIf(
+    Seq("spark-3.0", "spark-3.1", "spark-3.2", "spark-3.3", "spark-3.4").contains(
+      System.getProperty("blaze.shim")))
+  def convertWindowGroupLimitExec(exec: SparkPlan): SparkPlan =
+    throw new NotImplementedError(s"window group limit not supported: $exec")
+
   def convertGenerateExec(exec: GenerateExec): SparkPlan = {
     logDebug(s"Converting GenerateExec: ${Shims.get.simpleStringWithNodeId(exec)}")
     logDebug(s"  generator: ${exec.generator}")
@@ -843,19 +1064,34 @@ object BlazeConverters extends Logging {
     convertToNative(exec)
   }
 
+  def convertRangeExec(exec: RangeExec): SparkPlan = {
+    logDebug(s"Converting RangeExec: ${Shims.get.simpleStringWithNodeId(exec)}")
+    Shims.get.createNativeRangeExec(exec.start, exec.end, exec.step, exec.numSlices, exec.output)
+  }
+
   def convertDataWritingCommandExec(exec: DataWritingCommandExec): SparkPlan = {
     logDebug(s"Converting DataWritingCommandExec: ${Shims.get.simpleStringWithNodeId(exec)}")
     exec match {
       case DataWritingCommandExec(cmd: InsertIntoHiveTable, child)
           if cmd.table.storage.outputFormat.contains(
             classOf[MapredParquetOutputFormat].getName) =>
-        // add an extra SortExec to sort child with dynamic columns
+        // since spark 3.3, bucketed hive tables are written with hive hash bucket ids, which
+        // are not supported by native parquet sink
+        if (cmd.table.bucketSpec.isDefined &&
+          !Seq("spark-3.0", "spark-3.1", "spark-3.2").contains(Shims.get.shimVersion)) {
+          throw new NotImplementedError("unsupported writing to bucketed hive table")
+        }
+
+        // add an extra SortExec to sort child with dynamic columns, unless the native sink
+        // writes dynamic partitions with concurrent writers
         // add row number to achieve stable sort
         var sortedChild = convertToNative(child)
         val numDynParts = cmd.partition.count(_._2.isEmpty)
         val requiredOrdering =
           child.output.slice(child.output.length - numDynParts, child.output.length)
-        if (requiredOrdering.nonEmpty && child.outputOrdering.map(_.child) != requiredOrdering) {
+        if (requiredOrdering.nonEmpty &&
+          child.outputOrdering.map(_.child) != requiredOrdering &&
+          BlazeConf.PARQUET_SINK_MAX_CONCURRENT_WRITERS.intConf() <= 0) {
           val rowNumExpr = StubExpr("RowNum", LongType, nullable = false)
           sortedChild = Shims.get.createNativeSortExec(
             requiredOrdering.map(SortOrder(_, Ascending)) ++ Seq(
@@ -885,8 +1121,9 @@ object BlazeConverters extends Logging {
       return false
     }
     plan match {
-      case _: NativeParquetScanBase | _: NativeOrcScanBase | _: NativeHiveTableScanBase |
-          _: NativeUnionBase =>
+      case _: NativeParquetScanBase | _: NativeOrcScanBase | _: NativeCsvScanBase |
+          _: NativeJsonScanBase | _: NativeHiveTableScanBase | _: NativeUnionBase |
+          _: NativeScanAggregatePushdownBase =>
         true
       case _: ConvertToNativeBase => needRenameColumns(plan.children.head)
       case exec if NativeHelper.isNative(exec) =>
@@ -15,6 +15,8 @@
  */
 package org.apache.spark.sql.blaze
 
+import scala.collection.mutable
+
 import org.apache.spark.internal.Logging
 import org.apache.spark.sql.execution.metric.SQLMetric
 
@@ -24,16 +26,28 @@ case class MetricNode(
     metricValueHandler: Option[(String, Long) => Unit] = None)
     extends Logging {
 
+  // cumulative values already added in the current task
+  @transient private lazy val addedValues = mutable.Map[String, Long]()
+
   def getChild(i: Int): MetricNode =
     children(i)
 
-  def add(metricName: String, v: Long): Unit = {
-    metricValueHandler.foreach(_.apply(metricName, v))
-    if (v > 0) {
-      metrics.get(metricName).foreach(_.add(v))
+  /// adds the cumulative value of a metric. values are published several times while the
+  /// task is running, only the increment since the last publishing is added.
+  def add(metricName: String, v: Long): Unit = synchronized {
+    val added = addedValues.getOrElse(metricName, 0L)
+    if (v > added) {
+      addedValues(metricName) = v
+      metricValueHandler.foreach(_.apply(metricName, v - added))
+      metrics.get(metricName).foreach(_.add(v - added))
     }
   }
 
+  /// adds cumulative values of several metrics, called by native with one jni call.
+  def addAll(metricNames: Array[String], values: Array[Long]): Unit = synchronized {
+    metricNames.indices.foreach(i => add(metricNames(i), values(i)))
+  }
+
   def foreach(fn: MetricNode => Unit): Unit = {
     fn(this)
     this.children.foreach(_.foreach(fn))
@@ -29,8 +29,25 @@ import com.google.protobuf.ByteString
 import org.apache.spark.SparkEnv
 import org.blaze.{protobuf => pb}
 import org.apache.spark.internal.Logging
-import org.apache.spark.sql.catalyst.expressions.{Abs, Acos, Add, Alias, And, Asin, Atan, AttributeReference, BitwiseAnd, BitwiseOr, BoundReference, CaseWhen, Cast, Ceil, CheckOverflow, Coalesce, Concat, ConcatWs, Contains, Cos, CreateArray, CreateNamedStruct, Divide, EndsWith, EqualTo, Exp, Expression, Floor, GetArrayItem, GetMapValue, GetStructField, GreaterThan, GreaterThanOrEqual, If, In, InSet, IsNotNull, IsNull, Length, LessThan, LessThanOrEqual, Like, Literal, Log, Log10, Log2, Lower, MakeDecimal, Md5, Multiply, Murmur3Hash, Not, NullIf, OctetLength, Or, Remainder, Sha2, ShiftLeft, ShiftRight, Signum, Sin, Sqrt, StartsWith, StringRepeat, StringSpace, StringTrim, StringTrimLeft, StringTrimRight, Substring, Subtract, Tan, TruncDate, Unevaluable, UnscaledValue, Upper}
+import org.apache.spark.sql.catalyst.expressions.{Abs, Acos, Add, AddMonths, Alias, And, Asin, Atan, AttributeReference, BitwiseAnd, BitwiseOr, BoundReference, CaseWhen, Cast, Ceil, CheckOverflow, Coalesce, Concat, ConcatWs, Contains, Cos, CreateArray, CreateNamedStruct, DateFormatClass, Divide, EndsWith, EqualTo, Exp, Expression, Floor, FromUnixTime, GetArrayItem, GetMapValue, GetStructField, GreaterThan, GreaterThanOrEqual, If, In, InSet, IsNotNull, IsNull, Length, LessThan, LessThanOrEqual, Like, Literal, Log, Log10, Log2, Lower, MakeDate, MakeDecimal, Md5, MonthsBetween, Multiply, Murmur3Hash, NextDay, Not, NullIf, OctetLength, Or, RegExpExtract, RegExpReplace, Remainder, RLike, Sha2, ShiftLeft, ShiftRight, Signum, Sin, Sqrt, StartsWith, StringRepeat, StringSpace, StringTrim, StringTrimLeft, StringTrimRight, Substring, Subtract, Tan, TimeZoneAwareExpression, ToTimestamp, TruncDate, TruncTimestamp, Unevaluable, UnscaledValue, Upper}
 import org.apache.spark.sql.catalyst.expressions.aggregate.AggregateExpression
+import org.apache.spark.sql.catalyst.expressions.ArrayAggregate
+import org.apache.spark.sql.catalyst.expressions.ArrayExists
+import org.apache.spark.sql.catalyst.expressions.ArrayFilter
+import org.apache.spark.sql.catalyst.expressions.ArrayForAll
+import org.apache.spark.sql.catalyst.expressions.ArrayTransform
+import org.apache.spark.sql.catalyst.expressions.CreateMap
+import org.apache.spark.sql.catalyst.expressions.ElementAt
+import org.apache.spark.sql.catalyst.expressions.HigherOrderFunction
+import org.apache.spark.sql.catalyst.expressions.InputFileName
+import org.apache.spark.sql.catalyst.expressions.LambdaFunction
+import org.apache.spark.sql.catalyst.expressions.MapKeys
+import org.apache.spark.sql.catalyst.expressions.MapValues
+import org.apache.spark.sql.catalyst.expressions.MonotonicallyIncreasingID
+import org.apache.spark.sql.catalyst.expressions.NamedLambdaVariable
+import org.apache.spark.sql.catalyst.expressions.RaiseError
+import org.apache.spark.sql.catalyst.expressions.SparkPartitionID
+import org.apache.spark.sql.catalyst.expressions.ZipWith
 import org.apache.spark.sql.catalyst.expressions.aggregate.Average
 import org.apache.spark.sql.catalyst.expressions.aggregate.CollectList
 import org.apache.spark.sql.catalyst.expressions.aggregate.CollectSet
@@ -38,7 +55,9 @@ import org.apache.spark.sql.catalyst.expressions.aggregate.Count
 import org.apache.spark.sql.catalyst.expressions.aggregate.Max
 import org.apache.spark.sql.catalyst.expressions.aggregate.Min
 import org.apache.spark.sql.catalyst.expressions.aggregate.Sum
+import org.apache.spark.sql.catalyst.expressions.aggregate.TypedImperativeAggregate
 import org.apache.spark.sql.catalyst.expressions.Attribute
+import org.apache.spark.sql.catalyst.expressions.BinaryArithmetic
 import org.apache.spark.sql.catalyst.expressions.aggregate.First
 import org.apache.spark.sql.catalyst.expressions.codegen.CodegenContext
 import org.apache.spark.sql.catalyst.expressions.codegen.ExprCode
@@ -53,6 +72,7 @@ import org.apache.spark.sql.catalyst.util.ArrayData
 import org.apache.spark.sql.catalyst.InternalRow
 import org.apache.spark.sql.catalyst.expressions.DayOfMonth
 import org.apache.spark.sql.catalyst.expressions.GetJsonObject
+import org.apache.spark.sql.catalyst.expressions.JsonToStructs
 import org.apache.spark.sql.catalyst.expressions.LeafExpression
 import org.apache.spark.sql.catalyst.expressions.Month
 import org.apache.spark.sql.catalyst.expressions.XxHash64
@@ -75,6 +95,7 @@ import org.apache.spark.sql.types.DecimalType
 import org.apache.spark.sql.types.DoubleType
 import org.apache.spark.sql.types.FloatType
 import org.apache.spark.sql.types.IntegerType
+import org.apache.spark.sql.types.IntegralType
 import org.apache.spark.sql.types.LongType
 import org.apache.spark.sql.types.MapType
 import org.apache.spark.sql.types.NullType
@@ -90,6 +111,8 @@ import org.blaze.protobuf.PhysicalExprNode
 object NativeConverters extends Logging {
   val udfJsonEnabled: Boolean =
     SparkEnv.get.conf.getBoolean("spark.blaze.udf.UDFJson.enabled", defaultValue = true)
+  val fromJsonEnabled: Boolean =
+    SparkEnv.get.conf.getBoolean("spark.blaze.expr.fromJson.enabled", defaultValue = true)
 
   def convertScalarType(dataType: DataType): pb.ScalarType = {
     val scalarTypeBuilder = dataType match {
@@ -309,8 +332,9 @@ object NativeConverters extends Logging {
 
     try {
       // get number of inconvertible children
+      // lambda functions are only convertible as part of their higher-order functions
       var numInconvertibleChildren = 0
-      sparkExpr.children.foreach { child =>
+      sparkExpr.children.filterNot(_.isInstanceOf[LambdaFunction]).foreach { child =>
         try {
           convertExprWithFallback(child, isPruningExpr = false, fallbackToError)
         } catch {
@@ -326,16 +350,18 @@ object NativeConverters extends Logging {
       numInconvertibleChildren match {
         case 0 => convertExprWithFallback(sparkExpr, isPruningExpr = false, fallbackToError)
         case 1 =>
-          val childrenConverted = sparkExpr.mapChildren { child =>
-            try {
-              val converted =
-                convertExprWithFallback(child, isPruningExpr = false, fallbackToError)
-              Shims.get.createNativeExprWrapper(converted, child.dataType, child.nullable)
-            } catch {
-              case _: NotImplementedError =>
-                val fallbacked = convertExpr(child)
-                Shims.get.createNativeExprWrapper(fallbacked, child.dataType, child.nullable)
-            }
+          val childrenConverted = sparkExpr.mapChildren {
+            case child: LambdaFunction => child
+            case child =>
+              try {
+                val converted =
+                  convertExprWithFallback(child, isPruningExpr = false, fallbackToError)
+                Shims.get.createNativeExprWrapper(converted, child.dataType, child.nullable)
+              } catch {
+                case _: NotImplementedError =>
+                  val fallbacked = convertExpr(child)
+                  Shims.get.createNativeExprWrapper(fallbacked, child.dataType, child.nullable)
+              }
           }
           convertExprWithFallback(childrenConverted, isPruningExpr = false, fallbackToError)
         case _ =>
@@ -363,6 +389,11 @@ object NativeConverters extends Logging {
             }
         })
 
+        // attributes inside inconvertible subtrees cannot be evaluated by the wrapper
+        if (bound.references.nonEmpty) {
+          throw new NotImplementedError(s"unsupported expression with unbound references: $bound")
+        }
+
         val paramsSchema = StructType(
           convertedChildren.values
             .map(ref => StructField("", ref.dataType, ref.nullable))
@@ -483,16 +514,10 @@ object NativeConverters extends Logging {
         }
 
       // cast
-      // not performing native cast for timestamp/dates (will use UDFWrapper instead)
-      case cast: Cast if !Seq(cast.dataType, cast.child.dataType).contains(TimestampType) =>
-        buildExprNode {
-          _.setTryCast(
-            pb.PhysicalTryCastNode
-              .newBuilder()
-              .setExpr(convertExprWithFallback(cast.child, isPruningExpr, fallback))
-              .setArrowType(convertDataType(cast.dataType))
-              .build())
-        }
+      case cast: Cast =>
+        val mode = Shims.get.getCastMode(cast)
+        val timeZone = timeZoneId(cast)
+        buildCastExprNode(cast.child, cast.dataType, mode, timeZone, isPruningExpr, fallback)
 
       // in
       case In(value, list) if list.forall(_.isInstanceOf[Literal]) =>
@@ -556,183 +581,54 @@ object NativeConverters extends Logging {
       case GreaterThanOrEqual(lhs, rhs) => buildBinaryExprNode(lhs, rhs, "GtEq")
       case LessThanOrEqual(lhs, rhs) => buildBinaryExprNode(lhs, rhs, "LtEq")
 
-      case e: Add =>
-        val lhs = e.left
-        val rhs = e.right
-        val resultType = e.dataType
-        if (lhs.dataType.isInstanceOf[DecimalType] && rhs.dataType.isInstanceOf[DecimalType]) {
-          def resultDecimalType(p1: Int, s1: Int, p2: Int, s2: Int): DecimalType = {
-            val resultScale = max(s1, s2)
-            val resultPrecision = max(p1 - s1, p2 - s2) + resultScale + 1
-            if (SQLConf.get.decimalOperationsAllowPrecisionLoss) {
-              DecimalType.adjustPrecisionScale(resultPrecision, resultScale)
-            } else {
-              DecimalType.bounded(resultPrecision, resultScale)
-            }
-          }
-          val resultType = (lhs.dataType, rhs.dataType) match {
-            case (lhsType: DecimalType, rhsType: DecimalType) =>
-              resultDecimalType(
-                lhsType.precision,
-                lhsType.scale,
-                rhsType.precision,
-                rhsType.scale)
-          }
-
-          buildExprNode {
-            _.setCast(pb.PhysicalCastNode
-              .newBuilder()
-              .setArrowType(convertDataType(resultType))
-              .setExpr(buildExprNode {
-                _.setBinaryExpr(
-                  pb.PhysicalBinaryExprNode
-                    .newBuilder()
-                    .setL(convertExprWithFallback(Cast(lhs, resultType), isPruningExpr, fallback))
-                    .setR(convertExprWithFallback(rhs, isPruningExpr, fallback))
-                    .setOp("Plus"))
-              }))
-          }
-        } else {
-          buildBinaryExprNode(lhs, rhs, "Plus")
-        }
-
-      case e: Subtract =>
-        val lhs = e.left
-        val rhs = e.right
-        val resultType = e.dataType
-        if (lhs.dataType.isInstanceOf[DecimalType] && rhs.dataType.isInstanceOf[DecimalType]) {
-          // copied from spark3.5
-          def resultDecimalType(p1: Int, s1: Int, p2: Int, s2: Int): DecimalType = {
-            val resultScale = max(s1, s2)
-            val resultPrecision = max(p1 - s1, p2 - s2) + resultScale + 1
-            if (SQLConf.get.decimalOperationsAllowPrecisionLoss) {
-              DecimalType.adjustPrecisionScale(resultPrecision, resultScale)
-            } else {
-              DecimalType.bounded(resultPrecision, resultScale)
-            }
-          }
-          val resultType = (lhs.dataType, rhs.dataType) match {
-            case (lhsType: DecimalType, rhsType: DecimalType) =>
-              resultDecimalType(
-                lhsType.precision,
-                lhsType.scale,
-                rhsType.precision,
-                rhsType.scale)
-          }
-
-          buildExprNode {
-            _.setCast(pb.PhysicalCastNode
+      // decimal arithmetic
+      case e: BinaryArithmetic if isDecimalArithmetic(e) =>
+        buildExprNode {
+          _.setDecimalArithmeticExpr(
+            pb.PhysicalDecimalArithmeticNode
               .newBuilder()
-              .setArrowType(convertDataType(resultType))
-              .setExpr(buildExprNode {
-                _.setBinaryExpr(
-                  pb.PhysicalBinaryExprNode
-                    .newBuilder()
-                    .setL(convertExprWithFallback(Cast(lhs, resultType), isPruningExpr, fallback))
-                    .setR(convertExprWithFallback(rhs, isPruningExpr, fallback))
-                    .setOp("Minus"))
-              }))
-          }
-        } else {
-          buildBinaryExprNode(lhs, rhs, "Minus")
+              .setL(convertExprWithFallback(e.left, isPruningExpr, fallback))
+              .setR(convertExprWithFallback(e.right, isPruningExpr, fallback))
+              .setOp(e match {
+                case _: Add => pb.DecimalArithmeticOp.DECIMAL_ARITHMETIC_OP_ADD
+                case _: Subtract => pb.DecimalArithmeticOp.DECIMAL_ARITHMETIC_OP_SUBTRACT
+                case _: Multiply => pb.DecimalArithmeticOp.DECIMAL_ARITHMETIC_OP_MULTIPLY
+                case _: Divide => pb.DecimalArithmeticOp.DECIMAL_ARITHMETIC_OP_DIVIDE
+                case _: Remainder => pb.DecimalArithmeticOp.DECIMAL_ARITHMETIC_OP_REMAINDER
+              })
+              .setReturnType(convertDataType(decimalArithmeticResultType(e)))
+              .setFailOnOverflow(Shims.get.isAnsiArithmetic(e)))
         }
 
-      case e: Multiply =>
-        val lhs = e.left
-        val rhs = e.right
-        if (lhs.dataType.isInstanceOf[DecimalType] && rhs.dataType.isInstanceOf[DecimalType]) {
-          // copied from spark3.5
-          def resultDecimalType(p1: Int, s1: Int, p2: Int, s2: Int): DecimalType = {
-            val resultScale = s1 + s2
-            val resultPrecision = p1 + p2 + 1
-            if (SQLConf.get.decimalOperationsAllowPrecisionLoss) {
-              DecimalType.adjustPrecisionScale(resultPrecision, resultScale)
-            } else {
-              DecimalType.bounded(resultPrecision, resultScale)
-            }
-          }
-          val resultType = (lhs.dataType, rhs.dataType) match {
-            case (lhsType: DecimalType, rhsType: DecimalType) =>
-              resultDecimalType(
-                lhsType.precision,
-                lhsType.scale,
-                rhsType.precision,
-                rhsType.scale)
-          }
-
-          buildExprNode {
-            _.setCast(pb.PhysicalCastNode
-              .newBuilder()
-              .setArrowType(convertDataType(resultType))
-              .setExpr(buildExprNode {
-                _.setBinaryExpr(
-                  pb.PhysicalBinaryExprNode
-                    .newBuilder()
-                    .setL(convertExprWithFallback(Cast(lhs, resultType), isPruningExpr, fallback))
-                    .setR(convertExprWithFallback(rhs, isPruningExpr, fallback))
-                    .setOp("Multiply"))
-              }))
-          }
-        } else {
-          buildBinaryExprNode(lhs, rhs, "Multiply")
+      // ansi arithmetic, overflows and division by zero are raised as spark errors
+      case e: BinaryArithmetic if Shims.get.isAnsiArithmetic(e) && isAnsiCheckedArithmetic(e) =>
+        val name = e match {
+          case _: Add => "AnsiAdd"
+          case _: Subtract => "AnsiSubtract"
+          case _: Multiply => "AnsiMultiply"
+          case _: Divide => "AnsiDivide"
+          case _: Remainder => "AnsiRemainder"
         }
+        val lhsCasted = castIfNecessary(e.left, e.dataType)
+        val rhsCasted = castIfNecessary(e.right, e.dataType)
+        buildExtScalarFunction(name, lhsCasted :: rhsCasted :: Nil, e.dataType)
 
+      case e: Add => buildBinaryExprNode(e.left, e.right, "Plus")
+      case e: Subtract => buildBinaryExprNode(e.left, e.right, "Minus")
+      case e: Multiply => buildBinaryExprNode(e.left, e.right, "Multiply")
       case e: Divide =>
         val lhs = e.left
         val rhs = e.right
-        if (lhs.dataType.isInstanceOf[DecimalType] && rhs.dataType.isInstanceOf[DecimalType]) {
-          // copied from spark3.5
-          def resultDecimalType(p1: Int, s1: Int, p2: Int, s2: Int): DecimalType = {
-            if (SQLConf.get.decimalOperationsAllowPrecisionLoss) {
-              val intDig = p1 - s1 + s2
-              val scale = max(DecimalType.MINIMUM_ADJUSTED_SCALE, s1 + p2 + 1)
-              val prec = intDig + scale
-              DecimalType.adjustPrecisionScale(prec, scale)
-            } else {
-              var intDig = min(DecimalType.MAX_SCALE, p1 - s1 + s2)
-              var decDig = min(DecimalType.MAX_SCALE, max(6, s1 + p2 + 1))
-              val diff = (intDig + decDig) - DecimalType.MAX_SCALE
-              if (diff > 0) {
-                decDig -= diff / 2 + 1
-                intDig = DecimalType.MAX_SCALE - decDig
-              }
-              DecimalType.bounded(intDig + decDig, decDig)
-            }
-          }
-          val resultType = (lhs.dataType, rhs.dataType) match {
-            case (lhsType: DecimalType, rhsType: DecimalType) =>
-              resultDecimalType(
-                lhsType.precision,
-                lhsType.scale,
-                rhsType.precision,
-                rhsType.scale)
-          }
-
-          buildExprNode {
-            _.setCast(pb.PhysicalCastNode
+        val resultType = e.dataType
+        val lhsCasted = castIfNecessary(lhs, resultType)
+        val rhsCasted = castIfNecessary(rhs, resultType)
+        buildExprNode {
+          _.setBinaryExpr(
+            pb.PhysicalBinaryExprNode
               .newBuilder()
-              .setArrowType(convertDataType(resultType))
-              .setExpr(buildExprNode {
-                _.setBinaryExpr(
-                  pb.PhysicalBinaryExprNode
-                    .newBuilder()
-                    .setL(convertExprWithFallback(Cast(lhs, resultType), isPruningExpr, fallback))
-                    .setR(buildExtScalarFunction("NullIfZero", rhs :: Nil, rhs.dataType))
-                    .setOp("Divide"))
-              }))
-          }
-        } else {
-          val resultType = e.dataType
-          val lhsCasted = castIfNecessary(lhs, resultType)
-          val rhsCasted = castIfNecessary(rhs, resultType)
-          buildExprNode {
-            _.setBinaryExpr(
-              pb.PhysicalBinaryExprNode
-                .newBuilder()
-                .setL(convertExprWithFallback(lhsCasted, isPruningExpr, fallback))
-                .setR(buildExtScalarFunction("NullIfZero", rhsCasted :: Nil, rhs.dataType))
-                .setOp("Divide"))
-          }
+              .setL(convertExprWithFallback(lhsCasted, isPruningExpr, fallback))
+              .setR(buildExtScalarFunction("NullIfZero", rhsCasted :: Nil, rhs.dataType))
+              .setOp("Divide"))
         }
 
       case e: Remainder =>
@@ -854,8 +750,6 @@ object NativeConverters extends Logging {
         buildScalarFunction(pb.ScalarFunction.Rtrim, e.srcStr +: e.trimStr.toSeq, e.dataType)
       case e @ NullIf(left, right, _) =>
         buildExtScalarFunction("NullIf", left :: right :: Nil, e.dataType)
-      case e: TruncDate =>
-        buildScalarFunction(pb.ScalarFunction.DateTrunc, e.children, e.dataType)
       case Md5(_1) =>
         buildScalarFunction(pb.ScalarFunction.MD5, Seq(unpackBinaryTypeCast(_1)), StringType)
       case Sha2(_1, Literal(224, _)) =>
@@ -877,6 +771,52 @@ object NativeConverters extends Logging {
       case Month(child) => buildExtScalarFunction("Month", child :: Nil, IntegerType)
       case DayOfMonth(child) => buildExtScalarFunction("Day", child :: Nil, IntegerType)
 
+      // datetime functions with literal java patterns/truncation levels, parsing is only
+      // supported in non-ansi mode where unparsable inputs produce nulls
+      case e: FromUnixTime
+          if BlazeConf.DATETIME_FUNCTIONS_ENABLE.booleanConf()
+            && isNativeDateTimePatternSupported(e.format) =>
+        val args = e.sec :: e.format :: timeZoneLiteral(e) :: Nil
+        buildExtScalarFunction("FromUnixTime", args, e.dataType)
+      case e: ToTimestamp
+          if BlazeConf.DATETIME_FUNCTIONS_ENABLE.booleanConf()
+            && e.dataType == LongType // unix_timestamp/to_unix_timestamp
+            && Seq(StringType, DateType, TimestampType).contains(e.left.dataType)
+            && isNativeDateTimePatternSupported(e.right)
+            && !SQLConf.get.ansiEnabled =>
+        val args = e.left :: e.right :: timeZoneLiteral(e) :: Nil
+        buildExtScalarFunction("UnixTimestamp", args, e.dataType)
+      case e: DateFormatClass
+          if BlazeConf.DATETIME_FUNCTIONS_ENABLE.booleanConf()
+            && e.left.dataType == TimestampType
+            && isNativeDateTimePatternSupported(e.right) =>
+        val args = e.left :: e.right :: timeZoneLiteral(e) :: Nil
+        buildExtScalarFunction("DateFormat", args, e.dataType)
+      case e: TruncDate
+          if BlazeConf.DATETIME_FUNCTIONS_ENABLE.booleanConf()
+            && e.format.isInstanceOf[Literal] =>
+        buildExtScalarFunction("TruncDate", e.date :: e.format :: Nil, e.dataType)
+      case e: TruncTimestamp
+          if BlazeConf.DATETIME_FUNCTIONS_ENABLE.booleanConf()
+            && e.format.isInstanceOf[Literal] =>
+        val args = e.format :: e.timestamp :: timeZoneLiteral(e) :: Nil
+        buildExtScalarFunction("DateTrunc", args, e.dataType)
+      case e: AddMonths if BlazeConf.DATETIME_FUNCTIONS_ENABLE.booleanConf() =>
+        buildExtScalarFunction("AddMonths", e.startDate :: e.numMonths :: Nil, e.dataType)
+      case e: MonthsBetween
+          if BlazeConf.DATETIME_FUNCTIONS_ENABLE.booleanConf()
+            && e.roundOff.isInstanceOf[Literal] =>
+        val args = e.date1 :: e.date2 :: e.roundOff :: timeZoneLiteral(e) :: Nil
+        buildExtScalarFunction("MonthsBetween", args, e.dataType)
+      case e: NextDay
+          if BlazeConf.DATETIME_FUNCTIONS_ENABLE.booleanConf()
+            && !SQLConf.get.ansiEnabled =>
+        buildExtScalarFunction("NextDay", e.startDate :: e.dayOfWeek :: Nil, e.dataType)
+      case e: MakeDate
+          if BlazeConf.DATETIME_FUNCTIONS_ENABLE.booleanConf()
+            && !SQLConf.get.ansiEnabled =>
+        buildExtScalarFunction("MakeDate", e.year :: e.month :: e.day :: Nil, e.dataType)
+
       // startswith is converted to scalar function in pruning-expr mode
       case StartsWith(expr, Literal(prefix, StringType)) if isPruningExpr =>
         buildExprNode(
@@ -981,7 +921,28 @@ object NativeConverters extends Logging {
             .apply(precision, IntegerType) :: Literal.apply(scale, IntegerType) :: Nil
         buildExtScalarFunction("CheckOverflow", args, DecimalType(precision, scale))
 
-      case e: CreateArray => buildExtScalarFunction("MakeArray", e.children, e.dataType)
+      case e: CreateArray if e.children.nonEmpty =>
+        buildExtScalarFunction("MakeArray", e.children, e.dataType)
+
+      case e: CreateMap =>
+        val dedupPolicy = SQLConf.get.getConfString("spark.sql.mapKeyDedupPolicy", "EXCEPTION")
+        buildExprNode {
+          _.setCreateMap(
+            pb.PhysicalCreateMapExprNode
+              .newBuilder()
+              .addAllArgs(e.children
+                .map(arg => convertExprWithFallback(arg, isPruningExpr, fallback))
+                .asJava)
+              .setReturnType(convertDataType(e.dataType))
+              .setDedupLastWin(dedupPolicy.equalsIgnoreCase("LAST_WIN")))
+        }
+
+      case e: MapKeys => buildExtScalarFunction("MapKeys", e.children, e.dataType)
+      case e: MapValues => buildExtScalarFunction("MapValues", e.children, e.dataType)
+
+      // out-of-bound indices and missing keys are errors in ANSI mode
+      case e: ElementAt if !SQLConf.get.ansiEnabled =>
+        buildExtScalarFunction("ElementAt", e.left :: e.right :: Nil, e.dataType)
 
       case e: CreateNamedStruct =>
         buildExprNode {
@@ -1021,6 +982,9 @@ object NativeConverters extends Logging {
               .setKey(convertValue(value, dataType)))
         }
 
+      case e: GetMapValue if !SQLConf.get.ansiEnabled =>
+        buildExtScalarFunction("ElementAt", e.child :: e.key :: Nil, e.dataType)
+
       case e: GetStructField =>
         buildExprNode {
           _.setGetIndexedFieldExpr(
@@ -1035,6 +999,48 @@ object NativeConverters extends Logging {
           _.setRowNumExpr(pb.RowNumExprNode.newBuilder())
         }
 
+      // context-dependent expressions, evaluated with task info of the native runtime
+      case _: SparkPartitionID =>
+        buildExprNode {
+          _.setSparkPartitionIdExpr(pb.SparkPartitionIdExprNode.newBuilder())
+        }
+      case _: MonotonicallyIncreasingID =>
+        buildExprNode {
+          _.setMonotonicallyIncreasingIdExpr(pb.MonotonicallyIncreasingIdExprNode.newBuilder())
+        }
+      case _: InputFileName =>
+        buildExprNode {
+          _.setInputFileNameExpr(pb.InputFileNameExprNode.newBuilder())
+        }
+
+      // raise_error() and assert_true(), the message is the only child before spark3.4
+      case e: RaiseError if e.children.length == 1 && e.children.head.dataType == StringType =>
+        buildExprNode {
+          _.setRaiseErrorExpr(
+            pb.RaiseErrorExprNode
+              .newBuilder()
+              .setMessage(convertExprWithFallback(e.children.head, isPruningExpr, fallback))
+              .setReturnType(convertDataType(e.dataType)))
+        }
+
+      case e: RLike
+          if BlazeConf.REGEXP_FUNCTIONS_ENABLE.booleanConf()
+            && isNativeRegexpSupported(e.right) =>
+        buildExtScalarFunction("RLike", e.children, e.dataType)
+
+      case e: RegExpExtract
+          if BlazeConf.REGEXP_FUNCTIONS_ENABLE.booleanConf()
+            && isNativeRegexpSupported(e.regexp)
+            && e.idx.isInstanceOf[Literal] =>
+        buildExtScalarFunction("RegExpExtract", e.children, e.dataType)
+
+      case e: RegExpReplace
+          if BlazeConf.REGEXP_FUNCTIONS_ENABLE.booleanConf()
+            && isNativeRegexpSupported(e.regexp)
+            && e.children.drop(2).forall(_.isInstanceOf[Literal]) =>
+        // children are (subject, regexp, rep) or (subject, regexp, rep, pos) since spark 3.1
+        buildExtScalarFunction("RegExpReplace", e.children, e.dataType)
+
       // hive UDFJson
       // hive UDFJson
       case e
@@ -1051,6 +1057,29 @@ object NativeConverters extends Logging {
           nullable = false)
         buildExtScalarFunction("GetParsedJsonObject", parsed :: e.children(1) :: Nil, StringType)
 
+      // from_json() with struct schema and default options
+      case e: JsonToStructs
+          if fromJsonEnabled
+            && e.options.isEmpty
+            && e.child.dataType == StringType
+            && e.schema.isInstanceOf[StructType]
+            && isFromJsonSupportedType(e.schema) =>
+        buildExprNode {
+          _.setFromJsonExpr(
+            pb.FromJsonExprNode
+              .newBuilder()
+              .setExpr(convertExprWithFallback(e.child, isPruningExpr, fallback))
+              .setReturnType(convertDataType(e.dataType)))
+        }
+
+      // higher-order functions with lambda functions evaluated in native
+      case e: HigherOrderFunction if BlazeConf.HIGHER_ORDER_FUNCTIONS_ENABLE.booleanConf() =>
+        try {
+          buildHigherOrderFunctionNode(e, isPruningExpr, fallback)
+        } catch {
+          case _: NotImplementedError => fallback(e)
+        }
+
       // hive UDF brickhouse.array_union
       case e
           if getFunctionClassName(e).contains("brickhouse.udf.collect.ArrayUnionUDF")
@@ -1068,6 +1097,73 @@ object NativeConverters extends Logging {
     }
   }
 
+  def buildHigherOrderFunctionNode(
+      e: HigherOrderFunction,
+      isPruningExpr: Boolean,
+      fallback: Expression => pb.PhysicalExprNode): pb.PhysicalExprNode = {
+    val (func, args, lambda, finish) = e match {
+      case ArrayTransform(arg, f: LambdaFunction) =>
+        (pb.HigherOrderFunction.HOF_TRANSFORM, arg :: Nil, f, None)
+      case ArrayFilter(arg, f: LambdaFunction) =>
+        (pb.HigherOrderFunction.HOF_FILTER, arg :: Nil, f, None)
+      case ArrayExists(arg, f: LambdaFunction, true) =>
+        (pb.HigherOrderFunction.HOF_EXISTS, arg :: Nil, f, None)
+      case ArrayForAll(arg, f: LambdaFunction) =>
+        (pb.HigherOrderFunction.HOF_FOR_ALL, arg :: Nil, f, None)
+      case ArrayAggregate(arg, zero, merge: LambdaFunction, finish: LambdaFunction) =>
+        (pb.HigherOrderFunction.HOF_AGGREGATE, arg :: zero :: Nil, merge, Some(finish))
+      case ZipWith(left, right, f: LambdaFunction) =>
+        (pb.HigherOrderFunction.HOF_ZIP_WITH, left :: right :: Nil, f, None)
+      case _ =>
+        throw new NotImplementedError(s"unsupported higher-order function: $e")
+    }
+    val builder = pb.PhysicalHigherOrderFunctionExprNode
+      .newBuilder()
+      .setFunc(func)
+      .addAllArgs(args.map(convertExprWithFallback(_, isPruningExpr, fallback)).asJava)
+      .setLambda(convertLambdaFunction(lambda))
+      .setReturnType(convertDataType(e.dataType))
+    finish.foreach(f => builder.setFinish(convertLambdaFunction(f)))
+    buildExprNode(_.setHigherOrderFunctionExpr(builder))
+  }
+
+  // lambda variables are appended to the input batch while evaluating lambda bodies in
+  // native, names are suffixed with expr ids to avoid conflicts with nested lambdas.
+  // bodies must be fully convertible because they are evaluated on flattened elements.
+  def convertLambdaFunction(f: LambdaFunction): pb.PhysicalLambdaFunctionNode = {
+    val variables = f.arguments.map {
+      case v: NamedLambdaVariable => v
+      case v => throw new NotImplementedError(s"unsupported lambda variable: $v")
+    }
+    val variableFields = variables.map { v =>
+      StructField(s"${v.name}#${v.exprId.id}", v.dataType, v.nullable)
+    }
+    val variableNodes = variables.zip(variableFields).map { case (v, field) =>
+      val node = buildExprNode {
+        _.setLambdaVariableExpr(
+          pb.PhysicalLambdaVariableExprNode
+            .newBuilder()
+            .setName(field.name)
+            .setArrowType(convertDataType(field.dataType))
+            .setNullable(field.nullable))
+      }
+      v.exprId -> Shims.get.createNativeExprWrapper(node, v.dataType, v.nullable)
+    }.toMap
+
+    val body = f.function.transformDown {
+      case v: NamedLambdaVariable if variableNodes.contains(v.exprId) => variableNodes(v.exprId)
+    }
+    val convertedBody = convertExprWithFallback(
+      body,
+      isPruningExpr = false,
+      e => throw new NotImplementedError(s"unsupported expression in lambda: $e"))
+    pb.PhysicalLambdaFunctionNode
+      .newBuilder()
+      .setBody(convertedBody)
+      .addAllVariables(variableFields.map(convertField).asJava)
+      .build()
+  }
+
   def convertAggregateExpr(e: AggregateExpression): pb.PhysicalExprNode = {
     assert(Shims.get.getAggregateExpressionFilter(e).isEmpty)
     val aggBuilder = pb.PhysicalAggExprNode.newBuilder()
@@ -1147,7 +1243,28 @@ object NativeConverters extends Logging {
           case Some(converted) => return converted
           case _ =>
         }
-        throw new NotImplementedError(s"unsupported aggregate expression: (${e.getClass}) $e")
+        e.aggregateFunction match {
+          // hive UDAFs and other typed imperative aggregates are evaluated in the jvm
+          case udaf: TypedImperativeAggregate[_] if BlazeConf.UDAF_WRAPPER_ENABLE.booleanConf() =>
+            val paramsSchema = StructType(udaf.children.zipWithIndex.map { case (child, i) =>
+              StructField(s"_$i", child.dataType, child.nullable)
+            })
+            val bound = udaf.withNewChildren(udaf.children.zipWithIndex.map { case (child, i) =>
+              BoundReference(i, child.dataType, child.nullable)
+            })
+            val serialized =
+              serializeExpression(bound.asInstanceOf[Expression with Serializable], paramsSchema)
+            aggBuilder.setAggFunction(pb.AggFunction.UDAF)
+            aggBuilder.setUdaf(
+              pb.AggUdaf
+                .newBuilder()
+                .setSerialized(ByteString.copyFrom(serialized))
+                .setReturnType(convertDataType(udaf.dataType))
+                .setReturnNullable(udaf.nullable))
+            aggBuilder.addAllChildren(udaf.children.map(convertExpr).asJava)
+          case _ =>
+            throw new NotImplementedError(s"unsupported aggregate expression: (${e.getClass}) $e")
+        }
     }
     pb.PhysicalExprNode
       .newBuilder()
@@ -1217,6 +1334,170 @@ object NativeConverters extends Logging {
           .setReturnType(convertDataType(dataType)))
     }
 
+  // timestamps are parsed, formatted and converted from/to dates natively in the given
+  // time zone, which is normally the session time zone
+  def buildCastExprNode(
+      child: Expression,
+      dataType: DataType,
+      mode: pb.CastMode,
+      timeZoneId: String,
+      isPruningExpr: Boolean,
+      fallback: Expression => pb.PhysicalExprNode): pb.PhysicalExprNode = {
+    buildExprNode {
+      _.setTryCast(
+        pb.PhysicalTryCastNode
+          .newBuilder()
+          .setExpr(convertExprWithFallback(child, isPruningExpr, fallback))
+          .setArrowType(convertDataType(dataType))
+          .setMode(mode)
+          .setTimeZone(timeZoneId)
+          .build())
+    }
+  }
+
+  // constructs not supported by the native regex engine: lookarounds, atomic groups,
+  // backreferences, java specific escapes, character properties and flags, possessive
+  // quantifiers and class differences
+  private val nativeUnsupportedRegexpSyntax = Seq(
+    """\(\?<?[=!>]""",
+    """\\(?![dDwWsSeQEpPbBAztnrfaxu])[a-zA-Z0-9]""",
+    """\\[pP]\{(java|In)""",
+    """[*+?}]\+""",
+    """\(\?[imsxu-]*[a-zA-Z&&[^imsxu]]""",
+    """\[[^\]]*--""").map(_.r)
+
+  def isNativeRegexpSupported(pattern: Expression): Boolean = {
+    pattern match {
+      case Literal(null, StringType) => true
+      case Literal(pattern, StringType) =>
+        !nativeUnsupportedRegexpSyntax.exists(_.findFirstIn(pattern.toString).isDefined)
+      case _ => false
+    }
+  }
+
+  // max widths of pattern letters supported by native datetime formatting and parsing
+  private val nativeDateTimePatternLetters = Map(
+    'y' -> 9,
+    'S' -> 9,
+    'Z' -> 5,
+    'M' -> 4,
+    'L' -> 4,
+    'E' -> 4,
+    'D' -> 3,
+    'X' -> 3,
+    'x' -> 3,
+    'd' -> 2,
+    'Q' -> 2,
+    'q' -> 2,
+    'H' -> 2,
+    'h' -> 2,
+    'K' -> 2,
+    'k' -> 2,
+    'm' -> 2,
+    's' -> 2,
+    'a' -> 1)
+
+  // patterns are interpreted like spark3's DateTimeFormatter, the legacy SimpleDateFormat
+  // (spark.sql.legacy.timeParserPolicy=LEGACY) is not supported
+  def isNativeDateTimePatternSupported(format: Expression): Boolean = {
+    val timeParserPolicy = SQLConf.get.getConfString("spark.sql.legacy.timeParserPolicy", "")
+    format match {
+      case _ if timeParserPolicy.equalsIgnoreCase("LEGACY") => false
+      case Literal(null, StringType) => true
+      case Literal(pattern, StringType) =>
+        // optional sections and unclosed quotes are not supported
+        val unquoted = pattern.toString.replaceAll("'[^']*'", "")
+        !unquoted.exists("[]{}#'".contains(_)) &&
+        """([a-zA-Z])\1*""".r.findAllIn(unquoted).forall { letters =>
+          nativeDateTimePatternLetters.get(letters.head).exists(letters.length <= _)
+        }
+      case _ => false
+    }
+  }
+
+  // time zone of timestamp conversions passed to native, spark resolves it to the session
+  // time zone, which may differ from the local time zone of executors
+  def timeZoneId(e: TimeZoneAwareExpression): String = {
+    e.timeZoneId.getOrElse(SQLConf.get.sessionLocalTimeZone)
+  }
+
+  def timeZoneLiteral(e: TimeZoneAwareExpression): Literal = {
+    Literal(timeZoneId(e))
+  }
+
+  def isFromJsonSupportedType(dataType: DataType): Boolean = {
+    dataType match {
+      case BooleanType | ByteType | ShortType | IntegerType | LongType => true
+      case FloatType | DoubleType | StringType | _: DecimalType => true
+      case t: ArrayType => isFromJsonSupportedType(t.elementType)
+      case t: MapType => t.keyType == StringType && isFromJsonSupportedType(t.valueType)
+      case t: StructType => t.fields.forall(field => isFromJsonSupportedType(field.dataType))
+      case _ => false
+    }
+  }
+
+  // overflow checks are only needed by integral types, while division by zero is checked
+  // for all non-decimal types
+  def isAnsiCheckedArithmetic(e: BinaryArithmetic): Boolean = {
+    e match {
+      case _: Add | _: Subtract | _: Multiply => e.dataType.isInstanceOf[IntegralType]
+      case _: Divide | _: Remainder =>
+        e.dataType.isInstanceOf[IntegralType] || e.dataType == FloatType ||
+          e.dataType == DoubleType
+      case _ => false
+    }
+  }
+
+  def isDecimalArithmetic(e: BinaryArithmetic): Boolean = {
+    val isSupportedOp = e match {
+      case _: Add | _: Subtract | _: Multiply | _: Divide | _: Remainder => true
+      case _ => false
+    }
+    isSupportedOp && Seq(e.left, e.right).forall(_.dataType.isInstanceOf[DecimalType])
+  }
+
+  // result types of decimal arithmetic with precision promotion, copied from spark3.5.
+  // in older versions the operands are already promoted and the operation is wrapped in
+  // CheckOverflow, where the result is casted to the final type
+  def decimalArithmeticResultType(e: BinaryArithmetic): DecimalType = {
+    val (p1, s1, p2, s2) = (e.left.dataType, e.right.dataType) match {
+      case (lhsType: DecimalType, rhsType: DecimalType) =>
+        (lhsType.precision, lhsType.scale, rhsType.precision, rhsType.scale)
+    }
+    val allowPrecisionLoss = SQLConf.get.decimalOperationsAllowPrecisionLoss
+    def adjusted(precision: Int, scale: Int): DecimalType = {
+      if (allowPrecisionLoss) {
+        DecimalType.adjustPrecisionScale(precision, scale)
+      } else {
+        DecimalType.bounded(precision, scale)
+      }
+    }
+
+    e match {
+      case _: Add | _: Subtract =>
+        val resultScale = max(s1, s2)
+        adjusted(max(p1 - s1, p2 - s2) + resultScale + 1, resultScale)
+      case _: Multiply =>
+        adjusted(p1 + p2 + 1, s1 + s2)
+      case _: Divide if allowPrecisionLoss =>
+        val intDig = p1 - s1 + s2
+        val scale = max(DecimalType.MINIMUM_ADJUSTED_SCALE, s1 + p2 + 1)
+        DecimalType.adjustPrecisionScale(intDig + scale, scale)
+      case _: Divide =>
+        var intDig = min(DecimalType.MAX_SCALE, p1 - s1 + s2)
+        var decDig = min(DecimalType.MAX_SCALE, max(6, s1 + p2 + 1))
+        val diff = (intDig + decDig) - DecimalType.MAX_SCALE
+        if (diff > 0) {
+          decDig -= diff / 2 + 1
+          intDig = DecimalType.MAX_SCALE - decDig
+        }
+        DecimalType.bounded(intDig + decDig, decDig)
+      case _: Remainder =>
+        val resultScale = max(s1, s2)
+        adjusted(min(p1 - s1, p2 - s2) + resultScale, resultScale)
+    }
+  }
+
   def castIfNecessary(expr: Expression, dataType: DataType): Expression = {
     if (expr.dataType == dataType) {
       return expr
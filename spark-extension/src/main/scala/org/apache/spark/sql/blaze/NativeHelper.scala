@@ -28,6 +28,7 @@ import org.blaze.protobuf.PhysicalPlanNode
 import org.apache.spark.internal.Logging
 import org.apache.spark.internal.config
 import org.apache.spark.sql.catalyst.InternalRow
+import org.apache.spark.sql.catalyst.expressions.Expression
 import org.apache.spark.sql.execution.SparkPlan
 import org.apache.spark.sql.execution.metric.SQLMetric
 import org.apache.spark.sql.execution.metric.SQLMetrics
@@ -89,36 +90,52 @@ object NativeHelper extends Logging {
     BlazeCallNativeWrapper(nativePlan, partition, context, metrics).getRowIterator
   }
 
-  def getDefaultNativeMetrics(sc: SparkContext): Map[String, SQLMetric] = {
-    def metric(name: String) = SQLMetrics.createMetric(sc, name)
-    def nanoTimingMetric(name: String) = SQLMetrics.createNanoTimingMetric(sc, name)
-    def sizeMetric(name: String) = SQLMetrics.createSizeMetric(sc, name)
+  // (name, kind, displayName) of all metrics published by native exec nodes
+  private lazy val nativeMetricDefinitions: Seq[(String, String, String)] = {
+    BlazeCallNativeWrapper.initNative()
+    JniBridge.getNativeMetricDefinitions().toSeq.map { definition =>
+      val Array(name, kind, displayName) = definition.split(":", 3)
+      (name, kind, displayName)
+    }
+  }
 
-    var metrics = TreeMap(
-      "stage_id" -> metric("stageId"),
-      "output_rows" -> metric("Native.output_rows"),
-      "output_batches" -> metric("Native.output_batches"),
-      "elapsed_compute" -> nanoTimingMetric("Native.elapsed_compute"),
-      "build_hash_map_time" -> nanoTimingMetric("Native.build_hash_map_time"),
-      "probed_side_hash_time" -> nanoTimingMetric("Native.probed_side_hash_time"),
-      "probed_side_search_time" -> nanoTimingMetric("Native.probed_side_search_time"),
-      "probed_side_compare_time" -> nanoTimingMetric("Native.probed_side_compare_time"),
-      "build_output_time" -> nanoTimingMetric("Native.build_output_time"),
-      "mem_spill_count" -> metric("Native.mem_spill_count"),
-      "mem_spill_size" -> sizeMetric("Native.mem_spill_size"),
-      "mem_spill_iotime" -> nanoTimingMetric("Native.mem_spill_iotime"),
-      "disk_spill_size" -> sizeMetric("Native.disk_spill_size"),
-      "disk_spill_iotime" -> nanoTimingMetric("Native.disk_spill_iotime"),
-      "sort_time" -> nanoTimingMetric("Native.sort_time"),
-      "output_io_time" -> nanoTimingMetric("Native.output_io_time"),
-      "shuffle_read_total_time" -> nanoTimingMetric("Native.shuffle_read_total_time"))
+  private val inputBatchStatisticsMetrics =
+    Set("input_batch_count", "input_row_count", "input_batch_mem_size")
 
-    if (BlazeConf.INPUT_BATCH_STATISTICS_ENABLE.booleanConf()) {
-      metrics ++= TreeMap(
-        "input_batch_count" -> metric("Native.input_batches"),
-        "input_row_count" -> metric("Native.input_rows"),
-        "input_batch_mem_size" -> sizeMetric("Native.input_mem_bytes"))
+  def getDefaultNativeMetrics(sc: SparkContext): Map[String, SQLMetric] = {
+    val inputBatchStatisticsEnabled = BlazeConf.INPUT_BATCH_STATISTICS_ENABLE.booleanConf()
+    val nativeMetrics = nativeMetricDefinitions
+      .filter { case (name, _, _) =>
+        inputBatchStatisticsEnabled || !inputBatchStatisticsMetrics.contains(name)
+      }
+      .map {
+        case (name, "size", displayName) =>
+          name -> SQLMetrics.createSizeMetric(sc, displayName)
+        case (name, "nanoTiming", displayName) =>
+          name -> SQLMetrics.createNanoTimingMetric(sc, displayName)
+        case (name, _, displayName) =>
+          name -> SQLMetrics.createMetric(sc, displayName)
+      }
+    TreeMap("stage_id" -> SQLMetrics.createMetric(sc, "stageId")) ++ nativeMetrics
+  }
+
+  // sampled evaluation time of each expression, reported by native
+  // filter/projection as ${prefix}_expr_time_${i}
+  def getExprMetrics(
+      sc: SparkContext,
+      prefix: String,
+      exprs: Seq[Expression]): Map[String, SQLMetric] = {
+    if (!BlazeConf.EXPR_METRICS_ENABLE.booleanConf()) {
+      return Map.empty
     }
-    metrics
+    exprs.zipWithIndex.map { case (expr, i) =>
+      val desc = expr.sql match {
+        case sql if sql.length > 80 => sql.take(77) + "..."
+        case sql => sql
+      }
+      s"${prefix}_expr_time_$i" -> SQLMetrics.createNanoTimingMetric(
+        sc,
+        s"Native.${prefix}_expr_time[$i]: $desc")
+    }.toMap
   }
 }
@@ -0,0 +1,99 @@
+/*
+ * Copyright 2022 The Blaze Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package org.apache.spark.sql.blaze
+
+import scala.collection.JavaConverters._
+import scala.util.Failure
+import scala.util.Success
+import scala.util.Try
+
+import org.apache.arrow.c.ArrowArray
+import org.apache.arrow.c.Data
+import org.apache.arrow.vector.VectorSchemaRoot
+import org.apache.arrow.vector.dictionary.DictionaryProvider.MapDictionaryProvider
+import org.apache.spark.internal.Logging
+import org.apache.spark.sql.blaze.util.Using
+import org.apache.spark.sql.catalyst.InternalRow
+import org.apache.spark.sql.catalyst.expressions.Expression
+import org.apache.spark.sql.execution.blaze.arrowio.util.ArrowUtils
+import org.apache.spark.sql.execution.blaze.arrowio.util.ArrowWriter
+import org.apache.spark.sql.types.StructType
+import org.blaze.{protobuf => pb}
+
+/**
+ * Evaluates partition pruning predicates natively on the driver side. listing heavily
+ * partitioned tables (tens of thousands of partitions) spends much time evaluating the
+ * predicates row by row in JVM, here all partition values are exported to native via FFI
+ * and evaluated in vectorized way.
+ */
+object NativePartitionPruning extends Logging {
+
+  /**
+   * Returns indices of the partitions satisfying all the predicates, or None if native
+   * partition pruning is disabled, the number of partitions is too small, or any predicate
+   * cannot be converted. predicates reference partition columns by name.
+   */
+  def prunePartitions(
+      partitionSchema: StructType,
+      predicates: Seq[Expression],
+      partitionValues: Seq[InternalRow]): Option[Array[Int]] = {
+
+    val minPartitions = BlazeConf.NATIVE_PARTITION_PRUNING_MIN_PARTITIONS.intConf()
+    if (minPartitions <= 0 || partitionValues.length < minPartitions) {
+      return None
+    }
+
+    val fallbackToError = (e: Expression) =>
+      throw new NotImplementedError(s"unsupported partition pruning expression: $e")
+    val request = Try {
+      pb.PartitionPruningRequest
+        .newBuilder()
+        .setPartitionSchema(NativeConverters.convertSchema(partitionSchema))
+        .addAllPruningPredicates(predicates.map { predicate =>
+          NativeConverters.convertExprWithFallback(
+            predicate,
+            isPruningExpr = true,
+            fallbackToError)
+        }.asJava)
+        .build()
+    } match {
+      case Success(request) => request
+      case Failure(e) =>
+        logWarning(s"falling back to non-native partition pruning: ${e.getMessage}")
+        return None
+    }
+
+    BlazeCallNativeWrapper.initNative()
+    Using.resource(ArrowUtils.newChildAllocator(getClass.getName)) { allocator =>
+      Using.resources(
+        VectorSchemaRoot.create(ArrowUtils.toArrowSchema(partitionSchema), allocator),
+        ArrowArray.allocateNew(allocator)) { (root, ffiArray) =>
+        val writer = ArrowWriter.create(root)
+        partitionValues.foreach(writer.write)
+        writer.finish()
+
+        // exported partition values are moved into and released by native side
+        Data.exportVectorSchemaRoot(allocator, root, new MapDictionaryProvider(), ffiArray)
+        val survivedIndices =
+          JniBridge.prunePartitions(request.toByteArray, ffiArray.memoryAddress())
+        logInfo(
+          s"native partition pruning: ${survivedIndices.length} of " +
+            s"${partitionValues.length} partitions survived")
+        Some(survivedIndices)
+      }
+    }
+  }
+}
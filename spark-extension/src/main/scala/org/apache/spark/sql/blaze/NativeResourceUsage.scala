@@ -0,0 +1,54 @@
+/*
+ * Copyright 2022 The Blaze Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package org.apache.spark.sql.blaze
+
+/**
+ * Native resource usage summary of all finished tasks of a stage in the current executor.
+ *
+ * @param numTasks number of finished native tasks
+ * @param cpuTimeNs elapsed compute time of all native operators
+ * @param peakMemUsed max of peak managed memory used by each task
+ * @param memSpilledBytes bytes spilled to on-heap memory
+ * @param diskSpilledBytes bytes spilled to disk
+ * @param ioBytes bytes of files and shuffle data read/written
+ */
+case class NativeResourceUsage(
+    numTasks: Long,
+    cpuTimeNs: Long,
+    peakMemUsed: Long,
+    memSpilledBytes: Long,
+    diskSpilledBytes: Long,
+    ioBytes: Long)
+
+object NativeResourceUsage {
+
+  /** returns the summary of the stage, or None if no native task of the stage is finished */
+  def getStage(stageId: Int): Option[NativeResourceUsage] = {
+    Option(JniBridge.getStageResourceUsage(stageId)).map(fromNative)
+  }
+
+  private def fromNative(values: Array[Long]): NativeResourceUsage = {
+    val Array(numTasks, cpuTimeNs, peakMemUsed, memSpilledBytes, diskSpilledBytes, ioBytes) =
+      values
+    NativeResourceUsage(
+      numTasks,
+      cpuTimeNs,
+      peakMemUsed,
+      memSpilledBytes,
+      diskSpilledBytes,
+      ioBytes)
+  }
+}
@@ -0,0 +1,80 @@
+/*
+ * Copyright 2022 The Blaze Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package org.apache.spark.sql.blaze
+
+import java.util.concurrent.atomic.AtomicLong
+
+import org.apache.spark.internal.Logging
+import org.apache.spark.sql.catalyst.expressions.AttributeReference
+import org.apache.spark.sql.catalyst.expressions.Expression
+import org.apache.spark.sql.catalyst.trees.TreeNodeTag
+import org.apache.spark.sql.execution.SparkPlan
+import org.apache.spark.sql.execution.blaze.plan.NativeFilterBase
+import org.apache.spark.sql.execution.blaze.plan.NativeRenameColumnsBase
+
+/**
+ * Min/max runtime filters of join keys. a native join publishes the key range of its built (or
+ * finished) side at runtime, and the native filter on top of the other side skips batches whose
+ * key range cannot match, like dynamic partition pruning at batch granularity.
+ */
+object NativeRuntimeFilters extends Logging {
+
+  /** runtime filters (filter id, key) applied by a native filter */
+  val consumerTag: TreeNodeTag[Seq[(String, Expression)]] =
+    TreeNodeTag("blaze.runtimeFilters.consumer")
+
+  /** runtime filter ids of each key published by the left side of a native join */
+  val leftProducerTag: TreeNodeTag[Seq[String]] = TreeNodeTag("blaze.runtimeFilters.left")
+
+  /** runtime filter ids of each key published by the right side of a native join */
+  val rightProducerTag: TreeNodeTag[Seq[String]] = TreeNodeTag("blaze.runtimeFilters.right")
+
+  private val nextFilterId = new AtomicLong(0)
+
+  /**
+   * Assigns runtime filters published by the producer keys, and consumed by the native filter on
+   * top of the consumer plan. returns filter ids of each key, empty for keys without filter. the
+   * caller must ensure that consumer rows not matching the producer side are not needed.
+   */
+  def assign(
+      producerKeys: Seq[Expression],
+      consumerKeys: Seq[Expression],
+      consumer: SparkPlan): Seq[String] = {
+
+    val consumerFilter = findConsumerFilter(consumer)
+    if (!BlazeConf.RUNTIME_FILTER_ENABLE.booleanConf() || consumerFilter.isEmpty) {
+      return producerKeys.map(_ => "")
+    }
+    val filter = consumerFilter.get
+    producerKeys.zip(consumerKeys).map {
+      case (_, key: AttributeReference) if filter.output.exists(_.exprId == key.exprId) =>
+        val filterId = s"rf_${nextFilterId.getAndIncrement()}"
+        val runtimeFilters = filter.getTagValue(consumerTag).getOrElse(Nil)
+        filter.setTagValue(consumerTag, runtimeFilters :+ (filterId, key))
+        logInfo(s"assigned runtime filter $filterId on key $key")
+        filterId
+      case _ => ""
+    }
+  }
+
+  // filters below other operators (like sort) may have consumed all input before the runtime
+  // filter is published, so only filters directly under the join are used
+  private def findConsumerFilter(plan: SparkPlan): Option[NativeFilterBase] = plan match {
+    case filter: NativeFilterBase => Some(filter)
+    case rename: NativeRenameColumnsBase => findConsumerFilter(rename.child)
+    case _ => None
+  }
+}
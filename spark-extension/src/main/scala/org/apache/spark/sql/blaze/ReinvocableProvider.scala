@@ -0,0 +1,25 @@
+/*
+ * Copyright 2022 The Blaze Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package org.apache.spark.sql.blaze
+
+/**
+ * A jni resource provider which creates a fresh result on every invocation and has no side
+ * effects when failing, so native callers may invoke it again after transient errors. other
+ * providers are invoked only once.
+ */
+class ReinvocableProvider[T](provide: () => T) extends (() => T) {
+  override def apply(): T = provide()
+}
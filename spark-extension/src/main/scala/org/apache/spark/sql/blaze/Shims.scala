@@ -25,6 +25,7 @@ import org.apache.spark.scheduler.MapStatus
 import org.apache.spark.shuffle.IndexShuffleBlockResolver
 import org.apache.spark.shuffle.ShuffleHandle
 import org.apache.spark.shuffle.ShuffleWriteMetricsReporter
+import org.apache.spark.sql.catalyst.expressions.Cast
 import org.apache.spark.sql.catalyst.expressions.Expression
 import org.apache.spark.sql.catalyst.expressions.aggregate.AggregateExpression
 import org.apache.spark.sql.catalyst.plans.physical.BroadcastMode
@@ -84,6 +85,14 @@ abstract class Shims {
       joinType: JoinType,
       broadcastSide: BroadcastSide): NativeBroadcastJoinBase
 
+  def createNativeBroadcastNestedLoopJoinExec(
+      left: SparkPlan,
+      right: SparkPlan,
+      outputPartitioning: Partitioning,
+      joinType: JoinType,
+      broadcastSide: BroadcastSide,
+      condition: Option[Expression]): NativeBroadcastNestedLoopJoinBase
+
   def createNativeSortMergeJoinExec(
       left: SparkPlan,
       right: SparkPlan,
@@ -113,7 +122,10 @@ abstract class Shims {
       generatorOutput: Seq[Attribute],
       child: SparkPlan): NativeGenerateBase
 
-  def createNativeGlobalLimitExec(limit: Long, child: SparkPlan): NativeGlobalLimitBase
+  def createNativeGlobalLimitExec(
+      limit: Long,
+      offset: Long,
+      child: SparkPlan): NativeGlobalLimitBase
 
   def createNativeLocalLimitExec(limit: Long, child: SparkPlan): NativeLocalLimitBase
 
@@ -125,11 +137,27 @@ abstract class Shims {
 
   def createNativeOrcScanExec(basedFileScan: FileSourceScanExec): NativeOrcScanBase
 
+  def createNativeCsvScanExec(basedFileScan: FileSourceScanExec): NativeCsvScanBase
+
+  def createNativeJsonScanExec(basedFileScan: FileSourceScanExec): NativeJsonScanBase
+
+  def createNativeScanAggregatePushdownExec(
+      basedScan: NativeFileSourceScanBase,
+      output: Seq[Attribute],
+      pushedAggregates: Seq[pb.PushedAggregate]): NativeScanAggregatePushdownBase
+
   def createNativeProjectExec(
       projectList: Seq[NamedExpression],
       child: SparkPlan,
       addTypeCast: Boolean = false): NativeProjectBase
 
+  def createNativeRangeExec(
+      start: Long,
+      end: Long,
+      step: Long,
+      numSlices: Int,
+      output: Seq[Attribute]): NativeRangeBase
+
   def createNativeRenameColumnsExec(
       child: SparkPlan,
       newColumnNames: Seq[String]): NativeRenameColumnsBase
@@ -144,9 +172,16 @@ abstract class Shims {
       global: Boolean,
       child: SparkPlan): NativeSortBase
 
+  def createNativeTopKFrequentExec(
+      expr: Expression,
+      k: Int,
+      child: SparkPlan): NativeTopKFrequentBase
+
   def createNativeTakeOrderedExec(
       limit: Long,
+      offset: Long,
       sortOrder: Seq[SortOrder],
+      projectList: Seq[NamedExpression],
       child: SparkPlan): NativeTakeOrderedBase
 
   def createNativePartialTakeOrderedExec(
@@ -163,6 +198,14 @@ abstract class Shims {
       orderSpec: Seq[SortOrder],
       child: SparkPlan): NativeWindowBase
 
+  def createNativeWindowGroupLimitExec(
+      partitionSpec: Seq[Expression],
+      orderSpec: Seq[SortOrder],
+      rankLikeFunction: Expression,
+      limit: Int,
+      partial: Boolean,
+      child: SparkPlan): NativeWindowGroupLimitBase
+
   def createNativeParquetSinkExec(
       sparkSession: SparkSession,
       table: CatalogTable,
@@ -203,6 +246,11 @@ abstract class Shims {
 
   def getLikeEscapeChar(expr: Expression): Char
 
+  def getCastMode(cast: Cast): pb.CastMode
+
+  // whether arithmetic expressions fail on overflow and division by zero
+  def isAnsiArithmetic(e: Expression): Boolean
+
   def getAggregateExpressionFilter(expr: Expression): Option[Expression]
 
   def createFileSegment(file: File, offset: Long, length: Long, numRecords: Long): FileSegment
@@ -0,0 +1,159 @@
+/*
+ * Copyright 2022 The Blaze Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package org.apache.spark.sql.blaze
+
+import java.nio.ByteBuffer
+
+import scala.collection.mutable.ArrayBuffer
+
+import org.apache.arrow.c.ArrowArray
+import org.apache.arrow.c.Data
+import org.apache.arrow.memory.BufferAllocator
+import org.apache.arrow.vector.VectorSchemaRoot
+import org.apache.arrow.vector.dictionary.DictionaryProvider
+import org.apache.arrow.vector.dictionary.DictionaryProvider.MapDictionaryProvider
+import org.apache.arrow.vector.types.pojo.Schema
+import org.apache.spark.TaskContext
+import org.apache.spark.internal.Logging
+import org.apache.spark.sql.blaze.util.Using
+import org.apache.spark.sql.catalyst.InternalRow
+import org.apache.spark.sql.catalyst.expressions.BoundReference
+import org.apache.spark.sql.catalyst.expressions.Nondeterministic
+import org.apache.spark.sql.catalyst.expressions.UnsafeProjection
+import org.apache.spark.sql.catalyst.expressions.aggregate.TypedImperativeAggregate
+import org.apache.spark.sql.execution.blaze.arrowio.ColumnarHelper
+import org.apache.spark.sql.execution.blaze.arrowio.util.ArrowUtils
+import org.apache.spark.sql.execution.blaze.arrowio.util.ArrowWriter
+import org.apache.spark.sql.types.BinaryType
+import org.apache.spark.sql.types.IntegerType
+import org.apache.spark.sql.types.StructField
+import org.apache.spark.sql.types.StructType
+
+/**
+ * Evaluates a TypedImperativeAggregate (including hive UDAFs) for native aggregations.
+ * Aggregation buffers are exchanged with the native side in their serialized form, null
+ * buffers stand for newly created ones.
+ */
+case class SparkUDAFWrapperContext(serialized: ByteBuffer) extends Logging {
+  private val (expr, javaParamsSchema) =
+    NativeConverters.deserializeExpression[TypedImperativeAggregate[Any]]({
+      val bytes = new Array[Byte](serialized.remaining())
+      serialized.get(bytes)
+      bytes
+    })
+
+  // initialize all nondeterministic children exprs
+  expr.foreach {
+    case nondeterministic: Nondeterministic =>
+      nondeterministic.initialize(TaskContext.get.partitionId())
+    case _ =>
+  }
+
+  private val dictionaryProvider: DictionaryProvider = new MapDictionaryProvider()
+  private val accsSchema =
+    ArrowUtils.toArrowSchema(StructType(Seq(StructField("acc", BinaryType, nullable = true))))
+  private val outputSchema =
+    ArrowUtils.toArrowSchema(StructType(Seq(StructField("", expr.dataType, expr.nullable))))
+  private val updateRowsSchema = ArrowUtils.toArrowSchema(
+    StructType(StructField("accIdx", IntegerType, nullable = true) +: javaParamsSchema.fields))
+  private val mergeRowsSchema = ArrowUtils.toArrowSchema(
+    StructType(
+      Seq(
+        StructField("accIdx", IntegerType, nullable = true),
+        StructField("merging", BinaryType, nullable = true))))
+
+  // params are placed after the acc index column
+  private val paramsToUnsafe = {
+    val toUnsafe = UnsafeProjection.create(javaParamsSchema.fields.zipWithIndex.map {
+      case (field, i) => BoundReference(i + 1, field.dataType, field.nullable)
+    })
+    toUnsafe.initialize(Option(TaskContext.get()).map(_.partitionId()).getOrElse(0))
+    toUnsafe
+  }
+
+  def update(importAccsPtr: Long, importRowsPtr: Long, exportFFIArrayPtr: Long): Unit = {
+    withAccs(importAccsPtr, exportFFIArrayPtr) { (buffers, batchAllocator) =>
+      importRows(importRowsPtr, updateRowsSchema, batchAllocator) { row =>
+        val accIdx = row.getInt(0)
+        buffers(accIdx) = expr.update(buffers(accIdx), paramsToUnsafe(row))
+      }
+      buffers.map(buffer => InternalRow(expr.serialize(buffer)))
+    }
+  }
+
+  def merge(importAccsPtr: Long, importRowsPtr: Long, exportFFIArrayPtr: Long): Unit = {
+    withAccs(importAccsPtr, exportFFIArrayPtr) { (buffers, batchAllocator) =>
+      importRows(importRowsPtr, mergeRowsSchema, batchAllocator) { row =>
+        val accIdx = row.getInt(0)
+        buffers(accIdx) = expr.merge(buffers(accIdx), expr.deserialize(row.getBinary(1)))
+      }
+      buffers.map(buffer => InternalRow(expr.serialize(buffer)))
+    }
+  }
+
+  def eval(importAccsPtr: Long, exportFFIArrayPtr: Long): Unit = {
+    withAccs(importAccsPtr, exportFFIArrayPtr, outputSchema) { (buffers, _) =>
+      buffers.map(buffer => InternalRow(expr.eval(buffer)))
+    }
+  }
+
+  private def withAccs(
+      importAccsPtr: Long,
+      exportFFIArrayPtr: Long,
+      exportSchema: Schema = accsSchema)(
+      f: (ArrayBuffer[Any], BufferAllocator) => Seq[InternalRow]): Unit = {
+    Using.resource(ArrowUtils.newChildAllocator(getClass.getName)) { batchAllocator =>
+      Using.resources(
+        VectorSchemaRoot.create(exportSchema, batchAllocator),
+        VectorSchemaRoot.create(accsSchema, batchAllocator),
+        ArrowArray.wrap(importAccsPtr),
+        ArrowArray.wrap(exportFFIArrayPtr)) { (outputRoot, accsRoot, importArray, exportArray) =>
+        // import accumulators, null accumulators are newly created
+        Data.importIntoVectorSchemaRoot(batchAllocator, importArray, accsRoot, dictionaryProvider)
+        val buffers = ArrayBuffer[Any]()
+        for (accRow <- ColumnarHelper.batchAsRowIter(ColumnarHelper.rootAsBatch(accsRoot))) {
+          buffers += (if (accRow.isNullAt(0)) {
+            expr.createAggregationBuffer()
+          } else {
+            expr.deserialize(accRow.getBinary(0))
+          })
+        }
+
+        // write results to output root
+        val outputWriter = ArrowWriter.create(outputRoot)
+        f(buffers, batchAllocator).foreach(outputWriter.write)
+        outputWriter.finish()
+
+        // export to output using root allocator
+        Data.exportVectorSchemaRoot(
+          ArrowUtils.rootAllocator,
+          outputRoot,
+          dictionaryProvider,
+          exportArray)
+      }
+    }
+  }
+
+  private def importRows(importRowsPtr: Long, rowsSchema: Schema, allocator: BufferAllocator)(
+      f: InternalRow => Unit): Unit = {
+    Using.resources(
+      VectorSchemaRoot.create(rowsSchema, allocator),
+      ArrowArray.wrap(importRowsPtr)) { (rowsRoot, importArray) =>
+      Data.importIntoVectorSchemaRoot(allocator, importArray, rowsRoot, dictionaryProvider)
+      ColumnarHelper.batchAsRowIter(ColumnarHelper.rootAsBatch(rowsRoot)).foreach(f)
+    }
+  }
+}
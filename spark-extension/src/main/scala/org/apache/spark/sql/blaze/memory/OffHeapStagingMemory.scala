@@ -0,0 +1,60 @@
+/*
+ * Copyright 2022 The Blaze Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package org.apache.spark.sql.blaze.memory
+
+import scala.collection.mutable
+
+import org.apache.spark.TaskContext
+import org.apache.spark.internal.Logging
+import org.apache.spark.memory.MemoryConsumer
+import org.apache.spark.memory.MemoryMode
+
+/**
+ * books the size of native buffered shuffle data in spark's off-heap execution pool, so that
+ * the data shares spark.memory.offHeap.size with other off-heap consumers instead of being
+ * counted twice. this is accounting only, no off-heap memory is allocated here. booked memory
+ * is released by its owner after writing, it is never spilled by spark.
+ */
+class OffHeapStagingMemory(taskContext: TaskContext)
+    extends MemoryConsumer(
+      taskContext.taskMemoryManager,
+      taskContext.taskMemoryManager.pageSizeBytes(),
+      MemoryMode.OFF_HEAP)
+    with Logging {
+
+  taskContext.addTaskCompletionListener { _ =>
+    OffHeapStagingMemory.synchronized {
+      OffHeapStagingMemory.all.remove(taskContext.taskAttemptId())
+    }
+  }
+
+  @SuppressWarnings(Array("unused"))
+  def acquire(size: Long): Long = acquireMemory(size)
+
+  @SuppressWarnings(Array("unused"))
+  def release(size: Long): Unit = freeMemory(size)
+
+  override def spill(size: Long, trigger: MemoryConsumer): Long = 0L
+}
+
+object OffHeapStagingMemory {
+  val all: mutable.Map[Long, OffHeapStagingMemory] = mutable.Map()
+
+  def current: OffHeapStagingMemory = synchronized {
+    val taskContext = TaskContext.get
+    all.getOrElseUpdate(taskContext.taskAttemptId(), new OffHeapStagingMemory(taskContext))
+  }
+}
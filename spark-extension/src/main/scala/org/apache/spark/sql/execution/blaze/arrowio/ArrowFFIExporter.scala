@@ -15,32 +15,55 @@
  */
 package org.apache.spark.sql.execution.blaze.arrowio
 
+import scala.collection.JavaConverters._
+
 import org.apache.arrow.c.ArrowArray
 import org.apache.arrow.c.ArrowSchema
 import org.apache.arrow.c.Data
+import org.apache.arrow.vector.BaseFixedWidthVector
+import org.apache.arrow.vector.BitVectorHelper
+import org.apache.arrow.vector.FieldVector
 import org.apache.arrow.vector.VectorSchemaRoot
 import org.apache.arrow.vector.dictionary.DictionaryProvider.MapDictionaryProvider
+import org.apache.arrow.vector.types.pojo.Field
 import org.apache.spark.sql.catalyst.InternalRow
+import org.apache.spark.sql.execution.blaze.arrowio.util.ArrowColumnVector
 import org.apache.spark.sql.execution.blaze.arrowio.util.ArrowUtils
 import org.apache.spark.sql.execution.blaze.arrowio.util.ArrowWriter
-import org.apache.spark.sql.types.StructType
+import org.apache.spark.sql.execution.vectorized.OffHeapColumnVector
+import org.apache.spark.sql.execution.vectorized.OnHeapColumnVector
+import org.apache.spark.sql.types._
+import org.apache.spark.sql.vectorized.{ArrowColumnVector => SparkArrowColumnVector}
+import org.apache.spark.sql.vectorized.ColumnarBatch
+import org.apache.spark.sql.vectorized.ColumnVector
 import org.apache.spark.sql.blaze.BlazeConf
 import org.apache.spark.sql.blaze.util.Using
+import org.apache.spark.unsafe.Platform
 import org.apache.spark.TaskContext
 
-class ArrowFFIExporter(rowIter: Iterator[InternalRow], schema: StructType) {
+class ArrowFFIExporter private (
+    rowIter: Iterator[InternalRow],
+    batchIter: Iterator[ColumnarBatch],
+    schema: StructType) {
+
+  def this(rowIter: Iterator[InternalRow], schema: StructType) =
+    this(rowIter, Iterator.empty, schema)
+
   private val maxBatchNumRows = BlazeConf.BATCH_SIZE.intConf()
   private val maxBatchMemorySize = 1 << 24 // 16MB
 
   private val arrowSchema = ArrowUtils.toArrowSchema(schema)
   private val emptyDictionaryProvider = new MapDictionaryProvider()
 
+  // rows of the current non-arrow columnar batch
+  private var batchRowIter: Iterator[InternalRow] = Iterator.empty
+
   def hasNext: Boolean = {
     val tc = TaskContext.get()
     if (tc != null && (tc.isCompleted() || tc.isInterrupted())) {
       return false
     }
-    rowIter.hasNext
+    rowIter.hasNext || batchRowIter.hasNext || batchIter.hasNext
   }
 
   def exportSchema(exportArrowSchemaPtr: Long): Unit = {
@@ -55,8 +78,33 @@ class ArrowFFIExporter(rowIter: Iterator[InternalRow], schema: StructType) {
     val tc = TaskContext.get()
 
     if (tc != null && (tc.isCompleted() || tc.isInterrupted())) return false
-    if (!rowIter.hasNext) return false
+    if (rowIter.hasNext) {
+      exportRows(rowIter, exportArrowArrayPtr)
+      return true
+    }
 
+    while (!batchRowIter.hasNext && batchIter.hasNext) {
+      val batch = batchIter.next()
+      if (batch.numRows() > 0) {
+        if (isArrowCompatible(batch)) {
+          exportArrowBatch(batch, exportArrowArrayPtr)
+          return true
+        }
+        if (isSparkColumnar(batch)) {
+          exportSparkColumnarBatch(batch, exportArrowArrayPtr)
+          return true
+        }
+        batchRowIter = batch.rowIterator().asScala
+      }
+    }
+    if (batchRowIter.hasNext) {
+      exportRows(batchRowIter, exportArrowArrayPtr)
+      return true
+    }
+    false
+  }
+
+  private def exportRows(rows: Iterator[InternalRow], exportArrowArrayPtr: Long): Unit = {
     Using.resource(ArrowUtils.newChildAllocator(getClass.getName)) { batchAllocator =>
       Using.resources(
         VectorSchemaRoot.create(arrowSchema, batchAllocator),
@@ -64,10 +112,10 @@ class ArrowFFIExporter(rowIter: Iterator[InternalRow], schema: StructType) {
         val arrowWriter = ArrowWriter.create(root)
         var rowCount = 0
 
-        while (rowIter.hasNext
+        while (rows.hasNext
           && rowCount < maxBatchNumRows
           && batchAllocator.getAllocatedMemory < maxBatchMemorySize) {
-          arrowWriter.write(rowIter.next())
+          arrowWriter.write(rows.next())
           rowCount += 1
         }
         arrowWriter.finish()
@@ -80,6 +128,156 @@ class ArrowFFIExporter(rowIter: Iterator[InternalRow], schema: StructType) {
           exportArray)
       }
     }
-    true
   }
+
+  // batches produced by arrow-based readers are exported without copying. the buffers
+  // are transferred out of the batch, so that the producer can reuse its vectors, and
+  // are released by native side through the release callback of the exported array.
+  private def exportArrowBatch(batch: ColumnarBatch, exportArrowArrayPtr: Long): Unit = {
+    val vectors = (0 until batch.numCols()).map { i =>
+      val vector = batch.column(i).asInstanceOf[ArrowColumnVector].getValueVector
+      val transferPair = vector.getTransferPair(vector.getAllocator)
+      transferPair.transfer()
+      transferPair.getTo.asInstanceOf[FieldVector]
+    }
+    Using.resources(
+      new VectorSchemaRoot(vectors.asJava),
+      ArrowArray.wrap(exportArrowArrayPtr)) { case (root, exportArray) =>
+      root.setRowCount(batch.numRows())
+      Data.exportVectorSchemaRoot(
+        ArrowUtils.rootAllocator,
+        root,
+        emptyDictionaryProvider,
+        exportArray)
+    }
+  }
+
+  // batches of spark's own column vectors (produced by the vectorized parquet/orc readers
+  // and arrow-based python udfs) are copied column by column instead of row by row. spark's
+  // ArrowColumnVector cannot be exported without copying, because its vectors belong to the
+  // unrelocated arrow library, not the one bundled with blaze.
+  private def exportSparkColumnarBatch(batch: ColumnarBatch, exportArrowArrayPtr: Long): Unit = {
+    Using.resource(ArrowUtils.newChildAllocator(getClass.getName)) { batchAllocator =>
+      Using.resources(
+        VectorSchemaRoot.create(arrowSchema, batchAllocator),
+        ArrowArray.wrap(exportArrowArrayPtr)) { case (root, exportArray) =>
+        val numRows = batch.numRows()
+        root.getFieldVectors.asScala.zipWithIndex.foreach { case (vector, i) =>
+          copyColumn(batch, i, vector, numRows)
+        }
+        root.setRowCount(numRows)
+
+        // export using root allocator
+        Data.exportVectorSchemaRoot(
+          ArrowUtils.rootAllocator,
+          root,
+          emptyDictionaryProvider,
+          exportArray)
+      }
+    }
+  }
+
+  private def copyColumn(
+      batch: ColumnarBatch,
+      ordinal: Int,
+      vector: FieldVector,
+      numRows: Int): Unit = {
+    val column = batch.column(ordinal)
+    (column.dataType(), vector) match {
+      case (dataType, vector: BaseFixedWidthVector) if fixedWidthOf(dataType) > 0 =>
+        val numBytes = numRows.toLong * fixedWidthOf(dataType)
+        vector.allocateNew(numRows)
+        copyFixedWidthValues(column, numRows, vector.getDataBuffer.memoryAddress(), numBytes)
+
+        val validityBuffer = vector.getValidityBuffer
+        var i = 0
+        while (i < numRows) {
+          BitVectorHelper.setValidityBit(validityBuffer, i, if (column.isNullAt(i)) 0 else 1)
+          i += 1
+        }
+        vector.setValueCount(numRows)
+
+      case _ =>
+        vector.allocateNew()
+        val writer = ArrowWriter.createFieldWriter(vector)
+        var i = 0
+        while (i < numRows) {
+          writer.write(batch.getRow(i), ordinal)
+          i += 1
+        }
+        writer.finish()
+    }
+  }
+
+  private def copyFixedWidthValues(
+      column: ColumnVector,
+      numRows: Int,
+      address: Long,
+      numBytes: Long): Unit = {
+    column match {
+      case column: OffHeapColumnVector if !column.hasDictionary =>
+        Platform.copyMemory(null, column.valuesNativeAddress(), null, address, numBytes)
+      case _ =>
+        val (values, offset) = column.dataType() match {
+          case ByteType => (column.getBytes(0, numRows), Platform.BYTE_ARRAY_OFFSET)
+          case ShortType => (column.getShorts(0, numRows), Platform.SHORT_ARRAY_OFFSET)
+          case IntegerType | DateType => (column.getInts(0, numRows), Platform.INT_ARRAY_OFFSET)
+          case LongType | TimestampType =>
+            (column.getLongs(0, numRows), Platform.LONG_ARRAY_OFFSET)
+          case FloatType => (column.getFloats(0, numRows), Platform.FLOAT_ARRAY_OFFSET)
+          case DoubleType => (column.getDoubles(0, numRows), Platform.DOUBLE_ARRAY_OFFSET)
+        }
+        Platform.copyMemory(values, offset, null, address, numBytes)
+    }
+  }
+
+  private def fixedWidthOf(dataType: DataType): Int = dataType match {
+    case ByteType => 1
+    case ShortType => 2
+    case IntegerType | DateType | FloatType => 4
+    case LongType | TimestampType | DoubleType => 8
+    case _ => 0
+  }
+
+  private def isSparkColumnar(batch: ColumnarBatch): Boolean = {
+    batch.numCols() == schema.length &&
+    (0 until batch.numCols()).forall { i =>
+      val isSparkColumn = batch.column(i) match {
+        case _: OnHeapColumnVector | _: OffHeapColumnVector | _: SparkArrowColumnVector => true
+        case _ => false
+      }
+      isSparkColumn && batch.column(i).dataType() == schema(i).dataType
+    }
+  }
+
+  private def isArrowCompatible(batch: ColumnarBatch): Boolean = {
+    def sameType(field: Field, expected: Field): Boolean = {
+      field.getType == expected.getType &&
+      field.getChildren.size == expected.getChildren.size &&
+      field.getChildren.asScala.zip(expected.getChildren.asScala).forall { case (f, e) =>
+        sameType(f, e)
+      }
+    }
+    val expectedFields = arrowSchema.getFields.asScala
+    batch.numCols() == expectedFields.length &&
+    (0 until batch.numCols()).forall { i =>
+      batch.column(i) match {
+        case column: ArrowColumnVector =>
+          column.getValueVector.isInstanceOf[FieldVector] &&
+            sameType(column.getValueVector.getField, expectedFields(i))
+        case _ => false
+      }
+    }
+  }
+}
+
+object ArrowFFIExporter {
+
+  /**
+   * Creates an exporter of columnar batches. batches of arrow-backed columns with the expected
+   * types are exported without copying, batches of spark's column vectors are copied column by
+   * column, other batches are exported row by row.
+   */
+  def fromColumnarBatches(batchIter: Iterator[ColumnarBatch], schema: StructType) =
+    new ArrowFFIExporter(Iterator.empty, batchIter, schema)
 }
@@ -19,13 +19,17 @@ import java.util.UUID
 
 import scala.collection.immutable.SortedMap
 
+import org.apache.spark.rdd.RDD
 import org.apache.spark.sql.blaze.JniBridge
+import org.apache.spark.sql.catalyst.InternalRow
 import org.apache.spark.sql.catalyst.expressions.Attribute
 import org.apache.spark.sql.catalyst.plans.physical.Partitioning
 import org.apache.spark.sql.execution.metric.SQLMetric
+import org.apache.spark.sql.execution.ColumnarToRowExec
 import org.apache.spark.sql.execution.SparkPlan
 import org.apache.spark.sql.execution.UnaryExecNode
 import org.apache.spark.sql.types.StructType
+import org.apache.spark.sql.vectorized.ColumnarBatch
 import org.apache.spark.sql.blaze.MetricNode
 import org.apache.spark.sql.blaze.NativeConverters
 import org.apache.spark.sql.blaze.NativeRDD
@@ -59,7 +63,13 @@ abstract class ConvertToNativeBase(override val child: SparkPlan)
   val nativeSchema: Schema = NativeConverters.convertSchema(renamedSchema)
 
   override def doExecuteNative(): NativeRDD = {
-    val inputRDD = child.execute()
+    // columnar input (like vectorized scans) is exported batch by batch instead of being
+    // converted to unsafe rows
+    val input: Either[RDD[InternalRow], RDD[ColumnarBatch]] = child match {
+      case ColumnarToRowExec(columnarChild) => Right(columnarChild.executeColumnar())
+      case _ => Left(child.execute())
+    }
+    val inputRDD: RDD[_] = input.fold(identity, identity)
     val numInputPartitions = inputRDD.getNumPartitions
     val nativeMetrics = MetricNode(metrics, Nil)
 
@@ -71,9 +81,16 @@ abstract class ConvertToNativeBase(override val child: SparkPlan)
       Shims.get.getRDDShuffleReadFull(inputRDD),
       (partition, context) => {
 
-        val inputRowIter = inputRDD.compute(partition, context)
+        val exporter = input match {
+          case Left(rowRDD) =>
+            val inputRowIter = rowRDD.compute(partition, context)
+            new ArrowFFIExporter(inputRowIter, renamedSchema)
+          case Right(batchRDD) =>
+            val inputBatchIter = batchRDD.compute(partition, context)
+            ArrowFFIExporter.fromColumnarBatches(inputBatchIter, renamedSchema)
+        }
         val resourceId = s"ConvertToNativeExec:${UUID.randomUUID().toString}"
-        JniBridge.resourcesMap.put(resourceId, new ArrowFFIExporter(inputRowIter, renamedSchema))
+        JniBridge.resourcesMap.put(resourceId, exporter)
 
         PhysicalPlanNode
           .newBuilder()
@@ -0,0 +1,127 @@
+/*
+ * Copyright 2022 The Blaze Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package org.apache.spark.sql.execution.blaze.plan
+
+import java.net.URI
+
+import scala.util.Try
+
+import com.google.protobuf.ByteString
+import org.apache.commons.lang3.reflect.MethodUtils
+import org.apache.hadoop.fs.Path
+import org.apache.spark.broadcast.Broadcast
+import org.apache.spark.sql.blaze.BlazeConf
+import org.apache.spark.sql.execution.FileSourceScanExec
+import org.apache.spark.sql.execution.datasources.PartitionedFile
+import org.apache.spark.util.Utils
+import org.blaze.{protobuf => pb}
+
+/**
+ * Delta lake is accessed by reflection, so that blaze does not depend on a specific delta
+ * version. deletion vectors are read from the dv map broadcasted by DeltaParquetFileFormat
+ * (delta 2.4 ~ 3.0) or the constant metadata of partitioned files (delta 3.1+).
+ */
+object DeltaUtil {
+  // internal column added by delta for filtering deleted rows
+  val isRowDeletedColumnName = "__delta_internal_is_row_deleted"
+
+  private val rowIndexFilterIdEncodedKey = "row_index_filter_id_encoded"
+  private val rowIndexFilterTypeKey = "row_index_filter_type"
+
+  def isDeltaFileFormat(scan: FileSourceScanExec): Boolean =
+    scan.relation.fileFormat.getClass.getName == "org.apache.spark.sql.delta.DeltaParquetFileFormat"
+
+  /**
+   * Returns native deletion vectors of the scanned files keyed by file paths. the native scan
+   * skips deleted rows and fills the internal is-row-deleted column with zeros.
+   */
+  def nativeDeletionVectors(
+      scan: FileSourceScanExec,
+      files: Seq[PartitionedFile]): Map[String, pb.DeletionVector] = {
+    if (!isDeltaFileFormat(scan)) {
+      return Map.empty
+    }
+    def unsupported(reason: String): Nothing =
+      throw new NotImplementedError(s"native parquet scan does not support delta $reason")
+
+    scan.requiredSchema
+      .map(_.name)
+      .filter(name => name.startsWith("__delta_internal_") || name.startsWith("_tmp_metadata_"))
+      .filter(_ != isRowDeletedColumnName)
+      .foreach(name => unsupported(s"internal column $name"))
+    if (!scan.requiredSchema.exists(_.name == isRowDeletedColumnName)) {
+      return Map.empty
+    }
+    if (!BlazeConf.DELTA_DELETION_VECTOR_ENABLE.booleanConf()) {
+      unsupported("deletion vectors")
+    }
+
+    val fileFormat = scan.relation.fileFormat
+    val tablePath = MethodUtils.invokeMethod(scan.relation.location, "path").asInstanceOf[Path]
+    val broadcastDvMap = Try(MethodUtils.invokeMethod(fileFormat, "broadcastDvMap")).toOption
+      .flatMap(_.asInstanceOf[Option[Broadcast[Map[URI, AnyRef]]]])
+
+    files.flatMap { file =>
+      val filePath = file.filePath.toString
+      val descriptorAndFilterType = broadcastDvMap match {
+        case Some(dvMap) =>
+          dvMap.value.get(new URI(filePath)).map { dv =>
+            (
+              MethodUtils.invokeMethod(dv, "descriptor"),
+              MethodUtils.invokeMethod(dv, "filterType").toString)
+          }
+        case None =>
+          Try(MethodUtils.invokeMethod(file, "otherConstantMetadataColumnValues")).toOption
+            .map(_.asInstanceOf[Map[String, Any]])
+            .flatMap(metadata =>
+              metadata.get(rowIndexFilterIdEncodedKey).filter(_ != null).map { encoded =>
+                val descriptorObject = Utils
+                  .classForName("org.apache.spark.sql.delta.actions.DeletionVectorDescriptor$")
+                  .getField("MODULE$")
+                  .get(null)
+                (
+                  MethodUtils
+                    .invokeMethod(descriptorObject, "deserializeFromBase64", encoded.toString),
+                  metadata(rowIndexFilterTypeKey).toString)
+              })
+      }
+
+      descriptorAndFilterType.map { case (descriptor, filterType) =>
+        // rows in the bitmap are kept instead of deleted with IF_NOT_CONTAINED
+        if (filterType != "IF_CONTAINED") {
+          unsupported(s"row index filter type $filterType")
+        }
+        filePath -> nativeDeletionVector(descriptor, tablePath)
+      }
+    }.toMap
+  }
+
+  private def nativeDeletionVector(descriptor: AnyRef, tablePath: Path): pb.DeletionVector = {
+    def invoke(methodName: String, args: AnyRef*): AnyRef =
+      MethodUtils.invokeMethod(descriptor, methodName, args: _*)
+
+    val builder = pb.DeletionVector.newBuilder()
+    if (invoke("isInline").asInstanceOf[Boolean]) {
+      builder.setInlineData(ByteString.copyFrom(invoke("inlineData").asInstanceOf[Array[Byte]]))
+    } else {
+      builder
+        .setPath(invoke("absolutePath", tablePath).toString)
+        .setOffset(invoke("offset").asInstanceOf[Option[Any]].map(_.toString.toLong).getOrElse(0L))
+        .setSize(invoke("sizeInBytes").asInstanceOf[Int])
+    }
+    builder.build()
+  }
+}
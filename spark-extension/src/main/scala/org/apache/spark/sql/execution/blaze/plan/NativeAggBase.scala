@@ -76,6 +76,8 @@ abstract class NativeAggBase(
         "stage_id",
         "output_rows",
         "elapsed_compute",
+        "coalesce_input_batches",
+        "coalesce_output_batches",
         "mem_spill_count",
         "mem_spill_size",
         "mem_spill_iotime",
@@ -122,6 +124,10 @@ abstract class NativeAggBase(
 
   private def nativeGroupingNames = groupingExpressions.map(Util.getFieldNameByExprId)
 
+  // declared key types, decimal and timestamp keys are canonicalized to them in native side
+  private def nativeGroupingTypes =
+    groupingExpressions.map(e => NativeConverters.convertDataType(e.dataType))
+
   private def nativeAggrNames = nativeAggrInfos.map(_.outputAttr).map(_.name)
 
   private def nativeAggrModes = nativeAggrInfos.map(_.mode match {
@@ -136,6 +142,7 @@ abstract class NativeAggBase(
   nativeAggrs
   nativeGroupingExprs
   nativeGroupingNames
+  nativeGroupingTypes
   nativeAggrs
   nativeAggrModes
 
@@ -167,6 +174,7 @@ abstract class NativeAggBase(
     val nativeAggrModes = this.nativeAggrModes
     val nativeAggrs = this.nativeAggrs
     val nativeGroupingExprs = this.nativeGroupingExprs
+    val nativeGroupingTypes = this.nativeGroupingTypes
 
     new NativeRDD(
       sparkContext,
@@ -189,6 +197,7 @@ abstract class NativeAggBase(
               .addAllMode(nativeAggrModes.asJava)
               .addAllAggExpr(nativeAggrs.asJava)
               .addAllGroupingExpr(nativeGroupingExprs.asJava)
+              .addAllGroupingType(nativeGroupingTypes.asJava)
               .setInitialInputBufferOffset(initialInputBufferOffset)
               .setSupportsPartialSkipping(supportsPartialSkipping)
               .setInput(inputPlan))
@@ -15,7 +15,6 @@
  */
 package org.apache.spark.sql.execution.blaze.plan
 
-import java.io.ByteArrayOutputStream
 import java.nio.ByteBuffer
 import java.util.UUID
 import java.util.concurrent.Future
@@ -24,6 +23,7 @@ import java.util.concurrent.TimeUnit
 
 import scala.collection.JavaConverters._
 import scala.collection.immutable.SortedMap
+import scala.collection.mutable.ArrayBuffer
 import scala.concurrent.Promise
 
 import org.apache.commons.lang3.reflect.MethodUtils
@@ -41,6 +41,7 @@ import org.apache.spark.sql.blaze.NativeConverters
 import org.apache.spark.sql.blaze.NativeHelper
 import org.apache.spark.sql.blaze.NativeRDD
 import org.apache.spark.sql.blaze.NativeSupports
+import org.apache.spark.sql.blaze.ReinvocableProvider
 import org.apache.spark.sql.blaze.Shims
 import org.apache.spark.sql.catalyst.InternalRow
 import org.apache.spark.sql.catalyst.expressions.Attribute
@@ -56,6 +57,7 @@ import org.apache.spark.sql.catalyst.trees.TreeNodeTag
 import org.apache.spark.sql.execution.SparkPlan
 import org.apache.spark.sql.execution.SQLExecution
 import org.apache.spark.sql.execution.blaze.plan.NativeBroadcastExchangeBase.buildBroadcastData
+import org.apache.spark.sql.execution.blaze.plan.NativeBroadcastExchangeBase.readChunk
 import org.apache.spark.sql.execution.blaze.shuffle.BlockObject
 import org.apache.spark.sql.execution.exchange.BroadcastExchangeExec
 import org.apache.spark.sql.execution.exchange.BroadcastExchangeLike
@@ -175,7 +177,8 @@ abstract class NativeBroadcastExchangeBase(mode: BroadcastMode, override val chi
             })
         }
 
-        JniBridge.resourcesMap.put(resourceId, () => provideIpcIterator())
+        // reading broadcast value may fail on transient errors, it is safe to retry
+        JniBridge.resourcesMap.put(resourceId, new ReinvocableProvider(provideIpcIterator))
         pb.PhysicalPlanNode
           .newBuilder()
           .setIpcReader(
@@ -207,14 +210,12 @@ abstract class NativeBroadcastExchangeBase(mode: BroadcastMode, override val chi
 
         override def compute(split: Partition, context: TaskContext): Iterator[Array[Byte]] = {
           val resourceId = s"ArrowBroadcastExchangeExec.input:${UUID.randomUUID()}"
-          val bos = new ByteArrayOutputStream()
+          val chunks = ArrayBuffer[Array[Byte]]()
           JniBridge.resourcesMap.put(
             resourceId,
             (byteBuffer: ByteBuffer) => {
-              val byteArray = new Array[Byte](byteBuffer.capacity())
-              byteBuffer.get(byteArray)
-              bos.write(byteArray)
-              metrics("dataSize") += byteArray.length
+              chunks += readChunk(byteBuffer)
+              metrics("dataSize") += chunks.last.length
             })
 
           val input = inputRDD.nativePlan(inputRDD.partitions(split.index), context)
@@ -238,7 +239,7 @@ abstract class NativeBroadcastExchangeBase(mode: BroadcastMode, override val chi
           assert(iter.isEmpty)
 
           // return ipcs as iterator
-          Iterator.single(bos.toByteArray)
+          chunks.iterator
         }
       }
 
@@ -322,11 +323,9 @@ object NativeBroadcastExchangeBase {
     JniBridge.resourcesMap.put(readerIpcProviderResourceId, () => provideIpcIterator())
 
     // output
-    val bos = new ByteArrayOutputStream()
+    val chunks = ArrayBuffer[Array[Byte]]()
     val consumeIpc = (byteBuffer: ByteBuffer) => {
-      val byteArray = new Array[Byte](byteBuffer.capacity())
-      byteBuffer.get(byteArray)
-      bos.write(byteArray)
+      chunks += readChunk(byteBuffer)
     }
     JniBridge.resourcesMap.put(writerIpcProviderResourceId, consumeIpc)
 
@@ -335,6 +334,15 @@ object NativeBroadcastExchangeBase {
       override def index: Int = 0
     }
     assert(NativeHelper.executeNativePlan(exec, null, singlePartition, None).isEmpty)
-    Array(bos.toByteArray)
+    chunks.toArray
+  }
+
+  // each buffer passed from native ipc writer is a complete compressed block which can be
+  // read independently, so broadcast data is kept as a list of blocks instead of one large
+  // array, avoiding the 2GB array limit and the copying of concatenation.
+  private def readChunk(byteBuffer: ByteBuffer): Array[Byte] = {
+    val byteArray = new Array[Byte](byteBuffer.capacity())
+    byteBuffer.get(byteArray)
+    byteArray
   }
 }
@@ -24,6 +24,7 @@ import org.apache.spark.sql.blaze.MetricNode
 import org.apache.spark.sql.blaze.NativeConverters
 import org.apache.spark.sql.blaze.NativeHelper
 import org.apache.spark.sql.blaze.NativeRDD
+import org.apache.spark.sql.blaze.NativeRuntimeFilters
 import org.apache.spark.sql.blaze.NativeSupports
 import org.apache.spark.sql.blaze.Shims
 import org.apache.spark.sql.catalyst.expressions.Cast
@@ -57,6 +58,9 @@ abstract class NativeBroadcastJoinBase(
         "stage_id",
         "output_rows",
         "elapsed_compute",
+        "coalesce_input_batches",
+        "coalesce_output_batches",
+        "hash_collisions",
         "probed_side_hash_time",
         "probed_side_search_time",
         "probed_side_compare_time",
@@ -126,6 +130,10 @@ abstract class NativeBroadcastJoinBase(
       case BroadcastLeft => (rightRDD, leftRDD)
       case BroadcastRight => (leftRDD, rightRDD)
     }
+    val runtimeFilterIds = broadcastSide match {
+      case BroadcastLeft => getTagValue(NativeRuntimeFilters.leftProducerTag)
+      case BroadcastRight => getTagValue(NativeRuntimeFilters.rightProducerTag)
+    }
 
     new NativeRDD(
       sparkContext,
@@ -158,6 +166,7 @@ abstract class NativeBroadcastJoinBase(
           .setBroadcastSide(nativeBroadcastSide)
           .setCachedBuildHashMapId(cachedBuildHashMapId)
           .addAllOn(nativeJoinOn.asJava)
+          .addAllRuntimeFilterIds(runtimeFilterIds.getOrElse(Nil).asJava)
 
         pb.PhysicalPlanNode.newBuilder().setBroadcastJoin(broadcastJoinExec).build()
       },
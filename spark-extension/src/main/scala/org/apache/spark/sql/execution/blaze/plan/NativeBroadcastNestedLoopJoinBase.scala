@@ -0,0 +1,280 @@
+/*
+ * Copyright 2022 The Blaze Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package org.apache.spark.sql.execution.blaze.plan
+
+import scala.collection.JavaConverters._
+import scala.collection.immutable.SortedMap
+
+import org.apache.spark.OneToOneDependency
+import org.apache.spark.Partition
+import org.apache.spark.sql.blaze.BlazeConf
+import org.apache.spark.sql.blaze.MetricNode
+import org.apache.spark.sql.blaze.NativeConverters
+import org.apache.spark.sql.blaze.NativeHelper
+import org.apache.spark.sql.blaze.NativeRDD
+import org.apache.spark.sql.blaze.NativeSupports
+import org.apache.spark.sql.catalyst.expressions.Attribute
+import org.apache.spark.sql.catalyst.expressions.AttributeSet
+import org.apache.spark.sql.catalyst.expressions.Expression
+import org.apache.spark.sql.catalyst.expressions.GreaterThan
+import org.apache.spark.sql.catalyst.expressions.GreaterThanOrEqual
+import org.apache.spark.sql.catalyst.expressions.LessThan
+import org.apache.spark.sql.catalyst.expressions.LessThanOrEqual
+import org.apache.spark.sql.catalyst.expressions.PredicateHelper
+import org.apache.spark.sql.catalyst.plans.ExistenceJoin
+import org.apache.spark.sql.catalyst.plans.FullOuter
+import org.apache.spark.sql.catalyst.plans.InnerLike
+import org.apache.spark.sql.catalyst.plans.JoinType
+import org.apache.spark.sql.catalyst.plans.LeftExistence
+import org.apache.spark.sql.catalyst.plans.LeftOuter
+import org.apache.spark.sql.catalyst.plans.RightOuter
+import org.apache.spark.sql.catalyst.plans.physical.Partitioning
+import org.apache.spark.sql.execution.BinaryExecNode
+import org.apache.spark.sql.execution.SparkPlan
+import org.apache.spark.sql.execution.metric.SQLMetric
+import org.apache.spark.sql.types.DataType
+import org.apache.spark.sql.types.DateType
+import org.apache.spark.sql.types.DecimalType
+import org.apache.spark.sql.types.IntegralType
+import org.apache.spark.sql.types.TimestampType
+import org.blaze.{protobuf => pb}
+
+abstract class NativeBroadcastNestedLoopJoinBase(
+    override val left: SparkPlan,
+    override val right: SparkPlan,
+    override val outputPartitioning: Partitioning,
+    joinType: JoinType,
+    broadcastSide: BroadcastSide,
+    condition: Option[Expression])
+    extends BinaryExecNode
+    with NativeSupports {
+
+  override lazy val metrics: Map[String, SQLMetric] = SortedMap[String, SQLMetric]() ++ Map(
+    NativeHelper
+      .getDefaultNativeMetrics(sparkContext)
+      .filterKeys(Set(
+        "stage_id",
+        "output_rows",
+        "elapsed_compute",
+        "coalesce_input_batches",
+        "coalesce_output_batches",
+        "input_batch_count",
+        "input_batch_mem_size",
+        "input_row_count"))
+      .toSeq: _*) ++ NativeHelper.getExprMetrics(sparkContext, "filter", condition.toSeq)
+
+  override def output: Seq[Attribute] = joinType match {
+    case _: InnerLike => left.output ++ right.output
+    case LeftOuter => left.output ++ right.output.map(_.withNullability(true))
+    case RightOuter => left.output.map(_.withNullability(true)) ++ right.output
+    case FullOuter =>
+      left.output.map(_.withNullability(true)) ++ right.output.map(_.withNullability(true))
+    case j: ExistenceJoin => left.output :+ j.exists
+    case LeftExistence(_) => left.output
+    case j => throw new NotImplementedError(s"unsupported join type: $j")
+  }
+
+  private def nativeSchema = Util.getNativeSchema(output)
+
+  private def nativeJoinType = NativeConverters.convertJoinType(joinType)
+
+  private def nativeBroadcastSide = broadcastSide match {
+    case BroadcastLeft => pb.JoinSide.LEFT_SIDE
+    case BroadcastRight => pb.JoinSide.RIGHT_SIDE
+  }
+
+  // join filter is evaluated on all columns of both sides
+  private def nativeJoinFilter = condition.map { cond =>
+    val leftColumnIndices = left.output.indices.map { i =>
+      pb.ColumnIndex.newBuilder().setIndex(i).setSide(pb.JoinSide.LEFT_SIDE).build()
+    }
+    val rightColumnIndices = right.output.indices.map { i =>
+      pb.ColumnIndex.newBuilder().setIndex(i).setSide(pb.JoinSide.RIGHT_SIDE).build()
+    }
+    pb.JoinFilter
+      .newBuilder()
+      .setExpression(NativeConverters.convertExpr(cond))
+      .addAllColumnIndices((leftColumnIndices ++ rightColumnIndices).asJava)
+      .setSchema(Util.getNativeSchema(left.output ++ right.output))
+      .build()
+  }
+
+  // conditions comparing intervals of both sides are executed with interval join
+  private def intervalJoinCondition = joinType match {
+    case _: InnerLike | LeftOuter | RightOuter | FullOuter
+        if BlazeConf.INTERVAL_JOIN_ENABLE.booleanConf() =>
+      condition.flatMap(IntervalJoinCondition.extract(_, left.output, right.output))
+    case _ => None
+  }
+
+  private def nativeIntervalBounds = intervalJoinCondition.map { cond =>
+    Seq(cond.leftStart, cond.leftEnd, cond.rightStart, cond.rightEnd)
+      .map(NativeConverters.convertExpr)
+  }
+
+  // check whether native converting is supported
+  nativeSchema
+  nativeJoinType
+  nativeBroadcastSide
+  if (nativeIntervalBounds.isEmpty) {
+    nativeJoinFilter
+  }
+
+  override def doExecuteNative(): NativeRDD = {
+    val leftRDD = NativeHelper.executeNative(left)
+    val rightRDD = NativeHelper.executeNative(right)
+    val nativeMetrics = MetricNode(metrics, leftRDD.metrics :: rightRDD.metrics :: Nil)
+    val nativeSchema = this.nativeSchema
+    val nativeJoinType = this.nativeJoinType
+    val nativeBroadcastSide = this.nativeBroadcastSide
+    val intervalEndsInclusive = this.intervalJoinCondition.map { cond =>
+      (cond.leftEndInclusive, cond.rightEndInclusive)
+    }
+    val nativeIntervalBounds = this.nativeIntervalBounds
+    val nativeJoinFilter = if (nativeIntervalBounds.isEmpty) this.nativeJoinFilter else None
+
+    val streamedRDD = broadcastSide match {
+      case BroadcastLeft => rightRDD
+      case BroadcastRight => leftRDD
+    }
+
+    new NativeRDD(
+      sparkContext,
+      nativeMetrics,
+      streamedRDD.partitions,
+      rddDependencies = new OneToOneDependency(streamedRDD) :: Nil,
+      streamedRDD.isShuffleReadFull,
+      (partition, context) => {
+        val partition0 = new Partition() {
+          override def index: Int = 0
+        }
+        val (leftChild, rightChild) = broadcastSide match {
+          case BroadcastLeft =>
+            (
+              leftRDD.nativePlan(partition0, context),
+              rightRDD.nativePlan(rightRDD.partitions(partition.index), context))
+          case BroadcastRight =>
+            (
+              leftRDD.nativePlan(leftRDD.partitions(partition.index), context),
+              rightRDD.nativePlan(partition0, context))
+        }
+
+        (nativeIntervalBounds, intervalEndsInclusive) match {
+          case (
+                Some(Seq(leftStart, leftEnd, rightStart, rightEnd)),
+                Some((leftEndInclusive, rightEndInclusive))) =>
+            val intervalJoinExec = pb.IntervalJoinExecNode
+              .newBuilder()
+              .setSchema(nativeSchema)
+              .setLeft(leftChild)
+              .setRight(rightChild)
+              .setJoinType(nativeJoinType)
+              .setBroadcastSide(nativeBroadcastSide)
+              .setLeftStart(leftStart)
+              .setLeftEnd(leftEnd)
+              .setRightStart(rightStart)
+              .setRightEnd(rightEnd)
+              .setLeftEndInclusive(leftEndInclusive)
+              .setRightEndInclusive(rightEndInclusive)
+            pb.PhysicalPlanNode.newBuilder().setIntervalJoin(intervalJoinExec).build()
+
+          case _ =>
+            val nestedLoopJoinExec = pb.BroadcastNestedLoopJoinExecNode
+              .newBuilder()
+              .setSchema(nativeSchema)
+              .setLeft(leftChild)
+              .setRight(rightChild)
+              .setJoinType(nativeJoinType)
+              .setBroadcastSide(nativeBroadcastSide)
+            nativeJoinFilter.foreach(nestedLoopJoinExec.setJoinFilter)
+            pb.PhysicalPlanNode.newBuilder().setBroadcastNestedLoopJoin(nestedLoopJoinExec).build()
+        }
+      },
+      friendlyName = "NativeRDD.BroadcastNestedLoopJoin")
+  }
+}
+
+/**
+ * Join condition of interval join: `leftStart <(=) rightEnd AND rightStart <(=) leftEnd`, where
+ * `<=` is used if the end is inclusive. point-in-interval conditions like `l.ts BETWEEN r.start
+ * AND r.end` are intervals with the same start and end on one side.
+ */
+case class IntervalJoinCondition(
+    leftStart: Expression,
+    leftEnd: Expression,
+    rightStart: Expression,
+    rightEnd: Expression,
+    leftEndInclusive: Boolean,
+    rightEndInclusive: Boolean)
+
+object IntervalJoinCondition extends PredicateHelper {
+
+  /**
+   * Extracts the interval join condition, returns None if the condition contains anything
+   * other than the two interval comparisons, or bounds are not of the same orderable type.
+   */
+  def extract(
+      condition: Expression,
+      leftOutput: Seq[Attribute],
+      rightOutput: Seq[Attribute]): Option[IntervalJoinCondition] = {
+
+    val leftAttrs = AttributeSet(leftOutput)
+    val rightAttrs = AttributeSet(rightOutput)
+    def isLeft(e: Expression) = e.references.nonEmpty && e.references.subsetOf(leftAttrs)
+    def isRight(e: Expression) = e.references.nonEmpty && e.references.subsetOf(rightAttrs)
+
+    // normalize comparisons to (smaller, larger, inclusive)
+    val comparisons = splitConjunctivePredicates(condition).map {
+      case LessThan(a, b) => Some((a, b, false))
+      case LessThanOrEqual(a, b) => Some((a, b, true))
+      case GreaterThan(a, b) => Some((b, a, false))
+      case GreaterThanOrEqual(a, b) => Some((b, a, true))
+      case _ => None
+    }
+    if (comparisons.length != 2 || comparisons.exists(_.isEmpty)) {
+      return None
+    }
+
+    // leftStart <(=) rightEnd
+    val leftStartBeforeRightEnd = comparisons.flatten.find { case (a, b, _) =>
+      isLeft(a) && isRight(b)
+    }
+    // rightStart <(=) leftEnd
+    val rightStartBeforeLeftEnd = comparisons.flatten.find { case (a, b, _) =>
+      isRight(a) && isLeft(b)
+    }
+    for {
+      (leftStart, rightEnd, rightEndInclusive) <- leftStartBeforeRightEnd
+      (rightStart, leftEnd, leftEndInclusive) <- rightStartBeforeLeftEnd
+      if Seq(leftStart, leftEnd, rightStart, rightEnd).map(_.dataType).distinct match {
+        case Seq(dataType) => isSupportedBoundType(dataType)
+        case _ => false
+      }
+    } yield IntervalJoinCondition(
+      leftStart,
+      leftEnd,
+      rightStart,
+      rightEnd,
+      leftEndInclusive,
+      rightEndInclusive)
+  }
+
+  // floating point types are not supported since -0.0 and 0.0 are ordered differently
+  private def isSupportedBoundType(dataType: DataType): Boolean = dataType match {
+    case _: IntegralType | _: DecimalType | DateType | TimestampType => true
+    case _ => false
+  }
+}
@@ -0,0 +1,155 @@
+/*
+ * Copyright 2022 The Blaze Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package org.apache.spark.sql.execution.blaze.plan
+
+import java.util.UUID
+
+import scala.collection.JavaConverters._
+
+import org.apache.spark.Partition
+import org.apache.spark.TaskContext
+import org.apache.spark.sql.blaze.MetricNode
+import org.apache.spark.sql.blaze.NativeRDD
+import org.apache.spark.sql.catalyst.csv.CSVExprUtils
+import org.apache.spark.sql.catalyst.util.CaseInsensitiveMap
+import org.apache.spark.sql.execution.FileSourceScanExec
+import org.apache.spark.sql.execution.datasources.FilePartition
+import org.apache.spark.sql.internal.SQLConf
+import org.apache.spark.sql.types._
+import org.blaze.{protobuf => pb}
+
+abstract class NativeCsvScanBase(basedFileScan: FileSourceScanExec)
+    extends NativeFileSourceScanBase(basedFileScan) {
+
+  private val nativeCsvOptions: pb.CsvScanOptions = {
+    val options = CaseInsensitiveMap(basedFileScan.relation.options)
+    def unsupported(reason: String): Nothing =
+      throw new NotImplementedError(s"native csv scan does not support $reason")
+
+    // options changing the record format are not supported
+    if (options.get("multiLine").exists(_.toBoolean)) unsupported("multiLine=true")
+    if (options.get("ignoreLeadingWhiteSpace").exists(_.toBoolean) ||
+      options.get("ignoreTrailingWhiteSpace").exists(_.toBoolean)) {
+      unsupported("ignoring white spaces")
+    }
+    options
+      .get("encoding")
+      .orElse(options.get("charset"))
+      .filterNot(_.equalsIgnoreCase("UTF-8"))
+      .foreach(encoding => unsupported(s"encoding $encoding"))
+    Seq("comment", "lineSep", "emptyValue", "nanValue", "positiveInf", "negativeInf", "locale")
+      .filter(options.contains)
+      .foreach(option => unsupported(s"option $option"))
+
+    basedFileScan.requiredSchema.foreach(field =>
+      field.dataType match {
+        case BooleanType | ByteType | ShortType | IntegerType | LongType | FloatType |
+            DoubleType | StringType | DateType =>
+        case _: DecimalType =>
+        case dataType => unsupported(s"type $dataType")
+      })
+
+    val dateFormat = options.getOrElse("dateFormat", "yyyy-MM-dd")
+    if (!dateFormat.replaceAll("'[^']*'", "").forall(c => !c.isLetter || "yMd".contains(c))) {
+      unsupported(s"dateFormat $dateFormat")
+    }
+
+    val mode = options.getOrElse("mode", "PERMISSIVE").toUpperCase match {
+      case "DROPMALFORMED" => pb.ParseMode.DROP_MALFORMED
+      case "FAILFAST" => pb.ParseMode.FAIL_FAST
+      case _ => pb.ParseMode.PERMISSIVE
+    }
+    val quote = options.getOrElse("quote", "\"")
+    val escape = options.getOrElse("escape", "\\")
+
+    pb.CsvScanOptions
+      .newBuilder()
+      .setDelimiter(CSVExprUtils.toDelimiterStr(options.getOrElse("sep", options
+        .getOrElse("delimiter", ","))))
+      .setQuote(if (quote == "\u0000") "" else quote)
+      .setEscape(if (escape == "\u0000") "" else escape)
+      .setHeader(options.get("header").exists(_.toBoolean))
+      .setNullValue(options.getOrElse("nullValue", ""))
+      .setDateFormat(dateFormat)
+      .setMode(mode)
+      .setColumnNameOfCorruptRecord(
+        options.getOrElse("columnNameOfCorruptRecord", SQLConf.get.columnNameOfCorruptRecord))
+      .build()
+  }
+
+  override def doExecuteNative(): NativeRDD = {
+    val partitions = inputFileScanRDD.filePartitions.toArray
+    val nativeMetrics = MetricNode(
+      metrics,
+      Nil,
+      Some({
+        case ("bytes_scanned", v) =>
+          val inputMetric = TaskContext.get.taskMetrics().inputMetrics
+          inputMetric.incBytesRead(v)
+        case ("output_rows", v) =>
+          val inputMetric = TaskContext.get.taskMetrics().inputMetrics
+          inputMetric.incRecordsRead(v)
+        case _ =>
+      }))
+    val nativeCsvOptions = this.nativeCsvOptions
+    val nativeFileSchema = this.nativeFileSchema
+    val nativeFileGroups = this.nativeFileGroups
+    val nativePartitionSchema = this.nativePartitionSchema
+    val projection = schema.map(field => basedFileScan.relation.schema.fieldIndex(field.name))
+    val broadcastedHadoopConf = this.broadcastedHadoopConf
+    val nativeDynamicPartitionFilters = this.nativeDynamicPartitionFilters
+    val numPartitions = partitions.length
+
+    new NativeRDD(
+      sparkContext,
+      nativeMetrics,
+      partitions.asInstanceOf[Array[Partition]],
+      Nil,
+      rddShuffleReadFull = true,
+      (partition, _) => {
+        val resourceId = s"NativeCsvScanExec:${UUID.randomUUID().toString}"
+        putJniBridgeResource(resourceId, broadcastedHadoopConf)
+
+        val nativeFileGroup = nativeFileGroups(partition.asInstanceOf[FilePartition])
+        val nativeFileScanExecConf = pb.FileScanExecConf
+          .newBuilder()
+          .setNumPartitions(numPartitions)
+          .setPartitionIndex(partition.index)
+          .setStatistics(pb.Statistics.getDefaultInstance)
+          .setSchema(nativeFileSchema)
+          .setFileGroup(nativeFileGroup)
+          .addAllProjection(projection.map(Integer.valueOf).asJava)
+          .setPartitionSchema(nativePartitionSchema)
+          .addAllDynamicPartitionFilters(nativeDynamicPartitionFilters().asJava)
+          .build()
+
+        val nativeCsvScanExecBuilder = pb.CsvScanExecNode
+          .newBuilder()
+          .setBaseConf(nativeFileScanExecConf)
+          .setFsResourceId(resourceId)
+          .setOptions(nativeCsvOptions)
+
+        pb.PhysicalPlanNode
+          .newBuilder()
+          .setCsvScan(nativeCsvScanExecBuilder.build())
+          .build()
+      },
+      friendlyName = "NativeRDD.CsvScan")
+  }
+
+  override val nodeName: String =
+    s"NativeCsvScan ${basedFileScan.tableIdentifier.map(_.unquotedString).getOrElse("")}"
+}
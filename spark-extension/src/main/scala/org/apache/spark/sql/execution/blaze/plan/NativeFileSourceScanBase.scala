@@ -17,6 +17,7 @@ package org.apache.spark.sql.execution.blaze.plan
 
 import java.net.URI
 import java.security.PrivilegedExceptionAction
+import java.util.UUID
 
 import scala.collection.JavaConverters._
 import scala.collection.immutable.SortedMap
@@ -26,14 +27,19 @@ import org.apache.hadoop.fs.FileSystem
 import org.apache.spark.broadcast.Broadcast
 import org.blaze.{protobuf => pb}
 import org.apache.spark.rdd.MapPartitionsRDD
+import org.apache.spark.sql.blaze.BlazeConf
 import org.apache.spark.sql.blaze.JniBridge
 import org.apache.spark.sql.blaze.NativeConverters
 import org.apache.spark.sql.blaze.NativeHelper
 import org.apache.spark.sql.blaze.NativeSupports
 import org.apache.spark.sql.blaze.Shims
+import org.apache.spark.sql.catalyst.InternalRow
 import org.apache.spark.sql.catalyst.expressions.Attribute
+import org.apache.spark.sql.catalyst.expressions.AttributeReference
+import org.apache.spark.sql.catalyst.expressions.DynamicPruningExpression
 import org.apache.spark.sql.catalyst.plans.physical.Partitioning
 import org.apache.spark.sql.execution.FileSourceScanExec
+import org.apache.spark.sql.execution.InSubqueryExec
 import org.apache.spark.sql.execution.LeafExecNode
 import org.apache.spark.sql.execution.datasources.FileScanRDD
 import org.apache.spark.sql.execution.metric.SQLMetric
@@ -41,6 +47,7 @@ import org.apache.spark.sql.execution.datasources.FilePartition
 import org.apache.spark.sql.execution.datasources.PartitionedFile
 import org.apache.spark.sql.execution.metric.SQLMetrics
 import org.apache.spark.sql.execution.SparkPlan
+import org.apache.spark.sql.execution.blaze.arrowio.ArrowFFIExporter
 import org.apache.spark.sql.types.NullType
 import org.apache.spark.sql.types.StructField
 import org.apache.spark.sql.types.StructType
@@ -60,6 +67,16 @@ abstract class NativeFileSourceScanBase(basedFileScan: FileSourceScanExec)
       ("row_groups_pruned", SQLMetrics
         .createMetric(sparkContext, "Native.row_groups_pruned")) :+
       ("bytes_scanned", SQLMetrics.createSizeMetric(sparkContext, "Native.bytes_scanned")) :+
+      ("dynamic_partition_pruned_files", SQLMetrics
+        .createMetric(sparkContext, "Native.dynamic_partition_pruned_files")) :+
+      ("deletion_vector_deleted_rows", SQLMetrics
+        .createMetric(sparkContext, "Native.deletion_vector_deleted_rows")) :+
+      ("metadata_aggregated_files", SQLMetrics
+        .createMetric(sparkContext, "Native.metadata_aggregated_files")) :+
+      ("pushdown_rows_filtered", SQLMetrics
+        .createMetric(sparkContext, "Native.pushdown_rows_filtered")) :+
+      ("pushdown_eval_time", SQLMetrics
+        .createNanoTimingMetric(sparkContext, "Native.pushdown_eval_time")) :+
       ("io_time", SQLMetrics.createNanoTimingMetric(sparkContext, "Native.io_time")) :+
       ("io_time_getfs", SQLMetrics
         .createNanoTimingMetric(sparkContext, "Native.io_time_getfs")): _*)
@@ -67,17 +84,48 @@ abstract class NativeFileSourceScanBase(basedFileScan: FileSourceScanExec)
   override val output: Seq[Attribute] = basedFileScan.output
   override val outputPartitioning: Partitioning = basedFileScan.outputPartitioning
 
+  def fileSourceScan: FileSourceScanExec = basedFileScan
+
+  private val partitionSchema = basedFileScan.relation.partitionSchema
+
+  // dynamic pruning filters of the form `partitionColumn IN (subquery)` are evaluated by the
+  // native scan at runtime, tuples of (partition column index, subquery)
+  private val nativeDynamicPruningFilters: Seq[(Int, InSubqueryExec)] =
+    if (BlazeConf.NATIVE_DYNAMIC_PARTITION_PRUNING_ENABLE.booleanConf()) {
+      basedFileScan.partitionFilters.collect {
+        case DynamicPruningExpression(inSubquery: InSubqueryExec)
+            if inSubquery.child.isInstanceOf[AttributeReference] &&
+              partitionSchema.exists(field =>
+                field.name == inSubquery.child.asInstanceOf[AttributeReference].name &&
+                  field.dataType == inSubquery.child.dataType) =>
+          val name = inSubquery.child.asInstanceOf[AttributeReference].name
+          (partitionSchema.fieldIndex(name), inSubquery)
+      }
+    } else {
+      Nil
+    }
+
   protected val inputFileScanRDD: FileScanRDD = {
-    MethodUtils.invokeMethod(basedFileScan, true, "prepare")
-    MethodUtils.invokeMethod(basedFileScan, true, "waitForSubqueries")
-    basedFileScan.inputRDDs().head match {
+    // files are listed without the natively evaluated dynamic pruning filters, so that
+    // we don't wait for their subqueries here
+    val staticFileScan = if (nativeDynamicPruningFilters.nonEmpty) {
+      val nativeFilters = nativeDynamicPruningFilters.map(_._2)
+      basedFileScan.copy(partitionFilters = basedFileScan.partitionFilters.filterNot {
+        case DynamicPruningExpression(inSubquery: InSubqueryExec) =>
+          nativeFilters.exists(_ eq inSubquery)
+        case _ => false
+      })
+    } else {
+      basedFileScan
+    }
+    MethodUtils.invokeMethod(staticFileScan, true, "prepare")
+    MethodUtils.invokeMethod(staticFileScan, true, "waitForSubqueries")
+    staticFileScan.inputRDDs().head match {
       case rdd: FileScanRDD => rdd
       case rdd: MapPartitionsRDD[_, _] => rdd.prev.asInstanceOf[FileScanRDD]
     }
   }
 
-  private val partitionSchema = basedFileScan.relation.partitionSchema
-
   private val fileSizes = inputFileScanRDD.filePartitions
     .flatMap(_.files)
     .groupBy(_.filePath)
@@ -88,14 +136,26 @@ abstract class NativeFileSourceScanBase(basedFileScan: FileSourceScanExec)
     basedFileScan.dataFilters
       .map(expr => NativeConverters.convertScanPruningExpr(expr))
 
-  protected def nativeFileSchema: pb.Schema =
-    NativeConverters.convertSchema(StructType(basedFileScan.relation.dataSchema.map {
+  protected def nativeFileSchema: pb.Schema = {
+    val fileSchema = StructType(basedFileScan.relation.dataSchema.map {
       case field if basedFileScan.requiredSchema.exists(_.name == field.name) =>
         field.copy(nullable = true)
       case field =>
         // avoid converting unsupported type in non-used fields
         StructField(field.name, NullType, nullable = true)
-    }))
+    })
+
+    // parquet field ids are passed as arrow field metadata for resolving columns by ids
+    val nativeSchemaBuilder = NativeConverters.convertSchema(fileSchema).toBuilder
+    fileSchema.zipWithIndex.foreach { case (field, i) =>
+      if (field.metadata.contains("parquet.field.id")) {
+        nativeSchemaBuilder
+          .getColumnsBuilder(i)
+          .putMetadata("PARQUET:field_id", field.metadata.getLong("parquet.field.id").toString)
+      }
+    }
+    nativeSchemaBuilder.build()
+  }
 
   protected def nativePartitionSchema: pb.Schema =
     NativeConverters.convertSchema(partitionSchema)
@@ -108,7 +168,7 @@ abstract class NativeFileSourceScanBase(basedFileScan: FileSourceScanExec)
           file.partitionValues.get(index, field.dataType),
           field.dataType)
       }
-      pb.PartitionedFile
+      val nativePartitionedFileBuilder = pb.PartitionedFile
         .newBuilder()
         .setPath(s"${file.filePath}")
         .setSize(fileSizes(file.filePath))
@@ -120,7 +180,8 @@ abstract class NativeFileSourceScanBase(basedFileScan: FileSourceScanExec)
             .setStart(file.start)
             .setEnd(file.start + file.length)
             .build())
-        .build()
+      nativeDeletionVector(file).foreach(dv => nativePartitionedFileBuilder.setDeletionVector(dv))
+      nativePartitionedFileBuilder.build()
     }
     pb.FileGroup
       .newBuilder()
@@ -128,12 +189,47 @@ abstract class NativeFileSourceScanBase(basedFileScan: FileSourceScanExec)
       .build()
   }
 
+  /** Returns the deletion vector of a file, whose deleted rows are skipped by the native scan. */
+  protected def nativeDeletionVector(file: PartitionedFile): Option[pb.DeletionVector] = None
+
   // check whether native converting is supported
   nativePruningPredicateFilters
   nativeFileSchema
   nativePartitionSchema
   nativeFileGroups
 
+  /**
+   * Returns a function creating native dynamic partition filters of a task, which exports the
+   * surviving partition values via jni bridge resources and must be called in executor side.
+   * the pruning subqueries are waited for here in driver side.
+   */
+  protected def nativeDynamicPartitionFilters: () => Seq[pb.DynamicPartitionFilter] = {
+    nativeDynamicPruningFilters.foreach { case (_, inSubquery) =>
+      if (inSubquery.values().isEmpty) {
+        inSubquery.updateResult()
+      }
+    }
+    val filters = nativeDynamicPruningFilters
+    val partitionSchema = this.partitionSchema
+
+    () =>
+      filters.flatMap { case (partitionColumn, inSubquery) =>
+        inSubquery.values().map { values =>
+          val resourceId = s"NativeDynamicPartitionFilter:${UUID.randomUUID().toString}"
+          val valueSchema =
+            StructType(StructField("v", partitionSchema(partitionColumn).dataType) :: Nil)
+          val valueRows = values.iterator.map(value => InternalRow(value))
+          JniBridge.resourcesMap.put(resourceId, new ArrowFFIExporter(valueRows, valueSchema))
+
+          pb.DynamicPartitionFilter
+            .newBuilder()
+            .setPartitionColumn(partitionColumn)
+            .setResourceId(resourceId)
+            .build()
+        }
+      }
+  }
+
   protected def putJniBridgeResource(
       resourceId: String,
       broadcastedHadoopConf: Broadcast[SerializableConfiguration]): Unit = {
@@ -23,6 +23,7 @@ import org.apache.spark.sql.blaze.MetricNode
 import org.apache.spark.sql.blaze.NativeConverters
 import org.apache.spark.sql.blaze.NativeRDD
 import org.apache.spark.sql.blaze.NativeHelper
+import org.apache.spark.sql.blaze.NativeRuntimeFilters
 import org.apache.spark.sql.catalyst.expressions.Attribute
 import org.apache.spark.sql.catalyst.expressions.Expression
 import org.apache.spark.sql.catalyst.expressions.SortOrder
@@ -34,6 +35,7 @@ import org.apache.spark.OneToOneDependency
 import org.blaze.protobuf.FilterExecNode
 import org.blaze.protobuf.PhysicalExprNode
 import org.blaze.protobuf.PhysicalPlanNode
+import org.blaze.protobuf.RuntimeFilterNode
 import org.apache.spark.sql.blaze.NativeSupports
 import org.apache.spark.sql.catalyst.expressions.And
 import org.apache.spark.sql.catalyst.expressions.AttributeReference
@@ -54,15 +56,17 @@ abstract class NativeFilterBase(condition: Expression, override val child: Spark
           "elapsed_compute",
           "input_batch_count",
           "input_batch_mem_size",
-          "input_row_count"))
-      .toSeq: _*)
+          "input_row_count",
+          "filter_input_rows",
+          "runtime_filter_pruned_rows"))
+      .toSeq: _*) ++ NativeHelper.getExprMetrics(sparkContext, "filter", splittedFilterExprs)
 
   override def output: Seq[Attribute] = FilterExec(condition, child).output
   override def outputPartitioning: Partitioning = child.outputPartitioning
   override def outputOrdering: Seq[SortOrder] = child.outputOrdering
 
-  private def nativeFilterExprs = {
-    val splittedExprs = ArrayBuffer[PhysicalExprNode]()
+  private def splittedFilterExprs = {
+    val splittedExprs = ArrayBuffer[Expression]()
 
     // do not split simple IsNotNull(col) exprs
     def isNaiveIsNotNullColumns(expr: Expression): Boolean = {
@@ -77,13 +81,16 @@ abstract class NativeFilterBase(condition: Expression, override val child: Spark
         case e @ And(lhs, rhs) if !isNaiveIsNotNullColumns(e) =>
           split(lhs)
           split(rhs)
-        case expr => splittedExprs.append(NativeConverters.convertExpr(expr))
+        case expr => splittedExprs.append(expr)
       }
     }
     split(condition)
     splittedExprs
   }
 
+  private def nativeFilterExprs: Seq[PhysicalExprNode] =
+    splittedFilterExprs.map(NativeConverters.convertExpr(_))
+
   // check whether native converting is supported
   nativeFilterExprs
 
@@ -91,6 +98,14 @@ abstract class NativeFilterBase(condition: Expression, override val child: Spark
     val inputRDD = NativeHelper.executeNative(child)
     val nativeMetrics = MetricNode(metrics, inputRDD.metrics :: Nil)
     val nativeFilterExprs = this.nativeFilterExprs
+    val nativeRuntimeFilters = getTagValue(NativeRuntimeFilters.consumerTag).getOrElse(Nil).map {
+      case (id, key) =>
+        RuntimeFilterNode
+          .newBuilder()
+          .setId(id)
+          .setKey(NativeConverters.convertExpr(key))
+          .build()
+    }
     new NativeRDD(
       sparkContext,
       nativeMetrics,
@@ -103,6 +118,7 @@ abstract class NativeFilterBase(condition: Expression, override val child: Spark
           .newBuilder()
           .setInput(inputRDD.nativePlan(inputPartition, taskContext))
           .addAllExpr(nativeFilterExprs.asJava)
+          .addAllRuntimeFilters(nativeRuntimeFilters.asJava)
           .build()
         PhysicalPlanNode.newBuilder().setFilter(nativeFilterExec).build()
       },
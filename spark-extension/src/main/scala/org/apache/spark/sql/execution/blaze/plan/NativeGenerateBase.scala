@@ -30,10 +30,12 @@ import org.apache.spark.sql.catalyst.expressions.AttributeReference
 import org.apache.spark.sql.catalyst.expressions.BoundReference
 import org.apache.spark.sql.catalyst.expressions.Explode
 import org.apache.spark.sql.catalyst.expressions.Generator
+import org.apache.spark.sql.catalyst.expressions.Inline
 import org.apache.spark.sql.catalyst.expressions.JsonTuple
 import org.apache.spark.sql.catalyst.expressions.Literal
 import org.apache.spark.sql.catalyst.expressions.PosExplode
 import org.apache.spark.sql.catalyst.expressions.SortOrder
+import org.apache.spark.sql.catalyst.expressions.Stack
 import org.apache.spark.sql.catalyst.plans.physical.Partitioning
 import org.apache.spark.sql.execution.SparkPlan
 import org.apache.spark.sql.execution.UnaryExecNode
@@ -62,6 +64,8 @@ abstract class NativeGenerateBase(
           "stage_id",
           "output_rows",
           "elapsed_compute",
+          "coalesce_input_batches",
+          "coalesce_output_batches",
           "input_batch_count",
           "input_batch_mem_size",
           "input_row_count"))
@@ -84,6 +88,19 @@ abstract class NativeGenerateBase(
         .setFunc(pb.GenerateFunction.PosExplode)
         .addChild(NativeConverters.convertExpr(child))
         .build()
+    case Inline(child) =>
+      pb.Generator
+        .newBuilder()
+        .setFunc(pb.GenerateFunction.Inline)
+        .addChild(NativeConverters.convertExpr(child))
+        .build()
+    case Stack(children) if children.head.foldable =>
+      pb.Generator
+        .newBuilder()
+        .setFunc(pb.GenerateFunction.Stack)
+        .addChild(NativeConverters.convertExpr(Literal(children.head.eval())))
+        .addAllChild(children.drop(1).map(NativeConverters.convertExpr).asJava)
+        .build()
     case JsonTuple(children)
         if NativeConverters.udfJsonEnabled && children.drop(1).forall(_.isInstanceOf[Literal]) =>
       pb.Generator
@@ -33,7 +33,7 @@ import org.blaze.protobuf.LimitExecNode
 import org.blaze.protobuf.PhysicalPlanNode
 import org.apache.spark.sql.blaze.NativeSupports
 
-abstract class NativeGlobalLimitBase(limit: Long, override val child: SparkPlan)
+abstract class NativeGlobalLimitBase(limit: Long, offset: Long, override val child: SparkPlan)
     extends UnaryExecNode
     with NativeSupports {
 
@@ -64,6 +64,7 @@ abstract class NativeGlobalLimitBase(limit: Long, override val child: SparkPlan)
           .newBuilder()
           .setInput(inputRDD.nativePlan(inputPartition, taskContext))
           .setLimit(limit)
+          .setOffset(offset)
           .build()
         PhysicalPlanNode.newBuilder().setLimit(nativeLimitExec).build()
       },
@@ -0,0 +1,158 @@
+/*
+ * Copyright 2022 The Blaze Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package org.apache.spark.sql.execution.blaze.plan
+
+import java.util.UUID
+
+import scala.collection.JavaConverters._
+
+import org.apache.spark.Partition
+import org.apache.spark.TaskContext
+import org.apache.spark.sql.blaze.MetricNode
+import org.apache.spark.sql.blaze.NativeRDD
+import org.apache.spark.sql.catalyst.util.CaseInsensitiveMap
+import org.apache.spark.sql.execution.FileSourceScanExec
+import org.apache.spark.sql.execution.datasources.FilePartition
+import org.apache.spark.sql.internal.SQLConf
+import org.apache.spark.sql.types._
+import org.blaze.{protobuf => pb}
+
+abstract class NativeJsonScanBase(basedFileScan: FileSourceScanExec)
+    extends NativeFileSourceScanBase(basedFileScan) {
+
+  private val nativeJsonOptions: pb.JsonScanOptions = {
+    val options = CaseInsensitiveMap(basedFileScan.relation.options)
+    def unsupported(reason: String): Nothing =
+      throw new NotImplementedError(s"native json scan does not support $reason")
+
+    // options changing the record format are not supported. note that single quoted
+    // strings and unquoted NaN/Infinity tokens (allowed by default in spark) are
+    // always treated as malformed records
+    if (options.get("multiLine").exists(_.toBoolean)) unsupported("multiLine=true")
+    options
+      .get("encoding")
+      .orElse(options.get("charset"))
+      .filterNot(_.equalsIgnoreCase("UTF-8"))
+      .foreach(encoding => unsupported(s"encoding $encoding"))
+    Seq(
+      "allowComments",
+      "allowUnquotedFieldNames",
+      "allowNumericLeadingZeros",
+      "allowBackslashEscapingAnyCharacter",
+      "allowUnquotedControlChars")
+      .filter(options.get(_).exists(_.toBoolean))
+      .foreach(option => unsupported(s"option $option"))
+    Seq("lineSep", "locale")
+      .filter(options.contains)
+      .foreach(option => unsupported(s"option $option"))
+
+    def isSupportedType(dataType: DataType): Boolean = dataType match {
+      case BooleanType | ByteType | ShortType | IntegerType | LongType | FloatType | DoubleType |
+          StringType | DateType | NullType =>
+        true
+      case _: DecimalType => true
+      case ArrayType(elementType, _) => isSupportedType(elementType)
+      case StructType(fields) => fields.forall(field => isSupportedType(field.dataType))
+      case MapType(StringType, valueType, _) => isSupportedType(valueType)
+      case _ => false
+    }
+    basedFileScan.requiredSchema
+      .filterNot(field => isSupportedType(field.dataType))
+      .foreach(field => unsupported(s"type ${field.dataType}"))
+
+    val dateFormat = options.getOrElse("dateFormat", "yyyy-MM-dd")
+    if (!dateFormat.replaceAll("'[^']*'", "").forall(c => !c.isLetter || "yMd".contains(c))) {
+      unsupported(s"dateFormat $dateFormat")
+    }
+
+    val mode = options.getOrElse("mode", "PERMISSIVE").toUpperCase match {
+      case "DROPMALFORMED" => pb.ParseMode.DROP_MALFORMED
+      case "FAILFAST" => pb.ParseMode.FAIL_FAST
+      case _ => pb.ParseMode.PERMISSIVE
+    }
+
+    pb.JsonScanOptions
+      .newBuilder()
+      .setMode(mode)
+      .setColumnNameOfCorruptRecord(
+        options.getOrElse("columnNameOfCorruptRecord", SQLConf.get.columnNameOfCorruptRecord))
+      .setDateFormat(dateFormat)
+      .build()
+  }
+
+  override def doExecuteNative(): NativeRDD = {
+    val partitions = inputFileScanRDD.filePartitions.toArray
+    val nativeMetrics = MetricNode(
+      metrics,
+      Nil,
+      Some({
+        case ("bytes_scanned", v) =>
+          val inputMetric = TaskContext.get.taskMetrics().inputMetrics
+          inputMetric.incBytesRead(v)
+        case ("output_rows", v) =>
+          val inputMetric = TaskContext.get.taskMetrics().inputMetrics
+          inputMetric.incRecordsRead(v)
+        case _ =>
+      }))
+    val nativeJsonOptions = this.nativeJsonOptions
+    val nativeFileSchema = this.nativeFileSchema
+    val nativeFileGroups = this.nativeFileGroups
+    val nativePartitionSchema = this.nativePartitionSchema
+    val projection = schema.map(field => basedFileScan.relation.schema.fieldIndex(field.name))
+    val broadcastedHadoopConf = this.broadcastedHadoopConf
+    val nativeDynamicPartitionFilters = this.nativeDynamicPartitionFilters
+    val numPartitions = partitions.length
+
+    new NativeRDD(
+      sparkContext,
+      nativeMetrics,
+      partitions.asInstanceOf[Array[Partition]],
+      Nil,
+      rddShuffleReadFull = true,
+      (partition, _) => {
+        val resourceId = s"NativeJsonScanExec:${UUID.randomUUID().toString}"
+        putJniBridgeResource(resourceId, broadcastedHadoopConf)
+
+        val nativeFileGroup = nativeFileGroups(partition.asInstanceOf[FilePartition])
+        val nativeFileScanExecConf = pb.FileScanExecConf
+          .newBuilder()
+          .setNumPartitions(numPartitions)
+          .setPartitionIndex(partition.index)
+          .setStatistics(pb.Statistics.getDefaultInstance)
+          .setSchema(nativeFileSchema)
+          .setFileGroup(nativeFileGroup)
+          .addAllProjection(projection.map(Integer.valueOf).asJava)
+          .setPartitionSchema(nativePartitionSchema)
+          .addAllDynamicPartitionFilters(nativeDynamicPartitionFilters().asJava)
+          .build()
+
+        val nativeJsonScanExecBuilder = pb.JsonScanExecNode
+          .newBuilder()
+          .setBaseConf(nativeFileScanExecConf)
+          .setFsResourceId(resourceId)
+          .setOptions(nativeJsonOptions)
+
+        pb.PhysicalPlanNode
+          .newBuilder()
+          .setJsonScan(nativeJsonScanExecBuilder.build())
+          .build()
+      },
+      friendlyName = "NativeRDD.JsonScan")
+  }
+
+  override val nodeName: String =
+    s"NativeJsonScan ${basedFileScan.tableIdentifier.map(_.unquotedString).getOrElse("")}"
+}
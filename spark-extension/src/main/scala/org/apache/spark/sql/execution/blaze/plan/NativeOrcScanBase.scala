@@ -50,6 +50,7 @@ abstract class NativeOrcScanBase(basedFileScan: FileSourceScanExec)
     val nativePartitionSchema = this.nativePartitionSchema
     val projection = schema.map(field => basedFileScan.relation.schema.fieldIndex(field.name))
     val broadcastedHadoopConf = this.broadcastedHadoopConf
+    val nativeDynamicPartitionFilters = this.nativeDynamicPartitionFilters
     val numPartitions = partitions.length
 
     new NativeRDD(
@@ -72,6 +73,7 @@ abstract class NativeOrcScanBase(basedFileScan: FileSourceScanExec)
           .setFileGroup(nativeFileGroup)
           .addAllProjection(projection.map(Integer.valueOf).asJava)
           .setPartitionSchema(nativePartitionSchema)
+          .addAllDynamicPartitionFilters(nativeDynamicPartitionFilters().asJava)
           .build()
 
         val nativeOrcScanExecBuilder = pb.OrcScanExecNode
@@ -26,10 +26,18 @@ import org.apache.spark.sql.blaze.MetricNode
 import org.apache.spark.sql.blaze.NativeRDD
 import org.apache.spark.sql.execution.FileSourceScanExec
 import org.apache.spark.sql.execution.datasources.FilePartition
+import org.apache.spark.sql.execution.datasources.PartitionedFile
 
 abstract class NativeParquetScanBase(basedFileScan: FileSourceScanExec)
     extends NativeFileSourceScanBase(basedFileScan) {
 
+  // deletion vectors of delta tables, keyed by file paths
+  private val nativeDeletionVectors: Map[String, pb.DeletionVector] =
+    DeltaUtil.nativeDeletionVectors(basedFileScan, inputFileScanRDD.filePartitions.flatMap(_.files))
+
+  override protected def nativeDeletionVector(file: PartitionedFile): Option[pb.DeletionVector] =
+    nativeDeletionVectors.get(file.filePath.toString)
+
   override def doExecuteNative(): NativeRDD = {
     val partitions = inputFileScanRDD.filePartitions.toArray
     val nativeMetrics = MetricNode(
@@ -51,6 +59,7 @@ abstract class NativeParquetScanBase(basedFileScan: FileSourceScanExec)
 
     val projection = schema.map(field => basedFileScan.relation.schema.fieldIndex(field.name))
     val broadcastedHadoopConf = this.broadcastedHadoopConf
+    val nativeDynamicPartitionFilters = this.nativeDynamicPartitionFilters
     val numPartitions = partitions.length
 
     new NativeRDD(
@@ -73,6 +82,7 @@ abstract class NativeParquetScanBase(basedFileScan: FileSourceScanExec)
           .setFileGroup(nativeFileGroup)
           .addAllProjection(projection.map(Integer.valueOf).asJava)
           .setPartitionSchema(nativePartitionSchema)
+          .addAllDynamicPartitionFilters(nativeDynamicPartitionFilters().asJava)
           .build()
 
         val nativeParquetScanExecBuilder = pb.ParquetScanExecNode
@@ -34,6 +34,7 @@ import org.apache.hadoop.hive.serde2.typeinfo.TypeInfoUtils
 import org.apache.hadoop.hive.serde2.SerDeUtils
 import org.apache.hadoop.mapred.JobConf
 import org.apache.hadoop.mapreduce.Job
+import org.apache.spark.sql.blaze.BlazeConf
 import org.apache.spark.sql.blaze.JniBridge
 import org.apache.spark.sql.SparkSession
 import org.apache.spark.sql.blaze.MetricNode
@@ -41,6 +42,7 @@ import org.apache.spark.sql.blaze.NativeHelper
 import org.apache.spark.sql.blaze.NativeRDD
 import org.apache.spark.sql.blaze.NativeSupports
 import org.apache.spark.sql.catalyst.catalog.CatalogTable
+import org.apache.spark.sql.catalyst.expressions.Ascending
 import org.apache.spark.sql.catalyst.expressions.Attribute
 import org.apache.spark.sql.catalyst.expressions.SortOrder
 import org.apache.spark.sql.catalyst.plans.physical.Partitioning
@@ -68,7 +70,19 @@ abstract class NativeParquetSinkBase(
 
   override def outputPartitioning: Partitioning = child.outputPartitioning
 
-  override def outputOrdering: Seq[SortOrder] = child.outputOrdering
+  private val numDynParts = partition.count(_._2.isEmpty)
+  private val maxConcurrentWriters = BlazeConf.PARQUET_SINK_MAX_CONCURRENT_WRITERS.intConf()
+
+  // with concurrent writers, the native sink outputs only one row for each dynamic partition
+  // (used for opening the output file), so the output is treated as sorted by dynamic
+  // partition columns and spark's writer does not sort it again
+  override def outputOrdering: Seq[SortOrder] = {
+    if (maxConcurrentWriters > 0 && numDynParts > 0) {
+      child.output.takeRight(numDynParts).map(SortOrder(_, Ascending))
+    } else {
+      child.outputOrdering
+    }
+  }
 
   override def doExecuteNative(): NativeRDD = {
     val hiveQlTable = HiveClientHelper.toHiveTable(table)
@@ -83,7 +97,9 @@ abstract class NativeParquetSinkBase(
     parquetFileFormat.prepareWrite(sparkSession, job, Map(), tableSchema)
 
     val serializableConf = new SerializableConfiguration(job.getConfiguration)
-    val numDynParts = partition.count(_._2.isEmpty)
+    val numDynParts = this.numDynParts
+    val maxConcurrentWriters = this.maxConcurrentWriters
+    val maxRecordsPerFile = sparkSession.sessionState.conf.maxRecordsPerFile
 
     val inputRDD = NativeHelper.executeNative(child)
     val nativeMetrics = MetricNode(metrics, inputRDD.metrics :: Nil)
@@ -129,15 +145,19 @@ abstract class NativeParquetSinkBase(
         val schema = HiveSchemaConverter.convert(columnNames, columnTypes)
         DataWritableWriteSupport.setSchema(schema, job.getConfiguration)
 
-        // init parquet props
-        val nativeProps = job.getConfiguration.asScala
-          .filter(_.getKey.startsWith("parquet."))
-          .map(entry =>
+        // init parquet props, including spark's output timestamp type and max records per file
+        val nativeProps = (job.getConfiguration.asScala
+          .filter(entry =>
+            entry.getKey.startsWith("parquet.") || entry.getKey.startsWith("spark.sql.parquet."))
+          .map(entry => (entry.getKey, entry.getValue)) ++
+          Seq("spark.sql.files.maxRecordsPerFile" -> maxRecordsPerFile.toString))
+          .map { case (key, value) =>
             ParquetProp
               .newBuilder()
-              .setKey(entry.getKey)
-              .setValue(entry.getValue)
-              .build())
+              .setKey(key)
+              .setValue(value)
+              .build()
+          }
 
         val inputPartition = inputRDD.partitions(partition.index)
         val parquetSink = ParquetSinkExecNode
@@ -145,6 +165,7 @@ abstract class NativeParquetSinkBase(
           .setInput(inputRDD.nativePlan(inputPartition, context))
           .setFsResourceId(resourceId)
           .setNumDynParts(numDynParts)
+          .setMaxConcurrentWriters(maxConcurrentWriters.max(0))
           .addAllProp(nativeProps.asJava)
         PhysicalPlanNode.newBuilder().setParquetSink(parquetSink).build()
       },
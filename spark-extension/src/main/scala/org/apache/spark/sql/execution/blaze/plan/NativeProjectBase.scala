@@ -55,10 +55,12 @@ abstract class NativeProjectBase(
           "stage_id",
           "output_rows",
           "elapsed_compute",
+          "coalesce_input_batches",
+          "coalesce_output_batches",
           "input_batch_count",
           "input_batch_mem_size",
           "input_row_count"))
-      .toSeq: _*)
+      .toSeq: _*) ++ NativeHelper.getExprMetrics(sparkContext, "project", projectList)
 
   override def output: Seq[Attribute] = projectList.map(_.toAttribute)
   override def outputPartitioning: Partitioning = child.outputPartitioning
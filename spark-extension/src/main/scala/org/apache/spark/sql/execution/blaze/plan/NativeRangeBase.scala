@@ -0,0 +1,110 @@
+/*
+ * Copyright 2022 The Blaze Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package org.apache.spark.sql.execution.blaze.plan
+
+import scala.collection.immutable.SortedMap
+
+import org.apache.spark.Partition
+import org.apache.spark.sql.blaze.MetricNode
+import org.apache.spark.sql.blaze.NativeHelper
+import org.apache.spark.sql.blaze.NativeRDD
+import org.apache.spark.sql.blaze.NativeSupports
+import org.apache.spark.sql.catalyst.expressions.Ascending
+import org.apache.spark.sql.catalyst.expressions.Attribute
+import org.apache.spark.sql.catalyst.expressions.Descending
+import org.apache.spark.sql.catalyst.expressions.SortOrder
+import org.apache.spark.sql.catalyst.plans.physical.Partitioning
+import org.apache.spark.sql.catalyst.plans.physical.RangePartitioning
+import org.apache.spark.sql.catalyst.plans.physical.SinglePartition
+import org.apache.spark.sql.catalyst.plans.physical.UnknownPartitioning
+import org.apache.spark.sql.execution.LeafExecNode
+import org.apache.spark.sql.execution.metric.SQLMetric
+import org.blaze.protobuf.PhysicalPlanNode
+import org.blaze.protobuf.RangeExecNode
+
+abstract class NativeRangeBase(
+    start: Long,
+    end: Long,
+    step: Long,
+    numSlices: Int,
+    override val output: Seq[Attribute])
+    extends LeafExecNode
+    with NativeSupports {
+
+  override lazy val metrics: Map[String, SQLMetric] = SortedMap[String, SQLMetric]() ++ Map(
+    NativeHelper
+      .getDefaultNativeMetrics(sparkContext)
+      .filterKeys(Set("stage_id", "output_rows", "elapsed_compute"))
+      .toSeq: _*)
+
+  // ordering and partitioning are the same as spark's RangeExec
+  override def outputOrdering: Seq[SortOrder] = {
+    val direction = if (step > 0) Ascending else Descending
+    SortOrder(output.head, direction) :: Nil
+  }
+
+  override def outputPartitioning: Partitioning = {
+    if (isEmptyRange) {
+      UnknownPartitioning(0)
+    } else if (numSlices == 1) {
+      SinglePartition
+    } else {
+      RangePartitioning(outputOrdering, numSlices)
+    }
+  }
+
+  private def isEmptyRange: Boolean = numElements <= 0
+
+  private def numElements: BigInt = {
+    val safeStart = BigInt(start)
+    val safeEnd = BigInt(end)
+    if ((safeEnd - safeStart) % step == 0 || (safeEnd > safeStart) != (step > 0)) {
+      (safeEnd - safeStart) / step
+    } else {
+      // the remainder has the same sign with range, could add 1 more
+      (safeEnd - safeStart) / step + 1
+    }
+  }
+
+  override def doExecuteNative(): NativeRDD = {
+    val nativeMetrics = MetricNode(metrics, Nil)
+    val nativeSchema = Util.getNativeSchema(output)
+    val numPartitions = if (isEmptyRange) 0 else numSlices
+    val partitions = (0 until numPartitions).map { i =>
+      new Partition {
+        override def index: Int = i
+      }
+    }.toArray
+
+    new NativeRDD(
+      sparkContext,
+      nativeMetrics,
+      partitions.asInstanceOf[Array[Partition]],
+      Nil,
+      rddShuffleReadFull = true,
+      (_, _) => {
+        val range = RangeExecNode
+          .newBuilder()
+          .setStart(start)
+          .setEnd(end)
+          .setStep(step)
+          .setNumSlices(numSlices)
+          .setSchema(nativeSchema)
+        PhysicalPlanNode.newBuilder().setRange(range).build()
+      },
+      friendlyName = "NativeRDD.Range")
+  }
+}
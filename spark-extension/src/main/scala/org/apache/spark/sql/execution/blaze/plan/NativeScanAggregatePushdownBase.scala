@@ -0,0 +1,72 @@
+/*
+ * Copyright 2022 The Blaze Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package org.apache.spark.sql.execution.blaze.plan
+
+import scala.collection.JavaConverters._
+
+import org.apache.spark.OneToOneDependency
+import org.apache.spark.sql.blaze.NativeHelper
+import org.apache.spark.sql.blaze.NativeRDD
+import org.apache.spark.sql.blaze.NativeSupports
+import org.apache.spark.sql.catalyst.expressions.Attribute
+import org.apache.spark.sql.catalyst.plans.physical.Partitioning
+import org.apache.spark.sql.execution.LeafExecNode
+import org.apache.spark.sql.execution.metric.SQLMetric
+import org.blaze.{protobuf => pb}
+
+/**
+ * A partial aggregate without grouping keys pushed down into a native parquet/orc scan. the
+ * scan answers `count`, `min` and `max` from file statistics if possible, and outputs a single
+ * row of aggregate buffers (in the same layout as spark's partial aggregate) per partition.
+ */
+abstract class NativeScanAggregatePushdownBase(
+    basedScan: NativeFileSourceScanBase,
+    override val output: Seq[Attribute],
+    pushedAggregates: Seq[pb.PushedAggregate])
+    extends LeafExecNode
+    with NativeSupports {
+
+  override lazy val metrics: Map[String, SQLMetric] = basedScan.metrics
+
+  override def outputPartitioning: Partitioning = basedScan.outputPartitioning
+
+  override def doExecuteNative(): NativeRDD = {
+    val inputRDD = NativeHelper.executeNative(basedScan)
+    val pushedAggregates = this.pushedAggregates
+
+    new NativeRDD(
+      sparkContext,
+      inputRDD.metrics,
+      rddPartitions = inputRDD.partitions,
+      rddDependencies = new OneToOneDependency(inputRDD) :: Nil,
+      inputRDD.isShuffleReadFull,
+      (partition, taskContext) => {
+        val inputPlan = inputRDD.nativePlan(inputRDD.partitions(partition.index), taskContext)
+        if (inputPlan.hasParquetScan) {
+          val parquetScan = inputPlan.getParquetScan.toBuilder
+            .addAllPushedAggregates(pushedAggregates.asJava)
+          inputPlan.toBuilder.setParquetScan(parquetScan).build()
+        } else {
+          val orcScan = inputPlan.getOrcScan.toBuilder
+            .addAllPushedAggregates(pushedAggregates.asJava)
+          inputPlan.toBuilder.setOrcScan(orcScan).build()
+        }
+      },
+      friendlyName = "NativeRDD.ScanAggregatePushdown")
+  }
+
+  override def nodeName: String = "NativeScanAggregatePushdown"
+}
@@ -21,7 +21,9 @@ import org.apache.spark.Partitioner
 import org.apache.spark.ShuffleDependency
 import org.apache.spark.SparkEnv
 import org.apache.spark.TaskContext
-import org.blaze.protobuf.{IpcReaderExecNode, PhysicalHashRepartition, PhysicalSingleRepartition, PhysicalRoundRobinRepartition, PhysicalPlanNode, PhysicalRepartition, Schema}
+import org.apache.commons.lang3.reflect.FieldUtils
+import org.apache.spark.RangePartitioner
+import org.blaze.protobuf.{IpcReaderExecNode, PhysicalExprNode, PhysicalHashRepartition, PhysicalRangeRepartition, PhysicalSingleRepartition, PhysicalSortExprNode, PhysicalRoundRobinRepartition, PhysicalPlanNode, PhysicalRepartition, ScalarValue, Schema}
 import org.apache.spark.rdd.RDD
 import org.apache.spark.serializer.Serializer
 import org.apache.spark.shuffle.ShuffleWriteProcessor
@@ -34,8 +36,14 @@ import org.apache.spark.sql.blaze.NativeSupports
 import org.apache.spark.sql.blaze.Shims
 import org.apache.spark.sql.catalyst.plans.physical.Partitioning
 import org.apache.spark.sql.catalyst.InternalRow
+import org.apache.spark.sql.catalyst.expressions.Ascending
 import org.apache.spark.sql.catalyst.expressions.Attribute
+import org.apache.spark.sql.catalyst.expressions.BoundReference
+import org.apache.spark.sql.catalyst.expressions.NullsFirst
+import org.apache.spark.sql.catalyst.expressions.UnsafeProjection
+import org.apache.spark.sql.catalyst.expressions.codegen.LazilyGeneratedOrdering
 import org.apache.spark.sql.catalyst.plans.physical.HashPartitioning
+import org.apache.spark.sql.catalyst.plans.physical.RangePartitioning
 import org.apache.spark.sql.catalyst.plans.physical.SinglePartition
 import org.apache.spark.sql.catalyst.plans.physical.RoundRobinPartitioning
 import org.apache.spark.sql.execution.exchange.ShuffleExchangeLike
@@ -43,7 +51,9 @@ import org.apache.spark.sql.execution.metric.{SQLMetric, SQLMetrics, SQLShuffleR
 import org.apache.spark.sql.execution.{SQLExecution, SparkPlan, UnsafeRowSerializer}
 import org.apache.spark.sql.execution.blaze.shuffle.BlazeBlockStoreShuffleReaderBase
 import org.apache.spark.sql.execution.blaze.shuffle.BlazeShuffleDependency
+import org.apache.spark.sql.internal.SQLConf
 import org.apache.spark.util.CompletionIterator
+import org.apache.spark.util.MutablePair
 import org.apache.spark.OneToOneDependency
 
 abstract class NativeShuffleExchangeBase(
@@ -87,9 +97,98 @@ abstract class NativeShuffleExchangeBase(
     case _ => null
   }
 
+  private def nativeRangeSortExprs = outputPartitioning match {
+    case RangePartitioning(sortOrder, _) =>
+      sortOrder.map { sortOrder =>
+        PhysicalExprNode
+          .newBuilder()
+          .setSort(
+            PhysicalSortExprNode
+              .newBuilder()
+              .setExpr(NativeConverters.convertExpr(sortOrder.child))
+              .setAsc(sortOrder.direction == Ascending)
+              .setNullsFirst(sortOrder.nullOrdering == NullsFirst)
+              .build())
+          .build()
+      }.toList
+    case _ => null
+  }
+
+  // bounds of range partitioning, sampled from input rows in the same way as
+  // spark's ShuffleExchangeExec. values are flattened in row-major order.
+  // computed on driver when preparing the shuffle dependency.
+  private lazy val nativeRangeBounds: Seq[ScalarValue] = outputPartitioning match {
+    case RangePartitioning(sortOrder, numPartitions) =>
+      val childOutput = child.output
+      val sortingExprs = sortOrder.map(_.child)
+      val rddForSampling = inputRDD.mapPartitionsInternal { iter =>
+        val projection = UnsafeProjection.create(sortingExprs, childOutput)
+        val mutablePair = new MutablePair[InternalRow, Null]()
+        iter.map(row => mutablePair.update(projection(row).copy(), null))
+      }
+      val orderingAttributes = sortOrder.zipWithIndex.map { case (order, i) =>
+        order.copy(child = BoundReference(i, order.dataType, order.nullable))
+      }
+      implicit val ordering: Ordering[InternalRow] =
+        new LazilyGeneratedOrdering(orderingAttributes)
+      val partitioner = new RangePartitioner(
+        numPartitions,
+        rddForSampling,
+        ascending = true,
+        samplePointsPerPartitionHint = SQLConf.get.rangeExchangeSampleSizePerPartition)
+      val bounds = FieldUtils
+        .readField(partitioner, "rangeBounds", true)
+        .asInstanceOf[Array[InternalRow]]
+
+      val dataTypes = sortOrder.map(_.dataType)
+      bounds.toSeq.flatMap { bound =>
+        dataTypes.zipWithIndex.map { case (dataType, i) =>
+          NativeConverters.convertValue(bound.get(i, dataType), dataType)
+        }
+      }
+    case _ => Nil
+  }
+
+  private def nativeOutputPartitioning: PhysicalRepartition.Builder = {
+    val numPartitions = outputPartitioning.numPartitions
+    val repartitionBuilder = PhysicalRepartition.newBuilder()
+    outputPartitioning match {
+      case SinglePartition =>
+        repartitionBuilder
+          .setSingleRepartition(
+            PhysicalSingleRepartition
+              .newBuilder()
+              .setPartitionCount(1))
+      case HashPartitioning(_, _) =>
+        repartitionBuilder
+          .setHashRepartition(
+            PhysicalHashRepartition
+              .newBuilder()
+              .setPartitionCount(numPartitions)
+              .addAllHashExpr(nativeHashExprs.asJava))
+      case RoundRobinPartitioning(_) =>
+        repartitionBuilder
+          .setRoundRobinRepartition(
+            PhysicalRoundRobinRepartition
+              .newBuilder()
+              .setPartitionCount(numPartitions))
+      case RangePartitioning(_, _) =>
+        repartitionBuilder
+          .setRangeRepartition(
+            PhysicalRangeRepartition
+              .newBuilder()
+              .setPartitionCount(numPartitions)
+              .addAllSortExpr(nativeRangeSortExprs.asJava)
+              .addAllBoundValues(nativeRangeBounds.asJava))
+      case p =>
+        throw new NotImplementedError(s"cannot convert partitioning to native: $p")
+    }
+  }
+
   // check whether native converting is supported
   nativeSchema
   nativeHashExprs
+  nativeRangeSortExprs
 
   protected def doExecuteNonNative(): RDD[InternalRow]
 
@@ -119,6 +218,7 @@ abstract class NativeShuffleExchangeBase(
         val shuffleReadMetrics = taskContext.taskMetrics().createTempShuffleReadMetrics()
         val metricReporter = new SQLShuffleReadMetricsReporter(shuffleReadMetrics, metrics)
         val nativeSchema = this.nativeSchema
+        val nativeOutputPartitioning = this.nativeOutputPartitioning
 
         // store fetch iterator in jni resource before native compute
         val jniResourceId = s"NativeShuffleReadExec:${UUID.randomUUID().toString}"
@@ -144,6 +244,9 @@ abstract class NativeShuffleExchangeBase(
               .setSchema(nativeSchema)
               .setNumPartitions(rdd.getNumPartitions)
               .setIpcProviderResourceId(jniResourceId)
+              // each partition is read fully, so the data is still distributed
+              // by the shuffle's partitioning
+              .setPartitioning(nativeOutputPartitioning)
               .build())
           .build()
       },
@@ -177,6 +280,7 @@ abstract class NativeShuffleExchangeBase(
         case _ =>
       }))
     val nativeHashExprs = this.nativeHashExprs
+    nativeRangeBounds // sample range bounds on driver before tasks are serialized
 
     val nativeShuffleRDD = new NativeRDD(
       nativeInputRDD.sparkContext,
@@ -186,30 +290,7 @@ abstract class NativeShuffleExchangeBase(
       nativeInputRDD.isShuffleReadFull,
       (partition, taskContext) => {
         val nativeInputPartition = nativeInputRDD.partitions(partition.index)
-        val repartitionBuilder = PhysicalRepartition.newBuilder()
-        val nativeOutputPartitioning = outputPartitioning match {
-          case SinglePartition =>
-            repartitionBuilder
-              .setSingleRepartition(
-                PhysicalSingleRepartition
-                  .newBuilder()
-                  .setPartitionCount(1))
-          case HashPartitioning(_, _) =>
-            repartitionBuilder
-              .setHashRepartition(
-                PhysicalHashRepartition
-                  .newBuilder()
-                  .setPartitionCount(numPartitions)
-                  .addAllHashExpr(nativeHashExprs.asJava))
-          case RoundRobinPartitioning(_) =>
-            repartitionBuilder
-              .setRoundRobinRepartition(
-                PhysicalRoundRobinRepartition
-                  .newBuilder()
-                  .setPartitionCount(numPartitions))
-          case p =>
-            throw new NotImplementedError(s"cannot convert partitioning to native: $p")
-        }
+        val nativeOutputPartitioning = this.nativeOutputPartitioning
 
         val input = nativeInputRDD.nativePlan(nativeInputPartition, taskContext)
         val nativeShuffleWriteExec =
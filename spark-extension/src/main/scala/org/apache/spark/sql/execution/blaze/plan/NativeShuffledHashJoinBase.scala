@@ -51,6 +51,9 @@ abstract class NativeShuffledHashJoinBase(
         "stage_id",
         "output_rows",
         "elapsed_compute",
+        "coalesce_input_batches",
+        "coalesce_output_batches",
+        "hash_collisions",
         "build_hash_map_time",
         "probed_side_hash_time",
         "probed_side_search_time",
@@ -54,6 +54,8 @@ abstract class NativeSortBase(
         "stage_id",
         "output_rows",
         "elapsed_compute",
+        "coalesce_input_batches",
+        "coalesce_output_batches",
         "mem_spill_count",
         "mem_spill_size",
         "mem_spill_iotime",
@@ -26,6 +26,7 @@ import org.apache.spark.sql.blaze.MetricNode
 import org.apache.spark.sql.blaze.NativeConverters
 import org.apache.spark.sql.blaze.NativeHelper
 import org.apache.spark.sql.blaze.NativeRDD
+import org.apache.spark.sql.blaze.NativeRuntimeFilters
 import org.apache.spark.sql.blaze.NativeSupports
 import org.apache.spark.sql.catalyst.expressions.Ascending
 import org.apache.spark.sql.catalyst.expressions.Expression
@@ -58,9 +59,13 @@ abstract class NativeSortMergeJoinBase(
           "stage_id",
           "output_rows",
           "elapsed_compute",
+          "coalesce_input_batches",
+          "coalesce_output_batches",
           "input_batch_count",
           "input_batch_mem_size",
-          "input_row_count"))
+          "input_row_count",
+          "skewed_key_count",
+          "skewed_key_max_rows"))
       .toSeq: _*)
 
   override def requiredChildOrdering: Seq[Seq[SortOrder]] =
@@ -106,6 +111,8 @@ abstract class NativeSortMergeJoinBase(
     val nativeSortOptions = this.nativeSortOptions
     val nativeJoinOn = this.nativeJoinOn
     val nativeJoinType = this.nativeJoinType
+    val leftRuntimeFilterIds = getTagValue(NativeRuntimeFilters.leftProducerTag).getOrElse(Nil)
+    val rightRuntimeFilterIds = getTagValue(NativeRuntimeFilters.rightProducerTag).getOrElse(Nil)
 
     val partitions = if (joinType != RightOuter) {
       leftRDD.partitions
@@ -147,6 +154,8 @@ abstract class NativeSortMergeJoinBase(
           .setJoinType(nativeJoinType)
           .addAllOn(nativeJoinOn.asJava)
           .addAllSortOptions(nativeSortOptions.asJava)
+          .addAllLeftRuntimeFilterIds(leftRuntimeFilterIds.asJava)
+          .addAllRightRuntimeFilterIds(rightRuntimeFilterIds.asJava)
         PhysicalPlanNode.newBuilder().setSortMergeJoin(sortMergeJoinExec).build()
       },
       friendlyName = "NativeRDD.SortMergeJoin")
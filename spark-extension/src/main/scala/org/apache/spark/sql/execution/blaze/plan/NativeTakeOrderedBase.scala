@@ -25,8 +25,10 @@ import org.apache.spark.sql.blaze.NativeHelper
 import org.apache.spark.sql.blaze.NativeRDD
 import org.apache.spark.sql.catalyst.expressions.Ascending
 import org.apache.spark.sql.catalyst.expressions.Attribute
+import org.apache.spark.sql.catalyst.expressions.NamedExpression
 import org.apache.spark.sql.catalyst.expressions.NullsFirst
 import org.apache.spark.sql.catalyst.expressions.SortOrder
+import org.apache.spark.sql.catalyst.expressions.UnsafeProjection
 import org.apache.spark.sql.catalyst.plans.physical.Partitioning
 import org.apache.spark.sql.catalyst.plans.physical.SinglePartition
 import org.apache.spark.sql.catalyst.InternalRow
@@ -43,10 +45,13 @@ import org.blaze.protobuf.PhysicalExprNode
 import org.blaze.protobuf.PhysicalPlanNode
 import org.blaze.protobuf.PhysicalSortExprNode
 import org.blaze.protobuf.SortExecNode
+import org.blaze.protobuf.TakeOrderedAndProjectExecNode
 
 abstract class NativeTakeOrderedBase(
     limit: Long,
+    offset: Long,
     sortOrder: Seq[SortOrder],
+    projectList: Seq[NamedExpression],
     override val child: SparkPlan)
     extends UnaryExecNode
     with NativeSupports {
@@ -55,9 +60,9 @@ abstract class NativeTakeOrderedBase(
     NativeHelper
       .getDefaultNativeMetrics(sparkContext)
       .filterKeys(Set("stage_id", "output_rows", "elapsed_compute"))
-      .toSeq: _*)
+      .toSeq: _*) ++ NativeHelper.getExprMetrics(sparkContext, "project", projectList)
 
-  override def output: Seq[Attribute] = child.output
+  override def output: Seq[Attribute] = projectList.map(_.toAttribute)
   override def outputPartitioning: Partitioning = SinglePartition
   override def outputOrdering: Seq[SortOrder] = sortOrder
 
@@ -75,8 +80,9 @@ abstract class NativeTakeOrderedBase(
   }
 
   override def executeCollect(): Array[InternalRow] = {
-    val partial = Shims.get.createNativePartialTakeOrderedExec(limit, sortOrder, child, metrics)
-    val ord = new LazilyGeneratedOrdering(sortOrder, output)
+    val fetch = limit + offset
+    val partial = Shims.get.createNativePartialTakeOrderedExec(fetch, sortOrder, child, metrics)
+    val ord = new LazilyGeneratedOrdering(sortOrder, child.output)
 
     // all partitions are sorted, so perform a sorted-merge to achieve the result
     partial
@@ -88,7 +94,7 @@ abstract class NativeTakeOrderedBase(
         var i = 0
         var j = 0
 
-        while (result.length < limit && (i < array1.length || j < array2.length)) {
+        while (result.length < fetch && (i < array1.length || j < array2.length)) {
           0 match {
             case _ if i == array1.length =>
               result.append(array2(j))
@@ -108,39 +114,52 @@ abstract class NativeTakeOrderedBase(
         }
         result.toArray
       }
+      .drop(offset.toInt)
+      .map(UnsafeProjection.create(projectList, child.output))
+      .map(_.copy())
   }
 
+  private def nativeProject = NativeProjectBase.getNativeProjectBuilder(projectList).buildPartial()
+
   // check whether native converting is supported
   nativeSortExprs
+  nativeProject
 
   override def doExecuteNative(): NativeRDD = {
-    val partial = Shims.get.createNativePartialTakeOrderedExec(limit, sortOrder, child, metrics)
-    if (!partial.outputPartitioning.isInstanceOf[UnknownPartitioning]
-      && partial.outputPartitioning.numPartitions <= 1) {
-      return NativeHelper.executeNative(partial)
-    }
-
-    // merge top-K from every children partitions into a single partition
-    val shuffled = Shims.get.createNativeShuffleExchangeExec(SinglePartition, partial)
-    val shuffledRDD = NativeHelper.executeNative(shuffled)
+    val inputRDD =
+      if (!child.outputPartitioning.isInstanceOf[UnknownPartitioning]
+        && child.outputPartitioning.numPartitions <= 1) {
+        NativeHelper.executeNative(child)
+      } else {
+        // merge top-K from every children partitions into a single partition
+        val partial =
+          Shims.get.createNativePartialTakeOrderedExec(limit + offset, sortOrder, child, metrics)
+        val shuffled = Shims.get.createNativeShuffleExchangeExec(SinglePartition, partial)
+        NativeHelper.executeNative(shuffled)
+      }
     val nativeSortExprs = this.nativeSortExprs
+    val nativeProject = this.nativeProject
 
-    // take top-K from the final partition
+    // take top-K from the final partition, skipping offset rows and projecting
+    // the output in the same operator
     new NativeRDD(
       sparkContext,
-      metrics = MetricNode(metrics, shuffledRDD.metrics :: Nil),
-      shuffledRDD.partitions,
-      new OneToOneDependency(shuffledRDD) :: Nil,
+      metrics = MetricNode(metrics, inputRDD.metrics :: Nil),
+      inputRDD.partitions,
+      new OneToOneDependency(inputRDD) :: Nil,
       rddShuffleReadFull = false,
       (_, taskContext) => {
-        val inputPartition = shuffledRDD.partitions(0)
-        val nativeTakeOrderedExec = SortExecNode
+        val inputPartition = inputRDD.partitions(0)
+        val nativeTakeOrderedExec = TakeOrderedAndProjectExecNode
           .newBuilder()
-          .setInput(shuffledRDD.nativePlan(inputPartition, taskContext))
-          .addAllExpr(nativeSortExprs.asJava)
-          .setFetchLimit(FetchLimit.newBuilder().setLimit(limit))
+          .setInput(inputRDD.nativePlan(inputPartition, taskContext))
+          .addAllSortExpr(nativeSortExprs.asJava)
+          .setLimit(limit)
+          .setOffset(offset)
+          .addAllProjectionExpr(nativeProject.getExprList)
+          .addAllProjectionExprName(nativeProject.getExprNameList)
           .build()
-        PhysicalPlanNode.newBuilder().setSort(nativeTakeOrderedExec).build()
+        PhysicalPlanNode.newBuilder().setTakeOrderedAndProject(nativeTakeOrderedExec).build()
       },
       friendlyName = "NativeRDD.FinalTakeOrdered")
   }
@@ -0,0 +1,118 @@
+/*
+ * Copyright 2022 The Blaze Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package org.apache.spark.sql.execution.blaze.plan
+
+import scala.collection.immutable.SortedMap
+
+import org.apache.spark.OneToOneDependency
+import org.apache.spark.sql.blaze.MetricNode
+import org.apache.spark.sql.blaze.NativeConverters
+import org.apache.spark.sql.blaze.NativeHelper
+import org.apache.spark.sql.blaze.NativeRDD
+import org.apache.spark.sql.blaze.NativeSupports
+import org.apache.spark.sql.catalyst.expressions.Attribute
+import org.apache.spark.sql.catalyst.expressions.AttributeReference
+import org.apache.spark.sql.catalyst.expressions.Expression
+import org.apache.spark.sql.catalyst.expressions.SortOrder
+import org.apache.spark.sql.catalyst.plans.physical.Partitioning
+import org.apache.spark.sql.catalyst.plans.physical.UnknownPartitioning
+import org.apache.spark.sql.execution.SparkPlan
+import org.apache.spark.sql.execution.UnaryExecNode
+import org.apache.spark.sql.execution.metric.SQLMetric
+import org.apache.spark.sql.types.LongType
+import org.blaze.protobuf.PhysicalPlanNode
+import org.blaze.protobuf.TopKFrequentExecNode
+
+/**
+ * Samples the K most frequent values of an expression in each partition with native count-min
+ * sketch, outputs (value, count) rows. counts are estimated and never less than the real counts.
+ * used by skew-aware planning (e.g. choosing keys to salt) rather than query execution.
+ */
+abstract class NativeTopKFrequentBase(
+    expr: Expression,
+    k: Int,
+    override val output: Seq[Attribute],
+    override val child: SparkPlan)
+    extends UnaryExecNode
+    with NativeSupports {
+
+  override lazy val metrics: Map[String, SQLMetric] = SortedMap[String, SQLMetric]() ++ Map(
+    NativeHelper
+      .getDefaultNativeMetrics(sparkContext)
+      .filterKeys(
+        Set(
+          "stage_id",
+          "output_rows",
+          "elapsed_compute",
+          "input_batch_count",
+          "input_batch_mem_size",
+          "input_row_count"))
+      .toSeq: _*)
+
+  override def outputPartitioning: Partitioning =
+    UnknownPartitioning(child.outputPartitioning.numPartitions)
+  override def outputOrdering: Seq[SortOrder] = Nil
+
+  private def nativeExpr = NativeConverters.convertExpr(expr)
+
+  // check whether native converting is supported
+  nativeExpr
+
+  /**
+   * Merges the per-partition results into the global top-k values and their estimated counts,
+   * sorted by count descending.
+   */
+  def collectTopKFrequent(): Seq[(Any, Long)] = {
+    executeCollect()
+      .map(row => (row.get(0, expr.dataType), row.getLong(1)))
+      .groupBy(_._1)
+      .map { case (value, counts) => (value, counts.map(_._2).sum) }
+      .toSeq
+      .sortBy(-_._2)
+      .take(k)
+  }
+
+  override def doExecuteNative(): NativeRDD = {
+    val inputRDD = NativeHelper.executeNative(child)
+    val nativeMetrics = MetricNode(metrics, inputRDD.metrics :: Nil)
+    val nativeExpr = this.nativeExpr
+    val k = this.k
+
+    new NativeRDD(
+      sparkContext,
+      nativeMetrics,
+      rddPartitions = inputRDD.partitions,
+      rddDependencies = new OneToOneDependency(inputRDD) :: Nil,
+      inputRDD.isShuffleReadFull,
+      (partition, taskContext) => {
+        val inputPartition = inputRDD.partitions(partition.index)
+        val nativeTopKFrequentExec = TopKFrequentExecNode
+          .newBuilder()
+          .setInput(inputRDD.nativePlan(inputPartition, taskContext))
+          .setExpr(nativeExpr)
+          .setK(k)
+          .build()
+        PhysicalPlanNode.newBuilder().setTopKFrequent(nativeTopKFrequentExec).build()
+      },
+      friendlyName = "NativeRDD.TopKFrequent")
+  }
+}
+
+object NativeTopKFrequentBase {
+  def outputAttributes(expr: Expression): Seq[Attribute] = Seq(
+    AttributeReference("value", expr.dataType, nullable = true)(),
+    AttributeReference("count", LongType, nullable = false)())
+}
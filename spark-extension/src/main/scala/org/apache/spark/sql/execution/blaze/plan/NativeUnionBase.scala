@@ -36,6 +36,7 @@ import org.blaze.protobuf.EmptyPartitionsExecNode
 import org.blaze.protobuf.PhysicalPlanNode
 import org.blaze.protobuf.Schema
 import org.blaze.protobuf.UnionExecNode
+import org.blaze.protobuf.UnionInput
 import org.apache.spark.sql.blaze.NativeSupports
 
 abstract class NativeUnionBase(override val children: Seq[SparkPlan])
@@ -96,13 +97,28 @@ abstract class NativeUnionBase(override val children: Seq[SparkPlan])
       rdds.forall(_.isShuffleReadFull),
       (partition, taskContext) => {
         val unionPartition = unionedPartitions(partition.index)
-        val unionChildrenExecs = rdds.zipWithIndex.map {
+        val unionInputs = rdds.zipWithIndex.map {
           case (rdd, rddIndex) if rddIndex == unionPartition.parentRddIndex =>
-            rdd.nativePlan(unionPartition.parentPartition, taskContext)
-          case (rdd, _) =>
-            nativeEmptyPartitionExec(rdd.getNumPartitions)
+            UnionInput
+              .newBuilder()
+              .setInput(rdd.nativePlan(unionPartition.parentPartition, taskContext))
+              .setPartition(unionPartition.parentPartition.index)
+              .build()
+          case _ =>
+            // other children are kept as empty placeholders, so that native
+            // metrics are still aligned with the children
+            UnionInput
+              .newBuilder()
+              .setInput(nativeEmptyPartitionExec(1))
+              .setPartition(0)
+              .build()
         }
-        val union = UnionExecNode.newBuilder().addAllChildren(unionChildrenExecs.asJava)
+        val union = UnionExecNode
+          .newBuilder()
+          .addAllInput(unionInputs.asJava)
+          .setSchema(nativeSchema)
+          .setNumPartitions(unionedPartitions.length)
+          .setCurPartition(partition.index)
         PhysicalPlanNode.newBuilder().setUnion(union).build()
       },
       friendlyName = "NativeRDD.Union")
@@ -47,6 +47,18 @@ import org.apache.spark.sql.catalyst.expressions.aggregate.Count
 import org.apache.spark.sql.catalyst.expressions.aggregate.Max
 import org.apache.spark.sql.catalyst.expressions.aggregate.Min
 import org.apache.spark.sql.catalyst.expressions.aggregate.Sum
+import org.apache.spark.sql.catalyst.expressions.CurrentRow
+import org.apache.spark.sql.catalyst.expressions.Literal
+import org.apache.spark.sql.catalyst.expressions.RangeFrame
+import org.apache.spark.sql.catalyst.expressions.RowFrame
+import org.apache.spark.sql.catalyst.expressions.SpecifiedWindowFrame
+import org.apache.spark.sql.catalyst.expressions.UnboundedFollowing
+import org.apache.spark.sql.catalyst.expressions.UnboundedPreceding
+import org.apache.spark.sql.catalyst.expressions.WindowFrame
+import org.apache.spark.sql.types.ByteType
+import org.apache.spark.sql.types.IntegerType
+import org.apache.spark.sql.types.LongType
+import org.apache.spark.sql.types.ShortType
 
 abstract class NativeWindowBase(
     windowExpression: Seq[NamedExpression],
@@ -59,7 +71,13 @@ abstract class NativeWindowBase(
   override lazy val metrics: Map[String, SQLMetric] = SortedMap[String, SQLMetric]() ++ Map(
     NativeHelper
       .getDefaultNativeMetrics(sparkContext)
-      .filterKeys(Set("stage_id", "output_rows", "elapsed_compute"))
+      .filterKeys(
+        Set(
+          "stage_id",
+          "output_rows",
+          "elapsed_compute",
+          "coalesce_input_batches",
+          "coalesce_output_batches"))
       .toSeq: _*)
 
   override def output: Seq[Attribute] = child.output ++ windowExpression.map(_.toAttribute)
@@ -106,42 +124,32 @@ abstract class NativeWindowBase(
             windowExprBuilder.setWindowFunc(pb.WindowFunction.DENSE_RANK)
 
           case e: Sum =>
-            assert(
-              spec.frameSpecification == RowNumber().frame, // only supports RowFrame(Unbounde, CurrentRow)
-              s"window frame not supported: ${spec.frameSpecification}")
             windowExprBuilder.setFuncType(pb.WindowFunctionType.Agg)
+            windowExprBuilder.setFrame(convertFrame(spec.frameSpecification))
             windowExprBuilder.setAggFunc(pb.AggFunction.SUM)
             windowExprBuilder.addChildren(NativeConverters.convertExpr(e.child))
 
           case e: Average =>
-            assert(
-              spec.frameSpecification == RowNumber().frame, // only supports RowFrame(Unbounded, CurrentRow)
-              s"window frame not supported: ${spec.frameSpecification}")
             windowExprBuilder.setFuncType(pb.WindowFunctionType.Agg)
+            windowExprBuilder.setFrame(convertFrame(spec.frameSpecification))
             windowExprBuilder.setAggFunc(pb.AggFunction.AVG)
             windowExprBuilder.addChildren(NativeConverters.convertExpr(e.child))
 
           case e: Max =>
-            assert(
-              spec.frameSpecification == RowNumber().frame, // only supports RowFrame(Unbounded, CurrentRow)
-              s"window frame not supported: ${spec.frameSpecification}")
             windowExprBuilder.setFuncType(pb.WindowFunctionType.Agg)
+            windowExprBuilder.setFrame(convertFrame(spec.frameSpecification))
             windowExprBuilder.setAggFunc(pb.AggFunction.MAX)
             windowExprBuilder.addChildren(NativeConverters.convertExpr(e.child))
 
           case e: Min =>
-            assert(
-              spec.frameSpecification == RowNumber().frame, // only supports RowFrame(Unbounded, CurrentRow)
-              s"window frame not supported: ${spec.frameSpecification}")
             windowExprBuilder.setFuncType(pb.WindowFunctionType.Agg)
+            windowExprBuilder.setFrame(convertFrame(spec.frameSpecification))
             windowExprBuilder.setAggFunc(pb.AggFunction.MIN)
             windowExprBuilder.addChildren(NativeConverters.convertExpr(e.child))
 
           case Count(child :: Nil) =>
-            assert(
-              spec.frameSpecification == RowNumber().frame, // only supports RowFrame(Unbounded, CurrentRow)
-              s"window frame not supported: ${spec.frameSpecification}")
             windowExprBuilder.setFuncType(pb.WindowFunctionType.Agg)
+            windowExprBuilder.setFrame(convertFrame(spec.frameSpecification))
             windowExprBuilder.setAggFunc(pb.AggFunction.COUNT)
             windowExprBuilder.addChildren(NativeConverters.convertExpr(child))
 
@@ -154,6 +162,50 @@ abstract class NativeWindowBase(
     windowExprBuilder.build()
   }
 
+  // converts ROWS/RANGE frames of aggregate functions, only literal integral
+  // offsets are supported
+  private def convertFrame(frame: WindowFrame): pb.WindowFrame = frame match {
+    // frames ending in UNBOUNDED FOLLOWING (like the default frame of aggregate functions
+    // without ORDER BY) are not supported natively
+    case SpecifiedWindowFrame(_, _, UnboundedFollowing) =>
+      throw new NotImplementedError(s"window frame not supported: $frame")
+    case SpecifiedWindowFrame(frameType, lower, upper) =>
+      val units = frameType match {
+        case RowFrame => pb.WindowFrameUnits.ROWS
+        case RangeFrame => pb.WindowFrameUnits.RANGE
+      }
+      pb.WindowFrame
+        .newBuilder()
+        .setUnits(units)
+        .setStart(convertFrameBound(lower))
+        .setEnd(convertFrameBound(upper))
+        .build()
+    case other =>
+      throw new NotImplementedError(s"window frame not supported: $other")
+  }
+
+  private def convertFrameBound(bound: Expression): pb.WindowFrameBound = {
+    val builder = pb.WindowFrameBound.newBuilder()
+    bound match {
+      case UnboundedPreceding =>
+        builder.setBoundType(pb.WindowFrameBoundType.UNBOUNDED_PRECEDING)
+      case CurrentRow =>
+        builder.setBoundType(pb.WindowFrameBoundType.CURRENT_ROW)
+      case Literal(v: Number, ByteType | ShortType | IntegerType | LongType) =>
+        val offset = v.longValue()
+        if (offset < 0) {
+          builder.setBoundType(pb.WindowFrameBoundType.PRECEDING).setOffset(-offset)
+        } else if (offset > 0) {
+          builder.setBoundType(pb.WindowFrameBoundType.FOLLOWING).setOffset(offset)
+        } else {
+          builder.setBoundType(pb.WindowFrameBoundType.CURRENT_ROW)
+        }
+      case other =>
+        throw new NotImplementedError(s"window frame bound not supported: $other")
+    }
+    builder.build()
+  }
+
   private def nativePartitionSpecExprs = partitionSpec.map { partition =>
     NativeConverters.convertExpr(partition)
   }
@@ -0,0 +1,140 @@
+/*
+ * Copyright 2022 The Blaze Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package org.apache.spark.sql.execution.blaze.plan
+
+import scala.collection.JavaConverters._
+import scala.collection.immutable.SortedMap
+
+import org.apache.spark.OneToOneDependency
+import org.apache.spark.sql.blaze.MetricNode
+import org.apache.spark.sql.blaze.NativeConverters
+import org.apache.spark.sql.blaze.NativeHelper
+import org.apache.spark.sql.blaze.NativeRDD
+import org.apache.spark.sql.blaze.NativeSupports
+import org.apache.spark.sql.catalyst.expressions.Ascending
+import org.apache.spark.sql.catalyst.expressions.Attribute
+import org.apache.spark.sql.catalyst.expressions.DenseRank
+import org.apache.spark.sql.catalyst.expressions.Expression
+import org.apache.spark.sql.catalyst.expressions.NullsFirst
+import org.apache.spark.sql.catalyst.expressions.Rank
+import org.apache.spark.sql.catalyst.expressions.RowNumber
+import org.apache.spark.sql.catalyst.expressions.SortOrder
+import org.apache.spark.sql.catalyst.plans.physical.AllTuples
+import org.apache.spark.sql.catalyst.plans.physical.ClusteredDistribution
+import org.apache.spark.sql.catalyst.plans.physical.Distribution
+import org.apache.spark.sql.catalyst.plans.physical.Partitioning
+import org.apache.spark.sql.catalyst.plans.physical.UnspecifiedDistribution
+import org.apache.spark.sql.execution.SparkPlan
+import org.apache.spark.sql.execution.UnaryExecNode
+import org.apache.spark.sql.execution.metric.SQLMetric
+import org.blaze.{protobuf => pb}
+
+/**
+ * Native equivalent of spark's WindowGroupLimitExec, keeps only rows whose row_number/rank/
+ * dense_rank is not greater than limit in each window partition, without buffering the whole
+ * partition.
+ */
+abstract class NativeWindowGroupLimitBase(
+    partitionSpec: Seq[Expression],
+    orderSpec: Seq[SortOrder],
+    rankLikeFunction: Expression,
+    limit: Int,
+    partial: Boolean,
+    override val child: SparkPlan)
+    extends UnaryExecNode
+    with NativeSupports {
+
+  override lazy val metrics: Map[String, SQLMetric] = SortedMap[String, SQLMetric]() ++ Map(
+    NativeHelper
+      .getDefaultNativeMetrics(sparkContext)
+      .filterKeys(Set("stage_id", "output_rows", "elapsed_compute"))
+      .toSeq: _*)
+
+  override def output: Seq[Attribute] = child.output
+  override def outputPartitioning: Partitioning = child.outputPartitioning
+  override def outputOrdering: Seq[SortOrder] = child.outputOrdering
+
+  override def requiredChildDistribution: Seq[Distribution] = {
+    if (partial) {
+      UnspecifiedDistribution :: Nil
+    } else if (partitionSpec.isEmpty) {
+      AllTuples :: Nil
+    } else {
+      ClusteredDistribution(partitionSpec) :: Nil
+    }
+  }
+
+  override def requiredChildOrdering: Seq[Seq[SortOrder]] =
+    Seq(partitionSpec.map(SortOrder(_, Ascending)) ++ orderSpec)
+
+  private def nativeRankFunc = rankLikeFunction match {
+    case _: RowNumber => pb.WindowFunction.ROW_NUMBER
+    case _: Rank => pb.WindowFunction.RANK
+    case _: DenseRank => pb.WindowFunction.DENSE_RANK
+    case other =>
+      throw new NotImplementedError(s"window group limit function not supported: $other")
+  }
+
+  private def nativePartitionSpecExprs = partitionSpec.map { partition =>
+    NativeConverters.convertExpr(partition)
+  }
+
+  private def nativeOrderSpecExprs = orderSpec.map { sortOrder =>
+    pb.PhysicalExprNode
+      .newBuilder()
+      .setSort(
+        pb.PhysicalSortExprNode
+          .newBuilder()
+          .setExpr(NativeConverters.convertExpr(sortOrder.child))
+          .setAsc(sortOrder.direction == Ascending)
+          .setNullsFirst(sortOrder.nullOrdering == NullsFirst)
+          .build())
+      .build()
+  }
+
+  // check whether native converting is supported
+  nativeRankFunc
+  nativeOrderSpecExprs
+  nativePartitionSpecExprs
+
+  override def doExecuteNative(): NativeRDD = {
+    val inputRDD = NativeHelper.executeNative(child)
+    val nativeMetrics = MetricNode(metrics, inputRDD.metrics :: Nil)
+    val nativeRankFunc = this.nativeRankFunc
+    val nativeOrderSpecExprs = this.nativeOrderSpecExprs
+    val nativePartitionSpecExprs = this.nativePartitionSpecExprs
+
+    new NativeRDD(
+      sparkContext,
+      nativeMetrics,
+      rddPartitions = inputRDD.partitions,
+      rddDependencies = new OneToOneDependency(inputRDD) :: Nil,
+      inputRDD.isShuffleReadFull,
+      (partition, taskContext) => {
+        val inputPartition = inputRDD.partitions(partition.index)
+        val nativeWindowGroupLimitExec = pb.WindowGroupLimitExecNode
+          .newBuilder()
+          .setInput(inputRDD.nativePlan(inputPartition, taskContext))
+          .addAllPartitionSpec(nativePartitionSpecExprs.asJava)
+          .addAllOrderSpec(nativeOrderSpecExprs.asJava)
+          .setRankFunc(nativeRankFunc)
+          .setLimit(limit)
+          .build()
+        pb.PhysicalPlanNode.newBuilder().setWindowGroupLimit(nativeWindowGroupLimitExec).build()
+      },
+      friendlyName = "NativeRDD.WindowGroupLimit")
+  }
+}
@@ -18,7 +18,7 @@ package org.apache.spark.sql.execution.blaze.shuffle
 import java.nio.ByteBuffer
 import java.nio.ByteOrder
 import java.nio.file.Files
-import java.nio.file.Paths
+import java.nio.file.Path
 
 import org.apache.spark.Partition
 import org.apache.spark.ShuffleDependency
@@ -35,6 +35,7 @@ import org.apache.spark.shuffle.ShuffleWriter
 import org.apache.spark.sql.blaze.NativeHelper
 import org.apache.spark.sql.blaze.NativeRDD
 import org.apache.spark.sql.blaze.Shims
+import org.apache.spark.util.Utils
 
 abstract class BlazeShuffleWriterBase[K, V](metrics: ShuffleWriteMetricsReporter)
     extends ShuffleWriter[K, V]
@@ -58,32 +59,58 @@ abstract class BlazeShuffleWriterBase[K, V](metrics: ShuffleWriteMetricsReporter
 
     val shuffleBlockResolver =
       SparkEnv.get.shuffleManager.shuffleBlockResolver.asInstanceOf[IndexShuffleBlockResolver]
-    val dataFile = shuffleBlockResolver.getDataFile(dep.shuffleId, mapId)
-    val tempDataFilename = dataFile.getPath.replace(".data", ".data.tmp")
-    val tempIndexFilename = dataFile.getPath.replace(".data", ".index.tmp")
-    val tempDataFilePath = Paths.get(tempDataFilename)
-    val tempIndexFilePath = Paths.get(tempIndexFilename)
-
-    val nativeShuffleWriterExec = PhysicalPlanNode
-      .newBuilder()
-      .setShuffleWriter(
-        ShuffleWriterExecNode
-          .newBuilder(nativeShuffleRDD.nativePlan(partition, context).getShuffleWriter)
-          .setOutputDataFile(tempDataFilename)
-          .setOutputIndexFile(tempIndexFilename)
-          .build())
-      .build()
-    val iterator = NativeHelper.executeNativePlan(
-      nativeShuffleWriterExec,
-      nativeShuffleRDD.metrics,
-      partition,
-      Some(context))
-    assert(iterator.toArray.isEmpty)
-
-    // get partition lengths from shuffle write output index file
+
+    // output files are unique to this attempt, so speculative attempts of the
+    // same map task never write the same files. if another attempt has already
+    // committed its output, the block resolver keeps the committed output and
+    // fills partitionLengths with the committed lengths.
+    val tempDataFile = Utils.tempFileWith(shuffleBlockResolver.getDataFile(dep.shuffleId, mapId))
+    val tempIndexFile =
+      Utils.tempFileWith(shuffleBlockResolver.getIndexFile(dep.shuffleId, mapId))
+
+    try {
+      val nativeShuffleWriterExec = PhysicalPlanNode
+        .newBuilder()
+        .setShuffleWriter(
+          ShuffleWriterExecNode
+            .newBuilder(nativeShuffleRDD.nativePlan(partition, context).getShuffleWriter)
+            .setOutputDataFile(tempDataFile.getPath)
+            .setOutputIndexFile(tempIndexFile.getPath)
+            .build())
+        .build()
+      val iterator = NativeHelper.executeNativePlan(
+        nativeShuffleWriterExec,
+        nativeShuffleRDD.metrics,
+        partition,
+        Some(context))
+      assert(iterator.toArray.isEmpty)
+
+      // get partition lengths from shuffle write output index file
+      partitionLengths = readPartitionLengths(tempIndexFile.toPath)
+
+      // update metrics
+      val dataSize = Files.size(tempDataFile.toPath)
+      metrics.incBytesWritten(dataSize)
+
+      Shims.get.commit(
+        dep,
+        shuffleBlockResolver,
+        tempDataFile,
+        mapId,
+        partitionLengths,
+        dataSize,
+        context)
+    } finally {
+      // temp data file is moved or removed by the block resolver on commit
+      Files.deleteIfExists(tempIndexFile.toPath)
+      Files.deleteIfExists(tempDataFile.toPath)
+    }
+  }
+
+  private def readPartitionLengths(indexFilePath: Path): Array[Long] = {
     var offset = 0L
-    partitionLengths = Files
-      .readAllBytes(tempIndexFilePath)
+    Files
+      .readAllBytes(indexFilePath)
       .grouped(8)
       .drop(1) // first partition offset is always 0
       .map(indexBytes => {
@@ -94,19 +121,6 @@ abstract class BlazeShuffleWriterBase[K, V](metrics: ShuffleWriteMetricsReporter
         partitionLength
       })
       .toArray
-
-    // update metrics
-    val dataSize = Files.size(tempDataFilePath)
-    metrics.incBytesWritten(dataSize)
-
-    Shims.get.commit(
-      dep,
-      shuffleBlockResolver,
-      tempDataFilePath.toFile,
-      mapId,
-      partitionLengths,
-      dataSize,
-      context)
   }
 
   override def stop(success: Boolean): Option[MapStatus] = None